@@ -0,0 +1,75 @@
+//! Validator throughput with each transaction's digest taken afresh every
+//! time it is looked up, as it was, against taken once and kept with it.
+//!
+//! Run with `cargo bench -p lasr_actors --bench validator_throughput`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use lasr_types::{
+    Address, PayloadBuilder, RecoverableSignature, Transaction, TransactionType, U256,
+};
+use secp256k1::{Message, Secp256k1, SecretKey};
+use sha3::{Digest, Keccak256};
+
+const TRANSACTIONS: u64 = 2_000;
+
+/// How often a transaction is looked up by its hash on its way through the
+/// pending pool, the validator and the receipt index, its signature aside.
+const LOOKUPS: usize = 8;
+
+fn signed(secret_key: &SecretKey, nonce: u64) -> Transaction {
+    let from = Address::from(secret_key.public_key(&Secp256k1::new()));
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(nonce)))
+        .from(from.inner())
+        .to([2; 20])
+        .program_id([0; 20])
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(nonce))
+        .build()
+        .unwrap();
+    let sig: RecoverableSignature = Secp256k1::new()
+        .sign_ecdsa_recoverable(&Message::from_digest(payload.digest()), secret_key)
+        .into();
+    (payload, sig).into()
+}
+
+/// The transactions validated a second, each looked up and its signature
+/// checked against the digest `digest` takes.
+fn throughput(transactions: &[Transaction], digest: impl Fn(&Transaction) -> [u8; 32]) -> f64 {
+    let started = Instant::now();
+    for transaction in transactions {
+        for _ in 0..LOOKUPS {
+            black_box(digest(transaction));
+        }
+        let signer = transaction
+            .sig()
+            .unwrap()
+            .recover(&digest(transaction))
+            .unwrap();
+        assert_eq!(signer, transaction.from());
+    }
+    transactions.len() as f64 / started.elapsed().max(Duration::from_nanos(1)).as_secs_f64()
+}
+
+fn main() {
+    let secret_key = SecretKey::from_slice(&[7; 32]).unwrap();
+    let transactions = |offset: u64| -> Vec<Transaction> {
+        (offset..offset + TRANSACTIONS)
+            .map(|nonce| signed(&secret_key, nonce))
+            .collect()
+    };
+
+    let recomputed = throughput(&transactions(0), |transaction| {
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&Keccak256::digest(transaction.as_bytes()));
+        digest
+    });
+    let cached = throughput(&transactions(TRANSACTIONS), Transaction::digest);
+    println!("recomputed: {recomputed:>10.0} transactions/s");
+    println!("cached:     {cached:>10.0} transactions/s");
+    println!("speedup:    {:>10.2}x", cached / recomputed);
+}
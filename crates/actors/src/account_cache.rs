@@ -1,13 +1,19 @@
-use crate::{helpers::Coerce, process_group_changed, AccountValue, MAX_BATCH_SIZE};
+use crate::{
+    check_verse_supply_invariant,
+    encoding::{self, Encoding},
+    helpers::Coerce,
+    mailbox_handling, mailbox_probed, process_group_changed, AccountValue, Notifications,
+    VerseSupplyError, MAX_BATCH_SIZE,
+};
 use async_trait::async_trait;
 use futures::stream::FuturesUnordered;
 use lasr_messages::{
-    AccountCacheMessage, ActorName, ActorType, RpcMessage, RpcResponseError, SupervisorType,
-    TransactionResponse,
+    AccountCacheMessage, ActorName, ActorType, CacheError, LasrError, RpcErrorCode, RpcMessage,
+    RpcResponseError, SupervisorType, TransactionResponse,
 };
 #[cfg(feature = "mock_storage")]
 use lasr_types::MockPersistenceStore;
-use lasr_types::{Account, AccountType, Address, PersistenceStore};
+use lasr_types::{Account, AccountType, Address, PersistenceStore, Transaction, U256};
 use ractor::{
     concurrency::OneshotReceiver, Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent,
 };
@@ -21,7 +27,13 @@ use tikv_client::RawClient as TikvClient;
 use tokio::sync::mpsc::Sender;
 
 #[derive(Debug, Clone, Default)]
-pub struct AccountCacheActor;
+pub struct AccountCacheActor {
+    notifications: Notifications,
+    /// What the receipts and attestations stored are encoded in.
+    encoding: Encoding,
+    /// Whether the VERSE supply is audited after every batch written.
+    verse_audit: bool,
+}
 
 pub type StorageRef = <AccountCacheActor as Actor>::Arguments;
 
@@ -44,6 +56,19 @@ pub enum AccountCacheError {
     Custom(String),
 }
 
+impl From<AccountCacheError> for LasrError {
+    fn from(e: AccountCacheError) -> Self {
+        let e = match e {
+            AccountCacheError::RactorRegistryError => CacheError::Unavailable(e.to_string()),
+            AccountCacheError::FailedAccountAcquisition { addr } => {
+                CacheError::AccountNotFound(addr)
+            }
+            AccountCacheError::Custom(reason) => CacheError::Custom(reason),
+        };
+        LasrError::Cache(e)
+    }
+}
+
 pub struct AccountCache<S: PersistenceStore> {
     inner: AccountCacheInner,
     storage: S,
@@ -57,6 +82,41 @@ impl<S: PersistenceStore> AccountCache<S> {
     }
 }
 
+impl AccountCache<StorageRef> {
+    /// The account at `address` from the cache, or the persistence store if
+    /// it is not cached.
+    async fn read(&self, address: &Address) -> Option<Account> {
+        let hex_address = &address.to_full_string();
+        if let Some(account) = self.inner.get(address) {
+            tracing::debug!(
+                "retrieved account from account cache for address {hex_address}: {account:?}"
+            );
+            return Some(account.clone());
+        }
+        // Pass to persistence store
+        tracing::debug!(
+            "Account not found in AccountCache for address {hex_address}, connecting to persistence store."
+        );
+        let acc_key = address.to_full_string();
+
+        // Pull `Account` data from persistence store
+        PersistenceStore::get(&self.storage, acc_key.to_owned().into())
+            .await
+            .typecast()
+            .log_err(|e| AccountCacheError::Custom(format!("failed to find Account with address: {hex_address} in persistence store: {e:?}")))
+            .flatten()
+            .and_then(|returned_data| {
+                AccountValue::decode(&returned_data)
+                    .typecast()
+                    .log_err(|e| e)
+                    .map(|AccountValue { account }| {
+                        tracing::debug!("retrieved account from persistence store for address {hex_address}: {account:?}");
+                        account
+                    })
+            })
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, Default)]
 pub struct AccountCacheInner {
@@ -64,6 +124,9 @@ pub struct AccountCacheInner {
     receivers: FuturesUnordered<OneshotReceiver<Address>>,
     batch_interval: Duration,
     last_batch: Option<Instant>,
+    verse_audit: bool,
+    /// The sender of each transaction holding a pending debit, by hash.
+    reserved: HashMap<String, Address>,
 }
 
 impl AccountCacheInner {
@@ -77,6 +140,8 @@ impl AccountCacheInner {
             receivers: FuturesUnordered::new(),
             batch_interval: Duration::from_secs(batch_interval_secs),
             last_batch: None,
+            verse_audit: false,
+            reserved: HashMap::new(),
         }
     }
 
@@ -101,6 +166,8 @@ impl AccountCacheInner {
     ) -> Result<(), Box<dyn std::error::Error + Send>> {
         let addr = account.owner_address();
         if let Some(a) = self.cache.get_mut(&addr) {
+            let mut account = account;
+            account.keep_pending_debits(a);
             *a = account;
             return Ok(());
         }
@@ -115,11 +182,86 @@ impl AccountCacheInner {
         &mut self,
         account: Account,
     ) -> Result<(), Box<dyn std::error::Error + Send>> {
+        self.insert(account);
+        self.check_build_batch()?;
+
+        Ok(())
+    }
+
+    /// Writes every account of a transaction in one step so the cache never
+    /// holds only part of its effects.
+    pub(crate) fn handle_cache_write_batch(
+        &mut self,
+        accounts: Vec<Account>,
+    ) -> Result<(), Box<dyn std::error::Error + Send>> {
+        for account in accounts {
+            self.insert(account);
+        }
+        if self.verse_audit {
+            if let Err(e) = self.audit_verse_supply() {
+                tracing::error!("VERSE supply audit failed: {e}");
+            }
+        }
+        self.check_build_batch()?;
+
+        Ok(())
+    }
+
+    /// Holds what `transaction` takes from its cached sender, unless that
+    /// is more than the sender can spend on top of what it already holds.
+    /// A transaction already holding its debit is not held twice.
+    pub(crate) fn reserve_debit(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<(), AccountCacheError> {
+        if !transaction.transaction_type().debits_sender() || transaction.value().is_zero() {
+            return Ok(());
+        }
+        let addr = transaction.from();
+        let transaction_hash = transaction.hash_string();
+        let account = self
+            .cache
+            .get_mut(&addr)
+            .ok_or(AccountCacheError::FailedAccountAcquisition { addr })?;
+        if account
+            .pending_debits()
+            .inner()
+            .contains_key(&transaction_hash)
+        {
+            return Ok(());
+        }
+        account
+            .validate_spendable(&transaction.program_id(), transaction.value())
+            .map_err(|e| AccountCacheError::Custom(e.to_string()))?;
+        account.reserve_debit(transaction);
+        self.reserved.insert(transaction_hash, addr);
+        Ok(())
+    }
+
+    /// Releases what the transaction `transaction_hash` held, if it held
+    /// anything.
+    pub(crate) fn release_debit(&mut self, transaction_hash: &str) {
+        if let Some(address) = self.reserved.remove(transaction_hash) {
+            if let Some(account) = self.cache.get_mut(&address) {
+                account.release_debit(transaction_hash);
+            }
+        }
+    }
+
+    /// Checks that the VERSE held by cached accounts adds up to the recorded
+    /// supply. Only meaningful when every VERSE holder is cached, which is why
+    /// it only runs automatically when `caches.audit_verse_supply` is set.
+    pub(crate) fn audit_verse_supply(&self) -> Result<U256, VerseSupplyError> {
+        check_verse_supply_invariant(self.cache.values())
+    }
+
+    fn insert(&mut self, mut account: Account) {
         match account.account_type() {
             AccountType::User => {
                 let address = account.owner_address();
                 if let Some(entry) = self.cache.get_mut(&address) {
                     tracing::info!("Found account: 0x{:x} in cache, updating...", &address);
+                    account.keep_pending_debits(entry);
                     *entry = account;
                 } else {
                     tracing::info!(
@@ -140,6 +282,7 @@ impl AccountCacheInner {
                         "Found program_account: 0x{:x} in cache, updating...",
                         &program_address
                     );
+                    account.keep_pending_debits(entry);
                     *entry = account;
                 } else {
                     tracing::info!(
@@ -155,10 +298,6 @@ impl AccountCacheInner {
                 }
             }
         }
-
-        self.check_build_batch()?;
-
-        Ok(())
     }
 
     fn check_build_batch(&mut self) -> Result<(), Box<dyn std::error::Error + Send>> {
@@ -183,9 +322,37 @@ impl AccountCacheInner {
     }
 }
 
+/// Receipts share the persistence store with accounts, so their keys are
+/// prefixed to keep them apart from addresses.
+fn receipt_key(transaction_hash: &str) -> String {
+    format!("receipt:{transaction_hash}")
+}
+
+fn attestation_key(transaction_hash: &str) -> String {
+    format!("attestation:{transaction_hash}")
+}
+
 impl AccountCacheActor {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Publishes every account written to `notifications`.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Stores receipts and attestations encoded in `encoding`.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Audits the VERSE supply after every batch written.
+    pub fn with_verse_audit(mut self, audit: bool) -> Self {
+        self.verse_audit = audit;
+        self
     }
 }
 
@@ -203,7 +370,9 @@ impl Actor for AccountCacheActor {
         _myself: ActorRef<Self::Msg>,
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(AccountCache::new(args))
+        let mut cache = AccountCache::new(args);
+        cache.inner.verse_audit = self.verse_audit;
+        Ok(cache)
     }
 
     async fn handle(
@@ -212,7 +381,11 @@ impl Actor for AccountCacheActor {
         message: Self::Msg,
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
+        let _handling = mailbox_handling(ActorType::AccountCache);
         match message {
+            AccountCacheMessage::ProbeMailbox(probe) => {
+                mailbox_probed(ActorType::AccountCache, probe)
+            }
             AccountCacheMessage::Write {
                 account,
                 who,
@@ -226,45 +399,42 @@ impl Actor for AccountCacheActor {
                     location
                 );
                 let _ = state.inner.handle_cache_write(account.clone());
+                self.notifications.account_written(&account);
                 tracing::debug!("Account written to for address {owner}: {:?}", &account);
             }
+            AccountCacheMessage::WriteBatch {
+                accounts,
+                who,
+                location,
+            } => {
+                tracing::debug!(
+                    "Received account cache batch write request from {} for {} accounts: WHERE: {}",
+                    who.to_string(),
+                    accounts.len(),
+                    location
+                );
+                let _ = state.inner.handle_cache_write_batch(accounts.clone());
+                for account in &accounts {
+                    self.notifications.account_written(account);
+                }
+            }
             AccountCacheMessage::Read { address, tx, who } => {
-                let hex_address = &address.to_full_string();
                 tracing::debug!(
                     "Recieved account cache read request from {} for address: {}",
                     who.to_string(),
-                    hex_address
+                    address.to_full_string()
                 );
-                let account = if let Some(account) = state.inner.get(&address) {
-                    tracing::debug!("retrieved account from account cache for address {hex_address}: {account:?}");
-                    Some(account.clone())
-                } else {
-                    // Pass to persistence store
-                    tracing::debug!(
-                        "Account not found in AccountCache for address {hex_address}, connecting to persistence store."
-                    );
-                    let acc_key = address.to_full_string();
-
-                    // Pull `Account` data from persistence store
-                    PersistenceStore::get(
-                        &state.storage,
-                        acc_key.to_owned().into()
-                    )
-                    .await
-                    .typecast()
-                    .log_err(|e| AccountCacheError::Custom(format!("failed to find Account with address: {hex_address} in persistence store: {e:?}")))
-                    .flatten()
-                    .and_then(|returned_data| {
-                        bincode::deserialize(&returned_data)
-                            .typecast()
-                            .log_err(|e| e)
-                            .map(|AccountValue { account }| {
-                                tracing::debug!("retrieved account from persistence store for address {hex_address}: {account:?}");
-                                account
-                            })
-                    })
-                };
-                let _ = tx.send(account);
+                let _ = tx.send(state.read(&address).await);
+            }
+            AccountCacheMessage::ReadSnapshot { addresses, tx } => {
+                // Answered in one message, so no write lands between reads.
+                let mut snapshot = HashMap::with_capacity(addresses.len());
+                for address in addresses {
+                    if let Some(account) = state.read(&address).await {
+                        snapshot.insert(address, account);
+                    }
+                }
+                let _ = tx.send(snapshot);
             }
             AccountCacheMessage::Remove { address } => {
                 let _ = state.inner.remove(&address);
@@ -273,6 +443,124 @@ impl Actor for AccountCacheActor {
                 if let Err(_e) = state.inner.update(account.clone()) {
                     let _ = state.inner.handle_cache_write(account.clone());
                 }
+                self.notifications.account_written(&account);
+            }
+            AccountCacheMessage::WriteReceipt {
+                transaction_hash,
+                receipt,
+            } => {
+                if let Some(bytes) = self.encoding.encode(&receipt).typecast().log_err(|e| {
+                    AccountCacheError::Custom(format!(
+                        "failed to serialize receipt for transaction {transaction_hash}: {e:?}"
+                    ))
+                }) {
+                    PersistenceStore::put(
+                        &state.storage,
+                        receipt_key(&transaction_hash).into(),
+                        bytes,
+                    )
+                    .await
+                    .typecast()
+                    .log_err(|e| {
+                        AccountCacheError::Custom(format!(
+                            "failed to persist receipt for transaction {transaction_hash}: {e:?}"
+                        ))
+                    });
+                }
+            }
+            AccountCacheMessage::ReadReceipt {
+                transaction_hash,
+                tx,
+            } => {
+                let receipt =
+                    PersistenceStore::get(&state.storage, receipt_key(&transaction_hash).into())
+                        .await
+                        .typecast()
+                        .log_err(|e| {
+                            AccountCacheError::Custom(format!(
+                                "failed to read receipt for transaction {transaction_hash}: {e:?}"
+                            ))
+                        })
+                        .flatten()
+                        .and_then(|bytes| encoding::decode(&bytes).typecast().log_err(|e| e));
+                let _ = tx.send(receipt);
+            }
+            AccountCacheMessage::WriteAttestation {
+                transaction_hash,
+                attestation,
+            } => {
+                if let Some(bytes) = self.encoding.encode(&attestation).typecast().log_err(|e| {
+                    AccountCacheError::Custom(format!(
+                        "failed to serialize attestation for transaction {transaction_hash}: {e:?}"
+                    ))
+                }) {
+                    PersistenceStore::put(
+                        &state.storage,
+                        attestation_key(&transaction_hash).into(),
+                        bytes,
+                    )
+                    .await
+                    .typecast()
+                    .log_err(|e| {
+                        AccountCacheError::Custom(format!(
+                            "failed to persist attestation for transaction {transaction_hash}: {e:?}"
+                        ))
+                    });
+                }
+            }
+            AccountCacheMessage::ReadAttestation {
+                transaction_hash,
+                tx,
+            } => {
+                let attestation = PersistenceStore::get(
+                    &state.storage,
+                    attestation_key(&transaction_hash).into(),
+                )
+                .await
+                .typecast()
+                .log_err(|e| {
+                    AccountCacheError::Custom(format!(
+                        "failed to read attestation for transaction {transaction_hash}: {e:?}"
+                    ))
+                })
+                .flatten()
+                .and_then(|bytes| encoding::decode(&bytes).typecast().log_err(|e| e));
+                let _ = tx.send(attestation);
+            }
+            AccountCacheMessage::PruneReceipts { transaction_hashes } => {
+                for transaction_hash in &transaction_hashes {
+                    for key in [
+                        receipt_key(transaction_hash),
+                        attestation_key(transaction_hash),
+                    ] {
+                        PersistenceStore::delete(&state.storage, key.into())
+                            .await
+                            .typecast()
+                            .log_err(|e| {
+                                AccountCacheError::Custom(format!(
+                                    "failed to prune the receipt of transaction {transaction_hash}: {e:?}"
+                                ))
+                            });
+                    }
+                }
+            }
+            AccountCacheMessage::ReserveDebit { transaction, tx } => {
+                // Held against the cached account, so it is read into the
+                // cache first.
+                let sender = transaction.from();
+                if state.inner.get(&sender).is_none() {
+                    if let Some(account) = state.read(&sender).await {
+                        state.inner.insert(account);
+                    }
+                }
+                let reserved = state
+                    .inner
+                    .reserve_debit(&transaction)
+                    .map_err(|e| e.to_string());
+                let _ = tx.send(reserved);
+            }
+            AccountCacheMessage::ReleaseDebit { transaction_hash } => {
+                state.inner.release_debit(&transaction_hash);
             }
             AccountCacheMessage::TryGetAccount { address, reply } => {
                 if let Some(account) = state.inner.get(&address) {
@@ -285,6 +573,7 @@ impl Actor for AccountCacheActor {
                         description:
                             "Unable to acquire account from Persistence Store or Protocol Cache"
                                 .to_string(),
+                        code: RpcErrorCode::NotFound,
                     });
                     let _ = reply.send(RpcMessage::Response {
                         response,
@@ -292,6 +581,8 @@ impl Actor for AccountCacheActor {
                     });
                 }
             }
+            #[cfg(feature = "crash_test")]
+            AccountCacheMessage::Panic => panic!("account cache told to panic"),
         }
         Ok(())
     }
@@ -363,3 +654,94 @@ impl Actor for AccountCacheSupervisor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod account_cache_tests {
+    use crate::{mint_verse_supply, new_verse_program_account, AccountCacheInner, VERSE_ADDR};
+    use lasr_types::{
+        Account, AccountType, Address, Transaction, TransactionBuilder, TransactionType, U256,
+    };
+
+    fn verse_holder(address: [u8; 20], amount: u64) -> Account {
+        let mut account = Account::new(Address::new(address));
+        account
+            .apply_transfer_to_instruction(&VERSE_ADDR, &Some(U256::from(amount)), &vec![], None)
+            .expect("failed to credit VERSE");
+        account
+    }
+
+    #[test]
+    fn verse_supply_audit_catches_corrupted_cache() {
+        let mut verse_account =
+            new_verse_program_account().expect("failed to build VERSE program account");
+        mint_verse_supply(&mut verse_account, U256::from(15)).expect("failed to mint VERSE");
+
+        let mut inner = AccountCacheInner::new();
+        inner
+            .handle_cache_write_batch(vec![
+                verse_account,
+                verse_holder([1; 20], 10),
+                verse_holder([2; 20], 5),
+            ])
+            .expect("failed to write accounts");
+        assert_eq!(inner.audit_verse_supply().unwrap(), U256::from(15));
+
+        // A balance that appears without a matching mint breaks the invariant.
+        inner
+            .handle_cache_write(verse_holder([2; 20], 6))
+            .expect("failed to write account");
+        assert!(inner.audit_verse_supply().is_err());
+    }
+
+    /// A send of `value` VERSE from `[1; 20]` at `nonce`.
+    fn send(value: u64, nonce: u64) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(nonce)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id(VERSE_ADDR.inner())
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(value))
+            .nonce(U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn sends_cannot_together_spend_more_than_the_sender_holds() {
+        let mut inner = AccountCacheInner::new();
+        inner
+            .handle_cache_write(verse_holder([1; 20], 10))
+            .expect("failed to write account");
+        let first = send(6, 1);
+        inner.reserve_debit(&first).unwrap();
+        // Submitted again, it is not held twice.
+        inner.reserve_debit(&first).unwrap();
+
+        // The balance covers the second, but not on top of the first, even
+        // once the account is written again.
+        let second = send(6, 2);
+        assert!(inner.reserve_debit(&second).is_err());
+        inner
+            .handle_cache_write(verse_holder([1; 20], 10))
+            .expect("failed to write account");
+        assert!(inner.reserve_debit(&second).is_err());
+        inner.reserve_debit(&send(4, 2)).unwrap();
+
+        // The first failing releases what it held.
+        inner.release_debit(&first.hash_string());
+        inner.release_debit(&send(4, 2).hash_string());
+        inner.reserve_debit(&second).unwrap();
+        assert_eq!(
+            inner
+                .get(&Address::new([1; 20]))
+                .unwrap()
+                .spendable(&VERSE_ADDR),
+            U256::from(4)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Authenticating callers of the `admin_*` methods.
+//!
+//! Operators reach the admin methods either with the bearer token in
+//! `ADMIN_RPC_TOKEN`, on any listener, or from the local host on the listener
+//! at `ADMIN_RPC_ADDR`, which has to be a loopback address. Any other caller
+//! is answered as unauthorized, and the node answers every admin method to
+//! nobody when neither is configured. Each admin call is logged with who made
+//! it, and so is each one turned away.
+use futures::future::{ready, Either, Ready};
+use jsonrpsee::{
+    server::middleware::rpc::RpcServiceT,
+    types::{ErrorObjectOwned as RpcError, Request},
+    MethodResponse,
+};
+use lasr_messages::RpcErrorCode;
+use std::fmt::Display;
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+
+/// The namespace the admin methods are served in.
+pub const ADMIN_METHOD_PREFIX: &str = "admin_";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AdminError {
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("the admin listener at {0} is not bound to a loopback address")]
+    NotLoopback(SocketAddr),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl AdminError {
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            AdminError::Unauthorized(_) => RpcErrorCode::Unauthorized,
+            AdminError::NotLoopback(_) | AdminError::Custom(_) => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// Who a listener answers the admin methods to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminAccess {
+    /// Nobody.
+    Disabled,
+    /// Callers with `Authorization: Bearer <token>`.
+    Token(String),
+    /// Callers on the local host.
+    Localhost,
+}
+
+/// An operator let through to the admin methods, and how they were.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminCaller {
+    Token(IpAddr),
+    Localhost(IpAddr),
+}
+
+impl Display for AdminCaller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminCaller::Token(client) => write!(f, "{client} with the admin token"),
+            AdminCaller::Localhost(client) => write!(f, "{client} on the local admin listener"),
+        }
+    }
+}
+
+impl AdminAccess {
+    /// Authenticates `client`, which sent the `authorization` header if any.
+    pub fn authorize(
+        &self,
+        client: IpAddr,
+        authorization: Option<&str>,
+    ) -> Result<AdminCaller, AdminError> {
+        match self {
+            AdminAccess::Disabled => Err(AdminError::Unauthorized(
+                "admin methods are not served on this listener".to_string(),
+            )),
+            AdminAccess::Token(token) => {
+                let presented = authorization
+                    .and_then(|header| header.strip_prefix("Bearer "))
+                    .ok_or_else(|| {
+                        AdminError::Unauthorized("admin methods need a bearer token".to_string())
+                    })?;
+                if tokens_match(presented.trim().as_bytes(), token.as_bytes()) {
+                    Ok(AdminCaller::Token(client))
+                } else {
+                    Err(AdminError::Unauthorized(
+                        "the bearer token is not the admin token".to_string(),
+                    ))
+                }
+            }
+            AdminAccess::Localhost if client.is_loopback() => Ok(AdminCaller::Localhost(client)),
+            AdminAccess::Localhost => Err(AdminError::Unauthorized(
+                "admin methods are only answered to the local host".to_string(),
+            )),
+        }
+    }
+}
+
+/// Compares every byte whatever the first that differs, so how long the
+/// comparison takes does not say how much of a guess was right.
+fn tokens_match(presented: &[u8], token: &[u8]) -> bool {
+    presented.len() == token.len()
+        && presented
+            .iter()
+            .zip(token)
+            .fold(0, |differ, (a, b)| differ | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+    /// Where the listener answering the admin methods to the local host is
+    /// bound, if there is one.
+    pub listen_addr: Option<SocketAddr>,
+}
+
+impl AdminConfig {
+    pub fn new(token: Option<String>, listen_addr: Option<SocketAddr>) -> Result<Self, AdminError> {
+        if let Some(addr) = listen_addr.filter(|addr| !addr.ip().is_loopback()) {
+            return Err(AdminError::NotLoopback(addr));
+        }
+        Ok(Self {
+            token: token.filter(|token| !token.is_empty()),
+            listen_addr,
+        })
+    }
+
+    /// Who the public listener answers the admin methods to.
+    pub fn public_access(&self) -> AdminAccess {
+        match &self.token {
+            Some(token) => AdminAccess::Token(token.clone()),
+            None => AdminAccess::Disabled,
+        }
+    }
+}
+
+/// Answers the admin calls of a caller who is not an operator as
+/// unauthorized, logging every admin call.
+#[derive(Debug, Clone)]
+pub struct AdminService<S> {
+    pub service: S,
+    pub caller: Result<AdminCaller, AdminError>,
+}
+
+impl<'a, S> RpcServiceT<'a> for AdminService<S>
+where
+    S: RpcServiceT<'a> + Send + Sync,
+{
+    type Future = Either<Ready<MethodResponse>, S::Future>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let method = request.method_name();
+        if !method.starts_with(ADMIN_METHOD_PREFIX) {
+            return Either::Right(self.service.call(request));
+        }
+        match &self.caller {
+            Ok(caller) => {
+                tracing::warn!("admin call {method} by {caller}");
+                Either::Right(self.service.call(request))
+            }
+            Err(error) => {
+                tracing::warn!("admin call {method} refused: {error}");
+                Either::Left(ready(MethodResponse::error(
+                    request.id,
+                    RpcError::owned(error.code().code(), error.to_string(), None::<()>),
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod admin_tests {
+    use super::*;
+
+    #[test]
+    fn callers_are_authorized_by_token_or_from_the_local_host() {
+        let local = IpAddr::from([127, 0, 0, 1]);
+        let remote = IpAddr::from([10, 0, 0, 2]);
+        let token = AdminAccess::Token("secret".to_string());
+        assert_eq!(
+            token.authorize(remote, Some("Bearer secret")),
+            Ok(AdminCaller::Token(remote))
+        );
+        for authorization in [None, Some("secret"), Some("Bearer secreT"), Some("Bearer")] {
+            assert!(matches!(
+                token.authorize(local, authorization),
+                Err(AdminError::Unauthorized(_))
+            ));
+        }
+
+        assert_eq!(
+            AdminAccess::Localhost.authorize(local, None),
+            Ok(AdminCaller::Localhost(local))
+        );
+        assert!(AdminAccess::Localhost.authorize(remote, None).is_err());
+        assert!(AdminAccess::Disabled
+            .authorize(local, Some("Bearer secret"))
+            .is_err());
+    }
+
+    #[test]
+    fn the_admin_listener_is_bound_to_loopback() {
+        let public: SocketAddr = "0.0.0.0:9293".parse().unwrap();
+        assert_eq!(
+            AdminConfig::new(None, Some(public)),
+            Err(AdminError::NotLoopback(public))
+        );
+        let config =
+            AdminConfig::new(Some(String::new()), Some("127.0.0.1:9293".parse().unwrap())).unwrap();
+        assert_eq!(config.token, None);
+        assert_eq!(config.public_access(), AdminAccess::Disabled);
+    }
+}
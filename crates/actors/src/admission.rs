@@ -0,0 +1,1213 @@
+//! Checks a transaction has to pass before the node takes it on.
+//!
+//! The RPC server runs them before a transaction is handed to the scheduler,
+//! so one that could never be applied is turned away at once with an error
+//! saying why, instead of failing somewhere past the pending pool. The
+//! validator runs the same checks again against the sender's account as it
+//! is by the time the transaction's turn comes, so the two never disagree on
+//! what is valid. Transactions carry no chain id, so there is none to check.
+//!
+//! A sender whose account names an auth program is not held to its
+//! signature. The program is asked instead, with [`Admission::authorize`],
+//! once the other checks pass. A transaction signed by a key the sender
+//! delegated to is taken as the sender's own while it stays within the
+//! delegation. As the validator checks it against the account as it is when
+//! the transaction's turn comes, a revocation holds for every transaction
+//! applied after it, pending before it or not.
+//!
+//! A transaction may name a window it is valid in. One whose window has
+//! closed is turned away, and one whose window has yet to open is taken, to
+//! be held by the pending pool until it does. The validator holds it to the
+//! window as it is when the transaction is applied.
+use std::collections::BTreeMap;
+
+use lasr_messages::{ActorType, LasrError, RpcError, RpcErrorCode};
+use lasr_types::{
+    Account, AccountMetadata, Address, DelegationOp, NonceError, Transaction, TransactionType,
+    Validity, U256,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    ask_auth_program, unix_now, AuthLimits, FeeEstimate, FeeSchedule, InputError, InputLimits,
+    PolicyRule, VERSE_ADDR,
+};
+
+pub const DEFAULT_MAX_NONCE_AHEAD: u64 = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize)]
+#[serde(rename_all = "camelCase", tag = "reason")]
+pub enum AdmissionError {
+    #[error("transaction signature is invalid: {error}")]
+    InvalidSignature { error: String },
+
+    #[error("transaction is signed by {signer}, not its sender {from}")]
+    SenderMismatch { from: Address, signer: Address },
+
+    #[error(
+        "transaction is signed by {delegate}, outside what its sender delegated to it: {error}"
+    )]
+    OutsideDelegation { delegate: Address, error: String },
+
+    #[error("transaction is not authorized by auth program {auth_program}: {error}")]
+    #[serde(rename_all = "camelCase")]
+    Unauthorized {
+        auth_program: Address,
+        error: String,
+    },
+
+    #[error("call transaction has no program id")]
+    MissingProgramId,
+
+    #[error("{error}")]
+    InvalidDelegation { error: String },
+
+    #[error("{error}")]
+    InvalidAccountMetadata { error: String },
+
+    /// What the transaction's type carries is not something it can be
+    /// applied with.
+    #[error("{error}")]
+    InvalidTransactionType { error: String },
+
+    #[error("nonce {nonce} has been used already, the next is {next}")]
+    NonceTooLow { nonce: U256, next: U256 },
+
+    #[error("nonce {nonce} is too far ahead, the highest accepted is {max}")]
+    NonceTooHigh { nonce: U256, max: U256 },
+
+    #[error("the sender has used every nonce")]
+    NoncesExhausted,
+
+    #[error(
+        "transaction is valid from {valid_after}, which is not before it expires at {valid_before}"
+    )]
+    #[serde(rename_all = "camelCase")]
+    EmptyWindow { valid_after: u64, valid_before: u64 },
+
+    #[error("transaction is not valid until {valid_after}")]
+    #[serde(rename_all = "camelCase")]
+    NotYetValid { valid_after: u64 },
+
+    #[error("transaction expired at {valid_before}")]
+    #[serde(rename_all = "camelCase")]
+    Expired { valid_before: u64 },
+
+    #[error("max fee of {max_fee} is under the fee floor of {floor}")]
+    #[serde(rename_all = "camelCase")]
+    FeeTooLow { max_fee: U256, floor: U256 },
+
+    /// The sender holds less than `value` of the token, or none of it, in
+    /// which case `balance` is zero. For VERSE, `value` includes the fee.
+    #[error("balance of {balance} in {program_id} is less than the {value} taken")]
+    #[serde(rename_all = "camelCase")]
+    InsufficientBalance {
+        program_id: Address,
+        balance: U256,
+        value: U256,
+    },
+
+    /// Denied by the admission policy in force, for breaking `rule`.
+    #[error("transaction is denied by the admission policy: {error}")]
+    Denied { rule: PolicyRule, error: String },
+
+    #[error("{0}")]
+    #[serde(untagged)]
+    Inputs(InputError),
+}
+
+impl From<NonceError> for AdmissionError {
+    fn from(e: NonceError) -> Self {
+        match e {
+            NonceError::TooLow { nonce, next } | NonceError::Replayed { nonce, next } => {
+                AdmissionError::NonceTooLow { nonce, next }
+            }
+            NonceError::TooHigh { nonce, max } => AdmissionError::NonceTooHigh { nonce, max },
+            NonceError::Exhausted => AdmissionError::NoncesExhausted,
+        }
+    }
+}
+
+impl AdmissionError {
+    /// The RPC error code the rejection is answered with. Its data tells the
+    /// rejections sharing a code apart.
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            AdmissionError::InvalidSignature { .. } | AdmissionError::Unauthorized { .. } => {
+                RpcErrorCode::InvalidSignature
+            }
+            AdmissionError::SenderMismatch { .. } | AdmissionError::OutsideDelegation { .. } => {
+                RpcErrorCode::SenderMismatch
+            }
+            AdmissionError::MissingProgramId => RpcErrorCode::MissingProgramId,
+            AdmissionError::InvalidDelegation { .. }
+            | AdmissionError::InvalidAccountMetadata { .. }
+            | AdmissionError::InvalidTransactionType { .. } => RpcErrorCode::InvalidParams,
+            AdmissionError::NonceTooLow { .. }
+            | AdmissionError::NonceTooHigh { .. }
+            | AdmissionError::NoncesExhausted => RpcErrorCode::InvalidNonce,
+            AdmissionError::EmptyWindow { .. } | AdmissionError::NotYetValid { .. } => {
+                RpcErrorCode::InvalidParams
+            }
+            AdmissionError::Expired { .. } => RpcErrorCode::Expired,
+            AdmissionError::FeeTooLow { .. } => RpcErrorCode::FeeTooLow,
+            AdmissionError::InsufficientBalance { .. } => RpcErrorCode::InsufficientBalance,
+            AdmissionError::Denied { .. } => RpcErrorCode::PolicyDenied,
+            AdmissionError::Inputs(_) => RpcErrorCode::InvalidParams,
+        }
+    }
+}
+
+impl From<AdmissionError> for LasrError {
+    fn from(e: AdmissionError) -> Self {
+        LasrError::Rpc(RpcError::Rejected {
+            code: e.code(),
+            reason: e.to_string(),
+        })
+    }
+}
+
+impl From<InputError> for AdmissionError {
+    fn from(e: InputError) -> Self {
+        AdmissionError::Inputs(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Admission {
+    input_limits: InputLimits,
+    /// How far past the sender's next nonce a transaction may be, to wait in
+    /// the pending pool for those before it.
+    max_nonce_ahead: u64,
+    fees: FeeSchedule,
+    auth_limits: AuthLimits,
+}
+
+impl Default for Admission {
+    fn default() -> Self {
+        Self {
+            input_limits: InputLimits::default(),
+            max_nonce_ahead: DEFAULT_MAX_NONCE_AHEAD,
+            fees: FeeSchedule::default(),
+            auth_limits: AuthLimits::default(),
+        }
+    }
+}
+
+impl Admission {
+    pub fn with_input_limits(mut self, input_limits: InputLimits) -> Self {
+        self.input_limits = input_limits;
+        self
+    }
+
+    pub fn with_max_nonce_ahead(mut self, max_nonce_ahead: u64) -> Self {
+        self.max_nonce_ahead = max_nonce_ahead;
+        self
+    }
+
+    pub fn with_fee_schedule(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_auth_limits(mut self, auth_limits: AuthLimits) -> Self {
+        self.auth_limits = auth_limits;
+        self
+    }
+
+    pub fn input_limits(&self) -> &InputLimits {
+        &self.input_limits
+    }
+
+    pub fn fees(&self) -> &FeeSchedule {
+        &self.fees
+    }
+
+    /// Runs every check on `transaction`, cheapest first, against the
+    /// account of its sender, if there is one, and that of the program it
+    /// calls. The authorization of a sender whose account names an auth
+    /// program is left to [`Admission::authorize`], and one whose window
+    /// has yet to open is not turned away for it.
+    pub fn check(
+        &self,
+        transaction: &Transaction,
+        sender: Option<&Account>,
+        program: Option<&Account>,
+    ) -> Result<(), AdmissionError> {
+        check_program_id(transaction)?;
+        check_transaction_type(transaction)?;
+        check_window(transaction, unix_now())?;
+        self.input_limits.check(transaction, program)?;
+        check_signer(transaction, sender)?;
+        check_delegation(transaction, sender)?;
+        check_account_metadata(transaction, sender)?;
+        self.check_nonce(transaction, sender)?;
+        self.check_fee(transaction)?;
+        check_spendable(transaction, sender, self.fees.charge(transaction))
+    }
+
+    /// Estimates the fees of `transaction`, signed or not, with why it would
+    /// be turned away, if it would, by every check but its signature's. One
+    /// without a `maxFee` is checked as if it offered what it is estimated
+    /// to be charged.
+    pub fn estimate(
+        &self,
+        transaction: &Transaction,
+        sender: Option<&Account>,
+        program: Option<&Account>,
+    ) -> FeeEstimate {
+        let mut estimate = self.fees.estimate(transaction);
+        let checked = check_program_id(transaction)
+            .and_then(|_| check_transaction_type(transaction))
+            .and_then(|_| check_window(transaction, unix_now()))
+            .and_then(|_| {
+                self.input_limits
+                    .check(transaction, program)
+                    .map_err(AdmissionError::from)
+            })
+            .and_then(|_| self.check_nonce(transaction, sender))
+            .and_then(|_| match transaction.max_fee() {
+                Some(_) => self.check_fee(transaction),
+                None => Ok(()),
+            })
+            .and_then(|_| check_spendable(transaction, sender, estimate.fee));
+        estimate.rejection = checked.err();
+        estimate
+    }
+
+    /// Asks the auth program the account of `sender` names, if it names
+    /// one, whether it authorizes `transaction`, reading the program's
+    /// account as `who`. A sender naming none is held to its signature by
+    /// [`Admission::check`] instead.
+    pub async fn authorize(
+        &self,
+        transaction: &Transaction,
+        sender: Option<&Account>,
+        who: ActorType,
+    ) -> Result<(), AdmissionError> {
+        let Some(sender) = sender else {
+            return Ok(());
+        };
+        match sender.auth_program() {
+            Some(auth_program) => {
+                ask_auth_program(transaction, sender, auth_program, &self.auth_limits, who).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the most `transaction` would pay covers the fee floor, taking
+    /// a missing `maxFee` as none.
+    pub fn check_fee(&self, transaction: &Transaction) -> Result<(), AdmissionError> {
+        let max_fee = transaction.max_fee().unwrap_or_default();
+        let floor = self.fees.floor(transaction);
+        if max_fee < floor {
+            return Err(AdmissionError::FeeTooLow { max_fee, floor });
+        }
+        Ok(())
+    }
+
+    /// Checks the nonce is one the sender has not used, and no further ahead
+    /// of the next than `max_nonce_ahead`. A sender with no account has used
+    /// none, and sends its first at 1.
+    pub fn check_nonce(
+        &self,
+        transaction: &Transaction,
+        sender: Option<&Account>,
+    ) -> Result<(), AdmissionError> {
+        let unused = Account::default();
+        sender
+            .unwrap_or(&unused)
+            .accept_nonce_within(transaction.nonce(), self.max_nonce_ahead)
+            .map_err(AdmissionError::from)
+    }
+}
+
+fn check_program_id(transaction: &Transaction) -> Result<(), AdmissionError> {
+    let is_call = matches!(transaction.transaction_type(), TransactionType::Call(_));
+    if is_call && transaction.program_id() == Address::default() {
+        return Err(AdmissionError::MissingProgramId);
+    }
+    Ok(())
+}
+
+/// Checks what the type of `transaction` carries is something it can be
+/// applied with: a deploy names a package and takes no value, a bridge out a
+/// recipient for the value it takes, and a burn of VERSE burns its value.
+/// Bridge outs naming no recipient, which were never applied, are turned
+/// away.
+fn check_transaction_type(transaction: &Transaction) -> Result<(), AdmissionError> {
+    let invalid = |error: String| Err(AdmissionError::InvalidTransactionType { error });
+    let value = transaction.value();
+    match transaction.transaction_type() {
+        TransactionType::Deploy { content_id, .. } if content_id.is_empty() => {
+            invalid("deploy transaction names no content id".to_string())
+        }
+        TransactionType::Deploy { .. } if !value.is_zero() => {
+            invalid(format!("deploy transaction takes no value, not {value}"))
+        }
+        TransactionType::BridgeOutTo { recipient, .. } if recipient == Address::default() => {
+            invalid("bridge out transaction names no recipient".to_string())
+        }
+        TransactionType::BridgeOutTo { .. } if value.is_zero() => {
+            invalid("bridge out transaction takes no value".to_string())
+        }
+        TransactionType::BurnAmount { .. } if transaction.program_id() != VERSE_ADDR => {
+            invalid("burn transactions are only supported for VERSE".to_string())
+        }
+        TransactionType::BurnAmount { .. } if value.is_zero() => {
+            invalid("burn transaction burns no value".to_string())
+        }
+        TransactionType::BridgeOut(_) => {
+            invalid("bridge out transaction names no recipient, send a bridgeOutTo".to_string())
+        }
+        TransactionType::SetAccountMetadata(_) if !value.is_zero() => invalid(format!(
+            "account metadata transaction takes no value, not {value}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the window `transaction` is valid in is not empty, and has not
+/// closed by `now`, in seconds since the epoch.
+pub fn check_window(transaction: &Transaction, now: u64) -> Result<(), AdmissionError> {
+    if let (Some(valid_after), Some(valid_before)) =
+        (transaction.valid_after(), transaction.valid_before())
+    {
+        if valid_after >= valid_before {
+            return Err(AdmissionError::EmptyWindow {
+                valid_after,
+                valid_before,
+            });
+        }
+    }
+    match transaction.validity_at(now) {
+        Validity::Expired { valid_before } => Err(AdmissionError::Expired { valid_before }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks `transaction` may be applied at `now`, in seconds since the
+/// epoch, inside the window it is valid in.
+pub fn check_valid_at(transaction: &Transaction, now: u64) -> Result<(), AdmissionError> {
+    check_window(transaction, now)?;
+    match transaction.validity_at(now) {
+        Validity::NotYet { valid_after } => Err(AdmissionError::NotYetValid { valid_after }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the signature recovers to the sender.
+pub fn check_signature(transaction: &Transaction) -> Result<(), AdmissionError> {
+    let signer = transaction
+        .recover()
+        .map_err(|e| AdmissionError::InvalidSignature {
+            error: e.to_string(),
+        })?;
+    if signer != transaction.from() {
+        return Err(AdmissionError::SenderMismatch {
+            from: transaction.from(),
+            signer,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the signature recovers to the sender, or to a key the sender
+/// delegated to within what it delegated, unless the account of `sender`
+/// names an auth program, which is asked instead.
+pub fn check_signer(
+    transaction: &Transaction,
+    sender: Option<&Account>,
+) -> Result<(), AdmissionError> {
+    if sender.and_then(Account::auth_program).is_some() {
+        return Ok(());
+    }
+    let signed = check_signature(transaction);
+    let Err(AdmissionError::SenderMismatch { signer, .. }) = signed else {
+        return signed;
+    };
+    match sender.and_then(|account| account.delegation(&signer)) {
+        Some(delegation) => delegation.permits(transaction, unix_now()).map_err(|e| {
+            AdmissionError::OutsideDelegation {
+                delegate: signer,
+                error: e.to_string(),
+            }
+        }),
+        None => signed,
+    }
+}
+
+/// Checks a delegate transaction grants or revokes something the account of
+/// `sender` can have granted or revoked.
+fn check_delegation(
+    transaction: &Transaction,
+    sender: Option<&Account>,
+) -> Result<(), AdmissionError> {
+    if !transaction.transaction_type().is_delegate() {
+        return Ok(());
+    }
+    let invalid = |error: String| AdmissionError::InvalidDelegation { error };
+    let op = DelegationOp::of(transaction).map_err(|e| invalid(e.to_string()))?;
+    let mut account = sender
+        .cloned()
+        .ok_or_else(|| invalid("the sender has no account to delegate from".to_string()))?;
+    account
+        .apply_delegation_op(op)
+        .map_err(|e| invalid(e.to_string()))
+}
+
+/// Checks an account metadata transaction carries metadata within the
+/// limits, for an account `sender` has.
+fn check_account_metadata(
+    transaction: &Transaction,
+    sender: Option<&Account>,
+) -> Result<(), AdmissionError> {
+    if !transaction.transaction_type().is_set_account_metadata() {
+        return Ok(());
+    }
+    let invalid = |error: String| AdmissionError::InvalidAccountMetadata { error };
+    AccountMetadata::of(transaction).map_err(|e| invalid(e.to_string()))?;
+    match sender {
+        Some(_) => Ok(()),
+        None => Err(invalid(
+            "the sender has no account to set metadata on".to_string(),
+        )),
+    }
+}
+
+/// Checks the sender holds the value a send, a burn or a bridge out takes
+/// from them, and the VERSE for a `fee` on top.
+pub fn check_balance(
+    transaction: &Transaction,
+    sender: Option<&Account>,
+    fee: U256,
+) -> Result<(), AdmissionError> {
+    check_holds(transaction, sender, fee, Account::balance)
+}
+
+/// Like [`check_balance`], less what the sender's pending debits will take,
+/// so sends admitted but not yet applied cannot together spend more than it
+/// holds.
+pub fn check_spendable(
+    transaction: &Transaction,
+    sender: Option<&Account>,
+    fee: U256,
+) -> Result<(), AdmissionError> {
+    check_holds(transaction, sender, fee, Account::spendable)
+}
+
+fn check_holds(
+    transaction: &Transaction,
+    sender: Option<&Account>,
+    fee: U256,
+    holds: fn(&Account, &Address) -> U256,
+) -> Result<(), AdmissionError> {
+    let mut taken = BTreeMap::new();
+    if transaction.transaction_type().debits_sender() {
+        taken.insert(transaction.program_id(), transaction.value());
+    }
+    if !fee.is_zero() {
+        let verse = taken.entry(VERSE_ADDR).or_insert_with(U256::zero);
+        *verse = verse.saturating_add(fee);
+    }
+    for (program_id, value) in taken {
+        let held = sender
+            .filter(|account| account.token(&program_id).is_some())
+            .map(|account| holds(account, &program_id));
+        match held {
+            Some(balance) if balance >= value => {}
+            _ => {
+                return Err(AdmissionError::InsufficientBalance {
+                    program_id,
+                    balance: held.unwrap_or_default(),
+                    value,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod admission_tests {
+    use super::*;
+    use lasr_types::{
+        AccountBuilder, AccountType, ArbitraryData, Delegation, Metadata, Payload, PayloadBuilder,
+        RecoverableSignature, Status, TokenBuilder,
+    };
+    use proptest::prelude::*;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::time::Instant;
+
+    const TOKEN: [u8; 20] = [9; 20];
+
+    fn key(byte: u8) -> (SecretKey, Address) {
+        let secret_key = SecretKey::from_slice(&[byte; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        (secret_key, Address::from(public_key))
+    }
+
+    fn payload(
+        transaction_type: TransactionType,
+        from: Address,
+        program_id: [u8; 20],
+        nonce: u64,
+        inputs: &str,
+    ) -> Payload {
+        PayloadBuilder::default()
+            .transaction_type(transaction_type)
+            .from(from.into())
+            .to([2; 20])
+            .program_id(program_id)
+            .inputs(inputs.to_string())
+            .op(String::new())
+            .value(U256::from(10))
+            .nonce(U256::from(nonce))
+            .build()
+            .unwrap()
+    }
+
+    fn sign(payload: Payload, secret_key: &SecretKey) -> Transaction {
+        let sig = RecoverableSignature::sign(&payload.digest(), secret_key);
+        (payload, sig).into()
+    }
+
+    /// A transaction from the holder of the key made from `from`, signed
+    /// with the key made from `signer`.
+    fn signed(
+        transaction_type: TransactionType,
+        from: u8,
+        signer: u8,
+        program_id: [u8; 20],
+        nonce: u64,
+    ) -> Transaction {
+        let payload = payload(transaction_type, key(from).1, program_id, nonce, "");
+        sign(payload, &key(signer).0)
+    }
+
+    fn send(nonce: u64) -> Transaction {
+        signed(TransactionType::Send(U256::from(0)), 1, 1, TOKEN, nonce)
+    }
+
+    /// A send from the holder of the key made from 1 offering fees.
+    fn send_with_fees(nonce: u64, max_fee: Option<u64>, priority_fee: Option<u64>) -> Transaction {
+        let payload = PayloadBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from(key(1).1.into())
+            .to([2; 20])
+            .program_id(TOKEN)
+            .inputs("abcd".to_string())
+            .op(String::new())
+            .value(U256::from(10))
+            .nonce(U256::from(nonce))
+            .max_fee(max_fee.map(U256::from))
+            .priority_fee(priority_fee.map(U256::from))
+            .build()
+            .unwrap();
+        sign(payload, &key(1).0)
+    }
+
+    /// A send from the holder of the key made from 1, valid from
+    /// `valid_after` and before `valid_before`.
+    fn send_within(valid_after: Option<u64>, valid_before: Option<u64>) -> Transaction {
+        let payload = PayloadBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from(key(1).1.into())
+            .to([2; 20])
+            .program_id(TOKEN)
+            .inputs(String::new())
+            .op(String::new())
+            .value(U256::from(10))
+            .nonce(U256::from(1))
+            .valid_after(valid_after)
+            .valid_before(valid_before)
+            .build()
+            .unwrap();
+        sign(payload, &key(1).0)
+    }
+
+    fn account(nonce: u64, balance: u64) -> Account {
+        account_holding(nonce, &[(Address::new(TOKEN), balance)])
+    }
+
+    /// An account holding `verse` of VERSE besides 100 of the token sent.
+    fn account_with_verse(nonce: u64, verse: u64) -> Account {
+        account_holding(nonce, &[(Address::new(TOKEN), 100), (VERSE_ADDR, verse)])
+    }
+
+    fn account_holding(nonce: u64, balances: &[(Address, u64)]) -> Account {
+        let (_, owner) = key(1);
+        let programs = balances.iter().map(|(program_id, balance)| {
+            let token = TokenBuilder::default()
+                .program_id(*program_id)
+                .owner_id(owner)
+                .balance(U256::from(*balance))
+                .metadata(Metadata::new())
+                .token_ids(vec![])
+                .allowance(BTreeMap::new())
+                .approvals(BTreeMap::new())
+                .data(ArbitraryData::new())
+                .status(Status::Free)
+                .build()
+                .unwrap();
+            (*program_id, token)
+        });
+        AccountBuilder::default()
+            .account_type(AccountType::User)
+            .program_namespace(None)
+            .owner_address(owner)
+            .programs(programs.collect())
+            .nonce(U256::from(nonce))
+            .program_account_data(ArbitraryData::new())
+            .program_account_metadata(Metadata::new())
+            .program_account_linked_programs(BTreeSet::new())
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn a_signed_send_within_the_balance_is_admitted() {
+        let admission = Admission::default();
+        assert_eq!(
+            admission.check(&send(4), Some(&account(3, 10)), None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn signatures_must_recover_to_the_sender() {
+        let admission = Admission::default();
+        let sender = account(0, 10);
+        let unsigned: Transaction =
+            payload(TransactionType::Send(U256::from(0)), key(1).1, TOKEN, 1, "").into();
+        assert!(matches!(
+            admission.check(&unsigned, Some(&sender), None),
+            Err(AdmissionError::InvalidSignature { .. })
+        ));
+
+        let forged = signed(TransactionType::Send(U256::from(0)), 1, 2, TOKEN, 1);
+        assert_eq!(
+            admission.check(&forged, Some(&sender), None),
+            Err(AdmissionError::SenderMismatch {
+                from: key(1).1,
+                signer: key(2).1,
+            })
+        );
+
+        // The high-s twin of the sender's own signature is as valid to
+        // secp256k1, and is turned away.
+        let payload = payload(TransactionType::Send(U256::from(0)), key(1).1, TOKEN, 1, "");
+        let sig = RecoverableSignature::sign(&payload.digest(), &key(1).0);
+        let order = U256::from_str_radix(
+            "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+            16,
+        )
+        .unwrap();
+        let mut s = [0; 32];
+        (order - U256::from_big_endian(&sig.get_s())).to_big_endian(&mut s);
+        let twin = RecoverableSignature::new(sig.get_r(), s, sig.get_v() ^ 1);
+        let malleated: Transaction = (payload, twin).into();
+        assert!(matches!(
+            admission.check(&malleated, Some(&sender), None),
+            Err(AdmissionError::InvalidSignature { error }) if error.contains("malleable")
+        ));
+    }
+
+    #[test]
+    fn an_account_naming_an_auth_program_is_not_held_to_its_signature() {
+        let admission = Admission::default();
+        let forged = signed(TransactionType::Send(U256::from(0)), 1, 2, TOKEN, 1);
+        let mut sender = account(0, 10);
+        assert!(check_signer(&forged, Some(&sender)).is_err());
+
+        sender.set_auth_program(Some(Address::new([0xab; 20])));
+        assert_eq!(check_signer(&forged, Some(&sender)), Ok(()));
+        assert_eq!(admission.check(&forged, Some(&sender), None), Ok(()));
+
+        let refused = AdmissionError::Unauthorized {
+            auth_program: Address::new([0xab; 20]),
+            error: "the auth program refused it".to_string(),
+        };
+        assert_eq!(refused.code(), RpcErrorCode::InvalidSignature);
+        assert_eq!(
+            serde_json::to_value(&refused).unwrap()["reason"],
+            "unauthorized"
+        );
+    }
+
+    #[test]
+    fn a_delegate_signs_for_the_sender_within_its_delegation() {
+        let admission = Admission::default();
+        let mut sender = account(0, 100);
+        sender
+            .apply_delegation_op(DelegationOp::Grant(Delegation {
+                delegate: key(2).1,
+                program_ids: BTreeSet::from([Address::new(TOKEN)]),
+                max_value: U256::from(10),
+                expires_at_nonce: Some(U256::from(1)),
+                expires_at: None,
+            }))
+            .unwrap();
+
+        let delegated = signed(TransactionType::Send(U256::from(0)), 1, 2, TOKEN, 1);
+        assert_eq!(admission.check(&delegated, Some(&sender), None), Ok(()));
+
+        let past_expiry = signed(TransactionType::Send(U256::from(0)), 1, 2, TOKEN, 2);
+        let e = admission
+            .check(&past_expiry, Some(&sender), None)
+            .unwrap_err();
+        assert_eq!(e.code(), RpcErrorCode::SenderMismatch);
+        assert!(matches!(
+            e,
+            AdmissionError::OutsideDelegation { delegate, .. } if delegate == key(2).1
+        ));
+
+        let stranger = signed(TransactionType::Send(U256::from(0)), 1, 3, TOKEN, 1);
+        assert!(matches!(
+            admission.check(&stranger, Some(&sender), None),
+            Err(AdmissionError::SenderMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn a_delegate_transaction_must_grant_or_revoke_something() {
+        let admission = Admission::default();
+        let delegate = |inputs: &str| {
+            let payload = payload(
+                TransactionType::Delegate(U256::from(0)),
+                key(1).1,
+                TOKEN,
+                1,
+                inputs,
+            );
+            sign(payload, &key(1).0)
+        };
+        let sender = account(0, 100);
+
+        let revoke = serde_json::to_string(&DelegationOp::Revoke(key(2).1)).unwrap();
+        assert_eq!(
+            admission.check(&delegate(&revoke), Some(&sender), None),
+            Err(AdmissionError::InvalidDelegation {
+                error: format!("nothing is delegated to {}", key(2).1),
+            })
+        );
+        let e = admission
+            .check(&delegate("{}"), Some(&sender), None)
+            .unwrap_err();
+        assert_eq!(e.code(), RpcErrorCode::InvalidParams);
+    }
+
+    #[test]
+    fn account_metadata_must_be_within_the_limits() {
+        let admission = Admission::default();
+        let set = |inputs: String, value: u64| {
+            let payload = PayloadBuilder::default()
+                .transaction_type(TransactionType::SetAccountMetadata(U256::from(0)))
+                .from(key(1).1.into())
+                .to([0; 20])
+                .program_id([0; 20])
+                .inputs(inputs)
+                .op(String::new())
+                .value(U256::from(value))
+                .nonce(U256::from(1))
+                .build()
+                .unwrap();
+            sign(payload, &key(1).0)
+        };
+        let sender = account(0, 100);
+
+        let named = r#"{"displayName":"alice"}"#.to_string();
+        assert_eq!(
+            admission.check(&set(named.clone(), 0), Some(&sender), None),
+            Ok(())
+        );
+        assert!(matches!(
+            admission.check(&set(named.clone(), 1), Some(&sender), None),
+            Err(AdmissionError::InvalidTransactionType { .. })
+        ));
+        assert!(matches!(
+            admission.check(&set(named, 0), None, None),
+            Err(AdmissionError::InvalidAccountMetadata { .. })
+        ));
+
+        let too_long = format!(r#"{{"avatarUri":"{}"}}"#, "a".repeat(513));
+        let e = admission
+            .check(&set(too_long, 0), Some(&sender), None)
+            .unwrap_err();
+        assert_eq!(
+            e,
+            AdmissionError::InvalidAccountMetadata {
+                error: "avatarUri is 513 bytes long, over the 512 allowed".to_string(),
+            }
+        );
+        assert_eq!(e.code(), RpcErrorCode::InvalidParams);
+    }
+
+    #[test]
+    fn calls_must_name_a_program() {
+        let call = signed(TransactionType::Call(U256::from(0)), 1, 1, [0; 20], 1);
+        assert_eq!(
+            Admission::default().check(&call, Some(&account(0, 0)), None),
+            Err(AdmissionError::MissingProgramId)
+        );
+    }
+
+    #[test]
+    fn each_transaction_type_must_carry_what_it_is_applied_with() {
+        let admission = Admission::default();
+        let check = |transaction_type: TransactionType, program_id: [u8; 20]| {
+            let transaction = signed(transaction_type, 1, 1, program_id, 1);
+            admission.check(&transaction, Some(&account_with_verse(0, 100)), None)
+        };
+        let invalid = |error: &str| {
+            Err(AdmissionError::InvalidTransactionType {
+                error: error.to_string(),
+            })
+        };
+        let deploy = |content_id: &str| TransactionType::Deploy {
+            nonce: U256::from(0),
+            content_id: content_id.to_string(),
+            runtime: Default::default(),
+        };
+        assert_eq!(
+            check(deploy(""), [0; 20]),
+            invalid("deploy transaction names no content id")
+        );
+        assert_eq!(
+            check(deploy("bafyprogram"), [0; 20]),
+            invalid("deploy transaction takes no value, not 10")
+        );
+
+        let bridge_out = |recipient: Address| TransactionType::BridgeOutTo {
+            nonce: U256::from(0),
+            recipient,
+        };
+        assert_eq!(
+            check(bridge_out(Address::default()), TOKEN),
+            invalid("bridge out transaction names no recipient")
+        );
+        assert!(check(bridge_out(Address::new([7; 20])), TOKEN).is_ok());
+        assert_eq!(
+            check(TransactionType::BridgeOut(U256::from(0)), TOKEN)
+                .unwrap_err()
+                .code(),
+            RpcErrorCode::InvalidParams
+        );
+
+        let burn = TransactionType::BurnAmount {
+            nonce: U256::from(0),
+        };
+        assert_eq!(
+            check(burn.clone(), TOKEN),
+            invalid("burn transactions are only supported for VERSE")
+        );
+        assert!(check(burn.clone(), VERSE_ADDR.inner()).is_ok());
+        let burned = signed(burn, 1, 1, VERSE_ADDR.inner(), 1);
+        assert!(matches!(
+            admission.check(&burned, Some(&account_with_verse(0, 9)), None),
+            Err(AdmissionError::InsufficientBalance { .. })
+        ));
+    }
+
+    #[test]
+    fn oversized_inputs_are_turned_away() {
+        let admission = Admission::default().with_input_limits(InputLimits::new(0, 0));
+        let oversized = sign(
+            payload(
+                TransactionType::Send(U256::from(0)),
+                key(1).1,
+                TOKEN,
+                1,
+                "a",
+            ),
+            &key(1).0,
+        );
+        let e = admission
+            .check(&oversized, Some(&account(0, 10)), None)
+            .unwrap_err();
+        assert_eq!(e.code(), RpcErrorCode::InvalidParams);
+        assert_eq!(
+            serde_json::to_value(&e).unwrap(),
+            serde_json::json!({ "reason": "tooLarge", "size": 1, "limit": 0 })
+        );
+    }
+
+    #[test]
+    fn nonces_must_be_unused_and_within_the_window() {
+        let admission = Admission::default().with_max_nonce_ahead(8);
+        let sender = account(3, 10);
+        assert_eq!(
+            admission.check(&send(3), Some(&sender), None),
+            Err(AdmissionError::NonceTooLow {
+                nonce: U256::from(3),
+                next: U256::from(4),
+            })
+        );
+        assert!(admission.check(&send(12), Some(&sender), None).is_ok());
+        let e = admission.check(&send(13), Some(&sender), None).unwrap_err();
+        assert_eq!(
+            e,
+            AdmissionError::NonceTooHigh {
+                nonce: U256::from(13),
+                max: U256::from(12),
+            }
+        );
+        assert_eq!(e.code(), RpcErrorCode::InvalidNonce);
+
+        // A sender with no account yet starts from the first nonce, 1.
+        assert!(admission.check_nonce(&send(1), None).is_ok());
+        assert!(admission.check_nonce(&send(0), None).is_err());
+    }
+
+    #[test]
+    fn a_new_account_sends_its_first_two_transactions_at_one_and_two() {
+        let admission = Admission::default().with_max_nonce_ahead(0);
+        let mut sender = account(0, 100);
+        assert_eq!(
+            admission.check(&send(0), Some(&sender), None),
+            Err(AdmissionError::NonceTooLow {
+                nonce: U256::from(0),
+                next: U256::from(1),
+            })
+        );
+        for nonce in 1..=2 {
+            assert_eq!(admission.check(&send(nonce), Some(&sender), None), Ok(()));
+            sender.apply_send_transaction(send(nonce), None).unwrap();
+        }
+        assert_eq!(sender.nonce(), U256::from(2));
+    }
+
+    #[test]
+    fn sends_must_be_covered_by_the_balance() {
+        let admission = Admission::default();
+        assert_eq!(
+            admission.check(&send(1), Some(&account(0, 9)), None),
+            Err(AdmissionError::InsufficientBalance {
+                program_id: Address::new(TOKEN),
+                balance: U256::from(9),
+                value: U256::from(10),
+            })
+        );
+        let e = admission.check(&send(1), None, None).unwrap_err();
+        assert!(matches!(
+            e,
+            AdmissionError::InsufficientBalance { balance, .. } if balance == U256::from(0)
+        ));
+        assert_eq!(e.code(), RpcErrorCode::InsufficientBalance);
+    }
+
+    #[test]
+    fn max_fees_under_the_floor_are_turned_away() {
+        // 100 for the send and 2 for each of its 4 bytes of inputs.
+        let admission = Admission::default().with_fee_schedule(FeeSchedule::new(100, 2, 0, 0));
+        let sender = account_with_verse(0, 1000);
+        let e = admission
+            .check(&send_with_fees(1, Some(107), None), Some(&sender), None)
+            .unwrap_err();
+        assert_eq!(
+            e,
+            AdmissionError::FeeTooLow {
+                max_fee: U256::from(107),
+                floor: U256::from(108),
+            }
+        );
+        assert_eq!(e.code(), RpcErrorCode::FeeTooLow);
+        assert_eq!(
+            admission.check(&send_with_fees(1, Some(108), None), Some(&sender), None),
+            Ok(())
+        );
+
+        // Transactions signed without fees are admitted only while there is
+        // no floor.
+        let legacy = send_with_fees(1, None, None);
+        assert_eq!(legacy.hash_string(), {
+            let unsigned: Transaction = payload(
+                TransactionType::Send(U256::from(0)),
+                key(1).1,
+                TOKEN,
+                1,
+                "abcd",
+            )
+            .into();
+            unsigned.hash_string()
+        });
+        assert!(matches!(
+            admission.check(&legacy, Some(&sender), None),
+            Err(AdmissionError::FeeTooLow { max_fee, .. }) if max_fee.is_zero()
+        ));
+        assert_eq!(
+            Admission::default().check(&legacy, Some(&sender), None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn fees_must_be_covered_by_the_verse_held() {
+        let admission = Admission::default().with_fee_schedule(FeeSchedule::new(100, 0, 0, 0));
+        let offered = send_with_fees(1, Some(500), Some(20));
+        assert_eq!(
+            admission.check(&offered, Some(&account_with_verse(0, 119)), None),
+            Err(AdmissionError::InsufficientBalance {
+                program_id: VERSE_ADDR,
+                balance: U256::from(119),
+                value: U256::from(120),
+            })
+        );
+        assert_eq!(
+            admission.check(&offered, Some(&account_with_verse(0, 120)), None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn estimates_are_what_is_charged() {
+        let admission = Admission::default().with_fee_schedule(FeeSchedule::new(100, 2, 0, 0));
+        let sender = account_with_verse(0, 1000);
+        let preview: Transaction = payload(
+            TransactionType::Send(U256::from(0)),
+            key(1).1,
+            TOKEN,
+            1,
+            "abcd",
+        )
+        .into();
+        let estimate = admission.estimate(&preview, Some(&sender), None);
+        assert_eq!(estimate.floor, U256::from(108));
+        assert_eq!(estimate.max_fee, U256::from(108));
+        assert_eq!(estimate.rejection, None);
+
+        let sent = send_with_fees(1, Some(estimate.max_fee.as_u64()), None);
+        assert_eq!(admission.check(&sent, Some(&sender), None), Ok(()));
+        assert_eq!(admission.fees().charge(&sent), estimate.fee);
+
+        // The estimate says why a transaction would be turned away.
+        let estimate = admission.estimate(&preview, Some(&account_with_verse(0, 10)), None);
+        assert!(matches!(
+            estimate.rejection,
+            Some(AdmissionError::InsufficientBalance { program_id, .. }) if program_id == VERSE_ADDR
+        ));
+        assert_eq!(
+            serde_json::to_value(&estimate).unwrap()["rejection"]["reason"],
+            "insufficientBalance"
+        );
+    }
+
+    #[test]
+    fn a_transaction_not_yet_valid_is_admitted_to_be_held_and_a_closed_one_is_not() {
+        let admission = Admission::default();
+        let sender = account(0, 10);
+        let now = unix_now();
+        let later = send_within(Some(now + 60), None);
+        assert!(admission.check(&later, Some(&sender), None).is_ok());
+        assert_eq!(
+            check_valid_at(&later, now),
+            Err(AdmissionError::NotYetValid {
+                valid_after: now + 60
+            })
+        );
+        assert!(check_valid_at(&later, now + 60).is_ok());
+
+        let closed = send_within(None, Some(now));
+        let e = admission.check(&closed, Some(&sender), None).unwrap_err();
+        assert_eq!(e, AdmissionError::Expired { valid_before: now });
+        assert_eq!(e.code(), RpcErrorCode::Expired);
+        assert!(check_window(&closed, now - 1).is_ok());
+
+        let empty = send_within(Some(now + 10), Some(now + 10));
+        assert_eq!(
+            check_window(&empty, now),
+            Err(AdmissionError::EmptyWindow {
+                valid_after: now + 10,
+                valid_before: now + 10,
+            })
+        );
+        assert!(check_valid_at(&send(1), now).is_ok());
+    }
+
+    /// Sends at each nonce up to `highest`, indexed by nonce.
+    fn sends(highest: u64) -> Vec<Transaction> {
+        (0..=highest).map(send).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn nonces_are_admitted_at_most_once_and_in_turn(
+            offered in prop::collection::vec(0u64..16, 0..48)
+        ) {
+            let admission = Admission::default().with_max_nonce_ahead(0);
+            let sends = sends(16);
+            let mut sender = account(0, 1_000);
+            let mut admitted = Vec::new();
+            for nonce in offered {
+                let send = &sends[nonce as usize];
+                if admission.check_nonce(send, Some(&sender)).is_ok() {
+                    sender.apply_send_transaction(send.clone(), None).unwrap();
+                    admitted.push(nonce);
+                }
+            }
+            prop_assert!(admitted.first().map_or(true, |first| *first == 1));
+            prop_assert!(admitted.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        }
+
+        #[test]
+        fn every_nonce_offered_until_none_are_taken_is_admitted_once(
+            offered in (1u64..24)
+                .prop_flat_map(|highest| {
+                    prop::collection::vec(1usize..4, highest as usize).prop_map(|copies| {
+                        copies
+                            .into_iter()
+                            .enumerate()
+                            .flat_map(|(nonce, copies)| vec![nonce as u64 + 1; copies])
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .prop_shuffle()
+        ) {
+            let highest = offered.iter().copied().max().unwrap();
+            let admission = Admission::default().with_max_nonce_ahead(0);
+            let sends = sends(highest);
+            let mut sender = account(0, 1_000);
+            let mut admitted = BTreeMap::<u64, usize>::new();
+            loop {
+                let mut progressed = false;
+                for nonce in &offered {
+                    let send = &sends[*nonce as usize];
+                    if admission.check_nonce(send, Some(&sender)).is_ok() {
+                        sender.apply_send_transaction(send.clone(), None).unwrap();
+                        *admitted.entry(*nonce).or_default() += 1;
+                        progressed = true;
+                    }
+                }
+                if !progressed {
+                    break;
+                }
+            }
+            prop_assert_eq!(admitted.len() as u64, highest);
+            prop_assert!(admitted.values().all(|count| *count == 1));
+            prop_assert_eq!(sender.nonce(), U256::from(highest));
+        }
+    }
+
+    /// Times admission of a signed send, most of which is recovering the
+    /// signature. Run with `cargo test -- --ignored admission_latency`.
+    #[test]
+    #[ignore]
+    fn admission_latency() {
+        let admission = Admission::default();
+        let sender = account(0, 10);
+        let transactions: Vec<Transaction> = (1..=1000).map(|nonce| send(nonce % 64 + 1)).collect();
+        let started = Instant::now();
+        for transaction in &transactions {
+            assert!(admission.check(transaction, Some(&sender), None).is_ok());
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "admitted {} transactions in {elapsed:?}, {:?} each",
+            transactions.len(),
+            elapsed / transactions.len() as u32
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! Restricting who may transact with the node, and what they may call.
+//!
+//! An [`AdmissionPolicy`] is asked about every transaction the RPC server
+//! admits, and again once it is dispatched: by the executor before a call is
+//! run, and by the validator before any transaction is applied. So a
+//! transaction pending when the policy is swapped is held to the new one. A
+//! transaction denied is answered as `PolicyDenied`, with the rule it broke
+//! in the error data, and counted by rule.
+//!
+//! The node runs a [`ConfigPolicy`] read from the JSON at
+//! `admission.policy_path` in its configuration, if it is set, and one
+//! denying nothing if not.
+//! It is swapped at runtime through `admin_setAdmissionPolicy`.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use lasr_types::{Address, Transaction, TransactionType, U256};
+use prometheus::IntCounterVec;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{metrics, AdmissionError};
+
+fn denials() -> &'static IntCounterVec {
+    static DENIALS: OnceLock<IntCounterVec> = OnceLock::new();
+    DENIALS.get_or_init(|| {
+        metrics::counter_vec(
+            "admission",
+            "policy_denials_total",
+            "Transactions denied by the admission policy, by the rule they broke.",
+            &["rule"],
+        )
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum AdmissionPolicyError {
+    #[error("failed to read admission policy: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("admission policy is invalid: {0}")]
+    Invalid(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// The rule a transaction was denied by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyRule {
+    SenderDenied,
+    SenderNotAllowed,
+    ProgramDenied,
+    ProgramNotAllowed,
+    ValueTooHigh,
+}
+
+impl PolicyRule {
+    pub const ALL: [PolicyRule; 5] = [
+        PolicyRule::SenderDenied,
+        PolicyRule::SenderNotAllowed,
+        PolicyRule::ProgramDenied,
+        PolicyRule::ProgramNotAllowed,
+        PolicyRule::ValueTooHigh,
+    ];
+
+    /// The `rule` label its denials are counted under.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyRule::SenderDenied => "senderDenied",
+            PolicyRule::SenderNotAllowed => "senderNotAllowed",
+            PolicyRule::ProgramDenied => "programDenied",
+            PolicyRule::ProgramNotAllowed => "programNotAllowed",
+            PolicyRule::ValueTooHigh => "valueTooHigh",
+        }
+    }
+}
+
+/// Why a policy denied a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{error}")]
+pub struct PolicyDenial {
+    pub rule: PolicyRule,
+    pub error: String,
+}
+
+impl From<PolicyDenial> for AdmissionError {
+    fn from(denial: PolicyDenial) -> Self {
+        AdmissionError::Denied {
+            rule: denial.rule,
+            error: denial.error,
+        }
+    }
+}
+
+pub trait AdmissionPolicy: Debug + Send + Sync {
+    /// What the policy is, reported by `admin_getAdmissionPolicy`.
+    fn name(&self) -> &'static str;
+
+    /// Checks `transaction` is one the policy lets through.
+    fn evaluate(&self, transaction: &Transaction) -> Result<(), PolicyDenial>;
+
+    /// The config the policy was read from, if it was.
+    fn config(&self) -> Option<&PolicyConfig> {
+        None
+    }
+}
+
+/// Addresses let through. Those denied never are, and while `allow` is set
+/// only those on it are.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AddressRules {
+    #[serde(default)]
+    pub allow: Option<BTreeSet<Address>>,
+    #[serde(default)]
+    pub deny: BTreeSet<Address>,
+}
+
+impl AddressRules {
+    fn check(
+        &self,
+        address: &Address,
+        denied: PolicyRule,
+        not_allowed: PolicyRule,
+        what: &str,
+    ) -> Result<(), PolicyDenial> {
+        let address = *address;
+        if self.deny.contains(&address) {
+            return Err(PolicyDenial {
+                rule: denied,
+                error: format!("{what} {} is denied", address.to_full_string()),
+            });
+        }
+        match &self.allow {
+            Some(allow) if !allow.contains(&address) => Err(PolicyDenial {
+                rule: not_allowed,
+                error: format!("{what} {} is not allowed", address.to_full_string()),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Who a transaction may be sent from.
+    #[serde(default)]
+    pub senders: AddressRules,
+    /// The programs a call may be made to.
+    #[serde(default)]
+    pub programs: AddressRules,
+    /// The most value a transaction may carry, unlimited if unset.
+    #[serde(default)]
+    pub max_value: Option<U256>,
+}
+
+impl PolicyConfig {
+    pub fn from_file(path: &Path) -> Result<Self, AdmissionPolicyError> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+/// The policy of a [`PolicyConfig`], denying nothing by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigPolicy {
+    config: PolicyConfig,
+}
+
+impl ConfigPolicy {
+    pub fn new(config: PolicyConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl AdmissionPolicy for ConfigPolicy {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn evaluate(&self, transaction: &Transaction) -> Result<(), PolicyDenial> {
+        self.config.senders.check(
+            &transaction.from(),
+            PolicyRule::SenderDenied,
+            PolicyRule::SenderNotAllowed,
+            "sender",
+        )?;
+        if matches!(transaction.transaction_type(), TransactionType::Call(_)) {
+            self.config.programs.check(
+                &transaction.program_id(),
+                PolicyRule::ProgramDenied,
+                PolicyRule::ProgramNotAllowed,
+                "program",
+            )?;
+        }
+        match self.config.max_value {
+            Some(max_value) if transaction.value() > max_value => Err(PolicyDenial {
+                rule: PolicyRule::ValueTooHigh,
+                error: format!(
+                    "value of {} is over the most allowed, {max_value}",
+                    transaction.value()
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn config(&self) -> Option<&PolicyConfig> {
+        Some(&self.config)
+    }
+}
+
+/// The policy in force, and the transactions it has denied, as reported by
+/// `admin_getAdmissionPolicy`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionPolicyStatus {
+    pub policy: &'static str,
+    pub config: Option<PolicyConfig>,
+    pub denied: BTreeMap<PolicyRule, u64>,
+}
+
+/// The policy in force, shared by everything that checks transactions
+/// against it and swapped for all of them at once.
+#[derive(Debug, Clone)]
+pub struct PolicyGate {
+    policy: Arc<RwLock<Arc<dyn AdmissionPolicy>>>,
+}
+
+impl Default for PolicyGate {
+    fn default() -> Self {
+        Self::new(Arc::new(ConfigPolicy::default()))
+    }
+}
+
+impl PolicyGate {
+    pub fn new(policy: Arc<dyn AdmissionPolicy>) -> Self {
+        Self {
+            policy: Arc::new(RwLock::new(policy)),
+        }
+    }
+
+    /// Reads the policy at `path`, denying nothing if there is none.
+    pub fn open(path: Option<&Path>) -> Result<Self, AdmissionPolicyError> {
+        match path {
+            Some(path) => {
+                let config = PolicyConfig::from_file(path)?;
+                Ok(Self::new(Arc::new(ConfigPolicy::new(config))))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn policy(&self) -> Arc<dyn AdmissionPolicy> {
+        Arc::clone(&self.policy.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Holds every transaction checked from now on to `policy`.
+    pub fn swap(&self, policy: Arc<dyn AdmissionPolicy>) {
+        *self.policy.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    /// Holds every transaction checked from now on to the policy of
+    /// `config`.
+    pub fn set(&self, config: PolicyConfig) {
+        self.swap(Arc::new(ConfigPolicy::new(config)));
+    }
+
+    /// Checks `transaction` against the policy in force, counting it by the
+    /// rule it broke if it is denied.
+    pub fn check(&self, transaction: &Transaction) -> Result<(), AdmissionError> {
+        self.policy().evaluate(transaction).map_err(|denial| {
+            denials().with_label_values(&[denial.rule.as_str()]).inc();
+            tracing::warn!(
+                "denied {} by the admission policy: {denial}",
+                transaction.hash_string()
+            );
+            AdmissionError::from(denial)
+        })
+    }
+
+    pub fn status(&self) -> AdmissionPolicyStatus {
+        let policy = self.policy();
+        AdmissionPolicyStatus {
+            policy: policy.name(),
+            config: policy.config().cloned(),
+            denied: PolicyRule::ALL
+                .into_iter()
+                .map(|rule| (rule, denials().with_label_values(&[rule.as_str()]).get()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod admission_policy_tests {
+    use super::*;
+    use lasr_types::TransactionBuilder;
+
+    fn transaction(
+        transaction_type: TransactionType,
+        from: u8,
+        program: u8,
+        value: u64,
+    ) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(transaction_type)
+            .from([from; 20])
+            .to([2; 20])
+            .program_id([program; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(value))
+            .nonce(U256::from(1))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    fn call(from: u8, program: u8) -> Transaction {
+        transaction(TransactionType::Call(U256::from(0)), from, program, 0)
+    }
+
+    fn send(from: u8, value: u64) -> Transaction {
+        transaction(TransactionType::Send(U256::from(0)), from, 0, value)
+    }
+
+    fn rule(policy: &ConfigPolicy, transaction: &Transaction) -> Option<PolicyRule> {
+        policy.evaluate(transaction).err().map(|denial| denial.rule)
+    }
+
+    fn addresses(bytes: &[u8]) -> BTreeSet<Address> {
+        bytes.iter().map(|byte| Address::new([*byte; 20])).collect()
+    }
+
+    #[test]
+    fn senders_are_held_to_the_lists() {
+        let policy = ConfigPolicy::new(PolicyConfig {
+            senders: AddressRules {
+                allow: Some(addresses(&[1, 3])),
+                deny: addresses(&[3]),
+            },
+            ..Default::default()
+        });
+        assert_eq!(rule(&policy, &send(1, 5)), None);
+        assert_eq!(
+            rule(&policy, &send(2, 5)),
+            Some(PolicyRule::SenderNotAllowed)
+        );
+        // Denied even though it is allowed.
+        assert_eq!(rule(&policy, &send(3, 5)), Some(PolicyRule::SenderDenied));
+        assert_eq!(rule(&ConfigPolicy::default(), &send(2, 5)), None);
+    }
+
+    #[test]
+    fn only_calls_are_held_to_the_program_lists() {
+        let policy = ConfigPolicy::new(PolicyConfig {
+            programs: AddressRules {
+                allow: Some(addresses(&[7, 8])),
+                deny: addresses(&[8]),
+            },
+            ..Default::default()
+        });
+        assert_eq!(rule(&policy, &call(1, 7)), None);
+        assert_eq!(rule(&policy, &call(1, 8)), Some(PolicyRule::ProgramDenied));
+        assert_eq!(
+            rule(&policy, &call(1, 9)),
+            Some(PolicyRule::ProgramNotAllowed)
+        );
+        assert_eq!(rule(&policy, &send(1, 5)), None);
+    }
+
+    #[test]
+    fn value_is_held_to_the_most_allowed() {
+        let policy = ConfigPolicy::new(PolicyConfig {
+            max_value: Some(U256::from(100)),
+            ..Default::default()
+        });
+        assert_eq!(rule(&policy, &send(1, 100)), None);
+        assert_eq!(rule(&policy, &send(1, 101)), Some(PolicyRule::ValueTooHigh));
+    }
+
+    #[test]
+    fn a_swapped_policy_holds_every_clone_of_the_gate() {
+        let gate = PolicyGate::default();
+        let validator = gate.clone();
+        assert!(validator.check(&send(4, 5)).is_ok());
+        let before = gate.status().denied[&PolicyRule::SenderDenied];
+
+        let config: PolicyConfig = serde_json::from_str(&format!(
+            r#"{{"senders": {{"deny": ["{}"]}}}}"#,
+            Address::new([4; 20]).to_full_string()
+        ))
+        .unwrap();
+        gate.set(config.clone());
+        assert!(matches!(
+            validator.check(&send(4, 5)),
+            Err(AdmissionError::Denied {
+                rule: PolicyRule::SenderDenied,
+                ..
+            })
+        ));
+        let status = validator.status();
+        assert_eq!(status.policy, "config");
+        assert_eq!(status.config, Some(config));
+        assert_eq!(status.denied[&PolicyRule::SenderDenied], before + 1);
+        assert!(serde_json::from_str::<PolicyConfig>(r#"{"senders": {"block": []}}"#).is_err());
+    }
+}
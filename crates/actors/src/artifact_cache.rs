@@ -0,0 +1,322 @@
+//! Prepared program artifacts, keyed by content id.
+//!
+//! Preparing a program means fetching its package from the content store
+//! and building its container bundle, which takes seconds. The executor keeps
+//! the bundles it has prepared and reuses them for every later call to the
+//! same content, evicting the least recently used once their combined size
+//! passes the configured bound.
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Fetches and prepares program artifacts on a cache miss, and removes them
+/// when they are evicted.
+#[async_trait]
+pub trait ArtifactFetcher {
+    /// Fetches and prepares the artifact for `content_id`, returning the
+    /// number of bytes it takes up.
+    async fn prepare(&self, content_id: &str) -> std::io::Result<u64>;
+
+    /// Removes a prepared artifact.
+    fn discard(&self, content_id: &str) -> std::io::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct ArtifactCacheMetrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl ArtifactCacheMetrics {
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+pub struct ArtifactCache {
+    capacity_bytes: u64,
+    used_bytes: u64,
+    sizes: HashMap<String, u64>,
+    /// Least recently used first.
+    order: VecDeque<String>,
+    /// The content each program was last seen with.
+    programs: HashMap<String, String>,
+    metrics: Arc<ArtifactCacheMetrics>,
+}
+
+impl Default for ArtifactCache {
+    fn default() -> Self {
+        Self::new(2048 * 1024 * 1024)
+    }
+}
+
+impl ArtifactCache {
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            capacity_bytes,
+            used_bytes: 0,
+            sizes: HashMap::new(),
+            order: VecDeque::new(),
+            programs: HashMap::new(),
+            metrics: Arc::new(ArtifactCacheMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<ArtifactCacheMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    pub fn contains(&self, content_id: &str) -> bool {
+        self.sizes.contains_key(content_id)
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes
+    }
+
+    pub fn capacity_bytes(&self) -> u64 {
+        self.capacity_bytes
+    }
+
+    /// The content `program_id` was last prepared with.
+    pub fn content_of(&self, program_id: &str) -> Option<&str> {
+        self.programs
+            .get(&program_id.to_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Makes sure the artifact for `content_id` is prepared, fetching it
+    /// only if it is not already cached.
+    pub async fn get_or_prepare<F: ArtifactFetcher + Sync>(
+        &mut self,
+        content_id: &str,
+        fetcher: &F,
+    ) -> std::io::Result<()> {
+        if self.sizes.contains_key(content_id) {
+            self.metrics.hits.fetch_add(1, Ordering::Relaxed);
+            self.touch(content_id);
+            tracing::debug!("artifact cache hit for {content_id}");
+            return Ok(());
+        }
+
+        self.metrics.misses.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("artifact cache miss for {content_id}, preparing artifact");
+        let size = fetcher.prepare(content_id).await?;
+        self.sizes.insert(content_id.to_string(), size);
+        self.order.push_back(content_id.to_string());
+        self.used_bytes = self.used_bytes.saturating_add(size);
+        self.evict(fetcher);
+
+        Ok(())
+    }
+
+    /// Prepares the artifact `program_id` now runs, first dropping the
+    /// artifact it ran before if its content changed. Used both when a
+    /// program is registered and when it is called, so a program whose
+    /// content is replaced never runs the stale artifact.
+    pub async fn bind<F: ArtifactFetcher + Sync>(
+        &mut self,
+        program_id: &str,
+        content_id: &str,
+        fetcher: &F,
+    ) -> std::io::Result<()> {
+        let program_id = program_id.to_lowercase();
+        if let Some(previous) = self.programs.get(&program_id).cloned() {
+            if previous != content_id {
+                tracing::info!(
+                    "program {program_id} changed content from {previous} to {content_id}"
+                );
+                self.invalidate(&previous, fetcher);
+            }
+        }
+        self.programs.insert(program_id, content_id.to_string());
+        self.get_or_prepare(content_id, fetcher).await
+    }
+
+    /// Drops the artifact for `content_id`, returning whether it was cached.
+    pub fn invalidate<F: ArtifactFetcher>(&mut self, content_id: &str, fetcher: &F) -> bool {
+        if self.remove(content_id, fetcher) {
+            self.metrics.invalidations.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    fn touch(&mut self, content_id: &str) {
+        if let Some(pos) = self.order.iter().position(|id| id == content_id) {
+            if let Some(id) = self.order.remove(pos) {
+                self.order.push_back(id);
+            }
+        }
+    }
+
+    fn remove<F: ArtifactFetcher>(&mut self, content_id: &str, fetcher: &F) -> bool {
+        let Some(size) = self.sizes.remove(content_id) else {
+            return false;
+        };
+        self.order.retain(|id| id != content_id);
+        self.used_bytes = self.used_bytes.saturating_sub(size);
+        if let Err(e) = fetcher.discard(content_id) {
+            tracing::error!("failed to remove artifact for {content_id}: {e}");
+        }
+        true
+    }
+
+    /// Evicts until the cache is back under its bound. The artifact that was
+    /// just prepared is always kept, even if it alone is over the bound.
+    fn evict<F: ArtifactFetcher>(&mut self, fetcher: &F) {
+        while self.used_bytes > self.capacity_bytes && self.order.len() > 1 {
+            let Some(content_id) = self.order.front().cloned() else {
+                break;
+            };
+            tracing::info!("evicting artifact for {content_id} from the artifact cache");
+            if self.remove(&content_id, fetcher) {
+                self.metrics.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod artifact_cache_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockFetcher {
+        size: u64,
+        fetched: Mutex<Vec<String>>,
+        discarded: Mutex<Vec<String>>,
+    }
+
+    impl MockFetcher {
+        fn with_size(size: u64) -> Self {
+            Self {
+                size,
+                ..Default::default()
+            }
+        }
+
+        fn fetched(&self) -> Vec<String> {
+            self.fetched.lock().unwrap().clone()
+        }
+
+        fn discarded(&self) -> Vec<String> {
+            self.discarded.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl ArtifactFetcher for MockFetcher {
+        async fn prepare(&self, content_id: &str) -> std::io::Result<u64> {
+            self.fetched.lock().unwrap().push(content_id.to_string());
+            Ok(self.size)
+        }
+
+        fn discard(&self, content_id: &str) -> std::io::Result<()> {
+            self.discarded.lock().unwrap().push(content_id.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn second_use_skips_fetch() {
+        let fetcher = MockFetcher::with_size(10);
+        let mut cache = ArtifactCache::new(100);
+
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+
+        assert_eq!(fetcher.fetched(), vec!["cid-a".to_string()]);
+        assert_eq!(cache.metrics().misses(), 1);
+        assert_eq!(cache.metrics().hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_is_evicted_over_bound() {
+        let fetcher = MockFetcher::with_size(40);
+        let mut cache = ArtifactCache::new(100);
+
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+        cache.get_or_prepare("cid-b", &fetcher).await.unwrap();
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+        cache.get_or_prepare("cid-c", &fetcher).await.unwrap();
+
+        assert!(cache.contains("cid-a"));
+        assert!(!cache.contains("cid-b"));
+        assert!(cache.contains("cid-c"));
+        assert_eq!(cache.used_bytes(), 80);
+        assert_eq!(fetcher.discarded(), vec!["cid-b".to_string()]);
+        assert_eq!(cache.metrics().evictions(), 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_artifact_is_kept() {
+        let fetcher = MockFetcher::with_size(500);
+        let mut cache = ArtifactCache::new(100);
+
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+
+        assert!(cache.contains("cid-a"));
+        assert!(fetcher.discarded().is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_content_invalidates_previous_artifact() {
+        let fetcher = MockFetcher::with_size(10);
+        let mut cache = ArtifactCache::new(100);
+
+        cache.bind("0xPROGRAM", "cid-a", &fetcher).await.unwrap();
+        cache.bind("0xprogram", "cid-a", &fetcher).await.unwrap();
+        assert_eq!(fetcher.fetched(), vec!["cid-a".to_string()]);
+
+        cache.bind("0xprogram", "cid-b", &fetcher).await.unwrap();
+
+        assert!(!cache.contains("cid-a"));
+        assert!(cache.contains("cid-b"));
+        assert_eq!(fetcher.discarded(), vec!["cid-a".to_string()]);
+        assert_eq!(cache.metrics().invalidations(), 1);
+        assert_eq!(
+            fetcher.fetched(),
+            vec!["cid-a".to_string(), "cid-b".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalidated_artifact_is_fetched_again() {
+        let fetcher = MockFetcher::with_size(10);
+        let mut cache = ArtifactCache::new(100);
+
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+        assert!(cache.invalidate("cid-a", &fetcher));
+        assert!(!cache.invalidate("cid-a", &fetcher));
+        cache.get_or_prepare("cid-a", &fetcher).await.unwrap();
+
+        assert_eq!(fetcher.fetched().len(), 2);
+        assert_eq!(cache.metrics().misses(), 2);
+    }
+}
@@ -0,0 +1,181 @@
+//! The operator key a node signs execution attestations with.
+//!
+//! Signing happens in the batcher once a call's outputs have been applied,
+//! and only in builds with the `attestations` feature. A node rotating its key
+//! keeps listing the keys it retired, so attestations they signed are still
+//! recognised as this node's.
+use lasr_types::{
+    outputs_hash, token_deltas, Account, AccountDigest, Address, ExecutionAttestation, Outputs,
+};
+use secp256k1::SecretKey;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::{KeyHandle, KeyRole, NodeKeys};
+
+#[derive(Debug, Error)]
+pub enum AttestorError {
+    #[error("invalid attestation key: {0}")]
+    InvalidKey(#[from] secp256k1::Error),
+
+    #[error("invalid retired attestation signer {0}")]
+    InvalidSigner(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Attestor {
+    key: KeyHandle,
+}
+
+impl Attestor {
+    pub fn new(secret_key: SecretKey, retired_signers: Vec<Address>) -> Self {
+        Self::from_keys(&NodeKeys::new(secret_key).with_key(
+            KeyRole::Attestation,
+            secret_key,
+            retired_signers,
+        ))
+    }
+
+    /// Signs with the node's attestation key, whichever it is rotated to.
+    pub fn from_keys(keys: &NodeKeys) -> Self {
+        Self {
+            key: keys.key(KeyRole::Attestation),
+        }
+    }
+
+    /// Parses a hex encoded `secret_key` and a comma separated list of
+    /// `retired_signers` addresses.
+    pub fn from_config(secret_key: &str, retired_signers: &str) -> Result<Self, AttestorError> {
+        let secret_key = SecretKey::from_str(secret_key.trim_start_matches("0x"))?;
+        let retired_signers = retired_signers
+            .split(',')
+            .map(str::trim)
+            .filter(|signer| !signer.is_empty())
+            .map(|signer| {
+                Address::from_hex(signer).map_err(|_| AttestorError::InvalidSigner(signer.into()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(secret_key, retired_signers))
+    }
+
+    /// The address attestations are currently signed by.
+    pub fn signer(&self) -> Address {
+        self.key.signer()
+    }
+
+    /// The current signer followed by every retired one.
+    pub fn trusted_signers(&self) -> Vec<Address> {
+        self.key.trusted_signers()
+    }
+
+    /// Signs the execution of `transaction_hash`, given every account it
+    /// changed as it was before and after the outputs were applied.
+    pub fn attest<'a>(
+        &self,
+        transaction_hash: String,
+        outputs: &Outputs,
+        changes: impl IntoIterator<Item = (Address, Option<&'a Account>, &'a Account)>,
+    ) -> ExecutionAttestation {
+        let mut changes: Vec<_> = changes.into_iter().collect();
+        changes.sort_by_key(|(address, _, _)| *address);
+
+        let mut input_accounts = Vec::with_capacity(changes.len());
+        let mut deltas = Vec::new();
+        for (address, before, after) in changes {
+            input_accounts.push(AccountDigest::new(address, before));
+            deltas.extend(token_deltas(address, before, after));
+        }
+
+        ExecutionAttestation::sign(
+            transaction_hash,
+            input_accounts,
+            deltas,
+            outputs_hash(outputs),
+            &self.key.secret_key(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod attestation_tests {
+    use super::*;
+    use lasr_types::{ArbitraryData, Inputs, Metadata, Status, TokenBuilder, U256};
+    use secp256k1::Secp256k1;
+    use std::collections::BTreeMap;
+
+    fn account(owner: Address, program_id: Address, balance: u64) -> Account {
+        let mut account = Account::new(owner);
+        let token = TokenBuilder::default()
+            .program_id(program_id)
+            .owner_id(owner)
+            .balance(U256::from(balance))
+            .metadata(Metadata::new())
+            .token_ids(vec![])
+            .allowance(BTreeMap::new())
+            .approvals(BTreeMap::new())
+            .data(ArbitraryData::new())
+            .status(Status::Free)
+            .build()
+            .expect("failed to build test token");
+        account.insert_program(&program_id, token);
+        account
+    }
+
+    fn outputs() -> Outputs {
+        Outputs::new(Inputs::default(), Vec::new())
+    }
+
+    #[test]
+    fn tampered_delta_fails_verification() {
+        let (secret_key, _) = Secp256k1::new().generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let attestor = Attestor::new(secret_key, Vec::new());
+        let program_id = Address::new([9; 20]);
+        let sender = Address::new([1; 20]);
+        let receiver = Address::new([2; 20]);
+        let sender_before = account(sender, program_id, 100);
+        let sender_after = account(sender, program_id, 60);
+        let receiver_after = account(receiver, program_id, 40);
+
+        let mut attestation = attestor.attest(
+            "0xabcdef".to_string(),
+            &outputs(),
+            [
+                (receiver, None, &receiver_after),
+                (sender, Some(&sender_before), &sender_after),
+            ],
+        );
+        attestation
+            .verify_signed_by(&attestor.trusted_signers())
+            .unwrap();
+        assert_eq!(attestation.input_accounts[0].address, sender);
+        assert!(attestation.input_accounts[1].hash.is_none());
+        assert_eq!(attestation.token_deltas.len(), 2);
+        assert_eq!(attestation.token_deltas[0].before, U256::from(100));
+        assert_eq!(attestation.token_deltas[0].after, U256::from(60));
+
+        attestation.token_deltas[1].after = U256::from(4000);
+        assert!(attestation.verify().is_err());
+    }
+
+    #[test]
+    fn retired_signers_stay_trusted() {
+        let secp = Secp256k1::new();
+        let (old_key, _) = secp.generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let (new_key, _) = secp.generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let old = Attestor::new(old_key, Vec::new());
+        let signed_before_rotation = old.attest("0x01".to_string(), &outputs(), []);
+
+        let rotated = Attestor::from_config(
+            &new_key.display_secret().to_string(),
+            &format!(" {} ,", old.signer().to_full_string()),
+        )
+        .unwrap();
+        assert_ne!(rotated.signer(), old.signer());
+        signed_before_rotation
+            .verify_signed_by(&rotated.trusted_signers())
+            .unwrap();
+        assert!(signed_before_rotation
+            .verify_signed_by(&[rotated.signer()])
+            .is_err());
+    }
+}
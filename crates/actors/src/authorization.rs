@@ -0,0 +1,120 @@
+//! Authorization of transactions by a program, for accounts that name one in
+//! place of a signature by their owner's key.
+//!
+//! A program account may hand the question of who acts for it to an auth
+//! program, e.g. one accepting any two of three keys, or a session key. Its
+//! transactions are then not held to their secp256k1 signature. The auth
+//! program is run with op `authorize`, the transaction and the account it is
+//! sent from as its inputs, and answers `true` or `false`, its verdict. The
+//! RPC server asks before a transaction is admitted, and the validator again
+//! when the transaction's turn comes, as it checks signatures.
+//!
+//! The run is a simulation held to a budget far tighter than a call's, the
+//! [`AuthLimits`]. Nothing it outputs is applied and its logs are not
+//! published, so an auth program can read the transaction and the account
+//! and change neither. Only the account a transaction is sent from is asked
+//! about, never the auth program's own, so one authorization never runs
+//! another.
+use std::time::Duration;
+
+use lasr_messages::{ActorType, ExecutorMessage};
+use lasr_types::{Account, AccountType, Address, Inputs, Transaction};
+use ractor::{rpc::CallResult, ActorRef};
+
+use crate::{get_account, AdmissionError};
+
+/// The op an auth program is run with.
+pub const AUTH_OP: &str = "authorize";
+
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_millis(500);
+pub const DEFAULT_AUTH_MEMORY_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Bytes an auth program may write, a `true` or `false` with room to spare.
+pub const MAX_AUTH_OUTPUT_BYTES: u64 = 64;
+
+/// How long past its timeout an authorization waits on the executor, for
+/// the auth program's artifact to be prepared.
+const AUTH_PREPARE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The budget an auth program runs within, on top of the limits it would
+/// run a call under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthLimits {
+    pub timeout: Duration,
+    pub memory_bytes: u64,
+}
+
+impl Default for AuthLimits {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_AUTH_TIMEOUT,
+            memory_bytes: DEFAULT_AUTH_MEMORY_BYTES,
+        }
+    }
+}
+
+/// Asks the auth program `sender` names whether it authorizes
+/// `transaction`, reading the program's account as `who`.
+pub async fn ask_auth_program(
+    transaction: &Transaction,
+    sender: &Account,
+    auth_program: Address,
+    limits: &AuthLimits,
+    who: ActorType,
+) -> Result<(), AdmissionError> {
+    let unauthorized = |error: String| AdmissionError::Unauthorized {
+        auth_program,
+        error,
+    };
+    let program = match get_account(auth_program, who).await {
+        Some(program) if matches!(program.account_type(), AccountType::Program(_)) => program,
+        _ => return Err(unauthorized("there is no such program".to_string())),
+    };
+    let inputs = Inputs {
+        version: 1,
+        account_info: sender.clone(),
+        transaction: transaction.clone(),
+        op: AUTH_OP.to_string(),
+        inputs: transaction.inputs(),
+    };
+
+    let executor: ActorRef<ExecutorMessage> =
+        ractor::registry::where_is(ActorType::Executor.to_string())
+            .ok_or_else(|| unauthorized("the executor is not running".to_string()))?
+            .into();
+    let answer = executor
+        .call(
+            |reply| ExecutorMessage::Authorize {
+                program,
+                inputs,
+                timeout: limits.timeout,
+                memory_bytes: limits.memory_bytes,
+                reply,
+            },
+            Some(limits.timeout + AUTH_PREPARE_TIMEOUT),
+        )
+        .await
+        .map_err(|e| unauthorized(e.to_string()))?;
+    let outputs = match answer {
+        CallResult::Success(Ok(outputs)) => outputs,
+        CallResult::Success(Err(reason)) => return Err(unauthorized(reason.to_string())),
+        CallResult::Timeout => {
+            return Err(unauthorized(format!(
+                "no answer within {:?}",
+                limits.timeout
+            )))
+        }
+        CallResult::SenderError => {
+            return Err(unauthorized(
+                "the executor dropped the authorization".to_string(),
+            ))
+        }
+    };
+    match serde_json::from_str::<bool>(outputs.trim()) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(unauthorized("the auth program refused it".to_string())),
+        Err(_) => Err(unauthorized(
+            "the auth program answered neither true nor false".to_string(),
+        )),
+    }
+}
@@ -3,10 +3,12 @@ use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     error::Error as StdError,
     fmt::{Debug, Display},
-    sync::Arc,
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 
 use async_trait::async_trait;
+use borsh::{BorshDeserialize, BorshSerialize};
 use eigenda_client::{batch, proof::BlobVerificationProof, response::BlobResponse};
 use ethereum_types::H256;
 use flate2::{
@@ -16,8 +18,9 @@ use flate2::{
 use futures::{
     future::BoxFuture,
     stream::{FuturesUnordered, StreamExt},
-    FutureExt,
+    Future, FutureExt,
 };
+use prometheus::{IntCounter, IntGauge};
 use ractor::{
     concurrency::{oneshot, OneshotReceiver},
     errors::MessagingErr,
@@ -37,26 +40,38 @@ use tokio::{
     },
     task::JoinHandle,
 };
+use tracing::Instrument;
 use web3::types::BlockNumber;
 
+use crate::encoding::{self, Encoding, EncodingError};
+#[cfg(feature = "attestations")]
+use crate::Attestor;
 use crate::{
-    account_cache, get_account, get_actor_ref, handle_actor_response, process_group_changed,
-    AccountCacheActor, AccountCacheError, ActorExt, Coerce, DaClientError, EoClientError,
-    PendingTransactionError, SchedulerError, StaticFuture, StorageRef, UnorderedFuturePool,
+    account_cache, cast_between, get_account, get_actor_ref, handle_actor_response, lifecycle,
+    mailbox_handling, mailbox_probed, metrics, process_group_changed, unix_now, AccountCacheActor,
+    AccountCacheError, ActorExt, BatchFeed, BatchIndex, BatchReceipts, BatchRecord, Coerce,
+    CommitEntry, CommitLog, DaClientError, EmittedEvent, EoClientError, EventLog, FeeSchedule,
+    LifecycleStage, PendingTransactionError, ProgramRecord, ProgramRegistry, ProgramStats,
+    RecentTransactions, SchedulerError, StaticFuture, StorageRef, TokenDelta,
+    TransactionInclusions, TransactionIndex, UnorderedFuturePool, INPUT_REGISTRATION_FIELDS,
 };
+use crate::{burn_verse_supply, mint_verse_supply, new_verse_program_account, token_deltas};
 use lasr_messages::{
-    AccountCacheMessage, ActorName, ActorType, BatcherMessage, DaClientMessage, EoMessage,
-    PendingTransactionMessage, SchedulerMessage, SupervisorType,
+    AccountCacheMessage, AccountProof, ActorName, ActorType, BatchError, BatchHeader, BatchStatus,
+    BatcherMessage, DaClientMessage, EoMessage, InclusionProof, LasrError,
+    PendingTransactionMessage, SchedulerMessage, SupervisorType, Withdrawal,
 };
 
+use lasr_compute::PROGRAM_RUNTIME_KEY;
 use lasr_contract::create_program_id;
 
 use lasr_types::{
-    Account, AccountBuilder, AccountType, Address, AddressOrNamespace, ArbitraryData,
-    BurnInstruction, ContractLogType, CreateInstruction, Instruction, Metadata, MetadataValue,
-    Namespace, Outputs, PersistenceStore, ProgramAccount, ProgramUpdate, TokenDistribution,
-    TokenOrProgramUpdate, TokenUpdate, Transaction, TransactionType, TransferInstruction,
-    UpdateInstruction, U256,
+    account_leaf, merkle_root, transaction_leaf, Account, AccountBuilder, AccountMetadata,
+    AccountType, Address, AddressOrNamespace, ArbitraryData, BurnInstruction, ContractLogType,
+    CreateInstruction, DelegationOp, Instruction, MerkleTree, Metadata, MetadataValue, Namespace,
+    Outputs, PersistenceStore, ProgramAccount, ProgramEvent, ProgramUpdate, StorageQuota, Token,
+    TokenDistribution, TokenOrProgramUpdate, TokenUpdate, Transaction, TransactionType,
+    TransferInstruction, UpdateInstruction, U256,
 };
 
 use derive_builder::Builder;
@@ -94,6 +109,19 @@ pub enum BatcherError {
     Custom(String),
 }
 
+impl From<BatcherError> for LasrError {
+    fn from(e: BatcherError) -> Self {
+        let e = match e {
+            BatcherError::Stdio(e) => BatchError::Persistence(e.to_string()),
+            BatcherError::FailedTransaction { msg, .. } => BatchError::InvalidTransaction(msg),
+            BatcherError::Custom(reason) => BatchError::Custom(reason),
+            // The batcher could not reach an actor, or be reached.
+            e => BatchError::Unavailable(e.to_string()),
+        };
+        LasrError::Batch(e)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct BatcherActor {
     future_pool: UnorderedFuturePool<StaticFuture<Result<(), BatcherError>>>,
@@ -111,18 +139,46 @@ impl ActorName for BatcherActor {
     }
 }
 
-#[derive(Builder, Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(
+    Builder, Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize, Default,
+)]
 pub struct Batch {
     transactions: HashMap<String, Transaction>,
     accounts: HashMap<String, Account>,
+    /// The events each call in the batch emitted, by transaction hash.
+    #[builder(default)]
+    #[serde(default)]
+    events: BTreeMap<String, Vec<ProgramEvent>>,
+    /// What the batch is encoded in when it is dispersed.
+    #[builder(default)]
+    #[serde(skip)]
+    #[borsh(skip)]
+    encoding: Encoding,
 }
 
 // Structure for persistence store `Account` values
-#[derive(Debug, Hash, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(
+    Debug, Hash, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq,
+)]
 pub struct AccountValue {
     pub account: Account,
 }
 
+impl AccountValue {
+    /// The account stored as `bytes`, in an envelope or bare, and in the
+    /// layout accounts are written in now or one they were written in before.
+    pub fn decode(bytes: &[u8]) -> Result<Self, EncodingError> {
+        encoding::decode(bytes).or_else(|e| {
+            let account = match Encoding::of(bytes) {
+                Some(Encoding::Bincode) => Account::from_legacy_bincode(&bytes[1..]),
+                Some(Encoding::Borsh) => Account::from_legacy_borsh(&bytes[1..]),
+                None => Account::from_legacy_bincode(bytes),
+            };
+            account.map(|account| AccountValue { account }).ok_or(e)
+        })
+    }
+}
+
 // // Structure for persistence store `Transaction` values
 // #[derive(Debug, Hash, Clone, Serialize, Deserialize, PartialEq, Eq)]
 // pub struct TransactionValue {
@@ -134,13 +190,138 @@ impl Batch {
         Self {
             transactions: HashMap::new(),
             accounts: HashMap::new(),
+            events: BTreeMap::new(),
+            encoding: Encoding::default(),
         }
     }
 
+    /// Disperses the batch encoded in `encoding`.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
     pub fn empty(&self) -> bool {
         self.transactions().is_empty() && self.accounts().is_empty()
     }
 
+    /// The accounts the batch leaves behind by address, in order, each with
+    /// the hash of the account as it is persisted.
+    fn account_hashes(&self) -> Result<Vec<(Address, H256)>, BatcherError> {
+        let mut accounts = self
+            .accounts
+            .iter()
+            .map(|(address, account)| {
+                let address = Address::from_hex(address)
+                    .map_err(|e| BatcherError::Custom(format!("{address}: {e}")))?;
+                let bytes =
+                    bincode::serialize(account).map_err(|e| BatcherError::Custom(e.to_string()))?;
+                Ok((address, H256::from_slice(&Keccak256::digest(bytes))))
+            })
+            .collect::<Result<Vec<_>, BatcherError>>()?;
+        accounts.sort_by_key(|(address, _)| *address);
+        Ok(accounts)
+    }
+
+    /// The hashes of the batch's transactions, in order.
+    fn transaction_hashes(&self) -> Result<Vec<H256>, BatcherError> {
+        let mut hashes = self
+            .transactions
+            .keys()
+            .map(|transaction_hash| {
+                hex::decode(transaction_hash.trim_start_matches("0x"))
+                    .ok()
+                    .filter(|bytes| bytes.len() == 32)
+                    .map(|bytes| H256::from_slice(&bytes))
+                    .ok_or_else(|| {
+                        BatcherError::Custom(format!("{transaction_hash} is not a hash"))
+                    })
+            })
+            .collect::<Result<Vec<_>, BatcherError>>()?;
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    /// A commitment to the accounts the batch leaves behind, the Merkle root
+    /// of each by its address and hash, in order of address.
+    pub fn state_root(&self) -> Result<H256, BatcherError> {
+        let leaves: Vec<H256> = self
+            .account_hashes()?
+            .iter()
+            .map(|(address, account_hash)| account_leaf(address, account_hash))
+            .collect();
+        Ok(merkle_root(&leaves))
+    }
+
+    /// A commitment to the hashes of the batch's transactions, the Merkle
+    /// root of each in order.
+    pub fn transactions_root(&self) -> Result<H256, BatcherError> {
+        let leaves: Vec<H256> = self
+            .transaction_hashes()?
+            .iter()
+            .map(transaction_leaf)
+            .collect();
+        Ok(merkle_root(&leaves))
+    }
+
+    /// The proof of each of the batch's transactions as batch `batch_id`,
+    /// with the accounts it was sent from and to, against the batch's roots.
+    pub fn inclusion_proofs(&self, batch_id: u64) -> Result<Vec<InclusionProof>, BatcherError> {
+        let accounts = self.account_hashes()?;
+        let state = MerkleTree::new(
+            accounts
+                .iter()
+                .map(|(address, account_hash)| account_leaf(address, account_hash))
+                .collect(),
+        );
+        let account_proof = |address: Address| {
+            let index = accounts
+                .binary_search_by_key(&address, |(address, _)| *address)
+                .ok()?;
+            Some(AccountProof {
+                address,
+                account_hash: accounts[index].1,
+                proof: state.proof(index)?,
+            })
+        };
+        let hashes = self.transaction_hashes()?;
+        let transactions = MerkleTree::new(hashes.iter().map(transaction_leaf).collect());
+        let proofs = hashes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, transaction_hash)| {
+                let key = format!("0x{}", hex::encode(transaction_hash));
+                let transaction = self.transactions.get(&key)?;
+                let mut parties = vec![transaction.from(), transaction.to()];
+                parties.dedup();
+                Some(InclusionProof {
+                    batch_id,
+                    transaction_hash: *transaction_hash,
+                    proof: transactions.proof(index)?,
+                    accounts: parties.into_iter().filter_map(&account_proof).collect(),
+                })
+            })
+            .collect();
+        Ok(proofs)
+    }
+
+    /// A commitment to the events the batch's calls emitted, hashing each
+    /// call's in order of transaction hash.
+    pub fn events_root(&self) -> Result<H256, BatcherError> {
+        let mut hasher = Keccak256::new();
+        for (transaction_hash, events) in &self.events {
+            hasher.update(transaction_hash.as_bytes());
+            hasher.update(
+                serde_json::to_vec(events).map_err(|e| BatcherError::Custom(e.to_string()))?,
+            );
+        }
+        Ok(H256::from_slice(&hasher.finalize()))
+    }
+
     pub fn get_user_account(&self, address: Address) -> Option<Account> {
         if let Some(ua) = self.accounts().get(&address.to_full_string()) {
             return Some(ua.clone());
@@ -168,24 +349,27 @@ impl Batch {
     }
 
     pub(super) fn serialize_batch(&self) -> Option<Vec<u8>> {
-        Some(
-            serde_json::to_string(&self)
-                .typecast()
-                .log_err(|e| {
-                    BatcherError::Custom(format!(
-                        "ERROR: failed to serialize batch to json string: {e:?}"
-                    ))
-                })?
-                .as_bytes()
-                .to_vec(),
-        )
+        self.encoding
+            .encode(self)
+            .typecast()
+            .log_err(|e| BatcherError::Custom(format!("ERROR: failed to serialize batch: {e:?}")))
     }
 
     pub(super) fn deserialize_batch(bytes: Vec<u8>) -> Option<Self> {
         let decompressed = Batch::decompress_batch(bytes)?;
-        serde_json::from_str(&String::from_utf8_lossy(&decompressed))
+        // Batches dispersed before they were put in envelopes are JSON.
+        if decompressed.first() == Some(&b'{') {
+            return serde_json::from_slice(&decompressed)
+                .typecast()
+                .log_err(|e| {
+                    BatcherError::Custom(format!("ERROR: failed to deserialize batch: {e:?}"))
+                });
+        }
+        let format = Encoding::of(&decompressed).unwrap_or_default();
+        encoding::decode::<Batch>(&decompressed)
             .typecast()
             .log_err(|e| BatcherError::Custom(format!("ERROR: failed to deserialize batch: {e:?}")))
+            .map(|batch| batch.with_encoding(format))
     }
 
     pub(super) fn compress_batch(&self) -> Option<Vec<u8>> {
@@ -246,8 +430,14 @@ impl Batch {
     pub(super) fn transaction_would_exceed_capacity(
         &self,
         transaction: Transaction,
+        events: &[ProgramEvent],
     ) -> Option<bool> {
         let mut test_batch = self.clone();
+        if !events.is_empty() {
+            test_batch
+                .events
+                .insert(transaction.hash_string(), events.to_vec());
+        }
         test_batch
             .transactions
             .insert(transaction.hash_string(), transaction.clone());
@@ -267,10 +457,22 @@ impl Batch {
     }
 
     pub fn insert_transaction(&mut self, transaction: Transaction) -> Result<(), BatcherError> {
+        self.insert_transaction_with_events(transaction, Vec::new())
+    }
+
+    /// Inserts `transaction` along with the `events` its program emitted.
+    pub fn insert_transaction_with_events(
+        &mut self,
+        transaction: Transaction,
+        events: Vec<ProgramEvent>,
+    ) -> Result<(), BatcherError> {
         if self
-            .transaction_would_exceed_capacity(transaction.clone())
+            .transaction_would_exceed_capacity(transaction.clone(), &events)
             .is_some_and(|at_cap| !at_cap)
         {
+            if !events.is_empty() {
+                self.events.insert(transaction.hash_string(), events);
+            }
             self.transactions
                 .insert(transaction.hash_string(), transaction.clone());
             return Ok(());
@@ -309,13 +511,97 @@ impl Batch {
     pub fn accounts(&self) -> HashMap<String, Account> {
         self.accounts.clone()
     }
+
+    pub fn events(&self) -> &BTreeMap<String, Vec<ProgramEvent>> {
+        &self.events
+    }
+}
+
+struct BatcherMetrics {
+    committed: IntCounter,
+    finalized: IntCounter,
+    latest_batch_id: IntGauge,
+    largest_account_bytes: IntGauge,
+}
+
+fn batcher_metrics() -> &'static BatcherMetrics {
+    static METRICS: OnceLock<BatcherMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| BatcherMetrics {
+        committed: metrics::counter(
+            "batcher",
+            "transactions_committed_total",
+            "Transactions whose accounts were committed to the account cache.",
+        ),
+        finalized: metrics::counter(
+            "batcher",
+            "batches_finalized_total",
+            "Batches whose blobs were verified on the DA layer.",
+        ),
+        latest_batch_id: metrics::gauge(
+            "batcher",
+            "latest_batch_id",
+            "The id of the batch finalized last.",
+        ),
+        largest_account_bytes: metrics::gauge(
+            "batcher",
+            "largest_account_bytes",
+            "The bytes of metadata and data in the largest account committed.",
+        ),
+    })
+}
+
+/// Where a batcher sealing every transaction as soon as it is applied
+/// writes accounts to, and records the batches settled in.
+#[derive(Clone)]
+struct InstantSeal {
+    storage_ref: StorageRef,
+    receipts: BatchReceipts,
 }
 
 pub struct Batcher {
     parent: Batch,
     children: VecDeque<Batch>,
-    cache: HashMap<String /* request_id*/, Batch>,
+    cache: HashMap<String /* request_id*/, (Batch, u64 /* sealed_at */)>,
     receiver_thread_tx: Sender<OneshotReceiver<(String, BlobVerificationProof)>>,
+    applied: RecentTransactions<()>,
+    /// The id the next batch finalized is settled under.
+    next_batch_id: u64,
+    /// The balances changed by the transactions applied, and the batches
+    /// they went into.
+    inclusions: TransactionInclusions,
+    /// The programs registered, and the batches their registrations went
+    /// into.
+    programs: ProgramRegistry,
+    /// The bytes of state each program's calls wrote, among what else they
+    /// cost.
+    program_stats: ProgramStats,
+    /// The batches finalized, as `getBatch` reports them.
+    batches: BatchIndex,
+    /// The transactions finalized by the accounts and programs they
+    /// involve, if they are indexed.
+    transaction_index: Option<TransactionIndex>,
+    /// The events emitted in the batches finalized, as `getEvents` reports
+    /// them.
+    event_log: EventLog,
+    /// The batches finalized, published whole to the followers subscribed.
+    batch_feed: BatchFeed,
+    /// What the transactions applied are charged.
+    fees: FeeSchedule,
+    /// How many bytes of metadata and data the transactions applied may
+    /// leave in an account.
+    storage_quota: StorageQuota,
+    /// The bytes counted against its quota of every account committed, as
+    /// it was committed last.
+    account_sizes: HashMap<Address, usize>,
+    /// Set on a devnet that seals every transaction before answering it.
+    instant_seal: Option<InstantSeal>,
+    /// The transactions committed and not yet finalized, kept so a crash
+    /// loses none that was answered.
+    commit_log: CommitLog,
+    /// What the accounts stored and the batches dispersed are encoded in.
+    encoding: Encoding,
+    #[cfg(feature = "attestations")]
+    attestor: Option<Attestor>,
 }
 
 impl Batcher {
@@ -342,7 +628,7 @@ impl Batcher {
                                 proof
                             };
 
-                            batcher.cast(message).typecast().log_err(|err|  {
+                            cast_between(ActorType::DaClient, ActorType::Batcher, &batcher, message).typecast().log_err(|err|  {
                                 BatcherError::Custom(format!("failed to cast blob verification proof: {err:?}"))
                             });
                         }
@@ -360,6 +646,371 @@ impl Batcher {
             children: VecDeque::new(),
             cache: HashMap::new(),
             receiver_thread_tx,
+            applied: RecentTransactions::default(),
+            next_batch_id: 0,
+            inclusions: TransactionInclusions::default(),
+            programs: ProgramRegistry::default(),
+            program_stats: ProgramStats::default(),
+            batches: BatchIndex::default(),
+            transaction_index: None,
+            event_log: EventLog::default(),
+            batch_feed: BatchFeed::default(),
+            fees: FeeSchedule::default(),
+            storage_quota: StorageQuota::default(),
+            account_sizes: HashMap::new(),
+            instant_seal: None,
+            commit_log: CommitLog::default(),
+            encoding: Encoding::default(),
+            #[cfg(feature = "attestations")]
+            attestor: None,
+        }
+    }
+
+    /// What became of the transactions applied, shared with whatever
+    /// reports on them.
+    pub fn inclusions(&self) -> TransactionInclusions {
+        self.inclusions.clone()
+    }
+
+    /// The programs registered, shared with whatever reports on them.
+    pub fn programs(&self) -> ProgramRegistry {
+        self.programs.clone()
+    }
+
+    /// The batches finalized, shared with whatever reports on them.
+    pub fn batches(&self) -> BatchIndex {
+        self.batches.clone()
+    }
+
+    /// The events emitted in the batches finalized, shared with whatever
+    /// reports on them.
+    pub fn event_log(&self) -> EventLog {
+        self.event_log.clone()
+    }
+
+    /// Records the events emitted in every batch finalized in `event_log`.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = event_log;
+        self
+    }
+
+    /// Publishes every batch finalized, whole, to `batch_feed`.
+    pub fn with_batch_feed(mut self, batch_feed: BatchFeed) -> Self {
+        self.batch_feed = batch_feed;
+        self
+    }
+
+    /// Records batches in `batches`, numbering them on from the last it
+    /// holds.
+    pub fn with_batch_index(mut self, batches: BatchIndex) -> Self {
+        self.next_batch_id = batches.next_batch_id();
+        self.batches = batches;
+        self
+    }
+
+    /// Indexes the transactions of every batch finalized in `index`.
+    pub fn with_transaction_index(mut self, index: TransactionIndex) -> Self {
+        self.transaction_index = Some(index);
+        self
+    }
+
+    /// Charges transactions by `fees` instead of charging nothing.
+    pub fn with_fee_schedule(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Remembers the last `window` transactions applied, and what became of
+    /// them.
+    pub fn with_recent_window(mut self, window: usize) -> Self {
+        self.applied = RecentTransactions::new(window);
+        self.inclusions = TransactionInclusions::new(window);
+        self
+    }
+
+    /// Counts the bytes of state each program's calls write in
+    /// `program_stats`.
+    pub fn with_program_stats(mut self, program_stats: ProgramStats) -> Self {
+        self.program_stats = program_stats;
+        self
+    }
+
+    /// Holds the accounts the transactions applied leave to `quota`.
+    pub fn with_storage_quota(mut self, quota: StorageQuota) -> Self {
+        self.storage_quota = quota;
+        self
+    }
+
+    /// Seals every transaction applied from now on into a batch of its own
+    /// before it is answered, writing its accounts to `storage_ref`, and
+    /// waits for the DA layer to confirm the batch rather than polling for
+    /// it. Nothing is submitted for settlement: the batch is recorded in
+    /// `receipts` as settled as soon as it is finalized, as a devnet settles
+    /// nowhere else.
+    pub fn with_instant_seal(mut self, storage_ref: StorageRef, receipts: BatchReceipts) -> Self {
+        self.instant_seal = Some(InstantSeal {
+            storage_ref,
+            receipts,
+        });
+        self
+    }
+
+    /// The accounts committed, largest first, with the bytes counted
+    /// against their quota.
+    pub fn account_sizes(&self) -> Vec<(Address, usize)> {
+        let mut sizes: Vec<_> = self
+            .account_sizes
+            .iter()
+            .map(|(address, size)| (*address, *size))
+            .collect();
+        sizes.sort_by(|(a, a_size), (b, b_size)| b_size.cmp(a_size).then(a.cmp(b)));
+        sizes
+    }
+
+    /// Logs every transaction committed from now on to `commit_log` before
+    /// it is answered, until the batch it goes into is finalized.
+    pub fn with_commit_log(mut self, commit_log: CommitLog) -> Self {
+        self.commit_log = commit_log;
+        self
+    }
+
+    /// Stores accounts and disperses batches encoded in `encoding`.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self.parent.encoding = encoding;
+        for child in &mut self.children {
+            child.encoding = encoding;
+        }
+        self
+    }
+
+    /// Takes back `batches` the node could not seal before it last stopped,
+    /// to be dispersed ahead of anything applied from now on. Their
+    /// transactions count as applied, so none is applied twice.
+    pub fn with_unsealed(mut self, batches: Vec<Batch>) -> Self {
+        let encoding = self.encoding;
+        let mut batches: VecDeque<Batch> = batches
+            .into_iter()
+            .filter(|batch| !batch.empty())
+            .map(|batch| batch.with_encoding(encoding))
+            .collect();
+        for batch in &batches {
+            for transaction_hash in batch.transactions.keys() {
+                self.applied.insert(transaction_hash.clone(), ());
+            }
+        }
+        if self.parent.empty() {
+            if let Some(parent) = batches.pop_front() {
+                self.parent = parent;
+            }
+        }
+        batches.append(&mut self.children);
+        self.children = batches;
+        self
+    }
+
+    /// The batches applied to but not yet dispersed, oldest first.
+    pub fn unsealed(&self) -> Vec<Batch> {
+        std::iter::once(&self.parent)
+            .chain(self.children.iter())
+            .filter(|batch| !batch.empty())
+            .cloned()
+            .collect()
+    }
+
+    fn unsealed_count(&self) -> usize {
+        usize::from(!self.parent.empty())
+            + self.children.iter().filter(|batch| !batch.empty()).count()
+    }
+
+    /// Writes the accounts of the batches not yet dispersed to the account
+    /// cache, oldest first, as they are newer than the persistence store's.
+    pub async fn cache_unsealed(batcher: Arc<Mutex<Batcher>>) {
+        let unsealed = batcher.lock().await.unsealed();
+        for batch in unsealed {
+            for account in batch.accounts.values() {
+                Batcher::cache_account(account, "Batcher::cache_unsealed".to_string()).await;
+            }
+        }
+    }
+
+    /// Seals and disperses every batch not yet dispersed, returning how many
+    /// were. Fails once one cannot be, leaving it and those after it to be
+    /// dispersed later.
+    pub async fn seal(
+        batcher: Arc<Mutex<Batcher>>,
+        storage_ref: StorageRef,
+    ) -> Result<usize, BatcherError> {
+        let mut sealed = 0;
+        loop {
+            let unsealed = batcher.lock().await.unsealed_count();
+            if unsealed == 0 {
+                return Ok(sealed);
+            }
+            Batcher::handle_next_batch_request(Arc::clone(&batcher), storage_ref.clone()).await?;
+            if batcher.lock().await.unsealed_count() >= unsealed {
+                return Err(BatcherError::Custom(
+                    "the batch could not be dispersed".to_string(),
+                ));
+            }
+            sealed += 1;
+        }
+    }
+
+    /// Puts `entries`, the transactions committed but not finalized before
+    /// the node last stopped, back into the batches to seal, oldest first,
+    /// caching the accounts they left. Those already applied, or already in
+    /// a batch finalized, are skipped. Returns how many were put back.
+    pub async fn replay_commits(
+        batcher: Arc<Mutex<Batcher>>,
+        entries: Vec<CommitEntry>,
+    ) -> Result<usize, BatcherError> {
+        let (finalized, commit_log): (HashSet<String>, CommitLog) = {
+            let guard = batcher.lock().await;
+            let finalized = guard
+                .batches
+                .list()
+                .into_iter()
+                .flat_map(|record| record.transaction_hashes)
+                .collect();
+            (finalized, guard.commit_log.clone())
+        };
+        let mut replayed = 0;
+        for entry in entries {
+            let transaction_hash = entry.transaction.hash_string();
+            if finalized.contains(&transaction_hash) {
+                commit_log
+                    .finalized(&[transaction_hash])
+                    .map_err(|e| BatcherError::Custom(e.to_string()))?;
+                continue;
+            }
+            if !batcher.lock().await.applied.insert(transaction_hash, ()) {
+                continue;
+            }
+            for account in entry.accounts {
+                Batcher::add_account_to_batch(
+                    &batcher,
+                    account,
+                    "Batcher::replay_commits".to_string(),
+                )
+                .await?;
+            }
+            Batcher::add_transaction_to_batch(
+                Arc::clone(&batcher),
+                entry.transaction,
+                entry.events,
+            )
+            .await;
+            replayed += 1;
+        }
+        if replayed > 0 {
+            tracing::warn!("put back {replayed} transactions committed but not finalized");
+        }
+        Ok(replayed)
+    }
+
+    /// Logs `transaction`, whose `accounts` were just committed, before it
+    /// is answered, then adds it to the batch its accounts went into.
+    async fn committed(
+        batcher: &Arc<Mutex<Batcher>>,
+        transaction: &Transaction,
+        accounts: Vec<Account>,
+        events: Vec<ProgramEvent>,
+    ) -> Result<(), BatcherError> {
+        let commit_log = batcher.lock().await.commit_log.clone();
+        commit_log
+            .committed(transaction, accounts, events.clone())
+            .map_err(|e| BatcherError::FailedTransaction {
+                msg: format!("failed to log the commit: {e}"),
+                txn: Box::new(transaction.clone()),
+            })?;
+        Batcher::add_transaction_to_batch(Arc::clone(batcher), transaction.clone(), events).await;
+        Batcher::seal_applied(batcher).await;
+        Ok(())
+    }
+
+    /// Seals the transaction just applied, if every transaction is sealed as
+    /// soon as it is. A batch that cannot be is left to the batch requestor.
+    async fn seal_applied(batcher: &Arc<Mutex<Batcher>>) {
+        let instant_seal = batcher.lock().await.instant_seal.clone();
+        if let Some(InstantSeal { storage_ref, .. }) = instant_seal {
+            if let Err(e) = Batcher::seal(Arc::clone(batcher), storage_ref).await {
+                tracing::error!("failed to seal the transaction just applied: {e}");
+            }
+        }
+    }
+
+    /// Signs an attestation for every call applied from now on.
+    #[cfg(feature = "attestations")]
+    pub fn with_attestor(mut self, attestor: Attestor) -> Self {
+        self.attestor = Some(attestor);
+        self
+    }
+
+    /// Runs `apply` unless `transaction_hash` has already been applied or is
+    /// being applied, so a re-delivered transaction cannot move funds twice.
+    /// The hash is released again if `apply` fails, allowing a retry.
+    pub async fn apply_once<F>(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction_hash: String,
+        apply: F,
+    ) -> Result<(), BatcherError>
+    where
+        F: Future<Output = Result<(), BatcherError>>,
+    {
+        if !batcher
+            .lock()
+            .await
+            .applied
+            .insert(transaction_hash.clone(), ())
+        {
+            tracing::warn!("transaction {transaction_hash} was already applied, skipping");
+            return Ok(());
+        }
+
+        let result = apply
+            .instrument(lifecycle::stage(LifecycleStage::Batcher, &transaction_hash))
+            .await;
+        if result.is_err() {
+            batcher.lock().await.applied.remove(&transaction_hash);
+        }
+        result
+    }
+
+    /// Applies `transaction` as its type says, a call with the `outputs` it
+    /// executed to. Every type is matched, so none is appended to a batch
+    /// and left unapplied without failing.
+    pub async fn apply_transaction(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+        outputs: Option<Outputs>,
+    ) -> Result<(), BatcherError> {
+        match transaction.transaction_type() {
+            TransactionType::Send(_) | TransactionType::BridgeIn(_) => {
+                Batcher::add_transaction_to_account(batcher, transaction).await
+            }
+            TransactionType::Call(_) => match outputs {
+                Some(outputs) => {
+                    Batcher::apply_instructions_to_accounts(batcher, transaction, outputs).await
+                }
+                None => Err(BatcherError::FailedTransaction {
+                    msg: "call transaction result did not contain outputs".to_string(),
+                    txn: Box::new(transaction),
+                }),
+            },
+            TransactionType::RegisterProgram(_) | TransactionType::Deploy { .. } => {
+                Batcher::apply_program_registration(batcher, transaction).await
+            }
+            TransactionType::Burn(_) | TransactionType::BurnAmount { .. } => {
+                Batcher::apply_verse_burn(batcher, transaction).await
+            }
+            TransactionType::BridgeOut(_) | TransactionType::BridgeOutTo { .. } => {
+                Batcher::apply_bridge_out(batcher, transaction).await
+            }
+            TransactionType::Delegate(_) => Batcher::apply_delegation(batcher, transaction).await,
+            TransactionType::SetAccountMetadata(_) => {
+                Batcher::apply_account_metadata(batcher, transaction).await
+            }
         }
     }
 
@@ -376,7 +1027,12 @@ impl Batcher {
                 who: ActorType::Batcher,
                 location,
             };
-            if let Err(err) = account_cache.cast(message) {
+            if let Err(err) = cast_between(
+                ActorType::Batcher,
+                ActorType::AccountCache,
+                &account_cache,
+                message,
+            ) {
                 tracing::error!("failed to cast write message to account cache: {err:?}");
             }
         }
@@ -385,24 +1041,27 @@ impl Batcher {
     pub(super) async fn add_transaction_to_batch(
         batcher: Arc<Mutex<Batcher>>,
         transaction: Transaction,
+        events: Vec<ProgramEvent>,
     ) {
         let mut guard = batcher.lock().await;
         let mut new_batch = false;
-        let mut res = guard.parent.insert_transaction(transaction.clone());
+        let mut res = guard
+            .parent
+            .insert_transaction_with_events(transaction.clone(), events.clone());
         let mut iter = guard.children.iter_mut();
         while let Err(ref mut e) = res {
             tracing::error!("{e}");
             if let Some(mut child) = iter.next() {
-                res = child.insert_transaction(transaction.clone());
+                res = child.insert_transaction_with_events(transaction.clone(), events.clone());
             } else {
                 new_batch = true;
             }
         }
 
         if new_batch {
-            let mut batch = Batch::new();
+            let mut batch = Batch::new().with_encoding(guard.encoding);
             batch
-                .insert_transaction(transaction)
+                .insert_transaction_with_events(transaction, events)
                 .typecast()
                 .log_err(|e| e);
             guard.children.push_back(batch);
@@ -429,7 +1088,7 @@ impl Batcher {
         }
 
         if new_batch {
-            let mut batch = Batch::new();
+            let mut batch = Batch::new().with_encoding(guard.encoding);
             batch.insert_account(account).typecast().log_err(|e| e);
             guard.children.push_back(batch);
         }
@@ -437,10 +1096,204 @@ impl Batcher {
         Ok(())
     }
 
-    pub async fn add_transaction_to_account(
-        batcher: Arc<Mutex<Batcher>>,
-        transaction: Transaction,
-    ) -> Result<(), BatcherError> {
+    /// Publishes every account staged for a single transaction. The whole set
+    /// is validated first and then handed to the account cache in one write,
+    /// so readers never observe some of a transaction's accounts updated and
+    /// others not. Nothing is written if validation fails. Returns the
+    /// balances the write changed.
+    pub(super) async fn commit_batch_buffer(
+        batcher: &Arc<Mutex<Batcher>>,
+        accounts: Vec<Account>,
+        location: String,
+    ) -> Result<Vec<TokenDelta>, BatcherError> {
+        Batcher::validate_batch_buffer(&accounts)?;
+
+        let quota = batcher.lock().await.storage_quota.clone();
+        let mut deltas = Vec::new();
+        let mut sizes = Vec::new();
+        for account in &accounts {
+            let address = match account.account_type() {
+                AccountType::Program(program_address) => program_address,
+                AccountType::User => account.owner_address(),
+            };
+            let before = get_account(address, ActorType::Batcher).await;
+            quota
+                .check_account(before.as_ref(), account)
+                .map_err(|e| BatcherError::Custom(e.to_string()))?;
+            deltas.extend(token_deltas(before.as_ref(), account));
+            sizes.push((address, quota.account_size(account)));
+        }
+
+        let account_cache =
+            get_actor_ref::<AccountCacheMessage, AccountCacheError>(ActorType::AccountCache)
+                .ok_or(BatcherError::Custom(
+                    "failed to acquire AccountCacheActor".to_string(),
+                ))?;
+        cast_between(
+            ActorType::Batcher,
+            ActorType::AccountCache,
+            &account_cache,
+            AccountCacheMessage::WriteBatch {
+                accounts: accounts.clone(),
+                who: ActorType::Batcher,
+                location,
+            },
+        )?;
+        batcher_metrics().committed.inc();
+
+        let mut guard = batcher.lock().await;
+        guard.record_account_sizes(sizes);
+        for account in accounts {
+            let mut res = guard.parent.insert_account(account.clone());
+            let mut iter = guard.children.iter_mut();
+            let mut new_batch = false;
+            while res.is_err() {
+                if let Some(child) = iter.next() {
+                    res = child.insert_account(account.clone());
+                } else {
+                    new_batch = true;
+                    break;
+                }
+            }
+
+            if new_batch {
+                let mut batch = Batch::new().with_encoding(guard.encoding);
+                batch.insert_account(account).typecast().log_err(|e| e);
+                guard.children.push_back(batch);
+            }
+        }
+
+        Ok(deltas)
+    }
+
+    /// Checks the staged accounts as a set before any of them are published.
+    /// Records the `sizes` of the accounts just committed, warning of each
+    /// that has grown past three quarters of its quota.
+    fn record_account_sizes(&mut self, sizes: Vec<(Address, usize)>) {
+        let warn_at = self.storage_quota.max_account_bytes / 4 * 3;
+        for (address, size) in sizes {
+            let before = self.account_sizes.insert(address, size).unwrap_or(0);
+            if size > warn_at && before <= warn_at && !self.storage_quota.is_exempt(&address) {
+                tracing::warn!(
+                    "account {} holds {size} bytes of metadata and data, near its limit of {}",
+                    address.to_full_string(),
+                    self.storage_quota.max_account_bytes
+                );
+            }
+        }
+        let largest = self.account_sizes.values().max().copied().unwrap_or(0);
+        batcher_metrics()
+            .largest_account_bytes
+            .set(i64::try_from(largest).unwrap_or(i64::MAX));
+    }
+
+    pub(super) fn validate_batch_buffer(accounts: &[Account]) -> Result<(), BatcherError> {
+        let mut seen = HashSet::new();
+        for account in accounts {
+            let address = match account.account_type() {
+                AccountType::Program(program_address) => program_address,
+                AccountType::User => account.owner_address(),
+            };
+            if !seen.insert(address) {
+                return Err(BatcherError::Custom(format!(
+                    "account {} staged more than once in a single transaction",
+                    address.to_full_string()
+                )));
+            }
+
+            bincode::serialize(&AccountValue {
+                account: account.clone(),
+            })
+            .map_err(|e| {
+                BatcherError::Custom(format!(
+                    "staged account {} cannot be persisted: {e:?}",
+                    address.to_full_string()
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the program account whose metadata and data new tokens inherit.
+    /// The VERSE program account only records supply, so VERSE tokens are
+    /// treated like any other native token here.
+    async fn get_token_program_account(program_id: Address) -> Option<Account> {
+        if program_id == VERSE_ADDR {
+            return None;
+        }
+        get_account(program_id, ActorType::Batcher).await
+    }
+
+    /// Fetches the VERSE program account from the buffer or the cache, creating
+    /// it on first mint.
+    async fn get_verse_program_account(
+        batch_buffer: &HashMap<String, Account>,
+    ) -> Result<Account, BatcherError> {
+        if let Some(account) = batch_buffer.get(&VERSE_ADDR.to_full_string()) {
+            return Ok(account.clone());
+        }
+        match get_account(VERSE_ADDR, ActorType::Batcher).await {
+            Some(account) => Ok(account),
+            None => new_verse_program_account().map_err(|e| BatcherError::Custom(e.to_string())),
+        }
+    }
+
+    /// Stages the fee `transaction` is charged by `fees` into `batch_buffer`,
+    /// debiting it from the sender's VERSE and burning it from the supply.
+    /// Answers the sender's VERSE token after the debit, or `None` if there
+    /// is no fee to charge.
+    pub async fn stage_fee(
+        fees: &FeeSchedule,
+        transaction: &Transaction,
+        batch_buffer: &mut HashMap<String, Account>,
+    ) -> Result<Option<Token>, BatcherError> {
+        let fee = fees.charge(transaction);
+        if fee.is_zero() {
+            return Ok(None);
+        }
+
+        let from = transaction.from().to_full_string();
+        let mut sender = match batch_buffer.get(&from) {
+            Some(account) => account.clone(),
+            None => get_account(transaction.from(), ActorType::Batcher)
+                .await
+                .ok_or(BatcherError::FailedTransaction {
+                    msg: "sender account does not exist".to_string(),
+                    txn: Box::new(transaction.clone()),
+                })?,
+        };
+        let token = sender
+            .apply_burn_instruction(&VERSE_ADDR, &Some(fee), &[])
+            .map_err(|e| BatcherError::FailedTransaction {
+                msg: format!("unable to charge a fee of {fee}: {e}"),
+                txn: Box::new(transaction.clone()),
+            })?;
+        let mut verse_account = Batcher::get_verse_program_account(batch_buffer).await?;
+        burn_verse_supply(&mut verse_account, fee).map_err(|e| {
+            BatcherError::FailedTransaction {
+                msg: e.to_string(),
+                txn: Box::new(transaction.clone()),
+            }
+        })?;
+        batch_buffer.insert(from, sender);
+        batch_buffer.insert(VERSE_ADDR.to_full_string(), verse_account);
+        Ok(Some(token))
+    }
+
+    /// Stages the accounts a send, or a bridge in, changes without writing
+    /// them, returning them with the token the sender holds after it.
+    pub async fn stage_send(
+        transaction: &Transaction,
+    ) -> Result<(HashMap<String, Account>, Token), BatcherError> {
+        if transaction.to() == VERSE_ADDR {
+            return Err(BatcherError::FailedTransaction {
+                msg: "VERSE cannot be sent to its program account, use a burn transaction"
+                    .to_string(),
+                txn: Box::new(transaction.clone()),
+            });
+        }
+
         let mut batch_buffer = HashMap::new();
         tracing::warn!(
             "checking account cache for account associated with address {:?} to add transaction: {:?}",
@@ -450,7 +1303,10 @@ impl Batcher {
         let mut from_account = get_account(transaction.from(), ActorType::Batcher).await;
         let (from_account, token) = if let Some(mut account) = from_account {
             tracing::warn!("found account, token pair");
-            account.increment_nonce();
+            // A send advances the nonce as it is applied, a bridge in here.
+            if transaction.transaction_type().is_bridge_in() {
+                account.increment_nonce();
+            }
             let token = account
                 .apply_send_transaction(transaction.clone(), None)
                 .map_err(|e| BatcherError::FailedTransaction {
@@ -480,14 +1336,14 @@ impl Batcher {
                 .program_account_data(ArbitraryData::new())
                 .program_account_metadata(Metadata::new())
                 .program_account_linked_programs(BTreeSet::new())
-                .build()
+                .try_build()
                 .map_err(|e| BatcherError::FailedTransaction {
                     msg: e.to_string(),
                     txn: Box::new(transaction.clone()),
                 })?;
 
             if let Some(program_account) =
-                get_account(transaction.program_id(), ActorType::Batcher).await
+                Batcher::get_token_program_account(transaction.program_id()).await
             {
                 let token = account
                     .apply_send_transaction(transaction.clone(), Some(&program_account))
@@ -526,17 +1382,21 @@ impl Batcher {
             let to_account = if let Some(mut account) = to_account {
                 tracing::warn!("found `to` account: {}", transaction.to().to_full_string());
                 if let Some(program_account) =
-                    get_account(transaction.program_id(), ActorType::Batcher).await
+                    Batcher::get_token_program_account(transaction.program_id()).await
                 {
-                    let _ =
-                        account.apply_send_transaction(transaction.clone(), Some(&program_account));
+                    account
+                        .apply_send_transaction(transaction.clone(), Some(&program_account))
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     tracing::warn!(
                         "applied send transaction, account {} now has new token",
                         account.owner_address().to_full_string()
                     );
                     tracing::warn!(
                         "token_entry: {:?}",
-                        &account.programs().get(&transaction.program_id())
+                        &account.token(&transaction.program_id())
                     );
                     account
                 } else if transaction.program_id() == ETH_ADDR {
@@ -544,14 +1404,24 @@ impl Batcher {
                         "applying ETH to account {}",
                         transaction.to().to_full_string()
                     );
-                    let _ = account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account
                 } else if transaction.program_id() == VERSE_ADDR {
                     tracing::warn!(
                         "applying VERSE to account {}",
                         transaction.to().to_full_string()
                     );
-                    let _ = account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account
                 } else {
                     return Err(BatcherError::FailedTransaction {
@@ -576,7 +1446,7 @@ impl Batcher {
                     .program_account_data(ArbitraryData::new())
                     .program_account_metadata(Metadata::new())
                     .program_account_linked_programs(BTreeSet::new())
-                    .build()
+                    .try_build()
                     .map_err(|e| BatcherError::FailedTransaction {
                         msg: e.to_string(),
                         txn: Box::new(transaction.clone()),
@@ -584,24 +1454,38 @@ impl Batcher {
 
                 tracing::warn!("applying transaction to `to` account");
                 if let Some(program_account) =
-                    get_account(transaction.program_id(), ActorType::Batcher).await
+                    Batcher::get_token_program_account(transaction.program_id()).await
                 {
-                    let _ =
-                        account.apply_send_transaction(transaction.clone(), Some(&program_account));
+                    account
+                        .apply_send_transaction(transaction.clone(), Some(&program_account))
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     tracing::warn!(
                         "applied send transaction, account {} now has new token",
                         account.owner_address().to_full_string()
                     );
                     tracing::warn!(
                         "token_entry: {:?}",
-                        &account.programs().get(&transaction.program_id())
+                        &account.token(&transaction.program_id())
                     );
                     account
                 } else if transaction.program_id() == ETH_ADDR {
-                    account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account
                 } else if transaction.program_id() == VERSE_ADDR {
-                    let _ = account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account
                 } else {
                     return Err(BatcherError::FailedTransaction {
@@ -620,24 +1504,38 @@ impl Batcher {
                 batch_buffer.get_mut(&transaction.to().to_full_string())
             {
                 if let Some(program_account) =
-                    get_account(transaction.program_id(), ActorType::Batcher).await
+                    Batcher::get_token_program_account(transaction.program_id()).await
                 {
-                    let _ =
-                        account.apply_send_transaction(transaction.clone(), Some(&program_account));
+                    account
+                        .apply_send_transaction(transaction.clone(), Some(&program_account))
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     tracing::warn!(
                         "applied send transaction, account {} now has new token",
                         account.owner_address().to_full_string()
                     );
                     tracing::warn!(
                         "token_entry: {:?}",
-                        &account.programs().get(&transaction.program_id())
+                        &account.token(&transaction.program_id())
                     );
                     account.clone()
                 } else if transaction.program_id() == ETH_ADDR {
-                    account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account.clone()
                 } else if transaction.program_id() == VERSE_ADDR {
-                    let _ = account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account.clone()
                 } else {
                     return Err(BatcherError::FailedTransaction {
@@ -652,24 +1550,38 @@ impl Batcher {
                 get_account(transaction.to(), ActorType::Batcher).await
             {
                 if let Some(program_account) =
-                    get_account(transaction.program_id(), ActorType::Batcher).await
+                    Batcher::get_token_program_account(transaction.program_id()).await
                 {
-                    let _ =
-                        account.apply_send_transaction(transaction.clone(), Some(&program_account));
+                    account
+                        .apply_send_transaction(transaction.clone(), Some(&program_account))
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     tracing::warn!(
                         "applied send transaction, account {} now has new token",
                         account.owner_address().to_full_string()
                     );
                     tracing::warn!(
                         "token_entry: {:?}",
-                        &account.programs().get(&transaction.program_id())
+                        &account.token(&transaction.program_id())
                     );
                     account.clone()
                 } else if transaction.program_id() == ETH_ADDR {
-                    account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account.clone()
                 } else if transaction.program_id() == VERSE_ADDR {
-                    let _ = account.apply_send_transaction(transaction.clone(), None);
+                    account
+                        .apply_send_transaction(transaction.clone(), None)
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                     account.clone()
                 } else {
                     return Err(BatcherError::FailedTransaction {
@@ -687,68 +1599,369 @@ impl Batcher {
                 });
             };
 
-            batch_buffer.insert(transaction.to().to_full_string(), to_account.clone());
+            batch_buffer.insert(transaction.to().to_full_string(), to_account.clone());
+        }
+
+        // Bridging in is the only way new VERSE enters circulation.
+        if transaction.transaction_type().is_bridge_in() && transaction.program_id() == VERSE_ADDR {
+            let mut verse_account = Batcher::get_verse_program_account(&batch_buffer).await?;
+            mint_verse_supply(&mut verse_account, transaction.value()).map_err(|e| {
+                BatcherError::FailedTransaction {
+                    msg: e.to_string(),
+                    txn: Box::new(transaction.clone()),
+                }
+            })?;
+            batch_buffer.insert(VERSE_ADDR.to_full_string(), verse_account);
+        }
+
+        Ok((batch_buffer, token))
+    }
+
+    pub async fn add_transaction_to_account(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        let (mut batch_buffer, mut token) = Batcher::stage_send(&transaction).await?;
+        let fees = batcher.lock().await.fees;
+        if let Some(verse) = Batcher::stage_fee(&fees, &transaction, &mut batch_buffer).await? {
+            if transaction.program_id() == VERSE_ADDR {
+                token = verse;
+            }
+        }
+
+        // The debit and credit of a send are published together or not at all.
+        tracing::info!("adding accounts to batch");
+        let accounts: Vec<Account> = batch_buffer.into_values().collect();
+        let token_deltas = Batcher::commit_batch_buffer(
+            &batcher,
+            accounts.clone(),
+            "add_transaction_to_account".to_string(),
+        )
+        .await
+        .map_err(|e| BatcherError::FailedTransaction {
+            msg: e.to_string(),
+            txn: Box::new(transaction.clone()),
+        })?;
+        batcher
+            .lock()
+            .await
+            .inclusions
+            .applied(&transaction, token_deltas);
+
+        tracing::info!("adding transaction to batch");
+        Batcher::committed(&batcher, &transaction, accounts, Vec::new()).await?;
+
+        if let Some(scheduler) =
+            get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+        {
+            let message = SchedulerMessage::TransactionApplied {
+                transaction_hash: transaction.clone().hash_string(),
+                token: token.clone(),
+            };
+
+            scheduler
+                .cast(message)
+                .map_err(|e| BatcherError::FailedTransaction {
+                    msg: e.to_string(),
+                    txn: Box::new(transaction.clone()),
+                })?;
+        } else {
+            return Err(BatcherError::FailedTransaction {
+                msg: "failed to acquire SchedulerActor".to_string(),
+                txn: Box::new(transaction.clone()),
+            });
+        }
+
+        if let Some(pending_tx) = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+            ActorType::PendingTransactions,
+        ) {
+            let message = PendingTransactionMessage::Valid {
+                transaction: transaction.clone(),
+                cert: None,
+            };
+
+            pending_tx
+                .cast(message)
+                .map_err(|e| BatcherError::FailedTransaction {
+                    msg: e.to_string(),
+                    txn: Box::new(transaction),
+                })?;
+        } else {
+            return Err(BatcherError::FailedTransaction {
+                msg: "failed to acquire PendingTransactionActor".to_string(),
+                txn: Box::new(transaction.clone()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Burns VERSE from the sender, reducing its balance and the total supply
+    /// together.
+    pub async fn apply_verse_burn(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        if transaction.program_id() != VERSE_ADDR {
+            return Err(BatcherError::FailedTransaction {
+                msg: "burn transactions are only supported for VERSE".to_string(),
+                txn: Box::new(transaction.clone()),
+            });
+        }
+        Batcher::apply_debit(batcher, transaction, "apply_verse_burn").await
+    }
+
+    /// Takes the value of a bridge out off the sender's balance of the
+    /// program's token, and off the supply for VERSE. The batch's header
+    /// carries it as a withdrawal, which the settlement contract releases to
+    /// the recipient once the header is settled.
+    pub async fn apply_bridge_out(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        if !transaction.transaction_type().is_bridge_out_to() {
+            return Err(BatcherError::FailedTransaction {
+                msg: "bridge outs must name their recipient".to_string(),
+                txn: Box::new(transaction.clone()),
+            });
+        }
+        Batcher::apply_debit(batcher, transaction, "apply_bridge_out").await
+    }
+
+    /// Takes the value of `transaction` off its sender's balance of the
+    /// program's token, and off the supply for VERSE, with its fee.
+    async fn apply_debit(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+        location: &str,
+    ) -> Result<(), BatcherError> {
+        let program_id = transaction.program_id();
+        let mut batch_buffer = HashMap::new();
+        let mut account = get_account(transaction.from(), ActorType::Batcher)
+            .await
+            .ok_or(BatcherError::FailedTransaction {
+                msg: "sender account does not exist".to_string(),
+                txn: Box::new(transaction.clone()),
+            })?;
+        account.increment_nonce();
+        let token = account
+            .apply_burn_instruction(&program_id, &Some(transaction.value()), &[])
+            .map_err(|e| BatcherError::FailedTransaction {
+                msg: e.to_string(),
+                txn: Box::new(transaction.clone()),
+            })?;
+        batch_buffer.insert(transaction.from().to_full_string(), account);
+
+        if program_id == VERSE_ADDR {
+            let mut verse_account = Batcher::get_verse_program_account(&batch_buffer).await?;
+            burn_verse_supply(&mut verse_account, transaction.value()).map_err(|e| {
+                BatcherError::FailedTransaction {
+                    msg: e.to_string(),
+                    txn: Box::new(transaction.clone()),
+                }
+            })?;
+            batch_buffer.insert(VERSE_ADDR.to_full_string(), verse_account);
+        }
+        let fees = batcher.lock().await.fees;
+        let token = Batcher::stage_fee(&fees, &transaction, &mut batch_buffer)
+            .await?
+            .unwrap_or(token);
+
+        let accounts: Vec<Account> = batch_buffer.into_values().collect();
+        let token_deltas =
+            Batcher::commit_batch_buffer(&batcher, accounts.clone(), location.to_string())
+                .await
+                .map_err(|e| BatcherError::FailedTransaction {
+                    msg: e.to_string(),
+                    txn: Box::new(transaction.clone()),
+                })?;
+        batcher
+            .lock()
+            .await
+            .inclusions
+            .applied(&transaction, token_deltas);
+
+        Batcher::committed(&batcher, &transaction, accounts, Vec::new()).await?;
+
+        let scheduler = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+            .ok_or(BatcherError::FailedTransaction {
+                msg: "failed to acquire SchedulerActor".to_string(),
+                txn: Box::new(transaction.clone()),
+            })?;
+        scheduler
+            .cast(SchedulerMessage::TransactionApplied {
+                transaction_hash: transaction.hash_string(),
+                token,
+            })
+            .map_err(|e| BatcherError::FailedTransaction {
+                msg: e.to_string(),
+                txn: Box::new(transaction.clone()),
+            })?;
+
+        let pending_tx = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+            ActorType::PendingTransactions,
+        )
+        .ok_or(BatcherError::FailedTransaction {
+            msg: "failed to acquire PendingTransactionActor".to_string(),
+            txn: Box::new(transaction.clone()),
+        })?;
+        pending_tx
+            .cast(PendingTransactionMessage::Valid {
+                transaction: transaction.clone(),
+                cert: None,
+            })
+            .map_err(|e| BatcherError::FailedTransaction {
+                msg: e.to_string(),
+                txn: Box::new(transaction),
+            })?;
+
+        Ok(())
+    }
+
+    /// Grants or revokes the delegation a delegate transaction carries in
+    /// its inputs, on the sender's account.
+    pub async fn apply_delegation(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        let op = DelegationOp::of(&transaction);
+        Batcher::apply_to_sender(batcher, transaction, "apply_delegation", |account| {
+            let op = op.map_err(|e| e.to_string())?;
+            account.apply_delegation_op(op).map_err(|e| e.to_string())
+        })
+        .await
+    }
+
+    /// Sets the sender's account metadata to what an account metadata
+    /// transaction carries in its inputs.
+    pub async fn apply_account_metadata(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        let metadata = AccountMetadata::of(&transaction);
+        Batcher::apply_to_sender(batcher, transaction, "apply_account_metadata", |account| {
+            account.set_account_metadata(metadata.map_err(|e| e.to_string())?);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Applies `update` to the sender's account alone, as a transaction
+    /// that moves no value but the fee does, committed from `location`.
+    async fn apply_to_sender(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+        location: &str,
+        update: impl FnOnce(&mut Account) -> Result<(), String>,
+    ) -> Result<(), BatcherError> {
+        let failed = |msg: String| BatcherError::FailedTransaction {
+            msg,
+            txn: Box::new(transaction.clone()),
+        };
+        let mut batch_buffer = HashMap::new();
+        let mut account = get_account(transaction.from(), ActorType::Batcher)
+            .await
+            .ok_or_else(|| failed("sender account does not exist".to_string()))?;
+        account.increment_nonce();
+        update(&mut account).map_err(failed)?;
+        let token = account
+            .token(&transaction.program_id())
+            .cloned()
+            .unwrap_or_else(|| transaction.clone().into());
+        batch_buffer.insert(transaction.from().to_full_string(), account);
+        let fees = batcher.lock().await.fees;
+        let token = Batcher::stage_fee(&fees, &transaction, &mut batch_buffer)
+            .await?
+            .unwrap_or(token);
+
+        let accounts: Vec<Account> = batch_buffer.into_values().collect();
+        let token_deltas =
+            Batcher::commit_batch_buffer(&batcher, accounts.clone(), location.to_string())
+                .await
+                .map_err(|e| failed(e.to_string()))?;
+        batcher
+            .lock()
+            .await
+            .inclusions
+            .applied(&transaction, token_deltas);
+
+        Batcher::committed(&batcher, &transaction, accounts, Vec::new()).await?;
+
+        let scheduler = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+            .ok_or_else(|| failed("failed to acquire SchedulerActor".to_string()))?;
+        scheduler
+            .cast(SchedulerMessage::TransactionApplied {
+                transaction_hash: transaction.hash_string(),
+                token,
+            })
+            .map_err(|e| failed(e.to_string()))?;
+
+        let pending_tx = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+            ActorType::PendingTransactions,
+        )
+        .ok_or_else(|| failed("failed to acquire PendingTransactionActor".to_string()))?;
+        pending_tx
+            .cast(PendingTransactionMessage::Valid {
+                transaction: transaction.clone(),
+                cert: None,
+            })
+            .map_err(|e| failed(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Debits a bridge in that was credited from an event the settlement
+    /// layer has since abandoned, and forgets that it was applied so the
+    /// event is credited again if the canonical branch includes it too.
+    pub async fn revert_bridge_in(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        let transaction_hash = transaction.hash_string();
+        if !batcher.lock().await.applied.contains(&transaction_hash) {
+            tracing::warn!("bridge in {transaction_hash} was retracted before it was applied");
+            return Ok(());
         }
 
-        for (_, account) in batch_buffer {
-            tracing::info!("adding account to batch");
-            Batcher::add_account_to_batch(
-                &batcher,
-                account,
-                "add_transaction_to_account".to_string(),
-            )
+        let mut batch_buffer = HashMap::new();
+        let mut account = get_account(transaction.from(), ActorType::Batcher)
             .await
+            .ok_or(BatcherError::FailedTransaction {
+                msg: "bridged account does not exist".to_string(),
+                txn: Box::new(transaction.clone()),
+            })?;
+        account
+            .apply_burn_instruction(&transaction.program_id(), &Some(transaction.value()), &[])
             .map_err(|e| BatcherError::FailedTransaction {
-                msg: e.to_string(),
+                msg: format!("unable to revert retracted bridge in: {e}"),
                 txn: Box::new(transaction.clone()),
             })?;
-        }
-
-        tracing::info!("adding transaction to batch");
-        Batcher::add_transaction_to_batch(batcher, transaction.clone()).await;
-
-        if let Some(scheduler) =
-            get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
-        {
-            let message = SchedulerMessage::TransactionApplied {
-                transaction_hash: transaction.clone().hash_string(),
-                token: token.clone(),
-            };
+        batch_buffer.insert(transaction.from().to_full_string(), account);
 
-            scheduler
-                .cast(message)
-                .map_err(|e| BatcherError::FailedTransaction {
+        if transaction.program_id() == VERSE_ADDR {
+            let mut verse_account = Batcher::get_verse_program_account(&batch_buffer).await?;
+            burn_verse_supply(&mut verse_account, transaction.value()).map_err(|e| {
+                BatcherError::FailedTransaction {
                     msg: e.to_string(),
                     txn: Box::new(transaction.clone()),
-                })?;
-        } else {
-            return Err(BatcherError::FailedTransaction {
-                msg: "failed to acquire SchedulerActor".to_string(),
-                txn: Box::new(transaction.clone()),
-            });
+                }
+            })?;
+            batch_buffer.insert(VERSE_ADDR.to_full_string(), verse_account);
         }
 
-        if let Some(pending_tx) = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
-            ActorType::PendingTransactions,
-        ) {
-            let message = PendingTransactionMessage::Valid {
-                transaction: transaction.clone(),
-                cert: None,
-            };
-
-            pending_tx
-                .cast(message)
-                .map_err(|e| BatcherError::FailedTransaction {
-                    msg: e.to_string(),
-                    txn: Box::new(transaction),
-                })?;
-        } else {
-            return Err(BatcherError::FailedTransaction {
-                msg: "failed to acquire PendingTransactionActor".to_string(),
-                txn: Box::new(transaction.clone()),
-            });
-        }
+        Batcher::commit_batch_buffer(
+            &batcher,
+            batch_buffer.into_values().collect(),
+            "revert_bridge_in".to_string(),
+        )
+        .await
+        .map_err(|e| BatcherError::FailedTransaction {
+            msg: e.to_string(),
+            txn: Box::new(transaction.clone()),
+        })?;
 
+        batcher.lock().await.applied.remove(&transaction_hash);
+        tracing::warn!("reverted retracted bridge in {transaction_hash}");
         Ok(())
     }
 
@@ -848,7 +2061,8 @@ impl Batcher {
         if let Some(mut account) =
             Batcher::get_transfer_to_account(transaction, &to, batch_buffer).await
         {
-            if let Some(program_account) = get_account(*transfer.token(), ActorType::Batcher).await
+            if let Some(program_account) =
+                Batcher::get_token_program_account(*transfer.token()).await
             {
                 account
                     .apply_transfer_to_instruction(
@@ -889,11 +2103,11 @@ impl Batcher {
                         .program_account_linked_programs(BTreeSet::new())
                         .program_account_metadata(Metadata::new())
                         .program_account_data(ArbitraryData::new())
-                        .build().map_err(|e| {
+                        .try_build().map_err(|e| {
                             BatcherError::Custom(e.to_string())
                         })?;
 
-                    if let Some(program_account) = get_account(*transfer.token(), ActorType::Batcher).await {
+                    if let Some(program_account) = Batcher::get_token_program_account(*transfer.token()).await {
                         account.apply_transfer_to_instruction(
                             transfer.token(), transfer.amount(), transfer.ids(), Some(&program_account)
                         ).map_err(|e| {
@@ -925,7 +2139,6 @@ impl Batcher {
     }
 
     async fn apply_transfer_instruction(
-        batcher: &Arc<Mutex<Batcher>>,
         transaction: &Transaction,
         transfer: &TransferInstruction,
         batch_buffer: &mut HashMap<Address, Account>,
@@ -950,6 +2163,11 @@ impl Batcher {
         burn: &BurnInstruction,
         batch_buffer: &mut HashMap<Address, Account>,
     ) -> Result<Account, BatcherError> {
+        if burn.token() == &VERSE_ADDR {
+            return Err(BatcherError::Custom(
+                "VERSE can only be burned with a burn transaction".to_string(),
+            ));
+        }
         let burn_address = burn.from();
         let mut account =
             Batcher::get_transfer_from_account(transaction, burn_address, batch_buffer).await?;
@@ -963,6 +2181,7 @@ impl Batcher {
         transaction: &Transaction,
         distribution: &TokenDistribution,
         batch_buffer: &mut HashMap<Address, Account>,
+        quota: &StorageQuota,
     ) -> Result<Account, BatcherError> {
         let program_id = match distribution.program_id() {
             AddressOrNamespace::This => transaction.to(),
@@ -973,6 +2192,11 @@ impl Batcher {
                 ))
             }
         };
+        if program_id == VERSE_ADDR {
+            return Err(BatcherError::Custom(
+                "VERSE can only be minted by bridging in".to_string(),
+            ));
+        }
         match distribution.to() {
             AddressOrNamespace::This => {
                 tracing::warn!("Distribution going to {:?}", transaction.to());
@@ -991,7 +2215,8 @@ impl Batcher {
                             distribution.amount(),
                             distribution.token_ids(),
                             distribution.update_fields(),
-                            &program_account
+                            &program_account,
+                            quota
                         ).map_err(|e| {
                             BatcherError::Custom(
                                 e.to_string()
@@ -1011,7 +2236,7 @@ impl Batcher {
                         .program_account_linked_programs(BTreeSet::new())
                         .program_account_metadata(Metadata::new())
                         .program_account_data(ArbitraryData::new())
-                        .build().map_err(|e| BatcherError::Custom(e.to_string()))?;
+                        .try_build().map_err(|e| BatcherError::Custom(e.to_string()))?;
 
                     if let Some(program_account) = get_account(program_id,ActorType::Batcher).await {
                         acct.apply_token_distribution(
@@ -1019,7 +2244,8 @@ impl Batcher {
                             distribution.amount(),
                             distribution.token_ids(),
                             distribution.update_fields(),
-                            &program_account
+                            &program_account,
+                            quota
                         ).map_err(|e| {
                             BatcherError::Custom(
                                 e.to_string()
@@ -1048,7 +2274,8 @@ impl Batcher {
                             distribution.amount(),
                             distribution.token_ids(),
                             distribution.update_fields(),
-                            &program_account
+                            &program_account,
+                            quota
                         ).map_err(|e| {
                             BatcherError::Custom(
                                 e.to_string()
@@ -1069,7 +2296,7 @@ impl Batcher {
                         .program_account_linked_programs(BTreeSet::new())
                         .program_account_metadata(Metadata::new())
                         .program_account_data(ArbitraryData::new())
-                        .build().map_err(|e| BatcherError::Custom(e.to_string()))?;
+                        .try_build().map_err(|e| BatcherError::Custom(e.to_string()))?;
 
                     if let Some(program_account) = get_account(program_id,ActorType::Batcher).await {
                         account.apply_token_distribution(
@@ -1077,7 +2304,8 @@ impl Batcher {
                             distribution.amount(),
                             distribution.token_ids(),
                             distribution.update_fields(),
-                            &program_account
+                            &program_account,
+                            quota
                         ).map_err(|e| {
                             BatcherError::Custom(
                                 e.to_string()
@@ -1104,6 +2332,7 @@ impl Batcher {
         transaction: &Transaction,
         token_update: &TokenUpdate,
         batch_buffer: &mut HashMap<Address, Account>,
+        quota: &StorageQuota,
     ) -> Result<Account, BatcherError> {
         let program_id = match token_update.token() {
             AddressOrNamespace::This => transaction.to(),
@@ -1125,6 +2354,7 @@ impl Batcher {
                                 &program_id,
                                 token_update.updates(),
                                 &program_account,
+                                quota,
                             )
                             .map_err(|e| BatcherError::Custom(e.to_string()))?;
                         return Ok(account.clone());
@@ -1148,6 +2378,7 @@ impl Batcher {
                                 &program_id,
                                 token_update.updates(),
                                 &program_account,
+                                quota,
                             )
                             .map_err(|e| BatcherError::Custom(e.to_string()))?;
                         Ok(account)
@@ -1173,6 +2404,7 @@ impl Batcher {
                                 &program_id,
                                 token_update.updates(),
                                 &program_account,
+                                quota,
                             )
                             .map_err(|e| BatcherError::Custom(e.to_string()))?;
                         return Ok(account.clone());
@@ -1192,6 +2424,7 @@ impl Batcher {
                                 &program_id,
                                 token_update.updates(),
                                 &program_account,
+                                quota,
                             )
                             .map_err(|e| BatcherError::Custom(e.to_string()))?;
                         Ok(account)
@@ -1211,7 +2444,7 @@ impl Batcher {
                         .program_account_linked_programs(BTreeSet::new())
                         .program_account_metadata(Metadata::new())
                         .program_account_data(ArbitraryData::new())
-                        .build()
+                        .try_build()
                         .map_err(|e| BatcherError::Custom(e.to_string()))?;
 
                     if let Some(program_account) = get_account(program_id, ActorType::Batcher).await
@@ -1221,6 +2454,7 @@ impl Batcher {
                                 &program_id,
                                 token_update.updates(),
                                 &program_account,
+                                quota,
                             )
                             .map_err(|e| BatcherError::Custom(e.to_string()))?;
                         Ok(account)
@@ -1294,7 +2528,7 @@ impl Batcher {
                         .program_account_linked_programs(BTreeSet::new())
                         .program_account_metadata(Metadata::new())
                         .program_account_data(ArbitraryData::new())
-                        .build()
+                        .try_build()
                         .map_err(|e| BatcherError::Custom(e.to_string()))?;
 
                     account
@@ -1313,11 +2547,12 @@ impl Batcher {
         transaction: &Transaction,
         update: &TokenOrProgramUpdate,
         batch_buffer: &mut HashMap<Address, Account>,
+        quota: &StorageQuota,
     ) -> Result<Account, BatcherError> {
         match update {
             TokenOrProgramUpdate::TokenUpdate(token_update) => {
                 tracing::warn!("received token update: {:?}", token_update);
-                Batcher::apply_token_update(transaction, token_update, batch_buffer).await
+                Batcher::apply_token_update(transaction, token_update, batch_buffer, quota).await
             }
             TokenOrProgramUpdate::ProgramUpdate(program_update) => {
                 tracing::warn!("received program update: {:?}", &program_update);
@@ -1329,6 +2564,18 @@ impl Batcher {
     pub async fn apply_program_registration(
         batcher: Arc<Mutex<Batcher>>,
         transaction: Transaction,
+    ) -> Result<(), BatcherError> {
+        Batcher::apply_program_registration_in_runtime(batcher, transaction, None).await
+    }
+
+    /// Registers the program, recording the runtime it executes in on its
+    /// program account when the executor reported one, or a deploy declared
+    /// it. A deploy whose package runs in another runtime than it declared
+    /// fails.
+    pub async fn apply_program_registration_in_runtime(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+        runtime: Option<String>,
     ) -> Result<(), BatcherError> {
         if let Some(scheduler_actor) =
             get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
@@ -1352,28 +2599,49 @@ impl Batcher {
                 Some(account) => account,
             };
 
-            let json: serde_json::Map<String, Value> = serde_json::from_str(&transaction.inputs())
-                .map_err(|e| BatcherError::FailedTransaction {
-                    msg: e.to_string(),
-                    txn: Box::new(transaction.clone()),
-                })?;
+            // A deploy names its package in its type, and may carry no inputs.
+            let inputs = transaction.inputs();
+            let json: serde_json::Map<String, Value> =
+                if inputs.is_empty() && transaction.transaction_type().is_deploy() {
+                    serde_json::Map::new()
+                } else {
+                    serde_json::from_str(&inputs).map_err(|e| BatcherError::FailedTransaction {
+                        msg: e.to_string(),
+                        txn: Box::new(transaction.clone()),
+                    })?
+                };
 
-            let content_id = {
-                match json
+            let (content_id, declared) = match transaction.transaction_type() {
+                TransactionType::Deploy {
+                    content_id,
+                    runtime,
+                    ..
+                } => (content_id, Some(runtime.to_string())),
+                _ => match json
                     .get("contentId")
                     .ok_or(BatcherError::FailedTransaction {
                         msg: "content id is required".to_string(),
                         txn: Box::new(transaction.clone()),
                     })? {
-                    Value::String(cid) => cid.clone(),
+                    Value::String(cid) => (cid.clone(), None),
                     _ => {
                         return Err(BatcherError::FailedTransaction {
                             msg: "contentId is incorrect type: Must be String".to_string(),
                             txn: Box::new(transaction.clone()),
                         })
                     }
-                }
+                },
             };
+            if let (Some(runtime), Some(declared)) = (&runtime, &declared) {
+                if runtime != declared {
+                    return Err(BatcherError::FailedTransaction {
+                        msg: format!(
+                            "deploy declares the {declared} runtime, its package runs in {runtime}"
+                        ),
+                        txn: Box::new(transaction.clone()),
+                    });
+                }
+            }
 
             let program_id = create_program_id(content_id.clone(), &transaction).map_err(|e| {
                 BatcherError::FailedTransaction {
@@ -1386,6 +2654,19 @@ impl Batcher {
             metadata
                 .inner_mut()
                 .insert("content_id".to_string(), content_id);
+            if let Some(runtime) = runtime.or(declared) {
+                metadata
+                    .inner_mut()
+                    .insert(PROGRAM_RUNTIME_KEY.to_string(), runtime);
+            }
+            for (field, key) in INPUT_REGISTRATION_FIELDS {
+                let value = match json.get(field) {
+                    Some(Value::String(value)) => value.clone(),
+                    Some(Value::Number(value)) => value.to_string(),
+                    _ => continue,
+                };
+                metadata.inner_mut().insert(key.to_string(), value);
+            }
             let mut program_account = AccountBuilder::default()
                 .account_type(AccountType::Program(program_id))
                 .owner_address(transaction.from())
@@ -1395,12 +2676,19 @@ impl Batcher {
                 .program_account_linked_programs(BTreeSet::new())
                 .program_account_data(ArbitraryData::new())
                 .program_account_metadata(metadata)
-                .build()
+                .try_build()
                 .map_err(|e| BatcherError::FailedTransaction {
                     msg: e.to_string(),
                     txn: Box::new(transaction.clone()),
                 })?;
 
+            account.increment_nonce();
+            let mut charged = HashMap::from([(transaction.from().to_full_string(), account)]);
+            let fees = batcher.lock().await.fees;
+            Batcher::stage_fee(&fees, &transaction, &mut charged).await?;
+
+            let record = ProgramRecord::new(&program_account, &transaction);
+            let mut accounts = vec![program_account.clone()];
             Batcher::add_account_to_batch(
                 &batcher,
                 program_account,
@@ -1412,18 +2700,21 @@ impl Batcher {
                 txn: Box::new(transaction.clone()),
             })?;
 
-            account.increment_nonce();
-
-            Batcher::add_account_to_batch(
-                &batcher,
-                account,
-                "apply_program_registration: for user account".to_string(),
-            )
-            .await
-            .map_err(|e| BatcherError::FailedTransaction {
-                msg: e.to_string(),
-                txn: Box::new(transaction.clone()),
-            })?;
+            for account in charged.into_values() {
+                accounts.push(account.clone());
+                Batcher::add_account_to_batch(
+                    &batcher,
+                    account,
+                    "apply_program_registration: for user account".to_string(),
+                )
+                .await
+                .map_err(|e| BatcherError::FailedTransaction {
+                    msg: e.to_string(),
+                    txn: Box::new(transaction.clone()),
+                })?;
+            }
+            batcher.lock().await.programs.registered(record);
+            Batcher::committed(&batcher, &transaction, accounts, Vec::new()).await?;
 
             let message = SchedulerMessage::RegistrationSuccess {
                 program_id,
@@ -1443,6 +2734,33 @@ impl Batcher {
         }
     }
 
+    /// Attests to the accounts in `batch_buffer` against their state before
+    /// it is committed, if the batcher has an attestor.
+    #[cfg(feature = "attestations")]
+    async fn attest(
+        batcher: &Arc<Mutex<Batcher>>,
+        transaction: &Transaction,
+        outputs: &Outputs,
+        batch_buffer: &HashMap<Address, Account>,
+    ) -> Option<lasr_types::ExecutionAttestation> {
+        let attestor = batcher.lock().await.attestor.clone()?;
+        let mut before = HashMap::new();
+        for address in batch_buffer.keys() {
+            if let Some(account) = get_account(*address, ActorType::Batcher).await {
+                before.insert(*address, account);
+            }
+        }
+        Some(
+            attestor.attest(
+                transaction.hash_string(),
+                outputs,
+                batch_buffer
+                    .iter()
+                    .map(|(address, after)| (*address, before.get(address), after)),
+            ),
+        )
+    }
+
     fn add_account_to_batch_buffer(batch_buffer: &mut HashMap<Address, Account>, account: Account) {
         match &account.account_type() {
             AccountType::User => {
@@ -1484,18 +2802,20 @@ impl Batcher {
                 .program_account_linked_programs(BTreeSet::new())
                 .programs(BTreeMap::new())
                 .nonce(U256::from(0))
-                .build()
+                .try_build()
                 .map_err(|e| BatcherError::Custom(e.to_string()))?;
 
             Ok(account)
         }
     }
 
-    pub async fn apply_instructions_to_accounts(
-        batcher: Arc<Mutex<Batcher>>,
-        transaction: Transaction,
-        outputs: Outputs,
-    ) -> Result<(), BatcherError> {
+    /// Stages the accounts the instructions in `outputs` change, with the
+    /// caller's nonce incremented, without writing them.
+    pub async fn stage_instructions(
+        transaction: &Transaction,
+        outputs: &Outputs,
+        quota: &StorageQuota,
+    ) -> Result<HashMap<Address, Account>, BatcherError> {
         let mut batch_buffer = HashMap::new();
         let mut caller = get_account(transaction.to(), ActorType::Batcher)
             .await
@@ -1504,26 +2824,17 @@ impl Batcher {
                 txn: Box::new(transaction.clone()),
             })?;
 
+        // The caller is staged alongside every other account touched by the
+        // outputs, so a failed instruction also discards the nonce increment.
         caller.increment_nonce();
-
-        Batcher::add_account_to_batch(
-            &batcher,
-            caller,
-            "apply_instructions_to_accounts: for caller account".to_string(),
-        )
-        .await
-        .map_err(|e| BatcherError::FailedTransaction {
-            msg: e.to_string(),
-            txn: Box::new(transaction.clone()),
-        })?;
+        Batcher::add_account_to_batch_buffer(&mut batch_buffer, caller);
 
         for instruction in outputs.instructions().iter().cloned() {
             match instruction {
                 Instruction::Transfer(mut transfer) => {
                     tracing::warn!("Applying transfer instruction: {:?}", transfer);
                     let (from_account, to_account) = Batcher::apply_transfer_instruction(
-                        &batcher,
-                        &transaction,
+                        transaction,
                         &transfer,
                         &mut batch_buffer,
                     )
@@ -1554,13 +2865,17 @@ impl Batcher {
                     );
                     for dist in create.distribution() {
                         tracing::warn!("Applying distribution: {:?}", create);
-                        let account =
-                            Batcher::apply_distribution(&transaction, dist, &mut batch_buffer)
-                                .await
-                                .map_err(|e| BatcherError::FailedTransaction {
-                                    msg: e.to_string(),
-                                    txn: Box::new(transaction.clone()),
-                                })?;
+                        let account = Batcher::apply_distribution(
+                            &transaction,
+                            dist,
+                            &mut batch_buffer,
+                            quota,
+                        )
+                        .await
+                        .map_err(|e| BatcherError::FailedTransaction {
+                            msg: e.to_string(),
+                            txn: Box::new(transaction.clone()),
+                        })?;
                         Batcher::add_account_to_batch_buffer(&mut batch_buffer, account);
                     }
 
@@ -1582,6 +2897,7 @@ impl Batcher {
                             &transaction,
                             token_or_program_update,
                             &mut batch_buffer,
+                            quota,
                         )
                         .await
                         .map_err(|e| BatcherError::FailedTransaction {
@@ -1600,21 +2916,64 @@ impl Batcher {
             }
         }
 
-        for (_, account) in batch_buffer {
-            Batcher::add_account_to_batch(
-                &batcher,
-                account,
-                "apply_instructions_to_accounts: for batch buffer".to_string(),
-            )
-            .await
-            .map_err(|e| BatcherError::FailedTransaction {
-                msg: e.to_string(),
-                txn: Box::new(transaction.clone()),
-            })?;
+        Ok(batch_buffer)
+    }
+
+    pub async fn apply_instructions_to_accounts(
+        batcher: Arc<Mutex<Batcher>>,
+        transaction: Transaction,
+        outputs: Outputs,
+    ) -> Result<(), BatcherError> {
+        let (fees, quota) = {
+            let guard = batcher.lock().await;
+            (guard.fees, guard.storage_quota.clone())
+        };
+        let mut batch_buffer = Batcher::stage_instructions(&transaction, &outputs, &quota).await?;
+        let mut charged = HashMap::new();
+        for address in [transaction.from(), VERSE_ADDR] {
+            if let Some(account) = batch_buffer.remove(&address) {
+                charged.insert(address.to_full_string(), account);
+            }
+        }
+        Batcher::stage_fee(&fees, &transaction, &mut charged).await?;
+        for account in charged.into_values() {
+            Batcher::add_account_to_batch_buffer(&mut batch_buffer, account);
+        }
+
+        #[cfg(feature = "attestations")]
+        let attestation = Batcher::attest(&batcher, &transaction, &outputs, &batch_buffer).await;
+        #[cfg(not(feature = "attestations"))]
+        let attestation = None;
+
+        let accounts: Vec<Account> = batch_buffer.into_values().collect();
+        let token_deltas = Batcher::commit_batch_buffer(
+            &batcher,
+            accounts.clone(),
+            "apply_instructions_to_accounts: for batch buffer".to_string(),
+        )
+        .await
+        .map_err(|e| BatcherError::FailedTransaction {
+            msg: e.to_string(),
+            txn: Box::new(transaction.clone()),
+        })?;
+        {
+            let guard = batcher.lock().await;
+            guard.inclusions.applied(&transaction, token_deltas);
+            guard
+                .inclusions
+                .emitted(&transaction, outputs.events().clone());
+            let written = accounts
+                .iter()
+                .filter_map(|account| guard.encoding.encode(account).ok())
+                .map(|bytes| bytes.len() as u64)
+                .sum();
+            guard
+                .program_stats
+                .wrote(&transaction.program_id().to_full_string(), written);
         }
 
         tracing::warn!("Adding transaction to a batch");
-        Batcher::add_transaction_to_batch(batcher, transaction.clone()).await;
+        Batcher::committed(&batcher, &transaction, accounts, outputs.events().clone()).await?;
 
         if let Some(scheduler_actor) =
             get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
@@ -1654,6 +3013,7 @@ impl Batcher {
                 let message = SchedulerMessage::CallTransactionApplied {
                     transaction_hash: transaction.hash_string(),
                     account,
+                    attestation,
                 };
 
                 tracing::warn!("Informing scheduler that the call transaction was applied");
@@ -1684,7 +3044,7 @@ impl Batcher {
         .and_then(|pending_transactions| {
             let message = PendingTransactionMessage::Invalid {
                 transaction,
-                e: Box::new(BatcherError::Custom(err)) as Box<dyn std::error::Error + Send>,
+                e: BatcherError::Custom(err).into(),
             };
             pending_transactions.cast(message).typecast().log_err(|e| {
                 PendingTransactionError::Custom(format!(
@@ -1714,7 +3074,7 @@ impl Batcher {
                         //TiKV will accept any key if of type String, OR Vec<u8>
                         let acc_val = AccountValue { account: data };
                         // Serialize `Account` data to be stored.
-                        if let Ok(val) = bincode::serialize(&acc_val) {
+                        if let Ok(val) = guard.encoding.encode(&acc_val) {
                             if let Err(e) =
                                 PersistenceStore::put(&storage_ref, addr.clone().into(), val).await
                             {
@@ -1763,12 +3123,15 @@ impl Batcher {
                 {
                     let (tx, rx) = oneshot();
                     tracing::info!("Sending message to DA Client to store batch");
+                    let transaction_hashes: Vec<String> =
+                        guard.parent.transactions.keys().cloned().collect();
                     let message = DaClientMessage::StoreBatch {
                         batch: guard
                             .parent
                             .encode_batch()
                             .ok_or(BatcherError::Custom("failed to encode batch".to_string()))?,
                         tx,
+                        span: lifecycle::dispersal(&transaction_hashes),
                     };
                     da_client
                         .cast(message)
@@ -1788,14 +3151,14 @@ impl Batcher {
                         &blob_response.request_id()
                     );
                     let parent = guard.parent.clone();
-                    guard.cache.insert(blob_response.request_id(), parent);
+                    guard.dispersed(blob_response.request_id(), parent);
 
                     if let Some(child) = guard.children.pop_front() {
                         guard.parent = child;
                         return Ok(());
                     }
 
-                    guard.parent = Batch::new();
+                    guard.parent = Batch::new().with_encoding(guard.encoding);
 
                     Some(blob_response)
                 } else {
@@ -1816,10 +3179,17 @@ impl Batcher {
 
     async fn request_blob_validation(batcher: Arc<Mutex<Batcher>>, request_id: String) {
         let (tx, rx) = oneshot();
-        {
+        // Sealing at once, the batch is finalized here, before the
+        // transaction sealed is answered, rather than by the receivers.
+        let awaited = {
             let guard = batcher.lock().await;
-            guard.receiver_thread_tx.send(rx).await;
-        }
+            if guard.instant_seal.is_some() {
+                Some(rx)
+            } else {
+                guard.receiver_thread_tx.send(rx).await;
+                None
+            }
+        };
         if let Some(da_actor) = get_actor_ref::<DaClientMessage, DaClientError>(ActorType::DaClient)
         {
             if let Err(err) = da_actor.cast(DaClientMessage::ValidateBlob { request_id, tx }) {
@@ -1828,6 +3198,101 @@ impl Batcher {
                 );
             }
         }
+        if let Some(rx) = awaited {
+            match rx.await {
+                Ok((request_id, proof)) => {
+                    if let Err(e) =
+                        Batcher::handle_blob_verification_proof(batcher, request_id, proof).await
+                    {
+                        tracing::error!("failed to finalize the batch sealed: {e}");
+                    }
+                }
+                Err(e) => tracing::error!("the DA layer did not confirm the batch sealed: {e:?}"),
+            }
+        }
+    }
+
+    /// Holds `batch` as the blob the DA layer is dispersing under
+    /// `request_id`, until the blob is verified.
+    pub fn dispersed(&mut self, request_id: String, batch: Batch) {
+        self.cache.insert(request_id, (batch, unix_now()));
+    }
+
+    /// Numbers the batch dispersed under `request_id`, now its blob is
+    /// verified as `blob_index` of the DA batch `blob_commitment`, and
+    /// records it along with what went into it. The batch itself is no
+    /// longer kept; its accounts are read back from the DA layer.
+    pub async fn finalize_batch(
+        batcher: Arc<Mutex<Batcher>>,
+        request_id: &str,
+        blob_commitment: H256,
+        blob_index: u128,
+    ) -> Result<(BatchHeader, HashSet<String>), BatcherError> {
+        let mut guard = batcher.lock().await;
+        let (batch, sealed_at) = guard
+            .cache
+            .remove(request_id)
+            .ok_or(BatcherError::Custom("request id not in cache".to_string()))?;
+        let accounts: HashSet<String> = batch.accounts.keys().cloned().collect();
+        let mut transactions: Vec<String> = batch.transactions.keys().cloned().collect();
+        transactions.sort();
+        let state_root = batch.state_root()?;
+        let transactions_root = batch.transactions_root()?;
+        let events_root = batch.events_root()?;
+        let batch_id = guard.next_batch_id;
+        let previous_state_root = guard
+            .batches
+            .latest()
+            .map(|previous| previous.state_root)
+            .unwrap_or_default();
+        guard.next_batch_id += 1;
+        guard.inclusions.batched(&transactions, batch_id);
+        guard.inclusions.proven(batch.inclusion_proofs(batch_id)?);
+        guard.programs.batched(&transactions, batch_id);
+        for transaction_hash in &transactions {
+            lifecycle::finished(transaction_hash);
+        }
+        let record = BatchRecord {
+            batch_id,
+            state_root,
+            previous_state_root,
+            transactions_root,
+            events_root,
+            blob_commitment,
+            blob_index,
+            withdrawals: transactions
+                .iter()
+                .filter_map(|hash| batch.transactions.get(hash))
+                .filter_map(Withdrawal::of)
+                .collect(),
+            account_count: accounts.len(),
+            transaction_hashes: transactions.clone(),
+            sealed_at,
+            finalized_at: unix_now(),
+        };
+        let header = record.header();
+        if let Some(index) = &guard.transaction_index {
+            if let Err(e) = index.batched(&record, batch.transactions.values()) {
+                tracing::error!("failed to index the transactions of batch {batch_id}: {e}");
+            }
+        }
+        if let Err(e) = guard
+            .event_log
+            .batched(batch_id, EmittedEvent::of_batch(batch_id, &batch))
+        {
+            tracing::error!("failed to record the events of batch {batch_id}: {e}");
+        }
+        if let Err(e) = guard.batches.finalized(record.clone()) {
+            tracing::error!("failed to record batch {batch_id} in the batch index: {e}");
+        }
+        guard.batch_feed.publish(&record, &batch);
+        if let Err(e) = guard.commit_log.finalized(&transactions) {
+            tracing::error!("failed to consume the commits of batch {batch_id}: {e}");
+        }
+        let metrics = batcher_metrics();
+        metrics.finalized.inc();
+        metrics.latest_batch_id.set(batch_id as i64);
+        Ok((header, accounts))
     }
 
     pub(super) async fn handle_blob_verification_proof(
@@ -1837,43 +3302,57 @@ impl Batcher {
     ) -> Result<(), BatcherError> {
         tracing::info!("received blob verification proof");
 
+        let decoded = base64::decode(proof.batch_metadata().batch_header_hash().to_string())
+            .map_err(|e| {
+                BatcherError::Custom(format!("unable to decode batch_header_hash(): {e:?}"))
+            })?;
+        if decoded.len() != 32 {
+            return Err(BatcherError::Custom(format!(
+                "batch_header_hash() is {} bytes, not 32",
+                decoded.len()
+            )));
+        }
+        let batch_header_hash = H256::from_slice(&decoded);
+        let blob_index = proof.blob_index();
+
+        let instant_seal = batcher.lock().await.instant_seal.clone();
+        // The DA layer commits to the blob through the header of the batch it
+        // was dispersed in.
+        let (header, accounts) =
+            Batcher::finalize_batch(batcher, &request_id, batch_header_hash, blob_index).await?;
+
+        // A devnet settles nowhere, so the batch is settled once it is final.
+        if let Some(InstantSeal { receipts, .. }) = instant_seal {
+            receipts.set(
+                &header,
+                BatchStatus::Settled {
+                    transaction_hash: H256::zero(),
+                    block_number: 0,
+                },
+            );
+            return Ok(());
+        }
+
         if let Some(eo_client) = get_actor_ref::<EoMessage, EoClientError>(ActorType::EoClient) {
-            let accounts: HashSet<String> = {
-                let guard = batcher.lock().await;
-                guard
-                    .cache
-                    .get(&request_id)
-                    .ok_or(BatcherError::Custom("request id not in cache".to_string()))?
-                    .accounts
-                    .keys()
-                    .cloned()
-                    .collect()
+            let message = EoMessage::Settle {
+                accounts,
+                batch_header_hash,
+                blob_index,
             };
 
-            base64::decode(proof.batch_metadata().batch_header_hash().to_string())
+            eo_client.cast(message).typecast().log_err(|e| {
+                EoClientError::Custom(format!(
+                    "failed to cast settle message to EoClientActor: {e:?}"
+                ))
+            });
+
+            eo_client
+                .cast(EoMessage::SettleBatchHeader { header })
                 .typecast()
                 .log_err(|e| {
-                    BatcherError::Custom("unable to decode batch_header_hash()".to_string())
-                })
-                .and_then(|decoded| {
-                    let mut bytes = [0u8; 32];
-                    bytes.copy_from_slice(&decoded);
-
-                    let batch_header_hash = H256(bytes);
-
-                    let blob_index = proof.blob_index();
-
-                    let message = EoMessage::Settle {
-                        accounts,
-                        batch_header_hash,
-                        blob_index,
-                    };
-
-                    eo_client.cast(message).typecast().log_err(|e| {
-                        EoClientError::Custom(format!(
-                            "failed to cast settle message to EoClientActor: {e:?}"
-                        ))
-                    })
+                    EoClientError::Custom(format!(
+                        "failed to cast batch header to EoClientActor: {e:?}"
+                    ))
                 });
         }
 
@@ -1902,7 +3381,9 @@ impl Actor for BatcherActor {
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
         let batcher_ptr = Arc::clone(state);
+        let _handling = mailbox_handling(ActorType::Batcher);
         match message {
+            BatcherMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Batcher, probe),
             BatcherMessage::GetNextBatch { storage_ref } => {
                 Batcher::handle_next_batch_request(batcher_ptr, storage_ref).await?;
                 // let mut guard = self.future_pool.lock().await;
@@ -1913,41 +3394,27 @@ impl Actor for BatcherActor {
                 outputs,
             } => {
                 tracing::warn!("appending transaction to batch");
-                match transaction.transaction_type() {
-                    TransactionType::Send(_) => {
-                        tracing::warn!("send transaction");
-                        let fut =
-                            Batcher::add_transaction_to_account(batcher_ptr, transaction.clone());
-                        let mut guard = self.future_pool.lock().await;
-                        guard.push(fut.boxed());
-                    }
-                    TransactionType::BridgeIn(_) => {
-                        tracing::warn!("bridge in transaction");
-                        let fut =
-                            Batcher::add_transaction_to_account(batcher_ptr, transaction.clone());
-                        let mut guard = self.future_pool.lock().await;
-                        guard.push(fut.boxed());
-                    }
-                    TransactionType::Call(_) => {
-                        if let Some(o) = outputs {
-                            let fut = Batcher::apply_instructions_to_accounts(
-                                batcher_ptr,
-                                transaction,
-                                o,
-                            );
-                            let mut guard = self.future_pool.lock().await;
-                            guard.push(fut.boxed());
-                        } else {
-                            tracing::error!("Call transaction result did not contain outputs")
-                        }
-                    }
-                    TransactionType::RegisterProgram(_) => {
-                        let fut = Batcher::apply_program_registration(batcher_ptr, transaction);
-                        let mut guard = self.future_pool.lock().await;
-                        guard.push(fut.boxed());
-                    }
-                    TransactionType::BridgeOut(_) => {}
-                }
+                let transaction_hash = transaction.hash_string();
+                let applied_ptr = Arc::clone(&batcher_ptr);
+                let fut = Batcher::apply_transaction(batcher_ptr, transaction, outputs);
+                let fut = Batcher::apply_once(applied_ptr, transaction_hash, fut);
+                let mut guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            BatcherMessage::AppendProgramRegistration {
+                transaction,
+                runtime,
+            } => {
+                let transaction_hash = transaction.hash_string();
+                let applied_ptr = Arc::clone(&batcher_ptr);
+                let fut = Batcher::apply_program_registration_in_runtime(
+                    batcher_ptr,
+                    transaction,
+                    Some(runtime),
+                );
+                let fut = Batcher::apply_once(applied_ptr, transaction_hash, fut);
+                let mut guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
             }
             BatcherMessage::BlobVerificationProof { request_id, proof } => {
                 tracing::info!("received blob verification proof");
@@ -1955,6 +3422,13 @@ impl Actor for BatcherActor {
                 let mut guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
+            BatcherMessage::RevertBridgeIn { transaction } => {
+                let fut = Batcher::revert_bridge_in(batcher_ptr, transaction);
+                let mut guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            #[cfg(feature = "crash_test")]
+            BatcherMessage::Panic => panic!("batcher told to panic"),
         }
         Ok(())
     }
@@ -2058,19 +3532,18 @@ impl Actor for BatcherSupervisor {
     }
 }
 
+/// Asks the batcher for the next batch every `batch_interval`, until told to
+/// stop.
 pub async fn batch_requestor(
     mut stopper: tokio::sync::mpsc::Receiver<u8>,
     storage_ref: StorageRef,
+    batch_interval: Duration,
 ) {
     if let Some(batcher) = ractor::registry::where_is(ActorType::Batcher.to_string()) {
         let batcher: ActorRef<BatcherMessage> = batcher.into();
-        let batch_interval_secs = std::env::var("BATCH_INTERVAL")
-            .unwrap_or_else(|_| "180".to_string())
-            .parse::<u64>()
-            .unwrap_or(180);
         loop {
             tracing::info!("SLEEPING THEN REQUESTING NEXT BATCH");
-            tokio::time::sleep(tokio::time::Duration::from_secs(batch_interval_secs)).await;
+            tokio::time::sleep(batch_interval).await;
             let message = BatcherMessage::GetNextBatch {
                 storage_ref: storage_ref.clone(),
             };
@@ -2095,7 +3568,6 @@ mod batcher_tests {
     use anyhow::Result;
     use eigenda_client::proof::BlobVerificationProof;
     use futures::{FutureExt, StreamExt};
-    use lasr_types::TransactionType;
     use std::sync::Arc;
     use tokio::sync::Mutex;
 
@@ -2113,6 +3585,7 @@ mod batcher_tests {
         async fn handle(&self, message: Self::Msg, state: &mut Self::State) -> Result<()> {
             let batcher_ptr = Arc::clone(state);
             match message {
+                BatcherMessage::ProbeMailbox(_) => {}
                 BatcherMessage::GetNextBatch { storage_ref } => {
                     let fut = Batcher::handle_next_batch_request(batcher_ptr, storage_ref);
                     let mut guard = self.future_pool.lock().await;
@@ -2123,45 +3596,21 @@ mod batcher_tests {
                     outputs,
                 } => {
                     tracing::warn!("appending transaction to batch");
-                    match transaction.transaction_type() {
-                        TransactionType::Send(_) => {
-                            tracing::warn!("send transaction");
-                            let fut = Batcher::add_transaction_to_account(
-                                batcher_ptr,
-                                transaction.clone(),
-                            );
-                            let mut guard = self.future_pool.lock().await;
-                            guard.push(fut.boxed());
-                        }
-                        TransactionType::BridgeIn(_) => {
-                            tracing::warn!("bridge in transaction");
-                            let fut = Batcher::add_transaction_to_account(
-                                batcher_ptr,
-                                transaction.clone(),
-                            );
-                            let mut guard = self.future_pool.lock().await;
-                            guard.push(fut.boxed());
-                        }
-                        TransactionType::Call(_) => {
-                            if let Some(o) = outputs {
-                                let fut = Batcher::apply_instructions_to_accounts(
-                                    batcher_ptr,
-                                    transaction,
-                                    o,
-                                );
-                                let mut guard = self.future_pool.lock().await;
-                                guard.push(fut.boxed());
-                            } else {
-                                tracing::error!("Call transaction result did not contain outputs")
-                            }
-                        }
-                        TransactionType::RegisterProgram(_) => {
-                            let fut = Batcher::apply_program_registration(batcher_ptr, transaction);
-                            let mut guard = self.future_pool.lock().await;
-                            guard.push(fut.boxed());
-                        }
-                        TransactionType::BridgeOut(_) => {}
-                    }
+                    let fut = Batcher::apply_transaction(batcher_ptr, transaction, outputs);
+                    let mut guard = self.future_pool.lock().await;
+                    guard.push(fut.boxed());
+                }
+                BatcherMessage::AppendProgramRegistration {
+                    transaction,
+                    runtime,
+                } => {
+                    let fut = Batcher::apply_program_registration_in_runtime(
+                        batcher_ptr,
+                        transaction,
+                        Some(runtime),
+                    );
+                    let mut guard = self.future_pool.lock().await;
+                    guard.push(fut.boxed());
                 }
                 BatcherMessage::BlobVerificationProof { request_id, proof } => {
                     tracing::info!("received blob verification proof");
@@ -2170,6 +3619,13 @@ mod batcher_tests {
                     let mut guard = self.future_pool.lock().await;
                     guard.push(fut.boxed());
                 }
+                BatcherMessage::RevertBridgeIn { transaction } => {
+                    let fut = Batcher::revert_bridge_in(batcher_ptr, transaction);
+                    let mut guard = self.future_pool.lock().await;
+                    guard.push(fut.boxed());
+                }
+                #[cfg(feature = "crash_test")]
+                BatcherMessage::Panic => panic!("batcher told to panic"),
             }
             Ok(())
         }
@@ -0,0 +1,262 @@
+//! The batches this node has sealed, for `getBatch`, `getLatestBatch` and
+//! `getBatches`.
+//!
+//! The batcher records a batch once its blob is verified on the DA layer.
+//! Only the header of each batch and the hashes of its transactions are
+//! kept; the accounts a batch holds are decoded from its blob when asked for.
+//! With a path, each record is appended to a file as it is made, and read
+//! back on the next start, so batch ids carry on where they left off.
+//!
+//! Records are written as JSON in a [`RecordLog`], as in the pending log.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use ethereum_types::H256;
+use lasr_messages::{BatchHeader, BatchStatus, Withdrawal};
+use lasr_types::Account;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::record_log::{RecordLog, RecordLogError};
+
+#[derive(Debug, Error)]
+pub enum BatchIndexError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// What a batch was sealed as.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchRecord {
+    pub batch_id: u64,
+    /// Commits to the accounts as the batch left them.
+    pub state_root: H256,
+    /// The state root of the batch before it, chaining each batch to the
+    /// last. Zero for the first.
+    #[serde(default)]
+    pub previous_state_root: H256,
+    /// Commits to the hashes of the batch's transactions. Zero for batches
+    /// recorded before transactions were proven.
+    #[serde(default)]
+    pub transactions_root: H256,
+    /// Commits to the events the batch's calls emitted. Zero for batches
+    /// recorded before events were.
+    #[serde(default)]
+    pub events_root: H256,
+    /// The DA layer's header hash of the blob the batch is in.
+    pub blob_commitment: H256,
+    pub blob_index: u128,
+    /// The value the batch's bridge outs took off the chain. Empty for
+    /// batches recorded before bridge outs were settled.
+    #[serde(default)]
+    pub withdrawals: Vec<Withdrawal>,
+    pub account_count: usize,
+    pub transaction_hashes: Vec<String>,
+    /// Seconds since the epoch the batch was dispersed to the DA layer at.
+    pub sealed_at: u64,
+    /// Seconds since the epoch the batch's blob was verified at.
+    pub finalized_at: u64,
+}
+
+impl BatchRecord {
+    /// The header the batch is settled under.
+    pub fn header(&self) -> BatchHeader {
+        BatchHeader {
+            batch_id: self.batch_id,
+            state_root: self.state_root,
+            transactions_root: self.transactions_root,
+            events_root: self.events_root,
+            blob_commitment: self.blob_commitment,
+            blob_index: self.blob_index,
+            withdrawals: self.withdrawals.clone(),
+        }
+    }
+}
+
+/// A batch's record, with how far it has got settling and, if asked for,
+/// the accounts it holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchInfo {
+    #[serde(flatten)]
+    pub record: BatchRecord,
+    #[serde(flatten)]
+    pub status: BatchStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<Account>>,
+}
+
+#[derive(Debug, Default)]
+struct BatchIndexInner {
+    batches: BTreeMap<u64, BatchRecord>,
+    log: Option<RecordLog<BatchRecord>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchIndex {
+    index: Arc<Mutex<BatchIndexInner>>,
+}
+
+impl BatchIndex {
+    /// Opens the index at `path`, creating it if there is none, with the
+    /// batches recorded there already.
+    pub fn open(path: &Path) -> Result<Self, BatchIndexError> {
+        let (log, records) = RecordLog::open(path)?;
+        let batches = records
+            .into_iter()
+            .map(|record| (record.batch_id, record))
+            .collect();
+        Ok(Self {
+            index: Arc::new(Mutex::new(BatchIndexInner {
+                batches,
+                log: Some(log),
+            })),
+        })
+    }
+
+    /// Records a batch whose blob was verified, appending it to the file if
+    /// the index has one.
+    pub fn finalized(&self, record: BatchRecord) -> Result<(), BatchIndexError> {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(log) = index.log.as_mut() {
+            log.append_synced([&record])?;
+        }
+        index.batches.insert(record.batch_id, record);
+        Ok(())
+    }
+
+    pub fn get(&self, batch_id: u64) -> Option<BatchRecord> {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .batches
+            .get(&batch_id)
+            .cloned()
+    }
+
+    pub fn latest(&self) -> Option<BatchRecord> {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .batches
+            .last_key_value()
+            .map(|(_, record)| record.clone())
+    }
+
+    /// The id the next batch sealed is recorded under.
+    pub fn next_batch_id(&self) -> u64 {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .batches
+            .last_key_value()
+            .map(|(batch_id, _)| batch_id + 1)
+            .unwrap_or_default()
+    }
+
+    /// The batch, of `through` and those before it, that `transaction_hash`
+    /// went into, if any.
+    pub fn find_transaction(&self, transaction_hash: &str, through: u64) -> Option<BatchRecord> {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .batches
+            .range(..=through)
+            .rev()
+            .find(|(_, record)| {
+                record
+                    .transaction_hashes
+                    .iter()
+                    .any(|hash| hash == transaction_hash)
+            })
+            .map(|(_, record)| record.clone())
+    }
+
+    /// Every batch recorded, oldest first.
+    pub fn list(&self) -> Vec<BatchRecord> {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .batches
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod batches_tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::record_log::{encode_record, Json};
+
+    fn record(batch_id: u64) -> BatchRecord {
+        BatchRecord {
+            batch_id,
+            state_root: H256::repeat_byte(1),
+            previous_state_root: H256::repeat_byte(1),
+            transactions_root: H256::repeat_byte(4),
+            events_root: H256::repeat_byte(3),
+            blob_commitment: H256::repeat_byte(2),
+            blob_index: batch_id as u128,
+            withdrawals: Vec::new(),
+            account_count: 2,
+            transaction_hashes: vec![format!("0x{batch_id:064x}")],
+            sealed_at: 10,
+            finalized_at: 12,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("batch_index_{}_{}.log", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    #[test]
+    fn batches_are_read_back_after_a_restart() {
+        let path = temp_path("restart");
+        let index = BatchIndex::open(&path).unwrap();
+        assert_eq!(index.next_batch_id(), 0);
+        assert_eq!(index.latest(), None);
+        for batch_id in 0..3 {
+            index.finalized(record(batch_id)).unwrap();
+        }
+        drop(index);
+
+        // A record torn off by a crash is dropped.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&encode_record(&Json, &record(3)).unwrap()[..20])
+            .unwrap();
+        drop(file);
+
+        let index = BatchIndex::open(&path).unwrap();
+        assert_eq!(index.next_batch_id(), 3);
+        assert_eq!(index.latest(), Some(record(2)));
+        assert_eq!(index.get(1), Some(record(1)));
+        assert_eq!(
+            index
+                .list()
+                .iter()
+                .map(|record| record.batch_id)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        index.finalized(record(3)).unwrap();
+        drop(index);
+        assert_eq!(BatchIndex::open(&path).unwrap().next_batch_id(), 4);
+        std::fs::remove_file(&path).ok();
+    }
+}
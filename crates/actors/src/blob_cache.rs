@@ -115,6 +115,10 @@ impl Actor for BlobCacheActor {
         _message: Self::Msg,
         _state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
+        #[cfg(feature = "crash_test")]
+        if let BlobCacheMessage::Panic = _message {
+            panic!("blob cache told to panic");
+        }
         Ok(())
     }
 }
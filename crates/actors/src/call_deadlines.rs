@@ -0,0 +1,266 @@
+//! Deadlines on the calls handed to the executor.
+//!
+//! Every time a call is handed to the executor it is given until the
+//! deadline to complete. A call that has not is cancelled with the executor.
+//! If the executor confirms it stopped the call before its outputs were
+//! taken, nothing of it was committed and it is retried. Otherwise its
+//! outputs may still be applied, so it is failed rather than run twice. The
+//! call keeps its execution credit until then, and hands it back when it is
+//! failed.
+//!
+//! Whichever of a call's completion and its deadline arrives first decides
+//! what happens to it. The other is ignored and counted, so a late
+//! completion does not release the calls behind one already failed, and a
+//! deadline does not cancel a call that completed just before it.
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use prometheus::{IntCounter, IntCounterVec};
+
+use crate::metrics;
+
+/// The time a call has to complete once it is handed to the executor,
+/// unless `CALL_DEADLINE_MS` says otherwise.
+pub const DEFAULT_CALL_DEADLINE: Duration = Duration::from_secs(120);
+
+/// The time the executor has to answer a cancellation before the call is
+/// taken to have possibly been committed.
+pub const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct CallDeadlineMetrics {
+    exceeded: IntCounter,
+    cancellations: IntCounterVec,
+    ignored: IntCounterVec,
+}
+
+fn call_deadline_metrics() -> &'static CallDeadlineMetrics {
+    static METRICS: OnceLock<CallDeadlineMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| CallDeadlineMetrics {
+        exceeded: metrics::counter(
+            "pending",
+            "call_deadlines_exceeded_total",
+            "Calls that did not complete before their deadline.",
+        ),
+        cancellations: metrics::counter_vec(
+            "pending",
+            "call_cancellations_total",
+            "Cancellations of calls past their deadline, by whether the executor confirmed them.",
+            &["outcome"],
+        ),
+        ignored: metrics::counter_vec(
+            "pending",
+            "call_deadline_signals_ignored_total",
+            "Completions and deadlines of calls already settled by the other, by signal.",
+            &["signal"],
+        ),
+    })
+}
+
+/// Counts the cancellation of a call past its deadline, which the executor
+/// `confirmed` or not.
+fn cancellation_answered(confirmed: bool) {
+    let outcome = match confirmed {
+        true => "aborted",
+        false => "unconfirmed",
+    };
+    call_deadline_metrics()
+        .cancellations
+        .with_label_values(&[outcome])
+        .inc();
+}
+
+/// Where the cancellation of a call past its deadline has got to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cancellation {
+    /// The executor has yet to answer.
+    Pending,
+    /// The executor could not confirm it stopped the call, which may still
+    /// complete.
+    Unconfirmed,
+}
+
+/// The calls with the executor and when they were handed to it, and those
+/// that ran past their deadline.
+#[derive(Clone, Debug)]
+pub struct CallDeadlines {
+    deadline: Option<Duration>,
+    next_dispatch: u64,
+    /// The latest dispatch of each call with the executor.
+    dispatched: HashMap<String, u64>,
+    /// Calls that ran past their deadline, until they are run again, their
+    /// cancellation is confirmed, or their late completion arrives.
+    timed_out: HashMap<String, Cancellation>,
+}
+
+impl Default for CallDeadlines {
+    fn default() -> Self {
+        Self::new(Some(DEFAULT_CALL_DEADLINE))
+    }
+}
+
+impl CallDeadlines {
+    /// Gives calls `deadline` to complete, or as long as they take if none.
+    pub fn new(deadline: Option<Duration>) -> Self {
+        Self {
+            deadline,
+            next_dispatch: 0,
+            dispatched: HashMap::new(),
+            timed_out: HashMap::new(),
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Records that `transaction_hash` is handed to the executor, returning
+    /// the dispatch its deadline is for, or `None` if there are no deadlines.
+    pub fn dispatched(&mut self, transaction_hash: &str) -> Option<u64> {
+        self.deadline?;
+        self.next_dispatch += 1;
+        self.timed_out.remove(transaction_hash);
+        self.dispatched
+            .insert(transaction_hash.to_string(), self.next_dispatch);
+        Some(self.next_dispatch)
+    }
+
+    /// Records that `transaction_hash` completed, returning `false` if it
+    /// had already run past its deadline, in which case the completion is
+    /// ignored.
+    pub fn completed(&mut self, transaction_hash: &str) -> bool {
+        self.dispatched.remove(transaction_hash);
+        let Some(cancellation) = self.timed_out.get(transaction_hash) else {
+            return true;
+        };
+        // The answer to a cancellation still on its way settles the call.
+        if *cancellation == Cancellation::Unconfirmed {
+            self.timed_out.remove(transaction_hash);
+        }
+        call_deadline_metrics()
+            .ignored
+            .with_label_values(&["completion"])
+            .inc();
+        false
+    }
+
+    /// Records that `dispatch` of `transaction_hash` reached its deadline,
+    /// returning `false` if the call completed, failed or was handed to the
+    /// executor again since, in which case the deadline is ignored.
+    pub fn expired(&mut self, transaction_hash: &str, dispatch: u64) -> bool {
+        if self.dispatched.get(transaction_hash) != Some(&dispatch) {
+            call_deadline_metrics()
+                .ignored
+                .with_label_values(&["deadline"])
+                .inc();
+            return false;
+        }
+        self.dispatched.remove(transaction_hash);
+        self.timed_out
+            .insert(transaction_hash.to_string(), Cancellation::Pending);
+        call_deadline_metrics().exceeded.inc();
+        true
+    }
+
+    /// Records the executor's answer to the cancellation of
+    /// `transaction_hash`, returning `false` if the call was settled
+    /// otherwise meanwhile, in which case the answer is ignored.
+    pub fn cancelled(&mut self, transaction_hash: &str, confirmed: bool) -> bool {
+        cancellation_answered(confirmed);
+        if self.timed_out.get(transaction_hash) != Some(&Cancellation::Pending) {
+            call_deadline_metrics()
+                .ignored
+                .with_label_values(&["deadline"])
+                .inc();
+            return false;
+        }
+        match confirmed {
+            true => self.timed_out.remove(transaction_hash),
+            false => self
+                .timed_out
+                .insert(transaction_hash.to_string(), Cancellation::Unconfirmed),
+        };
+        true
+    }
+
+    /// Whether `transaction_hash` ran past its deadline and has not been
+    /// run again since.
+    pub fn is_timed_out(&self, transaction_hash: &str) -> bool {
+        self.timed_out.contains_key(transaction_hash)
+    }
+
+    /// Stops waiting on a call that failed. One failed after its deadline
+    /// but before its cancellation was answered is settled by the failure.
+    /// One whose cancellation went unconfirmed is still marked timed out, as
+    /// its late completion may yet arrive.
+    pub fn forget(&mut self, transaction_hash: &str) {
+        self.dispatched.remove(transaction_hash);
+        if self.timed_out.get(transaction_hash) == Some(&Cancellation::Pending) {
+            self.timed_out.remove(transaction_hash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod call_deadlines_tests {
+    use super::*;
+
+    #[test]
+    fn the_first_of_a_completion_and_a_deadline_wins() {
+        let mut deadlines = CallDeadlines::new(Some(Duration::from_millis(10)));
+
+        // Completed in time, so its deadline is ignored.
+        let dispatch = deadlines.dispatched("a").unwrap();
+        assert!(deadlines.completed("a"));
+        assert!(!deadlines.expired("a", dispatch));
+
+        // Past its deadline, so its completion is ignored, even once the
+        // executor could not confirm it was cancelled.
+        let dispatch = deadlines.dispatched("b").unwrap();
+        assert!(deadlines.expired("b", dispatch));
+        assert!(!deadlines.completed("b"));
+        assert!(deadlines.cancelled("b", false));
+        assert!(deadlines.is_timed_out("b"));
+        assert!(!deadlines.completed("b"));
+        assert!(!deadlines.is_timed_out("b"));
+
+        // Cancelled for sure, so nothing of it is left to arrive.
+        let dispatch = deadlines.dispatched("c").unwrap();
+        assert!(deadlines.expired("c", dispatch));
+        assert!(deadlines.cancelled("c", true));
+        assert!(!deadlines.is_timed_out("c"));
+    }
+
+    #[test]
+    fn only_the_latest_dispatch_of_a_call_expires() {
+        let mut deadlines = CallDeadlines::new(Some(Duration::from_millis(10)));
+        let first = deadlines.dispatched("a").unwrap();
+        assert!(deadlines.expired("a", first));
+
+        // Run again, the call is no longer timed out, and the deadline of
+        // its first run no longer counts.
+        let second = deadlines.dispatched("a").unwrap();
+        assert!(!deadlines.is_timed_out("a"));
+        assert!(!deadlines.expired("a", first));
+        assert!(deadlines.completed("a"));
+        assert!(!deadlines.expired("a", second));
+
+        // A call failed meanwhile is not timed out by its deadline.
+        let third = deadlines.dispatched("a").unwrap();
+        deadlines.forget("a");
+        assert!(!deadlines.expired("a", third));
+
+        // Nor is one run again before its cancellation is answered.
+        let fourth = deadlines.dispatched("a").unwrap();
+        assert!(deadlines.expired("a", fourth));
+        deadlines.dispatched("a").unwrap();
+        assert!(!deadlines.cancelled("a", true));
+    }
+
+    #[test]
+    fn no_deadline_leaves_calls_as_long_as_they_take() {
+        let mut deadlines = CallDeadlines::new(None);
+        assert_eq!(deadlines.dispatched("a"), None);
+        assert!(deadlines.completed("a"));
+    }
+}
@@ -0,0 +1,342 @@
+//! Fault injection for the messages actors cast one another, so tests can
+//! provoke the loss, duplication, delay and reordering that are otherwise
+//! left to chance.
+//!
+//! Sends between the actors whose guarantees are tested go through
+//! `cast_between`, naming the actor sending and the one sent to. Without the
+//! `chaos` feature it is a plain `ActorRef::cast` and nothing else here is
+//! compiled. With it, a test installs a `ChaosPlan` of faults for named
+//! pairs, drawn from an RNG seeded by the plan, so a failing run is
+//! reproduced by its seed.
+use lasr_messages::ActorType;
+use ractor::{ActorRef, MessagingErr};
+
+#[cfg(feature = "chaos")]
+pub use layer::*;
+
+/// Casts `message` to `actor`, the `to` actor, from the `from` actor.
+#[cfg(not(feature = "chaos"))]
+#[inline]
+pub fn cast_between<M: ractor::Message>(
+    _from: ActorType,
+    _to: ActorType,
+    actor: &ActorRef<M>,
+    message: M,
+) -> Result<(), MessagingErr<M>> {
+    actor.cast(message)
+}
+
+/// Casts `message` to `actor`, the `to` actor, from the `from` actor,
+/// injecting the fault the installed plan draws for the pair, if any.
+#[cfg(feature = "chaos")]
+pub fn cast_between<M: Replay>(
+    from: ActorType,
+    to: ActorType,
+    actor: &ActorRef<M>,
+    message: M,
+) -> Result<(), MessagingErr<M>> {
+    layer::cast(from, to, actor, message)
+}
+
+#[cfg(feature = "chaos")]
+mod layer {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, MutexGuard};
+    use std::time::Duration;
+
+    use lasr_messages::{AccountCacheMessage, ActorType, BatcherMessage, EngineMessage};
+    use ractor::{ActorRef, MessagingErr};
+
+    /// The plan installed, if a test installed one.
+    static CHAOS: Mutex<Option<Chaos>> = Mutex::new(None);
+
+    type Held = Box<dyn FnOnce() + Send>;
+
+    /// What is done to a message instead of casting it once, at once.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Fault {
+        Drop,
+        /// Cast twice. Messages carrying a reply port cannot be, and are
+        /// cast once.
+        Duplicate,
+        Delay(Duration),
+        /// Held back until the next message between the same pair has been
+        /// cast, or the plan is released.
+        Reorder,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ChaosRule {
+        pub from: ActorType,
+        pub to: ActorType,
+        pub fault: Fault,
+        /// The chance, from 0 to 1, of a message between the pair drawing
+        /// the fault.
+        pub probability: f64,
+    }
+
+    /// A fault that was injected, in the order they were.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct InjectedFault {
+        pub from: ActorType,
+        pub to: ActorType,
+        pub fault: Fault,
+    }
+
+    /// The faults to inject, and the seed they are drawn with.
+    #[derive(Debug, Clone, Default)]
+    pub struct ChaosPlan {
+        seed: u64,
+        rules: Vec<ChaosRule>,
+    }
+
+    impl ChaosPlan {
+        pub fn new(seed: u64) -> Self {
+            Self {
+                seed,
+                rules: Vec::new(),
+            }
+        }
+
+        /// Injects `fault` into messages from `from` to `to`, with the chance
+        /// `probability` each. Of the rules for a pair, the first drawn is
+        /// injected.
+        pub fn with_rule(
+            mut self,
+            from: ActorType,
+            to: ActorType,
+            fault: Fault,
+            probability: f64,
+        ) -> Self {
+            self.rules.push(ChaosRule {
+                from,
+                to,
+                fault,
+                probability,
+            });
+            self
+        }
+
+        /// Installs the plan for every message cast until the guard
+        /// returned is dropped.
+        pub fn install(self) -> ChaosGuard {
+            *chaos() = Some(Chaos {
+                rng: SplitMix64(self.seed),
+                rules: self.rules,
+                held: HashMap::new(),
+                injected: Vec::new(),
+            });
+            ChaosGuard { _private: () }
+        }
+    }
+
+    /// The plan installed. Dropping it uninstalls the plan and casts the
+    /// messages still held back.
+    #[derive(Debug)]
+    pub struct ChaosGuard {
+        _private: (),
+    }
+
+    impl ChaosGuard {
+        /// The faults injected so far.
+        pub fn injected(&self) -> Vec<InjectedFault> {
+            chaos()
+                .as_ref()
+                .map(|chaos| chaos.injected.clone())
+                .unwrap_or_default()
+        }
+
+        /// Casts every message held back to be reordered.
+        pub fn release(&self) {
+            let held: Vec<Held> = chaos()
+                .as_mut()
+                .map(|chaos| chaos.held.drain().flat_map(|(_, held)| held).collect())
+                .unwrap_or_default();
+            for cast in held {
+                cast();
+            }
+        }
+    }
+
+    impl Drop for ChaosGuard {
+        fn drop(&mut self) {
+            self.release();
+            *chaos() = None;
+        }
+    }
+
+    struct Chaos {
+        rng: SplitMix64,
+        rules: Vec<ChaosRule>,
+        held: HashMap<(ActorType, ActorType), Vec<Held>>,
+        injected: Vec<InjectedFault>,
+    }
+
+    impl Chaos {
+        /// The fault a message from `from` to `to` draws, if any.
+        fn draw(&mut self, from: &ActorType, to: &ActorType) -> Option<Fault> {
+            let fault = self
+                .rules
+                .iter()
+                .filter(|rule| &rule.from == from && &rule.to == to)
+                .find(|rule| self.rng.chance() < rule.probability)
+                .map(|rule| rule.fault)?;
+            self.injected.push(InjectedFault {
+                from: from.clone(),
+                to: to.clone(),
+                fault,
+            });
+            Some(fault)
+        }
+    }
+
+    fn chaos() -> MutexGuard<'static, Option<Chaos>> {
+        CHAOS.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A small, seeded RNG, so the faults drawn depend on nothing but the
+    /// plan's seed and the order messages are cast in.
+    struct SplitMix64(u64);
+
+    impl SplitMix64 {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        /// A draw from `[0, 1)`.
+        fn chance(&mut self) -> f64 {
+            (self.next() >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    /// A message that can be cast again, as a duplicate. Those carrying a
+    /// reply port answer `None`.
+    pub trait Replay: ractor::Message + Sized {
+        fn replay(&self) -> Option<Self>;
+    }
+
+    impl Replay for EngineMessage {
+        fn replay(&self) -> Option<Self> {
+            match self {
+                EngineMessage::Call { transaction } => Some(EngineMessage::Call {
+                    transaction: transaction.clone(),
+                }),
+                EngineMessage::Send {
+                    transaction,
+                    replace,
+                } => Some(EngineMessage::Send {
+                    transaction: transaction.clone(),
+                    replace: *replace,
+                }),
+                EngineMessage::RegisterProgram { transaction } => {
+                    Some(EngineMessage::RegisterProgram {
+                        transaction: transaction.clone(),
+                    })
+                }
+                _ => None,
+            }
+        }
+    }
+
+    impl Replay for BatcherMessage {
+        fn replay(&self) -> Option<Self> {
+            match self {
+                BatcherMessage::BlobVerificationProof { request_id, proof } => {
+                    Some(BatcherMessage::BlobVerificationProof {
+                        request_id: request_id.clone(),
+                        proof: proof.clone(),
+                    })
+                }
+                _ => None,
+            }
+        }
+    }
+
+    impl Replay for AccountCacheMessage {
+        fn replay(&self) -> Option<Self> {
+            match self {
+                AccountCacheMessage::Write {
+                    account,
+                    who,
+                    location,
+                } => Some(AccountCacheMessage::Write {
+                    account: account.clone(),
+                    who: who.clone(),
+                    location: location.clone(),
+                }),
+                AccountCacheMessage::WriteBatch {
+                    accounts,
+                    who,
+                    location,
+                } => Some(AccountCacheMessage::WriteBatch {
+                    accounts: accounts.clone(),
+                    who: who.clone(),
+                    location: location.clone(),
+                }),
+                _ => None,
+            }
+        }
+    }
+
+    pub(super) fn cast<M: Replay>(
+        from: ActorType,
+        to: ActorType,
+        actor: &ActorRef<M>,
+        message: M,
+    ) -> Result<(), MessagingErr<M>> {
+        let pair = (from, to);
+        let (fault, held) = {
+            let mut chaos = chaos();
+            let Some(chaos) = chaos.as_mut() else {
+                return actor.cast(message);
+            };
+            let fault = chaos.draw(&pair.0, &pair.1);
+            let held = chaos.held.remove(&pair).unwrap_or_default();
+            (fault, held)
+        };
+        let result = match fault {
+            Some(Fault::Drop) => {
+                tracing::warn!("chaos dropped a message from {} to {}", pair.0, pair.1);
+                Ok(())
+            }
+            Some(Fault::Duplicate) => {
+                let copy = message.replay();
+                actor.cast(message)?;
+                match copy {
+                    Some(copy) => actor.cast(copy),
+                    None => Ok(()),
+                }
+            }
+            Some(Fault::Delay(delay)) => {
+                let actor = actor.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let _ = actor.cast(message);
+                });
+                Ok(())
+            }
+            // A message already held is overtaken by this one, which is then
+            // cast at once rather than held too.
+            Some(Fault::Reorder) if held.is_empty() => {
+                let actor = actor.clone();
+                let mut chaos = chaos();
+                let Some(chaos) = chaos.as_mut() else {
+                    return actor.cast(message);
+                };
+                chaos.held.entry(pair).or_default().push(Box::new(move || {
+                    let _ = actor.cast(message);
+                }));
+                Ok(())
+            }
+            _ => actor.cast(message),
+        };
+        for cast in held {
+            cast();
+        }
+        result
+    }
+}
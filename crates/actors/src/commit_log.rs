@@ -0,0 +1,449 @@
+//! A write-ahead log of the transactions the batcher commits, so a node that
+//! crashes before sealing them loses none it answered.
+//!
+//! A transaction is appended, with the accounts it left and the events it
+//! emitted, and synced, once its accounts are written to the account cache
+//! and before it is answered. Once the batch it went into is finalized on
+//! the DA layer, a record of the batch's transactions is appended, and
+//! synced, consuming them. Replaying the log gives back the transactions
+//! committed but not consumed, in the order they were committed, to be put
+//! back into the batches to seal. Once nothing is left unconsumed, the log
+//! is truncated; once consumed transactions make up most of it, it is
+//! rewritten with only those that are not.
+//!
+//! Records are written in a [`RecordLog`], each an envelope in the node's
+//! encoding, or JSON in logs written before there were envelopes. A
+//! transaction whose record is torn by a crash was not answered.
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use lasr_types::{Account, ProgramEvent, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::encoding::{self, Encoding};
+use crate::record_log::{RecordCodec, RecordLog, RecordLogError};
+
+/// Records below which the log is never compacted.
+pub const COMMIT_LOG_COMPACT_MIN_RECORDS: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum CommitLogError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// A transaction the batcher committed, as it goes into a batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitEntry {
+    /// Where the transaction was committed among those in the log.
+    pub sequence: u64,
+    pub transaction: Transaction,
+    /// The accounts the transaction left, whole.
+    pub accounts: Vec<Account>,
+    pub events: Vec<ProgramEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+enum CommitLogRecord {
+    Committed(CommitEntry),
+    Finalized { transaction_hashes: Vec<String> },
+}
+
+/// Records in an envelope of the node's encoding, read back in either, or
+/// as JSON as they were written before there were envelopes.
+#[derive(Debug, Clone, Copy, Default)]
+struct Envelope(Encoding);
+
+impl RecordCodec<CommitLogRecord> for Envelope {
+    fn encode(&self, record: &CommitLogRecord) -> Result<Vec<u8>, String> {
+        self.0.encode(record).map_err(|e| e.to_string())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<CommitLogRecord, String> {
+        let Some(encoding) = Encoding::of(bytes) else {
+            return serde_json::from_slice(bytes).map_err(|e| e.to_string());
+        };
+        let record = encoding::decode(bytes).map_err(|e| e.to_string())?;
+        // bincode stops once it has read a record, so a length stretched over
+        // the start of the next would otherwise go unnoticed.
+        let len = encoding.encode(&record).map_err(|e| e.to_string())?.len();
+        if len != bytes.len() {
+            return Err(format!(
+                "the record is {len} bytes, not the {} its length says",
+                bytes.len()
+            ));
+        }
+        Ok(record)
+    }
+}
+
+#[derive(Debug, Default)]
+struct CommitLogInner {
+    log: Option<RecordLog<CommitLogRecord, Envelope>>,
+    /// The transactions committed and not yet consumed, by sequence.
+    live: BTreeMap<u64, CommitEntry>,
+    sequences: HashMap<String, u64>,
+    next_sequence: u64,
+}
+
+/// The log the batcher commits to. Without a path, the default, nothing is
+/// logged.
+#[derive(Debug, Clone, Default)]
+pub struct CommitLog {
+    inner: Arc<Mutex<CommitLogInner>>,
+}
+
+impl CommitLog {
+    /// Opens the log at `path`, creating it if there is none, and returns it
+    /// with the transactions committed and not consumed there, oldest first.
+    pub fn open(path: &Path) -> Result<(Self, Vec<CommitEntry>), CommitLogError> {
+        let (log, records) = RecordLog::open(path)?;
+        let mut inner = CommitLogInner::default();
+        for record in records {
+            match record {
+                CommitLogRecord::Committed(entry) => {
+                    inner.next_sequence = inner.next_sequence.max(entry.sequence + 1);
+                    inner.insert(entry);
+                }
+                CommitLogRecord::Finalized { transaction_hashes } => {
+                    inner.consume(&transaction_hashes);
+                }
+            }
+        }
+        inner.log = Some(log);
+
+        let replayed = inner.live.values().cloned().collect();
+        Ok((
+            Self {
+                inner: Arc::new(Mutex::new(inner)),
+            },
+            replayed,
+        ))
+    }
+
+    /// Writes the records appended from now on encoded in `encoding`.
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        if let Some(log) = self
+            .inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .log
+            .as_mut()
+        {
+            log.set_codec(Envelope(encoding));
+        }
+        self
+    }
+
+    /// Appends `transaction`, which left `accounts` and emitted `events`,
+    /// and syncs it, returning where it was committed.
+    pub fn committed(
+        &self,
+        transaction: &Transaction,
+        accounts: Vec<Account>,
+        events: Vec<ProgramEvent>,
+    ) -> Result<u64, CommitLogError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.log.is_none() {
+            return Ok(0);
+        }
+        let entry = CommitEntry {
+            sequence: inner.next_sequence,
+            transaction: transaction.clone(),
+            accounts,
+            events,
+        };
+        inner.append(&CommitLogRecord::Committed(entry.clone()))?;
+        inner.next_sequence += 1;
+        let sequence = entry.sequence;
+        inner.insert(entry);
+        Ok(sequence)
+    }
+
+    /// Consumes those of `transaction_hashes` in the log, now the batch they
+    /// went into is finalized, truncating or compacting the log if it can
+    /// be.
+    pub fn finalized(&self, transaction_hashes: &[String]) -> Result<(), CommitLogError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction_hashes: Vec<String> = transaction_hashes
+            .iter()
+            .filter(|transaction_hash| inner.sequences.contains_key(*transaction_hash))
+            .cloned()
+            .collect();
+        if inner.log.is_none() || transaction_hashes.is_empty() {
+            return Ok(());
+        }
+        inner.append(&CommitLogRecord::Finalized {
+            transaction_hashes: transaction_hashes.clone(),
+        })?;
+        inner.consume(&transaction_hashes);
+        let records = inner.log.as_ref().map_or(0, RecordLog::records);
+        if inner.live.is_empty() {
+            inner.truncate()
+        } else if records >= COMMIT_LOG_COMPACT_MIN_RECORDS && records > 2 * inner.live.len() {
+            inner.compact()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// How many transactions are committed and not yet consumed.
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .live
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CommitLogInner {
+    fn insert(&mut self, entry: CommitEntry) {
+        let transaction_hash = entry.transaction.hash_string();
+        if let Some(sequence) = self.sequences.insert(transaction_hash, entry.sequence) {
+            self.live.remove(&sequence);
+        }
+        self.live.insert(entry.sequence, entry);
+    }
+
+    fn consume(&mut self, transaction_hashes: &[String]) {
+        for transaction_hash in transaction_hashes {
+            if let Some(sequence) = self.sequences.remove(transaction_hash) {
+                self.live.remove(&sequence);
+            }
+        }
+    }
+
+    fn append(&mut self, record: &CommitLogRecord) -> Result<(), CommitLogError> {
+        if let Some(log) = self.log.as_mut() {
+            log.append_synced([record])?;
+        }
+        Ok(())
+    }
+
+    fn truncate(&mut self) -> Result<(), CommitLogError> {
+        if let Some(log) = self.log.as_mut() {
+            log.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log with only the transactions not yet consumed.
+    fn compact(&mut self) -> Result<(), CommitLogError> {
+        let Some(log) = self.log.as_mut() else {
+            return Ok(());
+        };
+        let compacted = log.records();
+        let records: Vec<_> = self
+            .live
+            .values()
+            .cloned()
+            .map(CommitLogRecord::Committed)
+            .collect();
+        log.rewrite(&records)?;
+
+        tracing::info!(
+            "compacted the commit log from {compacted} records to {}",
+            records.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod commit_log_tests {
+    use super::*;
+    use crate::test_fixtures::send;
+    use lasr_types::Address;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn accounts(nonce: u64) -> Vec<Account> {
+        vec![Account::new(Address::new([nonce as u8; 20]))]
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("commit_log_{}_{}.log", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn nonces(replayed: &[CommitEntry]) -> Vec<u64> {
+        replayed
+            .iter()
+            .map(|entry| entry.transaction.nonce().as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn commits_not_yet_finalized_are_replayed_in_order() {
+        let path = temp_path("replayed");
+        let (log, replayed) = CommitLog::open(&path).unwrap();
+        assert!(replayed.is_empty());
+        for nonce in [2, 0, 1, 3] {
+            log.committed(&send(nonce), accounts(nonce), Vec::new())
+                .unwrap();
+        }
+        log.finalized(&[send(0).hash_string(), send(9).hash_string()])
+            .unwrap();
+        drop(log);
+
+        let (log, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![2, 1, 3]);
+        assert_eq!(replayed[0].accounts, accounts(2));
+        assert_eq!(log.len(), 3);
+
+        // Committed after a restart, a transaction is replayed after those
+        // committed before it.
+        assert_eq!(log.committed(&send(4), accounts(4), Vec::new()).unwrap(), 4);
+        drop(log);
+        let (_, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![2, 1, 3, 4]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_torn_commit_is_dropped() {
+        let path = temp_path("torn");
+        let (log, _) = CommitLog::open(&path).unwrap();
+        log.committed(&send(0), accounts(0), Vec::new()).unwrap();
+        log.committed(&send(1), accounts(1), Vec::new()).unwrap();
+        drop(log);
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let (log, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![0]);
+        log.committed(&send(2), accounts(2), Vec::new()).unwrap();
+        drop(log);
+        let (_, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![0, 2]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_whole_commit_that_does_not_decode_fails_the_open_and_is_kept() {
+        let path = temp_path("corrupt");
+        let (log, _) = CommitLog::open(&path).unwrap();
+        log.committed(&send(0), accounts(0), Vec::new()).unwrap();
+        drop(log);
+        let first = std::fs::metadata(&path).unwrap().len();
+        let (log, _) = CommitLog::open(&path).unwrap();
+        log.committed(&send(1), accounts(1), Vec::new()).unwrap();
+        drop(log);
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        // A flipped byte inside the first record, with the second after it.
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] = b'x';
+        std::fs::write(&path, &bytes).unwrap();
+        let error = CommitLog::open(&path).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                CommitLogError::Log(RecordLogError::Corrupt { offset: 0, .. })
+            ),
+            "{error:?}"
+        );
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        // A length pointing past the next record's start, but inside the file.
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] = Encoding::Bincode.format_byte();
+        let stretched = (first - 4 + 8) as u32;
+        bytes[..4].copy_from_slice(&stretched.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(CommitLog::open(&path).is_err());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn records_in_either_encoding_or_json_are_replayed() {
+        let path = temp_path("encodings");
+        let (log, _) = CommitLog::open(&path).unwrap();
+        log.committed(&send(0), accounts(0), Vec::new()).unwrap();
+        let log = log.with_encoding(Encoding::Borsh);
+        log.committed(&send(1), accounts(1), Vec::new()).unwrap();
+        drop(log);
+        // As a log written before there were envelopes.
+        let entry = CommitEntry {
+            sequence: 2,
+            transaction: send(2),
+            accounts: accounts(2),
+            events: Vec::new(),
+        };
+        let json = serde_json::to_vec(&CommitLogRecord::Committed(entry)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&(json.len() as u32).to_le_bytes()).unwrap();
+        file.write_all(&json).unwrap();
+        drop(file);
+
+        let (_, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![0, 1, 2]);
+        assert_eq!(replayed[1].accounts, accounts(1));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn the_log_is_truncated_once_everything_in_it_is_finalized() {
+        let path = temp_path("truncated");
+        let (log, _) = CommitLog::open(&path).unwrap();
+        for nonce in 0..3 {
+            log.committed(&send(nonce), accounts(nonce), Vec::new())
+                .unwrap();
+        }
+        log.finalized(&[send(0).hash_string(), send(1).hash_string()])
+            .unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        log.finalized(&[send(2).hash_string()]).unwrap();
+        assert!(log.is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        log.committed(&send(3), accounts(3), Vec::new()).unwrap();
+        drop(log);
+        let (_, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![3]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn compaction_keeps_only_what_is_not_finalized() {
+        let path = temp_path("compacted");
+        let (log, _) = CommitLog::open(&path).unwrap();
+        log.committed(&send(0), accounts(0), Vec::new()).unwrap();
+        for nonce in 1..=COMMIT_LOG_COMPACT_MIN_RECORDS as u64 {
+            log.committed(&send(nonce), accounts(nonce), Vec::new())
+                .unwrap();
+            log.finalized(&[send(nonce).hash_string()]).unwrap();
+        }
+        assert_eq!(log.inner.lock().unwrap().records, 1);
+        drop(log);
+
+        let (log, replayed) = CommitLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![0]);
+        assert_eq!(log.inner.lock().unwrap().records, 1);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,2925 @@
+//! The node's configuration, read once as it starts.
+//!
+//! Settings are layered, each layer overriding the one before it: the
+//! defaults, then the TOML file at `NODE_CONFIG_PATH` if it is set, then the
+//! environment variables the node has always been configured by. The file
+//! has a table for each section, keyed as in [`SETTINGS`]:
+//!
+//! ```toml
+//! [rpc]
+//! port = 9292
+//!
+//! [batcher]
+//! interval_secs = 60
+//! ```
+//!
+//! The whole configuration is checked before anything is started, and every
+//! setting found invalid is reported at once rather than the first alone.
+//! Secrets, the operator key and the admin token among them, are not part of
+//! it and are only ever read from the environment.
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use eo_listener::{
+    provider_urls, EventFilterConfig, EventFilterSet, IngestionMode, DEFAULT_CONFIRMATIONS,
+    DEFAULT_FILTER_BACKFILL_BLOCKS, DEFAULT_GAP_TIMEOUT, DEFAULT_LOG_RANGE,
+    DEFAULT_PROVIDER_RETRY_AFTER, DEFAULT_PROVIDER_TIMEOUT,
+};
+use lasr_compute::{
+    ExecutionLimits, ExecutionLimitsConfig, ExecutionLimitsOverride, ExecutionLogs, GatewayFetcher,
+    LocalStoreFetcher, ProgramFetchers, DEFAULT_LOG_MAX_LINES, DEFAULT_LOG_SUBSCRIBER_BUFFER,
+    DEFAULT_PROGRAM_FETCH_TIMEOUT,
+};
+use lasr_types::{StorageQuota, DEFAULT_MAX_ACCOUNT_BYTES, DEFAULT_MAX_TOKEN_BYTES, U256};
+use thiserror::Error;
+use web3::types::Address as EthereumAddress;
+
+use crate::{
+    encoding::Encoding, AdminConfig, AdminError, Admission, AdmissionPolicyError, ArtifactCache,
+    AuthLimits, DaHealth, DeadLetterError, DeadLetters, ExecutionPool, ExecutionVerifier, Fairness,
+    FaucetConfig, FeeSchedule, FollowerConfig, InputLimits, LoadShedder, LoadSheddingConfig,
+    LoadSignals, MailboxConfig, MailboxLoad, MailboxMonitor, MempoolLimits, Notifications,
+    OutputLimits, Paginator, PendingConfig, PolicyConfig, PolicyGate, ProgramStats,
+    ProgramStatsError, RateLimit, RateLimitConfig, RequestLimits, RestartPolicy, RetentionPolicy,
+    RetryPolicy, SchedulingConfig, SettlerConfig, SupervisionPolicy, UnsealedBatches,
+    DEFAULT_DA_FAILURE_THRESHOLD, DEFAULT_ETH_CHAIN_ID, DEFAULT_EXECUTION_QUEUE_DEPTH,
+    DEFAULT_EXPENSIVE_RATE_LIMIT_BURST, DEFAULT_EXPENSIVE_RATE_LIMIT_PER_SECOND,
+    DEFAULT_FAUCET_COOLDOWN_SECS, DEFAULT_FAUCET_MAX_AMOUNT, DEFAULT_FOLLOWER_RECONNECT_MS,
+    DEFAULT_FOLLOWER_REPLICA_DIR, DEFAULT_FOLLOWER_WITNESS_SAMPLE,
+    DEFAULT_MAILBOX_HIGH_WATER_DEPTH, DEFAULT_MAILBOX_HIGH_WATER_LATENCY_MS,
+    DEFAULT_MAILBOX_RECOVER_PERCENT, DEFAULT_MAX_DEAD_LETTERS, DEFAULT_MAX_INPUT_BYTES,
+    DEFAULT_MAX_NONCE_AHEAD, DEFAULT_MAX_PROGRAM_INPUT_BYTES, DEFAULT_MAX_RESPONSE_BYTES,
+    DEFAULT_PROGRAM_STATS_FLUSH_SECS, DEFAULT_RATE_LIMIT_BURST, DEFAULT_RATE_LIMIT_PER_SECOND,
+    DEFAULT_RECENT_TRANSACTIONS_WINDOW, DEFAULT_SETTLEMENT_ATTEMPTS, DEFAULT_SETTLEMENT_GAS_LIMIT,
+    DEFAULT_SETTLEMENT_REPLACE_AFTER, DEFAULT_SHED_EVENT_LOOP_LAG_MS, DEFAULT_SHED_EXECUTION_QUEUE,
+    DEFAULT_SHED_PENDING_TRANSACTIONS, DEFAULT_SHED_RECOVER_PERCENT, DEFAULT_SHED_RETRY_AFTER_MS,
+    DEFAULT_SHUTDOWN_DEADLINE_SECS, DEFAULT_SHUTDOWN_DRAIN_SECS, DEFAULT_SUBSCRIPTION_BUFFER,
+    DEFAULT_TOP_PROGRAMS, DEFAULT_UNSEALED_BATCHES_PATH, DEFAULT_VERIFICATION_PERCENT, ETH_ADDR,
+    KEYSTORE_MANIFEST, VERSE_ADDR,
+};
+
+/// Every setting, keyed as in the config file, with the environment
+/// variable overriding it.
+pub const SETTINGS: &[(&str, &str)] = &[
+    ("rpc.host", "RPC_HOST"),
+    ("rpc.port", "PORT"),
+    ("rpc.admin_addr", "ADMIN_RPC_ADDR"),
+    ("rpc.rest", "RPC_REST"),
+    ("rpc.max_batch_size", "RPC_MAX_BATCH_SIZE"),
+    ("rpc.max_request_bytes", "RPC_MAX_REQUEST_BYTES"),
+    ("rpc.eth_chain_id", "ETH_CHAIN_ID"),
+    (
+        "rpc.simulate_unsigned_payloads",
+        "SIMULATE_UNSIGNED_PAYLOADS",
+    ),
+    ("rpc.rate_limit_per_second", "RPC_RATE_LIMIT_PER_SECOND"),
+    ("rpc.rate_limit_burst", "RPC_RATE_LIMIT_BURST"),
+    (
+        "rpc.expensive_rate_limit_per_second",
+        "RPC_EXPENSIVE_RATE_LIMIT_PER_SECOND",
+    ),
+    (
+        "rpc.expensive_rate_limit_burst",
+        "RPC_EXPENSIVE_RATE_LIMIT_BURST",
+    ),
+    ("rpc.rate_limit_allowlist", "RPC_RATE_LIMIT_ALLOWLIST"),
+    ("rpc.subscription_buffer", "SUBSCRIPTION_BUFFER"),
+    ("rpc.max_response_bytes", "RPC_MAX_RESPONSE_BYTES"),
+    ("caches.artifact_cache_max_mb", "ARTIFACT_CACHE_MAX_MB"),
+    ("caches.audit_verse_supply", "AUDIT_VERSE_SUPPLY"),
+    (
+        "caches.pending_max_transactions",
+        "PENDING_MAX_TRANSACTIONS",
+    ),
+    ("caches.pending_max_bytes", "PENDING_MAX_BYTES"),
+    (
+        "caches.pending_sender_quota_transactions",
+        "PENDING_SENDER_QUOTA_TRANSACTIONS",
+    ),
+    (
+        "caches.pending_sender_quota_bytes",
+        "PENDING_SENDER_QUOTA_BYTES",
+    ),
+    ("pending.log_path", "PENDING_LOG_PATH"),
+    ("pending.ttl_secs", "PENDING_TRANSACTION_TTL_SECS"),
+    (
+        "pending.max_future_transactions",
+        "PENDING_MAX_FUTURE_TRANSACTIONS",
+    ),
+    ("pending.max_in_flight", "PENDING_MAX_IN_FLIGHT"),
+    ("pending.call_deadline_ms", "CALL_DEADLINE_MS"),
+    ("scheduling.policy", "SCHEDULING_POLICY"),
+    ("scheduling.age_boost", "SCHEDULING_AGE_BOOST"),
+    ("scheduling.sender_quantum", "SCHEDULING_SENDER_QUANTUM"),
+    ("scheduling.sender_weights", "SCHEDULING_SENDER_WEIGHTS"),
+    ("batcher.interval_secs", "BATCH_INTERVAL"),
+    ("batcher.commit_log_path", "COMMIT_LOG_PATH"),
+    ("batcher.batch_index_path", "BATCH_INDEX_PATH"),
+    ("batcher.unsealed_batches_path", "UNSEALED_BATCHES_PATH"),
+    ("da.backend", "DA_BACKEND"),
+    ("da.server_address", "EIGENDA_SERVER_ADDRESS"),
+    ("da.proto_path", "EIGENDA_PROTO_PATH"),
+    ("da.local_path", "LOCAL_DA_PATH"),
+    ("da.failure_threshold", "DA_FAILURE_THRESHOLD"),
+    ("eo_server.eth_rpc_urls", "ETH_RPC_URL"),
+    ("eo_server.contract_address", "EO_CONTRACT_ADDRESS"),
+    ("eo_server.blocks_processed_path", "BLOCKS_PROCESSED_PATH"),
+    ("eo_server.event_filters", "EO_EVENT_FILTERS"),
+    ("eo_server.log_range", "EO_LOG_RANGE"),
+    (
+        "eo_server.filter_backfill_blocks",
+        "EO_FILTER_BACKFILL_BLOCKS",
+    ),
+    (
+        "eo_server.provider_timeout_secs",
+        "ETH_RPC_PROVIDER_TIMEOUT_SECS",
+    ),
+    (
+        "eo_server.provider_retry_after_secs",
+        "ETH_RPC_PROVIDER_RETRY_AFTER_SECS",
+    ),
+    ("eo_server.ingestion_mode", "EO_INGESTION_MODE"),
+    ("eo_server.ws_url", "ETH_WS_URL"),
+    ("eo_server.confirmations", "EO_CONFIRMATIONS"),
+    ("eo_server.gap_timeout_secs", "EO_GAP_TIMEOUT_SECS"),
+    ("executor.timeout_secs", "EXECUTION_TIMEOUT_SECS"),
+    ("executor.memory_limit_mb", "EXECUTION_MEMORY_LIMIT_MB"),
+    ("executor.output_limit_kb", "EXECUTION_OUTPUT_LIMIT_KB"),
+    ("executor.concurrency", "EXECUTION_CONCURRENCY"),
+    ("executor.queue_depth", "EXECUTION_QUEUE_DEPTH"),
+    ("executor.limit_overrides", "EXECUTION_LIMIT_OVERRIDES"),
+    ("executor.log_max_lines", "EXECUTION_LOG_MAX_LINES"),
+    (
+        "executor.log_subscriber_buffer",
+        "EXECUTION_LOG_SUBSCRIBER_BUFFER",
+    ),
+    (
+        "executor.program_fetch_timeout_secs",
+        "PROGRAM_FETCH_TIMEOUT_SECS",
+    ),
+    ("executor.program_gateway_url", "PROGRAM_GATEWAY_URL"),
+    (
+        "executor.health_check_interval_secs",
+        "EXECUTOR_HEALTH_CHECK_INTERVAL_SECS",
+    ),
+    (
+        "executor.health_check_timeout_secs",
+        "EXECUTOR_HEALTH_CHECK_TIMEOUT_SECS",
+    ),
+    (
+        "executor.restart_after_failures",
+        "EXECUTOR_RESTART_AFTER_FAILURES",
+    ),
+    (
+        "executor.verification_percent",
+        "EXECUTION_VERIFICATION_PERCENT",
+    ),
+    ("outputs.max_bytes", "MAX_OUTPUT_BYTES"),
+    ("outputs.max_instructions", "MAX_OUTPUT_INSTRUCTIONS"),
+    ("outputs.max_instruction_items", "MAX_INSTRUCTION_ITEMS"),
+    ("outputs.max_log_length", "MAX_OUTPUT_LOG_LENGTH"),
+    ("outputs.max_events", "MAX_OUTPUT_EVENTS"),
+    ("outputs.max_event_bytes", "MAX_EVENT_BYTES"),
+    ("fees.base", "FEE_BASE"),
+    ("fees.per_input_byte", "FEE_PER_INPUT_BYTE"),
+    ("fees.call", "FEE_CALL"),
+    ("fees.deploy", "FEE_DEPLOY"),
+    ("admission.policy_path", "ADMISSION_POLICY_PATH"),
+    ("admission.auth_timeout_ms", "AUTH_TIMEOUT_MS"),
+    ("admission.auth_memory_limit_mb", "AUTH_MEMORY_LIMIT_MB"),
+    ("admission.max_nonce_ahead", "MAX_NONCE_AHEAD"),
+    ("admission.max_input_bytes", "MAX_INPUT_BYTES"),
+    (
+        "admission.max_program_input_bytes",
+        "MAX_PROGRAM_INPUT_BYTES",
+    ),
+    ("devnet.enabled", "DEVNET"),
+    ("devnet.faucet_max_amount", "FAUCET_MAX_AMOUNT"),
+    ("devnet.faucet_cooldown_secs", "FAUCET_COOLDOWN_SECS"),
+    ("devnet.instant_seal", "DEVNET_INSTANT_SEAL"),
+    ("genesis.path", "GENESIS_PATH"),
+    ("keys.keystore_path", "KEYSTORE_PATH"),
+    ("transaction_index.enabled", "TRANSACTION_INDEX"),
+    ("transaction_index.path", "TRANSACTION_INDEX_PATH"),
+    ("receipts.recent_window", "RECENT_TRANSACTIONS_WINDOW"),
+    ("receipts.persist", "PERSIST_TRANSACTION_RECEIPTS"),
+    ("storage.max_token_bytes", "STORAGE_MAX_TOKEN_BYTES"),
+    ("storage.max_account_bytes", "STORAGE_MAX_ACCOUNT_BYTES"),
+    (
+        "storage.exempt_system_programs",
+        "STORAGE_EXEMPT_SYSTEM_PROGRAMS",
+    ),
+    ("storage.encoding", "STORAGE_ENCODING"),
+    ("retention.blob_days", "RETENTION_BLOB_DAYS"),
+    ("retention.blob_batches", "RETENTION_BLOB_BATCHES"),
+    ("retention.receipt_days", "RETENTION_RECEIPT_DAYS"),
+    ("retention.receipt_batches", "RETENTION_RECEIPT_BATCHES"),
+    ("retention.prune_interval_secs", "PRUNE_INTERVAL_SECS"),
+    ("retention.marks_path", "PRUNE_MARKS_PATH"),
+    ("program_stats.path", "PROGRAM_STATS_PATH"),
+    ("program_stats.top", "PROGRAM_STATS_TOP"),
+    ("program_stats.flush_secs", "PROGRAM_STATS_FLUSH_SECS"),
+    ("events.log_path", "EVENT_LOG_PATH"),
+    ("settlement.contract_address", "SETTLEMENT_CONTRACT_ADDRESS"),
+    ("settlement.gas_limit", "SETTLEMENT_GAS_LIMIT"),
+    (
+        "settlement.replace_after_secs",
+        "SETTLEMENT_REPLACE_AFTER_SECS",
+    ),
+    ("settlement.max_attempts", "SETTLEMENT_MAX_ATTEMPTS"),
+    ("dead_letters.path", "DEAD_LETTER_PATH"),
+    ("dead_letters.max_entries", "DEAD_LETTER_MAX_ENTRIES"),
+    ("supervision.max_restarts", "ACTOR_MAX_RESTARTS"),
+    (
+        "supervision.restart_window_secs",
+        "ACTOR_RESTART_WINDOW_SECS",
+    ),
+    ("retry.max_attempts", "CALL_RETRY_MAX_ATTEMPTS"),
+    ("retry.backoff_ms", "CALL_RETRY_BACKOFF_MS"),
+    ("shutdown.drain_secs", "SHUTDOWN_DRAIN_SECS"),
+    ("shutdown.deadline_secs", "SHUTDOWN_DEADLINE_SECS"),
+    (
+        "load_shedding.pending_transactions",
+        "LOAD_SHED_PENDING_TRANSACTIONS",
+    ),
+    ("load_shedding.execution_queue", "LOAD_SHED_EXECUTION_QUEUE"),
+    (
+        "load_shedding.event_loop_lag_ms",
+        "LOAD_SHED_EVENT_LOOP_LAG_MS",
+    ),
+    ("load_shedding.recover_percent", "LOAD_SHED_RECOVER_PERCENT"),
+    ("load_shedding.retry_after_ms", "LOAD_SHED_RETRY_AFTER_MS"),
+    ("mailboxes.high_water_depth", "MAILBOX_HIGH_WATER_DEPTH"),
+    (
+        "mailboxes.high_water_latency_ms",
+        "MAILBOX_HIGH_WATER_LATENCY_MS",
+    ),
+    ("mailboxes.recover_percent", "MAILBOX_RECOVER_PERCENT"),
+    ("follow.primary_url", "FOLLOW_PRIMARY_URL"),
+    ("follow.witness_sample", "FOLLOWER_WITNESS_SAMPLE"),
+    ("follow.reconnect_ms", "FOLLOWER_RECONNECT_MS"),
+    ("follow.replica_dir", "FOLLOWER_REPLICA_DIR"),
+];
+
+/// The executable oracle deployed first to a fresh local chain.
+const DEVNET_CONTRACT_ADDRESS: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+/// A setting found invalid, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{setting}{}: {reason}", .origin.as_ref().map(|origin| format!(" (from {origin})")).unwrap_or_default())]
+pub struct ConfigError {
+    pub setting: String,
+    /// Where the value found invalid was read from, if it was not checked
+    /// once every layer was applied.
+    pub origin: Option<String>,
+    pub reason: String,
+}
+
+impl ConfigError {
+    fn new(setting: &str, reason: impl Display) -> Self {
+        Self {
+            setting: setting.to_string(),
+            origin: None,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Every setting found invalid.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid node configuration: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcConfig {
+    pub host: IpAddr,
+    pub port: u16,
+    /// Where the listener answering the admin methods to the local host is
+    /// bound, if there is one.
+    pub admin_addr: Option<SocketAddr>,
+    /// Whether the read-only REST resources are served alongside the
+    /// methods.
+    pub rest: bool,
+    pub requests: RequestLimits,
+    /// The chain the `eth` methods answer for, unless the genesis names one.
+    pub eth_chain_id: u64,
+    /// Whether `simulatePayload` simulates unsigned payloads.
+    pub simulate_unsigned_payloads: bool,
+    /// The limit of the methods that are not expensive.
+    pub rate_limit: RateLimit,
+    /// The limit of each of the `EXPENSIVE_METHODS`.
+    pub expensive_rate_limit: RateLimit,
+    /// Clients never rate limited.
+    pub rate_limit_allowlist: Vec<IpAddr>,
+    /// Updates buffered for a subscriber that falls behind.
+    pub subscription_buffer: usize,
+    /// Bytes the entries of a page of a list serialize to at most.
+    pub max_response_bytes: usize,
+}
+
+impl RpcConfig {
+    pub fn bind_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    /// The admin methods, guarded by `token` if one is given.
+    pub fn admin(&self, token: Option<String>) -> Result<AdminConfig, AdminError> {
+        AdminConfig::new(token, self.admin_addr)
+    }
+
+    pub fn rate_limits(&self) -> RateLimitConfig {
+        RateLimitConfig::new(
+            self.rate_limit,
+            self.expensive_rate_limit,
+            self.rate_limit_allowlist.clone(),
+        )
+    }
+
+    pub fn paginator(&self) -> Paginator {
+        Paginator::new(self.max_response_bytes)
+    }
+
+    pub fn notifications(&self) -> Notifications {
+        Notifications::new(self.subscription_buffer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub artifact_cache_max_mb: u64,
+    /// The bounds of the pending pool.
+    pub mempool: MempoolLimits,
+    /// Whether the VERSE held by the cached accounts is checked against the
+    /// recorded supply after every batch written.
+    pub audit_verse_supply: bool,
+}
+
+impl CacheConfig {
+    pub fn artifact_cache(&self) -> ArtifactCache {
+        ArtifactCache::new(self.artifact_cache_max_mb.saturating_mul(1024 * 1024))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatcherConfig {
+    /// How often the batch being built is sealed and dispersed.
+    pub interval: Duration,
+    /// Where the transactions committed are logged until their batch is
+    /// finalized.
+    pub commit_log_path: PathBuf,
+    /// Where the batches sealed are indexed by number.
+    pub batch_index_path: PathBuf,
+    /// Where the batches not dispersed before the node stopped are kept
+    /// until it starts again.
+    pub unsealed_batches_path: PathBuf,
+}
+
+impl BatcherConfig {
+    pub fn unsealed_batches(&self) -> UnsealedBatches {
+        UnsealedBatches::new(&self.unsealed_batches_path)
+    }
+}
+
+/// Where batches are dispersed to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DaBackend {
+    #[default]
+    EigenDa,
+    /// Blobs written to files in a local directory, for devnets and tests.
+    LocalFile,
+}
+
+impl FromStr for DaBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "eigenda" => Ok(DaBackend::EigenDa),
+            "local" => Ok(DaBackend::LocalFile),
+            other => Err(format!(
+                "unknown DA backend {other}, expected eigenda or local"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaConfig {
+    pub backend: DaBackend,
+    /// The disperser, as `host:port`.
+    pub server_address: String,
+    pub proto_path: PathBuf,
+    /// The directory blobs are written to with the local backend.
+    pub local_path: PathBuf,
+    /// Failures in a row that open the breaker on the DA layer.
+    pub failure_threshold: u32,
+}
+
+impl DaConfig {
+    pub fn health(&self) -> DaHealth {
+        DaHealth::new(self.failure_threshold)
+    }
+}
+
+/// How the settlement layer's events reach the node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ingestion {
+    /// Logs are queried every block time.
+    #[default]
+    Polling,
+    /// Logs are pushed over the websocket at `eo_server.ws_url`, and queried
+    /// only to fill gaps.
+    Subscription,
+}
+
+impl FromStr for Ingestion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "polling" => Ok(Ingestion::Polling),
+            "subscription" => Ok(Ingestion::Subscription),
+            other => Err(format!(
+                "unknown ingestion mode {other}, expected polling or subscription"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EoServerConfig {
+    /// The providers settlement events are read from, in order of
+    /// preference.
+    pub eth_rpc_urls: Vec<String>,
+    pub contract_address: String,
+    pub blocks_processed_path: PathBuf,
+    /// Contracts watched beyond the executable oracle.
+    pub event_filters: EventFilterConfig,
+    /// Most blocks asked for in one log query.
+    pub log_range: u64,
+    /// Most blocks back a newly watched contract's events are read from.
+    pub filter_backfill_blocks: u64,
+    /// How long a provider gets to answer before the next one is tried.
+    pub provider_timeout: Duration,
+    /// How long a provider that failed is skipped for.
+    pub provider_retry_after: Duration,
+    pub ingestion: Ingestion,
+    /// The websocket events are pushed over in subscription mode.
+    pub ws_url: Option<String>,
+    /// Blocks before an event is final.
+    pub confirmations: u64,
+    /// How long a gap in a contract's events is waited on before it is
+    /// backfilled.
+    pub gap_timeout: Duration,
+}
+
+impl EoServerConfig {
+    pub fn ingestion_mode(&self) -> IngestionMode {
+        match self.ingestion {
+            Ingestion::Polling => IngestionMode::Polling,
+            Ingestion::Subscription => {
+                IngestionMode::Subscription(self.ws_url.clone().unwrap_or_default())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutorConfig {
+    pub limits: ExecutionLimits,
+    /// The programs run under limits of their own, by program id.
+    pub limit_overrides: HashMap<String, ExecutionLimitsOverride>,
+    /// Executions run at once.
+    pub concurrency: usize,
+    /// Executions waiting for one of those to finish.
+    pub queue_depth: usize,
+    /// Lines kept of each execution's logs.
+    pub log_max_lines: usize,
+    /// Lines buffered for a log subscriber that falls behind.
+    pub log_subscriber_buffer: usize,
+    /// How long each source gets to return program content.
+    pub program_fetch_timeout: Duration,
+    /// The IPFS gateway program content is fetched from when the node's own
+    /// store does not have it, if there is one.
+    pub program_gateway_url: Option<String>,
+    /// When the runtime is checked, and restarted.
+    pub health: RestartPolicy,
+    /// The percentage of executions run again to check they match.
+    pub verification_percent: f64,
+}
+
+impl ExecutorConfig {
+    pub fn pool(&self) -> ExecutionPool {
+        ExecutionPool::new(self.concurrency, self.queue_depth)
+    }
+
+    pub fn verifier(&self) -> ExecutionVerifier {
+        ExecutionVerifier::new(self.verification_percent)
+    }
+
+    pub fn limits_config(&self) -> ExecutionLimitsConfig {
+        ExecutionLimitsConfig::new(self.limits).with_overrides(&self.limit_overrides)
+    }
+
+    pub fn execution_logs(&self) -> ExecutionLogs {
+        ExecutionLogs::new(self.log_max_lines, self.log_subscriber_buffer)
+    }
+
+    /// The node's IPFS store at `store`, then the gateway if there is one.
+    pub fn program_fetchers(&self, store: Option<String>) -> ProgramFetchers {
+        let fetchers = ProgramFetchers::new(self.program_fetch_timeout)
+            .with(Arc::new(LocalStoreFetcher::new(store)));
+        match &self.program_gateway_url {
+            Some(url) => fetchers.with(Arc::new(GatewayFetcher::new(url.clone()))),
+            None => fetchers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeeConfig {
+    pub base: u64,
+    pub per_input_byte: u64,
+    pub call: u64,
+    pub deploy: u64,
+}
+
+impl FeeConfig {
+    pub fn schedule(&self) -> FeeSchedule {
+        FeeSchedule::new(self.base, self.per_input_byte, self.call, self.deploy)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionConfig {
+    /// The JSON of the rules transactions are held to, if there are any.
+    pub policy_path: Option<PathBuf>,
+    /// The budget auth programs run within.
+    pub auth: AuthLimits,
+    /// Most nonces past the next one a transaction is admitted with.
+    pub max_nonce_ahead: u64,
+    /// The largest inputs of programs that do not declare a limit.
+    pub max_input_bytes: usize,
+    /// The most a program may declare it takes.
+    pub max_program_input_bytes: usize,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            policy_path: None,
+            auth: AuthLimits::default(),
+            max_nonce_ahead: DEFAULT_MAX_NONCE_AHEAD,
+            max_input_bytes: DEFAULT_MAX_INPUT_BYTES,
+            max_program_input_bytes: DEFAULT_MAX_PROGRAM_INPUT_BYTES,
+        }
+    }
+}
+
+impl AdmissionConfig {
+    pub fn input_limits(&self) -> InputLimits {
+        InputLimits::new(self.max_input_bytes, self.max_program_input_bytes)
+    }
+
+    /// The policy at `policy_path`, or one denying nothing.
+    pub fn policy_gate(&self) -> Result<PolicyGate, AdmissionPolicyError> {
+        PolicyGate::open(self.policy_path.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevnetConfig {
+    /// Whether the node runs a devnet, and so hands out funds.
+    pub enabled: bool,
+    pub faucet_max_amount: U256,
+    pub faucet_cooldown: Duration,
+    /// Whether every transaction is sealed into a batch of its own, and
+    /// settled, before it is answered, rather than on the batcher's timer.
+    pub instant_seal: bool,
+}
+
+impl DevnetConfig {
+    pub fn faucet(&self) -> FaucetConfig {
+        FaucetConfig {
+            enabled: self.enabled,
+            max_amount: self.faucet_max_amount,
+            cooldown: self.faucet_cooldown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GenesisConfig {
+    /// The genesis file applied the first time the node starts, if the
+    /// chain has one.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeysConfig {
+    /// The keystore the operator keys are loaded from, if they are not
+    /// given in the environment.
+    pub keystore_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionIndexConfig {
+    /// Whether the transactions finalized are indexed by the accounts and
+    /// programs they involve, which keeps every one on disk.
+    pub enabled: bool,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptsConfig {
+    /// How many transactions are remembered as recently applied, answering
+    /// for their outcomes and keeping them from being applied again.
+    pub recent_window: usize,
+    /// Whether outcomes are also written to, and looked up from, the
+    /// persistent receipt index once they fall out of the recent window.
+    pub persist: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageConfig {
+    /// Bytes of metadata and data one token may hold.
+    pub max_token_bytes: usize,
+    /// Bytes of metadata and data one account may hold across its tokens.
+    pub max_account_bytes: usize,
+    /// Whether ETH and VERSE tokens, and their program accounts, are left
+    /// out of the budgets.
+    pub exempt_system_programs: bool,
+    /// What accounts, receipts, the commit log and batches are written in.
+    /// Whatever was written in either is read back.
+    pub encoding: Encoding,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            max_token_bytes: DEFAULT_MAX_TOKEN_BYTES,
+            max_account_bytes: DEFAULT_MAX_ACCOUNT_BYTES,
+            exempt_system_programs: true,
+            encoding: Encoding::default(),
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn quota(&self) -> StorageQuota {
+        StorageQuota {
+            max_token_bytes: self.max_token_bytes,
+            max_account_bytes: self.max_account_bytes,
+            exempt: match self.exempt_system_programs {
+                true => BTreeSet::from([ETH_ADDR, VERSE_ADDR]),
+                false => BTreeSet::new(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramStatsConfig {
+    /// Where the counts are flushed to.
+    pub path: PathBuf,
+    /// Programs exported to Prometheus.
+    pub top: usize,
+    pub flush_interval: Duration,
+}
+
+impl ProgramStatsConfig {
+    pub fn open(&self) -> Result<ProgramStats, ProgramStatsError> {
+        Ok(ProgramStats::open(&self.path)?
+            .with_top(self.top)
+            .with_flush_interval(self.flush_interval))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventsConfig {
+    /// Where the events programs emit are logged.
+    pub log_path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettlementConfig {
+    /// The contract batch headers are submitted to, if they are submitted at
+    /// all.
+    pub contract_address: Option<EthereumAddress>,
+    pub gas_limit: u64,
+    /// How long a submission may go unmined before it is replaced.
+    pub replace_after: Duration,
+    pub max_attempts: u32,
+}
+
+impl Default for SettlementConfig {
+    fn default() -> Self {
+        Self {
+            contract_address: None,
+            gas_limit: DEFAULT_SETTLEMENT_GAS_LIMIT,
+            replace_after: DEFAULT_SETTLEMENT_REPLACE_AFTER,
+            max_attempts: DEFAULT_SETTLEMENT_ATTEMPTS,
+        }
+    }
+}
+
+impl SettlementConfig {
+    pub fn settler(&self) -> Option<SettlerConfig> {
+        self.contract_address.map(|contract| SettlerConfig {
+            gas_limit: self.gas_limit,
+            replace_after: self.replace_after,
+            max_attempts: self.max_attempts,
+            ..SettlerConfig::new(contract)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLettersConfig {
+    /// Where the calls given up on are parked.
+    pub path: PathBuf,
+    pub max_entries: usize,
+}
+
+impl DeadLettersConfig {
+    pub fn open(&self) -> Result<DeadLetters, DeadLetterError> {
+        Ok(DeadLetters::open(&self.path)?.with_max_entries(self.max_entries))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownConfig {
+    /// How long the node keeps serving once it reports not ready, for load
+    /// balancers to stop sending it requests.
+    pub drain_period: Duration,
+    /// How long what the node took on is given to drain.
+    pub deadline: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadShedConfig {
+    /// The signals the node is overloaded at, any one of them reached.
+    pub shed: LoadSignals,
+    /// The share, in percent, of each threshold a signal falls to before the
+    /// node recovers.
+    pub recover_percent: u64,
+    /// How long a write turned away is told to wait.
+    pub retry_after: Duration,
+}
+
+impl LoadShedConfig {
+    pub fn shedder(&self) -> LoadShedder {
+        LoadShedder::new(LoadSheddingConfig::new(
+            self.shed,
+            self.recover_percent,
+            self.retry_after,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MailboxesConfig {
+    /// The load a core actor is overloaded at, either one reached.
+    pub high_water: MailboxLoad,
+    /// The share, in percent, of each high-water mark a mailbox falls to
+    /// before its actor recovers.
+    pub recover_percent: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowConfig {
+    /// The websocket URL of the primary followed, if the node follows one
+    /// rather than running on its own.
+    pub primary_url: Option<String>,
+    /// One in how many of each batch's transactions has its inclusion
+    /// replayed.
+    pub witness_sample: u64,
+    pub reconnect_after: Duration,
+    pub replica_dir: PathBuf,
+}
+
+impl FollowConfig {
+    pub fn follower(&self) -> Option<FollowerConfig> {
+        self.primary_url.as_ref().map(|url| {
+            FollowerConfig::new(url)
+                .with_witness_sample(self.witness_sample)
+                .with_reconnect_after(self.reconnect_after)
+                .with_replica_dir(&self.replica_dir)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeConfig {
+    pub rpc: RpcConfig,
+    pub caches: CacheConfig,
+    pub pending: PendingConfig,
+    pub scheduling: SchedulingConfig,
+    pub batcher: BatcherConfig,
+    pub da: DaConfig,
+    pub eo_server: EoServerConfig,
+    pub executor: ExecutorConfig,
+    /// The bounds of the outputs programs return.
+    pub outputs: OutputLimits,
+    pub fees: FeeConfig,
+    pub admission: AdmissionConfig,
+    pub devnet: DevnetConfig,
+    pub genesis: GenesisConfig,
+    pub keys: KeysConfig,
+    pub transaction_index: TransactionIndexConfig,
+    pub receipts: ReceiptsConfig,
+    pub storage: StorageConfig,
+    pub retention: RetentionPolicy,
+    pub program_stats: ProgramStatsConfig,
+    pub events: EventsConfig,
+    pub settlement: SettlementConfig,
+    pub dead_letters: DeadLettersConfig,
+    pub supervision: SupervisionPolicy,
+    pub retry: RetryPolicy,
+    pub shutdown: ShutdownConfig,
+    pub load_shedding: LoadShedConfig,
+    pub mailboxes: MailboxesConfig,
+    pub follow: FollowConfig,
+}
+
+impl Default for NodeConfig {
+    /// The defaults a node is deployed with. The providers and the contract
+    /// address have none, and must be set.
+    fn default() -> Self {
+        Self {
+            rpc: RpcConfig {
+                host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                port: 9292,
+                admin_addr: None,
+                rest: false,
+                requests: RequestLimits::default(),
+                eth_chain_id: DEFAULT_ETH_CHAIN_ID,
+                simulate_unsigned_payloads: false,
+                rate_limit: RateLimit {
+                    per_second: DEFAULT_RATE_LIMIT_PER_SECOND,
+                    burst: DEFAULT_RATE_LIMIT_BURST,
+                },
+                expensive_rate_limit: RateLimit {
+                    per_second: DEFAULT_EXPENSIVE_RATE_LIMIT_PER_SECOND,
+                    burst: DEFAULT_EXPENSIVE_RATE_LIMIT_BURST,
+                },
+                rate_limit_allowlist: Vec::new(),
+                subscription_buffer: DEFAULT_SUBSCRIPTION_BUFFER,
+                max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            },
+            caches: CacheConfig {
+                artifact_cache_max_mb: 2048,
+                mempool: MempoolLimits::default(),
+                audit_verse_supply: false,
+            },
+            pending: PendingConfig {
+                log_path: Some(PathBuf::from("./pending_transactions.log")),
+                ..PendingConfig::default()
+            },
+            scheduling: SchedulingConfig::default(),
+            batcher: BatcherConfig {
+                interval: Duration::from_secs(180),
+                commit_log_path: PathBuf::from("./commit.log"),
+                batch_index_path: PathBuf::from("./batches.log"),
+                unsealed_batches_path: PathBuf::from(DEFAULT_UNSEALED_BATCHES_PATH),
+            },
+            da: DaConfig {
+                backend: DaBackend::EigenDa,
+                server_address: "disperser-holesky.eigenda.xyz:443".to_string(),
+                proto_path: PathBuf::from("./eigenda/api/proto/disperser/disperser.proto"),
+                local_path: PathBuf::from("./local_da"),
+                failure_threshold: DEFAULT_DA_FAILURE_THRESHOLD,
+            },
+            eo_server: EoServerConfig {
+                eth_rpc_urls: Vec::new(),
+                contract_address: String::new(),
+                blocks_processed_path: PathBuf::from("./blocks_processed.dat"),
+                event_filters: EventFilterConfig::default(),
+                log_range: DEFAULT_LOG_RANGE,
+                filter_backfill_blocks: DEFAULT_FILTER_BACKFILL_BLOCKS,
+                provider_timeout: DEFAULT_PROVIDER_TIMEOUT,
+                provider_retry_after: DEFAULT_PROVIDER_RETRY_AFTER,
+                ingestion: Ingestion::Polling,
+                ws_url: None,
+                confirmations: DEFAULT_CONFIRMATIONS,
+                gap_timeout: DEFAULT_GAP_TIMEOUT,
+            },
+            executor: ExecutorConfig {
+                limits: ExecutionLimits::default(),
+                limit_overrides: HashMap::new(),
+                concurrency: num_cpus::get(),
+                queue_depth: DEFAULT_EXECUTION_QUEUE_DEPTH,
+                log_max_lines: DEFAULT_LOG_MAX_LINES,
+                log_subscriber_buffer: DEFAULT_LOG_SUBSCRIBER_BUFFER,
+                program_fetch_timeout: DEFAULT_PROGRAM_FETCH_TIMEOUT,
+                program_gateway_url: None,
+                health: RestartPolicy::default(),
+                verification_percent: DEFAULT_VERIFICATION_PERCENT,
+            },
+            outputs: OutputLimits::default(),
+            fees: FeeConfig::default(),
+            admission: AdmissionConfig::default(),
+            devnet: DevnetConfig {
+                enabled: false,
+                faucet_max_amount: U256::from(DEFAULT_FAUCET_MAX_AMOUNT),
+                faucet_cooldown: Duration::from_secs(DEFAULT_FAUCET_COOLDOWN_SECS),
+                instant_seal: false,
+            },
+            genesis: GenesisConfig::default(),
+            keys: KeysConfig::default(),
+            transaction_index: TransactionIndexConfig {
+                enabled: false,
+                path: PathBuf::from("./transaction_index.log"),
+            },
+            receipts: ReceiptsConfig {
+                recent_window: DEFAULT_RECENT_TRANSACTIONS_WINDOW,
+                persist: true,
+            },
+            storage: StorageConfig::default(),
+            retention: RetentionPolicy::default(),
+            program_stats: ProgramStatsConfig {
+                path: PathBuf::from("./program_stats.log"),
+                top: DEFAULT_TOP_PROGRAMS,
+                flush_interval: Duration::from_secs(DEFAULT_PROGRAM_STATS_FLUSH_SECS),
+            },
+            events: EventsConfig {
+                log_path: PathBuf::from("./events.log"),
+            },
+            settlement: SettlementConfig::default(),
+            dead_letters: DeadLettersConfig {
+                path: PathBuf::from("./dead_letters.log"),
+                max_entries: DEFAULT_MAX_DEAD_LETTERS,
+            },
+            supervision: SupervisionPolicy::default(),
+            retry: RetryPolicy::default(),
+            shutdown: ShutdownConfig {
+                drain_period: Duration::from_secs(DEFAULT_SHUTDOWN_DRAIN_SECS),
+                deadline: Duration::from_secs(DEFAULT_SHUTDOWN_DEADLINE_SECS),
+            },
+            load_shedding: LoadShedConfig {
+                shed: LoadSignals {
+                    pending_transactions: DEFAULT_SHED_PENDING_TRANSACTIONS,
+                    execution_queue: DEFAULT_SHED_EXECUTION_QUEUE,
+                    event_loop_lag: Duration::from_millis(DEFAULT_SHED_EVENT_LOOP_LAG_MS),
+                },
+                recover_percent: DEFAULT_SHED_RECOVER_PERCENT,
+                retry_after: Duration::from_millis(DEFAULT_SHED_RETRY_AFTER_MS),
+            },
+            mailboxes: MailboxesConfig {
+                high_water: MailboxLoad {
+                    depth: DEFAULT_MAILBOX_HIGH_WATER_DEPTH,
+                    latency: Duration::from_millis(DEFAULT_MAILBOX_HIGH_WATER_LATENCY_MS),
+                },
+                recover_percent: DEFAULT_MAILBOX_RECOVER_PERCENT,
+            },
+            follow: FollowConfig {
+                primary_url: None,
+                witness_sample: DEFAULT_FOLLOWER_WITNESS_SAMPLE,
+                reconnect_after: Duration::from_millis(DEFAULT_FOLLOWER_RECONNECT_MS),
+                replica_dir: PathBuf::from(DEFAULT_FOLLOWER_REPLICA_DIR),
+            },
+        }
+    }
+}
+
+impl NodeConfig {
+    /// A node against a fresh local chain, with the faucet on.
+    pub fn default_devnet() -> Self {
+        let mut config = Self::default();
+        config.eo_server.eth_rpc_urls = vec!["http://127.0.0.1:8545".to_string()];
+        config.eo_server.contract_address = DEVNET_CONTRACT_ADDRESS.to_string();
+        config.devnet.enabled = true;
+        config
+    }
+
+    /// A devnet config to change for a test.
+    pub fn builder() -> NodeConfigBuilder {
+        NodeConfigBuilder {
+            config: Self::default_devnet(),
+        }
+    }
+
+    /// Layers the file at `NODE_CONFIG_PATH`, if it is set, and the
+    /// environment over the defaults.
+    pub fn load() -> Result<Self, ConfigErrors> {
+        let file = match std::env::var("NODE_CONFIG_PATH") {
+            Ok(path) => {
+                let path = PathBuf::from(path);
+                let contents = std::fs::read_to_string(&path).map_err(|e| {
+                    ConfigErrors(vec![ConfigError::new(
+                        "NODE_CONFIG_PATH",
+                        format!("failed to read {}: {e}", path.display()),
+                    )])
+                })?;
+                Some((path, contents))
+            }
+            Err(_) => None,
+        };
+        Self::layered(
+            Self::default(),
+            file.as_ref()
+                .map(|(path, contents)| (path.as_path(), contents.as_str())),
+            |var| std::env::var(var).ok(),
+        )
+    }
+
+    /// Layers the TOML `file`, read from the path given with it, and then
+    /// what `env` has for each variable over `base`, and checks the result.
+    pub fn layered(
+        base: Self,
+        file: Option<(&Path, &str)>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigErrors> {
+        let mut config = base;
+        let mut errors = Vec::new();
+        if let Some((path, contents)) = file {
+            match contents.parse::<toml::Table>() {
+                Ok(table) => {
+                    errors.extend(unknown_settings(path, &table));
+                    config.apply(&mut Loader {
+                        layer: Layer::File {
+                            path,
+                            table: &table,
+                        },
+                        errors: &mut errors,
+                    });
+                }
+                Err(e) => errors.push(ConfigError {
+                    setting: "NODE_CONFIG_PATH".to_string(),
+                    origin: Some(path.display().to_string()),
+                    reason: format!("not valid TOML: {e}"),
+                }),
+            }
+        }
+        config.apply(&mut Loader {
+            layer: Layer::Env(&env),
+            errors: &mut errors,
+        });
+        if let Err(ConfigErrors(invalid)) = config.validate() {
+            errors.extend(invalid);
+        }
+        match errors.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigErrors(errors)),
+        }
+    }
+
+    fn apply(&mut self, loader: &mut Loader) {
+        loader.set("rpc.host", &mut self.rpc.host);
+        loader.set("rpc.port", &mut self.rpc.port);
+        loader.set_with("rpc.admin_addr", &mut self.rpc.admin_addr, |raw| {
+            raw.parse::<SocketAddr>()
+                .map(Some)
+                .map_err(|e| e.to_string())
+        });
+        loader.set("rpc.rest", &mut self.rpc.rest);
+        let rpc = &mut self.rpc;
+        loader.set("rpc.max_batch_size", &mut rpc.requests.max_batch_size);
+        loader.set("rpc.max_request_bytes", &mut rpc.requests.max_request_bytes);
+        loader.set("rpc.eth_chain_id", &mut rpc.eth_chain_id);
+        loader.set(
+            "rpc.simulate_unsigned_payloads",
+            &mut rpc.simulate_unsigned_payloads,
+        );
+        loader.set("rpc.rate_limit_per_second", &mut rpc.rate_limit.per_second);
+        loader.set("rpc.rate_limit_burst", &mut rpc.rate_limit.burst);
+        loader.set(
+            "rpc.expensive_rate_limit_per_second",
+            &mut rpc.expensive_rate_limit.per_second,
+        );
+        loader.set(
+            "rpc.expensive_rate_limit_burst",
+            &mut rpc.expensive_rate_limit.burst,
+        );
+        loader.set_with(
+            "rpc.rate_limit_allowlist",
+            &mut rpc.rate_limit_allowlist,
+            |raw| {
+                raw.split(',')
+                    .map(str::trim)
+                    .filter(|ip| !ip.is_empty())
+                    .map(|ip| ip.parse::<IpAddr>().map_err(|e| format!("{ip}: {e}")))
+                    .collect()
+            },
+        );
+        loader.set("rpc.subscription_buffer", &mut rpc.subscription_buffer);
+        loader.set("rpc.max_response_bytes", &mut rpc.max_response_bytes);
+        loader.set(
+            "caches.artifact_cache_max_mb",
+            &mut self.caches.artifact_cache_max_mb,
+        );
+        loader.set(
+            "caches.audit_verse_supply",
+            &mut self.caches.audit_verse_supply,
+        );
+        let mempool = &mut self.caches.mempool;
+        loader.set(
+            "caches.pending_max_transactions",
+            &mut mempool.max_transactions,
+        );
+        loader.set("caches.pending_max_bytes", &mut mempool.max_bytes);
+        loader.set(
+            "caches.pending_sender_quota_transactions",
+            &mut mempool.sender_quota_transactions,
+        );
+        loader.set(
+            "caches.pending_sender_quota_bytes",
+            &mut mempool.sender_quota_bytes,
+        );
+        let pending = &mut self.pending;
+        loader.set_with("pending.log_path", &mut pending.log_path, |raw| {
+            Ok(Some(PathBuf::from(raw)))
+        });
+        loader.set_with("pending.ttl_secs", &mut pending.ttl, secs);
+        loader.set(
+            "pending.max_future_transactions",
+            &mut pending.max_future_transactions,
+        );
+        loader.set("pending.max_in_flight", &mut pending.max_in_flight);
+        loader.set_with(
+            "pending.call_deadline_ms",
+            &mut pending.call_deadline,
+            |raw| {
+                raw.parse::<u64>()
+                    .map(|ms| (ms > 0).then(|| Duration::from_millis(ms)))
+                    .map_err(|e| e.to_string())
+            },
+        );
+        let scheduling = &mut self.scheduling;
+        loader.set("scheduling.policy", &mut scheduling.order);
+        loader.set("scheduling.age_boost", &mut scheduling.age_boost);
+        loader.set(
+            "scheduling.sender_quantum",
+            &mut scheduling.fairness.quantum,
+        );
+        loader.set_with(
+            "scheduling.sender_weights",
+            &mut scheduling.fairness.weights,
+            Fairness::parse_weights,
+        );
+        loader.set_with("batcher.interval_secs", &mut self.batcher.interval, secs);
+        loader.set("batcher.commit_log_path", &mut self.batcher.commit_log_path);
+        loader.set(
+            "batcher.batch_index_path",
+            &mut self.batcher.batch_index_path,
+        );
+        loader.set(
+            "batcher.unsealed_batches_path",
+            &mut self.batcher.unsealed_batches_path,
+        );
+        loader.set("da.backend", &mut self.da.backend);
+        loader.set("da.server_address", &mut self.da.server_address);
+        loader.set("da.proto_path", &mut self.da.proto_path);
+        loader.set("da.local_path", &mut self.da.local_path);
+        loader.set("da.failure_threshold", &mut self.da.failure_threshold);
+        loader.set_with(
+            "eo_server.eth_rpc_urls",
+            &mut self.eo_server.eth_rpc_urls,
+            |raw| Ok(provider_urls(raw)),
+        );
+        loader.set(
+            "eo_server.contract_address",
+            &mut self.eo_server.contract_address,
+        );
+        loader.set(
+            "eo_server.blocks_processed_path",
+            &mut self.eo_server.blocks_processed_path,
+        );
+        loader.set_with(
+            "eo_server.event_filters",
+            &mut self.eo_server.event_filters,
+            |raw| serde_json::from_str(raw).map_err(|e| e.to_string()),
+        );
+        loader.set("eo_server.log_range", &mut self.eo_server.log_range);
+        loader.set(
+            "eo_server.filter_backfill_blocks",
+            &mut self.eo_server.filter_backfill_blocks,
+        );
+        let eo_server = &mut self.eo_server;
+        loader.set_with(
+            "eo_server.provider_timeout_secs",
+            &mut eo_server.provider_timeout,
+            secs,
+        );
+        loader.set_with(
+            "eo_server.provider_retry_after_secs",
+            &mut eo_server.provider_retry_after,
+            secs,
+        );
+        loader.set("eo_server.ingestion_mode", &mut eo_server.ingestion);
+        loader.set_with("eo_server.ws_url", &mut eo_server.ws_url, |raw| {
+            Ok((!raw.is_empty()).then(|| raw.to_string()))
+        });
+        loader.set("eo_server.confirmations", &mut eo_server.confirmations);
+        loader.set_with(
+            "eo_server.gap_timeout_secs",
+            &mut eo_server.gap_timeout,
+            secs,
+        );
+        let limits = &mut self.executor.limits;
+        loader.set_with("executor.timeout_secs", &mut limits.timeout, secs);
+        loader.set_with(
+            "executor.memory_limit_mb",
+            &mut limits.memory_bytes,
+            |raw| scaled(raw, 1024 * 1024),
+        );
+        loader.set_with(
+            "executor.output_limit_kb",
+            &mut limits.output_bytes,
+            |raw| scaled(raw, 1024),
+        );
+        loader.set("executor.concurrency", &mut self.executor.concurrency);
+        loader.set("executor.queue_depth", &mut self.executor.queue_depth);
+        let executor = &mut self.executor;
+        loader.set_with(
+            "executor.limit_overrides",
+            &mut executor.limit_overrides,
+            |raw| serde_json::from_str(raw).map_err(|e| e.to_string()),
+        );
+        loader.set("executor.log_max_lines", &mut executor.log_max_lines);
+        loader.set(
+            "executor.log_subscriber_buffer",
+            &mut executor.log_subscriber_buffer,
+        );
+        loader.set_with(
+            "executor.program_fetch_timeout_secs",
+            &mut executor.program_fetch_timeout,
+            secs,
+        );
+        loader.set_with(
+            "executor.program_gateway_url",
+            &mut executor.program_gateway_url,
+            |raw| Ok((!raw.is_empty()).then(|| raw.to_string())),
+        );
+        loader.set_with(
+            "executor.health_check_interval_secs",
+            &mut executor.health.interval,
+            secs,
+        );
+        loader.set_with(
+            "executor.health_check_timeout_secs",
+            &mut executor.health.probe_timeout,
+            secs,
+        );
+        loader.set(
+            "executor.restart_after_failures",
+            &mut executor.health.failure_threshold,
+        );
+        loader.set(
+            "executor.verification_percent",
+            &mut executor.verification_percent,
+        );
+        let outputs = &mut self.outputs;
+        loader.set("outputs.max_bytes", &mut outputs.max_bytes);
+        loader.set("outputs.max_instructions", &mut outputs.max_instructions);
+        loader.set("outputs.max_instruction_items", &mut outputs.max_items);
+        loader.set("outputs.max_log_length", &mut outputs.max_log_length);
+        loader.set("outputs.max_events", &mut outputs.max_events);
+        loader.set("outputs.max_event_bytes", &mut outputs.max_event_bytes);
+        loader.set("fees.base", &mut self.fees.base);
+        loader.set("fees.per_input_byte", &mut self.fees.per_input_byte);
+        loader.set("fees.call", &mut self.fees.call);
+        loader.set("fees.deploy", &mut self.fees.deploy);
+        let admission = &mut self.admission;
+        loader.set_with("admission.policy_path", &mut admission.policy_path, |raw| {
+            Ok(Some(PathBuf::from(raw)))
+        });
+        loader.set_with(
+            "admission.auth_timeout_ms",
+            &mut admission.auth.timeout,
+            millis,
+        );
+        loader.set_with(
+            "admission.auth_memory_limit_mb",
+            &mut admission.auth.memory_bytes,
+            |raw| scaled(raw, 1024 * 1024),
+        );
+        loader.set("admission.max_nonce_ahead", &mut admission.max_nonce_ahead);
+        loader.set("admission.max_input_bytes", &mut admission.max_input_bytes);
+        loader.set(
+            "admission.max_program_input_bytes",
+            &mut admission.max_program_input_bytes,
+        );
+        loader.set("devnet.enabled", &mut self.devnet.enabled);
+        loader.set_with(
+            "devnet.faucet_max_amount",
+            &mut self.devnet.faucet_max_amount,
+            |raw| U256::from_dec_str(raw).map_err(|e| format!("{e:?}")),
+        );
+        loader.set_with(
+            "devnet.faucet_cooldown_secs",
+            &mut self.devnet.faucet_cooldown,
+            secs,
+        );
+        loader.set("devnet.instant_seal", &mut self.devnet.instant_seal);
+        loader.set_with("genesis.path", &mut self.genesis.path, |raw| {
+            Ok(Some(PathBuf::from(raw)))
+        });
+        loader.set_with("keys.keystore_path", &mut self.keys.keystore_path, |raw| {
+            Ok(Some(PathBuf::from(raw)))
+        });
+        loader.set(
+            "transaction_index.enabled",
+            &mut self.transaction_index.enabled,
+        );
+        loader.set("transaction_index.path", &mut self.transaction_index.path);
+        loader.set("receipts.recent_window", &mut self.receipts.recent_window);
+        loader.set("receipts.persist", &mut self.receipts.persist);
+        loader.set("storage.max_token_bytes", &mut self.storage.max_token_bytes);
+        loader.set(
+            "storage.max_account_bytes",
+            &mut self.storage.max_account_bytes,
+        );
+        loader.set(
+            "storage.exempt_system_programs",
+            &mut self.storage.exempt_system_programs,
+        );
+        loader.set("storage.encoding", &mut self.storage.encoding);
+        let retention = &mut self.retention;
+        loader.set_with("retention.blob_days", &mut retention.blobs.days, some);
+        loader.set_with("retention.blob_batches", &mut retention.blobs.batches, some);
+        loader.set_with("retention.receipt_days", &mut retention.receipts.days, some);
+        loader.set_with(
+            "retention.receipt_batches",
+            &mut retention.receipts.batches,
+            some,
+        );
+        loader.set_with(
+            "retention.prune_interval_secs",
+            &mut retention.interval,
+            secs,
+        );
+        loader.set("retention.marks_path", &mut retention.marks_path);
+        loader.set("program_stats.path", &mut self.program_stats.path);
+        loader.set("program_stats.top", &mut self.program_stats.top);
+        loader.set_with(
+            "program_stats.flush_secs",
+            &mut self.program_stats.flush_interval,
+            secs,
+        );
+        loader.set("events.log_path", &mut self.events.log_path);
+        let settlement = &mut self.settlement;
+        loader.set_with(
+            "settlement.contract_address",
+            &mut settlement.contract_address,
+            |raw| {
+                raw.parse::<EthereumAddress>()
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            },
+        );
+        loader.set("settlement.gas_limit", &mut settlement.gas_limit);
+        loader.set_with(
+            "settlement.replace_after_secs",
+            &mut settlement.replace_after,
+            secs,
+        );
+        loader.set("settlement.max_attempts", &mut settlement.max_attempts);
+        loader.set("dead_letters.path", &mut self.dead_letters.path);
+        loader.set(
+            "dead_letters.max_entries",
+            &mut self.dead_letters.max_entries,
+        );
+        loader.set(
+            "supervision.max_restarts",
+            &mut self.supervision.max_restarts,
+        );
+        loader.set_with(
+            "supervision.restart_window_secs",
+            &mut self.supervision.window,
+            secs,
+        );
+        loader.set("retry.max_attempts", &mut self.retry.max_attempts);
+        loader.set_with("retry.backoff_ms", &mut self.retry.backoff, millis);
+        loader.set_with("shutdown.drain_secs", &mut self.shutdown.drain_period, secs);
+        loader.set_with("shutdown.deadline_secs", &mut self.shutdown.deadline, secs);
+        let load_shedding = &mut self.load_shedding;
+        loader.set(
+            "load_shedding.pending_transactions",
+            &mut load_shedding.shed.pending_transactions,
+        );
+        loader.set(
+            "load_shedding.execution_queue",
+            &mut load_shedding.shed.execution_queue,
+        );
+        loader.set_with(
+            "load_shedding.event_loop_lag_ms",
+            &mut load_shedding.shed.event_loop_lag,
+            millis,
+        );
+        loader.set(
+            "load_shedding.recover_percent",
+            &mut load_shedding.recover_percent,
+        );
+        loader.set_with(
+            "load_shedding.retry_after_ms",
+            &mut load_shedding.retry_after,
+            millis,
+        );
+        let mailboxes = &mut self.mailboxes;
+        loader.set(
+            "mailboxes.high_water_depth",
+            &mut mailboxes.high_water.depth,
+        );
+        loader.set_with(
+            "mailboxes.high_water_latency_ms",
+            &mut mailboxes.high_water.latency,
+            millis,
+        );
+        loader.set("mailboxes.recover_percent", &mut mailboxes.recover_percent);
+        let follow = &mut self.follow;
+        loader.set_with("follow.primary_url", &mut follow.primary_url, |raw| {
+            Ok((!raw.is_empty()).then(|| raw.to_string()))
+        });
+        loader.set("follow.witness_sample", &mut follow.witness_sample);
+        loader.set_with("follow.reconnect_ms", &mut follow.reconnect_after, millis);
+        loader.set("follow.replica_dir", &mut follow.replica_dir);
+    }
+
+    /// Checks the settings make sense together, reporting every one that
+    /// does not.
+    pub fn validate(&self) -> Result<(), ConfigErrors> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, setting: &str, reason: &str| {
+            if !ok {
+                errors.push(ConfigError::new(setting, reason));
+            }
+        };
+        check(self.rpc.port != 0, "rpc.port", "must not be 0");
+        check(
+            self.rpc
+                .admin_addr
+                .filter(|addr| !addr.ip().is_loopback())
+                .is_none(),
+            "rpc.admin_addr",
+            "must be a loopback address",
+        );
+        check(
+            self.rpc.requests.max_request_bytes > 0,
+            "rpc.max_request_bytes",
+            "must be at least 1",
+        );
+        check(
+            self.rpc.eth_chain_id != 0,
+            "rpc.eth_chain_id",
+            "must not be 0",
+        );
+        for (limit, per_second, burst) in [
+            (
+                &self.rpc.rate_limit,
+                "rpc.rate_limit_per_second",
+                "rpc.rate_limit_burst",
+            ),
+            (
+                &self.rpc.expensive_rate_limit,
+                "rpc.expensive_rate_limit_per_second",
+                "rpc.expensive_rate_limit_burst",
+            ),
+        ] {
+            check(
+                limit.per_second.is_finite() && limit.per_second > 0.0,
+                per_second,
+                "must be more than 0",
+            );
+            check(limit.burst > 0, burst, "must be at least 1");
+        }
+        check(
+            self.rpc.subscription_buffer > 0,
+            "rpc.subscription_buffer",
+            "must be at least 1",
+        );
+        check(
+            self.rpc.max_response_bytes > 0,
+            "rpc.max_response_bytes",
+            "must be at least 1",
+        );
+        check(
+            self.pending
+                .log_path
+                .iter()
+                .all(|path| !path.as_os_str().is_empty()),
+            "pending.log_path",
+            "must not be empty",
+        );
+        check(
+            !self.pending.ttl.is_zero(),
+            "pending.ttl_secs",
+            "must be at least 1",
+        );
+        check(
+            self.pending.max_in_flight > 0,
+            "pending.max_in_flight",
+            "must be at least 1",
+        );
+        check(
+            !self.batcher.interval.is_zero(),
+            "batcher.interval_secs",
+            "must be at least 1",
+        );
+        check(
+            !self.batcher.commit_log_path.as_os_str().is_empty(),
+            "batcher.commit_log_path",
+            "must be set",
+        );
+        check(
+            !self.batcher.batch_index_path.as_os_str().is_empty(),
+            "batcher.batch_index_path",
+            "must be set",
+        );
+        check(
+            !self.batcher.unsealed_batches_path.as_os_str().is_empty(),
+            "batcher.unsealed_batches_path",
+            "must be set",
+        );
+        check(
+            self.da.backend != DaBackend::EigenDa
+                || self
+                    .da
+                    .server_address
+                    .rsplit_once(':')
+                    .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok()),
+            "da.server_address",
+            "must be host:port",
+        );
+        check(
+            self.da.failure_threshold > 0,
+            "da.failure_threshold",
+            "must be at least 1",
+        );
+        check(
+            !self.eo_server.eth_rpc_urls.is_empty(),
+            "eo_server.eth_rpc_urls",
+            "must list at least one provider",
+        );
+        check(
+            self.eo_server.eth_rpc_urls.iter().all(|url| {
+                ["http://", "https://", "ws://", "wss://"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+            }),
+            "eo_server.eth_rpc_urls",
+            "every provider must be an http, https, ws or wss url",
+        );
+        check(
+            self.eo_server
+                .contract_address
+                .strip_prefix("0x")
+                .is_some_and(|hex| hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit())),
+            "eo_server.contract_address",
+            "must be a 0x prefixed 20 byte address",
+        );
+        check(
+            !self.eo_server.blocks_processed_path.as_os_str().is_empty(),
+            "eo_server.blocks_processed_path",
+            "must be set",
+        );
+        check(
+            self.eo_server.log_range > 0,
+            "eo_server.log_range",
+            "must be at least 1",
+        );
+        check(
+            !self.eo_server.provider_timeout.is_zero(),
+            "eo_server.provider_timeout_secs",
+            "must be at least 1",
+        );
+        check(
+            self.eo_server.ingestion != Ingestion::Subscription
+                || self
+                    .eo_server
+                    .ws_url
+                    .as_ref()
+                    .is_some_and(|url| url.starts_with("ws://") || url.starts_with("wss://")),
+            "eo_server.ws_url",
+            "must be a ws or wss url in subscription mode",
+        );
+        check(
+            !self.eo_server.gap_timeout.is_zero(),
+            "eo_server.gap_timeout_secs",
+            "must be at least 1",
+        );
+        check(
+            !self.executor.limits.timeout.is_zero(),
+            "executor.timeout_secs",
+            "must be at least 1",
+        );
+        check(
+            self.executor.limits.memory_bytes > 0,
+            "executor.memory_limit_mb",
+            "must be at least 1",
+        );
+        check(
+            self.executor.concurrency > 0,
+            "executor.concurrency",
+            "must be at least 1",
+        );
+        check(
+            self.executor.log_subscriber_buffer > 0,
+            "executor.log_subscriber_buffer",
+            "must be at least 1",
+        );
+        check(
+            !self.executor.program_fetch_timeout.is_zero(),
+            "executor.program_fetch_timeout_secs",
+            "must be at least 1",
+        );
+        check(
+            self.executor
+                .program_gateway_url
+                .iter()
+                .all(|url| url.starts_with("http://") || url.starts_with("https://")),
+            "executor.program_gateway_url",
+            "must be an http or https url",
+        );
+        check(
+            !self.executor.health.interval.is_zero(),
+            "executor.health_check_interval_secs",
+            "must be at least 1",
+        );
+        check(
+            !self.executor.health.probe_timeout.is_zero(),
+            "executor.health_check_timeout_secs",
+            "must be at least 1",
+        );
+        check(
+            self.executor.health.failure_threshold > 0,
+            "executor.restart_after_failures",
+            "must be at least 1",
+        );
+        check(
+            (0.0..=100.0).contains(&self.executor.verification_percent),
+            "executor.verification_percent",
+            "must be from 0 to 100",
+        );
+        check(
+            self.outputs.max_bytes > 0,
+            "outputs.max_bytes",
+            "must be at least 1",
+        );
+        check(
+            !self.admission.auth.timeout.is_zero(),
+            "admission.auth_timeout_ms",
+            "must be at least 1",
+        );
+        check(
+            self.admission.auth.memory_bytes > 0,
+            "admission.auth_memory_limit_mb",
+            "must be at least 1",
+        );
+        check(
+            self.admission.max_input_bytes > 0,
+            "admission.max_input_bytes",
+            "must be at least 1",
+        );
+        check(
+            self.admission.max_program_input_bytes >= self.admission.max_input_bytes,
+            "admission.max_program_input_bytes",
+            "must not be less than admission.max_input_bytes",
+        );
+        check(
+            !self.devnet.enabled || !self.devnet.faucet_max_amount.is_zero(),
+            "devnet.faucet_max_amount",
+            "must be more than 0 on a devnet",
+        );
+        check(
+            !self.devnet.instant_seal
+                || (self.devnet.enabled && self.da.backend == DaBackend::LocalFile),
+            "devnet.instant_seal",
+            "may only be set on a devnet with the local DA backend",
+        );
+        check(
+            self.genesis.path.iter().all(|path| path.is_file()),
+            "genesis.path",
+            "must be a file",
+        );
+        check(
+            self.keys
+                .keystore_path
+                .iter()
+                .all(|path| path.join(KEYSTORE_MANIFEST).is_file()),
+            "keys.keystore_path",
+            "must be a directory holding keys.toml",
+        );
+        check(
+            !self.transaction_index.enabled || !self.transaction_index.path.as_os_str().is_empty(),
+            "transaction_index.path",
+            "must be set when transactions are indexed",
+        );
+        check(
+            self.receipts.recent_window > 0,
+            "receipts.recent_window",
+            "must be at least 1",
+        );
+        check(
+            self.storage.max_token_bytes <= self.storage.max_account_bytes,
+            "storage.max_token_bytes",
+            "must not be more than storage.max_account_bytes",
+        );
+        check(
+            self.retention.receipts.covers(&self.retention.blobs),
+            "retention.receipt_days",
+            "receipts must be kept at least as long as blobs",
+        );
+        check(
+            !self.retention.interval.is_zero(),
+            "retention.prune_interval_secs",
+            "must be at least 1",
+        );
+        check(
+            !self.program_stats.path.as_os_str().is_empty(),
+            "program_stats.path",
+            "must be set",
+        );
+        check(
+            !self.program_stats.flush_interval.is_zero(),
+            "program_stats.flush_secs",
+            "must be at least 1",
+        );
+        check(
+            !self.events.log_path.as_os_str().is_empty(),
+            "events.log_path",
+            "must be set",
+        );
+        check(
+            self.settlement.gas_limit > 0,
+            "settlement.gas_limit",
+            "must be at least 1",
+        );
+        check(
+            !self.settlement.replace_after.is_zero(),
+            "settlement.replace_after_secs",
+            "must be at least 1",
+        );
+        check(
+            self.settlement.max_attempts > 0,
+            "settlement.max_attempts",
+            "must be at least 1",
+        );
+        check(
+            !self.dead_letters.path.as_os_str().is_empty(),
+            "dead_letters.path",
+            "must be set",
+        );
+        check(
+            self.dead_letters.max_entries > 0,
+            "dead_letters.max_entries",
+            "must be at least 1",
+        );
+        check(
+            !self.supervision.window.is_zero(),
+            "supervision.restart_window_secs",
+            "must be at least 1",
+        );
+        check(
+            !self.retry.backoff.is_zero(),
+            "retry.backoff_ms",
+            "must be at least 1",
+        );
+        check(
+            !self.shutdown.deadline.is_zero(),
+            "shutdown.deadline_secs",
+            "must be at least 1",
+        );
+        let shed = &self.load_shedding.shed;
+        for (ok, setting) in [
+            (
+                shed.pending_transactions > 0,
+                "load_shedding.pending_transactions",
+            ),
+            (shed.execution_queue > 0, "load_shedding.execution_queue"),
+            (
+                !shed.event_loop_lag.is_zero(),
+                "load_shedding.event_loop_lag_ms",
+            ),
+            (
+                !self.load_shedding.retry_after.is_zero(),
+                "load_shedding.retry_after_ms",
+            ),
+        ] {
+            check(ok, setting, "must be at least 1");
+        }
+        check(
+            self.load_shedding.recover_percent <= 100,
+            "load_shedding.recover_percent",
+            "must be at most 100",
+        );
+        check(
+            self.mailboxes.high_water.depth > 0,
+            "mailboxes.high_water_depth",
+            "must be at least 1",
+        );
+        check(
+            !self.mailboxes.high_water.latency.is_zero(),
+            "mailboxes.high_water_latency_ms",
+            "must be at least 1",
+        );
+        check(
+            self.mailboxes.recover_percent <= 100,
+            "mailboxes.recover_percent",
+            "must be at most 100",
+        );
+        check(
+            self.follow
+                .primary_url
+                .iter()
+                .all(|url| url.starts_with("ws://") || url.starts_with("wss://")),
+            "follow.primary_url",
+            "must be a ws or wss url",
+        );
+        check(
+            !self.follow.reconnect_after.is_zero(),
+            "follow.reconnect_ms",
+            "must be at least 1",
+        );
+        check(
+            !self.follow.replica_dir.as_os_str().is_empty(),
+            "follow.replica_dir",
+            "must be set",
+        );
+        if let Err(e) = self.event_filters() {
+            errors.push(ConfigError::new("eo_server.event_filters", e));
+        }
+        if let Some(path) = &self.admission.policy_path {
+            if let Err(e) = PolicyConfig::from_file(path) {
+                errors.push(ConfigError::new("admission.policy_path", e));
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigErrors(errors)),
+        }
+    }
+
+    /// What transactions are checked with as they are admitted and applied.
+    pub fn admission(&self) -> Admission {
+        Admission::default()
+            .with_input_limits(self.admission.input_limits())
+            .with_max_nonce_ahead(self.admission.max_nonce_ahead)
+            .with_fee_schedule(self.fees.schedule())
+            .with_auth_limits(self.admission.auth)
+    }
+
+    /// Watches the core actors' mailboxes, telling the transactions turned
+    /// away while one is overloaded to wait as long as those shed for load.
+    pub fn mailbox_monitor(&self) -> MailboxMonitor {
+        MailboxMonitor::new(MailboxConfig::new(
+            self.mailboxes.high_water,
+            self.mailboxes.recover_percent,
+            self.load_shedding.retry_after,
+        ))
+        .with_core_actors()
+    }
+
+    /// The contracts watched beyond the executable oracle, checked.
+    pub fn event_filters(&self) -> Result<EventFilterSet, eo_listener::EoServerError> {
+        self.eo_server.event_filters.validate()
+    }
+}
+
+/// Changes a devnet config for a test, checking it once built.
+#[derive(Debug, Clone)]
+pub struct NodeConfigBuilder {
+    config: NodeConfig,
+}
+
+impl NodeConfigBuilder {
+    pub fn rpc_port(mut self, port: u16) -> Self {
+        self.config.rpc.port = port;
+        self
+    }
+
+    pub fn admin_addr(mut self, admin_addr: SocketAddr) -> Self {
+        self.config.rpc.admin_addr = Some(admin_addr);
+        self
+    }
+
+    pub fn rest(mut self, enabled: bool) -> Self {
+        self.config.rpc.rest = enabled;
+        self
+    }
+
+    pub fn mempool_limits(mut self, limits: MempoolLimits) -> Self {
+        self.config.caches.mempool = limits;
+        self
+    }
+
+    pub fn pending(mut self, pending: PendingConfig) -> Self {
+        self.config.pending = pending;
+        self
+    }
+
+    pub fn scheduling(mut self, scheduling: SchedulingConfig) -> Self {
+        self.config.scheduling = scheduling;
+        self
+    }
+
+    pub fn batch_interval(mut self, interval: Duration) -> Self {
+        self.config.batcher.interval = interval;
+        self
+    }
+
+    /// Logs the transactions committed to the file at `path` until their
+    /// batch is finalized.
+    pub fn commit_log_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.batcher.commit_log_path = path.into();
+        self
+    }
+
+    pub fn eth_rpc_urls(mut self, urls: Vec<String>) -> Self {
+        self.config.eo_server.eth_rpc_urls = urls;
+        self
+    }
+
+    pub fn contract_address(mut self, address: impl Into<String>) -> Self {
+        self.config.eo_server.contract_address = address.into();
+        self
+    }
+
+    pub fn execution_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.config.executor.limits = limits;
+        self
+    }
+
+    pub fn fees(mut self, fees: FeeConfig) -> Self {
+        self.config.fees = fees;
+        self
+    }
+
+    /// Holds transactions to the policy in the JSON at `path`.
+    pub fn admission_policy(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.admission.policy_path = Some(path.into());
+        self
+    }
+
+    pub fn auth_limits(mut self, limits: AuthLimits) -> Self {
+        self.config.admission.auth = limits;
+        self
+    }
+
+    pub fn devnet(mut self, enabled: bool) -> Self {
+        self.config.devnet.enabled = enabled;
+        self
+    }
+
+    /// Seals and settles every transaction before answering it.
+    pub fn instant_seal(mut self, enabled: bool) -> Self {
+        self.config.devnet.instant_seal = enabled;
+        self
+    }
+
+    /// Writes blobs to files under `path` rather than dispersing them.
+    pub fn local_da(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.da.backend = DaBackend::LocalFile;
+        self.config.da.local_path = path.into();
+        self
+    }
+
+    /// Indexes the transactions finalized by the accounts and programs they
+    /// involve.
+    pub fn index_transactions(mut self, enabled: bool) -> Self {
+        self.config.transaction_index.enabled = enabled;
+        self
+    }
+
+    /// Keeps the transaction index in the file at `path`.
+    pub fn transaction_index_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.transaction_index.path = path.into();
+        self
+    }
+
+    pub fn storage(mut self, storage: StorageConfig) -> Self {
+        self.config.storage = storage;
+        self
+    }
+
+    /// Writes what the node stores and disperses encoded in `encoding`.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.config.storage.encoding = encoding;
+        self
+    }
+
+    /// Prunes blobs and receipts past `retention`.
+    pub fn retention(mut self, retention: RetentionPolicy) -> Self {
+        self.config.retention = retention;
+        self
+    }
+
+    /// Starts the chain from the genesis file at `path`.
+    pub fn genesis(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.genesis.path = Some(path.into());
+        self
+    }
+
+    /// Loads the operator keys from the keystore at `path`.
+    pub fn keystore(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.keys.keystore_path = Some(path.into());
+        self
+    }
+
+    /// Flushes the program stats to the file at `path`.
+    pub fn program_stats_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.program_stats.path = path.into();
+        self
+    }
+
+    /// Logs the events programs emit to the file at `path`.
+    pub fn event_log_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.events.log_path = path.into();
+        self
+    }
+
+    pub fn build(self) -> Result<NodeConfig, ConfigErrors> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Where settings are read from.
+enum Layer<'a> {
+    File {
+        path: &'a Path,
+        table: &'a toml::Table,
+    },
+    Env(&'a dyn Fn(&str) -> Option<String>),
+}
+
+impl Layer<'_> {
+    /// The value of the setting `key`, overridden by `var`, as it would be
+    /// written in the environment.
+    fn get(&self, key: &str, var: &str) -> Option<String> {
+        match self {
+            Layer::File { table, .. } => {
+                let (section, name) = key.split_once('.')?;
+                table.get(section)?.as_table()?.get(name).map(raw_value)
+            }
+            Layer::Env(env) => env(var),
+        }
+    }
+
+    fn origin(&self, var: &str) -> String {
+        match self {
+            Layer::File { path, .. } => path.display().to_string(),
+            Layer::Env(_) => var.to_string(),
+        }
+    }
+}
+
+/// A value from the file as it would be written in the environment: lists
+/// of strings comma separated, tables as JSON.
+fn raw_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(values) if values.iter().all(toml::Value::is_str) => values
+            .iter()
+            .filter_map(toml::Value::as_str)
+            .collect::<Vec<_>>()
+            .join(","),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            serde_json::to_string(value).unwrap_or_default()
+        }
+        other => other.to_string(),
+    }
+}
+
+/// The settings in `table` that are not known, most likely misspelt.
+fn unknown_settings(path: &Path, table: &toml::Table) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    for (section, value) in table {
+        let Some(settings) = value.as_table() else {
+            errors.push(ConfigError {
+                setting: section.clone(),
+                origin: Some(path.display().to_string()),
+                reason: "expected a table of settings".to_string(),
+            });
+            continue;
+        };
+        for name in settings.keys() {
+            let key = format!("{section}.{name}");
+            if !SETTINGS.iter().any(|(known, _)| *known == key) {
+                errors.push(ConfigError {
+                    setting: key,
+                    origin: Some(path.display().to_string()),
+                    reason: "unknown setting".to_string(),
+                });
+            }
+        }
+    }
+    errors
+}
+
+/// Applies a layer, keeping the errors of every setting it has invalid.
+struct Loader<'a> {
+    layer: Layer<'a>,
+    errors: &'a mut Vec<ConfigError>,
+}
+
+impl Loader<'_> {
+    fn set<T>(&mut self, key: &str, field: &mut T)
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.set_with(key, field, |raw| {
+            raw.parse::<T>().map_err(|e| e.to_string())
+        })
+    }
+
+    fn set_with<T>(
+        &mut self,
+        key: &str,
+        field: &mut T,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) {
+        let var = SETTINGS
+            .iter()
+            .find_map(|(known, var)| (*known == key).then_some(*var))
+            .unwrap_or_default();
+        if let Some(raw) = self.layer.get(key, var) {
+            match parse(raw.trim()) {
+                Ok(value) => *field = value,
+                Err(e) => self.errors.push(ConfigError {
+                    setting: key.to_string(),
+                    origin: Some(self.layer.origin(var)),
+                    reason: format!("{raw:?} is not valid: {e}"),
+                }),
+            }
+        }
+    }
+}
+
+fn secs(raw: &str) -> Result<Duration, String> {
+    raw.parse::<u64>()
+        .map(Duration::from_secs)
+        .map_err(|e| e.to_string())
+}
+
+fn millis(raw: &str) -> Result<Duration, String> {
+    raw.parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|e| e.to_string())
+}
+
+/// A count that is unset unless given.
+fn some(raw: &str) -> Result<Option<u64>, String> {
+    raw.parse::<u64>().map(Some).map_err(|e| e.to_string())
+}
+
+fn scaled(raw: &str, unit: u64) -> Result<u64, String> {
+    raw.parse::<u64>()
+        .map(|value| value.saturating_mul(unit))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use crate::SchedulingOrder;
+    use lasr_types::Address;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(var, value)| (var.to_string(), value.to_string()))
+            .collect();
+        move |var| vars.get(var).cloned()
+    }
+
+    fn settings(errors: &ConfigErrors) -> Vec<&str> {
+        errors
+            .0
+            .iter()
+            .map(|error| error.setting.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file_which_overrides_the_defaults() {
+        let file = r#"
+            [rpc]
+            port = 8000
+            host = "127.0.0.1"
+
+            [batcher]
+            interval_secs = 30
+
+            [eo_server]
+            eth_rpc_urls = ["http://a:8545", "http://b:8545"]
+        "#;
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            Some((Path::new("lasr.toml"), file)),
+            env(&[("PORT", "9000"), ("FEE_BASE", "5")]),
+        )
+        .unwrap();
+
+        // Set in both, the environment wins.
+        assert_eq!(config.rpc.port, 9000);
+        // Set in the file alone.
+        assert_eq!(config.rpc.host, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(config.batcher.interval, Duration::from_secs(30));
+        assert_eq!(
+            config.eo_server.eth_rpc_urls,
+            vec!["http://a:8545".to_string(), "http://b:8545".to_string()]
+        );
+        // Set in the environment alone.
+        assert_eq!(config.fees.schedule(), FeeSchedule::new(5, 0, 0, 0));
+        // Set in neither.
+        assert_eq!(config.executor.queue_depth, 256);
+        assert_eq!(
+            config.eo_server.contract_address,
+            DEVNET_CONTRACT_ADDRESS.to_string()
+        );
+    }
+
+    #[test]
+    fn every_invalid_setting_is_reported_at_once() {
+        let file = r#"
+            [rpc]
+            prot = 8000
+
+            [executor]
+            concurrency = "many"
+        "#;
+        let errors = NodeConfig::layered(
+            NodeConfig::default(),
+            Some((Path::new("lasr.toml"), file)),
+            env(&[
+                ("BATCH_INTERVAL", "0"),
+                ("ADMIN_RPC_ADDR", "0.0.0.0:9393"),
+                ("FEE_CALL", "-1"),
+            ]),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "rpc.prot",
+                "executor.concurrency",
+                "fees.call",
+                "rpc.admin_addr",
+                "batcher.interval_secs",
+                "eo_server.eth_rpc_urls",
+                "eo_server.contract_address",
+            ]
+        );
+        assert_eq!(errors.0[2].origin.as_deref(), Some("FEE_CALL"));
+        assert_eq!(errors.0[1].origin.as_deref(), Some("lasr.toml"));
+    }
+
+    #[test]
+    fn a_file_that_is_not_toml_is_reported_with_the_rest() {
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            Some((Path::new("lasr.toml"), "[rpc")),
+            env(&[("DA_BACKEND", "celestia")]),
+        )
+        .unwrap_err();
+        assert_eq!(settings(&errors), vec!["NODE_CONFIG_PATH", "da.backend"]);
+    }
+
+    #[test]
+    fn a_devnet_config_is_valid_and_changed_by_the_builder() {
+        assert!(NodeConfig::default_devnet().validate().is_ok());
+        assert!(NodeConfig::default_devnet().devnet.faucet().enabled);
+
+        let errors = NodeConfig::builder()
+            .rpc_port(0)
+            .contract_address("0x1234")
+            .batch_interval(Duration::from_secs(5))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec!["rpc.port", "eo_server.contract_address"]
+        );
+        let config = NodeConfig::builder()
+            .batch_interval(Duration::from_secs(5))
+            .devnet(false)
+            .encoding(Encoding::Borsh)
+            .rest(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.batcher.interval, Duration::from_secs(5));
+        assert!(!config.devnet.faucet().enabled);
+        assert_eq!(config.storage.encoding, Encoding::Borsh);
+        assert!(config.rpc.rest);
+
+        let errors = NodeConfig::builder()
+            .commit_log_path("")
+            .build()
+            .unwrap_err();
+        assert_eq!(settings(&errors), vec!["batcher.commit_log_path"]);
+
+        let errors = NodeConfig::builder()
+            .instant_seal(true)
+            .build()
+            .unwrap_err();
+        assert_eq!(settings(&errors), vec!["devnet.instant_seal"]);
+        let config = NodeConfig::builder()
+            .instant_seal(true)
+            .local_da("./da")
+            .build()
+            .unwrap();
+        assert!(config.devnet.instant_seal);
+
+        let errors = NodeConfig::builder()
+            .genesis("./no_such_genesis.toml")
+            .build()
+            .unwrap_err();
+        assert_eq!(settings(&errors), vec!["genesis.path"]);
+
+        let errors = NodeConfig::builder()
+            .keystore("./no_such_keystore")
+            .build()
+            .unwrap_err();
+        assert_eq!(settings(&errors), vec!["keys.keystore_path"]);
+
+        let errors = NodeConfig::builder()
+            .index_transactions(true)
+            .transaction_index_path("")
+            .build()
+            .unwrap_err();
+        assert_eq!(settings(&errors), vec!["transaction_index.path"]);
+
+        let errors = NodeConfig::builder()
+            .storage(StorageConfig {
+                max_token_bytes: 2048,
+                max_account_bytes: 1024,
+                exempt_system_programs: true,
+                ..StorageConfig::default()
+            })
+            .build()
+            .unwrap_err();
+        assert_eq!(settings(&errors), vec!["storage.max_token_bytes"]);
+    }
+
+    #[test]
+    fn settings_the_actors_once_read_themselves_are_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("EO_LOG_RANGE", "500"),
+                ("AUTH_TIMEOUT_MS", "250"),
+                ("PROGRAM_STATS_TOP", "3"),
+                ("EVENT_LOG_PATH", "./node/events.log"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.eo_server.log_range, 500);
+        assert_eq!(config.admission.auth.timeout, Duration::from_millis(250));
+        assert_eq!(config.program_stats.top, 3);
+        assert_eq!(config.events.log_path, PathBuf::from("./node/events.log"));
+
+        // A typo is reported rather than replaced by the default.
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("EXECUTION_CONCURRENCY", "eight"),
+                ("EO_FILTER_BACKFILL_BLOCKS", "-1"),
+                ("AUTH_MEMORY_LIMIT_MB", "0"),
+                ("PROGRAM_STATS_FLUSH_SECS", "0"),
+                ("ADMISSION_POLICY_PATH", "./no_such_policy.json"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "eo_server.filter_backfill_blocks",
+                "executor.concurrency",
+                "admission.auth_memory_limit_mb",
+                "program_stats.flush_secs",
+                "admission.policy_path",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_rpc_server_limits_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("RPC_MAX_BATCH_SIZE", "10"),
+                ("ETH_CHAIN_ID", "5"),
+                ("RPC_EXPENSIVE_RATE_LIMIT_BURST", "3"),
+                ("RPC_RATE_LIMIT_ALLOWLIST", "127.0.0.1, 10.0.0.1"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.rpc.requests.max_batch_size, 10);
+        assert_eq!(config.rpc.eth_chain_id, 5);
+        let limits = config.rpc.rate_limits();
+        assert_eq!(limits.methods["lasr_call"].burst, 3);
+        assert_eq!(limits.default.burst, DEFAULT_RATE_LIMIT_BURST);
+        assert_eq!(
+            limits.allowlist,
+            vec![
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))
+            ]
+        );
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("RPC_RATE_LIMIT_ALLOWLIST", "10.0.0.300"),
+                ("RPC_RATE_LIMIT_PER_SECOND", "0"),
+                ("RPC_MAX_REQUEST_BYTES", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "rpc.rate_limit_allowlist",
+                "rpc.max_request_bytes",
+                "rpc.rate_limit_per_second",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_pending_pool_and_its_scheduling_are_read_and_checked_with_the_rest() {
+        let sender = Address::new([7; 20]);
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("PENDING_TRANSACTION_TTL_SECS", "30"),
+                ("CALL_DEADLINE_MS", "0"),
+                ("SCHEDULING_POLICY", "priority"),
+                (
+                    "SCHEDULING_SENDER_WEIGHTS",
+                    &format!("{}=3", sender.to_full_string()),
+                ),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.pending.ttl, Duration::from_secs(30));
+        assert_eq!(config.pending.call_deadline, None);
+        assert_eq!(config.scheduling.order, SchedulingOrder::Priority);
+        assert_eq!(config.scheduling.fairness.share(&sender), Some(12));
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("SCHEDULING_POLICY", "lottery"),
+                ("SCHEDULING_SENDER_WEIGHTS", "0x1234"),
+                ("PENDING_MAX_IN_FLIGHT", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "scheduling.policy",
+                "scheduling.sender_weights",
+                "pending.max_in_flight",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_executor_and_its_runtime_are_read_and_checked_with_the_rest() {
+        let file = r#"
+            [executor]
+            timeout_secs = 5
+            limit_overrides = { "0xABC" = { timeoutSecs = 300 } }
+        "#;
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            Some((Path::new("node.toml"), file)),
+            env(&[
+                ("EXECUTION_LOG_MAX_LINES", "10"),
+                ("PROGRAM_GATEWAY_URL", "https://ipfs.io"),
+                ("EXECUTOR_RESTART_AFTER_FAILURES", "5"),
+            ]),
+        )
+        .unwrap();
+        let limits = config.executor.limits_config();
+        assert_eq!(
+            limits.for_program("0xabc").timeout,
+            Duration::from_secs(300)
+        );
+        assert_eq!(limits.for_program("0xdef").timeout, Duration::from_secs(5));
+        assert_eq!(config.executor.execution_logs().max_lines(), 10);
+        assert_eq!(
+            config.executor.program_gateway_url.as_deref(),
+            Some("https://ipfs.io")
+        );
+        assert_eq!(config.executor.health.failure_threshold, 5);
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("EXECUTION_LIMIT_OVERRIDES", r#"{"0xabc": {"timeout": 1}}"#),
+                ("PROGRAM_GATEWAY_URL", "ipfs.io"),
+                ("EXECUTOR_HEALTH_CHECK_INTERVAL_SECS", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "executor.limit_overrides",
+                "executor.program_gateway_url",
+                "executor.health_check_interval_secs",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_node_files_supervision_and_shutdown_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("SUBSCRIPTION_BUFFER", "16"),
+                ("BATCH_INDEX_PATH", "./node/batches.log"),
+                ("DEAD_LETTER_MAX_ENTRIES", "50"),
+                ("ACTOR_RESTART_WINDOW_SECS", "120"),
+                ("SHUTDOWN_DRAIN_SECS", "0"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.rpc.subscription_buffer, 16);
+        assert_eq!(
+            config.batcher.batch_index_path,
+            PathBuf::from("./node/batches.log")
+        );
+        assert_eq!(config.dead_letters.max_entries, 50);
+        assert_eq!(config.supervision.window, Duration::from_secs(120));
+        assert_eq!(config.shutdown.drain_period, Duration::ZERO);
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("ACTOR_MAX_RESTARTS", "-1"),
+                ("UNSEALED_BATCHES_PATH", ""),
+                ("DEAD_LETTER_MAX_ENTRIES", "0"),
+                ("SHUTDOWN_DEADLINE_SECS", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "supervision.max_restarts",
+                "batcher.unsealed_batches_path",
+                "dead_letters.max_entries",
+                "shutdown.deadline_secs",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_admission_limits_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[("MAX_NONCE_AHEAD", "8"), ("MAX_INPUT_BYTES", "1024")]),
+        )
+        .unwrap();
+        assert_eq!(config.admission.max_nonce_ahead, 8);
+        assert_eq!(
+            config.admission.input_limits(),
+            InputLimits::new(1024, DEFAULT_MAX_PROGRAM_INPUT_BYTES)
+        );
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("MAX_NONCE_AHEAD", "many"),
+                ("MAX_INPUT_BYTES", "2048"),
+                ("MAX_PROGRAM_INPUT_BYTES", "1024"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "admission.max_nonce_ahead",
+                "admission.max_program_input_bytes"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_settlement_layer_connection_is_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("EO_INGESTION_MODE", "subscription"),
+                ("ETH_WS_URL", "ws://localhost:8546"),
+                ("EO_CONFIRMATIONS", "3"),
+                (
+                    "SETTLEMENT_CONTRACT_ADDRESS",
+                    "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+                ),
+                ("SETTLEMENT_MAX_ATTEMPTS", "2"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.eo_server.ingestion_mode(),
+            IngestionMode::Subscription("ws://localhost:8546".to_string())
+        );
+        assert_eq!(config.eo_server.confirmations, 3);
+        let settler = config.settlement.settler().unwrap();
+        assert_eq!(settler.max_attempts, 2);
+        assert_eq!(settler.gas_limit, DEFAULT_SETTLEMENT_GAS_LIMIT);
+        assert!(NodeConfig::default_devnet().settlement.settler().is_none());
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("EO_INGESTION_MODE", "subscription"),
+                ("SETTLEMENT_CONTRACT_ADDRESS", "nowhere"),
+                ("SETTLEMENT_MAX_ATTEMPTS", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "settlement.contract_address",
+                "eo_server.ws_url",
+                "settlement.max_attempts"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_receipts_and_call_retries_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("RECENT_TRANSACTIONS_WINDOW", "500"),
+                ("PERSIST_TRANSACTION_RECEIPTS", "false"),
+                ("CALL_RETRY_BACKOFF_MS", "10"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.receipts,
+            ReceiptsConfig {
+                recent_window: 500,
+                persist: false
+            }
+        );
+        assert_eq!(
+            config.retry,
+            RetryPolicy {
+                max_attempts: RetryPolicy::default().max_attempts,
+                backoff: Duration::from_millis(10),
+            }
+        );
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("PERSIST_TRANSACTION_RECEIPTS", "sometimes"),
+                ("RECENT_TRANSACTIONS_WINDOW", "0"),
+                ("CALL_RETRY_BACKOFF_MS", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "receipts.persist",
+                "receipts.recent_window",
+                "retry.backoff_ms"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_output_bounds_and_node_checks_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("MAX_OUTPUT_EVENTS", "8"),
+                ("RPC_MAX_RESPONSE_BYTES", "4096"),
+                ("DA_FAILURE_THRESHOLD", "5"),
+                ("EXECUTION_VERIFICATION_PERCENT", "12.5"),
+                ("AUDIT_VERSE_SUPPLY", "true"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.outputs,
+            OutputLimits {
+                max_events: 8,
+                ..OutputLimits::default()
+            }
+        );
+        assert_eq!(config.rpc.max_response_bytes, 4096);
+        assert_eq!(config.da.failure_threshold, 5);
+        assert_eq!(config.executor.verifier().sample_percent(), 12.5);
+        assert!(config.caches.audit_verse_supply);
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("MAX_EVENT_BYTES", "lots"),
+                ("DA_FAILURE_THRESHOLD", "0"),
+                ("EXECUTION_VERIFICATION_PERCENT", "150"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "outputs.max_event_bytes",
+                "da.failure_threshold",
+                "executor.verification_percent"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_load_shedding_thresholds_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("LOAD_SHED_PENDING_TRANSACTIONS", "100"),
+                ("LOAD_SHED_RECOVER_PERCENT", "80"),
+            ]),
+        )
+        .unwrap();
+        let shedding = config.load_shedding.shedder().config();
+        assert_eq!(shedding.shed.pending_transactions, 100);
+        assert_eq!(shedding.recover.pending_transactions, 80);
+        assert_eq!(
+            shedding.retry_after,
+            Duration::from_millis(DEFAULT_SHED_RETRY_AFTER_MS)
+        );
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("LOAD_SHED_EVENT_LOOP_LAG_MS", "soon"),
+                ("LOAD_SHED_EXECUTION_QUEUE", "0"),
+                ("LOAD_SHED_RECOVER_PERCENT", "150"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec![
+                "load_shedding.event_loop_lag_ms",
+                "load_shedding.execution_queue",
+                "load_shedding.recover_percent"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_node_follows_a_primary_only_once_its_url_is_set() {
+        assert_eq!(NodeConfig::default_devnet().follow.follower(), None);
+
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("FOLLOW_PRIMARY_URL", "ws://primary:9292"),
+                ("FOLLOWER_WITNESS_SAMPLE", "0"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.follow.follower(),
+            Some(FollowerConfig::new("ws://primary:9292").with_witness_sample(0))
+        );
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("FOLLOW_PRIMARY_URL", "http://primary:9292"),
+                ("FOLLOWER_RECONNECT_MS", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec!["follow.primary_url", "follow.reconnect_ms"]
+        );
+    }
+
+    #[test]
+    fn the_mailbox_marks_are_read_and_checked_with_the_rest() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("MAILBOX_HIGH_WATER_DEPTH", "200"),
+                ("MAILBOX_RECOVER_PERCENT", "25"),
+                ("LOAD_SHED_RETRY_AFTER_MS", "500"),
+            ]),
+        )
+        .unwrap();
+        let mailboxes = config.mailbox_monitor().config();
+        assert_eq!(mailboxes.high_water.depth, 200);
+        assert_eq!(mailboxes.recover.depth, 50);
+        assert_eq!(mailboxes.retry_after, Duration::from_millis(500));
+
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("MAILBOX_HIGH_WATER_DEPTH", "0"),
+                ("MAILBOX_RECOVER_PERCENT", "101"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec!["mailboxes.high_water_depth", "mailboxes.recover_percent"]
+        );
+    }
+
+    #[test]
+    fn the_system_programs_are_exempt_from_storage_quotas_by_default() {
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[("STORAGE_MAX_ACCOUNT_BYTES", "32768")]),
+        )
+        .unwrap();
+        let quota = config.storage.quota();
+        assert_eq!(quota.max_account_bytes, 32768);
+        assert!(quota.is_exempt(&ETH_ADDR) && quota.is_exempt(&VERSE_ADDR));
+
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("STORAGE_MAX_ACCOUNT_BYTES", "65536"),
+                ("STORAGE_EXEMPT_SYSTEM_PROGRAMS", "false"),
+            ]),
+        )
+        .unwrap();
+        assert!(config.storage.quota().exempt.is_empty());
+    }
+
+    #[test]
+    fn everything_is_kept_unless_a_retention_is_set() {
+        let config = NodeConfig::default_devnet();
+        assert!(!config.retention.prunes());
+
+        let config = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("RETENTION_BLOB_BATCHES", "100"),
+                ("RETENTION_RECEIPT_DAYS", "30"),
+                ("RETENTION_RECEIPT_BATCHES", "1000"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.retention.blobs.batches, Some(100));
+        assert_eq!(config.retention.blobs.days, None);
+        assert_eq!(config.retention.receipts.days, Some(30));
+
+        // Receipts may not go before the blobs they were proven against.
+        let errors = NodeConfig::layered(
+            NodeConfig::default_devnet(),
+            None,
+            env(&[
+                ("RETENTION_BLOB_DAYS", "30"),
+                ("RETENTION_RECEIPT_DAYS", "7"),
+                ("PRUNE_INTERVAL_SECS", "0"),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            settings(&errors),
+            vec!["retention.receipt_days", "retention.prune_interval_secs"]
+        );
+    }
+}
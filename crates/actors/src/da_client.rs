@@ -1,6 +1,9 @@
 use std::{sync::Arc, time::Duration};
 
-use crate::{process_group_changed, ActorExt, Batch, Coerce, StaticFuture, UnorderedFuturePool};
+use crate::{
+    process_group_changed, ActorExt, Batch, Coerce, DaHealth, LocalFileDa, StaticFuture,
+    UnorderedFuturePool,
+};
 use async_trait::async_trait;
 use eigenda_client::{
     blob::EncodedBlob,
@@ -14,20 +17,24 @@ use futures::{
     stream::{FuturesUnordered, StreamExt},
     FutureExt,
 };
-use lasr_messages::{ActorName, ActorType, DaClientMessage, SupervisorType};
+use lasr_compute::{ContentAddress, ProgramFetcher};
+use lasr_messages::{ActorName, ActorType, DaClientMessage, DaError, LasrError, SupervisorType};
 use lasr_types::{Account, AccountType, Address};
 use ractor::{
-    concurrency::OneshotSender, Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent,
+    concurrency::{oneshot, OneshotSender},
+    Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent,
 };
 use thiserror::Error;
 use tokio::{
     sync::{mpsc::Sender, Mutex},
     task::JoinHandle,
 };
+use tracing::Instrument;
 
 #[derive(Clone, Debug, Default)]
 pub struct DaClientActor {
     future_pool: UnorderedFuturePool<StaticFuture<()>>,
+    health: DaHealth,
 }
 
 impl ActorName for DaClientActor {
@@ -40,17 +47,33 @@ impl DaClientActor {
     pub fn new() -> Self {
         Self {
             future_pool: Arc::new(Mutex::new(FuturesUnordered::new())),
+            health: DaHealth::default(),
         }
     }
+
+    /// Opens the breaker after as many failures in a row as `health` allows
+    /// instead of the default.
+    pub fn with_health(mut self, health: DaHealth) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// The outcomes of the requests made to the DA layer, behind the breaker
+    /// `ready` and `nodeStatus` report.
+    pub fn health(&self) -> DaHealth {
+        self.health.clone()
+    }
+
     async fn store_batch(
         da_client: Arc<Mutex<DaClient>>,
+        health: DaHealth,
         batch: String,
         tx: OneshotSender<Result<BlobResponse, std::io::Error>>,
     ) {
         tracing::info!("DA Client asked to store blob");
         let blob_response = {
             let state = da_client.lock().await;
-            state.disperse_blobs(batch).await
+            health.record(state.disperse_blobs(batch).await)
         };
         if let Err(err) = tx.send(blob_response) {
             tracing::error!("DaClient Error: failed to send blob_response: {err:?}");
@@ -58,61 +81,157 @@ impl DaClientActor {
     }
     async fn validate_blob(
         da_client: Arc<Mutex<DaClient>>,
+        health: DaHealth,
         request_id: String,
         tx: OneshotSender<(String, BlobVerificationProof)>,
     ) {
         tracing::info!("DA Client asked to validate blob");
-        let client = {
+        let layer = {
             let state = da_client.lock().await;
-            state.client.clone()
+            state.layer.clone()
         };
-        validate_blob(client, request_id, tx).await;
+        match layer {
+            DaLayer::EigenDa(client) => {
+                validate_blob(client, health, request_id, tx).await;
+            }
+            // Stored blobs are confirmed as soon as they are written.
+            DaLayer::LocalFile(local) => match health.record(local.proof(&request_id)) {
+                Ok(proof) => {
+                    let _ = tx.send((request_id, proof));
+                }
+                Err(err) => {
+                    tracing::error!("DaClient Error: failed to prove blob {request_id}: {err:?}")
+                }
+            },
+        }
     }
     async fn retrieve_account(
         da_client: Arc<Mutex<DaClient>>,
+        health: DaHealth,
         address: Address,
         batch_header_hash: H256,
         blob_index: u128,
         tx: OneshotSender<Option<Account>>,
     ) {
         tracing::warn!("Received a RetrieveAccount message");
-        let batch_header_hash = base64::encode(batch_header_hash.0);
         let res = {
             let state = da_client.lock().await;
-            state
-                .client
-                .retrieve_blob(&batch_header_hash.clone().into(), blob_index)
+            state.retrieve_blob(batch_header_hash, blob_index)
         };
+        record_retrieval(&health, &res);
         match res {
             Ok(blob) => {
-                if let Ok(blob) = EncodedBlob::from_str(&blob) {
-                    if let Some(batch) = Batch::decode_batch(&blob.data()) {
-                        let account = batch.get_user_account(address);
-                        if let Err(Some(account)) = tx.send(account.clone()) {
-                            tracing::error!(
-                                "DaClient Error: failed to send account data for address: {}",
-                                account.owner_address()
+                if let Some(batch) = Batch::decode_batch(&blob) {
+                    let account = batch.get_user_account(address);
+                    if let Err(Some(account)) = tx.send(account.clone()) {
+                        tracing::error!(
+                            "DaClient Error: failed to send account data for address: {}",
+                            account.owner_address()
+                        );
+                    }
+                    tracing::warn!("successfully decoded account blob");
+                    if let Some(acct) = account {
+                        if let AccountType::Program(addr) = acct.account_type() {
+                            tracing::warn!("found account: {}", addr.to_full_string());
+                        } else {
+                            tracing::warn!(
+                                "found account: {}",
+                                acct.owner_address().to_full_string()
                             );
                         }
-                        tracing::warn!("successfully decoded account blob");
-                        if let Some(acct) = account {
-                            if let AccountType::Program(addr) = acct.account_type() {
-                                tracing::warn!("found account: {}", addr.to_full_string());
-                            } else {
-                                tracing::warn!("found account: {}", acct.owner_address().to_full_string());
-                            }
-                        }
-                    };
+                    }
                 }
             }
-            Err(err) => tracing::error!("Error attempting to retreive account for batcher_header_hash {batch_header_hash} and blob_index {blob_index}: {err:?}"),
+            Err(err) => {
+                tracing::error!("Error attempting to retreive account for batcher_header_hash {} and blob_index {blob_index}: {err:?}", base64::encode(batch_header_hash.0));
+            }
+        }
+    }
+    async fn retrieve_blob(
+        da_client: Arc<Mutex<DaClient>>,
+        health: DaHealth,
+        batch_header_hash: H256,
+        blob_index: u128,
+        tx: OneshotSender<Result<Vec<u8>, std::io::Error>>,
+    ) {
+        let res = {
+            let state = da_client.lock().await;
+            state.retrieve_blob(batch_header_hash, blob_index)
+        };
+        record_retrieval(&health, &res);
+        let blob = res.map(String::into_bytes);
+        if tx.send(blob).is_err() {
+            tracing::error!("DaClient Error: failed to send retrieved blob");
         }
     }
 }
 
+/// Fetches program packages stored in blobs on the DA layer, through the
+/// [`DaClientActor`].
+#[derive(Debug, Clone, Default)]
+pub struct DaProgramFetcher;
+
+#[async_trait]
+impl ProgramFetcher for DaProgramFetcher {
+    fn name(&self) -> &str {
+        "DA layer"
+    }
+
+    fn supports(&self, address: &ContentAddress) -> bool {
+        matches!(address, ContentAddress::DataAvailability { .. })
+    }
+
+    async fn fetch(&self, address: &ContentAddress) -> std::io::Result<Vec<u8>> {
+        let ContentAddress::DataAvailability {
+            batch_header_hash,
+            blob_index,
+            ..
+        } = address
+        else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "not a DA content id",
+            ));
+        };
+        let batch_header_hash = hex::decode(batch_header_hash)
+            .ok()
+            .filter(|hash| hash.len() == 32)
+            .map(|hash| H256::from_slice(&hash))
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid batch header hash",
+            ))?;
+        let da_client: ActorRef<DaClientMessage> =
+            ractor::registry::where_is(ActorType::DaClient.to_string())
+                .ok_or(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "unable to acquire DaClient",
+                ))?
+                .into();
+
+        let (tx, rx) = oneshot();
+        da_client
+            .cast(DaClientMessage::RetrieveBlob {
+                batch_header_hash,
+                blob_index: *blob_index,
+                tx,
+            })
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        rx.await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?
+    }
+}
+
+/// Where batches are stored and read back from.
+#[derive(Clone, Debug)]
+enum DaLayer {
+    EigenDa(EigenDaGrpcClient),
+    LocalFile(LocalFileDa),
+}
+
 #[derive(Clone, Debug)]
 pub struct DaClient {
-    client: EigenDaGrpcClient,
+    layer: DaLayer,
 }
 
 #[derive(Clone, Debug, Error, Default)]
@@ -125,13 +244,88 @@ pub enum DaClientError {
     Custom(String),
 }
 
+impl From<DaClientError> for LasrError {
+    fn from(e: DaClientError) -> Self {
+        let e = match e {
+            DaClientError::RactorRegistryError => DaError::Unavailable(e.to_string()),
+            DaClientError::Custom(reason) => DaError::Custom(reason),
+        };
+        LasrError::Da(e)
+    }
+}
+
 impl DaClient {
     pub fn new(client: EigenDaGrpcClient) -> Self {
-        Self { client }
+        Self {
+            layer: DaLayer::EigenDa(client),
+        }
+    }
+
+    /// Stores batches in files under the directory `local` keeps, rather
+    /// than dispersing them.
+    pub fn local(local: LocalFileDa) -> Self {
+        Self {
+            layer: DaLayer::LocalFile(local),
+        }
+    }
+
+    /// The directory batches are stored in, if they are kept locally.
+    pub fn local_files(&self) -> Option<LocalFileDa> {
+        match &self.layer {
+            DaLayer::EigenDa(_) => None,
+            DaLayer::LocalFile(local) => Some(local.clone()),
+        }
     }
 
     async fn disperse_blobs(&self, batch: String) -> Result<BlobResponse, std::io::Error> {
-        self.client.disperse_blob(batch)
+        match &self.layer {
+            DaLayer::EigenDa(client) => client.disperse_blob(batch),
+            DaLayer::LocalFile(local) => local.store(&batch),
+        }
+    }
+
+    /// The encoded batch stored at `blob_index` under `batch_header_hash`.
+    /// Data that is not an encoded blob is `InvalidData`.
+    pub(crate) fn retrieve_blob(
+        &self,
+        batch_header_hash: H256,
+        blob_index: u128,
+    ) -> Result<String, std::io::Error> {
+        match &self.layer {
+            DaLayer::EigenDa(client) => {
+                let encoded_hash = base64::encode(batch_header_hash.0);
+                let blob = client
+                    .retrieve_blob(&encoded_hash.clone().into(), blob_index)
+                    .map_err(|err| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!(
+                                "failed to retrieve blob {blob_index} of batch {encoded_hash}: {err:?}"
+                            ),
+                        )
+                    })?;
+                EncodedBlob::from_str(&blob)
+                    .map(|blob| blob.data().to_string())
+                    .map_err(|_| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "retrieved data is not an encoded blob",
+                        )
+                    })
+            }
+            DaLayer::LocalFile(local) => local.retrieve(batch_header_hash, blob_index),
+        }
+    }
+}
+
+/// Counts a retrieval against the DA layer's health, unless it answered
+/// with data that could not be decoded.
+fn record_retrieval(health: &DaHealth, res: &Result<String, std::io::Error>) {
+    match res {
+        Err(err) if err.kind() != std::io::ErrorKind::InvalidData => {
+            health.failed(format!("{err:?}"))
+        }
+        _ => health.succeeded(),
     }
 }
 
@@ -159,15 +353,25 @@ impl Actor for DaClientActor {
         let da_client_ptr = Arc::clone(state);
         match message {
             // Optimistically and naively store account blobs
-            DaClientMessage::StoreBatch { batch, tx } => {
-                DaClientActor::store_batch(da_client_ptr, batch, tx).await;
+            DaClientMessage::StoreBatch { batch, tx, span } => {
+                DaClientActor::store_batch(da_client_ptr, self.health(), batch, tx)
+                    .instrument(span)
+                    .await;
                 // let guard = self.future_pool.lock().await;
                 // guard.push(fut.boxed());
             }
             DaClientMessage::ValidateBlob { request_id, tx } => {
-                let fut = DaClientActor::validate_blob(da_client_ptr, request_id, tx);
-                let guard = self.future_pool.lock().await;
-                guard.push(fut.boxed());
+                let local = matches!(da_client_ptr.lock().await.layer, DaLayer::LocalFile(_));
+                let fut =
+                    DaClientActor::validate_blob(da_client_ptr, self.health(), request_id, tx);
+                // A local blob is confirmed as soon as it is written, so is
+                // answered at once rather than polled for.
+                if local {
+                    fut.await;
+                } else {
+                    let guard = self.future_pool.lock().await;
+                    guard.push(fut.boxed());
+                }
             }
             // Optimistically and naively retreive account blobs
             DaClientMessage::RetrieveAccount {
@@ -178,6 +382,7 @@ impl Actor for DaClientActor {
             } => {
                 let fut = DaClientActor::retrieve_account(
                     da_client_ptr,
+                    self.health(),
                     address,
                     batch_header_hash,
                     blob_index,
@@ -186,6 +391,21 @@ impl Actor for DaClientActor {
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
+            DaClientMessage::RetrieveBlob {
+                batch_header_hash,
+                blob_index,
+                tx,
+            } => {
+                let fut = DaClientActor::retrieve_blob(
+                    da_client_ptr,
+                    self.health(),
+                    batch_header_hash,
+                    blob_index,
+                    tx,
+                );
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
             DaClientMessage::RetrieveTransaction { .. } => {}
             DaClientMessage::RetrieveContract { .. } => {}
             _ => {}
@@ -228,14 +448,15 @@ async fn get_blob_status(
 #[async_recursion::async_recursion]
 async fn poll_blob_status(
     client: EigenDaGrpcClient,
+    health: DaHealth,
     request_id: String,
     tx: OneshotSender<(String, BlobVerificationProof)>,
 ) -> Result<(), Box<dyn std::error::Error + Send>> {
-    let res = get_blob_status(&client, &request_id).await;
+    let res = health.record(get_blob_status(&client, &request_id).await);
     if let Ok(status) = res {
         if status.status().clone() != BlobResult::Confirmed {
             tokio::time::sleep(Duration::from_secs(30)).await;
-            return poll_blob_status(client.clone(), request_id.clone(), tx).await;
+            return poll_blob_status(client.clone(), health, request_id.clone(), tx).await;
         } else if let Some(proof) = status.blob_verification_proof() {
             tracing::info!("acquired verification proof, sending back to batcher");
             let _ = tx.send((request_id, proof.clone()));
@@ -250,11 +471,12 @@ async fn poll_blob_status(
 
 async fn validate_blob(
     client: EigenDaGrpcClient,
+    health: DaHealth,
     request_id: String,
     tx: OneshotSender<(String, BlobVerificationProof)>,
 ) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send>>> {
     tracing::info!("spawning blob validation task");
-    tokio::task::spawn(async move { poll_blob_status(client, request_id, tx).await })
+    tokio::task::spawn(async move { poll_blob_status(client, health, request_id, tx).await })
 }
 
 pub struct DaClientSupervisor {
@@ -0,0 +1,364 @@
+//! Calls the scheduler gave up retrying, for `admin_getDeadLetters`,
+//! `admin_redriveDeadLetter` and `admin_purgeDeadLetters`.
+//!
+//! A call retried as often as the retry policy allows is parked here with
+//! every failure it had, rather than dropped, until an operator re-drives or
+//! purges it. Its receipt reads as failed with `exhaustedRetries` while it is
+//! parked. The queue is bounded, and makes room by dropping the letter parked
+//! longest. With a path, the letters are written to a file whenever they
+//! change, and read back on the next start.
+//!
+//! Letters are written as JSON in a [`RecordLog`], as in the pending log.
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use lasr_types::Transaction;
+use prometheus::{IntCounter, IntGauge};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::record_log::{RecordLog, RecordLogError};
+use crate::{metrics, RetryFailure};
+
+/// The most letters parked at once, unless the node config says otherwise.
+pub const DEFAULT_MAX_DEAD_LETTERS: usize = 1000;
+
+#[derive(Debug, Error)]
+pub enum DeadLetterError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// A call given up on, with what went wrong each time it ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub transaction_hash: String,
+    pub transaction: Transaction,
+    /// Every failure of the call, oldest first.
+    pub failures: Vec<RetryFailure>,
+    /// Seconds since the epoch the call first failed at.
+    pub first_failed_at: u64,
+    /// Seconds since the epoch the call was given up on at.
+    pub dead_lettered_at: u64,
+}
+
+impl DeadLetter {
+    /// The letter of `transaction`, given up on after `failures`.
+    pub fn new(transaction: Transaction, failures: Vec<RetryFailure>) -> Self {
+        let first_failed_at = failures
+            .first()
+            .map(|failure| failure.failed_at)
+            .unwrap_or_default();
+        let dead_lettered_at = failures
+            .last()
+            .map(|failure| failure.failed_at)
+            .unwrap_or_default();
+        Self {
+            transaction_hash: transaction.hash_string(),
+            transaction,
+            failures,
+            first_failed_at,
+            dead_lettered_at,
+        }
+    }
+
+    /// The description of the last failure, which the call's sender was
+    /// answered with.
+    pub fn description(&self) -> String {
+        self.failures
+            .last()
+            .map(|failure| failure.description.clone())
+            .unwrap_or_default()
+    }
+}
+
+struct DeadLetterMetrics {
+    parked: IntGauge,
+    dropped: IntCounter,
+}
+
+fn dead_letter_metrics() -> &'static DeadLetterMetrics {
+    static METRICS: OnceLock<DeadLetterMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| DeadLetterMetrics {
+        parked: metrics::gauge(
+            "scheduler",
+            "dead_letters",
+            "Calls parked in the dead letters after exhausting their retries.",
+        ),
+        dropped: metrics::counter(
+            "scheduler",
+            "dead_letters_dropped_total",
+            "Dead letters dropped to make room for newer ones.",
+        ),
+    })
+}
+
+#[derive(Debug, Default)]
+struct DeadLettersInner {
+    letters: VecDeque<DeadLetter>,
+    log: Option<RecordLog<DeadLetter>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadLetters {
+    inner: Arc<Mutex<DeadLettersInner>>,
+    max_entries: usize,
+}
+
+impl Default for DeadLetters {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(DeadLettersInner::default())),
+            max_entries: DEFAULT_MAX_DEAD_LETTERS,
+        }
+    }
+}
+
+impl DeadLetters {
+    /// Opens the letters at `path`, creating the file if there is none, with
+    /// those parked there already. Holds at most `DEFAULT_MAX_DEAD_LETTERS`.
+    pub fn open(path: &Path) -> Result<Self, DeadLetterError> {
+        let (log, letters) = RecordLog::open(path)?;
+        let letters = VecDeque::from(letters);
+        dead_letter_metrics().parked.set(letters.len() as i64);
+        Ok(Self {
+            inner: Arc::new(Mutex::new(DeadLettersInner {
+                letters,
+                log: Some(log),
+            })),
+            max_entries: DEFAULT_MAX_DEAD_LETTERS,
+        })
+    }
+
+    /// Holds at most `max_entries` letters, dropping the oldest past them.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Parks `letter`, in place of any parked for the same transaction,
+    /// dropping the letters parked longest if there is no room for it.
+    pub fn park(&self, letter: DeadLetter) -> Result<(), DeadLetterError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .letters
+            .retain(|parked| parked.transaction_hash != letter.transaction_hash);
+        inner.letters.push_back(letter);
+        while inner.letters.len() > self.max_entries.max(1) {
+            if let Some(dropped) = inner.letters.pop_front() {
+                tracing::warn!(
+                    "dead letters are full, dropping {}",
+                    dropped.transaction_hash
+                );
+                dead_letter_metrics().dropped.inc();
+            }
+        }
+        inner.persist()
+    }
+
+    pub fn get(&self, transaction_hash: &str) -> Option<DeadLetter> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .letters
+            .iter()
+            .find(|letter| letter.transaction_hash == transaction_hash)
+            .cloned()
+    }
+
+    /// Every letter parked, oldest first.
+    pub fn list(&self) -> Vec<DeadLetter> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .letters
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .letters
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes the letter of `transaction_hash` out of the queue, to be run
+    /// again.
+    pub fn take(&self, transaction_hash: &str) -> Result<Option<DeadLetter>, DeadLetterError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(position) = inner
+            .letters
+            .iter()
+            .position(|letter| letter.transaction_hash == transaction_hash)
+        else {
+            return Ok(None);
+        };
+        let letter = inner.letters.remove(position);
+        inner.persist()?;
+        Ok(letter)
+    }
+
+    /// Drops the letter of `transaction_hash`, or every letter without one,
+    /// returning how many were dropped.
+    pub fn purge(&self, transaction_hash: Option<&str>) -> Result<usize, DeadLetterError> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let before = inner.letters.len();
+        match transaction_hash {
+            Some(transaction_hash) => inner
+                .letters
+                .retain(|letter| letter.transaction_hash != transaction_hash),
+            None => inner.letters.clear(),
+        }
+        let purged = before - inner.letters.len();
+        if purged > 0 {
+            inner.persist()?;
+        }
+        Ok(purged)
+    }
+}
+
+impl DeadLettersInner {
+    /// Writes every letter over the file, if there is one, so a crash
+    /// leaves either the old letters or the new.
+    fn persist(&mut self) -> Result<(), DeadLetterError> {
+        dead_letter_metrics().parked.set(self.letters.len() as i64);
+        if let Some(log) = self.log.as_mut() {
+            log.rewrite(&self.letters)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod dead_letters_tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::record_log::{encode_record, Json};
+    use lasr_messages::RetryReason;
+    use lasr_types::{TransactionBuilder, TransactionType, U256};
+
+    fn letter(nonce: u64) -> DeadLetter {
+        let transaction = TransactionBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(0)))
+            .from([1; 20])
+            .to([3; 20])
+            .program_id([3; 20])
+            .op("run".to_string())
+            .inputs(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap();
+        DeadLetter::new(
+            transaction,
+            vec![
+                RetryFailure {
+                    reason: RetryReason::Saturated,
+                    description: "saturated".to_string(),
+                    failed_at: 10,
+                },
+                RetryFailure {
+                    reason: RetryReason::Restarting,
+                    description: "restarting".to_string(),
+                    failed_at: 12,
+                },
+            ],
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("dead_letters_{}_{}.log", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn hashes(letters: &DeadLetters) -> Vec<String> {
+        letters
+            .list()
+            .into_iter()
+            .map(|letter| letter.transaction_hash)
+            .collect()
+    }
+
+    #[test]
+    fn the_oldest_letters_make_room_for_new_ones() {
+        let letters = DeadLetters::default().with_max_entries(2);
+        for nonce in 0..3 {
+            letters.park(letter(nonce)).unwrap();
+        }
+        assert_eq!(
+            hashes(&letters),
+            vec![letter(1).transaction_hash, letter(2).transaction_hash]
+        );
+        // Parking a call again moves it to the back without taking room.
+        letters.park(letter(1)).unwrap();
+        assert_eq!(
+            hashes(&letters),
+            vec![letter(2).transaction_hash, letter(1).transaction_hash]
+        );
+        assert_eq!(letters.get(&letter(1).transaction_hash), Some(letter(1)));
+        assert_eq!(
+            letters
+                .get(&letter(1).transaction_hash)
+                .unwrap()
+                .first_failed_at,
+            10
+        );
+        assert_eq!(letter(1).dead_lettered_at, 12);
+        assert_eq!(letter(1).description(), "restarting");
+    }
+
+    #[test]
+    fn letters_are_read_back_after_a_restart() {
+        let path = temp_path("restart");
+        let letters = DeadLetters::open(&path).unwrap();
+        for nonce in 0..4 {
+            letters.park(letter(nonce)).unwrap();
+        }
+        assert_eq!(
+            letters.take(&letter(1).transaction_hash).unwrap(),
+            Some(letter(1))
+        );
+        assert_eq!(letters.take(&letter(1).transaction_hash).unwrap(), None);
+        assert_eq!(letters.purge(Some(&letter(3).transaction_hash)).unwrap(), 1);
+        drop(letters);
+
+        // A record torn off by a crash is dropped.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&encode_record(&Json, &letter(5)).unwrap()[..20])
+            .unwrap();
+        drop(file);
+
+        let letters = DeadLetters::open(&path).unwrap();
+        assert_eq!(
+            hashes(&letters),
+            vec![letter(0).transaction_hash, letter(2).transaction_hash]
+        );
+        assert_eq!(letters.purge(None).unwrap(), 2);
+        drop(letters);
+        assert!(DeadLetters::open(&path).unwrap().is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+}
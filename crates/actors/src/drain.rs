@@ -0,0 +1,292 @@
+//! Stopping the node without losing what it has taken on.
+//!
+//! Once the node starts shutting down it reports not ready and turns writes
+//! away, and what it took on before then is drained stage by stage, each
+//! stage handing on to the next:
+//!
+//! 1. admission: the writes admitted are answered,
+//! 2. scheduler: new transactions are turned away, and those in flight are
+//!    seen through,
+//! 3. engine: what it is working on is finished and written to the account
+//!    cache,
+//! 4. batcher: the batch it was building is sealed and dispersed.
+//!
+//! Only then are the actors stopped. The whole drain is bounded by a
+//! deadline. A stage still going then is abandoned with what
+//! it had left logged, and the batches not yet dispersed are written to the
+//! unsealed batches, to be taken back on the next start. The pending pool
+//! is kept over restarts by the pending log either way.
+use std::fmt::Display;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use lasr_messages::{ActorType, EngineMessage, SchedulerMessage};
+use prometheus::{HistogramVec, IntCounterVec};
+use ractor::{rpc::CallResult, ActorRef, RpcReplyPort};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::{metrics, Batcher, Shutdown, StorageRef, UnsealedBatches};
+
+pub const DEFAULT_SHUTDOWN_DEADLINE_SECS: u64 = 30;
+
+/// How often a stage is asked whether it has drained.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The time an actor has to answer whether it has drained, or to stop.
+const ACTOR_ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The actors stopped once the node has drained, in the order transactions
+/// pass through them.
+const STOP_ORDER: [ActorType; 13] = [
+    ActorType::RpcServer,
+    ActorType::Scheduler,
+    ActorType::PendingTransactions,
+    ActorType::Validator,
+    ActorType::Engine,
+    ActorType::Executor,
+    ActorType::RemoteExecutor,
+    ActorType::Batcher,
+    ActorType::DaClient,
+    ActorType::EoClient,
+    ActorType::EoServer,
+    ActorType::BlobCache,
+    ActorType::AccountCache,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrainStage {
+    Admission,
+    Scheduler,
+    Engine,
+    Batcher,
+}
+
+impl DrainStage {
+    pub const ALL: [DrainStage; 4] = [
+        DrainStage::Admission,
+        DrainStage::Scheduler,
+        DrainStage::Engine,
+        DrainStage::Batcher,
+    ];
+}
+
+impl Display for DrainStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DrainStage::Admission => write!(f, "admission"),
+            DrainStage::Scheduler => write!(f, "scheduler"),
+            DrainStage::Engine => write!(f, "engine"),
+            DrainStage::Batcher => write!(f, "batcher"),
+        }
+    }
+}
+
+struct DrainMetrics {
+    duration: HistogramVec,
+    abandoned: IntCounterVec,
+}
+
+fn drain_metrics() -> &'static DrainMetrics {
+    static METRICS: OnceLock<DrainMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| DrainMetrics {
+        duration: metrics::histogram_vec(
+            "shutdown",
+            "drain_stage_seconds",
+            "Time each stage of the shutdown took to drain.",
+            &["stage"],
+        ),
+        abandoned: metrics::counter_vec(
+            "shutdown",
+            "drain_stages_abandoned_total",
+            "Stages of the shutdown still draining at its deadline, by stage.",
+            &["stage"],
+        ),
+    })
+}
+
+/// What a shutdown drained, and what it left behind.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrainReport {
+    /// The stages drained, in order.
+    pub drained: Vec<DrainStage>,
+    /// The stage still draining at the deadline, if any, and what it had
+    /// left.
+    pub abandoned: Option<(DrainStage, usize)>,
+    /// Batches sealed and dispersed.
+    pub sealed: usize,
+    /// Batches written to the unsealed batches for the next start.
+    pub persisted: usize,
+}
+
+/// Drains the node once it starts shutting down.
+pub struct ShutdownDrain {
+    shutdown: Shutdown,
+    batcher: Arc<Mutex<Batcher>>,
+    storage_ref: StorageRef,
+    unsealed: UnsealedBatches,
+    deadline: Duration,
+}
+
+impl ShutdownDrain {
+    /// Drains the writes admitted under `shutdown` through to `batcher`,
+    /// sealing its batches into `storage_ref`, within the default deadline.
+    pub fn new(shutdown: Shutdown, batcher: Arc<Mutex<Batcher>>, storage_ref: StorageRef) -> Self {
+        Self {
+            shutdown,
+            batcher,
+            storage_ref,
+            unsealed: UnsealedBatches::default(),
+            deadline: Duration::from_secs(DEFAULT_SHUTDOWN_DEADLINE_SECS),
+        }
+    }
+
+    /// Gives the drain `deadline` instead of the default one.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Writes the batches left undispersed to `unsealed` instead of the
+    /// default path.
+    pub fn with_unsealed_batches(mut self, unsealed: UnsealedBatches) -> Self {
+        self.unsealed = unsealed;
+        self
+    }
+
+    /// Starts the shutdown, if it has not started yet, and drains each stage
+    /// in turn until they all have or the deadline passes.
+    pub async fn run(self) -> DrainReport {
+        self.shutdown.start();
+        let deadline = Instant::now() + self.deadline;
+        let mut report = DrainReport::default();
+        for stage in DrainStage::ALL {
+            let started = Instant::now();
+            let mut left = 0;
+            let drained =
+                tokio::time::timeout_at(deadline, self.drain(stage, &mut left, &mut report)).await;
+            drain_metrics()
+                .duration
+                .with_label_values(&[&stage.to_string()])
+                .observe(started.elapsed().as_secs_f64());
+            match drained {
+                Ok(true) => {
+                    tracing::info!("drained the {stage} stage in {:?}", started.elapsed());
+                    report.drained.push(stage);
+                }
+                // Gave up before the deadline, what it left is kept below.
+                Ok(false) => {}
+                Err(_) => {
+                    tracing::error!(
+                        "abandoning the shutdown at the {stage} stage after {:?}, with {left} left in flight",
+                        self.deadline
+                    );
+                    drain_metrics()
+                        .abandoned
+                        .with_label_values(&[&stage.to_string()])
+                        .inc();
+                    report.abandoned = Some((stage, left));
+                    break;
+                }
+            }
+        }
+
+        let unsealed = self.batcher.lock().await.unsealed();
+        if !unsealed.is_empty() {
+            match self.unsealed.write(&unsealed) {
+                Ok(persisted) => {
+                    tracing::warn!("kept {persisted} batches not dispersed for the next start");
+                    report.persisted = persisted;
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "failed to keep {} batches not dispersed, their transactions are lost: {e}",
+                        unsealed.len()
+                    )
+                }
+            }
+        }
+        report
+    }
+
+    /// Drains `stage`, keeping track of how much it has `left`, returning
+    /// `false` if it gave up with some left.
+    async fn drain(&self, stage: DrainStage, left: &mut usize, report: &mut DrainReport) -> bool {
+        match stage {
+            DrainStage::Admission => loop {
+                *left = self.shutdown.writes_in_flight();
+                if *left == 0 {
+                    return true;
+                }
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            },
+            DrainStage::Scheduler => {
+                drain_actor(ActorType::Scheduler, left, |reply| {
+                    SchedulerMessage::Drain { reply }
+                })
+                .await;
+                true
+            }
+            DrainStage::Engine => {
+                drain_actor(ActorType::Engine, left, |reply| EngineMessage::Flush {
+                    reply,
+                })
+                .await;
+                true
+            }
+            DrainStage::Batcher => {
+                *left = self.batcher.lock().await.unsealed().len();
+                match Batcher::seal(Arc::clone(&self.batcher), self.storage_ref.clone()).await {
+                    Ok(sealed) => {
+                        report.sealed = sealed;
+                        true
+                    }
+                    Err(e) => {
+                        tracing::error!("failed to seal the batches on shutdown: {e}");
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stops every actor still running, in the order transactions pass
+    /// through them.
+    pub async fn stop_actors() {
+        for actor_type in STOP_ORDER {
+            if let Some(actor) = ractor::registry::where_is(actor_type.to_string()) {
+                if let Err(e) = actor
+                    .stop_and_wait(Some("shutdown".to_string()), Some(ACTOR_ANSWER_TIMEOUT))
+                    .await
+                {
+                    tracing::error!("failed to stop the {actor_type} actor: {e:?}");
+                }
+            }
+        }
+    }
+}
+
+/// Asks the `actor_type` actor how much it has in flight until it has
+/// nothing. One that is not running has nothing to drain.
+async fn drain_actor<M, F>(actor_type: ActorType, left: &mut usize, message: F)
+where
+    M: ractor::Message,
+    F: Fn(RpcReplyPort<usize>) -> M,
+{
+    loop {
+        let Some(actor) = ractor::registry::where_is(actor_type.to_string()) else {
+            tracing::warn!("the {actor_type} actor is not running, leaving nothing to drain");
+            return;
+        };
+        let actor: ActorRef<M> = actor.into();
+        if let Ok(CallResult::Success(in_flight)) =
+            actor.call(&message, Some(ACTOR_ANSWER_TIMEOUT)).await
+        {
+            *left = in_flight;
+            if in_flight == 0 {
+                return;
+            }
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
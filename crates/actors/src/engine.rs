@@ -6,8 +6,9 @@ use std::{
 };
 
 use crate::{
-    check_account_cache, create_handler, handle_actor_response, process_group_changed, ActorExt,
-    Coerce, StaticFuture, UnorderedFuturePool,
+    cast_between, check_account_cache, create_handler, handle_actor_response, lifecycle,
+    mailbox_handling, mailbox_probed, process_group_changed, validate_outputs, ActorExt, Coerce,
+    LifecycleStage, OutputLimits, OutputValidationError, StaticFuture, UnorderedFuturePool,
 };
 use async_trait::async_trait;
 use eigenda_client::payload::EigenDaBlobPayload;
@@ -17,9 +18,9 @@ use futures::{
 };
 use lasr_contract::create_program_id;
 use lasr_messages::{
-    AccountCacheMessage, ActorName, ActorType, BridgeEvent, DaClientMessage, EngineMessage,
-    EoEvent, EoMessage, ExecutorMessage, PendingTransactionMessage, SchedulerMessage,
-    SupervisorType, ValidatorMessage,
+    AccountCacheMessage, ActorName, ActorType, BatcherMessage, BridgeEvent, DaClientMessage,
+    EngineMessage, EoEvent, EoMessage, ExecutorMessage, LasrError, PendingTransactionMessage,
+    SchedulerMessage, SupervisorType, ValidatorMessage,
 };
 use ractor::{
     concurrency::{oneshot, OneshotReceiver, OneshotSender},
@@ -33,13 +34,72 @@ use jsonrpsee::{tracing::trace_span, types::ErrorObjectOwned as RpcError};
 use lasr_types::{
     Account, AccountType, Address, AddressOrNamespace, ArbitraryData, Metadata, Outputs,
     RecoverableSignature, Status, Token, TokenBuilder, Transaction, TransactionBuilder,
-    TransactionType,
+    TransactionType, U256,
 };
 use tokio::sync::{mpsc::Sender, Mutex};
+use tracing::Instrument;
+
+/// The way a transaction of each type takes through the node. Every type is
+/// matched, so a new one cannot be sent before it is given a way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionRoute {
+    /// Held in the pending pool, validated against the sender's account and
+    /// applied by the batcher.
+    Send,
+    /// Executed by its program before it is validated.
+    Call,
+    /// Its package retrieved and its program registered.
+    Register,
+    /// Made by the node itself, never sent.
+    System,
+}
+
+impl TransactionRoute {
+    pub fn of(transaction_type: &TransactionType) -> Self {
+        match transaction_type {
+            TransactionType::Send(_)
+            | TransactionType::Burn(_)
+            | TransactionType::BurnAmount { .. }
+            | TransactionType::BridgeOut(_)
+            | TransactionType::BridgeOutTo { .. }
+            | TransactionType::Delegate(_)
+            | TransactionType::SetAccountMetadata(_) => TransactionRoute::Send,
+            TransactionType::Call(_) => TransactionRoute::Call,
+            TransactionType::RegisterProgram(_) | TransactionType::Deploy { .. } => {
+                TransactionRoute::Register
+            }
+            TransactionType::BridgeIn(_) => TransactionRoute::System,
+        }
+    }
+
+    /// Checks `transaction` takes this way, failing it if not.
+    pub fn check(self, transaction: &Transaction) -> Result<(), EngineError> {
+        let transaction_type = transaction.transaction_type();
+        let route = TransactionRoute::of(&transaction_type);
+        if route != self {
+            return Err(EngineError::Custom(format!(
+                "{transaction_type} is a {route} transaction, not a {self} one"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for TransactionRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionRoute::Send => write!(f, "send"),
+            TransactionRoute::Call => write!(f, "call"),
+            TransactionRoute::Register => write!(f, "register"),
+            TransactionRoute::System => write!(f, "system"),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct EngineActor {
     future_pool: UnorderedFuturePool<StaticFuture<Result<(), EngineError>>>,
+    output_limits: OutputLimits,
 }
 impl ActorName for EngineActor {
     fn name(&self) -> ractor::ActorName {
@@ -51,6 +111,12 @@ impl ActorName for EngineActor {
 pub enum EngineError {
     #[error("{0:?}")]
     Custom(String),
+
+    #[error("program {program_id} reverted: {reason}")]
+    ProgramReverted {
+        program_id: String,
+        reason: OutputValidationError,
+    },
 }
 impl Default for EngineError {
     fn default() -> Self {
@@ -58,13 +124,36 @@ impl Default for EngineError {
     }
 }
 
+impl From<EngineError> for LasrError {
+    fn from(e: EngineError) -> Self {
+        let e = match e {
+            EngineError::Custom(reason) => lasr_messages::EngineError::Custom(reason),
+            EngineError::ProgramReverted { program_id, reason } => {
+                lasr_messages::EngineError::ProgramReverted {
+                    program_id,
+                    reason: reason.to_string(),
+                }
+            }
+        };
+        LasrError::Engine(e)
+    }
+}
+
 impl EngineActor {
     pub fn new() -> Self {
         Self {
             future_pool: Arc::new(Mutex::new(FuturesUnordered::new())),
+            output_limits: OutputLimits::default(),
         }
     }
 
+    /// Checks the outputs of programs against `output_limits` instead of the
+    /// default limits.
+    pub fn with_output_limits(mut self, output_limits: OutputLimits) -> Self {
+        self.output_limits = output_limits;
+        self
+    }
+
     async fn get_account(&self, address: &Address, account_type: AccountType) -> Account {
         if let AccountType::Program(program_address) = account_type {
             if let Ok(Some(account)) = self.check_cache(address).await {
@@ -74,7 +163,12 @@ impl EngineActor {
             return account;
         }
 
-        Account::new(account_type, None, *address, None)
+        match account_type {
+            AccountType::Program(program_address) => {
+                Account::new_program(program_address, *address)
+            }
+            AccountType::User => Account::new(*address),
+        }
     }
 
     async fn get_caller_account(&self, address: &Address) -> Result<Account, EngineError> {
@@ -107,7 +201,13 @@ impl EngineActor {
             location,
         };
         if let Some(cache_actor) = ractor::registry::where_is(ActorType::AccountCache.to_string()) {
-            if let Err(e) = cache_actor.send_message(message) {
+            let cache_actor: ActorRef<AccountCacheMessage> = cache_actor.into();
+            if let Err(e) = cast_between(
+                ActorType::Engine,
+                ActorType::AccountCache,
+                &cache_actor,
+                message,
+            ) {
                 tracing::error!("AccountCacheActor Error: failed to send write message for account address: {owner}: {e:?}");
             }
         } else {
@@ -148,6 +248,7 @@ impl EngineActor {
     async fn set_pending_transaction(
         transaction: Transaction,
         outputs: Option<Outputs>,
+        replace: bool,
     ) -> Result<(), EngineError> {
         tracing::info!(
             "acquiring pending transaction actor to set transaction: {}",
@@ -162,6 +263,7 @@ impl EngineActor {
         let message = PendingTransactionMessage::New {
             transaction,
             outputs,
+            replace,
         };
         actor
             .cast(message)
@@ -170,24 +272,75 @@ impl EngineActor {
         Ok(())
     }
 
+    fn bridge_in_transaction(event: &BridgeEvent) -> Result<Transaction, EngineError> {
+        EngineActor::mint_transaction(
+            event.program_id().into(),
+            event.user().into(),
+            event.amount(),
+            event.bridge_event_id(),
+        )
+    }
+
+    /// The system transaction crediting `amount` of `program_id` to `user`,
+    /// minting it if it is VERSE. Bridge ins are built this way from their
+    /// event, with its id.
+    pub fn mint_transaction(
+        program_id: Address,
+        user: Address,
+        amount: U256,
+        id: U256,
+    ) -> Result<Transaction, EngineError> {
+        TransactionBuilder::default()
+            .program_id(program_id.into())
+            .from(user.into())
+            .to(user.into())
+            .transaction_type(TransactionType::BridgeIn(id))
+            .value(amount)
+            .inputs(String::new())
+            .op(String::new())
+            .nonce(id)
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .map_err(|e| EngineError::Custom(e.to_string()))
+    }
+
+    /// Mints outside of the settlement layer, for the devnet faucet.
+    async fn handle_mint(
+        address: Address,
+        program_id: Address,
+        amount: U256,
+        id: U256,
+    ) -> Result<String, EngineError> {
+        let transaction = EngineActor::mint_transaction(program_id, address, amount, id)?;
+        let transaction_hash = transaction.hash_string();
+        EngineActor::set_pending_transaction(transaction, None, false).await?;
+        Ok(transaction_hash)
+    }
+
     async fn handle_bridge_event(logs: &Vec<BridgeEvent>) -> Result<(), EngineError> {
         for event in logs {
             // Turn event into Transaction
-            let transaction = TransactionBuilder::default()
-                .program_id(event.program_id().into())
-                .from(event.user().into())
-                .to(event.user().into())
-                .transaction_type(TransactionType::BridgeIn(event.bridge_event_id()))
-                .value(event.amount())
-                .inputs(String::new())
-                .op(String::new())
-                .nonce(event.bridge_event_id())
-                .v(0)
-                .r([0; 32])
-                .s([0; 32])
-                .build()
+            let transaction = EngineActor::bridge_in_transaction(event)?;
+            EngineActor::set_pending_transaction(transaction.clone(), None, false).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Bridge ins are built from their event the same way they were when it
+    /// was first seen, so the batcher can find the credit to take back.
+    async fn handle_bridge_retraction(logs: &Vec<BridgeEvent>) -> Result<(), EngineError> {
+        let batcher: ActorRef<BatcherMessage> =
+            ractor::registry::where_is(ActorType::Batcher.to_string())
+                .ok_or(EngineError::Custom("unable to acquire batcher".to_string()))?
+                .into();
+        for event in logs {
+            let transaction = EngineActor::bridge_in_transaction(event)?;
+            batcher
+                .cast(BatcherMessage::RevertBridgeIn { transaction })
                 .map_err(|e| EngineError::Custom(e.to_string()))?;
-            EngineActor::set_pending_transaction(transaction.clone(), None).await?;
         }
 
         Ok(())
@@ -195,6 +348,7 @@ impl EngineActor {
 
     async fn handle_call(transaction: Transaction) -> Result<(), EngineError> {
         tracing::info!("handling call transaction: {}", transaction.hash_string());
+        TransactionRoute::Call.check(&transaction)?;
         let message = ExecutorMessage::Set { transaction };
         EngineActor::inform_executor(message).await?;
         Ok(())
@@ -219,34 +373,36 @@ impl EngineActor {
         Ok(())
     }
 
-    async fn handle_send(transaction: Transaction) -> Result<(), EngineError> {
+    async fn handle_send(transaction: Transaction, replace: bool) -> Result<(), EngineError> {
         tracing::info!("scheduler handling send: {}", transaction.hash_string());
-        EngineActor::set_pending_transaction(transaction, None).await
+        TransactionRoute::Send.check(&transaction)?;
+        EngineActor::set_pending_transaction(transaction, None, replace).await
     }
 
     async fn handle_register_program(transaction: Transaction) -> Result<(), EngineError> {
         tracing::info!("Creating program address");
-        let mut transaction_id = [0u8; 32];
-        transaction_id.copy_from_slice(&transaction.hash()[..]);
-        let json: serde_json::Map<String, Value> = serde_json::from_str(&transaction.inputs())
-            .map_err(|e| EngineError::Custom(e.to_string()))?;
-
-        let content_id = {
-            if let Some(id) = json.get("contentId") {
-                match id {
-                    Value::String(h) => h,
-                    _ => {
-                        //TODO(asmith): Allow arrays but validate the items in the array
-                        return Err(EngineError::Custom(
-                            "contentId is incorrect type".to_string(),
-                        ));
+        TransactionRoute::Register.check(&transaction)?;
+        let content_id = match transaction.transaction_type() {
+            TransactionType::Deploy { content_id, .. } => content_id,
+            _ => {
+                let json: serde_json::Map<String, Value> =
+                    serde_json::from_str(&transaction.inputs())
+                        .map_err(|e| EngineError::Custom(e.to_string()))?;
+                if let Some(id) = json.get("contentId") {
+                    match id {
+                        Value::String(h) => h.to_string(),
+                        _ => {
+                            //TODO(asmith): Allow arrays but validate the items in the array
+                            return Err(EngineError::Custom(
+                                "contentId is incorrect type".to_string(),
+                            ));
+                        }
                     }
+                } else {
+                    return Err(EngineError::Custom("contentId is required".to_string()));
                 }
-            } else {
-                return Err(EngineError::Custom("contentId is required".to_string()));
             }
-        }
-        .to_string();
+        };
 
         #[cfg(not(feature = "remote"))]
         let message = ExecutorMessage::Create {
@@ -273,6 +429,7 @@ impl EngineActor {
         transaction: Transaction,
         transaction_hash: String,
         outputs: &str,
+        output_limits: &OutputLimits,
     ) -> Result<(), EngineError> {
         // Parse the outputs into instructions
         // Outputs { inputs, instructions };
@@ -280,25 +437,21 @@ impl EngineActor {
             "transaction: {} received by engine as success",
             transaction_hash.clone()
         );
-        let outputs_json =
-            serde_json::from_str(outputs).map_err(|e| EngineError::Custom(e.to_string()));
-
-        let parsed_outputs: Outputs = match outputs_json {
-            Ok(outputs) => {
-                tracing::info!("Output parsed: {:?}", &outputs);
-                outputs
-            }
-            Err(e) => {
-                let error_string = e.to_string();
-                tracing::error!("Engine Error: Deserialization of outputs failed: {}", e);
-                EngineActor::respond_with_error(
-                    transaction.hash_string(),
-                    outputs.to_owned(),
-                    error_string,
-                );
-                return Err(e);
-            }
-        };
+        let parsed_outputs: Outputs =
+            match validate_outputs(outputs, &transaction, output_limits).await {
+                Ok(outputs) => {
+                    tracing::info!("Output parsed: {:?}", &outputs);
+                    outputs
+                }
+                Err(reason) => {
+                    let e = EngineError::ProgramReverted {
+                        program_id: transaction.program_id().to_full_string(),
+                        reason,
+                    };
+                    tracing::error!("Engine Error: Validation of outputs failed: {}", e);
+                    return Err(e);
+                }
+            };
 
         let pending_transactions: ActorRef<PendingTransactionMessage> = {
             ractor::registry::where_is(ActorType::PendingTransactions.to_string())
@@ -313,6 +466,7 @@ impl EngineActor {
         let message = PendingTransactionMessage::New {
             transaction,
             outputs: Some(parsed_outputs),
+            replace: false,
         };
         pending_transactions
             .cast(message)
@@ -326,8 +480,16 @@ impl EngineActor {
         transaction: Transaction,
         transaction_hash: String,
         outputs: String,
+        output_limits: OutputLimits,
     ) -> Result<(), EngineError> {
-        match EngineActor::call_success(transaction, transaction_hash.clone(), &outputs).await {
+        match EngineActor::call_success(
+            transaction,
+            transaction_hash.clone(),
+            &outputs,
+            &output_limits,
+        )
+        .await
+        {
             Err(e) => {
                 //TODO Handle error cases
                 let _ = EngineActor::respond_with_error(transaction_hash, outputs, e.to_string());
@@ -383,6 +545,23 @@ impl EngineActor {
         }
         Ok(())
     }
+
+    async fn handle_eo_retraction(event: EoEvent) -> Result<(), EngineError> {
+        match event {
+            EoEvent::Bridge(log) => {
+                tracing::warn!("Engine Received EO Bridge Retraction");
+                EngineActor::handle_bridge_retraction(&log)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::error!("{e:?}");
+                    });
+            }
+            EoEvent::Settlement(log) => {
+                tracing::warn!("Engine Received EO Settlement Retraction");
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -405,22 +584,34 @@ impl Actor for EngineActor {
         message: Self::Msg,
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
+        let _handling = mailbox_handling(ActorType::Engine);
         match message {
+            EngineMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Engine, probe),
             EngineMessage::EoEvent { event } => {
                 let fut = EngineActor::handle_eo_event(event);
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
+            EngineMessage::EoRetraction { event } => {
+                let fut = EngineActor::handle_eo_retraction(event);
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
             EngineMessage::Cache { account, .. } => {
                 EngineActor::write_to_cache(account, "EngineMessage::Cache".to_string())
             }
             EngineMessage::Call { transaction } => {
-                let fut = EngineActor::handle_call(transaction);
+                let span = lifecycle::stage(LifecycleStage::Engine, &transaction.hash_string());
+                let fut = EngineActor::handle_call(transaction).instrument(span);
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
-            EngineMessage::Send { transaction } => {
-                let fut = EngineActor::handle_send(transaction);
+            EngineMessage::Send {
+                transaction,
+                replace,
+            } => {
+                let span = lifecycle::stage(LifecycleStage::Engine, &transaction.hash_string());
+                let fut = EngineActor::handle_send(transaction, replace).instrument(span);
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
@@ -438,13 +629,34 @@ impl Actor for EngineActor {
                 transaction_hash,
                 outputs,
             } => {
-                let fut = EngineActor::handle_call_success(transaction, transaction_hash, outputs);
+                let fut = EngineActor::handle_call_success(
+                    transaction,
+                    transaction_hash,
+                    outputs,
+                    self.output_limits,
+                );
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
             EngineMessage::RegistrationSuccess { transaction_hash } => {
                 EngineActor::handle_registration_success(transaction_hash);
             }
+            EngineMessage::Mint {
+                address,
+                program_id,
+                amount,
+                id,
+                reply,
+            } => {
+                let minted = EngineActor::handle_mint(address, program_id, amount, id)
+                    .await
+                    .map_err(|e| e.to_string());
+                reply.send(minted).ok();
+            }
+            EngineMessage::Flush { reply } => {
+                let guard = self.future_pool.lock().await;
+                reply.send(guard.len()).ok();
+            }
             _ => {}
         }
         Ok(())
@@ -582,6 +794,7 @@ mod engine_tests {
                 engine_actor_ref.clone(),
                 EngineMessage::Send {
                     transaction: Transaction::default(),
+                    replace: false,
                 },
                 &mut (),
             )
@@ -11,7 +11,6 @@ use hex;
 use ractor::concurrency::OneshotSender;
 use ractor::{Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent};
 use secp256k1::SecretKey;
-use sha3::{Digest, Keccak256};
 use thiserror::Error;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::{mpsc::Receiver, Mutex};
@@ -23,9 +22,12 @@ use web3::types::{Address as EthereumAddress, TransactionId, TransactionReceipt,
 use web3::Web3;
 
 use crate::{
-    process_group_changed, ActorExt, Coerce, EoServerError, StaticFuture, UnorderedFuturePool,
+    process_group_changed, ActorExt, BatchReceipts, Coerce, EoServerError, KeyHandle, Settler,
+    StaticFuture, UnorderedFuturePool,
+};
+use lasr_messages::{
+    ActorName, ActorType, BatchHeader, BatchStatus, EoMessage, HashOrError, SupervisorType,
 };
-use lasr_messages::{ActorName, ActorType, EoMessage, HashOrError, SupervisorType};
 use lasr_types::{Address, U256};
 
 #[derive(Clone, Debug)]
@@ -186,15 +188,37 @@ impl EoClientActor {
             }
         }
     }
+
+    /// Submits `header` once the batches finalized before it are settled,
+    /// so submissions never race each other for a nonce.
+    async fn settle_batch_header(eo_client: Arc<Mutex<EoClient>>, header: BatchHeader) {
+        let (settler, receipts) = {
+            let guard = eo_client.lock().await;
+            (guard.settler.clone(), guard.batch_receipts())
+        };
+        let Some(settler) = settler else {
+            tracing::debug!(
+                "no settlement contract configured, batch {} not submitted",
+                header.batch_id
+            );
+            return;
+        };
+        receipts.set(&header, BatchStatus::Finalized);
+        settler.lock().await.submit(&header).await;
+    }
 }
 
 pub struct EoClient {
     contract: Contract<Http>,
     web3: Web3<Http>,
     address: EthereumAddress,
-    //TODO: read this in from dotenv when signing
-    sk: web3::signing::SecretKey,
+    /// Signs the settlements, with whichever settlement key is active.
+    key: KeyHandle,
     pending: HashMap<(H256, u128), tokio::task::JoinHandle<()>>,
+    /// Submits batch headers to the settlement contract, one at a time, if
+    /// one is configured.
+    settler: Option<Arc<Mutex<Settler>>>,
+    batch_receipts: BatchReceipts,
 }
 
 impl EoClient {
@@ -202,7 +226,7 @@ impl EoClient {
         web3: Web3<Http>,
         contract: Contract<Http>,
         address: Address,
-        sk: web3::signing::SecretKey,
+        key: KeyHandle,
     ) -> web3::Result<Self> {
         let address = EoClient::parse_checksum_address(address)?;
 
@@ -210,11 +234,23 @@ impl EoClient {
             contract,
             web3,
             address,
-            sk,
+            key,
             pending: HashMap::new(),
+            settler: None,
+            batch_receipts: BatchReceipts::default(),
         })
     }
 
+    pub fn with_settler(mut self, settler: Settler) -> Self {
+        self.batch_receipts = settler.receipts();
+        self.settler = Some(Arc::new(Mutex::new(settler)));
+        self
+    }
+
+    pub fn batch_receipts(&self) -> BatchReceipts {
+        self.batch_receipts.clone()
+    }
+
     pub(super) fn insert_pending(
         &mut self,
         blob_info: (H256, u128),
@@ -237,9 +273,10 @@ impl EoClient {
     }
 
     fn parse_checksum_address(address: Address) -> Result<EthereumAddress, web3::Error> {
-        let bytes = address.as_ref();
-        let checksum = EoClient::to_checksum_address(bytes);
-        checksum.parse().map_err(|_| web3::Error::Internal)
+        address
+            .to_checksum_string()
+            .parse()
+            .map_err(|_| web3::Error::Internal)
     }
 
     async fn get_blob_index(&self, address: EthereumAddress) -> Option<(H256, u128)> {
@@ -290,29 +327,6 @@ impl EoClient {
             .ok()
     }
 
-    fn to_checksum_address(bytes: &[u8]) -> String {
-        let hex_address = hex::encode(bytes).to_lowercase();
-        let mut hasher = Keccak256::new();
-        hasher.update(hex_address.as_bytes());
-        let hash = hasher.finalize();
-        let checksum_address: String = hex_address
-            .char_indices()
-            .map(|(i, c)| {
-                if c.is_ascii_digit() {
-                    c.to_string()
-                } else if hash[i / 2] >> 4 >= 8 && i % 2 == 0
-                    || hash[i / 2] & 0x0f >= 8 && i % 2 != 0
-                {
-                    c.to_uppercase().to_string()
-                } else {
-                    c.to_lowercase().to_string()
-                }
-            })
-            .collect();
-
-        format!("0x{}", checksum_address)
-    }
-
     pub(super) async fn settle_batch(
         &mut self,
         accounts: HashSet<String>,
@@ -327,7 +341,7 @@ impl EoClient {
                 )) as Box<dyn std::error::Error>)?
                 .into();
 
-        let mut sk = self.sk;
+        let mut sk = self.key.web3_key();
         let mut contract = self.contract.clone();
         let mut web3 = self.web3.clone();
 
@@ -497,6 +511,11 @@ impl Actor for EoClientActor {
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
+            EoMessage::SettleBatchHeader { header } => {
+                let fut = EoClientActor::settle_batch_header(eo_client_ptr, header);
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
             EoMessage::SettleSuccess {
                 batch_header_hash,
                 blob_index,
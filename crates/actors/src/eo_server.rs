@@ -6,7 +6,7 @@ use crate::{
     UnorderedFuturePool,
 };
 use async_trait::async_trait;
-use eo_listener::{BlocksProcessed, EoServer as InnerEoServer, EventLogResult, EventType};
+use eo_listener::{BlocksProcessed, EoServer as InnerEoServer, EventLogResult, EventType, Reorg};
 use futures::{
     stream::{FuturesUnordered, StreamExt},
     FutureExt,
@@ -46,13 +46,27 @@ impl ActorName for EoServerActor {
     }
 }
 
+/// How long the listener waits for a new block once it has caught up.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
 pub struct EoServerWrapper {
     server: InnerEoServer,
+    poll_interval: Duration,
 }
 
 impl EoServerWrapper {
     pub fn new(server: InnerEoServer) -> Self {
-        Self { server }
+        Self {
+            server,
+            poll_interval: POLL_INTERVAL,
+        }
+    }
+
+    /// Waits `poll_interval` for a new block once caught up instead of the
+    /// 15 seconds a settlement layer block is given.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
     }
 
     pub async fn run(mut self, path: String, storage: StorageRef) -> Result<(), EoServerError> {
@@ -65,7 +79,7 @@ impl EoServerWrapper {
                     ))?
                     .into();
 
-            let mut interval = tokio::time::interval(Duration::from_secs(15));
+            let mut interval = tokio::time::interval(self.poll_interval);
             loop {
                 let logs = match timeout(LOG_TIMEOUT, self.server.next()).await {
                     Ok(logs) => logs,
@@ -101,13 +115,43 @@ impl EoServerWrapper {
                     }
                 }
 
+                match self.server.check_for_reorg().await {
+                    Ok(Some(reorg)) => {
+                        for (log_type, log) in retracted_logs(reorg) {
+                            eo_actor
+                                .cast(EoMessage::Retract { log_type, log })
+                                .typecast()
+                                .log_err(|e| EoServerError::Custom(e.to_string()));
+                        }
+
+                        self.server.save_blocks_processed();
+                        update_blocks_processed_in_persistence(path.clone(), storage.clone())
+                            .await
+                            .typecast()
+                            .log_err(|e| e);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::error!("EoServer Error: unable to check for reorgs: {e:?}")
+                    }
+                }
+
+                // Only saved once the logs scanned have been handed on, so a
+                // restart scans them again rather than skipping them.
+                if let Err(e) = self.server.save_checkpoint().await {
+                    tracing::error!("EoServer Error: unable to save checkpoint: {e:?}");
+                }
+
                 if let ActorStatus::Stopped = eo_actor.get_status() {
                     tracing::error!(
                         "EoServerActor stopped! Waiting 15s, then attempting to reacquire EoServerActor..."
                     );
                     break;
                 }
-                interval.tick().await;
+                self.server.check_provider_health().await;
+                if !self.server.is_catching_up() && !self.server.is_live() {
+                    interval.tick().await;
+                }
             }
         }
 
@@ -115,6 +159,25 @@ impl EoServerWrapper {
     }
 }
 
+/// Groups the events a reorg retracted by their type, keeping the order they
+/// were recorded in.
+fn retracted_logs(reorg: Reorg) -> Vec<(EventType, Vec<Log>)> {
+    let (bridge, settlement): (Vec<_>, Vec<_>) = reorg
+        .retracted
+        .into_iter()
+        .partition(|event| matches!(event.event_type, EventType::Bridge(_)));
+    [bridge, settlement]
+        .into_iter()
+        .filter_map(|events| {
+            let log_type = events.first()?.event_type.clone();
+            Some((
+                log_type,
+                events.into_iter().map(|event| event.log).collect(),
+            ))
+        })
+        .collect()
+}
+
 pub async fn update_blocks_processed_in_persistence(
     path: String,
     storage: StorageRef,
@@ -161,7 +224,15 @@ impl EoServerActor {
 
     fn handle_eo_event(events: EoEvent) -> Result<(), EoServerError> {
         tracing::warn!("discovered EO event: {:?}", events);
-        let message = EngineMessage::EoEvent { event: events };
+        EoServerActor::inform_engine(EngineMessage::EoEvent { event: events })
+    }
+
+    fn handle_eo_retraction(events: EoEvent) -> Result<(), EoServerError> {
+        tracing::warn!("retracting EO event: {:?}", events);
+        EoServerActor::inform_engine(EngineMessage::EoRetraction { event: events })
+    }
+
+    fn inform_engine(message: EngineMessage) -> Result<(), EoServerError> {
         let engine: ActorRef<EngineMessage> =
             ractor::registry::where_is(ActorType::Engine.to_string())
                 .ok_or(EoServerError::Custom(
@@ -325,6 +396,23 @@ impl EoServerActor {
         )))
     }
 
+    fn handle_retraction(log: Vec<web3::ethabi::Log>, log_type: EventType) {
+        let parsed = match log_type {
+            EventType::Bridge(_) => EoServerActor::parse_bridge_log(log).map(EoEvent::from),
+            EventType::Settlement(_) => EoServerActor::parse_settlement_log(log).map(EoEvent::from),
+        };
+        match parsed {
+            Ok(event) => {
+                if let Err(e) = EoServerActor::handle_eo_retraction(event) {
+                    tracing::error!("eo_server encountered an error: {e:?}");
+                }
+            }
+            Err(e) => {
+                tracing::error!("Error parsing retracted log: {e:?}");
+            }
+        }
+    }
+
     fn handle_log(log: Vec<web3::ethabi::Log>, log_type: EventType) {
         match log_type {
             EventType::Bridge(_) => {
@@ -391,6 +479,9 @@ impl Actor for EoServerActor {
             EoMessage::Log { log, log_type } => {
                 EoServerActor::handle_log(log, log_type);
             }
+            EoMessage::Retract { log, log_type } => {
+                EoServerActor::handle_retraction(log, log_type);
+            }
             EoMessage::Bridge {
                 program_id,
                 address,
@@ -0,0 +1,280 @@
+//! The events programs emitted in the batches this node has finalized, for
+//! `getEvents` and `subscribeEvents`.
+//!
+//! A call's events go into its batch with it, and the batch header commits
+//! to them in its events root. Once the batch's blob is verified on the DA
+//! layer the batcher records them here under the batch, appending each to
+//! the log's [`RecordLog`] as JSON, as the batch index does, and publishes
+//! them to subscribers. The file is read back on the next start.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use lasr_types::{Address, EventFilter};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+use crate::record_log::{RecordLog, RecordLogError};
+use crate::{Batch, Subscription, DEFAULT_SUBSCRIPTION_BUFFER};
+
+#[derive(Debug, Error)]
+pub enum EventLogError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// An event as it was recorded, with the call that emitted it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmittedEvent {
+    pub batch_id: u64,
+    pub transaction_hash: String,
+    pub program_id: Address,
+    /// Position of the event among those its call emitted, starting at 0.
+    pub index: usize,
+    pub topic: String,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl EmittedEvent {
+    /// Where the event is in the log, oldest batch first, which its pages
+    /// are ordered by.
+    pub fn key(&self) -> (u64, String, usize) {
+        (self.batch_id, self.transaction_hash.clone(), self.index)
+    }
+
+    /// The events the calls in `batch` emitted, as recorded under
+    /// `batch_id`.
+    pub fn of_batch(batch_id: u64, batch: &Batch) -> Vec<EmittedEvent> {
+        let transactions = batch.transactions();
+        batch
+            .events()
+            .iter()
+            .filter_map(|(transaction_hash, events)| {
+                let transaction = transactions.get(transaction_hash)?;
+                Some((transaction_hash, transaction.program_id(), events))
+            })
+            .flat_map(|(transaction_hash, program_id, events)| {
+                events
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, event)| EmittedEvent {
+                        batch_id,
+                        transaction_hash: transaction_hash.clone(),
+                        program_id,
+                        index,
+                        topic: event.topic.clone(),
+                        attributes: event.attributes.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    pub fn matches(&self, filter: &EventFilter) -> bool {
+        filter.matches(&self.program_id, &self.topic)
+    }
+}
+
+#[derive(Debug, Default)]
+struct EventLogInner {
+    events: BTreeMap<(u64, String, usize), EmittedEvent>,
+    /// The batches whose events are recorded.
+    batches: BTreeSet<u64>,
+    log: Option<RecordLog<EmittedEvent>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    log: Arc<Mutex<EventLogInner>>,
+    emitted: broadcast::Sender<EmittedEvent>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::with_inner(EventLogInner::default())
+    }
+}
+
+impl EventLog {
+    fn with_inner(inner: EventLogInner) -> Self {
+        Self {
+            log: Arc::new(Mutex::new(inner)),
+            emitted: broadcast::channel(DEFAULT_SUBSCRIPTION_BUFFER).0,
+        }
+    }
+
+    /// Opens the log at `path`, creating it if there is none, with the
+    /// events recorded there already.
+    pub fn open(path: &Path) -> Result<Self, EventLogError> {
+        let (log, events) = RecordLog::open(path)?;
+        let mut inner = EventLogInner::default();
+        for event in events {
+            inner.batches.insert(event.batch_id);
+            inner.events.insert(event.key(), event);
+        }
+        inner.log = Some(log);
+
+        Ok(Self::with_inner(inner))
+    }
+
+    /// Records the `events` of batch `batch_id`, unless the batch is
+    /// recorded already, appending them to the file if the log has one and
+    /// publishing them to subscribers.
+    pub fn batched(&self, batch_id: u64, events: Vec<EmittedEvent>) -> Result<(), EventLogError> {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if !log.batches.insert(batch_id) {
+            return Ok(());
+        }
+        if let Some(records) = log.log.as_mut() {
+            records.append_synced(&events)?;
+        }
+        for event in events {
+            // Only fails while no one is subscribed.
+            self.emitted.send(event.clone()).ok();
+            log.events.insert(event.key(), event);
+        }
+        Ok(())
+    }
+
+    /// Every event recorded that `filter` matches, oldest first.
+    pub fn matching(&self, filter: &EventFilter) -> Vec<EmittedEvent> {
+        self.log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .events
+            .values()
+            .filter(|event| event.matches(filter))
+            .cloned()
+            .collect()
+    }
+
+    /// Subscribes to every event recorded from now on.
+    pub fn subscribe(&self) -> Subscription<EmittedEvent> {
+        Subscription::new(self.emitted.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod events_tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use lasr_types::{ProgramEvent, TransactionBuilder, TransactionType, U256};
+    use std::path::PathBuf;
+
+    fn event(batch_id: u64, program_id: u8, index: usize, topic: &str) -> EmittedEvent {
+        EmittedEvent {
+            batch_id,
+            transaction_hash: format!("0x{batch_id:02x}"),
+            program_id: Address::new([program_id; 20]),
+            index,
+            topic: topic.to_string(),
+            attributes: BTreeMap::from([("amount".to_string(), "5".to_string())]),
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("event_log_{}_{}.log", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn by_topic(prefix: &str) -> EventFilter {
+        EventFilter {
+            topic_prefix: Some(prefix.to_string()),
+            ..EventFilter::default()
+        }
+    }
+
+    #[test]
+    fn events_are_read_back_after_a_restart_without_a_torn_record() {
+        let path = temp_path("restart");
+        let log = EventLog::open(&path).unwrap();
+        log.batched(
+            0,
+            vec![event(0, 1, 0, "transfer"), event(0, 1, 1, "approval")],
+        )
+        .unwrap();
+        log.batched(1, vec![event(1, 2, 0, "transfer.batch")])
+            .unwrap();
+        drop(log);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[200, 0, 0, 0, b'{']).unwrap();
+        drop(file);
+
+        let log = EventLog::open(&path).unwrap();
+        assert_eq!(log.matching(&EventFilter::default()).len(), 3);
+        let transfers = log.matching(&by_topic("transfer"));
+        assert_eq!(
+            transfers.iter().map(|e| e.batch_id).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        let program = EventFilter {
+            program_id: Some(Address::new([1; 20])),
+            ..by_topic("transfer")
+        };
+        assert_eq!(log.matching(&program), vec![event(0, 1, 0, "transfer")]);
+
+        // A batch is recorded once, before the restart or after it.
+        log.batched(1, vec![event(1, 2, 0, "transfer.batch")])
+            .unwrap();
+        log.batched(2, vec![event(2, 2, 0, "mint")]).unwrap();
+        drop(log);
+        let log = EventLog::open(&path).unwrap();
+        assert_eq!(log.matching(&EventFilter::default()).len(), 4);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn the_events_in_a_batch_are_published_under_their_calls() {
+        let transaction = TransactionBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(0)))
+            .from([1; 20])
+            .to([1; 20])
+            .program_id([9; 20])
+            .op("transfer".to_string())
+            .inputs(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(0))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap();
+        let mut batch = Batch::new();
+        batch
+            .insert_transaction_with_events(
+                transaction.clone(),
+                vec![
+                    ProgramEvent::new("transfer").with_attribute("amount", "5"),
+                    ProgramEvent::new("approval"),
+                ],
+            )
+            .unwrap();
+
+        let log = EventLog::default();
+        let mut subscription = log.subscribe();
+        log.batched(4, EmittedEvent::of_batch(4, &batch)).unwrap();
+
+        let (first, dropped) = subscription.next().await.unwrap();
+        assert_eq!(dropped, 0);
+        assert_eq!(first.transaction_hash, transaction.hash_string());
+        assert_eq!(first.program_id, Address::new([9; 20]));
+        assert_eq!((first.batch_id, first.index), (4, 0));
+        assert_eq!(
+            first.attributes.get("amount").map(String::as_str),
+            Some("5")
+        );
+        let (second, _) = subscription.next().await.unwrap();
+        assert_eq!((second.index, second.topic.as_str()), (1, "approval"));
+    }
+}
@@ -0,0 +1,266 @@
+//! Bounded pool that program executions run in.
+//!
+//! At most `max_concurrent` programs run at once and at most `max_queued`
+//! more wait for a free slot. Anything submitted past that is turned away so
+//! the executor can push back on the scheduler instead of piling up work.
+//! Every execution runs in its own task, so a program that fails or panics
+//! only takes its own execution down and always gives its slot back.
+use lasr_messages::{ExecutorError, LasrError};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// Executions waiting for a slot by default.
+pub const DEFAULT_EXECUTION_QUEUE_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ExecutionPoolError {
+    #[error("execution queue is full with {queued} executions waiting")]
+    Saturated { queued: usize },
+}
+
+impl From<ExecutionPoolError> for LasrError {
+    fn from(e: ExecutionPoolError) -> Self {
+        match e {
+            ExecutionPoolError::Saturated { queued } => {
+                LasrError::Executor(ExecutorError::Saturated { queued })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ExecutionPoolMetrics {
+    queued: AtomicUsize,
+    active: AtomicUsize,
+    admitted: AtomicU64,
+    rejected: AtomicU64,
+    total_wait_micros: AtomicU64,
+}
+
+impl ExecutionPoolMetrics {
+    /// Executions admitted but still waiting for a slot.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Executions currently running.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn admitted(&self) -> u64 {
+        self.admitted.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Time admitted executions have spent waiting for a slot, summed.
+    pub fn total_wait(&self) -> Duration {
+        Duration::from_micros(self.total_wait_micros.load(Ordering::Relaxed))
+    }
+
+    /// Mean time an execution that got a slot waited for it.
+    pub fn average_wait(&self) -> Duration {
+        let started = self.admitted().saturating_sub(self.queue_depth() as u64);
+        if started == 0 {
+            return Duration::ZERO;
+        }
+        self.total_wait() / started as u32
+    }
+}
+
+/// Decrements a counter when dropped, so an execution that panics or is
+/// aborted is still taken off the books.
+struct CounterGuard<'a>(&'a AtomicUsize);
+
+impl<'a> CounterGuard<'a> {
+    fn increment(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for CounterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutionPool {
+    slots: Arc<Semaphore>,
+    max_concurrent: usize,
+    max_queued: usize,
+    metrics: Arc<ExecutionPoolMetrics>,
+}
+
+impl Default for ExecutionPool {
+    /// As many executions at once as there are cpus.
+    fn default() -> Self {
+        Self::new(num_cpus::get(), DEFAULT_EXECUTION_QUEUE_DEPTH)
+    }
+}
+
+impl ExecutionPool {
+    pub fn new(max_concurrent: usize, max_queued: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            max_queued,
+            metrics: Arc::new(ExecutionPoolMetrics::default()),
+        }
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    pub fn max_queued(&self) -> usize {
+        self.max_queued
+    }
+
+    pub fn metrics(&self) -> Arc<ExecutionPoolMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Queues `execution` to run once a slot is free, or turns it away if
+    /// the queue is already full.
+    pub fn submit<F>(&self, execution: F) -> Result<JoinHandle<F::Output>, ExecutionPoolError>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let max_queued = self.max_queued;
+        if let Err(queued) =
+            self.metrics
+                .queued
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                    (queued < max_queued).then_some(queued + 1)
+                })
+        {
+            self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!("execution pool saturated with {queued} executions queued");
+            return Err(ExecutionPoolError::Saturated { queued });
+        }
+        self.metrics.admitted.fetch_add(1, Ordering::Relaxed);
+
+        let slots = Arc::clone(&self.slots);
+        let metrics = Arc::clone(&self.metrics);
+        Ok(tokio::spawn(async move {
+            let enqueued_at = Instant::now();
+            // Already counted by the admission check above.
+            let queued = CounterGuard(&metrics.queued);
+            // The semaphore is never closed, so this only waits.
+            let _slot = slots.acquire_owned().await.ok();
+            drop(queued);
+
+            let waited = enqueued_at.elapsed();
+            metrics
+                .total_wait_micros
+                .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+            tracing::debug!("execution started after waiting {waited:?} for a slot");
+
+            let _active = CounterGuard::increment(&metrics.active);
+            execution.await
+        }))
+    }
+}
+
+#[cfg(test)]
+mod execution_pool_tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    async fn wait_until(mut condition: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("condition was never met");
+    }
+
+    #[tokio::test]
+    async fn saturated_pool_queues_then_rejects() {
+        let pool = ExecutionPool::new(1, 2);
+        let metrics = pool.metrics();
+
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let running = pool
+            .submit(async move {
+                let _ = release_rx.await;
+                "first"
+            })
+            .unwrap();
+        wait_until(|| metrics.active() == 1).await;
+
+        let queued = vec![
+            pool.submit(async { "second" }).unwrap(),
+            pool.submit(async { "third" }).unwrap(),
+        ];
+        assert_eq!(metrics.queue_depth(), 2);
+        assert_eq!(metrics.active(), 1);
+
+        assert_eq!(
+            pool.submit(async { "fourth" }).unwrap_err(),
+            ExecutionPoolError::Saturated { queued: 2 }
+        );
+        assert_eq!(metrics.rejected(), 1);
+
+        release_tx.send(()).unwrap();
+        assert_eq!(running.await.unwrap(), "first");
+        for handle in queued {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(metrics.queue_depth(), 0);
+        assert_eq!(metrics.active(), 0);
+        assert_eq!(metrics.admitted(), 3);
+        assert!(pool.submit(async {}).is_ok());
+    }
+
+    #[tokio::test]
+    async fn queued_executions_record_wait_time() {
+        let pool = ExecutionPool::new(1, 1);
+        let metrics = pool.metrics();
+
+        let first = pool
+            .submit(tokio::time::sleep(Duration::from_millis(50)))
+            .unwrap();
+        wait_until(|| metrics.active() == 1).await;
+        let second = pool.submit(async {}).unwrap();
+
+        first.await.unwrap();
+        second.await.unwrap();
+
+        assert!(metrics.total_wait() >= Duration::from_millis(40));
+        assert!(metrics.average_wait() > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn panicking_execution_does_not_poison_the_pool() {
+        let pool = ExecutionPool::new(1, 4);
+        let metrics = pool.metrics();
+
+        let panicked = pool
+            .submit(async {
+                panic!("program blew up");
+            })
+            .unwrap();
+        let healthy = pool.submit(async { 7 }).unwrap();
+
+        assert!(panicked.await.unwrap_err().is_panic());
+        assert_eq!(healthy.await.unwrap(), 7);
+        assert_eq!(metrics.active(), 0);
+        assert_eq!(metrics.queue_depth(), 0);
+    }
+}
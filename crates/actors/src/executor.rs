@@ -1,5 +1,12 @@
 use crate::{
-    get_account, process_group_changed, ActorExt, Coerce, StaticFuture, UnorderedFuturePool,
+    get_account, lifecycle, mailbox_handling, mailbox_probed, process_group_changed, ActorExt,
+    ArtifactCache, Coerce, InputLimits, LifecycleStage, PolicyGate, StaticFuture,
+    UnorderedFuturePool,
+};
+#[cfg(not(feature = "remote"))]
+use crate::{
+    metrics, ArtifactFetcher, ExecutionPool, ExecutionPoolError, ExecutionVerifier, HealthMonitor,
+    ProgramStats, RestartPolicy, RuntimeProbe, VerifiedExecution, MAX_AUTH_OUTPUT_BYTES,
 };
 use async_trait::async_trait;
 use futures::stream::{FuturesUnordered, StreamExt};
@@ -8,19 +15,35 @@ use internal_rpc::api::InternalRpcApiClient;
 #[cfg(feature = "remote")]
 use internal_rpc::job_queue::job::{ComputeJobExecutionType, ServiceJobState, ServiceJobType};
 use jsonrpsee::{core::client::ClientT, ws_client::WsClient};
+use lasr_compute::ExecutionError;
 #[cfg(not(feature = "remote"))]
-use lasr_compute::OciManager;
+use lasr_compute::{
+    ContentAddress, ExecutionLimits, ExecutionLogs, FetchError, OciManager, ProgramRuntime,
+    PROGRAM_RUNTIME_KEY,
+};
 use lasr_contract::create_program_id;
+#[cfg(not(feature = "remote"))]
+use lasr_messages::ExecutorError;
 use lasr_messages::{ActorName, BatcherMessage, SupervisorType};
 use lasr_messages::{
-    ActorType, EngineMessage, ExecutorMessage, PendingTransactionMessage, SchedulerMessage,
+    ActorType, EngineMessage, ExecutorHealth, ExecutorMessage, ExecutorStatus, LasrError,
+    PendingTransactionMessage, SchedulerMessage,
 };
+#[cfg(not(feature = "remote"))]
+use lasr_types::{Account, AccountType};
 use lasr_types::{Inputs, ProgramSchema, Required, Transaction};
+#[cfg(not(feature = "remote"))]
+use prometheus::{HistogramVec, IntCounterVec};
+#[cfg(not(feature = "remote"))]
+use ractor::RpcReplyPort;
 use ractor::{Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent};
-use serde::{Deserialize, Serialize};
 #[cfg(feature = "remote")]
 use std::time::Duration;
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::Arc,
+};
 use thiserror::Error;
 #[cfg(feature = "remote")]
 use tokio::sync::mpsc::Receiver;
@@ -28,6 +51,38 @@ use tokio::{
     sync::{mpsc::Sender, Mutex},
     task::JoinHandle,
 };
+#[cfg(not(feature = "remote"))]
+use tracing::Instrument;
+
+/// Number of recently registered programs whose artifacts are prepared
+/// again if they are evicted.
+#[cfg(not(feature = "remote"))]
+const RECENTLY_REGISTERED: usize = 64;
+
+#[cfg(not(feature = "remote"))]
+struct ExecutionMetrics {
+    executions: IntCounterVec,
+    duration: HistogramVec,
+}
+
+#[cfg(not(feature = "remote"))]
+fn execution_metrics() -> &'static ExecutionMetrics {
+    static METRICS: std::sync::OnceLock<ExecutionMetrics> = std::sync::OnceLock::new();
+    METRICS.get_or_init(|| ExecutionMetrics {
+        executions: metrics::counter_vec(
+            "executor",
+            "executions_total",
+            "Calls executed, by runtime and outcome.",
+            &["runtime", "outcome"],
+        ),
+        duration: metrics::histogram_vec(
+            "executor",
+            "execution_duration_seconds",
+            "Seconds taken to execute calls, by runtime.",
+            &["runtime"],
+        ),
+    })
+}
 
 #[derive(Debug)]
 #[allow(unused)]
@@ -67,10 +122,32 @@ impl PendingJob {
     }
 }
 
-// This will be a weighted LRU cache that captures the size of the
-// containers and kills/deletes LRU containers
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct DynCache;
+#[cfg(not(feature = "remote"))]
+#[async_trait]
+impl ArtifactFetcher for OciManager {
+    async fn prepare(&self, content_id: &str) -> std::io::Result<u64> {
+        self.bundle(content_id).await?;
+        self.bundle_size(content_id)
+    }
+
+    fn discard(&self, content_id: &str) -> std::io::Result<()> {
+        self.remove_bundle(content_id)
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+#[async_trait]
+impl RuntimeProbe for OciManager {
+    async fn probe(&self) -> std::io::Result<()> {
+        self.check_runtime()
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+
+    async fn reinitialize(&self) -> std::io::Result<()> {
+        self.reset_runtime().await
+    }
+}
 
 #[allow(unused)]
 pub struct ExecutionEngine<C: InternalRpcApiClient> {
@@ -82,8 +159,25 @@ pub struct ExecutionEngine<C: InternalRpcApiClient> {
     storage_rpc_client: C,
     #[cfg(feature = "remote")]
     pending: HashMap<uuid::Uuid, PendingJob>,
-    handles: HashMap<(String, String), tokio::task::JoinHandle<std::io::Result<String>>>,
-    cache: DynCache,
+    handles: HashMap<(String, String), tokio::task::JoinHandle<Result<String, ExecutionError>>>,
+    cache: ArtifactCache,
+    input_limits: InputLimits,
+    /// The admission policy calls are held to again before they are run.
+    policy: PolicyGate,
+    #[cfg(not(feature = "remote"))]
+    pool: ExecutionPool,
+    #[cfg(not(feature = "remote"))]
+    probe: Arc<dyn RuntimeProbe>,
+    #[cfg(not(feature = "remote"))]
+    health: HealthMonitor,
+    #[cfg(not(feature = "remote"))]
+    verifier: ExecutionVerifier,
+    /// What each program's executions cost.
+    #[cfg(not(feature = "remote"))]
+    program_stats: ProgramStats,
+    /// Program and content ids of the latest registrations, oldest first.
+    #[cfg(not(feature = "remote"))]
+    recent: VecDeque<(String, String)>,
     #[cfg(not(feature = "remote"))]
     phantom: std::marker::PhantomData<C>,
 }
@@ -96,10 +190,24 @@ impl<C: InternalRpcApiClient> ExecutionEngine<C> {
             storage_rpc_client,
             pending: HashMap::new(),
             handles: HashMap::new(),
-            cache: DynCache,
+            cache: ArtifactCache::default(),
+            input_limits: InputLimits::default(),
+            policy: PolicyGate::default(),
         }
     }
 
+    /// Caches artifacts in `cache` instead of the default cache.
+    pub fn with_artifact_cache(mut self, cache: ArtifactCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Holds calls to the admission policy of `policy` before they are run.
+    pub fn with_admission_policy(mut self, policy: PolicyGate) -> Self {
+        self.policy = policy;
+        self
+    }
+
     pub(super) fn spawn_poll(
         &self,
         job_id: uuid::Uuid,
@@ -140,13 +248,103 @@ impl<C: InternalRpcApiClient> ExecutionEngine<C> {
 impl<C: ClientT> ExecutionEngine<C> {
     pub fn new(manager: OciManager) -> Self {
         Self {
+            probe: Arc::new(manager.clone()),
             manager,
             handles: HashMap::new(),
-            cache: DynCache,
+            cache: ArtifactCache::default(),
+            input_limits: InputLimits::default(),
+            policy: PolicyGate::default(),
+            pool: ExecutionPool::default(),
+            health: HealthMonitor::default(),
+            verifier: ExecutionVerifier::default(),
+            program_stats: ProgramStats::default(),
+            recent: VecDeque::new(),
             phantom: std::marker::PhantomData,
         }
     }
 
+    /// Checks the runtime with `probe` under `policy` instead of probing the
+    /// `OciManager`.
+    pub fn with_runtime_probe(
+        mut self,
+        probe: Arc<dyn RuntimeProbe>,
+        policy: RestartPolicy,
+    ) -> Self {
+        self.probe = probe;
+        self.health = HealthMonitor::new(policy);
+        self
+    }
+
+    /// Restarts the runtime under `policy` instead of the default one.
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.health = HealthMonitor::new(policy);
+        self
+    }
+
+    /// Runs executions in `pool` instead of the default pool.
+    pub fn with_execution_pool(mut self, pool: ExecutionPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Caches artifacts in `cache` instead of the default cache.
+    pub fn with_artifact_cache(mut self, cache: ArtifactCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn health(&self) -> &ExecutorHealth {
+        self.health.health()
+    }
+
+    /// Runs one health check, restarting the runtime if it has now failed
+    /// too many in a row. Returns the hashes of the transactions that were
+    /// still executing when it restarted, which are aborted and have to be
+    /// retried.
+    pub(super) async fn check_health(&mut self) -> Vec<String> {
+        let probe = Arc::clone(&self.probe);
+        if !self.health.check(probe.as_ref()).await {
+            return Vec::new();
+        }
+
+        // Executions that already finished have sent their results and are
+        // answered as usual.
+        let unfinished: Vec<(String, String)> = self
+            .handles
+            .iter()
+            .filter(|(_, handle)| !handle.is_finished())
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut interrupted = Vec::with_capacity(unfinished.len());
+        for key in unfinished {
+            if let Some(handle) = self.handles.remove(&key) {
+                handle.abort();
+            }
+            interrupted.push(key.1);
+        }
+
+        self.health.restart(probe.as_ref()).await;
+        interrupted
+    }
+
+    /// Aborts the execution of `transaction_hash`, returning whether it was
+    /// stopped before its results were taken. Once they are, its outputs may
+    /// already be on their way to being applied.
+    pub(super) fn cancel(&mut self, transaction_hash: &str) -> bool {
+        let key = self
+            .handles
+            .keys()
+            .find(|(_, hash)| hash == transaction_hash)
+            .cloned();
+        match key.and_then(|key| self.handles.remove(&key)) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
     pub(super) async fn pin_object(
         &self,
         content_id: &str,
@@ -160,28 +358,235 @@ impl<C: ClientT> ExecutionEngine<C> {
         Ok(())
     }
 
-    pub(super) async fn execute(
+    /// Prepares the artifact for `content_id` unless it is already cached,
+    /// dropping the artifact `program_id` ran before if its content changed.
+    pub(super) async fn prepare_artifact(
+        &mut self,
+        program_id: &str,
+        content_id: &str,
+    ) -> std::io::Result<()> {
+        self.cache.bind(program_id, content_id, &self.manager).await
+    }
+
+    pub fn artifact_cache(&self) -> &ArtifactCache {
+        &self.cache
+    }
+
+    /// Fetches the package for `content_id` and checks it against its hash.
+    pub(super) async fn prefetch_program(&self, content_id: &str) -> Result<(), FetchError> {
+        self.manager.prefetch_program(content_id).await
+    }
+
+    pub(super) fn registered(&mut self, program_id: String, content_id: String) {
+        self.recent.retain(|(program, _)| program != &program_id);
+        self.recent.push_back((program_id, content_id));
+        while self.recent.len() > RECENTLY_REGISTERED {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Prepares the artifact of the most recently registered program that
+    /// has been evicted from the cache, if there is room for it, so its
+    /// next call does not have to fetch it. Programs whose content has been
+    /// replaced since they were registered are skipped.
+    pub(super) async fn prefetch_recent(&mut self) {
+        if self.cache.used_bytes() >= self.cache.capacity_bytes() {
+            return;
+        }
+        let Some((program_id, content_id)) = self
+            .recent
+            .iter()
+            .rev()
+            .find(|(program_id, content_id)| {
+                !self.cache.contains(content_id)
+                    && self
+                        .cache
+                        .content_of(program_id)
+                        .map_or(true, |current| current == content_id)
+            })
+            .cloned()
+        else {
+            return;
+        };
+
+        tracing::info!("prefetching evicted program {program_id} ({content_id})");
+        let prepared = match self.prefetch_program(&content_id).await {
+            Ok(()) => self
+                .prepare_artifact(&program_id, &content_id)
+                .await
+                .map_err(FetchError::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = prepared {
+            tracing::warn!("failed to prefetch program {program_id}: {e}");
+        }
+    }
+
+    /// Queues the program to run in the execution pool. The returned handle
+    /// resolves once the container has run and the executor has been told
+    /// its results are ready.
+    pub(super) fn execute(
         &self,
-        content_id: impl AsRef<Path> + Send + 'static,
+        content_id: String,
         program_id: String,
+        runtime: ProgramRuntime,
         transaction: Transaction,
         inputs: Inputs,
         transaction_hash: &str,
-    ) -> std::io::Result<tokio::task::JoinHandle<std::io::Result<String>>> {
-        let handle = self
-            .manager
-            .run_container(
-                content_id,
-                program_id,
-                Some(transaction),
-                inputs,
-                Some(transaction_hash.to_owned()),
+    ) -> Result<tokio::task::JoinHandle<Result<String, ExecutionError>>, ExecutionPoolError> {
+        let manager = self.manager.clone();
+        let program_stats = self.program_stats.clone();
+        let transaction_hash = transaction_hash.to_owned();
+        let verification = self.verifier.should_verify(&transaction_hash).then(|| {
+            (
+                self.verifier.clone(),
+                self.pool.clone(),
+                VerifiedExecution {
+                    transaction_hash: transaction_hash.clone(),
+                    program_id: program_id.clone(),
+                    content_id: content_id.clone(),
+                    inputs: inputs.clone(),
+                },
             )
-            .await?;
+        });
+        let handle = self.pool.submit(async move {
+            let results = ExecutorMessage::Results {
+                content_id: content_id.clone(),
+                program_id: program_id.clone(),
+                transaction_hash: Some(transaction_hash.clone()),
+                transaction: Some(transaction.clone()),
+            };
+            let counted = program_id.clone();
+            let begun = std::time::Instant::now();
+            let started = manager
+                .run_container(
+                    content_id,
+                    program_id,
+                    runtime,
+                    Some(transaction),
+                    inputs,
+                    Some(transaction_hash),
+                )
+                .await;
+            let result: Result<String, ExecutionError> = match started {
+                Ok(container) => match container.await {
+                    Ok(result) => {
+                        if let (Ok(output), Some((verifier, pool, execution))) =
+                            (&result, verification)
+                        {
+                            let rerun = {
+                                let execution = execution.clone();
+                                async move {
+                                    // Logged to a recorder of its own so its
+                                    // lines are not published twice.
+                                    let logs = ExecutionLogs::default()
+                                        .recorder(&execution.program_id, None);
+                                    manager
+                                        .run_program(
+                                            &execution.content_id,
+                                            &execution.program_id,
+                                            runtime,
+                                            &execution.inputs,
+                                            &logs,
+                                        )
+                                        .await
+                                }
+                            };
+                            verifier.reexecute(&pool, execution, output.clone(), rerun);
+                        }
+                        result
+                    }
+                    Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e).into()),
+                },
+                Err(e) => {
+                    // The container never started, so nothing else will tell
+                    // the executor this execution is finished.
+                    if let Some(executor) =
+                        ractor::registry::where_is(ActorType::Executor.to_string())
+                    {
+                        let executor: ActorRef<ExecutorMessage> = executor.into();
+                        executor.cast(results).typecast().log_err(|e| e);
+                    }
+                    Err(e.into())
+                }
+            };
+            let elapsed = begun.elapsed();
+            program_stats.executed(&counted, result.is_ok(), elapsed);
+            let runtime = runtime.to_string();
+            let outcome = if result.is_ok() { "ok" } else { "error" };
+            let metrics = execution_metrics();
+            metrics
+                .executions
+                .with_label_values(&[&runtime, outcome])
+                .inc();
+            metrics
+                .duration
+                .with_label_values(&[&runtime])
+                .observe(elapsed.as_secs_f64());
+            result
+        })?;
         tracing::warn!("returning handle to executor");
         Ok(handle)
     }
 
+    /// Runs a program for a simulation, logging to a recorder of its own and
+    /// leaving the verifier and pending executions alone.
+    pub(super) fn simulate(
+        &self,
+        content_id: String,
+        program_id: String,
+        runtime: ProgramRuntime,
+        inputs: Inputs,
+        budget: Option<ExecutionLimits>,
+    ) -> Result<tokio::task::JoinHandle<Result<String, ExecutionError>>, ExecutionPoolError> {
+        let manager = self.manager.clone();
+        self.pool.submit(async move {
+            let logs = ExecutionLogs::default().recorder(&program_id, None);
+            manager
+                .run_program_within(
+                    &content_id,
+                    &program_id,
+                    runtime,
+                    &inputs,
+                    &logs,
+                    budget.as_ref(),
+                )
+                .await
+        })
+    }
+
+    pub fn execution_pool(&self) -> &ExecutionPool {
+        &self.pool
+    }
+
+    pub fn verifier(&self) -> &ExecutionVerifier {
+        &self.verifier
+    }
+
+    /// Verifies executions with `verifier` instead of the default one.
+    pub fn with_verifier(mut self, verifier: ExecutionVerifier) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// Holds calls to the admission policy of `policy` before they are run.
+    pub fn with_admission_policy(mut self, policy: PolicyGate) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Counts each program's executions in `program_stats`.
+    pub fn with_program_stats(mut self, program_stats: ProgramStats) -> Self {
+        self.program_stats = program_stats;
+        self
+    }
+
+    /// Limits inputs with `input_limits` instead of the default limits.
+    pub fn with_input_limits(mut self, input_limits: InputLimits) -> Self {
+        self.input_limits = input_limits;
+        self
+    }
+
     pub fn get_program_schema(
         &self,
         content_id: impl AsRef<Path> + Send,
@@ -197,6 +602,9 @@ impl<C: ClientT> ExecutionEngine<C> {
         inputs: String,
     ) -> std::io::Result<Inputs> {
         if let Some(program_account) = get_account(transaction.to(), ActorType::Executor).await {
+            self.input_limits
+                .check(transaction, Some(&program_account))
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
             Ok(Inputs {
                 version: 1,
                 account_info: program_account.clone(),
@@ -307,7 +715,10 @@ impl ExecutorActor {
     }
 
     #[cfg(not(feature = "remote"))]
-    fn registration_success(transaction: Transaction) -> std::io::Result<()> {
+    fn registration_success(
+        transaction: Transaction,
+        runtime: ProgramRuntime,
+    ) -> std::io::Result<()> {
         let actor: ActorRef<BatcherMessage> =
             ractor::registry::where_is(ActorType::Batcher.to_string())
                 .ok_or(std::io::Error::new(
@@ -316,9 +727,9 @@ impl ExecutorActor {
                 ))?
                 .into();
 
-        let message = BatcherMessage::AppendTransaction {
+        let message = BatcherMessage::AppendProgramRegistration {
             transaction,
-            outputs: None,
+            runtime: runtime.to_string(),
         };
         actor
             .cast(message)
@@ -413,6 +824,85 @@ impl ExecutorActor {
         Ok(())
     }
 
+    pub fn execution_saturated(transaction_hash: String, queued: usize) -> std::io::Result<()> {
+        let actor: ActorRef<SchedulerMessage> =
+            ractor::registry::where_is(ActorType::Scheduler.to_string())
+                .ok_or(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Unable to acquire Scheduler actor",
+                ))?
+                .into();
+
+        let message = SchedulerMessage::ExecutorSaturated {
+            transaction_hash,
+            queued,
+        };
+
+        actor
+            .cast(message)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn execution_restarting(transaction_hash: String) -> std::io::Result<()> {
+        let actor: ActorRef<SchedulerMessage> =
+            ractor::registry::where_is(ActorType::Scheduler.to_string())
+                .ok_or(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Unable to acquire Scheduler actor",
+                ))?
+                .into();
+
+        let message = SchedulerMessage::ExecutorRestarting { transaction_hash };
+
+        actor
+            .cast(message)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn artifact_unavailable(
+        transaction_hash: String,
+        err: impl std::error::Error,
+    ) -> std::io::Result<()> {
+        let actor: ActorRef<SchedulerMessage> =
+            ractor::registry::where_is(ActorType::Scheduler.to_string())
+                .ok_or(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Unable to acquire Scheduler actor",
+                ))?
+                .into();
+
+        let message = SchedulerMessage::ArtifactUnavailable {
+            transaction_hash,
+            error: err.to_string(),
+        };
+
+        actor
+            .cast(message)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        Ok(())
+    }
+
+    fn report_health(health: ExecutorHealth) -> std::io::Result<()> {
+        let supervisor: ActorRef<ExecutorMessage> =
+            ractor::registry::where_is(SupervisorType::Executor.to_string())
+                .ok_or(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Unable to acquire Executor supervisor",
+                ))?
+                .into();
+
+        supervisor
+            .cast(ExecutorMessage::HealthReport(health))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+        Ok(())
+    }
+
     pub fn call_queued_for_exec(transaction: Transaction) -> std::io::Result<()> {
         let actor: ActorRef<SchedulerMessage> =
             ractor::registry::where_is(ActorType::Scheduler.to_string())
@@ -441,45 +931,61 @@ impl ExecutorActor {
         tracing::info!("Received create program bundle request");
         // Build the container spec and create the container image
         tracing::info!("attempting to pin object from IPFS");
-        let manager = {
-            let state = engine.lock().await;
-            let manager = state.manager.clone();
-            if (manager.check_pinned_status(&content_id).await).is_err() {
-                match state.pin_object(&content_id.clone(), true).await {
-                    Ok(()) => {
-                        tracing::info!("Successfully pinned objects");
-                    }
-                    Err(e) => {
-                        tracing::error!("Error pinning objects: {e:?}");
-                        let _ = ExecutorActor::registration_error(
-                            transaction.hash_string(),
-                            e.to_string(),
-                        );
-                    }
+        let program_id = match create_program_id(content_id.clone(), &transaction) {
+            Ok(program_id) => program_id,
+            Err(e) => {
+                tracing::error!("Executor Error: Registration failed: {e:?}");
+                if let Err(e) =
+                    ExecutorActor::registration_error(transaction.hash_string(), e.to_string())
+                {
+                    tracing::error!(
+                        "Executor Error: Failure while handling registration error: {e:?}"
+                    );
                 }
+                return;
             }
-
-            manager
         };
 
-        match manager.bundle(content_id.clone()).await {
-            Ok(()) => match create_program_id(content_id, &transaction) {
-                Ok(_program_id) => {
-                    if let Err(e) = ExecutorActor::registration_success(transaction.clone()) {
-                        tracing::error!("Executor Error: Registration failed: {e:?}");
-                    }
+        let mut state = engine.lock().await;
+        if let Err(e) = state.prefetch_program(&content_id).await {
+            tracing::error!("Executor Error: Registration failed: {e}");
+            if let Err(e) =
+                ExecutorActor::registration_error(transaction.hash_string(), e.to_string())
+            {
+                tracing::error!("Executor Error: Failure while handling registration error: {e:?}");
+            }
+            return;
+        }
+
+        // Only content on IPFS can be pinned.
+        let pinnable = content_id
+            .parse::<ContentAddress>()
+            .is_ok_and(|address| address.is_ipfs());
+        if pinnable && (state.manager.check_pinned_status(&content_id).await).is_err() {
+            match state.pin_object(&content_id.clone(), true).await {
+                Ok(()) => {
+                    tracing::info!("Successfully pinned objects");
                 }
                 Err(e) => {
+                    tracing::error!("Error pinning objects: {e:?}");
+                    let _ =
+                        ExecutorActor::registration_error(transaction.hash_string(), e.to_string());
+                }
+            }
+        }
+
+        let prepared = state
+            .prepare_artifact(&program_id.to_full_string(), &content_id)
+            .await
+            .and_then(|()| state.manager.program_runtime(&content_id));
+        match prepared {
+            Ok(runtime) => {
+                tracing::info!("program {} runs in the {} runtime", &content_id, runtime);
+                state.registered(program_id.to_full_string(), content_id.clone());
+                if let Err(e) = ExecutorActor::registration_success(transaction.clone(), runtime) {
                     tracing::error!("Executor Error: Registration failed: {e:?}");
-                    if let Err(e) =
-                        ExecutorActor::registration_error(transaction.hash_string(), e.to_string())
-                    {
-                        tracing::error!(
-                            "Executor Error: Failure while handling registration error: {e:?}"
-                        );
-                    }
                 }
-            },
+            }
             Err(e) => {
                 tracing::error!("Executor Error: Registration failed: {e:?}");
                 if let Err(e) =
@@ -563,22 +1069,43 @@ impl ExecutorActor {
                     .get("content_id")
                     .unwrap_or(&program_id.to_full_string())
                     .to_owned();
+                let runtime = account
+                    .program_account_metadata()
+                    .inner()
+                    .get(PROGRAM_RUNTIME_KEY)
+                    .and_then(|runtime| runtime.parse::<ProgramRuntime>().ok())
+                    .unwrap_or_default();
                 let mut state = engine.lock().await;
+                // A call pending when the policy was swapped is held to the
+                // one in force now.
+                if let Err(e) = state.policy.check(&transaction) {
+                    tracing::error!("Rejecting call {transaction_hash}: {e}");
+                    let _ = ExecutorActor::execution_error(&transaction_hash, e);
+                    return;
+                }
                 match state
                     .parse_inputs(/*&schema,*/ &transaction, op, inputs)
                     .await
                 {
                     Ok(inputs) => {
-                        match state
-                            .execute(
-                                content_id,
-                                program_id.to_full_string(),
-                                transaction,
-                                inputs,
-                                &transaction_hash,
-                            )
+                        if let Err(e) = state
+                            .prepare_artifact(&program_id.to_full_string(), &content_id)
                             .await
                         {
+                            tracing::error!(
+                                "Executor Error: Unable to prepare program artifact: {e:?}"
+                            );
+                            let _ = ExecutorActor::artifact_unavailable(transaction_hash, e);
+                            return;
+                        }
+                        match state.execute(
+                            content_id,
+                            program_id.to_full_string(),
+                            runtime,
+                            transaction,
+                            inputs,
+                            &transaction_hash,
+                        ) {
                             Ok(handle) => {
                                 tracing::warn!("result successful, placing handle in handles");
                                 state.handles.insert(
@@ -586,12 +1113,13 @@ impl ExecutorActor {
                                     handle,
                                 );
                             }
-                            Err(e) => {
-                                let error_string = format!(
-                                    "Executor Error: Error while attempting to run container: {e:?}"
+                            Err(ExecutionPoolError::Saturated { queued }) => {
+                                tracing::warn!(
+                                    "Executor saturated, pushing back on transaction: {}",
+                                    &transaction_hash
                                 );
-                                tracing::error!("{}", &error_string);
-                                let _ = ExecutorActor::execution_error(&transaction_hash, e);
+                                let _ =
+                                    ExecutorActor::execution_saturated(transaction_hash, queued);
                             }
                         }
                     }
@@ -612,6 +1140,113 @@ impl ExecutorActor {
             }
         }
     }
+    /// Runs `program` with `inputs` without applying its outputs or
+    /// publishing its logs, held to `budget` as well as its own limits if
+    /// given one, and replies with its outputs.
+    #[cfg(not(feature = "remote"))]
+    async fn simulate(
+        engine: Arc<Mutex<ExecutionEngine<WsClient>>>,
+        program: Account,
+        inputs: Inputs,
+        budget: Option<ExecutionLimits>,
+        reply: RpcReplyPort<Result<String, LasrError>>,
+    ) {
+        let program_id = match program.account_type() {
+            AccountType::Program(program_id) => program_id,
+            AccountType::User => inputs.transaction.program_id(),
+        }
+        .to_full_string();
+        let metadata = program.program_account_metadata();
+        let content_id = metadata
+            .inner()
+            .get("content_id")
+            .unwrap_or(&program_id)
+            .to_owned();
+        let runtime = metadata
+            .inner()
+            .get(PROGRAM_RUNTIME_KEY)
+            .and_then(|runtime| runtime.parse::<ProgramRuntime>().ok())
+            .unwrap_or_default();
+        let handle = {
+            let mut state = engine.lock().await;
+            if let Err(e) = state
+                .input_limits
+                .check(&inputs.transaction, Some(&inputs.account_info))
+            {
+                let _ = reply.send(Err(e.into()));
+                return;
+            }
+            if let Err(e) = state.prepare_artifact(&program_id, &content_id).await {
+                let _ = reply.send(Err(
+                    ExecutorError::ArtifactUnavailable(format!("{e:?}")).into()
+                ));
+                return;
+            }
+            state.simulate(content_id, program_id, runtime, inputs, budget)
+        };
+        let outputs = match handle {
+            Ok(handle) => match handle.await {
+                Ok(outputs) => outputs.map_err(LasrError::from),
+                Err(e) => Err(ExecutorError::Failed(e.to_string()).into()),
+            },
+            Err(e) => Err(e.into()),
+        };
+        if reply.send(outputs).is_err() {
+            tracing::error!("failed to reply with simulated outputs");
+        }
+    }
+
+    /// Grants the pending transactions a credit for each execution the pool
+    /// can run or queue, so calls past that wait there rather than here.
+    #[cfg(not(feature = "remote"))]
+    async fn grant_credits(engine: Arc<Mutex<ExecutionEngine<WsClient>>>) {
+        let credits = {
+            let state = engine.lock().await;
+            let pool = state.execution_pool();
+            pool.max_concurrent() + pool.max_queued()
+        };
+        if let Some(pending) =
+            ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+        {
+            let pending: ActorRef<PendingTransactionMessage> = pending.into();
+            let _ = pending.cast(PendingTransactionMessage::ExecutionCredits { credits });
+        }
+    }
+
+    #[cfg(not(feature = "remote"))]
+    async fn health_check(engine: Arc<Mutex<ExecutionEngine<WsClient>>>) {
+        let (previous, health, interrupted) = {
+            let mut state = engine.lock().await;
+            let previous = state.health().clone();
+            let interrupted = state.check_health().await;
+            (previous, state.health().clone(), interrupted)
+        };
+
+        for transaction_hash in interrupted {
+            tracing::warn!(
+                "failing transaction {} interrupted by executor restart",
+                &transaction_hash
+            );
+            ExecutorActor::execution_restarting(transaction_hash)
+                .typecast()
+                .log_err(|e| e);
+        }
+        if health.status != previous.status
+            || health.restarts != previous.restarts
+            || health.status != ExecutorStatus::Healthy
+        {
+            ExecutorActor::report_health(health.clone())
+                .typecast()
+                .log_err(|e| e);
+        }
+
+        // Between checks of a healthy runtime is a quiet moment to bring
+        // back what was evicted.
+        if health.status == ExecutorStatus::Healthy {
+            engine.lock().await.prefetch_recent().await;
+        }
+    }
+
     async fn results(
         engine: Arc<Mutex<ExecutionEngine<WsClient>>>,
         transaction: Option<Transaction>,
@@ -658,12 +1293,22 @@ impl ExecutorActor {
                                 }
                             }
                             Ok(Err(e)) => {
-                                tracing::error!(
-                                    "Error returned from `call` transaction: {}, program: {}: {:?}",
-                                    &hash,
-                                    &content_id,
-                                    e
-                                );
+                                if let ExecutionError::ResourceExceeded { kind, limit } = &e {
+                                    tracing::error!(
+                                        "`call` transaction: {}, program: {} was killed for exceeding its {} limit of {}",
+                                        &hash,
+                                        &content_id,
+                                        kind,
+                                        limit
+                                    );
+                                } else {
+                                    tracing::error!(
+                                        "Error returned from `call` transaction: {}, program: {}: {:?}",
+                                        &hash,
+                                        &content_id,
+                                        e
+                                    );
+                                }
                                 if let Err(e) = ExecutorActor::execution_error(&hash, e) {
                                     tracing::error!(
                                         "Executor Error: Error while handling execution error: {e:?}"
@@ -714,6 +1359,17 @@ impl Actor for ExecutorActor {
         Ok(args)
     }
 
+    /// Grants credits afresh whenever the executor starts, so those held by
+    /// calls lost in a restart cannot keep the rest waiting.
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        ExecutorActor::grant_credits(Arc::clone(state)).await;
+        Ok(())
+    }
+
     async fn handle(
         &self,
         _: ActorRef<Self::Msg>,
@@ -721,7 +1377,9 @@ impl Actor for ExecutorActor {
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
         let engine_ptr = Arc::clone(state);
+        let _handling = mailbox_handling(ActorType::Executor);
         match message {
+            ExecutorMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Executor, probe),
             ExecutorMessage::Retrieve { .. } => {
                 // Retrieve the package from IPFS
                 // Convert the package into a payload
@@ -741,7 +1399,8 @@ impl Actor for ExecutorActor {
                 guard.push(fut.boxed());
             }
             ExecutorMessage::Exec { transaction } => {
-                let fut = ExecutorActor::exec(engine_ptr, transaction);
+                let span = lifecycle::stage(LifecycleStage::Executor, &transaction.hash_string());
+                let fut = ExecutorActor::exec(engine_ptr, transaction).instrument(span);
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
@@ -761,6 +1420,77 @@ impl Actor for ExecutorActor {
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
+            ExecutorMessage::GrantCredits => {
+                let fut = ExecutorActor::grant_credits(engine_ptr);
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            ExecutorMessage::Cancel {
+                transaction_hash,
+                reply,
+            } => {
+                let fut = async move {
+                    let cancelled = engine_ptr.lock().await.cancel(&transaction_hash);
+                    tracing::warn!("cancelling {transaction_hash}, stopped in time: {cancelled}");
+                    if reply.send(cancelled).is_err() {
+                        tracing::error!(
+                            "failed to reply to the cancellation of {transaction_hash}"
+                        );
+                    }
+                };
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            ExecutorMessage::HealthCheck => {
+                let fut = ExecutorActor::health_check(engine_ptr);
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            ExecutorMessage::GetHealth { reply } => {
+                let fut = async move {
+                    let health = engine_ptr.lock().await.health().clone();
+                    if reply.send(health).is_err() {
+                        tracing::error!("failed to reply with executor health");
+                    }
+                };
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            ExecutorMessage::Simulate {
+                program,
+                inputs,
+                reply,
+            } => {
+                let fut = ExecutorActor::simulate(engine_ptr, program, inputs, None, reply);
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            ExecutorMessage::Authorize {
+                program,
+                inputs,
+                timeout,
+                memory_bytes,
+                reply,
+            } => {
+                let budget = ExecutionLimits {
+                    timeout,
+                    memory_bytes,
+                    output_bytes: MAX_AUTH_OUTPUT_BYTES,
+                };
+                let fut = ExecutorActor::simulate(engine_ptr, program, inputs, Some(budget), reply);
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
+            ExecutorMessage::GetExecutionMismatches { reply } => {
+                let fut = async move {
+                    let mismatches = engine_ptr.lock().await.verifier().mismatches();
+                    if reply.send(mismatches).is_err() {
+                        tracing::error!("failed to reply with execution mismatches");
+                    }
+                };
+                let guard = self.future_pool.lock().await;
+                guard.push(fut.boxed());
+            }
             _ => {}
         }
 
@@ -789,7 +1519,9 @@ impl Actor for ExecutorActor {
         message: Self::Msg,
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
+        let _handling = mailbox_handling(ActorType::Executor);
         match message {
+            ExecutorMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Executor, probe),
             ExecutorMessage::Retrieve {
                 content_id,
                 program_id: _,
@@ -822,6 +1554,7 @@ impl Actor for ExecutorActor {
                 }
             }
             ExecutorMessage::Exec { transaction } => {
+                let _span = lifecycle::stage(LifecycleStage::Executor, &transaction.hash_string());
                 // Fire off RPC call to compute runtime
                 // program_address, op, inputs, transaction_hash
                 let inputs = Inputs {
@@ -831,7 +1564,17 @@ impl Actor for ExecutorActor {
                     op: transaction.op(),
                     inputs: transaction.inputs(),
                 };
+                if let Err(e) = state.policy.check(&transaction) {
+                    tracing::error!("Rejecting call {}: {e}", transaction.hash_string());
+                    let _ = ExecutorActor::execution_error(&transaction.hash_string(), e);
+                    return Ok(());
+                }
                 if let Some(account) = get_account(transaction.to(), ActorType::Executor).await {
+                    if let Err(e) = state.input_limits.check(&transaction, Some(&account)) {
+                        tracing::error!("Rejecting call {}: {e}", transaction.hash_string());
+                        let _ = ExecutorActor::execution_error(&transaction.hash_string(), e);
+                        return Ok(());
+                    }
                     let metadata = account.program_account_metadata();
                     if let Some(cid) = metadata.inner().get("content_id") {
                         tracing::info!("found cid, converting inputs to json");
@@ -938,6 +1681,20 @@ impl Actor for ExecutorActor {
                 }
             }
             ExecutorMessage::Results { .. } => {}
+            ExecutorMessage::Simulate { reply, .. } => {
+                let _ = reply.send(Err(LasrError::Custom(
+                    "calls cannot be simulated on a remote compute agent".to_string(),
+                )));
+            }
+            ExecutorMessage::Authorize { reply, .. } => {
+                let _ = reply.send(Err(LasrError::Custom(
+                    "auth programs cannot be run on a remote compute agent".to_string(),
+                )));
+            }
+            // A job with the compute agent cannot be confirmed stopped.
+            ExecutorMessage::Cancel { reply, .. } => {
+                let _ = reply.send(false);
+            }
             _ => {}
         }
 
@@ -1003,6 +1760,30 @@ impl Actor for ExecutorSupervisor {
         Ok(())
     }
 
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let ExecutorMessage::HealthReport(health) = message {
+            match health.status {
+                ExecutorStatus::Healthy => {
+                    tracing::info!("executor is healthy after {} restarts", health.restarts);
+                }
+                status => {
+                    tracing::error!(
+                        "executor is {:?} after {} consecutive failed health checks: {}",
+                        status,
+                        health.consecutive_failures,
+                        health.last_error.as_deref().unwrap_or("no error recorded")
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn handle_supervisor_evt(
         &self,
         _myself: ActorRef<Self::Msg>,
@@ -1038,15 +1819,128 @@ impl Actor for ExecutorSupervisor {
 
 #[cfg(test)]
 mod executor_tests {
-    use crate::{ActorExt, ExecutionEngine, ExecutorActor};
+    use crate::{ActorExt, ExecutionEngine, ExecutorActor, RestartPolicy, RuntimeProbe};
+    use async_trait::async_trait;
     use jsonrpsee::ws_client::WsClient;
     use lasr_compute::{OciBundler, OciBundlerBuilder, OciManager};
-    use lasr_messages::{ActorType, ExecutorMessage};
+    use lasr_messages::{ActorType, ExecutorMessage, ExecutorStatus};
     use lasr_types::Transaction;
     use ractor::Actor;
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
+    use std::time::Duration;
     use tokio::sync::Mutex;
 
+    fn oci_manager() -> OciManager {
+        let bundler: OciBundler<String, String> = OciBundlerBuilder::default()
+            .runtime("/usr/local/bin/runsc".to_string())
+            .base_images("./base_image".to_string())
+            .containers("./containers".to_string())
+            .payload_path("./payload".to_string())
+            .build()
+            .expect("failed to build bundler");
+        OciManager::new(bundler, None)
+    }
+
+    /// A runtime whose probes hang until it is reinitialized.
+    struct WedgedRuntime {
+        wedged: AtomicBool,
+    }
+
+    #[async_trait]
+    impl RuntimeProbe for WedgedRuntime {
+        async fn probe(&self) -> std::io::Result<()> {
+            if self.wedged.load(Ordering::SeqCst) {
+                std::future::pending::<()>().await;
+            }
+            Ok(())
+        }
+
+        async fn reinitialize(&self) -> std::io::Result<()> {
+            self.wedged.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_fails_only_unfinished_executions() {
+        let runtime = Arc::new(WedgedRuntime {
+            wedged: AtomicBool::new(true),
+        });
+        let mut engine = ExecutionEngine::<WsClient>::new(oci_manager()).with_runtime_probe(
+            runtime.clone(),
+            RestartPolicy {
+                interval: Duration::from_secs(1),
+                probe_timeout: Duration::from_millis(50),
+                failure_threshold: 2,
+            },
+        );
+
+        let finished = engine.pool.submit(async { Ok("{}".to_string()) }).unwrap();
+        while !finished.is_finished() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let stuck = engine
+            .pool
+            .submit(std::future::pending::<
+                Result<String, lasr_compute::ExecutionError>,
+            >())
+            .unwrap();
+        engine.handles.insert(
+            ("0xprogram".to_string(), "0xfinished".to_string()),
+            finished,
+        );
+        engine
+            .handles
+            .insert(("0xprogram".to_string(), "0xstuck".to_string()), stuck);
+
+        assert!(engine.check_health().await.is_empty());
+        assert_eq!(engine.health().status, ExecutorStatus::Degraded);
+
+        let interrupted = engine.check_health().await;
+        assert_eq!(interrupted, vec!["0xstuck".to_string()]);
+        assert_eq!(engine.health().status, ExecutorStatus::Healthy);
+        assert_eq!(engine.health().restarts, 1);
+
+        // The finished execution is still answered with its own result.
+        let finished = engine
+            .handles
+            .remove(&("0xprogram".to_string(), "0xfinished".to_string()))
+            .expect("finished execution was dropped by the restart");
+        assert_eq!(finished.await.unwrap().unwrap(), "{}");
+        assert!(engine.handles.is_empty());
+        // The aborted execution gave its slot back.
+        while engine.execution_pool().metrics().active() != 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert!(engine.check_health().await.is_empty());
+        assert_eq!(engine.health().status, ExecutorStatus::Healthy);
+    }
+
+    #[tokio::test]
+    async fn only_executions_whose_results_are_not_taken_are_cancelled() {
+        let mut engine = ExecutionEngine::<WsClient>::new(oci_manager());
+        let hung = engine
+            .pool
+            .submit(std::future::pending::<
+                Result<String, lasr_compute::ExecutionError>,
+            >())
+            .unwrap();
+        engine
+            .handles
+            .insert(("0xprogram".to_string(), "0xhung".to_string()), hung);
+
+        assert!(engine.cancel("0xhung"));
+        assert!(engine.handles.is_empty());
+        while engine.execution_pool().metrics().active() != 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // Once its results are taken, or if it never started, there is
+        // nothing left to stop.
+        assert!(!engine.cancel("0xhung"));
+    }
+
     #[tokio::test]
     async fn test_executor_future_handler() {
         let executor_actor = ExecutorActor::new();
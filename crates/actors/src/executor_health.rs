@@ -0,0 +1,237 @@
+//! Health of the runtime the executor runs programs in.
+//!
+//! A wedged runtime does not crash anything, it just stops finishing
+//! executions. The executor probes its runtime on an interval, and once
+//! enough probes in a row have failed it gives up on whatever was still
+//! executing, tears the runtime down and brings it back up.
+use crate::unix_now;
+use async_trait::async_trait;
+use lasr_messages::{ActorType, ExecutorHealth, ExecutorMessage, ExecutorStatus};
+use ractor::ActorRef;
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[async_trait]
+pub trait RuntimeProbe: Send + Sync {
+    /// Checks the runtime can still execute programs.
+    async fn probe(&self) -> std::io::Result<()>;
+
+    /// Tears the runtime down and brings it back up.
+    async fn reinitialize(&self) -> std::io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Time between health checks.
+    pub interval: Duration,
+    /// How long a probe or restart may take before it counts as failed.
+    pub probe_timeout: Duration,
+    /// Consecutive failed checks that trigger a restart.
+    pub failure_threshold: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            probe_timeout: Duration::from_secs(10),
+            failure_threshold: 3,
+        }
+    }
+}
+
+/// Tracks the results of health checks and decides when to restart.
+#[derive(Debug, Clone, Default)]
+pub struct HealthMonitor {
+    policy: RestartPolicy,
+    health: ExecutorHealth,
+}
+
+impl HealthMonitor {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            health: ExecutorHealth::default(),
+        }
+    }
+
+    pub fn policy(&self) -> &RestartPolicy {
+        &self.policy
+    }
+
+    pub fn health(&self) -> &ExecutorHealth {
+        &self.health
+    }
+
+    /// Probes the runtime once, returning whether enough checks in a row
+    /// have now failed that it should be restarted.
+    pub async fn check<P: RuntimeProbe + ?Sized>(&mut self, probe: &P) -> bool {
+        let result = self.bounded(probe.probe()).await;
+        self.health.last_checked = Some(unix_now());
+        match result {
+            Ok(()) => {
+                self.health.status = ExecutorStatus::Healthy;
+                self.health.consecutive_failures = 0;
+                self.health.last_error = None;
+                false
+            }
+            Err(e) => {
+                tracing::warn!("executor health check failed: {e}");
+                self.health.consecutive_failures += 1;
+                self.health.last_error = Some(e.to_string());
+                if self.health.consecutive_failures >= self.policy.failure_threshold {
+                    self.health.status = ExecutorStatus::Restarting;
+                    return true;
+                }
+                if self.health.status != ExecutorStatus::Unhealthy {
+                    self.health.status = ExecutorStatus::Degraded;
+                }
+                false
+            }
+        }
+    }
+
+    /// Reinitializes the runtime and probes it again. The runtime is
+    /// healthy again only if that probe passes.
+    pub async fn restart<P: RuntimeProbe + ?Sized>(&mut self, probe: &P) {
+        tracing::error!(
+            "restarting executor runtime after {} failed health checks",
+            self.health.consecutive_failures
+        );
+        self.health.restarts += 1;
+        self.health.consecutive_failures = 0;
+
+        let result = match self.bounded(probe.reinitialize()).await {
+            Ok(()) => self.bounded(probe.probe()).await,
+            Err(e) => Err(e),
+        };
+        self.health.last_checked = Some(unix_now());
+        match result {
+            Ok(()) => {
+                tracing::info!("executor runtime recovered after restart");
+                self.health.status = ExecutorStatus::Healthy;
+                self.health.last_error = None;
+            }
+            Err(e) => {
+                tracing::error!("executor runtime is still failing after restart: {e}");
+                self.health.status = ExecutorStatus::Unhealthy;
+                self.health.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    async fn bounded(
+        &self,
+        fut: impl std::future::Future<Output = std::io::Result<()>>,
+    ) -> std::io::Result<()> {
+        match timeout(self.policy.probe_timeout, fut).await {
+            Ok(result) => result,
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "runtime did not respond within {:?}",
+                    self.policy.probe_timeout
+                ),
+            )),
+        }
+    }
+}
+
+/// Asks the executor to check its runtime every `interval`.
+pub async fn schedule_health_checks(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if let Some(executor) = ractor::registry::where_is(ActorType::Executor.to_string()) {
+            let executor: ActorRef<ExecutorMessage> = executor.into();
+            if let Err(e) = executor.cast(ExecutorMessage::HealthCheck) {
+                tracing::error!("failed to ask executor for a health check: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod executor_health_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    /// A runtime that hangs on every probe while wedged.
+    #[derive(Default)]
+    struct MockRuntime {
+        wedged: AtomicBool,
+        stays_wedged: bool,
+        reinitialized: AtomicU32,
+    }
+
+    #[async_trait]
+    impl RuntimeProbe for MockRuntime {
+        async fn probe(&self) -> std::io::Result<()> {
+            if self.wedged.load(Ordering::SeqCst) {
+                std::future::pending::<()>().await;
+            }
+            Ok(())
+        }
+
+        async fn reinitialize(&self) -> std::io::Result<()> {
+            self.reinitialized.fetch_add(1, Ordering::SeqCst);
+            if !self.stays_wedged {
+                self.wedged.store(false, Ordering::SeqCst);
+            }
+            Ok(())
+        }
+    }
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy {
+            interval: Duration::from_secs(1),
+            probe_timeout: Duration::from_millis(50),
+            failure_threshold: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn wedged_runtime_is_restarted_after_consecutive_failures() {
+        let runtime = MockRuntime::default();
+        let mut monitor = HealthMonitor::new(policy());
+
+        assert!(!monitor.check(&runtime).await);
+        assert_eq!(monitor.health().status, ExecutorStatus::Healthy);
+
+        runtime.wedged.store(true, Ordering::SeqCst);
+        assert!(!monitor.check(&runtime).await);
+        assert_eq!(monitor.health().status, ExecutorStatus::Degraded);
+        assert!(monitor.check(&runtime).await);
+        assert_eq!(monitor.health().status, ExecutorStatus::Restarting);
+
+        monitor.restart(&runtime).await;
+        assert_eq!(runtime.reinitialized.load(Ordering::SeqCst), 1);
+        assert_eq!(monitor.health().status, ExecutorStatus::Healthy);
+        assert_eq!(monitor.health().restarts, 1);
+        assert_eq!(monitor.health().consecutive_failures, 0);
+        assert!(!monitor.check(&runtime).await);
+    }
+
+    #[tokio::test]
+    async fn runtime_that_does_not_recover_is_unhealthy() {
+        let runtime = MockRuntime {
+            stays_wedged: true,
+            ..Default::default()
+        };
+        runtime.wedged.store(true, Ordering::SeqCst);
+        let mut monitor = HealthMonitor::new(policy());
+
+        assert!(!monitor.check(&runtime).await);
+        assert!(monitor.check(&runtime).await);
+        monitor.restart(&runtime).await;
+        assert_eq!(monitor.health().status, ExecutorStatus::Unhealthy);
+        assert!(monitor.health().last_error.is_some());
+
+        // Stays unhealthy rather than looking merely degraded, and restarts
+        // again once the threshold is reached.
+        assert!(!monitor.check(&runtime).await);
+        assert_eq!(monitor.health().status, ExecutorStatus::Unhealthy);
+        assert!(monitor.check(&runtime).await);
+    }
+}
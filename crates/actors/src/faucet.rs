@@ -0,0 +1,200 @@
+//! The devnet faucet behind `faucet`.
+//!
+//! A node started with `DEVNET` set mints VERSE, or ETH, to whoever asks,
+//! through the same system transaction a bridge in is applied as. Each
+//! request is capped, and an address has to wait out the cooldown before it
+//! is funded again. Nodes not on a devnet answer that the faucet is disabled.
+use lasr_messages::RpcErrorCode;
+use lasr_types::{Address, U256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::{ETH_ADDR, VERSE_ADDR};
+
+pub const DEFAULT_FAUCET_COOLDOWN_SECS: u64 = 60;
+/// Ten whole tokens of 18 decimals.
+pub const DEFAULT_FAUCET_MAX_AMOUNT: u64 = 10_000_000_000_000_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FaucetError {
+    #[error("the faucet is only enabled on devnet nodes")]
+    Disabled,
+
+    #[error("{address} was funded recently, retry after {}s", retry_after.as_secs().max(1))]
+    Cooldown {
+        address: Address,
+        retry_after: Duration,
+    },
+
+    #[error("{amount} is more than the {cap} the faucet hands out at once")]
+    OverCap { amount: U256, cap: U256 },
+
+    #[error("the faucet only hands out VERSE and ETH, not {0}")]
+    UnsupportedToken(Address),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl FaucetError {
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            FaucetError::Disabled => RpcErrorCode::Disabled,
+            FaucetError::Cooldown { .. } => RpcErrorCode::RateLimited,
+            FaucetError::OverCap { .. } | FaucetError::UnsupportedToken(_) => {
+                RpcErrorCode::InvalidParams
+            }
+            FaucetError::Custom(_) => RpcErrorCode::Internal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaucetConfig {
+    pub enabled: bool,
+    /// The most handed out in one request.
+    pub max_amount: U256,
+    /// How long an address waits between requests.
+    pub cooldown: Duration,
+}
+
+impl Default for FaucetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_amount: U256::from(DEFAULT_FAUCET_MAX_AMOUNT),
+            cooldown: Duration::from_secs(DEFAULT_FAUCET_COOLDOWN_SECS),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Faucet {
+    config: FaucetConfig,
+    /// When each address was last funded, while its cooldown lasts.
+    funded: Arc<Mutex<HashMap<Address, Instant>>>,
+}
+
+impl Faucet {
+    pub fn new(config: FaucetConfig) -> Self {
+        Self {
+            config,
+            funded: Arc::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Checks `address` may be handed `amount` of `program_id` now, and if
+    /// so starts its cooldown.
+    pub fn check(
+        &self,
+        address: Address,
+        program_id: Address,
+        amount: U256,
+    ) -> Result<(), FaucetError> {
+        if !self.config.enabled {
+            return Err(FaucetError::Disabled);
+        }
+        if program_id != VERSE_ADDR && program_id != ETH_ADDR {
+            return Err(FaucetError::UnsupportedToken(program_id));
+        }
+        if amount > self.config.max_amount {
+            return Err(FaucetError::OverCap {
+                amount,
+                cap: self.config.max_amount,
+            });
+        }
+
+        let now = Instant::now();
+        let cooldown = self.config.cooldown;
+        let mut funded = self.funded.lock().unwrap_or_else(|e| e.into_inner());
+        funded.retain(|_, at| now.duration_since(*at) < cooldown);
+        if let Some(at) = funded.get(&address) {
+            return Err(FaucetError::Cooldown {
+                address,
+                retry_after: cooldown - now.duration_since(*at),
+            });
+        }
+        funded.insert(address, now);
+        Ok(())
+    }
+
+    /// Lets `address` ask again at once, for a mint that did not go through.
+    pub fn forget(&self, address: &Address) {
+        self.funded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(address);
+    }
+
+    /// The id of a mint made now, which no other mint shares.
+    pub fn mint_id() -> U256 {
+        U256::from(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod faucet_tests {
+    use super::*;
+
+    fn faucet(cooldown: Duration) -> Faucet {
+        Faucet::new(FaucetConfig {
+            enabled: true,
+            max_amount: U256::from(100),
+            cooldown,
+        })
+    }
+
+    #[test]
+    fn addresses_wait_out_their_cooldown() {
+        let faucet = faucet(Duration::from_secs(60));
+        let (first, second) = (Address::new([1; 20]), Address::new([2; 20]));
+        faucet.check(first, VERSE_ADDR, U256::from(10)).unwrap();
+        assert!(matches!(
+            faucet.check(first, ETH_ADDR, U256::from(10)),
+            Err(FaucetError::Cooldown { .. })
+        ));
+        faucet.check(second, VERSE_ADDR, U256::from(10)).unwrap();
+
+        faucet.forget(&first);
+        faucet.check(first, VERSE_ADDR, U256::from(10)).unwrap();
+
+        let faucet = self::faucet(Duration::ZERO);
+        faucet.check(first, VERSE_ADDR, U256::from(10)).unwrap();
+        faucet.check(first, VERSE_ADDR, U256::from(10)).unwrap();
+    }
+
+    #[test]
+    fn requests_are_capped_and_gated() {
+        let faucet = faucet(Duration::from_secs(60));
+        let address = Address::new([1; 20]);
+        assert_eq!(
+            faucet.check(address, VERSE_ADDR, U256::from(101)),
+            Err(FaucetError::OverCap {
+                amount: U256::from(101),
+                cap: U256::from(100),
+            })
+        );
+        assert_eq!(
+            faucet.check(address, Address::new([9; 20]), U256::from(1)),
+            Err(FaucetError::UnsupportedToken(Address::new([9; 20])))
+        );
+        // Neither started the cooldown.
+        faucet.check(address, VERSE_ADDR, U256::from(100)).unwrap();
+
+        assert_eq!(
+            Faucet::default().check(address, VERSE_ADDR, U256::from(1)),
+            Err(FaucetError::Disabled)
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! What transactions cost, for admission, `estimateFees` and the batcher.
+//!
+//! A transaction's floor is a base fee, a fee for every byte of its inputs
+//! and a fee for the kind of execution it asks for. Its sender sets the most
+//! they will pay in `maxFee` and may offer a `priorityFee` over the floor.
+//! A transaction whose `maxFee` is under the floor is turned away, and one
+//! that is applied is charged the floor and its priority fee, up to its
+//! `maxFee`, in VERSE burned from the sender. The schedule defaults to
+//! nothing, so transactions signed without fees stay valid until an operator
+//! sets one.
+use lasr_types::{Transaction, TransactionType, U256};
+use serde::Serialize;
+
+use crate::AdmissionError;
+
+/// The kinds of execution a transaction is charged for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecutionClass {
+    /// Sends, burns, bridge outs, delegations and account metadata, which
+    /// run no program.
+    Transfer,
+    /// Calls, which run a program.
+    Call,
+    /// Program registrations and deploys.
+    Deploy,
+    /// Bridge ins, made by the node itself and never charged.
+    System,
+}
+
+impl ExecutionClass {
+    pub fn of(transaction_type: &TransactionType) -> Self {
+        match transaction_type {
+            TransactionType::Send(_)
+            | TransactionType::Burn(_)
+            | TransactionType::BurnAmount { .. }
+            | TransactionType::BridgeOut(_)
+            | TransactionType::BridgeOutTo { .. }
+            | TransactionType::Delegate(_)
+            | TransactionType::SetAccountMetadata(_) => ExecutionClass::Transfer,
+            TransactionType::Call(_) => ExecutionClass::Call,
+            TransactionType::RegisterProgram(_) | TransactionType::Deploy { .. } => {
+                ExecutionClass::Deploy
+            }
+            TransactionType::BridgeIn(_) => ExecutionClass::System,
+        }
+    }
+}
+
+/// The fees, in VERSE, the floor of a transaction is made of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    base_fee: u64,
+    per_input_byte_fee: u64,
+    call_fee: u64,
+    deploy_fee: u64,
+}
+
+/// A transaction's floor, broken down, with what it would be charged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeEstimate {
+    pub execution_class: ExecutionClass,
+    pub input_bytes: usize,
+    pub base_fee: U256,
+    pub input_fee: U256,
+    pub execution_fee: U256,
+    /// The least `maxFee` the transaction is admitted with.
+    pub floor: U256,
+    pub priority_fee: U256,
+    /// The transaction's `maxFee`, or the floor and its priority fee if it
+    /// has none.
+    pub max_fee: U256,
+    /// What the transaction would be charged with `maxFee`.
+    pub fee: U256,
+    /// Why the transaction would be turned away, if it would.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<AdmissionError>,
+}
+
+impl FeeSchedule {
+    pub fn new(base_fee: u64, per_input_byte_fee: u64, call_fee: u64, deploy_fee: u64) -> Self {
+        Self {
+            base_fee,
+            per_input_byte_fee,
+            call_fee,
+            deploy_fee,
+        }
+    }
+
+    /// The floor of `transaction`, broken down. One without a `maxFee` is
+    /// estimated as if it offered the floor and its priority fee.
+    pub fn estimate(&self, transaction: &Transaction) -> FeeEstimate {
+        let execution_class = ExecutionClass::of(&transaction.transaction_type());
+        let input_bytes = transaction.inputs().len();
+        let (base_fee, input_fee, execution_fee) = match execution_class {
+            ExecutionClass::System => (U256::zero(), U256::zero(), U256::zero()),
+            class => (
+                U256::from(self.base_fee),
+                U256::from(self.per_input_byte_fee).saturating_mul(U256::from(input_bytes)),
+                U256::from(match class {
+                    ExecutionClass::Call => self.call_fee,
+                    ExecutionClass::Deploy => self.deploy_fee,
+                    _ => 0,
+                }),
+            ),
+        };
+        let floor = base_fee
+            .saturating_add(input_fee)
+            .saturating_add(execution_fee);
+        let priority_fee = transaction.priority_fee().unwrap_or_default();
+        let max_fee = transaction
+            .max_fee()
+            .unwrap_or_else(|| floor.saturating_add(priority_fee));
+        FeeEstimate {
+            execution_class,
+            input_bytes,
+            base_fee,
+            input_fee,
+            execution_fee,
+            floor,
+            priority_fee,
+            max_fee,
+            fee: floor.saturating_add(priority_fee).min(max_fee),
+            rejection: None,
+        }
+    }
+
+    /// The least `maxFee` `transaction` is admitted with.
+    pub fn floor(&self, transaction: &Transaction) -> U256 {
+        self.estimate(transaction).floor
+    }
+
+    /// What applying `transaction` costs its sender: the floor and its
+    /// priority fee, up to its `maxFee`, which is none if it has none.
+    pub fn charge(&self, transaction: &Transaction) -> U256 {
+        let estimate = self.estimate(transaction);
+        if estimate.execution_class == ExecutionClass::System {
+            return U256::zero();
+        }
+        estimate.fee.min(transaction.max_fee().unwrap_or_default())
+    }
+
+    /// What `transaction` is charged over its floor, which is what the
+    /// priority scheduling policy ranks it by.
+    pub fn priority_fee_paid(&self, transaction: &Transaction) -> U256 {
+        self.charge(transaction)
+            .saturating_sub(self.floor(transaction))
+    }
+}
+
+#[cfg(test)]
+mod fees_tests {
+    use super::*;
+    use lasr_types::PayloadBuilder;
+
+    fn transaction(
+        transaction_type: TransactionType,
+        inputs: &str,
+        max_fee: Option<u64>,
+        priority_fee: Option<u64>,
+    ) -> Transaction {
+        PayloadBuilder::default()
+            .transaction_type(transaction_type)
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([3; 20])
+            .inputs(inputs.to_string())
+            .op(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(0))
+            .max_fee(max_fee.map(U256::from))
+            .priority_fee(priority_fee.map(U256::from))
+            .build()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn the_floor_adds_up_what_a_transaction_asks_for() {
+        let fees = FeeSchedule::new(100, 2, 50, 1000);
+        let call = transaction(TransactionType::Call(U256::zero()), "abcd", None, Some(7));
+        let estimate = fees.estimate(&call);
+        assert_eq!(estimate.execution_class, ExecutionClass::Call);
+        assert_eq!(estimate.input_bytes, 4);
+        assert_eq!(estimate.input_fee, U256::from(8));
+        assert_eq!(estimate.floor, U256::from(158));
+        assert_eq!(estimate.max_fee, U256::from(165));
+        assert_eq!(estimate.fee, U256::from(165));
+
+        let send = transaction(TransactionType::Send(U256::zero()), "", None, None);
+        assert_eq!(fees.floor(&send), U256::from(100));
+        let deploy = transaction(
+            TransactionType::RegisterProgram(U256::zero()),
+            "",
+            None,
+            None,
+        );
+        assert_eq!(fees.floor(&deploy), U256::from(1100));
+        let bridge_in = transaction(TransactionType::BridgeIn(U256::zero()), "abcd", None, None);
+        assert_eq!(fees.floor(&bridge_in), U256::zero());
+        let bridge_in = transaction(
+            TransactionType::BridgeIn(U256::zero()),
+            "",
+            Some(9),
+            Some(9),
+        );
+        assert_eq!(fees.charge(&bridge_in), U256::zero());
+    }
+
+    #[test]
+    fn the_charge_never_goes_over_the_max_fee() {
+        let fees = FeeSchedule::new(100, 0, 0, 0);
+        let send = |max_fee, priority_fee| {
+            transaction(
+                TransactionType::Send(U256::zero()),
+                "",
+                max_fee,
+                priority_fee,
+            )
+        };
+        assert_eq!(fees.charge(&send(Some(500), Some(20))), U256::from(120));
+        assert_eq!(fees.charge(&send(Some(110), Some(20))), U256::from(110));
+        // A transaction without a max fee pays nothing, and is only admitted
+        // while the floor is zero.
+        assert_eq!(fees.charge(&send(None, Some(20))), U256::zero());
+        assert_eq!(
+            FeeSchedule::default().charge(&send(Some(500), None)),
+            U256::zero()
+        );
+    }
+}
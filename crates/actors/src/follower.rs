@@ -0,0 +1,825 @@
+//! Following a primary node as a read replica.
+//!
+//! A primary streams every batch it finalizes, whole, to
+//! `lasr_subscribeBatches` subscribers. A follower subscribes, verifies each
+//! batch before applying the accounts it left to its own replica, and serves
+//! what it has applied read-only, turning every write away as `ReadOnly`.
+//!
+//! A batch is verified against the header it was recorded under: its
+//! transactions must hash to what they are keyed by and be those the header
+//! names, and its state, transactions and events roots are recomputed from
+//! its contents. Batches are chained by id, each following the one applied
+//! last, by the state root each records of the one before it, and by the
+//! accounts they leave, none going back on the nonce the replica holds. A
+//! sample of its transactions then has its inclusion proofs replayed as a
+//! light client would check them, and must name accounts the batch holds.
+//!
+//! A replica opened in a directory keeps the records of the batches it
+//! applied in a [`BatchIndex`] there, and the accounts they left in a
+//! [`RecordLog`] beside it, so a follower restarted carries on from the
+//! batch it applied last.
+//!
+//! Batches missed, while disconnected or by falling behind the stream, are
+//! read back from the DA layer under the headers the primary recorded them
+//! with, and verified alike, before the stream is applied again.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use ethereum_types::H256;
+use jsonrpsee::{
+    core::ClientError,
+    types::ErrorObjectOwned as RpcError,
+    ws_client::{WsClient, WsClientBuilder},
+    RpcModule,
+};
+use lasr_messages::{verify_receipt, BatchStatus, RpcErrorCode};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Account, AccountState, AccountType, Address};
+use prometheus::{IntCounter, IntGauge};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+use crate::{
+    metrics, parse_address,
+    record_log::{RecordLog, RecordLogError},
+    Batch, BatchIndex, BatchIndexError, BatchInfo, BatchRecord, DaClient, Genesis, Subscription,
+};
+
+/// Batches buffered for each `lasr_subscribeBatches` subscriber. A batch is
+/// up to half a megabyte, so a follower further behind is told how many it
+/// missed and reads them from the DA layer instead.
+pub const DEFAULT_BATCH_FEED_BUFFER: usize = 16;
+/// One in how many of each batch's transactions has its inclusion replayed.
+pub const DEFAULT_FOLLOWER_WITNESS_SAMPLE: u64 = 4;
+pub const DEFAULT_FOLLOWER_RECONNECT_MS: u64 = 1_000;
+pub const DEFAULT_FOLLOWER_REPLICA_DIR: &str = "./replica";
+
+/// The files a replica is kept in, in its directory.
+const REPLICA_BATCHES: &str = "batches.log";
+const REPLICA_ACCOUNTS: &str = "accounts.log";
+
+/// The writes a follower turns away.
+const WRITE_METHODS: [&str; 6] = [
+    "lasr_send",
+    "lasr_call",
+    "lasr_replace",
+    "lasr_sendRaw",
+    "lasr_registerProgram",
+    "lasr_faucet",
+];
+
+struct FollowerMetrics {
+    applied: IntCounter,
+    filled: IntCounter,
+    rejected: IntCounter,
+    latest_batch_id: IntGauge,
+}
+
+fn follower_metrics() -> &'static FollowerMetrics {
+    static METRICS: OnceLock<FollowerMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| FollowerMetrics {
+        applied: metrics::counter(
+            "follower",
+            "applied_total",
+            "Batches verified and applied from the primary.",
+        ),
+        filled: metrics::counter(
+            "follower",
+            "filled_total",
+            "Batches missed from the primary's stream and read from the DA layer.",
+        ),
+        rejected: metrics::counter(
+            "follower",
+            "rejected_total",
+            "Batches from the primary that failed verification.",
+        ),
+        latest_batch_id: metrics::gauge(
+            "follower",
+            "latest_batch_id",
+            "The id of the batch applied last.",
+        ),
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum FollowerError {
+    #[error(transparent)]
+    Rpc(#[from] ClientError),
+
+    #[error(transparent)]
+    Verification(#[from] BatchVerificationError),
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Index(#[from] BatchIndexError),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// Why a batch from the primary was not applied.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum BatchVerificationError {
+    #[error("batch {batch_id} does not follow the batch applied last, {expected} is next")]
+    OutOfOrder { expected: u64, batch_id: u64 },
+
+    #[error("batch {batch_id} does not chain to the state root of the batch applied last")]
+    Unchained { batch_id: u64 },
+
+    #[error("transaction {key} in batch {batch_id} does not hash to its key")]
+    TransactionHash { batch_id: u64, key: String },
+
+    #[error("batch {batch_id} does not hold what its header names")]
+    Contents { batch_id: u64 },
+
+    #[error("the {root} root of batch {batch_id} does not match its contents")]
+    Root { batch_id: u64, root: &'static str },
+
+    #[error("batch {batch_id} takes the nonce of {} back", address.to_full_string())]
+    NonceRegressed { batch_id: u64, address: Address },
+
+    #[error("the inclusion of {transaction_hash} in batch {batch_id} is not witnessed")]
+    Witness {
+        batch_id: u64,
+        transaction_hash: String,
+    },
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// A batch finalized, whole: the record it was finalized as, and the batch
+/// itself, as dispersed to the DA layer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealedBatch {
+    pub record: BatchRecord,
+    pub batch: Batch,
+}
+
+/// A batch sent to a `lasr_subscribeBatches` subscriber.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchNotification {
+    pub batch: SealedBatch,
+    /// Batches missed before this one because the subscriber fell behind.
+    pub dropped: u64,
+}
+
+/// The batches the batcher finalizes, published whole to those subscribed.
+#[derive(Debug, Clone)]
+pub struct BatchFeed {
+    published: broadcast::Sender<SealedBatch>,
+}
+
+impl Default for BatchFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_BATCH_FEED_BUFFER)
+    }
+}
+
+impl BatchFeed {
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            published: broadcast::channel(buffer.max(1)).0,
+        }
+    }
+
+    /// Publishes `batch`, finalized as `record`. It is only copied while
+    /// someone is subscribed.
+    pub fn publish(&self, record: &BatchRecord, batch: &Batch) {
+        if self.published.receiver_count() == 0 {
+            return;
+        }
+        // Only fails once every subscriber is gone.
+        let _ = self.published.send(SealedBatch {
+            record: record.clone(),
+            batch: batch.clone(),
+        });
+    }
+
+    pub fn subscribe(&self) -> Subscription<SealedBatch> {
+        Subscription::new(self.published.subscribe())
+    }
+}
+
+/// What a follower has applied: the accounts batches left, and the records
+/// of those batches.
+#[derive(Debug, Clone, Default)]
+pub struct Replica {
+    accounts: Arc<RwLock<HashMap<Address, Account>>>,
+    batches: BatchIndex,
+    /// Where the accounts applied are kept across restarts, if anywhere.
+    log: Option<Arc<Mutex<RecordLog<Account>>>>,
+}
+
+impl Replica {
+    /// A replica holding the accounts `genesis` starts the primary from.
+    pub fn from_genesis(genesis: &Genesis) -> Result<Self, FollowerError> {
+        let replica = Self::default();
+        replica.hold(genesis_accounts(genesis)?);
+        Ok(replica)
+    }
+
+    /// Opens the replica kept in `dir`, creating it if there is none, with
+    /// what it applied before. A replica applying from the first batch
+    /// starts from the accounts of `genesis`, if any.
+    pub fn open(dir: &Path, genesis: Option<&Genesis>) -> Result<Self, FollowerError> {
+        std::fs::create_dir_all(dir)?;
+        let batches = BatchIndex::open(&dir.join(REPLICA_BATCHES))?;
+        let path = dir.join(REPLICA_ACCOUNTS);
+        let mut applied = RecordLog::<Account>::read(&path)?;
+        if batches.latest().is_none() {
+            // Accounts logged by a first batch never recorded are applied
+            // again with it.
+            applied = match genesis {
+                Some(genesis) => genesis_accounts(genesis)?,
+                None => Vec::new(),
+            };
+        }
+        let replica = Self {
+            batches,
+            ..Self::default()
+        };
+        replica.hold(applied);
+        // Rewritten with only the latest of each account, so the log grows
+        // no larger than the accounts held.
+        let held: Vec<Account> = replica
+            .accounts
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        let log = RecordLog::create(&path, &held)?;
+        tracing::info!(
+            "opened the replica in {} at batch {}, holding {} accounts",
+            dir.display(),
+            replica.next_batch_id(),
+            held.len()
+        );
+        Ok(Self {
+            log: Some(Arc::new(Mutex::new(log))),
+            ..replica
+        })
+    }
+
+    /// Holds `accounts`, each in place of what was held under its address.
+    fn hold(&self, accounts: impl IntoIterator<Item = Account>) {
+        let mut held = self.accounts.write().unwrap_or_else(|e| e.into_inner());
+        for account in accounts {
+            held.insert(key(&account), account);
+        }
+    }
+
+    /// The account at `address`, as the batches applied left it. Program
+    /// accounts are held under the program's address.
+    pub fn account(&self, address: Address) -> Option<Account> {
+        self.accounts
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&address)
+            .cloned()
+    }
+
+    /// The records of the batches applied.
+    pub fn batches(&self) -> BatchIndex {
+        self.batches.clone()
+    }
+
+    /// The id of the batch to apply next.
+    pub fn next_batch_id(&self) -> u64 {
+        self.batches.next_batch_id()
+    }
+
+    /// Checks `sealed` may be applied next, replaying the inclusion of one
+    /// in `witness_sample` of its transactions, or none if it is 0.
+    pub fn verify(
+        &self,
+        sealed: &SealedBatch,
+        witness_sample: u64,
+    ) -> Result<(), BatchVerificationError> {
+        let SealedBatch { record, batch } = sealed;
+        let batch_id = record.batch_id;
+        let expected = self.next_batch_id();
+        if batch_id != expected {
+            return Err(BatchVerificationError::OutOfOrder { expected, batch_id });
+        }
+        let previous_state_root = self
+            .batches
+            .latest()
+            .map(|previous| previous.state_root)
+            .unwrap_or_default();
+        if record.previous_state_root != previous_state_root {
+            return Err(BatchVerificationError::Unchained { batch_id });
+        }
+
+        let transactions = batch.transactions();
+        if let Some(key) = transactions
+            .iter()
+            .find(|(key, transaction)| transaction.hash_string() != **key)
+            .map(|(key, _)| key.clone())
+        {
+            return Err(BatchVerificationError::TransactionHash { batch_id, key });
+        }
+        let mut hashes: Vec<String> = transactions.keys().cloned().collect();
+        hashes.sort();
+        if hashes != record.transaction_hashes || batch.accounts().len() != record.account_count {
+            return Err(BatchVerificationError::Contents { batch_id });
+        }
+
+        let custom = |e: crate::BatcherError| BatchVerificationError::Custom(e.to_string());
+        // Batches recorded before transactions and events were committed to
+        // hold zero for those roots.
+        let roots = [
+            (
+                "state",
+                record.state_root,
+                batch.state_root().map_err(custom)?,
+            ),
+            (
+                "transactions",
+                record.transactions_root,
+                batch.transactions_root().map_err(custom)?,
+            ),
+            (
+                "events",
+                record.events_root,
+                batch.events_root().map_err(custom)?,
+            ),
+        ];
+        for (root, recorded, recomputed) in roots {
+            if recorded != recomputed && (root == "state" || !recorded.is_zero()) {
+                return Err(BatchVerificationError::Root { batch_id, root });
+            }
+        }
+
+        {
+            let held = self.accounts.read().unwrap_or_else(|e| e.into_inner());
+            for account in batch.accounts().values() {
+                let address = key(account);
+                if held
+                    .get(&address)
+                    .is_some_and(|held| account.nonce() < held.nonce())
+                {
+                    return Err(BatchVerificationError::NonceRegressed { batch_id, address });
+                }
+            }
+        }
+
+        let header = record.header();
+        for proof in batch
+            .inclusion_proofs(batch_id)
+            .map_err(custom)?
+            .iter()
+            .filter(|proof| sampled(&proof.transaction_hash, witness_sample))
+        {
+            if proof.accounts.is_empty() || !verify_receipt(proof, &header) {
+                return Err(BatchVerificationError::Witness {
+                    batch_id,
+                    transaction_hash: format!("0x{}", hex::encode(proof.transaction_hash)),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies `sealed` and applies the accounts it left.
+    pub fn apply(
+        &self,
+        sealed: SealedBatch,
+        witness_sample: u64,
+    ) -> Result<(), BatchVerificationError> {
+        if let Err(e) = self.verify(&sealed, witness_sample) {
+            follower_metrics().rejected.inc();
+            return Err(e);
+        }
+        let SealedBatch { record, batch } = sealed;
+        let batch_id = record.batch_id;
+        let accounts: Vec<Account> = batch.accounts().into_values().collect();
+        // The accounts are logged before the batch is recorded, so a
+        // follower stopped in between applies the batch again.
+        if let Some(log) = &self.log {
+            log.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .append_synced(&accounts)
+                .map_err(|e| BatchVerificationError::Custom(e.to_string()))?;
+        }
+        self.hold(accounts);
+        self.batches
+            .finalized(record)
+            .map_err(|e| BatchVerificationError::Custom(e.to_string()))?;
+        let metrics = follower_metrics();
+        metrics.applied.inc();
+        metrics.latest_batch_id.set(batch_id as i64);
+        Ok(())
+    }
+}
+
+fn genesis_accounts(genesis: &Genesis) -> Result<Vec<Account>, FollowerError> {
+    genesis
+        .accounts()
+        .map_err(|e| FollowerError::Custom(e.to_string()))
+}
+
+/// The address `account` is held under.
+fn key(account: &Account) -> Address {
+    match account.account_type() {
+        AccountType::User => account.owner_address(),
+        AccountType::Program(program_address) => program_address,
+    }
+}
+
+/// Whether the inclusion of `transaction_hash` is among the one in `sample`
+/// replayed. Sampled by hash, so every follower replays the same ones.
+fn sampled(transaction_hash: &H256, sample: u64) -> bool {
+    sample > 0 && transaction_hash.to_low_u64_be() % sample == 0
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowerConfig {
+    /// The websocket URL of the primary's RPC server.
+    pub primary_url: String,
+    /// One in how many of each batch's transactions has its inclusion
+    /// replayed, every one at 1 and none at 0.
+    pub witness_sample: u64,
+    /// How long to wait before connecting again once the primary is lost.
+    pub reconnect_after: Duration,
+    /// Where the replica is kept across restarts.
+    pub replica_dir: PathBuf,
+}
+
+impl FollowerConfig {
+    pub fn new(primary_url: impl Into<String>) -> Self {
+        Self {
+            primary_url: primary_url.into(),
+            witness_sample: DEFAULT_FOLLOWER_WITNESS_SAMPLE,
+            reconnect_after: Duration::from_millis(DEFAULT_FOLLOWER_RECONNECT_MS),
+            replica_dir: PathBuf::from(DEFAULT_FOLLOWER_REPLICA_DIR),
+        }
+    }
+
+    pub fn with_witness_sample(mut self, witness_sample: u64) -> Self {
+        self.witness_sample = witness_sample;
+        self
+    }
+
+    pub fn with_reconnect_after(mut self, reconnect_after: Duration) -> Self {
+        self.reconnect_after = reconnect_after;
+        self
+    }
+
+    pub fn with_replica_dir(mut self, replica_dir: impl Into<PathBuf>) -> Self {
+        self.replica_dir = replica_dir.into();
+        self
+    }
+}
+
+/// Follows a primary into a replica, filling what it misses from the DA
+/// layer the primary disperses to.
+#[derive(Debug, Clone)]
+pub struct Follower {
+    config: FollowerConfig,
+    replica: Replica,
+    da: DaClient,
+}
+
+impl Follower {
+    pub fn new(config: FollowerConfig, replica: Replica, da: DaClient) -> Self {
+        Self {
+            config,
+            replica,
+            da,
+        }
+    }
+
+    pub fn replica(&self) -> Replica {
+        self.replica.clone()
+    }
+
+    /// Follows the primary for good, connecting again each time it is lost.
+    pub async fn run(self) {
+        loop {
+            match self.follow().await {
+                Ok(()) => tracing::warn!("the primary closed the batch stream"),
+                Err(e) => tracing::error!("stopped following the primary: {e}"),
+            }
+            tokio::time::sleep(self.config.reconnect_after).await;
+        }
+    }
+
+    /// Follows the primary until it is lost: subscribes to its batches,
+    /// fills in those finalized since the replica's last, then applies each
+    /// streamed, filling in any it missed first.
+    pub async fn follow(&self) -> Result<(), FollowerError> {
+        let client = WsClientBuilder::default()
+            .build(&self.config.primary_url)
+            .await?;
+        // Subscribed before catching up, so a batch finalized in between is
+        // streamed rather than missed.
+        let mut batches = client.subscribe_batches().await?;
+        let latest: Option<BatchRecord> = serde_json::from_str(&client.get_latest_batch().await?)?;
+        if let Some(latest) = latest {
+            self.fill(&client, latest.batch_id + 1).await?;
+        }
+        tracing::warn!(
+            "following {} from batch {}",
+            self.config.primary_url,
+            self.replica.next_batch_id()
+        );
+
+        while let Some(notification) = batches.next().await {
+            let BatchNotification { batch, dropped } = serde_json::from_value(notification?)?;
+            let batch_id = batch.record.batch_id;
+            if batch_id < self.replica.next_batch_id() {
+                continue;
+            }
+            if dropped > 0 {
+                tracing::warn!("fell {dropped} batches behind the primary's stream");
+            }
+            self.fill(&client, batch_id).await?;
+            self.replica.apply(batch, self.config.witness_sample)?;
+        }
+        Ok(())
+    }
+
+    /// Reads every batch the replica has not applied before `until` from the
+    /// DA layer, under the record the primary finalized it as, and applies
+    /// it. Returns how many were.
+    async fn fill(&self, client: &WsClient, until: u64) -> Result<usize, FollowerError> {
+        let mut filled = 0;
+        while self.replica.next_batch_id() < until {
+            let batch_id = self.replica.next_batch_id();
+            let record: BatchRecord =
+                serde_json::from_str(&client.get_batch(batch_id, None).await?)?;
+            let da = self.da.clone();
+            let blob = tokio::task::spawn_blocking(move || {
+                da.retrieve_blob(record.blob_commitment, record.blob_index)
+                    .map(|blob| (record, blob))
+            })
+            .await
+            .map_err(|e| FollowerError::Custom(e.to_string()))?;
+            let (record, blob) = blob?;
+            let batch = Batch::decode_batch(&blob).ok_or_else(|| {
+                FollowerError::Custom(format!(
+                    "the blob of batch {batch_id} does not decode to a batch"
+                ))
+            })?;
+            self.replica
+                .apply(SealedBatch { record, batch }, self.config.witness_sample)?;
+            follower_metrics().filled.inc();
+            filled += 1;
+        }
+        if filled > 0 {
+            tracing::warn!("filled in {filled} batches missed from the DA layer");
+        }
+        Ok(filled)
+    }
+}
+
+fn read_only() -> RpcError {
+    RpcError::owned(
+        RpcErrorCode::ReadOnly.code(),
+        "this node follows a primary and takes no writes",
+        None::<()>,
+    )
+}
+
+fn internal(e: impl std::fmt::Display) -> RpcError {
+    RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+}
+
+/// The methods a follower serves from `replica`: `getAccount`, `getBatch`
+/// and `getLatestBatch`, answered as the primary answers them, with every
+/// batch finalized as the primary finalized it. Writes are turned away.
+pub fn read_only_rpc(replica: Replica) -> Result<RpcModule<Replica>, FollowerError> {
+    let register = |e: jsonrpsee::core::RegisterMethodError| FollowerError::Custom(e.to_string());
+    let info = |record: BatchRecord| BatchInfo {
+        record,
+        status: BatchStatus::Finalized,
+        accounts: None,
+    };
+    let mut module = RpcModule::new(replica);
+    module
+        .register_method("lasr_getAccount", |params, replica| {
+            let address: String = params.one()?;
+            let address = parse_address("address", &address)?;
+            serde_json::to_string(&AccountState::new(
+                address,
+                replica.account(address).as_ref(),
+            ))
+            .map_err(internal)
+        })
+        .map_err(register)?;
+    module
+        .register_method("lasr_getBatch", move |params, replica| {
+            let mut params = params.sequence();
+            let batch_id: u64 = params.next()?;
+            if params.optional_next::<bool>()?.unwrap_or(false) {
+                return Err(RpcError::owned(
+                    RpcErrorCode::Disabled.code(),
+                    "a follower does not keep the accounts of batches",
+                    None::<()>,
+                ));
+            }
+            let record = replica.batches.get(batch_id).ok_or_else(|| {
+                RpcError::owned(
+                    RpcErrorCode::NotFound.code(),
+                    format!("no batch {batch_id} has been applied"),
+                    None::<()>,
+                )
+            })?;
+            serde_json::to_string(&info(record)).map_err(internal)
+        })
+        .map_err(register)?;
+    module
+        .register_method("lasr_getLatestBatch", move |_, replica| {
+            serde_json::to_string(&replica.batches.latest().map(info)).map_err(internal)
+        })
+        .map_err(register)?;
+    for method in WRITE_METHODS {
+        module
+            .register_method(method, |_, _| Err::<(), _>(read_only()))
+            .map_err(register)?;
+    }
+    Ok(module)
+}
+
+#[cfg(test)]
+mod follower_tests {
+    use super::*;
+    use lasr_types::{TransactionBuilder, TransactionType, U256};
+
+    fn account(byte: u8, nonce: u64) -> Account {
+        let mut account = Account::new(Address::new([byte; 20]));
+        for _ in 0..nonce {
+            account.increment_nonce();
+        }
+        account
+    }
+
+    /// Batch `batch_id`, sealed as the batcher would seal a send from an
+    /// account at `nonce`, after the batch sealed at `nonce - 1`.
+    fn sealed(batch_id: u64, nonce: u64) -> SealedBatch {
+        let previous_state_root = match nonce {
+            0 | 1 => H256::zero(),
+            nonce => sealed(batch_id, nonce - 1).record.state_root,
+        };
+        SealedBatch {
+            record: BatchRecord {
+                previous_state_root,
+                ..unchained(batch_id, nonce).record
+            },
+            ..unchained(batch_id, nonce)
+        }
+    }
+
+    fn unchained(batch_id: u64, nonce: u64) -> SealedBatch {
+        let transaction = TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(nonce)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap();
+        let mut batch = Batch::new();
+        batch.insert_transaction(transaction.clone()).unwrap();
+        batch.insert_account(account(1, nonce)).unwrap();
+        batch.insert_account(account(2, 0)).unwrap();
+        SealedBatch {
+            record: BatchRecord {
+                batch_id,
+                state_root: batch.state_root().unwrap(),
+                previous_state_root: H256::zero(),
+                transactions_root: batch.transactions_root().unwrap(),
+                events_root: batch.events_root().unwrap(),
+                blob_commitment: H256::repeat_byte(7),
+                blob_index: batch_id as u128,
+                withdrawals: Vec::new(),
+                account_count: 2,
+                transaction_hashes: vec![transaction.hash_string()],
+                sealed_at: 0,
+                finalized_at: 0,
+            },
+            batch,
+        }
+    }
+
+    #[test]
+    fn batches_are_applied_in_order_and_witnessed() {
+        let replica = Replica::default();
+        replica.apply(sealed(0, 1), 1).unwrap();
+        assert_eq!(
+            replica.apply(sealed(2, 3), 1),
+            Err(BatchVerificationError::OutOfOrder {
+                expected: 1,
+                batch_id: 2
+            })
+        );
+        replica.apply(sealed(1, 2), 1).unwrap();
+        assert_eq!(replica.next_batch_id(), 2);
+        assert_eq!(
+            replica.account(Address::new([1; 20])).unwrap().nonce(),
+            U256::from(2)
+        );
+        // A batch taking an account's nonce back does not chain on.
+        let regressed = SealedBatch {
+            record: BatchRecord {
+                previous_state_root: sealed(1, 2).record.state_root,
+                ..sealed(2, 1).record
+            },
+            ..sealed(2, 1)
+        };
+        assert_eq!(
+            replica.apply(regressed, 1),
+            Err(BatchVerificationError::NonceRegressed {
+                batch_id: 2,
+                address: Address::new([1; 20])
+            })
+        );
+    }
+
+    #[test]
+    fn a_batch_not_chained_to_the_one_applied_last_is_rejected() {
+        let replica = Replica::default();
+        assert_eq!(
+            replica.verify(&sealed(0, 2), 1),
+            Err(BatchVerificationError::Unchained { batch_id: 0 })
+        );
+        replica.apply(sealed(0, 1), 1).unwrap();
+        assert_eq!(
+            replica.verify(&unchained(1, 2), 1),
+            Err(BatchVerificationError::Unchained { batch_id: 1 })
+        );
+        replica.verify(&sealed(1, 2), 1).unwrap();
+    }
+
+    #[test]
+    fn a_replica_reopened_carries_on_from_the_batch_applied_last() {
+        let dir = std::env::temp_dir().join(format!("replica_{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let replica = Replica::open(&dir, None).unwrap();
+        replica.apply(sealed(0, 1), 1).unwrap();
+        replica.apply(sealed(1, 2), 1).unwrap();
+        drop(replica);
+
+        let replica = Replica::open(&dir, None).unwrap();
+        assert_eq!(replica.next_batch_id(), 2);
+        assert_eq!(
+            replica.account(Address::new([1; 20])).unwrap().nonce(),
+            U256::from(2)
+        );
+        replica.apply(sealed(2, 3), 1).unwrap();
+        drop(replica);
+        assert_eq!(Replica::open(&dir, None).unwrap().next_batch_id(), 3);
+        // Reopened, each account is logged once however many batches left it.
+        let logged = RecordLog::<Account>::read(&dir.join(REPLICA_ACCOUNTS)).unwrap();
+        assert_eq!(logged.len(), 2);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_batch_not_matching_its_header_is_rejected() {
+        let replica = Replica::default();
+        let mut tampered = sealed(0, 1);
+        tampered.record.state_root = H256::repeat_byte(1);
+        assert_eq!(
+            replica.verify(&tampered, 1),
+            Err(BatchVerificationError::Root {
+                batch_id: 0,
+                root: "state"
+            })
+        );
+
+        let mut tampered = sealed(0, 1);
+        tampered.batch.insert_account(account(3, 0)).unwrap();
+        assert_eq!(
+            replica.verify(&tampered, 1),
+            Err(BatchVerificationError::Contents { batch_id: 0 })
+        );
+
+        // Batches recorded before events were committed to are still taken.
+        let mut legacy = sealed(0, 1);
+        legacy.record.events_root = H256::zero();
+        replica.verify(&legacy, 1).unwrap();
+        assert_eq!(replica.next_batch_id(), 0);
+    }
+}
@@ -0,0 +1,459 @@
+//! The accounts and programs a chain starts with.
+//!
+//! A genesis file, TOML or JSON by its extension, sets the chain id, the
+//! programs registered from the start with the content their artifacts are
+//! found by, and the accounts with what they hold of VERSE or of those
+//! programs. Amounts are decimal or 0x hex strings:
+//!
+//! ```toml
+//! chain_id = 1279349586
+//!
+//! [[programs]]
+//! program_id = "0x00000000000000000000000000000000000000aa"
+//! owner = "0x00000000000000000000000000000000000000bb"
+//! content_id = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi"
+//! metadata = { symbol = "TKN" }
+//!
+//! [[accounts]]
+//! address = "0x00000000000000000000000000000000000000cc"
+//! balances = { "0x0000000000000000000000000000000000000001" = "1000000" }
+//! ```
+//!
+//! A program may also name an `auth_program`, another the genesis registers,
+//! to authorize the transactions sent from its account.
+//!
+//! The genesis is applied to storage the first time a node starts, before
+//! any actor is, and its hash is recorded only once every account is
+//! written. A node stopped part way through writes the same accounts again
+//! on its next start, so the genesis is in storage whole or not at all. A
+//! node started on storage recording a different genesis refuses to start.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use lasr_types::{
+    Account, AccountBuilder, AccountType, Address, ArbitraryData, Metadata, PersistenceStore, U256,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+
+use crate::{
+    encoding::Encoding, mint_verse_supply, new_verse_program_account, AccountValue, ProgramRecord,
+    ProgramRegistry, StorageRef, VERSE_ADDR,
+};
+
+/// The key the hash of the genesis a node was started from is stored under.
+pub const STORAGE_GENESIS_HASH_KEY: &str = "genesis_hash";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GenesisError {
+    #[error("failed to read genesis file {path}: {reason}")]
+    Read { path: String, reason: String },
+
+    #[error("failed to parse genesis file: {0}")]
+    Parse(String),
+
+    #[error("invalid genesis: {0}")]
+    Invalid(String),
+
+    #[error("storage was started from genesis {recorded}, not {genesis}")]
+    Mismatch { recorded: String, genesis: String },
+
+    #[error("{key} is already in storage and was not put there by this genesis")]
+    Occupied { key: String },
+
+    #[error("genesis storage error: {0}")]
+    Storage(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// Whether applying a genesis wrote it, or found it recorded already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenesisOutcome {
+    Applied,
+    AlreadyApplied,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Genesis {
+    pub chain_id: u64,
+    #[serde(default)]
+    pub programs: Vec<GenesisProgram>,
+    #[serde(default)]
+    pub accounts: Vec<GenesisAccount>,
+}
+
+/// A program registered from the start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisProgram {
+    pub program_id: Address,
+    pub owner: Address,
+    /// The content hash of the program's artifact.
+    pub content_id: String,
+    /// Set on the program account beside the content id.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// The program authorizing the transactions sent from the program
+    /// account, one the genesis registers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_program: Option<Address>,
+}
+
+/// An account and what it holds of each program, by program id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisAccount {
+    pub address: Address,
+    #[serde(default, deserialize_with = "amounts")]
+    pub balances: BTreeMap<Address, U256>,
+}
+
+fn amounts<'de, D>(deserializer: D) -> Result<BTreeMap<Address, U256>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<Address, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(program_id, amount)| {
+            let parsed = match amount.strip_prefix("0x") {
+                Some(hex) => U256::from_str_radix(hex, 16).ok(),
+                None => U256::from_dec_str(&amount).ok(),
+            };
+            parsed.map(|parsed| (program_id, parsed)).ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "amount {amount} is not a decimal or 0x hex number"
+                ))
+            })
+        })
+        .collect()
+}
+
+/// The key an account is stored under, its program id for a program
+/// account.
+fn storage_key(account: &Account) -> String {
+    match account.account_type() {
+        AccountType::Program(program_id) => program_id.to_full_string(),
+        AccountType::User => account.owner_address().to_full_string(),
+    }
+}
+
+impl GenesisProgram {
+    /// The program account, as a registration committed by the batcher
+    /// would have built it.
+    pub fn account(&self) -> Result<Account, GenesisError> {
+        let mut metadata = Metadata::new();
+        for (key, value) in &self.metadata {
+            metadata.inner_mut().insert(key.clone(), value.clone());
+        }
+        metadata
+            .inner_mut()
+            .insert("content_id".to_string(), self.content_id.clone());
+        AccountBuilder::default()
+            .account_type(AccountType::Program(self.program_id))
+            .owner_address(self.owner)
+            .nonce(U256::from(0))
+            .programs(BTreeMap::new())
+            .program_namespace(None)
+            .program_account_linked_programs(BTreeSet::new())
+            .program_account_data(ArbitraryData::new())
+            .program_account_metadata(metadata)
+            .auth_program(self.auth_program)
+            .try_build()
+            .map_err(|e| GenesisError::Custom(e.to_string()))
+    }
+}
+
+impl Genesis {
+    /// Reads the genesis at `path`, as JSON if it ends in `.json` and as
+    /// TOML otherwise, and checks it.
+    pub fn load(path: &Path) -> Result<Self, GenesisError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| GenesisError::Read {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+        let genesis: Genesis = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => {
+                serde_json::from_str(&contents).map_err(|e| GenesisError::Parse(e.to_string()))?
+            }
+            _ => toml::from_str(&contents).map_err(|e| GenesisError::Parse(e.to_string()))?,
+        };
+        genesis.validate()?;
+        Ok(genesis)
+    }
+
+    /// Checks every account and program is listed once, and that balances,
+    /// and auth programs, are only of VERSE or of programs the genesis
+    /// registers.
+    pub fn validate(&self) -> Result<(), GenesisError> {
+        if self.chain_id == 0 {
+            return Err(GenesisError::Invalid("chain_id must not be 0".to_string()));
+        }
+        let mut programs = BTreeSet::new();
+        for program in &self.programs {
+            if program.program_id == VERSE_ADDR {
+                return Err(GenesisError::Invalid(
+                    "VERSE cannot be registered as a program".to_string(),
+                ));
+            }
+            if program.content_id.is_empty() {
+                return Err(GenesisError::Invalid(format!(
+                    "program {} has no content_id",
+                    program.program_id.to_full_string()
+                )));
+            }
+            if !programs.insert(program.program_id) {
+                return Err(GenesisError::Invalid(format!(
+                    "program {} is listed twice",
+                    program.program_id.to_full_string()
+                )));
+            }
+        }
+        if let Some(program) = self.programs.iter().find(|program| {
+            program
+                .auth_program
+                .is_some_and(|auth_program| !programs.contains(&auth_program))
+        }) {
+            return Err(GenesisError::Invalid(format!(
+                "the auth program of program {} is not registered at genesis",
+                program.program_id.to_full_string()
+            )));
+        }
+        let mut accounts = BTreeSet::new();
+        for account in &self.accounts {
+            if account.address == VERSE_ADDR || programs.contains(&account.address) {
+                return Err(GenesisError::Invalid(format!(
+                    "account {} is a program id",
+                    account.address.to_full_string()
+                )));
+            }
+            if !accounts.insert(account.address) {
+                return Err(GenesisError::Invalid(format!(
+                    "account {} is listed twice",
+                    account.address.to_full_string()
+                )));
+            }
+            if let Some(program_id) = account
+                .balances
+                .keys()
+                .find(|program_id| **program_id != VERSE_ADDR && !programs.contains(program_id))
+            {
+                return Err(GenesisError::Invalid(format!(
+                    "account {} holds {}, which is neither VERSE nor a program registered at genesis",
+                    account.address.to_full_string(),
+                    program_id.to_full_string()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The keccak hash of the genesis, over its canonical JSON form, so the
+    /// same genesis hashes the same whichever format it was written in.
+    pub fn hash(&self) -> Result<String, GenesisError> {
+        let canonical =
+            serde_json::to_vec(self).map_err(|e| GenesisError::Custom(e.to_string()))?;
+        Ok(format!("0x{}", hex::encode(Keccak256::digest(canonical))))
+    }
+
+    /// Every account the genesis puts in storage: its user accounts, its
+    /// program accounts, and the VERSE program account recording the VERSE
+    /// the user accounts hold as the supply.
+    pub fn accounts(&self) -> Result<Vec<Account>, GenesisError> {
+        self.validate()?;
+        let programs = self
+            .programs
+            .iter()
+            .map(|program| Ok((program.program_id, program.account()?)))
+            .collect::<Result<BTreeMap<_, _>, GenesisError>>()?;
+        let mut verse =
+            new_verse_program_account().map_err(|e| GenesisError::Custom(e.to_string()))?;
+        let mut accounts = Vec::with_capacity(self.accounts.len() + programs.len() + 1);
+        for genesis_account in &self.accounts {
+            let mut account = Account::new(genesis_account.address);
+            for (program_id, amount) in &genesis_account.balances {
+                account
+                    .apply_transfer_to_instruction(
+                        program_id,
+                        &Some(*amount),
+                        &vec![],
+                        programs.get(program_id),
+                    )
+                    .map_err(|e| GenesisError::Custom(e.to_string()))?;
+                if *program_id == VERSE_ADDR {
+                    mint_verse_supply(&mut verse, *amount)
+                        .map_err(|e| GenesisError::Invalid(e.to_string()))?;
+                }
+            }
+            accounts.push(account);
+        }
+        accounts.extend(programs.into_values());
+        accounts.push(verse);
+        Ok(accounts)
+    }
+
+    /// Records the programs the genesis registers in `registry`, which
+    /// holds only what the node registered since it started.
+    pub fn register_programs(&self, registry: &ProgramRegistry) -> Result<(), GenesisError> {
+        let hash = self.hash()?;
+        for program in &self.programs {
+            registry.at_genesis(ProgramRecord::at_genesis(&program.account()?, &hash));
+        }
+        Ok(())
+    }
+
+    /// Writes the genesis to `storage` unless it is recorded there already,
+    /// refusing storage started from another genesis, or holding accounts
+    /// this genesis would overwrite.
+    pub async fn apply(&self, storage: &StorageRef) -> Result<GenesisOutcome, GenesisError> {
+        self.apply_encoded(storage, Encoding::default()).await
+    }
+
+    /// As [`Genesis::apply`], writing the accounts encoded in `encoding`.
+    pub async fn apply_encoded(
+        &self,
+        storage: &StorageRef,
+        encoding: Encoding,
+    ) -> Result<GenesisOutcome, GenesisError> {
+        let hash = self.hash()?;
+        if let Some(recorded) = recorded_genesis_hash(storage).await? {
+            return match recorded == hash {
+                true => Ok(GenesisOutcome::AlreadyApplied),
+                false => Err(GenesisError::Mismatch {
+                    recorded,
+                    genesis: hash,
+                }),
+            };
+        }
+
+        let mut values = Vec::new();
+        for account in self.accounts()? {
+            let key = storage_key(&account);
+            let value = AccountValue { account };
+            // Only what an earlier, interrupted, application of this genesis
+            // wrote may already be there, in whichever encoding it wrote.
+            if stored(storage, &key)
+                .await?
+                .is_some_and(|stored| AccountValue::decode(&stored).ok().as_ref() != Some(&value))
+            {
+                return Err(GenesisError::Occupied { key });
+            }
+            let value = encoding
+                .encode(&value)
+                .map_err(|e| GenesisError::Custom(e.to_string()))?;
+            values.push((key, value));
+        }
+        for (key, value) in values {
+            PersistenceStore::put(storage, key.into(), value)
+                .await
+                .map_err(|e| GenesisError::Storage(e.to_string()))?;
+        }
+        PersistenceStore::put(
+            storage,
+            STORAGE_GENESIS_HASH_KEY.to_string().into(),
+            hash.into_bytes(),
+        )
+        .await
+        .map_err(|e| GenesisError::Storage(e.to_string()))?;
+        Ok(GenesisOutcome::Applied)
+    }
+}
+
+async fn stored(storage: &StorageRef, key: &str) -> Result<Option<Vec<u8>>, GenesisError> {
+    let stored = PersistenceStore::get(storage, key.to_string().into()).await;
+    // The mock store answers a key it does not hold with an error.
+    #[cfg(feature = "mock_storage")]
+    let stored = stored.ok().flatten();
+    #[cfg(not(feature = "mock_storage"))]
+    let stored = stored.map_err(|e| GenesisError::Storage(e.to_string()))?;
+    Ok(stored)
+}
+
+/// The hash of the genesis `storage` was started from, if it was.
+pub async fn recorded_genesis_hash(storage: &StorageRef) -> Result<Option<String>, GenesisError> {
+    stored(storage, STORAGE_GENESIS_HASH_KEY)
+        .await?
+        .map(|hash| String::from_utf8(hash).map_err(|e| GenesisError::Storage(e.to_string())))
+        .transpose()
+}
+
+#[cfg(test)]
+mod genesis_tests {
+    use super::*;
+
+    const GENESIS: &str = r#"
+        chain_id = 7
+
+        [[programs]]
+        program_id = "0x00000000000000000000000000000000000000aa"
+        owner = "0x00000000000000000000000000000000000000bb"
+        content_id = "bafy"
+
+        [[accounts]]
+        address = "0x00000000000000000000000000000000000000cc"
+        balances = { "0x0000000000000000000000000000000000000001" = "1000", "0x00000000000000000000000000000000000000aa" = "0x10" }
+    "#;
+
+    #[test]
+    fn toml_and_json_of_the_same_genesis_hash_the_same() {
+        let from_toml: Genesis = toml::from_str(GENESIS).unwrap();
+        assert_eq!(
+            from_toml.accounts[0].balances.values().collect::<Vec<_>>(),
+            vec![&U256::from(1000), &U256::from(16)]
+        );
+        let json = serde_json::json!({
+            "chain_id": 7,
+            "programs": [{
+                "program_id": "0x00000000000000000000000000000000000000aa",
+                "owner": "0x00000000000000000000000000000000000000bb",
+                "content_id": "bafy",
+            }],
+            "accounts": [{
+                "address": "0x00000000000000000000000000000000000000cc",
+                "balances": {
+                    "0x00000000000000000000000000000000000000aa": "16",
+                    "0x0000000000000000000000000000000000000001": "0x3e8",
+                },
+            }],
+        });
+        let from_json: Genesis = serde_json::from_value(json).unwrap();
+        assert_eq!(from_toml.hash().unwrap(), from_json.hash().unwrap());
+
+        let mut other = from_toml.clone();
+        other.chain_id = 8;
+        assert_ne!(other.hash().unwrap(), from_toml.hash().unwrap());
+    }
+
+    #[test]
+    fn balances_of_programs_not_registered_are_invalid() {
+        let mut genesis: Genesis = toml::from_str(GENESIS).unwrap();
+        assert!(genesis.validate().is_ok());
+        genesis.programs.clear();
+        assert!(matches!(
+            genesis.validate(),
+            Err(GenesisError::Invalid(reason)) if reason.contains("0x00000000000000000000000000000000000000aa")
+        ));
+        assert!(toml::from_str::<Genesis>("chain_id = 7\nforks = []").is_err());
+    }
+
+    #[test]
+    fn an_auth_program_must_be_registered() {
+        let mut genesis: Genesis = toml::from_str(GENESIS).unwrap();
+        let hash = genesis.hash().unwrap();
+        genesis.programs[0].auth_program = Some(Address::new([0xab; 20]));
+        assert!(matches!(
+            genesis.validate(),
+            Err(GenesisError::Invalid(reason)) if reason.contains("auth program")
+        ));
+
+        // A program may authorize its own account.
+        genesis.programs[0].auth_program = Some(genesis.programs[0].program_id);
+        assert!(genesis.validate().is_ok());
+        assert_ne!(genesis.hash().unwrap(), hash);
+        let account = genesis.programs[0].account().unwrap();
+        assert_eq!(account.auth_program(), Some(genesis.programs[0].program_id));
+    }
+}
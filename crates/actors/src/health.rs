@@ -0,0 +1,327 @@
+//! What load balancers and orchestrators ask of the node.
+//!
+//! `health` answers whenever the process is up. `ready` checks the account
+//! cache, the executor's runtime, the DA layer and the settlement providers,
+//! and reports the node not ready once it has started shutting down, so it is
+//! taken out of rotation before its connections are drained.
+use crate::metrics;
+use lasr_messages::{ActorType, ExecutorHealth, ExecutorStatus};
+use prometheus::{IntCounterVec, IntGauge};
+use ractor::ActorStatus;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::signal::unix::{signal, SignalKind};
+
+use eo_listener::SettlementProvidersStatus;
+
+pub const DEFAULT_DA_FAILURE_THRESHOLD: u32 = 3;
+pub const DEFAULT_SHUTDOWN_DRAIN_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BreakerState {
+    #[default]
+    Closed,
+    /// Enough requests to the DA layer failed in a row for it to be taken as
+    /// unreachable, until one succeeds.
+    Open,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaStatus {
+    pub breaker: BreakerState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+struct DaMetrics {
+    requests: IntCounterVec,
+    breaker_open: IntGauge,
+}
+
+fn da_metrics() -> &'static DaMetrics {
+    static METRICS: OnceLock<DaMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| DaMetrics {
+        requests: metrics::counter_vec(
+            "da_client",
+            "requests_total",
+            "Requests made to the DA layer, by outcome.",
+            &["outcome"],
+        ),
+        breaker_open: metrics::gauge(
+            "da_client",
+            "breaker_open",
+            "Whether the DA layer is taken as unreachable, 1 if so.",
+        ),
+    })
+}
+
+/// The outcomes of the DA client's requests to the DA layer.
+#[derive(Debug, Clone)]
+pub struct DaHealth {
+    status: Arc<Mutex<DaStatus>>,
+    /// Failures in a row that open the breaker.
+    threshold: u32,
+}
+
+impl Default for DaHealth {
+    fn default() -> Self {
+        Self::new(DEFAULT_DA_FAILURE_THRESHOLD)
+    }
+}
+
+impl DaHealth {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            status: Arc::default(),
+            threshold: threshold.max(1),
+        }
+    }
+
+    pub fn succeeded(&self) {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.consecutive_failures = 0;
+        status.breaker = BreakerState::Closed;
+        let metrics = da_metrics();
+        metrics.requests.with_label_values(&["ok"]).inc();
+        metrics.breaker_open.set(0);
+    }
+
+    pub fn failed(&self, error: impl std::fmt::Display) {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.consecutive_failures = status.consecutive_failures.saturating_add(1);
+        status.last_error = Some(error.to_string());
+        if status.consecutive_failures >= self.threshold {
+            status.breaker = BreakerState::Open;
+        }
+        let metrics = da_metrics();
+        metrics.requests.with_label_values(&["error"]).inc();
+        metrics
+            .breaker_open
+            .set(i64::from(status.breaker == BreakerState::Open));
+    }
+
+    /// Records the outcome of `result`, handing it back.
+    pub fn record<T, E: std::fmt::Display>(&self, result: Result<T, E>) -> Result<T, E> {
+        match &result {
+            Ok(_) => self.succeeded(),
+            Err(e) => self.failed(e),
+        }
+        result
+    }
+
+    pub fn status(&self) -> DaStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Set once the node starts shutting down, and never unset. Counts the
+/// writes admitted before then that have yet to be answered.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    started: Arc<AtomicBool>,
+    writes: Arc<AtomicUsize>,
+}
+
+/// A write admitted before the node started shutting down, counted as in
+/// flight until it is dropped.
+#[derive(Debug)]
+pub struct Admitted {
+    writes: Arc<AtomicUsize>,
+}
+
+impl Drop for Admitted {
+    fn drop(&mut self) {
+        self.writes.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    /// Waits for the process to be interrupted or terminated.
+    pub async fn signalled() -> std::io::Result<()> {
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            interrupted = tokio::signal::ctrl_c() => interrupted,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+
+    pub fn start(&self) {
+        self.started.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    /// Admits a write to be answered before the node stops, or `None` once
+    /// it has started shutting down. The write is counted before the check,
+    /// so one admitted as shutdown starts is still waited for.
+    pub fn admit(&self) -> Option<Admitted> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        let admitted = Admitted {
+            writes: Arc::clone(&self.writes),
+        };
+        (!self.is_started()).then_some(admitted)
+    }
+
+    /// Writes admitted that have yet to be answered.
+    pub fn writes_in_flight(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentStatus {
+    pub ready: bool,
+    /// Why the component is not ready.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ComponentStatus {
+    fn ready() -> Self {
+        Self {
+            ready: true,
+            detail: None,
+        }
+    }
+
+    fn not_ready(detail: impl Into<String>) -> Self {
+        Self {
+            ready: false,
+            detail: Some(detail.into()),
+        }
+    }
+
+    /// Whether the actor `actor_type` is running.
+    pub fn actor(actor_type: ActorType) -> Self {
+        match ractor::registry::where_is(actor_type.to_string()) {
+            Some(actor) if actor.get_status() == ActorStatus::Running => Self::ready(),
+            Some(actor) => Self::not_ready(format!("{actor_type} is {:?}", actor.get_status())),
+            None => Self::not_ready(format!("{actor_type} is not running")),
+        }
+    }
+
+    /// Ready while the runtime is healthy, or failing too rarely yet to be
+    /// restarted.
+    pub fn executor(health: &ExecutorHealth) -> Self {
+        match health.status {
+            ExecutorStatus::Healthy | ExecutorStatus::Degraded => Self::ready(),
+            status => Self::not_ready(
+                health
+                    .last_error
+                    .clone()
+                    .unwrap_or_else(|| format!("executor is {status:?}")),
+            ),
+        }
+    }
+
+    /// Ready while the DA client is running and its breaker is closed.
+    pub fn da(status: &DaStatus) -> Self {
+        let actor = Self::actor(ActorType::DaClient);
+        if actor.ready && status.breaker == BreakerState::Open {
+            return Self::not_ready(format!(
+                "{} requests to the DA layer failed in a row, the last with: {}",
+                status.consecutive_failures,
+                status.last_error.as_deref().unwrap_or("no error")
+            ));
+        }
+        actor
+    }
+
+    /// Ready while any of the eo_server's settlement layer providers is up.
+    pub fn settlement_providers(status: &SettlementProvidersStatus) -> Self {
+        if status.providers.iter().any(|provider| !provider.down) {
+            Self::ready()
+        } else {
+            Self::not_ready("every settlement provider is down")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    /// Whether every component is ready and the node is not shutting down.
+    pub ready: bool,
+    pub shutting_down: bool,
+    pub components: BTreeMap<&'static str, ComponentStatus>,
+}
+
+impl Readiness {
+    pub fn new(shutting_down: bool, components: BTreeMap<&'static str, ComponentStatus>) -> Self {
+        Self {
+            ready: !shutting_down && components.values().all(|component| component.ready),
+            shutting_down,
+            components,
+        }
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::*;
+    use eo_listener::ProviderStatus;
+
+    #[test]
+    fn the_breaker_opens_after_enough_failures_in_a_row() {
+        let health = DaHealth::new(2);
+        health.failed("timed out");
+        health.succeeded();
+        health.failed("timed out");
+        assert_eq!(health.status().breaker, BreakerState::Closed);
+        let _ = health.record(Err::<(), _>("connection refused"));
+        assert_eq!(
+            health.status(),
+            DaStatus {
+                breaker: BreakerState::Open,
+                consecutive_failures: 2,
+                last_error: Some("connection refused".to_string()),
+            }
+        );
+        let _ = health.record(Ok::<_, String>(()));
+        assert_eq!(health.status().breaker, BreakerState::Closed);
+        assert_eq!(health.status().consecutive_failures, 0);
+    }
+
+    #[test]
+    fn a_node_shutting_down_is_not_ready() {
+        let components = BTreeMap::from([("executor", ComponentStatus::ready())]);
+        assert!(Readiness::new(false, components.clone()).ready);
+        assert!(!Readiness::new(true, components.clone()).ready);
+
+        let mut components = components;
+        components.insert("da", ComponentStatus::not_ready("down"));
+        assert!(!Readiness::new(false, components).ready);
+    }
+
+    #[test]
+    fn the_eo_server_is_ready_while_any_provider_is_up() {
+        let provider = |down| ProviderStatus {
+            url: "https://rpc.example".to_string(),
+            active: false,
+            down,
+            requests: 0,
+            errors: 0,
+            error_rate: 0.0,
+            last_error: None,
+        };
+        let status = |providers| SettlementProvidersStatus {
+            switches: 0,
+            providers,
+        };
+        assert!(
+            ComponentStatus::settlement_providers(&status(vec![provider(true), provider(false)]))
+                .ready
+        );
+        assert!(!ComponentStatus::settlement_providers(&status(vec![provider(true)])).ready);
+    }
+}
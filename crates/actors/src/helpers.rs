@@ -1,13 +1,14 @@
+use std::collections::{HashMap, VecDeque};
 use std::error::Error as StdError;
 use std::fmt::Debug;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::AccountCacheError;
 use futures::future::BoxFuture;
 use futures::stream::{FuturesOrdered, FuturesUnordered};
-use lasr_messages::{AccountCacheMessage, ActorType};
-use lasr_types::{Account, Address};
+use lasr_messages::{AccountCacheMessage, ActorType, TransactionResponse};
+use lasr_types::{Account, Address, ExecutionAttestation, Transaction};
 use ractor::concurrency::{oneshot, OneshotReceiver};
 use ractor::pg::GroupChangeMessage;
 use ractor::ActorRef;
@@ -154,6 +155,76 @@ impl<T, E: Debug> From<ActorResult<T, E>> for Result<T, E> {
     }
 }
 
+/// How many transactions are remembered as recently applied.
+pub const DEFAULT_RECENT_TRANSACTIONS_WINDOW: usize = 10_000;
+
+/// A bounded record of recently seen transaction hashes. Once `capacity`
+/// hashes are held, recording another evicts the oldest.
+#[derive(Debug, Clone)]
+pub struct RecentTransactions<V> {
+    capacity: usize,
+    order: VecDeque<String>,
+    entries: HashMap<String, V>,
+}
+
+impl<V> Default for RecentTransactions<V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECENT_TRANSACTIONS_WINDOW)
+    }
+}
+
+impl<V> RecentTransactions<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            order: VecDeque::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, transaction_hash: &str) -> bool {
+        self.entries.contains_key(transaction_hash)
+    }
+
+    pub fn get(&self, transaction_hash: &str) -> Option<&V> {
+        self.entries.get(transaction_hash)
+    }
+
+    pub fn get_mut(&mut self, transaction_hash: &str) -> Option<&mut V> {
+        self.entries.get_mut(transaction_hash)
+    }
+
+    /// Records `transaction_hash`, returning `false` without replacing the
+    /// existing value if it was already present.
+    pub fn insert(&mut self, transaction_hash: String, value: V) -> bool {
+        if self.entries.contains_key(&transaction_hash) {
+            return false;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.order.push_back(transaction_hash.clone());
+        self.entries.insert(transaction_hash, value);
+        true
+    }
+
+    pub fn remove(&mut self, transaction_hash: &str) -> Option<V> {
+        let value = self.entries.remove(transaction_hash)?;
+        self.order.retain(|hash| hash != transaction_hash);
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
 pub fn process_group_changed(group_change_message: GroupChangeMessage) {
     match group_change_message {
         GroupChangeMessage::Join(scope, group, actors) => {
@@ -371,10 +442,574 @@ pub async fn check_account_cache(address: Address, who: ActorType) -> Option<Acc
     Some(account)
 }
 
+/// Looks up the receipt of a transaction that has already been applied in
+/// the persistent receipt index.
+pub async fn get_receipt(transaction_hash: String) -> Option<TransactionResponse> {
+    let actor: ActorRef<AccountCacheMessage> =
+        ractor::registry::where_is(ActorType::AccountCache.to_string())?.into();
+
+    let (tx, rx) = oneshot();
+    let message = AccountCacheMessage::ReadReceipt {
+        transaction_hash,
+        tx,
+    };
+    actor.cast(message).ok()?;
+
+    let handler = create_handler!(account_cache_response);
+    handle_actor_response(rx, handler).await.ok()
+}
+
+/// Looks up the attestation of an applied call in the persistent index.
+pub async fn get_attestation(transaction_hash: String) -> Option<ExecutionAttestation> {
+    let actor: ActorRef<AccountCacheMessage> =
+        ractor::registry::where_is(ActorType::AccountCache.to_string())?.into();
+
+    let (tx, rx) = oneshot();
+    let message = AccountCacheMessage::ReadAttestation {
+        transaction_hash,
+        tx,
+    };
+    actor.cast(message).ok()?;
+
+    let handler = create_handler!(account_cache_response);
+    handle_actor_response(rx, handler).await.ok()
+}
+
+/// Holds what `transaction` takes from its sender in the account cache until
+/// [`release_debit`] releases it, answering why not if the sender cannot
+/// spend it on top of what its other pending transactions hold. Without an
+/// account cache there is nothing to hold it against.
+pub async fn reserve_debit(transaction: &Transaction) -> Result<(), String> {
+    let Some(actor) = ractor::registry::where_is(ActorType::AccountCache.to_string()) else {
+        return Ok(());
+    };
+    let actor: ActorRef<AccountCacheMessage> = actor.into();
+
+    let (tx, rx) = oneshot();
+    let message = AccountCacheMessage::ReserveDebit {
+        transaction: transaction.clone(),
+        tx,
+    };
+    actor.cast(message).map_err(|e| e.to_string())?;
+    rx.await.map_err(|e| e.to_string())?
+}
+
+/// Releases what the transaction `transaction_hash` held, once it is applied
+/// or turned away.
+pub fn release_debit(transaction_hash: &str) {
+    if let Some(actor) = ractor::registry::where_is(ActorType::AccountCache.to_string()) {
+        let actor: ActorRef<AccountCacheMessage> = actor.into();
+        let _ = actor.cast(AccountCacheMessage::ReleaseDebit {
+            transaction_hash: transaction_hash.to_string(),
+        });
+    }
+}
+
+/// Seconds since the epoch, or 0 on a clock set before it.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
 pub async fn get_account(address: Address, who: ActorType) -> Option<Account> {
     tracing::debug!(
         "Attempting to get account information from AccountCache for address: {}",
         address.to_full_string()
     );
+    // A simulation reads only the snapshot it was started with.
+    if let Some(account) = crate::viewed_account(&address) {
+        return account;
+    }
     check_account_cache(address, who).await
 }
+
+/// The registry every actor registers its metrics in, served in the
+/// Prometheus text format at `/metrics`.
+///
+/// Metrics are named `lasr_<module>_<metric>`. Labels only take a fixed set
+/// of values, never addresses or hashes, so the series exported stay few.
+/// The one exception is the program ids of the few programs
+/// `ProgramStats` exports, which it bounds itself.
+pub mod metrics {
+    use prometheus::core::Collector;
+    use prometheus::{
+        Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+        IntGaugeVec, Opts, Registry, TextEncoder,
+    };
+    use std::sync::OnceLock;
+
+    /// The prefix of every metric the node exports.
+    pub const NAMESPACE: &str = "lasr";
+
+    /// The content type of `encode`'s output.
+    pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+    fn registry() -> &'static Registry {
+        static REGISTRY: OnceLock<Registry> = OnceLock::new();
+        REGISTRY.get_or_init(Registry::new)
+    }
+
+    fn opts(module: &str, name: &str, help: &str) -> Opts {
+        Opts::new(name, help).namespace(NAMESPACE).subsystem(module)
+    }
+
+    /// Registers `metric`, which still counts if it could not be, so a
+    /// metric registered twice is only missing from the scrape.
+    fn register<M: Collector + Clone + 'static>(metric: M) -> M {
+        if let Err(e) = registry().register(Box::new(metric.clone())) {
+            tracing::warn!("failed to register metric: {e}");
+        }
+        metric
+    }
+
+    pub fn counter(module: &str, name: &str, help: &str) -> IntCounter {
+        register(IntCounter::with_opts(opts(module, name, help)).expect("invalid metric name"))
+    }
+
+    pub fn counter_vec(module: &str, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+        register(IntCounterVec::new(opts(module, name, help), labels).expect("invalid metric name"))
+    }
+
+    pub fn gauge(module: &str, name: &str, help: &str) -> IntGauge {
+        register(IntGauge::with_opts(opts(module, name, help)).expect("invalid metric name"))
+    }
+
+    pub fn gauge_vec(module: &str, name: &str, help: &str, labels: &[&str]) -> IntGaugeVec {
+        register(IntGaugeVec::new(opts(module, name, help), labels).expect("invalid metric name"))
+    }
+
+    /// A histogram of seconds, in the default buckets.
+    pub fn histogram(module: &str, name: &str, help: &str) -> Histogram {
+        register(
+            Histogram::with_opts(HistogramOpts::from(opts(module, name, help)))
+                .expect("invalid metric name"),
+        )
+    }
+
+    pub fn histogram_vec(module: &str, name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+        register(
+            HistogramVec::new(HistogramOpts::from(opts(module, name, help)), labels)
+                .expect("invalid metric name"),
+        )
+    }
+
+    /// Every metric registered, in the Prometheus text format.
+    pub fn encode() -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&registry().gather(), &mut buffer) {
+            tracing::error!("failed to encode metrics: {e}");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// The envelope the node's internal formats are written in: the accounts and
+/// receipts it stores, its commit log, and the batches it disperses.
+///
+/// An envelope is a format byte followed by the value in that format, bincode
+/// or borsh. Whichever the node writes, it reads either back. Borsh's layout
+/// is specified byte for byte, for readers outside Rust: integers are little
+/// endian, `U256`s 32 bytes of them, strings and sequences a `u32` length
+/// then their contents, options a `0` or `1` byte, enums their variant's
+/// index as a byte, and maps their entries in order of key.
+pub mod encoding {
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Display;
+    use std::str::FromStr;
+    use thiserror::Error;
+
+    /// The format byte of an envelope holding bincode.
+    pub const BINCODE_FORMAT: u8 = 0xb1;
+    /// The format byte of an envelope holding borsh.
+    pub const BORSH_FORMAT: u8 = 0xb2;
+
+    #[derive(Debug, Error)]
+    pub enum EncodingError {
+        #[error("bincode: {0}")]
+        Bincode(#[from] bincode::Error),
+
+        #[error("borsh: {0}")]
+        Borsh(#[from] std::io::Error),
+
+        #[error("nothing to decode")]
+        Empty,
+
+        #[error("{0}")]
+        Custom(String),
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub enum Encoding {
+        /// bincode 1's default layout, which the node has always written.
+        #[default]
+        Bincode,
+        Borsh,
+    }
+
+    impl Encoding {
+        pub fn format_byte(&self) -> u8 {
+            match self {
+                Encoding::Bincode => BINCODE_FORMAT,
+                Encoding::Borsh => BORSH_FORMAT,
+            }
+        }
+
+        /// The encoding of the envelope `bytes`, if they are one.
+        pub fn of(bytes: &[u8]) -> Option<Self> {
+            match *bytes.first()? {
+                BINCODE_FORMAT => Some(Encoding::Bincode),
+                BORSH_FORMAT => Some(Encoding::Borsh),
+                _ => None,
+            }
+        }
+
+        /// `value` in an envelope of this encoding.
+        pub fn encode<T>(&self, value: &T) -> Result<Vec<u8>, EncodingError>
+        where
+            T: Serialize + BorshSerialize,
+        {
+            let mut bytes = vec![self.format_byte()];
+            match self {
+                Encoding::Bincode => bincode::serialize_into(&mut bytes, value)?,
+                Encoding::Borsh => BorshSerialize::serialize(value, &mut bytes)?,
+            }
+            Ok(bytes)
+        }
+    }
+
+    /// The value in the envelope `bytes`, in either encoding. Bytes without a
+    /// format byte are read as bare bincode, as values were written before
+    /// they were put in envelopes. None of those begins with a format byte:
+    /// each begins with a small variant index or the length of a hash.
+    pub fn decode<T>(bytes: &[u8]) -> Result<T, EncodingError>
+    where
+        T: DeserializeOwned + BorshDeserialize,
+    {
+        if bytes.is_empty() {
+            return Err(EncodingError::Empty);
+        }
+        match Encoding::of(bytes) {
+            Some(Encoding::Bincode) => Ok(bincode::deserialize(&bytes[1..])?),
+            Some(Encoding::Borsh) => Ok(borsh::from_slice(&bytes[1..])?),
+            None => Ok(bincode::deserialize(bytes)?),
+        }
+    }
+
+    impl FromStr for Encoding {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.to_lowercase().as_str() {
+                "bincode" => Ok(Encoding::Bincode),
+                "borsh" => Ok(Encoding::Borsh),
+                other => Err(format!(
+                    "unknown encoding {other}, expected bincode or borsh"
+                )),
+            }
+        }
+    }
+
+    impl Display for Encoding {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Encoding::Bincode => write!(f, "bincode"),
+                Encoding::Borsh => write!(f, "borsh"),
+            }
+        }
+    }
+}
+
+/// The logs the node keeps over restarts: the pending log, the commit log,
+/// the batch, transaction and event indexes, the dead letters, the program
+/// stats and the unsealed batches.
+///
+/// Records are a little endian `u32` length followed by that many bytes of
+/// the record, JSON unless the log says otherwise. A record torn by a crash,
+/// with fewer bytes left in the file than its length, is dropped when the log
+/// is opened. A whole record that does not decode was not torn, and may be
+/// followed by records already acknowledged, so it fails the open and the
+/// file is left as it is.
+pub mod record_log {
+    use std::fmt;
+    use std::fs::{File, OpenOptions};
+    use std::io::{ErrorKind, Read, Write};
+    use std::marker::PhantomData;
+    use std::path::{Path, PathBuf};
+
+    use serde::{de::DeserializeOwned, Serialize};
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    pub enum RecordLogError {
+        #[error("{0}")]
+        Io(#[from] std::io::Error),
+
+        #[error("the record at byte {offset} of {} is corrupt: {reason}", .path.display())]
+        Corrupt {
+            path: PathBuf,
+            offset: usize,
+            reason: String,
+        },
+
+        #[error("a record could not be encoded: {0}")]
+        Encode(String),
+    }
+
+    /// How the records of a log are written and read back.
+    pub trait RecordCodec<T> {
+        fn encode(&self, record: &T) -> Result<Vec<u8>, String>;
+        fn decode(&self, bytes: &[u8]) -> Result<T, String>;
+    }
+
+    /// Records written as JSON, as transactions are read back the way they
+    /// arrive over RPC.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Json;
+
+    impl<T: Serialize + DeserializeOwned> RecordCodec<T> for Json {
+        fn encode(&self, record: &T) -> Result<Vec<u8>, String> {
+            serde_json::to_vec(record).map_err(|e| e.to_string())
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<T, String> {
+            serde_json::from_slice(bytes).map_err(|e| e.to_string())
+        }
+    }
+
+    /// A file of records of `T`, appended to as they are made and read back
+    /// when it is opened.
+    pub struct RecordLog<T, C = Json> {
+        path: PathBuf,
+        file: File,
+        codec: C,
+        /// Records in the file, read back or appended since.
+        records: usize,
+        _records: PhantomData<fn() -> T>,
+    }
+
+    impl<T, C> fmt::Debug for RecordLog<T, C> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("RecordLog")
+                .field("path", &self.path)
+                .field("records", &self.records)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl<T, C: RecordCodec<T> + Default> RecordLog<T, C> {
+        /// Opens the log at `path`, creating it if there is none, and returns
+        /// it with the records it holds, oldest first.
+        pub fn open(path: &Path) -> Result<(Self, Vec<T>), RecordLogError> {
+            Self::open_with(path, C::default())
+        }
+
+        /// Writes `records` over whatever is at `path`, returning the log
+        /// open to append to.
+        pub fn create<'a>(
+            path: &Path,
+            records: impl IntoIterator<Item = &'a T>,
+        ) -> Result<Self, RecordLogError>
+        where
+            T: 'a,
+        {
+            Self::create_with(path, records, C::default())
+        }
+
+        /// The records of the log at `path`, none if there is no file there,
+        /// read without opening it to append to.
+        pub fn read(path: &Path) -> Result<Vec<T>, RecordLogError> {
+            let buf = match std::fs::read(path) {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e.into()),
+            };
+            let (records, len) = read_records(path, &buf, &C::default())?;
+            if len < buf.len() {
+                tracing::warn!(
+                    "dropping {} bytes torn from the end of {}",
+                    buf.len() - len,
+                    path.display()
+                );
+            }
+            Ok(records)
+        }
+    }
+
+    impl<T, C: RecordCodec<T>> RecordLog<T, C> {
+        /// [`RecordLog::open`], with records written and read by `codec`.
+        pub fn open_with(path: &Path, codec: C) -> Result<(Self, Vec<T>), RecordLogError> {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(path)?;
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+
+            let (records, len) = read_records(path, &buf, &codec)?;
+            if len < buf.len() {
+                tracing::warn!(
+                    "dropping {} bytes torn from the end of {}",
+                    buf.len() - len,
+                    path.display()
+                );
+                file.set_len(len as u64)?;
+            }
+            let log = Self {
+                path: path.to_path_buf(),
+                file,
+                codec,
+                records: records.len(),
+                _records: PhantomData,
+            };
+            Ok((log, records))
+        }
+
+        /// [`RecordLog::create`], with records written by `codec`. They are
+        /// written beside `path` first and moved into place, so a crash
+        /// leaves either what was there or `records`.
+        pub fn create_with<'a>(
+            path: &Path,
+            records: impl IntoIterator<Item = &'a T>,
+            codec: C,
+        ) -> Result<Self, RecordLogError>
+        where
+            T: 'a,
+        {
+            let staged = path.with_extension("staged");
+            let (bytes, count) = frames(&codec, records)?;
+            let mut file = File::create(&staged)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            std::fs::rename(&staged, path)?;
+            Ok(Self {
+                path: path.to_path_buf(),
+                file: OpenOptions::new().append(true).open(path)?,
+                codec,
+                records: count,
+                _records: PhantomData,
+            })
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Records in the file, read back when it was opened or appended
+        /// since.
+        pub fn records(&self) -> usize {
+            self.records
+        }
+
+        /// Writes the records appended from now on with `codec`.
+        pub fn set_codec(&mut self, codec: C) {
+            self.codec = codec;
+        }
+
+        /// Appends `records` in one write, without syncing them.
+        pub fn append<'a>(
+            &mut self,
+            records: impl IntoIterator<Item = &'a T>,
+        ) -> Result<(), RecordLogError>
+        where
+            T: 'a,
+        {
+            let (bytes, count) = frames(&self.codec, records)?;
+            self.file.write_all(&bytes)?;
+            self.records += count;
+            Ok(())
+        }
+
+        /// Appends `records` in one write and syncs them.
+        pub fn append_synced<'a>(
+            &mut self,
+            records: impl IntoIterator<Item = &'a T>,
+        ) -> Result<(), RecordLogError>
+        where
+            T: 'a,
+        {
+            self.append(records)?;
+            self.file.sync_data()?;
+            Ok(())
+        }
+
+        /// Rewrites the log with only `records`.
+        pub fn rewrite<'a>(
+            &mut self,
+            records: impl IntoIterator<Item = &'a T>,
+        ) -> Result<(), RecordLogError>
+        where
+            T: 'a,
+            C: Clone,
+        {
+            *self = Self::create_with(&self.path, records, self.codec.clone())?;
+            Ok(())
+        }
+
+        /// Empties the log.
+        pub fn truncate(&mut self) -> Result<(), RecordLogError> {
+            self.file.set_len(0)?;
+            self.file.sync_all()?;
+            self.records = 0;
+            Ok(())
+        }
+    }
+
+    /// `record` as it is written to a log, its length first.
+    pub fn encode_record<T>(
+        codec: &impl RecordCodec<T>,
+        record: &T,
+    ) -> Result<Vec<u8>, RecordLogError> {
+        let bytes = codec.encode(record).map_err(RecordLogError::Encode)?;
+        let len = u32::try_from(bytes.len()).map_err(|_| {
+            RecordLogError::Encode(format!("a record of {} bytes is too large", bytes.len()))
+        })?;
+        Ok([&len.to_le_bytes()[..], &bytes].concat())
+    }
+
+    fn frames<'a, T: 'a>(
+        codec: &impl RecordCodec<T>,
+        records: impl IntoIterator<Item = &'a T>,
+    ) -> Result<(Vec<u8>, usize), RecordLogError> {
+        let mut bytes = Vec::new();
+        let mut count = 0;
+        for record in records {
+            bytes.extend(encode_record(codec, record)?);
+            count += 1;
+        }
+        Ok((bytes, count))
+    }
+
+    /// The records at the start of `buf`, read from `path`, and the bytes
+    /// they take up. Those left after them are a torn record.
+    fn read_records<T>(
+        path: &Path,
+        buf: &[u8],
+        codec: &impl RecordCodec<T>,
+    ) -> Result<(Vec<T>, usize), RecordLogError> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while let Some(bytes) = frame(&buf[offset..]) {
+            let record = codec
+                .decode(bytes)
+                .map_err(|reason| RecordLogError::Corrupt {
+                    path: path.to_path_buf(),
+                    offset,
+                    reason,
+                })?;
+            offset += 4 + bytes.len();
+            records.push(record);
+        }
+        Ok((records, offset))
+    }
+
+    /// The bytes of the record at the start of `buf`, or `None` if fewer are
+    /// left than it takes up.
+    fn frame(buf: &[u8]) -> Option<&[u8]> {
+        let len = u32::from_le_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+        buf.get(4..4usize.checked_add(len)?)
+    }
+}
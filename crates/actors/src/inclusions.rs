@@ -0,0 +1,245 @@
+//! What became of the transactions the batcher applied.
+//!
+//! The batcher records the balances each transaction changed and the events
+//! its program emitted as it commits its accounts, and the batch the
+//! transaction went into once that batch's blob is verified on the DA layer,
+//! with the proof that it did, for `getTransactionReceipt` to report.
+//! Only the most recent transactions are kept, as many as
+//! `receipts.recent_window` allows.
+use crate::{RecentTransactions, DEFAULT_RECENT_TRANSACTIONS_WINDOW};
+use lasr_messages::InclusionProof;
+use lasr_types::{Account, AccountType, Address, ProgramEvent, Transaction, U256};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+/// A balance a transaction changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenDelta {
+    pub address: Address,
+    pub program_id: Address,
+    pub before: U256,
+    pub after: U256,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inclusion {
+    /// The sender and recipient, known once the transaction is applied.
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    pub token_deltas: Vec<TokenDelta>,
+    pub events: Vec<ProgramEvent>,
+    /// Set once the batch the transaction is in has been finalized.
+    pub batch_id: Option<u64>,
+    /// That the transaction is in the batch, set along with `batch_id`.
+    pub proof: Option<InclusionProof>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionInclusions {
+    inclusions: Arc<Mutex<RecentTransactions<Inclusion>>>,
+}
+
+impl Default for TransactionInclusions {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECENT_TRANSACTIONS_WINDOW)
+    }
+}
+
+impl TransactionInclusions {
+    /// Remembers what became of the last `capacity` transactions applied.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inclusions: Arc::new(Mutex::new(RecentTransactions::new(capacity))),
+        }
+    }
+
+    /// Records the balances `transaction` changed as it was applied.
+    pub fn applied(&self, transaction: &Transaction, token_deltas: Vec<TokenDelta>) {
+        self.inclusions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                transaction.hash_string(),
+                Inclusion {
+                    from: Some(transaction.from()),
+                    to: Some(transaction.to()),
+                    token_deltas,
+                    events: Vec::new(),
+                    batch_id: None,
+                    proof: None,
+                },
+            );
+    }
+
+    /// Records the `events` the program of `transaction` emitted, once it
+    /// is applied.
+    pub fn emitted(&self, transaction: &Transaction, events: Vec<ProgramEvent>) {
+        let mut inclusions = self.inclusions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(inclusion) = inclusions.get_mut(&transaction.hash_string()) {
+            inclusion.events = events;
+        }
+    }
+
+    /// Records that the transactions in `transaction_hashes` went into batch
+    /// `batch_id`.
+    pub fn batched<'a>(
+        &self,
+        transaction_hashes: impl IntoIterator<Item = &'a String>,
+        batch_id: u64,
+    ) {
+        let mut inclusions = self.inclusions.lock().unwrap_or_else(|e| e.into_inner());
+        for transaction_hash in transaction_hashes {
+            match inclusions.get_mut(transaction_hash) {
+                Some(inclusion) => inclusion.batch_id = Some(batch_id),
+                None => {
+                    inclusions.insert(
+                        transaction_hash.clone(),
+                        Inclusion {
+                            batch_id: Some(batch_id),
+                            ..Inclusion::default()
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Records the proof of each transaction in `proofs` that it went into
+    /// the batch it names.
+    pub fn proven(&self, proofs: Vec<InclusionProof>) {
+        let mut inclusions = self.inclusions.lock().unwrap_or_else(|e| e.into_inner());
+        for proof in proofs {
+            let transaction_hash = format!("0x{}", hex::encode(proof.transaction_hash));
+            if let Some(inclusion) = inclusions.get_mut(&transaction_hash) {
+                inclusion.proof = Some(proof);
+            }
+        }
+    }
+
+    pub fn get(&self, transaction_hash: &str) -> Option<Inclusion> {
+        self.inclusions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(transaction_hash)
+            .cloned()
+    }
+}
+
+/// The balances that differ between `before`, if the account existed, and
+/// `after`, in program order.
+pub fn token_deltas(before: Option<&Account>, after: &Account) -> Vec<TokenDelta> {
+    let address = match after.account_type() {
+        AccountType::User => after.owner_address(),
+        AccountType::Program(program_address) => program_address,
+    };
+    let program_ids: BTreeSet<Address> = before
+        .into_iter()
+        .flat_map(|account| account.tokens_iter())
+        .chain(after.tokens_iter())
+        .map(|(program_id, _)| program_id)
+        .copied()
+        .collect();
+    program_ids
+        .into_iter()
+        .filter_map(|program_id| {
+            let before = before
+                .map(|account| account.balance(&program_id))
+                .unwrap_or_default();
+            let after = after.balance(&program_id);
+            (before != after).then_some(TokenDelta {
+                address,
+                program_id,
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod inclusions_tests {
+    use super::*;
+    use lasr_types::{
+        ArbitraryData, Metadata, Status, TokenBuilder, TransactionBuilder, TransactionType,
+    };
+    use std::collections::BTreeMap;
+
+    fn holding(balances: &[(u8, u64)]) -> Account {
+        let mut account = Account::new(Address::new([1; 20]));
+        for (program, balance) in balances {
+            let token = TokenBuilder::default()
+                .program_id(Address::new([*program; 20]))
+                .owner_id(Address::new([1; 20]))
+                .balance(U256::from(*balance))
+                .metadata(Metadata::new())
+                .token_ids(Vec::new())
+                .allowance(BTreeMap::new())
+                .approvals(BTreeMap::new())
+                .data(ArbitraryData::new())
+                .status(Status::Free)
+                .build()
+                .unwrap();
+            account
+                .programs_mut()
+                .insert(Address::new([*program; 20]), token);
+        }
+        account
+    }
+
+    #[test]
+    fn only_changed_balances_are_deltas() {
+        let before = holding(&[(2, 10), (3, 5)]);
+        let after = holding(&[(2, 4), (3, 5), (4, 1)]);
+        let delta = |program: u8, before: u64, after: u64| TokenDelta {
+            address: Address::new([1; 20]),
+            program_id: Address::new([program; 20]),
+            before: U256::from(before),
+            after: U256::from(after),
+        };
+        assert_eq!(
+            token_deltas(Some(&before), &after),
+            vec![delta(2, 10, 4), delta(4, 0, 1)]
+        );
+        assert_eq!(token_deltas(None, &holding(&[(2, 0)])), Vec::new());
+    }
+
+    #[test]
+    fn a_transaction_batched_keeps_its_deltas_and_events() {
+        let inclusions = TransactionInclusions::default();
+        let transaction = TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([2; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(7))
+            .nonce(U256::from(1))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap();
+        let transaction_hash = transaction.hash_string();
+        let deltas = token_deltas(None, &holding(&[(2, 7)]));
+        inclusions.applied(&transaction, deltas.clone());
+        inclusions.emitted(&transaction, vec![ProgramEvent::new("minted")]);
+        inclusions.batched([&transaction_hash, &"0xb".to_string()], 3);
+
+        assert_eq!(
+            inclusions.get(&transaction_hash),
+            Some(Inclusion {
+                from: Some(Address::new([1; 20])),
+                to: Some(Address::new([2; 20])),
+                token_deltas: deltas,
+                events: vec![ProgramEvent::new("minted")],
+                batch_id: Some(3),
+                proof: None,
+            })
+        );
+        assert_eq!(inclusions.get("0xb").unwrap().batch_id, Some(3));
+        assert_eq!(inclusions.get("0xc"), None);
+    }
+}
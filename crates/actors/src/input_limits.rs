@@ -0,0 +1,235 @@
+//! Limits on the `inputs` a transaction carries into a program.
+//!
+//! Inputs are checked when the RPC server admits a transaction, again when
+//! the executor parses them and once more when the validator checks the
+//! call's outputs, so an oversized payload is turned away no matter how it
+//! reached the node. A program can declare at registration that it takes
+//! larger inputs, up to a ceiling set by the operator, and that its inputs
+//! must be JSON.
+use lasr_messages::{LasrError, RpcError, RpcErrorCode};
+use lasr_types::{Account, Transaction};
+use serde::Serialize;
+use thiserror::Error;
+
+/// The largest inputs, in bytes, of programs that do not declare a limit.
+pub const DEFAULT_MAX_INPUT_BYTES: usize = 128 * 1024;
+
+/// The most, in bytes, a program may declare it takes.
+pub const DEFAULT_MAX_PROGRAM_INPUT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Program metadata key holding the largest inputs, in bytes, a program
+/// accepts. Set from `maxInputBytes` in the registration inputs.
+pub const MAX_INPUT_BYTES_KEY: &str = "max_input_bytes";
+
+/// Program metadata key holding the format a program's inputs must be in.
+/// Set from `inputFormat` in the registration inputs.
+pub const INPUT_FORMAT_KEY: &str = "input_format";
+
+/// The `inputFormat` of programs whose inputs must be JSON.
+pub const JSON_INPUT_FORMAT: &str = "json";
+
+/// Registration input fields that are copied into program metadata, with
+/// the key each is stored under.
+pub const INPUT_REGISTRATION_FIELDS: [(&str, &str); 2] = [
+    ("maxInputBytes", MAX_INPUT_BYTES_KEY),
+    ("inputFormat", INPUT_FORMAT_KEY),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize)]
+#[serde(rename_all = "camelCase", tag = "reason")]
+pub enum InputError {
+    #[error("transaction inputs are {size} bytes, over the limit of {limit} bytes")]
+    #[serde(rename_all = "camelCase")]
+    TooLarge { size: usize, limit: usize },
+
+    #[error("transaction inputs must be JSON for this program: {error}")]
+    #[serde(rename_all = "camelCase")]
+    InvalidJson { error: String },
+}
+
+impl From<InputError> for LasrError {
+    fn from(e: InputError) -> Self {
+        LasrError::Rpc(RpcError::Rejected {
+            code: RpcErrorCode::InvalidParams,
+            reason: e.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLimits {
+    /// Limit for programs that do not declare one.
+    max_input_bytes: usize,
+    /// The most any program may declare.
+    max_program_input_bytes: usize,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_INPUT_BYTES, DEFAULT_MAX_PROGRAM_INPUT_BYTES)
+    }
+}
+
+impl InputLimits {
+    pub fn new(max_input_bytes: usize, max_program_input_bytes: usize) -> Self {
+        Self {
+            max_input_bytes,
+            max_program_input_bytes: max_program_input_bytes.max(max_input_bytes),
+        }
+    }
+
+    /// The largest inputs `program` accepts, or the default limit if there is
+    /// no program account.
+    pub fn limit_for(&self, program: Option<&Account>) -> usize {
+        program
+            .and_then(|program| {
+                program
+                    .program_account_metadata()
+                    .inner()
+                    .get(MAX_INPUT_BYTES_KEY)
+                    .and_then(|limit| limit.parse::<usize>().ok())
+            })
+            .map(|limit| limit.min(self.max_program_input_bytes))
+            .unwrap_or(self.max_input_bytes)
+    }
+
+    /// Checks the inputs of `transaction` against the limit of `program` and,
+    /// if it declares a JSON interface, that they are JSON.
+    pub fn check(
+        &self,
+        transaction: &Transaction,
+        program: Option<&Account>,
+    ) -> Result<(), InputError> {
+        // Inputs are a `String`, so they are always valid UTF-8 by the time
+        // they get here; only their size and format are left to check.
+        let inputs = transaction.inputs();
+        let limit = self.limit_for(program);
+        if inputs.len() > limit {
+            return Err(InputError::TooLarge {
+                size: inputs.len(),
+                limit,
+            });
+        }
+
+        let requires_json = program.is_some_and(|program| {
+            program
+                .program_account_metadata()
+                .inner()
+                .get(INPUT_FORMAT_KEY)
+                .is_some_and(|format| format.eq_ignore_ascii_case(JSON_INPUT_FORMAT))
+        });
+        if requires_json {
+            serde_json::from_str::<serde::de::IgnoredAny>(&inputs).map_err(|e| {
+                InputError::InvalidJson {
+                    error: e.to_string(),
+                }
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod input_limits_tests {
+    use super::*;
+    use lasr_types::{
+        AccountBuilder, AccountType, Address, ArbitraryData, Metadata, PayloadBuilder,
+        TransactionType, U256,
+    };
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn transaction(inputs: String) -> Transaction {
+        PayloadBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(0)))
+            .from([1; 20])
+            .to([3; 20])
+            .program_id([3; 20])
+            .inputs(inputs)
+            .op(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(0))
+            .build()
+            .expect("failed to build payload")
+            .into()
+    }
+
+    fn program(metadata: &[(&str, &str)]) -> Account {
+        let mut program_metadata = Metadata::new();
+        for (key, value) in metadata {
+            program_metadata
+                .inner_mut()
+                .insert(key.to_string(), value.to_string());
+        }
+        AccountBuilder::default()
+            .account_type(AccountType::Program(Address::new([3; 20])))
+            .owner_address(Address::new([1; 20]))
+            .nonce(U256::from(0))
+            .programs(BTreeMap::new())
+            .program_namespace(None)
+            .program_account_linked_programs(BTreeSet::new())
+            .program_account_data(ArbitraryData::new())
+            .program_account_metadata(program_metadata)
+            .try_build()
+            .expect("failed to build program account")
+    }
+
+    #[test]
+    fn inputs_at_the_limit_are_accepted() {
+        let limits = InputLimits::new(16, 64);
+        assert!(limits.check(&transaction("a".repeat(16)), None).is_ok());
+        assert_eq!(
+            limits.check(&transaction("a".repeat(17)), None),
+            Err(InputError::TooLarge {
+                size: 17,
+                limit: 16
+            })
+        );
+    }
+
+    #[test]
+    fn programs_can_raise_their_limit_up_to_the_ceiling() {
+        let limits = InputLimits::new(16, 64);
+        let data_heavy = program(&[(MAX_INPUT_BYTES_KEY, "32")]);
+        assert!(limits
+            .check(&transaction("a".repeat(32)), Some(&data_heavy))
+            .is_ok());
+        assert!(limits
+            .check(&transaction("a".repeat(33)), Some(&data_heavy))
+            .is_err());
+
+        let greedy = program(&[(MAX_INPUT_BYTES_KEY, "1000000")]);
+        assert_eq!(limits.limit_for(Some(&greedy)), 64);
+    }
+
+    #[test]
+    fn json_programs_reject_malformed_inputs() {
+        let limits = InputLimits::default();
+        let json = program(&[(INPUT_FORMAT_KEY, JSON_INPUT_FORMAT)]);
+        assert!(limits
+            .check(&transaction(r#"{"amount":1}"#.to_string()), Some(&json))
+            .is_ok());
+        assert!(matches!(
+            limits.check(&transaction("{\"amount\":".to_string()), Some(&json)),
+            Err(InputError::InvalidJson { .. })
+        ));
+        // Programs without a declared format take anything.
+        assert!(limits
+            .check(
+                &transaction("{\"amount\":".to_string()),
+                Some(&program(&[]))
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn errors_report_the_limit() {
+        let error = InputError::TooLarge {
+            size: 17,
+            limit: 16,
+        };
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "reason": "tooLarge", "size": 17, "limit": 16 })
+        );
+    }
+}
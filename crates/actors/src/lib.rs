@@ -1,32 +1,139 @@
 pub mod account_cache;
+pub mod admin;
+pub mod admission;
+pub mod admission_policy;
+pub mod artifact_cache;
+pub mod attestation;
+pub mod authorization;
 pub mod batcher;
+pub mod batches;
 pub mod blob_cache;
+pub mod call_deadlines;
+pub mod chaos;
+pub mod commit_log;
+pub mod config;
 pub mod da_client;
+pub mod dead_letters;
+pub mod drain;
 pub mod engine;
 pub mod eo_client;
 pub mod eo_server;
+pub mod events;
+pub mod execution_pool;
 pub mod executor;
+pub mod executor_health;
+pub mod faucet;
+pub mod fees;
+pub mod follower;
+pub mod genesis;
+pub mod health;
 pub mod helpers;
+pub mod inclusions;
+pub mod input_limits;
+pub mod lifecycle;
+pub mod load_shedding;
+pub mod local_da;
+pub mod mailboxes;
 pub mod manager;
+pub mod mempool;
+pub mod node_keys;
+pub mod notifications;
+pub mod outputs;
+pub mod pagination;
+pub mod params;
+pub mod pending_log;
 pub mod pending_transactions;
+pub mod program_stats;
+pub mod programs;
+pub mod rate_limits;
+pub mod rest;
+pub mod retention;
+pub mod retry;
 pub mod rpc_server;
 pub mod scheduler;
+pub mod scheduling;
+pub mod settler;
+pub mod simulation;
+pub mod supervision;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(feature = "test_harness")]
+pub mod test_harness;
+pub mod transaction_index;
+pub mod unsealed_batches;
 pub mod validator;
+pub mod verification;
+pub mod verse;
 
 pub use account_cache::*;
+pub use admin::*;
+pub use admission::*;
+pub use admission_policy::*;
+pub use artifact_cache::*;
+pub use attestation::*;
+pub use authorization::*;
 pub use batcher::*;
+pub use batches::*;
 pub use blob_cache::*;
+pub use call_deadlines::*;
+pub use chaos::*;
+pub use commit_log::*;
+pub use config::*;
 pub use da_client::*;
+pub use dead_letters::*;
+pub use drain::*;
 pub use engine::*;
 pub use eo_client::*;
 pub use eo_server::*;
+pub use events::*;
+pub use execution_pool::*;
 pub use executor::*;
+pub use executor_health::*;
+pub use faucet::*;
+pub use fees::*;
+pub use follower::*;
+pub use genesis::*;
+pub use health::*;
 pub use helpers::*;
+pub use inclusions::*;
+pub use input_limits::*;
+pub use lifecycle::*;
+pub use load_shedding::*;
+pub use local_da::*;
+pub use mailboxes::*;
 pub use manager::*;
+pub use mempool::*;
+pub use node_keys::*;
+pub use notifications::*;
+pub use outputs::*;
+pub use pagination::*;
+pub use params::*;
+pub use pending_log::*;
 pub use pending_transactions::*;
+pub use program_stats::*;
+pub use programs::*;
+pub use rate_limits::*;
+pub use rest::*;
+pub use retention::*;
+pub use retry::*;
 pub use rpc_server::*;
 pub use scheduler::*;
+pub use scheduling::*;
+pub use settler::*;
+pub use simulation::*;
+pub use supervision::*;
+#[cfg(feature = "test_harness")]
+pub use test_harness::*;
+pub use transaction_index::*;
+pub use unsealed_batches::*;
 pub use validator::*;
+pub use verification::*;
+pub use verse::*;
+
+#[cfg(all(feature = "chaos", not(debug_assertions)))]
+compile_error!(
+    "the chaos feature injects faults into actor messaging, and is for test builds only"
+);
 
 pub const MAX_BATCH_SIZE: usize = 1024 * 512;
 pub const ETH_PROGRAM_ID: [u8; 20] = [0u8; 20];
@@ -0,0 +1,207 @@
+//! Spans following a transaction from admission to its batch.
+//!
+//! The RPC server opens a `transaction` span for every transaction it
+//! admits, carrying the transaction's hash, and each actor the transaction
+//! passes through does its part in a span of its own under it, found by the
+//! hash of the transaction it is handed. With `LOG_FILTER` taking in
+//! `lasr::lifecycle=debug`, every span is logged as it closes with its
+//! timings, so filtering the log on a hash shows the whole path and how long
+//! each stage took. A batch is dispersed in a `da_client` span linked to the
+//! spans of the transactions in it, which the batcher hands to the DA client
+//! along with the batch.
+//!
+//! The span of a transaction is closed once it is batched or turned away.
+//! `SpanTimings` records how long every lifecycle span was open, by name,
+//! in `lasr_lifecycle_span_seconds`.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use prometheus::HistogramVec;
+use tracing::{span::Attributes, Id, Span, Subscriber};
+use tracing_subscriber::{filter::filter_fn, layer::Context, registry::LookupSpan, Layer};
+
+use crate::metrics;
+
+/// The target every lifecycle span is opened under.
+pub const LIFECYCLE_TARGET: &str = "lasr::lifecycle";
+
+/// The transactions whose spans are held open at once. The span of the
+/// oldest is closed to make room for another.
+const MAX_OPEN_TRANSACTIONS: usize = 65_536;
+
+/// The actors a transaction passes through, each doing its part in a span
+/// named for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LifecycleStage {
+    Rpc,
+    Scheduler,
+    Engine,
+    Executor,
+    Pending,
+    Validator,
+    Batcher,
+    DaClient,
+}
+
+impl LifecycleStage {
+    /// A span for this stage's part in the transaction `tx_hash`, under
+    /// `parent`, or on its own if there is none.
+    fn span(self, parent: Option<Id>, tx_hash: &str) -> Span {
+        macro_rules! stage_span {
+            ($name:literal) => {
+                tracing::debug_span!(target: LIFECYCLE_TARGET, parent: parent, $name, tx_hash)
+            };
+        }
+        match self {
+            LifecycleStage::Rpc => stage_span!("rpc"),
+            LifecycleStage::Scheduler => stage_span!("scheduler"),
+            LifecycleStage::Engine => stage_span!("engine"),
+            LifecycleStage::Executor => stage_span!("executor"),
+            LifecycleStage::Pending => stage_span!("pending"),
+            LifecycleStage::Validator => stage_span!("validator"),
+            LifecycleStage::Batcher => stage_span!("batcher"),
+            LifecycleStage::DaClient => stage_span!("da_client"),
+        }
+    }
+}
+
+/// The spans of the transactions admitted and not yet batched or turned
+/// away, by hash.
+#[derive(Default)]
+struct OpenTransactions {
+    spans: HashMap<String, Span>,
+    /// Hashes in the order their spans were opened.
+    opened: VecDeque<String>,
+}
+
+fn open_transactions() -> &'static Mutex<OpenTransactions> {
+    static OPEN: OnceLock<Mutex<OpenTransactions>> = OnceLock::new();
+    OPEN.get_or_init(Default::default)
+}
+
+/// Opens the span of the transaction `tx_hash` as it is admitted, returning
+/// whether it was not open already.
+pub fn admitted(tx_hash: &str) -> bool {
+    let mut open = open_transactions()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if open.spans.contains_key(tx_hash) {
+        return false;
+    }
+    while open.spans.len() >= MAX_OPEN_TRANSACTIONS {
+        match open.opened.pop_front() {
+            Some(oldest) => {
+                open.spans.remove(&oldest);
+            }
+            None => break,
+        }
+    }
+    let span = tracing::debug_span!(
+        target: LIFECYCLE_TARGET,
+        parent: None,
+        "transaction",
+        tx_hash
+    );
+    open.spans.insert(tx_hash.to_string(), span);
+    open.opened.push_back(tx_hash.to_string());
+    true
+}
+
+/// A span for `stage`'s part in the transaction `tx_hash`, under the span
+/// of the transaction if it is still open.
+pub fn stage(stage: LifecycleStage, tx_hash: &str) -> Span {
+    let parent = {
+        let open = open_transactions()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        open.spans.get(tx_hash).and_then(Span::id)
+    };
+    stage.span(parent, tx_hash)
+}
+
+/// The span a batch of the transactions `tx_hashes` is dispersed in, linked
+/// to the spans of those still open.
+pub fn dispersal(tx_hashes: &[String]) -> Span {
+    let span = tracing::debug_span!(
+        target: LIFECYCLE_TARGET,
+        parent: None,
+        "da_client",
+        transactions = tx_hashes.len()
+    );
+    let open = open_transactions()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    for tx_hash in tx_hashes {
+        if let Some(transaction) = open.spans.get(tx_hash) {
+            span.follows_from(transaction);
+        }
+    }
+    span
+}
+
+/// Closes the span of the transaction `tx_hash`, once it is batched or
+/// turned away.
+pub fn finished(tx_hash: &str) {
+    let mut open = open_transactions()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if open.spans.remove(tx_hash).is_some() {
+        open.opened.retain(|opened| opened != tx_hash);
+    }
+}
+
+struct LifecycleMetrics {
+    duration: HistogramVec,
+}
+
+fn lifecycle_metrics() -> &'static LifecycleMetrics {
+    static METRICS: OnceLock<LifecycleMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| LifecycleMetrics {
+        duration: metrics::histogram_vec(
+            "lifecycle",
+            "span_seconds",
+            "Time each lifecycle span was open, by span.",
+            &["span"],
+        ),
+    })
+}
+
+/// When a span was opened.
+struct Opened(Instant);
+
+/// Records how long every lifecycle span was open in the metrics registry.
+pub struct SpanTimings;
+
+impl SpanTimings {
+    /// The layer recording the timings, taking only lifecycle spans, so it
+    /// can sit beside the layers logs are filtered for.
+    pub fn layer<S>() -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        SpanTimings.with_filter(filter_fn(|metadata| metadata.target() == LIFECYCLE_TARGET))
+    }
+}
+
+impl<S> Layer<S> for SpanTimings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(Opened(opened)) = span.extensions().get::<Opened>() {
+                lifecycle_metrics()
+                    .duration
+                    .with_label_values(&[span.name()])
+                    .observe(opened.elapsed().as_secs_f64());
+            }
+        }
+    }
+}
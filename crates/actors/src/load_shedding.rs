@@ -0,0 +1,320 @@
+//! Turning away writes while the node is overloaded.
+//!
+//! A monitor samples how many transactions are pending, how many executions
+//! wait for a slot and how late the event loop wakes. Once any of them
+//! reaches its threshold the node is overloaded, and sends and calls are
+//! answered as `Overloaded`, with how long to wait, before they are queued.
+//! Reads are served as ever. The node is not overloaded again until every
+//! signal has fallen to its recovery threshold, a share of the one it was
+//! overloaded at, so a signal hovering at a threshold does not flap.
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use jsonrpsee::types::ErrorObjectOwned as RpcError;
+use lasr_messages::RpcErrorCode;
+use prometheus::{IntCounter, IntGauge};
+
+use crate::rate_limits::RetryAfter;
+use crate::{metrics, ExecutionPoolMetrics, MempoolMetrics};
+
+pub const DEFAULT_SHED_PENDING_TRANSACTIONS: u64 = 3_584;
+pub const DEFAULT_SHED_EXECUTION_QUEUE: usize = 224;
+pub const DEFAULT_SHED_EVENT_LOOP_LAG_MS: u64 = 250;
+/// The share, in percent, of each threshold a signal falls to before the
+/// node recovers.
+pub const DEFAULT_SHED_RECOVER_PERCENT: u64 = 50;
+pub const DEFAULT_SHED_RETRY_AFTER_MS: u64 = 1_000;
+
+/// How often `monitor_load` samples the signals.
+pub const LOAD_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+struct LoadSheddingMetrics {
+    shed: IntCounter,
+    overloaded: IntGauge,
+    overloads: IntCounter,
+}
+
+fn load_shedding_metrics() -> &'static LoadSheddingMetrics {
+    static METRICS: OnceLock<LoadSheddingMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| LoadSheddingMetrics {
+        shed: metrics::counter(
+            "rpc",
+            "shed_total",
+            "RPC writes turned away while the node was overloaded.",
+        ),
+        overloaded: metrics::gauge(
+            "rpc",
+            "overloaded",
+            "Whether the node is overloaded and turning writes away.",
+        ),
+        overloads: metrics::counter(
+            "rpc",
+            "overloads_total",
+            "Times the node became overloaded.",
+        ),
+    })
+}
+
+/// What the node is loaded by, as sampled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadSignals {
+    /// Transactions pending in the scheduler's pool.
+    pub pending_transactions: u64,
+    /// Executions waiting for a slot.
+    pub execution_queue: usize,
+    /// How late the event loop woke for a timer.
+    pub event_loop_lag: Duration,
+}
+
+impl LoadSignals {
+    /// Whether any signal has reached its threshold in `thresholds`.
+    fn any_reach(&self, thresholds: &LoadSignals) -> bool {
+        self.pending_transactions >= thresholds.pending_transactions
+            || self.execution_queue >= thresholds.execution_queue
+            || self.event_loop_lag >= thresholds.event_loop_lag
+    }
+
+    /// Whether every signal is at or under its threshold in `thresholds`.
+    fn all_within(&self, thresholds: &LoadSignals) -> bool {
+        self.pending_transactions <= thresholds.pending_transactions
+            && self.execution_queue <= thresholds.execution_queue
+            && self.event_loop_lag <= thresholds.event_loop_lag
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadSheddingConfig {
+    /// The signals the node is overloaded at, any one of them reached.
+    pub shed: LoadSignals,
+    /// The signals the node recovers at, every one of them fallen to.
+    pub recover: LoadSignals,
+    /// How long a write turned away is told to wait.
+    pub retry_after: Duration,
+}
+
+impl Default for LoadSheddingConfig {
+    fn default() -> Self {
+        Self::new(
+            LoadSignals {
+                pending_transactions: DEFAULT_SHED_PENDING_TRANSACTIONS,
+                execution_queue: DEFAULT_SHED_EXECUTION_QUEUE,
+                event_loop_lag: Duration::from_millis(DEFAULT_SHED_EVENT_LOOP_LAG_MS),
+            },
+            DEFAULT_SHED_RECOVER_PERCENT,
+            Duration::from_millis(DEFAULT_SHED_RETRY_AFTER_MS),
+        )
+    }
+}
+
+impl LoadSheddingConfig {
+    /// Overloaded at `shed`, recovering once every signal is at
+    /// `recover_percent` of it.
+    pub fn new(shed: LoadSignals, recover_percent: u64, retry_after: Duration) -> Self {
+        let percent = recover_percent.min(100);
+        Self {
+            shed,
+            recover: LoadSignals {
+                pending_transactions: shed.pending_transactions * percent / 100,
+                execution_queue: shed.execution_queue * percent as usize / 100,
+                event_loop_lag: shed.event_loop_lag * percent as u32 / 100,
+            },
+            retry_after,
+        }
+    }
+}
+
+/// A write turned away while the node is overloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overloaded {
+    pub retry_after: Duration,
+}
+
+impl From<Overloaded> for RpcError {
+    fn from(overloaded: Overloaded) -> Self {
+        let retry_after_ms = overloaded.retry_after.as_millis().max(1) as u64;
+        RpcError::owned(
+            RpcErrorCode::Overloaded.code(),
+            format!("the node is overloaded, retry after {retry_after_ms}ms"),
+            Some(RetryAfter { retry_after_ms }),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoadState {
+    overloaded: bool,
+    /// The signals last observed.
+    signals: LoadSignals,
+}
+
+/// Whether the node is overloaded, as last observed, shared by the monitor
+/// and the RPC server.
+#[derive(Debug, Clone, Default)]
+pub struct LoadShedder {
+    config: LoadSheddingConfig,
+    state: Arc<Mutex<LoadState>>,
+}
+
+impl LoadShedder {
+    pub fn new(config: LoadSheddingConfig) -> Self {
+        Self {
+            config,
+            state: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> LoadSheddingConfig {
+        self.config
+    }
+
+    /// Takes in what the node is loaded by, answering whether it is now
+    /// overloaded.
+    pub fn observe(&self, signals: LoadSignals) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.signals = signals;
+        if !state.overloaded && signals.any_reach(&self.config.shed) {
+            state.overloaded = true;
+            load_shedding_metrics().overloaded.set(1);
+            load_shedding_metrics().overloads.inc();
+            tracing::warn!(
+                "the node is overloaded, turning writes away: {} pending, {} queued for execution, event loop {}ms late",
+                signals.pending_transactions,
+                signals.execution_queue,
+                signals.event_loop_lag.as_millis()
+            );
+        } else if state.overloaded && signals.all_within(&self.config.recover) {
+            state.overloaded = false;
+            load_shedding_metrics().overloaded.set(0);
+            tracing::info!(
+                "the node has recovered from overload: {} pending, {} queued for execution, event loop {}ms late",
+                signals.pending_transactions,
+                signals.execution_queue,
+                signals.event_loop_lag.as_millis()
+            );
+        }
+        state.overloaded
+    }
+
+    pub fn is_overloaded(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .overloaded
+    }
+
+    /// The signals last observed.
+    pub fn signals(&self) -> LoadSignals {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).signals
+    }
+
+    /// Turns a write away, and counts it, while the node is overloaded.
+    pub fn check(&self) -> Result<(), Overloaded> {
+        if !self.is_overloaded() {
+            return Ok(());
+        }
+        load_shedding_metrics().shed.inc();
+        tracing::debug!("turned a write away while overloaded");
+        Err(Overloaded {
+            retry_after: self.config.retry_after,
+        })
+    }
+}
+
+/// Samples what the node is loaded by every `interval` into `shedder`: the
+/// transactions pending in `mempool`, the executions waiting in `execution`,
+/// if the node runs them, and how late the event loop wakes for each sample.
+pub async fn monitor_load(
+    shedder: LoadShedder,
+    mempool: Arc<MempoolMetrics>,
+    execution: Option<Arc<ExecutionPoolMetrics>>,
+    interval: Duration,
+) {
+    loop {
+        let slept = Instant::now();
+        tokio::time::sleep(interval).await;
+        let event_loop_lag = slept.elapsed().saturating_sub(interval);
+        shedder.observe(LoadSignals {
+            pending_transactions: mempool.transactions(),
+            execution_queue: execution
+                .as_ref()
+                .map(|execution| execution.queue_depth())
+                .unwrap_or_default(),
+            event_loop_lag,
+        });
+    }
+}
+
+#[cfg(test)]
+mod load_shedding_tests {
+    use super::*;
+
+    fn shedder() -> LoadShedder {
+        LoadShedder::new(LoadSheddingConfig::new(
+            LoadSignals {
+                pending_transactions: 100,
+                execution_queue: 10,
+                event_loop_lag: Duration::from_millis(200),
+            },
+            50,
+            Duration::from_millis(500),
+        ))
+    }
+
+    fn pending(pending_transactions: u64) -> LoadSignals {
+        LoadSignals {
+            pending_transactions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn any_signal_past_its_threshold_overloads_the_node() {
+        for signals in [
+            pending(100),
+            LoadSignals {
+                execution_queue: 12,
+                ..Default::default()
+            },
+            LoadSignals {
+                event_loop_lag: Duration::from_millis(300),
+                ..Default::default()
+            },
+        ] {
+            let shedder = shedder();
+            assert!(shedder.check().is_ok());
+            assert!(shedder.observe(signals), "{signals:?}");
+            assert_eq!(
+                shedder.check(),
+                Err(Overloaded {
+                    retry_after: Duration::from_millis(500)
+                })
+            );
+        }
+        assert!(!shedder().observe(pending(99)));
+    }
+
+    #[test]
+    fn the_node_recovers_only_once_every_signal_has_fallen_far_enough() {
+        let shedder = shedder();
+        assert!(shedder.observe(pending(150)));
+        // Hovering about the threshold keeps it overloaded rather than
+        // flapping.
+        for pending_transactions in [99, 101, 80, 100, 51] {
+            assert!(shedder.observe(pending(pending_transactions)));
+        }
+        assert!(shedder.observe(LoadSignals {
+            pending_transactions: 10,
+            execution_queue: 6,
+            event_loop_lag: Duration::ZERO,
+        }));
+        assert!(!shedder.observe(LoadSignals {
+            pending_transactions: 50,
+            execution_queue: 5,
+            event_loop_lag: Duration::from_millis(100),
+        }));
+        assert!(shedder.check().is_ok());
+        // And stays so until a threshold is reached again.
+        assert!(!shedder.observe(pending(99)));
+        assert!(shedder.observe(pending(100)));
+    }
+}
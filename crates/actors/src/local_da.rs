@@ -0,0 +1,242 @@
+//! A DA layer kept in a local directory, for devnets and tests.
+//!
+//! Every batch stored is written to its own file, named for the hash of the
+//! batch and the index it was stored at, and is confirmed at once: the proof
+//! handed back commits to the blob by that hash and index, which is all it
+//! is read back by. Nothing is dispersed anywhere, so a node using it
+//! settles batches no one else can retrieve. Blobs pruned past the node's
+//! retention are deleted, and the index they were stored at is never used
+//! again.
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use eigenda_client::{proof::BlobVerificationProof, response::BlobResponse};
+use ethereum_types::H256;
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+
+const BLOB_EXTENSION: &str = "blob";
+/// Holds the index past the highest of those pruned, so blobs stored after
+/// every one there was is pruned do not reuse it.
+const PRUNED_FILE: &str = "pruned";
+
+#[derive(Clone, Debug)]
+pub struct LocalFileDa {
+    dir: PathBuf,
+    /// The index the next batch is stored at.
+    next_index: Arc<AtomicU64>,
+}
+
+impl LocalFileDa {
+    /// Keeps blobs under `dir`, creating it if need be, and carries on from
+    /// the blobs already there, and those pruned from it.
+    pub fn open(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let stored = std::fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some(BLOB_EXTENSION) {
+                    return None;
+                }
+                let (_, blob_index) = parse_request_id(path.file_stem()?.to_str()?).ok()?;
+                u64::try_from(blob_index).ok()
+            })
+            .map(|blob_index| blob_index + 1)
+            .max()
+            .unwrap_or_default();
+        let pruned = match std::fs::read_to_string(dir.join(PRUNED_FILE)) {
+            Ok(pruned) => pruned
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            dir,
+            next_index: Arc::new(AtomicU64::new(stored.max(pruned))),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes `batch` to a file of its own, answering as the disperser does
+    /// with a request id the blob can be validated by.
+    pub fn store(&self, batch: &str) -> std::io::Result<BlobResponse> {
+        let batch_header_hash = H256::from_slice(&Keccak256::digest(batch.as_bytes()));
+        let blob_index = self.next_index.fetch_add(1, Ordering::SeqCst);
+        std::fs::write(self.blob_path(batch_header_hash, blob_index as u128), batch)?;
+        let request_id = format!("{}_{blob_index}", hex::encode(batch_header_hash.0));
+        serde_json::from_value(json!({
+            "result": "PROCESSING",
+            "requestId": request_id,
+            "request_id": request_id,
+        }))
+        .map_err(invalid_data)
+    }
+
+    /// The proof the blob stored under `request_id` is confirmed with.
+    pub fn proof(&self, request_id: &str) -> std::io::Result<BlobVerificationProof> {
+        let (batch_header_hash, blob_index) = parse_request_id(request_id)?;
+        if !self.blob_path(batch_header_hash, blob_index).exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no blob was stored for request {request_id}"),
+            ));
+        }
+        let mut proof =
+            serde_json::to_value(BlobVerificationProof::default()).map_err(invalid_data)?;
+        set_field(
+            &mut proof,
+            "batchheaderhash",
+            &json!(base64::encode(batch_header_hash.0)),
+        );
+        set_field(&mut proof, "blobindex", &json!(blob_index as u64));
+        serde_json::from_value(proof).map_err(invalid_data)
+    }
+
+    /// The batch stored at `blob_index` under `batch_header_hash`.
+    pub fn retrieve(&self, batch_header_hash: H256, blob_index: u128) -> std::io::Result<String> {
+        std::fs::read_to_string(self.blob_path(batch_header_hash, blob_index))
+    }
+
+    /// How many bytes the blob stored at `blob_index` under
+    /// `batch_header_hash` takes up, if it is still there.
+    pub fn blob_size(&self, batch_header_hash: H256, blob_index: u128) -> Option<u64> {
+        std::fs::metadata(self.blob_path(batch_header_hash, blob_index))
+            .ok()
+            .map(|metadata| metadata.len())
+    }
+
+    /// Deletes the blob stored at `blob_index` under `batch_header_hash`,
+    /// answering how many bytes it took up, none if it was gone already.
+    pub fn prune(&self, batch_header_hash: H256, blob_index: u128) -> std::io::Result<u64> {
+        let Some(size) = self.blob_size(batch_header_hash, blob_index) else {
+            return Ok(0);
+        };
+        let pruned = self.dir.join(PRUNED_FILE);
+        let floor = std::fs::read_to_string(&pruned)
+            .ok()
+            .and_then(|floor| floor.trim().parse::<u128>().ok())
+            .unwrap_or_default();
+        if blob_index + 1 > floor {
+            std::fs::write(&pruned, (blob_index + 1).to_string())?;
+        }
+        std::fs::remove_file(self.blob_path(batch_header_hash, blob_index))?;
+        Ok(size)
+    }
+
+    fn blob_path(&self, batch_header_hash: H256, blob_index: u128) -> PathBuf {
+        self.dir.join(format!(
+            "{}_{blob_index}.{BLOB_EXTENSION}",
+            hex::encode(batch_header_hash.0)
+        ))
+    }
+}
+
+fn parse_request_id(request_id: &str) -> std::io::Result<(H256, u128)> {
+    let invalid = || {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{request_id} is not a local blob request id"),
+        )
+    };
+    let (hash, index) = request_id.split_once('_').ok_or_else(invalid)?;
+    let hash = hex::decode(hash)
+        .ok()
+        .filter(|hash| hash.len() == 32)
+        .ok_or_else(invalid)?;
+    let index = index.parse::<u128>().map_err(|_| invalid())?;
+    Ok((H256::from_slice(&hash), index))
+}
+
+/// Sets every field of `value` called `name`, however it is cased, to `to`.
+fn set_field(value: &mut Value, name: &str, to: &Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if key.replace('_', "").eq_ignore_ascii_case(name) {
+                    *field = to.clone();
+                } else {
+                    set_field(field, name, to);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| set_field(item, name, to)),
+        _ => {}
+    }
+}
+
+fn invalid_data(e: serde_json::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod local_da_tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("local_da_{}_{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    #[test]
+    fn a_stored_batch_is_proven_and_read_back_by_its_proof() {
+        let da = LocalFileDa::open(temp_dir("stored")).unwrap();
+        let first = da.store("first").unwrap();
+        let second = da.store("second").unwrap();
+        assert_ne!(first.request_id(), second.request_id());
+
+        let proof = da.proof(&second.request_id()).unwrap();
+        let batch_header_hash = H256::from_slice(
+            &base64::decode(proof.batch_metadata().batch_header_hash().to_string()).unwrap(),
+        );
+        assert_eq!(proof.blob_index(), 1);
+        assert_eq!(
+            da.retrieve(batch_header_hash, proof.blob_index()).unwrap(),
+            "second"
+        );
+        assert!(da.proof("not_a_request").is_err());
+    }
+
+    #[test]
+    fn blobs_are_indexed_on_from_those_already_stored() {
+        let dir = temp_dir("reopened");
+        LocalFileDa::open(&dir).unwrap().store("first").unwrap();
+        let reopened = LocalFileDa::open(&dir).unwrap();
+        let response = reopened.store("second").unwrap();
+        assert_eq!(
+            reopened.proof(&response.request_id()).unwrap().blob_index(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_pruned_blob_is_gone_and_its_index_not_used_again() {
+        let dir = temp_dir("pruned");
+        let da = LocalFileDa::open(&dir).unwrap();
+        let proof = da.proof(&da.store("first").unwrap().request_id()).unwrap();
+        let batch_header_hash = H256::from_slice(
+            &base64::decode(proof.batch_metadata().batch_header_hash().to_string()).unwrap(),
+        );
+        assert_eq!(da.blob_size(batch_header_hash, 0), Some(5));
+        assert_eq!(da.prune(batch_header_hash, 0).unwrap(), 5);
+        assert_eq!(da.prune(batch_header_hash, 0).unwrap(), 0);
+        assert!(da.retrieve(batch_header_hash, 0).is_err());
+        assert_eq!(da.blob_size(batch_header_hash, 0), None);
+
+        // With none left, the next blob is still stored past the pruned one.
+        let reopened = LocalFileDa::open(&dir).unwrap();
+        let response = reopened.store("second").unwrap();
+        assert_eq!(
+            reopened.proof(&response.request_id()).unwrap().blob_index(),
+            1
+        );
+    }
+}
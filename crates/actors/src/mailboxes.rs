@@ -0,0 +1,477 @@
+//! Watching the core actors' mailboxes for them falling behind.
+//!
+//! Each core actor times every message it handles and counts it handled. A
+//! monitor casts a probe to each of them in turn, which the actor hands back
+//! once it reaches it: the messages it handled in between are those that
+//! were queued ahead of the probe, and how long the probe waited is how far
+//! behind the actor is. A probe still out counts as waiting all the while,
+//! so an actor stuck on one message is caught as surely as one buried under
+//! many. Once either reaches its high-water mark the actor is overloaded,
+//! which `lasr_nodeStatus` reports, and the RPC server and the scheduler
+//! turn new transactions away. It is not overloaded again until both have
+//! fallen to their recovery marks, a share of the high-water ones, so a
+//! mailbox hovering at a mark does not flap.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use lasr_messages::{
+    AccountCacheMessage, ActorType, BatcherMessage, EngineMessage, ExecutorMessage, MailboxProbe,
+    PendingTransactionMessage, SchedulerMessage, ValidatorMessage,
+};
+use prometheus::{HistogramVec, IntCounter, IntCounterVec, IntGaugeVec};
+use ractor::{ActorCell, ActorId, ActorRef};
+use serde::Serialize;
+
+use crate::{metrics, Overloaded, DEFAULT_SHED_RETRY_AFTER_MS};
+
+pub const DEFAULT_MAILBOX_HIGH_WATER_DEPTH: u64 = 1_024;
+pub const DEFAULT_MAILBOX_HIGH_WATER_LATENCY_MS: u64 = 1_000;
+/// The share, in percent, of each high-water mark a mailbox falls to before
+/// its actor recovers.
+pub const DEFAULT_MAILBOX_RECOVER_PERCENT: u64 = 50;
+
+/// How often `monitor_mailboxes` probes the actors.
+pub const MAILBOX_PROBE_INTERVAL: Duration = Duration::from_millis(250);
+
+struct MailboxMetrics {
+    depth: IntGaugeVec,
+    latency: IntGaugeVec,
+    handle_seconds: HistogramVec,
+    overloaded: IntGaugeVec,
+    overloads: IntCounterVec,
+    shed: IntCounter,
+}
+
+fn mailbox_metrics() -> &'static MailboxMetrics {
+    static METRICS: OnceLock<MailboxMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| MailboxMetrics {
+        depth: metrics::gauge_vec(
+            "mailbox",
+            "depth",
+            "Messages queued ahead of the last probe in each actor's mailbox.",
+            &["actor"],
+        ),
+        latency: metrics::gauge_vec(
+            "mailbox",
+            "latency_ms",
+            "How long the last probe waited in each actor's mailbox, in milliseconds.",
+            &["actor"],
+        ),
+        handle_seconds: metrics::histogram_vec(
+            "mailbox",
+            "handle_seconds",
+            "How long each actor took to handle a message.",
+            &["actor"],
+        ),
+        overloaded: metrics::gauge_vec(
+            "mailbox",
+            "overloaded",
+            "Whether each actor's mailbox is past its high-water mark, 1 if so.",
+            &["actor"],
+        ),
+        overloads: metrics::counter_vec(
+            "mailbox",
+            "overloads_total",
+            "Times each actor's mailbox passed its high-water mark.",
+            &["actor"],
+        ),
+        shed: metrics::counter(
+            "mailbox",
+            "shed_total",
+            "Transactions turned away while a core actor was overloaded.",
+        ),
+    })
+}
+
+/// How far behind an actor is, as its mailbox was last probed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MailboxLoad {
+    /// Messages queued ahead of the probe.
+    pub depth: u64,
+    /// How long the probe waited to be reached.
+    pub latency: Duration,
+}
+
+impl MailboxLoad {
+    fn any_reach(&self, marks: &MailboxLoad) -> bool {
+        self.depth >= marks.depth || self.latency >= marks.latency
+    }
+
+    fn all_within(&self, marks: &MailboxLoad) -> bool {
+        self.depth <= marks.depth && self.latency <= marks.latency
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MailboxConfig {
+    /// The load an actor is overloaded at, either one reached.
+    pub high_water: MailboxLoad,
+    /// The load an actor recovers at, both fallen to.
+    pub recover: MailboxLoad,
+    /// How long a transaction turned away is told to wait.
+    pub retry_after: Duration,
+}
+
+impl Default for MailboxConfig {
+    fn default() -> Self {
+        Self::new(
+            MailboxLoad {
+                depth: DEFAULT_MAILBOX_HIGH_WATER_DEPTH,
+                latency: Duration::from_millis(DEFAULT_MAILBOX_HIGH_WATER_LATENCY_MS),
+            },
+            DEFAULT_MAILBOX_RECOVER_PERCENT,
+            Duration::from_millis(DEFAULT_SHED_RETRY_AFTER_MS),
+        )
+    }
+}
+
+impl MailboxConfig {
+    /// Overloaded at `high_water`, recovering once both are at
+    /// `recover_percent` of it.
+    pub fn new(high_water: MailboxLoad, recover_percent: u64, retry_after: Duration) -> Self {
+        let percent = recover_percent.min(100);
+        Self {
+            high_water,
+            recover: MailboxLoad {
+                depth: high_water.depth * percent / 100,
+                latency: high_water.latency * percent as u32 / 100,
+            },
+            retry_after,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Probes {
+    /// The probe cast and not yet handed back, and the actor it was cast to.
+    out: Option<(MailboxProbe, ActorId)>,
+    /// What the last probe handed back found.
+    last: MailboxLoad,
+}
+
+/// What an actor records of the messages it handles, for the monitor to
+/// read.
+#[derive(Debug, Default)]
+struct Mailbox {
+    handled: AtomicU64,
+    probes: Mutex<Probes>,
+}
+
+fn mailbox(actor_type: &ActorType) -> Arc<Mailbox> {
+    static MAILBOXES: OnceLock<RwLock<HashMap<ActorType, Arc<Mailbox>>>> = OnceLock::new();
+    let mailboxes = MAILBOXES.get_or_init(RwLock::default);
+    if let Some(mailbox) = mailboxes
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(actor_type)
+    {
+        return Arc::clone(mailbox);
+    }
+    Arc::clone(
+        mailboxes
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(actor_type.clone())
+            .or_default(),
+    )
+}
+
+/// An actor handling a message, timed until it is dropped and then counted
+/// handled.
+pub struct MailboxHandling {
+    actor_type: ActorType,
+    mailbox: Arc<Mailbox>,
+    started: Instant,
+}
+
+impl Drop for MailboxHandling {
+    fn drop(&mut self) {
+        self.mailbox.handled.fetch_add(1, Ordering::Relaxed);
+        mailbox_metrics()
+            .handle_seconds
+            .with_label_values(&[&self.actor_type.to_string()])
+            .observe(self.started.elapsed().as_secs_f64());
+    }
+}
+
+/// Starts `actor_type` handling a message, held for as long as it does.
+pub fn mailbox_handling(actor_type: ActorType) -> MailboxHandling {
+    MailboxHandling {
+        mailbox: mailbox(&actor_type),
+        actor_type,
+        started: Instant::now(),
+    }
+}
+
+/// Hands `probe` back from `actor_type`, which has just reached it.
+pub fn mailbox_probed(actor_type: ActorType, probe: MailboxProbe) {
+    let mailbox = mailbox(&actor_type);
+    let handled = mailbox.handled.load(Ordering::Relaxed);
+    let mut probes = mailbox.probes.lock().unwrap_or_else(|e| e.into_inner());
+    probes.out = None;
+    probes.last = MailboxLoad {
+        depth: handled.saturating_sub(probe.handled),
+        latency: probe.sent_at.elapsed(),
+    };
+}
+
+/// Casts a probe to an actor taking messages of type `M`.
+fn cast_probe<M>(actor: &ActorCell, probe: MailboxProbe) -> bool
+where
+    M: ractor::Message + From<MailboxProbe>,
+{
+    ActorRef::<M>::from(actor.clone())
+        .cast(M::from(probe))
+        .is_ok()
+}
+
+/// How far behind an actor is, as `lasr_nodeStatus` reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailboxStatus {
+    /// Messages queued ahead of the last probe.
+    pub depth: u64,
+    /// How long the last probe waited, or the one still out has so far.
+    pub latency_ms: u64,
+    pub overloaded: bool,
+}
+
+/// Which of the actors watched are overloaded, as last probed, shared by
+/// the monitor, the RPC server and the scheduler. One watching no actors
+/// finds none overloaded.
+#[derive(Debug, Clone, Default)]
+pub struct MailboxMonitor {
+    config: MailboxConfig,
+    watched: Vec<(ActorType, fn(&ActorCell, MailboxProbe) -> bool)>,
+    status: Arc<Mutex<BTreeMap<ActorType, MailboxStatus>>>,
+}
+
+impl MailboxMonitor {
+    pub fn new(config: MailboxConfig) -> Self {
+        Self {
+            config,
+            watched: Vec::new(),
+            status: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> MailboxConfig {
+        self.config
+    }
+
+    /// Watches the actor registered as `actor_type`, taking messages of type
+    /// `M`, as respawned by its supervisor too.
+    pub fn watch<M>(mut self, actor_type: ActorType) -> Self
+    where
+        M: ractor::Message + From<MailboxProbe>,
+    {
+        self.watched.push((actor_type, cast_probe::<M>));
+        self
+    }
+
+    /// Watches the actors every transaction passes through.
+    pub fn with_core_actors(self) -> Self {
+        self.watch::<SchedulerMessage>(ActorType::Scheduler)
+            .watch::<ValidatorMessage>(ActorType::Validator)
+            .watch::<EngineMessage>(ActorType::Engine)
+            .watch::<PendingTransactionMessage>(ActorType::PendingTransactions)
+            .watch::<AccountCacheMessage>(ActorType::AccountCache)
+            .watch::<BatcherMessage>(ActorType::Batcher)
+            .watch::<ExecutorMessage>(ActorType::Executor)
+    }
+
+    /// Reads what the last probe to each actor found, or the one still out
+    /// has so far, and casts a new one to each actor without one out.
+    pub fn probe(&self) {
+        for (actor_type, cast) in &self.watched {
+            let actor = ractor::registry::where_is(actor_type.to_string());
+            let mailbox = mailbox(actor_type);
+            let load = {
+                let mut probes = mailbox.probes.lock().unwrap_or_else(|e| e.into_inner());
+                // A probe cast to an actor since respawned is never handed
+                // back.
+                let respawned = probes.out.as_ref().is_some_and(|(_, id)| {
+                    actor.as_ref().map(|actor| actor.get_id()).as_ref() != Some(id)
+                });
+                if respawned {
+                    probes.out = None;
+                }
+                let handled = mailbox.handled.load(Ordering::Relaxed);
+                if let Some((probe, _)) = &probes.out {
+                    MailboxLoad {
+                        depth: probes.last.depth.max(handled.saturating_sub(probe.handled)),
+                        latency: probe.sent_at.elapsed(),
+                    }
+                } else {
+                    if let Some(actor) = &actor {
+                        let probe = MailboxProbe {
+                            sent_at: Instant::now(),
+                            handled,
+                        };
+                        if cast(actor, probe) {
+                            probes.out = Some((probe, actor.get_id()));
+                        }
+                    }
+                    probes.last
+                }
+            };
+            self.observe(actor_type.clone(), load);
+        }
+    }
+
+    /// Takes in how far behind `actor_type` is, answering whether it is now
+    /// overloaded.
+    pub fn observe(&self, actor_type: ActorType, load: MailboxLoad) -> bool {
+        let label = actor_type.to_string();
+        let metrics = mailbox_metrics();
+        metrics
+            .depth
+            .with_label_values(&[&label])
+            .set(load.depth as i64);
+        metrics
+            .latency
+            .with_label_values(&[&label])
+            .set(load.latency.as_millis() as i64);
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        let status = status.entry(actor_type).or_default();
+        status.depth = load.depth;
+        status.latency_ms = load.latency.as_millis() as u64;
+        if !status.overloaded && load.any_reach(&self.config.high_water) {
+            status.overloaded = true;
+            metrics.overloaded.with_label_values(&[&label]).set(1);
+            metrics.overloads.with_label_values(&[&label]).inc();
+            tracing::warn!(
+                "the {label} actor is overloaded: {} messages queued, {}ms behind",
+                load.depth,
+                load.latency.as_millis()
+            );
+        } else if status.overloaded && load.all_within(&self.config.recover) {
+            status.overloaded = false;
+            metrics.overloaded.with_label_values(&[&label]).set(0);
+            tracing::info!(
+                "the {label} actor has caught up: {} messages queued, {}ms behind",
+                load.depth,
+                load.latency.as_millis()
+            );
+        }
+        status.overloaded
+    }
+
+    /// The actors overloaded, as last probed.
+    pub fn overloaded(&self) -> Vec<ActorType> {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, status)| status.overloaded)
+            .map(|(actor_type, _)| actor_type.clone())
+            .collect()
+    }
+
+    /// How far behind each actor watched is, by actor.
+    pub fn status(&self) -> BTreeMap<String, MailboxStatus> {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(actor_type, status)| (actor_type.to_string(), *status))
+            .collect()
+    }
+
+    /// Turns a transaction away, and counts it, while an actor is
+    /// overloaded.
+    pub fn check(&self) -> Result<(), Overloaded> {
+        if self.overloaded().is_empty() {
+            return Ok(());
+        }
+        mailbox_metrics().shed.inc();
+        Err(Overloaded {
+            retry_after: self.config.retry_after,
+        })
+    }
+}
+
+/// Probes the mailboxes `monitor` watches every `interval`.
+pub async fn monitor_mailboxes(monitor: MailboxMonitor, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        monitor.probe();
+    }
+}
+
+#[cfg(test)]
+mod mailboxes_tests {
+    use super::*;
+
+    fn monitor() -> MailboxMonitor {
+        MailboxMonitor::new(MailboxConfig::new(
+            MailboxLoad {
+                depth: 100,
+                latency: Duration::from_millis(200),
+            },
+            50,
+            Duration::from_millis(500),
+        ))
+    }
+
+    fn depth(depth: u64) -> MailboxLoad {
+        MailboxLoad {
+            depth,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn an_actor_is_overloaded_until_its_mailbox_has_fallen_far_enough() {
+        let monitor = monitor();
+        assert!(monitor.check().is_ok());
+        assert!(!monitor.observe(ActorType::Batcher, depth(99)));
+        assert!(monitor.observe(
+            ActorType::Batcher,
+            MailboxLoad {
+                depth: 0,
+                latency: Duration::from_millis(200),
+            }
+        ));
+        assert_eq!(monitor.overloaded(), vec![ActorType::Batcher]);
+        assert_eq!(
+            monitor.check(),
+            Err(Overloaded {
+                retry_after: Duration::from_millis(500)
+            })
+        );
+        // Hovering about a mark keeps it overloaded rather than flapping.
+        for queued in [99, 101, 51] {
+            assert!(monitor.observe(ActorType::Batcher, depth(queued)));
+        }
+        assert!(!monitor.observe(
+            ActorType::Batcher,
+            MailboxLoad {
+                depth: 50,
+                latency: Duration::from_millis(100),
+            }
+        ));
+        assert!(monitor.check().is_ok());
+        assert_eq!(
+            monitor.status()["batcher"],
+            MailboxStatus {
+                depth: 50,
+                latency_ms: 100,
+                overloaded: false,
+            }
+        );
+    }
+
+    #[test]
+    fn actors_are_overloaded_apart() {
+        let monitor = monitor();
+        assert!(monitor.observe(ActorType::Engine, depth(150)));
+        assert!(!monitor.observe(ActorType::Validator, depth(10)));
+        assert_eq!(monitor.overloaded(), vec![ActorType::Engine]);
+        assert!(!monitor.status()["validator"].overloaded);
+        assert!(!monitor.observe(ActorType::Engine, depth(0)));
+        assert!(monitor.overloaded().is_empty());
+    }
+}
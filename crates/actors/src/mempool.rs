@@ -0,0 +1,188 @@
+//! Limits on what the pending pool holds, and the metrics kept on it.
+//!
+//! The pool is capped by the number of transactions pending and their total
+//! size, held for a gap in their nonces or not. Each sender also has a quota,
+//! which only matters once the pool is full: a sender may go over it while
+//! there is room, but once the pool is full only a replacement, or a sender
+//! under their quota, gets in, by evicting the latest transactions of the
+//! senders furthest over theirs. Bridge ins come from the settlement layer
+//! and are never limited.
+use crate::metrics;
+use lasr_messages::MempoolFull;
+use prometheus::{IntCounter, IntGauge};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// Transactions, and their total size in bytes, pending in the pool or from
+/// one sender.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Occupancy {
+    pub transactions: u64,
+    pub bytes: u64,
+}
+
+impl Occupancy {
+    pub fn add(&mut self, bytes: u64) {
+        self.transactions += 1;
+        self.bytes += bytes;
+    }
+
+    pub fn remove(&mut self, bytes: u64) {
+        self.transactions = self.transactions.saturating_sub(1);
+        self.bytes = self.bytes.saturating_sub(bytes);
+    }
+
+    /// This occupancy with one more transaction of `bytes`.
+    pub fn with(mut self, bytes: u64) -> Self {
+        self.add(bytes);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MempoolLimits {
+    pub max_transactions: u64,
+    pub max_bytes: u64,
+    pub sender_quota_transactions: u64,
+    pub sender_quota_bytes: u64,
+}
+
+impl Default for MempoolLimits {
+    fn default() -> Self {
+        Self {
+            max_transactions: 4096,
+            max_bytes: 64 * 1024 * 1024,
+            sender_quota_transactions: 64,
+            sender_quota_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+impl MempoolLimits {
+    pub fn fits(&self, pool: Occupancy) -> bool {
+        pool.transactions <= self.max_transactions && pool.bytes <= self.max_bytes
+    }
+
+    pub fn within_quota(&self, sender: Occupancy) -> bool {
+        sender.transactions <= self.sender_quota_transactions
+            && sender.bytes <= self.sender_quota_bytes
+    }
+
+    /// How far over their quota a sender is, as the larger of the shares of
+    /// their quotas they hold. Over 1 is over quota.
+    pub fn quota_used(&self, sender: Occupancy) -> f64 {
+        let share = |used: u64, quota: u64| used as f64 / quota.max(1) as f64;
+        share(sender.transactions, self.sender_quota_transactions)
+            .max(share(sender.bytes, self.sender_quota_bytes))
+    }
+
+    pub fn full(&self, pool: Occupancy, over_quota: bool) -> MempoolFull {
+        MempoolFull {
+            transactions: pool.transactions,
+            max_transactions: self.max_transactions,
+            bytes: pool.bytes,
+            max_bytes: self.max_bytes,
+            over_quota,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MempoolStatus {
+    pub transactions: u64,
+    pub bytes: u64,
+    pub evicted: u64,
+    pub rejected: u64,
+}
+
+struct PoolMetrics {
+    transactions: IntGauge,
+    bytes: IntGauge,
+    evicted: IntCounter,
+    rejected: IntCounter,
+}
+
+fn pool_metrics() -> &'static PoolMetrics {
+    static METRICS: OnceLock<PoolMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| PoolMetrics {
+        transactions: metrics::gauge(
+            "mempool",
+            "transactions",
+            "Transactions pending, including those held for a gap in their nonces.",
+        ),
+        bytes: metrics::gauge(
+            "mempool",
+            "bytes",
+            "Total size in bytes of the transactions pending.",
+        ),
+        evicted: metrics::counter(
+            "mempool",
+            "evicted_total",
+            "Transactions evicted to make room for others.",
+        ),
+        rejected: metrics::counter(
+            "mempool",
+            "rejected_total",
+            "Submissions turned away because the pool was full.",
+        ),
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct MempoolMetrics {
+    transactions: AtomicU64,
+    bytes: AtomicU64,
+    evicted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl MempoolMetrics {
+    /// Transactions pending, including those held for a gap in their nonces.
+    pub fn transactions(&self) -> u64 {
+        self.transactions.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Transactions evicted to make room for others.
+    pub fn evicted(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+
+    /// Submissions turned away because the pool was full.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> MempoolStatus {
+        MempoolStatus {
+            transactions: self.transactions(),
+            bytes: self.bytes(),
+            evicted: self.evicted(),
+            rejected: self.rejected(),
+        }
+    }
+
+    pub(crate) fn record_occupancy(&self, pool: Occupancy) {
+        self.transactions
+            .store(pool.transactions, Ordering::Relaxed);
+        self.bytes.store(pool.bytes, Ordering::Relaxed);
+        let metrics = pool_metrics();
+        metrics.transactions.set(pool.transactions as i64);
+        metrics.bytes.set(pool.bytes as i64);
+    }
+
+    pub(crate) fn record_eviction(&self) {
+        self.evicted.fetch_add(1, Ordering::Relaxed);
+        pool_metrics().evicted.inc();
+    }
+
+    pub(crate) fn record_rejection(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        pool_metrics().rejected.inc();
+    }
+}
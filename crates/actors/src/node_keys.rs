@@ -0,0 +1,595 @@
+//! The operator keys a node signs with, by what it signs with them.
+//!
+//! Settlement submissions to the contracts are signed with the settlement
+//! key, and execution attestations with the attestation key, which is the
+//! settlement key unless the keystore sets one of its own. Whatever signs
+//! holds a [`KeyHandle`] for its role and reads the active key each time it
+//! signs, so a rotation takes effect from the next signature on. A key
+//! rotated out stops signing but its signer stays trusted, so what it signed
+//! before the rotation still verifies as this node's.
+//!
+//! Keys are loaded from a keystore directory of Web3 Secret Storage files,
+//! encrypted with one password, and a `keys.toml` naming the file of each
+//! role's active key and the signers of those it retired. A rotation names
+//! a file already placed in the keystore; `keys.toml` is rewritten before
+//! the key is swapped, so a node restarted after a rotation signs with the
+//! key it rotated to.
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use lasr_types::Address;
+use schemars::JsonSchema;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+
+/// The file in the keystore naming the keys in use.
+pub const KEYSTORE_MANIFEST: &str = "keys.toml";
+
+#[derive(Debug, Error)]
+pub enum NodeKeysError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("unable to decrypt key file {file}: {reason}")]
+    Keystore { file: String, reason: String },
+
+    #[error("key file {0} does not hold a secp256k1 key")]
+    InvalidKey(String),
+
+    #[error("invalid {role} key: {source}")]
+    InvalidSecretKey {
+        role: KeyRole,
+        source: secp256k1::Error,
+    },
+
+    #[error("invalid retired signer {0}")]
+    InvalidSigner(String),
+
+    #[error("key file {0} must be the name of a file in the keystore")]
+    InvalidFileName(String),
+
+    #[error("invalid {KEYSTORE_MANIFEST}: {0}")]
+    Manifest(String),
+
+    #[error("{signer} is already the active {role} key")]
+    AlreadyActive { role: KeyRole, signer: Address },
+
+    #[error("the node's keys are not loaded from a keystore, so cannot be rotated")]
+    NoKeystore,
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// What a node signs with a key.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyRole {
+    /// Transactions submitting batches, and their settlement, to the
+    /// settlement layer's contracts.
+    Settlement,
+    /// Attestations of the executions the node applied.
+    Attestation,
+}
+
+impl KeyRole {
+    pub const ALL: [KeyRole; 2] = [KeyRole::Settlement, KeyRole::Attestation];
+}
+
+impl std::fmt::Display for KeyRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyRole::Settlement => write!(f, "settlement"),
+            KeyRole::Attestation => write!(f, "attestation"),
+        }
+    }
+}
+
+impl FromStr for KeyRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "settlement" => Ok(KeyRole::Settlement),
+            "attestation" => Ok(KeyRole::Attestation),
+            other => Err(format!(
+                "unknown key role {other}, expected settlement or attestation"
+            )),
+        }
+    }
+}
+
+/// A key the node signs with, or signed with before rotating it out, as
+/// reported without giving the key away.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyFingerprint {
+    pub role: KeyRole,
+    pub signer: Address,
+    /// The first 8 bytes of the keccak256 hash of `signer`, hex encoded.
+    pub fingerprint: String,
+    /// Whether the key signs, rather than only being trusted.
+    pub active: bool,
+}
+
+impl KeyFingerprint {
+    fn new(role: KeyRole, signer: Address, active: bool) -> Self {
+        let hash = Keccak256::digest(signer.inner());
+        Self {
+            role,
+            signer,
+            fingerprint: format!("0x{}", hex::encode(&hash[..8])),
+            active,
+        }
+    }
+}
+
+/// What `keys.toml` holds for a role.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// The key file the role signs with.
+    active: String,
+    /// The signers of the keys the role rotated out, newest first.
+    #[serde(default)]
+    retired: Vec<Address>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct KeystoreManifest {
+    settlement: ManifestEntry,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attestation: Option<ManifestEntry>,
+}
+
+impl KeystoreManifest {
+    fn entry(&self, role: KeyRole) -> &ManifestEntry {
+        match (role, &self.attestation) {
+            (KeyRole::Attestation, Some(entry)) => entry,
+            _ => &self.settlement,
+        }
+    }
+
+    fn entry_mut(&mut self, role: KeyRole) -> &mut ManifestEntry {
+        match role {
+            KeyRole::Settlement => &mut self.settlement,
+            KeyRole::Attestation => {
+                let settlement = &self.settlement;
+                self.attestation.get_or_insert_with(|| settlement.clone())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RoleKeys {
+    secret_key: SecretKey,
+    signer: Address,
+    retired: Vec<Address>,
+}
+
+impl RoleKeys {
+    fn new(secret_key: SecretKey, retired: Vec<Address>) -> Self {
+        let signer = Address::from(PublicKey::from_secret_key(
+            &Secp256k1::signing_only(),
+            &secret_key,
+        ));
+        Self {
+            secret_key,
+            signer,
+            retired,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Keystore {
+    dir: PathBuf,
+    password: String,
+    manifest: KeystoreManifest,
+}
+
+#[derive(Debug)]
+struct NodeKeysInner {
+    settlement: RoleKeys,
+    attestation: RoleKeys,
+    keystore: Option<Keystore>,
+}
+
+impl NodeKeysInner {
+    fn role(&self, role: KeyRole) -> &RoleKeys {
+        match role {
+            KeyRole::Settlement => &self.settlement,
+            KeyRole::Attestation => &self.attestation,
+        }
+    }
+
+    fn role_mut(&mut self, role: KeyRole) -> &mut RoleKeys {
+        match role {
+            KeyRole::Settlement => &mut self.settlement,
+            KeyRole::Attestation => &mut self.attestation,
+        }
+    }
+}
+
+/// The node's keys, shared by whatever signs with them.
+#[derive(Debug, Clone)]
+pub struct NodeKeys {
+    inner: Arc<RwLock<NodeKeysInner>>,
+}
+
+impl NodeKeys {
+    /// Keys signing everything with `secret_key`, which cannot be rotated.
+    pub fn new(secret_key: SecretKey) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(NodeKeysInner {
+                settlement: RoleKeys::new(secret_key, Vec::new()),
+                attestation: RoleKeys::new(secret_key, Vec::new()),
+                keystore: None,
+            })),
+        }
+    }
+
+    /// Signs for `role` with `secret_key`, still trusting the
+    /// `retired` signers.
+    pub fn with_key(self, role: KeyRole, secret_key: SecretKey, retired: Vec<Address>) -> Self {
+        *self.write().role_mut(role) = RoleKeys::new(secret_key, retired);
+        self
+    }
+
+    /// Parses a hex encoded settlement `secret_key`, a hex encoded
+    /// `attestation_secret_key`, and a comma separated list of
+    /// `attestation_retired_signers` addresses. Keys given this way cannot be
+    /// rotated while the node runs.
+    pub fn from_config(
+        secret_key: &str,
+        attestation_secret_key: &str,
+        attestation_retired_signers: &str,
+    ) -> Result<Self, NodeKeysError> {
+        let parse = |role: KeyRole, key: &str| {
+            SecretKey::from_str(key.trim_start_matches("0x"))
+                .map_err(|source| NodeKeysError::InvalidSecretKey { role, source })
+        };
+        let retired = attestation_retired_signers
+            .split(',')
+            .map(str::trim)
+            .filter(|signer| !signer.is_empty())
+            .map(|signer| {
+                Address::from_hex(signer).map_err(|_| NodeKeysError::InvalidSigner(signer.into()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(
+            NodeKeys::new(parse(KeyRole::Settlement, secret_key)?).with_key(
+                KeyRole::Attestation,
+                parse(KeyRole::Attestation, attestation_secret_key)?,
+                retired,
+            ),
+        )
+    }
+
+    /// Loads the keys `keys.toml` in the keystore at `dir` names, decrypting
+    /// them with `password`.
+    pub fn open(dir: &Path, password: &str) -> Result<Self, NodeKeysError> {
+        let manifest: KeystoreManifest =
+            toml::from_str(&std::fs::read_to_string(dir.join(KEYSTORE_MANIFEST))?)
+                .map_err(|e| NodeKeysError::Manifest(e.to_string()))?;
+        let load = |role: KeyRole| -> Result<RoleKeys, NodeKeysError> {
+            let entry = manifest.entry(role);
+            Ok(RoleKeys::new(
+                decrypt(dir, &entry.active, password)?,
+                entry.retired.clone(),
+            ))
+        };
+        let settlement = load(KeyRole::Settlement)?;
+        let attestation = match manifest.attestation {
+            Some(_) => load(KeyRole::Attestation)?,
+            None => settlement.clone(),
+        };
+        Ok(Self {
+            inner: Arc::new(RwLock::new(NodeKeysInner {
+                settlement,
+                attestation,
+                keystore: Some(Keystore {
+                    dir: dir.to_path_buf(),
+                    password: password.to_string(),
+                    manifest,
+                }),
+            })),
+        })
+    }
+
+    /// The handle signing for `role`.
+    pub fn key(&self, role: KeyRole) -> KeyHandle {
+        KeyHandle {
+            role,
+            keys: self.clone(),
+        }
+    }
+
+    /// Makes the key in keystore file `file` the one signing for `role`,
+    /// retiring the one that did, and returns it. The old key's signer stays
+    /// trusted. Signatures made while the keys are swapped are made with one
+    /// key or the other, never neither. An attestation key that is the
+    /// settlement key is rotated with it.
+    pub fn rotate(&self, role: KeyRole, file: &str) -> Result<KeyFingerprint, NodeKeysError> {
+        let mut inner = self.write();
+        let Some(keystore) = &inner.keystore else {
+            return Err(NodeKeysError::NoKeystore);
+        };
+        let secret_key = decrypt(&keystore.dir, file, &keystore.password)?;
+        let current = inner.role(role);
+        let mut rotated = RoleKeys::new(secret_key, Vec::new());
+        if rotated.signer == current.signer {
+            return Err(NodeKeysError::AlreadyActive {
+                role,
+                signer: rotated.signer,
+            });
+        }
+        rotated.retired = std::iter::once(current.signer)
+            .chain(current.retired.iter().copied())
+            .filter(|signer| *signer != rotated.signer)
+            .collect();
+
+        let mut manifest = keystore.manifest.clone();
+        *manifest.entry_mut(role) = ManifestEntry {
+            active: file.to_string(),
+            retired: rotated.retired.clone(),
+        };
+        write_manifest(&keystore.dir, &manifest)?;
+
+        let fingerprint = KeyFingerprint::new(role, rotated.signer, true);
+        tracing::warn!(
+            "rotated the {role} key to {} ({})",
+            rotated.signer.to_full_string(),
+            fingerprint.fingerprint
+        );
+        if role == KeyRole::Settlement && manifest.attestation.is_none() {
+            inner.attestation = rotated.clone();
+        }
+        *inner.role_mut(role) = rotated;
+        if let Some(keystore) = &mut inner.keystore {
+            keystore.manifest = manifest;
+        }
+        Ok(fingerprint)
+    }
+
+    /// Every key signing, and every one retired, by role.
+    pub fn fingerprints(&self) -> Vec<KeyFingerprint> {
+        let inner = self.read();
+        KeyRole::ALL
+            .into_iter()
+            .flat_map(|role| {
+                let keys = inner.role(role);
+                std::iter::once(KeyFingerprint::new(role, keys.signer, true)).chain(
+                    keys.retired
+                        .iter()
+                        .map(move |signer| KeyFingerprint::new(role, *signer, false)),
+                )
+            })
+            .collect()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, NodeKeysInner> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, NodeKeysInner> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Signs for one role with whichever key is active for it.
+#[derive(Debug, Clone)]
+pub struct KeyHandle {
+    role: KeyRole,
+    keys: NodeKeys,
+}
+
+impl KeyHandle {
+    pub fn role(&self) -> KeyRole {
+        self.role
+    }
+
+    /// The key to sign with now.
+    pub fn secret_key(&self) -> SecretKey {
+        self.keys.read().role(self.role).secret_key
+    }
+
+    /// The key to sign with now, for signing with `web3`.
+    pub fn web3_key(&self) -> web3::signing::SecretKey {
+        web3::signing::SecretKey::from_slice(&self.secret_key().secret_bytes())
+            .expect("a secp256k1 key is a secp256k1 key")
+    }
+
+    /// The address of the key to sign with now.
+    pub fn signer(&self) -> Address {
+        self.keys.read().role(self.role).signer
+    }
+
+    /// The current signer followed by every retired one, newest first.
+    pub fn trusted_signers(&self) -> Vec<Address> {
+        let inner = self.keys.read();
+        let keys = inner.role(self.role);
+        std::iter::once(keys.signer)
+            .chain(keys.retired.iter().copied())
+            .collect()
+    }
+}
+
+/// Decrypts keystore file `file` in `dir` with `password`.
+fn decrypt(dir: &Path, file: &str, password: &str) -> Result<SecretKey, NodeKeysError> {
+    if file.is_empty() || Path::new(file).file_name() != Some(file.as_ref()) {
+        return Err(NodeKeysError::InvalidFileName(file.to_string()));
+    }
+    let bytes = eth_keystore::decrypt_key(dir.join(file), password).map_err(|e| {
+        NodeKeysError::Keystore {
+            file: file.to_string(),
+            reason: e.to_string(),
+        }
+    })?;
+    SecretKey::from_slice(&bytes).map_err(|_| NodeKeysError::InvalidKey(file.to_string()))
+}
+
+/// Replaces `keys.toml` in `dir` with `manifest`, whole or not at all.
+fn write_manifest(dir: &Path, manifest: &KeystoreManifest) -> Result<(), NodeKeysError> {
+    let contents = toml::to_string(manifest).map_err(|e| NodeKeysError::Manifest(e.to_string()))?;
+    let path = dir.join(KEYSTORE_MANIFEST);
+    let rewriting = path.with_extension("toml.rewriting");
+    {
+        let mut file = std::fs::File::create(&rewriting)?;
+        std::io::Write::write_all(&mut file, contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&rewriting, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod node_keys_tests {
+    use super::*;
+    use crate::Attestor;
+    use lasr_types::{Inputs, Outputs};
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn keystore(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lasr_node_keys_{}_{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `secret_key` to keystore file `file` in `dir`.
+    fn key_file(dir: &Path, file: &str, secret_key: &SecretKey) {
+        eth_keystore::encrypt_key(
+            dir,
+            &mut secp256k1::rand::rngs::OsRng,
+            secret_key.secret_bytes(),
+            PASSWORD,
+            Some(file),
+        )
+        .unwrap();
+    }
+
+    fn manifest(dir: &Path, contents: &str) {
+        std::fs::write(dir.join(KEYSTORE_MANIFEST), contents).unwrap();
+    }
+
+    fn key(byte: u8) -> SecretKey {
+        SecretKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn signer(secret_key: &SecretKey) -> Address {
+        Address::from(PublicKey::from_secret_key(&Secp256k1::new(), secret_key))
+    }
+
+    fn outputs() -> Outputs {
+        Outputs::new(Inputs::default(), Vec::new())
+    }
+
+    #[test]
+    fn the_settlement_key_attests_unless_the_keystore_says_otherwise() {
+        let dir = keystore("shared");
+        key_file(&dir, "operator.json", &key(1));
+        manifest(&dir, "[settlement]\nactive = \"operator.json\"\n");
+
+        let keys = NodeKeys::open(&dir, PASSWORD).unwrap();
+        assert_eq!(keys.key(KeyRole::Settlement).signer(), signer(&key(1)));
+        assert_eq!(keys.key(KeyRole::Attestation).signer(), signer(&key(1)));
+        assert_eq!(keys.fingerprints().len(), 2);
+        assert!(keys.fingerprints().iter().all(|key| key.active));
+
+        assert!(matches!(
+            NodeKeys::open(&dir, "wrong password"),
+            Err(NodeKeysError::Keystore { .. })
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn attestations_signed_before_a_rotation_still_verify_after_it() {
+        let dir = keystore("rotation");
+        key_file(&dir, "operator.json", &key(1));
+        key_file(&dir, "attestation-1.json", &key(2));
+        key_file(&dir, "attestation-2.json", &key(3));
+        manifest(
+            &dir,
+            "[settlement]\nactive = \"operator.json\"\n\n\
+             [attestation]\nactive = \"attestation-1.json\"\n",
+        );
+        let keys = NodeKeys::open(&dir, PASSWORD).unwrap();
+        let attestor = Attestor::from_keys(&keys);
+        let before = attestor.attest("0x01".to_string(), &outputs(), []);
+        assert_eq!(before.signer, signer(&key(2)));
+
+        let rotated = keys
+            .rotate(KeyRole::Attestation, "attestation-2.json")
+            .unwrap();
+        assert_eq!(rotated.signer, signer(&key(3)));
+        // The attestor built before the rotation signs with the new key.
+        let after = attestor.attest("0x02".to_string(), &outputs(), []);
+        assert_eq!(after.signer, signer(&key(3)));
+        assert_eq!(
+            attestor.trusted_signers(),
+            vec![signer(&key(3)), signer(&key(2))]
+        );
+        before
+            .verify_signed_by(&attestor.trusted_signers())
+            .unwrap();
+        after.verify_signed_by(&attestor.trusted_signers()).unwrap();
+        assert!(before.verify_signed_by(&[attestor.signer()]).is_err());
+        // The settlement key is left alone.
+        assert_eq!(keys.key(KeyRole::Settlement).signer(), signer(&key(1)));
+
+        let retired: Vec<_> = keys
+            .fingerprints()
+            .into_iter()
+            .filter(|key| !key.active)
+            .collect();
+        assert_eq!(retired.len(), 1);
+        assert_eq!(retired[0].signer, signer(&key(2)));
+        assert_eq!(retired[0].fingerprint.len(), 18);
+
+        // Restarted, the node signs with the key it rotated to.
+        let reopened = NodeKeys::open(&dir, PASSWORD).unwrap();
+        assert_eq!(reopened.fingerprints(), keys.fingerprints());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_rotation_the_keystore_cannot_make_changes_nothing() {
+        let dir = keystore("refused");
+        key_file(&dir, "operator.json", &key(1));
+        key_file(&dir, "copy.json", &key(1));
+        manifest(&dir, "[settlement]\nactive = \"operator.json\"\n");
+        let keys = NodeKeys::open(&dir, PASSWORD).unwrap();
+
+        assert!(matches!(
+            keys.rotate(KeyRole::Settlement, "copy.json"),
+            Err(NodeKeysError::AlreadyActive { .. })
+        ));
+        assert!(matches!(
+            keys.rotate(KeyRole::Settlement, "missing.json"),
+            Err(NodeKeysError::Keystore { .. })
+        ));
+        assert!(matches!(
+            keys.rotate(KeyRole::Settlement, "../operator.json"),
+            Err(NodeKeysError::InvalidFileName(_))
+        ));
+        assert!(matches!(
+            NodeKeys::new(key(1)).rotate(KeyRole::Settlement, "copy.json"),
+            Err(NodeKeysError::NoKeystore)
+        ));
+        assert_eq!(keys.fingerprints().len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(KEYSTORE_MANIFEST)).unwrap(),
+            "[settlement]\nactive = \"operator.json\"\n"
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
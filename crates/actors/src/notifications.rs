@@ -0,0 +1,184 @@
+//! Notifications of the changes the node makes, for RPC subscribers.
+//!
+//! The account cache publishes every account it writes, and the scheduler
+//! the outcome of every transaction it answers. Each account and each
+//! transaction hash gets its own broadcast channel, made when it is first
+//! subscribed to and dropped once its last subscriber has gone, so nothing is
+//! kept for what no one watches. A subscriber that falls behind loses the
+//! oldest notifications it had not read yet and is told how many it lost,
+//! rather than holding up the publisher.
+use lasr_messages::TransactionResponse;
+use lasr_types::{Account, AccountType, Address};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Notifications buffered for each subscriber unless configured otherwise.
+pub const DEFAULT_SUBSCRIPTION_BUFFER: usize = 256;
+
+#[derive(Debug)]
+struct Topics<K, T> {
+    channels: Arc<Mutex<HashMap<K, broadcast::Sender<T>>>>,
+}
+
+impl<K, T> Clone for Topics<K, T> {
+    fn clone(&self) -> Self {
+        Self {
+            channels: Arc::clone(&self.channels),
+        }
+    }
+}
+
+impl<K, T> Default for Topics<K, T> {
+    fn default() -> Self {
+        Self {
+            channels: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<K: Eq + Hash, T: Clone> Topics<K, T> {
+    fn subscribe(&self, key: K, buffer: usize) -> Subscription<T> {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        let receiver = channels
+            .entry(key)
+            .or_insert_with(|| broadcast::channel(buffer).0)
+            .subscribe();
+        Subscription::new(receiver)
+    }
+
+    fn publish(&self, key: &K, item: &T) {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = channels.get(key) {
+            // Only fails once every subscriber is gone.
+            if sender.send(item.clone()).is_err() {
+                channels.remove(key);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifications {
+    buffer: usize,
+    accounts: Topics<Address, Account>,
+    transactions: Topics<String, TransactionResponse>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self::new(DEFAULT_SUBSCRIPTION_BUFFER)
+    }
+}
+
+impl Notifications {
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            buffer: buffer.max(1),
+            accounts: Topics::default(),
+            transactions: Topics::default(),
+        }
+    }
+
+    /// Subscribes to the account at `address` each time it is written.
+    pub fn subscribe_account(&self, address: Address) -> Subscription<Account> {
+        self.accounts.subscribe(address, self.buffer)
+    }
+
+    /// Subscribes to the outcomes `transaction_hash` is answered with.
+    pub fn subscribe_transaction(
+        &self,
+        transaction_hash: &str,
+    ) -> Subscription<TransactionResponse> {
+        self.transactions
+            .subscribe(transaction_hash.to_lowercase(), self.buffer)
+    }
+
+    /// Publishes `account` to those subscribed to it. Program accounts are
+    /// kept under the program's address, as in the account cache.
+    pub fn account_written(&self, account: &Account) {
+        let address = match account.account_type() {
+            AccountType::User => account.owner_address(),
+            AccountType::Program(program_address) => program_address,
+        };
+        self.accounts.publish(&address, account);
+    }
+
+    pub fn transaction_answered(&self, transaction_hash: &str, response: &TransactionResponse) {
+        self.transactions
+            .publish(&transaction_hash.to_lowercase(), response);
+    }
+}
+
+/// Notifications from one subscription, in the order they were published.
+#[derive(Debug)]
+pub struct Subscription<T> {
+    receiver: broadcast::Receiver<T>,
+}
+
+impl<T: Clone> Subscription<T> {
+    pub(crate) fn new(receiver: broadcast::Receiver<T>) -> Self {
+        Self { receiver }
+    }
+
+    /// The next notification, with the number dropped since the last one
+    /// because the subscriber fell behind, or `None` once nothing more can
+    /// be published to it.
+    pub async fn next(&mut self) -> Option<(T, u64)> {
+        let mut dropped = 0;
+        loop {
+            match self.receiver.recv().await {
+                Ok(item) => return Some((item, dropped)),
+                Err(broadcast::error::RecvError::Lagged(missed)) => dropped += missed,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod notifications_tests {
+    use super::*;
+    use lasr_types::U256;
+
+    fn account(nonce: u64) -> Account {
+        let mut account = Account::new(Address::new([1; 20]));
+        for _ in 0..nonce {
+            account.increment_nonce();
+        }
+        account
+    }
+
+    #[tokio::test]
+    async fn a_slow_subscriber_is_told_how_many_it_missed() {
+        let notifications = Notifications::new(2);
+        let mut subscription = notifications.subscribe_account(Address::new([1; 20]));
+        for nonce in 0..5 {
+            notifications.account_written(&account(nonce));
+        }
+        // Someone else's account is not delivered.
+        notifications.account_written(&Account::new(Address::new([2; 20])));
+
+        let (first, dropped) = subscription.next().await.unwrap();
+        assert_eq!(first.nonce(), U256::from(3));
+        assert_eq!(dropped, 3);
+        let (second, dropped) = subscription.next().await.unwrap();
+        assert_eq!(second.nonce(), U256::from(4));
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn a_channel_is_dropped_with_its_last_subscriber() {
+        let notifications = Notifications::default();
+        let subscription = notifications.subscribe_transaction("0xAB");
+        drop(subscription);
+        notifications.transaction_answered("0xab", &TransactionResponse::Expired);
+        assert!(notifications
+            .transactions
+            .channels
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+}
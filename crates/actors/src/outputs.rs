@@ -0,0 +1,632 @@
+//! Validation of the outputs a program returns, before the engine hands them
+//! on to be applied.
+//!
+//! Outputs are parsed strictly into [`Outputs`], checked against size and
+//! count caps, and every account an instruction debits or modifies must be
+//! one the transaction is allowed to touch. Anything that fails is a revert
+//! by the program, it never reaches the batcher.
+use std::collections::HashSet;
+
+use lasr_types::{
+    Account, Address, AddressOrNamespace, ContractLogType, Instruction, Outputs,
+    TokenOrProgramUpdate, Transaction,
+};
+use thiserror::Error;
+
+use crate::get_account;
+use lasr_messages::ActorType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLimits {
+    /// Size of the raw outputs in bytes.
+    pub max_bytes: usize,
+    pub max_instructions: usize,
+    /// Length of any list inside a single instruction, e.g. distributions,
+    /// updates or token ids.
+    pub max_items: usize,
+    pub max_log_length: usize,
+    pub max_events: usize,
+    /// Size of a single event's topic and attributes in bytes.
+    pub max_event_bytes: usize,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            max_instructions: 256,
+            max_items: 1024,
+            max_log_length: 4096,
+            max_events: 64,
+            max_event_bytes: 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum OutputValidationError {
+    #[error("outputs are {size} bytes, the limit is {limit}")]
+    TooLarge { size: usize, limit: usize },
+
+    #[error("outputs are malformed: {0}")]
+    Malformed(String),
+
+    #[error("outputs contain {count} instructions, the limit is {limit}")]
+    TooManyInstructions { count: usize, limit: usize },
+
+    #[error("instruction {index} contains {count} {what}, the limit is {limit}")]
+    TooManyItems {
+        index: usize,
+        what: &'static str,
+        count: usize,
+        limit: usize,
+    },
+
+    #[error(
+        "instruction {index} touches {account}, which the transaction is not allowed to touch"
+    )]
+    UnauthorizedAccount { index: usize, account: String },
+
+    #[error("outputs contain {count} events, the limit is {limit}")]
+    TooManyEvents { count: usize, limit: usize },
+
+    #[error("event {index} is {size} bytes, the limit is {limit}")]
+    EventTooLarge {
+        index: usize,
+        size: usize,
+        limit: usize,
+    },
+
+    #[error("event {index} has no topic")]
+    MissingTopic { index: usize },
+}
+
+/// An account other than the caller or the program that an instruction
+/// debits. These are only allowed if the account approved the program, or
+/// the caller, to spend `token` on its behalf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThirdPartyDebit {
+    pub index: usize,
+    pub account: Address,
+    pub token: Address,
+}
+
+/// Parses and checks `outputs` without looking up any accounts, returning
+/// the third party debits that still need their approvals checked.
+pub fn parse_outputs(
+    outputs: &str,
+    transaction: &Transaction,
+    limits: &OutputLimits,
+) -> Result<(Outputs, Vec<ThirdPartyDebit>), OutputValidationError> {
+    if outputs.len() > limits.max_bytes {
+        return Err(OutputValidationError::TooLarge {
+            size: outputs.len(),
+            limit: limits.max_bytes,
+        });
+    }
+
+    let parsed: Outputs = serde_json::from_str(outputs)
+        .map_err(|e| OutputValidationError::Malformed(e.to_string()))?;
+
+    let instructions = parsed.instructions();
+    if instructions.len() > limits.max_instructions {
+        return Err(OutputValidationError::TooManyInstructions {
+            count: instructions.len(),
+            limit: limits.max_instructions,
+        });
+    }
+
+    let events = parsed.events();
+    if events.len() > limits.max_events {
+        return Err(OutputValidationError::TooManyEvents {
+            count: events.len(),
+            limit: limits.max_events,
+        });
+    }
+    for (index, event) in events.iter().enumerate() {
+        if event.topic.is_empty() {
+            return Err(OutputValidationError::MissingTopic { index });
+        }
+        if event.size() > limits.max_event_bytes {
+            return Err(OutputValidationError::EventTooLarge {
+                index,
+                size: event.size(),
+                limit: limits.max_event_bytes,
+            });
+        }
+    }
+
+    let caller = transaction.from();
+    let program = transaction.to();
+    let is_caller_or_program = |address: &AddressOrNamespace| match address {
+        AddressOrNamespace::This => true,
+        AddressOrNamespace::Address(address) => *address == caller || *address == program,
+        AddressOrNamespace::Namespace(_) => false,
+    };
+    let unauthorized =
+        |index: usize, account: &AddressOrNamespace| OutputValidationError::UnauthorizedAccount {
+            index,
+            account: match account {
+                AddressOrNamespace::This => program.to_full_string(),
+                AddressOrNamespace::Address(address) => address.to_full_string(),
+                AddressOrNamespace::Namespace(namespace) => format!("{namespace:?}"),
+            },
+        };
+
+    // Programs created by these outputs may be updated by them too.
+    let mut created: HashSet<Address> = HashSet::new();
+    let mut third_party_debits = Vec::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        let check_items = |what: &'static str, count: usize| {
+            if count > limits.max_items {
+                return Err(OutputValidationError::TooManyItems {
+                    index,
+                    what,
+                    count,
+                    limit: limits.max_items,
+                });
+            }
+            Ok(())
+        };
+
+        match instruction {
+            Instruction::Create(create) => {
+                check_items("distributions", create.distribution().len())?;
+                for distribution in create.distribution() {
+                    check_items("token ids", distribution.token_ids().len())?;
+                    check_items("update fields", distribution.update_fields().len())?;
+                }
+                if let AddressOrNamespace::Address(program_id) = create.program_id() {
+                    created.insert(*program_id);
+                }
+            }
+            Instruction::Update(update) => {
+                check_items("updates", update.updates().len())?;
+                for update in update.updates() {
+                    match update {
+                        TokenOrProgramUpdate::TokenUpdate(token_update) => {
+                            check_items("update fields", token_update.updates().len())?;
+                            // A program may update its own token on any
+                            // account, other tokens only on the caller's.
+                            let own_token = match token_update.token() {
+                                AddressOrNamespace::This => true,
+                                AddressOrNamespace::Address(token) => *token == program,
+                                AddressOrNamespace::Namespace(_) => false,
+                            };
+                            if !own_token && !is_caller_or_program(token_update.account()) {
+                                return Err(unauthorized(index, token_update.account()));
+                            }
+                        }
+                        TokenOrProgramUpdate::ProgramUpdate(program_update) => {
+                            check_items("update fields", program_update.updates().len())?;
+                            let account = program_update.account();
+                            let created_here = matches!(
+                                account,
+                                AddressOrNamespace::Address(address) if created.contains(address)
+                            );
+                            if !created_here && !is_caller_or_program(account) {
+                                return Err(unauthorized(index, account));
+                            }
+                        }
+                    }
+                }
+            }
+            Instruction::Transfer(transfer) => {
+                check_items("token ids", transfer.ids().len())?;
+                match transfer.from() {
+                    from if is_caller_or_program(from) => {}
+                    AddressOrNamespace::Address(from) => third_party_debits.push(ThirdPartyDebit {
+                        index,
+                        account: *from,
+                        token: *transfer.token(),
+                    }),
+                    from => return Err(unauthorized(index, from)),
+                }
+            }
+            Instruction::Burn(burn) => {
+                check_items("token ids", burn.token_ids().len())?;
+                if *burn.caller() != caller {
+                    return Err(unauthorized(
+                        index,
+                        &AddressOrNamespace::Address(*burn.caller()),
+                    ));
+                }
+                match burn.from() {
+                    from if is_caller_or_program(from) => {}
+                    AddressOrNamespace::Address(from) => third_party_debits.push(ThirdPartyDebit {
+                        index,
+                        account: *from,
+                        token: *burn.token(),
+                    }),
+                    from => return Err(unauthorized(index, from)),
+                }
+            }
+            Instruction::Log(log) => {
+                let message = match &log.0 {
+                    ContractLogType::Info(message)
+                    | ContractLogType::Error(message)
+                    | ContractLogType::Warn(message)
+                    | ContractLogType::Debug(message) => message,
+                };
+                if message.len() > limits.max_log_length {
+                    return Err(OutputValidationError::TooManyItems {
+                        index,
+                        what: "log bytes",
+                        count: message.len(),
+                        limit: limits.max_log_length,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok((parsed, third_party_debits))
+}
+
+/// Whether `account` has approved the program, or the caller, to spend
+/// `token` on its behalf.
+pub fn approves_spender(account: &Account, token: &Address, transaction: &Transaction) -> bool {
+    account.token(token).is_some_and(|entry| {
+        let spenders = [transaction.to(), transaction.from()];
+        spenders.iter().any(|spender| {
+            entry.allowance().contains_key(spender) || entry.approvals().contains_key(spender)
+        })
+    })
+}
+
+/// Parses and checks `outputs`, including the approvals behind any debit of
+/// an account other than the caller or the program.
+pub async fn validate_outputs(
+    outputs: &str,
+    transaction: &Transaction,
+    limits: &OutputLimits,
+) -> Result<Outputs, OutputValidationError> {
+    let (parsed, third_party_debits) = parse_outputs(outputs, transaction, limits)?;
+    for debit in third_party_debits {
+        let approved = get_account(debit.account, ActorType::Engine)
+            .await
+            .is_some_and(|account| approves_spender(&account, &debit.token, transaction));
+        if !approved {
+            return Err(OutputValidationError::UnauthorizedAccount {
+                index: debit.index,
+                account: debit.account.to_full_string(),
+            });
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod outputs_tests {
+    use super::*;
+    use lasr_types::{
+        BurnInstructionBuilder, Inputs, LogInstruction, Namespace, OutputsBuilder, PayloadBuilder,
+        ProgramEvent, ProgramUpdate, TransactionType, TransferInstructionBuilder,
+        UpdateInstruction, U256,
+    };
+    use proptest::prelude::*;
+    use serde_json::{json, Value};
+
+    fn caller() -> Address {
+        Address::new([1; 20])
+    }
+
+    fn program() -> Address {
+        Address::new([2; 20])
+    }
+
+    fn other() -> Address {
+        Address::new([3; 20])
+    }
+
+    fn test_call() -> Transaction {
+        PayloadBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(0)))
+            .from(caller().into())
+            .to(program().into())
+            .program_id(program().into())
+            .inputs(String::new())
+            .op(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(0))
+            .build()
+            .expect("failed to build payload")
+            .into()
+    }
+
+    fn transfer(from: AddressOrNamespace) -> Instruction {
+        Instruction::Transfer(
+            TransferInstructionBuilder::new()
+                .token(program())
+                .from(from)
+                .to(AddressOrNamespace::Address(other()))
+                .amount(U256::from(1))
+                .build()
+                .expect("failed to build transfer instruction"),
+        )
+    }
+
+    fn outputs_json(instructions: Vec<Instruction>) -> Value {
+        let outputs = OutputsBuilder::new()
+            .inputs(Inputs::default())
+            .extend_instructions(instructions)
+            .build()
+            .expect("failed to build outputs");
+        serde_json::to_value(outputs).expect("failed to serialize outputs")
+    }
+
+    fn parse(outputs: &str) -> Result<(Outputs, Vec<ThirdPartyDebit>), OutputValidationError> {
+        parse_outputs(outputs, &test_call(), &OutputLimits::default())
+    }
+
+    #[test]
+    fn well_formed_outputs_are_accepted() {
+        let outputs = outputs_json(vec![
+            transfer(AddressOrNamespace::Address(caller())),
+            transfer(AddressOrNamespace::This),
+        ])
+        .to_string();
+
+        let (parsed, third_party_debits) = parse(&outputs).expect("outputs were rejected");
+        assert_eq!(parsed.instructions().len(), 2);
+        assert!(third_party_debits.is_empty());
+    }
+
+    #[test]
+    fn malformed_outputs_are_rejected() {
+        let valid = outputs_json(vec![transfer(AddressOrNamespace::This)]);
+        let with = |edit: fn(&mut Value)| {
+            let mut outputs = valid.clone();
+            edit(&mut outputs);
+            outputs.to_string()
+        };
+
+        let corpus = vec![
+            ("empty", String::new()),
+            ("not json", "instructions".to_string()),
+            (
+                "truncated",
+                valid.to_string()[..valid.to_string().len() / 2].to_string(),
+            ),
+            ("empty object", "{}".to_string()),
+            ("array", "[]".to_string()),
+            ("null", "null".to_string()),
+            (
+                "unknown top level field",
+                with(|o| o["nestedCalls"] = json!([])),
+            ),
+            (
+                "missing instructions",
+                with(|o| {
+                    o.as_object_mut().unwrap().remove("instructions");
+                }),
+            ),
+            (
+                "instructions not a list",
+                with(|o| o["instructions"] = json!("transfer")),
+            ),
+            (
+                "unknown instruction",
+                with(|o| o["instructions"] = json!([{ "selfDestruct": {} }])),
+            ),
+            (
+                "unknown instruction field",
+                with(|o| o["instructions"][0]["transfer"]["memo"] = json!("hi")),
+            ),
+            (
+                "missing instruction field",
+                with(|o| {
+                    o["instructions"][0]["transfer"]
+                        .as_object_mut()
+                        .unwrap()
+                        .remove("token");
+                }),
+            ),
+            (
+                "token is not an address",
+                with(|o| o["instructions"][0]["transfer"]["token"] = json!(42)),
+            ),
+            (
+                "ids not a list",
+                with(|o| o["instructions"][0]["transfer"]["ids"] = json!({})),
+            ),
+        ];
+
+        for (name, outputs) in corpus {
+            assert!(
+                matches!(parse(&outputs), Err(OutputValidationError::Malformed(_))),
+                "{name} outputs were not rejected as malformed"
+            );
+        }
+    }
+
+    #[test]
+    fn outputs_over_caps_are_rejected() {
+        let outputs = outputs_json(vec![transfer(AddressOrNamespace::This); 3]).to_string();
+        let limits = |edit: fn(&mut OutputLimits)| {
+            let mut limits = OutputLimits::default();
+            edit(&mut limits);
+            limits
+        };
+
+        assert!(matches!(
+            parse_outputs(&outputs, &test_call(), &limits(|l| l.max_bytes = 16)),
+            Err(OutputValidationError::TooLarge { limit: 16, .. })
+        ));
+        assert!(matches!(
+            parse_outputs(&outputs, &test_call(), &limits(|l| l.max_instructions = 2)),
+            Err(OutputValidationError::TooManyInstructions { count: 3, limit: 2 })
+        ));
+
+        let many_ids = Instruction::Transfer(
+            TransferInstructionBuilder::new()
+                .token(program())
+                .from(AddressOrNamespace::This)
+                .to(AddressOrNamespace::Address(other()))
+                .extend_ids((0..5).map(U256::from).collect())
+                .build()
+                .expect("failed to build transfer instruction"),
+        );
+        let outputs = outputs_json(vec![many_ids]).to_string();
+        assert!(matches!(
+            parse_outputs(&outputs, &test_call(), &limits(|l| l.max_items = 4)),
+            Err(OutputValidationError::TooManyItems {
+                index: 0,
+                count: 5,
+                limit: 4,
+                ..
+            })
+        ));
+
+        let long_log = Instruction::Log(LogInstruction(ContractLogType::Info("x".repeat(10))));
+        let outputs = outputs_json(vec![long_log]).to_string();
+        assert!(matches!(
+            parse_outputs(&outputs, &test_call(), &limits(|l| l.max_log_length = 9)),
+            Err(OutputValidationError::TooManyItems { limit: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn events_are_parsed_and_checked_against_their_caps() {
+        let mut outputs = outputs_json(vec![transfer(AddressOrNamespace::This)]);
+        outputs["events"] = json!([
+            { "topic": "transfer", "attributes": { "to": other(), "amount": "1" } },
+            { "topic": "minted" },
+        ]);
+        let (parsed, _) = parse(&outputs.to_string()).expect("outputs were rejected");
+        assert_eq!(
+            parsed.events(),
+            &vec![
+                ProgramEvent::new("transfer")
+                    .with_attribute("to", other().to_full_string())
+                    .with_attribute("amount", "1"),
+                ProgramEvent::new("minted"),
+            ]
+        );
+        let limits = |edit: fn(&mut OutputLimits)| {
+            let mut limits = OutputLimits::default();
+            edit(&mut limits);
+            limits
+        };
+        assert!(matches!(
+            parse_outputs(
+                &outputs.to_string(),
+                &test_call(),
+                &limits(|l| l.max_events = 1)
+            ),
+            Err(OutputValidationError::TooManyEvents { count: 2, limit: 1 })
+        ));
+        assert!(matches!(
+            parse_outputs(
+                &outputs.to_string(),
+                &test_call(),
+                &limits(|l| l.max_event_bytes = 8)
+            ),
+            Err(OutputValidationError::EventTooLarge {
+                index: 0,
+                limit: 8,
+                ..
+            })
+        ));
+
+        outputs["events"][1]["topic"] = json!("");
+        assert_eq!(
+            parse(&outputs.to_string()).unwrap_err(),
+            OutputValidationError::MissingTopic { index: 1 }
+        );
+        for malformed in [
+            json!([{ "topic": "transfer", "indexed": true }]),
+            json!([{ "attributes": {} }]),
+            json!([{ "topic": "transfer", "attributes": { "amount": 1 } }]),
+        ] {
+            outputs["events"] = malformed;
+            assert!(matches!(
+                parse(&outputs.to_string()),
+                Err(OutputValidationError::Malformed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn outputs_touching_unrelated_accounts_are_rejected() {
+        let foreign_program_update = Instruction::Update(UpdateInstruction::new(vec![
+            TokenOrProgramUpdate::ProgramUpdate(ProgramUpdate::new(
+                AddressOrNamespace::Address(other()),
+                vec![],
+            )),
+        ]));
+        let impersonating_burn = Instruction::Burn(
+            BurnInstructionBuilder::new()
+                .caller(other())
+                .program_id(AddressOrNamespace::This)
+                .token(program())
+                .from(AddressOrNamespace::Address(caller()))
+                .amount(U256::from(1))
+                .build()
+                .expect("failed to build burn instruction"),
+        );
+        let namespace_debit = transfer(AddressOrNamespace::Namespace(Namespace::from(
+            "SOME_NAMESPACE".to_string(),
+        )));
+
+        for instruction in [foreign_program_update, impersonating_burn, namespace_debit] {
+            let outputs = outputs_json(vec![transfer(AddressOrNamespace::This), instruction]);
+            assert!(matches!(
+                parse(&outputs.to_string()),
+                Err(OutputValidationError::UnauthorizedAccount { index: 1, .. })
+            ));
+        }
+
+        let outputs = outputs_json(vec![transfer(AddressOrNamespace::Address(other()))]);
+        let (_, third_party_debits) =
+            parse(&outputs.to_string()).expect("third party transfer was rejected early");
+        assert_eq!(
+            third_party_debits,
+            vec![ThirdPartyDebit {
+                index: 0,
+                account: other(),
+                token: program(),
+            }]
+        );
+    }
+
+    /// A byte JSON is made of, to mutate valid outputs with.
+    fn json_byte() -> impl Strategy<Value = u8> {
+        prop::sample::select(b"{}[]\":,0123456789abcdefx-.\\ nultrue".to_vec())
+    }
+
+    proptest! {
+        /// Mutates valid outputs at random and checks the parser only ever
+        /// returns an error, never panics.
+        #[test]
+        fn fuzz_parse_outputs(
+            edits in prop::collection::vec(
+                (any::<prop::sample::Index>(), 0u8..3, json_byte()),
+                1..=8,
+            )
+        ) {
+            let mut bytes = outputs_json(vec![
+                transfer(AddressOrNamespace::This),
+                Instruction::Log(LogInstruction(ContractLogType::Info("log".to_string()))),
+            ])
+            .to_string()
+            .into_bytes();
+            for (at, edit, byte) in edits {
+                if bytes.is_empty() {
+                    break;
+                }
+                let at = at.index(bytes.len());
+                match edit {
+                    0 => bytes[at] = byte,
+                    1 => {
+                        bytes.remove(at);
+                    }
+                    _ => bytes.insert(at, byte),
+                }
+            }
+            let outputs = String::from_utf8_lossy(&bytes);
+            let _ = parse(&outputs);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Paging of the RPC methods that answer lists.
+//!
+//! Entries are ordered by a key that does not change while they exist, and
+//! the cursor a page hands back is the key of its last entry, so the next
+//! page starts after it however many entries came or went in between. A
+//! page is also cut short once it would serialize to more than the response
+//! size cap, and says so with `truncated`.
+use lasr_messages::RpcErrorCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+/// Entries answered per page unless the caller asks for another number.
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+/// The most entries answered in one page.
+pub const MAX_PAGE_SIZE: usize = 100;
+/// Bytes the entries of a page serialize to at most, unless
+/// `rpc.max_response_bytes` says otherwise.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Error)]
+pub enum PaginationError {
+    #[error("cursor is not one this node handed out: {0}")]
+    InvalidCursor(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl PaginationError {
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            PaginationError::InvalidCursor(_) => RpcErrorCode::InvalidParams,
+            PaginationError::Custom(_) => RpcErrorCode::Internal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Entries in the whole list.
+    pub total: usize,
+    /// Where the next page starts, if there is one.
+    pub next_cursor: Option<String>,
+    /// Whether entries were left off this page to keep the response under
+    /// the size cap, rather than for the limit.
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor<K> {
+    after: K,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Paginator {
+    max_response_bytes: usize,
+}
+
+impl Default for Paginator {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RESPONSE_BYTES)
+    }
+}
+
+impl Paginator {
+    pub fn new(max_response_bytes: usize) -> Self {
+        Self { max_response_bytes }
+    }
+
+    /// The page of `items`, ordered by `key`, that starts after `cursor`,
+    /// holding at most `limit` entries. An entry too large for the size cap
+    /// on its own is still answered alone, so paging always moves on.
+    pub fn page<T, K>(
+        &self,
+        mut items: Vec<T>,
+        key: impl Fn(&T) -> K,
+        cursor: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Page<T>, PaginationError>
+    where
+        T: Serialize,
+        K: Serialize + DeserializeOwned + Ord,
+    {
+        items.sort_by_key(&key);
+        let total = items.len();
+        let start = match cursor {
+            Some(cursor) => {
+                let after = decode::<K>(cursor)?;
+                items.partition_point(|item| key(item) <= after)
+            }
+            None => 0,
+        };
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        let mut page = Vec::new();
+        let mut bytes = 0;
+        let mut truncated = false;
+        let mut rest = items.into_iter().skip(start).peekable();
+        while page.len() < limit {
+            let Some(item) = rest.peek() else {
+                break;
+            };
+            let size = serde_json::to_vec(item)
+                .map_err(|e| PaginationError::Custom(e.to_string()))?
+                .len()
+                + 1;
+            if !page.is_empty() && bytes + size > self.max_response_bytes {
+                truncated = true;
+                break;
+            }
+            bytes += size;
+            page.extend(rest.next());
+        }
+
+        let next_cursor = match (rest.peek(), page.last()) {
+            (Some(_), Some(last)) => Some(encode(&key(last))?),
+            _ => None,
+        };
+        Ok(Page {
+            items: page,
+            total,
+            next_cursor,
+            truncated,
+        })
+    }
+}
+
+fn encode<K: Serialize>(after: &K) -> Result<String, PaginationError> {
+    let json = serde_json::to_vec(&Cursor { after })
+        .map_err(|e| PaginationError::Custom(e.to_string()))?;
+    Ok(base64::encode_config(json, base64::URL_SAFE_NO_PAD))
+}
+
+fn decode<K: DeserializeOwned>(cursor: &str) -> Result<K, PaginationError> {
+    let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| PaginationError::InvalidCursor(e.to_string()))?;
+    serde_json::from_slice::<Cursor<K>>(&json)
+        .map(|cursor| cursor.after)
+        .map_err(|e| PaginationError::InvalidCursor(e.to_string()))
+}
+
+#[cfg(test)]
+mod pagination_tests {
+    use super::*;
+
+    fn keys(page: &Page<u64>) -> Vec<u64> {
+        page.items.clone()
+    }
+
+    #[test]
+    fn pages_resume_after_their_cursor_while_the_list_changes() {
+        let paginator = Paginator::default();
+        let first = paginator
+            .page(vec![5, 1, 4, 2, 3], |n| *n, None, Some(2))
+            .unwrap();
+        assert_eq!(keys(&first), vec![1, 2]);
+        assert_eq!(first.total, 5);
+        assert!(!first.truncated);
+
+        // 2 went and 0 came before the next page was asked for.
+        let next = paginator
+            .page(
+                vec![0, 5, 1, 4, 3],
+                |n| *n,
+                first.next_cursor.as_deref(),
+                Some(2),
+            )
+            .unwrap();
+        assert_eq!(keys(&next), vec![3, 4]);
+        let last = paginator
+            .page(
+                vec![0, 5, 1, 4, 3],
+                |n| *n,
+                next.next_cursor.as_deref(),
+                Some(2),
+            )
+            .unwrap();
+        assert_eq!(keys(&last), vec![5]);
+        assert_eq!(last.next_cursor, None);
+
+        let capped = paginator
+            .page((0..1000).collect(), |n| *n, None, Some(usize::MAX))
+            .unwrap();
+        assert_eq!(capped.items.len(), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn pages_past_the_size_cap_are_truncated() {
+        // Each entry serializes to 10 bytes, with the separator.
+        let items: Vec<String> = (0..10).map(|n| format!("entry-{n}")).collect();
+        let paginator = Paginator::new(35);
+        let page = paginator
+            .page(items.clone(), |item| item.clone(), None, Some(10))
+            .unwrap();
+        assert_eq!(page.items, vec!["entry-0", "entry-1", "entry-2"]);
+        assert!(page.truncated);
+        let next = paginator
+            .page(
+                items,
+                |item| item.clone(),
+                page.next_cursor.as_deref(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(next.items, vec!["entry-3", "entry-4", "entry-5"]);
+
+        let alone = Paginator::new(1)
+            .page(
+                vec!["too large".to_string()],
+                |item| item.clone(),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(alone.items.len(), 1);
+        assert_eq!(alone.next_cursor, None);
+    }
+
+    #[test]
+    fn cursors_not_handed_out_are_rejected() {
+        let paginator = Paginator::default();
+        for cursor in ["not base64!", "e30", &encode(&"a string").unwrap()] {
+            assert!(matches!(
+                paginator.page(vec![1u64], |n| *n, Some(cursor), None),
+                Err(PaginationError::InvalidCursor(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Checks of the hex strings RPC requests name accounts and transactions
+//! with.
+//!
+//! Lengths are checked before anything is decoded, and a parameter that fails
+//! is answered as invalid params with its name and what was wrong with it.
+//! Addresses are held to the strict form: `0x`, 40 digits, and a checksum
+//! if written in mixed case.
+use jsonrpsee::types::ErrorObjectOwned as RpcError;
+use lasr_messages::RpcErrorCode;
+use lasr_types::{Address, AddressError};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParamError {
+    #[error("`{field}` must be {bytes} bytes of hex, not {digits} hex digits")]
+    HexLength {
+        field: &'static str,
+        bytes: usize,
+        digits: usize,
+    },
+
+    #[error("`{field}` is not hex")]
+    NotHex { field: &'static str },
+
+    #[error("`{field}` must start with 0x")]
+    Prefix { field: &'static str },
+
+    #[error("`{field}` does not match its checksum {checksummed}")]
+    Checksum {
+        field: &'static str,
+        checksummed: String,
+    },
+}
+
+impl ParamError {
+    pub fn code(&self) -> RpcErrorCode {
+        RpcErrorCode::InvalidParams
+    }
+}
+
+impl From<ParamError> for RpcError {
+    fn from(error: ParamError) -> Self {
+        RpcError::owned(error.code().code(), error.to_string(), None::<()>)
+    }
+}
+
+/// Decodes the `N` bytes of hex, with or without a `0x` prefix, that
+/// parameter `field` holds.
+pub fn parse_hex<const N: usize>(field: &'static str, value: &str) -> Result<[u8; N], ParamError> {
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    if hex.len() != 2 * N {
+        return Err(ParamError::HexLength {
+            field,
+            bytes: N,
+            digits: hex.len(),
+        });
+    }
+    let mut bytes = [0; N];
+    hex::decode_to_slice(hex, &mut bytes).map_err(|_| ParamError::NotHex { field })?;
+    Ok(bytes)
+}
+
+pub fn parse_address(field: &'static str, value: &str) -> Result<Address, ParamError> {
+    Address::from_hex(value).map_err(|error| match error {
+        AddressError::Prefix => ParamError::Prefix { field },
+        AddressError::Length(digits) => ParamError::HexLength {
+            field,
+            bytes: 20,
+            digits,
+        },
+        AddressError::NotHex => ParamError::NotHex { field },
+        AddressError::Checksum(checksummed) => ParamError::Checksum { field, checksummed },
+    })
+}
+
+pub fn parse_hash(field: &'static str, value: &str) -> Result<[u8; 32], ParamError> {
+    parse_hex(field, value)
+}
+
+#[cfg(test)]
+mod params_tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn hex_of_the_wrong_length_names_its_parameter() {
+        assert_eq!(
+            parse_address("address", &format!("0x{}", "01".repeat(20))),
+            Ok(Address::new([1; 20]))
+        );
+        assert_eq!(
+            parse_address("address", &"ab".repeat(20)),
+            Err(ParamError::Prefix { field: "address" })
+        );
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        assert_eq!(
+            parse_address("to", checksummed),
+            parse_address("to", &checksummed.to_lowercase())
+        );
+        assert_eq!(
+            parse_address("to", &checksummed.replace('e', "E")),
+            Err(ParamError::Checksum {
+                field: "to",
+                checksummed: checksummed.to_string(),
+            })
+        );
+        assert_eq!(
+            parse_address("programId", &format!("0x{}", "01".repeat(19))),
+            Err(ParamError::HexLength {
+                field: "programId",
+                bytes: 20,
+                digits: 38,
+            })
+        );
+        assert_eq!(
+            parse_hash("txHash", &format!("0x{}", "zz".repeat(32))),
+            Err(ParamError::NotHex { field: "txHash" })
+        );
+        assert_eq!(
+            parse_address("address", &format!("0xé{}", "0".repeat(38))),
+            Err(ParamError::NotHex { field: "address" })
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn any_string_is_parsed_or_refused(value in any::<String>()) {
+            let _ = parse_address("address", &value);
+            let _ = parse_hash("txHash", &value);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! An append-only log of the transactions accepted into the pending pool,
+//! so a node that restarts has them pending again.
+//!
+//! Each transaction is appended, and synced, as soon as it is accepted into
+//! the pool. A tombstone is appended once it leaves the pool, whether it was
+//! validated, found invalid, expired, replaced or evicted. Tombstones are not
+//! synced: one lost to a crash only means a transaction is validated again,
+//! and turned away, on the next start. Replaying the log gives back the
+//! transactions without a tombstone, oldest first, to be submitted and
+//! validated afresh against the state they left behind. Once tombstones make
+//! up most of it, the log is rewritten with only the pool.
+//!
+//! Records are written as JSON in a [`RecordLog`], as transactions are read
+//! back the way they arrive over RPC.
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use lasr_types::{Outputs, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::record_log::{RecordLog, RecordLogError};
+
+/// Records below which the log is never compacted.
+pub const PENDING_LOG_COMPACT_MIN_RECORDS: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum PendingLogError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum PendingLogRecord {
+    Accepted {
+        transaction: Transaction,
+        outputs: Option<Outputs>,
+    },
+    Removed {
+        transaction_hash: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct PendingLog {
+    log: RecordLog<PendingLogRecord>,
+    /// Transactions accepted without a tombstone yet.
+    live: HashSet<String>,
+}
+
+impl PendingLog {
+    /// Opens the log at `path`, creating it if there is none, and returns it
+    /// with the transactions it holds, oldest first.
+    pub fn open(
+        path: &Path,
+    ) -> Result<(Self, Vec<(Transaction, Option<Outputs>)>), PendingLogError> {
+        let (log, records) = RecordLog::open(path)?;
+        let mut replayed: Vec<Option<(Transaction, Option<Outputs>)>> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for record in records {
+            match record {
+                PendingLogRecord::Accepted {
+                    transaction,
+                    outputs,
+                } => {
+                    let transaction_hash = transaction.hash_string();
+                    if !index.contains_key(&transaction_hash) {
+                        index.insert(transaction_hash, replayed.len());
+                        replayed.push(Some((transaction, outputs)));
+                    }
+                }
+                PendingLogRecord::Removed { transaction_hash } => {
+                    if let Some(position) = index.remove(&transaction_hash) {
+                        replayed[position] = None;
+                    }
+                }
+            }
+        }
+        let log = Self {
+            log,
+            live: index.into_keys().collect(),
+        };
+        Ok((log, replayed.into_iter().flatten().collect()))
+    }
+
+    /// Appends `transaction`, unless it is in the log already.
+    pub fn accepted(
+        &mut self,
+        transaction: &Transaction,
+        outputs: &Option<Outputs>,
+    ) -> Result<(), PendingLogError> {
+        let transaction_hash = transaction.hash_string();
+        if self.live.contains(&transaction_hash) {
+            return Ok(());
+        }
+        self.log.append_synced([&PendingLogRecord::Accepted {
+            transaction: transaction.clone(),
+            outputs: outputs.clone(),
+        }])?;
+        self.live.insert(transaction_hash);
+        Ok(())
+    }
+
+    /// Appends a tombstone for `transaction_hash`, if it is in the log.
+    pub fn removed(&mut self, transaction_hash: &str) -> Result<(), PendingLogError> {
+        if !self.live.remove(transaction_hash) {
+            return Ok(());
+        }
+        self.log.append([&PendingLogRecord::Removed {
+            transaction_hash: transaction_hash.to_string(),
+        }])?;
+        Ok(())
+    }
+
+    /// Whether the log is mostly tombstones and the transactions they bury.
+    pub fn needs_compaction(&self) -> bool {
+        let records = self.log.records();
+        records >= PENDING_LOG_COMPACT_MIN_RECORDS && records > 2 * self.live.len()
+    }
+
+    /// Rewrites the log with only `transactions`, the pool as it is now.
+    pub fn compact(
+        &mut self,
+        transactions: Vec<(Transaction, Option<Outputs>)>,
+    ) -> Result<(), PendingLogError> {
+        let compacted = self.log.records();
+        let live = transactions
+            .iter()
+            .map(|(transaction, _)| transaction.hash_string())
+            .collect();
+        let records: Vec<_> = transactions
+            .into_iter()
+            .map(|(transaction, outputs)| PendingLogRecord::Accepted {
+                transaction,
+                outputs,
+            })
+            .collect();
+        self.log.rewrite(&records)?;
+
+        tracing::info!(
+            "compacted the pending log from {compacted} records to {}",
+            records.len()
+        );
+        self.live = live;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod pending_log_tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    use crate::test_fixtures::send;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("pending_log_{}_{}.log", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn nonces(replayed: &[(Transaction, Option<Outputs>)]) -> Vec<u64> {
+        replayed
+            .iter()
+            .map(|(transaction, _)| transaction.nonce().as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn transactions_without_a_tombstone_are_replayed_in_order() {
+        let path = temp_path("replayed");
+        let (mut log, replayed) = PendingLog::open(&path).unwrap();
+        assert!(replayed.is_empty());
+        for nonce in [2, 0, 1] {
+            log.accepted(&send(nonce), &None).unwrap();
+        }
+        log.accepted(&send(2), &None).unwrap();
+        log.removed(&send(0).hash_string()).unwrap();
+        log.removed(&send(5).hash_string()).unwrap();
+        drop(log);
+
+        let (mut log, replayed) = PendingLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![2, 1]);
+        assert_eq!(log.log.records(), 4);
+
+        // One removed may come back, after those accepted since.
+        log.accepted(&send(0), &None).unwrap();
+        drop(log);
+        let (_, replayed) = PendingLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![2, 1, 0]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_torn_record_is_dropped() {
+        let path = temp_path("torn");
+        let (mut log, _) = PendingLog::open(&path).unwrap();
+        log.accepted(&send(0), &None).unwrap();
+        log.accepted(&send(1), &None).unwrap();
+        drop(log);
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let (mut log, replayed) = PendingLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![0]);
+        log.accepted(&send(2), &None).unwrap();
+        drop(log);
+        let (_, replayed) = PendingLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![0, 2]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_whole_record_that_does_not_decode_fails_the_open() {
+        let path = temp_path("corrupt");
+        let (mut log, _) = PendingLog::open(&path).unwrap();
+        log.accepted(&send(0), &None).unwrap();
+        log.accepted(&send(1), &None).unwrap();
+        drop(log);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] = b'[';
+        std::fs::write(&path, &bytes).unwrap();
+
+        let error = PendingLog::open(&path).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                PendingLogError::Log(RecordLogError::Corrupt { offset: 0, .. })
+            ),
+            "{error:?}"
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn compaction_keeps_only_the_pool() {
+        let path = temp_path("compacted");
+        let (mut log, _) = PendingLog::open(&path).unwrap();
+        for nonce in 0..PENDING_LOG_COMPACT_MIN_RECORDS as u64 {
+            log.accepted(&send(nonce), &None).unwrap();
+            log.removed(&send(nonce).hash_string()).unwrap();
+        }
+        assert!(log.needs_compaction());
+        log.compact(vec![(send(7), None)]).unwrap();
+        assert!(!log.needs_compaction());
+        log.accepted(&send(8), &None).unwrap();
+        drop(log);
+
+        let (log, replayed) = PendingLog::open(&path).unwrap();
+        assert_eq!(nonces(&replayed), vec![7, 8]);
+        assert_eq!(log.log.records(), 2);
+        std::fs::remove_file(&path).ok();
+    }
+}
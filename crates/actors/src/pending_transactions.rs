@@ -1,9 +1,11 @@
 use lasr_messages::{
-    ActorName, ActorType, ExecutorMessage, PendingTransactionMessage, SchedulerMessage,
-    SupervisorType, ValidatorMessage,
+    ActorName, ActorType, ExecutionCreditStatus, ExecutorMessage, LasrError, MempoolFull,
+    PendingStatus, PendingTransactionMessage, PendingTransactionSummary, RpcError, RpcErrorCode,
+    SchedulerMessage, SupervisorType, ValidatorMessage,
 };
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
     time::Duration,
 };
@@ -11,17 +13,61 @@ use tokio::sync::mpsc::Sender;
 
 use async_trait::async_trait;
 use chrono::prelude::*;
-use lasr_types::{Address, AddressOrNamespace, Outputs, Transaction, TransactionType};
-use ractor::{Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent};
+use lasr_types::{
+    Address, AddressOrNamespace, Outputs, Transaction, TransactionType, Validity, U256,
+};
+use ractor::{rpc::CallResult, Actor, ActorCell, ActorProcessingErr, ActorRef, SupervisionEvent};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    get_actor_ref, helpers::Coerce, process_group_changed, SchedulerError, ValidatorError,
+    get_account, get_actor_ref, helpers::Coerce, lifecycle, mailbox_handling, mailbox_probed,
+    process_group_changed, CallDeadlines, Fairness, FeeSchedule, FifoPolicy, LifecycleStage,
+    MempoolLimits, MempoolMetrics, Occupancy, PendingLog, PendingLogError, ReadyQueue,
+    SchedulerError, SchedulingConfig, SchedulingPolicy, ValidatorError, CANCEL_TIMEOUT,
+    DEFAULT_CALL_DEADLINE, DEFAULT_MAX_IN_FLIGHT, DEFAULT_SENDER_QUANTUM,
 };
 
 pub const PENDING_TIMEOUT: u64 = 15000;
+pub const DEFAULT_PENDING_TTL: Duration = Duration::from_millis(PENDING_TIMEOUT);
+pub const DEFAULT_MAX_FUTURE_TRANSACTIONS: usize = 16;
+/// How far ahead a transaction may be valid from by default and still be
+/// held until it is.
+pub const DEFAULT_MAX_SCHEDULED_AHEAD: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often the transactions held until they are valid are checked for
+/// those whose time has come.
+pub const SCHEDULED_RELEASE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long transactions wait in the pending pool, how many are held back,
+/// and where the pool is kept across restarts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingConfig {
+    /// Where the pool is kept across restarts, if anywhere.
+    pub log_path: Option<PathBuf>,
+    /// How long a transaction waits to be handed to the validator before it
+    /// is evicted.
+    pub ttl: Duration,
+    /// The most transactions held for a gap per sender.
+    pub max_future_transactions: usize,
+    /// The most transactions with the validator at once.
+    pub max_in_flight: usize,
+    /// How long a call handed to the executor has to complete, or as long
+    /// as it takes if none.
+    pub call_deadline: Option<Duration>,
+}
+
+impl Default for PendingConfig {
+    fn default() -> Self {
+        Self {
+            log_path: None,
+            ttl: DEFAULT_PENDING_TTL,
+            max_future_transactions: DEFAULT_MAX_FUTURE_TRANSACTIONS,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            call_deadline: Some(DEFAULT_CALL_DEADLINE),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Vertex {
@@ -30,18 +76,68 @@ pub struct Vertex {
     outputs: Option<Outputs>,
     accounts_touched: HashSet<Address>,
     dependent_transactions: Vec<String>,
+    /// Whether the transaction has been handed to the validator, after which
+    /// it can no longer be replaced.
+    scheduled: bool,
+    /// The space the transaction takes up in the pool.
+    bytes: u64,
+}
+
+/// The space `transaction` and its `outputs` take up in the pending pool.
+fn pending_bytes(transaction: &Transaction, outputs: &Option<Outputs>) -> u64 {
+    bincode::serialized_size(&(transaction, outputs)).unwrap_or_default()
+}
+
+/// The accounts a call reads and writes, as far as can be told before it
+/// runs. Its outputs are only known once it has, so the set is what the
+/// transaction names: the call writes its sender's account and the account
+/// of the program it calls, whose data and metadata it may update, and
+/// reads its token's. Calls to one program so run one after another, each
+/// on what the last left. What its outputs turn out to touch is checked as
+/// they are validated, which they are in the order the calls were made.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AccountAccess {
+    pub reads: HashSet<Address>,
+    pub writes: HashSet<Address>,
+}
+
+impl AccountAccess {
+    pub fn of(transaction: &Transaction) -> Self {
+        let writes = HashSet::from([transaction.from(), transaction.to()]);
+        let reads = HashSet::from([transaction.program_id()])
+            .difference(&writes)
+            .copied()
+            .collect();
+        Self { reads, writes }
+    }
+
+    /// Whether running both at once could have either see the other's
+    /// writes half done.
+    pub fn conflicts_with(&self, other: &AccountAccess) -> bool {
+        !self.writes.is_disjoint(&other.writes)
+            || !self.writes.is_disjoint(&other.reads)
+            || !self.reads.is_disjoint(&other.writes)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct PreCallVertex {
     transaction: Transaction,
     program_account: Address,
+    /// The call's place in the order calls were made.
+    sequence: u64,
+    access: AccountAccess,
+    /// Calls made after this one that conflict with it, and wait for it.
     dependencies: Vec<String>,
+    /// Calls made before this one that conflict with it and have yet to
+    /// complete.
+    waiting_on: HashSet<String>,
 }
 
 impl Vertex {
     pub fn new(transaction: Transaction, outputs: Option<Outputs>) -> Vertex {
         let accounts_touched = Vertex::extract_accounts_touched(&transaction, &outputs);
+        let bytes = pending_bytes(&transaction, &outputs);
 
         let timestamp = Utc::now().timestamp_millis() as u64;
         Vertex {
@@ -50,6 +146,8 @@ impl Vertex {
             outputs,
             accounts_touched,
             dependent_transactions: Vec::new(),
+            scheduled: false,
+            bytes,
         }
     }
 
@@ -57,6 +155,19 @@ impl Vertex {
         &self.accounts_touched
     }
 
+    fn summary(&self, now: u64) -> PendingTransactionSummary {
+        PendingTransactionSummary {
+            hash: self.transaction.hash_string(),
+            nonce: self.transaction.nonce(),
+            transaction_type: self.transaction.transaction_type().to_string(),
+            age_ms: now.saturating_sub(self.timestamp),
+            status: match self.scheduled {
+                true => PendingStatus::Validating,
+                false => PendingStatus::Pending,
+            },
+        }
+    }
+
     pub fn accounts_touched_mut(&mut self) -> &mut HashSet<Address> {
         &mut self.accounts_touched
     }
@@ -92,259 +203,1441 @@ impl Vertex {
 }
 
 impl PreCallVertex {
-    pub fn new(transaction: Transaction) -> PreCallVertex {
+    pub fn new(transaction: Transaction, sequence: u64) -> PreCallVertex {
         let program_account = transaction.to();
+        let access = AccountAccess::of(&transaction);
         PreCallVertex {
             transaction,
             program_account,
+            sequence,
+            access,
             dependencies: Vec::new(),
+            waiting_on: HashSet::new(),
         }
     }
 }
 
+/// A call's turn to commit, and its outputs if they arrived before the
+/// calls made ahead of it committed.
+#[derive(Clone, Debug)]
+struct CommitSlot {
+    transaction_hash: String,
+    /// Milliseconds since the epoch the call completed at.
+    executed_at: Option<u64>,
+    outputs: Option<(Transaction, Option<Outputs>)>,
+}
+
+/// A call waiting to be run again, and the calls from its sender that
+/// arrived since, held so they do not run before it.
+#[derive(Clone, Debug)]
+struct RetryingCall {
+    transaction_hash: String,
+    held: Vec<Transaction>,
+}
+
+/// The execution credits the executor granted, and the calls holding them.
+#[derive(Clone, Debug, Default)]
+struct CallCredits {
+    granted: usize,
+    in_flight: HashSet<String>,
+}
+
+/// The calls not yet completed, each dispatched once the calls made before
+/// it that it conflicts with have completed, so calls that do not conflict
+/// run at once, up to the executor's credits. Their outputs are committed
+/// to the pending graph in the order the calls were made, however the
+/// executor finishes them.
 #[derive(Clone, Debug, Default)]
 pub struct PreCallGraph {
     vertices: HashMap<String, Arc<RwLock<PreCallVertex>>>,
-    program_index: HashMap<Address, Vec<String>>,
+    next_sequence: u64,
+    /// Calls yet to commit or fail, by sequence.
+    commits: BTreeMap<u64, CommitSlot>,
+    /// The sequence of each call in `commits`.
+    sequences: HashMap<String, u64>,
+    /// Outputs of calls whose turn to commit has come, for the pending graph
+    /// to take.
+    committed: VecDeque<(Transaction, Option<Outputs>)>,
+    /// Calls being retried, by sender.
+    retrying: HashMap<Address, RetryingCall>,
+    /// The executor's credits, once it has granted them. Until then, calls
+    /// are handed to it as they come.
+    credits: Option<CallCredits>,
+    /// Calls waiting for a credit, with the milliseconds since the epoch
+    /// they started waiting at.
+    waiting: VecDeque<(Transaction, u64)>,
+    /// The deadlines of the calls with the executor.
+    deadlines: CallDeadlines,
 }
 
 impl PreCallGraph {
     pub fn new() -> PreCallGraph {
         PreCallGraph {
             vertices: HashMap::new(),
-            program_index: HashMap::new(),
+            next_sequence: 0,
+            commits: BTreeMap::new(),
+            sequences: HashMap::new(),
+            committed: VecDeque::new(),
+            retrying: HashMap::new(),
+            credits: None,
+            waiting: VecDeque::new(),
+            deadlines: CallDeadlines::default(),
+        }
+    }
+
+    /// Gives calls handed to the executor `deadline` to complete, or as long
+    /// as they take if none.
+    pub fn with_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadlines = CallDeadlines::new(deadline);
+        self
+    }
+
+    /// Takes the executor's grant of `credits`, voiding those it granted
+    /// before, and hands it the calls waiting for them.
+    pub fn grant_credits(&mut self, credits: usize) {
+        tracing::info!("executor granted {credits} execution credits");
+        self.credits = Some(CallCredits {
+            granted: credits.max(1),
+            in_flight: HashSet::new(),
+        });
+        self.dispatch_waiting();
+    }
+
+    pub fn credit_status(&self) -> ExecutionCreditStatus {
+        ExecutionCreditStatus {
+            granted: self.credits.as_ref().map(|credits| credits.granted),
+            in_flight: self
+                .credits
+                .as_ref()
+                .map_or(0, |credits| credits.in_flight.len()),
+            waiting: self.waiting.len(),
+        }
+    }
+
+    /// Hands `transaction` to the executor if it has a credit to spare, or
+    /// has it wait for one.
+    fn execute(&mut self, transaction: Transaction) {
+        if self.credits.is_none() {
+            let _ = self.dispatch(transaction);
+            return;
         }
+        self.waiting
+            .push_back((transaction, Utc::now().timestamp_millis() as u64));
+        self.dispatch_waiting();
+    }
+
+    fn dispatch_waiting(&mut self) {
+        loop {
+            let Some(credits) = self.credits.as_mut() else {
+                return;
+            };
+            if credits.in_flight.len() >= credits.granted {
+                break;
+            }
+            let Some((transaction, _)) = self.waiting.pop_front() else {
+                break;
+            };
+            credits.in_flight.insert(transaction.hash_string());
+            if let Err(e) = self.dispatch(transaction) {
+                tracing::error!("unable to hand call to the executor: {e}");
+            }
+        }
+    }
+
+    /// Hands `transaction` to the executor, timing it out if it has not
+    /// completed by its deadline.
+    fn dispatch(&mut self, transaction: Transaction) -> std::io::Result<()> {
+        let transaction_hash = transaction.hash_string();
+        Self::send_to_executor(transaction)?;
+        if let Some((dispatch, deadline)) = self.start_deadline(&transaction_hash) {
+            Self::expire_after(transaction_hash, dispatch, deadline);
+        }
+        Ok(())
+    }
+
+    /// Starts the deadline of `transaction_hash`, about to be handed to the
+    /// executor, returning the dispatch it is for and how long it has.
+    fn start_deadline(&mut self, transaction_hash: &str) -> Option<(u64, Duration)> {
+        let deadline = self.deadlines.deadline()?;
+        let dispatch = self.deadlines.dispatched(transaction_hash)?;
+        Some((dispatch, deadline))
+    }
+
+    /// Tells the pending transactions once `dispatch` of `transaction_hash`
+    /// reaches its deadline.
+    fn expire_after(transaction_hash: String, dispatch: u64, deadline: Duration) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        runtime.spawn(async move {
+            tokio::time::sleep(deadline).await;
+            if let Some(pending) = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+                ActorType::PendingTransactions,
+            ) {
+                pending
+                    .cast(PendingTransactionMessage::CallDeadline {
+                        transaction_hash,
+                        dispatch,
+                    })
+                    .typecast()
+                    .log_err(|e| {
+                        PendingTransactionError::Custom(format!(
+                            "failed to cast CallDeadline to pending transactions: {e:?}"
+                        ))
+                    });
+            }
+        });
+    }
+
+    /// Whether `dispatch` of `transaction_hash` reaching its deadline times
+    /// it out, the call having neither completed nor been run again since.
+    fn call_deadline(&mut self, transaction_hash: &str, dispatch: u64) -> bool {
+        let expired = self.deadlines.expired(transaction_hash, dispatch);
+        if expired {
+            tracing::warn!("{transaction_hash} ran past its deadline, cancelling it");
+        } else {
+            tracing::debug!("ignoring the deadline of {transaction_hash}, which was settled");
+        }
+        expired
+    }
+
+    /// Whether the executor's answer to the cancellation of
+    /// `transaction_hash` settles the call.
+    fn call_cancelled(&mut self, transaction_hash: &str, confirmed: bool) -> bool {
+        self.deadlines.cancelled(transaction_hash, confirmed)
+    }
+
+    /// Whether the completion of `transaction_hash` counts, which it does
+    /// unless the call already ran past its deadline.
+    fn call_completed(&mut self, transaction_hash: &str) -> bool {
+        self.deadlines.completed(transaction_hash)
+    }
+
+    /// Gives back the credit `transaction_hash` held, once it has left the
+    /// executor. A call being retried keeps its credit until it does.
+    fn return_credit(&mut self, transaction_hash: &str) {
+        let returned = self
+            .credits
+            .as_mut()
+            .is_some_and(|credits| credits.in_flight.remove(transaction_hash));
+        if returned {
+            self.dispatch_waiting();
+        }
+    }
+
+    /// Drops the calls that have waited longer than `ttl` for a credit,
+    /// returning their hashes, so the calls held behind them go ahead.
+    pub fn expire_waiting(&mut self, ttl: Duration) -> Vec<String> {
+        let now = Utc::now().timestamp_millis() as u64;
+        let ttl = ttl.as_millis() as u64;
+        let (expired, waiting): (VecDeque<_>, VecDeque<_>) = std::mem::take(&mut self.waiting)
+            .into_iter()
+            .partition(|(_, since)| now.saturating_sub(*since) >= ttl);
+        self.waiting = waiting;
+        expired
+            .into_iter()
+            .map(|(transaction, _)| {
+                let transaction_hash = transaction.hash_string();
+                tracing::warn!("{transaction_hash} has expired waiting for the executor");
+                if let Err(e) = self.handle_failed_call(&transaction_hash) {
+                    tracing::error!("Error expiring call {transaction_hash}: {e}");
+                }
+                transaction_hash
+            })
+            .collect()
     }
 
     pub fn add_call(&mut self, transaction: Transaction) {
+        if let Some(retrying) = self.retrying.get_mut(&transaction.from()) {
+            tracing::warn!(
+                "holding call {} until {} from the same sender is retried",
+                &transaction.hash_string(),
+                &retrying.transaction_hash
+            );
+            retrying.held.push(transaction);
+            return;
+        }
+        let transaction_id = transaction.hash_string();
+        if self.vertices.contains_key(&transaction_id) {
+            tracing::warn!("call {transaction_id} is already in the pre-call graph");
+            return;
+        }
         tracing::info!(
             "adding call: {} to pre-call graph for program: {}",
-            &transaction.hash_string(),
+            &transaction_id,
             &transaction.to()
         );
-        let transaction_id = transaction.hash_string();
+        self.next_sequence += 1;
+        let sequence = self.next_sequence;
+        let mut vertex = PreCallVertex::new(transaction.clone(), sequence);
+
+        // Every call still in the graph was made before this one, so this
+        // one waits for those it conflicts with.
+        for (earlier_id, earlier) in self.vertices.iter() {
+            if let Ok(mut earlier) = earlier.write() {
+                if earlier.access.conflicts_with(&vertex.access) {
+                    earlier.dependencies.push(transaction_id.clone());
+                    vertex.waiting_on.insert(earlier_id.clone());
+                }
+            }
+        }
+        self.sequences.insert(transaction_id.clone(), sequence);
+        self.commits.insert(
+            sequence,
+            CommitSlot {
+                transaction_hash: transaction_id.clone(),
+                executed_at: None,
+                outputs: None,
+            },
+        );
 
-        // New transaction, new vertex, who dis
-        let vertex = Arc::new(RwLock::new(PreCallVertex::new(transaction.clone())));
+        let waiting_on = vertex.waiting_on.len();
+        self.vertices
+            .insert(transaction_id.clone(), Arc::new(RwLock::new(vertex)));
+        if waiting_on == 0 {
+            tracing::warn!("no conflicting calls found, executing");
+            self.execute(transaction);
+        } else {
+            tracing::warn!("holding call {transaction_id} until the {waiting_on} calls it conflicts with complete");
+        }
+    }
 
-        // set has_dependencies flag to false
-        let mut has_dependencies = false;
+    /// The call to run again for `transaction_hash`, holding the calls its
+    /// sender makes until it completes or fails.
+    fn retry_call(&mut self, transaction_hash: &str) -> Option<Transaction> {
+        let transaction = self
+            .vertices
+            .get(transaction_hash)?
+            .read()
+            .ok()?
+            .transaction
+            .clone();
+        self.retrying
+            .entry(transaction.from())
+            .or_insert_with(|| RetryingCall {
+                transaction_hash: transaction_hash.to_string(),
+                held: Vec::new(),
+            });
+        Some(transaction)
+    }
 
-        // check if we can acquire the read guard
-        if let Ok(guard) = vertex.read() {
-            // guard acquired, get the transaction its related to and
-            // pull the to address, this is the address to the program
-            // being called
-            let vertex_program_id = guard.transaction.to();
+    /// Adds back, in nonce order, the calls held for `transaction_hash`.
+    fn release_held(&mut self, transaction_hash: &str) {
+        let Some(sender) = self
+            .retrying
+            .iter()
+            .find(|(_, retrying)| retrying.transaction_hash == transaction_hash)
+            .map(|(sender, _)| *sender)
+        else {
+            return;
+        };
+        if let Some(mut retrying) = self.retrying.remove(&sender) {
+            retrying.held.sort_by_key(|transaction| transaction.nonce());
+            for transaction in retrying.held {
+                self.add_call(transaction);
+            }
+        }
+    }
 
-            // Insert the vertex into the graph vertices map, with transaction id/hash as key
-            self.vertices.insert(transaction_id.clone(), vertex.clone());
+    fn handle_completed_exec(&mut self, transaction_hash: &str) -> std::io::Result<()> {
+        if let Some(slot) = self
+            .sequences
+            .get(transaction_hash)
+            .and_then(|sequence| self.commits.get_mut(sequence))
+        {
+            slot.executed_at = Some(Utc::now().timestamp_millis() as u64);
+        }
+        self.return_credit(transaction_hash);
+        let completed = self.dispatch_next(transaction_hash);
+        self.release_held(transaction_hash);
+        completed
+    }
 
-            // Check if an entry for the program id exists in the program index.
-            // if so this means it has dependencies
-            let dependencies = self.program_index.entry(vertex_program_id).or_default();
+    /// Drops a call that failed for good, so the calls waiting on it go
+    /// ahead. A call that failed before it reached the graph has no vertex.
+    fn handle_failed_call(&mut self, transaction_hash: &str) -> std::io::Result<()> {
+        self.deadlines.forget(transaction_hash);
+        self.settle(transaction_hash);
+        if self.vertices.contains_key(transaction_hash) {
+            return self.handle_completed_exec(transaction_hash);
+        }
+        self.return_credit(transaction_hash);
+        self.release_held(transaction_hash);
+        Ok(())
+    }
 
-            for dependency_id in dependencies.iter() {
-                // For each dependency it has, get the relevant transaction vertex
-                if let Some(dep_vertex) = self.vertices.get(dependency_id) {
-                    tracing::warn!(
-                        "found pre-call dependencies: {} for transaction: {}",
-                        &dependency_id,
-                        &transaction_id
-                    );
-                    // For each dependent transaction vertex acquire a write guard
-                    if let Ok(mut dep_guard) = dep_vertex.write() {
-                        // check if the dependencies include the current transaction
-                        if dep_guard.dependencies.contains(&transaction_id.clone()) {
-                            // If not, add it.
-                            dep_guard.dependencies.push(transaction_id.clone());
-                        }
-                        //If so don't add it, but set the has dependencies flag to true
-                        //in either case, as a dependency exists
-                        has_dependencies = true;
+    /// Drops the vertex of a call that completed or failed, and hands on, in
+    /// the order they were made, the calls that no longer wait on any other.
+    fn dispatch_next(&mut self, transaction_hash: &str) -> std::io::Result<()> {
+        let vertex = self.vertices.remove(transaction_hash).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!(
+                    "unable to find vertex associated with transaction {}",
+                    transaction_hash
+                ),
+            )
+        })?;
+        let dependencies = vertex
+            .read()
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "unable to acquire read guard on vertex for executed transaction",
+                )
+            })?
+            .dependencies
+            .clone();
+
+        let mut ready = Vec::new();
+        for dependency in dependencies {
+            // A call failed before it was dispatched no longer has a vertex.
+            let Some(next) = self.vertices.get(&dependency) else {
+                continue;
+            };
+            let mut next = next.write().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("unable to acquire write guard on vertex for {dependency}"),
+                )
+            })?;
+            next.waiting_on.remove(transaction_hash);
+            if next.waiting_on.is_empty() {
+                ready.push((next.sequence, next.transaction.clone()));
+            }
+        }
+        ready.sort_by_key(|(sequence, _)| *sequence);
+        for (_, transaction) in ready {
+            self.execute(transaction);
+        }
+
+        Ok(())
+    }
+
+    /// Takes the outputs of `transaction`, committed once the calls made
+    /// before it have committed or failed. A call not made through the graph
+    /// has no turn to wait for, and is committed at once.
+    pub fn commit(&mut self, transaction: Transaction, outputs: Option<Outputs>) {
+        let slot = self
+            .sequences
+            .get(&transaction.hash_string())
+            .and_then(|sequence| self.commits.get_mut(sequence));
+        match slot {
+            Some(slot) => slot.outputs = Some((transaction, outputs)),
+            None => self.committed.push_back((transaction, outputs)),
+        }
+        self.release_commits();
+    }
+
+    /// Gives up the turn to commit of a call that failed, so the calls made
+    /// after it are not held back by it.
+    fn settle(&mut self, transaction_hash: &str) {
+        if let Some(sequence) = self.sequences.remove(transaction_hash) {
+            self.commits.remove(&sequence);
+            self.release_commits();
+        }
+    }
+
+    /// Gives up on the outputs of calls that completed longer than `ttl`
+    /// ago and never had them arrive, returning their hashes.
+    pub fn expire_commits(&mut self, ttl: Duration) -> Vec<String> {
+        let now = Utc::now().timestamp_millis() as u64;
+        let ttl = ttl.as_millis() as u64;
+        let expired: Vec<String> = self
+            .commits
+            .values()
+            .filter(|slot| slot.outputs.is_none())
+            .filter(|slot| {
+                slot.executed_at
+                    .is_some_and(|at| now.saturating_sub(at) >= ttl)
+            })
+            .map(|slot| slot.transaction_hash.clone())
+            .collect();
+        for transaction_hash in &expired {
+            tracing::warn!(
+                "outputs of {transaction_hash} never arrived, no longer waiting on them"
+            );
+            self.settle(transaction_hash);
+        }
+        expired
+    }
+
+    /// Moves the outputs at the front of the commit order to `committed`,
+    /// up to the first call with none yet.
+    fn release_commits(&mut self) {
+        while let Some(entry) = self.commits.first_entry() {
+            if entry.get().outputs.is_none() {
+                break;
+            }
+            let slot = entry.remove();
+            self.sequences.remove(&slot.transaction_hash);
+            if let Some(outputs) = slot.outputs {
+                self.committed.push_back(outputs);
+            }
+        }
+    }
+
+    /// The outputs of calls whose turn to commit has come, in the order the
+    /// calls were made.
+    pub fn take_committed(&mut self) -> Vec<(Transaction, Option<Outputs>)> {
+        self.committed.drain(..).collect()
+    }
+
+    fn send_to_executor(transaction: Transaction) -> std::io::Result<()> {
+        let executor: ActorRef<ExecutorMessage> =
+            ractor::registry::where_is(ActorType::Executor.to_string())
+                .ok_or(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "unable to acquire executor actor",
+                ))?
+                .into();
+
+        let message = ExecutorMessage::Exec { transaction };
+
+        let _ = executor.cast(message);
+
+        Ok(())
+    }
+}
+
+/// A transaction held until the nonces before it from its sender arrive.
+#[derive(Clone, Debug)]
+struct HeldTransaction {
+    transaction: Transaction,
+    outputs: Option<Outputs>,
+    timestamp: u64,
+    bytes: u64,
+}
+
+/// The nonce next in line from one sender, and their transactions with
+/// later nonces.
+#[derive(Clone, Debug)]
+struct SenderQueue {
+    next: U256,
+    held: BTreeMap<U256, HeldTransaction>,
+}
+
+impl SenderQueue {
+    fn new(next: U256) -> Self {
+        Self {
+            next,
+            held: BTreeMap::new(),
+        }
+    }
+
+    /// Checks that `transaction` may be held, returning the hash of the one
+    /// held with its nonce that it replaces, if any.
+    fn check_hold(
+        &self,
+        transaction: &Transaction,
+        replace: bool,
+        max_future: usize,
+    ) -> Result<Option<String>, PendingTransactionError> {
+        let nonce = transaction.nonce();
+        match self.held.get(&nonce) {
+            Some(held) if replace => Ok(Some(held.transaction.hash_string())),
+            Some(held) => Err(PendingTransactionError::NoncePending {
+                nonce,
+                pending: held.transaction.hash_string(),
+            }),
+            None if replace => Err(PendingTransactionError::NothingToReplace {
+                from: transaction.from(),
+                nonce,
+            }),
+            None if self.held.len() >= max_future => Err(PendingTransactionError::TooManyFuture {
+                from: transaction.from(),
+                max_future,
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Holds `transaction`, returning the one held with its nonce before,
+    /// if any.
+    fn hold(
+        &mut self,
+        transaction: Transaction,
+        outputs: Option<Outputs>,
+    ) -> Option<HeldTransaction> {
+        tracing::info!(
+            "holding {} with nonce {} until nonce {} arrives",
+            transaction.hash_string(),
+            transaction.nonce(),
+            self.next
+        );
+        let bytes = pending_bytes(&transaction, &outputs);
+        self.held.insert(
+            transaction.nonce(),
+            HeldTransaction {
+                transaction,
+                outputs,
+                timestamp: Utc::now().timestamp_millis() as u64,
+                bytes,
+            },
+        )
+    }
+
+    fn summary(&self, held: &HeldTransaction, now: u64) -> PendingTransactionSummary {
+        PendingTransactionSummary {
+            hash: held.transaction.hash_string(),
+            nonce: held.transaction.nonce(),
+            transaction_type: held.transaction.transaction_type().to_string(),
+            age_ms: now.saturating_sub(held.timestamp),
+            status: PendingStatus::Queued {
+                waiting_on: self.next,
+            },
+        }
+    }
+
+    /// Moves past the nonce next in line, and past those after it that are
+    /// `pending` already, returning the run of transactions held for the
+    /// nonces after it.
+    fn advance(&mut self, pending: impl Fn(&U256) -> bool) -> Vec<HeldTransaction> {
+        self.next += U256::from(1);
+        let mut ready = Vec::new();
+        loop {
+            match self.held.remove(&self.next) {
+                Some(held) => ready.push(held),
+                None if pending(&self.next) => {}
+                None => break,
+            }
+            self.next += U256::from(1);
+        }
+        ready
+    }
+}
+
+/// A transaction held until the time it is valid from.
+#[derive(Clone, Debug)]
+struct ScheduledTransaction {
+    transaction: Transaction,
+    outputs: Option<Outputs>,
+    replace: bool,
+    timestamp: u64,
+    /// The space it takes up in the pending pool, where it is counted while
+    /// it is held.
+    bytes: u64,
+}
+
+/// Transactions not valid yet, in the order they become valid.
+///
+/// They hold no nonce while they wait: the sender's other transactions go
+/// ahead as if they were not there, those with the same nonce included. Once
+/// valid, each is submitted as if it had just arrived, so it is applied if
+/// its nonce is next, held for a gap if it is ahead, and turned away if it
+/// was used meanwhile.
+#[derive(Clone, Debug)]
+pub struct ScheduledQueue {
+    held: BTreeMap<(u64, String), ScheduledTransaction>,
+    /// When each transaction held is valid from, by hash.
+    valid_after: HashMap<String, u64>,
+    /// How many transactions each sender holds.
+    per_sender: HashMap<Address, usize>,
+    /// How far ahead of now, in seconds, a transaction may be valid from.
+    max_ahead: u64,
+}
+
+impl Default for ScheduledQueue {
+    fn default() -> Self {
+        Self {
+            held: BTreeMap::new(),
+            valid_after: HashMap::new(),
+            per_sender: HashMap::new(),
+            max_ahead: DEFAULT_MAX_SCHEDULED_AHEAD.as_secs(),
+        }
+    }
+}
+
+impl ScheduledQueue {
+    /// Holds no transaction valid from further ahead than `max_ahead`.
+    pub fn with_max_ahead(mut self, max_ahead: Duration) -> Self {
+        self.max_ahead = max_ahead.as_secs();
+        self
+    }
+
+    /// Whether `transaction`, valid from `valid_after`, may be held at
+    /// `now`, both in seconds since the epoch: it is valid from no further
+    /// ahead than the queue holds to, and its sender holds fewer than
+    /// `max_scheduled` already.
+    fn check_hold(
+        &self,
+        transaction: &Transaction,
+        valid_after: u64,
+        now: u64,
+        max_scheduled: usize,
+    ) -> Result<(), PendingTransactionError> {
+        let latest = now.saturating_add(self.max_ahead);
+        if valid_after > latest {
+            return Err(PendingTransactionError::TooFarAhead {
+                valid_after,
+                latest,
+            });
+        }
+        let from = transaction.from();
+        if self.per_sender.get(&from).copied().unwrap_or_default() >= max_scheduled {
+            return Err(PendingTransactionError::TooManyScheduled {
+                from,
+                max_scheduled,
+            });
+        }
+        Ok(())
+    }
+
+    /// Holds `transaction`, of `bytes`, until `valid_after`.
+    fn hold(
+        &mut self,
+        transaction: Transaction,
+        outputs: Option<Outputs>,
+        replace: bool,
+        valid_after: u64,
+        bytes: u64,
+    ) {
+        tracing::info!(
+            "holding {} until it is valid at {valid_after}",
+            transaction.hash_string()
+        );
+        let transaction_hash = transaction.hash_string();
+        *self.per_sender.entry(transaction.from()).or_default() += 1;
+        self.valid_after
+            .insert(transaction_hash.clone(), valid_after);
+        self.held.insert(
+            (valid_after, transaction_hash),
+            ScheduledTransaction {
+                transaction,
+                outputs,
+                replace,
+                timestamp: Utc::now().timestamp_millis() as u64,
+                bytes,
+            },
+        );
+    }
+
+    /// Takes `held`, no longer held, out of the indexes.
+    fn forget(&mut self, held: &ScheduledTransaction) {
+        self.valid_after.remove(&held.transaction.hash_string());
+        let from = held.transaction.from();
+        if let Some(count) = self.per_sender.get_mut(&from) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_sender.remove(&from);
+            }
+        }
+    }
+
+    /// Takes the transactions valid by `now`, in seconds since the epoch,
+    /// in the order they became valid.
+    fn take_due(&mut self, now: u64) -> Vec<ScheduledTransaction> {
+        let later = self.held.split_off(&(now + 1, String::new()));
+        let due: Vec<ScheduledTransaction> = std::mem::replace(&mut self.held, later)
+            .into_values()
+            .collect();
+        for held in &due {
+            self.forget(held);
+        }
+        due
+    }
+
+    /// Evicts the transactions whose window closed by `now`, in seconds
+    /// since the epoch, before they became valid, returning them.
+    fn expire(&mut self, now: u64) -> Vec<ScheduledTransaction> {
+        let closed: Vec<(u64, String)> = self
+            .held
+            .iter()
+            .filter(|(_, held)| {
+                matches!(held.transaction.validity_at(now), Validity::Expired { .. })
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut expired = Vec::with_capacity(closed.len());
+        for key in closed {
+            if let Some(held) = self.held.remove(&key) {
+                tracing::warn!("{} expired before it was valid, evicting it", key.1);
+                self.forget(&held);
+                expired.push(held);
+            }
+        }
+        expired
+    }
+
+    pub fn contains(&self, transaction_hash: &str) -> bool {
+        self.valid_after.contains_key(transaction_hash)
+    }
+
+    fn summary(
+        valid_after: u64,
+        held: &ScheduledTransaction,
+        now: u64,
+    ) -> PendingTransactionSummary {
+        PendingTransactionSummary {
+            hash: held.transaction.hash_string(),
+            nonce: held.transaction.nonce(),
+            transaction_type: held.transaction.transaction_type().to_string(),
+            age_ms: now.saturating_sub(held.timestamp),
+            status: PendingStatus::Scheduled { valid_after },
+        }
+    }
+
+    fn find(&self, transaction_hash: &str, now: u64) -> Option<PendingTransactionSummary> {
+        let valid_after = *self.valid_after.get(transaction_hash)?;
+        self.held
+            .get(&(valid_after, transaction_hash.to_string()))
+            .map(|held| Self::summary(valid_after, held, now))
+    }
+
+    fn held_from(&self, address: &Address, now: u64) -> Vec<PendingTransactionSummary> {
+        self.held
+            .iter()
+            .filter(|(_, held)| held.transaction.from() == *address)
+            .map(|((valid_after, _), held)| Self::summary(*valid_after, held, now))
+            .collect()
+    }
+
+    /// The sends held, which are kept in the pending log. Calls are not
+    /// logged before they run, held or not.
+    fn sends(&self) -> impl Iterator<Item = (Transaction, Option<Outputs>)> + '_ {
+        self.held
+            .values()
+            .filter(|held| !held.transaction.transaction_type().is_call())
+            .map(|held| (held.transaction.clone(), held.outputs.clone()))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PendingGraph {
+    vertices: HashMap<String, Arc<RwLock<Vertex>>>,
+    account_index: HashMap<Address, VecDeque<String>>,
+    /// The vertices listing each transaction among their dependents, by its
+    /// hash, so one leaving the graph is struck from theirs directly.
+    listed_by: HashMap<String, HashSet<String>>,
+    /// The pending transaction from each sender with each nonce.
+    nonces: HashMap<Address, BTreeMap<U256, String>>,
+    /// The senders with transactions pending, and those held for a gap in
+    /// their nonces.
+    senders: HashMap<Address, SenderQueue>,
+    /// The sender and nonce of each transaction held for a gap, by its hash.
+    held_at: HashMap<String, (Address, U256)>,
+    /// What the pool holds in all and from each sender, counted as
+    /// transactions come and go. Bridge ins are not counted.
+    occupancy: Occupancy,
+    sender_occupancy: HashMap<Address, Occupancy>,
+    /// The transactions handed to the validator that are still pending.
+    in_flight: usize,
+    /// The most transactions held for a gap per sender.
+    max_future: usize,
+    /// How long a transaction waits to be handed to the validator before it
+    /// is evicted.
+    ttl: Duration,
+    limits: MempoolLimits,
+    metrics: Arc<MempoolMetrics>,
+    /// Transactions evicted to make room for others, until they are taken.
+    evicted: Vec<String>,
+    /// Transactions that wait on nothing pending, until they are handed to
+    /// the validator.
+    ready: ReadyQueue,
+    /// The most transactions with the validator at once.
+    max_in_flight: usize,
+}
+
+impl Default for PendingGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PendingGraph {
+    pub fn new() -> PendingGraph {
+        PendingGraph {
+            vertices: HashMap::new(),
+            account_index: HashMap::new(),
+            listed_by: HashMap::new(),
+            nonces: HashMap::new(),
+            senders: HashMap::new(),
+            held_at: HashMap::new(),
+            occupancy: Occupancy::default(),
+            sender_occupancy: HashMap::new(),
+            in_flight: 0,
+            max_future: DEFAULT_MAX_FUTURE_TRANSACTIONS,
+            ttl: DEFAULT_PENDING_TTL,
+            limits: MempoolLimits::default(),
+            metrics: Arc::new(MempoolMetrics::default()),
+            evicted: Vec::new(),
+            ready: ReadyQueue::new(Arc::new(FifoPolicy))
+                .with_fairness(Fairness::new(DEFAULT_SENDER_QUANTUM)),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: MempoolLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Holds at most `max_future` transactions per sender for a gap in
+    /// their nonces.
+    pub fn with_max_future(mut self, max_future: usize) -> Self {
+        self.max_future = max_future;
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<MempoolMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Orders the transactions ready for the validator by `policy`.
+    pub fn with_scheduling_policy(mut self, policy: Arc<dyn SchedulingPolicy>) -> Self {
+        self.ready = ReadyQueue::new(policy).with_fairness(self.ready.fairness().clone());
+        self
+    }
+
+    /// Holds the senders of transactions ready for the validator to the
+    /// shares of `fairness`.
+    pub fn with_fairness(mut self, fairness: Fairness) -> Self {
+        self.ready = std::mem::take(&mut self.ready).with_fairness(fairness);
+        self
+    }
+
+    /// Caps the transactions with the validator at once, at least one.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// Whether the graph is tracking the nonces of `sender`, which it stops
+    /// doing once nothing from them is pending.
+    pub fn tracks(&self, sender: &Address) -> bool {
+        self.senders.contains_key(sender)
+    }
+
+    /// Adds `transaction` to the graph if it is next in line from its sender,
+    /// releasing the run of those held behind it in nonce order, or holds it
+    /// until the nonces before it arrive. `next` is the nonce next in line
+    /// for a sender the graph is not tracking yet, as confirmed by their
+    /// account; without it, `transaction` is taken to be next.
+    ///
+    /// One from the same sender with the same nonce pending already turns it
+    /// away. With `replace`, that one is dropped for `transaction` instead,
+    /// as long as it has not been handed to the validator yet, and its hash
+    /// returned. A replacement is never chosen by its fee, so it has to be
+    /// asked for explicitly.
+    pub fn submit(
+        &mut self,
+        transaction: Transaction,
+        outputs: Option<Outputs>,
+        replace: bool,
+        next: Option<U256>,
+    ) -> Result<Option<String>, PendingTransactionError> {
+        if transaction.transaction_type().is_bridge_in() {
+            self.add_transaction(transaction, outputs);
+            return Ok(None);
+        }
+
+        let key = (transaction.from(), transaction.nonce());
+        let transaction_hash = transaction.hash_string();
+        let max_future = self.max_future;
+        let queue = self
+            .senders
+            .entry(key.0)
+            .or_insert_with(|| SenderQueue::new(next.unwrap_or(key.1)));
+        if key.1 > queue.next {
+            if queue
+                .held
+                .get(&key.1)
+                .is_some_and(|held| held.transaction == transaction)
+            {
+                return Ok(None);
+            }
+            let replaced = queue.check_hold(&transaction, replace, max_future)?;
+            let bytes = pending_bytes(&transaction, &outputs);
+            self.make_room(key.0, bytes, replaced.as_deref())?;
+            self.hold(transaction, outputs);
+            return Ok(replaced);
+        }
+        let in_line = key.1 == queue.next;
+
+        let replaced = match self.pending_at(&key.0, &key.1).cloned() {
+            Some(pending) if pending == transaction_hash => {
+                tracing::warn!("{transaction_hash} is already pending, skipping..");
+                return Ok(None);
+            }
+            Some(pending) if replace => {
+                if self.is_scheduled(&pending) {
+                    return Err(PendingTransactionError::AlreadyScheduled(pending));
+                }
+                Some(pending)
+            }
+            Some(pending) => {
+                return Err(PendingTransactionError::NoncePending {
+                    nonce: key.1,
+                    pending,
+                })
+            }
+            None if replace => {
+                return Err(PendingTransactionError::NothingToReplace {
+                    from: key.0,
+                    nonce: key.1,
+                })
+            }
+            None if !in_line => {
+                return Err(PendingTransactionError::NonceTooLow {
+                    nonce: key.1,
+                    next: self.senders[&key.0].next,
+                })
+            }
+            None => None,
+        };
+
+        let bytes = pending_bytes(&transaction, &outputs);
+        self.make_room(key.0, bytes, replaced.as_deref())?;
+        if let Some(pending) = &replaced {
+            self.forget(pending);
+        }
+        self.release(transaction, outputs);
+        if in_line {
+            let pending = self.nonces.get(&key.0);
+            let ready = self
+                .senders
+                .get_mut(&key.0)
+                .map(|queue| {
+                    queue.advance(|nonce| pending.is_some_and(|nonces| nonces.contains_key(nonce)))
+                })
+                .unwrap_or_default();
+            for held in ready {
+                self.unhold(&held);
+                tracing::info!(
+                    "releasing {} with nonce {} held for the gap before it",
+                    held.transaction.hash_string(),
+                    held.transaction.nonce()
+                );
+                self.release(held.transaction, held.outputs);
+            }
+        }
+        Ok(replaced)
+    }
+
+    fn release(&mut self, transaction: Transaction, outputs: Option<Outputs>) {
+        let (sender, nonce) = (transaction.from(), transaction.nonce());
+        let transaction_hash = transaction.hash_string();
+        self.add_transaction(transaction, outputs);
+        self.nonces
+            .entry(sender)
+            .or_default()
+            .insert(nonce, transaction_hash);
+    }
+
+    /// The hash of the transaction pending from `sender` at `nonce`, if any.
+    fn pending_at(&self, sender: &Address, nonce: &U256) -> Option<&String> {
+        self.nonces.get(sender)?.get(nonce)
+    }
+
+    /// Holds `transaction` for the gap before it, in place of the one held
+    /// with its nonce before, if any.
+    fn hold(&mut self, transaction: Transaction, outputs: Option<Outputs>) {
+        let (sender, nonce) = (transaction.from(), transaction.nonce());
+        let transaction_hash = transaction.hash_string();
+        let Some(queue) = self.senders.get_mut(&sender) else {
+            return;
+        };
+        let replaced = queue.hold(transaction, outputs);
+        let bytes = queue.held.get(&nonce).map_or(0, |held| held.bytes);
+        if let Some(replaced) = replaced {
+            self.unhold(&replaced);
+        }
+        self.held_at.insert(transaction_hash, (sender, nonce));
+        self.count(sender, bytes);
+    }
+
+    /// Takes `held`, no longer held, out of the index and the counts.
+    fn unhold(&mut self, held: &HeldTransaction) {
+        self.held_at.remove(&held.transaction.hash_string());
+        self.uncount(held.transaction.from(), held.bytes);
+    }
+
+    fn count(&mut self, sender: Address, bytes: u64) {
+        self.occupancy.add(bytes);
+        self.sender_occupancy.entry(sender).or_default().add(bytes);
+    }
+
+    fn uncount(&mut self, sender: Address, bytes: u64) {
+        self.occupancy.remove(bytes);
+        if let Some(occupancy) = self.sender_occupancy.get_mut(&sender) {
+            occupancy.remove(bytes);
+            if occupancy.transactions == 0 {
+                self.sender_occupancy.remove(&sender);
+            }
+        }
+    }
+
+    /// Takes the vertex of `transaction_hash` out of the graph, its indexes
+    /// and counts, and off the dependents of the vertices listing it,
+    /// returning it and those of them left with no dependents.
+    fn remove_vertex(
+        &mut self,
+        transaction_hash: &str,
+    ) -> Option<(Arc<RwLock<Vertex>>, Vec<String>)> {
+        let vertex = self.vertices.remove(transaction_hash)?;
+        let mut emptied = Vec::new();
+        for id in self.listed_by.remove(transaction_hash).unwrap_or_default() {
+            let Some(listing) = self.vertices.get(&id) else {
+                continue;
+            };
+            if let Ok(mut guard) = listing.write() {
+                guard
+                    .dependent_transactions
+                    .retain(|hash| hash != transaction_hash);
+                if guard.dependent_transactions.is_empty() {
+                    emptied.push(id);
+                }
+            }
+        }
+        if let Ok(guard) = vertex.read() {
+            for dependent in &guard.dependent_transactions {
+                if let Some(listing) = self.listed_by.get_mut(dependent) {
+                    listing.remove(transaction_hash);
+                }
+            }
+            for account in &guard.accounts_touched {
+                if let Some(transactions) = self.account_index.get_mut(account) {
+                    transactions.retain(|hash| hash != transaction_hash);
+                }
+            }
+            if guard.scheduled {
+                self.in_flight = self.in_flight.saturating_sub(1);
+            }
+            let transaction = &guard.transaction;
+            if !transaction.transaction_type().is_bridge_in() {
+                self.uncount(transaction.from(), guard.bytes);
+                if let Some(nonces) = self.nonces.get_mut(&transaction.from()) {
+                    if nonces
+                        .get(&transaction.nonce())
+                        .is_some_and(|hash| hash == transaction_hash)
+                    {
+                        nonces.remove(&transaction.nonce());
                     }
+                    if nonces.is_empty() {
+                        self.nonces.remove(&transaction.from());
+                    }
+                }
+            }
+        }
+        Some((vertex, emptied))
+    }
+
+    fn is_scheduled(&self, transaction_hash: &str) -> bool {
+        self.vertices
+            .get(transaction_hash)
+            .and_then(|vertex| vertex.read().ok().map(|guard| guard.scheduled))
+            .unwrap_or(false)
+    }
+
+    /// Drops a transaction that was never scheduled from the graph, along
+    /// with every reference to it.
+    fn forget(&mut self, transaction_hash: &str) {
+        if self.remove_vertex(transaction_hash).is_some() {
+            self.ready.remove(transaction_hash);
+        }
+    }
+
+    /// Evicts the transactions that have waited longer than the TTL without
+    /// being handed to the validator, or past the window they are valid in,
+    /// returning their hashes, and schedules those waiting behind them that
+    /// no longer wait on anything. One handed to the validator is pinned
+    /// until its outcome arrives.
+    pub fn clean_graph(&mut self) -> Vec<String> {
+        let now = Utc::now().timestamp_millis() as u64;
+        let ttl = self.ttl.as_millis() as u64;
+        let closed = |transaction: &Transaction| {
+            matches!(
+                transaction.validity_at(now / 1000),
+                Validity::Expired { .. }
+            )
+        };
+        let mut expired: Vec<String> = self
+            .vertices
+            .iter()
+            .filter_map(|(hash, vtx)| {
+                let guard = vtx.read().ok()?;
+                let elapsed = now.saturating_sub(guard.timestamp);
+                let stale = elapsed >= ttl || closed(&guard.transaction);
+                (!guard.scheduled && stale).then(|| hash.clone())
+            })
+            .collect();
+
+        for hash in &expired {
+            tracing::warn!("{} has expired, evicting it", &hash);
+        }
+        self.drop_unscheduled(&expired);
+
+        // Those held for a gap in their sender's nonces expire alike, and
+        // senders with nothing pending are no longer tracked.
+        let stale: Vec<(Address, U256)> = self
+            .senders
+            .iter()
+            .flat_map(|(sender, queue)| {
+                queue
+                    .held
+                    .iter()
+                    .filter(|(_, held)| {
+                        now.saturating_sub(held.timestamp) >= ttl || closed(&held.transaction)
+                    })
+                    .map(|(nonce, _)| (*sender, *nonce))
+            })
+            .collect();
+        for (sender, nonce) in stale {
+            let Some(held) = self
+                .senders
+                .get_mut(&sender)
+                .and_then(|queue| queue.held.remove(&nonce))
+            else {
+                continue;
+            };
+            tracing::warn!(
+                "{} has expired, evicting it",
+                held.transaction.hash_string()
+            );
+            expired.push(held.transaction.hash_string());
+            self.unhold(&held);
+        }
+        let nonces = &self.nonces;
+        self.senders
+            .retain(|sender, queue| !queue.held.is_empty() || nonces.contains_key(sender));
+
+        expired
+    }
+
+    /// Drops transactions that were never handed to the validator, putting
+    /// their nonces back in line, and schedules those waiting behind them
+    /// that no longer wait on anything.
+    fn drop_unscheduled(&mut self, hashes: &[String]) {
+        let mut deps = Vec::new();
+        for hash in hashes {
+            if let Some(vtx) = self.vertices.get(hash).cloned() {
+                if let Ok(guard) = vtx.read() {
+                    deps.extend(guard.dependent_transactions.iter().cloned());
+                    self.rewind(&guard.transaction);
                 }
             }
+            self.forget(hash);
+        }
+
+        tracing::warn!("collecting transactions ready for validation");
+        deps.sort();
+        deps.dedup();
+        let ready_for_validation: Vec<String> = deps
+            .into_iter()
+            .filter(|dep| {
+                let Some(guard) = self.vertices.get(dep).and_then(|vtx| vtx.read().ok()) else {
+                    return false;
+                };
+                !guard.scheduled
+                    && guard.accounts_touched().iter().all(|account| {
+                        self.account_index
+                            .get(account)
+                            .and_then(|act_deps| act_deps.front())
+                            .map_or(true, |front| front == dep)
+                    })
+            })
+            .collect();
+
+        self.schedule_ready(ready_for_validation);
+    }
+
+    /// Puts the nonce of `transaction`, dropped without being applied, back
+    /// in line for its sender.
+    fn rewind(&mut self, transaction: &Transaction) {
+        if transaction.transaction_type().is_bridge_in() {
+            return;
+        }
+        if let Some(queue) = self.senders.get_mut(&transaction.from()) {
+            queue.next = queue.next.min(transaction.nonce());
+        }
+    }
+
+    /// The space `transaction_hash` takes up in the pool, pending or held.
+    fn bytes_of(&self, transaction_hash: &str) -> Option<u64> {
+        if let Some(vertex) = self.vertices.get(transaction_hash) {
+            return vertex.read().ok().map(|guard| guard.bytes);
+        }
+        let (sender, nonce) = self.held_at.get(transaction_hash)?;
+        self.senders
+            .get(sender)?
+            .held
+            .get(nonce)
+            .map(|held| held.bytes)
+    }
+
+    /// Makes room for a transaction of `bytes` from `sender` that replaces
+    /// the one with hash `replacing`, if any. A full pool only takes it if it
+    /// is a replacement or the sender is within their quota, by evicting the
+    /// latest transactions of the senders furthest over theirs.
+    fn make_room(
+        &mut self,
+        sender: Address,
+        bytes: u64,
+        replacing: Option<&str>,
+    ) -> Result<(), PendingTransactionError> {
+        // What is replaced is the sender's own, and so never evicted here.
+        let replaced = replacing.and_then(|hash| self.bytes_of(hash));
+        let without_replaced = |mut occupancy: Occupancy| {
+            if let Some(bytes) = replaced {
+                occupancy.remove(bytes);
+            }
+            occupancy
+        };
+        let own = without_replaced(
+            self.sender_occupancy
+                .get(&sender)
+                .copied()
+                .unwrap_or_default(),
+        );
+        let over_quota = !self.limits.within_quota(own.with(bytes));
+        loop {
+            let pool = without_replaced(self.occupancy);
+            if self.limits.fits(pool.with(bytes)) {
+                return Ok(());
+            }
+            let mut over: Vec<(Address, f64)> = self
+                .sender_occupancy
+                .iter()
+                .filter(|(other, occupancy)| {
+                    **other != sender && !self.limits.within_quota(**occupancy)
+                })
+                .map(|(other, occupancy)| (*other, self.limits.quota_used(*occupancy)))
+                .collect();
+            over.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let victim = match replacing.is_some() || !over_quota {
+                true => over
+                    .iter()
+                    .find_map(|(other, _)| self.latest_evictable(other)),
+                false => None,
+            };
+            let Some(victim) = victim else {
+                self.metrics.record_rejection();
+                return Err(PendingTransactionError::MempoolFull(
+                    self.limits.full(pool, over_quota),
+                ));
+            };
+            self.evict(&victim);
         }
+    }
 
-        // If the transaction has dependencies, it can't be executed yet
-        // if it does not, forward it to executor for execution
-        if !has_dependencies {
-            tracing::warn!("no dependencies found, executing");
-            let _ = self.send_to_executor(transaction);
+    /// The transaction from `sender` the others depend on least: the one
+    /// held with the highest nonce, or else the pending one with the highest
+    /// nonce that has not been handed to the validator.
+    fn latest_evictable(&self, sender: &Address) -> Option<String> {
+        if let Some((_, held)) = self
+            .senders
+            .get(sender)
+            .and_then(|queue| queue.held.iter().next_back())
+        {
+            return Some(held.transaction.hash_string());
         }
+        self.nonces
+            .get(sender)?
+            .values()
+            .rev()
+            .find(|hash| !self.is_scheduled(hash))
+            .cloned()
     }
 
-    fn handle_completed_exec(&mut self, transaction_hash: &str) -> std::io::Result<()> {
-        // When a transaction has completed execution, get the next transaction
-        // dependency
-        let next = {
-            if let Some(exec_vertex) = self.vertices.remove(transaction_hash) {
-                if let Ok(mut guard) = exec_vertex.write() {
-                    if !guard.dependencies.is_empty() {
-                        guard.dependencies.remove(0)
-                    } else {
-                        return Ok(());
-                    }
-                } else {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "unable to acquire write guard on vertex for executed transaction",
-                    ));
+    fn evict(&mut self, transaction_hash: &str) {
+        tracing::warn!("{transaction_hash} evicted to make room in the pending pool");
+        match self.held_at.get(transaction_hash).copied() {
+            Some((sender, nonce)) => {
+                if let Some(held) = self
+                    .senders
+                    .get_mut(&sender)
+                    .and_then(|queue| queue.held.remove(&nonce))
+                {
+                    self.unhold(&held);
                 }
-            } else {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!(
-                        "unable to find vertex associated with transaction {}",
-                        transaction_hash
-                    ),
-                ));
-            }
-        };
-
-        if let Some(next_vtx) = self.vertices.get(&next) {
-            if let Ok(guard) = next_vtx.read() {
-                let transaction = guard.transaction.clone();
-                let _ = self.send_to_executor(transaction);
-            } else {
-                //return error
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("unable to find vertex associated with transaction {}", next),
-                ));
             }
-        };
-
-        Ok(())
+            None => self.drop_unscheduled(&[transaction_hash.to_string()]),
+        }
+        self.evicted.push(transaction_hash.to_string());
+        self.metrics.record_eviction();
     }
 
-    fn send_to_executor(&self, transaction: Transaction) -> std::io::Result<()> {
-        let executor: ActorRef<ExecutorMessage> =
-            ractor::registry::where_is(ActorType::Executor.to_string())
-                .ok_or(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "unable to acquire executor actor",
-                ))?
-                .into();
-
-        let message = ExecutorMessage::Exec { transaction };
-
-        let _ = executor.cast(message);
-
-        Ok(())
+    /// Whether `transaction_hash` is in the pool, held for a gap in its
+    /// sender's nonces or not.
+    pub fn contains(&self, transaction_hash: &str) -> bool {
+        self.vertices.contains_key(transaction_hash) || self.held_at.contains_key(transaction_hash)
     }
-}
 
-#[derive(Clone, Debug, Default)]
-pub struct PendingGraph {
-    vertices: HashMap<String, Arc<RwLock<Vertex>>>,
-    account_index: HashMap<Address, VecDeque<String>>,
-}
+    /// Where `transaction_hash` has got to, if it is in the pool.
+    pub fn summary(&self, transaction_hash: &str) -> Option<PendingTransactionSummary> {
+        let now = Utc::now().timestamp_millis() as u64;
+        if let Some(guard) = self
+            .vertices
+            .get(transaction_hash)
+            .and_then(|vertex| vertex.read().ok())
+        {
+            return Some(guard.summary(now));
+        }
+        let (sender, nonce) = self.held_at.get(transaction_hash)?;
+        let queue = self.senders.get(sender)?;
+        queue.held.get(nonce).map(|held| queue.summary(held, now))
+    }
 
-impl PendingGraph {
-    pub fn new() -> PendingGraph {
-        PendingGraph {
-            vertices: HashMap::new(),
-            account_index: HashMap::new(),
+    /// The transactions in the pool from `address`, in nonce order.
+    pub fn pending_from(&self, address: &Address) -> Vec<PendingTransactionSummary> {
+        let now = Utc::now().timestamp_millis() as u64;
+        let mut transactions: Vec<PendingTransactionSummary> = self
+            .vertices
+            .values()
+            .filter_map(|vertex| {
+                let guard = vertex.read().ok()?;
+                (guard.transaction.from() == *address).then(|| guard.summary(now))
+            })
+            .collect();
+        if let Some(queue) = self.senders.get(address) {
+            transactions.extend(queue.held.values().map(|held| queue.summary(held, now)));
         }
+        transactions.sort_by(|a, b| a.nonce.cmp(&b.nonce).then_with(|| a.hash.cmp(&b.hash)));
+        transactions
     }
 
-    pub fn clean_graph(&mut self) {
-        // Look at all vertices, see if any have timed out,
-        // if any have timed out, check if they have dependent transactions
-        // queue dependent transactions up for execution.
-        // check every 15 seconds.
-        for (hash, vtx) in self.vertices.clone() {
-            tracing::warn!("checking if {} has timed out", &hash);
-            // Get this vertex
-            let mut timed_out = false;
-            // Check if we can get read guard
-            if let Ok(guard) = vtx.read() {
-                // Check if it is timed out
-                let elapsed = Utc::now().timestamp_millis() as u64 - guard.timestamp;
-                if elapsed >= PENDING_TIMEOUT {
-                    // Set timedout flag
-                    tracing::warn!("{} has indeed timed out", &hash);
-                    timed_out = true;
-                }
-            }
+    /// Every transaction in the pool, held for a gap in its nonces or not,
+    /// ordered by sender and nonce.
+    pub fn transactions(&self) -> Vec<(Transaction, Option<Outputs>)> {
+        let mut transactions: Vec<(Transaction, Option<Outputs>)> = self
+            .vertices
+            .values()
+            .filter_map(|vertex| {
+                let guard = vertex.read().ok()?;
+                Some((guard.transaction.clone(), guard.outputs.clone()))
+            })
+            .chain(self.senders.values().flat_map(|queue| {
+                queue
+                    .held
+                    .values()
+                    .map(|held| (held.transaction.clone(), held.outputs.clone()))
+            }))
+            .collect();
+        transactions.sort_by(|(a, _), (b, _)| {
+            a.from()
+                .cmp(&b.from())
+                .then_with(|| a.nonce().cmp(&b.nonce()))
+        });
+        transactions
+    }
 
-            let mut deps = Vec::new();
-            if timed_out {
-                // If timed out
-                if let Some(vtx) = self.vertices.remove(&hash) {
-                    // get the vertex
-                    if let Ok(guard) = vtx.read() {
-                        // get the dependent transaction hashes and push them into
-                        // a vector
-                        for dep in &guard.dependent_transactions {
-                            tracing::warn!("collecting dependent transaction hashes");
-                            deps.push(dep.clone());
-                        }
-                    }
-                }
-            }
+    /// The transactions evicted to make room for others since last taken.
+    pub fn take_evicted(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.evicted)
+    }
 
-            tracing::warn!("collecting transactions ready for validation");
-            let ready_for_validation: Vec<String> = deps.iter().filter_map(|dep| {
-                match self.vertices.get_mut(dep) {
-                    Some(vtx) => {
-                        match vtx.write() {
-                            Ok(mut guard) => {
-                                guard.accounts_touched_mut().iter().for_each(|account| {
-                                    let account_dependencies = self.account_index.get_mut(account);
-                                    if let Some(act_deps) = account_dependencies {
-                                        if let Some(index) = &act_deps.iter().position(|h| h.clone() == hash) {
-                                            act_deps.remove(*index);
-                                        }
-                                    }
-                                });
-
-                                let accounts_involved = guard.accounts_touched().clone();
-                                let act_next: VecDeque<String> = accounts_involved.iter().filter_map(|acct| {
-                                    let account_dependencies = self.account_index.get(acct);
-                                    if let Some(act_deps) = account_dependencies {
-                                        act_deps.front().cloned()
-                                    } else {
-                                        None
-                                    }
-                                }).collect();
-
-                                if act_next.iter().all(|h| h == dep) {
-                                    Some(dep.clone())
-                                } else {
-                                    None
-                                }
-                            }
-                            Err(e) => {
-                                tracing::error!("Error attempting to acquire write guard for vertex for transaction: {}: {}", &dep, e);
-                                None
-                            }
-                        }
-                    }
-                    None => None
-                }
-            }).collect();
-
-            for transaction_hash in ready_for_validation {
-                if let Some(vtx) = self.vertices.clone().get(&transaction_hash) {
-                    match vtx.read() {
-                        Ok(guard) => {
-                            let transaction = guard.transaction.clone();
-                            let outputs = guard.outputs.clone();
-                            tracing::warn!("scheduling: {} with validator", transaction_hash);
-                            let _ = self.schedule_with_validator(transaction, outputs);
-                        }
-                        Err(e) => tracing::error!(
-                            "Unable to acquire read guard on vertex: {}: {}",
-                            transaction_hash,
-                            e
-                        ),
-                    }
-                }
-            }
-        }
+    pub fn metrics(&self) -> Arc<MempoolMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    pub fn record_occupancy(&self) {
+        self.metrics.record_occupancy(self.occupancy);
     }
 
     pub fn add_transaction(&mut self, transaction: Transaction, outputs: Option<Outputs>) {
@@ -353,6 +1646,8 @@ impl PendingGraph {
             &transaction.hash_string()
         );
         let transaction_id = transaction.hash_string();
+        // One in the graph already is put back afresh, not counted twice.
+        self.forget(&transaction_id);
 
         // Create a new vertex
         let vertex = Arc::new(RwLock::new(Vertex::new(
@@ -370,6 +1665,9 @@ impl PendingGraph {
 
             // insert the vertex into the vertices map
             self.vertices.insert(transaction_id.clone(), vertex.clone());
+            if !transaction.transaction_type().is_bridge_in() {
+                self.count(transaction.from(), guard.bytes);
+            }
 
             for account in vertex_accounts {
                 // for each account involved in the transaction
@@ -402,6 +1700,10 @@ impl PendingGraph {
                                 dep_guard
                                     .dependent_transactions
                                     .push(transaction_id.clone());
+                                self.listed_by
+                                    .entry(transaction_id.clone())
+                                    .or_default()
+                                    .insert(dependency_id.clone());
                             }
                             // set the dependency flag to true
                             has_dependencies = true;
@@ -417,103 +1719,70 @@ impl PendingGraph {
         }
 
         if !has_dependencies {
-            // If there are no dependencies, then go ahead and schedule the
-            // transaction with the validator
-            tracing::warn!("no dependencies found, scheduling with validator");
-            let _ = self.schedule_with_validator(transaction, outputs);
+            // If there are no dependencies, the transaction is ready to be
+            // scheduled with the validator
+            tracing::warn!("no dependencies found, queueing for the validator");
+            self.schedule_ready(vec![transaction_id]);
         }
     }
 
-    fn handle_valid(&mut self, validated_transaction_hash: &str) -> Vec<String> {
-        tracing::info!("handling validated transaction");
-        let mut transactions_ready_for_validation = Vec::new();
-        if let Some(validated_vertex) = self.vertices.remove(validated_transaction_hash) {
-            for (id, vertex) in self.vertices.iter() {
-                tracing::info!("checking for dependent transactions");
-                if let Ok(mut guard) = vertex.write() {
-                    if let Some(index) = guard
-                        .dependent_transactions
-                        .iter()
-                        .position(|hash| hash == validated_transaction_hash)
-                    {
-                        let _ = guard.dependent_transactions.remove(index);
-                        if guard.dependent_transactions.is_empty() {
-                            tracing::info!(
-                                "marking dependent transaction: {} ready for validation",
-                                &id
-                            );
-                            transactions_ready_for_validation.push(id.clone());
-                        }
-                    }
-                }
+    /// Queues the transactions `transaction_ids`, which wait on nothing
+    /// pending, for the validator, and hands it as many of those queued as
+    /// it has room for, by the scheduling policy, returning their hashes.
+    /// Called whenever a transaction leaves the validator too, with none.
+    pub fn schedule_ready(&mut self, transaction_ids: Vec<String>) -> Vec<String> {
+        for id in transaction_ids {
+            let Some(guard) = self.vertices.get(&id).and_then(|vtx| vtx.read().ok()) else {
+                continue;
+            };
+            if !guard.scheduled {
+                self.ready.push(guard.transaction.clone(), guard.timestamp);
             }
+        }
 
-            if let Ok(guard) = validated_vertex.read() {
-                for account in guard.accounts_touched.iter() {
-                    if let Some(transactions) = self.account_index.get_mut(account) {
-                        tracing::info!(
-                            "removing validated transaction {:?} from dependency graph for account: {}", 
-                            &validated_transaction_hash, &account
-                        );
-                        if let Some(index) = &transactions
-                            .iter()
-                            .position(|hash| hash == validated_transaction_hash)
-                        {
-                            transactions.remove(*index);
-                        }
-                    }
-                }
-            }
+        let mut scheduled = Vec::new();
+        while self.in_flight < self.max_in_flight {
+            let Some(transaction) = self.ready.pop() else {
+                break;
+            };
+            let Some((transaction, outputs)) =
+                self.get_transactions(vec![transaction.hash_string()]).pop()
+            else {
+                continue;
+            };
+            tracing::warn!("scheduling: {} with validator", transaction.hash_string());
+            scheduled.push(transaction.hash_string());
+            let _ = self.schedule_with_validator(transaction, outputs);
         }
+        scheduled
+    }
 
-        transactions_ready_for_validation
+    fn handle_valid(&mut self, validated_transaction_hash: &str) -> Vec<String> {
+        tracing::info!("handling validated transaction");
+        let Some((_, ready)) = self.remove_vertex(validated_transaction_hash) else {
+            return Vec::new();
+        };
+        for id in &ready {
+            tracing::info!("marking dependent transaction: {id} ready for validation");
+        }
+        ready
     }
 
     fn handle_invalid(
         &mut self,
         invalid_transaction_hash: &str,
-        e: Box<dyn std::error::Error + Send>,
+        e: LasrError,
     ) -> Result<Vec<String>, PendingTransactionError> {
         tracing::info!("handling invalid transaction");
         let mut transactions_ready_for_validation = Vec::new();
-        if let Some(invalid_vertex) = self.vertices.remove(invalid_transaction_hash) {
-            for (id, vertex) in self.vertices.iter() {
-                tracing::info!("checking for dependenty transactions");
-                if let Ok(mut guard) = vertex.write() {
-                    if let Some(index) = guard
-                        .dependent_transactions
-                        .iter()
-                        .position(|hash| hash == invalid_transaction_hash)
-                    {
-                        let _ = guard.dependent_transactions.remove(index);
-                        if guard.dependent_transactions.is_empty() {
-                            tracing::info!(
-                                "marking dependent transaction: {} ready for validation",
-                                &id
-                            );
-                            transactions_ready_for_validation.push(id.clone());
-                        }
-                    }
-                }
-            }
-
+        if let Some((invalid_vertex, ready)) = self.remove_vertex(invalid_transaction_hash) {
             if let Ok(guard) = invalid_vertex.read() {
-                for account in guard.accounts_touched.iter() {
-                    if let Some(transactions) = self.account_index.get_mut(account) {
-                        tracing::info!(
-                            "removing invalid transaction {:?} from depdendency graph for account: {}",
-                            &invalid_transaction_hash, &account
-                        );
-
-                        if let Some(index) = &transactions
-                            .iter()
-                            .position(|hash| hash == invalid_transaction_hash)
-                        {
-                            transactions.remove(*index);
-                        }
-                    }
-                }
+                self.rewind(&guard.transaction);
+            }
+            for id in &ready {
+                tracing::info!("marking dependent transaction: {id} ready for validation");
             }
+            transactions_ready_for_validation = ready;
         }
 
         if let Some(scheduler) =
@@ -560,6 +1829,14 @@ impl PendingGraph {
         transaction: Transaction,
         outputs: Option<Outputs>,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(vertex) = self.vertices.get(&transaction.hash_string()) {
+            if let Ok(mut guard) = vertex.write() {
+                if !guard.scheduled {
+                    guard.scheduled = true;
+                    self.in_flight += 1;
+                }
+            }
+        }
         if let Some(validator) =
             get_actor_ref::<ValidatorMessage, ValidatorError>(ActorType::Validator)
         {
@@ -569,7 +1846,14 @@ impl PendingGraph {
             );
             let transaction_type = transaction.transaction_type();
             let message = match &transaction_type {
-                TransactionType::Send(_) => ValidatorMessage::PendingTransaction { transaction },
+                TransactionType::Send(_)
+                | TransactionType::Burn(_)
+                | TransactionType::BurnAmount { .. }
+                | TransactionType::BridgeOutTo { .. }
+                | TransactionType::Delegate(_)
+                | TransactionType::SetAccountMetadata(_) => {
+                    ValidatorMessage::PendingTransaction { transaction }
+                }
                 TransactionType::Call(_) => ValidatorMessage::PendingCall {
                     outputs,
                     transaction,
@@ -600,6 +1884,13 @@ impl PendingGraph {
 pub struct DependencyGraphs {
     pub pending: PendingGraph,
     pub pre_call: PreCallGraph,
+    /// Transactions not valid yet, held apart from both graphs until they
+    /// are.
+    pub scheduled: ScheduledQueue,
+    /// Where the pending pool is kept across restarts, if anywhere.
+    log: Option<PendingLog>,
+    /// Transactions read back from the log, until they are replayed.
+    replay: Vec<(Transaction, Option<Outputs>)>,
 }
 
 impl DependencyGraphs {
@@ -607,6 +1898,81 @@ impl DependencyGraphs {
         Self {
             pending: PendingGraph::new(),
             pre_call: PreCallGraph::new(),
+            scheduled: ScheduledQueue::default(),
+            log: None,
+            replay: Vec::new(),
+        }
+    }
+
+    /// Keeps the pending pool in the log at `path`, reading back the
+    /// transactions it held to be replayed.
+    pub fn with_log(mut self, path: &Path) -> Result<Self, PendingLogError> {
+        let (log, replay) = PendingLog::open(path)?;
+        tracing::info!(
+            "read {} pending transactions back from {}",
+            replay.len(),
+            path.display()
+        );
+        self.log = Some(log);
+        self.replay = replay;
+        Ok(self)
+    }
+
+    /// Holds `transaction` until `valid_after`, counted in the pool's
+    /// occupancy while it waits, so it is held only if the pool has room
+    /// for it as it would for one pending.
+    fn hold_until(
+        &mut self,
+        transaction: &Transaction,
+        outputs: &Option<Outputs>,
+        replace: bool,
+        valid_after: u64,
+        now: u64,
+    ) -> Result<(), PendingTransactionError> {
+        self.scheduled
+            .check_hold(transaction, valid_after, now, self.pending.max_future)?;
+        let (from, bytes) = (transaction.from(), pending_bytes(transaction, outputs));
+        self.pending.make_room(from, bytes, None)?;
+        self.scheduled.hold(
+            transaction.clone(),
+            outputs.clone(),
+            replace,
+            valid_after,
+            bytes,
+        );
+        self.pending.count(from, bytes);
+        Ok(())
+    }
+
+    fn log_accepted(&mut self, transaction: &Transaction, outputs: &Option<Outputs>) {
+        if let Some(log) = self.log.as_mut() {
+            if let Err(e) = log.accepted(transaction, outputs) {
+                tracing::error!(
+                    "failed to log pending transaction {}: {e}",
+                    transaction.hash_string()
+                );
+            }
+        }
+    }
+
+    fn log_removed(&mut self, transaction_hash: &str) {
+        if let Some(log) = self.log.as_mut() {
+            if let Err(e) = log.removed(transaction_hash) {
+                tracing::error!("failed to log removal of transaction {transaction_hash}: {e}");
+            }
+        }
+    }
+
+    fn compact_log(&mut self) {
+        let Some(log) = self.log.as_mut() else {
+            return;
+        };
+        if log.needs_compaction() {
+            let mut transactions = self.pending.transactions();
+            transactions.extend(self.scheduled.sends());
+            if let Err(e) = log.compact(transactions) {
+                tracing::error!("failed to compact the pending log: {e}");
+            }
         }
     }
 
@@ -614,16 +1980,42 @@ impl DependencyGraphs {
         self.pending.add_transaction(transaction, outputs);
     }
 
+    pub fn submit(
+        &mut self,
+        transaction: Transaction,
+        outputs: Option<Outputs>,
+        replace: bool,
+        next: Option<U256>,
+    ) -> Result<Option<String>, PendingTransactionError> {
+        self.pending.submit(transaction, outputs, replace, next)
+    }
+
     pub fn add_call(&mut self, transaction: Transaction) {
         self.pre_call.add_call(transaction);
     }
 
     pub fn handle_completed_exec(&mut self, transaction_hash: &str) {
+        if !self.pre_call.call_completed(transaction_hash) {
+            tracing::warn!(
+                "ignoring the completion of {transaction_hash}, which ran past its deadline"
+            );
+            return;
+        }
         if let Err(e) = self.pre_call.handle_completed_exec(transaction_hash) {
             tracing::error!("Error in handle_completed_exec: {e}");
         }
     }
 
+    pub fn retry_call(&mut self, transaction_hash: &str) -> Option<Transaction> {
+        self.pre_call.retry_call(transaction_hash)
+    }
+
+    pub fn handle_failed_call(&mut self, transaction_hash: &str) {
+        if let Err(e) = self.pre_call.handle_failed_call(transaction_hash) {
+            tracing::error!("Error in handle_failed_call: {e}");
+        }
+    }
+
     pub fn get_transactions(
         &self,
         transaction_ids: Vec<String>,
@@ -643,16 +2035,46 @@ impl DependencyGraphs {
         self.pending.handle_valid(transaction_hash)
     }
 
+    pub fn schedule_ready(&mut self, transaction_ids: Vec<String>) -> Vec<String> {
+        self.pending.schedule_ready(transaction_ids)
+    }
+
     pub fn handle_invalid(
         &mut self,
         transaction_hash: &str,
-        e: Box<dyn std::error::Error + Send>,
+        e: LasrError,
     ) -> Result<Vec<String>, PendingTransactionError> {
         self.pending.handle_invalid(transaction_hash, e)
     }
 
-    pub fn clean_pending_graph(&mut self) {
-        self.pending.clean_graph();
+    /// Where `transaction_hash` has got to, if it is in the pool, held until
+    /// it is valid or not.
+    pub fn summary(&self, transaction_hash: &str) -> Option<PendingTransactionSummary> {
+        let now = Utc::now().timestamp_millis() as u64;
+        self.pending
+            .summary(transaction_hash)
+            .or_else(|| self.scheduled.find(transaction_hash, now))
+    }
+
+    /// The transactions in the pool from `address`, held until they are
+    /// valid or not, in nonce order.
+    pub fn pending_from(&self, address: &Address) -> Vec<PendingTransactionSummary> {
+        let now = Utc::now().timestamp_millis() as u64;
+        let mut transactions = self.pending.pending_from(address);
+        transactions.extend(self.scheduled.held_from(address, now));
+        transactions.sort_by(|a, b| a.nonce.cmp(&b.nonce).then_with(|| a.hash.cmp(&b.hash)));
+        transactions
+    }
+
+    /// Evicts the transactions that have waited longer than the TTL, and
+    /// the calls that have waited as long for the executor to have room.
+    /// Calls whose outputs have not arrived as long after they completed
+    /// no longer hold back the commits of those made after them.
+    pub fn clean_pending_graph(&mut self) -> Vec<String> {
+        let mut expired = self.pending.clean_graph();
+        expired.extend(self.pre_call.expire_waiting(self.pending.ttl));
+        self.pre_call.expire_commits(self.pending.ttl);
+        expired
     }
 
     pub fn clean_pre_call_graph(&mut self) {
@@ -660,31 +2082,373 @@ impl DependencyGraphs {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct PendingTransactionActor {
-    bridge_in_transactions: std::sync::Arc<tokio::sync::Mutex<Vec<Transaction>>>,
-}
-impl PendingTransactionActor {
-    pub fn new() -> Self {
-        Self {
-            bridge_in_transactions: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
+#[derive(Debug, Clone, Default)]
+pub struct PendingTransactionActor {
+    bridge_in_transactions: std::sync::Arc<tokio::sync::Mutex<Vec<Transaction>>>,
+    mempool_metrics: Arc<MempoolMetrics>,
+    config: PendingConfig,
+    mempool_limits: MempoolLimits,
+    scheduling: SchedulingConfig,
+    fees: FeeSchedule,
+}
+impl PendingTransactionActor {
+    pub fn new() -> Self {
+        Self {
+            bridge_in_transactions: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            mempool_metrics: Arc::new(MempoolMetrics::default()),
+            config: PendingConfig::default(),
+            mempool_limits: MempoolLimits::default(),
+            scheduling: SchedulingConfig::default(),
+            fees: FeeSchedule::default(),
+        }
+    }
+
+    /// Runs the pending pool as `config` says.
+    pub fn with_config(mut self, config: PendingConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Keeps the pending pool in the log at `path`, so it survives the node
+    /// restarting.
+    pub fn with_log_path(mut self, path: PathBuf) -> Self {
+        self.config.log_path = Some(path);
+        self
+    }
+
+    /// Bounds the pending pool by `limits` instead of the defaults.
+    pub fn with_mempool_limits(mut self, limits: MempoolLimits) -> Self {
+        self.mempool_limits = limits;
+        self
+    }
+
+    /// Hands transactions to the validator as `scheduling` says.
+    pub fn with_scheduling(mut self, scheduling: SchedulingConfig) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    /// Ranks transactions by the priority fees they pay over their floor
+    /// under `fees`, when scheduled by priority.
+    pub fn with_fee_schedule(mut self, fees: FeeSchedule) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Whether `transaction` is the first time this bridge in has been seen.
+    async fn first_bridge_in(&self, transaction: &Transaction) -> bool {
+        let mut bridge_in_transactions = self.bridge_in_transactions.lock().await;
+        match bridge_in_transactions.contains(transaction) {
+            true => false,
+            false => {
+                bridge_in_transactions.push(transaction.clone());
+                true
+            }
+        }
+    }
+
+    /// Occupancy of the pending pool and the evictions from it.
+    pub fn mempool_metrics(&self) -> Arc<MempoolMetrics> {
+        Arc::clone(&self.mempool_metrics)
+    }
+}
+impl ActorName for PendingTransactionActor {
+    fn name(&self) -> ractor::ActorName {
+        ActorType::PendingTransactions.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Error, Default)]
+pub enum PendingTransactionError {
+    #[default]
+    #[error("failed to acquire PendingTransactionActor from registry")]
+    RactorRegistryError,
+
+    #[error("transaction {pending} with nonce {nonce} is already pending, replace it instead")]
+    NoncePending { nonce: U256, pending: String },
+
+    #[error("transaction {0} is already being validated and cannot be replaced")]
+    AlreadyScheduled(String),
+
+    #[error("no transaction from {from} with nonce {nonce} is pending to be replaced")]
+    NothingToReplace { from: Address, nonce: U256 },
+
+    #[error("nonce {nonce} has been used already, the next is {next}")]
+    NonceTooLow { nonce: U256, next: U256 },
+
+    #[error("{from} already has {max_future} transactions waiting for earlier nonces")]
+    TooManyFuture { from: Address, max_future: usize },
+
+    #[error("{from} already has {max_scheduled} transactions held until they are valid")]
+    TooManyScheduled { from: Address, max_scheduled: usize },
+
+    #[error("valid only from {valid_after}, later than the latest held until, {latest}")]
+    TooFarAhead { valid_after: u64, latest: u64 },
+
+    #[error("{0}")]
+    MempoolFull(MempoolFull),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl PendingTransactionError {
+    /// The RPC error code the pool turning a transaction away is answered
+    /// with.
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            PendingTransactionError::NoncePending { .. }
+            | PendingTransactionError::AlreadyScheduled(_)
+            | PendingTransactionError::NonceTooLow { .. }
+            | PendingTransactionError::TooManyFuture { .. } => RpcErrorCode::InvalidNonce,
+            PendingTransactionError::NothingToReplace { .. } => RpcErrorCode::NotFound,
+            PendingTransactionError::MempoolFull(_)
+            | PendingTransactionError::TooManyScheduled { .. } => RpcErrorCode::MempoolFull,
+            PendingTransactionError::TooFarAhead { .. } => RpcErrorCode::InvalidParams,
+            PendingTransactionError::RactorRegistryError | PendingTransactionError::Custom(_) => {
+                RpcErrorCode::Internal
+            }
+        }
+    }
+}
+
+impl From<PendingTransactionError> for LasrError {
+    fn from(e: PendingTransactionError) -> Self {
+        let e = match e {
+            PendingTransactionError::RactorRegistryError => {
+                RpcError::Unavailable(ActorType::PendingTransactions.to_string())
+            }
+            PendingTransactionError::Custom(reason) => RpcError::Custom(reason),
+            e => RpcError::Rejected {
+                code: e.code(),
+                reason: e.to_string(),
+            },
+        };
+        LasrError::Rpc(e)
+    }
+}
+
+/// Drops the transactions evicted to make room for others from the log,
+/// telling the scheduler of them.
+fn report_evicted(state: &mut DependencyGraphs) {
+    let evicted = state.pending.take_evicted();
+    for transaction_hash in &evicted {
+        state.log_removed(transaction_hash);
+    }
+    if let Some(scheduler) = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+    {
+        for transaction_hash in evicted {
+            let message = SchedulerMessage::TransactionEvicted { transaction_hash };
+            scheduler.cast(message).typecast().log_err(|e| {
+                SchedulerError::Custom(format!(
+                    "failed to cast TransactionEvicted to scheduler: {e:?}"
+                ))
+            });
+        }
+    }
+}
+
+/// Submits `transaction` to the pending pool, telling the scheduler of those
+/// it evicted or replaced, or of it being turned away or already pending.
+/// Returns whether it was accepted.
+async fn submit_pending(
+    state: &mut DependencyGraphs,
+    transaction: Transaction,
+    outputs: Option<Outputs>,
+    replace: bool,
+) -> bool {
+    let transaction_hash = transaction.hash_string();
+    if state.pending.contains(&transaction_hash) || state.scheduled.contains(&transaction_hash) {
+        tracing::warn!("{transaction_hash} is already pending, skipping..");
+        if let Some(scheduler) =
+            get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+        {
+            let message = SchedulerMessage::TransactionAlreadyPending { transaction_hash };
+            scheduler.cast(message).typecast().log_err(|e| {
+                SchedulerError::Custom(format!(
+                    "failed to cast TransactionAlreadyPending to scheduler: {e:?}"
+                ))
+            });
+        }
+        return false;
+    }
+    let sender = transaction.from();
+    let next = if transaction.transaction_type().is_bridge_in() || state.pending.tracks(&sender) {
+        None
+    } else {
+        get_account(sender, ActorType::PendingTransactions)
+            .await
+            .map(|account| account.expected_nonce())
+    };
+    let submitted = state.submit(transaction, outputs, replace, next);
+    // Those evicted to make room are gone whether or not room was made.
+    report_evicted(state);
+    let replaced = match submitted {
+        Ok(replaced) => replaced,
+        Err(e) => {
+            tracing::error!("transaction {transaction_hash} turned away: {e}");
+            if let Some(scheduler) =
+                get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+            {
+                let message = match e {
+                    PendingTransactionError::MempoolFull(full) => SchedulerMessage::MempoolFull {
+                        transaction_hash,
+                        full,
+                    },
+                    e => SchedulerMessage::SendTransactionFailure {
+                        transaction_hash,
+                        error: e.into(),
+                    },
+                };
+                scheduler.cast(message).typecast().log_err(|e| {
+                    SchedulerError::Custom(format!(
+                        "failed to cast SendTransactionFailure to scheduler: {e:?}"
+                    ))
+                });
+            }
+            return false;
+        }
+    };
+    tracing::warn!(
+        "added transaction: {} to dependency graph",
+        transaction_hash
+    );
+    if let Some(replaced) = replaced {
+        state.log_removed(&replaced);
+        if let Some(scheduler) =
+            get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+        {
+            let message = SchedulerMessage::TransactionReplaced {
+                transaction_hash: replaced,
+                replaced_by: transaction_hash,
+            };
+            scheduler.cast(message).typecast().log_err(|e| {
+                SchedulerError::Custom(format!(
+                    "failed to cast TransactionReplaced to scheduler: {e:?}"
+                ))
+            });
+        }
+    }
+    true
+}
+
+/// Holds `transaction` until it is valid, if it is not yet and is not a
+/// bridge in, telling the scheduler it is held or turned away. Returns
+/// `None` for one to be submitted now, and otherwise whether it was held.
+fn hold_scheduled(
+    state: &mut DependencyGraphs,
+    transaction: &Transaction,
+    outputs: &Option<Outputs>,
+    replace: bool,
+) -> Option<bool> {
+    if transaction.transaction_type().is_bridge_in() {
+        return None;
+    }
+    let now = Utc::now().timestamp() as u64;
+    let Validity::NotYet { valid_after } = transaction.validity_at(now) else {
+        return None;
+    };
+    let transaction_hash = transaction.hash_string();
+    let mut held = false;
+    let message = if state.scheduled.contains(&transaction_hash) {
+        tracing::warn!("{transaction_hash} is already held until it is valid, skipping..");
+        SchedulerMessage::TransactionAlreadyPending { transaction_hash }
+    } else {
+        let holding = state.hold_until(transaction, outputs, replace, valid_after, now);
+        report_evicted(state);
+        match holding {
+            Ok(()) => {
+                held = true;
+                SchedulerMessage::TransactionScheduled {
+                    transaction_hash,
+                    valid_after,
+                }
+            }
+            Err(PendingTransactionError::MempoolFull(full)) => {
+                tracing::error!("transaction {transaction_hash} turned away: {full}");
+                SchedulerMessage::MempoolFull {
+                    transaction_hash,
+                    full,
+                }
+            }
+            Err(e) => {
+                tracing::error!("transaction {transaction_hash} turned away: {e}");
+                SchedulerMessage::SendTransactionFailure {
+                    transaction_hash,
+                    error: e.into(),
+                }
+            }
         }
+    };
+    if let Some(scheduler) = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+    {
+        scheduler.cast(message).typecast().log_err(|e| {
+            SchedulerError::Custom(format!(
+                "failed to cast held transaction to scheduler: {e:?}"
+            ))
+        });
     }
+    Some(held)
 }
-impl ActorName for PendingTransactionActor {
-    fn name(&self) -> ractor::ActorName {
-        ActorType::PendingTransactions.to_string()
+
+/// Submits the transactions held until a time that has now come, as if they
+/// had just arrived, calls to be run and everything else to the pending
+/// graph. Returns the hashes of those whose window closed before they were
+/// released, which are evicted instead.
+async fn release_scheduled(state: &mut DependencyGraphs) -> Vec<String> {
+    let now = Utc::now().timestamp() as u64;
+    let mut expired = Vec::new();
+    for held in state.scheduled.take_due(now) {
+        state.pending.uncount(held.transaction.from(), held.bytes);
+        let transaction_hash = held.transaction.hash_string();
+        if matches!(held.transaction.validity_at(now), Validity::Expired { .. }) {
+            tracing::warn!("{transaction_hash} expired before it was released, evicting it");
+            expired.push(transaction_hash);
+        } else if held.transaction.transaction_type().is_call() {
+            tracing::info!("releasing {transaction_hash}, which is now valid");
+            state.add_call(held.transaction);
+        } else {
+            tracing::info!("releasing {transaction_hash}, which is now valid");
+            if !submit_pending(state, held.transaction, held.outputs, held.replace).await {
+                state.log_removed(&transaction_hash);
+            }
+        }
     }
+    expired
 }
 
-#[derive(Debug, Clone, Error, Default)]
-pub enum PendingTransactionError {
-    #[default]
-    #[error("failed to acquire PendingTransactionActor from registry")]
-    RactorRegistryError,
+/// Evicts the transactions held whose window closed before they became
+/// valid, returning their hashes.
+fn expire_scheduled(state: &mut DependencyGraphs) -> Vec<String> {
+    let now = Utc::now().timestamp() as u64;
+    state
+        .scheduled
+        .expire(now)
+        .into_iter()
+        .map(|held| {
+            state.pending.uncount(held.transaction.from(), held.bytes);
+            held.transaction.hash_string()
+        })
+        .collect()
+}
 
-    #[error("{0}")]
-    Custom(String),
+/// Drops the `expired` transactions from the log, and tells the scheduler
+/// they expired.
+fn evict_expired(state: &mut DependencyGraphs, expired: Vec<String>) {
+    for transaction_hash in &expired {
+        state.log_removed(transaction_hash);
+    }
+    if let Some(scheduler) = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+    {
+        for transaction_hash in expired {
+            let message = SchedulerMessage::TransactionExpired { transaction_hash };
+            scheduler.cast(message).typecast().log_err(|e| {
+                SchedulerError::Custom(format!(
+                    "failed to cast TransactionExpired to scheduler: {e:?}"
+                ))
+            });
+        }
+    }
 }
 
 #[async_trait]
@@ -698,7 +2462,35 @@ impl Actor for PendingTransactionActor {
         _myself: ActorRef<Self::Msg>,
         _: (),
     ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(DependencyGraphs::new())
+        let mut graphs = DependencyGraphs::new();
+        graphs.pending = graphs
+            .pending
+            .with_metrics(self.mempool_metrics())
+            .with_limits(self.mempool_limits)
+            .with_ttl(self.config.ttl)
+            .with_max_future(self.config.max_future_transactions)
+            .with_max_in_flight(self.config.max_in_flight)
+            .with_scheduling_policy(self.scheduling.policy(self.fees))
+            .with_fairness(self.scheduling.fairness.clone());
+        graphs.pre_call = graphs.pre_call.with_deadline(self.config.call_deadline);
+        if let Some(path) = &self.config.log_path {
+            graphs = graphs.with_log(path)?;
+        }
+        Ok(graphs)
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        // An executor started before this actor granted its credits to no
+        // one, so it is asked for them again.
+        if let Some(executor) = ractor::registry::where_is(ActorType::Executor.to_string()) {
+            let executor: ActorRef<ExecutorMessage> = executor.into();
+            let _ = executor.cast(ExecutorMessage::GrantCredits);
+        }
+        Ok(())
     }
 
     async fn handle(
@@ -707,38 +2499,54 @@ impl Actor for PendingTransactionActor {
         message: Self::Msg,
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
+        let _handling = mailbox_handling(ActorType::PendingTransactions);
         match message {
+            PendingTransactionMessage::ProbeMailbox(probe) => {
+                mailbox_probed(ActorType::PendingTransactions, probe)
+            }
             PendingTransactionMessage::New {
                 transaction,
                 outputs,
+                replace,
             } => {
+                let _span = lifecycle::stage(LifecycleStage::Pending, &transaction.hash_string());
                 tracing::warn!("received new transction {}", transaction.hash_string());
-                if transaction.transaction_type().is_bridge_in() {
-                    let mut bridge_in_transactions = self.bridge_in_transactions.lock().await;
-                    match bridge_in_transactions.contains(&transaction) {
-                        true => {
-                            tracing::warn!("found duplicate bridge in transaction, skipping..");
-                            return Ok(());
-                        }
-                        false => bridge_in_transactions.push(transaction.clone()),
+                if transaction.transaction_type().is_bridge_in()
+                    && !self.first_bridge_in(&transaction).await
+                {
+                    tracing::warn!("found duplicate bridge in transaction, skipping..");
+                    return Ok(());
+                }
+                if transaction.transaction_type().is_call() && outputs.is_some() {
+                    // Outputs of calls are committed in the order the calls
+                    // were made, however the executor finished them.
+                    state.pre_call.commit(transaction, outputs);
+                    submit_committed(state).await;
+                } else {
+                    let logged = (transaction.clone(), outputs.clone());
+                    let accepted = match hold_scheduled(state, &transaction, &outputs, replace) {
+                        Some(held) => held,
+                        None => submit_pending(state, transaction, outputs, replace).await,
+                    };
+                    if accepted {
+                        state.log_accepted(&logged.0, &logged.1);
                     }
                 }
-                state.add_transaction(transaction.clone(), outputs);
-                tracing::warn!(
-                    "added transaction: {} to dependency graph",
-                    transaction.hash_string()
-                );
             }
             PendingTransactionMessage::NewCall { transaction } => {
+                let _span = lifecycle::stage(LifecycleStage::Pending, &transaction.hash_string());
                 tracing::warn!(
                     "received new call transaction {}",
                     transaction.hash_string()
                 );
-                state.add_call(transaction.clone());
-                tracing::warn!(
-                    "added call transaction: {} to pre-call dependency graph",
-                    transaction.hash_string()
-                );
+                // One not valid yet is run once it is.
+                if hold_scheduled(state, &transaction, &None, false).is_none() {
+                    state.add_call(transaction.clone());
+                    tracing::warn!(
+                        "added call transaction: {} to pre-call dependency graph",
+                        transaction.hash_string()
+                    );
+                }
             }
             PendingTransactionMessage::ExecSuccess { transaction } => {
                 tracing::warn!(
@@ -747,32 +2555,76 @@ impl Actor for PendingTransactionActor {
                 );
                 state.handle_completed_exec(&transaction.hash_string());
             }
+            PendingTransactionMessage::RetryCall {
+                transaction_hash,
+                reason,
+                after,
+            } => match state.retry_call(&transaction_hash) {
+                Some(transaction) => {
+                    tracing::warn!("retrying call {transaction_hash} in {after:?} ({reason})");
+                    let deadline = state.pre_call.start_deadline(&transaction_hash);
+                    tokio::spawn(async move {
+                        tokio::time::sleep(after).await;
+                        if let Err(e) = PreCallGraph::send_to_executor(transaction) {
+                            tracing::error!("unable to retry call {transaction_hash}: {e}");
+                        } else if let Some((dispatch, deadline)) = deadline {
+                            PreCallGraph::expire_after(transaction_hash, dispatch, deadline);
+                        }
+                    });
+                }
+                None => {
+                    tracing::error!("no call {transaction_hash} in the pre-call graph to retry");
+                    fail_missing_call(transaction_hash);
+                }
+            },
+            PendingTransactionMessage::CallFailed { transaction_hash } => {
+                state.handle_failed_call(&transaction_hash);
+                submit_committed(state).await;
+            }
+            PendingTransactionMessage::CallDeadline {
+                transaction_hash,
+                dispatch,
+            } => {
+                if state.pre_call.call_deadline(&transaction_hash, dispatch) {
+                    tokio::spawn(cancel_call(transaction_hash));
+                }
+            }
+            PendingTransactionMessage::CallCancelled {
+                transaction_hash,
+                confirmed,
+            } => {
+                if state.pre_call.call_cancelled(&transaction_hash, confirmed) {
+                    call_timed_out(transaction_hash, !confirmed);
+                }
+            }
+            PendingTransactionMessage::ExecutionCredits { credits } => {
+                state.pre_call.grant_credits(credits);
+            }
+            PendingTransactionMessage::GetExecutionCredits { reply } => {
+                let _ = reply.send(state.pre_call.credit_status());
+            }
             PendingTransactionMessage::Valid { transaction, .. } => {
                 tracing::info!(
                     "received notice transaction is valid: {}",
                     transaction.hash_string()
                 );
                 let get_transactions = state.handle_valid(&transaction.hash_string());
-                let transactions_ready_for_validation = state.get_transactions(get_transactions);
-
-                for (transaction, outputs) in transactions_ready_for_validation {
-                    let _ = state.schedule_with_validator(transaction, outputs);
-                }
+                state.log_removed(&transaction.hash_string());
+                state.schedule_ready(get_transactions);
             }
             PendingTransactionMessage::Invalid { transaction, e } => {
                 tracing::error!("transaction: {} is invalid: {e}", transaction.hash_string());
+                state.log_removed(&transaction.hash_string());
+                lifecycle::finished(&transaction.hash_string());
                 let transactions_ready_for_validation =
                     match state.handle_invalid(&transaction.hash_string(), e) {
-                        Ok(get_transactions) => state.get_transactions(get_transactions),
+                        Ok(get_transactions) => get_transactions,
                         Err(e) => {
                             tracing::error!("Error handling invalid transaction {e}");
                             vec![]
                         }
                     };
-
-                for (transaction, outputs) in transactions_ready_for_validation {
-                    let _ = state.schedule_with_validator(transaction, outputs);
-                }
+                state.schedule_ready(transactions_ready_for_validation);
             }
             PendingTransactionMessage::GetPendingTransaction {
                 transaction_hash: _,
@@ -780,24 +2632,61 @@ impl Actor for PendingTransactionActor {
             } => {
                 tracing::info!("Pending transaction requested");
             }
+            PendingTransactionMessage::GetPendingByAddress { address, reply } => {
+                let _ = reply.send(state.pending_from(&address));
+            }
+            PendingTransactionMessage::GetPendingByHash {
+                transaction_hash,
+                reply,
+            } => {
+                let transaction_hash = format!("0x{}", hex::encode(transaction_hash));
+                let _ = reply.send(state.summary(&transaction_hash));
+            }
             PendingTransactionMessage::ValidCall { transaction, .. } => {
                 let get_transactions = state.handle_valid(&transaction.hash_string());
+                state.log_removed(&transaction.hash_string());
                 tracing::warn!("received valid transactions in pending transaction in graph for transaction: {}", transaction.hash_string());
-                let transactions_ready_for_validation = state.get_transactions(get_transactions);
-
-                for (transaction, outputs) in transactions_ready_for_validation {
-                    tracing::warn!("scheduling: {} with validator", transaction.hash_string());
-                    let _ = state.schedule_with_validator(transaction, outputs);
-                }
+                state.schedule_ready(get_transactions);
             }
             PendingTransactionMessage::CleanGraph => {
                 tracing::warn!("Attempting to clean pending graph");
-                state.clean_pending_graph();
+                let mut expired = state.clean_pending_graph();
+                expired.extend(expire_scheduled(state));
+                expired.extend(release_scheduled(state).await);
+                submit_committed(state).await;
+                evict_expired(state, expired);
+                state.compact_log();
+            }
+            PendingTransactionMessage::ReleaseScheduled => {
+                let expired = release_scheduled(state).await;
+                evict_expired(state, expired);
+            }
+            PendingTransactionMessage::Replay => {
+                let replay = std::mem::take(&mut state.replay);
+                tracing::info!("replaying {} pending transactions", replay.len());
+                for (transaction, outputs) in replay {
+                    if transaction.transaction_type().is_bridge_in()
+                        && !self.first_bridge_in(&transaction).await
+                    {
+                        continue;
+                    }
+                    let transaction_hash = transaction.hash_string();
+                    let accepted = match hold_scheduled(state, &transaction, &outputs, false) {
+                        Some(held) => held,
+                        None => submit_pending(state, transaction, outputs, false).await,
+                    };
+                    if !accepted {
+                        state.log_removed(&transaction_hash);
+                    }
+                }
             }
             PendingTransactionMessage::Confirmed { .. } => {
                 todo!()
             }
+            #[cfg(feature = "crash_test")]
+            PendingTransactionMessage::Panic => panic!("pending transactions told to panic"),
         }
+        state.pending.record_occupancy();
         Ok(())
     }
 }
@@ -811,10 +2700,120 @@ pub async fn graph_cleaner() -> std::io::Result<()> {
             ))?
             .into();
 
+    // Held transactions are released on the shorter period, and the graph
+    // is cleaned, and those held evicted, on the longer.
+    let mut release = tokio::time::interval(SCHEDULED_RELEASE_INTERVAL);
+    let mut clean = tokio::time::interval(Duration::from_millis(PENDING_TIMEOUT));
+    clean.reset();
     loop {
-        tokio::time::sleep(Duration::from_millis(PENDING_TIMEOUT)).await;
-        let message = PendingTransactionMessage::CleanGraph;
-        let _ = pt_actor.clone().cast(message);
+        let message = tokio::select! {
+            _ = release.tick() => PendingTransactionMessage::ReleaseScheduled,
+            _ = clean.tick() => PendingTransactionMessage::CleanGraph,
+        };
+        let _ = pt_actor.cast(message);
+    }
+}
+
+/// Submits the outputs of the calls whose turn to commit has come, in the
+/// order the calls were made.
+async fn submit_committed(state: &mut DependencyGraphs) {
+    for (transaction, outputs) in state.pre_call.take_committed() {
+        let logged = (transaction.clone(), outputs.clone());
+        if submit_pending(state, transaction, outputs, false).await {
+            state.log_accepted(&logged.0, &logged.1);
+        }
+    }
+}
+
+/// Asks the executor to cancel a call past its deadline, and hands its
+/// answer back to the pending transactions. An executor that does not answer
+/// in time is taken not to have stopped the call.
+async fn cancel_call(transaction_hash: String) {
+    let confirmed =
+        match get_actor_ref::<ExecutorMessage, PendingTransactionError>(ActorType::Executor) {
+            Some(executor) => matches!(
+                executor
+                    .call(
+                        |reply| ExecutorMessage::Cancel {
+                            transaction_hash: transaction_hash.clone(),
+                            reply,
+                        },
+                        Some(CANCEL_TIMEOUT),
+                    )
+                    .await,
+                Ok(CallResult::Success(true))
+            ),
+            None => false,
+        };
+    if let Some(pending) = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+        ActorType::PendingTransactions,
+    ) {
+        pending
+            .cast(PendingTransactionMessage::CallCancelled {
+                transaction_hash,
+                confirmed,
+            })
+            .typecast()
+            .log_err(|e| {
+                PendingTransactionError::Custom(format!(
+                    "failed to cast CallCancelled to pending transactions: {e:?}"
+                ))
+            });
+    }
+}
+
+/// Tells the scheduler a call ran past its deadline and was cancelled, its
+/// outputs possibly `committed` unless the executor confirmed otherwise.
+fn call_timed_out(transaction_hash: String, committed: bool) {
+    if let Some(scheduler) = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+    {
+        scheduler
+            .cast(SchedulerMessage::CallTimedOut {
+                transaction_hash,
+                committed,
+            })
+            .typecast()
+            .log_err(|e| {
+                PendingTransactionError::Custom(format!(
+                    "failed to cast CallTimedOut to scheduler: {e:?}"
+                ))
+            });
+    }
+}
+
+/// Answers a call asked to be retried that is no longer in the graph.
+fn fail_missing_call(transaction_hash: String) {
+    if let Some(scheduler) = get_actor_ref::<SchedulerMessage, SchedulerError>(ActorType::Scheduler)
+    {
+        scheduler
+            .cast(SchedulerMessage::CallTransactionFailure {
+                transaction_hash,
+                outputs: String::new(),
+                error: "call to retry is no longer pending".to_string(),
+            })
+            .typecast()
+            .log_err(|e| {
+                PendingTransactionError::Custom(format!(
+                    "failed to cast CallTransactionFailure to scheduler: {e:?}"
+                ))
+            });
+    }
+}
+
+/// Has the pending transactions actor replay the transactions it read back
+/// from its log, once the actors they are handed on to are up.
+pub fn replay_pending_log() {
+    if let Some(actor) = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+        ActorType::PendingTransactions,
+    ) {
+        actor
+            .cast(PendingTransactionMessage::Replay)
+            .typecast()
+            .log_err(|e| {
+                PendingTransactionError::Custom(format!(
+                    "failed to cast Replay to pending transactions: {e:?}"
+                ))
+            });
     }
 }
 
@@ -884,3 +2883,914 @@ impl Actor for PendingTransactionSupervisor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod pending_transactions_tests {
+    use super::*;
+    use crate::test_fixtures::transfer;
+    use crate::{FeeSchedule, Page, Paginator, PriorityPolicy, MAX_PAGE_SIZE};
+    use lasr_types::TransactionBuilder;
+
+    fn send(nonce: u64, value: u64) -> Transaction {
+        transfer(1, 2, nonce, value)
+    }
+
+    /// Submits `transaction`, holding the transactions after it from the
+    /// same sender back until it is validated.
+    fn submit_blocking(graph: &mut PendingGraph, transaction: Transaction) {
+        let transaction_hash = transaction.hash_string();
+        let sender = transaction.from();
+        graph.submit(transaction, None, false, None).unwrap();
+        graph
+            .account_index
+            .entry(sender)
+            .or_default()
+            .push_back(transaction_hash);
+    }
+
+    #[test]
+    fn a_waiting_transaction_is_replaced() {
+        let mut graph = PendingGraph::new();
+        let blocking = send(0, 1);
+        submit_blocking(&mut graph, blocking.clone());
+        let waiting = send(1, 1);
+        assert_eq!(
+            graph.submit(waiting.clone(), None, false, None).unwrap(),
+            None
+        );
+        assert!(!graph.is_scheduled(&waiting.hash_string()));
+
+        let replacement = send(1, 2);
+        assert_eq!(
+            graph.submit(replacement.clone(), None, true, None).unwrap(),
+            Some(waiting.hash_string())
+        );
+        assert!(!graph.vertices.contains_key(&waiting.hash_string()));
+        assert!(!graph.is_scheduled(&replacement.hash_string()));
+        let dependents = graph.vertices[&blocking.hash_string()]
+            .read()
+            .unwrap()
+            .dependent_transactions
+            .clone();
+        assert_eq!(dependents, vec![replacement.hash_string()]);
+    }
+
+    #[test]
+    fn a_transaction_being_validated_is_not_replaced() {
+        let mut graph = PendingGraph::new();
+        let validating = send(0, 1);
+        graph.submit(validating.clone(), None, false, None).unwrap();
+        assert!(matches!(
+            graph.submit(send(0, 2), None, true, None),
+            Err(PendingTransactionError::AlreadyScheduled(pending)) if pending == validating.hash_string()
+        ));
+
+        graph.handle_valid(&validating.hash_string());
+        assert!(matches!(
+            graph.submit(send(0, 2), None, true, None),
+            Err(PendingTransactionError::NothingToReplace { .. })
+        ));
+    }
+
+    #[test]
+    fn submissions_of_a_pending_nonce_are_not_added_twice() {
+        let mut graph = PendingGraph::new();
+        let blocking = send(0, 1);
+        submit_blocking(&mut graph, blocking.clone());
+        let waiting = send(1, 1);
+        graph.submit(waiting.clone(), None, false, None).unwrap();
+        assert_eq!(
+            graph.submit(waiting.clone(), None, false, None).unwrap(),
+            None
+        );
+        assert_eq!(graph.vertices.len(), 2);
+        assert!(matches!(
+            graph.submit(send(1, 2), None, false, None),
+            Err(PendingTransactionError::NoncePending { pending, .. }) if pending == waiting.hash_string()
+        ));
+        assert!(graph.contains(&waiting.hash_string()));
+        assert!(!graph.contains(&send(1, 2).hash_string()));
+
+        // Replacements submitted together each replace the one before.
+        let first = send(1, 3);
+        let second = send(1, 4);
+        assert_eq!(
+            graph.submit(first.clone(), None, true, None).unwrap(),
+            Some(waiting.hash_string())
+        );
+        assert_eq!(
+            graph.submit(second.clone(), None, true, None).unwrap(),
+            Some(first.hash_string())
+        );
+        assert_eq!(graph.vertices.len(), 2);
+        assert!(graph.vertices.contains_key(&second.hash_string()));
+    }
+
+    /// Backdates `transaction`, pending or held, to when the graph began.
+    fn age(graph: &mut PendingGraph, transaction: &Transaction) {
+        if let Some(vertex) = graph.vertices.get(&transaction.hash_string()) {
+            vertex.write().unwrap().timestamp = 0;
+        }
+        if let Some(held) = graph
+            .senders
+            .get_mut(&transaction.from())
+            .and_then(|queue| queue.held.get_mut(&transaction.nonce()))
+        {
+            held.timestamp = 0;
+        }
+    }
+
+    fn held(graph: &PendingGraph, transaction: &Transaction) -> bool {
+        graph.senders[&transaction.from()]
+            .held
+            .get(&transaction.nonce())
+            .is_some_and(|held| held.transaction == *transaction)
+    }
+
+    #[test]
+    fn a_transaction_stuck_behind_a_nonce_gap_expires() {
+        let mut graph = PendingGraph::new().with_ttl(Duration::from_secs(60));
+        let blocking = send(0, 1);
+        submit_blocking(&mut graph, blocking.clone());
+        let gapped = send(2, 1);
+        let behind = send(3, 1);
+        graph.submit(gapped.clone(), None, false, None).unwrap();
+        graph.submit(behind.clone(), None, false, None).unwrap();
+        assert!(graph.clean_graph().is_empty());
+
+        age(&mut graph, &gapped);
+        assert_eq!(graph.clean_graph(), vec![gapped.hash_string()]);
+        assert!(!held(&graph, &gapped));
+        assert!(held(&graph, &behind));
+        assert!(!graph.vertices.contains_key(&behind.hash_string()));
+        // Its nonce is free to be sent again.
+        assert_eq!(graph.submit(send(2, 2), None, false, None).unwrap(), None);
+    }
+
+    /// A send from the sender of `send`, valid from `valid_after` and before
+    /// `valid_before`.
+    fn send_within(nonce: u64, valid_after: Option<u64>, valid_before: Option<u64>) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(nonce))
+            .valid_after(valid_after)
+            .valid_before(valid_before)
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn a_transaction_whose_window_closes_while_it_waits_expires() {
+        let mut graph = PendingGraph::new().with_ttl(Duration::from_secs(60));
+        let now = Utc::now().timestamp() as u64;
+        let blocking = send(0, 1);
+        submit_blocking(&mut graph, blocking.clone());
+        let closing = send_within(2, None, Some(now + 60));
+        let closed = send_within(3, None, Some(now));
+        graph.submit(closing.clone(), None, false, None).unwrap();
+        graph.submit(closed.clone(), None, false, None).unwrap();
+
+        assert_eq!(graph.clean_graph(), vec![closed.hash_string()]);
+        assert!(held(&graph, &closing));
+        assert!(!held(&graph, &closed));
+    }
+
+    #[test]
+    fn scheduled_transactions_are_released_in_the_order_they_become_valid() {
+        let mut queue = ScheduledQueue::default();
+        let first = send_within(1, Some(100), None);
+        let second = send_within(1, Some(200), Some(300));
+        let third = send_within(2, Some(150), Some(160));
+        for transaction in [&second, &first, &third] {
+            let valid_after = transaction.valid_after().unwrap();
+            queue.hold(transaction.clone(), None, false, valid_after, 1);
+        }
+        assert!(queue.contains(&first.hash_string()));
+
+        assert!(queue.take_due(99).is_empty());
+        let due: Vec<Transaction> = queue
+            .take_due(150)
+            .into_iter()
+            .map(|held| held.transaction)
+            .collect();
+        assert_eq!(due, vec![first.clone(), third.clone()]);
+        assert!(!queue.contains(&first.hash_string()));
+
+        // One whose window closes before it is valid is evicted.
+        assert!(queue.expire(299).is_empty());
+        let expired: Vec<Transaction> = queue
+            .expire(300)
+            .into_iter()
+            .map(|held| held.transaction)
+            .collect();
+        assert_eq!(expired, vec![second]);
+        assert!(queue.take_due(u64::MAX - 1).is_empty());
+        assert!(queue.per_sender.is_empty());
+    }
+
+    #[test]
+    fn a_sender_holds_no_more_scheduled_transactions_than_the_cap() {
+        let mut queue = ScheduledQueue::default();
+        for nonce in 0..2 {
+            let transaction = send_within(nonce, Some(100), None);
+            queue.check_hold(&transaction, 100, 0, 2).unwrap();
+            queue.hold(transaction, None, false, 100, 1);
+        }
+        assert!(matches!(
+            queue.check_hold(&send_within(2, Some(100), None), 100, 0, 2),
+            Err(PendingTransactionError::TooManyScheduled { from, max_scheduled: 2 })
+                if from == Address::new([1; 20])
+        ));
+        // Another sender is not held to the first's.
+        let other = TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from([3; 20])
+            .to([2; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(0))
+            .valid_after(Some(100))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap();
+        assert!(queue.check_hold(&other, 100, 0, 2).is_ok());
+
+        // Released, the first sender may hold more again.
+        queue.take_due(100);
+        assert!(queue
+            .check_hold(&send_within(2, Some(200), None), 200, 100, 2)
+            .is_ok());
+    }
+
+    #[test]
+    fn a_transaction_is_held_no_further_ahead_than_the_cap() {
+        let queue = ScheduledQueue::default().with_max_ahead(Duration::from_secs(60));
+        let transaction = send_within(0, Some(160), None);
+        assert!(queue.check_hold(&transaction, 160, 100, 16).is_ok());
+        assert!(matches!(
+            queue.check_hold(&transaction, 161, 100, 16),
+            Err(PendingTransactionError::TooFarAhead {
+                valid_after: 161,
+                latest: 160,
+            })
+        ));
+    }
+
+    #[test]
+    fn held_transactions_take_up_room_in_the_pending_pool() {
+        let limits = MempoolLimits {
+            max_transactions: 2,
+            max_bytes: u64::MAX,
+            sender_quota_transactions: 1,
+            sender_quota_bytes: u64::MAX,
+        };
+        let mut graphs = DependencyGraphs::new();
+        graphs.pending = PendingGraph::new().with_limits(limits);
+        let now = Utc::now().timestamp() as u64;
+        let later = now + 60;
+        let first = send_within(0, Some(later), None);
+        graphs.hold_until(&first, &None, false, later, now).unwrap();
+        assert_eq!(graphs.pending.occupancy.transactions, 1);
+        let other = transfer(3, 4, 0, 1);
+        graphs.hold_until(&other, &None, false, later, now).unwrap();
+
+        // The pool is full of those held, none of which can be evicted.
+        assert!(matches!(
+            graphs.hold_until(&send_within(1, Some(later), None), &None, false, later, now),
+            Err(PendingTransactionError::MempoolFull(MempoolFull {
+                transactions: 2,
+                ..
+            }))
+        ));
+        assert!(!graphs
+            .scheduled
+            .contains(&send_within(1, Some(later), None).hash_string()));
+
+        // Released, a held transaction is counted once it is pending instead.
+        for held in graphs.scheduled.take_due(later) {
+            graphs.pending.uncount(held.transaction.from(), held.bytes);
+        }
+        assert_eq!(graphs.pending.occupancy.transactions, 0);
+        assert!(graphs.pending.sender_occupancy.is_empty());
+    }
+
+    #[test]
+    fn a_transaction_being_validated_is_pinned_until_its_outcome() {
+        let mut graph = PendingGraph::new().with_ttl(Duration::from_secs(60));
+        let validating = send(0, 1);
+        graph.submit(validating.clone(), None, false, None).unwrap();
+        age(&mut graph, &validating);
+        assert!(graph.clean_graph().is_empty());
+        assert!(graph.vertices.contains_key(&validating.hash_string()));
+
+        graph.handle_valid(&validating.hash_string());
+        assert!(graph.vertices.is_empty());
+    }
+
+    #[test]
+    fn nonces_submitted_out_of_order_are_released_in_order() {
+        let mut graph = PendingGraph::new();
+        let next = Some(U256::from(0));
+        for nonce in [3, 1, 5] {
+            graph.submit(send(nonce, 1), None, false, next).unwrap();
+        }
+        assert!(graph.vertices.is_empty());
+        assert_eq!(graph.senders[&Address::from([1; 20])].held.len(), 3);
+
+        // Nonce 0 releases the run up to the gap at nonce 2.
+        graph.submit(send(0, 1), None, false, next).unwrap();
+        graph.submit(send(4, 1), None, false, next).unwrap();
+        assert_eq!(graph.vertices.len(), 2);
+        for nonce in [0, 1] {
+            assert!(graph.vertices.contains_key(&send(nonce, 1).hash_string()));
+        }
+        for nonce in [3, 4, 5] {
+            assert!(held(&graph, &send(nonce, 1)));
+        }
+        assert_eq!(graph.senders[&Address::from([1; 20])].next, U256::from(2));
+
+        graph.submit(send(2, 1), None, false, next).unwrap();
+        assert_eq!(graph.vertices.len(), 6);
+        let queue = &graph.senders[&Address::from([1; 20])];
+        assert!(queue.held.is_empty());
+        assert_eq!(queue.next, U256::from(6));
+    }
+
+    #[test]
+    fn futures_past_the_cap_and_stale_nonces_are_turned_away() {
+        let mut graph = PendingGraph::new();
+        graph.max_future = 2;
+        let next = Some(U256::from(4));
+        for nonce in [5, 6] {
+            graph.submit(send(nonce, 1), None, false, next).unwrap();
+        }
+        // Resubmitting one held already does not count against the cap.
+        assert_eq!(graph.submit(send(6, 1), None, false, next).unwrap(), None);
+        assert!(graph.contains(&send(6, 1).hash_string()));
+        assert!(matches!(
+            graph.submit(send(7, 1), None, false, next),
+            Err(PendingTransactionError::TooManyFuture { max_future: 2, .. })
+        ));
+        assert!(matches!(
+            graph.submit(send(3, 1), None, false, next),
+            Err(PendingTransactionError::NonceTooLow { .. })
+        ));
+
+        graph.submit(send(4, 1), None, false, next).unwrap();
+        assert_eq!(graph.vertices.len(), 3);
+        assert!(graph.submit(send(7, 1), None, false, next).is_ok());
+    }
+
+    #[test]
+    fn a_second_sender_gets_into_a_pool_flooded_by_the_first() {
+        let limits = MempoolLimits {
+            max_transactions: 8,
+            max_bytes: u64::MAX,
+            sender_quota_transactions: 2,
+            sender_quota_bytes: u64::MAX,
+        };
+        let mut graph = PendingGraph::new().with_limits(limits);
+        submit_blocking(&mut graph, send(0, 1));
+        for nonce in 1..8 {
+            graph.submit(send(nonce, 1), None, false, None).unwrap();
+        }
+        assert!(matches!(
+            graph.submit(send(8, 1), None, false, None),
+            Err(PendingTransactionError::MempoolFull(MempoolFull {
+                transactions: 8,
+                over_quota: true,
+                ..
+            }))
+        ));
+
+        // Each from the second sender evicts the flooding sender's latest.
+        graph
+            .submit(transfer(3, 4, 0, 1), None, false, None)
+            .unwrap();
+        graph
+            .submit(transfer(3, 4, 1, 1), None, false, None)
+            .unwrap();
+        assert_eq!(
+            graph.take_evicted(),
+            vec![send(7, 1).hash_string(), send(6, 1).hash_string()]
+        );
+        assert!(!graph.vertices.contains_key(&send(7, 1).hash_string()));
+        assert_eq!(graph.senders[&Address::from([1; 20])].next, U256::from(6));
+        // Now over their own quota, the second sender is turned away too.
+        assert!(matches!(
+            graph.submit(transfer(3, 4, 2, 1), None, false, None),
+            Err(PendingTransactionError::MempoolFull(MempoolFull {
+                over_quota: true,
+                ..
+            }))
+        ));
+
+        // A replacement takes the place of the one it replaces.
+        assert_eq!(
+            graph.submit(send(5, 2), None, true, None).unwrap(),
+            Some(send(5, 1).hash_string())
+        );
+        assert!(graph.take_evicted().is_empty());
+
+        graph.record_occupancy();
+        let metrics = graph.metrics();
+        assert_eq!(metrics.transactions(), 8);
+        assert_eq!((metrics.evicted(), metrics.rejected()), (2, 2));
+    }
+
+    /// What the pool holds in all and from each sender, counted afresh.
+    fn recount(graph: &PendingGraph) -> (Occupancy, HashMap<Address, Occupancy>) {
+        let mut pool = Occupancy::default();
+        let mut senders: HashMap<Address, Occupancy> = HashMap::new();
+        let pending = graph.vertices.values().filter_map(|vertex| {
+            let guard = vertex.read().unwrap();
+            (!guard.transaction.transaction_type().is_bridge_in())
+                .then(|| (guard.transaction.from(), guard.bytes))
+        });
+        let held = graph
+            .senders
+            .iter()
+            .flat_map(|(sender, queue)| queue.held.values().map(|held| (*sender, held.bytes)));
+        for (sender, bytes) in pending.chain(held).collect::<Vec<_>>() {
+            pool.add(bytes);
+            senders.entry(sender).or_default().add(bytes);
+        }
+        (pool, senders)
+    }
+
+    #[test]
+    fn the_pool_keeps_count_of_what_comes_and_goes() {
+        let limits = MempoolLimits {
+            max_transactions: 5,
+            max_bytes: u64::MAX,
+            sender_quota_transactions: 2,
+            sender_quota_bytes: u64::MAX,
+        };
+        let mut graph = PendingGraph::new().with_limits(limits);
+        let counted = |graph: &PendingGraph| {
+            assert_eq!(
+                recount(graph),
+                (graph.occupancy, graph.sender_occupancy.clone())
+            );
+            let scheduled = graph
+                .vertices
+                .values()
+                .filter(|vertex| vertex.read().unwrap().scheduled)
+                .count();
+            assert_eq!(graph.in_flight, scheduled);
+        };
+        submit_blocking(&mut graph, send(0, 1));
+        graph.submit(send(1, 1), None, false, None).unwrap();
+        graph.submit(send(3, 1), None, false, None).unwrap();
+        graph.submit(send(3, 2), None, true, None).unwrap();
+        graph.submit(send(4, 1), None, false, None).unwrap();
+        counted(&graph);
+        assert_eq!(graph.occupancy.transactions, 4);
+
+        // The second sender evicts the first's latest, held ones first.
+        graph
+            .submit(transfer(3, 4, 0, 1), None, false, None)
+            .unwrap();
+        graph
+            .submit(transfer(3, 4, 1, 1), None, false, None)
+            .unwrap();
+        counted(&graph);
+        assert_eq!(graph.take_evicted(), vec![send(4, 1).hash_string()]);
+        assert!(!graph.contains(&send(4, 1).hash_string()));
+
+        graph.handle_valid(&send(0, 1).hash_string());
+        counted(&graph);
+        // Filling the gap moves what was held into the graph without
+        // counting it twice.
+        graph.submit(send(2, 1), None, false, None).unwrap();
+        counted(&graph);
+        assert!(graph.held_at.is_empty());
+        assert_eq!(graph.occupancy.transactions, 5);
+        let invalid = LasrError::Custom("invalid".to_string());
+        let _ = graph.handle_invalid(&send(1, 1).hash_string(), invalid);
+        counted(&graph);
+        assert_eq!(graph.occupancy.transactions, 4);
+    }
+
+    #[test]
+    fn a_transaction_behind_a_gap_reports_the_nonce_it_waits_on() {
+        let mut graph = PendingGraph::new();
+        let blocking = send(0, 1);
+        submit_blocking(&mut graph, blocking.clone());
+        let gapped = send(2, 1);
+        graph.submit(gapped.clone(), None, false, None).unwrap();
+        graph.submit(send(3, 1), None, false, None).unwrap();
+
+        let summary = graph.summary(&gapped.hash_string()).unwrap();
+        assert_eq!(summary.nonce, U256::from(2));
+        assert_eq!(
+            summary.status,
+            PendingStatus::Queued {
+                waiting_on: U256::from(1)
+            }
+        );
+        assert_eq!(
+            graph.summary(&blocking.hash_string()).unwrap().status,
+            PendingStatus::Validating
+        );
+        assert!(graph.summary(&send(1, 1).hash_string()).is_none());
+
+        // Once the gap fills, it waits on the transactions before it instead.
+        graph.submit(send(1, 1), None, false, None).unwrap();
+        assert_eq!(
+            graph.summary(&gapped.hash_string()).unwrap().status,
+            PendingStatus::Pending
+        );
+    }
+
+    /// A page of `pending` as `getPendingTransactions` answers it.
+    fn page(
+        pending: Vec<PendingTransactionSummary>,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Page<PendingTransactionSummary> {
+        Paginator::default()
+            .page(
+                pending,
+                |summary| (summary.nonce, summary.hash.clone()),
+                cursor,
+                Some(limit),
+            )
+            .unwrap()
+    }
+
+    fn nonces(page: &Page<PendingTransactionSummary>) -> Vec<u64> {
+        page.items
+            .iter()
+            .map(|summary| summary.nonce.as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn pending_transactions_are_paged_in_nonce_order() {
+        let mut graph = PendingGraph::new();
+        submit_blocking(&mut graph, send(0, 1));
+        for nonce in [3, 2] {
+            graph.submit(send(nonce, 1), None, false, None).unwrap();
+        }
+        graph
+            .submit(transfer(3, 4, 0, 1), None, false, None)
+            .unwrap();
+
+        let sender = Address::from([1; 20]);
+        let first = page(graph.pending_from(&sender), None, 2);
+        assert_eq!(nonces(&first), vec![0, 2]);
+        assert_eq!(first.total, 3);
+        let last = page(graph.pending_from(&sender), first.next_cursor.as_deref(), 2);
+        assert_eq!(nonces(&last), vec![3]);
+        assert_eq!(last.next_cursor, None);
+
+        let capped = page(graph.pending_from(&sender), None, usize::MAX);
+        assert_eq!(capped.items.len(), 3);
+    }
+
+    #[test]
+    fn a_large_pool_is_paged_through_and_resumed_from_a_cursor() {
+        let limits = MempoolLimits {
+            max_transactions: u64::MAX,
+            max_bytes: u64::MAX,
+            sender_quota_transactions: u64::MAX,
+            sender_quota_bytes: u64::MAX,
+        };
+        let mut graph = PendingGraph::new().with_limits(limits);
+        for nonce in 0..10_000 {
+            graph.submit(send(nonce, 1), None, false, None).unwrap();
+        }
+        let sender = Address::from([1; 20]);
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        let mut resume_from = None;
+        loop {
+            let next = page(
+                graph.pending_from(&sender),
+                cursor.as_deref(),
+                MAX_PAGE_SIZE,
+            );
+            assert_eq!(next.total, 10_000);
+            assert!(!next.truncated);
+            seen.extend(nonces(&next));
+            cursor = next.next_cursor;
+            if seen.len() == 300 {
+                resume_from = cursor.clone();
+            }
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen, (0..10_000).collect::<Vec<_>>());
+
+        // Transactions arriving after the cursor was handed out are found
+        // further on, without those before it coming round again.
+        for nonce in 10_000..10_005 {
+            graph.submit(send(nonce, 1), None, false, None).unwrap();
+        }
+        let mut resumed = Vec::new();
+        let mut cursor = resume_from;
+        while let Some(after) = cursor {
+            let next = page(graph.pending_from(&sender), Some(&after), MAX_PAGE_SIZE);
+            resumed.extend(nonces(&next));
+            cursor = next.next_cursor;
+        }
+        assert_eq!(resumed, (300..10_005).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn calls_after_one_being_retried_are_held_until_it_completes() {
+        let mut graph = PreCallGraph::new();
+        let first = send(0, 1);
+        graph.add_call(first.clone());
+        assert_eq!(graph.retry_call(&first.hash_string()), Some(first.clone()));
+
+        graph.add_call(send(2, 1));
+        graph.add_call(send(1, 1));
+        graph.add_call(transfer(3, 2, 0, 1));
+        assert_eq!(graph.vertices.len(), 2);
+        let held: Vec<U256> = graph.retrying[&first.from()]
+            .held
+            .iter()
+            .map(|transaction| transaction.nonce())
+            .collect();
+        assert_eq!(held, vec![U256::from(2), U256::from(1)]);
+
+        graph.handle_completed_exec(&first.hash_string()).unwrap();
+        assert!(graph.retrying.is_empty());
+        assert!(!graph.vertices.contains_key(&first.hash_string()));
+        assert!(graph.vertices.contains_key(&send(1, 1).hash_string()));
+        assert!(graph.vertices.contains_key(&send(2, 1).hash_string()));
+
+        // A call failed before it reached the graph has nothing to release.
+        graph.handle_failed_call(&send(9, 1).hash_string()).unwrap();
+        assert_eq!(graph.vertices.len(), 3);
+    }
+
+    #[test]
+    fn calls_wait_for_an_execution_credit() {
+        let mut graph = PreCallGraph::new();
+        // Each to a program of its own, so none waits on another.
+        let call = |from| transfer(from, 50 + from, 0, 1);
+        let status = |graph: &PreCallGraph| {
+            let status = graph.credit_status();
+            (status.in_flight, status.waiting)
+        };
+        graph.grant_credits(2);
+        for from in 1..=4 {
+            graph.add_call(call(from));
+        }
+        assert_eq!(status(&graph), (2, 2));
+
+        graph.handle_completed_exec(&call(1).hash_string()).unwrap();
+        assert_eq!(status(&graph), (2, 1));
+        // Nothing is given back for a call that held no credit.
+        graph.handle_failed_call(&call(9).hash_string()).unwrap();
+        assert_eq!(status(&graph), (2, 1));
+
+        // A restarted executor's grant voids the credits held by the calls
+        // lost with it.
+        graph.grant_credits(2);
+        assert_eq!(status(&graph), (1, 0));
+
+        graph.add_call(call(5));
+        graph.add_call(call(6));
+        assert_eq!(status(&graph), (2, 1));
+        graph.waiting[0].1 = 0;
+        assert_eq!(
+            graph.expire_waiting(Duration::from_secs(60)),
+            vec![call(6).hash_string()]
+        );
+        assert_eq!(status(&graph), (2, 0));
+        assert!(!graph.vertices.contains_key(&call(6).hash_string()));
+    }
+
+    #[test]
+    fn only_calls_that_conflict_wait_for_each_other() {
+        let mut graph = PreCallGraph::new();
+        graph.grant_credits(8);
+        let in_flight = |graph: &PreCallGraph| graph.credit_status().in_flight;
+        // `b` shares only the token `a` reads, `c` is made by the same
+        // sender as `a`, and `d` calls the account `a` and `c` write.
+        let a = transfer(1, 50, 0, 1);
+        let b = transfer(2, 70, 0, 1);
+        let c = transfer(1, 60, 1, 1);
+        let d = transfer(3, 1, 0, 1);
+        for call in [&a, &b, &c, &d] {
+            graph.add_call(call.clone());
+        }
+        assert_eq!(in_flight(&graph), 2);
+        assert_eq!(
+            graph.vertices[&d.hash_string()].read().unwrap().waiting_on,
+            HashSet::from([a.hash_string(), c.hash_string()])
+        );
+
+        // Each waits for its own predecessors, not for every call before it.
+        graph.handle_completed_exec(&a.hash_string()).unwrap();
+        assert!(graph
+            .credits
+            .as_ref()
+            .unwrap()
+            .in_flight
+            .contains(&c.hash_string()));
+        assert_eq!(in_flight(&graph), 2);
+        graph.handle_completed_exec(&b.hash_string()).unwrap();
+        assert_eq!(in_flight(&graph), 1);
+        graph.handle_completed_exec(&c.hash_string()).unwrap();
+        assert!(graph
+            .credits
+            .as_ref()
+            .unwrap()
+            .in_flight
+            .contains(&d.hash_string()));
+        graph.handle_completed_exec(&d.hash_string()).unwrap();
+        assert_eq!(in_flight(&graph), 0);
+        assert!(graph.vertices.is_empty());
+    }
+
+    #[test]
+    fn calls_of_two_senders_to_one_program_run_one_after_the_other() {
+        let mut graph = PreCallGraph::new();
+        graph.grant_credits(8);
+        // Both bump the counter the program at `[50; 20]` keeps, so the
+        // second must run on what the first left of it.
+        let first = transfer(1, 50, 0, 1);
+        let second = transfer(2, 50, 0, 1);
+        graph.add_call(first.clone());
+        graph.add_call(second.clone());
+        let in_flight = |graph: &PreCallGraph| graph.credits.as_ref().unwrap().in_flight.clone();
+        assert_eq!(in_flight(&graph), HashSet::from([first.hash_string()]));
+        assert_eq!(
+            graph.vertices[&second.hash_string()]
+                .read()
+                .unwrap()
+                .waiting_on,
+            HashSet::from([first.hash_string()])
+        );
+
+        graph.handle_completed_exec(&first.hash_string()).unwrap();
+        assert_eq!(in_flight(&graph), HashSet::from([second.hash_string()]));
+    }
+
+    #[test]
+    fn outputs_commit_in_the_order_calls_were_made() {
+        let mut graph = PreCallGraph::new();
+        let calls: Vec<Transaction> = (1..=5)
+            .map(|from| transfer(from, 50 + from, 0, 1))
+            .collect();
+        for call in &calls {
+            graph.add_call(call.clone());
+        }
+        let committed = |graph: &mut PreCallGraph| -> Vec<String> {
+            graph
+                .take_committed()
+                .into_iter()
+                .map(|(transaction, _)| transaction.hash_string())
+                .collect()
+        };
+
+        // Finished last to first, the calls still commit first to last.
+        for call in calls[..3].iter().rev() {
+            graph.handle_completed_exec(&call.hash_string()).unwrap();
+            graph.commit(call.clone(), None);
+        }
+        assert_eq!(
+            committed(&mut graph),
+            calls[..3]
+                .iter()
+                .map(|call| call.hash_string())
+                .collect::<Vec<_>>()
+        );
+
+        // A call that failed holds none of those after it back.
+        graph.commit(calls[4].clone(), None);
+        assert!(committed(&mut graph).is_empty());
+        graph.handle_failed_call(&calls[3].hash_string()).unwrap();
+        assert_eq!(committed(&mut graph), vec![calls[4].hash_string()]);
+
+        // Nor does a call not made through the graph wait its turn.
+        graph.add_call(transfer(6, 50, 0, 1));
+        graph.commit(transfer(7, 50, 0, 1), None);
+        assert_eq!(
+            committed(&mut graph),
+            vec![transfer(7, 50, 0, 1).hash_string()]
+        );
+    }
+
+    #[test]
+    fn outputs_that_never_arrive_stop_holding_back_later_calls() {
+        let mut graph = PreCallGraph::new();
+        let lost = transfer(1, 50, 0, 1);
+        let later = transfer(2, 60, 0, 1);
+        graph.add_call(lost.clone());
+        graph.add_call(later.clone());
+        graph.handle_completed_exec(&lost.hash_string()).unwrap();
+        graph.handle_completed_exec(&later.hash_string()).unwrap();
+        graph.commit(later.clone(), None);
+        assert!(graph.take_committed().is_empty());
+
+        // Not yet waited on long enough.
+        assert!(graph.expire_commits(Duration::from_secs(60)).is_empty());
+        graph.commits.first_entry().unwrap().get_mut().executed_at = Some(0);
+        assert_eq!(
+            graph.expire_commits(Duration::from_secs(60)),
+            vec![lost.hash_string()]
+        );
+        assert_eq!(graph.take_committed().len(), 1);
+        assert!(graph.commits.is_empty());
+    }
+
+    fn paying(from: u8, nonce: u64, priority_fee: u64) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from([from; 20])
+            .to([from + 100; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(nonce))
+            .max_fee(Some(U256::from(priority_fee)))
+            .priority_fee(Some(U256::from(priority_fee)))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn ready_transactions_wait_for_room_with_the_validator_by_fee() {
+        let mut graph = PendingGraph::new()
+            .with_scheduling_policy(Arc::new(PriorityPolicy::new(FeeSchedule::default(), 0)))
+            .with_max_in_flight(1);
+        let first = paying(1, 0, 0);
+        graph.submit(first.clone(), None, false, None).unwrap();
+        let (low, high, free) = (paying(2, 0, 10), paying(3, 0, 100), paying(4, 0, 0));
+        for transaction in [&low, &high, &free] {
+            graph
+                .submit(transaction.clone(), None, false, None)
+                .unwrap();
+        }
+        assert!(graph.is_scheduled(&first.hash_string()));
+        assert!(!graph.is_scheduled(&high.hash_string()));
+        assert_eq!(graph.ready.len(), 3);
+
+        // Each transaction leaving the validator makes room for the one
+        // paying the most of those waiting.
+        let mut order = Vec::new();
+        for leaving in [first, high.clone(), low.clone()] {
+            let released = graph.handle_valid(&leaving.hash_string());
+            order.extend(graph.schedule_ready(released));
+        }
+        assert_eq!(
+            order,
+            vec![high.hash_string(), low.hash_string(), free.hash_string()]
+        );
+        assert!(graph.ready.is_empty());
+    }
+
+    #[test]
+    fn a_flooding_sender_does_not_keep_an_occasional_one_waiting() {
+        let mut graph = PendingGraph::new()
+            .with_scheduling_policy(Arc::new(PriorityPolicy::new(FeeSchedule::default(), 0)))
+            .with_fairness(Fairness::new(2))
+            .with_max_in_flight(1);
+        let flood: Vec<Transaction> = (0..10).map(|nonce| paying(1, nonce, 100)).collect();
+        for transaction in &flood {
+            graph
+                .submit(transaction.clone(), None, false, None)
+                .unwrap();
+        }
+        let mut in_flight = flood[0].hash_string();
+        assert!(graph.is_scheduled(&in_flight));
+
+        let occasional = paying(2, 0, 0);
+        graph.submit(occasional.clone(), None, false, None).unwrap();
+        let mut waited = 0;
+        while in_flight != occasional.hash_string() {
+            let released = graph.handle_valid(&in_flight);
+            in_flight = graph.schedule_ready(released).pop().unwrap();
+            waited += 1;
+        }
+        // However much more the flood pays, the occasional sender waits out
+        // at most the rest of the flood's share of the round.
+        assert!(waited <= 2, "waited behind {waited} of the flood");
+        assert_eq!(graph.ready.len(), flood.len() - waited);
+    }
+}
@@ -0,0 +1,492 @@
+//! What each program costs the node, for `admin_getProgramStats` and the
+//! hot programs the dashboard shows.
+//!
+//! The executor counts each program's invocations, how many of them failed
+//! and how long they ran, and the batcher the bytes of state each program's
+//! calls wrote. The counts are kept in memory, and `snapshot_program_stats`
+//! flushes them to a file every so often, read back on the next start, so a
+//! restart loses only what was counted since the last flush. Each snapshot
+//! also exports the programs invoked most to Prometheus. Programs past the
+//! top few are left out, so the series stay few however many programs run.
+//!
+//! The percentiles of a program's execution time are over its latest
+//! `RECENT_DURATIONS` executions.
+//!
+//! Counts are written as JSON in a [`RecordLog`], as in the dead letters.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use prometheus::IntGaugeVec;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::metrics;
+use crate::record_log::{RecordLog, RecordLogError};
+
+/// The executions of a program its percentiles are taken over.
+pub const RECENT_DURATIONS: usize = 256;
+
+/// The programs exported to Prometheus, unless `program_stats.top` says
+/// otherwise.
+pub const DEFAULT_TOP_PROGRAMS: usize = 10;
+
+/// Seconds between snapshots, unless `program_stats.flush_secs` says
+/// otherwise.
+pub const DEFAULT_PROGRAM_STATS_FLUSH_SECS: u64 = 60;
+
+#[derive(Debug, Error)]
+pub enum ProgramStatsError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// What programs are ranked by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgramMetric {
+    #[default]
+    Invocations,
+    Failures,
+    /// The time spent executing a program, over every invocation.
+    ExecutionTime,
+    BytesWritten,
+}
+
+impl std::fmt::Display for ProgramMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramMetric::Invocations => write!(f, "invocations"),
+            ProgramMetric::Failures => write!(f, "failures"),
+            ProgramMetric::ExecutionTime => write!(f, "executionTime"),
+            ProgramMetric::BytesWritten => write!(f, "bytesWritten"),
+        }
+    }
+}
+
+impl FromStr for ProgramMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "invocations" => Ok(ProgramMetric::Invocations),
+            "failures" => Ok(ProgramMetric::Failures),
+            "executiontime" => Ok(ProgramMetric::ExecutionTime),
+            "byteswritten" => Ok(ProgramMetric::BytesWritten),
+            other => Err(format!(
+                "unknown metric {other}, expected invocations, failures, executionTime or bytesWritten"
+            )),
+        }
+    }
+}
+
+/// A program's counts, as reported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramStat {
+    pub program_id: String,
+    pub invocations: u64,
+    pub failures: u64,
+    /// Microseconds spent executing the program, over every invocation.
+    pub total_execution_micros: u64,
+    pub p50_execution_micros: u64,
+    pub p95_execution_micros: u64,
+    pub p99_execution_micros: u64,
+    /// Bytes of the accounts the program's calls left, as they were stored.
+    pub bytes_written: u64,
+}
+
+impl ProgramStat {
+    pub fn metric(&self, metric: ProgramMetric) -> u64 {
+        match metric {
+            ProgramMetric::Invocations => self.invocations,
+            ProgramMetric::Failures => self.failures,
+            ProgramMetric::ExecutionTime => self.total_execution_micros,
+            ProgramMetric::BytesWritten => self.bytes_written,
+        }
+    }
+}
+
+/// What is kept of a program, and flushed as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgramCounters {
+    program_id: String,
+    invocations: u64,
+    failures: u64,
+    total_execution_micros: u64,
+    bytes_written: u64,
+    /// Microseconds each of the latest executions took, oldest first.
+    recent: VecDeque<u64>,
+}
+
+impl ProgramCounters {
+    fn stat(&self) -> ProgramStat {
+        let mut recent: Vec<u64> = self.recent.iter().copied().collect();
+        recent.sort_unstable();
+        ProgramStat {
+            program_id: self.program_id.clone(),
+            invocations: self.invocations,
+            failures: self.failures,
+            total_execution_micros: self.total_execution_micros,
+            p50_execution_micros: percentile(&recent, 50),
+            p95_execution_micros: percentile(&recent, 95),
+            p99_execution_micros: percentile(&recent, 99),
+            bytes_written: self.bytes_written,
+        }
+    }
+}
+
+/// The nearest rank `percent` percentile of `sorted`, 0 if it is empty.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied().unwrap_or_default()
+}
+
+struct ProgramStatsMetrics {
+    invocations: IntGaugeVec,
+    failures: IntGaugeVec,
+    execution_seconds: IntGaugeVec,
+    bytes_written: IntGaugeVec,
+}
+
+fn program_stats_metrics() -> &'static ProgramStatsMetrics {
+    static METRICS: OnceLock<ProgramStatsMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| ProgramStatsMetrics {
+        invocations: metrics::gauge_vec(
+            "programs",
+            "top_invocations",
+            "Invocations of the programs invoked most, by program.",
+            &["program"],
+        ),
+        failures: metrics::gauge_vec(
+            "programs",
+            "top_failures",
+            "Failed invocations of the programs invoked most, by program.",
+            &["program"],
+        ),
+        execution_seconds: metrics::gauge_vec(
+            "programs",
+            "top_execution_seconds",
+            "Seconds spent executing the programs invoked most, by program.",
+            &["program"],
+        ),
+        bytes_written: metrics::gauge_vec(
+            "programs",
+            "top_bytes_written",
+            "Bytes of state written by the programs invoked most, by program.",
+            &["program"],
+        ),
+    })
+}
+
+#[derive(Debug, Default)]
+struct ProgramStatsInner {
+    programs: HashMap<String, ProgramCounters>,
+    log: Option<RecordLog<ProgramCounters>>,
+    /// The programs exported at the last snapshot.
+    exported: HashSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgramStats {
+    inner: Arc<Mutex<ProgramStatsInner>>,
+    top: usize,
+    flush_interval: Duration,
+}
+
+impl Default for ProgramStats {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ProgramStatsInner::default())),
+            top: DEFAULT_TOP_PROGRAMS,
+            flush_interval: Duration::from_secs(DEFAULT_PROGRAM_STATS_FLUSH_SECS),
+        }
+    }
+}
+
+impl ProgramStats {
+    /// Opens the counts flushed to `path`, creating the file if there is
+    /// none. Exports at most [`DEFAULT_TOP_PROGRAMS`] programs, and
+    /// snapshots every [`DEFAULT_PROGRAM_STATS_FLUSH_SECS`].
+    pub fn open(path: &Path) -> Result<Self, ProgramStatsError> {
+        let (log, counters) = RecordLog::open(path)?;
+        let programs = counters
+            .into_iter()
+            .map(|counters| (counters.program_id.clone(), counters))
+            .collect();
+        Ok(Self {
+            inner: Arc::new(Mutex::new(ProgramStatsInner {
+                programs,
+                log: Some(log),
+                exported: HashSet::new(),
+            })),
+            ..Self::default()
+        })
+    }
+
+    /// Exports at most `top` programs to Prometheus.
+    pub fn with_top(mut self, top: usize) -> Self {
+        self.top = top;
+        self
+    }
+
+    /// Snapshots every `interval`.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Counts an invocation of `program_id` that ran for `duration`, and
+    /// whether it `succeeded`.
+    pub fn executed(&self, program_id: &str, succeeded: bool, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let counters = inner.counters(program_id);
+        counters.invocations += 1;
+        if !succeeded {
+            counters.failures += 1;
+        }
+        counters.total_execution_micros = counters.total_execution_micros.saturating_add(micros);
+        counters.recent.push_back(micros);
+        if counters.recent.len() > RECENT_DURATIONS {
+            counters.recent.pop_front();
+        }
+    }
+
+    /// Counts `bytes` of state written by a call to `program_id`.
+    pub fn wrote(&self, program_id: &str, bytes: u64) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let counters = inner.counters(program_id);
+        counters.bytes_written = counters.bytes_written.saturating_add(bytes);
+    }
+
+    pub fn get(&self, program_id: &str) -> Option<ProgramStat> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .programs
+            .get(program_id)
+            .map(ProgramCounters::stat)
+    }
+
+    /// Every program counted, in no particular order.
+    pub fn list(&self) -> Vec<ProgramStat> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .programs
+            .values()
+            .map(ProgramCounters::stat)
+            .collect()
+    }
+
+    /// The `count` programs highest by `metric`, highest first, those level
+    /// with each other by program id.
+    pub fn top(&self, metric: ProgramMetric, count: usize) -> Vec<ProgramStat> {
+        let mut stats = self.list();
+        stats.sort_by(|a, b| {
+            b.metric(metric)
+                .cmp(&a.metric(metric))
+                .then_with(|| a.program_id.cmp(&b.program_id))
+        });
+        stats.truncate(count);
+        stats
+    }
+
+    /// Exports the programs invoked most to Prometheus, dropping the series
+    /// of those no longer among them.
+    pub fn export(&self) {
+        let top = self.top(ProgramMetric::Invocations, self.top);
+        let metrics = program_stats_metrics();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let exported: HashSet<String> = top.iter().map(|stat| stat.program_id.clone()).collect();
+        for program_id in inner.exported.difference(&exported) {
+            for gauge in [
+                &metrics.invocations,
+                &metrics.failures,
+                &metrics.execution_seconds,
+                &metrics.bytes_written,
+            ] {
+                gauge.remove_label_values(&[program_id.as_str()]).ok();
+            }
+        }
+        let gauge = |value: u64| i64::try_from(value).unwrap_or(i64::MAX);
+        for stat in &top {
+            let labels = [stat.program_id.as_str()];
+            metrics
+                .invocations
+                .with_label_values(&labels)
+                .set(gauge(stat.invocations));
+            metrics
+                .failures
+                .with_label_values(&labels)
+                .set(gauge(stat.failures));
+            metrics
+                .execution_seconds
+                .with_label_values(&labels)
+                .set(gauge(stat.total_execution_micros / 1_000_000));
+            metrics
+                .bytes_written
+                .with_label_values(&labels)
+                .set(gauge(stat.bytes_written));
+        }
+        inner.exported = exported;
+    }
+
+    /// Writes every program's counts over the file, if there is one, so a
+    /// crash leaves either the old counts or the new.
+    pub fn flush(&self) -> Result<(), ProgramStatsError> {
+        let mut guard = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let inner = &mut *guard;
+        if let Some(log) = inner.log.as_mut() {
+            log.rewrite(inner.programs.values())?;
+        }
+        Ok(())
+    }
+}
+
+impl ProgramStatsInner {
+    fn counters(&mut self, program_id: &str) -> &mut ProgramCounters {
+        self.programs
+            .entry(program_id.to_string())
+            .or_insert_with(|| ProgramCounters {
+                program_id: program_id.to_string(),
+                ..ProgramCounters::default()
+            })
+    }
+}
+
+/// Exports and flushes `stats` every flush interval.
+pub async fn snapshot_program_stats(stats: ProgramStats) {
+    let mut ticker = tokio::time::interval(stats.flush_interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        stats.export();
+        if let Err(e) = stats.flush() {
+            tracing::error!("failed to flush program stats: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod program_stats_tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::record_log::{encode_record, Json};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("program_stats_{}_{}.log", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn ids(stats: &[ProgramStat]) -> Vec<&str> {
+        stats.iter().map(|stat| stat.program_id.as_str()).collect()
+    }
+
+    #[test]
+    fn percentiles_are_over_the_latest_executions() {
+        let stats = ProgramStats::default();
+        for millis in 1..=100 {
+            stats.executed("0xaa", millis % 10 != 0, Duration::from_millis(millis));
+        }
+        let stat = stats.get("0xaa").unwrap();
+        assert_eq!(stat.invocations, 100);
+        assert_eq!(stat.failures, 10);
+        assert_eq!(stat.total_execution_micros, 5050 * 1000);
+        assert_eq!(stat.p50_execution_micros, 50_000);
+        assert_eq!(stat.p95_execution_micros, 95_000);
+        assert_eq!(stat.p99_execution_micros, 99_000);
+
+        // Once the slow ones are old enough, they are out of the percentiles
+        // but still in the total.
+        for _ in 0..RECENT_DURATIONS {
+            stats.executed("0xaa", true, Duration::from_millis(1));
+        }
+        let stat = stats.get("0xaa").unwrap();
+        assert_eq!(stat.p99_execution_micros, 1000);
+        assert_eq!(
+            stat.total_execution_micros,
+            (5050 + RECENT_DURATIONS as u64) * 1000
+        );
+        assert_eq!(percentile(&[], 50), 0);
+        assert_eq!(percentile(&[7], 99), 7);
+    }
+
+    #[test]
+    fn programs_are_ranked_by_the_metric_asked_for() {
+        let stats = ProgramStats::default();
+        for (program_id, invocations, millis, bytes) in [
+            ("0xaa", 3, 1, 900),
+            ("0xbb", 5, 1, 100),
+            ("0xcc", 1, 50, 500),
+        ] {
+            for _ in 0..invocations {
+                stats.executed(
+                    program_id,
+                    program_id != "0xcc",
+                    Duration::from_millis(millis),
+                );
+            }
+            stats.wrote(program_id, bytes);
+        }
+
+        let top = |metric| ids(&stats.top(metric, 3)).join(",");
+        assert_eq!(top(ProgramMetric::Invocations), "0xbb,0xaa,0xcc");
+        assert_eq!(top(ProgramMetric::Failures), "0xcc,0xaa,0xbb");
+        assert_eq!(top(ProgramMetric::ExecutionTime), "0xcc,0xbb,0xaa");
+        assert_eq!(top(ProgramMetric::BytesWritten), "0xaa,0xcc,0xbb");
+        assert_eq!(ids(&stats.top(ProgramMetric::Invocations, 1)), vec!["0xbb"]);
+        assert_eq!(
+            "bytesWritten".parse::<ProgramMetric>(),
+            Ok(ProgramMetric::BytesWritten)
+        );
+        assert!("latency".parse::<ProgramMetric>().is_err());
+    }
+
+    #[test]
+    fn stats_are_read_back_after_a_restart() {
+        let path = temp_path("restart");
+        let stats = ProgramStats::open(&path).unwrap();
+        stats.executed("0xaa", true, Duration::from_millis(4));
+        stats.executed("0xbb", false, Duration::from_millis(8));
+        stats.wrote("0xbb", 64);
+        stats.flush().unwrap();
+        // Counted after the last flush, and so lost.
+        stats.executed("0xaa", true, Duration::from_millis(4));
+        let flushed = stats.get("0xbb").unwrap();
+        drop(stats);
+
+        // A record torn off by a crash is dropped.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        let torn = ProgramCounters {
+            program_id: "0xcc".to_string(),
+            ..ProgramCounters::default()
+        };
+        file.write_all(&encode_record(&Json, &torn).unwrap()[..10])
+            .unwrap();
+        drop(file);
+
+        let stats = ProgramStats::open(&path).unwrap();
+        assert_eq!(stats.get("0xaa").unwrap().invocations, 1);
+        assert_eq!(stats.get("0xbb"), Some(flushed));
+        assert_eq!(stats.get("0xcc"), None);
+        stats.executed("0xbb", true, Duration::from_millis(8));
+        assert_eq!(stats.get("0xbb").unwrap().p50_execution_micros, 8000);
+        std::fs::remove_file(&path).ok();
+    }
+}
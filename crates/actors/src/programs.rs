@@ -0,0 +1,271 @@
+//! The programs registered on this node, for `getProgram` and
+//! `listPrograms`.
+//!
+//! The batcher records a program as its registration is committed, and the
+//! batch the registration went into once that batch's blob is verified on
+//! the DA layer. Token metadata is read from the program account when asked
+//! for, since programs update it after they are registered.
+use crate::unix_now;
+use lasr_compute::PROGRAM_RUNTIME_KEY;
+use lasr_types::{Account, AccountType, Address, Metadata, Transaction, U256};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// What a program was registered as.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramRecord {
+    pub program_id: Address,
+    /// The content hash of the program's artifact.
+    pub content_id: String,
+    /// The runtime the executor reported the program executing in.
+    pub runtime: Option<String>,
+    pub owner: Address,
+    pub transaction_hash: String,
+    /// Seconds since the epoch the registration was committed at.
+    pub registered_at: u64,
+    /// Set once the batch the registration is in has been finalized.
+    pub batch_id: Option<u64>,
+    /// The program account's metadata as registered, with the limits and
+    /// input format its calls are held to.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ProgramRecord {
+    /// The record of `transaction` registering `program`.
+    pub fn new(program: &Account, transaction: &Transaction) -> Self {
+        let metadata = program.program_account_metadata().inner().clone();
+        Self {
+            program_id: match program.account_type() {
+                AccountType::Program(program_id) => program_id,
+                AccountType::User => program.owner_address(),
+            },
+            content_id: metadata.get("content_id").cloned().unwrap_or_default(),
+            runtime: metadata.get(PROGRAM_RUNTIME_KEY).cloned(),
+            owner: program.owner_address(),
+            transaction_hash: transaction.hash_string(),
+            registered_at: unix_now(),
+            batch_id: None,
+            metadata,
+        }
+    }
+
+    /// The record of `program`, registered by the genesis hashing to
+    /// `genesis_hash` rather than by a transaction, and in no batch.
+    pub fn at_genesis(program: &Account, genesis_hash: &str) -> Self {
+        let metadata = program.program_account_metadata().inner().clone();
+        Self {
+            program_id: match program.account_type() {
+                AccountType::Program(program_id) => program_id,
+                AccountType::User => program.owner_address(),
+            },
+            content_id: metadata.get("content_id").cloned().unwrap_or_default(),
+            runtime: metadata.get(PROGRAM_RUNTIME_KEY).cloned(),
+            owner: program.owner_address(),
+            transaction_hash: genesis_hash.to_string(),
+            registered_at: 0,
+            batch_id: None,
+            metadata,
+        }
+    }
+}
+
+/// The token a program's account describes, from the keys of its metadata
+/// that are set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenMetadata {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    pub total_supply: Option<U256>,
+    pub initialized_supply: Option<U256>,
+}
+
+impl TokenMetadata {
+    /// Parses the token keys of `metadata`, or `None` if none are set.
+    pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let supply = |key: &str| {
+            metadata
+                .get(key)
+                .and_then(|value| match value.strip_prefix("0x") {
+                    Some(hex) => U256::from_str_radix(hex, 16).ok(),
+                    None => U256::from_dec_str(value).ok(),
+                })
+        };
+        let token = Self {
+            name: metadata.get("name").cloned(),
+            symbol: metadata.get("symbol").cloned(),
+            decimals: metadata
+                .get("decimals")
+                .and_then(|decimals| decimals.parse().ok()),
+            total_supply: supply("total_supply"),
+            initialized_supply: supply("initialized_supply"),
+        };
+        (token != Self::default()).then_some(token)
+    }
+}
+
+/// A program's record, with the token its account describes now.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramInfo {
+    #[serde(flatten)]
+    pub record: ProgramRecord,
+    pub token_metadata: Option<TokenMetadata>,
+}
+
+impl ProgramInfo {
+    pub fn new(record: ProgramRecord, program: Option<&Account>) -> Self {
+        Self {
+            token_metadata: program.and_then(|program| {
+                TokenMetadata::from_metadata(program.program_account_metadata())
+            }),
+            record,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProgramIndex {
+    programs: BTreeMap<Address, ProgramRecord>,
+    /// The program each registration transaction registered.
+    registrations: HashMap<String, Address>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProgramRegistry {
+    index: Arc<Mutex<ProgramIndex>>,
+}
+
+impl ProgramRegistry {
+    /// Records a program whose registration was committed.
+    pub fn registered(&self, record: ProgramRecord) {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index
+            .registrations
+            .insert(record.transaction_hash.clone(), record.program_id);
+        index.programs.insert(record.program_id, record);
+    }
+
+    /// Records a program registered at genesis, which no batch will take
+    /// in.
+    pub fn at_genesis(&self, record: ProgramRecord) {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index.programs.insert(record.program_id, record);
+    }
+
+    /// Records that the registrations among `transaction_hashes` went into
+    /// batch `batch_id`.
+    pub fn batched<'a>(
+        &self,
+        transaction_hashes: impl IntoIterator<Item = &'a String>,
+        batch_id: u64,
+    ) {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        for transaction_hash in transaction_hashes {
+            if let Some(program_id) = index.registrations.remove(transaction_hash) {
+                if let Some(record) = index.programs.get_mut(&program_id) {
+                    record.batch_id = Some(batch_id);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, program_id: &Address) -> Option<ProgramRecord> {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .programs
+            .get(program_id)
+            .cloned()
+    }
+
+    /// Every program registered, by program id.
+    pub fn list(&self) -> Vec<ProgramRecord> {
+        self.index
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .programs
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod programs_tests {
+    use super::*;
+    use lasr_types::{TransactionBuilder, TransactionType};
+
+    fn registration(nonce: u64) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::RegisterProgram(U256::from(nonce)))
+            .from([1; 20])
+            .to([1; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    fn record(program: u8, transaction: &Transaction) -> ProgramRecord {
+        let account = Account::new_program(Address::new([program; 20]), Address::new([1; 20]));
+        ProgramRecord::new(&account, transaction)
+    }
+
+    #[test]
+    fn registrations_learn_their_batch() {
+        let registry = ProgramRegistry::default();
+        let (first, second) = (registration(0), registration(1));
+        registry.registered(record(2, &first));
+        registry.registered(record(3, &second));
+
+        registry.batched([&first.hash_string(), &"0xb".to_string()], 4);
+        assert_eq!(
+            registry.get(&Address::new([2; 20])).unwrap().batch_id,
+            Some(4)
+        );
+        assert_eq!(registry.get(&Address::new([3; 20])).unwrap().batch_id, None);
+        assert_eq!(
+            registry
+                .list()
+                .iter()
+                .map(|record| record.program_id)
+                .collect::<Vec<_>>(),
+            vec![Address::new([2; 20]), Address::new([3; 20])]
+        );
+        assert_eq!(registry.get(&Address::new([4; 20])), None);
+    }
+
+    #[test]
+    fn token_metadata_is_parsed_from_the_keys_set() {
+        let mut metadata = Metadata::new();
+        assert_eq!(TokenMetadata::from_metadata(&metadata), None);
+
+        metadata.insert("content_id".to_string(), "cid".to_string());
+        assert_eq!(TokenMetadata::from_metadata(&metadata), None);
+
+        metadata.insert("symbol".to_string(), "TKN".to_string());
+        metadata.insert("decimals".to_string(), "18".to_string());
+        metadata.insert("total_supply".to_string(), format!("0x{:064x}", 1000));
+        metadata.insert("initialized_supply".to_string(), "400".to_string());
+        assert_eq!(
+            TokenMetadata::from_metadata(&metadata),
+            Some(TokenMetadata {
+                name: None,
+                symbol: Some("TKN".to_string()),
+                decimals: Some(18),
+                total_supply: Some(U256::from(1000)),
+                initialized_supply: Some(U256::from(400)),
+            })
+        );
+    }
+}
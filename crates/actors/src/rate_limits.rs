@@ -0,0 +1,553 @@
+//! Rate limiting the requests made to the RPC server.
+//!
+//! Each client IP has a token bucket per method limited on its own, and one
+//! shared by every other method. A request takes a token from its bucket, and
+//! is answered as rate limited, with how long to wait, when the bucket is
+//! empty. Buckets refill at the limit's rate up to its burst. Clients on the
+//! allowlist are never limited. The limits are swapped at runtime through
+//! `admin_setRateLimits`, which starts every bucket full again.
+use crate::{metrics, rest_method, serve_rest, AdminAccess, AdminService, RequestLimits};
+use futures::future::{ready, BoxFuture, Either, Ready};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use jsonrpsee::{
+    server::{
+        middleware::rpc::{RpcServiceBuilder, RpcServiceT},
+        stop_channel, ServerBuilder, ServerHandle,
+    },
+    types::{ErrorObjectOwned as RpcError, Request},
+    MethodResponse, Methods,
+};
+use lasr_messages::RpcErrorCode;
+use prometheus::{HistogramVec, IntCounter, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tower::Service;
+
+pub const DEFAULT_RATE_LIMIT_PER_SECOND: f64 = 20.0;
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 100;
+pub const DEFAULT_EXPENSIVE_RATE_LIMIT_PER_SECOND: f64 = 2.0;
+pub const DEFAULT_EXPENSIVE_RATE_LIMIT_BURST: u32 = 10;
+
+/// The methods held to the expensive limit unless configured otherwise, as
+/// they run programs or scan the settlement layer.
+pub const EXPENSIVE_METHODS: [&str; 6] = [
+    "lasr_call",
+    "lasr_registerProgram",
+    "lasr_simulateTransaction",
+    "lasr_simulatePayload",
+    "admin_backfillSettlementEvents",
+    "admin_getExecutionMismatches",
+];
+
+/// Buckets kept before those full again are dropped, as a client with a full
+/// bucket is no different from one not seen.
+const MAX_TRACKED_BUCKETS: usize = 65_536;
+
+/// Where the node's metrics are served, alongside the RPC methods.
+pub const METRICS_PATH: &str = "/metrics";
+
+struct RpcMetrics {
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    rate_limited: IntCounter,
+}
+
+fn rpc_metrics() -> &'static RpcMetrics {
+    static METRICS: OnceLock<RpcMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| RpcMetrics {
+        requests: metrics::counter_vec(
+            "rpc",
+            "requests_total",
+            "RPC requests answered, by method and outcome.",
+            &["method", "outcome"],
+        ),
+        duration: metrics::histogram_vec(
+            "rpc",
+            "request_duration_seconds",
+            "Seconds taken to answer RPC requests, by method.",
+            &["method"],
+        ),
+        rate_limited: metrics::counter(
+            "rpc",
+            "rate_limited_total",
+            "RPC requests turned away by the rate limiter.",
+        ),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RateLimit {
+    /// Requests a bucket refills by each second.
+    pub per_second: f64,
+    /// Requests a full bucket holds, made as fast as the client likes.
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The limit of the bucket shared by the methods with none of their own.
+    pub default: RateLimit,
+    /// Methods, by their full name, limited in buckets of their own.
+    #[serde(default)]
+    pub methods: BTreeMap<String, RateLimit>,
+    /// Clients never limited, such as the node's own tooling.
+    #[serde(default)]
+    pub allowlist: Vec<IpAddr>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self::new(
+            RateLimit {
+                per_second: DEFAULT_RATE_LIMIT_PER_SECOND,
+                burst: DEFAULT_RATE_LIMIT_BURST,
+            },
+            RateLimit {
+                per_second: DEFAULT_EXPENSIVE_RATE_LIMIT_PER_SECOND,
+                burst: DEFAULT_EXPENSIVE_RATE_LIMIT_BURST,
+            },
+            Vec::new(),
+        )
+    }
+}
+
+impl RateLimitConfig {
+    /// Holds the `EXPENSIVE_METHODS` to `expensive` and every other method
+    /// to `default`, except for the clients on `allowlist`.
+    pub fn new(default: RateLimit, expensive: RateLimit, allowlist: Vec<IpAddr>) -> Self {
+        Self {
+            default,
+            methods: EXPENSIVE_METHODS
+                .iter()
+                .map(|method| (method.to_string(), expensive))
+                .collect(),
+            allowlist,
+        }
+    }
+
+    fn validate(&self) -> Result<(), RateLimitError> {
+        let limits = std::iter::once(("default", &self.default)).chain(
+            self.methods
+                .iter()
+                .map(|(method, limit)| (method.as_str(), limit)),
+        );
+        for (name, limit) in limits {
+            if !(limit.per_second.is_finite() && limit.per_second > 0.0) || limit.burst == 0 {
+                return Err(RateLimitError::InvalidLimit(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn limit(&self, method: &str) -> (Option<&str>, RateLimit) {
+        match self.methods.get_key_value(method) {
+            Some((method, limit)) => (Some(method.as_str()), *limit),
+            None => (None, self.default),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RateLimitError {
+    #[error("the {0} rate limit must refill by more than nothing and hold a request")]
+    InvalidLimit(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// A request turned away, and how long until its bucket holds a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throttled {
+    pub retry_after: Duration,
+}
+
+/// The error data of a request turned away, until it may be made again.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RetryAfter {
+    pub(crate) retry_after_ms: u64,
+}
+
+impl From<Throttled> for RpcError {
+    fn from(throttled: Throttled) -> Self {
+        let retry_after_ms = throttled.retry_after.as_millis().max(1) as u64;
+        RpcError::owned(
+            RpcErrorCode::RateLimited.code(),
+            format!("rate limited, retry after {retry_after_ms}ms"),
+            Some(RetryAfter { retry_after_ms }),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(f64::from(limit.burst));
+        self.refilled = now;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Limits {
+    config: RateLimitConfig,
+    /// Keyed by client and the method limited on its own, if any.
+    buckets: HashMap<(IpAddr, Option<String>), Bucket>,
+    throttled: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitStatus {
+    pub limits: RateLimitConfig,
+    /// Requests turned away since the node started, by method.
+    pub throttled: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    limits: Arc<Mutex<Limits>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Result<Self, RateLimitError> {
+        config.validate()?;
+        Ok(Self {
+            limits: Arc::new(Mutex::new(Limits {
+                config,
+                ..Limits::default()
+            })),
+        })
+    }
+
+    /// Takes a token from the bucket `method` is limited in for `client`.
+    pub fn check(&self, client: IpAddr, method: &str) -> Result<(), Throttled> {
+        self.check_at(client, method, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, method: &str, now: Instant) -> Result<(), Throttled> {
+        let mut limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+        let Limits {
+            config,
+            buckets,
+            throttled,
+        } = &mut *limits;
+        if config.allowlist.contains(&client) {
+            return Ok(());
+        }
+        let (limited, limit) = config.limit(method);
+        if buckets.len() >= MAX_TRACKED_BUCKETS {
+            buckets.retain(|(_, limited), bucket| {
+                let (_, limit) = config.limit(limited.as_deref().unwrap_or_default());
+                bucket.refill(limit, now);
+                bucket.tokens < f64::from(limit.burst)
+            });
+        }
+        let bucket = buckets
+            .entry((client, limited.map(str::to_string)))
+            .or_insert(Bucket {
+                tokens: f64::from(limit.burst),
+                refilled: now,
+            });
+        bucket.refill(limit, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        *throttled.entry(method.to_string()).or_default() += 1;
+        Err(Throttled {
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / limit.per_second),
+        })
+    }
+
+    /// Swaps the limits for `config`, starting every bucket full again.
+    pub fn set(&self, config: RateLimitConfig) -> Result<(), RateLimitError> {
+        config.validate()?;
+        let mut limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+        limits.config = config;
+        limits.buckets.clear();
+        Ok(())
+    }
+
+    pub fn status(&self) -> RateLimitStatus {
+        let limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+        RateLimitStatus {
+            limits: limits.config.clone(),
+            throttled: limits.throttled.clone(),
+        }
+    }
+}
+
+/// Answers the requests of `client` its rate limiter turns away, handing on
+/// the rest.
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    service: S,
+    rate_limiter: RateLimiter,
+    client: IpAddr,
+}
+
+impl<'a, S> RpcServiceT<'a> for RateLimitService<S>
+where
+    S: RpcServiceT<'a> + Send + Sync,
+{
+    type Future = Either<Ready<MethodResponse>, S::Future>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        match self.rate_limiter.check(self.client, request.method_name()) {
+            Ok(()) => Either::Right(self.service.call(request)),
+            Err(throttled) => {
+                rpc_metrics().rate_limited.inc();
+                tracing::debug!(
+                    "rate limited {} calling {}",
+                    self.client,
+                    request.method_name()
+                );
+                Either::Left(ready(MethodResponse::error(
+                    request.id,
+                    RpcError::from(throttled),
+                )))
+            }
+        }
+    }
+}
+
+/// Counts the requests `service` answers and how long each took. Methods
+/// the server does not have are counted together as `unknown`.
+#[derive(Debug, Clone)]
+pub struct MetricsService<S> {
+    service: S,
+    methods: Arc<HashSet<&'static str>>,
+}
+
+impl<'a, S> RpcServiceT<'a> for MetricsService<S>
+where
+    S: RpcServiceT<'a> + Send + Sync,
+    S::Future: Send + 'a,
+{
+    type Future = BoxFuture<'a, MethodResponse>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let method = self
+            .methods
+            .get(request.method_name())
+            .copied()
+            .unwrap_or("unknown");
+        let started = Instant::now();
+        let response = self.service.call(request);
+        Box::pin(async move {
+            let response = response.await;
+            let outcome = if response.is_success() { "ok" } else { "error" };
+            let metrics = rpc_metrics();
+            metrics.requests.with_label_values(&[method, outcome]).inc();
+            metrics
+                .duration
+                .with_label_values(&[method])
+                .observe(started.elapsed().as_secs_f64());
+            response
+        })
+    }
+}
+
+/// Serves `methods` at `addr`, over HTTP and websockets, holding each client
+/// to the limits of `rate_limiter` and answering the admin methods to the
+/// callers `admin` lets through, and the node's metrics at `METRICS_PATH`.
+/// Requests holding more than `limits` allow are turned away. Returns the
+/// address served at.
+pub async fn serve_rpc(
+    addr: SocketAddr,
+    methods: impl Into<Methods>,
+    rate_limiter: RateLimiter,
+    admin: AdminAccess,
+    rest: Option<axum::Router>,
+    limits: RequestLimits,
+) -> Result<(SocketAddr, ServerHandle), hyper::Error> {
+    let methods: Methods = methods.into();
+    let method_names: Arc<HashSet<&'static str>> = Arc::new(methods.method_names().collect());
+    let (stop_handle, server_handle) = stop_channel();
+    let service_builder = ServerBuilder::default()
+        .max_connections(1000)
+        .max_request_body_size(limits.max_request_bytes)
+        .set_batch_request_config(limits.batch_config())
+        .to_service_builder();
+    let shutdown = stop_handle.clone();
+
+    let make_service = make_service_fn(move |connection: &AddrStream| {
+        let client = connection.remote_addr().ip();
+        let methods = methods.clone();
+        let stop_handle = stop_handle.clone();
+        let service_builder = service_builder.clone();
+        let rate_limiter = rate_limiter.clone();
+        let method_names = method_names.clone();
+        let admin = admin.clone();
+        let rest = rest.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<hyper::Body>| {
+                let rate_limiter = rate_limiter.clone();
+                let method_names = method_names.clone();
+                let rest = rest.clone();
+                let caller = admin.authorize(
+                    client,
+                    request
+                        .headers()
+                        .get(hyper::header::AUTHORIZATION)
+                        .and_then(|header| header.to_str().ok()),
+                );
+                let mut service = service_builder
+                    .clone()
+                    .set_rpc_middleware(
+                        RpcServiceBuilder::new()
+                            .layer_fn(move |service| MetricsService {
+                                service,
+                                methods: method_names.clone(),
+                            })
+                            .layer_fn(move |service| RateLimitService {
+                                service,
+                                rate_limiter: rate_limiter.clone(),
+                                client,
+                            })
+                            .layer_fn(move |service| AdminService {
+                                service,
+                                caller: caller.clone(),
+                            }),
+                    )
+                    .build(methods.clone(), stop_handle.clone());
+                async move {
+                    if request.method() == hyper::Method::GET
+                        && request.uri().path() == METRICS_PATH
+                    {
+                        return Ok(hyper::Response::builder()
+                            .header(hyper::header::CONTENT_TYPE, metrics::CONTENT_TYPE)
+                            .body(hyper::Body::from(metrics::encode()))
+                            .expect("metrics response is valid"));
+                    }
+                    if let Some((rest, method)) = rest.zip(rest_method(request.uri().path())) {
+                        return Ok(serve_rest(rest, method, &rate_limiter, client, request).await);
+                    }
+                    service.call(request).await
+                }
+            }))
+        }
+    });
+
+    let server = hyper::Server::try_bind(&addr)?.serve(make_service);
+    let local_addr = server.local_addr();
+    tokio::spawn(server.with_graceful_shutdown(async move { shutdown.shutdown().await }));
+    Ok((local_addr, server_handle))
+}
+
+#[cfg(test)]
+mod rate_limits_tests {
+    use super::*;
+
+    fn limiter(default: RateLimit, methods: &[(&str, RateLimit)]) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            default,
+            methods: methods
+                .iter()
+                .map(|(method, limit)| (method.to_string(), *limit))
+                .collect(),
+            allowlist: vec![IpAddr::from([10, 0, 0, 1])],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn a_bucket_refills_at_its_rate_up_to_its_burst() {
+        let limiter = limiter(
+            RateLimit {
+                per_second: 2.0,
+                burst: 3,
+            },
+            &[],
+        );
+        let client = IpAddr::from([10, 0, 0, 2]);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(client, "lasr_send", start), Ok(()));
+        }
+        assert_eq!(
+            limiter.check_at(client, "lasr_getAccount", start),
+            Err(Throttled {
+                retry_after: Duration::from_millis(500)
+            })
+        );
+        // Half a second puts one token back.
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.check_at(client, "lasr_send", later), Ok(()));
+        assert!(limiter.check_at(client, "lasr_send", later).is_err());
+        // However long it waits, a client gets no more than the burst.
+        let much_later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(client, "lasr_send", much_later), Ok(()));
+        }
+        assert!(limiter.check_at(client, "lasr_send", much_later).is_err());
+
+        assert_eq!(
+            limiter.status().throttled,
+            BTreeMap::from([
+                ("lasr_getAccount".to_string(), 1),
+                ("lasr_send".to_string(), 2)
+            ])
+        );
+    }
+
+    #[test]
+    fn methods_limited_on_their_own_leave_the_shared_bucket_alone() {
+        let limit = |burst| RateLimit {
+            per_second: 1.0,
+            burst,
+        };
+        let limiter = limiter(limit(2), &[("lasr_call", limit(1))]);
+        let client = IpAddr::from([10, 0, 0, 2]);
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at(client, "lasr_call", now), Ok(()));
+        assert!(limiter.check_at(client, "lasr_call", now).is_err());
+        assert_eq!(limiter.check_at(client, "lasr_send", now), Ok(()));
+        assert_eq!(limiter.check_at(client, "lasr_send", now), Ok(()));
+        // Each client has buckets of its own, and some have none.
+        assert_eq!(
+            limiter.check_at(IpAddr::from([10, 0, 0, 3]), "lasr_call", now),
+            Ok(())
+        );
+        for _ in 0..10 {
+            assert_eq!(
+                limiter.check_at(IpAddr::from([10, 0, 0, 1]), "lasr_call", now),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn limits_that_never_admit_a_request_are_turned_away() {
+        let limiter = RateLimiter::default();
+        let mut config = RateLimitConfig::default();
+        config.methods.insert(
+            "lasr_send".to_string(),
+            RateLimit {
+                per_second: 0.0,
+                burst: 5,
+            },
+        );
+        assert!(matches!(
+            limiter.set(config),
+            Err(RateLimitError::InvalidLimit(method)) if method == "lasr_send"
+        ));
+        assert_eq!(limiter.status().limits, RateLimitConfig::default());
+    }
+}
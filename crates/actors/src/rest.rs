@@ -0,0 +1,277 @@
+//! Read-only REST resources served alongside the JSON-RPC methods.
+//!
+//! Each route answers with what the RPC method it maps to answers, as plain
+//! JSON, so a GET from a browser or curl sees the same shapes a JSON-RPC
+//! client does. Failures are answered with the JSON-RPC error object and the
+//! HTTP status its code corresponds to. Lists take the RPC methods' `cursor`
+//! and `limit` as query parameters. Account reads carry an ETag and answer a
+//! matching `If-None-Match` with `304 Not Modified`. Writes are JSON-RPC only.
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use jsonrpsee::types::ErrorObjectOwned as RpcError;
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::LasrRpcServer;
+use lasr_types::AccountState;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use std::net::IpAddr;
+use tower::Service;
+
+use crate::{parse_address, LasrRpcServerImpl, RateLimiter};
+
+/// The paths the REST resources are served under, with the RPC method each
+/// is served by and rate limited as.
+const REST_RESOURCES: [(&str, &str); 4] = [
+    ("/accounts/", "lasr_getAccount"),
+    ("/transactions/", "lasr_getTransactionReceipt"),
+    ("/batches", "lasr_getBatch"),
+    ("/programs", "lasr_getProgram"),
+];
+
+#[derive(Debug, Default, Deserialize)]
+struct PageQuery {
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BatchQuery {
+    full: Option<bool>,
+}
+
+/// The RPC method the REST resource at `path` is served by, if `path` is
+/// one.
+pub fn rest_method(path: &str) -> Option<&'static str> {
+    REST_RESOURCES
+        .iter()
+        .find(|(prefix, _)| path.starts_with(prefix))
+        .map(|(_, method)| *method)
+}
+
+/// The REST resources, answered by `rpc`.
+pub fn rest_router(rpc: LasrRpcServerImpl) -> Router {
+    Router::new()
+        .route("/accounts/:address", get(account))
+        .route("/accounts/:address/tokens/:program_id", get(token))
+        .route("/transactions/:hash", get(transaction))
+        .route("/batches", get(batches))
+        .route("/batches/:id", get(batch))
+        .route("/programs", get(programs))
+        .route("/programs/:id", get(program))
+        .fallback(not_found)
+        .with_state(rpc)
+}
+
+/// Answers `request` from `rest`, holding `client` to the limit of
+/// `method`, the RPC method the resource asked for is served by.
+pub async fn serve_rest(
+    mut rest: Router,
+    method: &str,
+    rate_limiter: &RateLimiter,
+    client: IpAddr,
+    request: hyper::Request<hyper::Body>,
+) -> hyper::Response<hyper::Body> {
+    let response = match rate_limiter.check(client, method) {
+        Ok(()) => rest.call(request).await.unwrap_or_else(|e| match e {}),
+        Err(throttled) => {
+            let retry_after = throttled.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = error(throttled.into());
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            response
+        }
+    };
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    hyper::Response::from_parts(parts, hyper::Body::from(body))
+}
+
+async fn account(
+    State(rpc): State<LasrRpcServerImpl>,
+    Path(address): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let state = match account_state(&rpc, address).await {
+        Ok(state) => state,
+        Err(e) => return error(e),
+    };
+    tagged(&headers, json_body(&state))
+}
+
+async fn token(
+    State(rpc): State<LasrRpcServerImpl>,
+    Path((address, program_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let program = match parse_address("program_id", &program_id) {
+        Ok(program) => program,
+        Err(e) => return error(e.into()),
+    };
+    let mut state = match account_state(&rpc, address).await {
+        Ok(state) => state,
+        Err(e) => return error(e),
+    };
+    match state.programs.remove(&program) {
+        Some(token) => tagged(&headers, json_body(&token)),
+        None => error(not_found_error(format!(
+            "account {} holds no token of {program_id}",
+            state.address.to_full_string()
+        ))),
+    }
+}
+
+async fn transaction(State(rpc): State<LasrRpcServerImpl>, Path(hash): Path<String>) -> Response {
+    let receipt = match rpc.get_transaction_receipt(hash.clone()).await {
+        Ok(receipt) => receipt,
+        Err(e) => return error(e),
+    };
+    match serde_json::from_str::<Value>(&receipt) {
+        Ok(receipt) if receipt["status"]["status"] == "unknown" => {
+            error(not_found_error(format!("no transaction {hash} is known")))
+        }
+        _ => json_response(receipt),
+    }
+}
+
+async fn batches(
+    State(rpc): State<LasrRpcServerImpl>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Response {
+    let Query(page) = match query {
+        Ok(query) => query,
+        Err(e) => return error(invalid_query(e)),
+    };
+    answer(rpc.get_batches(page.cursor, page.limit).await)
+}
+
+async fn batch(
+    State(rpc): State<LasrRpcServerImpl>,
+    Path(id): Path<String>,
+    query: Result<Query<BatchQuery>, QueryRejection>,
+) -> Response {
+    let Query(query) = match query {
+        Ok(query) => query,
+        Err(e) => return error(invalid_query(e)),
+    };
+    let Ok(batch_id) = id.parse::<u64>() else {
+        return error(RpcError::owned(
+            RpcErrorCode::InvalidParams.code(),
+            format!("batch id {id} is not a number"),
+            None::<()>,
+        ));
+    };
+    answer(rpc.get_batch(batch_id, query.full).await)
+}
+
+async fn programs(
+    State(rpc): State<LasrRpcServerImpl>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Response {
+    let Query(page) = match query {
+        Ok(query) => query,
+        Err(e) => return error(invalid_query(e)),
+    };
+    answer(rpc.list_programs(page.cursor, page.limit).await)
+}
+
+async fn program(State(rpc): State<LasrRpcServerImpl>, Path(id): Path<String>) -> Response {
+    answer(rpc.get_program(id).await)
+}
+
+async fn not_found() -> Response {
+    error(not_found_error("no such resource".to_string()))
+}
+
+/// The account at `address`, or not found if there is none.
+async fn account_state(rpc: &LasrRpcServerImpl, address: String) -> Result<AccountState, RpcError> {
+    let state = rpc.get_account(address.clone()).await?;
+    let state: AccountState = serde_json::from_str(&state)
+        .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))?;
+    if !state.found {
+        return Err(not_found_error(format!("no account {address} exists")));
+    }
+    Ok(state)
+}
+
+fn answer(result: Result<String, RpcError>) -> Response {
+    match result {
+        Ok(body) => json_response(body),
+        Err(e) => error(e),
+    }
+}
+
+fn json_body(value: &impl serde::Serialize) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn json_response(body: impl ToString) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// `body` with its ETag, or `304 Not Modified` if the client already holds
+/// it.
+fn tagged(headers: &HeaderMap, body: String) -> Response {
+    let etag = format!("\"{}\"", hex::encode(Keccak256::digest(body.as_bytes())));
+    let etag_header = HeaderValue::from_str(&etag).expect("an ETag is a valid header");
+    let held = headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag);
+    if held {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag_header)]).into_response();
+    }
+    let mut response = json_response(body);
+    response.headers_mut().insert(header::ETAG, etag_header);
+    response
+}
+
+fn error(e: RpcError) -> Response {
+    let mut body = json!({ "code": e.code(), "message": e.message() });
+    if let Some(data) = e.data() {
+        body["data"] = serde_json::from_str(data.get()).unwrap_or_default();
+    }
+    let mut response = json_response(body);
+    *response.status_mut() = status(e.code());
+    response
+}
+
+/// The HTTP status an RPC error code is answered with.
+fn status(code: i32) -> StatusCode {
+    match code {
+        code if code == RpcErrorCode::NotFound.code() => StatusCode::NOT_FOUND,
+        code if code == RpcErrorCode::InvalidParams.code() => StatusCode::BAD_REQUEST,
+        code if code == RpcErrorCode::RateLimited.code() => StatusCode::TOO_MANY_REQUESTS,
+        code if code == RpcErrorCode::Unauthorized.code() => StatusCode::UNAUTHORIZED,
+        code if code == RpcErrorCode::PolicyDenied.code() => StatusCode::FORBIDDEN,
+        code if code == RpcErrorCode::Disabled.code() => StatusCode::NOT_IMPLEMENTED,
+        code if code == RpcErrorCode::ShuttingDown.code() => StatusCode::SERVICE_UNAVAILABLE,
+        code if code == RpcErrorCode::Overloaded.code() => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn not_found_error(message: String) -> RpcError {
+    RpcError::owned(RpcErrorCode::NotFound.code(), message, None::<()>)
+}
+
+fn invalid_query(e: QueryRejection) -> RpcError {
+    RpcError::owned(
+        RpcErrorCode::InvalidParams.code(),
+        e.body_text(),
+        None::<()>,
+    )
+}
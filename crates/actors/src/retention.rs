@@ -0,0 +1,762 @@
+//! Pruning what the node keeps of its batches once they are past its
+//! retention, for `admin_prune` and `admin_getReclaimableSpace`.
+//!
+//! There are two tiers. The blob tier is the bytes of each batch's blob in
+//! the local DA directory. The receipt tier is the receipts and attestations
+//! of each batch's transactions, and their entries in the transaction index.
+//! Each tier is kept for the days after a batch was finalized, or for the
+//! latest batches, its window gives, whichever keeps more; with neither it
+//! is kept forever, which is the default. Receipts are kept at least as long
+//! as blobs, so a receipt always outlives the blob it was proven against.
+//!
+//! Only batches past finality and past the settlement-submission point are
+//! pruned: with settlement, a batch once its receipt says it was submitted
+//! or settled, and never while it is still to be submitted or failed to be.
+//! A batch with no receipt, finalized before the node last started, counts
+//! as submitted once a later one has been. How far each tier has been
+//! pruned is kept in the marks file, written once what it marks is gone, so
+//! a pruning cut short is picked up again where it left off. Batch records
+//! themselves are kept, for `getBatch` to tell clients where on the DA layer
+//! to retrieve what was pruned from.
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use ethereum_types::H256;
+use jsonrpsee::types::ErrorObjectOwned as RpcError;
+use lasr_messages::{AccountCacheMessage, ActorType, BatchStatus, RpcErrorCode};
+use prometheus::{IntCounterVec, IntGaugeVec};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    get_actor_ref, metrics, unix_now, AccountCacheError, BatchIndex, BatchReceipts, BatchRecord,
+    LocalFileDa, TransactionIndex,
+};
+
+pub const DEFAULT_PRUNE_INTERVAL: Duration = Duration::from_secs(3_600);
+pub const DEFAULT_PRUNE_MARKS_PATH: &str = "./pruned.json";
+
+const SECS_PER_DAY: u64 = 86_400;
+
+struct RetentionMetrics {
+    pruned_batches: IntCounterVec,
+    reclaimed_bytes: IntCounterVec,
+    pruned_through: IntGaugeVec,
+}
+
+fn retention_metrics() -> &'static RetentionMetrics {
+    static METRICS: OnceLock<RetentionMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| RetentionMetrics {
+        pruned_batches: metrics::counter_vec(
+            "retention",
+            "pruned_batches_total",
+            "Batches pruned past the node's retention, by tier.",
+            &["tier"],
+        ),
+        reclaimed_bytes: metrics::counter_vec(
+            "retention",
+            "reclaimed_bytes_total",
+            "Bytes reclaimed by pruning, by tier.",
+            &["tier"],
+        ),
+        pruned_through: metrics::gauge_vec(
+            "retention",
+            "pruned_through",
+            "The latest batch pruned, by tier.",
+            &["tier"],
+        ),
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum PruneError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// How long a tier is kept. Of `days` and `batches`, whichever keeps a
+/// batch longer does; with neither, it is kept forever.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionWindow {
+    /// Days after a batch was finalized it is kept for.
+    pub days: Option<u64>,
+    /// How many of the latest batches are kept.
+    pub batches: Option<u64>,
+}
+
+impl RetentionWindow {
+    pub fn is_forever(&self) -> bool {
+        self.days.is_none() && self.batches.is_none()
+    }
+
+    /// Whether `record` is kept, with `latest` the latest batch finalized
+    /// and `now` in seconds since the epoch.
+    pub fn keeps(&self, record: &BatchRecord, latest: u64, now: u64) -> bool {
+        if self.is_forever() {
+            return true;
+        }
+        let by_days = self.days.is_some_and(|days| {
+            now < record
+                .finalized_at
+                .saturating_add(days.saturating_mul(SECS_PER_DAY))
+        });
+        let by_batches = self
+            .batches
+            .is_some_and(|batches| latest.saturating_sub(record.batch_id) < batches);
+        by_days || by_batches
+    }
+
+    /// Whether this window keeps every batch `other` does.
+    pub fn covers(&self, other: &RetentionWindow) -> bool {
+        let at_least = |mine: Option<u64>, theirs: Option<u64>| match (mine, theirs) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(mine), Some(theirs)) => mine >= theirs,
+        };
+        self.is_forever()
+            || (!other.is_forever()
+                && at_least(self.days, other.days)
+                && at_least(self.batches, other.batches))
+    }
+}
+
+/// What the node keeps of its batches, and how often it prunes the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub blobs: RetentionWindow,
+    pub receipts: RetentionWindow,
+    pub interval: Duration,
+    /// Where how far each tier has been pruned is kept.
+    pub marks_path: PathBuf,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            blobs: RetentionWindow::default(),
+            receipts: RetentionWindow::default(),
+            interval: DEFAULT_PRUNE_INTERVAL,
+            marks_path: PathBuf::from(DEFAULT_PRUNE_MARKS_PATH),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn window(&self, tier: PruneTier) -> RetentionWindow {
+        match tier {
+            PruneTier::Blobs => self.blobs,
+            PruneTier::Receipts => self.receipts,
+        }
+    }
+
+    /// Whether anything is ever pruned.
+    pub fn prunes(&self) -> bool {
+        !self.blobs.is_forever() || !self.receipts.is_forever()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PruneTier {
+    Blobs,
+    Receipts,
+}
+
+impl PruneTier {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PruneTier::Blobs => "blobs",
+            PruneTier::Receipts => "receipts",
+        }
+    }
+}
+
+/// The latest batch each tier has been pruned through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneMarks {
+    pub blobs_through: Option<u64>,
+    pub receipts_through: Option<u64>,
+}
+
+impl PruneMarks {
+    pub fn through(&self, tier: PruneTier) -> Option<u64> {
+        match tier {
+            PruneTier::Blobs => self.blobs_through,
+            PruneTier::Receipts => self.receipts_through,
+        }
+    }
+
+    fn mark(&mut self, tier: PruneTier, through: u64) {
+        let mark = match tier {
+            PruneTier::Blobs => &mut self.blobs_through,
+            PruneTier::Receipts => &mut self.receipts_through,
+        };
+        *mark = (*mark).max(Some(through));
+    }
+}
+
+/// What was, or would be, pruned from a tier. `bytes` counts the blob
+/// files of the blob tier, and the transaction index entries of the
+/// receipt tier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TierReport {
+    /// The latest batch pruned, if any was.
+    pub through: Option<u64>,
+    pub batches: u64,
+    pub transactions: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub blobs: TierReport,
+    pub receipts: TierReport,
+}
+
+/// How a tier is kept, how far it has been pruned, and what pruning it now
+/// would reclaim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TierStatus {
+    pub window: RetentionWindow,
+    pub pruned_through: Option<u64>,
+    pub reclaimable: TierReport,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionStatus {
+    pub blobs: TierStatus,
+    pub receipts: TierStatus,
+}
+
+/// Something asked for that was pruned past the node's retention, with
+/// where on the DA layer to retrieve its batch from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pruned {
+    pub tier: PruneTier,
+    pub batch_id: u64,
+    pub blob_commitment: H256,
+    pub blob_index: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<String>,
+}
+
+impl Pruned {
+    fn of(tier: PruneTier, record: &BatchRecord, transaction_hash: Option<&str>) -> Self {
+        Self {
+            tier,
+            batch_id: record.batch_id,
+            blob_commitment: record.blob_commitment,
+            blob_index: record.blob_index,
+            transaction_hash: transaction_hash.map(str::to_string),
+        }
+    }
+}
+
+impl From<Pruned> for RpcError {
+    fn from(pruned: Pruned) -> Self {
+        let what = match pruned.tier {
+            PruneTier::Blobs => "the blob of batch",
+            PruneTier::Receipts => "the receipts of batch",
+        };
+        RpcError::owned(
+            RpcErrorCode::Pruned.code(),
+            format!(
+                "{what} {} are past this node's retention and were pruned, retrieve the batch \
+                 from the DA layer by its blob commitment and index",
+                pruned.batch_id
+            ),
+            Some(pruned),
+        )
+    }
+}
+
+/// Prunes each tier of the batches past the node's retention, and answers
+/// for what it has pruned.
+#[derive(Debug, Clone)]
+pub struct Pruner {
+    policy: RetentionPolicy,
+    batches: BatchIndex,
+    batch_receipts: Option<BatchReceipts>,
+    local_da: Option<LocalFileDa>,
+    transaction_index: Option<TransactionIndex>,
+    marks: Arc<Mutex<PruneMarks>>,
+    /// Held while pruning, so the interval and an operator never prune at
+    /// once.
+    pruning: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Pruner {
+    /// Picks up how far each tier has been pruned from the policy's marks
+    /// file, if there is one.
+    pub fn open(policy: RetentionPolicy, batches: BatchIndex) -> Result<Self, PruneError> {
+        let marks = match std::fs::read(&policy.marks_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                PruneError::Custom(format!(
+                    "failed to read prune marks from {}: {e}",
+                    policy.marks_path.display()
+                ))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => PruneMarks::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let pruner = Self {
+            policy,
+            batches,
+            batch_receipts: None,
+            local_da: None,
+            transaction_index: None,
+            marks: Arc::new(Mutex::new(marks)),
+            pruning: Arc::default(),
+        };
+        pruner.publish(&marks);
+        Ok(pruner)
+    }
+
+    /// Holds batches back from pruning until they are submitted for
+    /// settlement.
+    pub fn with_batch_receipts(mut self, batch_receipts: BatchReceipts) -> Self {
+        self.batch_receipts = Some(batch_receipts);
+        self
+    }
+
+    /// Prunes blobs from `local_da`. Without it there are no blob bytes on
+    /// the node to prune.
+    pub fn with_local_da(mut self, local_da: LocalFileDa) -> Self {
+        self.local_da = Some(local_da);
+        self
+    }
+
+    pub fn with_transaction_index(mut self, transaction_index: TransactionIndex) -> Self {
+        self.transaction_index = Some(transaction_index);
+        self
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    pub fn marks(&self) -> PruneMarks {
+        *self.marks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn pruned_through(&self, tier: PruneTier) -> Option<u64> {
+        self.marks().through(tier)
+    }
+
+    /// Answers `Pruned` if the blob of `record` was pruned.
+    pub fn blob_pruned(&self, record: &BatchRecord) -> Result<(), Pruned> {
+        match self.pruned_through(PruneTier::Blobs) {
+            Some(through) if record.batch_id <= through => {
+                Err(Pruned::of(PruneTier::Blobs, record, None))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Answers `Pruned` if `transaction_hash` went into a batch whose
+    /// receipts were pruned.
+    pub fn receipt_pruned(&self, transaction_hash: &str) -> Result<(), Pruned> {
+        let Some(through) = self.pruned_through(PruneTier::Receipts) else {
+            return Ok(());
+        };
+        match self.batches.find_transaction(transaction_hash, through) {
+            Some(record) => Err(Pruned::of(
+                PruneTier::Receipts,
+                &record,
+                Some(transaction_hash),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// What `prune` would reclaim from each tier now.
+    pub fn reclaimable(&self) -> PruneReport {
+        PruneReport {
+            blobs: self.report(PruneTier::Blobs, &self.prunable(PruneTier::Blobs)),
+            receipts: self.report(PruneTier::Receipts, &self.prunable(PruneTier::Receipts)),
+        }
+    }
+
+    pub fn status(&self) -> RetentionStatus {
+        let reclaimable = self.reclaimable();
+        let marks = self.marks();
+        let status = |tier, reclaimable| TierStatus {
+            window: self.policy.window(tier),
+            pruned_through: marks.through(tier),
+            reclaimable,
+        };
+        RetentionStatus {
+            blobs: status(PruneTier::Blobs, reclaimable.blobs),
+            receipts: status(PruneTier::Receipts, reclaimable.receipts),
+        }
+    }
+
+    /// Prunes each tier of the batches past its window, blobs first, and
+    /// answers what was reclaimed.
+    pub async fn prune(&self) -> Result<PruneReport, PruneError> {
+        let _pruning = self.pruning.lock().await;
+        Ok(PruneReport {
+            blobs: self.prune_blobs()?,
+            receipts: self.prune_receipts()?,
+        })
+    }
+
+    fn prune_blobs(&self) -> Result<TierReport, PruneError> {
+        let records = self.prunable(PruneTier::Blobs);
+        let mut report = self.report(PruneTier::Blobs, &records);
+        let Some(local_da) = &self.local_da else {
+            return Ok(report);
+        };
+        report.bytes = 0;
+        for record in &records {
+            report.bytes += local_da.prune(record.blob_commitment, record.blob_index)?;
+        }
+        self.mark(PruneTier::Blobs, &report)?;
+        Ok(report)
+    }
+
+    fn prune_receipts(&self) -> Result<TierReport, PruneError> {
+        let records = self.prunable(PruneTier::Receipts);
+        let mut report = self.report(PruneTier::Receipts, &records);
+        let Some(through) = report.through else {
+            return Ok(report);
+        };
+        let account_cache =
+            get_actor_ref::<AccountCacheMessage, AccountCacheError>(ActorType::AccountCache)
+                .ok_or_else(|| {
+                    PruneError::Custom("the account cache is not running".to_string())
+                })?;
+        let transaction_hashes = records
+            .iter()
+            .flat_map(|record| record.transaction_hashes.iter().cloned())
+            .collect();
+        account_cache
+            .cast(AccountCacheMessage::PruneReceipts { transaction_hashes })
+            .map_err(|e| PruneError::Custom(format!("failed to prune receipts: {e}")))?;
+        if let Some(transaction_index) = &self.transaction_index {
+            report.bytes = transaction_index
+                .prune(through)
+                .map_err(|e| PruneError::Custom(format!("failed to prune the index: {e}")))?;
+        }
+        self.mark(PruneTier::Receipts, &report)?;
+        Ok(report)
+    }
+
+    /// The batches, oldest first, past `tier`'s window and past the
+    /// settlement-submission point that have not been pruned from it yet.
+    fn prunable(&self, tier: PruneTier) -> Vec<BatchRecord> {
+        let window = self.policy.window(tier);
+        let Some(latest) = self.batches.latest() else {
+            return Vec::new();
+        };
+        if window.is_forever() || (tier == PruneTier::Blobs && self.local_da.is_none()) {
+            return Vec::new();
+        }
+        let now = unix_now();
+        let from = self.pruned_through(tier).map_or(0, |through| through + 1);
+        let mut prunable = Vec::new();
+        for batch_id in from..=latest.batch_id {
+            let Some(record) = self.batches.get(batch_id) else {
+                continue;
+            };
+            if window.keeps(&record, latest.batch_id, now) || !self.submitted(batch_id) {
+                break;
+            }
+            prunable.push(record);
+        }
+        prunable
+    }
+
+    fn submitted(&self, batch_id: u64) -> bool {
+        let Some(batch_receipts) = &self.batch_receipts else {
+            return true;
+        };
+        match batch_receipts.get(batch_id) {
+            Some(receipt) => matches!(
+                receipt.status,
+                BatchStatus::Submitted { .. } | BatchStatus::Settled { .. }
+            ),
+            None => batch_receipts
+                .latest_batch_id()
+                .is_some_and(|latest| batch_id < latest),
+        }
+    }
+
+    fn report(&self, tier: PruneTier, records: &[BatchRecord]) -> TierReport {
+        let through = records.last().map(|record| record.batch_id);
+        let bytes = match tier {
+            PruneTier::Blobs => self
+                .local_da
+                .as_ref()
+                .map(|local_da| {
+                    records
+                        .iter()
+                        .filter_map(|record| {
+                            local_da.blob_size(record.blob_commitment, record.blob_index)
+                        })
+                        .sum()
+                })
+                .unwrap_or_default(),
+            PruneTier::Receipts => match (&self.transaction_index, through) {
+                (Some(transaction_index), Some(through)) => transaction_index.size_through(through),
+                _ => 0,
+            },
+        };
+        TierReport {
+            through,
+            batches: records.len() as u64,
+            transactions: records
+                .iter()
+                .map(|record| record.transaction_hashes.len() as u64)
+                .sum(),
+            bytes,
+        }
+    }
+
+    /// Marks `tier` pruned through what `report` pruned, written aside and
+    /// moved over the marks file so a crash leaves one or the other whole.
+    fn mark(&self, tier: PruneTier, report: &TierReport) -> Result<(), PruneError> {
+        let Some(through) = report.through else {
+            return Ok(());
+        };
+        let mut marks = self.marks.lock().unwrap_or_else(|e| e.into_inner());
+        let mut marked = *marks;
+        marked.mark(tier, through);
+        let json = serde_json::to_vec(&marked)
+            .map_err(|e| PruneError::Custom(format!("failed to encode prune marks: {e}")))?;
+        let written = self.policy.marks_path.with_extension("tmp");
+        std::fs::write(&written, json)?;
+        std::fs::File::open(&written)?.sync_all()?;
+        std::fs::rename(&written, &self.policy.marks_path)?;
+        *marks = marked;
+        drop(marks);
+
+        let metrics = retention_metrics();
+        metrics
+            .pruned_batches
+            .with_label_values(&[tier.as_str()])
+            .inc_by(report.batches);
+        metrics
+            .reclaimed_bytes
+            .with_label_values(&[tier.as_str()])
+            .inc_by(report.bytes);
+        self.publish(&marked);
+        Ok(())
+    }
+
+    fn publish(&self, marks: &PruneMarks) {
+        for tier in [PruneTier::Blobs, PruneTier::Receipts] {
+            if let Some(through) = marks.through(tier) {
+                retention_metrics()
+                    .pruned_through
+                    .with_label_values(&[tier.as_str()])
+                    .set(through as i64);
+            }
+        }
+    }
+}
+
+/// Prunes with `pruner` every interval of its policy.
+pub async fn prune_periodically(pruner: Pruner) {
+    let interval = pruner.policy().interval;
+    loop {
+        tokio::time::sleep(interval).await;
+        match pruner.prune().await {
+            Ok(report) if report.blobs.through.is_some() || report.receipts.through.is_some() => {
+                tracing::info!(
+                    "pruned blobs through {:?} and receipts through {:?}, reclaiming {} bytes",
+                    report.blobs.through,
+                    report.receipts.through,
+                    report.blobs.bytes + report.receipts.bytes
+                );
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("failed to prune: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod retention_tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("retention_{}_{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn record(batch_id: u64, finalized_at: u64) -> BatchRecord {
+        BatchRecord {
+            batch_id,
+            state_root: H256::from_low_u64_be(batch_id),
+            previous_state_root: H256::zero(),
+            transactions_root: H256::zero(),
+            events_root: H256::zero(),
+            blob_commitment: H256::from_low_u64_be(batch_id + 100),
+            blob_index: batch_id as u128,
+            withdrawals: Vec::new(),
+            account_count: 1,
+            transaction_hashes: vec![format!("0x{batch_id:02x}")],
+            sealed_at: finalized_at,
+            finalized_at,
+        }
+    }
+
+    fn policy(dir: &Path, batches: u64) -> RetentionPolicy {
+        RetentionPolicy {
+            blobs: RetentionWindow {
+                days: None,
+                batches: Some(batches),
+            },
+            marks_path: dir.join("pruned.json"),
+            ..RetentionPolicy::default()
+        }
+    }
+
+    /// Stores `count` batches' blobs in `local_da`, and records them.
+    fn stored(local_da: &LocalFileDa, count: u64) -> BatchIndex {
+        let batches = BatchIndex::default();
+        for batch_id in 0..count {
+            let response = local_da.store(&format!("batch {batch_id}")).unwrap();
+            let proof = local_da.proof(&response.request_id()).unwrap();
+            let blob_commitment = H256::from_slice(
+                &base64::decode(proof.batch_metadata().batch_header_hash().to_string()).unwrap(),
+            );
+            batches
+                .finalized(BatchRecord {
+                    blob_commitment,
+                    blob_index: proof.blob_index(),
+                    ..record(batch_id, 0)
+                })
+                .unwrap();
+        }
+        batches
+    }
+
+    fn set(receipts: &BatchReceipts, batches: &BatchIndex, batch_id: u64, status: BatchStatus) {
+        receipts.set(&batches.get(batch_id).unwrap().header(), status);
+    }
+
+    fn submitted() -> BatchStatus {
+        BatchStatus::Submitted {
+            transaction_hash: H256::zero(),
+            attempts: 1,
+        }
+    }
+
+    #[test]
+    fn a_window_keeps_whichever_of_days_and_batches_keeps_more() {
+        let now = 100 * SECS_PER_DAY;
+        let old = record(1, now - 10 * SECS_PER_DAY);
+        let window = RetentionWindow {
+            days: Some(7),
+            batches: Some(5),
+        };
+        assert!(window.keeps(&old, 5, now), "among the latest five batches");
+        assert!(!window.keeps(&old, 6, now));
+        let longer = RetentionWindow {
+            days: Some(14),
+            batches: Some(5),
+        };
+        assert!(longer.keeps(&old, 60, now), "finalized within the days");
+        assert!(RetentionWindow::default().keeps(&old, 1_000, u64::MAX));
+
+        let forever = RetentionWindow::default();
+        assert!(forever.covers(&window));
+        assert!(!window.covers(&forever));
+        assert!(longer.covers(&window));
+        assert!(!window.covers(&longer));
+    }
+
+    #[tokio::test]
+    async fn only_batches_past_their_window_and_submitted_are_pruned() {
+        let dir = temp_dir("submitted");
+        let local_da = LocalFileDa::open(dir.join("da")).unwrap();
+        let batches = stored(&local_da, 6);
+        let receipts = BatchReceipts::default();
+        for batch_id in 0..6 {
+            let status = if batch_id < 2 {
+                submitted()
+            } else {
+                BatchStatus::Finalized
+            };
+            set(&receipts, &batches, batch_id, status);
+        }
+        let pruner = Pruner::open(policy(&dir, 2), batches.clone())
+            .unwrap()
+            .with_batch_receipts(receipts.clone())
+            .with_local_da(local_da.clone());
+
+        // Of the four past the window, only the two submitted go.
+        let report = pruner.prune().await.unwrap();
+        assert_eq!(report.blobs.through, Some(1));
+        assert_eq!(report.blobs.batches, 2);
+        assert_eq!(report.receipts, TierReport::default(), "kept forever");
+
+        // A failed submission holds back every batch after it.
+        let failed = BatchStatus::Failed {
+            reason: "reverted".to_string(),
+        };
+        set(&receipts, &batches, 2, failed);
+        set(&receipts, &batches, 3, submitted());
+        assert_eq!(pruner.reclaimable().blobs.batches, 0);
+        set(&receipts, &batches, 2, submitted());
+        assert_eq!(pruner.reclaimable().blobs.through, Some(3));
+        assert_eq!(pruner.prune().await.unwrap().blobs.through, Some(3));
+        let kept = batches.get(4).unwrap();
+        assert!(local_da
+            .retrieve(kept.blob_commitment, kept.blob_index)
+            .is_ok());
+
+        // How far it got survives a restart.
+        let reopened = Pruner::open(policy(&dir, 2), batches)
+            .unwrap()
+            .with_local_da(local_da);
+        assert_eq!(reopened.pruned_through(PruneTier::Blobs), Some(3));
+        assert_eq!(reopened.pruned_through(PruneTier::Receipts), None);
+        assert_eq!(reopened.reclaimable().blobs.batches, 0);
+    }
+
+    #[tokio::test]
+    async fn pruned_blobs_answer_where_to_retrieve_them() {
+        let dir = temp_dir("blobs");
+        let local_da = LocalFileDa::open(dir.join("da")).unwrap();
+        let batches = stored(&local_da, 3);
+        let pruner = Pruner::open(policy(&dir, 1), batches.clone())
+            .unwrap()
+            .with_local_da(local_da.clone());
+
+        let reclaimable = pruner.reclaimable().blobs;
+        let report = pruner.prune().await.unwrap().blobs;
+        assert_eq!(report, reclaimable);
+        assert_eq!(report.through, Some(1));
+        assert!(report.bytes > 0);
+
+        let first = batches.get(0).unwrap();
+        assert!(local_da
+            .retrieve(first.blob_commitment, first.blob_index)
+            .is_err());
+        assert!(pruner.blob_pruned(&batches.get(2).unwrap()).is_ok());
+        let pruned = pruner.blob_pruned(&first).unwrap_err();
+        assert_eq!(pruned.blob_commitment, first.blob_commitment);
+        assert_eq!(pruned.blob_index, first.blob_index);
+        let error = RpcError::from(pruned);
+        assert_eq!(error.code(), RpcErrorCode::Pruned.code());
+        assert!(error.data().unwrap().get().contains("blobCommitment"));
+    }
+}
@@ -0,0 +1,284 @@
+//! Retrying calls that failed through no fault of their sender.
+//!
+//! A call the executor turned away because it was saturated, was running
+//! when it restarted, whose artifact could not be prepared, or that ran past
+//! its deadline and was cancelled before producing outputs, is handed back
+//! to the pre-call graph and run again after a backoff that doubles with
+//! each attempt. Once it has been retried as often as the policy allows, it
+//! is parked in the dead letters with every failure it had, and its sender
+//! is answered with the last. Every other failure is down to the call itself
+//! and is final the first time.
+use crate::{metrics, unix_now};
+use lasr_messages::RetryReason;
+use prometheus::{IntCounter, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries of one call before it is failed.
+    pub max_attempts: u32,
+    /// The wait before the first retry.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The wait before retry `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryStatus {
+    pub saturated: u64,
+    pub restarting: u64,
+    pub artifact_unavailable: u64,
+    pub timed_out: u64,
+    /// Calls failed once they had been retried as often as allowed.
+    pub exhausted: u64,
+}
+
+struct CallRetryMetrics {
+    retried: IntCounterVec,
+    exhausted: IntCounter,
+}
+
+fn call_retry_metrics() -> &'static CallRetryMetrics {
+    static METRICS: OnceLock<CallRetryMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| CallRetryMetrics {
+        retried: metrics::counter_vec(
+            "scheduler",
+            "call_retries_total",
+            "Calls retried after a failure through no fault of their sender, by reason.",
+            &["reason"],
+        ),
+        exhausted: metrics::counter(
+            "scheduler",
+            "call_retries_exhausted_total",
+            "Calls failed once they had been retried as often as allowed.",
+        ),
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct RetryMetrics {
+    saturated: AtomicU64,
+    restarting: AtomicU64,
+    artifact_unavailable: AtomicU64,
+    timed_out: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+impl RetryMetrics {
+    fn counter(&self, reason: RetryReason) -> &AtomicU64 {
+        match reason {
+            RetryReason::Saturated => &self.saturated,
+            RetryReason::Restarting => &self.restarting,
+            RetryReason::ArtifactUnavailable => &self.artifact_unavailable,
+            RetryReason::TimedOut => &self.timed_out,
+        }
+    }
+
+    /// Retries made after a failure for `reason`.
+    pub fn retried(&self, reason: RetryReason) -> u64 {
+        self.counter(reason).load(Ordering::Relaxed)
+    }
+
+    pub fn exhausted(&self) -> u64 {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> RetryStatus {
+        RetryStatus {
+            saturated: self.retried(RetryReason::Saturated),
+            restarting: self.retried(RetryReason::Restarting),
+            artifact_unavailable: self.retried(RetryReason::ArtifactUnavailable),
+            timed_out: self.retried(RetryReason::TimedOut),
+            exhausted: self.exhausted(),
+        }
+    }
+}
+
+/// A failure of a call through no fault of its sender.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryFailure {
+    pub reason: RetryReason,
+    pub description: String,
+    /// Seconds since the epoch the call failed at.
+    pub failed_at: u64,
+}
+
+/// The failures of each call still being retried.
+#[derive(Debug, Default)]
+pub struct CallRetries {
+    policy: RetryPolicy,
+    attempts: HashMap<String, Vec<RetryFailure>>,
+    metrics: Arc<RetryMetrics>,
+}
+
+impl CallRetries {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            attempts: HashMap::new(),
+            metrics: Arc::new(RetryMetrics::default()),
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<RetryMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Counts a failure of `transaction_hash` for `reason`, returning the
+    /// wait before it is retried, or `None` if it has been retried as often
+    /// as it may be.
+    pub fn next(&mut self, transaction_hash: &str, reason: RetryReason) -> Option<Duration> {
+        self.fail(transaction_hash, reason, String::new()).ok()
+    }
+
+    /// Counts a failure of `transaction_hash` for `reason`, returning the
+    /// wait before it is retried, or every failure it had, this one last, if
+    /// it has been retried as often as it may be.
+    pub fn fail(
+        &mut self,
+        transaction_hash: &str,
+        reason: RetryReason,
+        description: String,
+    ) -> Result<Duration, Vec<RetryFailure>> {
+        let failure = RetryFailure {
+            reason,
+            description,
+            failed_at: unix_now(),
+        };
+        let failures = self
+            .attempts
+            .entry(transaction_hash.to_string())
+            .or_default();
+        failures.push(failure);
+        let attempt = failures.len() as u32;
+        if attempt > self.policy.max_attempts {
+            let failures = self.attempts.remove(transaction_hash).unwrap_or_default();
+            self.metrics.exhausted.fetch_add(1, Ordering::Relaxed);
+            call_retry_metrics().exhausted.inc();
+            return Err(failures);
+        }
+        self.metrics.counter(reason).fetch_add(1, Ordering::Relaxed);
+        let label = match reason {
+            RetryReason::Saturated => "saturated",
+            RetryReason::Restarting => "restarting",
+            RetryReason::ArtifactUnavailable => "artifact_unavailable",
+            RetryReason::TimedOut => "timed_out",
+        };
+        call_retry_metrics()
+            .retried
+            .with_label_values(&[label])
+            .inc();
+        Ok(self.policy.backoff(attempt))
+    }
+
+    /// Forgets the retries of a call that has completed or failed for good.
+    pub fn clear(&mut self, transaction_hash: &str) {
+        self.attempts.remove(transaction_hash);
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_with_each_attempt() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(100),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert!(policy.backoff(u32::MAX) >= policy.backoff(17));
+    }
+
+    #[test]
+    fn retries_stop_after_the_most_attempts() {
+        let metrics = Arc::new(RetryMetrics::default());
+        let mut retries = CallRetries::new(RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::from_millis(10),
+        })
+        .with_metrics(Arc::clone(&metrics));
+
+        assert_eq!(
+            retries.next("a", RetryReason::Saturated),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(
+            retries.next("a", RetryReason::Restarting),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(retries.next("a", RetryReason::Restarting), None);
+        // A call failed for good starts over if it is submitted again.
+        assert_eq!(
+            retries.next("a", RetryReason::ArtifactUnavailable),
+            Some(Duration::from_millis(10))
+        );
+        retries.clear("a");
+        assert_eq!(
+            retries.next("a", RetryReason::Saturated),
+            Some(Duration::from_millis(10))
+        );
+
+        assert_eq!(
+            metrics.status(),
+            RetryStatus {
+                saturated: 2,
+                restarting: 1,
+                artifact_unavailable: 1,
+                timed_out: 0,
+                exhausted: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn an_exhausted_call_hands_back_every_failure_it_had() {
+        let mut retries = CallRetries::new(RetryPolicy {
+            max_attempts: 1,
+            backoff: Duration::from_millis(10),
+        });
+        assert_eq!(
+            retries.fail("a", RetryReason::Saturated, "full".to_string()),
+            Ok(Duration::from_millis(10))
+        );
+        let failures = retries
+            .fail("a", RetryReason::Restarting, "restarted".to_string())
+            .unwrap_err();
+        assert_eq!(
+            failures
+                .iter()
+                .map(|failure| (failure.reason, failure.description.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (RetryReason::Saturated, "full"),
+                (RetryReason::Restarting, "restarted"),
+            ]
+        );
+        assert!(failures[0].failed_at <= failures[1].failed_at);
+    }
+}
@@ -1,27 +1,309 @@
 use async_trait::async_trait;
-use std::str::FromStr;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
 use tokio::sync::mpsc::Sender;
+use tracing::Instrument;
 
-use crate::{create_handler, handle_actor_response, process_group_changed, Coerce};
-use jsonrpsee::types::{
-    error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
-    ErrorObjectOwned as RpcError,
+use crate::{
+    check_signer, check_valid_at, create_handler, get_account, handle_actor_response, lifecycle,
+    parse_address, parse_hash, process_group_changed, release_debit, reserve_debit, simulate,
+    unix_now, verse_total_supply, Admission, Admitted, Batch, BatchFeed, BatchIndex, BatchInfo,
+    BatchNotification, BatchReceipts, BatchRecord, Coerce, ComponentStatus, DaHealth, DaStatus,
+    DeadLetters, EmittedEvent, EventLog, Faucet, FaucetError, Inclusion, IndexedTransaction,
+    InputLimits, KeyFingerprint, KeyRole, LifecycleStage, LoadShedder, MailboxMonitor,
+    MailboxStatus, MempoolMetrics, MempoolStatus, NodeKeys, NodeKeysError, Notifications,
+    OutputLimits, Page, PaginationError, Paginator, PolicyConfig, PolicyGate, ProgramInfo,
+    ProgramMetric, ProgramRegistry, ProgramStats, Pruner, RateLimitConfig, RateLimiter, Readiness,
+    RetryMetrics, RetryStatus, Shutdown, Subscription, Throttled, TokenDelta,
+    TransactionInclusions, TransactionIndex, TransactionRoute, ETH_ADDR, VERSE_ADDR,
 };
+use eo_listener::{
+    EventFilterConfig, EventFilters, ProviderMetrics, SettlementBackfill, SettlementProvidersStatus,
+};
+use ethereum_types::H256;
+use ethers_core::{
+    types::{
+        transaction::eip2718::TypedTransaction, Bytes, TransactionReceipt as EthTransactionReceipt,
+        H160, U256 as EthU256, U64,
+    },
+    utils::rlp::Rlp,
+};
+use jsonrpsee::{
+    core::{JsonValue, SubscriptionResult},
+    server::BatchRequestConfig,
+    types::ErrorObjectOwned as RpcError,
+    PendingSubscriptionSink, SubscriptionMessage, SubscriptionSink,
+};
+use lasr_compute::ExecutionLogs;
 use lasr_messages::{
-    ActorName, ActorType, RpcMessage, RpcRequestMethod, RpcResponseError, SchedulerMessage,
-    SupervisorType, TransactionResponse,
+    ActorName, ActorType, BatchReceipt, BatchStatus, DaClientMessage, EngineMessage,
+    ExecutorHealth, ExecutorMessage, ExecutorStatus, InclusionProof, PendingStatus,
+    PendingTransactionMessage, PendingTransactionSummary, RpcErrorCode, RpcMessage,
+    RpcRequestMethod, RpcResponseError, SchedulerMessage, SupervisorType, TransactionResponse,
+};
+use lasr_rpc::{AdminRpcServer, EthRpcServer, LasrRpcServer};
+use lasr_types::{
+    hex_quantity, Account, AccountState, Address, EventFilter, ExecutionAttestation, Payload,
+    ProgramEvent, StorageQuota, Transaction, TransactionType, U256,
 };
-use lasr_rpc::LasrRpcServer;
-use lasr_types::{Address, Transaction};
 use ractor::{
-    concurrency::oneshot, Actor, ActorCell, ActorProcessingErr, ActorRef, RpcReplyPort,
-    SupervisionEvent,
+    concurrency::oneshot, rpc::CallResult, Actor, ActorCell, ActorProcessingErr, ActorRef,
+    RpcReplyPort, SupervisionEvent,
 };
+use serde::Serialize;
 use thiserror::Error;
 
-#[derive(Debug)]
+/// How long requests answered by the executor or scheduler wait on them,
+/// e.g. before `getNodeStatus` reports the executor as unresponsive.
+const EXECUTOR_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long `getBatch` waits on the DA layer for the blob of a batch asked
+/// for in full.
+const BATCH_BLOB_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Calls accepted in one batch request by default.
+pub const DEFAULT_RPC_MAX_BATCH_SIZE: u32 = 100;
+
+/// Bytes a request body is turned away past by default, large enough for a
+/// program taking the most input `MAX_PROGRAM_INPUT_BYTES` allows.
+pub const DEFAULT_RPC_MAX_REQUEST_BYTES: u32 = 5 * 1024 * 1024;
+
+/// What one request to the RPC server may hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Calls accepted in one batch request, and no batches if 0.
+    pub max_batch_size: u32,
+    /// Bytes a request body is turned away past, before any of it is parsed.
+    pub max_request_bytes: u32,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_batch_size: DEFAULT_RPC_MAX_BATCH_SIZE,
+            max_request_bytes: DEFAULT_RPC_MAX_REQUEST_BYTES,
+        }
+    }
+}
+
+impl RequestLimits {
+    /// The batch requests the RPC server accepts. The calls in a batch are
+    /// run concurrently and answered in one array, each with the id it was
+    /// sent with and its own result or error, leaving out the notifications
+    /// in it. A batch of more calls than allowed is turned away whole.
+    pub fn batch_config(&self) -> BatchRequestConfig {
+        match self.max_batch_size {
+            0 => BatchRequestConfig::Disabled,
+            limit => BatchRequestConfig::Limit(limit),
+        }
+    }
+}
+
+/// The chain the `eth` methods answer for unless configured otherwise, the
+/// one transactions are signed for by default.
+pub const DEFAULT_ETH_CHAIN_ID: u64 = lasr_types::DEFAULT_CHAIN_ID;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeStatus {
+    executor: ExecutorHealth,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settlement_providers: Option<SettlementProvidersStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mempool: Option<MempoolStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retries: Option<RetryStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeSummary {
+    versions: NodeVersions,
+    chain_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latest_batch_id: Option<u64>,
+    /// Transactions in the pending pool.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending_transactions: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    da: Option<DaStatus>,
+    /// The keys the node signs with, and those it retired.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keys: Option<Vec<KeyFingerprint>>,
+    /// How far behind each core actor is, by actor.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    mailboxes: BTreeMap<String, MailboxStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NodeVersions {
+    node: &'static str,
+    jsonrpc: &'static str,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+enum TransactionStatus {
+    Pending,
+    #[serde(rename_all = "camelCase")]
+    Queued {
+        waiting_on: U256,
+    },
+    /// Held until `valid_after`, the earliest it may be applied.
+    #[serde(rename_all = "camelCase")]
+    Scheduled {
+        valid_after: u64,
+    },
+    Executing,
+    /// Applied, in a batch or waiting on one.
+    Included,
+    /// In a batch settled on the EO contract.
+    Settled,
+    Failed {
+        description: String,
+        /// What kind of failure it was, `exhaustedRetries` for a call parked
+        /// in the dead letters.
+        reason: RpcErrorCode,
+    },
+    Expired,
+    #[serde(rename_all = "camelCase")]
+    Replaced {
+        replaced_by: String,
+    },
+    Unknown,
+}
+
+/// What `getTransactionReceipt` answers. Fields a transaction has not got to
+/// yet are null, or empty for `token_deltas`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionReceipt {
+    transaction_hash: String,
+    status: TransactionStatus,
+    batch_id: Option<u64>,
+    blob_commitment: Option<H256>,
+    /// That the transaction is in batch `batch_id`, checked against the
+    /// batch's header with `verify_receipt`.
+    inclusion_proof: Option<InclusionProof>,
+    token_deltas: Vec<TokenDelta>,
+    events: Vec<ProgramEvent>,
+    revert_reason: Option<String>,
+    attestation: Option<ExecutionAttestation>,
+}
+
+/// A notification sent to a `subscribe` subscriber.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    #[serde(flatten)]
+    event: NotificationEvent,
+    /// Notifications missed before this one because the subscriber fell
+    /// behind.
+    dropped: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+enum NotificationEvent {
+    AccountChanged {
+        account: AccountState,
+    },
+    NewBatch {
+        batch: BatchReceipt,
+    },
+    TransactionStatus {
+        transaction_hash: String,
+        status: TransactionStatus,
+    },
+}
+
+/// An event sent to a `subscribeEvents` subscriber.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EventNotification {
+    #[serde(flatten)]
+    event: EmittedEvent,
+    /// Events missed before this one because the subscriber fell behind.
+    dropped: u64,
+}
+
+/// The error data of a submission of a transaction submitted before.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DuplicateTransaction {
+    transaction_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receipt: Option<TransactionResponse>,
+}
+
+/// The error data of a call whose program reverted.
+#[derive(Debug, Serialize)]
+struct RevertReason {
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FaucetResponse {
+    transaction_hash: String,
+    address: Address,
+    program_id: Address,
+    amount: U256,
+}
+
+#[derive(Debug, Serialize)]
+struct PurgedDeadLetters {
+    purged: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AttestationResponse {
+    attestation: ExecutionAttestation,
+    trusted: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct LasrRpcServerImpl {
     proxy: ActorRef<RpcMessage>,
+    execution_logs: ExecutionLogs,
+    node_keys: Option<NodeKeys>,
+    admission: Admission,
+    policy: PolicyGate,
+    settlement_providers: Option<Arc<ProviderMetrics>>,
+    mempool: Option<Arc<MempoolMetrics>>,
+    retries: Option<Arc<RetryMetrics>>,
+    event_filters: Option<EventFilters>,
+    settlement_backfill: Option<SettlementBackfill>,
+    batch_receipts: Option<BatchReceipts>,
+    inclusions: Option<TransactionInclusions>,
+    programs: Option<ProgramRegistry>,
+    batches: Option<BatchIndex>,
+    transaction_index: Option<TransactionIndex>,
+    event_log: Option<EventLog>,
+    batch_feed: Option<BatchFeed>,
+    dead_letters: Option<DeadLetters>,
+    program_stats: Option<ProgramStats>,
+    pruner: Option<Pruner>,
+    faucet: Faucet,
+    notifications: Notifications,
+    eth_chain_id: u64,
+    rate_limiter: Option<RateLimiter>,
+    da_health: Option<DaHealth>,
+    shutdown: Shutdown,
+    load_shedder: LoadShedder,
+    mailboxes: MailboxMonitor,
+    output_limits: OutputLimits,
+    storage_quota: StorageQuota,
+    paginator: Paginator,
+    /// Whether `simulatePayload` simulates payloads nobody signed.
+    unsigned_simulation: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -42,9 +324,13 @@ impl LasrRpcServerActor {
         data: RpcMessage,
         reply: RpcReplyPort<RpcMessage>,
     ) -> Result<(), RpcError> {
-        reply
-            .send(data)
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, format!("{:?}", e), None::<()>))
+        reply.send(data).map_err(|e| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                format!("{:?}", e),
+                None::<()>,
+            )
+        })
     }
 
     fn handle_call_request(
@@ -64,11 +350,13 @@ impl LasrRpcServerActor {
     fn handle_send_request(
         scheduler: ActorRef<SchedulerMessage>,
         transaction: Transaction,
+        replace: bool,
         reply: RpcReplyPort<RpcMessage>,
     ) -> Result<(), ActorProcessingErr> {
         Ok(scheduler
             .cast(SchedulerMessage::Send {
                 transaction,
+                replace,
                 rpc_reply: reply,
             })
             .map_err(Box::new)?)
@@ -107,7 +395,7 @@ impl LasrRpcServerActor {
         let scheduler = LasrRpcServerActor::get_scheduler()
             .map_err(Box::new)?
             .ok_or(RpcError::owned(
-                INTERNAL_ERROR_CODE,
+                RpcErrorCode::Internal.code(),
                 "Unable to acquire scheduler actor".to_string(),
                 None::<()>,
             ))
@@ -116,9 +404,10 @@ impl LasrRpcServerActor {
             RpcRequestMethod::Call { transaction } => {
                 LasrRpcServerActor::handle_call_request(scheduler, transaction, reply)
             }
-            RpcRequestMethod::Send { transaction } => {
-                LasrRpcServerActor::handle_send_request(scheduler, transaction, reply)
-            }
+            RpcRequestMethod::Send {
+                transaction,
+                replace,
+            } => LasrRpcServerActor::handle_send_request(scheduler, transaction, replace, reply),
             RpcRequestMethod::RegisterProgram { transaction } => {
                 LasrRpcServerActor::handle_register_program_request(scheduler, transaction, reply)
             }
@@ -134,7 +423,7 @@ impl LasrRpcServerActor {
         }
 
         Err(RpcError::owned(
-            INTERNAL_ERROR_CODE,
+            RpcErrorCode::Internal.code(),
             "unable to acquire scheduler".to_string(),
             None::<()>,
         ))
@@ -148,119 +437,67 @@ impl LasrRpcServer for LasrRpcServerImpl {
         // this should lead to the scheduling of a compute and validation
         // task with the scheduler
         tracing::info!("Received RPC `call` method");
-        let (tx, rx) = oneshot();
-        let reply = RpcReplyPort::from(tx);
-        self.send_rpc_call_method_to_self(transaction, reply)
-            .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>))?;
-
-        let handler = create_handler!(rpc_response, call);
-
-        match handle_actor_response(rx, handler)
-            .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, format!("Error: {e}"), None::<()>))
-        {
-            Ok(resp) => match resp {
-                TransactionResponse::AsyncCallResponse(transaction_hash) => Ok(transaction_hash),
-                TransactionResponse::CallResponse(account) => {
-                    let account_str = serde_json::to_string(&account).map_err(|e| {
-                        RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
-                    })?;
-                    return Ok(account_str);
-                }
-                TransactionResponse::TransactionError(rpc_response_error) => {
-                    return Err(RpcError::owned(
-                        INTERNAL_ERROR_CODE,
-                        format!("Error: {0}", rpc_response_error.description),
-                        None::<()>,
-                    ))
-                }
-                _ => {
-                    return Err(RpcError::owned(
-                        INVALID_PARAMS_CODE,
-                        "invalid response to `call` method".to_string(),
-                        None::<()>,
-                    ))
-                }
-            },
-            Err(e) => {
-                return Err(RpcError::owned(
-                    INTERNAL_ERROR_CODE,
-                    e.to_string(),
-                    None::<()>,
-                ))
-            }
-        }
+        let transaction_hash = transaction.hash_string();
+        in_lifecycle(&transaction_hash, self.submit_call(transaction)).await
     }
 
     async fn send(&self, transaction: Transaction) -> Result<String, RpcError> {
         tracing::info!("Received RPC send method");
-        let (tx, rx) = oneshot();
-        let reply = RpcReplyPort::from(tx);
-
-        self.send_rpc_send_method_to_self(transaction, reply)
-            .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>))?;
+        let transaction_hash = transaction.hash_string();
+        in_lifecycle(&transaction_hash, self.submit_send(transaction, false)).await
+    }
 
-        let handler = create_handler!(rpc_response, send);
+    async fn replace(&self, transaction: Transaction) -> Result<String, RpcError> {
+        tracing::info!("Received RPC replace method");
+        let transaction_hash = transaction.hash_string();
+        in_lifecycle(&transaction_hash, self.submit_send(transaction, true)).await
+    }
 
-        match handle_actor_response(rx, handler)
-            .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, format!("Error: {e}"), None::<()>))
-        {
-            Ok(resp) => match resp {
-                TransactionResponse::SendResponse(token) => {
-                    return serde_json::to_string(&token).map_err(|e| {
-                        RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
-                    })
-                }
-                TransactionResponse::TransactionError(rpc_response_error) => {
-                    tracing::error!("Returning error to client: {}", &rpc_response_error);
-                    return Err(RpcError::owned(
-                        INTERNAL_ERROR_CODE,
-                        format!("Error: {0}", rpc_response_error.description),
-                        None::<()>,
-                    ));
-                }
-                _ => {
-                    return Err(RpcError::owned(
-                        INVALID_PARAMS_CODE,
-                        "invalid response to `send` method".to_string(),
-                        None::<()>,
-                    ))
-                }
-            },
-            Err(e) => {
-                return Err(RpcError::owned(
-                    INTERNAL_ERROR_CODE,
-                    e.to_string(),
-                    None::<()>,
-                ))
-            }
-        }
+    async fn send_raw(&self, raw: String) -> Result<String, RpcError> {
+        tracing::info!("Received RPC sendRaw method");
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        let bytes = hex::decode(raw.strip_prefix("0x").unwrap_or(&raw))
+            .map_err(|e| invalid(format!("raw transaction is not hex: {e}")))?;
+        let transaction: Transaction = borsh::from_slice(&bytes)
+            .map_err(|e| invalid(format!("not a borsh encoded transaction: {e}")))?;
+        let transaction_hash = transaction.hash_string();
+        self.submit(transaction).await?;
+        Ok(transaction_hash)
     }
 
     async fn register_program(&self, transaction: Transaction) -> Result<String, RpcError> {
         tracing::info!("Received RPC registerProgram method");
+        routed(&transaction, TransactionRoute::Register)?;
+        let _admitted = self.admit(&transaction, None).await?;
+        // Registrations are not held until they are valid.
+        check_valid_at(&transaction, unix_now())
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), Some(e)))?;
+        let transaction_hash = transaction.hash_string();
         let (tx, rx) = oneshot();
         let reply = RpcReplyPort::from(tx);
 
         self.send_rpc_register_program_method_to_self(transaction, reply)
             .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>))?;
+            .map_err(|e| {
+                RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+            })?;
 
         let handler = create_handler!(rpc_response, registerProgram);
 
-        match handle_actor_response(rx, handler)
-            .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, format!("Error: {e}"), None::<()>))
-        {
+        match handle_actor_response(rx, handler).await.map_err(|e| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                format!("Error: {e}"),
+                None::<()>,
+            )
+        }) {
             Ok(resp) => match resp {
                 TransactionResponse::RegisterProgramResponse(opt) => match opt {
                     Some(program_id) => return Ok(program_id),
                     None => {
                         return Err(RpcError::owned(
-                            INTERNAL_ERROR_CODE,
+                            RpcErrorCode::Internal.code(),
                             "program registeration failed to return program_id".to_string(),
                             None::<()>,
                         ))
@@ -268,15 +505,17 @@ impl LasrRpcServer for LasrRpcServerImpl {
                 },
                 TransactionResponse::TransactionError(rpc_response_error) => {
                     tracing::error!("Returning error to client: {}", &rpc_response_error);
-                    return Err(RpcError::owned(
-                        INTERNAL_ERROR_CODE,
-                        format!("Error: {0}", rpc_response_error.description),
-                        None::<()>,
-                    ));
+                    return Err(transaction_error(rpc_response_error));
+                }
+                TransactionResponse::AlreadyPending => {
+                    return Err(already_pending(transaction_hash))
+                }
+                TransactionResponse::AlreadyIncluded(receipt) => {
+                    return Err(already_included(transaction_hash, *receipt))
                 }
                 _ => {
                     return Err(RpcError::owned(
-                        INVALID_PARAMS_CODE,
+                        RpcErrorCode::InvalidParams.code(),
                         "received invalid response for `registerProgram` method".to_string(),
                         None::<()>,
                     ));
@@ -284,7 +523,7 @@ impl LasrRpcServer for LasrRpcServerImpl {
             },
             Err(e) => {
                 return Err(RpcError::owned(
-                    INTERNAL_ERROR_CODE,
+                    RpcErrorCode::Internal.code(),
                     e.to_string(),
                     None::<()>,
                 ))
@@ -292,114 +531,2080 @@ impl LasrRpcServer for LasrRpcServerImpl {
         }
     }
 
+    async fn simulate_transaction(&self, transaction: Transaction) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC simulateTransaction method");
+        let sender = get_account(transaction.from(), ActorType::RpcServer).await;
+        check_signer(&transaction, sender.as_ref())
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), Some(e)))?;
+        self.admission
+            .authorize(&transaction, sender.as_ref(), ActorType::RpcServer)
+            .await
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), Some(e)))?;
+        self.simulate(transaction).await
+    }
+
+    async fn simulate_payload(&self, payload: Payload, from: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC simulatePayload method");
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        if !self.unsigned_simulation {
+            return Err(invalid(
+                "this node only simulates signed transactions".to_string(),
+            ));
+        }
+        let from = parse_address("from", &from)?;
+        let transaction = Transaction::from(payload);
+        if transaction.from() != from {
+            return Err(invalid(format!(
+                "payload is from {}, not {}",
+                transaction.from().to_full_string(),
+                from.to_full_string()
+            )));
+        }
+        self.simulate(transaction).await
+    }
+
+    async fn estimate_fees(&self, payload: Payload) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC estimateFees method");
+        let transaction = Transaction::from(payload);
+        let program = match transaction.transaction_type() {
+            TransactionType::Call(_) => {
+                get_account(transaction.program_id(), ActorType::RpcServer).await
+            }
+            _ => None,
+        };
+        let sender = get_account(transaction.from(), ActorType::RpcServer).await;
+        let estimate = self
+            .admission
+            .estimate(&transaction, sender.as_ref(), program.as_ref());
+        serde_json::to_string(&estimate)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
     async fn get_account(&self, address: String) -> Result<String, RpcError> {
         tracing::debug!("Received RPC getAccount method");
+        let address = parse_address("address", &address)?;
+        let account = get_account(address, ActorType::RpcServer).await;
 
-        let (tx, rx) = oneshot();
-        let reply = RpcReplyPort::from(tx);
+        serde_json::to_string(&AccountState::new(address, account.as_ref()))
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
 
-        self.send_rpc_get_account_method_to_self(address, reply)
+    async fn get_nonce(&self, address: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getNonce method for {address}");
+        let address = parse_address("address", &address)?;
+        let mut next = get_account(address, ActorType::RpcServer)
             .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>))?;
+            .unwrap_or_default()
+            .expected_nonce();
+        for summary in LasrRpcServerImpl::pending_by_address(address).await? {
+            if summary.nonce == next {
+                next = next.saturating_add(U256::from(1));
+            }
+        }
 
-        let handler = create_handler!(rpc_response, getAccount);
+        serde_json::to_string(&next)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
 
-        match handle_actor_response(rx, handler)
-            .await
-            .map_err(|e| RpcError::owned(INTERNAL_ERROR_CODE, format!("Error: {e}"), None::<()>))
-        {
-            Ok(resp) => match resp {
-                TransactionResponse::GetAccountResponse(account) => {
-                    tracing::debug!("received account response");
-                    return serde_json::to_string(&account).map_err(|e| {
-                        RpcError::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
-                    });
-                }
-                _ => {
-                    return Err(RpcError::owned(
-                        INVALID_PARAMS_CODE,
-                        "invalid response to `getAccount` methond".to_string(),
-                        None::<()>,
-                    ))
-                }
+    async fn get_verse_supply(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getVerseSupply method");
+
+        // No VERSE program account means nothing has been bridged in yet.
+        let supply = match get_account(VERSE_ADDR, ActorType::RpcServer).await {
+            Some(account) => verse_total_supply(&account).map_err(|e| {
+                RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+            })?,
+            None => U256::from(0),
+        };
+
+        Ok(hex_quantity::encode(&supply))
+    }
+
+    async fn faucet(
+        &self,
+        address: String,
+        amount: String,
+        program_id: Option<String>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC faucet method for {address}");
+        if !self.faucet.is_enabled() {
+            return Err(faucet_error(FaucetError::Disabled));
+        }
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        let address = parse_address("address", &address)?;
+        let program_id = match program_id {
+            Some(program_id) => parse_address("program_id", &program_id)?,
+            None => VERSE_ADDR,
+        };
+        let amount = match amount.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).ok(),
+            None => U256::from_dec_str(&amount).ok(),
+        }
+        .ok_or_else(|| {
+            invalid(format!(
+                "`amount` {amount} is not a decimal or 0x hex number"
+            ))
+        })?;
+        self.faucet
+            .check(address, program_id, amount)
+            .map_err(faucet_error)?;
+
+        let minted = LasrRpcServerImpl::mint(address, program_id, amount).await;
+        let transaction_hash = minted.map_err(|e| {
+            self.faucet.forget(&address);
+            faucet_error(e)
+        })?;
+
+        serde_json::to_string(&FaucetResponse {
+            transaction_hash,
+            address,
+            program_id,
+            amount,
+        })
+        .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_node_status(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getNodeStatus method");
+        let status = NodeStatus {
+            executor: LasrRpcServerImpl::executor_health().await,
+            settlement_providers: self
+                .settlement_providers
+                .as_ref()
+                .map(|metrics| metrics.status()),
+            mempool: self.mempool.as_ref().map(|metrics| metrics.status()),
+            retries: self.retries.as_ref().map(|metrics| metrics.status()),
+        };
+
+        serde_json::to_string(&status)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn health(&self) -> Result<String, RpcError> {
+        Ok(serde_json::json!({ "status": "ok" }).to_string())
+    }
+
+    async fn ready(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC ready method");
+        let mut components = BTreeMap::from([
+            (
+                "accountCache",
+                ComponentStatus::actor(ActorType::AccountCache),
+            ),
+            (
+                "executor",
+                ComponentStatus::executor(&LasrRpcServerImpl::executor_health().await),
+            ),
+        ]);
+        if let Some(health) = &self.da_health {
+            components.insert("da", ComponentStatus::da(&health.status()));
+        }
+        if let Some(metrics) = &self.settlement_providers {
+            components.insert(
+                "eoServer",
+                ComponentStatus::settlement_providers(&metrics.status()),
+            );
+        }
+        let readiness = Readiness::new(self.shutdown.is_started(), components);
+
+        serde_json::to_string(&readiness)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn node_status(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC nodeStatus method");
+        let summary = NodeSummary {
+            versions: NodeVersions {
+                node: env!("CARGO_PKG_VERSION"),
+                jsonrpc: "2.0",
             },
-            Err(e) => {
-                return Err(RpcError::owned(
-                    INTERNAL_ERROR_CODE,
-                    e.to_string(),
-                    None::<()>,
-                ))
+            chain_id: self.eth_chain_id,
+            latest_batch_id: self
+                .batch_receipts
+                .as_ref()
+                .and_then(|receipts| receipts.latest_batch_id()),
+            pending_transactions: self
+                .mempool
+                .as_ref()
+                .map(|metrics| metrics.status().transactions),
+            da: self.da_health.as_ref().map(|health| health.status()),
+            keys: self.node_keys.as_ref().map(|keys| keys.fingerprints()),
+            mailboxes: self.mailboxes.status(),
+        };
+
+        serde_json::to_string(&summary)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_batch_receipt(&self, batch_id: u64) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getBatchReceipt method for batch {batch_id}");
+        let receipts = self.batch_receipts.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "batches are not settled by this node",
+                None::<()>,
+            )
+        })?;
+        serde_json::to_string(&receipts.get(batch_id))
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_batch(&self, batch_id: u64, full: Option<bool>) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getBatch method for batch {batch_id}");
+        let Some(record) = self
+            .batches
+            .as_ref()
+            .and_then(|batches| batches.get(batch_id))
+        else {
+            return Err(RpcError::owned(
+                RpcErrorCode::NotFound.code(),
+                format!("no batch {batch_id} has been finalized"),
+                None::<()>,
+            ));
+        };
+        if full.unwrap_or(false) {
+            if let Some(pruner) = &self.pruner {
+                pruner.blob_pruned(&record)?;
             }
         }
+        let mut batch = self.batch_info(record);
+        if full.unwrap_or(false) {
+            batch.accounts = Some(LasrRpcServerImpl::batch_accounts(&batch.record).await?);
+        }
+
+        serde_json::to_string(&batch)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
     }
-}
 
-impl LasrRpcServerImpl {
-    pub fn new(proxy: ActorRef<RpcMessage>) -> Self {
-        Self { proxy }
+    async fn get_latest_batch(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getLatestBatch method");
+        let batch = self
+            .batches
+            .as_ref()
+            .and_then(|batches| batches.latest())
+            .map(|record| self.batch_info(record));
+
+        serde_json::to_string(&batch)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
     }
 
-    async fn send_rpc_call_method_to_self(
+    async fn get_batches(
         &self,
-        transaction: Transaction,
-        reply: RpcReplyPort<RpcMessage>,
-    ) -> Result<(), RpcResponseError> {
-        tracing::info!("Sending RPC call method to proxy actor");
-        self.proxy
-            .cast(RpcMessage::Request {
-                method: Box::new(RpcRequestMethod::Call { transaction }),
-                reply,
-            })
-            .map_err(|e| RpcResponseError {
-                description: e.to_string(),
-            })
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getBatches method");
+        let batches = self
+            .batches
+            .as_ref()
+            .map(|batches| batches.list())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|record| self.batch_info(record))
+            .collect();
+        let page = self
+            .paginator
+            .page(
+                batches,
+                |batch| batch.record.batch_id,
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
     }
 
-    async fn send_rpc_send_method_to_self(
+    async fn get_pending_transactions(
         &self,
-        transaction: Transaction,
-        reply: RpcReplyPort<RpcMessage>,
-    ) -> Result<(), RpcResponseError> {
-        self.get_myself()
-            .cast(RpcMessage::Request {
-                method: Box::new(RpcRequestMethod::Send { transaction }),
-                reply,
-            })
-            .map_err(|e| RpcResponseError {
-                description: e.to_string(),
-            })
+        address: String,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getPendingTransactions method for {address}");
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let address = parse_address("address", &address)?;
+        let pending = LasrRpcServerImpl::pending_by_address(address).await?;
+        let page = self
+            .paginator
+            .page(
+                pending,
+                |summary| (summary.nonce, summary.hash.clone()),
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page).map_err(|e| internal(e.to_string()))
     }
 
-    async fn send_rpc_register_program_method_to_self(
+    async fn get_program(&self, program_id: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getProgram method for {program_id}");
+        let address = parse_address("program_id", &program_id)?;
+        let Some(record) = self
+            .programs
+            .as_ref()
+            .and_then(|programs| programs.get(&address))
+        else {
+            return Err(RpcError::owned(
+                RpcErrorCode::NotFound.code(),
+                format!("no program registered as {program_id}"),
+                None::<()>,
+            ));
+        };
+        let program = get_account(address, ActorType::RpcServer).await;
+
+        serde_json::to_string(&ProgramInfo::new(record, program.as_ref()))
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn list_programs(
         &self,
-        transaction: Transaction,
-        reply: RpcReplyPort<RpcMessage>,
-    ) -> Result<(), RpcResponseError> {
-        self.get_myself()
-            .cast(RpcMessage::Request {
-                method: Box::new(RpcRequestMethod::RegisterProgram { transaction }),
-                reply,
-            })
-            .map_err(|e| RpcResponseError {
-                description: e.to_string(),
-            })
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC listPrograms method");
+        let programs = self
+            .programs
+            .as_ref()
+            .map(|programs| programs.list())
+            .unwrap_or_default();
+        let page = self
+            .paginator
+            .page(
+                programs,
+                |record| record.program_id,
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+        let mut items = Vec::with_capacity(page.items.len());
+        for record in page.items {
+            let program = get_account(record.program_id, ActorType::RpcServer).await;
+            items.push(ProgramInfo::new(record, program.as_ref()));
+        }
+        let page = Page {
+            items,
+            total: page.total,
+            next_cursor: page.next_cursor,
+            truncated: page.truncated,
+        };
+
+        serde_json::to_string(&page)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
     }
 
-    async fn send_rpc_get_account_method_to_self(
+    async fn get_transactions_by_address(
         &self,
         address: String,
-        reply: RpcReplyPort<RpcMessage>,
-    ) -> Result<(), RpcResponseError> {
-        let address: Address = Address::from_str(&address).map_err(|e| RpcResponseError {
-            description: (e.to_string()),
-        })?;
-        self.get_myself()
-            .cast(RpcMessage::Request {
-                method: Box::new(RpcRequestMethod::GetAccount { address }),
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getTransactionsByAddress method for {address}");
+        let address = parse_address("address", &address)?;
+        self.indexed_transactions(|index| index.by_address(&address), cursor, limit)
+    }
+
+    async fn get_transactions_by_program(
+        &self,
+        program_id: String,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getTransactionsByProgram method for {program_id}");
+        let program_id = parse_address("program_id", &program_id)?;
+        self.indexed_transactions(|index| index.by_program(&program_id), cursor, limit)
+    }
+
+    async fn get_events(
+        &self,
+        filter: Option<EventFilter>,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getEvents method for {filter:?}");
+        let events = self.event_log()?.matching(&filter.unwrap_or_default());
+        let page = self
+            .paginator
+            .page(events, EmittedEvent::key, cursor.as_deref(), limit)
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_transaction_status(&self, tx_hash: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getTransactionStatus method for {tx_hash}");
+        let status = self
+            .transaction_status(parse_hash("tx_hash", &tx_hash)?)
+            .await?;
+
+        serde_json::to_string(&status)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_transaction_receipt(&self, tx_hash: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getTransactionReceipt method for {tx_hash}");
+        let transaction_hash = parse_hash("tx_hash", &tx_hash)?;
+        let tx_hash = format!("0x{}", hex::encode(transaction_hash));
+        self.receipt_pruned(&tx_hash)?;
+        let status = self.transaction_status(transaction_hash).await?;
+        let inclusion = self.inclusion(&tx_hash);
+        let batch = inclusion
+            .batch_id
+            .zip(self.batch_receipts.as_ref())
+            .and_then(|(batch_id, receipts)| receipts.get(batch_id));
+        let revert_reason = match &status {
+            TransactionStatus::Failed { description, .. } => Some(description.clone()),
+            _ => None,
+        };
+        let receipt = TransactionReceipt {
+            status,
+            batch_id: inclusion.batch_id,
+            blob_commitment: batch.map(|batch| batch.header.blob_commitment),
+            inclusion_proof: inclusion.proof,
+            token_deltas: inclusion.token_deltas,
+            events: inclusion.events,
+            revert_reason,
+            attestation: LasrRpcServerImpl::attestation(&tx_hash).await?,
+            transaction_hash: tx_hash,
+        };
+
+        serde_json::to_string(&receipt)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_attestation(&self, tx_hash: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getAttestation method for {tx_hash}");
+        let transaction_hash = parse_hash("tx_hash", &tx_hash)?;
+        self.receipt_pruned(&format!("0x{}", hex::encode(transaction_hash)))?;
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let Some(attestation) = LasrRpcServerImpl::attestation(&tx_hash).await? else {
+            return Err(RpcError::owned(
+                RpcErrorCode::NotFound.code(),
+                format!("no attestation found for transaction {tx_hash}"),
+                None::<()>,
+            ));
+        };
+
+        let trusted = self.node_keys.as_ref().is_some_and(|keys| {
+            attestation
+                .verify_signed_by(&keys.key(KeyRole::Attestation).trusted_signers())
+                .is_ok()
+        });
+        serde_json::to_string(&AttestationResponse {
+            attestation,
+            trusted,
+        })
+        .map_err(|e| internal(e.to_string()))
+    }
+
+    async fn subscribe_execution_logs(
+        &self,
+        pending: PendingSubscriptionSink,
+        program_id: String,
+    ) -> SubscriptionResult {
+        tracing::debug!("Received RPC subscribeExecutionLogs method for {program_id}");
+        let mut logs = self.execution_logs.subscribe(&program_id);
+        let sink = pending.accept().await?;
+
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                event = logs.next() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+                    if sink.send(SubscriptionMessage::from_json(&event)?).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe_events(
+        &self,
+        pending: PendingSubscriptionSink,
+        filter: EventFilter,
+    ) -> SubscriptionResult {
+        tracing::debug!("Received RPC subscribeEvents method for {filter:?}");
+        let mut events = match self.event_log() {
+            Ok(event_log) => event_log.subscribe(),
+            Err(e) => {
+                pending.reject(e).await;
+                return Ok(());
+            }
+        };
+        let sink = pending.accept().await?;
+
+        // Events the filter left out are not missed, those dropped are.
+        let mut missed = 0;
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                next = events.next() => {
+                    let Some((event, dropped)) = next else {
+                        break;
+                    };
+                    missed += dropped;
+                    if !event.matches(&filter) {
+                        continue;
+                    }
+                    let notification = EventNotification { event, dropped: missed };
+                    if sink.send(SubscriptionMessage::from_json(&notification)?).await.is_err() {
+                        break;
+                    }
+                    missed = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe_batches(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        tracing::debug!("Received RPC subscribeBatches method");
+        let Some(batch_feed) = &self.batch_feed else {
+            pending
+                .reject(RpcError::owned(
+                    RpcErrorCode::Disabled.code(),
+                    "batches are not streamed from this node",
+                    None::<()>,
+                ))
+                .await;
+            return Ok(());
+        };
+        let mut batches = batch_feed.subscribe();
+        let sink = pending.accept().await?;
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                next = batches.next() => {
+                    let Some((batch, dropped)) = next else {
+                        break;
+                    };
+                    let notification = BatchNotification { batch, dropped };
+                    if sink.send(SubscriptionMessage::from_json(&notification)?).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe_notifications(
+        &self,
+        pending: PendingSubscriptionSink,
+        kind: String,
+        param: Option<String>,
+    ) -> SubscriptionResult {
+        tracing::debug!("Received RPC subscribe method for {kind} {param:?}");
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        match (kind.as_str(), param) {
+            ("accountChanged", Some(address)) => {
+                let address = match parse_address("param", &address) {
+                    Ok(address) => address,
+                    Err(e) => {
+                        pending.reject(RpcError::from(e)).await;
+                        return Ok(());
+                    }
+                };
+                let accounts = self.notifications.subscribe_account(address);
+                let sink = pending.accept().await?;
+                forward(sink, accounts, |account| {
+                    NotificationEvent::AccountChanged {
+                        account: AccountState::new(address, Some(&account)),
+                    }
+                })
+                .await
+            }
+            ("newBatch", None) => {
+                let Some(receipts) = &self.batch_receipts else {
+                    pending
+                        .reject(RpcError::owned(
+                            RpcErrorCode::Internal.code(),
+                            "batches are not reported on this node",
+                            None::<()>,
+                        ))
+                        .await;
+                    return Ok(());
+                };
+                let batches = receipts.subscribe();
+                let sink = pending.accept().await?;
+                forward(sink, batches, |batch| NotificationEvent::NewBatch { batch }).await
+            }
+            ("transactionStatus", Some(tx_hash)) => {
+                let transaction_hash = match parse_hash("param", &tx_hash) {
+                    Ok(transaction_hash) => transaction_hash,
+                    Err(e) => {
+                        pending.reject(RpcError::from(e)).await;
+                        return Ok(());
+                    }
+                };
+                let tx_hash = format!("0x{}", hex::encode(transaction_hash));
+                // Subscribed before looking the status up, so an outcome
+                // arriving in between is not missed.
+                let outcomes = self.notifications.subscribe_transaction(&tx_hash);
+                let status = match self.transaction_status(transaction_hash).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        pending.reject(e).await;
+                        return Ok(());
+                    }
+                };
+                let sink = pending.accept().await?;
+                let current = Notification {
+                    event: NotificationEvent::TransactionStatus {
+                        transaction_hash: tx_hash.clone(),
+                        status,
+                    },
+                    dropped: 0,
+                };
+                if sink
+                    .send(SubscriptionMessage::from_json(&current)?)
+                    .await
+                    .is_err()
+                {
+                    return Ok(());
+                }
+                forward(sink, outcomes, |receipt| {
+                    NotificationEvent::TransactionStatus {
+                        transaction_hash: tx_hash.clone(),
+                        status: self.receipt_status(&tx_hash, Some(receipt)),
+                    }
+                })
+                .await
+            }
+            // Known kinds only get here with the wrong parameters.
+            (kind, _) => {
+                let error = match kind {
+                    "accountChanged" => "accountChanged needs an address".to_string(),
+                    "transactionStatus" => "transactionStatus needs a transaction hash".to_string(),
+                    "newBatch" => "newBatch takes no parameter".to_string(),
+                    kind => format!("{kind} is not a subscription"),
+                };
+                pending.reject(invalid(error)).await;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AdminRpcServer for LasrRpcServerImpl {
+    async fn get_execution_mismatches(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getExecutionMismatches method");
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let executor: ActorRef<ExecutorMessage> =
+            ractor::registry::where_is(ActorType::Executor.to_string())
+                .ok_or_else(|| internal("unable to acquire executor".to_string()))?
+                .into();
+        let mismatches = match executor
+            .call(
+                |reply| ExecutorMessage::GetExecutionMismatches { reply },
+                Some(EXECUTOR_REQUEST_TIMEOUT),
+            )
+            .await
+            .map_err(|e| internal(e.to_string()))?
+        {
+            CallResult::Success(mismatches) => mismatches,
+            CallResult::Timeout => {
+                return Err(internal("executor did not answer in time".to_string()))
+            }
+            CallResult::SenderError => {
+                return Err(internal("executor does not record mismatches".to_string()))
+            }
+        };
+        let page = self
+            .paginator
+            .page(
+                mismatches,
+                |mismatch| (mismatch.detected_at, mismatch.transaction_hash.clone()),
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page).map_err(|e| internal(e.to_string()))
+    }
+
+    async fn get_program_stats(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+        metric: Option<String>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getProgramStats method");
+        let program_stats = self.program_stats.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "programs are not counted on this node",
+                None::<()>,
+            )
+        })?;
+        let metric: ProgramMetric = metric
+            .map(|metric| metric.parse())
+            .transpose()
+            .map_err(|e: String| {
+                RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>)
+            })?
+            .unwrap_or_default();
+        let page = self
+            .paginator
+            .page(
+                program_stats.list(),
+                |stat| (u64::MAX - stat.metric(metric), stat.program_id.clone()),
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn reload_event_filters(&self, filters: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC reloadEventFilters method");
+        let event_filters = self.event_filters.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "event filters are not reloadable on this node",
+                None::<()>,
+            )
+        })?;
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        let config: EventFilterConfig =
+            serde_json::from_str(&filters).map_err(|e| invalid(e.to_string()))?;
+        let reload = event_filters
+            .reload(&config)
+            .map_err(|e| invalid(e.to_string()))?;
+        tracing::warn!(
+            "event filters reloaded: {} events added, {} removed",
+            reload.added.len(),
+            reload.removed.len()
+        );
+
+        serde_json::to_string(&reload)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn backfill_settlement_events(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC backfillSettlementEvents method");
+        let progress = self
+            .settlement_backfill()?
+            .start(from_block, to_block)
+            .map_err(|e| {
+                RpcError::owned(
+                    RpcErrorCode::InvalidParams.code(),
+                    e.to_string(),
+                    None::<()>,
+                )
+            })?;
+        tracing::warn!("backfilling settlement events from block {from_block} to block {to_block}");
+
+        serde_json::to_string(&progress)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_settlement_backfill(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getSettlementBackfill method");
+        serde_json::to_string(&self.settlement_backfill()?.progress())
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_rate_limits(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getRateLimits method");
+        serde_json::to_string(&self.rate_limiter()?.status())
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn set_rate_limits(&self, limits: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC setRateLimits method");
+        let rate_limiter = self.rate_limiter()?;
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        let config: RateLimitConfig =
+            serde_json::from_str(&limits).map_err(|e| invalid(e.to_string()))?;
+        rate_limiter
+            .set(config)
+            .map_err(|e| invalid(e.to_string()))?;
+        tracing::warn!("rate limits set to {limits}");
+
+        serde_json::to_string(&rate_limiter.status().limits)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_admission_policy(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getAdmissionPolicy method");
+        serde_json::to_string(&self.policy.status())
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn set_admission_policy(&self, policy: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC setAdmissionPolicy method");
+        let config: PolicyConfig = serde_json::from_str(&policy).map_err(|e| {
+            RpcError::owned(
+                RpcErrorCode::InvalidParams.code(),
+                e.to_string(),
+                None::<()>,
+            )
+        })?;
+        self.policy.set(config);
+        tracing::warn!("admission policy set to {policy}");
+
+        serde_json::to_string(&self.policy.status().config)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_dead_letters(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getDeadLetters method");
+        let page = self
+            .paginator
+            .page(
+                self.dead_letters()?.list(),
+                |letter| (letter.dead_lettered_at, letter.transaction_hash.clone()),
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn redrive_dead_letter(&self, tx_hash: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC redriveDeadLetter method for {tx_hash}");
+        let dead_letters = self.dead_letters()?;
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let transaction_hash = format!("0x{}", hex::encode(parse_hash("tx_hash", &tx_hash)?));
+        let not_found = || {
+            RpcError::owned(
+                RpcErrorCode::NotFound.code(),
+                format!("{transaction_hash} is not in the dead letters"),
+                None::<()>,
+            )
+        };
+        let letter = dead_letters.get(&transaction_hash).ok_or_else(not_found)?;
+        // The accounts may have moved on while the call was parked, so it is
+        // admitted again as if it had just been submitted. One turned away
+        // stays parked.
+        let program = get_account(letter.transaction.program_id(), ActorType::RpcServer).await;
+        let _admitted = self.admit(&letter.transaction, program.as_ref()).await?;
+
+        let letter = dead_letters
+            .take(&transaction_hash)
+            .map_err(|e| internal(e.to_string()))?
+            .ok_or_else(not_found)?;
+        // The call's outcome is read from its receipt, so nothing waits on
+        // the answer to this submission.
+        let (tx, _) = oneshot();
+        if let Err(e) = self
+            .send_rpc_call_method_to_self(letter.transaction.clone(), RpcReplyPort::from(tx))
+            .await
+        {
+            dead_letters
+                .park(letter)
+                .map_err(|e| internal(e.to_string()))?;
+            return Err(internal(e.to_string()));
+        }
+        tracing::warn!("re-driving {transaction_hash} from the dead letters");
+
+        serde_json::to_string(&letter).map_err(|e| internal(e.to_string()))
+    }
+
+    async fn purge_dead_letters(&self, tx_hash: Option<String>) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC purgeDeadLetters method");
+        let transaction_hash = tx_hash
+            .map(|tx_hash| {
+                parse_hash("tx_hash", &tx_hash)
+                    .map(|transaction_hash| format!("0x{}", hex::encode(transaction_hash)))
+            })
+            .transpose()?;
+        let purged = self
+            .dead_letters()?
+            .purge(transaction_hash.as_deref())
+            .map_err(|e| {
+                RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+            })?;
+        tracing::warn!("purged {purged} dead letters");
+
+        serde_json::to_string(&PurgedDeadLetters { purged })
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn rotate_key(&self, role: String, file: String) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC rotateKey method for the {role} key");
+        let keys = self.node_keys()?;
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        let role: KeyRole = role.parse().map_err(invalid)?;
+        let rotated = keys.rotate(role, &file).map_err(|e| match e {
+            NodeKeysError::Io(_) | NodeKeysError::Manifest(_) | NodeKeysError::NoKeystore => {
+                RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+            }
+            e => invalid(e.to_string()),
+        })?;
+
+        serde_json::to_string(&rotated)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn prune(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC prune method");
+        let report = self.pruner()?.prune().await.map_err(|e| {
+            RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+        })?;
+        tracing::warn!(
+            "pruned blobs through {:?} and receipts through {:?}",
+            report.blobs.through,
+            report.receipts.through
+        );
+
+        serde_json::to_string(&report)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    async fn get_reclaimable_space(&self) -> Result<String, RpcError> {
+        tracing::debug!("Received RPC getReclaimableSpace method");
+        serde_json::to_string(&self.pruner()?.status())
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+}
+
+#[async_trait]
+impl EthRpcServer for LasrRpcServerImpl {
+    async fn chain_id(&self) -> Result<U64, RpcError> {
+        tracing::debug!("Received RPC eth_chainId method");
+        Ok(U64::from(self.eth_chain_id))
+    }
+
+    async fn get_balance(
+        &self,
+        address: H160,
+        _block: Option<JsonValue>,
+    ) -> Result<EthU256, RpcError> {
+        tracing::debug!("Received RPC eth_getBalance method for {address:?}");
+        let balance = get_account(address.into(), ActorType::RpcServer)
+            .await
+            .map(|account| account.balance(&ETH_ADDR))
+            .unwrap_or_default();
+        Ok(balance.into())
+    }
+
+    async fn get_transaction_count(
+        &self,
+        address: H160,
+        _block: Option<JsonValue>,
+    ) -> Result<EthU256, RpcError> {
+        tracing::debug!("Received RPC eth_getTransactionCount method for {address:?}");
+        let nonce = get_account(address.into(), ActorType::RpcServer)
+            .await
+            .map(|account| account.nonce())
+            .unwrap_or_default();
+        Ok(nonce.into())
+    }
+
+    async fn send_raw_transaction(&self, raw: Bytes) -> Result<H256, RpcError> {
+        tracing::debug!("Received RPC eth_sendRawTransaction method");
+        let invalid =
+            |e: String| RpcError::owned(RpcErrorCode::InvalidParams.code(), e, None::<()>);
+        let (envelope, signature) = TypedTransaction::decode_signed(&Rlp::new(&raw))
+            .map_err(|e| invalid(format!("not a signed Ethereum transaction: {e}")))?;
+        if let Some(chain_id) = envelope.chain_id() {
+            if chain_id.as_u64() != self.eth_chain_id {
+                return Err(invalid(format!(
+                    "signed for chain {chain_id}, not chain {}",
+                    self.eth_chain_id
+                )));
+            }
+        }
+        let transaction: Transaction = envelope
+            .data()
+            .ok_or_else(|| invalid("carries no LASR transaction".to_string()))
+            .and_then(|data| {
+                serde_json::from_slice(data)
+                    .map_err(|e| invalid(format!("carries no LASR transaction: {e}")))
+            })?;
+        let signer = signature
+            .recover(envelope.sighash())
+            .map_err(|e| invalid(e.to_string()))?;
+        if Address::from(signer) != transaction.from() {
+            return Err(invalid(format!(
+                "signed by {signer:?}, not by the sender {}",
+                transaction.from().to_full_string()
+            )));
+        }
+
+        let transaction_hash = H256::from(transaction.digest());
+        self.submit(transaction).await?;
+        Ok(transaction_hash)
+    }
+
+    async fn get_transaction_receipt(
+        &self,
+        transaction_hash: H256,
+    ) -> Result<Option<EthTransactionReceipt>, RpcError> {
+        tracing::debug!("Received RPC eth_getTransactionReceipt method for {transaction_hash:?}");
+        self.receipt_pruned(&format!("0x{}", hex::encode(transaction_hash)))?;
+        let status = match self.transaction_status(transaction_hash.0).await? {
+            TransactionStatus::Included | TransactionStatus::Settled => 1,
+            TransactionStatus::Failed { .. } => 0,
+            _ => return Ok(None),
+        };
+        let inclusion = self.inclusion(&format!("0x{}", hex::encode(transaction_hash)));
+        let batch = inclusion
+            .batch_id
+            .zip(self.batch_receipts.as_ref())
+            .and_then(|(batch_id, receipts)| receipts.get(batch_id));
+
+        Ok(Some(EthTransactionReceipt {
+            transaction_hash,
+            block_number: inclusion.batch_id.map(U64::from),
+            block_hash: batch.map(|batch| batch.header.blob_commitment),
+            from: inclusion.from.map(H160::from).unwrap_or_default(),
+            to: inclusion.to.map(H160::from),
+            gas_used: Some(EthU256::zero()),
+            effective_gas_price: Some(EthU256::zero()),
+            status: Some(U64::from(status)),
+            ..Default::default()
+        }))
+    }
+}
+
+/// Sends `sink` a notification of each item from `subscription` until either
+/// is closed.
+async fn forward<T: Clone>(
+    sink: SubscriptionSink,
+    mut subscription: Subscription<T>,
+    event: impl Fn(T) -> NotificationEvent,
+) -> SubscriptionResult {
+    loop {
+        tokio::select! {
+            _ = sink.closed() => break,
+            next = subscription.next() => {
+                let Some((item, dropped)) = next else {
+                    break;
+                };
+                let notification = Notification {
+                    event: event(item),
+                    dropped,
+                };
+                if sink.send(SubscriptionMessage::from_json(&notification)?).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the admission of the transaction `transaction_hash` in the `rpc`
+/// span of its lifecycle, closing the transaction's span again if it was
+/// opened for this admission and the transaction was turned away.
+async fn in_lifecycle(
+    transaction_hash: &str,
+    admission: impl Future<Output = Result<String, RpcError>>,
+) -> Result<String, RpcError> {
+    let opened = lifecycle::admitted(transaction_hash);
+    let result = admission
+        .instrument(lifecycle::stage(LifecycleStage::Rpc, transaction_hash))
+        .await;
+    if opened && result.is_err() {
+        lifecycle::finished(transaction_hash);
+    }
+    result
+}
+
+/// A transaction the scheduler failed, answered with the code it was failed
+/// with. A program's revert reason is carried in the error data.
+fn transaction_error(error: RpcResponseError) -> RpcError {
+    let message = format!("{}: {}", error.code.message(), error.description);
+    match error.code {
+        RpcErrorCode::ProgramReverted => RpcError::owned(
+            error.code.code(),
+            message,
+            Some(RevertReason {
+                reason: error.description,
+            }),
+        ),
+        code => RpcError::owned(code.code(), message, None::<()>),
+    }
+}
+
+/// Turns `transaction` away unless the method it was sent with is for the
+/// `route` its type takes.
+fn routed(transaction: &Transaction, route: TransactionRoute) -> Result<(), RpcError> {
+    route.check(transaction).map_err(|e| {
+        RpcError::owned(
+            RpcErrorCode::InvalidParams.code(),
+            e.to_string(),
+            None::<()>,
+        )
+    })
+}
+
+fn already_pending(transaction_hash: String) -> RpcError {
+    RpcError::owned(
+        RpcErrorCode::AlreadyPending.code(),
+        format!("Error: transaction {transaction_hash} is already pending"),
+        Some(DuplicateTransaction {
+            transaction_hash,
+            receipt: None,
+        }),
+    )
+}
+
+fn already_included(transaction_hash: String, receipt: TransactionResponse) -> RpcError {
+    RpcError::owned(
+        RpcErrorCode::AlreadyIncluded.code(),
+        format!("Error: transaction {transaction_hash} was already included"),
+        Some(DuplicateTransaction {
+            transaction_hash,
+            receipt: Some(receipt),
+        }),
+    )
+}
+
+fn faucet_error(error: FaucetError) -> RpcError {
+    match error {
+        FaucetError::Cooldown { retry_after, .. } => Throttled { retry_after }.into(),
+        error => RpcError::owned(error.code().code(), error.to_string(), None::<()>),
+    }
+}
+
+fn paging_error(error: PaginationError) -> RpcError {
+    RpcError::owned(error.code().code(), error.to_string(), None::<()>)
+}
+
+impl LasrRpcServerImpl {
+    /// Sends `transaction` by the method for its type.
+    async fn submit(&self, transaction: Transaction) -> Result<String, RpcError> {
+        match TransactionRoute::of(&transaction.transaction_type()) {
+            TransactionRoute::Send => self.submit_send(transaction, false).await,
+            TransactionRoute::Call => LasrRpcServer::call(self, transaction).await,
+            TransactionRoute::Register => LasrRpcServer::register_program(self, transaction).await,
+            TransactionRoute::System => Err(RpcError::owned(
+                RpcErrorCode::InvalidParams.code(),
+                format!(
+                    "{} transactions are not sent over RPC",
+                    transaction.transaction_type()
+                ),
+                None::<()>,
+            )),
+        }
+    }
+
+    /// What is pending from `address`, in nonce order.
+    async fn pending_by_address(
+        address: Address,
+    ) -> Result<Vec<PendingTransactionSummary>, RpcError> {
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        match LasrRpcServerImpl::pending_transactions()?
+            .call(
+                |reply| PendingTransactionMessage::GetPendingByAddress { address, reply },
+                Some(EXECUTOR_REQUEST_TIMEOUT),
+            )
+            .await
+            .map_err(|e| internal(e.to_string()))?
+        {
+            CallResult::Success(pending) => Ok(pending),
+            CallResult::Timeout => Err(internal(
+                "pending transactions did not answer in time".to_string(),
+            )),
+            CallResult::SenderError => Err(internal(
+                "pending transactions dropped the request".to_string(),
+            )),
+        }
+    }
+
+    pub fn new(proxy: ActorRef<RpcMessage>, execution_logs: ExecutionLogs) -> Self {
+        Self {
+            proxy,
+            execution_logs,
+            node_keys: None,
+            admission: Admission::default(),
+            policy: PolicyGate::default(),
+            settlement_providers: None,
+            mempool: None,
+            retries: None,
+            event_filters: None,
+            settlement_backfill: None,
+            batch_receipts: None,
+            inclusions: None,
+            programs: None,
+            batches: None,
+            transaction_index: None,
+            event_log: None,
+            batch_feed: None,
+            dead_letters: None,
+            program_stats: None,
+            pruner: None,
+            faucet: Faucet::default(),
+            notifications: Notifications::default(),
+            eth_chain_id: DEFAULT_ETH_CHAIN_ID,
+            rate_limiter: None,
+            da_health: None,
+            shutdown: Shutdown::default(),
+            load_shedder: LoadShedder::default(),
+            mailboxes: MailboxMonitor::default(),
+            output_limits: OutputLimits::default(),
+            storage_quota: StorageQuota::default(),
+            paginator: Paginator::default(),
+            unsigned_simulation: false,
+        }
+    }
+
+    async fn simulate(&self, transaction: Transaction) -> Result<String, RpcError> {
+        let mut simulation = simulate(&transaction, &self.output_limits, &self.storage_quota)
+            .await
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), None::<()>))?;
+        simulation.estimated_fee = self.admission.fees().estimate(&transaction).fee;
+        serde_json::to_string(&simulation)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    fn event_log(&self) -> Result<&EventLog, RpcError> {
+        self.event_log.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Disabled.code(),
+                "events are not recorded on this node",
+                None::<()>,
+            )
+        })
+    }
+
+    /// The page of the transactions `lookup` finds in the index starting
+    /// after `cursor`.
+    fn indexed_transactions(
+        &self,
+        lookup: impl FnOnce(&TransactionIndex) -> Vec<IndexedTransaction>,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError> {
+        let index = self.transaction_index.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Disabled.code(),
+                "transactions are not indexed on this node",
+                None::<()>,
+            )
+        })?;
+        let page = self
+            .paginator
+            .page(
+                lookup(index),
+                IndexedTransaction::key,
+                cursor.as_deref(),
+                limit,
+            )
+            .map_err(paging_error)?;
+
+        serde_json::to_string(&page)
+            .map_err(|e| RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>))
+    }
+
+    /// `record` with where its settlement has got to, which is finalized
+    /// until the settler has a receipt for it.
+    fn batch_info(&self, record: BatchRecord) -> BatchInfo {
+        let status = self
+            .batch_receipts
+            .as_ref()
+            .and_then(|receipts| receipts.get(record.batch_id))
+            .map(|receipt| receipt.status)
+            .unwrap_or(BatchStatus::Finalized);
+        BatchInfo {
+            record,
+            status,
+            accounts: None,
+        }
+    }
+
+    /// The accounts batch `record` holds, by address, decoded from its blob
+    /// on the DA layer.
+    async fn batch_accounts(record: &BatchRecord) -> Result<Vec<Account>, RpcError> {
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let da_client: ActorRef<DaClientMessage> =
+            ractor::registry::where_is(ActorType::DaClient.to_string())
+                .ok_or_else(|| internal("the DA client is not running".to_string()))?
+                .into();
+        let (tx, rx) = oneshot();
+        da_client
+            .cast(DaClientMessage::RetrieveBlob {
+                batch_header_hash: record.blob_commitment,
+                blob_index: record.blob_index,
+                tx,
+            })
+            .map_err(|e| internal(e.to_string()))?;
+        let blob = tokio::time::timeout(BATCH_BLOB_TIMEOUT, rx)
+            .await
+            .map_err(|_| {
+                internal(format!(
+                    "the DA layer did not answer with batch {} in time",
+                    record.batch_id
+                ))
+            })?
+            .map_err(|e| internal(e.to_string()))?
+            .map_err(|e| internal(e.to_string()))?;
+        let batch = std::str::from_utf8(&blob)
+            .ok()
+            .and_then(Batch::decode_batch)
+            .ok_or_else(|| {
+                internal(format!(
+                    "the blob of batch {} does not decode to a batch",
+                    record.batch_id
+                ))
+            })?;
+
+        Ok(batch
+            .accounts()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_values()
+            .collect())
+    }
+
+    /// Calls the program `transaction` names, answering with its result, or
+    /// with the transaction's hash if the call is finished later.
+    async fn submit_call(&self, transaction: Transaction) -> Result<String, RpcError> {
+        routed(&transaction, TransactionRoute::Call)?;
+        let program = get_account(transaction.program_id(), ActorType::RpcServer).await;
+        let _admitted = self.admit(&transaction, program.as_ref()).await?;
+        let transaction_hash = transaction.hash_string();
+        let (tx, rx) = oneshot();
+        let reply = RpcReplyPort::from(tx);
+        self.send_rpc_call_method_to_self(transaction, reply)
+            .await
+            .map_err(|e| {
+                RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+            })?;
+
+        let handler = create_handler!(rpc_response, call);
+
+        match handle_actor_response(rx, handler).await.map_err(|e| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                format!("Error: {e}"),
+                None::<()>,
+            )
+        }) {
+            Ok(resp) => match resp {
+                TransactionResponse::AsyncCallResponse(transaction_hash) => Ok(transaction_hash),
+                TransactionResponse::Scheduled { .. } => Ok(transaction_hash),
+                TransactionResponse::CallResponse(account) => {
+                    let account_str = serde_json::to_string(&account).map_err(|e| {
+                        RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+                    })?;
+                    return Ok(account_str);
+                }
+                TransactionResponse::TransactionError(rpc_response_error) => {
+                    return Err(transaction_error(rpc_response_error))
+                }
+                TransactionResponse::AlreadyPending => {
+                    return Err(already_pending(transaction_hash))
+                }
+                TransactionResponse::AlreadyIncluded(receipt) => {
+                    return Err(already_included(transaction_hash, *receipt))
+                }
+                _ => {
+                    return Err(RpcError::owned(
+                        RpcErrorCode::InvalidParams.code(),
+                        "invalid response to `call` method".to_string(),
+                        None::<()>,
+                    ))
+                }
+            },
+            Err(e) => {
+                return Err(RpcError::owned(
+                    RpcErrorCode::Internal.code(),
+                    e.to_string(),
+                    None::<()>,
+                ))
+            }
+        }
+    }
+
+    /// Sends `transaction`, in place of the one pending from the same sender
+    /// with the same nonce if `replace`, and waits for its receipt.
+    async fn submit_send(
+        &self,
+        transaction: Transaction,
+        replace: bool,
+    ) -> Result<String, RpcError> {
+        routed(&transaction, TransactionRoute::Send)?;
+        let _admitted = self.admit(&transaction, None).await?;
+        // Held until the scheduler answers it, so a second send cannot be
+        // admitted against the same balance meanwhile.
+        reserve_debit(&transaction).await.map_err(|e| {
+            RpcError::owned(RpcErrorCode::InsufficientBalance.code(), e, None::<()>)
+        })?;
+        let transaction_hash = transaction.hash_string();
+        let (tx, rx) = oneshot();
+        let reply = RpcReplyPort::from(tx);
+
+        self.send_rpc_send_method_to_self(transaction, replace, reply)
+            .await
+            .map_err(|e| {
+                release_debit(&transaction_hash);
+                RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+            })?;
+
+        let handler = create_handler!(rpc_response, send);
+
+        match handle_actor_response(rx, handler).await.map_err(|e| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                format!("Error: {e}"),
+                None::<()>,
+            )
+        }) {
+            Ok(resp) => match resp {
+                TransactionResponse::SendResponse(token) => {
+                    return serde_json::to_string(&token).map_err(|e| {
+                        RpcError::owned(RpcErrorCode::Internal.code(), e.to_string(), None::<()>)
+                    })
+                }
+                TransactionResponse::Scheduled { .. } => return Ok(transaction_hash),
+                TransactionResponse::TransactionError(rpc_response_error) => {
+                    tracing::error!("Returning error to client: {}", &rpc_response_error);
+                    return Err(transaction_error(rpc_response_error));
+                }
+                TransactionResponse::Replaced(replaced_by) => {
+                    return Err(RpcError::owned(
+                        RpcErrorCode::Replaced.code(),
+                        format!("Error: transaction was replaced by {replaced_by}"),
+                        None::<()>,
+                    ))
+                }
+                TransactionResponse::Expired => {
+                    return Err(RpcError::owned(
+                        RpcErrorCode::Expired.code(),
+                        "Error: transaction expired before it was validated".to_string(),
+                        None::<()>,
+                    ))
+                }
+                TransactionResponse::MempoolFull(full) => {
+                    return Err(RpcError::owned(
+                        RpcErrorCode::MempoolFull.code(),
+                        format!("Error: {full}"),
+                        Some(full),
+                    ))
+                }
+                TransactionResponse::Evicted => {
+                    return Err(RpcError::owned(
+                        RpcErrorCode::MempoolFull.code(),
+                        "Error: transaction was evicted from a full mempool".to_string(),
+                        None::<()>,
+                    ))
+                }
+                TransactionResponse::AlreadyPending => {
+                    return Err(already_pending(transaction_hash))
+                }
+                TransactionResponse::AlreadyIncluded(receipt) => {
+                    return Err(already_included(transaction_hash, *receipt))
+                }
+                _ => {
+                    return Err(RpcError::owned(
+                        RpcErrorCode::InvalidParams.code(),
+                        "invalid response to `send` method".to_string(),
+                        None::<()>,
+                    ))
+                }
+            },
+            Err(e) => {
+                return Err(RpcError::owned(
+                    RpcErrorCode::Internal.code(),
+                    e.to_string(),
+                    None::<()>,
+                ))
+            }
+        }
+    }
+
+    fn pending_transactions() -> Result<ActorRef<PendingTransactionMessage>, RpcError> {
+        ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+            .map(ActorRef::from)
+            .ok_or_else(|| {
+                RpcError::owned(
+                    RpcErrorCode::Internal.code(),
+                    "unable to acquire pending transactions",
+                    None::<()>,
+                )
+            })
+    }
+
+    /// Where `transaction_hash` has got to, from the pending pool while it
+    /// is there and from its receipt once it has left.
+    async fn transaction_status(
+        &self,
+        transaction_hash: [u8; 32],
+    ) -> Result<TransactionStatus, RpcError> {
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let pending = match LasrRpcServerImpl::pending_transactions()?
+            .call(
+                |reply| PendingTransactionMessage::GetPendingByHash {
+                    transaction_hash,
+                    reply,
+                },
+                Some(EXECUTOR_REQUEST_TIMEOUT),
+            )
+            .await
+            .map_err(|e| internal(e.to_string()))?
+        {
+            CallResult::Success(pending) => pending,
+            CallResult::Timeout => {
+                return Err(internal(
+                    "pending transactions did not answer in time".to_string(),
+                ))
+            }
+            CallResult::SenderError => {
+                return Err(internal(
+                    "pending transactions dropped the request".to_string(),
+                ))
+            }
+        };
+        let status = match pending {
+            Some(summary) => match summary.status {
+                PendingStatus::Pending => TransactionStatus::Pending,
+                PendingStatus::Validating => TransactionStatus::Executing,
+                PendingStatus::Queued { waiting_on } => TransactionStatus::Queued { waiting_on },
+                PendingStatus::Scheduled { valid_after } => {
+                    TransactionStatus::Scheduled { valid_after }
+                }
+            },
+            None => {
+                let scheduler: ActorRef<SchedulerMessage> =
+                    ractor::registry::where_is(ActorType::Scheduler.to_string())
+                        .ok_or_else(|| internal("unable to acquire scheduler".to_string()))?
+                        .into();
+                let tx_hash = format!("0x{}", hex::encode(transaction_hash));
+                let receipt = match scheduler
+                    .call(
+                        |reply| SchedulerMessage::GetReceipt {
+                            transaction_hash: tx_hash.clone(),
+                            reply,
+                        },
+                        Some(EXECUTOR_REQUEST_TIMEOUT),
+                    )
+                    .await
+                    .map_err(|e| internal(e.to_string()))?
+                {
+                    CallResult::Success(receipt) => receipt,
+                    CallResult::Timeout => {
+                        return Err(internal("scheduler did not answer in time".to_string()))
+                    }
+                    CallResult::SenderError => {
+                        return Err(internal("scheduler dropped the request".to_string()))
+                    }
+                };
+                self.receipt_status(&tx_hash, receipt)
+            }
+        };
+
+        Ok(status)
+    }
+
+    /// The status of a transaction that has left the pending pool, from its
+    /// receipt and, once applied, the batch it went into.
+    fn receipt_status(
+        &self,
+        transaction_hash: &str,
+        receipt: Option<TransactionResponse>,
+    ) -> TransactionStatus {
+        match receipt {
+            None | Some(TransactionResponse::Evicted) => TransactionStatus::Unknown,
+            Some(TransactionResponse::Expired) => TransactionStatus::Expired,
+            Some(TransactionResponse::Replaced(replaced_by)) => {
+                TransactionStatus::Replaced { replaced_by }
+            }
+            Some(TransactionResponse::TransactionError(e)) => TransactionStatus::Failed {
+                description: e.description,
+                reason: e.code,
+            },
+            Some(_) => {
+                let settled = self
+                    .inclusion(transaction_hash)
+                    .batch_id
+                    .zip(self.batch_receipts.as_ref())
+                    .and_then(|(batch_id, receipts)| receipts.get(batch_id))
+                    .is_some_and(|batch| matches!(batch.status, BatchStatus::Settled { .. }));
+                if settled {
+                    TransactionStatus::Settled
+                } else {
+                    TransactionStatus::Included
+                }
+            }
+        }
+    }
+
+    fn inclusion(&self, transaction_hash: &str) -> Inclusion {
+        self.inclusions
+            .as_ref()
+            .and_then(|inclusions| inclusions.get(transaction_hash))
+            .unwrap_or_default()
+    }
+
+    /// The attestation the scheduler recorded for `transaction_hash`, if any.
+    async fn attestation(transaction_hash: &str) -> Result<Option<ExecutionAttestation>, RpcError> {
+        let internal = |e: String| RpcError::owned(RpcErrorCode::Internal.code(), e, None::<()>);
+        let scheduler: ActorRef<SchedulerMessage> =
+            ractor::registry::where_is(ActorType::Scheduler.to_string())
+                .ok_or_else(|| internal("unable to acquire scheduler".to_string()))?
+                .into();
+        match scheduler
+            .call(
+                |reply| SchedulerMessage::GetAttestation {
+                    transaction_hash: transaction_hash.to_string(),
+                    reply,
+                },
+                Some(EXECUTOR_REQUEST_TIMEOUT),
+            )
+            .await
+            .map_err(|e| internal(e.to_string()))?
+        {
+            CallResult::Success(attestation) => Ok(attestation),
+            CallResult::Timeout => Err(internal("scheduler did not answer in time".to_string())),
+            CallResult::SenderError => Err(internal(
+                "scheduler does not record attestations".to_string(),
+            )),
+        }
+    }
+
+    /// Limits inputs with `input_limits` instead of the default limits.
+    pub fn with_input_limits(mut self, input_limits: InputLimits) -> Self {
+        self.admission = self.admission.with_input_limits(input_limits);
+        self
+    }
+
+    /// Checks transactions with `admission` instead of the default checks.
+    pub fn with_admission(mut self, admission: Admission) -> Self {
+        self.admission = admission;
+        self
+    }
+
+    /// Turns away transactions that fail admission against their sender's
+    /// account and, for calls, `program`, or are denied by the admission
+    /// policy, with the reason in the error data, every transaction while
+    /// the node or one of its core actors is overloaded, and every
+    /// transaction once the node has started shutting down. One admitted is
+    /// waited for by the shutdown for as long as it is held.
+    async fn admit(
+        &self,
+        transaction: &Transaction,
+        program: Option<&Account>,
+    ) -> Result<Admitted, RpcError> {
+        self.load_shedder.check()?;
+        self.mailboxes.check()?;
+        let admitted = self.shutdown.admit().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::ShuttingDown.code(),
+                "the node is shutting down, retry against another node".to_string(),
+                None::<()>,
+            )
+        })?;
+        self.policy
+            .check(transaction)
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), Some(e)))?;
+        let sender = get_account(transaction.from(), ActorType::RpcServer).await;
+        self.admission
+            .check(transaction, sender.as_ref(), program)
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), Some(e)))?;
+        self.admission
+            .authorize(transaction, sender.as_ref(), ActorType::RpcServer)
+            .await
+            .map_err(|e| RpcError::owned(e.code().code(), e.to_string(), Some(e)))?;
+        Ok(admitted)
+    }
+
+    /// The keys the node signs with, rotated by `rotateKey` and reported by
+    /// `nodeStatus`. The attestations of their signers, current and retired,
+    /// are those `getAttestation` reports as trusted.
+    pub fn with_node_keys(mut self, keys: NodeKeys) -> Self {
+        self.node_keys = Some(keys);
+        self
+    }
+
+    /// The settlement layer providers `getNodeStatus` reports on.
+    pub fn with_settlement_providers(mut self, metrics: Arc<ProviderMetrics>) -> Self {
+        self.settlement_providers = Some(metrics);
+        self
+    }
+
+    /// Pending pool occupancy reported by `getNodeStatus`.
+    pub fn with_mempool_metrics(mut self, metrics: Arc<MempoolMetrics>) -> Self {
+        self.mempool = Some(metrics);
+        self
+    }
+
+    /// Calls retried by the scheduler, reported by `getNodeStatus`.
+    pub fn with_retry_metrics(mut self, metrics: Arc<RetryMetrics>) -> Self {
+        self.retries = Some(metrics);
+        self
+    }
+
+    /// The eo_server filter set `admin_reloadEventFilters` swaps.
+    pub fn with_event_filters(mut self, filters: EventFilters) -> Self {
+        self.event_filters = Some(filters);
+        self
+    }
+
+    /// The eo_server backfill `admin_backfillSettlementEvents` starts.
+    pub fn with_settlement_backfill(mut self, backfill: SettlementBackfill) -> Self {
+        self.settlement_backfill = Some(backfill);
+        self
+    }
+
+    /// The receipts `getBatchReceipt` reports, and `newBatch` subscribers
+    /// are notified of.
+    pub fn with_batch_receipts(mut self, receipts: BatchReceipts) -> Self {
+        self.batch_receipts = Some(receipts);
+        self
+    }
+
+    /// Reports the balances and batches of applied transactions from
+    /// `inclusions` in `getTransactionReceipt`.
+    pub fn with_transaction_inclusions(mut self, inclusions: TransactionInclusions) -> Self {
+        self.inclusions = Some(inclusions);
+        self
+    }
+
+    /// The programs `getProgram` and `listPrograms` report.
+    pub fn with_program_registry(mut self, programs: ProgramRegistry) -> Self {
+        self.programs = Some(programs);
+        self
+    }
+
+    /// The batches `getBatch`, `getLatestBatch` and `getBatches` report.
+    pub fn with_batch_index(mut self, batches: BatchIndex) -> Self {
+        self.batches = Some(batches);
+        self
+    }
+
+    /// The transactions `getTransactionsByAddress` and
+    /// `getTransactionsByProgram` look up.
+    pub fn with_transaction_index(mut self, index: TransactionIndex) -> Self {
+        self.transaction_index = Some(index);
+        self
+    }
+
+    /// The events `getEvents` looks up and `subscribeEvents` streams.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// The batches `subscribeBatches` streams, whole.
+    pub fn with_batch_feed(mut self, batch_feed: BatchFeed) -> Self {
+        self.batch_feed = Some(batch_feed);
+        self
+    }
+
+    /// The calls given up on that `admin_getDeadLetters` lists and
+    /// `admin_redriveDeadLetter` and `admin_purgeDeadLetters` take out.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetters) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// What each program has cost the node, as `admin_getProgramStats`
+    /// ranks them.
+    pub fn with_program_stats(mut self, program_stats: ProgramStats) -> Self {
+        self.program_stats = Some(program_stats);
+        self
+    }
+
+    /// Prunes blobs and receipts past the node's retention when
+    /// `admin_prune` asks, and answers for those it pruned with where on
+    /// the DA layer to retrieve them.
+    pub fn with_pruner(mut self, pruner: Pruner) -> Self {
+        self.pruner = Some(pruner);
+        self
+    }
+
+    /// Hands out funds through `faucet` instead of turning faucet requests
+    /// away.
+    pub fn with_faucet(mut self, faucet: Faucet) -> Self {
+        self.faucet = faucet;
+        self
+    }
+
+    /// Answers the `eth` methods for `chain_id` instead of
+    /// `DEFAULT_ETH_CHAIN_ID`.
+    pub fn with_eth_chain_id(mut self, chain_id: u64) -> Self {
+        self.eth_chain_id = chain_id;
+        self
+    }
+
+    /// The accounts and transaction outcomes `subscribe` subscribers are
+    /// notified of.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// The rate limiter the server holds clients to, reported and adjusted
+    /// by `admin_getRateLimits` and `admin_setRateLimits`.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// The DA client's record of its requests, behind the breaker `ready`
+    /// and `nodeStatus` report.
+    pub fn with_da_health(mut self, health: DaHealth) -> Self {
+        self.da_health = Some(health);
+        self
+    }
+
+    /// Reports the node not ready once `shutdown` has started.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Holds transactions to the admission policy of `policy`, reported and
+    /// swapped by `admin_getAdmissionPolicy` and `admin_setAdmissionPolicy`.
+    pub fn with_admission_policy(mut self, policy: PolicyGate) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Turns writes away while `load_shedder` finds the node overloaded.
+    pub fn with_load_shedder(mut self, load_shedder: LoadShedder) -> Self {
+        self.load_shedder = load_shedder;
+        self
+    }
+
+    /// Turns writes away while `mailboxes` finds a core actor overloaded,
+    /// and reports how far behind each is in `lasr_nodeStatus`.
+    pub fn with_mailboxes(mut self, mailboxes: MailboxMonitor) -> Self {
+        self.mailboxes = mailboxes;
+        self
+    }
+
+    /// Pages lists with `paginator` instead of the default one.
+    pub fn with_paginator(mut self, paginator: Paginator) -> Self {
+        self.paginator = paginator;
+        self
+    }
+
+    /// Simulates calls against `output_limits` instead of the default
+    /// limits.
+    pub fn with_output_limits(mut self, output_limits: OutputLimits) -> Self {
+        self.output_limits = output_limits;
+        self
+    }
+
+    /// Simulates calls holding the accounts they leave to `quota`, as the
+    /// batcher applies them.
+    pub fn with_storage_quota(mut self, quota: StorageQuota) -> Self {
+        self.storage_quota = quota;
+        self
+    }
+
+    /// Whether `simulatePayload` simulates unsigned payloads, which it does
+    /// not by default.
+    pub fn with_unsigned_simulation(mut self, unsigned_simulation: bool) -> Self {
+        self.unsigned_simulation = unsigned_simulation;
+        self
+    }
+
+    fn rate_limiter(&self) -> Result<&RateLimiter, RpcError> {
+        self.rate_limiter.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "requests are not rate limited on this node",
+                None::<()>,
+            )
+        })
+    }
+
+    fn node_keys(&self) -> Result<&NodeKeys, RpcError> {
+        self.node_keys.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "keys are not managed by this node",
+                None::<()>,
+            )
+        })
+    }
+
+    fn dead_letters(&self) -> Result<&DeadLetters, RpcError> {
+        self.dead_letters.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "failed calls are not dead lettered on this node",
+                None::<()>,
+            )
+        })
+    }
+
+    fn pruner(&self) -> Result<&Pruner, RpcError> {
+        self.pruner.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Disabled.code(),
+                "nothing is pruned on this node",
+                None::<()>,
+            )
+        })
+    }
+
+    /// Answers `Pruned` if the receipt of `tx_hash` is past the node's
+    /// retention.
+    fn receipt_pruned(&self, tx_hash: &str) -> Result<(), RpcError> {
+        match &self.pruner {
+            Some(pruner) => pruner.receipt_pruned(tx_hash).map_err(RpcError::from),
+            None => Ok(()),
+        }
+    }
+
+    fn settlement_backfill(&self) -> Result<&SettlementBackfill, RpcError> {
+        self.settlement_backfill.as_ref().ok_or_else(|| {
+            RpcError::owned(
+                RpcErrorCode::Internal.code(),
+                "settlement events cannot be backfilled on this node",
+                None::<()>,
+            )
+        })
+    }
+
+    /// Has the engine mint `amount` of `program_id` to `address`, answering
+    /// the minting transaction's hash.
+    async fn mint(
+        address: Address,
+        program_id: Address,
+        amount: U256,
+    ) -> Result<String, FaucetError> {
+        let engine: ActorRef<EngineMessage> =
+            ractor::registry::where_is(ActorType::Engine.to_string())
+                .ok_or_else(|| FaucetError::Custom("engine is not running".to_string()))?
+                .into();
+        match engine
+            .call(
+                |reply| EngineMessage::Mint {
+                    address,
+                    program_id,
+                    amount,
+                    id: Faucet::mint_id(),
+                    reply,
+                },
+                Some(EXECUTOR_REQUEST_TIMEOUT),
+            )
+            .await
+            .map_err(|e| FaucetError::Custom(e.to_string()))?
+        {
+            CallResult::Success(minted) => minted.map_err(FaucetError::Custom),
+            CallResult::Timeout => Err(FaucetError::Custom(
+                "engine did not mint in time".to_string(),
+            )),
+            CallResult::SenderError => {
+                Err(FaucetError::Custom("engine dropped the mint".to_string()))
+            }
+        }
+    }
+
+    /// The executor's own report of its health, or an unhealthy report if it
+    /// does not answer in time.
+    async fn executor_health() -> ExecutorHealth {
+        let unresponsive = |error: String| ExecutorHealth {
+            status: ExecutorStatus::Unhealthy,
+            last_error: Some(error),
+            ..Default::default()
+        };
+        let Some(executor) = ractor::registry::where_is(ActorType::Executor.to_string()) else {
+            return unresponsive("executor is not running".to_string());
+        };
+        let executor: ActorRef<ExecutorMessage> = executor.into();
+        match executor
+            .call(
+                |reply| ExecutorMessage::GetHealth { reply },
+                Some(EXECUTOR_REQUEST_TIMEOUT),
+            )
+            .await
+        {
+            Ok(CallResult::Success(health)) => health,
+            Ok(CallResult::Timeout) => unresponsive(format!(
+                "executor did not report its health within {:?}",
+                EXECUTOR_REQUEST_TIMEOUT
+            )),
+            Ok(CallResult::SenderError) => {
+                unresponsive("executor does not report its health".to_string())
+            }
+            Err(e) => unresponsive(e.to_string()),
+        }
+    }
+
+    async fn send_rpc_call_method_to_self(
+        &self,
+        transaction: Transaction,
+        reply: RpcReplyPort<RpcMessage>,
+    ) -> Result<(), RpcResponseError> {
+        tracing::info!("Sending RPC call method to proxy actor");
+        self.proxy
+            .cast(RpcMessage::Request {
+                method: Box::new(RpcRequestMethod::Call { transaction }),
+                reply,
+            })
+            .map_err(|e| RpcResponseError {
+                description: e.to_string(),
+                code: RpcErrorCode::Internal,
+            })
+    }
+
+    async fn send_rpc_send_method_to_self(
+        &self,
+        transaction: Transaction,
+        replace: bool,
+        reply: RpcReplyPort<RpcMessage>,
+    ) -> Result<(), RpcResponseError> {
+        self.get_myself()
+            .cast(RpcMessage::Request {
+                method: Box::new(RpcRequestMethod::Send {
+                    transaction,
+                    replace,
+                }),
+                reply,
+            })
+            .map_err(|e| RpcResponseError {
+                description: e.to_string(),
+                code: RpcErrorCode::Internal,
+            })
+    }
+
+    async fn send_rpc_register_program_method_to_self(
+        &self,
+        transaction: Transaction,
+        reply: RpcReplyPort<RpcMessage>,
+    ) -> Result<(), RpcResponseError> {
+        self.get_myself()
+            .cast(RpcMessage::Request {
+                method: Box::new(RpcRequestMethod::RegisterProgram { transaction }),
                 reply,
             })
             .map_err(|e| RpcResponseError {
                 description: e.to_string(),
+                code: RpcErrorCode::Internal,
             })
     }
 
@@ -435,7 +2640,7 @@ impl Actor for LasrRpcServerActor {
             }
             RpcMessage::Response { response, reply } => {
                 let reply = reply.ok_or(Box::new(RpcError::owned(
-                    INTERNAL_ERROR_CODE,
+                    RpcErrorCode::Internal.code(),
                     "Unable to acquire rpc reply sender in RpcMessage::Response".to_string(),
                     None::<()>,
                 )))?;
@@ -1,22 +1,29 @@
 #![allow(unused)]
 use crate::{
-    create_handler, da_client, eo_server, get_account, handle_actor_response,
-    process_group_changed, Coerce,
+    cast_between, create_handler, da_client, eo_server, get_account, get_actor_ref,
+    get_attestation, get_receipt, handle_actor_response, lifecycle, mailbox_handling,
+    mailbox_probed, process_group_changed, release_debit, CallRetries, Coerce, DeadLetter,
+    DeadLetters, LifecycleStage, MailboxMonitor, Notifications, PendingTransactionError,
+    RecentTransactions, RetryFailure, RetryMetrics, RetryPolicy,
+    DEFAULT_RECENT_TRANSACTIONS_WINDOW,
 };
 use async_trait::async_trait;
 use futures::stream::FuturesUnordered;
 use jsonrpsee::types::ErrorObjectOwned as RpcError;
 use lasr_messages::{
     AccountCacheMessage, ActorName, ActorType, DaClientMessage, EngineMessage, EoMessage,
-    RpcMessage, RpcResponseError, SchedulerMessage, SupervisorType, TransactionResponse,
-    ValidatorMessage,
+    PendingTransactionMessage, RetryReason, RpcErrorCode, RpcMessage, RpcResponseError,
+    SchedulerMessage, SupervisorType, TransactionResponse, ValidatorMessage,
 };
-use lasr_types::{Address, RecoverableSignature, Transaction};
+use lasr_types::{Address, ExecutionAttestation, RecoverableSignature, Transaction};
 use ractor::{concurrency::oneshot, Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
 use ractor::{ActorCell, SupervisionEvent};
 use std::default;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 use thiserror::*;
 use tokio::sync::mpsc::Sender;
 use tokio::task::JoinHandle;
@@ -41,9 +48,398 @@ pub struct SchedulerState {
     pub scheduler_results_handler: JoinHandle<()>,
 }
 
+/// Reply ports waiting on in-flight transactions, and the outcomes of
+/// transactions that have already been applied. A transaction resubmitted
+/// while in flight is answered `AlreadyPending`, and one resubmitted
+/// afterwards `AlreadyIncluded` with the original receipt; neither is
+/// executed again. Submissions from every connection pass through the
+/// scheduler one at a time, so of duplicates arriving together only the
+/// first is dispatched.
+pub struct TransactionReplies {
+    /// Transactions in flight, with the reply port of their submission until
+    /// it is answered.
+    waiting: HashMap<String, Option<RpcReplyPort<RpcMessage>>>,
+    /// Transactions answered as held until they are valid. Nothing waits on
+    /// them, so being turned away once released is recorded as their
+    /// outcome.
+    scheduled: HashSet<String>,
+    applied: RecentTransactions<TransactionResponse>,
+    /// Attestations of applied calls, kept alongside their receipts.
+    attestations: RecentTransactions<ExecutionAttestation>,
+    /// Whether applied outcomes are also written to, and looked up from, the
+    /// persistent receipt index once they fall out of `applied`.
+    persist_receipts: bool,
+    /// Calls being retried after failing through no fault of their sender.
+    retries: CallRetries,
+    /// Calls in flight, kept so one given up on can be parked with what it
+    /// was.
+    calls: HashMap<String, Transaction>,
+    /// Calls given up on after exhausting their retries.
+    dead_letters: DeadLetters,
+    /// Where the outcomes of transactions are published.
+    notifications: Notifications,
+    /// Set once the node starts shutting down, after which new transactions
+    /// are turned away.
+    draining: bool,
+}
+
+impl TransactionReplies {
+    /// Remembers the outcomes of the last `window` transactions applied,
+    /// retrying the calls that failed transiently by `retry`.
+    pub fn new(window: usize, persist_receipts: bool, retry: RetryPolicy) -> Self {
+        Self {
+            waiting: HashMap::new(),
+            scheduled: HashSet::new(),
+            applied: RecentTransactions::new(window),
+            attestations: RecentTransactions::new(window),
+            persist_receipts,
+            retries: CallRetries::new(retry),
+            calls: HashMap::new(),
+            dead_letters: DeadLetters::default(),
+            notifications: Notifications::default(),
+            draining: false,
+        }
+    }
+
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetters) -> Self {
+        self.dead_letters = dead_letters;
+        self
+    }
+
+    pub fn with_retry_metrics(mut self, metrics: Arc<RetryMetrics>) -> Self {
+        self.retries = self.retries.with_metrics(metrics);
+        self
+    }
+
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Returns the receipt of an already applied transaction, checking the
+    /// recent window before the persistent receipt index. An expired or
+    /// evicted transaction may be submitted again, so its receipt is not
+    /// returned.
+    async fn applied_receipt(&self, transaction_hash: &str) -> Option<TransactionResponse> {
+        let receipt = match self.applied.get(transaction_hash) {
+            Some(receipt) => Some(receipt.clone()),
+            None if self.persist_receipts => get_receipt(transaction_hash.to_string()).await,
+            None => None,
+        };
+        receipt.filter(|receipt| {
+            !matches!(
+                receipt,
+                TransactionResponse::Expired | TransactionResponse::Evicted
+            )
+        })
+    }
+
+    /// Returns the attestation of an applied call, checking the recent window
+    /// before the persistent index.
+    async fn attestation(&self, transaction_hash: &str) -> Option<ExecutionAttestation> {
+        if let Some(attestation) = self.attestations.get(transaction_hash) {
+            return Some(attestation.clone());
+        }
+        if self.persist_receipts {
+            return get_attestation(transaction_hash.to_string()).await;
+        }
+        None
+    }
+
+    /// The receipt of a call parked in the dead letters, failed with the
+    /// last failure it had.
+    fn dead_letter_receipt(&self, transaction_hash: &str) -> Option<TransactionResponse> {
+        self.dead_letters.get(transaction_hash).map(|letter| {
+            TransactionResponse::TransactionError(RpcResponseError {
+                description: letter.description(),
+                code: RpcErrorCode::ExhaustedRetries,
+            })
+        })
+    }
+
+    /// Holds `rpc_reply` for `transaction_hash`, returning `true` if the
+    /// transaction should be dispatched. A duplicate of one in flight is
+    /// answered `AlreadyPending` instead.
+    fn wait_on(&mut self, transaction_hash: String, rpc_reply: RpcReplyPort<RpcMessage>) -> bool {
+        if self.waiting.contains_key(&transaction_hash) {
+            tracing::warn!("{transaction_hash} is already in flight, not dispatching it again");
+            reply(rpc_reply, TransactionResponse::AlreadyPending);
+            return false;
+        }
+        self.waiting.insert(transaction_hash, Some(rpc_reply));
+        true
+    }
+
+    /// Sends `response` to the submission of `transaction_hash`, which is no
+    /// longer in flight. Unless it only turns that submission away, it is
+    /// the transaction's outcome and is published as well. Unless it is a
+    /// duplicate's, what the transaction held of its sender's is released.
+    fn respond(&mut self, transaction_hash: &str, response: TransactionResponse) {
+        if !matches!(
+            response,
+            TransactionResponse::AlreadyPending | TransactionResponse::MempoolFull(_)
+        ) {
+            self.notifications
+                .transaction_answered(transaction_hash, &response);
+        }
+        if !matches!(response, TransactionResponse::AlreadyPending) {
+            release_debit(transaction_hash);
+        }
+        self.calls.remove(transaction_hash);
+        if let Some(Some(rpc_reply)) = self.waiting.remove(transaction_hash) {
+            reply(rpc_reply, response);
+        }
+    }
+
+    /// Sends `response` to the submission of `transaction_hash`, which stays
+    /// in flight.
+    fn acknowledge(&mut self, transaction_hash: &str, response: TransactionResponse) {
+        if let Some(rpc_reply) = self
+            .waiting
+            .get_mut(transaction_hash)
+            .and_then(|waiting| waiting.take())
+        {
+            reply(rpc_reply, response);
+        }
+    }
+
+    /// Answers the submission of `transaction_hash` that it is held until
+    /// `valid_after`. It is no longer waited on, its outcome being as long
+    /// in coming, so it neither holds up a shutdown nor is answered again.
+    fn scheduled(&mut self, transaction_hash: &str, valid_after: u64) {
+        self.scheduled.insert(transaction_hash.to_string());
+        if let Some(Some(rpc_reply)) = self.waiting.remove(transaction_hash) {
+            reply(rpc_reply, TransactionResponse::Scheduled { valid_after });
+        }
+    }
+
+    /// Answers a submission turned away with `response`, recording it as the
+    /// outcome of one that was held until it was valid.
+    fn turned_away(&mut self, transaction_hash: String, response: TransactionResponse) {
+        match self.scheduled.contains(&transaction_hash) {
+            true => self.record_applied(transaction_hash, response),
+            false => self.respond(&transaction_hash, response),
+        }
+    }
+
+    /// Records the outcome of a transaction applied or replaced, and answers
+    /// everything waiting on it.
+    fn record_applied(&mut self, transaction_hash: String, receipt: TransactionResponse) {
+        self.scheduled.remove(&transaction_hash);
+        self.respond(&transaction_hash, receipt.clone());
+        if self.persist_receipts {
+            if let Some(account_cache) =
+                ractor::registry::where_is(ActorType::AccountCache.to_string())
+            {
+                let account_cache: ActorRef<AccountCacheMessage> = account_cache.into();
+                account_cache
+                    .cast(AccountCacheMessage::WriteReceipt {
+                        transaction_hash: transaction_hash.clone(),
+                        receipt: receipt.clone(),
+                    })
+                    .typecast()
+                    .log_err(|e| {
+                        SchedulerError::Custom(format!(
+                            "failed to cast receipt for {transaction_hash} to account cache: {e:?}"
+                        ))
+                    });
+            }
+        }
+        self.applied.insert(transaction_hash, receipt);
+    }
+
+    /// Hands a call that failed for `reason` back to the pending transactions
+    /// to run again, or parks it in the dead letters and answers it with
+    /// `description` once it has been retried as often as it may be. A call
+    /// failed that way is not recorded, so it may be re-driven or submitted
+    /// again.
+    fn retry_call(&mut self, transaction_hash: String, reason: RetryReason, description: String) {
+        let after = match self
+            .retries
+            .fail(&transaction_hash, reason, description.clone())
+        {
+            Ok(after) => after,
+            Err(failures) => {
+                tracing::warn!("{transaction_hash} failed for good after retries: {description}");
+                call_failed(&transaction_hash);
+                self.dead_letter(&transaction_hash, failures);
+                self.respond(
+                    &transaction_hash,
+                    TransactionResponse::TransactionError(RpcResponseError {
+                        description,
+                        code: RpcErrorCode::ExhaustedRetries,
+                    }),
+                );
+                return;
+            }
+        };
+        tracing::warn!("retrying {transaction_hash} in {after:?}, it failed with {reason}");
+        let retried = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+            ActorType::PendingTransactions,
+        )
+        .map(|pending| {
+            pending.cast(PendingTransactionMessage::RetryCall {
+                transaction_hash: transaction_hash.clone(),
+                reason,
+                after,
+            })
+        });
+        if !matches!(retried, Some(Ok(()))) {
+            tracing::error!("unable to hand {transaction_hash} back to pending transactions");
+            self.retries.clear(&transaction_hash);
+            self.respond(
+                &transaction_hash,
+                TransactionResponse::TransactionError(RpcResponseError {
+                    description,
+                    code: RpcErrorCode::Internal,
+                }),
+            );
+        }
+    }
+
+    /// Parks a call given up on after `failures` in the dead letters.
+    fn dead_letter(&mut self, transaction_hash: &str, failures: Vec<RetryFailure>) {
+        let Some(transaction) = self.calls.get(transaction_hash).cloned() else {
+            tracing::error!("{transaction_hash} was not submitted here, unable to park it");
+            return;
+        };
+        self.dead_letters
+            .park(DeadLetter::new(transaction, failures))
+            .typecast()
+            .log_err(|e| {
+                SchedulerError::Custom(format!(
+                    "failed to park {transaction_hash} in the dead letters: {e:?}"
+                ))
+            });
+    }
+
+    /// Records a call that failed because of the call itself.
+    fn fail_call(&mut self, transaction_hash: String, description: String) {
+        self.retries.clear(&transaction_hash);
+        call_failed(&transaction_hash);
+        self.record_applied(
+            transaction_hash,
+            TransactionResponse::TransactionError(RpcResponseError {
+                description,
+                code: RpcErrorCode::ProgramReverted,
+            }),
+        );
+    }
+
+    /// Records the attestation of an applied call next to its receipt.
+    fn record_attestation(&mut self, transaction_hash: String, attestation: ExecutionAttestation) {
+        if self.persist_receipts {
+            if let Some(account_cache) =
+                ractor::registry::where_is(ActorType::AccountCache.to_string())
+            {
+                let account_cache: ActorRef<AccountCacheMessage> = account_cache.into();
+                account_cache
+                    .cast(AccountCacheMessage::WriteAttestation {
+                        transaction_hash: transaction_hash.clone(),
+                        attestation: attestation.clone(),
+                    })
+                    .typecast()
+                    .log_err(|e| {
+                        SchedulerError::Custom(format!(
+                            "failed to cast attestation for {transaction_hash} to account cache: {e:?}"
+                        ))
+                    });
+            }
+        }
+        self.attestations.insert(transaction_hash, attestation);
+    }
+}
+
+/// Turns `transaction_hash` away from a node that is shutting down.
+fn shutting_down(rpc_reply: RpcReplyPort<RpcMessage>, transaction_hash: &str) {
+    tracing::warn!("turning {transaction_hash} away, the node is shutting down");
+    reply(
+        rpc_reply,
+        TransactionResponse::TransactionError(RpcResponseError {
+            description: format!(
+                "Transaction {} was not accepted, the node is shutting down, retry later",
+                transaction_hash
+            ),
+            code: RpcErrorCode::ShuttingDown,
+        }),
+    );
+}
+
+fn actors_overloaded(
+    rpc_reply: RpcReplyPort<RpcMessage>,
+    transaction_hash: &str,
+    overloaded: &[ActorType],
+) {
+    let actors = overloaded
+        .iter()
+        .map(|actor_type| actor_type.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::warn!("turning {transaction_hash} away, behind on the {actors} mailboxes");
+    reply(
+        rpc_reply,
+        TransactionResponse::TransactionError(RpcResponseError {
+            description: format!(
+                "Transaction {} was not accepted, the node is behind on its {} mailboxes, retry later",
+                transaction_hash, actors
+            ),
+            code: RpcErrorCode::Overloaded,
+        }),
+    );
+}
+
+fn reply(rpc_reply: RpcReplyPort<RpcMessage>, response: TransactionResponse) {
+    let message = RpcMessage::Response {
+        response: Ok(response),
+        reply: None,
+    };
+    rpc_reply.send(message);
+}
+
+/// Tells the pending transactions a call failed for good, so the calls
+/// waiting on it are released.
+fn call_failed(transaction_hash: &str) {
+    if let Some(pending) = get_actor_ref::<PendingTransactionMessage, PendingTransactionError>(
+        ActorType::PendingTransactions,
+    ) {
+        pending
+            .cast(PendingTransactionMessage::CallFailed {
+                transaction_hash: transaction_hash.to_string(),
+            })
+            .typecast()
+            .log_err(|e| {
+                SchedulerError::Custom(format!(
+                    "failed to cast CallFailed for {transaction_hash} to pending transactions: {e:?}"
+                ))
+            });
+    }
+}
+
 /// The actor struct for the scheduler actor
-#[derive(Debug, Clone, Default)]
-pub struct TaskScheduler;
+#[derive(Debug, Clone)]
+pub struct TaskScheduler {
+    retry_metrics: Arc<RetryMetrics>,
+    notifications: Notifications,
+    dead_letters: DeadLetters,
+    mailboxes: MailboxMonitor,
+    retry: RetryPolicy,
+    persist_receipts: bool,
+    recent_window: usize,
+}
+
+impl Default for TaskScheduler {
+    fn default() -> Self {
+        Self {
+            retry_metrics: Arc::default(),
+            notifications: Notifications::default(),
+            dead_letters: DeadLetters::default(),
+            mailboxes: MailboxMonitor::default(),
+            retry: RetryPolicy::default(),
+            persist_receipts: true,
+            recent_window: DEFAULT_RECENT_TRANSACTIONS_WINDOW,
+        }
+    }
+}
 impl ActorName for TaskScheduler {
     fn name(&self) -> ractor::ActorName {
         ActorType::Scheduler.to_string()
@@ -53,7 +449,53 @@ impl ActorName for TaskScheduler {
 impl TaskScheduler {
     /// Creates a new TaskScheduler with a reference to the Registry actor
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Calls retried after failing through no fault of their sender, by
+    /// reason.
+    pub fn retry_metrics(&self) -> Arc<RetryMetrics> {
+        Arc::clone(&self.retry_metrics)
+    }
+
+    /// Publishes the outcome of every transaction to `notifications`.
+    pub fn with_notifications(mut self, notifications: Notifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Parks calls given up on after exhausting their retries in
+    /// `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetters) -> Self {
+        self.dead_letters = dead_letters;
+        self
+    }
+
+    /// Turns new transactions away while `mailboxes` finds an actor
+    /// overloaded.
+    pub fn with_mailboxes(mut self, mailboxes: MailboxMonitor) -> Self {
+        self.mailboxes = mailboxes;
+        self
+    }
+
+    /// Retries the calls that failed through no fault of their sender by
+    /// `retry`.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Whether applied outcomes are also written to, and looked up from, the
+    /// persistent receipt index once they fall out of the recent window.
+    pub fn with_persisted_receipts(mut self, persist: bool) -> Self {
+        self.persist_receipts = persist;
+        self
+    }
+
+    /// Remembers the outcomes of the last `window` transactions applied.
+    pub fn with_recent_window(mut self, window: usize) -> Self {
+        self.recent_window = window;
+        self
     }
 
     async fn handle_get_account_request(
@@ -77,6 +519,7 @@ impl TaskScheduler {
                         description:
                             "unable to find account for address {address:?} in Persistence Store or Protocol Cache"
                                 .to_string(),
+                        code: RpcErrorCode::NotFound,
                     }),
                     reply: None,
                 })
@@ -86,7 +529,11 @@ impl TaskScheduler {
         Ok(())
     }
 
-    fn handle_send(&self, transaction: Transaction) -> Result<(), Box<dyn std::error::Error>> {
+    fn handle_send(
+        &self,
+        transaction: Transaction,
+        replace: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("scheduler handling send: {}", transaction.hash_string());
         let engine_actor: ActorRef<EngineMessage> =
             ractor::registry::where_is(ActorType::Engine.to_string())
@@ -95,9 +542,17 @@ impl TaskScheduler {
                 )))?
                 .into();
 
-        let message = EngineMessage::Send { transaction };
+        let message = EngineMessage::Send {
+            transaction,
+            replace,
+        };
 
-        engine_actor.cast(message)?;
+        cast_between(
+            ActorType::Scheduler,
+            ActorType::Engine,
+            &engine_actor,
+            message,
+        )?;
 
         Ok(())
     }
@@ -112,7 +567,12 @@ impl TaskScheduler {
                 .into();
 
         let message = EngineMessage::Call { transaction };
-        engine_actor.cast(message)?;
+        cast_between(
+            ActorType::Scheduler,
+            ActorType::Engine,
+            &engine_actor,
+            message,
+        )?;
 
         Ok(())
     }
@@ -130,7 +590,12 @@ impl TaskScheduler {
 
         let message = EngineMessage::RegisterProgram { transaction };
 
-        engine_actor.cast(message)?;
+        cast_between(
+            ActorType::Scheduler,
+            ActorType::Engine,
+            &engine_actor,
+            message,
+        )?;
 
         Ok(())
     }
@@ -139,7 +604,7 @@ impl TaskScheduler {
 #[async_trait]
 impl Actor for TaskScheduler {
     type Msg = SchedulerMessage;
-    type State = HashMap<String, RpcReplyPort<RpcMessage>>;
+    type State = TransactionReplies;
     type Arguments = ();
 
     async fn pre_start(
@@ -147,7 +612,12 @@ impl Actor for TaskScheduler {
         _myself: ActorRef<Self::Msg>,
         args: (),
     ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(HashMap::new())
+        Ok(
+            TransactionReplies::new(self.recent_window, self.persist_receipts, self.retry)
+                .with_retry_metrics(self.retry_metrics())
+                .with_notifications(self.notifications.clone())
+                .with_dead_letters(self.dead_letters.clone()),
+        )
     }
 
     async fn handle(
@@ -156,32 +626,86 @@ impl Actor for TaskScheduler {
         message: Self::Msg,
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
+        let _handling = mailbox_handling(ActorType::Scheduler);
         match message {
+            SchedulerMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Scheduler, probe),
             SchedulerMessage::Call {
                 transaction,
                 rpc_reply,
             } => {
                 tracing::info!("Scheduler received RPC `call` method. Prepping to send to Engine");
-                // Convert handle_call to async, store future in Arc<Mutex<FuturesUnordered>> in `Self::State`
-                // handle futures in separate thread.
-                self.handle_call(transaction.clone());
-                state.insert(transaction.hash_string(), rpc_reply);
+                let transaction_hash = transaction.hash_string();
+                let _span = lifecycle::stage(LifecycleStage::Scheduler, &transaction_hash);
+                let overloaded = self.mailboxes.overloaded();
+                if state.draining {
+                    shutting_down(rpc_reply, &transaction_hash);
+                } else if !overloaded.is_empty() {
+                    actors_overloaded(rpc_reply, &transaction_hash, &overloaded);
+                } else if let Some(receipt) = state.applied_receipt(&transaction_hash).await {
+                    tracing::warn!(
+                        "call {transaction_hash} was already applied, returning its receipt"
+                    );
+                    reply(
+                        rpc_reply,
+                        TransactionResponse::AlreadyIncluded(Box::new(receipt)),
+                    );
+                } else if state.wait_on(transaction_hash.clone(), rpc_reply) {
+                    // A call parked in the dead letters and submitted again
+                    // is run afresh.
+                    if let Ok(Some(_)) = state.dead_letters.take(&transaction_hash) {
+                        tracing::warn!("{transaction_hash} was submitted again, taking it out of the dead letters");
+                    }
+                    state.calls.insert(transaction_hash, transaction.clone());
+                    // Convert handle_call to async, store future in Arc<Mutex<FuturesUnordered>> in `Self::State`
+                    // handle futures in separate thread.
+                    self.handle_call(transaction);
+                }
             }
             SchedulerMessage::Send {
                 transaction,
+                replace,
                 rpc_reply,
             } => {
                 tracing::info!("Scheduler received RPC `send` method. Prepping to send to Pending Transactions");
-                self.handle_send(transaction.clone());
-                state.insert(transaction.hash_string(), rpc_reply);
+                let transaction_hash = transaction.hash_string();
+                let _span = lifecycle::stage(LifecycleStage::Scheduler, &transaction_hash);
+                let overloaded = self.mailboxes.overloaded();
+                if state.draining {
+                    shutting_down(rpc_reply, &transaction_hash);
+                } else if !overloaded.is_empty() {
+                    actors_overloaded(rpc_reply, &transaction_hash, &overloaded);
+                } else if let Some(receipt) = state.applied_receipt(&transaction_hash).await {
+                    tracing::warn!(
+                        "send {transaction_hash} was already applied, returning its receipt"
+                    );
+                    reply(
+                        rpc_reply,
+                        TransactionResponse::AlreadyIncluded(Box::new(receipt)),
+                    );
+                } else if state.wait_on(transaction_hash, rpc_reply) {
+                    self.handle_send(transaction, replace);
+                }
             }
             SchedulerMessage::RegisterProgram {
                 transaction,
                 rpc_reply,
             } => {
                 tracing::info!("Scheduler received RPC `registerProgram` method. Prepping to send to Validator & Engine");
-                self.handle_register_program(transaction.clone());
-                state.insert(transaction.hash_string(), rpc_reply);
+                let transaction_hash = transaction.hash_string();
+                let overloaded = self.mailboxes.overloaded();
+                if state.draining {
+                    shutting_down(rpc_reply, &transaction_hash);
+                } else if !overloaded.is_empty() {
+                    actors_overloaded(rpc_reply, &transaction_hash, &overloaded);
+                } else if let Some(receipt) = state.applied_receipt(&transaction_hash).await {
+                    tracing::warn!("registration {transaction_hash} was already applied, returning its receipt");
+                    reply(
+                        rpc_reply,
+                        TransactionResponse::AlreadyIncluded(Box::new(receipt)),
+                    );
+                } else if state.wait_on(transaction_hash, rpc_reply) {
+                    self.handle_register_program(transaction);
+                }
             }
             SchedulerMessage::GetAccount { address, rpc_reply } => {
                 tracing::debug!("Scheduler received RPC `getAccount` method for account: {:?}. Prepping to check cache", address);
@@ -190,96 +714,183 @@ impl Actor for TaskScheduler {
                 // if not in cache check DA
                 // if not in DA check archives
             }
+            SchedulerMessage::GetAttestation {
+                transaction_hash,
+                reply,
+            } => {
+                let _ = reply.send(state.attestation(&transaction_hash).await);
+            }
+            SchedulerMessage::GetReceipt {
+                transaction_hash,
+                reply,
+            } => {
+                let receipt = match state.applied_receipt(&transaction_hash).await {
+                    Some(receipt) => Some(receipt),
+                    None => state.dead_letter_receipt(&transaction_hash),
+                };
+                let _ = reply.send(receipt);
+            }
             SchedulerMessage::TransactionApplied {
                 transaction_hash,
                 token,
             } => {
                 tracing::warn!("Received TransactionApplied message, checking for RPCReplyPort");
-                if let Some(reply_port) = state.remove(&transaction_hash) {
-                    let response = Ok(TransactionResponse::SendResponse(token));
-                    let message = RpcMessage::Response {
-                        response,
-                        reply: None,
-                    };
-                    reply_port.send(message);
-                }
+                state.record_applied(transaction_hash, TransactionResponse::SendResponse(token));
             }
             SchedulerMessage::SendTransactionFailure {
                 transaction_hash,
                 error,
             } => {
-                if let Some(reply_port) = state.remove(&transaction_hash) {
-                    let response = Ok(TransactionResponse::TransactionError(RpcResponseError {
+                state.turned_away(
+                    transaction_hash,
+                    TransactionResponse::TransactionError(RpcResponseError {
                         description: error.to_string(),
-                    }));
-
-                    let message = RpcMessage::Response {
-                        response,
-                        reply: None,
-                    };
-                    reply_port.send(message);
-                }
+                        code: error.error_code(),
+                    }),
+                );
+            }
+            SchedulerMessage::TransactionReplaced {
+                transaction_hash,
+                replaced_by,
+            } => {
+                tracing::warn!(
+                    "{transaction_hash} was replaced by {replaced_by} before it was validated"
+                );
+                state.record_applied(transaction_hash, TransactionResponse::Replaced(replaced_by));
+            }
+            SchedulerMessage::TransactionExpired { transaction_hash } => {
+                tracing::warn!("{transaction_hash} expired before it was validated");
+                state.record_applied(transaction_hash, TransactionResponse::Expired);
+            }
+            SchedulerMessage::TransactionScheduled {
+                transaction_hash,
+                valid_after,
+            } => {
+                tracing::info!("{transaction_hash} is held until it is valid at {valid_after}");
+                state.scheduled(&transaction_hash, valid_after);
+            }
+            SchedulerMessage::MempoolFull {
+                transaction_hash,
+                full,
+            } => {
+                state.turned_away(transaction_hash, TransactionResponse::MempoolFull(full));
+            }
+            SchedulerMessage::TransactionAlreadyPending { transaction_hash } => {
+                state.respond(&transaction_hash, TransactionResponse::AlreadyPending);
+            }
+            SchedulerMessage::TransactionEvicted { transaction_hash } => {
+                tracing::warn!("{transaction_hash} was evicted from a full pending pool");
+                state.record_applied(transaction_hash, TransactionResponse::Evicted);
             }
             SchedulerMessage::RegistrationSuccess {
                 transaction,
                 program_id,
             } => {
-                if let Some(reply_port) = state.remove(&transaction.hash_string()) {
-                    let response = Ok(TransactionResponse::RegisterProgramResponse(Some(
-                        program_id.to_full_string(),
-                    )));
-
-                    let message = RpcMessage::Response {
-                        response,
-                        reply: None,
-                    };
-                    reply_port.send(message);
-                }
+                state.record_applied(
+                    transaction.hash_string(),
+                    TransactionResponse::RegisterProgramResponse(Some(program_id.to_full_string())),
+                );
             }
             SchedulerMessage::CallTransactionAsyncPending { transaction_hash } => {
-                if let Some(reply_port) = state.remove(&transaction_hash) {
-                    let response = Ok(TransactionResponse::AsyncCallResponse(transaction_hash));
-
-                    let message = RpcMessage::Response {
-                        response,
-                        reply: None,
-                    };
-                    reply_port.send(message);
-                };
+                // Not a final outcome, so it is not recorded as a receipt and
+                // the call stays in flight.
+                let response = TransactionResponse::AsyncCallResponse(transaction_hash.clone());
+                state.acknowledge(&transaction_hash, response);
             }
             SchedulerMessage::CallTransactionApplied {
                 transaction_hash,
                 account,
+                attestation,
             } => {
-                if let Some(reply_port) = state.remove(&transaction_hash) {
-                    let response = Ok(TransactionResponse::CallResponse(account));
-                    let message = RpcMessage::Response {
-                        response,
-                        reply: None,
-                    };
-                    reply_port.send(message);
+                state.retries.clear(&transaction_hash);
+                if let Some(attestation) = attestation {
+                    state.record_attestation(transaction_hash.clone(), attestation);
                 }
+                state.record_applied(transaction_hash, TransactionResponse::CallResponse(account));
             }
             SchedulerMessage::CallTransactionFailure {
                 transaction_hash,
                 outputs,
                 error,
             } => {
-                if let Some(reply_port) = state.remove(&transaction_hash) {
-                    let response = Ok(TransactionResponse::TransactionError(RpcResponseError {
-                        description: format!(
-                            "Transaction {} failed due to {}: {}",
-                            transaction_hash, error, outputs
-                        ),
-                    }));
-
-                    let message = RpcMessage::Response {
-                        response,
-                        reply: None,
-                    };
-                    reply_port.send(message);
+                let description = format!(
+                    "Transaction {} failed due to {}: {}",
+                    transaction_hash, error, outputs
+                );
+                state.fail_call(transaction_hash, description);
+            }
+            SchedulerMessage::ExecutorSaturated {
+                transaction_hash,
+                queued,
+            } => {
+                tracing::warn!(
+                    "executor saturated with {queued} executions queued, pushing back {transaction_hash}"
+                );
+                let description = format!(
+                    "Transaction {} was not executed, the executor is at capacity with {} executions queued, retry later",
+                    transaction_hash, queued
+                );
+                state.retry_call(transaction_hash, RetryReason::Saturated, description);
+            }
+            SchedulerMessage::ExecutorRestarting { transaction_hash } => {
+                tracing::warn!("executor restarted while {transaction_hash} was executing");
+                let description = format!(
+                    "Transaction {} was not executed, the executor restarted while running it, retry later",
+                    transaction_hash
+                );
+                state.retry_call(transaction_hash, RetryReason::Restarting, description);
+            }
+            SchedulerMessage::ArtifactUnavailable {
+                transaction_hash,
+                error,
+            } => {
+                tracing::warn!("artifact unavailable for {transaction_hash}: {error}");
+                let description = format!(
+                    "Transaction {} was not executed, its program could not be prepared: {}",
+                    transaction_hash, error
+                );
+                state.retry_call(
+                    transaction_hash,
+                    RetryReason::ArtifactUnavailable,
+                    description,
+                );
+            }
+            SchedulerMessage::CallTimedOut {
+                transaction_hash,
+                committed,
+            } => {
+                if committed {
+                    // Running it again could apply its outputs twice. Its
+                    // receipt is left to a late completion, if one comes.
+                    tracing::error!(
+                        "{transaction_hash} ran past its deadline and may have been applied"
+                    );
+                    state.retries.clear(&transaction_hash);
+                    call_failed(&transaction_hash);
+                    state.respond(
+                        &transaction_hash,
+                        TransactionResponse::TransactionError(RpcResponseError {
+                            description: format!(
+                                "Transaction {} ran past its deadline and could not be confirmed cancelled, its outputs may still be applied",
+                                transaction_hash
+                            ),
+                            code: RpcErrorCode::DeadlineExceeded,
+                        }),
+                    );
+                } else {
+                    tracing::warn!("{transaction_hash} ran past its deadline and was cancelled");
+                    let description = format!(
+                        "Transaction {} was not executed, it ran past its deadline and was cancelled, retry later",
+                        transaction_hash
+                    );
+                    state.retry_call(transaction_hash, RetryReason::TimedOut, description);
                 }
             }
+            SchedulerMessage::Drain { reply } => {
+                // What is in flight already is still seen through.
+                state.draining = true;
+                let _ = reply.send(state.waiting.len());
+            }
             _ => {}
         }
 
@@ -0,0 +1,670 @@
+//! The order transactions ready in the pending pool are handed to the
+//! validator in.
+//!
+//! A transaction is ready once nothing it depends on is pending. The pool
+//! hands at most `PENDING_MAX_IN_FLIGHT` to the validator at once, and when
+//! one finishes takes the next from the ready queue by its scheduling
+//! policy, set with `SCHEDULING_POLICY`:
+//!
+//! - `fifo`, the default, in the order they became ready.
+//! - `priority`, by the priority fee each pays over its fee floor, plus
+//!   `SCHEDULING_AGE_BOOST` for every second it has been pending, so one
+//!   paying nothing still overtakes those paying more once it has waited
+//!   long enough. Equals go in the order they became ready.
+//!
+//! Whatever the policy, a sender's transactions go in nonce order: only the
+//! lowest nonce ready from each sender is a candidate, however much those
+//! behind it pay.
+//!
+//! Senders share the validator in rounds. Each gets `SCHEDULING_SENDER_QUANTUM`
+//! transactions a round, times its weight in `SCHEDULING_SENDER_WEIGHTS`, and
+//! one that has used its share waits for the next round while any other
+//! sender has one ready, so a sender flooding the pool, however much it pays,
+//! cannot keep the others out. Bridge ins, sent by no one, take no share.
+//!
+//! The queue keeps each sender's transactions in nonce order, and the lowest
+//! of each, its head, in a heap by priority, so taking the next costs a log of
+//! the senders ready rather than a pass over every transaction. Priorities
+//! are ranked once, as they are queued, by a key that orders them as their
+//! priorities would at any later time.
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use lasr_types::{Address, Transaction, U256};
+use prometheus::{IntCounter, IntGauge};
+
+use crate::{metrics, FeeSchedule};
+
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+pub const DEFAULT_AGE_BOOST: u64 = 1;
+pub const DEFAULT_SENDER_QUANTUM: u64 = 4;
+
+struct SchedulingMetrics {
+    rounds: IntCounter,
+    deferred: IntCounter,
+    round_senders: IntGauge,
+    round_dispatched: IntGauge,
+    round_max_share: IntGauge,
+}
+
+fn scheduling_metrics() -> &'static SchedulingMetrics {
+    static METRICS: OnceLock<SchedulingMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| SchedulingMetrics {
+        rounds: metrics::counter(
+            "scheduling",
+            "rounds_total",
+            "Scheduling rounds ended, each once every sender ready has used its share.",
+        ),
+        deferred: metrics::counter(
+            "scheduling",
+            "deferred_total",
+            "Times a transaction was passed over because its sender had used its share.",
+        ),
+        round_senders: metrics::gauge(
+            "scheduling",
+            "round_senders",
+            "Senders handed a transaction to the validator in the last round.",
+        ),
+        round_dispatched: metrics::gauge(
+            "scheduling",
+            "round_dispatched",
+            "Transactions handed to the validator in the last round.",
+        ),
+        round_max_share: metrics::gauge(
+            "scheduling",
+            "round_max_share",
+            "The most transactions any one sender was handed in the last round.",
+        ),
+    })
+}
+
+pub trait SchedulingPolicy: Debug + Send + Sync {
+    /// The `SCHEDULING_POLICY` the policy is selected by.
+    fn name(&self) -> &'static str;
+
+    /// How urgently `transaction`, pending for `waited`, is to be handed to
+    /// the validator. The highest goes first.
+    fn priority(&self, transaction: &Transaction, waited: Duration) -> U256;
+
+    /// A key ranking `transaction`, pending since `pending_since`
+    /// milliseconds after the epoch, against others as `priority` ranks them
+    /// at any one time, to the millisecond.
+    fn rank(&self, transaction: &Transaction, pending_since: u64) -> U256;
+}
+
+/// Hands transactions on in the order they became ready.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FifoPolicy;
+
+impl SchedulingPolicy for FifoPolicy {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn priority(&self, _transaction: &Transaction, _waited: Duration) -> U256 {
+        U256::zero()
+    }
+
+    fn rank(&self, _transaction: &Transaction, _pending_since: u64) -> U256 {
+        U256::zero()
+    }
+}
+
+/// Hands transactions on by the priority fee they pay, boosted by how long
+/// they have been pending.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriorityPolicy {
+    fees: FeeSchedule,
+    /// Priority gained for each second pending, in VERSE.
+    age_boost: u64,
+}
+
+impl PriorityPolicy {
+    pub fn new(fees: FeeSchedule, age_boost: u64) -> Self {
+        Self { fees, age_boost }
+    }
+}
+
+impl SchedulingPolicy for PriorityPolicy {
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+
+    fn priority(&self, transaction: &Transaction, waited: Duration) -> U256 {
+        let boost = U256::from(self.age_boost).saturating_mul(U256::from(waited.as_secs()));
+        self.fees
+            .priority_fee_paid(transaction)
+            .saturating_add(boost)
+    }
+
+    fn rank(&self, transaction: &Transaction, pending_since: u64) -> U256 {
+        // The priority in thousandths, less the boost of a wait up to now,
+        // which every transaction has gained alike.
+        let earlier = U256::from(u64::MAX - pending_since);
+        self.fees
+            .priority_fee_paid(transaction)
+            .saturating_mul(U256::from(1_000))
+            .saturating_add(U256::from(self.age_boost).saturating_mul(earlier))
+    }
+}
+
+/// The order transactions ready for the validator are handed to it in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchedulingOrder {
+    #[default]
+    Fifo,
+    Priority,
+}
+
+impl FromStr for SchedulingOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Ok(SchedulingOrder::Fifo),
+            "priority" => Ok(SchedulingOrder::Priority),
+            other => Err(format!(
+                "unknown scheduling policy {other}, expected fifo or priority"
+            )),
+        }
+    }
+}
+
+/// How the transactions ready for the validator are scheduled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedulingConfig {
+    pub order: SchedulingOrder,
+    /// Priority a transaction gains for every second it has been pending,
+    /// when ordered by priority.
+    pub age_boost: u64,
+    pub fairness: Fairness,
+}
+
+impl Default for SchedulingConfig {
+    fn default() -> Self {
+        Self {
+            order: SchedulingOrder::Fifo,
+            age_boost: DEFAULT_AGE_BOOST,
+            fairness: Fairness::new(DEFAULT_SENDER_QUANTUM),
+        }
+    }
+}
+
+impl SchedulingConfig {
+    /// The policy of `order`, ranking transactions by the priority fees
+    /// they pay over their floor under `fees` when ordered by priority.
+    pub fn policy(&self, fees: FeeSchedule) -> Arc<dyn SchedulingPolicy> {
+        match self.order {
+            SchedulingOrder::Fifo => Arc::new(FifoPolicy),
+            SchedulingOrder::Priority => Arc::new(PriorityPolicy::new(fees, self.age_boost)),
+        }
+    }
+}
+
+/// How many transactions each sender is handed to the validator a round.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fairness {
+    /// Transactions a sender of weight one gets a round. With none, senders
+    /// are not held to a share.
+    pub quantum: u64,
+    /// Multiples of the quantum senders get, one unless given here.
+    pub weights: HashMap<Address, u64>,
+}
+
+impl Fairness {
+    pub fn new(quantum: u64) -> Self {
+        Self {
+            quantum,
+            weights: HashMap::new(),
+        }
+    }
+
+    /// Gives `sender` `weight` times the quantum a round, rather than once.
+    pub fn with_weight(mut self, sender: Address, weight: u64) -> Self {
+        self.weights.insert(sender, weight);
+        self
+    }
+
+    /// The weights in `raw`, a comma separated list of `address=weight`.
+    pub fn parse_weights(raw: &str) -> Result<HashMap<Address, u64>, String> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|weight| !weight.is_empty())
+            .map(|weight| {
+                weight
+                    .split_once('=')
+                    .and_then(|(sender, weight)| {
+                        Some((
+                            Address::from_hex(sender.trim()).ok()?,
+                            weight.trim().parse::<u64>().ok()?,
+                        ))
+                    })
+                    .ok_or_else(|| format!("{weight} is not address=weight"))
+            })
+            .collect()
+    }
+
+    /// The transactions `sender` gets a round, or `None` if senders are not
+    /// held to a share. Every sender gets at least one.
+    pub fn share(&self, sender: &Address) -> Option<u64> {
+        (self.quantum > 0).then(|| {
+            let weight = self.weights.get(sender).copied().unwrap_or(1);
+            self.quantum.saturating_mul(weight).max(1)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Ready {
+    transaction: Transaction,
+    transaction_hash: String,
+    /// Milliseconds since the epoch the transaction entered the pool at.
+    pending_since: u64,
+    /// The order it became ready in.
+    sequence: u64,
+}
+
+impl Ready {
+    /// The sender whose nonce order the transaction is held to, which a
+    /// bridge in, numbered by the settlement layer, is not.
+    fn sender(&self) -> Option<Address> {
+        (!self.transaction.transaction_type().is_bridge_in()).then(|| self.transaction.from())
+    }
+}
+
+/// A transaction that may be handed on next, ranked by the scheduling
+/// policy and then by the order it became ready in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Head {
+    rank: U256,
+    sequence: u64,
+    transaction_hash: String,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank
+            .cmp(&other.rank)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The transactions ready to be handed to the validator.
+#[derive(Debug, Clone)]
+pub struct ReadyQueue {
+    policy: Arc<dyn SchedulingPolicy>,
+    fairness: Fairness,
+    ready: HashMap<String, Ready>,
+    /// Each sender's transactions queued, by nonce and then the order they
+    /// became ready in. The first is the sender's head.
+    nonces: HashMap<Address, BTreeMap<(U256, u64), String>>,
+    /// The heads of senders with some of their share left this round, and
+    /// the bridge ins. Those taken since, or no longer a head, are skipped.
+    heads: BinaryHeap<Head>,
+    /// The heads of senders that have used their share this round.
+    spent: BinaryHeap<Head>,
+    next_sequence: u64,
+    /// The transactions each sender has been handed this round.
+    served: HashMap<Address, u64>,
+}
+
+impl Default for ReadyQueue {
+    fn default() -> Self {
+        Self::new(Arc::new(FifoPolicy))
+    }
+}
+
+impl ReadyQueue {
+    pub fn new(policy: Arc<dyn SchedulingPolicy>) -> Self {
+        Self {
+            policy,
+            fairness: Fairness::default(),
+            ready: HashMap::new(),
+            nonces: HashMap::new(),
+            heads: BinaryHeap::new(),
+            spent: BinaryHeap::new(),
+            next_sequence: 0,
+            served: HashMap::new(),
+        }
+    }
+
+    /// Holds senders to the shares of `fairness`.
+    pub fn with_fairness(mut self, fairness: Fairness) -> Self {
+        self.fairness = fairness;
+        self
+    }
+
+    pub fn policy(&self) -> &dyn SchedulingPolicy {
+        self.policy.as_ref()
+    }
+
+    pub fn fairness(&self) -> &Fairness {
+        &self.fairness
+    }
+
+    pub fn len(&self) -> usize {
+        self.ready.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_empty()
+    }
+
+    /// Queues `transaction`, pending since `pending_since` milliseconds
+    /// after the epoch, unless it is queued already.
+    pub fn push(&mut self, transaction: Transaction, pending_since: u64) {
+        let transaction_hash = transaction.hash_string();
+        if self.contains(&transaction_hash) {
+            return;
+        }
+        let ready = Ready {
+            transaction,
+            transaction_hash: transaction_hash.clone(),
+            pending_since,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        if let Some(sender) = ready.sender() {
+            self.nonces.entry(sender).or_default().insert(
+                (ready.transaction.nonce(), ready.sequence),
+                transaction_hash.clone(),
+            );
+        }
+        self.ready.insert(transaction_hash.clone(), ready);
+        self.queue_head(&transaction_hash);
+    }
+
+    pub fn contains(&self, transaction_hash: &str) -> bool {
+        self.ready.contains_key(transaction_hash)
+    }
+
+    pub fn remove(&mut self, transaction_hash: &str) {
+        self.take(transaction_hash);
+    }
+
+    /// Takes the transaction to hand on next.
+    pub fn pop(&mut self) -> Option<Transaction> {
+        loop {
+            let head = match self.heads.pop() {
+                Some(head) => head,
+                None if self.spent.is_empty() => return None,
+                None => {
+                    // Every sender with one ready has used its share, so the
+                    // round is over.
+                    self.end_round();
+                    std::mem::swap(&mut self.heads, &mut self.spent);
+                    continue;
+                }
+            };
+            let Some(ready) = self.ready.get(&head.transaction_hash) else {
+                continue;
+            };
+            if !self.is_head(ready) {
+                continue;
+            }
+            let sender = ready.sender();
+            if let Some(sender) = sender {
+                if !self.has_share(&sender) {
+                    scheduling_metrics().deferred.inc();
+                    self.spent.push(head);
+                    continue;
+                }
+                *self.served.entry(sender).or_default() += 1;
+            }
+            return self
+                .take(&head.transaction_hash)
+                .map(|ready| ready.transaction);
+        }
+    }
+
+    /// Takes `transaction_hash` out of the queue, queueing the head of its
+    /// sender's that follows it.
+    fn take(&mut self, transaction_hash: &str) -> Option<Ready> {
+        let ready = self.ready.remove(transaction_hash)?;
+        let Some(sender) = ready.sender() else {
+            return Some(ready);
+        };
+        let Some(nonces) = self.nonces.get_mut(&sender) else {
+            return Some(ready);
+        };
+        let was_head = nonces.values().next().map(String::as_str) == Some(transaction_hash);
+        nonces.remove(&(ready.transaction.nonce(), ready.sequence));
+        match nonces.values().next().cloned() {
+            Some(next) if was_head => self.queue_head(&next),
+            Some(_) => {}
+            None => {
+                self.nonces.remove(&sender);
+            }
+        }
+        Some(ready)
+    }
+
+    /// Ranks `transaction_hash` among the heads, if it is its sender's.
+    fn queue_head(&mut self, transaction_hash: &str) {
+        let Some(ready) = self.ready.get(transaction_hash) else {
+            return;
+        };
+        if !self.is_head(ready) {
+            return;
+        }
+        let head = Head {
+            rank: self.policy.rank(&ready.transaction, ready.pending_since),
+            sequence: ready.sequence,
+            transaction_hash: ready.transaction_hash.clone(),
+        };
+        match ready.sender() {
+            Some(sender) if !self.has_share(&sender) => self.spent.push(head),
+            _ => self.heads.push(head),
+        }
+    }
+
+    /// Whether `ready` is the lowest nonce queued from its sender.
+    fn is_head(&self, ready: &Ready) -> bool {
+        ready.sender().map_or(true, |sender| {
+            self.nonces
+                .get(&sender)
+                .and_then(|nonces| nonces.values().next())
+                .is_some_and(|head| *head == ready.transaction_hash)
+        })
+    }
+
+    /// Whether `sender` has some of its share left this round.
+    fn has_share(&self, sender: &Address) -> bool {
+        self.fairness.share(sender).map_or(true, |share| {
+            self.served.get(sender).copied().unwrap_or(0) < share
+        })
+    }
+
+    fn end_round(&mut self) {
+        let metrics = scheduling_metrics();
+        metrics.rounds.inc();
+        metrics.round_senders.set(self.served.len() as i64);
+        metrics
+            .round_dispatched
+            .set(self.served.values().sum::<u64>() as i64);
+        metrics
+            .round_max_share
+            .set(self.served.values().max().copied().unwrap_or(0) as i64);
+        self.served.clear();
+    }
+}
+
+#[cfg(test)]
+mod scheduling_tests {
+    use super::*;
+    use lasr_types::{PayloadBuilder, TransactionType};
+
+    const NOW: u64 = 1_000_000;
+
+    fn send(from: u8, nonce: u64, priority_fee: u64) -> Transaction {
+        PayloadBuilder::default()
+            .transaction_type(TransactionType::Send(U256::zero()))
+            .from([from; 20])
+            .to([9; 20])
+            .program_id([0; 20])
+            .inputs(String::new())
+            .op(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(nonce))
+            .max_fee(Some(U256::from(priority_fee)))
+            .priority_fee(Some(U256::from(priority_fee)))
+            .build()
+            .unwrap()
+            .into()
+    }
+
+    /// Pops everything queued at `NOW`, as `(sender, nonce)`.
+    fn drain(queue: &mut ReadyQueue) -> Vec<(u8, u64)> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|transaction| (transaction.from().inner()[0], transaction.nonce().as_u64()))
+            .collect()
+    }
+
+    /// Queues, in this order: a high fee one just sent, an old one paying
+    /// nothing, a middling fee one just sent and a newer one paying nothing.
+    fn mixed(queue: &mut ReadyQueue) {
+        queue.push(send(1, 0, 100), NOW);
+        queue.push(send(2, 0, 0), NOW - 30_000);
+        queue.push(send(3, 0, 50), NOW);
+        queue.push(send(4, 0, 0), NOW - 3_000);
+    }
+
+    #[test]
+    fn fifo_hands_transactions_on_as_they_became_ready() {
+        let mut queue = ReadyQueue::new(Arc::new(FifoPolicy));
+        mixed(&mut queue);
+        assert_eq!(drain(&mut queue), vec![(1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn priority_weighs_fees_against_age() {
+        // 10 for each second pending: 300 for the old one, and 30 for the
+        // newer one, which is still overtaken by both that pay.
+        let policy = PriorityPolicy::new(FeeSchedule::default(), 10);
+        let mut queue = ReadyQueue::new(Arc::new(policy));
+        mixed(&mut queue);
+        assert_eq!(drain(&mut queue), vec![(2, 0), (1, 0), (3, 0), (4, 0)]);
+
+        // Without a boost, fees alone decide.
+        let mut queue = ReadyQueue::new(Arc::new(PriorityPolicy::new(FeeSchedule::default(), 0)));
+        mixed(&mut queue);
+        assert_eq!(drain(&mut queue), vec![(1, 0), (3, 0), (2, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn priority_is_the_fee_paid_over_the_floor() {
+        // The floor of 40 takes all of the middling fee's 50 but 10, which
+        // the newer one pending pays nothing to beat.
+        let policy = PriorityPolicy::new(FeeSchedule::new(40, 0, 0, 0), 10);
+        assert_eq!(
+            policy.priority(&send(3, 0, 50), Duration::ZERO),
+            U256::from(10)
+        );
+        let mut queue = ReadyQueue::new(Arc::new(policy));
+        mixed(&mut queue);
+        assert_eq!(drain(&mut queue), vec![(2, 0), (1, 0), (4, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn a_sender_is_never_taken_out_of_nonce_order() {
+        let mut queue = ReadyQueue::new(Arc::new(PriorityPolicy::new(FeeSchedule::default(), 0)));
+        queue.push(send(5, 2, 1000), NOW);
+        queue.push(send(5, 1, 0), NOW);
+        queue.push(send(6, 0, 10), NOW);
+        assert_eq!(drain(&mut queue), vec![(6, 0), (5, 1), (5, 2)]);
+    }
+
+    #[test]
+    fn a_transaction_removed_makes_way_for_the_next_from_its_sender() {
+        let mut queue = ReadyQueue::new(Arc::new(FifoPolicy));
+        for nonce in 0..3 {
+            queue.push(send(5, nonce, 0), NOW);
+        }
+        queue.push(send(6, 0, 0), NOW);
+        queue.remove(&send(5, 0, 0).hash_string());
+        queue.remove(&send(5, 2, 0).hash_string());
+        assert!(!queue.contains(&send(5, 0, 0).hash_string()));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(drain(&mut queue), vec![(5, 1), (6, 0)]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn ranks_order_transactions_as_their_priorities_do() {
+        let policy = PriorityPolicy::new(FeeSchedule::default(), 10);
+        let ranked = [(send(1, 0, 100), NOW), (send(2, 0, 0), NOW - 30_000)];
+        let rank = |(transaction, since): &(Transaction, u64)| policy.rank(transaction, *since);
+        let priority = |(transaction, since): &(Transaction, u64), now: u64| {
+            policy.priority(transaction, Duration::from_millis(now - since))
+        };
+        for now in [NOW, NOW + 60_000] {
+            assert_eq!(
+                rank(&ranked[0]).cmp(&rank(&ranked[1])),
+                priority(&ranked[0], now).cmp(&priority(&ranked[1], now))
+            );
+        }
+    }
+
+    /// Queues `flooding` transactions from sender 7, each paying 100, and
+    /// `occasional` from sender 8 paying nothing, ranked by fee alone.
+    fn flooded(fairness: Fairness, flooding: u64, occasional: u64) -> ReadyQueue {
+        let policy = PriorityPolicy::new(FeeSchedule::default(), 0);
+        let mut queue = ReadyQueue::new(Arc::new(policy)).with_fairness(fairness);
+        for nonce in 0..flooding {
+            queue.push(send(7, nonce, 100), NOW);
+        }
+        for nonce in 0..occasional {
+            queue.push(send(8, nonce, 0), NOW);
+        }
+        queue
+    }
+
+    #[test]
+    fn a_flooding_sender_is_held_to_its_share_of_each_round() {
+        let flood = |nonces: std::ops::Range<u64>| nonces.map(|nonce| (7, nonce));
+
+        // Unheld, the sender paying more goes first however many it sends.
+        let mut queue = flooded(Fairness::new(0), 6, 1);
+        let unheld: Vec<_> = flood(0..6).chain([(8, 0)]).collect();
+        assert_eq!(drain(&mut queue), unheld);
+
+        // Two a round, the other sender waits behind two of the flood at
+        // most, and the flood carries on in the rounds after.
+        let mut queue = flooded(Fairness::new(2), 6, 1);
+        let held: Vec<_> = flood(0..2).chain([(8, 0)]).chain(flood(2..6)).collect();
+        assert_eq!(drain(&mut queue), held);
+    }
+
+    #[test]
+    fn weighted_senders_get_that_many_shares_a_round() {
+        let fairness = Fairness::new(1).with_weight(Address::new([7; 20]), 3);
+        assert_eq!(fairness.share(&Address::new([7; 20])), Some(3));
+        assert_eq!(fairness.share(&Address::new([8; 20])), Some(1));
+        assert_eq!(Fairness::new(0).share(&Address::new([8; 20])), None);
+
+        let mut queue = flooded(fairness, 6, 2);
+        assert_eq!(
+            drain(&mut queue),
+            vec![
+                (7, 0),
+                (7, 1),
+                (7, 2),
+                (8, 0),
+                (7, 3),
+                (7, 4),
+                (7, 5),
+                (8, 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Submission of batch headers to the settlement contract.
+//!
+//! Once a batch is finalized on the DA layer its header is sent to the
+//! settlement contract in a transaction signed with the operator key, so the
+//! contract can follow the rollup's progress. Nonces are tracked locally and
+//! fetched again whenever a submission leaves them in doubt, or the key is
+//! rotated. A header is resubmitted with the key it was first submitted
+//! with. A transaction not mined within `replace_after` is replaced by one
+//! with the same nonce at a higher gas price, until it is mined or the
+//! attempts run out, and the status of each batch is kept in its receipt.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use lasr_messages::{BatchHeader, BatchReceipt, BatchStatus};
+use tokio::sync::broadcast;
+use web3::ethabi::Token;
+use web3::signing::{Key, SecretKey, SecretKeyRef};
+use web3::transports::Http;
+use web3::types::{
+    Address as EthereumAddress, BlockNumber, Bytes, TransactionParameters, TransactionReceipt,
+    H256, U256, U64,
+};
+use web3::{Transport, Web3};
+
+use crate::{EoClientError, KeyHandle, Subscription, DEFAULT_SUBSCRIPTION_BUFFER};
+
+pub const DEFAULT_SETTLEMENT_GAS_LIMIT: u64 = 200_000;
+pub const DEFAULT_SETTLEMENT_REPLACE_AFTER: Duration = Duration::from_secs(60);
+pub const DEFAULT_SETTLEMENT_ATTEMPTS: u32 = 5;
+/// How much a replacement raises the gas price by, in percent. Nodes refuse
+/// replacements raising it by less than 10.
+pub const SETTLEMENT_GAS_PRICE_BUMP: u64 = 20;
+
+const SETTLEMENT_CONTRACT_ABI_JSON: &str = r#"[
+    {
+        "type": "function",
+        "name": "submitBatchHeader",
+        "stateMutability": "nonpayable",
+        "inputs": [
+            { "name": "batchId", "type": "uint256", "internalType": "uint256" },
+            { "name": "stateRoot", "type": "bytes32", "internalType": "bytes32" },
+            { "name": "blobCommitment", "type": "bytes32", "internalType": "bytes32" },
+            { "name": "blobIndex", "type": "uint128", "internalType": "uint128" },
+            {
+                "name": "withdrawals",
+                "type": "tuple[]",
+                "internalType": "struct Withdrawal[]",
+                "components": [
+                    { "name": "token", "type": "address", "internalType": "address" },
+                    { "name": "recipient", "type": "address", "internalType": "address" },
+                    { "name": "amount", "type": "uint256", "internalType": "uint256" }
+                ]
+            }
+        ],
+        "outputs": []
+    }
+]"#;
+
+/// The calldata submitting `header` to the settlement contract, with the
+/// withdrawals the contract releases once it is settled.
+pub fn batch_header_calldata(header: &BatchHeader) -> Result<Vec<u8>, EoClientError> {
+    let withdrawals = header
+        .withdrawals
+        .iter()
+        .map(|withdrawal| {
+            Token::Tuple(vec![
+                Token::Address(withdrawal.token.into()),
+                Token::Address(withdrawal.recipient.into()),
+                Token::Uint(withdrawal.amount.into()),
+            ])
+        })
+        .collect();
+    let abi = web3::ethabi::Contract::load(SETTLEMENT_CONTRACT_ABI_JSON.as_bytes())
+        .map_err(|e| EoClientError::Custom(e.to_string()))?;
+    abi.function("submitBatchHeader")
+        .and_then(|function| {
+            function.encode_input(&[
+                Token::Uint(header.batch_id.into()),
+                Token::FixedBytes(header.state_root.0.to_vec()),
+                Token::FixedBytes(header.blob_commitment.0.to_vec()),
+                Token::Uint(header.blob_index.into()),
+                Token::Array(withdrawals),
+            ])
+        })
+        .map_err(|e| EoClientError::Custom(e.to_string()))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlerConfig {
+    pub contract: EthereumAddress,
+    pub gas_limit: u64,
+    pub replace_after: Duration,
+    pub max_attempts: u32,
+    /// How often receipts are checked for while a submission is pending.
+    pub poll_interval: Duration,
+}
+
+impl SettlerConfig {
+    pub fn new(contract: EthereumAddress) -> Self {
+        Self {
+            contract,
+            gas_limit: DEFAULT_SETTLEMENT_GAS_LIMIT,
+            replace_after: DEFAULT_SETTLEMENT_REPLACE_AFTER,
+            max_attempts: DEFAULT_SETTLEMENT_ATTEMPTS,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The receipts of the batches submitted, shared with whatever reports on
+/// them. Every receipt set is also published to those subscribed.
+#[derive(Clone, Debug)]
+pub struct BatchReceipts {
+    receipts: Arc<std::sync::Mutex<BTreeMap<u64, BatchReceipt>>>,
+    published: broadcast::Sender<BatchReceipt>,
+}
+
+impl Default for BatchReceipts {
+    fn default() -> Self {
+        Self {
+            receipts: Arc::default(),
+            published: broadcast::channel(DEFAULT_SUBSCRIPTION_BUFFER).0,
+        }
+    }
+}
+
+impl BatchReceipts {
+    pub fn get(&self, batch_id: u64) -> Option<BatchReceipt> {
+        self.receipts.lock().unwrap().get(&batch_id).cloned()
+    }
+
+    /// The id of the latest batch with a receipt.
+    pub fn latest_batch_id(&self) -> Option<u64> {
+        self.receipts.lock().unwrap().keys().next_back().copied()
+    }
+
+    pub fn set(&self, header: &BatchHeader, status: BatchStatus) {
+        let receipt = BatchReceipt {
+            header: header.clone(),
+            status,
+        };
+        self.receipts
+            .lock()
+            .unwrap()
+            .insert(header.batch_id, receipt.clone());
+        // Only fails while no one is subscribed.
+        let _ = self.published.send(receipt);
+    }
+
+    /// Subscribes to the receipts of batches as they are finalized and as
+    /// their settlement gets on.
+    pub fn subscribe(&self) -> Subscription<BatchReceipt> {
+        Subscription::new(self.published.subscribe())
+    }
+}
+
+pub struct Settler<T: Transport = Http> {
+    web3: Web3<T>,
+    key: KeyHandle,
+    config: SettlerConfig,
+    /// The nonce the next submission is sent with, unless it needs fetching.
+    nonce: Option<U256>,
+    /// The signer `nonce` is the next of.
+    signer: Option<EthereumAddress>,
+    chain_id: Option<u64>,
+    receipts: BatchReceipts,
+}
+
+impl<T: Transport> std::fmt::Debug for Settler<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Settler")
+            .field("config", &self.config)
+            .field("nonce", &self.nonce)
+            .finish()
+    }
+}
+
+impl<T: Transport> Settler<T> {
+    pub fn new(web3: Web3<T>, key: KeyHandle, config: SettlerConfig) -> Self {
+        Self {
+            web3,
+            key,
+            config,
+            nonce: None,
+            signer: None,
+            chain_id: None,
+            receipts: BatchReceipts::default(),
+        }
+    }
+
+    pub fn receipts(&self) -> BatchReceipts {
+        self.receipts.clone()
+    }
+
+    /// Submits `header`, replacing the transaction while it is not mined,
+    /// and returns the status the batch ends up in.
+    pub async fn submit(&mut self, header: &BatchHeader) -> BatchStatus {
+        self.receipts.set(header, BatchStatus::Finalized);
+        let status = match batch_header_calldata(header) {
+            Ok(data) => self.submit_calldata(header, data).await,
+            Err(e) => BatchStatus::Failed {
+                reason: e.to_string(),
+            },
+        };
+        match &status {
+            BatchStatus::Settled {
+                transaction_hash,
+                block_number,
+            } => tracing::info!(
+                "batch {} settled in block {} by {:?}",
+                header.batch_id,
+                block_number,
+                transaction_hash
+            ),
+            other => tracing::error!("batch {} was not settled: {:?}", header.batch_id, other),
+        }
+        self.receipts.set(header, status.clone());
+        status
+    }
+
+    async fn submit_calldata(&mut self, header: &BatchHeader, data: Vec<u8>) -> BatchStatus {
+        let mut sent: Vec<H256> = Vec::new();
+        let mut gas_price: Option<U256> = None;
+        let mut last_error = String::new();
+        let key = self.key.web3_key();
+        for attempt in 1..=self.config.max_attempts {
+            if attempt > 1 {
+                // One of those replaced may have been mined meanwhile.
+                if let Some(receipt) = self.mined(&sent).await {
+                    return self.finished(receipt);
+                }
+            }
+            let sending = async {
+                let nonce = self.next_nonce(&key).await?;
+                let price = self.gas_price(gas_price).await?;
+                let hash = self.send(&key, nonce, price, data.clone()).await?;
+                Ok::<_, EoClientError>((price, hash))
+            };
+            match sending.await {
+                Ok((price, hash)) => {
+                    gas_price = Some(price);
+                    sent.push(hash);
+                    self.receipts.set(
+                        header,
+                        BatchStatus::Submitted {
+                            transaction_hash: hash,
+                            attempts: attempt,
+                        },
+                    );
+                    tracing::info!(
+                        "submitted batch {} header as {:?} at gas price {}",
+                        header.batch_id,
+                        hash,
+                        price
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!("unable to submit batch {} header: {}", header.batch_id, e);
+                    last_error = e.to_string();
+                    if sent.is_empty() {
+                        self.nonce = None;
+                    }
+                    tokio::time::sleep(self.config.poll_interval).await;
+                    continue;
+                }
+            }
+
+            let deadline = Instant::now() + self.config.replace_after;
+            while Instant::now() < deadline {
+                tokio::time::sleep(self.config.poll_interval).await;
+                if let Some(receipt) = self.mined(&sent).await {
+                    return self.finished(receipt);
+                }
+            }
+            tracing::warn!(
+                "batch {} header not mined within {:?}, replacing it",
+                header.batch_id,
+                self.config.replace_after
+            );
+        }
+
+        if let Some(receipt) = self.mined(&sent).await {
+            return self.finished(receipt);
+        }
+        // Whether any of them will still be mined is unknown.
+        self.nonce = None;
+        BatchStatus::Failed {
+            reason: match sent.is_empty() {
+                true => last_error,
+                false => format!("not mined after {} attempts", self.config.max_attempts),
+            },
+        }
+    }
+
+    fn finished(&mut self, receipt: TransactionReceipt) -> BatchStatus {
+        self.nonce = self.nonce.map(|nonce| nonce + 1);
+        if receipt.status == Some(U64::from(1)) {
+            BatchStatus::Settled {
+                transaction_hash: receipt.transaction_hash,
+                block_number: receipt.block_number.unwrap_or_default().as_u64(),
+            }
+        } else {
+            BatchStatus::Failed {
+                reason: format!("{:?} reverted", receipt.transaction_hash),
+            }
+        }
+    }
+
+    /// The receipt of whichever of `sent` was mined, if one was.
+    async fn mined(&self, sent: &[H256]) -> Option<TransactionReceipt> {
+        for hash in sent {
+            match self.web3.eth().transaction_receipt(*hash).await {
+                Ok(Some(receipt)) if receipt.block_number.is_some() => return Some(receipt),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("unable to get the receipt of {:?}: {}", hash, e),
+            }
+        }
+        None
+    }
+
+    async fn next_nonce(&mut self, key: &SecretKey) -> Result<U256, EoClientError> {
+        let address = SecretKeyRef::new(key).address();
+        if self.signer != Some(address) {
+            self.nonce = None;
+            self.signer = Some(address);
+        }
+        if let Some(nonce) = self.nonce {
+            return Ok(nonce);
+        }
+        let nonce = self
+            .web3
+            .eth()
+            .transaction_count(address, Some(BlockNumber::Pending))
+            .await
+            .map_err(|e| EoClientError::Custom(format!("unable to get nonce: {e}")))?;
+        self.nonce = Some(nonce);
+        Ok(nonce)
+    }
+
+    /// The network's gas price, raised to outbid `replacing` if given.
+    async fn gas_price(&self, replacing: Option<U256>) -> Result<U256, EoClientError> {
+        let price = self
+            .web3
+            .eth()
+            .gas_price()
+            .await
+            .map_err(|e| EoClientError::Custom(format!("unable to get gas price: {e}")))?;
+        Ok(match replacing {
+            Some(replacing) => price.max(replacing * (100 + SETTLEMENT_GAS_PRICE_BUMP) / 100),
+            None => price,
+        })
+    }
+
+    async fn send(
+        &mut self,
+        key: &SecretKey,
+        nonce: U256,
+        gas_price: U256,
+        data: Vec<u8>,
+    ) -> Result<H256, EoClientError> {
+        let chain_id = match self.chain_id {
+            Some(chain_id) => chain_id,
+            None => {
+                let chain_id = self
+                    .web3
+                    .eth()
+                    .chain_id()
+                    .await
+                    .map_err(|e| EoClientError::Custom(format!("unable to get chain id: {e}")))?
+                    .as_u64();
+                self.chain_id = Some(chain_id);
+                chain_id
+            }
+        };
+        let transaction = TransactionParameters {
+            nonce: Some(nonce),
+            to: Some(self.config.contract),
+            gas: self.config.gas_limit.into(),
+            gas_price: Some(gas_price),
+            data: Bytes(data),
+            chain_id: Some(chain_id),
+            ..Default::default()
+        };
+        let signed = self
+            .web3
+            .accounts()
+            .sign_transaction(transaction, key)
+            .await
+            .map_err(|e| EoClientError::Custom(format!("unable to sign: {e}")))?;
+        self.web3
+            .eth()
+            .send_raw_transaction(signed.raw_transaction)
+            .await
+            .map_err(|e| EoClientError::Custom(format!("unable to send: {e}")))
+    }
+}
@@ -0,0 +1,367 @@
+//! Read-only simulation of sends and calls, for `simulateTransaction`.
+//!
+//! A simulation stages a transaction's accounts the way the batcher would
+//! and reports what would change, without writing any of them. Every account
+//! it reads comes from one snapshot of the account cache, taken in a single
+//! message, so it never sees a transaction half applied. An account found to
+//! be needed that the snapshot was not taken with is added to it, and the
+//! simulation runs again on a new snapshot.
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+use lasr_messages::{AccountCacheMessage, ActorType, ExecutorMessage, RpcErrorCode};
+use lasr_types::{
+    Account, AccountType, Address, ContractLogType, Inputs, Instruction, Outputs, StorageQuota,
+    Transaction, TransactionType, U256,
+};
+use ractor::{concurrency::oneshot, rpc::CallResult, ActorRef};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{get_account, token_deltas, validate_outputs, Batcher, OutputLimits, TokenDelta};
+
+/// Snapshots taken for one simulation before giving up on one holding every
+/// account it reads.
+pub const MAX_SIMULATION_SNAPSHOTS: u32 = 4;
+
+/// How long a simulated call waits on the executor.
+const SIMULATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    static VIEW: RefCell<AccountView>;
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum SimulationError {
+    #[error("only sends and calls can be simulated")]
+    Unsupported,
+
+    #[error("failed to acquire AccountCacheActor from registry")]
+    AccountCacheUnavailable,
+
+    #[error("the executor did not simulate the call: {0}")]
+    Executor(String),
+
+    #[error("accounts kept changing over {0} snapshots, retry the simulation")]
+    Unsettled(u32),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl SimulationError {
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            SimulationError::Unsupported => RpcErrorCode::InvalidParams,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// What applying a transaction would do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Simulation {
+    /// Whether the transaction would be applied.
+    pub success: bool,
+    /// By address, then program.
+    pub token_deltas: Vec<TokenDelta>,
+    /// The logs the program emitted, in order.
+    pub events: Vec<SimulatedEvent>,
+    pub trace: SimulationTrace,
+    /// What the transaction would be charged, as `estimateFees` answers.
+    /// Set by whoever simulates it with a fee schedule, zero until then.
+    pub estimated_fee: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedEvent {
+    pub level: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationTrace {
+    /// The instructions the program returned, counted by kind.
+    pub instructions: BTreeMap<&'static str, usize>,
+    /// Accounts the transaction would write.
+    pub accounts_touched: usize,
+    pub output_bytes: usize,
+    pub duration_ms: u64,
+    /// Snapshots of the account cache taken before one held every account
+    /// read.
+    pub snapshots: u32,
+}
+
+/// The accounts a simulation reads in place of the account cache's.
+#[derive(Debug, Default)]
+struct AccountView {
+    snapshot: HashMap<Address, Account>,
+    /// Every address the snapshot was taken with, with an account or not.
+    taken: BTreeSet<Address>,
+    /// Addresses read that the snapshot was not taken with.
+    missed: BTreeSet<Address>,
+}
+
+/// The account at `address` in the snapshot of the simulation running on
+/// this task, or `None` outside of one.
+pub(crate) fn viewed_account(address: &Address) -> Option<Option<Account>> {
+    VIEW.try_with(|view| {
+        let mut view = view.borrow_mut();
+        if !view.taken.contains(address) {
+            view.missed.insert(*address);
+        }
+        view.snapshot.get(address).cloned()
+    })
+    .ok()
+}
+
+/// Takes the sender of `transaction` in the snapshot to the nonce before
+/// it, so a send ahead of its turn is simulated as it will apply once its
+/// turn comes. A send behind it is left to fail as it would.
+fn catch_up(transaction: &Transaction) {
+    let nonce = transaction.nonce();
+    let _ = VIEW.try_with(|view| {
+        if let Some(sender) = view.borrow_mut().snapshot.get_mut(&transaction.from()) {
+            if sender.expected_nonce() < nonce {
+                sender.set_nonce(nonce - U256::from(1));
+            }
+        }
+    });
+}
+
+/// Reads `addresses` from the account cache as of the same moment.
+pub async fn get_snapshot(
+    addresses: Vec<Address>,
+) -> Result<HashMap<Address, Account>, SimulationError> {
+    let account_cache: ActorRef<AccountCacheMessage> =
+        ractor::registry::where_is(ActorType::AccountCache.to_string())
+            .ok_or(SimulationError::AccountCacheUnavailable)?
+            .into();
+    let (tx, rx) = oneshot();
+    account_cache
+        .cast(AccountCacheMessage::ReadSnapshot { addresses, tx })
+        .map_err(|_| SimulationError::AccountCacheUnavailable)?;
+    rx.await
+        .map_err(|_| SimulationError::AccountCacheUnavailable)
+}
+
+/// Simulates `transaction` against the current state of the accounts it
+/// touches, without changing any of them, holding the accounts it leaves to
+/// `storage_quota` as applying it would.
+pub async fn simulate(
+    transaction: &Transaction,
+    output_limits: &OutputLimits,
+    storage_quota: &StorageQuota,
+) -> Result<Simulation, SimulationError> {
+    if !matches!(
+        transaction.transaction_type(),
+        TransactionType::Send(_) | TransactionType::Call(_)
+    ) {
+        return Err(SimulationError::Unsupported);
+    }
+
+    let started = Instant::now();
+    let mut addresses: BTreeSet<Address> = [
+        transaction.from(),
+        transaction.to(),
+        transaction.program_id(),
+    ]
+    .into();
+    for snapshots in 1..=MAX_SIMULATION_SNAPSHOTS {
+        let view = AccountView {
+            snapshot: get_snapshot(addresses.iter().copied().collect()).await?,
+            taken: addresses.clone(),
+            missed: BTreeSet::new(),
+        };
+        let (mut simulation, missed) = VIEW
+            .scope(RefCell::new(view), async {
+                let simulation = run(transaction, output_limits, storage_quota).await?;
+                let missed = VIEW.with(|view| std::mem::take(&mut view.borrow_mut().missed));
+                Ok::<_, SimulationError>((simulation, missed))
+            })
+            .await?;
+        if missed.is_empty() {
+            simulation.trace.duration_ms = started.elapsed().as_millis() as u64;
+            simulation.trace.snapshots = snapshots;
+            return Ok(simulation);
+        }
+        addresses.extend(missed);
+    }
+
+    Err(SimulationError::Unsettled(MAX_SIMULATION_SNAPSHOTS))
+}
+
+/// Runs one simulation inside the view of a snapshot.
+async fn run(
+    transaction: &Transaction,
+    output_limits: &OutputLimits,
+    storage_quota: &StorageQuota,
+) -> Result<Simulation, SimulationError> {
+    let mut trace = SimulationTrace::default();
+    let mut events = Vec::new();
+    let staged = match transaction.transaction_type() {
+        TransactionType::Call(_) => match execute(transaction).await? {
+            Ok(outputs) => {
+                trace.output_bytes = outputs.len();
+                match validate_outputs(&outputs, transaction, output_limits).await {
+                    Ok(outputs) => {
+                        trace.instructions = count_instructions(&outputs);
+                        events = emitted_events(&outputs);
+                        Batcher::stage_instructions(transaction, &outputs, storage_quota)
+                            .await
+                            .map(|accounts| accounts.into_values().collect::<Vec<_>>())
+                            .map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Err(e) => Err(e),
+        },
+        _ => {
+            catch_up(transaction);
+            Batcher::stage_send(transaction)
+                .await
+                .map(|(accounts, _)| accounts.into_values().collect())
+                .map_err(|e| e.to_string())
+        }
+    };
+
+    let staged = staged.and_then(|accounts| {
+        Batcher::validate_batch_buffer(&accounts)
+            .map(|_| accounts)
+            .map_err(|e| e.to_string())
+    });
+    let accounts = match staged {
+        Ok(accounts) => accounts,
+        Err(reason) => {
+            return Ok(Simulation {
+                success: false,
+                token_deltas: Vec::new(),
+                events,
+                trace,
+                estimated_fee: U256::from(0),
+                revert_reason: Some(reason),
+            })
+        }
+    };
+
+    let mut deltas = Vec::new();
+    for account in &accounts {
+        let address = match account.account_type() {
+            AccountType::Program(program_address) => program_address,
+            AccountType::User => account.owner_address(),
+        };
+        let before = get_account(address, ActorType::Batcher).await;
+        if let Err(e) = storage_quota.check_account(before.as_ref(), account) {
+            return Ok(Simulation {
+                success: false,
+                token_deltas: Vec::new(),
+                events,
+                trace,
+                estimated_fee: U256::from(0),
+                revert_reason: Some(e.to_string()),
+            });
+        }
+        deltas.extend(token_deltas(before.as_ref(), account));
+    }
+    deltas.sort_by_key(|delta| (delta.address, delta.program_id));
+    trace.accounts_touched = accounts.len();
+    Ok(Simulation {
+        success: true,
+        token_deltas: deltas,
+        events,
+        trace,
+        estimated_fee: U256::from(0),
+        revert_reason: None,
+    })
+}
+
+/// Has the executor run the program `transaction` calls, answering its
+/// outputs, or why it failed.
+async fn execute(transaction: &Transaction) -> Result<Result<String, String>, SimulationError> {
+    let Some(program) = get_account(transaction.program_id(), ActorType::Executor).await else {
+        return Ok(Err(format!(
+            "program account {} does not exist",
+            transaction.program_id().to_full_string()
+        )));
+    };
+    let Some(account_info) = get_account(transaction.to(), ActorType::Executor).await else {
+        return Ok(Err(format!(
+            "program account {} does not exist",
+            transaction.to().to_full_string()
+        )));
+    };
+    let inputs = Inputs {
+        version: 1,
+        account_info,
+        transaction: transaction.clone(),
+        op: transaction.op(),
+        inputs: transaction.inputs(),
+    };
+
+    let executor: ActorRef<ExecutorMessage> =
+        ractor::registry::where_is(ActorType::Executor.to_string())
+            .ok_or_else(|| SimulationError::Executor("executor is not running".to_string()))?
+            .into();
+    match executor
+        .call(
+            |reply| ExecutorMessage::Simulate {
+                program,
+                inputs,
+                reply,
+            },
+            Some(SIMULATION_TIMEOUT),
+        )
+        .await
+    {
+        Ok(CallResult::Success(outputs)) => Ok(outputs.map_err(|e| e.to_string())),
+        Ok(CallResult::Timeout) => Err(SimulationError::Executor(format!(
+            "no outputs within {SIMULATION_TIMEOUT:?}"
+        ))),
+        Ok(CallResult::SenderError) => Err(SimulationError::Executor(
+            "executor dropped the simulation".to_string(),
+        )),
+        Err(e) => Err(SimulationError::Executor(e.to_string())),
+    }
+}
+
+fn count_instructions(outputs: &Outputs) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for instruction in outputs.instructions() {
+        let kind = match instruction {
+            Instruction::Transfer(_) => "transfer",
+            Instruction::Burn(_) => "burn",
+            Instruction::Create(_) => "create",
+            Instruction::Update(_) => "update",
+            Instruction::Log(_) => "log",
+        };
+        *counts.entry(kind).or_default() += 1;
+    }
+    counts
+}
+
+fn emitted_events(outputs: &Outputs) -> Vec<SimulatedEvent> {
+    outputs
+        .instructions()
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Log(log) => Some(match &log.0 {
+                ContractLogType::Info(message) => ("info", message),
+                ContractLogType::Warn(message) => ("warn", message),
+                ContractLogType::Error(message) => ("error", message),
+                ContractLogType::Debug(message) => ("debug", message),
+            }),
+            _ => None,
+        })
+        .map(|(level, message)| SimulatedEvent {
+            level,
+            message: message.clone(),
+        })
+        .collect()
+}
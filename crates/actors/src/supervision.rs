@@ -0,0 +1,164 @@
+//! Restarting the actors that panic, within limits.
+//!
+//! Each supervisor hands the actors that panic under it back to the node to
+//! be respawned, and they come back up with the state they keep elsewhere:
+//! the pending pool replays the pending log, the account cache reads accounts
+//! from the persistence store and takes back those on the batches not yet
+//! sealed, and the batcher keeps the batches it holds across restarts. The
+//! blob cache holds nothing to take back.
+//!
+//! An actor that panics more than `max_restarts` times within the policy's
+//! `window` is crash looping, and restarting it again
+//! would only hide that, so the node shuts down instead.
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use lasr_messages::ActorType;
+use prometheus::IntCounterVec;
+use thiserror::Error;
+use tokio::time::Instant;
+
+use crate::metrics;
+
+pub const DEFAULT_ACTOR_MAX_RESTARTS: u32 = 5;
+pub const DEFAULT_ACTOR_RESTART_WINDOW_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupervisionPolicy {
+    /// Restarts allowed for an actor within `window`.
+    pub max_restarts: u32,
+    pub window: Duration,
+}
+
+impl Default for SupervisionPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: DEFAULT_ACTOR_MAX_RESTARTS,
+            window: Duration::from_secs(DEFAULT_ACTOR_RESTART_WINDOW_SECS),
+        }
+    }
+}
+
+/// An actor that panicked more often than its policy allows.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("the {actor_type} actor panicked {panics} times within {window:?}")]
+pub struct CrashLoop {
+    pub actor_type: ActorType,
+    pub panics: usize,
+    pub window: Duration,
+}
+
+struct SupervisionMetrics {
+    restarts: IntCounterVec,
+    crash_loops: IntCounterVec,
+}
+
+fn supervision_metrics() -> &'static SupervisionMetrics {
+    static METRICS: OnceLock<SupervisionMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| SupervisionMetrics {
+        restarts: metrics::counter_vec(
+            "supervision",
+            "actor_restarts_total",
+            "Actors restarted after panicking, by actor.",
+            &["actor"],
+        ),
+        crash_loops: metrics::counter_vec(
+            "supervision",
+            "crash_loops_total",
+            "Actors that panicked too often to be restarted again, by actor.",
+            &["actor"],
+        ),
+    })
+}
+
+/// The recent panics of each actor, deciding whether it is restarted.
+#[derive(Debug)]
+pub struct Restarts {
+    policy: SupervisionPolicy,
+    panics: HashMap<ActorType, VecDeque<Instant>>,
+}
+
+impl Restarts {
+    pub fn new(policy: SupervisionPolicy) -> Self {
+        Self {
+            policy,
+            panics: HashMap::new(),
+        }
+    }
+
+    /// Records `actor_type` panicking at `now`, returning how many times it
+    /// has within the window, or the crash loop it is in if that is more
+    /// than the policy allows.
+    pub fn record(&mut self, actor_type: ActorType, now: Instant) -> Result<usize, CrashLoop> {
+        let label = actor_type.to_string();
+        let panics = self.panics.entry(actor_type.clone()).or_default();
+        panics.push_back(now);
+        while panics
+            .front()
+            .is_some_and(|panicked| now.duration_since(*panicked) > self.policy.window)
+        {
+            panics.pop_front();
+        }
+        if panics.len() > self.policy.max_restarts as usize {
+            supervision_metrics()
+                .crash_loops
+                .with_label_values(&[&label])
+                .inc();
+            return Err(CrashLoop {
+                actor_type,
+                panics: panics.len(),
+                window: self.policy.window,
+            });
+        }
+        supervision_metrics()
+            .restarts
+            .with_label_values(&[&label])
+            .inc();
+        Ok(panics.len())
+    }
+}
+
+#[cfg(test)]
+mod supervision_tests {
+    use super::*;
+
+    fn restarts(max_restarts: u32) -> Restarts {
+        Restarts::new(SupervisionPolicy {
+            max_restarts,
+            window: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn an_actor_panicking_too_often_is_crash_looping() {
+        let mut restarts = restarts(2);
+        let now = Instant::now();
+        assert_eq!(restarts.record(ActorType::Batcher, now), Ok(1));
+        assert_eq!(restarts.record(ActorType::Batcher, now), Ok(2));
+        // Other actors have restarts of their own.
+        assert_eq!(restarts.record(ActorType::AccountCache, now), Ok(1));
+        assert_eq!(
+            restarts.record(ActorType::Batcher, now),
+            Err(CrashLoop {
+                actor_type: ActorType::Batcher,
+                panics: 3,
+                window: Duration::from_secs(60),
+            })
+        );
+    }
+
+    #[test]
+    fn panics_outside_the_window_are_forgotten() {
+        let mut restarts = restarts(1);
+        let now = Instant::now();
+        assert_eq!(restarts.record(ActorType::Batcher, now), Ok(1));
+        assert_eq!(
+            restarts.record(ActorType::Batcher, now + Duration::from_secs(61)),
+            Ok(1)
+        );
+        assert!(restarts
+            .record(ActorType::Batcher, now + Duration::from_secs(62))
+            .is_err());
+    }
+}
@@ -0,0 +1,26 @@
+//! Transactions shared by the unit tests.
+
+use lasr_types::{Transaction, TransactionBuilder, TransactionType, U256};
+
+/// An unsigned send of 1 from `[1; 20]` to `[2; 20]` with `nonce`.
+pub(crate) fn send(nonce: u64) -> Transaction {
+    transfer(1, 2, nonce, 1)
+}
+
+/// An unsigned send of `value` from `[from; 20]` to `[to; 20]`.
+pub(crate) fn transfer(from: u8, to: u8, nonce: u64, value: u64) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(0)))
+        .from([from; 20])
+        .to([to; 20])
+        .program_id([0; 20])
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(value))
+        .nonce(U256::from(nonce))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
@@ -0,0 +1,1176 @@
+//! The whole node, booted in-process for integration tests.
+//!
+//! A [`TestHarness`] runs every actor the node does against the mock
+//! persistence store, with the local-file DA backend, a scripted settlement
+//! chain served over http for the EO listener to scan, and the RPC server on
+//! a random local port with the faucet on. Two actors are stood in for: the
+//! executor answers simulated calls and auth programs from scripts the test
+//! sets, and the EO client records what the node would settle rather than
+//! submitting it.
+//!
+//! The node runs in instant-seal mode, sealing every transaction into a batch
+//! of its own and settling it before answering it, so what a wallet sends is
+//! there to read as soon as the send returns. A test that turns it off with
+//! [`NodeConfigBuilder::instant_seal`] has batches sealed only when it asks
+//! with [`TestHarness::seal_batch`], and settled only once it emits the event
+//! the contract would with [`TestHarness::settle`]. The actors are registered
+//! under their names, so tests running a harness run one at a time.
+//!
+//! A harness started with the commit log of one that crashed, with
+//! [`NodeConfigBuilder::commit_log_path`] and [`TestHarness::crash`], puts
+//! back in its batches what the other committed and did not finalize.
+//!
+//! Unless a test sets a genesis of its own, the node starts from one the
+//! harness writes, funding a single account with VERSE, which
+//! [`TestHarness::genesis_wallet`] sends from.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use eo_listener::{EoAddress, EoServerBuilder, EoServerError, FailoverProvider, ReorgTracker};
+use ethereum_types::H256;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use jsonrpsee::{
+    http_client::{HttpClient, HttpClientBuilder},
+    server::{ServerBuilder, ServerHandle},
+    types::ErrorObjectOwned,
+    RpcModule,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{ActorName, ActorType, BatchStatus, EoMessage, ExecutorMessage, LasrError};
+use lasr_rpc::{AdminRpcServer, EthRpcServer, LasrRpcClient, LasrRpcServer};
+use lasr_types::{
+    Account, Address, Inputs, MockPersistenceStore, Outputs, PayloadBuilder, PersistenceStore, U256,
+};
+use lasr_wallet::{Wallet, WalletBuilder};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use web3::ethabi::Token;
+use web3::types::{H160, U64};
+
+use crate::{
+    get_account, graph_cleaner, rest_router, serve_rpc, AccountCacheActor, ActorExt, AdminAccess,
+    BatchFeed, BatchIndex, BatchReceipts, BatchRecord, Batcher, BatcherActor, BlobCacheActor,
+    CommitLog, ConfigErrors, DaClient, DaClientActor, DrainReport, EngineActor, EoServerActor,
+    EoServerWrapper, EventLog, Faucet, Genesis, GenesisAccount, LasrRpcServerActor,
+    LasrRpcServerImpl, LoadShedder, LocalFileDa, NodeConfig, NodeConfigBuilder,
+    PendingTransactionActor, ProgramStats, Pruner, RateLimiter, Shutdown, ShutdownDrain,
+    StorageRef, TaskScheduler, TransactionIndex, UnsealedBatches, ValidatorActor, ValidatorCore,
+    DEFAULT_ETH_CHAIN_ID, VERSE_ADDR,
+};
+
+/// How long a harness waits for the node to get somewhere before giving up.
+const HARNESS_TIMEOUT: Duration = Duration::from_secs(10);
+const HARNESS_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// The settlement chain's block time, which `advance` mines blocks by.
+const CHAIN_BLOCK_TIME: Duration = Duration::from_secs(12);
+const CHAIN_GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+const ADMIN_TOKEN: &str = "harness-admin-token";
+
+static HARNESSES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Error)]
+pub enum HarnessError {
+    #[error(transparent)]
+    Config(#[from] ConfigErrors),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("timed out waiting for {0}")]
+    Timeout(String),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl From<EoServerError> for HarnessError {
+    fn from(e: EoServerError) -> Self {
+        HarnessError::Custom(e.to_string())
+    }
+}
+
+fn custom(e: impl std::fmt::Display) -> HarnessError {
+    HarnessError::Custom(e.to_string())
+}
+
+/// Polls `check` until it answers something, or `HARNESS_TIMEOUT` passes.
+async fn eventually<T, F, Fut>(what: &str, mut check: F) -> Result<T, HarnessError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    tokio::time::timeout(HARNESS_TIMEOUT, async {
+        loop {
+            if let Some(found) = check().await {
+                return found;
+            }
+            tokio::time::sleep(HARNESS_POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .map_err(|_| HarnessError::Timeout(what.to_string()))
+}
+
+/// Where an event emitted on the scripted chain landed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainEvent {
+    pub block_number: u64,
+    pub transaction_hash: H256,
+}
+
+#[derive(Debug, Default)]
+struct ChainState {
+    contract: H160,
+    head: u64,
+    logs: Vec<(u64, Value)>,
+    /// The block each event's logs were last queried through, by topic.
+    scanned: HashMap<H256, u64>,
+    bridge_event_id: u64,
+    blob_event_id: u64,
+}
+
+impl ChainState {
+    /// Mines a block holding a log of the contract's with `topics` and
+    /// `data`.
+    fn emit(&mut self, topics: Vec<H256>, data: Vec<u8>) -> ChainEvent {
+        self.head += 1;
+        let log_index = self.logs.len();
+        let event = ChainEvent {
+            block_number: self.head,
+            transaction_hash: H256::from_low_u64_be(log_index as u64 + 1),
+        };
+        self.logs.push((
+            self.head,
+            json!({
+                "address": self.contract,
+                "topics": topics,
+                "data": format!("0x{}", hex::encode(data)),
+                "blockHash": block_hash(self.head),
+                "blockNumber": U64::from(self.head),
+                "transactionHash": event.transaction_hash,
+                "transactionIndex": U64::zero(),
+                "logIndex": web3::types::U256::from(log_index),
+                "removed": false,
+            }),
+        ));
+        event
+    }
+}
+
+fn block_hash(number: u64) -> H256 {
+    H256::from_low_u64_be(number + 1)
+}
+
+fn block(number: u64) -> Value {
+    json!({
+        "hash": block_hash(number),
+        "parentHash": H256::from_low_u64_be(number),
+        "sha3Uncles": H256::zero(),
+        "miner": H160::zero(),
+        "stateRoot": H256::zero(),
+        "transactionsRoot": H256::zero(),
+        "receiptsRoot": H256::zero(),
+        "number": U64::from(number),
+        "gasUsed": web3::types::U256::zero(),
+        "gasLimit": web3::types::U256::zero(),
+        "extraData": "0x",
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "timestamp": web3::types::U256::from(
+            CHAIN_GENESIS_TIMESTAMP + number * CHAIN_BLOCK_TIME.as_secs()
+        ),
+        "difficulty": web3::types::U256::zero(),
+        "mixHash": H256::zero(),
+        "nonce": "0x0000000000000000",
+        "uncles": [],
+        "transactions": [],
+    })
+}
+
+fn topic(topics: Option<Vec<H256>>) -> H256 {
+    topics
+        .and_then(|topics| topics.first().copied())
+        .unwrap_or_default()
+}
+
+/// A settlement chain served over http, holding only the blocks and the EO
+/// contract's events a test puts on it.
+#[derive(Clone)]
+pub struct ScriptedChain {
+    state: Arc<std::sync::Mutex<ChainState>>,
+    addr: SocketAddr,
+    handle: ServerHandle,
+}
+
+impl ScriptedChain {
+    pub async fn start() -> Result<Self, HarnessError> {
+        let state = Arc::new(std::sync::Mutex::new(ChainState::default()));
+        let mut module = RpcModule::new(Arc::clone(&state));
+        module
+            .register_method("eth_blockNumber", |_, chain| {
+                Ok::<_, ErrorObjectOwned>(U64::from(chain.lock().unwrap().head))
+            })
+            .map_err(custom)?;
+        module
+            .register_method("eth_getBlockByNumber", |params, chain| {
+                let number: U64 = params.sequence().next()?;
+                let head = chain.lock().unwrap().head;
+                Ok::<_, ErrorObjectOwned>(if number.as_u64() <= head {
+                    block(number.as_u64())
+                } else {
+                    Value::Null
+                })
+            })
+            .map_err(custom)?;
+        module
+            .register_method("eth_getLogs", |params, chain| {
+                let filter: Value = params.sequence().next()?;
+                let block = |field: &str| {
+                    serde_json::from_value::<U64>(filter[field].clone())
+                        .map(|number| number.as_u64())
+                        .unwrap_or_default()
+                };
+                let (from, to) = (block("fromBlock"), block("toBlock"));
+                let queried = match &filter["topics"][0] {
+                    Value::Array(topics) => topics.first().cloned(),
+                    topic => Some(topic.clone()),
+                }
+                .and_then(|topic| serde_json::from_value::<H256>(topic).ok());
+                let mut chain = chain.lock().unwrap();
+                if let Some(queried) = queried {
+                    let scanned = chain.scanned.entry(queried).or_default();
+                    *scanned = (*scanned).max(to);
+                }
+                let filter = filter.to_string().to_lowercase();
+                Ok::<_, ErrorObjectOwned>(
+                    chain
+                        .logs
+                        .iter()
+                        .filter(|(number, log)| {
+                            (from..=to).contains(number)
+                                && filter.contains(log["address"].as_str().unwrap_or_default())
+                                && filter.contains(log["topics"][0].as_str().unwrap_or_default())
+                        })
+                        .map(|(_, log)| log.clone())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .map_err(custom)?;
+        module
+            .register_method("eth_getBalance", |_, _| {
+                Ok::<_, ErrorObjectOwned>(web3::types::U256::zero())
+            })
+            .map_err(custom)?;
+        module
+            .register_method("eth_chainId", |_, _| {
+                Ok::<_, ErrorObjectOwned>(U64::from(1337))
+            })
+            .map_err(custom)?;
+        module
+            .register_method("net_version", |_, _| {
+                Ok::<_, ErrorObjectOwned>("1337".to_string())
+            })
+            .map_err(custom)?;
+        let server = ServerBuilder::default()
+            .build("127.0.0.1:0")
+            .await
+            .map_err(custom)?;
+        let addr = server.local_addr().map_err(custom)?;
+        Ok(Self {
+            state,
+            addr,
+            handle: server.start(module),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Emits the contract's events from `contract` from now on.
+    pub fn set_contract(&self, contract: H160) {
+        self.state.lock().unwrap().contract = contract;
+    }
+
+    pub fn head(&self) -> u64 {
+        self.state.lock().unwrap().head
+    }
+
+    /// Mines `blocks` empty blocks.
+    pub fn mine(&self, blocks: u64) {
+        self.state.lock().unwrap().head += blocks;
+    }
+
+    /// Mines a block holding the `BlobIndexSettled` event the contract emits
+    /// once the blob at `blob_index` under `batch_header_hash` is settled.
+    pub fn emit_settlement(
+        &self,
+        accounts: &[H160],
+        batch_header_hash: H256,
+        blob_index: u128,
+    ) -> ChainEvent {
+        let accounts_topic = H256::from_slice(&web3::signing::keccak256(
+            &accounts
+                .iter()
+                .flat_map(|account| H256::from(*account).0)
+                .collect::<Vec<u8>>(),
+        ));
+        let mut chain = self.state.lock().unwrap();
+        chain.blob_event_id += 1;
+        let data = web3::ethabi::encode(&[
+            Token::FixedBytes(batch_header_hash.0.to_vec()),
+            Token::Uint(blob_index.into()),
+            Token::Uint(chain.blob_event_id.into()),
+        ]);
+        chain.emit(
+            vec![
+                topic(eo_listener::get_blob_index_settled_topic()),
+                accounts_topic,
+            ],
+            data,
+        )
+    }
+
+    /// Mines a block holding the `Bridge` event the contract emits when
+    /// `user` deposits `amount` of the token at `token_address`.
+    pub fn emit_bridge(
+        &self,
+        user: H160,
+        token_address: H160,
+        amount: web3::types::U256,
+        token_type: &str,
+    ) -> ChainEvent {
+        let mut chain = self.state.lock().unwrap();
+        chain.bridge_event_id += 1;
+        let data = web3::ethabi::encode(&[
+            Token::Uint(amount),
+            Token::Uint(0.into()),
+            Token::String(token_type.to_string()),
+            Token::Uint(chain.bridge_event_id.into()),
+        ]);
+        chain.emit(
+            vec![
+                topic(eo_listener::get_bridge_event_topic()),
+                H256::from(user),
+                H256::from(token_address),
+            ],
+            data,
+        )
+    }
+
+    /// The block both of the contract's events have been queried through.
+    pub fn scanned_through(&self) -> u64 {
+        let chain = self.state.lock().unwrap();
+        [
+            eo_listener::get_bridge_event_topic(),
+            eo_listener::get_blob_index_settled_topic(),
+        ]
+        .into_iter()
+        .map(|topics| chain.scanned.get(&topic(topics)).copied().unwrap_or(0))
+        .min()
+        .unwrap_or(0)
+    }
+
+    pub fn stop(&self) {
+        self.handle.stop().ok();
+    }
+}
+
+/// Answers what the program of a simulated call outputs for its inputs, or
+/// why it reverts.
+pub type ExecutorScript = Arc<dyn Fn(&Account, Inputs) -> Result<Outputs, String> + Send + Sync>;
+
+/// Answers whether the auth program authorizes the transaction in its
+/// inputs, or why it failed.
+pub type AuthScript = Arc<dyn Fn(&Account, Inputs) -> Result<bool, String> + Send + Sync>;
+
+#[derive(Clone)]
+struct Scripts {
+    call: ExecutorScript,
+    auth: AuthScript,
+}
+
+/// Stands in for the executor, running simulated calls and auth programs as
+/// the scripts say.
+struct ScriptedExecutor;
+
+#[async_trait]
+impl Actor for ScriptedExecutor {
+    type Msg = ExecutorMessage;
+    type State = Arc<std::sync::Mutex<Scripts>>;
+    type Arguments = Self::State;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        scripts: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(scripts)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        scripts: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            ExecutorMessage::Simulate {
+                program,
+                inputs,
+                reply,
+            } => {
+                let script = scripts.lock().unwrap().call.clone();
+                let result = script.as_ref()(&program, inputs)
+                    .and_then(|outputs| serde_json::to_string(&outputs).map_err(|e| e.to_string()))
+                    .map_err(LasrError::Custom);
+                reply.send(result).ok();
+            }
+            ExecutorMessage::Authorize {
+                program,
+                inputs,
+                reply,
+                ..
+            } => {
+                let script = scripts.lock().unwrap().auth.clone();
+                let result = script.as_ref()(&program, inputs)
+                    .map(|verdict| verdict.to_string())
+                    .map_err(LasrError::Custom);
+                reply.send(result).ok();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// A batch the node handed on to be settled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settlement {
+    pub accounts: HashSet<String>,
+    pub batch_header_hash: H256,
+    pub blob_index: u128,
+}
+
+/// Stands in for the EO client, recording the batches it is handed and
+/// marking them finalized as the settler would before submitting them.
+struct SettlementRecorder;
+
+#[async_trait]
+impl Actor for SettlementRecorder {
+    type Msg = EoMessage;
+    type State = (Arc<std::sync::Mutex<Vec<Settlement>>>, BatchReceipts);
+    type Arguments = Self::State;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(state)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let (settlements, receipts) = state;
+        match message {
+            EoMessage::Settle {
+                accounts,
+                batch_header_hash,
+                blob_index,
+            } => settlements.lock().unwrap().push(Settlement {
+                accounts,
+                batch_header_hash,
+                blob_index,
+            }),
+            EoMessage::SettleBatchHeader { header } => {
+                receipts.set(&header, BatchStatus::Finalized)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn no_programs() -> Scripts {
+    Scripts {
+        call: Arc::new(|_: &Account, _: Inputs| Err("no program is scripted".to_string())),
+        auth: Arc::new(|_: &Account, _: Inputs| Err("no auth program is scripted".to_string())),
+    }
+}
+
+fn harness_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "lasr_harness_{}_{}",
+        std::process::id(),
+        HARNESSES.fetch_add(1, Ordering::SeqCst)
+    ));
+    std::fs::remove_dir_all(&dir).ok();
+    dir
+}
+
+/// Writes a genesis funding a new account with `funds` VERSE to `path`,
+/// returning the account's key.
+fn write_genesis(path: &Path, funds: U256) -> Result<SecretKey, HarnessError> {
+    let (sk, pk) = Secp256k1::new().generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+    let genesis = Genesis {
+        chain_id: DEFAULT_ETH_CHAIN_ID,
+        programs: Vec::new(),
+        accounts: vec![GenesisAccount {
+            address: Address::from(pk),
+            balances: BTreeMap::from([(VERSE_ADDR, funds)]),
+        }],
+    };
+    std::fs::write(path, toml::to_string(&genesis).map_err(custom)?)?;
+    Ok(sk)
+}
+
+/// A node running in-process, with what a test drives it by.
+pub struct TestHarness {
+    config: NodeConfig,
+    dir: PathBuf,
+    chain: ScriptedChain,
+    storage: StorageRef,
+    genesis: Genesis,
+    /// The key of the account the harness's own genesis funds.
+    genesis_key: Option<SecretKey>,
+    batcher: Arc<Mutex<Batcher>>,
+    batches: BatchIndex,
+    batch_receipts: BatchReceipts,
+    program_stats: ProgramStats,
+    load_shedder: LoadShedder,
+    pruner: Pruner,
+    settlements: Arc<std::sync::Mutex<Vec<Settlement>>>,
+    scripts: Arc<std::sync::Mutex<Scripts>>,
+    shutdown: Shutdown,
+    rpc_url: String,
+    server: ServerHandle,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestHarness {
+    /// Boots a devnet node.
+    pub async fn start() -> Result<Self, HarnessError> {
+        Self::start_with(|config| config).await
+    }
+
+    /// Boots a node with the devnet config `configure` changes, sealing at
+    /// once unless it says otherwise. Its blobs, settlement provider and RPC
+    /// address are the harness's own.
+    pub async fn start_with(
+        configure: impl FnOnce(NodeConfigBuilder) -> NodeConfigBuilder,
+    ) -> Result<Self, HarnessError> {
+        let dir = harness_dir();
+        std::fs::create_dir_all(&dir)?;
+        let chain = ScriptedChain::start().await?;
+        let mut config = configure(
+            NodeConfig::builder()
+                .instant_seal(true)
+                .commit_log_path(dir.join("commit.log")),
+        )
+        .local_da(dir.join("da"))
+        .transaction_index_path(dir.join("transaction_index.log"))
+        .program_stats_path(dir.join("program_stats.log"))
+        .event_log_path(dir.join("events.log"))
+        .eth_rpc_urls(vec![chain.url()])
+        .build()?;
+        config.retention.marks_path = dir.join("pruned.json");
+        let (genesis_path, genesis_key) = match config.genesis.path.clone() {
+            Some(path) => (path, None),
+            None => {
+                let path = dir.join("genesis.toml");
+                let sk = write_genesis(&path, config.devnet.faucet_max_amount)?;
+                config.genesis.path = Some(path.clone());
+                (path, Some(sk))
+            }
+        };
+        // Read back as the node reads it, so the harness's own is checked too.
+        let genesis = Genesis::load(&genesis_path).map_err(custom)?;
+        let contract = EoAddress::new(&config.eo_server.contract_address)
+            .parse()
+            .map_err(custom)?;
+        chain.set_contract(contract);
+
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .map_err(custom)?;
+        genesis
+            .apply_encoded(&storage, config.storage.encoding)
+            .await
+            .map_err(custom)?;
+        let (receivers_thread_tx, receivers_thread_rx) = tokio::sync::mpsc::channel(128);
+        let batches = BatchIndex::open(&dir.join("batches.log")).map_err(custom)?;
+        let event_log = EventLog::open(&config.events.log_path).map_err(custom)?;
+        let batch_receipts = BatchReceipts::default();
+        let batch_feed = BatchFeed::default();
+        let program_stats = config.program_stats.open().map_err(custom)?;
+        let (commit_log, committed) =
+            CommitLog::open(&config.batcher.commit_log_path).map_err(custom)?;
+        let commit_log = commit_log.with_encoding(config.storage.encoding);
+        let mut batcher = Batcher::new(receivers_thread_tx)
+            .with_encoding(config.storage.encoding)
+            .with_batch_index(batches.clone())
+            .with_commit_log(commit_log)
+            .with_event_log(event_log.clone())
+            .with_batch_feed(batch_feed.clone())
+            .with_fee_schedule(config.fees.schedule())
+            .with_recent_window(config.receipts.recent_window)
+            .with_storage_quota(config.storage.quota())
+            .with_program_stats(program_stats.clone());
+        let transaction_index = if config.transaction_index.enabled {
+            Some(TransactionIndex::open(&config.transaction_index.path).map_err(custom)?)
+        } else {
+            None
+        };
+        if let Some(index) = &transaction_index {
+            batcher = batcher.with_transaction_index(index.clone());
+        }
+        if config.devnet.instant_seal {
+            batcher = batcher.with_instant_seal(storage.clone(), batch_receipts.clone());
+        }
+        let inclusions = batcher.inclusions();
+        let programs = batcher.programs();
+        genesis.register_programs(&programs).map_err(custom)?;
+        let batcher = Arc::new(Mutex::new(batcher));
+        let local_da = LocalFileDa::open(&config.da.local_path)?;
+        let mut pruner = Pruner::open(config.retention.clone(), batches.clone())
+            .map_err(custom)?
+            .with_batch_receipts(batch_receipts.clone())
+            .with_local_da(local_da.clone());
+        if let Some(index) = &transaction_index {
+            pruner = pruner.with_transaction_index(index.clone());
+        }
+        let da_client = Arc::new(Mutex::new(DaClient::local(local_da)));
+        let admission_policy = config.admission.policy_gate().map_err(custom)?;
+        let validator_core = Arc::new(Mutex::new(
+            ValidatorCore::default()
+                .with_admission(config.admission())
+                .with_admission_policy(admission_policy.clone()),
+        ));
+        let settlements = Arc::default();
+        let scripts = Arc::new(std::sync::Mutex::new(no_programs()));
+
+        let blob_cache_actor = BlobCacheActor::new();
+        let account_cache_actor = AccountCacheActor::new()
+            .with_encoding(config.storage.encoding)
+            .with_verse_audit(config.caches.audit_verse_supply);
+        let pending_transaction_actor = PendingTransactionActor::new()
+            .with_config(config.pending.clone())
+            .with_log_path(dir.join("pending_transactions.log"))
+            .with_mempool_limits(config.caches.mempool)
+            .with_scheduling(config.scheduling.clone())
+            .with_fee_schedule(config.fees.schedule());
+        let lasr_rpc_actor = LasrRpcServerActor::new();
+        let scheduler_actor = TaskScheduler::new()
+            .with_retry_policy(config.retry)
+            .with_persisted_receipts(config.receipts.persist)
+            .with_recent_window(config.receipts.recent_window);
+        let eo_server_actor = EoServerActor::new();
+        let engine_actor = EngineActor::new().with_output_limits(config.outputs);
+        let validator_actor = ValidatorActor::new();
+        let da_client_actor = DaClientActor::new().with_health(config.da.health());
+        let da_health = da_client_actor.health();
+        let batcher_actor = BatcherActor::new();
+
+        let spawn_failed = |e: ractor::SpawnErr| custom(format!("failed to spawn actor: {e}"));
+        Actor::spawn(Some(blob_cache_actor.name()), blob_cache_actor, ())
+            .await
+            .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(account_cache_actor.name()),
+            account_cache_actor,
+            storage.clone(),
+        )
+        .await
+        .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(pending_transaction_actor.name()),
+            pending_transaction_actor,
+            (),
+        )
+        .await
+        .map_err(spawn_failed)?;
+        let (lasr_rpc_actor_ref, _) = Actor::spawn(Some(lasr_rpc_actor.name()), lasr_rpc_actor, ())
+            .await
+            .map_err(spawn_failed)?;
+        Actor::spawn(Some(scheduler_actor.name()), scheduler_actor, ())
+            .await
+            .map_err(spawn_failed)?;
+        Actor::spawn(Some(eo_server_actor.name()), eo_server_actor, ())
+            .await
+            .map_err(spawn_failed)?;
+        Actor::spawn(Some(engine_actor.name()), engine_actor.clone(), ())
+            .await
+            .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(validator_actor.name()),
+            validator_actor.clone(),
+            validator_core,
+        )
+        .await
+        .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(da_client_actor.name()),
+            da_client_actor.clone(),
+            da_client,
+        )
+        .await
+        .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(batcher_actor.name()),
+            batcher_actor.clone(),
+            batcher.clone(),
+        )
+        .await
+        .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(ActorType::Executor.to_string()),
+            ScriptedExecutor,
+            Arc::clone(&scripts),
+        )
+        .await
+        .map_err(spawn_failed)?;
+        Actor::spawn(
+            Some(ActorType::EoClient.to_string()),
+            SettlementRecorder,
+            (Arc::clone(&settlements), batch_receipts.clone()),
+        )
+        .await
+        .map_err(spawn_failed)?;
+
+        let future_pool = || {
+            tokio_rayon::rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .map_err(custom)
+        };
+        let mut tasks = vec![
+            tokio::spawn(async move {
+                Batcher::run_receivers(receivers_thread_rx).await.ok();
+            }),
+            ActorExt::spawn_future_handler(batcher_actor, future_pool()?),
+            ActorExt::spawn_future_handler(validator_actor, future_pool()?),
+            ActorExt::spawn_future_handler(da_client_actor, future_pool()?),
+            ActorExt::spawn_future_handler(engine_actor, future_pool()?),
+            // Releases the transactions held until they are valid.
+            tokio::spawn(async {
+                graph_cleaner().await.ok();
+            }),
+        ];
+        Batcher::replay_commits(batcher.clone(), committed)
+            .await
+            .map_err(custom)?;
+
+        let blocks_processed_path = dir.join("blocks_processed.dat");
+        let eo_server = eo_server(&config, contract, &blocks_processed_path).await?;
+        let wrapper = EoServerWrapper::new(eo_server).with_poll_interval(HARNESS_POLL_INTERVAL);
+        let path = blocks_processed_path.to_string_lossy().to_string();
+        let eo_storage = storage.clone();
+        tasks.push(tokio::spawn(async move {
+            wrapper.run(path, eo_storage).await.ok();
+        }));
+
+        let shutdown = Shutdown::default();
+        let load_shedder = config.load_shedding.shedder();
+        let mut rpc = LasrRpcServerImpl::new(lasr_rpc_actor_ref, ExecutionLogs::default())
+            .with_admission(config.admission())
+            .with_faucet(Faucet::new(config.devnet.faucet()))
+            .with_paginator(config.rpc.paginator())
+            .with_output_limits(config.outputs)
+            .with_admission_policy(admission_policy)
+            .with_batch_receipts(batch_receipts.clone())
+            .with_transaction_inclusions(inclusions)
+            .with_program_registry(programs)
+            .with_batch_index(batches.clone())
+            .with_event_log(event_log)
+            .with_batch_feed(batch_feed)
+            .with_storage_quota(config.storage.quota())
+            .with_da_health(da_health)
+            .with_program_stats(program_stats.clone())
+            .with_unsigned_simulation(true)
+            .with_eth_chain_id(genesis.chain_id)
+            .with_load_shedder(load_shedder.clone())
+            .with_pruner(pruner.clone())
+            .with_shutdown(shutdown.clone());
+        if let Some(index) = transaction_index {
+            rpc = rpc.with_transaction_index(index);
+        }
+        let rest = config.rpc.rest.then(|| rest_router(rpc.clone()));
+        let mut methods = LasrRpcServer::into_rpc(rpc.clone());
+        methods
+            .merge(EthRpcServer::into_rpc(rpc.clone()))
+            .map_err(custom)?;
+        methods
+            .merge(AdminRpcServer::into_rpc(rpc))
+            .map_err(custom)?;
+        let (addr, server) = serve_rpc(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            methods,
+            RateLimiter::default(),
+            AdminAccess::Token(ADMIN_TOKEN.to_string()),
+            rest,
+            config.rpc.requests,
+        )
+        .await
+        .map_err(custom)?;
+
+        Ok(Self {
+            config,
+            dir,
+            chain,
+            storage,
+            genesis,
+            genesis_key,
+            batcher,
+            batches,
+            batch_receipts,
+            program_stats,
+            load_shedder,
+            pruner,
+            settlements,
+            scripts,
+            shutdown,
+            rpc_url: format!("http://{addr}"),
+            server,
+            tasks,
+        })
+    }
+
+    pub fn config(&self) -> &NodeConfig {
+        &self.config
+    }
+
+    /// The genesis the node was started from.
+    pub fn genesis(&self) -> &Genesis {
+        &self.genesis
+    }
+
+    pub fn chain(&self) -> &ScriptedChain {
+        &self.chain
+    }
+
+    pub fn batcher(&self) -> Arc<Mutex<Batcher>> {
+        self.batcher.clone()
+    }
+
+    /// What each program has cost the node. Nothing executes calls in the
+    /// harness, so a test counts their executions here as the executor would.
+    pub fn program_stats(&self) -> ProgramStats {
+        self.program_stats.clone()
+    }
+
+    /// Whether the node turns writes away as overloaded. Nothing samples the
+    /// node's load in the harness, so a test observes the signals here.
+    pub fn load_shedder(&self) -> LoadShedder {
+        self.load_shedder.clone()
+    }
+
+    /// What prunes the node's blobs and receipts. Nothing prunes on an
+    /// interval in the harness, so a test prunes through `admin_prune` or
+    /// here.
+    pub fn pruner(&self) -> Pruner {
+        self.pruner.clone()
+    }
+
+    pub fn batches(&self) -> BatchIndex {
+        self.batches.clone()
+    }
+
+    pub fn batch_receipts(&self) -> BatchReceipts {
+        self.batch_receipts.clone()
+    }
+
+    /// The batches handed on to be settled, in the order they were.
+    pub fn settlements(&self) -> Vec<Settlement> {
+        self.settlements.lock().unwrap().clone()
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    pub fn rpc(&self) -> Result<HttpClient, HarnessError> {
+        HttpClientBuilder::default()
+            .build(&self.rpc_url)
+            .map_err(custom)
+    }
+
+    /// The bearer token the admin methods let callers through with.
+    pub fn admin_token(&self) -> &str {
+        ADMIN_TOKEN
+    }
+
+    /// A client let through to the admin methods.
+    pub fn admin(&self) -> Result<HttpClient, HarnessError> {
+        let authorization =
+            HeaderValue::from_str(&format!("Bearer {ADMIN_TOKEN}")).map_err(custom)?;
+        HttpClientBuilder::default()
+            .set_headers(HeaderMap::from_iter([(AUTHORIZATION, authorization)]))
+            .build(&self.rpc_url)
+            .map_err(custom)
+    }
+
+    /// Runs simulated calls as `script` says from now on.
+    pub fn script_executor(
+        &self,
+        script: impl Fn(&Account, Inputs) -> Result<Outputs, String> + Send + Sync + 'static,
+    ) {
+        self.scripts.lock().unwrap().call = Arc::new(script);
+    }
+
+    /// Runs auth programs as `script` says from now on.
+    pub fn script_auth(
+        &self,
+        script: impl Fn(&Account, Inputs) -> Result<bool, String> + Send + Sync + 'static,
+    ) {
+        self.scripts.lock().unwrap().auth = Arc::new(script);
+    }
+
+    /// The account at `address`, as the node holds it now.
+    pub async fn account(&self, address: Address) -> Option<Account> {
+        get_account(address, ActorType::RpcServer).await
+    }
+
+    /// Waits for the account at `address` to be as `expected` says.
+    pub async fn wait_for_account(
+        &self,
+        address: Address,
+        expected: impl Fn(&Account) -> bool,
+    ) -> Result<Account, HarnessError> {
+        let expected = &expected;
+        eventually(
+            &format!("account {}", address.to_full_string()),
+            || async move {
+                self.account(address)
+                    .await
+                    .filter(|account| expected(account))
+            },
+        )
+        .await
+    }
+
+    /// A wallet for a new account, funded with as much VERSE as the faucet
+    /// hands out at once.
+    pub async fn wallet(&self) -> Result<Wallet<HttpClient>, HarnessError> {
+        let (sk, pk) = Secp256k1::new().generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let address = Address::from(pk);
+        let funds = self.config.devnet.faucet_max_amount;
+        self.rpc()?
+            .faucet(address.to_full_string(), funds.to_string(), None)
+            .await
+            .map_err(custom)?;
+        let account = self
+            .wait_for_account(address, |account| account.balance(&VERSE_ADDR) >= funds)
+            .await?;
+        self.wallet_for(sk, account)
+    }
+
+    /// A wallet for the account the harness's genesis funded, with as much
+    /// VERSE as the faucet hands out at once from the start.
+    pub async fn genesis_wallet(&self) -> Result<Wallet<HttpClient>, HarnessError> {
+        let sk = self
+            .genesis_key
+            .ok_or_else(|| custom("the node was started from the test's own genesis"))?;
+        let address = Address::from(PublicKey::from_secret_key(&Secp256k1::new(), &sk));
+        let account = self
+            .account(address)
+            .await
+            .ok_or_else(|| custom("the genesis account is not held by the node"))?;
+        self.wallet_for(sk, account)
+    }
+
+    fn wallet_for(
+        &self,
+        sk: SecretKey,
+        account: Account,
+    ) -> Result<Wallet<HttpClient>, HarnessError> {
+        let address = account.owner_address();
+        WalletBuilder::default()
+            .client(self.rpc()?)
+            .sk(sk)
+            .builder(PayloadBuilder::default())
+            .address(address)
+            .account(account)
+            .build()
+            .map_err(custom)
+    }
+
+    /// Mines `blocks` blocks on the settlement chain, and waits for the EO
+    /// listener to scan them.
+    pub async fn advance_blocks(&self, blocks: u64) -> Result<(), HarnessError> {
+        self.chain.mine(blocks);
+        self.wait_scanned().await
+    }
+
+    /// Moves the settlement chain on by `duration`, a block for every block
+    /// time there is in it. The node's own clock is not moved.
+    pub async fn advance(&self, duration: Duration) -> Result<(), HarnessError> {
+        let blocks = (duration.as_secs() / CHAIN_BLOCK_TIME.as_secs()).max(1);
+        self.advance_blocks(blocks).await
+    }
+
+    /// Seals every batch the node holds as the batch requestor would, and
+    /// waits for them to be finalized on the DA layer.
+    pub async fn seal_batch(&self) -> Result<Vec<BatchRecord>, HarnessError> {
+        let first = self.batches.next_batch_id();
+        let sealed = Batcher::seal(self.batcher.clone(), self.storage.clone())
+            .await
+            .map_err(custom)? as u64;
+        eventually("the sealed batches to be finalized", || async move {
+            (self.batches.next_batch_id() >= first + sealed).then_some(())
+        })
+        .await?;
+        Ok((first..first + sealed)
+            .filter_map(|batch_id| self.batches.get(batch_id))
+            .collect())
+    }
+
+    /// Settles batch `batch_id` as the contract would once the settler
+    /// submitted it, emitting the event and waiting for it to be scanned.
+    pub async fn settle(&self, batch_id: u64) -> Result<ChainEvent, HarnessError> {
+        let record = self
+            .batches
+            .get(batch_id)
+            .ok_or_else(|| custom(format!("no batch {batch_id} was finalized")))?;
+        let record = &record;
+        let settlement = eventually(
+            &format!("batch {batch_id} to be handed on for settlement"),
+            || async move {
+                self.settlements().into_iter().find(|settlement| {
+                    settlement.batch_header_hash == record.blob_commitment
+                        && settlement.blob_index == record.blob_index
+                })
+            },
+        )
+        .await?;
+        let accounts = settlement
+            .accounts
+            .iter()
+            .filter_map(|account| H160::from_str(account).ok())
+            .collect::<Vec<_>>();
+        let event = self
+            .inject_settlement(&accounts, record.blob_commitment, record.blob_index)
+            .await?;
+        self.batch_receipts.set(
+            &record.header(),
+            BatchStatus::Settled {
+                transaction_hash: event.transaction_hash,
+                block_number: event.block_number,
+            },
+        );
+        Ok(event)
+    }
+
+    /// Emits a `BlobIndexSettled` event, whether or not the node sealed the
+    /// blob it names, and waits for it to be scanned.
+    pub async fn inject_settlement(
+        &self,
+        accounts: &[H160],
+        batch_header_hash: H256,
+        blob_index: u128,
+    ) -> Result<ChainEvent, HarnessError> {
+        let event = self
+            .chain
+            .emit_settlement(accounts, batch_header_hash, blob_index);
+        self.wait_scanned().await?;
+        Ok(event)
+    }
+
+    /// Emits a `Bridge` event depositing `amount` for `user`, and waits for
+    /// it to be scanned.
+    pub async fn inject_bridge(
+        &self,
+        user: H160,
+        token_address: H160,
+        amount: web3::types::U256,
+        token_type: &str,
+    ) -> Result<ChainEvent, HarnessError> {
+        let event = self
+            .chain
+            .emit_bridge(user, token_address, amount, token_type);
+        self.wait_scanned().await?;
+        Ok(event)
+    }
+
+    async fn wait_scanned(&self) -> Result<(), HarnessError> {
+        let head = self.chain.head();
+        eventually(&format!("block {head} to be scanned"), || async move {
+            (self.chain.scanned_through() >= head).then_some(())
+        })
+        .await
+    }
+
+    /// Drains the node as a signal would, then stops it.
+    pub async fn stop(self) -> DrainReport {
+        let report = ShutdownDrain::new(self.shutdown, self.batcher, self.storage)
+            .with_deadline(HARNESS_TIMEOUT)
+            .with_unsealed_batches(UnsealedBatches::new(self.dir.join("unsealed_batches")))
+            .run()
+            .await;
+        self.server.stop().ok();
+        self.server.stopped().await;
+        ShutdownDrain::stop_actors().await;
+        for task in self.tasks {
+            task.abort();
+        }
+        self.chain.stop();
+        std::fs::remove_dir_all(&self.dir).ok();
+        report
+    }
+
+    /// Stops the node as a crash would, draining nothing and keeping none
+    /// of the batches it had not sealed. Only its commit log is left, if a
+    /// test put it outside the harness's own directory.
+    pub async fn crash(self) {
+        self.server.stop().ok();
+        self.server.stopped().await;
+        ShutdownDrain::stop_actors().await;
+        for task in self.tasks {
+            task.abort();
+        }
+        self.chain.stop();
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+async fn eo_server(
+    config: &NodeConfig,
+    contract: H160,
+    path: &Path,
+) -> Result<eo_listener::EoServer, HarnessError> {
+    let provider = FailoverProvider::from_urls(&config.eo_server.eth_rpc_urls)?;
+    let web3 = provider.primary().clone();
+    let abi = eo_listener::get_abi().map_err(custom)?;
+    let blob_settled_event = abi.event("BlobIndexSettled").map_err(custom)?.clone();
+    let bridge_event = abi.event("Bridge").map_err(custom)?.clone();
+    let mut eo_server = EoServerBuilder::default()
+        .contract(web3::contract::Contract::new(web3.eth(), contract, abi))
+        .web3(web3)
+        .provider(provider)
+        .eo_address(EoAddress::new(&config.eo_server.contract_address))
+        .block_time(HARNESS_POLL_INTERVAL)
+        .bridge_processed_blocks(Default::default())
+        .settled_processed_blocks(Default::default())
+        .bridge_topic(eo_listener::get_bridge_event_topic())
+        .blob_settled_topic(eo_listener::get_blob_index_settled_topic())
+        .current_bridge_filter_block(0.into())
+        .current_blob_settlement_filter_block(0.into())
+        .blob_settled_event(blob_settled_event)
+        .bridge_event(bridge_event)
+        .path(path.to_path_buf())
+        .reorg_tracker(ReorgTracker::new(0))
+        .build()
+        .map_err(custom)?;
+    eo_server.resume().await?;
+    Ok(eo_server)
+}
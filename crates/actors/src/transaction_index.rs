@@ -0,0 +1,405 @@
+//! The transactions in the batches this node has finalized, by the accounts
+//! and programs they involve, for `getTransactionsByAddress` and
+//! `getTransactionsByProgram`.
+//!
+//! The batcher indexes a batch's transactions by sender, recipient and
+//! program id once the batch's blob is verified on the DA layer. Indexing is
+//! off unless `TRANSACTION_INDEX` is set, since every transaction is kept on
+//! disk. Each entry is appended to the index's [`RecordLog`] as it is made,
+//! as JSON as in the batch index, and read back on the next start. Batches
+//! finalized while the index was off, or lost with its file, are indexed
+//! again from their blobs by [`TransactionIndex::rebuild`], unless they are
+//! past the node's retention and were pruned from it.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use lasr_types::{Address, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::record_log::{encode_record, Json, RecordLog, RecordLogError};
+use crate::{Batch, BatchIndex, BatchRecord, DaClient};
+
+#[derive(Debug, Error)]
+pub enum TransactionIndexError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// A transaction as it is indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedTransaction {
+    pub batch_id: u64,
+    pub transaction_hash: String,
+    /// Seconds since the epoch the transaction's batch was finalized at.
+    pub timestamp: u64,
+    pub from: Address,
+    pub to: Address,
+    pub program_id: Address,
+}
+
+impl IndexedTransaction {
+    /// Where the transaction is in the index, oldest batch first, which its
+    /// pages are ordered by.
+    pub fn key(&self) -> (u64, String) {
+        (self.batch_id, self.transaction_hash.clone())
+    }
+}
+
+#[derive(Debug, Default)]
+struct TransactionIndexInner {
+    transactions: BTreeMap<(u64, String), IndexedTransaction>,
+    senders: HashMap<Address, BTreeSet<(u64, String)>>,
+    recipients: HashMap<Address, BTreeSet<(u64, String)>>,
+    programs: HashMap<Address, BTreeSet<(u64, String)>>,
+    /// The batches whose transactions are indexed.
+    batches: BTreeSet<u64>,
+    /// The batch the transactions of it and every batch before it were
+    /// pruned through, if any were.
+    pruned_through: Option<u64>,
+    log: Option<RecordLog<IndexedTransaction>>,
+}
+
+impl TransactionIndexInner {
+    fn insert(&mut self, transaction: IndexedTransaction) {
+        let key = transaction.key();
+        self.batches.insert(transaction.batch_id);
+        self.senders
+            .entry(transaction.from)
+            .or_default()
+            .insert(key.clone());
+        self.recipients
+            .entry(transaction.to)
+            .or_default()
+            .insert(key.clone());
+        self.programs
+            .entry(transaction.program_id)
+            .or_default()
+            .insert(key.clone());
+        self.transactions.insert(key, transaction);
+    }
+
+    fn get<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a (u64, String)>,
+    ) -> Vec<IndexedTransaction> {
+        keys.into_iter()
+            .filter_map(|key| self.transactions.get(key).cloned())
+            .collect()
+    }
+
+    fn is_pruned(&self, batch_id: u64) -> bool {
+        self.pruned_through
+            .is_some_and(|pruned_through| batch_id <= pruned_through)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransactionIndex {
+    index: Arc<Mutex<TransactionIndexInner>>,
+}
+
+impl TransactionIndex {
+    /// Opens the index at `path`, creating it if there is none, with the
+    /// transactions indexed there already.
+    pub fn open(path: &Path) -> Result<Self, TransactionIndexError> {
+        let (log, transactions) = RecordLog::open(path)?;
+        let mut inner = TransactionIndexInner::default();
+        for transaction in transactions {
+            inner.insert(transaction);
+        }
+        inner.log = Some(log);
+
+        Ok(Self {
+            index: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    /// Indexes the `transactions` in the batch `record` finalized, unless
+    /// the batch is indexed already, appending them to the file if the index
+    /// has one.
+    pub fn batched<'a>(
+        &self,
+        record: &BatchRecord,
+        transactions: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Result<(), TransactionIndexError> {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        if index.is_pruned(record.batch_id) || !index.batches.insert(record.batch_id) {
+            return Ok(());
+        }
+        let mut transactions: Vec<_> = transactions
+            .into_iter()
+            .map(|transaction| IndexedTransaction {
+                batch_id: record.batch_id,
+                transaction_hash: transaction.hash_string(),
+                timestamp: record.finalized_at,
+                from: transaction.from(),
+                to: transaction.to(),
+                program_id: transaction.program_id(),
+            })
+            .collect();
+        transactions.sort_by_key(IndexedTransaction::key);
+        if let Some(log) = index.log.as_mut() {
+            log.append_synced(&transactions)?;
+        }
+        for transaction in transactions {
+            index.insert(transaction);
+        }
+        Ok(())
+    }
+
+    /// Indexes every batch in `batches` the index does not hold and has not
+    /// pruned, reading each one's transactions back from its blob, and
+    /// answers how many batches were.
+    pub fn rebuild(
+        &self,
+        batches: &BatchIndex,
+        da_client: &DaClient,
+    ) -> Result<usize, TransactionIndexError> {
+        let missing: Vec<_> = {
+            let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+            batches
+                .list()
+                .into_iter()
+                .filter(|record| {
+                    !index.batches.contains(&record.batch_id) && !index.is_pruned(record.batch_id)
+                })
+                .collect()
+        };
+        for record in &missing {
+            let blob = da_client.retrieve_blob(record.blob_commitment, record.blob_index)?;
+            let batch = Batch::decode_batch(&blob).ok_or_else(|| {
+                TransactionIndexError::Custom(format!(
+                    "the blob of batch {} does not decode to a batch",
+                    record.batch_id
+                ))
+            })?;
+            self.batched(record, batch.transactions().values())?;
+        }
+        Ok(missing.len())
+    }
+
+    /// How many bytes of the index's file the transactions of batch
+    /// `through` and every batch before it take up.
+    pub fn size_through(&self, through: u64) -> u64 {
+        let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index
+            .transactions
+            .range(..(through + 1, String::new()))
+            .filter_map(|(_, transaction)| encode_record(&Json, transaction).ok())
+            .map(|bytes| bytes.len() as u64)
+            .sum()
+    }
+
+    /// Drops the transactions of batch `through` and every batch before it,
+    /// rewriting the file without them if the index has one, and answers
+    /// how many bytes it was shrunk by. They are not indexed again.
+    pub fn prune(&self, through: u64) -> Result<u64, TransactionIndexError> {
+        let mut guard = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        let index = &mut *guard;
+        index.pruned_through = index.pruned_through.max(Some(through));
+        let kept = index.transactions.split_off(&(through + 1, String::new()));
+        let pruned = std::mem::replace(&mut index.transactions, kept);
+        if pruned.is_empty() {
+            return Ok(0);
+        }
+        index.batches = index.batches.split_off(&(through + 1));
+        for keys in [
+            &mut index.senders,
+            &mut index.recipients,
+            &mut index.programs,
+        ] {
+            keys.retain(|_, keys| {
+                *keys = keys.split_off(&(through + 1, String::new()));
+                !keys.is_empty()
+            });
+        }
+        let mut freed = 0;
+        for transaction in pruned.values() {
+            freed += encode_record(&Json, transaction)?.len() as u64;
+        }
+        if let Some(log) = index.log.as_mut() {
+            // Written aside and moved over the file, so a crash leaves one or
+            // the other whole.
+            log.rewrite(index.transactions.values())?;
+        }
+        Ok(freed)
+    }
+
+    /// Every transaction indexed that `address` sent or received.
+    pub fn by_address(&self, address: &Address) -> Vec<IndexedTransaction> {
+        let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: BTreeSet<_> = [index.senders.get(address), index.recipients.get(address)]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        index.get(keys)
+    }
+
+    /// Every transaction indexed under `program_id`.
+    pub fn by_program(&self, program_id: &Address) -> Vec<IndexedTransaction> {
+        let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index.get(index.programs.get(program_id).into_iter().flatten())
+    }
+}
+
+#[cfg(test)]
+mod transaction_index_tests {
+    use super::*;
+    use ethereum_types::H256;
+    use lasr_types::{TransactionBuilder, TransactionType, U256};
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn transaction(from: u8, to: u8, program_id: u8, nonce: u64) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(0)))
+            .from([from; 20])
+            .to([to; 20])
+            .program_id([program_id; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(1))
+            .nonce(U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    fn record(batch_id: u64) -> BatchRecord {
+        BatchRecord {
+            batch_id,
+            state_root: H256::repeat_byte(1),
+            previous_state_root: H256::repeat_byte(1),
+            transactions_root: H256::repeat_byte(4),
+            events_root: H256::repeat_byte(3),
+            blob_commitment: H256::repeat_byte(2),
+            blob_index: batch_id as u128,
+            withdrawals: Vec::new(),
+            account_count: 2,
+            transaction_hashes: Vec::new(),
+            sealed_at: 10,
+            finalized_at: 12,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "transaction_index_{}_{}.log",
+            name,
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    #[test]
+    fn transactions_are_read_back_after_a_restart() {
+        let path = temp_path("restart");
+        let index = TransactionIndex::open(&path).unwrap();
+        index
+            .batched(
+                &record(0),
+                &[transaction(1, 2, 0, 0), transaction(2, 3, 9, 0)],
+            )
+            .unwrap();
+        index
+            .batched(&record(1), &[transaction(1, 3, 0, 1)])
+            .unwrap();
+        drop(index);
+
+        let index = TransactionIndex::open(&path).unwrap();
+        let sent = index.by_address(&Address::new([1; 20]));
+        assert_eq!(
+            sent.iter().map(|tx| tx.batch_id).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(index.by_address(&Address::new([3; 20])).len(), 2);
+        let programs = index.by_program(&Address::new([9; 20]));
+        assert_eq!(programs.len(), 1);
+        assert_eq!(programs[0].from, Address::new([2; 20]));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_torn_record_is_dropped_on_open() {
+        let path = temp_path("torn");
+        let index = TransactionIndex::open(&path).unwrap();
+        index
+            .batched(&record(0), &[transaction(1, 2, 0, 0)])
+            .unwrap();
+        drop(index);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[200, 0, 0, 0, b'{']).unwrap();
+        drop(file);
+
+        let index = TransactionIndex::open(&path).unwrap();
+        assert_eq!(index.by_address(&Address::new([1; 20])).len(), 1);
+        index
+            .batched(&record(1), &[transaction(1, 2, 0, 1)])
+            .unwrap();
+        drop(index);
+        let index = TransactionIndex::open(&path).unwrap();
+        assert_eq!(index.by_address(&Address::new([1; 20])).len(), 2);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_batch_is_indexed_once_and_a_self_send_listed_once() {
+        let index = TransactionIndex::default();
+        index
+            .batched(&record(0), &[transaction(1, 1, 0, 0)])
+            .unwrap();
+        index
+            .batched(
+                &record(0),
+                &[transaction(1, 1, 0, 0), transaction(1, 2, 0, 1)],
+            )
+            .unwrap();
+        assert_eq!(index.by_address(&Address::new([1; 20])).len(), 1);
+        assert!(index.by_address(&Address::new([2; 20])).is_empty());
+    }
+
+    #[test]
+    fn pruned_batches_are_dropped_from_the_file_and_not_indexed_again() {
+        let path = temp_path("pruned");
+        let index = TransactionIndex::open(&path).unwrap();
+        for batch_id in 0..3 {
+            index
+                .batched(&record(batch_id), &[transaction(1, 2, 0, batch_id)])
+                .unwrap();
+        }
+        let size = std::fs::metadata(&path).unwrap().len();
+        let through = index.size_through(1);
+        assert_eq!(index.prune(1).unwrap(), through);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size - through);
+        index
+            .batched(&record(0), &[transaction(1, 2, 0, 0)])
+            .unwrap();
+        assert_eq!(index.by_address(&Address::new([1; 20])).len(), 1);
+        index
+            .batched(&record(3), &[transaction(1, 2, 0, 3)])
+            .unwrap();
+        drop(index);
+
+        let index = TransactionIndex::open(&path).unwrap();
+        let sent = index.by_address(&Address::new([1; 20]));
+        assert_eq!(
+            sent.iter().map(|tx| tx.batch_id).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
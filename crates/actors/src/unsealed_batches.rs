@@ -0,0 +1,142 @@
+//! The batches the batcher still held when the node stopped without being
+//! able to seal and disperse them, so the transactions applied to them are
+//! not lost.
+//!
+//! They are written as the node shuts down, replacing whatever was written
+//! before, and taken back into the batcher on the next start to be dispersed
+//! ahead of anything new. Taking them removes the file, as from then on the
+//! batcher holds them again and writes them back if it stops before they are
+//! dispersed.
+//!
+//! Batches are written as JSON in a [`RecordLog`], one record each.
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::record_log::{Json, RecordLog, RecordLogError};
+use crate::Batch;
+
+pub const DEFAULT_UNSEALED_BATCHES_PATH: &str = "./unsealed_batches.log";
+
+#[derive(Debug, Error)]
+pub enum UnsealedBatchesError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Log(#[from] RecordLogError),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct UnsealedBatches {
+    path: PathBuf,
+}
+
+impl Default for UnsealedBatches {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNSEALED_BATCHES_PATH)
+    }
+}
+
+impl UnsealedBatches {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Writes the non-empty `batches`, oldest first, in place of whatever was
+    /// written before, returning how many were written.
+    pub fn write(&self, batches: &[Batch]) -> Result<usize, UnsealedBatchesError> {
+        let batches: Vec<&Batch> = batches.iter().filter(|batch| !batch.empty()).collect();
+        RecordLog::<Batch, Json>::create(&self.path, batches.iter().copied())?;
+        Ok(batches.len())
+    }
+
+    /// Takes the batches written, oldest first, removing the file. There are
+    /// none if nothing was written.
+    pub fn take(&self) -> Result<Vec<Batch>, UnsealedBatchesError> {
+        let batches = RecordLog::<Batch, Json>::read(&self.path)?;
+        if self.path.exists() {
+            std::fs::remove_file(&self.path)?;
+        }
+        Ok(batches)
+    }
+}
+
+#[cfg(test)]
+mod unsealed_batches_tests {
+    use super::*;
+    use lasr_types::{TransactionBuilder, TransactionType, U256};
+
+    fn batch(nonce: u64) -> Batch {
+        let mut batch = Batch::new();
+        batch
+            .insert_transaction(
+                TransactionBuilder::default()
+                    .transaction_type(TransactionType::Send(U256::from(0)))
+                    .from([1; 20])
+                    .to([2; 20])
+                    .program_id([0; 20])
+                    .op(String::new())
+                    .inputs(String::new())
+                    .value(U256::from(1))
+                    .nonce(U256::from(nonce))
+                    .v(0)
+                    .r([0; 32])
+                    .s([0; 32])
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        batch
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "unsealed_batches_{}_{}.log",
+            name,
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn hashes(batches: &[Batch]) -> Vec<Vec<String>> {
+        batches
+            .iter()
+            .map(|batch| batch.transactions().into_keys().collect())
+            .collect()
+    }
+
+    #[test]
+    fn batches_written_are_taken_back_once() {
+        let unsealed = UnsealedBatches::new(temp_path("taken"));
+        assert!(unsealed.take().unwrap().is_empty());
+
+        let batches = vec![batch(0), Batch::new(), batch(1)];
+        assert_eq!(unsealed.write(&batches).unwrap(), 2);
+        // Written again, they replace what was there.
+        assert_eq!(unsealed.write(&batches[1..]).unwrap(), 1);
+        assert_eq!(hashes(&unsealed.take().unwrap()), hashes(&[batch(1)]));
+        assert!(unsealed.take().unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_torn_batch_is_dropped() {
+        let path = temp_path("torn");
+        let unsealed = UnsealedBatches::new(&path);
+        unsealed.write(&[batch(0), batch(1)]).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        assert_eq!(hashes(&unsealed.take().unwrap()), hashes(&[batch(0)]));
+        assert!(!path.exists());
+    }
+}
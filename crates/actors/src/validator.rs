@@ -1,5 +1,8 @@
 use crate::{
-    get_account, process_group_changed, ActorExt, Coerce, StaticFuture, UnorderedFuturePool,
+    check_balance, check_signer, check_valid_at, get_account, lifecycle, mailbox_handling,
+    mailbox_probed, process_group_changed, unix_now, ActorExt, Admission, AuthLimits, Coerce,
+    FeeSchedule, InputError, InputLimits, LifecycleStage, PolicyGate, StaticFuture,
+    UnorderedFuturePool,
 };
 use async_trait::async_trait;
 use futures::{
@@ -7,13 +10,14 @@ use futures::{
     FutureExt,
 };
 use lasr_messages::{
-    ActorName, ActorType, BatcherMessage, PendingTransactionMessage, SupervisorType,
-    ValidatorMessage,
+    ActorName, ActorType, BatcherMessage, EngineError, LasrError, PendingTransactionMessage,
+    RpcErrorCode, SupervisorType, ValidatorMessage,
 };
 use ractor::{Actor, ActorCell, ActorProcessingErr, ActorRef, MessagingErr, SupervisionEvent};
 use std::{collections::HashMap, sync::Arc};
 use thiserror::Error;
 use tokio::sync::{mpsc::Sender, Mutex};
+use tracing::Instrument;
 
 use lasr_types::{
     Account, AccountType, AddressOrNamespace, Instruction, Outputs, TokenFieldValue,
@@ -29,9 +33,17 @@ pub enum ValidatorCoreError {
     },
 }
 
+impl From<ValidatorCoreError> for LasrError {
+    fn from(e: ValidatorCoreError) -> Self {
+        LasrError::Engine(EngineError::InvalidOutputs(e.to_string()))
+    }
+}
+
 #[derive(Debug)]
 pub struct ValidatorCore {
     pool: rayon::ThreadPool,
+    admission: Admission,
+    policy: PolicyGate,
 }
 
 impl Default for ValidatorCore {
@@ -41,11 +53,59 @@ impl Default for ValidatorCore {
             .build()
             .expect("failed to initialize rayon thread pool for validator core");
 
-        Self { pool }
+        Self {
+            pool,
+            admission: Admission::default(),
+            policy: PolicyGate::default(),
+        }
     }
 }
 
 impl ValidatorCore {
+    /// Holds transactions to `admission` instead of the default checks.
+    pub fn with_admission(mut self, admission: Admission) -> Self {
+        self.admission = admission;
+        self
+    }
+
+    /// Limits inputs with `input_limits` instead of the default limits.
+    pub fn with_input_limits(mut self, input_limits: InputLimits) -> Self {
+        self.admission = self.admission.with_input_limits(input_limits);
+        self
+    }
+
+    /// Holds transactions to `fees` instead of charging nothing.
+    pub fn with_fee_schedule(mut self, fees: FeeSchedule) -> Self {
+        self.admission = self.admission.with_fee_schedule(fees);
+        self
+    }
+
+    /// Runs auth programs within `auth_limits` instead of the defaults.
+    pub fn with_auth_limits(mut self, auth_limits: AuthLimits) -> Self {
+        self.admission = self.admission.with_auth_limits(auth_limits);
+        self
+    }
+
+    /// Holds transactions to the admission policy of `policy` as they are
+    /// applied, instead of one denying nothing.
+    pub fn with_admission_policy(mut self, policy: PolicyGate) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Checks the inputs of `tx` against the limits of the program it calls,
+    /// if that program is in `account_map`.
+    fn validate_inputs(
+        input_limits: &InputLimits,
+        tx: &Transaction,
+        account_map: &HashMap<AddressOrNamespace, Option<Account>>,
+    ) -> Result<(), InputError> {
+        let program = account_map
+            .get(&AddressOrNamespace::Address(tx.to()))
+            .and_then(Option::as_ref);
+        input_limits.check(tx, program)
+    }
+
     fn validate_bridge_in(
         &self,
     ) -> impl FnOnce(Transaction) -> Result<(), Box<dyn std::error::Error + Send>> {
@@ -76,7 +136,9 @@ impl ValidatorCore {
     fn validate_send(
         &self,
     ) -> impl FnOnce(Transaction, Account) -> Result<(), Box<dyn std::error::Error + Send>> {
-        |tx, account| {
+        let admission = self.admission;
+        let policy = self.policy.clone();
+        move |tx, mut account| {
             let pending_transactions: ActorRef<PendingTransactionMessage> =
                 ractor::registry::where_is(ActorType::PendingTransactions.to_string())
                     .ok_or(Box::new(ValidatorError::Custom(
@@ -84,25 +146,22 @@ impl ValidatorCore {
                     )) as Box<dyn std::error::Error + Send>)?
                     .into();
 
-            match (
-                account.validate_program_id(&tx.program_id()),
-                account.validate_balance(&tx.program_id(), tx.value()),
-                account.validate_nonce(tx.nonce()),
-                tx.verify_signature()
-                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>),
-            ) {
-                (Err(e), _, _, _) | (_, Err(e), _, _) | (_, _, Err(e), _) | (_, _, _, Err(e)) => {
-                    let error_string = e.to_string();
-                    let message = PendingTransactionMessage::Invalid {
-                        transaction: tx.clone(),
-                        e,
-                    };
-                    let _ = pending_transactions.cast(message);
-                    tracing::error!("{}", &error_string);
-                    return Err(Box::new(ValidatorError::Custom(error_string))
-                        as Box<dyn std::error::Error + Send>);
-                }
-                _ => {}
+            // What the send holds is its own to spend.
+            account.release_debit(&tx.hash_string());
+            if let Err(e) = policy
+                .check(&tx)
+                .and_then(|_| admission.check(&tx, Some(&account), None))
+                .and_then(|_| check_valid_at(&tx, unix_now()))
+            {
+                let error_string = e.to_string();
+                let message = PendingTransactionMessage::Invalid {
+                    transaction: tx.clone(),
+                    e: e.into(),
+                };
+                let _ = pending_transactions.cast(message);
+                tracing::error!("{}", &error_string);
+                return Err(Box::new(ValidatorError::Custom(error_string))
+                    as Box<dyn std::error::Error + Send>);
             }
 
             let batcher: ActorRef<BatcherMessage> =
@@ -131,7 +190,9 @@ impl ValidatorCore {
         Outputs,
         Transaction,
     ) -> Result<(), Box<dyn std::error::Error + Send>> {
-        |account_map, outputs, tx| {
+        let admission = self.admission;
+        let policy = self.policy.clone();
+        move |account_map, outputs, tx| {
             let pending_transactions: ActorRef<PendingTransactionMessage> =
                 ractor::registry::where_is(ActorType::PendingTransactions.to_string())
                     .ok_or(Box::new(ValidatorError::Custom(
@@ -140,11 +201,14 @@ impl ValidatorCore {
                     .into();
             tracing::warn!("attempting to validate call: {}", tx.hash_string());
 
-            if let Err(e) = tx.verify_signature() {
+            let sender = account_map
+                .get(&AddressOrNamespace::Address(tx.from()))
+                .and_then(Option::as_ref);
+            if let Err(e) = policy.check(&tx).and_then(|_| check_signer(&tx, sender)) {
                 let error_string = e.to_string();
                 let message = PendingTransactionMessage::Invalid {
                     transaction: tx.clone(),
-                    e: Box::new(e),
+                    e: e.into(),
                 };
                 let _ = pending_transactions.cast(message);
                 return Err(Box::new(ValidatorError::Custom(error_string))
@@ -152,6 +216,19 @@ impl ValidatorCore {
             }
 
             tracing::warn!("signature is valid");
+            if let Err(e) =
+                ValidatorCore::validate_inputs(admission.input_limits(), &tx, &account_map)
+            {
+                let error_string = e.to_string();
+                let message = PendingTransactionMessage::Invalid {
+                    transaction: tx.clone(),
+                    e: e.into(),
+                };
+                let _ = pending_transactions.cast(message);
+                return Err(Box::new(ValidatorError::Custom(error_string))
+                    as Box<dyn std::error::Error + Send>);
+            }
+
             tracing::warn!("acquiring caller from account map");
             let caller = match account_map.get(&AddressOrNamespace::Address(tx.from())) {
                 Some(Some(account)) => account,
@@ -160,10 +237,10 @@ impl ValidatorCore {
                         "unable to acquire `caller` account {}, does not exist",
                         tx.from().to_full_string()
                     );
-                    let err = Box::new(ValidatorError::Custom(error_string.clone()));
+                    let err = ValidatorError::Custom(error_string.clone());
                     let message = PendingTransactionMessage::Invalid {
                         transaction: tx.clone(),
-                        e: err,
+                        e: err.into(),
                     };
                     let _ = pending_transactions.cast(message);
                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -171,12 +248,17 @@ impl ValidatorCore {
                 }
             };
 
-            tracing::warn!("validating caller nonce");
-            if let Err(e) = caller.clone().validate_nonce(tx.nonce()) {
+            tracing::warn!("validating caller nonce, fee and window");
+            if let Err(e) = admission
+                .check_nonce(&tx, Some(caller))
+                .and_then(|_| check_valid_at(&tx, unix_now()))
+                .and_then(|_| admission.check_fee(&tx))
+                .and_then(|_| check_balance(&tx, Some(caller), admission.fees().charge(&tx)))
+            {
                 let error_string = e.to_string();
                 let message = PendingTransactionMessage::Invalid {
                     transaction: tx.clone(),
-                    e,
+                    e: e.into(),
                 };
                 let _ = pending_transactions.cast(message);
                 return Err(Box::new(ValidatorError::Custom(error_string))
@@ -241,7 +323,7 @@ impl ValidatorCore {
 
                     tracing::warn!(
                         "validating caller information: {:?}",
-                        caller.programs().get(token_address)
+                        caller.token(token_address)
                     );
                     // Check if the transferrer is the caller
                     if transfer_from.clone()
@@ -253,7 +335,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -269,7 +351,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -288,10 +370,10 @@ impl ValidatorCore {
                                 let error_string =
                                     "unable to acquire transferFrom account, does not exist"
                                         .to_string();
-                                let err = Box::new(ValidatorError::Custom(error_string.clone()));
+                                let err = ValidatorError::Custom(error_string.clone());
                                 let message = PendingTransactionMessage::Invalid {
                                     transaction: tx.clone(),
-                                    e: err,
+                                    e: err.into(),
                                 };
                                 let _ = pending_transactions.cast(message);
                                 return Err(Box::new(ValidatorError::Custom(error_string))
@@ -301,19 +383,15 @@ impl ValidatorCore {
 
                         // check that the account being debited indeed has the token
                         // we are debiting
-                        if transfer_from_account
-                            .programs()
-                            .get(token_address)
-                            .is_none()
-                        {
+                        if transfer_from_account.token(token_address).is_none() {
                             let error_string = format!(
                                 "unable to acquire token {} from account, does not exist",
                                 token_address
                             );
-                            let err = Box::new(ValidatorError::Custom(error_string.clone()));
+                            let err = ValidatorError::Custom(error_string.clone());
                             let message = PendingTransactionMessage::Invalid {
                                 transaction: tx.clone(),
-                                e: err,
+                                e: err.into(),
                             };
                             let _ = pending_transactions.cast(message);
                             return Err(Box::new(ValidatorError::Custom(error_string))
@@ -339,10 +417,10 @@ impl ValidatorCore {
                                     token_address.to_full_string(),
                                     e
                                 );
-                                let err = Box::new(ValidatorError::Custom(error_string.clone()));
+                                let err = ValidatorError::Custom(error_string.clone());
                                 let message = PendingTransactionMessage::Invalid {
                                     transaction: tx.clone(),
-                                    e: err,
+                                    e: err.into(),
                                 };
                                 let _ = pending_transactions.cast(message);
                                 return Err(Box::new(ValidatorError::Custom(error_string))
@@ -372,7 +450,7 @@ impl ValidatorCore {
                                                 let error_string = e.to_string();
                                                 let message = PendingTransactionMessage::Invalid {
                                                     transaction: tx.clone(),
-                                                    e,
+                                                    e: e.into(),
                                                 };
                                                 let _ = pending_transactions.cast(message);
                                                 return Err(Box::new(ValidatorError::Custom(
@@ -405,7 +483,7 @@ impl ValidatorCore {
                                         let error_string = e.to_string();
                                         let message = PendingTransactionMessage::Invalid {
                                             transaction: tx.clone(),
-                                            e,
+                                            e: e.into(),
                                         };
                                         let _ = pending_transactions.cast(message);
                                         return Err(Box::new(ValidatorError::Custom(error_string))
@@ -427,7 +505,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -457,7 +535,7 @@ impl ValidatorCore {
                                         let error_string = e.to_string();
                                         let message = PendingTransactionMessage::Invalid {
                                             transaction: tx.clone(),
-                                            e,
+                                            e: e.into(),
                                         };
                                         let _ = pending_transactions.cast(message);
                                         return Err(Box::new(ValidatorError::Custom(error_string))
@@ -488,7 +566,7 @@ impl ValidatorCore {
                             };
                             let message = PendingTransactionMessage::Invalid {
                                 transaction: tx.clone(),
-                                e: err,
+                                e: err.into(),
                             };
                             let _ = pending_transactions.cast(message);
                             return Err(Box::new(ValidatorError::Custom(
@@ -511,7 +589,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -527,7 +605,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -550,7 +628,7 @@ impl ValidatorCore {
                                     as Box<dyn std::error::Error + Send>;
                                 let message = PendingTransactionMessage::Invalid {
                                     transaction: tx.clone(),
-                                    e,
+                                    e: e.into(),
                                 };
                                 let _ = pending_transactions.cast(message);
                                 return Err(Box::new(ValidatorError::Custom(error_string))
@@ -560,7 +638,7 @@ impl ValidatorCore {
 
                         // check that the account being debited indeed has the token
                         // we are debiting
-                        if burn_from_account.programs().get(token_address).is_none() {
+                        if burn_from_account.token(token_address).is_none() {
                             let e = {
                                 Box::new(ValidatorError::Custom(
                                     "account being debited does not hold token".to_string(),
@@ -570,7 +648,7 @@ impl ValidatorCore {
                             let error_string = e.to_string();
                             let message = PendingTransactionMessage::Invalid {
                                 transaction: tx.clone(),
-                                e,
+                                e: e.into(),
                             };
                             let _ = pending_transactions.cast(message);
                             return Err(Box::new(ValidatorError::Custom(error_string))
@@ -584,7 +662,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -613,7 +691,7 @@ impl ValidatorCore {
                                         let error_string = e.to_string();
                                         let message = PendingTransactionMessage::Invalid {
                                             transaction: tx.clone(),
-                                            e,
+                                            e: e.into(),
                                         };
                                         let _ = pending_transactions.cast(message);
                                         return Err(Box::new(ValidatorError::Custom(error_string))
@@ -635,7 +713,7 @@ impl ValidatorCore {
                                     let error_string = e.to_string();
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e,
+                                        e: e.into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::Custom(error_string))
@@ -665,7 +743,7 @@ impl ValidatorCore {
                                         let error_string = e.to_string();
                                         let message = PendingTransactionMessage::Invalid {
                                             transaction: tx.clone(),
-                                            e,
+                                            e: e.into(),
                                         };
                                         let _ = pending_transactions.cast(message);
                                         return Err(Box::new(ValidatorError::Custom(error_string))
@@ -703,7 +781,7 @@ impl ValidatorCore {
                                                     let message =
                                                         PendingTransactionMessage::Invalid {
                                                             transaction: tx.clone(),
-                                                            e: err,
+                                                            e: err.into(),
                                                         };
                                                     let _ = pending_transactions.cast(message);
                                                     return Err(
@@ -724,7 +802,7 @@ impl ValidatorCore {
                                                 };
                                                 let message = PendingTransactionMessage::Invalid {
                                                     transaction: tx.clone(),
-                                                    e: err,
+                                                    e: err.into(),
                                                 };
                                                 let _ = pending_transactions.cast(message);
                                                 return Err(
@@ -741,17 +819,13 @@ impl ValidatorCore {
                                                 account_map.get(token_update.account())
                                             {
                                                 if acct.owner_address() != caller.owner_address() {
-                                                    let err = {
-                                                        Box::new(
-                                                            ValidatorError::Custom(
+                                                    let err = ValidatorError::Custom(
                                                                 "Allowances can only be updated by the account owner".to_string()
-                                                            )
-                                                        )
-                                                    };
+                                                            );
                                                     let message =
                                                         PendingTransactionMessage::Invalid {
                                                             transaction: tx.clone(),
-                                                            e: err,
+                                                            e: err.into(),
                                                         };
                                                     let _ = pending_transactions.cast(message);
                                                     return Err(
@@ -763,16 +837,12 @@ impl ValidatorCore {
                                                     );
                                                 }
                                             } else {
-                                                let err = {
-                                                    Box::new(
-                                                        ValidatorError::Custom(
+                                                let err = ValidatorError::Custom(
                                                             "Allowances can only be updated on accounts that exist".to_string()
-                                                        )
-                                                    )
-                                                };
+                                                        );
                                                 let message = PendingTransactionMessage::Invalid {
                                                     transaction: tx.clone(),
-                                                    e: err,
+                                                    e: err.into(),
                                                 };
                                                 let _ = pending_transactions.cast(message);
                                                 return Err(
@@ -789,17 +859,13 @@ impl ValidatorCore {
                                                 AddressOrNamespace::This => tx.to(),
                                                 AddressOrNamespace::Address(addr) => *addr,
                                                 AddressOrNamespace::Namespace(_namespace) => {
-                                                    let err = {
-                                                        Box::new(
-                                                            ValidatorError::Custom(
+                                                    let err = ValidatorError::Custom(
                                                                 "Namespaces not yet implemented for token updates".to_string()
-                                                            )
-                                                        )
-                                                    };
+                                                            );
                                                     let message =
                                                         PendingTransactionMessage::Invalid {
                                                             transaction: tx.clone(),
-                                                            e: err,
+                                                            e: err.into(),
                                                         };
                                                     let _ = pending_transactions.cast(message);
                                                     return Err(
@@ -817,7 +883,7 @@ impl ValidatorCore {
                                                 if account.owner_address() != caller.owner_address()
                                                 {
                                                     if let Some(program) =
-                                                        account.programs().get(&token_address)
+                                                        account.token(&token_address)
                                                     {
                                                         let approvals = program.approvals();
                                                         let program_approved =
@@ -834,7 +900,7 @@ impl ValidatorCore {
                                                                     )
                                                                 ) as Box<dyn std::error::Error + Send>
                                                             };
-                                                            let message = PendingTransactionMessage::Invalid { transaction: tx.clone(), e: err };
+                                                            let message = PendingTransactionMessage::Invalid { transaction: tx.clone(), e: err.into() };
                                                             let _ =
                                                                 pending_transactions.cast(message);
                                                             return Err(
@@ -871,9 +937,8 @@ impl ValidatorCore {
                                         };
                                         let message = PendingTransactionMessage::Invalid {
                                             transaction: tx.clone(),
-                                            e: Box::new(ValidatorError::ValidatorCoreError(
-                                                err_msg.clone(),
-                                            )),
+                                            e: ValidatorError::ValidatorCoreError(err_msg.clone())
+                                                .into(),
                                         };
                                         let _ = pending_transactions.cast(message);
                                         return Err(Box::new(ValidatorError::ValidatorCoreError(
@@ -888,9 +953,8 @@ impl ValidatorCore {
                                     };
                                     let message = PendingTransactionMessage::Invalid {
                                         transaction: tx.clone(),
-                                        e: Box::new(ValidatorError::ValidatorCoreError(
-                                            err_msg.clone(),
-                                        )),
+                                        e: ValidatorError::ValidatorCoreError(err_msg.clone())
+                                            .into(),
                                     };
                                     let _ = pending_transactions.cast(message);
                                     return Err(Box::new(ValidatorError::ValidatorCoreError(
@@ -908,7 +972,7 @@ impl ValidatorCore {
                         )) as Box<dyn std::error::Error + Send>;
                         let message = PendingTransactionMessage::Invalid {
                             transaction: tx.clone(),
-                            e: err,
+                            e: err.into(),
                         };
                         let _ = pending_transactions.cast(message);
                         return Err(Box::new(ValidatorError::Custom(
@@ -967,12 +1031,64 @@ pub enum ValidatorError {
     ValidatorCoreError(#[from] ValidatorCoreError),
 }
 
+impl ValidatorError {
+    /// The RPC error code a transaction failing validation is answered with.
+    pub fn code(&self) -> RpcErrorCode {
+        match self {
+            ValidatorError::ValidatorCoreError(_) => RpcErrorCode::ProgramReverted,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+impl From<ValidatorError> for LasrError {
+    fn from(e: ValidatorError) -> Self {
+        match e {
+            ValidatorError::ValidatorCoreError(e) => e.into(),
+            ValidatorError::RactorRegistryError => {
+                LasrError::Engine(EngineError::Unavailable(e.to_string()))
+            }
+            e => LasrError::Custom(e.to_string()),
+        }
+    }
+}
+
 impl ValidatorActor {
     pub fn new() -> Self {
         Self {
             future_pool: Arc::new(Mutex::new(FuturesUnordered::new())),
         }
     }
+
+    /// Asks the auth program the account of `transaction`'s sender names,
+    /// if it names one, whether it authorizes the transaction, and fails the
+    /// transaction with the pending transactions if not. Answers whether it
+    /// was authorized.
+    async fn authorize(
+        validator_core: &Arc<Mutex<ValidatorCore>>,
+        transaction: &Transaction,
+        sender: Option<&Account>,
+    ) -> Result<bool, ValidatorError> {
+        let admission = validator_core.lock().await.admission;
+        let Err(e) = admission
+            .authorize(transaction, sender, ActorType::Validator)
+            .await
+        else {
+            return Ok(true);
+        };
+        tracing::error!("{e}");
+        let pending_transactions: ActorRef<PendingTransactionMessage> =
+            ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+                .ok_or(ValidatorError::Custom(
+                    "unable to acquire pending transaction actor".to_string(),
+                ))?
+                .into();
+        pending_transactions.cast(PendingTransactionMessage::Invalid {
+            transaction: transaction.clone(),
+            e: e.into(),
+        })?;
+        Ok(false)
+    }
     async fn pending_transaction(
         validator_core: Arc<Mutex<ValidatorCore>>,
         transaction: Transaction,
@@ -985,7 +1101,12 @@ impl ValidatorActor {
         let transaction_type = transaction.transaction_type();
         let from_address = transaction.from();
         match transaction_type {
-            TransactionType::Send(_) => {
+            TransactionType::Send(_)
+            | TransactionType::Burn(_)
+            | TransactionType::BurnAmount { .. }
+            | TransactionType::BridgeOutTo { .. }
+            | TransactionType::Delegate(_)
+            | TransactionType::SetAccountMetadata(_) => {
                 tracing::info!("Received send transaction, checking account_cache for account {:?} from validator", &from_address);
                 let account =
                     if let Some(account) = get_account(from_address, ActorType::Validator).await {
@@ -1012,14 +1133,13 @@ impl ValidatorActor {
 
                     let message = PendingTransactionMessage::Invalid {
                         transaction,
-                        e: Box::new(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            "account does not exist",
-                        )) as Box<dyn std::error::Error + Send>,
+                        e: LasrError::Custom("account does not exist".to_string()),
                     };
 
                     actor.cast(message)?;
-                } else {
+                } else if ValidatorActor::authorize(&validator_core, &transaction, account.as_ref())
+                    .await?
+                {
                     tracing::info!("validating send transaction");
                     let state = validator_core.lock().await;
                     let op = state.validate_send();
@@ -1055,7 +1175,7 @@ impl ValidatorActor {
                 // for address
                 // naively validate
             }
-            TransactionType::RegisterProgram(_) => {
+            TransactionType::RegisterProgram(_) | TransactionType::Deploy { .. } => {
                 // get program
                 // build program
                 // validate sender sig
@@ -1134,6 +1254,12 @@ impl ValidatorActor {
                 }
             }
 
+            let caller = validator_accounts
+                .get(&AddressOrNamespace::Address(transaction.from()))
+                .and_then(Option::as_ref);
+            if !ValidatorActor::authorize(&validator_core, &transaction, caller).await? {
+                return Ok(());
+            }
             let state = validator_core.lock().await;
             let op = state.validate_call();
             state.pool.spawn_fifo(move || {
@@ -1145,12 +1271,12 @@ impl ValidatorActor {
                 ractor::registry::where_is(ActorType::PendingTransactions.to_string())
             {
                 let pending_transactions: ActorRef<PendingTransactionMessage> = actor.into();
-                let e = Box::new(ValidatorError::Custom(
+                let e = ValidatorError::Custom(
                     "Call transaction missing associated outputs".to_string(),
-                ));
+                );
                 let message = PendingTransactionMessage::Invalid {
                     transaction: transaction.clone(),
-                    e,
+                    e: e.into(),
                 };
                 let _ = pending_transactions.cast(message);
             }
@@ -1180,9 +1306,13 @@ impl Actor for ValidatorActor {
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
         let valcore_ptr = Arc::clone(state);
+        let _handling = mailbox_handling(ActorType::Validator);
         match message {
+            ValidatorMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Validator, probe),
             ValidatorMessage::PendingTransaction { transaction } => {
-                let fut = ValidatorActor::pending_transaction(valcore_ptr, transaction);
+                let span = lifecycle::stage(LifecycleStage::Validator, &transaction.hash_string());
+                let fut =
+                    ValidatorActor::pending_transaction(valcore_ptr, transaction).instrument(span);
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
@@ -1190,7 +1320,9 @@ impl Actor for ValidatorActor {
                 outputs,
                 transaction,
             } => {
-                let fut = ValidatorActor::pending_call(valcore_ptr, outputs, transaction);
+                let span = lifecycle::stage(LifecycleStage::Validator, &transaction.hash_string());
+                let fut = ValidatorActor::pending_call(valcore_ptr, outputs, transaction)
+                    .instrument(span);
                 let guard = self.future_pool.lock().await;
                 guard.push(fut.boxed());
             }
@@ -1303,13 +1435,69 @@ impl Actor for ValidatorSupervisor {
 
 #[cfg(test)]
 mod validator_tests {
-    use crate::{ActorExt, ValidatorActor, ValidatorCore};
+    use crate::{
+        ActorExt, InputError, InputLimits, ValidatorActor, ValidatorCore, MAX_INPUT_BYTES_KEY,
+    };
     use lasr_messages::{ActorType, ValidatorMessage};
-    use lasr_types::Transaction;
+    use lasr_types::{
+        AccountBuilder, AccountType, Address, AddressOrNamespace, ArbitraryData, Metadata,
+        PayloadBuilder, Transaction, TransactionType, U256,
+    };
     use ractor::Actor;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
     use std::sync::Arc;
     use tokio::sync::Mutex;
 
+    fn call(program_id: Address, inputs: String) -> Transaction {
+        PayloadBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(0)))
+            .from([1; 20])
+            .to(program_id.into())
+            .program_id(program_id.into())
+            .inputs(inputs)
+            .op(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(0))
+            .build()
+            .expect("failed to build payload")
+            .into()
+    }
+
+    #[test]
+    fn call_inputs_are_held_to_the_program_limit() {
+        let limits = InputLimits::new(8, 64);
+        let program_id = Address::new([3; 20]);
+        let mut metadata = Metadata::new();
+        metadata
+            .inner_mut()
+            .insert(MAX_INPUT_BYTES_KEY.to_string(), "16".to_string());
+        let program = AccountBuilder::default()
+            .account_type(AccountType::Program(program_id))
+            .owner_address(Address::new([1; 20]))
+            .nonce(U256::from(0))
+            .programs(BTreeMap::new())
+            .program_namespace(None)
+            .program_account_linked_programs(BTreeSet::new())
+            .program_account_data(ArbitraryData::new())
+            .program_account_metadata(metadata)
+            .try_build()
+            .expect("failed to build program account");
+        let account_map = HashMap::from([(AddressOrNamespace::Address(program_id), Some(program))]);
+
+        let at_limit = call(program_id, "a".repeat(16));
+        assert!(ValidatorCore::validate_inputs(&limits, &at_limit, &account_map).is_ok());
+        let over_limit = call(program_id, "a".repeat(17));
+        assert_eq!(
+            ValidatorCore::validate_inputs(&limits, &over_limit, &account_map),
+            Err(InputError::TooLarge {
+                size: 17,
+                limit: 16
+            })
+        );
+        // Without the program account only the default limit applies.
+        assert!(ValidatorCore::validate_inputs(&limits, &at_limit, &HashMap::new()).is_err());
+    }
+
     #[tokio::test]
     async fn test_validator_future_handler() {
         let validator_actor = ValidatorActor::new();
@@ -0,0 +1,339 @@
+//! Checks that program execution is deterministic by running a sample of
+//! transactions a second time and comparing the outputs.
+//!
+//! The second run happens in the background, in its own slot in the
+//! execution pool, after the first run's outputs have already been handed
+//! on. It never changes what is applied: the first outputs are always the
+//! ones used, and a second run that disagrees is only recorded, with the
+//! full inputs, so it can be reproduced offline.
+use crate::{unix_now, ExecutionPool};
+use lasr_compute::ExecutionError;
+use lasr_messages::ExecutionMismatch;
+use lasr_types::Inputs;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Number of mismatches kept. The oldest are dropped first.
+const MAX_RECORDED_MISMATCHES: usize = 256;
+/// The percentage of executions run again by default.
+pub const DEFAULT_VERIFICATION_PERCENT: f64 = 1.0;
+
+#[derive(Debug, Default)]
+pub struct VerificationMetrics {
+    sampled: AtomicU64,
+    matched: AtomicU64,
+    mismatched: AtomicU64,
+    failed: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl VerificationMetrics {
+    /// Executions picked to be run a second time.
+    pub fn sampled(&self) -> u64 {
+        self.sampled.load(Ordering::Relaxed)
+    }
+
+    pub fn matched(&self) -> u64 {
+        self.matched.load(Ordering::Relaxed)
+    }
+
+    pub fn mismatched(&self) -> u64 {
+        self.mismatched.load(Ordering::Relaxed)
+    }
+
+    /// Second runs that failed where the first succeeded.
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Sampled executions that were not run again because the execution pool
+    /// was full.
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+}
+
+/// The execution a second run is compared against.
+#[derive(Debug, Clone)]
+pub struct VerifiedExecution {
+    pub transaction_hash: String,
+    pub program_id: String,
+    pub content_id: String,
+    pub inputs: Inputs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationOutcome {
+    Matched,
+    Mismatched,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutionVerifier {
+    sample_percent: f64,
+    mismatches: Arc<Mutex<VecDeque<ExecutionMismatch>>>,
+    metrics: Arc<VerificationMetrics>,
+}
+
+impl Default for ExecutionVerifier {
+    fn default() -> Self {
+        Self::new(DEFAULT_VERIFICATION_PERCENT)
+    }
+}
+
+impl ExecutionVerifier {
+    /// Verifies `sample_percent` percent of executions, from 0 to 100.
+    pub fn new(sample_percent: f64) -> Self {
+        Self {
+            sample_percent: sample_percent.clamp(0.0, 100.0),
+            mismatches: Arc::new(Mutex::new(VecDeque::new())),
+            metrics: Arc::new(VerificationMetrics::default()),
+        }
+    }
+
+    pub fn sample_percent(&self) -> f64 {
+        self.sample_percent
+    }
+
+    pub fn metrics(&self) -> Arc<VerificationMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Mismatches found so far, oldest first.
+    pub fn mismatches(&self) -> Vec<ExecutionMismatch> {
+        self.mismatches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Whether the execution of `transaction_hash` should be run again. The
+    /// choice depends only on the hash, so every node samples the same
+    /// transactions.
+    pub fn should_verify(&self, transaction_hash: &str) -> bool {
+        if self.sample_percent <= 0.0 {
+            return false;
+        }
+        if self.sample_percent >= 100.0 {
+            return true;
+        }
+        // FNV-1a, stable across builds unlike the std hasher.
+        let hash = transaction_hash
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        ((hash % 10_000) as f64) < self.sample_percent * 100.0
+    }
+
+    /// Runs `rerun` in its own slot in `pool` and compares what it produces
+    /// with `first_output`. Returns without waiting for it.
+    pub fn reexecute<F>(
+        &self,
+        pool: &ExecutionPool,
+        execution: VerifiedExecution,
+        first_output: String,
+        rerun: F,
+    ) where
+        F: Future<Output = Result<String, ExecutionError>> + Send + 'static,
+    {
+        self.metrics.sampled.fetch_add(1, Ordering::Relaxed);
+        let verifier = self.clone();
+        if let Err(e) = pool.submit(async move {
+            verifier.verify(execution, &first_output, rerun).await;
+        }) {
+            self.metrics.skipped.fetch_add(1, Ordering::Relaxed);
+            tracing::debug!("skipping re-execution: {e}");
+        }
+    }
+
+    /// Waits for `rerun` and compares what it produces with `first_output`,
+    /// recording a mismatch if they differ.
+    pub async fn verify(
+        &self,
+        execution: VerifiedExecution,
+        first_output: &str,
+        rerun: impl Future<Output = Result<String, ExecutionError>>,
+    ) -> VerificationOutcome {
+        let second_output = match rerun.await {
+            Ok(output) => output,
+            Err(e) => {
+                self.metrics.failed.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    "re-execution of transaction {} failed where the first run succeeded: {e}",
+                    &execution.transaction_hash
+                );
+                return VerificationOutcome::Failed;
+            }
+        };
+
+        if canonicalize(first_output) == canonicalize(&second_output) {
+            self.metrics.matched.fetch_add(1, Ordering::Relaxed);
+            return VerificationOutcome::Matched;
+        }
+
+        self.metrics.mismatched.fetch_add(1, Ordering::Relaxed);
+        let mismatch = ExecutionMismatch {
+            transaction_hash: execution.transaction_hash,
+            program_id: execution.program_id,
+            content_id: execution.content_id,
+            inputs: execution.inputs,
+            first_output: first_output.to_string(),
+            second_output,
+            detected_at: unix_now(),
+        };
+        tracing::error!(
+            "nondeterministic execution of program {} in transaction {}: {}",
+            &mismatch.program_id,
+            &mismatch.transaction_hash,
+            serde_json::to_string(&mismatch).unwrap_or_default()
+        );
+
+        let mut mismatches = self.mismatches.lock().unwrap_or_else(|e| e.into_inner());
+        mismatches.push_back(mismatch);
+        while mismatches.len() > MAX_RECORDED_MISMATCHES {
+            mismatches.pop_front();
+        }
+        VerificationOutcome::Mismatched
+    }
+}
+
+/// Outputs as JSON with every object's keys sorted and no whitespace, so
+/// outputs that differ only in formatting compare equal. Anything that is not
+/// JSON is only trimmed.
+fn canonicalize(output: &str) -> String {
+    fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, sort_keys(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(sort_keys).collect())
+            }
+            value => value,
+        }
+    }
+
+    match serde_json::from_str::<serde_json::Value>(output) {
+        Ok(value) => sort_keys(value).to_string(),
+        Err(_) => output.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod verification_tests {
+    use super::*;
+    use lasr_compute::{run_with_limits, ExecutionLimits};
+    use tokio::process::Command;
+
+    fn execution() -> VerifiedExecution {
+        VerifiedExecution {
+            transaction_hash: "0xabcdef".to_string(),
+            program_id: "0x0303030303030303030303030303030303030303".to_string(),
+            content_id: "cid".to_string(),
+            inputs: Inputs {
+                op: "roll".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn run(script: &str) -> Result<String, ExecutionError> {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        let output = run_with_limits(command, b"{}", &ExecutionLimits::default(), None).await?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    #[tokio::test]
+    async fn nondeterministic_program_is_caught() {
+        // Every run is a new process with a new pid.
+        let program = r#"echo "{\"roll\":$$}""#;
+        let verifier = ExecutionVerifier::new(100.0);
+        assert!(verifier.should_verify("0xabcdef"));
+
+        let first = run(program).await.unwrap();
+        let outcome = verifier.verify(execution(), &first, run(program)).await;
+
+        assert_eq!(outcome, VerificationOutcome::Mismatched);
+        assert_eq!(verifier.metrics().mismatched(), 1);
+        let mismatches = verifier.mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].transaction_hash, "0xabcdef");
+        assert_eq!(mismatches[0].inputs.op, "roll");
+        assert_eq!(mismatches[0].first_output, first);
+        assert_ne!(mismatches[0].second_output, first);
+    }
+
+    #[tokio::test]
+    async fn deterministic_program_matches_regardless_of_formatting() {
+        let verifier = ExecutionVerifier::new(100.0);
+        let first = run(r#"echo '{"a": 1, "b": [{"c": 2, "d": 3}]}'"#)
+            .await
+            .unwrap();
+        let outcome = verifier
+            .verify(
+                execution(),
+                &first,
+                run(r#"echo '{"b":[{"d":3,"c":2}],"a":1}'"#),
+            )
+            .await;
+
+        assert_eq!(outcome, VerificationOutcome::Matched);
+        assert!(verifier.mismatches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_second_run_is_not_a_mismatch() {
+        let verifier = ExecutionVerifier::new(100.0);
+        let outcome = verifier.verify(execution(), "{}", run("exit 1")).await;
+
+        assert_eq!(outcome, VerificationOutcome::Failed);
+        assert_eq!(verifier.metrics().failed(), 1);
+        assert!(verifier.mismatches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reexecution_runs_in_the_pool() {
+        let verifier = ExecutionVerifier::new(100.0);
+        let pool = ExecutionPool::new(1, 4);
+        verifier.reexecute(&pool, execution(), "{}".to_string(), async {
+            Ok("{\"changed\":true}".to_string())
+        });
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while verifier.metrics().mismatched() == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("re-execution never finished");
+        assert_eq!(verifier.metrics().sampled(), 1);
+    }
+
+    #[test]
+    fn sampling_follows_the_configured_percentage() {
+        let hashes: Vec<String> = (0..10_000).map(|i| format!("0x{i:064x}")).collect();
+        let sampled = |percent: f64| {
+            let verifier = ExecutionVerifier::new(percent);
+            hashes.iter().filter(|h| verifier.should_verify(h)).count()
+        };
+
+        assert_eq!(sampled(0.0), 0);
+        assert_eq!(sampled(100.0), hashes.len());
+        let ten_percent = sampled(10.0);
+        assert!((800..1200).contains(&ten_percent), "{ten_percent}");
+    }
+}
@@ -0,0 +1,127 @@
+//! Supply accounting for VERSE, the native token.
+//!
+//! VERSE has no deployed program, so its program account is a system account
+//! whose only job is to record the total supply. Supply grows only when VERSE
+//! is bridged in and shrinks only through a `Burn` transaction; the batcher
+//! keeps the supply in the same batch buffer as the balances it moves, so both
+//! are published together.
+use std::collections::{BTreeMap, BTreeSet};
+
+use lasr_types::{Account, AccountBuilder, AccountType, ArbitraryData, Metadata, U256};
+use thiserror::Error;
+
+use crate::VERSE_ADDR;
+
+/// Metadata key on the VERSE program account holding the total supply.
+pub const VERSE_TOTAL_SUPPLY_KEY: &str = "total_supply";
+
+#[derive(Debug, Clone, Error)]
+pub enum VerseSupplyError {
+    #[error("VERSE supply {supply} does not match the {circulating} held by accounts")]
+    Mismatch { supply: U256, circulating: U256 },
+
+    #[error("VERSE supply overflow")]
+    Overflow,
+
+    #[error("cannot burn {amount} VERSE, total supply is {supply}")]
+    InsufficientSupply { amount: U256, supply: U256 },
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// Builds an empty VERSE program account, used the first time VERSE is minted.
+pub fn new_verse_program_account() -> Result<Account, VerseSupplyError> {
+    let mut metadata = Metadata::new();
+    metadata.insert(
+        VERSE_TOTAL_SUPPLY_KEY.to_string(),
+        format!("0x{:064x}", U256::from(0)),
+    );
+    AccountBuilder::default()
+        .account_type(AccountType::Program(VERSE_ADDR))
+        .program_namespace(None)
+        .owner_address(VERSE_ADDR)
+        .programs(BTreeMap::new())
+        .nonce(U256::from(0))
+        .program_account_data(ArbitraryData::new())
+        .program_account_metadata(metadata)
+        .program_account_linked_programs(BTreeSet::new())
+        .try_build()
+        .map_err(|e| VerseSupplyError::Custom(e.to_string()))
+}
+
+pub fn is_verse_program_account(account: &Account) -> bool {
+    account.account_type() == AccountType::Program(VERSE_ADDR)
+}
+
+/// Reads the total supply recorded on the VERSE program account.
+pub fn verse_total_supply(program_account: &Account) -> Result<U256, VerseSupplyError> {
+    match program_account
+        .program_account_metadata()
+        .get(VERSE_TOTAL_SUPPLY_KEY)
+    {
+        Some(supply) => U256::from_str_radix(supply.trim_start_matches("0x"), 16)
+            .map_err(|e| VerseSupplyError::Custom(format!("invalid VERSE supply: {e:?}"))),
+        None => Ok(U256::from(0)),
+    }
+}
+
+fn set_verse_total_supply(program_account: &mut Account, supply: U256) {
+    program_account.program_account_metadat_mut().insert(
+        VERSE_TOTAL_SUPPLY_KEY.to_string(),
+        format!("0x{:064x}", supply),
+    );
+}
+
+/// Adds newly minted VERSE to the recorded supply.
+pub fn mint_verse_supply(
+    program_account: &mut Account,
+    amount: U256,
+) -> Result<U256, VerseSupplyError> {
+    let supply = verse_total_supply(program_account)?
+        .checked_add(amount)
+        .ok_or(VerseSupplyError::Overflow)?;
+    set_verse_total_supply(program_account, supply);
+    Ok(supply)
+}
+
+/// Removes burned VERSE from the recorded supply.
+pub fn burn_verse_supply(
+    program_account: &mut Account,
+    amount: U256,
+) -> Result<U256, VerseSupplyError> {
+    let supply = verse_total_supply(program_account)?;
+    let remaining = supply
+        .checked_sub(amount)
+        .ok_or(VerseSupplyError::InsufficientSupply { amount, supply })?;
+    set_verse_total_supply(program_account, remaining);
+    Ok(remaining)
+}
+
+/// Checks that the VERSE held by `accounts` adds up to the recorded supply,
+/// returning the supply when it does. A missing VERSE program account means
+/// nothing has been minted yet.
+pub fn check_verse_supply_invariant<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+) -> Result<U256, VerseSupplyError> {
+    let mut supply = U256::from(0);
+    let mut circulating = U256::from(0);
+    for account in accounts {
+        if is_verse_program_account(account) {
+            supply = verse_total_supply(account)?;
+            continue;
+        }
+        circulating = circulating
+            .checked_add(account.balance(&VERSE_ADDR))
+            .ok_or(VerseSupplyError::Overflow)?;
+    }
+
+    if supply != circulating {
+        return Err(VerseSupplyError::Mismatch {
+            supply,
+            circulating,
+        });
+    }
+
+    Ok(supply)
+}
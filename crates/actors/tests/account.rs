@@ -8,19 +8,22 @@ use std::{
 };
 
 use lasr_actors::{
-    get_account, get_actor_ref, AccountCacheActor, AccountCacheError, Batcher, BatcherActor,
-    PendingTransactionActor, TaskScheduler, ETH_ADDR,
+    get_account, get_actor_ref, get_receipt, validate_outputs, verse_total_supply,
+    AccountCacheActor, AccountCacheError, Batcher, BatcherActor, FeeSchedule, OutputLimits,
+    PendingTransactionActor, TaskScheduler, ETH_ADDR, VERSE_ADDR,
 };
+use lasr_compute::{ExecutionLimits, OciBundlerBuilder, WasmRuntime, PROGRAM_RUNTIME_KEY};
 use lasr_messages::{
     AccountCacheMessage, ActorName, ActorType, BatcherMessage, PendingTransactionMessage,
-    SchedulerMessage,
+    RpcMessage, SchedulerMessage, TransactionResponse,
 };
 use lasr_types::{
     Account, AccountBuilder, AccountType, Address, AddressOrNamespace, ArbitraryData,
-    BurnInstructionBuilder, CreateInstructionBuilder, Inputs, Metadata, MockPersistenceStore,
-    Namespace, OutputsBuilder, PayloadBuilder, PersistenceStore, RecoverableSignature, Status,
-    TokenBuilder, TokenDistributionBuilder, TokenUpdateBuilder, TokenUpdateField, Transaction,
-    TransactionType, TransferInstructionBuilder, UpdateInstructionBuilder, U256,
+    BurnInstructionBuilder, CreateInstructionBuilder, DeployRuntime, Inputs, Metadata,
+    MockPersistenceStore, Namespace, OutputsBuilder, PayloadBuilder, PersistenceStore,
+    RecoverableSignature, Status, TokenBuilder, TokenDistributionBuilder, TokenUpdateBuilder,
+    TokenUpdateField, Transaction, TransactionType, TransferInstructionBuilder,
+    UpdateInstructionBuilder, U256,
 };
 
 use eigenda_client::proof::BlobVerificationProof;
@@ -35,12 +38,7 @@ use tokio::sync::{mpsc, Mutex};
 /// This is an account with nothing in it, with `Address([1; 20])`.
 pub fn test_default_user_account() -> Account {
     const RECEIVER_ADDRESS: [u8; 20] = [1; 20];
-    Account::new(
-        AccountType::User,
-        None,
-        Address::new(RECEIVER_ADDRESS),
-        None,
-    )
+    Account::new(Address::new(RECEIVER_ADDRESS))
 }
 
 pub fn receiver_test_account() -> Account {
@@ -86,7 +84,7 @@ pub fn sender_test_account_pair() -> (Account, Account) {
             .program_account_data(ArbitraryData::new())
             .program_account_metadata(Metadata::new())
             .program_account_linked_programs(test_program_set)
-            .build()
+            .try_build()
             .expect("failed to build test user account"),
         AccountBuilder::default()
             .account_type(AccountType::Program(program_address))
@@ -97,7 +95,7 @@ pub fn sender_test_account_pair() -> (Account, Account) {
             .program_account_data(ArbitraryData::new())
             .program_account_metadata(Metadata::new())
             .program_account_linked_programs(BTreeSet::new())
-            .build()
+            .try_build()
             .expect("failed to build test program account"),
     )
 }
@@ -144,6 +142,45 @@ fn test_send(
         .expect("failed to build payload")
         .into()
 }
+/// A test send transaction offering fees.
+fn test_send_with_fees(
+    amount: u64,
+    from: Address,
+    nonce: crate::U256,
+    to: Address,
+    max_fee: u64,
+    priority_fee: u64,
+) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(nonce))
+        .from(from.into())
+        .to(to.into())
+        .program_id(VERSE_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(crate::U256::from(amount))
+        .nonce(nonce)
+        .max_fee(Some(crate::U256::from(max_fee)))
+        .priority_fee(Some(crate::U256::from(priority_fee)))
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
+/// A test VERSE burn transaction.
+fn test_burn(amount: u64, from: Address, nonce: crate::U256) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Burn(nonce))
+        .from(from.into())
+        .to(from.into())
+        .program_id(VERSE_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(crate::U256::from(amount))
+        .nonce(nonce)
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
 fn test_register_program(nonce: crate::U256, from: Address, program_id: Address) -> Transaction {
     let payload = PayloadBuilder::default()
         .transaction_type(TransactionType::RegisterProgram(nonce))
@@ -182,6 +219,53 @@ fn test_call(nonce: crate::U256, from: Address, to: Address, program_id: Address
         .into()
 }
 
+/// A test deploy of the package at `content_id`, signed by a key of its own.
+fn test_deploy(nonce: crate::U256, from: Address, content_id: &str) -> Transaction {
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Deploy {
+            nonce,
+            content_id: content_id.to_string(),
+            runtime: DeployRuntime::Wasm,
+        })
+        .from(from.into())
+        .to([0; 20])
+        .program_id([0; 20])
+        .inputs(String::new())
+        .op(String::new())
+        .value(crate::U256::from(0))
+        .nonce(nonce)
+        .build()
+        .expect("failed to build payload");
+
+    let msg = secp256k1::Message::from_digest(payload.digest());
+    let secp = secp256k1::Secp256k1::new();
+    let keypair = secp.generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+    let sig: RecoverableSignature = secp.sign_ecdsa_recoverable(&msg, &keypair.0).into();
+
+    (payload, sig).into()
+}
+/// A test transaction of `transaction_type` taking `amount` of VERSE from
+/// `from`.
+fn test_debit(
+    transaction_type: TransactionType,
+    amount: u64,
+    from: Address,
+    nonce: crate::U256,
+) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(transaction_type)
+        .from(from.into())
+        .to(from.into())
+        .program_id(VERSE_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(crate::U256::from(amount))
+        .nonce(nonce)
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
+
 /// Everything needed to run minimal tests for a node. This excludes most things
 /// that use JRPC, IPFS, and compute related components.
 pub struct MinimalNode {
@@ -216,10 +300,17 @@ impl MinimalNode {
     /// }
     /// ```
     async fn new() -> anyhow::Result<Self> {
+        MinimalNode::charging(FeeSchedule::default()).await
+    }
+
+    /// Create a new `MinimalNode` whose batcher charges `fees`.
+    async fn charging(fees: FeeSchedule) -> anyhow::Result<Self> {
         // Channel is not used, but is necessary to construct a `Batcher` and cannot be zero.
         const CHANNEL_BUFFER: usize = 1;
         let (receivers_thread_tx, _batcher_rx) = mpsc::channel(CHANNEL_BUFFER);
-        let batcher = Arc::new(Mutex::new(Batcher::new(receivers_thread_tx)));
+        let batcher = Arc::new(Mutex::new(
+            Batcher::new(receivers_thread_tx).with_fee_schedule(fees),
+        ));
         let batcher_actor = BatcherActor::new();
         let batcher_actor =
             Actor::spawn(Some(batcher_actor.name()), batcher_actor, batcher.clone()).await?;
@@ -930,3 +1021,842 @@ async fn call_burn_event() {
         .await
         .unwrap();
 }
+
+/// Puts each account into storage and the account cache, keyed the same way
+/// the batcher keys them.
+async fn seed_accounts(
+    storage: &MockPersistenceStore<String, Vec<u8>>,
+    accounts: &[Account],
+    location: &str,
+) {
+    let account_cache =
+        get_actor_ref::<AccountCacheMessage, AccountCacheError>(ActorType::AccountCache)
+            .expect("failed to acquire account cache");
+    for account in accounts {
+        let key = match account.account_type() {
+            AccountType::Program(program_address) => program_address.to_full_string(),
+            AccountType::User => account.owner_address().to_full_string(),
+        };
+        assert!(
+            <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::put(
+                storage,
+                key,
+                bincode::serialize(account).expect("failed serialization of account")
+            )
+            .await
+            .is_ok()
+        );
+        assert!(account_cache
+            .send_message(AccountCacheMessage::Write {
+                account: account.clone(),
+                who: ActorType::AccountCache,
+                location: location.into(),
+            })
+            .is_ok());
+    }
+}
+
+#[serial]
+#[tokio::test]
+async fn send_event_failed_credit_leaves_no_partial_state() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            // The program account is deliberately left out, so the debit
+            // succeeds but the credit to the receiver fails.
+            let (from_account, from_program_account) = sender_test_account_pair();
+            let from_account_address = from_account.owner_address();
+            let AccountType::Program(from_program_address) = from_program_account.account_type()
+            else {
+                panic!("from_program_account is not a program account.")
+            };
+            let to_account = receiver_test_account();
+            let to_account_address = to_account.owner_address();
+            seed_accounts(
+                &node.mock_storage,
+                &[from_account.clone(), to_account.clone()],
+                "send_event_failed_credit_leaves_no_partial_state test",
+            )
+            .await;
+
+            const TRANSFER_AMOUNT: u64 = 1;
+            let send_transaction = test_send(
+                TRANSFER_AMOUNT,
+                from_account_address,
+                from_account.nonce(),
+                from_program_address,
+                to_account_address,
+            );
+            let res =
+                Batcher::add_transaction_to_account(node.batcher.clone(), send_transaction).await;
+
+            let from_account_after = get_account(from_account_address, ActorType::AccountCache)
+                .await
+                .expect("could not find from account");
+            let to_account_after = get_account(to_account_address, ActorType::AccountCache)
+                .await
+                .expect("could not find to account");
+
+            assert!(res.is_err());
+            assert_eq!(from_account_after, from_account);
+            assert_eq!(to_account_after, to_account);
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn call_event_failed_instruction_leaves_no_partial_state() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            let (from_account, from_program_account) = sender_test_account_pair();
+            let from_account_address = from_account.owner_address();
+            let AccountType::Program(from_program_address) = from_program_account.account_type()
+            else {
+                panic!("from_program_account is not a program account.")
+            };
+            let to_account = receiver_test_account();
+            let to_account_address = to_account.owner_address();
+            seed_accounts(
+                &node.mock_storage,
+                &[
+                    from_account.clone(),
+                    from_program_account.clone(),
+                    to_account.clone(),
+                ],
+                "call_event_failed_instruction_leaves_no_partial_state test",
+            )
+            .await;
+
+            const TRANSFER_AMOUNT: u64 = 1;
+            let transfer = || {
+                lasr_types::Instruction::Transfer(
+                    TransferInstructionBuilder::new()
+                        .token(from_program_address)
+                        .from(AddressOrNamespace::Address(from_account_address))
+                        .to(AddressOrNamespace::Address(to_account_address))
+                        .amount(U256::from(TRANSFER_AMOUNT))
+                        .build()
+                        .expect("failed to build transfer instruction"),
+                )
+            };
+            // Burning more than the sender holds always fails.
+            let over_burn = || {
+                lasr_types::Instruction::Burn(
+                    BurnInstructionBuilder::new()
+                        .caller(from_account_address)
+                        .program_id(AddressOrNamespace::Address(from_program_address))
+                        .token(from_program_address)
+                        .from(AddressOrNamespace::Address(from_account_address))
+                        .amount(from_account.balance(&from_program_address) + U256::from(1))
+                        .build()
+                        .expect("failed to build burn instruction"),
+                )
+            };
+
+            // Inject the failure first, in the middle and last.
+            const INSTRUCTION_COUNT: usize = 3;
+            for failing_index in 0..INSTRUCTION_COUNT {
+                let instructions = (0..INSTRUCTION_COUNT)
+                    .map(|i| {
+                        if i == failing_index {
+                            over_burn()
+                        } else {
+                            transfer()
+                        }
+                    })
+                    .collect();
+                let outputs = OutputsBuilder::new()
+                    .extend_instructions(instructions)
+                    .inputs(Inputs::default())
+                    .build()
+                    .expect("failed to build outputs");
+                let call_transaction = test_call(
+                    from_account.nonce(),
+                    from_account_address,
+                    to_account_address,
+                    from_program_address,
+                );
+
+                let res = Batcher::apply_instructions_to_accounts(
+                    node.batcher.clone(),
+                    call_transaction,
+                    outputs,
+                )
+                .await;
+
+                let from_account_after = get_account(from_account_address, ActorType::AccountCache)
+                    .await
+                    .expect("could not find from account");
+                let to_account_after = get_account(to_account_address, ActorType::AccountCache)
+                    .await
+                    .expect("could not find to account");
+
+                assert!(res.is_err(), "instruction {failing_index} should fail");
+                assert_eq!(from_account_after, from_account);
+                assert_eq!(to_account_after, to_account);
+            }
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn verse_mint_and_burn_event() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            let account = receiver_test_account();
+            let address = account.owner_address();
+            seed_accounts(
+                &node.mock_storage,
+                &[account.clone()],
+                "verse_mint_and_burn_event test",
+            )
+            .await;
+
+            const MINT_AMOUNT: u64 = 10;
+            const BURN_AMOUNT: u64 = 4;
+            let res = Batcher::add_transaction_to_account(
+                node.batcher.clone(),
+                test_bridge_in(MINT_AMOUNT, account.nonce(), VERSE_ADDR, address),
+            )
+            .await;
+            assert!(res.is_ok());
+
+            let minted = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(minted.balance(&VERSE_ADDR), U256::from(MINT_AMOUNT));
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT)
+            );
+
+            let res = Batcher::apply_verse_burn(
+                node.batcher.clone(),
+                test_burn(BURN_AMOUNT, address, minted.nonce()),
+            )
+            .await;
+            assert!(res.is_ok());
+
+            let burned = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(
+                burned.balance(&VERSE_ADDR),
+                U256::from(MINT_AMOUNT - BURN_AMOUNT)
+            );
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT - BURN_AMOUNT)
+            );
+
+            // Burning more than the holder has is rejected and changes nothing.
+            let res = Batcher::apply_verse_burn(
+                node.batcher.clone(),
+                test_burn(MINT_AMOUNT, address, burned.nonce()),
+            )
+            .await;
+            assert!(res.is_err());
+
+            let after_over_burn = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(after_over_burn, burned);
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT - BURN_AMOUNT)
+            );
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn fees_are_burned_from_the_sender_when_applied() {
+    // 10 for each transaction, with the send's priority fee of 5 on top.
+    MinimalNode::charging(FeeSchedule::new(10, 0, 0, 0))
+        .and_then(|node| async move {
+            let account = receiver_test_account();
+            let address = account.owner_address();
+            let receiver = Address::new([7; 20]);
+            seed_accounts(
+                &node.mock_storage,
+                &[account.clone()],
+                "fees_are_burned_from_the_sender_when_applied test",
+            )
+            .await;
+
+            // Bridge ins are never charged.
+            const MINT_AMOUNT: u64 = 1000;
+            let res = Batcher::add_transaction_to_account(
+                node.batcher.clone(),
+                test_bridge_in(MINT_AMOUNT, account.nonce(), VERSE_ADDR, address),
+            )
+            .await;
+            assert!(res.is_ok());
+            let minted = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            assert_eq!(minted.balance(&VERSE_ADDR), U256::from(MINT_AMOUNT));
+
+            const SEND_AMOUNT: u64 = 100;
+            let res = Batcher::add_transaction_to_account(
+                node.batcher.clone(),
+                test_send_with_fees(
+                    SEND_AMOUNT,
+                    address,
+                    minted.nonce() + U256::from(1),
+                    receiver,
+                    50,
+                    5,
+                ),
+            )
+            .await;
+            assert!(res.is_ok());
+            let sent = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let received = get_account(receiver, ActorType::AccountCache)
+                .await
+                .expect("could not find receiver account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(
+                sent.balance(&VERSE_ADDR),
+                U256::from(MINT_AMOUNT - SEND_AMOUNT - 15)
+            );
+            assert_eq!(received.balance(&VERSE_ADDR), U256::from(SEND_AMOUNT));
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT - 15)
+            );
+
+            // A send whose max fee does not cover its priority fee on top
+            // of the floor is charged its max fee.
+            let res = Batcher::add_transaction_to_account(
+                node.batcher.clone(),
+                test_send_with_fees(
+                    SEND_AMOUNT,
+                    address,
+                    sent.nonce() + U256::from(1),
+                    receiver,
+                    12,
+                    5,
+                ),
+            )
+            .await;
+            assert!(res.is_ok());
+            let capped = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            assert_eq!(
+                capped.balance(&VERSE_ADDR),
+                U256::from(MINT_AMOUNT - 2 * SEND_AMOUNT - 15 - 12)
+            );
+
+            // A legacy transaction without fees is charged nothing.
+            const BURN_AMOUNT: u64 = 4;
+            let res = Batcher::apply_verse_burn(
+                node.batcher.clone(),
+                test_burn(BURN_AMOUNT, address, capped.nonce()),
+            )
+            .await;
+            assert!(res.is_ok());
+            let burned = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(
+                burned.balance(&VERSE_ADDR),
+                capped.balance(&VERSE_ADDR) - U256::from(BURN_AMOUNT)
+            );
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT - 15 - 12 - BURN_AMOUNT)
+            );
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn duplicate_send_is_applied_once() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            let (from_account, from_program_account) = sender_test_account_pair();
+            let from_account_address = from_account.owner_address();
+            let AccountType::Program(from_program_address) = from_program_account.account_type()
+            else {
+                panic!("from_program_account is not a program account.")
+            };
+            let to_account = receiver_test_account();
+            let to_account_address = to_account.owner_address();
+            seed_accounts(
+                &node.mock_storage,
+                &[from_account.clone(), from_program_account, to_account],
+                "duplicate_send_is_applied_once test",
+            )
+            .await;
+
+            const TRANSFER_AMOUNT: u64 = 1;
+            let apply = |transaction: Transaction| {
+                Batcher::apply_once(
+                    node.batcher.clone(),
+                    transaction.hash_string(),
+                    Batcher::add_transaction_to_account(node.batcher.clone(), transaction),
+                )
+            };
+
+            // Serially: the resubmission is a no-op.
+            let serial_send = test_send(
+                TRANSFER_AMOUNT,
+                from_account_address,
+                U256::from(0),
+                from_program_address,
+                to_account_address,
+            );
+            assert!(apply(serial_send.clone()).await.is_ok());
+            assert!(apply(serial_send).await.is_ok());
+
+            let to_account_after = get_account(to_account_address, ActorType::AccountCache)
+                .await
+                .expect("could not find to account");
+            assert_eq!(
+                to_account_after.balance(&from_program_address),
+                U256::from(TRANSFER_AMOUNT)
+            );
+
+            // Concurrently: only one of the two deliveries is applied.
+            let concurrent_send = test_send(
+                TRANSFER_AMOUNT,
+                from_account_address,
+                U256::from(2),
+                from_program_address,
+                to_account_address,
+            );
+            let (first, second) = tokio::join!(
+                apply(concurrent_send.clone()),
+                apply(concurrent_send.clone())
+            );
+            assert!(first.is_ok() && second.is_ok());
+
+            let from_account_after = get_account(from_account_address, ActorType::AccountCache)
+                .await
+                .expect("could not find from account");
+            let to_account_after = get_account(to_account_address, ActorType::AccountCache)
+                .await
+                .expect("could not find to account");
+            assert_eq!(
+                from_account_after.balance(&from_program_address),
+                from_account.balance(&from_program_address) - U256::from(2 * TRANSFER_AMOUNT)
+            );
+            assert_eq!(
+                to_account_after.balance(&from_program_address),
+                U256::from(2 * TRANSFER_AMOUNT)
+            );
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn duplicate_send_gets_its_existing_status() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            let (from_account, _) = sender_test_account_pair();
+            let (program_id, token) = from_account
+                .programs()
+                .iter()
+                .next()
+                .map(|(program_id, token)| (*program_id, token.clone()))
+                .expect("sender has no tokens");
+            let send_transaction = test_send(
+                1,
+                from_account.owner_address(),
+                from_account.nonce(),
+                program_id,
+                receiver_test_account().owner_address(),
+            );
+            let transaction_hash = send_transaction.hash_string();
+
+            let submit = |transaction: Transaction| {
+                let (tx, rx) = ractor::concurrency::oneshot();
+                node.scheduler_actor
+                    .0
+                    .cast(SchedulerMessage::Send {
+                        transaction,
+                        replace: false,
+                        rpc_reply: tx.into(),
+                    })
+                    .expect("failed to submit send to scheduler");
+                rx
+            };
+            let response = |message: RpcMessage| match message {
+                RpcMessage::Response {
+                    response: Ok(response),
+                    ..
+                } => response,
+                other => panic!("expected a send response, got {other:?}"),
+            };
+
+            // Of submissions arriving together, as over different
+            // connections, one is dispatched and the rest are told it is
+            // pending.
+            let submissions: Vec<_> = (0..4)
+                .map(|_| {
+                    let scheduler = node.scheduler_actor.0.clone();
+                    let transaction = send_transaction.clone();
+                    tokio::spawn(async move {
+                        let (tx, rx) = ractor::concurrency::oneshot();
+                        scheduler
+                            .cast(SchedulerMessage::Send {
+                                transaction,
+                                replace: false,
+                                rpc_reply: tx.into(),
+                            })
+                            .expect("failed to submit send to scheduler");
+                        rx
+                    })
+                })
+                .collect();
+            let mut replies = Vec::new();
+            for submission in submissions {
+                replies.push(submission.await.expect("submission panicked"));
+            }
+            node.scheduler_actor
+                .0
+                .cast(SchedulerMessage::TransactionApplied {
+                    transaction_hash: transaction_hash.clone(),
+                    token: token.clone(),
+                })
+                .expect("failed to report applied send to scheduler");
+            let mut already_pending = 0;
+            for reply in replies {
+                match response(reply.await.expect("reply dropped")) {
+                    TransactionResponse::AlreadyPending => already_pending += 1,
+                    TransactionResponse::SendResponse(applied) => assert_eq!(applied, token),
+                    other => panic!("expected a send receipt, got {other:?}"),
+                }
+            }
+            assert_eq!(already_pending, 3);
+
+            // A later resubmission is answered straight away with the receipt.
+            let resubmitted = submit(send_transaction);
+            let TransactionResponse::AlreadyIncluded(receipt) =
+                response(resubmitted.await.expect("resubmitted reply dropped"))
+            else {
+                panic!("resubmission was not answered as already included");
+            };
+            let TransactionResponse::SendResponse(included) = *receipt else {
+                panic!("included receipt is not a send receipt");
+            };
+            assert_eq!(included, token);
+
+            let TransactionResponse::SendResponse(persisted) = get_receipt(transaction_hash)
+                .await
+                .expect("receipt was not persisted")
+            else {
+                panic!("persisted receipt is not a send receipt");
+            };
+            assert_eq!(persisted, token);
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn wasm_call_event() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            let (from_account, from_program_account) = sender_test_account_pair();
+            let from_account_address = from_account.owner_address();
+            let AccountType::Program(from_program_address) = from_program_account.account_type()
+            else {
+                panic!("from_program_account is not a program account.")
+            };
+            let to_account = receiver_test_account();
+            let to_account_address = to_account.owner_address();
+            seed_accounts(
+                &node.mock_storage,
+                &[
+                    from_account.clone(),
+                    from_program_account.clone(),
+                    to_account,
+                ],
+                "wasm_call_event test",
+            )
+            .await;
+
+            let call_transaction = test_call(
+                from_account.nonce(),
+                from_account_address,
+                from_program_address,
+                from_program_address,
+            );
+            let inputs = Inputs {
+                version: 1,
+                account_info: from_program_account,
+                transaction: call_transaction.clone(),
+                op: "transfer".to_string(),
+                inputs: String::new(),
+            };
+            let runtime = WasmRuntime::new(
+                OciBundlerBuilder::default()
+                    .runtime("/usr/local/bin/runsc".to_string())
+                    .base_images("./base_image".to_string())
+                    .containers("./containers".to_string())
+                    .payload_path("./payload".to_string())
+                    .build()
+                    .expect("failed to build bundler"),
+            );
+            let output = runtime
+                .run_module(
+                    include_bytes!("programs/transfer.wat").to_vec(),
+                    &serde_json::to_vec(&inputs).expect("failed to serialize inputs"),
+                    &ExecutionLimits::default(),
+                    None,
+                )
+                .await
+                .expect("wasm program failed");
+            let output = String::from_utf8(output).expect("wasm output is not utf8");
+
+            let outputs = validate_outputs(&output, &call_transaction, &OutputLimits::default())
+                .await
+                .expect("wasm outputs were rejected");
+            Batcher::apply_instructions_to_accounts(
+                node.batcher.clone(),
+                call_transaction,
+                outputs,
+            )
+            .await
+            .expect("failed to apply wasm outputs");
+
+            let from_account_with_updates =
+                get_account(from_account_address, ActorType::AccountCache)
+                    .await
+                    .expect("could not find from account");
+            let to_account_with_updates = get_account(to_account_address, ActorType::AccountCache)
+                .await
+                .expect("could not find to account");
+            assert_eq!(
+                from_account_with_updates.balance(&from_program_address),
+                from_account.balance(&from_program_address) - U256::from(1)
+            );
+            assert_eq!(
+                to_account_with_updates.balance(&from_program_address),
+                U256::from(1)
+            );
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+/// Seeds an account holding `amount` of VERSE, minted by a bridge in.
+async fn seed_verse_holder(node: &MinimalNode, amount: u64, location: &str) -> Account {
+    let account = receiver_test_account();
+    let address = account.owner_address();
+    seed_accounts(&node.mock_storage, &[account.clone()], location).await;
+    Batcher::add_transaction_to_account(
+        node.batcher.clone(),
+        test_bridge_in(amount, account.nonce(), VERSE_ADDR, address),
+    )
+    .await
+    .expect("failed to mint VERSE");
+    get_account(address, ActorType::AccountCache)
+        .await
+        .expect("could not find account")
+}
+
+#[serial]
+#[tokio::test]
+async fn deploy_event() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            let from_account = test_default_user_account();
+            seed_accounts(
+                &node.mock_storage,
+                &[from_account.clone()],
+                "deploy_event test",
+            )
+            .await;
+
+            const CONTENT_ID: &str = "bafyprogram";
+            let deploy = test_deploy(
+                from_account.nonce(),
+                from_account.owner_address(),
+                CONTENT_ID,
+            );
+            let program_id = lasr_contract::create_program_id(CONTENT_ID.to_string(), &deploy)
+                .expect("failed to generate finalized program id");
+
+            // A package running in another runtime than declared fails.
+            let res = Batcher::apply_program_registration_in_runtime(
+                node.batcher.clone(),
+                deploy.clone(),
+                Some("container".to_string()),
+            )
+            .await;
+            assert!(res.is_err());
+            assert!(get_account(program_id, ActorType::AccountCache)
+                .await
+                .is_none());
+
+            // Named in its type, the package needs no inputs.
+            let res = Batcher::apply_transaction(node.batcher.clone(), deploy.clone(), None).await;
+            assert!(res.is_ok());
+
+            let program = get_account(program_id, ActorType::AccountCache)
+                .await
+                .expect("the program was not registered");
+            let metadata = program.program_account_metadata().inner();
+            assert_eq!(
+                metadata.get("content_id").map(String::as_str),
+                Some(CONTENT_ID)
+            );
+            assert_eq!(
+                metadata.get(PROGRAM_RUNTIME_KEY).map(String::as_str),
+                Some("wasm")
+            );
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn bridge_out_event() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            const MINT_AMOUNT: u64 = 10;
+            const BRIDGE_AMOUNT: u64 = 4;
+            let minted = seed_verse_holder(&node, MINT_AMOUNT, "bridge_out_event test").await;
+            let address = minted.owner_address();
+            let recipient = Address::new([7; 20]);
+
+            // Bridge outs naming no recipient were never applied, and still are not.
+            let res = Batcher::apply_transaction(
+                node.batcher.clone(),
+                test_debit(
+                    TransactionType::BridgeOut(minted.nonce()),
+                    BRIDGE_AMOUNT,
+                    address,
+                    minted.nonce(),
+                ),
+                None,
+            )
+            .await;
+            assert!(res.is_err());
+
+            let bridge_out = TransactionType::BridgeOutTo {
+                nonce: minted.nonce(),
+                recipient,
+            };
+            let res = Batcher::apply_transaction(
+                node.batcher.clone(),
+                test_debit(bridge_out, BRIDGE_AMOUNT, address, minted.nonce()),
+                None,
+            )
+            .await;
+            assert!(res.is_ok());
+
+            let bridged = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(
+                bridged.balance(&VERSE_ADDR),
+                U256::from(MINT_AMOUNT - BRIDGE_AMOUNT)
+            );
+            assert_eq!(bridged.nonce(), minted.nonce() + U256::from(1));
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT - BRIDGE_AMOUNT)
+            );
+            // Nothing is credited on the chain, least of all to the recipient.
+            assert!(get_account(recipient, ActorType::AccountCache)
+                .await
+                .is_none());
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
+
+#[serial]
+#[tokio::test]
+async fn burn_amount_event() {
+    MinimalNode::new()
+        .and_then(|node| async move {
+            const MINT_AMOUNT: u64 = 10;
+            const BURN_AMOUNT: u64 = 4;
+            let minted = seed_verse_holder(&node, MINT_AMOUNT, "burn_amount_event test").await;
+            let address = minted.owner_address();
+            let burn = TransactionType::BurnAmount {
+                nonce: minted.nonce(),
+            };
+
+            let res = Batcher::apply_transaction(
+                node.batcher.clone(),
+                test_debit(burn, BURN_AMOUNT, address, minted.nonce()),
+                None,
+            )
+            .await;
+            assert!(res.is_ok());
+
+            let burned = get_account(address, ActorType::AccountCache)
+                .await
+                .expect("could not find account");
+            let verse_account = get_account(VERSE_ADDR, ActorType::AccountCache)
+                .await
+                .expect("could not find VERSE program account");
+            assert_eq!(
+                burned.balance(&VERSE_ADDR),
+                U256::from(MINT_AMOUNT - BURN_AMOUNT)
+            );
+            assert_eq!(
+                verse_total_supply(&verse_account).unwrap(),
+                U256::from(MINT_AMOUNT - BURN_AMOUNT)
+            );
+
+            MinimalNode::shutdown_and_wait(node).await
+        })
+        .await
+        .unwrap();
+}
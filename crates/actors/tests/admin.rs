@@ -0,0 +1,114 @@
+#![cfg(test)]
+//! Test coverage for authenticating callers of the admin methods.
+
+mod common;
+
+use common::rpc_proxy;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use jsonrpsee::{
+    core::{client::ClientT, ClientError},
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+    server::ServerHandle,
+};
+use lasr_actors::{serve_rpc, AdminAccess, LasrRpcServerImpl, RateLimiter, RequestLimits};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{RpcErrorCode, RpcMessage};
+use lasr_rpc::{AdminRpcClient, AdminRpcServer, LasrRpcClient, LasrRpcServer};
+use ractor::ActorRef;
+use serde_json::Value;
+use std::net::SocketAddr;
+
+async fn start(admin: AdminAccess) -> (ActorRef<RpcMessage>, ServerHandle, SocketAddr) {
+    let proxy = rpc_proxy().await;
+    let rate_limiter = RateLimiter::default();
+    let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+        .with_rate_limiter(rate_limiter.clone());
+    let mut methods = LasrRpcServer::into_rpc(rpc.clone());
+    methods
+        .merge(AdminRpcServer::into_rpc(rpc))
+        .expect("failed to merge admin methods");
+    let (addr, handle) = serve_rpc(
+        "127.0.0.1:0".parse().unwrap(),
+        methods,
+        rate_limiter,
+        admin,
+        None,
+        RequestLimits::default(),
+    )
+    .await
+    .expect("failed to serve rpc");
+    (proxy, handle, addr)
+}
+
+fn client(addr: SocketAddr, authorization: Option<&'static str>) -> HttpClient {
+    let mut headers = HeaderMap::new();
+    if let Some(authorization) = authorization {
+        headers.insert(AUTHORIZATION, HeaderValue::from_static(authorization));
+    }
+    HttpClientBuilder::default()
+        .set_headers(headers)
+        .build(format!("http://{addr}"))
+        .expect("failed to build rpc client")
+}
+
+fn unauthorized<T: std::fmt::Debug>(result: Result<T, ClientError>) {
+    match result {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::Unauthorized.code(), "{error:?}")
+        }
+        other => panic!("admin call was not refused: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn admin_calls_need_the_bearer_token() {
+    let (proxy, handle, addr) = start(AdminAccess::Token("operator".to_string())).await;
+
+    let status: Value = serde_json::from_str(
+        &client(addr, Some("Bearer operator"))
+            .get_rate_limits()
+            .await
+            .expect("operator was refused"),
+    )
+    .unwrap();
+    assert!(status["limits"].is_object());
+
+    for authorization in [None, Some("Bearer guess"), Some("operator")] {
+        let client = client(addr, authorization);
+        unauthorized(client.get_rate_limits().await);
+        unauthorized(client.set_rate_limits("{}".to_string()).await);
+        // Admin methods that do not exist are not told apart from those that
+        // do until the caller is authenticated.
+        unauthorized(
+            client
+                .request::<String, _>("admin_noSuchMethod", rpc_params![])
+                .await,
+        );
+        client.health().await.expect("public methods need no token");
+    }
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+async fn the_local_listener_answers_admin_calls_without_a_token() {
+    let (proxy, handle, addr) = start(AdminAccess::Localhost).await;
+    client(addr, None)
+        .get_rate_limits()
+        .await
+        .expect("local caller was refused");
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+
+    // Without a token or a local listener, nobody is an operator.
+    let (proxy, handle, addr) = start(AdminAccess::Disabled).await;
+    unauthorized(
+        client(addr, Some("Bearer operator"))
+            .get_rate_limits()
+            .await,
+    );
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
@@ -0,0 +1,120 @@
+#![cfg(test)]
+//! Test coverage for transactions turned away by the RPC server before they
+//! are handed to the scheduler.
+
+mod common;
+
+use common::{rpc_proxy, serve, signed, ws_client};
+use jsonrpsee::core::ClientError;
+use lasr_actors::{Admission, FeeSchedule, LasrRpcServerImpl};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{Address, Payload, PayloadBuilder, Transaction, TransactionType, U256};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+fn address(secret_key: &SecretKey) -> Address {
+    PublicKey::from_secret_key(&Secp256k1::new(), secret_key).into()
+}
+
+/// A send from the holder of `from`, signed by `signer`.
+fn send(from: &SecretKey, signer: &SecretKey) -> Transaction {
+    signed(payload(from, None), signer)
+}
+
+fn payload(from: &SecretKey, max_fee: Option<u64>) -> Payload {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(0)))
+        .from(address(from).into())
+        .to([2; 20])
+        .program_id([0; 20])
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(1))
+        .max_fee(max_fee.map(U256::from))
+        .build()
+        .expect("failed to build payload")
+}
+
+#[tokio::test]
+async fn rpc_server_rejects_a_forged_send_with_the_signer() {
+    let proxy = rpc_proxy().await;
+    let (server_handle, addr) =
+        serve(LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default()).into_rpc()).await;
+    let client = ws_client(addr).await;
+
+    let sender = SecretKey::from_slice(&[1; 32]).unwrap();
+    let forger = SecretKey::from_slice(&[2; 32]).unwrap();
+    match client.send(send(&sender, &forger)).await {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::SenderMismatch.code());
+            let data: serde_json::Value =
+                serde_json::from_str(error.data().expect("error has no data").get())
+                    .expect("error data is not JSON");
+            assert_eq!(
+                data,
+                serde_json::json!({
+                    "reason": "senderMismatch",
+                    "from": address(&sender),
+                    "signer": address(&forger),
+                })
+            );
+        }
+        other => panic!("forged send was admitted: {other:?}"),
+    }
+
+    server_handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+async fn rpc_server_rejects_a_max_fee_under_the_floor_and_estimates_it() {
+    let proxy = rpc_proxy().await;
+    let admission = Admission::default().with_fee_schedule(FeeSchedule::new(100, 0, 0, 0));
+    let (server_handle, addr) = serve(
+        LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_admission(admission)
+            .into_rpc(),
+    )
+    .await;
+    let client = ws_client(addr).await;
+
+    let sender = SecretKey::from_slice(&[1; 32]).unwrap();
+    match client
+        .send(signed(payload(&sender, Some(99)), &sender))
+        .await
+    {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::FeeTooLow.code());
+            let data: serde_json::Value =
+                serde_json::from_str(error.data().expect("error has no data").get())
+                    .expect("error data is not JSON");
+            assert_eq!(data["reason"], "feeTooLow");
+            assert_eq!(
+                data["floor"],
+                serde_json::to_value(U256::from(100)).unwrap()
+            );
+        }
+        other => panic!("send under the fee floor was admitted: {other:?}"),
+    }
+
+    let estimate: serde_json::Value = serde_json::from_str(
+        &client
+            .estimate_fees(payload(&sender, None))
+            .await
+            .expect("estimateFees failed"),
+    )
+    .expect("estimate is not JSON");
+    assert_eq!(estimate["executionClass"], "transfer");
+    assert_eq!(
+        estimate["floor"],
+        serde_json::to_value(U256::from(100)).unwrap()
+    );
+    assert_eq!(estimate["fee"], estimate["floor"]);
+    // The sender has no account, so holds nothing to send or pay it with.
+    assert_eq!(estimate["rejection"]["reason"], "insufficientBalance");
+
+    server_handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
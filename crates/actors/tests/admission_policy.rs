@@ -0,0 +1,237 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the admission policy restricting who may transact and
+//! what they may call, swapped at runtime and held to by transactions still
+//! pending when it is.
+
+mod common;
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use common::unix_now;
+use jsonrpsee::{core::ClientError, http_client::HttpClient};
+use lasr_actors::{AddressRules, PolicyConfig, TestHarness, VERSE_ADDR};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::{AdminRpcClient, LasrRpcClient};
+use lasr_types::{Address, Transaction, TransactionBuilder, TransactionType, U256};
+use lasr_wallet::Sent;
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn program(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+async fn set_policy(admin: &HttpClient, config: &PolicyConfig) {
+    let set: PolicyConfig = serde_json::from_str(
+        &admin
+            .set_admission_policy(serde_json::to_string(config).unwrap())
+            .await
+            .expect("failed to set admission policy"),
+    )
+    .unwrap();
+    assert_eq!(&set, config);
+}
+
+fn deny_senders(senders: &[Address]) -> PolicyConfig {
+    PolicyConfig {
+        senders: AddressRules {
+            allow: None,
+            deny: senders.iter().copied().collect(),
+        },
+        ..Default::default()
+    }
+}
+
+/// The rule broken by a request turned away as denied.
+fn denied_by(error: Option<&ClientError>) -> String {
+    match error {
+        Some(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::PolicyDenied.code());
+            let data: Value = serde_json::from_str(error.data().expect("error has no data").get())
+                .expect("error data is not JSON");
+            assert_eq!(data["reason"], "denied");
+            data["rule"].as_str().expect("no rule").to_string()
+        }
+        other => panic!("expected a denial, got {other:?}"),
+    }
+}
+
+fn call(from: Address, program_id: Address) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from(from.into())
+        .to(program_id.into())
+        .program_id(program_id.into())
+        .op("store".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(1))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+async fn denied(harness: &TestHarness, rule: &str) -> u64 {
+    let status: Value = serde_json::from_str(
+        &harness
+            .admin()
+            .unwrap()
+            .get_admission_policy()
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    status["denied"][rule]
+        .as_u64()
+        .expect("rule is not counted")
+}
+
+async fn wait_for_status(harness: &TestHarness, transaction_hash: &str, status: &str) -> Value {
+    let rpc = harness.rpc().unwrap();
+    for _ in 0..200 {
+        let answer: Value = serde_json::from_str(
+            &rpc.get_transaction_status(transaction_hash.to_string())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        if answer["status"] == status {
+            return answer;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("{transaction_hash} never became {status}");
+}
+
+#[tokio::test]
+#[serial]
+async fn each_rule_turns_away_what_it_covers_and_nothing_else() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let admin = harness.admin().unwrap();
+    let rpc = harness.rpc().unwrap();
+    let mut allowed = harness.wallet().await.expect("failed to fund wallet");
+    let mut blocked = harness.wallet().await.expect("failed to fund wallet");
+    let denied_before = denied(&harness, "senderDenied").await;
+
+    set_policy(
+        &admin,
+        &PolicyConfig {
+            senders: AddressRules {
+                allow: None,
+                deny: BTreeSet::from([blocked.address()]),
+            },
+            programs: AddressRules {
+                allow: Some(BTreeSet::from([program(0xaa), program(0xbb)])),
+                deny: BTreeSet::from([program(0xbb)]),
+            },
+            max_value: Some(U256::from(50)),
+        },
+    )
+    .await;
+
+    let e = blocked
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect_err("a denied sender was admitted");
+    assert_eq!(denied_by(e.downcast_ref::<ClientError>()), "senderDenied");
+    let e = allowed
+        .send(&receiver(), &VERSE_ADDR, U256::from(51))
+        .await
+        .expect_err("a send over the most value was admitted");
+    assert_eq!(denied_by(e.downcast_ref::<ClientError>()), "valueTooHigh");
+    for (program_id, rule) in [(0xbb, "programDenied"), (0xcc, "programNotAllowed")] {
+        let result = rpc.call(call(allowed.address(), program(program_id))).await;
+        assert_eq!(denied_by(result.as_ref().err()), rule);
+    }
+
+    // Within every rule, a send goes ahead.
+    allowed
+        .send(&receiver(), &VERSE_ADDR, U256::from(50))
+        .await
+        .expect("an allowed send was denied");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(50)
+        })
+        .await
+        .expect("the allowed send was not applied");
+    assert_eq!(denied(&harness, "senderDenied").await, denied_before + 1);
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_policy_swapped_mid_traffic_holds_from_the_next_transaction() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let admin = harness.admin().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+
+    let mut outcomes = Vec::new();
+    for sent in 0..6 {
+        if sent == 3 {
+            set_policy(&admin, &deny_senders(&[wallet.address()])).await;
+        }
+        outcomes.push(
+            wallet
+                .send(&receiver(), &VERSE_ADDR, U256::from(1))
+                .await
+                .is_ok(),
+        );
+    }
+    assert_eq!(outcomes, [true, true, true, false, false, false]);
+    assert_eq!(
+        harness
+            .account(receiver())
+            .await
+            .unwrap()
+            .balance(&VERSE_ADDR),
+        U256::from(3)
+    );
+
+    // And lifting it lets the sender through again.
+    set_policy(&admin, &PolicyConfig::default()).await;
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(1))
+        .await
+        .expect("the sender was denied after the policy was lifted");
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_transaction_pending_when_its_sender_is_denied_is_turned_away_at_dispatch() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let admin = harness.admin().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+
+    let sent = wallet
+        .send_within(
+            &receiver(),
+            &VERSE_ADDR,
+            U256::from(25),
+            Some(unix_now() + 2),
+            None,
+        )
+        .await
+        .expect("failed to send");
+    let Sent::Scheduled(held) = sent else {
+        panic!("the send was applied before it was valid: {sent:?}");
+    };
+    set_policy(&admin, &deny_senders(&[wallet.address()])).await;
+
+    let status = wait_for_status(&harness, &held, "failed").await;
+    assert_eq!(status["reason"], "policyDenied");
+    assert!(harness.account(receiver()).await.is_none());
+
+    harness.stop().await;
+}
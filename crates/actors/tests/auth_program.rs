@@ -0,0 +1,264 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for program accounts whose transactions are authorized by
+//! an auth program rather than a signature, with a sample 2-of-3 program.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use jsonrpsee::core::ClientError;
+use lasr_actors::{
+    Genesis, GenesisProgram, TestHarness, AUTH_OP, DEFAULT_ETH_CHAIN_ID, VERSE_ADDR,
+};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{
+    Account, AccountType, Address, Inputs, Payload, PayloadBuilder, RecoverableSignature,
+    Transaction, TransactionType, U256,
+};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serial_test::serial;
+
+fn wallet() -> Address {
+    Address::new([0xaa; 20])
+}
+
+fn multisig() -> Address {
+    Address::new([0xab; 20])
+}
+
+fn receiver() -> Address {
+    Address::new([7; 20])
+}
+
+fn key(byte: u8) -> SecretKey {
+    SecretKey::from_slice(&[byte; 32]).unwrap()
+}
+
+fn address(secret_key: &SecretKey) -> Address {
+    PublicKey::from_secret_key(&Secp256k1::new(), secret_key).into()
+}
+
+/// The wallet's three signers.
+fn signers() -> [SecretKey; 3] {
+    [key(11), key(12), key(13)]
+}
+
+async fn harness() -> TestHarness {
+    let path = std::env::temp_dir().join(format!("auth_program_{}.toml", std::process::id()));
+    let signers = signers()
+        .iter()
+        .map(|signer| address(signer).to_full_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let genesis = Genesis {
+        chain_id: DEFAULT_ETH_CHAIN_ID,
+        programs: vec![
+            GenesisProgram {
+                program_id: wallet(),
+                owner: address(&key(1)),
+                content_id: "bafy-wallet".to_string(),
+                metadata: BTreeMap::from([
+                    ("signers".to_string(), signers),
+                    ("threshold".to_string(), "2".to_string()),
+                ]),
+                auth_program: Some(multisig()),
+            },
+            GenesisProgram {
+                program_id: multisig(),
+                owner: address(&key(1)),
+                content_id: "bafy-multisig".to_string(),
+                metadata: BTreeMap::new(),
+                auth_program: None,
+            },
+        ],
+        accounts: Vec::new(),
+    };
+    std::fs::write(&path, toml::to_string(&genesis).unwrap()).unwrap();
+    let harness = TestHarness::start_with(|config| config.genesis(path))
+        .await
+        .expect("failed to start node");
+    harness
+        .rpc()
+        .unwrap()
+        .faucet(wallet().to_full_string(), "100".to_string(), None)
+        .await
+        .expect("failed to fund the wallet");
+    harness
+        .wait_for_account(wallet(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(100)
+        })
+        .await
+        .expect("the wallet was not funded");
+    harness
+}
+
+/// What the wallet's signers sign: the send, without the signatures it
+/// carries in its inputs.
+fn unsigned(transaction: &Transaction) -> Payload {
+    PayloadBuilder::default()
+        .transaction_type(transaction.transaction_type())
+        .from(transaction.from().into())
+        .to(transaction.to().into())
+        .program_id(transaction.program_id().into())
+        .inputs(String::new())
+        .op(transaction.op())
+        .value(transaction.value())
+        .nonce(transaction.nonce())
+        .build()
+        .unwrap()
+}
+
+/// The sample auth program: authorizes a transaction that carries the
+/// signatures of as many of the signers the account lists as its threshold.
+fn two_of_three(_program: &Account, inputs: Inputs) -> Result<bool, String> {
+    let metadata = inputs.account_info.program_account_metadata().inner();
+    let signers = metadata
+        .get("signers")
+        .ok_or("the account lists no signers")?
+        .split(',')
+        .map(|signer| Address::from_hex(signer).map_err(|e| e.to_string()))
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    let threshold: usize = metadata
+        .get("threshold")
+        .ok_or("the account has no threshold")?
+        .parse()
+        .map_err(|e: std::num::ParseIntError| e.to_string())?;
+    let signatures: Vec<RecoverableSignature> =
+        serde_json::from_str(&inputs.inputs).map_err(|e| e.to_string())?;
+    let message = unsigned(&inputs.transaction).digest();
+    let signed: BTreeSet<_> = signatures
+        .iter()
+        .filter_map(|signature| signature.recover(&message).ok())
+        .filter(|signer| signers.contains(signer))
+        .collect();
+    Ok(signed.len() >= threshold)
+}
+
+/// A send of 5 VERSE from the wallet, carrying the signatures of `by`.
+async fn send(harness: &TestHarness, by: &[&SecretKey]) -> Transaction {
+    let nonce = harness.account(wallet()).await.unwrap().nonce() + U256::from(1);
+    let payload = |inputs: String| {
+        PayloadBuilder::default()
+            .transaction_type(TransactionType::Send(nonce))
+            .from(wallet().into())
+            .to(receiver().into())
+            .program_id(VERSE_ADDR.into())
+            .inputs(inputs)
+            .op(String::new())
+            .value(U256::from(5))
+            .nonce(nonce)
+            .build()
+            .unwrap()
+    };
+    let message = Message::from_digest_slice(&payload(String::new()).hash()).unwrap();
+    let signatures: Vec<RecoverableSignature> = by
+        .iter()
+        .map(|signer| {
+            Secp256k1::new()
+                .sign_ecdsa_recoverable(&message, signer)
+                .into()
+        })
+        .collect();
+    Transaction::from(payload(serde_json::to_string(&signatures).unwrap()))
+}
+
+/// Why the RPC server turned `transaction` away, if it did.
+async fn refusal(harness: &TestHarness, transaction: Transaction) -> Option<String> {
+    match harness.rpc().unwrap().send(transaction).await {
+        Ok(_) => None,
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::InvalidSignature.code());
+            Some(error.message().to_string())
+        }
+        Err(e) => panic!("the send failed with {e}"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn two_of_three_signers_authorize_a_send_from_the_wallet() {
+    let harness = harness().await;
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let record = Arc::clone(&asked);
+    harness.script_auth(move |program, inputs| {
+        let asked_for = match inputs.account_info.account_type() {
+            AccountType::Program(program_id) => program_id,
+            AccountType::User => inputs.account_info.owner_address(),
+        };
+        record
+            .lock()
+            .unwrap()
+            .push((program.account_type(), asked_for, inputs.op.clone()));
+        two_of_three(program, inputs)
+    });
+    let [first, _, third] = signers();
+    let stranger = key(14);
+
+    for by in [vec![&first], vec![&first, &first], vec![&first, &stranger]] {
+        let reason = refusal(&harness, send(&harness, &by).await)
+            .await
+            .expect("a send without two of the signers was admitted");
+        assert!(
+            reason.contains(&format!(
+                "not authorized by auth program {}: the auth program refused it",
+                multisig()
+            )),
+            "{reason}"
+        );
+    }
+    assert_eq!(
+        asked.lock().unwrap().clone(),
+        vec![
+            (
+                AccountType::Program(multisig()),
+                wallet(),
+                AUTH_OP.to_string()
+            );
+            3
+        ]
+    );
+
+    // Signed by the wallet's owner, a send is held to the auth program all
+    // the same.
+    let unsigned_send = send(&harness, &[]).await;
+    let payload = unsigned(&unsigned_send);
+    let message = Message::from_digest_slice(&payload.hash()).unwrap();
+    let signature: RecoverableSignature = Secp256k1::new()
+        .sign_ecdsa_recoverable(&message, &key(1))
+        .into();
+    assert!(refusal(&harness, (payload, signature).into())
+        .await
+        .is_some());
+
+    asked.lock().unwrap().clear();
+    let authorized = send(&harness, &[&first, &third]).await;
+    assert_eq!(refusal(&harness, authorized).await, None);
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(5)
+        })
+        .await
+        .expect("the authorized send was not applied");
+    // Asked once on admission, and again by the validator.
+    assert_eq!(asked.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn an_auth_program_that_fails_refuses_the_send() {
+    let harness = harness().await;
+    harness.script_auth(|_, _| Err("execution exceeded its wall clock limit of 500ms".to_string()));
+    let [first, second, _] = signers();
+
+    let reason = refusal(&harness, send(&harness, &[&first, &second]).await)
+        .await
+        .expect("a send its auth program failed on was admitted");
+    assert!(
+        reason.ends_with("execution exceeded its wall clock limit of 500ms"),
+        "{reason}"
+    );
+    let wallet = harness.account(wallet()).await.unwrap();
+    assert_eq!(wallet.balance(&VERSE_ADDR), U256::from(100));
+    assert_eq!(wallet.auth_program(), Some(multisig()));
+}
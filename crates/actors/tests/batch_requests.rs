@@ -0,0 +1,147 @@
+#![cfg(test)]
+//! Test coverage for JSON-RPC batch requests to the RPC server.
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::rpc_proxy;
+use jsonrpsee::{
+    server::{ServerBuilder, ServerHandle},
+    types::error::{
+        INVALID_PARAMS_CODE, INVALID_REQUEST_CODE, METHOD_NOT_FOUND_CODE,
+        TOO_BIG_BATCH_REQUEST_CODE,
+    },
+};
+use lasr_actors::{LasrRpcServerImpl, RequestLimits};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::RpcMessage;
+use lasr_rpc::LasrRpcServer;
+use lasr_types::{AccountState, Address};
+use ractor::ActorRef;
+use serde_json::{json, Value};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn start(max_batch_size: u32) -> (ActorRef<RpcMessage>, ServerHandle, SocketAddr) {
+    let proxy = rpc_proxy().await;
+    let limits = RequestLimits {
+        max_batch_size,
+        ..RequestLimits::default()
+    };
+    let server = ServerBuilder::default()
+        .set_batch_request_config(limits.batch_config())
+        .build("127.0.0.1:0")
+        .await
+        .expect("failed to build rpc server");
+    let addr = server.local_addr().expect("server has no address");
+    let handle =
+        server.start(LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default()).into_rpc());
+    (proxy, handle, addr)
+}
+
+/// Posts `body` as it is, so batches can hold notifications and malformed
+/// calls, and returns the JSON answered, if any.
+async fn post(addr: SocketAddr, body: &Value) -> Option<Value> {
+    let body = body.to_string();
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("failed to connect to rpc server");
+    let request = format!(
+        "POST / HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .expect("response has no body");
+    (!body.is_empty()).then(|| serde_json::from_str(body).expect("response is not JSON"))
+}
+
+#[tokio::test]
+#[serial]
+async fn a_batch_is_answered_call_by_call() {
+    let (proxy, handle, addr) = start(4).await;
+    let address = Address::new([1; 20]);
+
+    let response = post(
+        addr,
+        &json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "lasr_getAccount", "params": [address.to_full_string()] },
+            { "jsonrpc": "2.0", "id": "two", "method": "lasr_getAccount", "params": ["not an address"] },
+            { "jsonrpc": "2.0", "method": "lasr_getVerseSupply", "params": [] },
+            { "jsonrpc": "2.0", "id": 3, "method": "lasr_getNothing", "params": [] },
+        ]),
+    )
+    .await
+    .expect("batch was not answered");
+
+    let answers = response.as_array().expect("batch answered with no array");
+    // The notification goes unanswered.
+    assert_eq!(answers.len(), 3);
+    let answer = |id: Value| {
+        answers
+            .iter()
+            .find(|answer| answer["id"] == id)
+            .unwrap_or_else(|| panic!("{id} was not answered"))
+    };
+    let account: AccountState = serde_json::from_str(
+        answer(json!(1))["result"]
+            .as_str()
+            .expect("account was not answered"),
+    )
+    .unwrap();
+    assert_eq!(account, AccountState::new(address, None));
+    assert_eq!(answer(json!("two"))["error"]["code"], INVALID_PARAMS_CODE);
+    assert_eq!(answer(json!(3))["error"]["code"], METHOD_NOT_FOUND_CODE);
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_malformed_call_fails_on_its_own() {
+    let (proxy, handle, addr) = start(4).await;
+
+    let response = post(
+        addr,
+        &json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "lasr_getVerseSupply", "params": [] },
+            { "id": 2 },
+        ]),
+    )
+    .await
+    .expect("batch was not answered");
+
+    let answers = response.as_array().expect("batch answered with no array");
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0]["id"], 1);
+    assert_eq!(answers[0]["result"], "0x0");
+    assert_eq!(answers[1]["error"]["code"], INVALID_REQUEST_CODE);
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_batch_over_the_cap_is_turned_away_whole() {
+    let (proxy, handle, addr) = start(4).await;
+
+    let batch: Vec<Value> = (0..5)
+        .map(|id| json!({ "jsonrpc": "2.0", "id": id, "method": "lasr_getVerseSupply", "params": [] }))
+        .collect();
+    let response = post(addr, &Value::Array(batch))
+        .await
+        .expect("batch was not answered");
+
+    assert_eq!(response["error"]["code"], TOO_BIG_BATCH_REQUEST_CODE);
+    assert_eq!(response["id"], Value::Null);
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
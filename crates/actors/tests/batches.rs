@@ -0,0 +1,299 @@
+#![cfg(test)]
+//! Test coverage for looking up the batches a node has finalized.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::{http_client, rpc_proxy, send, serve, spawn_as, stop};
+use eigenda_client::proof::BlobVerificationProof;
+use ethereum_types::H256;
+use jsonrpsee::{core::ClientError, http_client::HttpClient, server::ServerHandle};
+use lasr_actors::{BatchBuilder, BatchReceipts, Batcher, LasrRpcServerImpl};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{ActorType, BatchStatus, DaClientMessage, RpcErrorCode, Withdrawal};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{Account, Address, TransactionBuilder, TransactionType, U256};
+use ractor::{concurrency::OneshotReceiver, Actor, ActorCell, ActorProcessingErr, ActorRef};
+use serde_json::{json, Value};
+use serial_test::serial;
+use tokio::sync::{mpsc, Mutex};
+
+/// Stands in for the DA layer, answering with the blobs it was handed.
+struct MockDaClient;
+
+#[async_trait]
+impl Actor for MockDaClient {
+    type Msg = DaClientMessage;
+    type State = HashMap<(H256, u128), String>;
+    type Arguments = HashMap<(H256, u128), String>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        blobs: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(blobs)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        blobs: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let DaClientMessage::RetrieveBlob {
+            batch_header_hash,
+            blob_index,
+            tx,
+        } = message
+        {
+            let blob = blobs
+                .get(&(batch_header_hash, blob_index))
+                .map(|blob| blob.as_bytes().to_vec())
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no such blob"));
+            tx.send(blob).ok();
+        }
+        Ok(())
+    }
+}
+
+fn commitment(batch_id: u64) -> H256 {
+    H256::repeat_byte(batch_id as u8 + 1)
+}
+
+struct Node {
+    _batcher_rx: mpsc::Receiver<OneshotReceiver<(String, BlobVerificationProof)>>,
+    /// The accounts each batch holds, by batch id.
+    accounts: Vec<Vec<Account>>,
+    /// The transactions each batch holds, by batch id.
+    transactions: Vec<Vec<String>>,
+    actors: Vec<ActorCell>,
+    handle: ServerHandle,
+    client: HttpClient,
+}
+
+impl Node {
+    /// Starts a node that has sealed three batches, the second of them
+    /// settled.
+    async fn start() -> Self {
+        let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+        let batcher = Batcher::new(receivers_thread_tx);
+        let batches = batcher.batches();
+        let batcher = Arc::new(Mutex::new(batcher));
+        let receipts = BatchReceipts::default();
+
+        let mut blobs = HashMap::new();
+        let (mut accounts, mut transactions) = (Vec::new(), Vec::new());
+        for batch_id in 0..3u64 {
+            let mut held: Vec<Account> = (0..=batch_id)
+                .map(|n| Account::new(Address::new([batch_id as u8 * 10 + n as u8; 20])))
+                .collect();
+            held.sort_by_key(|account| account.owner_address().to_full_string());
+            let transaction = send(batch_id);
+            let batch = BatchBuilder::default()
+                .accounts(
+                    held.iter()
+                        .map(|account| (account.owner_address().to_full_string(), account.clone()))
+                        .collect(),
+                )
+                .transactions(HashMap::from([(
+                    transaction.hash_string(),
+                    transaction.clone(),
+                )]))
+                .build()
+                .expect("failed to build batch");
+            blobs.insert(
+                (commitment(batch_id), batch_id as u128),
+                batch.encode_batch().expect("failed to encode batch"),
+            );
+
+            let request_id = format!("request-{batch_id}");
+            batcher.lock().await.dispersed(request_id.clone(), batch);
+            let (header, _) = Batcher::finalize_batch(
+                batcher.clone(),
+                &request_id,
+                commitment(batch_id),
+                batch_id as u128,
+            )
+            .await
+            .expect("failed to finalize batch");
+            assert_eq!(header.batch_id, batch_id);
+            if batch_id == 1 {
+                receipts.set(
+                    &header,
+                    BatchStatus::Settled {
+                        transaction_hash: H256::repeat_byte(9),
+                        block_number: 42,
+                    },
+                );
+            }
+            accounts.push(held);
+            transactions.push(vec![transaction.hash_string()]);
+        }
+        // A batch is finalized once however often its blob is verified.
+        assert!(
+            Batcher::finalize_batch(batcher.clone(), "request-0", commitment(0), 0)
+                .await
+                .is_err()
+        );
+
+        let da_client = spawn_as(ActorType::DaClient, MockDaClient, blobs).await;
+        let proxy = rpc_proxy().await;
+        let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_batch_index(batches)
+            .with_batch_receipts(receipts);
+        let (handle, addr) = serve(rpc.into_rpc()).await;
+        Self {
+            _batcher_rx,
+            accounts,
+            transactions,
+            actors: vec![proxy.get_cell(), da_client.get_cell()],
+            handle,
+            client: http_client(addr),
+        }
+    }
+
+    async fn batch(&self, batch_id: u64, full: bool) -> Value {
+        serde_json::from_str(
+            &self
+                .client
+                .get_batch(batch_id, Some(full))
+                .await
+                .expect("getBatch failed"),
+        )
+        .expect("batch is not JSON")
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn finalized_batches_are_answered_by_id() {
+    let node = Node::start().await;
+
+    let batch = node.batch(1, false).await;
+    assert_eq!(batch["batchId"], 1);
+    assert_eq!(
+        batch["blobCommitment"],
+        format!("{:?}", commitment(1)).as_str()
+    );
+    assert_eq!(batch["blobIndex"], 1);
+    assert_eq!(batch["accountCount"], 2);
+    assert_eq!(batch["transactionHashes"], json!(node.transactions[1]));
+    assert!(batch["sealedAt"].as_u64().unwrap() <= batch["finalizedAt"].as_u64().unwrap());
+    assert_eq!(batch["status"], "settled");
+    assert_eq!(batch["blockNumber"], 42);
+    assert_eq!(batch.get("accounts"), None);
+    assert_eq!(node.batch(0, false).await["status"], "finalized");
+
+    // The accounts are read back from the batch's blob.
+    let batch = node.batch(2, true).await;
+    assert_eq!(batch["accountCount"], 3);
+    assert_eq!(
+        batch["accounts"],
+        serde_json::to_value(&node.accounts[2]).unwrap()
+    );
+
+    match node.client.get_batch(7, None).await {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::NotFound.code())
+        }
+        other => panic!("expected not found, got {other:?}"),
+    }
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn batches_are_listed_oldest_first() {
+    let node = Node::start().await;
+
+    let latest: Value =
+        serde_json::from_str(&node.client.get_latest_batch().await.unwrap()).unwrap();
+    assert_eq!(latest["batchId"], 2);
+    assert_eq!(latest["transactionHashes"], json!(node.transactions[2]));
+
+    let first: Value =
+        serde_json::from_str(&node.client.get_batches(None, Some(2)).await.unwrap()).unwrap();
+    assert_eq!(first["total"], 3);
+    assert_eq!(first["items"][0]["batchId"], 0);
+    assert_eq!(first["items"][1]["batchId"], 1);
+    let next: Value = serde_json::from_str(
+        &node
+            .client
+            .get_batches(first["nextCursor"].as_str().map(str::to_string), Some(2))
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(next["items"].as_array().unwrap().len(), 1);
+    assert_eq!(next["items"][0]["batchId"], 2);
+    assert_eq!(next["nextCursor"], Value::Null);
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_batch_header_carries_the_withdrawals_of_its_bridge_outs() {
+    let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+    let batcher = Arc::new(Mutex::new(Batcher::new(receivers_thread_tx)));
+    let recipient = Address::new([7; 20]);
+    let bridge_out = TransactionBuilder::default()
+        .transaction_type(TransactionType::BridgeOutTo {
+            nonce: U256::from(1),
+            recipient,
+        })
+        .from([1; 20])
+        .to([1; 20])
+        .program_id([3; 20])
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(5))
+        .nonce(U256::from(1))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap();
+    let sender = Account::new(Address::new([1; 20]));
+    let batch = BatchBuilder::default()
+        .accounts(HashMap::from([(
+            sender.owner_address().to_full_string(),
+            sender,
+        )]))
+        .transactions(
+            [send(0), bridge_out]
+                .into_iter()
+                .map(|transaction| (transaction.hash_string(), transaction))
+                .collect(),
+        )
+        .build()
+        .expect("failed to build batch");
+    batcher
+        .lock()
+        .await
+        .dispersed("request-0".to_string(), batch);
+
+    let (header, _) = Batcher::finalize_batch(batcher.clone(), "request-0", commitment(0), 0)
+        .await
+        .expect("failed to finalize batch");
+    assert_eq!(
+        header.withdrawals,
+        vec![Withdrawal {
+            token: Address::new([3; 20]),
+            recipient,
+            amount: U256::from(5),
+        }]
+    );
+    let batches = batcher.lock().await.batches();
+    assert_eq!(batches.get(0).unwrap().header(), header);
+}
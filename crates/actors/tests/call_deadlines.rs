@@ -0,0 +1,302 @@
+#![cfg(test)]
+//! Test coverage for calls that run past their deadline on a hung executor
+//! being cancelled, and retried or failed by whether the executor confirmed
+//! the cancellation.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{spawn_as, spawn_named, stop};
+use lasr_actors::{
+    metrics, PendingConfig, PendingTransactionActor, RetryMetrics, RetryPolicy, TaskScheduler,
+};
+use lasr_messages::{
+    ActorType, ExecutionCreditStatus, ExecutorMessage, PendingTransactionMessage, RetryReason,
+    RpcErrorCode, RpcMessage, SchedulerMessage, TransactionResponse,
+};
+use lasr_types::{Transaction, TransactionBuilder, TransactionType, U256};
+use ractor::{
+    concurrency::oneshot, rpc::CallResult, Actor, ActorCell, ActorProcessingErr, ActorRef,
+};
+use serial_test::serial;
+use tokio::sync::mpsc;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Seen {
+    Exec(String),
+    Cancel(String),
+}
+
+/// Stands in for an executor with room for one call, whose workers hang on
+/// every call they are given. Answers cancellations with whether it could
+/// confirm them.
+struct HungExecutor;
+
+fn grant() {
+    let pending: ActorRef<PendingTransactionMessage> =
+        ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+            .expect("pending transactions actor is not running")
+            .into();
+    pending
+        .cast(PendingTransactionMessage::ExecutionCredits { credits: 1 })
+        .expect("failed to cast to pending transactions");
+}
+
+#[async_trait]
+impl Actor for HungExecutor {
+    type Msg = ExecutorMessage;
+    type State = (mpsc::UnboundedSender<Seen>, bool);
+    type Arguments = (mpsc::UnboundedSender<Seen>, bool);
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(args)
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        grant();
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        (seen, confirm): &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            ExecutorMessage::Exec { transaction } => {
+                seen.send(Seen::Exec(transaction.hash_string())).ok();
+            }
+            ExecutorMessage::Cancel {
+                transaction_hash,
+                reply,
+            } => {
+                seen.send(Seen::Cancel(transaction_hash)).ok();
+                reply.send(*confirm).ok();
+            }
+            ExecutorMessage::GrantCredits => grant(),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn call(from: u8) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from([from; 20])
+        .to([3; 20])
+        .program_id([3; 20])
+        .op("run".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(0))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+/// The value of the sample of `name` with `labels`, or 0 if there is none.
+fn sample(name: &str, labels: &str) -> f64 {
+    let prefix = format!("{name}{{{labels}}} ");
+    metrics::encode()
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+struct Node {
+    scheduler: ActorRef<SchedulerMessage>,
+    pending: ActorRef<PendingTransactionMessage>,
+    seen: mpsc::UnboundedReceiver<Seen>,
+    retry_metrics: Arc<RetryMetrics>,
+    actors: Vec<ActorCell>,
+}
+
+impl Node {
+    async fn start(confirm: bool) -> Self {
+        let scheduler_actor = TaskScheduler::new()
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 1,
+                backoff: Duration::from_millis(10),
+            })
+            .with_persisted_receipts(false);
+        let retry_metrics = scheduler_actor.retry_metrics();
+        let scheduler = spawn_named(scheduler_actor, ()).await;
+        let pending = spawn_named(
+            PendingTransactionActor::new().with_config(PendingConfig {
+                call_deadline: Some(Duration::from_millis(100)),
+                ..PendingConfig::default()
+            }),
+            (),
+        )
+        .await;
+        let (seen_tx, seen) = mpsc::unbounded_channel();
+        let executor = spawn_as(ActorType::Executor, HungExecutor, (seen_tx, confirm)).await;
+        let node = Self {
+            scheduler: scheduler.clone(),
+            pending: pending.clone(),
+            seen,
+            retry_metrics,
+            actors: vec![
+                executor.get_cell(),
+                pending.get_cell(),
+                scheduler.get_cell(),
+            ],
+        };
+        while node.credits().await.granted.is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        node
+    }
+
+    /// Submits `transaction`, returning the receiver of its answer.
+    fn submit(&self, transaction: Transaction) -> oneshot::Receiver<RpcMessage> {
+        let (tx, rx) = oneshot();
+        self.scheduler
+            .cast(SchedulerMessage::Call {
+                transaction: transaction.clone(),
+                rpc_reply: tx.into(),
+            })
+            .unwrap();
+        self.pending
+            .cast(PendingTransactionMessage::NewCall { transaction })
+            .unwrap();
+        rx
+    }
+
+    async fn credits(&self) -> ExecutionCreditStatus {
+        let result = self
+            .pending
+            .call(
+                |reply| PendingTransactionMessage::GetExecutionCredits { reply },
+                Some(Duration::from_secs(5)),
+            )
+            .await
+            .expect("failed to call pending transactions actor");
+        match result {
+            CallResult::Success(status) => status,
+            _ => panic!("pending transactions actor did not answer"),
+        }
+    }
+
+    async fn next_seen(&mut self) -> Seen {
+        tokio::time::timeout(Duration::from_secs(5), self.seen.recv())
+            .await
+            .expect("the executor was handed nothing")
+            .expect("executor went away")
+    }
+
+    async fn stop(self) {
+        stop(self.actors).await;
+    }
+}
+
+async fn answer(rx: oneshot::Receiver<RpcMessage>) -> RpcErrorCode {
+    let reply = tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .expect("call was not answered")
+        .expect("call reply was dropped");
+    match reply {
+        RpcMessage::Response {
+            response: Ok(TransactionResponse::TransactionError(error)),
+            ..
+        } => error.code,
+        other => panic!("expected the call to fail, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn a_hung_call_cancelled_in_time_is_retried_until_it_is_given_up_on() {
+    let mut node = Node::start(true).await;
+    let hung = call(1).hash_string();
+    let rx = node.submit(call(1));
+    // Held back until the hung call gives its credit up.
+    node.pending
+        .cast(PendingTransactionMessage::NewCall {
+            transaction: call(2),
+        })
+        .unwrap();
+
+    assert_eq!(node.next_seen().await, Seen::Exec(hung.clone()));
+    assert_eq!(node.next_seen().await, Seen::Cancel(hung.clone()));
+    // Nothing of it was committed, so it is run again.
+    assert_eq!(node.next_seen().await, Seen::Exec(hung.clone()));
+    assert_eq!(node.next_seen().await, Seen::Cancel(hung.clone()));
+    assert_eq!(answer(rx).await, RpcErrorCode::ExhaustedRetries);
+    assert_eq!(node.retry_metrics.retried(RetryReason::TimedOut), 1);
+
+    // The credit it held is handed to the call waiting for it.
+    assert_eq!(node.next_seen().await, Seen::Exec(call(2).hash_string()));
+    assert_eq!(
+        node.credits().await,
+        ExecutionCreditStatus {
+            granted: Some(1),
+            in_flight: 1,
+            waiting: 0,
+        }
+    );
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_hung_call_not_confirmed_cancelled_is_failed_and_its_late_completion_ignored() {
+    let mut node = Node::start(false).await;
+    let hung = call(1).hash_string();
+    let rx = node.submit(call(1));
+    node.pending
+        .cast(PendingTransactionMessage::NewCall {
+            transaction: call(2),
+        })
+        .unwrap();
+
+    assert_eq!(node.next_seen().await, Seen::Exec(hung.clone()));
+    assert_eq!(node.next_seen().await, Seen::Cancel(hung.clone()));
+    // Its outputs may still be applied, so it is not run again.
+    assert_eq!(answer(rx).await, RpcErrorCode::DeadlineExceeded);
+    assert_eq!(node.next_seen().await, Seen::Exec(call(2).hash_string()));
+    assert_eq!(node.retry_metrics.retried(RetryReason::TimedOut), 0);
+
+    let ignored = "lasr_pending_call_deadline_signals_ignored_total";
+    let before = sample(ignored, "signal=\"completion\"");
+    node.pending
+        .cast(PendingTransactionMessage::ExecSuccess {
+            transaction: call(1),
+        })
+        .unwrap();
+    // The late completion neither hands on a credit nor releases anything.
+    assert_eq!(
+        node.credits().await,
+        ExecutionCreditStatus {
+            granted: Some(1),
+            in_flight: 1,
+            waiting: 0,
+        }
+    );
+    assert_eq!(sample(ignored, "signal=\"completion\""), before + 1.0);
+    assert!(
+        sample(
+            "lasr_pending_call_cancellations_total",
+            "outcome=\"unconfirmed\""
+        ) >= 1.0
+    );
+
+    node.stop().await;
+}
@@ -0,0 +1,141 @@
+#![cfg(test)]
+//! Test coverage for calls the executor fails through no fault of their
+//! sender being handed back and run again.
+
+mod common;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{spawn_as, spawn_named};
+use lasr_actors::{PendingTransactionActor, RetryPolicy, TaskScheduler};
+use lasr_messages::{
+    ActorType, ExecutorMessage, PendingTransactionMessage, RetryReason, RpcMessage,
+    SchedulerMessage, TransactionResponse,
+};
+use lasr_types::{Account, Address, Transaction, TransactionBuilder, TransactionType, U256};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use serial_test::serial;
+
+/// Stands in for the executor, failing the first two runs of a call, once
+/// saturated and once restarting, and applying it on the third.
+struct FlakyExecutor;
+
+#[async_trait]
+impl Actor for FlakyExecutor {
+    type Msg = ExecutorMessage;
+    type State = u32;
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(0)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        runs: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let ExecutorMessage::Exec { transaction } = message else {
+            return Ok(());
+        };
+        *runs += 1;
+        let transaction_hash = transaction.hash_string();
+        let scheduler: ActorRef<SchedulerMessage> =
+            ractor::registry::where_is(ActorType::Scheduler.to_string())
+                .expect("scheduler is not running")
+                .into();
+        let cast = match runs {
+            1 => scheduler.cast(SchedulerMessage::ExecutorSaturated {
+                transaction_hash,
+                queued: 8,
+            }),
+            2 => scheduler.cast(SchedulerMessage::ExecutorRestarting { transaction_hash }),
+            _ => {
+                let pending: ActorRef<PendingTransactionMessage> =
+                    ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+                        .expect("pending transactions actor is not running")
+                        .into();
+                pending
+                    .cast(PendingTransactionMessage::ExecSuccess {
+                        transaction: transaction.clone(),
+                    })
+                    .expect("failed to cast to pending transactions");
+                scheduler.cast(SchedulerMessage::CallTransactionApplied {
+                    transaction_hash,
+                    account: Account::new(transaction.from()),
+                    attestation: None,
+                })
+            }
+        };
+        cast.expect("failed to cast to scheduler");
+        Ok(())
+    }
+}
+
+fn call() -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from([1; 20])
+        .to([3; 20])
+        .program_id([3; 20])
+        .op("run".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(0))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn a_call_failed_twice_transiently_is_applied_on_the_third_run() {
+    let scheduler_actor = TaskScheduler::new().with_retry_policy(RetryPolicy {
+        backoff: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    });
+    let retry_metrics = scheduler_actor.retry_metrics();
+    let scheduler = spawn_named(scheduler_actor, ()).await;
+    let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+    let executor = spawn_as(ActorType::Executor, FlakyExecutor, ()).await;
+
+    let (tx, rx) = ractor::concurrency::oneshot();
+    scheduler
+        .cast(SchedulerMessage::Call {
+            transaction: call(),
+            rpc_reply: tx.into(),
+        })
+        .unwrap();
+    pending
+        .cast(PendingTransactionMessage::NewCall {
+            transaction: call(),
+        })
+        .unwrap();
+
+    let reply = tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .expect("call was not answered")
+        .expect("call reply was dropped");
+    match reply {
+        RpcMessage::Response {
+            response: Ok(TransactionResponse::CallResponse(account)),
+            ..
+        } => assert_eq!(account.owner_address(), Address::new([1; 20])),
+        other => panic!("expected a call receipt, got {other:?}"),
+    }
+    assert_eq!(retry_metrics.retried(RetryReason::Saturated), 1);
+    assert_eq!(retry_metrics.retried(RetryReason::Restarting), 1);
+    assert_eq!(retry_metrics.exhausted(), 0);
+
+    executor.stop_and_wait(None, None).await.unwrap();
+    pending.stop_and_wait(None, None).await.unwrap();
+    scheduler.stop_and_wait(None, None).await.unwrap();
+}
@@ -0,0 +1,258 @@
+#![cfg(test)]
+#![cfg(all(feature = "chaos", feature = "test_harness"))]
+//! Test coverage for what the node guarantees when messages between its
+//! actors are lost, duplicated, delayed or reordered, provoked by the chaos
+//! layer.
+
+use std::time::Duration;
+
+use lasr_actors::{cast_between, AccountCacheActor, ChaosPlan, Fault, TestHarness, VERSE_ADDR};
+use lasr_messages::{AccountCacheMessage, ActorType};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Account, Address, MockPersistenceStore, PersistenceStore, U256};
+use ractor::Actor;
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn other_receiver() -> Address {
+    Address::new([10; 20])
+}
+
+/// Waits for `ready` to hold, for as long as the harness waits for anything.
+async fn wait_until(what: &str, ready: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if ready() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+#[tokio::test]
+#[serial]
+async fn the_same_seed_injects_the_same_faults() {
+    let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+        .await
+        .expect("failed to create mock storage");
+    let (cache, _) = Actor::spawn(None, AccountCacheActor::new(), storage)
+        .await
+        .expect("failed to spawn account cache");
+    let run = |seed| {
+        let chaos = ChaosPlan::new(seed)
+            .with_rule(
+                ActorType::Batcher,
+                ActorType::AccountCache,
+                Fault::Drop,
+                0.5,
+            )
+            .install();
+        for byte in 0..32 {
+            let message = AccountCacheMessage::Write {
+                account: Account::new(Address::new([byte; 20])),
+                who: ActorType::Batcher,
+                location: "chaos test".into(),
+            };
+            cast_between(ActorType::Batcher, ActorType::AccountCache, &cache, message)
+                .expect("failed to cast");
+        }
+        // Messages between any other pair are left alone.
+        let message = AccountCacheMessage::Remove {
+            address: Address::new([0; 20]),
+        };
+        cast_between(ActorType::Engine, ActorType::AccountCache, &cache, message)
+            .expect("failed to cast");
+        chaos.injected()
+    };
+
+    let dropped = run(7);
+    assert!(!dropped.is_empty() && dropped.len() < 32, "{dropped:?}");
+    assert!(dropped
+        .iter()
+        .all(|fault| fault.from == ActorType::Batcher && fault.fault == Fault::Drop));
+    assert_eq!(run(7), dropped);
+    assert_ne!(run(8), dropped);
+
+    cache.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_send_delivered_to_the_engine_twice_is_applied_once() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    let before = harness.account(sender).await.expect("sender is not funded");
+    let chaos = ChaosPlan::new(1)
+        .with_rule(
+            ActorType::Scheduler,
+            ActorType::Engine,
+            Fault::Duplicate,
+            1.0,
+        )
+        .install();
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(25))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(25)
+        })
+        .await
+        .expect("the send was not applied");
+    assert_eq!(chaos.injected().len(), 1);
+
+    // Long enough for the duplicate to have been applied too, if it were.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let after = harness.account(sender).await.unwrap();
+    assert_eq!(after.nonce(), before.nonce() + U256::from(1));
+    assert_eq!(
+        after.balance(&VERSE_ADDR),
+        before.balance(&VERSE_ADDR) - U256::from(25)
+    );
+    assert_eq!(
+        harness
+            .account(receiver())
+            .await
+            .unwrap()
+            .balance(&VERSE_ADDR),
+        U256::from(25)
+    );
+
+    drop(chaos);
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn da_confirmations_out_of_order_finalize_the_batches_they_confirm() {
+    let harness = TestHarness::start_with(|config| config.instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    harness.seal_batch().await.unwrap();
+    let chaos = ChaosPlan::new(2)
+        .with_rule(ActorType::DaClient, ActorType::Batcher, Fault::Reorder, 1.0)
+        .install();
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(5)
+        })
+        .await
+        .expect("the first send was not applied");
+    let first_id = harness.batches().next_batch_id();
+
+    // The first batch's confirmation is held back, until the second batch's
+    // has overtaken it.
+    let (first, second) = tokio::join!(harness.seal_batch(), async {
+        wait_until("the first confirmation to be held", || {
+            !chaos.injected().is_empty()
+        })
+        .await;
+        wallet
+            .send(&other_receiver(), &VERSE_ADDR, U256::from(6))
+            .await
+            .expect("failed to send");
+        harness
+            .wait_for_account(other_receiver(), |account| {
+                account.balance(&VERSE_ADDR) == U256::from(6)
+            })
+            .await
+            .expect("the second send was not applied");
+        harness.seal_batch().await
+    });
+    first.expect("the first batch was not finalized");
+    second.expect("the second batch was not finalized");
+    wait_until("both batches to be finalized", || {
+        harness.batches().next_batch_id() == first_id + 2
+    })
+    .await;
+
+    // Numbered as they were finalized, each with the transaction it was
+    // sealed with.
+    for (batch_id, credited) in [(first_id, other_receiver()), (first_id + 1, receiver())] {
+        let record = harness.batches().get(batch_id).expect("batch is missing");
+        assert_eq!(record.transaction_hashes.len(), 1);
+        let receipt: Value = serde_json::from_str(
+            &rpc.get_transaction_receipt(record.transaction_hashes[0].clone())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(receipt["batchId"], batch_id);
+        assert!(receipt["tokenDeltas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|delta| delta["address"] == credited.to_string()));
+    }
+
+    drop(chaos);
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_delayed_cache_write_never_shows_half_a_transaction() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    let funded = harness
+        .account(sender)
+        .await
+        .expect("sender is not funded")
+        .balance(&VERSE_ADDR);
+    let chaos = ChaosPlan::new(3)
+        .with_rule(
+            ActorType::Batcher,
+            ActorType::AccountCache,
+            Fault::Delay(Duration::from_millis(300)),
+            1.0,
+        )
+        .install();
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(25))
+        .await
+        .expect("failed to send");
+    let debited = |account: Option<Account>| {
+        account.is_some_and(|account| account.balance(&VERSE_ADDR) < funded)
+    };
+    let credited = |account: Option<Account>| {
+        account.is_some_and(|account| account.balance(&VERSE_ADDR) == U256::from(25))
+    };
+    // Read one account and then the other: once the first shows the write,
+    // the second, read after it, must too.
+    let mut written = false;
+    for _ in 0..500 {
+        if credited(harness.account(receiver()).await) {
+            assert!(debited(harness.account(sender).await));
+        }
+        if debited(harness.account(sender).await) {
+            assert!(credited(harness.account(receiver()).await));
+            written = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(written, "the delayed write was never applied");
+    assert!(chaos
+        .injected()
+        .iter()
+        .any(|fault| matches!(fault.fault, Fault::Delay(_))));
+
+    drop(chaos);
+    harness.stop().await;
+}
@@ -0,0 +1,134 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the commit log, putting back after a crash what a node
+//! answered and did not see finalized on the DA layer.
+
+use std::path::{Path, PathBuf};
+
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{
+    Address, PayloadBuilder, RecoverableSignature, Transaction, TransactionType, U256,
+};
+use secp256k1::{Message, Secp256k1, SecretKey};
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).expect("answer is not JSON")
+}
+
+/// Kept outside either harness's directory, so the node started after the
+/// crash opens the log the one that crashed wrote.
+fn commit_log_path() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lasr_commit_log_{}", std::process::id()));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+async fn start(path: &Path) -> TestHarness {
+    TestHarness::start_with(|config| config.instant_seal(false).commit_log_path(path))
+        .await
+        .expect("failed to start node")
+}
+
+/// Sends 5 VERSE to the receiver, returning the send once it is answered.
+async fn send(harness: &TestHarness, secret_key: &SecretKey, from: Address) -> Transaction {
+    let nonce = harness.account(from).await.unwrap().nonce();
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(nonce))
+        .from(from.into())
+        .to(receiver().into())
+        .program_id(VERSE_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(5))
+        .nonce(nonce + U256::from(1))
+        .build()
+        .unwrap();
+    let message = Message::from_digest_slice(&payload.hash()).unwrap();
+    let signature: RecoverableSignature = Secp256k1::new()
+        .sign_ecdsa_recoverable(&message, secret_key)
+        .into();
+    let transaction: Transaction = (payload, signature).into();
+    harness
+        .rpc()
+        .unwrap()
+        .send(transaction.clone())
+        .await
+        .expect("failed to send");
+    transaction
+}
+
+#[tokio::test]
+#[serial]
+async fn sends_answered_before_a_crash_are_sealed_after_it() {
+    let path = commit_log_path();
+    let harness = start(&path).await;
+    let wallet = harness.genesis_wallet().await.unwrap();
+    let first = send(&harness, &wallet.secret_key(), wallet.address()).await;
+    let second = send(&harness, &wallet.secret_key(), wallet.address()).await;
+    // Answered, and gone before the batch requestor sealed them.
+    harness.crash().await;
+
+    let harness = start(&path).await;
+    let received = harness
+        .account(receiver())
+        .await
+        .expect("the sends were not put back");
+    assert_eq!(received.balance(&VERSE_ADDR), U256::from(10));
+
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    assert_eq!(sealed.len(), 1);
+    let mut sent = vec![first.hash_string(), second.hash_string()];
+    sent.sort();
+    assert_eq!(sealed[0].transaction_hashes, sent);
+    // Read back from the DA layer, with the accounts the sends left.
+    let rpc = harness.rpc().unwrap();
+    let info = parse(&rpc.get_batch(sealed[0].batch_id, Some(true)).await.unwrap());
+    assert_eq!(info["status"], "finalized");
+    assert_eq!(
+        info["accounts"].as_array().map(Vec::len),
+        Some(sealed[0].account_count)
+    );
+
+    // Finalized, the sends are not put back again.
+    harness.crash().await;
+    let harness = start(&path).await;
+    assert!(harness.seal_batch().await.unwrap().is_empty());
+    harness.stop().await;
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn only_what_was_not_finalized_is_put_back_after_a_crash() {
+    let path = commit_log_path();
+    let harness = start(&path).await;
+    let wallet = harness.genesis_wallet().await.unwrap();
+    let finalized = send(&harness, &wallet.secret_key(), wallet.address()).await;
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    assert_eq!(sealed[0].transaction_hashes, vec![finalized.hash_string()]);
+    let answered = send(&harness, &wallet.secret_key(), wallet.address()).await;
+    harness.crash().await;
+
+    let harness = start(&path).await;
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    assert_eq!(sealed.len(), 1);
+    assert_eq!(sealed[0].transaction_hashes, vec![answered.hash_string()]);
+    // The accounts put back are as the last send left them.
+    assert_eq!(
+        harness
+            .account(receiver())
+            .await
+            .unwrap()
+            .balance(&VERSE_ADDR),
+        U256::from(10)
+    );
+    harness.stop().await;
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,115 @@
+//! Fixtures shared by the integration tests: the actors and RPC server a
+//! test node is spawned from, clients of it, and the transactions sent.
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use jsonrpsee::{
+    http_client::{HttpClient, HttpClientBuilder},
+    server::{ServerBuilder, ServerHandle},
+    ws_client::{WsClient, WsClientBuilder},
+    Methods,
+};
+use lasr_actors::LasrRpcServerActor;
+use lasr_messages::{ActorName, ActorType, RpcMessage};
+use lasr_types::{
+    Payload, RecoverableSignature, Transaction, TransactionBuilder, TransactionType, U256,
+};
+use ractor::{Actor, ActorCell, ActorRef};
+use secp256k1::SecretKey;
+
+/// Spawns `actor` registered under its own name.
+pub async fn spawn_named<A>(actor: A, arguments: A::Arguments) -> ActorRef<A::Msg>
+where
+    A: Actor + ActorName,
+{
+    let name = actor.name();
+    Actor::spawn(Some(name.clone()), actor, arguments)
+        .await
+        .unwrap_or_else(|e| panic!("failed to spawn {name}: {e}"))
+        .0
+}
+
+/// Spawns `actor` registered as the actor of `actor_type`, which it stands
+/// in for.
+pub async fn spawn_as<A: Actor>(
+    actor_type: ActorType,
+    actor: A,
+    arguments: A::Arguments,
+) -> ActorRef<A::Msg> {
+    Actor::spawn(Some(actor_type.to_string()), actor, arguments)
+        .await
+        .unwrap_or_else(|e| panic!("failed to spawn mock {actor_type}: {e}"))
+        .0
+}
+
+/// Spawns the actor the RPC server hands requests to, unregistered.
+pub async fn rpc_proxy() -> ActorRef<RpcMessage> {
+    Actor::spawn(None, LasrRpcServerActor::new(), ())
+        .await
+        .expect("failed to spawn rpc actor")
+        .0
+}
+
+/// Serves `methods` on a random local port.
+pub async fn serve(methods: impl Into<Methods>) -> (ServerHandle, SocketAddr) {
+    let server = ServerBuilder::default()
+        .build("127.0.0.1:0")
+        .await
+        .expect("failed to build rpc server");
+    let addr = server.local_addr().expect("server has no address");
+    (server.start(methods), addr)
+}
+
+pub fn http_client(addr: SocketAddr) -> HttpClient {
+    HttpClientBuilder::default()
+        .build(format!("http://{addr}"))
+        .expect("failed to build rpc client")
+}
+
+pub async fn ws_client(addr: SocketAddr) -> WsClient {
+    WsClientBuilder::default()
+        .build(format!("ws://{addr}"))
+        .await
+        .expect("failed to connect to rpc server")
+}
+
+/// Stops `actors` in order, waiting for each.
+pub async fn stop(actors: Vec<ActorCell>) {
+    for actor in actors {
+        actor.stop_and_wait(None, None).await.ok();
+    }
+}
+
+/// An unsigned send of 1 from `[1; 20]` to `[2; 20]` with `nonce`.
+pub fn send(nonce: u64) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(0)))
+        .from([1; 20])
+        .to([2; 20])
+        .program_id([0; 20])
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(nonce))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+/// `payload` signed with `secret_key`.
+pub fn signed(payload: Payload, secret_key: &SecretKey) -> Transaction {
+    let signature = RecoverableSignature::sign(&payload.digest(), secret_key);
+    (payload, signature).into()
+}
+
+/// Seconds since the epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
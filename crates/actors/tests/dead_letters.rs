@@ -0,0 +1,342 @@
+#![cfg(test)]
+//! Test coverage for calls parked in the dead letters once their retries
+//! are exhausted, and listed, re-driven and purged by operators.
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{rpc_proxy, serve, signed, spawn_as, spawn_named, stop, ws_client};
+use jsonrpsee::{core::ClientError, server::ServerHandle, ws_client::WsClient};
+use lasr_actors::{
+    DeadLetters, LasrRpcServerImpl, PendingTransactionActor, RetryPolicy, TaskScheduler,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{
+    ActorType, EngineMessage, ExecutorMessage, PendingTransactionMessage, RpcErrorCode,
+    SchedulerMessage,
+};
+use lasr_rpc::{AdminRpcClient, AdminRpcServer, LasrRpcClient, LasrRpcServer};
+use lasr_types::{Account, Address, PayloadBuilder, Transaction, TransactionType, U256};
+use ractor::{Actor, ActorCell, ActorProcessingErr, ActorRef};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::Value;
+use serial_test::serial;
+
+/// Stands in for the engine, handing calls to the pending transactions.
+struct ForwardingEngine;
+
+#[async_trait]
+impl Actor for ForwardingEngine {
+    type Msg = EngineMessage;
+    type State = ();
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        _: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let EngineMessage::Call { transaction } = message {
+            let pending: ActorRef<PendingTransactionMessage> =
+                ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+                    .expect("pending transactions actor is not running")
+                    .into();
+            pending
+                .cast(PendingTransactionMessage::NewCall { transaction })
+                .expect("failed to cast to pending transactions");
+        }
+        Ok(())
+    }
+}
+
+/// Stands in for the executor, saturated until it is told it has room, and
+/// applying every call after.
+struct SaturatedExecutor;
+
+#[async_trait]
+impl Actor for SaturatedExecutor {
+    type Msg = ExecutorMessage;
+    type State = Arc<AtomicBool>;
+    type Arguments = Arc<AtomicBool>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        has_room: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(has_room)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        has_room: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let ExecutorMessage::Exec { transaction } = message else {
+            return Ok(());
+        };
+        let transaction_hash = transaction.hash_string();
+        let scheduler: ActorRef<SchedulerMessage> =
+            ractor::registry::where_is(ActorType::Scheduler.to_string())
+                .expect("scheduler is not running")
+                .into();
+        if !has_room.load(Ordering::SeqCst) {
+            scheduler
+                .cast(SchedulerMessage::ExecutorSaturated {
+                    transaction_hash,
+                    queued: 8,
+                })
+                .expect("failed to cast to scheduler");
+            return Ok(());
+        }
+        let pending: ActorRef<PendingTransactionMessage> =
+            ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+                .expect("pending transactions actor is not running")
+                .into();
+        pending
+            .cast(PendingTransactionMessage::ExecSuccess {
+                transaction: transaction.clone(),
+            })
+            .expect("failed to cast to pending transactions");
+        scheduler
+            .cast(SchedulerMessage::CallTransactionApplied {
+                transaction_hash,
+                account: Account::new(transaction.from()),
+                attestation: None,
+            })
+            .expect("failed to cast to scheduler");
+        Ok(())
+    }
+}
+
+fn call(nonce: u64) -> Transaction {
+    let secret_key = SecretKey::from_slice(&[7; 32]).unwrap();
+    let from: Address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from(from.into())
+        .to([3; 20])
+        .program_id([3; 20])
+        .inputs(String::new())
+        .op("run".to_string())
+        .value(U256::from(0))
+        .nonce(U256::from(nonce))
+        .build()
+        .expect("failed to build payload");
+    signed(payload, &secret_key)
+}
+
+struct Node {
+    client: WsClient,
+    has_room: Arc<AtomicBool>,
+    dead_letters: DeadLetters,
+    handle: ServerHandle,
+    actors: Vec<ActorCell>,
+}
+
+impl Node {
+    async fn start() -> Self {
+        let dead_letters = DeadLetters::default();
+        let scheduler = spawn_named(
+            TaskScheduler::new()
+                .with_dead_letters(dead_letters.clone())
+                .with_retry_policy(RetryPolicy {
+                    max_attempts: 1,
+                    backoff: Duration::from_millis(10),
+                })
+                .with_persisted_receipts(false),
+            (),
+        )
+        .await;
+        let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+        let engine = spawn_as(ActorType::Engine, ForwardingEngine, ()).await;
+        let has_room = Arc::new(AtomicBool::new(false));
+        let executor = spawn_as(
+            ActorType::Executor,
+            SaturatedExecutor,
+            Arc::clone(&has_room),
+        )
+        .await;
+        let proxy = rpc_proxy().await;
+
+        let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_dead_letters(dead_letters.clone());
+        let mut methods = LasrRpcServer::into_rpc(rpc.clone());
+        methods
+            .merge(AdminRpcServer::into_rpc(rpc))
+            .expect("failed to merge admin methods");
+        let (handle, addr) = serve(methods).await;
+        let client = ws_client(addr).await;
+
+        Self {
+            client,
+            has_room,
+            dead_letters,
+            handle,
+            actors: vec![
+                proxy.get_cell(),
+                executor.get_cell(),
+                engine.get_cell(),
+                pending.get_cell(),
+                scheduler.get_cell(),
+            ],
+        }
+    }
+
+    /// Submits `transaction`, which the saturated executor never runs.
+    async fn exhaust(&self, transaction: Transaction) {
+        match self.client.call(transaction).await {
+            Err(ClientError::Call(error)) => {
+                assert_eq!(
+                    error.code(),
+                    RpcErrorCode::ExhaustedRetries.code(),
+                    "{error:?}"
+                )
+            }
+            other => panic!("expected the call to exhaust its retries, got {other:?}"),
+        }
+    }
+
+    async fn status(&self, transaction_hash: &str) -> Value {
+        let receipt = self
+            .client
+            .get_transaction_receipt(transaction_hash.to_string())
+            .await
+            .expect("failed to get receipt");
+        serde_json::from_str::<Value>(&receipt).unwrap()["status"].clone()
+    }
+
+    async fn dead_letters(&self) -> Value {
+        serde_json::from_str(
+            &self
+                .client
+                .get_dead_letters(None, None)
+                .await
+                .expect("failed to list dead letters"),
+        )
+        .unwrap()
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn a_call_that_exhausts_its_retries_is_parked_and_re_driven() {
+    let node = Node::start().await;
+    let transaction = call(0);
+    let transaction_hash = transaction.hash_string();
+    node.exhaust(transaction.clone()).await;
+
+    let status = node.status(&transaction_hash).await;
+    assert_eq!(status["status"], "failed");
+    assert_eq!(status["reason"], "exhaustedRetries");
+
+    let letters = node.dead_letters().await;
+    assert_eq!(letters["total"], 1);
+    let letter = &letters["items"][0];
+    assert_eq!(letter["transactionHash"], transaction_hash.as_str());
+    let failures = letter["failures"]
+        .as_array()
+        .expect("letter has no failures");
+    assert_eq!(failures.len(), 2);
+    assert!(failures
+        .iter()
+        .all(|failure| failure["reason"] == "saturated"));
+    assert!(letter["firstFailedAt"].as_u64() <= letter["deadLetteredAt"].as_u64());
+
+    // Once the executor has room, the call re-driven is applied.
+    node.has_room.store(true, Ordering::SeqCst);
+    let redriven: Value = serde_json::from_str(
+        &node
+            .client
+            .redrive_dead_letter(transaction_hash.clone())
+            .await
+            .expect("failed to re-drive the call"),
+    )
+    .unwrap();
+    assert_eq!(redriven["transactionHash"], transaction_hash.as_str());
+    assert!(node.dead_letters.is_empty());
+    let mut status = node.status(&transaction_hash).await;
+    for _ in 0..100 {
+        if status["status"] == "included" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        status = node.status(&transaction_hash).await;
+    }
+    assert_eq!(status["status"], "included", "{status}");
+    assert_eq!(node.dead_letters().await["total"], 0);
+
+    // A call no longer parked is not re-driven again.
+    match node.client.redrive_dead_letter(transaction_hash).await {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::NotFound.code(), "{error:?}")
+        }
+        other => panic!("expected the call not to be found, got {other:?}"),
+    }
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn purged_letters_are_dropped_one_or_all_at_once() {
+    let node = Node::start().await;
+    for nonce in 0..3 {
+        node.exhaust(call(nonce)).await;
+    }
+    assert_eq!(node.dead_letters().await["total"], 3);
+
+    let purged: Value = serde_json::from_str(
+        &node
+            .client
+            .purge_dead_letters(Some(call(1).hash_string()))
+            .await
+            .expect("failed to purge a dead letter"),
+    )
+    .unwrap();
+    assert_eq!(purged["purged"], 1);
+    let hashes: Vec<String> = node
+        .dead_letters
+        .list()
+        .into_iter()
+        .map(|letter| letter.transaction_hash)
+        .collect();
+    assert_eq!(hashes, vec![call(0).hash_string(), call(2).hash_string()]);
+    // A purged call is forgotten rather than failed.
+    assert_eq!(
+        node.status(&call(1).hash_string()).await["status"],
+        "unknown"
+    );
+
+    let purged: Value = serde_json::from_str(
+        &node
+            .client
+            .purge_dead_letters(None)
+            .await
+            .expect("failed to purge the dead letters"),
+    )
+    .unwrap();
+    assert_eq!(purged["purged"], 2);
+    assert_eq!(node.dead_letters().await["total"], 0);
+
+    node.stop().await;
+}
@@ -0,0 +1,192 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for session keys, signing an account's transactions within
+//! what its owner delegated to them.
+
+use std::collections::BTreeSet;
+
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{
+    Address, Delegation, PayloadBuilder, RecoverableSignature, Transaction, TransactionType, U256,
+};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn session_key() -> (SecretKey, Address) {
+    let secret_key = SecretKey::from_slice(&[21; 32]).unwrap();
+    let address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    (secret_key, address)
+}
+
+/// Up to 10 VERSE a send, for any number of sends.
+fn delegation(delegate: Address) -> Delegation {
+    Delegation {
+        delegate,
+        program_ids: BTreeSet::from([VERSE_ADDR]),
+        max_value: U256::from(10),
+        expires_at_nonce: None,
+        expires_at: None,
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn a_session_key_sends_from_the_owners_account_within_its_delegation() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut owner = harness.wallet().await.expect("failed to fund wallet");
+    let (secret_key, delegate) = session_key();
+    owner
+        .delegate(delegation(delegate))
+        .await
+        .expect("failed to delegate");
+    let account = harness.account(owner.address()).await.unwrap();
+    assert_eq!(account.delegation(&delegate), Some(&delegation(delegate)));
+    let nonce = account.nonce();
+
+    let mut session = owner.clone().with_session_key(secret_key);
+    session
+        .send(&receiver(), &VERSE_ADDR, U256::from(10))
+        .await
+        .expect("a send within the delegation was refused");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(10)
+        })
+        .await
+        .expect("the send was not applied");
+    // The send is the owner's, with the owner's next nonce.
+    assert_eq!(
+        harness.account(owner.address()).await.unwrap().nonce(),
+        nonce + U256::from(1)
+    );
+
+    let error = session
+        .send(&receiver(), &VERSE_ADDR, U256::from(11))
+        .await
+        .expect_err("a send over the delegated value was taken");
+    assert!(
+        error
+            .to_string()
+            .contains("outside what its sender delegated"),
+        "{error}"
+    );
+    assert!(
+        error.to_string().contains("over the 10 delegated"),
+        "{error}"
+    );
+
+    let error = session
+        .call(
+            &Address::new([0xcc; 20]),
+            &receiver(),
+            U256::from(0),
+            &"play".to_string(),
+            &String::new(),
+        )
+        .await
+        .expect_err("a call of a program not delegated was taken");
+    assert!(
+        error.to_string().contains("is not one of those delegated"),
+        "{error}"
+    );
+
+    // Nor may the session key hand on, or take back, what it was given.
+    let error = session
+        .revoke(&delegate)
+        .await
+        .expect_err("the delegate revoked its own delegation");
+    assert!(
+        error.to_string().contains("only sends and calls"),
+        "{error}"
+    );
+
+    // A key delegated nothing is held to the owner's signature as before.
+    let stranger = SecretKey::from_slice(&[22; 32]).unwrap();
+    let error = owner
+        .clone()
+        .with_session_key(stranger)
+        .send(&receiver(), &VERSE_ADDR, U256::from(1))
+        .await
+        .expect_err("a send signed by a stranger was taken");
+    assert!(error.to_string().contains("not its sender"), "{error}");
+    assert_eq!(
+        harness
+            .account(receiver())
+            .await
+            .unwrap()
+            .balance(&VERSE_ADDR),
+        U256::from(10)
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn a_revocation_refuses_the_delegates_transactions_pending_behind_it() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut owner = harness.wallet().await.expect("failed to fund wallet");
+    let (secret_key, delegate) = session_key();
+    owner
+        .delegate(delegation(delegate))
+        .await
+        .expect("failed to delegate");
+    let nonce = harness.account(owner.address()).await.unwrap().nonce();
+
+    // Signed by the session key with the nonce after the next, the send is
+    // held until the owner's revocation takes the next.
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(nonce))
+        .from(owner.address().into())
+        .to(receiver().into())
+        .program_id(VERSE_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(5))
+        .nonce(nonce + U256::from(2))
+        .build()
+        .unwrap();
+    let message = Message::from_digest_slice(&payload.hash()).unwrap();
+    let signature: RecoverableSignature = Secp256k1::new()
+        .sign_ecdsa_recoverable(&message, &secret_key)
+        .into();
+    let pending: Transaction = (payload, signature).into();
+    let hash = pending.hash_string();
+    let rpc = harness.rpc().unwrap();
+    let held = tokio::spawn(async move { rpc.send(pending).await });
+    let rpc = harness.rpc().unwrap();
+    let address = owner.address().to_full_string();
+    let mut waited = 0;
+    while !rpc
+        .get_pending_transactions(address.clone(), None, None)
+        .await
+        .unwrap()
+        .contains(&hash)
+    {
+        waited += 1;
+        assert!(waited < 100, "the delegate's send was not held");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    owner.revoke(&delegate).await.expect("failed to revoke");
+    let error = held
+        .await
+        .unwrap()
+        .expect_err("the delegate's send was applied after the revocation");
+    assert!(error.to_string().contains("not its sender"), "{error}");
+
+    let account = harness.account(owner.address()).await.unwrap();
+    assert!(account.delegations().is_empty());
+    assert_eq!(account.nonce(), nonce + U256::from(1));
+    assert_eq!(
+        harness
+            .account(receiver())
+            .await
+            .map(|account| account.balance(&VERSE_ADDR))
+            .unwrap_or_default(),
+        U256::from(0)
+    );
+}
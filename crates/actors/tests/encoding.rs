@@ -0,0 +1,282 @@
+#![cfg(test)]
+//! Test coverage for the envelopes the node stores and disperses, round
+//! tripping every core type through both encodings and pinning the borsh
+//! layout to the byte vectors checked in under `fixtures/borsh`. Accounts in
+//! the layouts they were stored in before are under `fixtures/bincode` and
+//! the `_v2` fixture of `fixtures/borsh`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use lasr_actors::{
+    encoding::{self, Encoding, EncodingError},
+    AccountValue, Batch,
+};
+use lasr_messages::{RpcErrorCode, RpcResponseError, TransactionResponse};
+use lasr_types::{
+    Account, AccountBuilder, AccountDigest, AccountType, Address, ArbitraryData, Delegation,
+    DelegationOp, ExecutionAttestation, Metadata, ProgramEvent, RecoverableSignatureBuilder,
+    Status, Token, TokenBuilder, TokenDelta, Transaction, TransactionBuilder, TransactionType,
+    U256,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+const ENCODINGS: [Encoding; 2] = [Encoding::Bincode, Encoding::Borsh];
+
+fn program_id() -> Address {
+    Address::new([0xaa; 20])
+}
+
+fn alice() -> Address {
+    Address::new([1; 20])
+}
+
+fn bob() -> Address {
+    Address::new([2; 20])
+}
+
+fn fixture(hex: &str) -> Vec<u8> {
+    hex::decode(hex.trim()).expect("fixture is not hex")
+}
+
+fn token() -> Token {
+    let mut metadata = Metadata::new();
+    metadata.insert("symbol".to_string(), "TKN".to_string());
+    let mut data = ArbitraryData::new();
+    data.insert("k".to_string(), "v".to_string());
+    TokenBuilder::default()
+        .program_id(program_id())
+        .owner_id(alice())
+        .balance(U256::from(1_000))
+        .metadata(metadata)
+        .token_ids(vec![U256::from(7)])
+        .allowance(BTreeMap::from([(bob(), U256::from(5))]))
+        .approvals(BTreeMap::from([(bob(), vec![U256::from(7)])]))
+        .data(data)
+        .status(Status::Free)
+        .build()
+        .unwrap()
+}
+
+fn account() -> Account {
+    let delegation = Delegation {
+        delegate: bob(),
+        program_ids: BTreeSet::from([program_id()]),
+        max_value: U256::from(10),
+        expires_at_nonce: Some(U256::from(9)),
+        expires_at: None,
+    };
+    AccountBuilder::default()
+        .account_type(AccountType::User)
+        .program_namespace(None)
+        .owner_address(alice())
+        .programs(BTreeMap::from([(program_id(), token())]))
+        .nonce(U256::from(3))
+        .program_account_data(ArbitraryData::new())
+        .program_account_metadata(Metadata::new())
+        .program_account_linked_programs(BTreeSet::new())
+        .delegations(BTreeMap::from([(bob(), delegation)]))
+        .try_build()
+        .unwrap()
+}
+
+fn transaction() -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(3)))
+        .from(alice().into())
+        .to(bob().into())
+        .program_id(program_id().into())
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(5))
+        .nonce(U256::from(4))
+        .max_fee(Some(U256::from(2)))
+        .v(1)
+        .r([0x11; 32])
+        .s([0x22; 32])
+        .build()
+        .unwrap()
+}
+
+fn batch() -> Batch {
+    let mut batch = Batch::new();
+    batch
+        .insert_transaction_with_events(transaction(), vec![ProgramEvent::new("sent")])
+        .unwrap();
+    batch.insert_account(account()).unwrap();
+    batch
+}
+
+fn attestation() -> ExecutionAttestation {
+    ExecutionAttestation {
+        transaction_hash: transaction().hash_string(),
+        input_accounts: vec![
+            AccountDigest::new(alice(), Some(&account())),
+            AccountDigest::new(bob(), None),
+        ],
+        token_deltas: vec![TokenDelta {
+            address: bob(),
+            program_id: program_id(),
+            before: U256::from(0),
+            after: U256::from(5),
+        }],
+        trace_hash: "0x00".to_string(),
+        signer: alice(),
+        signature: RecoverableSignatureBuilder::default()
+            .r([0x33; 32])
+            .s([0x44; 32])
+            .v(0)
+            .build()
+            .unwrap(),
+    }
+}
+
+fn round_trip<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + BorshSerialize + BorshDeserialize + PartialEq + Debug,
+{
+    for format in ENCODINGS {
+        let bytes = format.encode(value).unwrap();
+        assert_eq!(Encoding::of(&bytes), Some(format));
+        assert_eq!(&encoding::decode::<T>(&bytes).unwrap(), value, "{format}");
+    }
+}
+
+#[test]
+fn every_core_type_round_trips_through_both_encodings() {
+    round_trip(&token());
+    round_trip(&account());
+    round_trip(&AccountValue { account: account() });
+    round_trip(&Account::new_program(program_id(), bob()));
+    round_trip(&transaction());
+    round_trip(&Transaction::default());
+    round_trip(&attestation());
+    round_trip(&ProgramEvent::new("sent"));
+
+    for response in [
+        TransactionResponse::SendResponse(token()),
+        TransactionResponse::GetAccountResponse(account()),
+        TransactionResponse::TransactionError(RpcResponseError {
+            code: RpcErrorCode::InvalidParams,
+            description: "no".to_string(),
+        }),
+        TransactionResponse::AlreadyIncluded(Box::new(TransactionResponse::Expired)),
+    ] {
+        for format in ENCODINGS {
+            let bytes = format.encode(&response).unwrap();
+            let decoded = encoding::decode::<TransactionResponse>(&bytes).unwrap();
+            assert_eq!(format!("{decoded:?}"), format!("{response:?}"));
+        }
+    }
+
+    for format in ENCODINGS {
+        let bytes = format.encode(&batch()).unwrap();
+        let decoded = encoding::decode::<Batch>(&bytes).unwrap();
+        assert_eq!(decoded.transactions(), batch().transactions());
+        assert_eq!(decoded.accounts(), batch().accounts());
+        assert_eq!(decoded.events(), batch().events());
+    }
+}
+
+#[test]
+fn the_borsh_layout_is_pinned_by_the_fixtures() {
+    let token_bytes = fixture(include_str!("fixtures/borsh/token.hex"));
+    assert_eq!(Encoding::Borsh.encode(&token()).unwrap(), token_bytes);
+    assert_eq!(encoding::decode::<Token>(&token_bytes).unwrap(), token());
+
+    let account_bytes = fixture(include_str!("fixtures/borsh/account.hex"));
+    assert_eq!(Encoding::Borsh.encode(&account()).unwrap(), account_bytes);
+    assert_eq!(
+        encoding::decode::<Account>(&account_bytes).unwrap(),
+        account()
+    );
+
+    let transaction_bytes = fixture(include_str!("fixtures/borsh/transaction.hex"));
+    assert_eq!(
+        Encoding::Borsh.encode(&transaction()).unwrap(),
+        transaction_bytes
+    );
+    assert_eq!(
+        encoding::decode::<Transaction>(&transaction_bytes).unwrap(),
+        transaction()
+    );
+
+    // The envelope of a batch, before it is compressed to be dispersed.
+    let batch_bytes = fixture(include_str!("fixtures/borsh/batch.hex"));
+    assert_eq!(Encoding::Borsh.encode(&batch()).unwrap(), batch_bytes);
+    let decoded = encoding::decode::<Batch>(&batch_bytes).unwrap();
+    assert_eq!(decoded.transactions(), batch().transactions());
+    assert_eq!(decoded.accounts(), batch().accounts());
+}
+
+#[test]
+fn a_dispersed_batch_is_read_back_in_the_encoding_it_was_written_in() {
+    for format in ENCODINGS {
+        let encoded = batch().with_encoding(format).encode_batch().unwrap();
+        let decoded = Batch::decode_batch(&encoded).expect("failed to decode batch");
+        assert_eq!(decoded.encoding(), format);
+        assert_eq!(decoded.transactions(), batch().transactions());
+        assert_eq!(decoded.accounts(), batch().accounts());
+        assert_eq!(decoded.events(), batch().events());
+    }
+}
+
+#[test]
+fn values_stored_before_there_were_envelopes_are_still_read() {
+    let value = AccountValue { account: account() };
+    let bare = bincode::serialize(&value).unwrap();
+    assert_eq!(Encoding::of(&bare), None);
+    assert_eq!(encoding::decode::<AccountValue>(&bare).unwrap(), value);
+
+    let bare = bincode::serialize(&attestation()).unwrap();
+    assert_eq!(Encoding::of(&bare), None);
+    assert_eq!(
+        encoding::decode::<ExecutionAttestation>(&bare).unwrap(),
+        attestation()
+    );
+
+    assert!(matches!(
+        encoding::decode::<AccountValue>(&[]),
+        Err(EncodingError::Empty)
+    ));
+    assert!(encoding::decode::<AccountValue>(&[Encoding::Borsh.format_byte(), 7]).is_err());
+}
+
+#[test]
+fn accounts_stored_before_fields_were_appended_to_them_are_still_read() {
+    // As the node first stored them, bare and with addresses and numbers as
+    // hex strings, before auth programs.
+    let bare = fixture(include_str!("fixtures/bincode/account_v0.hex"));
+    assert!(encoding::decode::<AccountValue>(&bare).is_err());
+    let mut undelegated = account();
+    undelegated
+        .apply_delegation_op(DelegationOp::Revoke(bob()))
+        .unwrap();
+    assert_eq!(AccountValue::decode(&bare).unwrap().account, undelegated);
+
+    // In envelopes, with delegations but before account metadata.
+    for bytes in [
+        fixture(include_str!("fixtures/bincode/account_v2.hex")),
+        fixture(include_str!("fixtures/borsh/account_v2.hex")),
+    ] {
+        assert!(encoding::decode::<AccountValue>(&bytes).is_err());
+        assert_eq!(AccountValue::decode(&bytes).unwrap().account, account());
+    }
+
+    for format in ENCODINGS {
+        let value = AccountValue { account: account() };
+        let bytes = format.encode(&value).unwrap();
+        assert_eq!(AccountValue::decode(&bytes).unwrap(), value);
+        assert!(AccountValue::decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
+
+#[test]
+fn encodings_are_named_as_they_are_configured() {
+    for format in ENCODINGS {
+        assert_eq!(format.to_string().parse::<Encoding>(), Ok(format));
+    }
+    assert_eq!("BORSH".parse::<Encoding>(), Ok(Encoding::Borsh));
+    assert!("json".parse::<Encoding>().is_err());
+}
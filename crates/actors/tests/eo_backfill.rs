@@ -0,0 +1,294 @@
+#![cfg(test)]
+//! Test coverage for backfilling settlement events over blocks already
+//! scanned, and resuming a backfill after a restart.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eo_listener::{
+    BackfillStatus, ContractFilterConfig, EoServer, EoServerBuilder, EventFilterConfig,
+    EventFilters, FailoverProvider,
+};
+use jsonrpsee::{
+    server::{ServerBuilder, ServerHandle},
+    types::ErrorObjectOwned,
+    RpcModule,
+};
+use serde_json::{json, Value};
+use web3::ethabi::Token;
+use web3::types::{H160, H256, U256, U64};
+
+const CONTRACT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+/// A chain read over http, with bridge deposits from two contracts.
+#[derive(Default)]
+struct Chain {
+    head: Mutex<u64>,
+    logs: Mutex<Vec<(u64, Value)>>,
+}
+
+impl Chain {
+    fn deposit(&self, contract: H160, number: u64, bridge_event_id: u64) {
+        let data = web3::ethabi::encode(&[
+            Token::Uint(100.into()),
+            Token::Uint(0.into()),
+            Token::String("fungible".to_string()),
+            Token::Uint(bridge_event_id.into()),
+        ]);
+        let mut logs = self.logs.lock().unwrap();
+        let log_index = logs.len();
+        logs.push((
+            number,
+            json!({
+                "address": contract,
+                "topics": [bridge_topic(), H256::from(H160::repeat_byte(1)), H256::zero()],
+                "data": format!("0x{}", hex::encode(data)),
+                "blockHash": H256::from_low_u64_be(number + 1),
+                "blockNumber": U64::from(number),
+                "transactionHash": H256::from_low_u64_be(number),
+                "transactionIndex": U64::zero(),
+                "logIndex": U256::from(log_index),
+                "removed": false,
+            }),
+        ));
+    }
+
+    async fn serve(self: &Arc<Self>) -> (SocketAddr, ServerHandle) {
+        let mut module = RpcModule::new(Arc::clone(self));
+        module
+            .register_method("eth_blockNumber", |_, chain| {
+                Ok::<_, ErrorObjectOwned>(U64::from(*chain.head.lock().unwrap()))
+            })
+            .expect("failed to register eth_blockNumber");
+        module
+            .register_method("eth_getLogs", |params, chain| {
+                let filter: Value = params.sequence().next()?;
+                let block = |field: &str| {
+                    serde_json::from_value::<U64>(filter[field].clone())
+                        .expect("not a block number")
+                        .as_u64()
+                };
+                let (from, to) = (block("fromBlock"), block("toBlock"));
+                let filter = filter.to_string();
+                Ok::<_, ErrorObjectOwned>(
+                    chain
+                        .logs
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .filter(|(number, log)| {
+                            (from..=to).contains(number)
+                                && filter.contains(log["address"].as_str().unwrap())
+                                && filter.contains(log["topics"][0].as_str().unwrap())
+                        })
+                        .map(|(_, log)| log.clone())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .expect("failed to register eth_getLogs");
+        // Checkpoints record the hash of the block they were saved at.
+        module
+            .register_method("eth_getBlockByNumber", |params, _| {
+                let number: U64 = params.sequence().next()?;
+                Ok::<_, ErrorObjectOwned>(block(number.as_u64()))
+            })
+            .expect("failed to register eth_getBlockByNumber");
+        let server = ServerBuilder::default()
+            .build("127.0.0.1:0")
+            .await
+            .expect("failed to build mock node");
+        let addr = server.local_addr().expect("mock node has no address");
+        (addr, server.start(module))
+    }
+}
+
+fn block(number: u64) -> Value {
+    json!({
+        "hash": H256::from_low_u64_be(number + 1),
+        "parentHash": H256::from_low_u64_be(number),
+        "sha3Uncles": H256::zero(),
+        "miner": H160::zero(),
+        "stateRoot": H256::zero(),
+        "transactionsRoot": H256::zero(),
+        "receiptsRoot": H256::zero(),
+        "number": U64::from(number),
+        "gasUsed": U256::zero(),
+        "gasLimit": U256::zero(),
+        "extraData": "0x",
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "timestamp": U256::from(number),
+        "difficulty": U256::zero(),
+        "mixHash": H256::zero(),
+        "nonce": "0x0000000000000000",
+        "uncles": [],
+        "transactions": [],
+    })
+}
+
+fn bridge_topic() -> H256 {
+    eo_listener::get_bridge_event_topic().expect("no bridge topic")[0]
+}
+
+fn second() -> H160 {
+    H160::repeat_byte(0xb2)
+}
+
+/// Deposits from the Executable Oracle, and from a second contract that was
+/// only watched from block 90 on.
+fn chain() -> Arc<Chain> {
+    let primary: H160 = CONTRACT.parse().unwrap();
+    let chain = Arc::new(Chain::default());
+    *chain.head.lock().unwrap() = 100;
+    for (number, bridge_event_id) in [(10, 1), (40, 2), (70, 3)] {
+        chain.deposit(primary, number, bridge_event_id);
+    }
+    for (number, bridge_event_id) in [(15, 1), (45, 2), (95, 3)] {
+        chain.deposit(second(), number, bridge_event_id);
+    }
+    chain
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("eo_backfill_{}_{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn eo_server(addr: SocketAddr, path: &Path, log_range: u64) -> EoServer {
+    let provider =
+        FailoverProvider::from_urls(&[format!("http://{addr}")]).expect("invalid provider url");
+    let web3 = provider.primary().clone();
+    let abi = eo_listener::get_abi().expect("invalid abi");
+    let contract = web3::contract::Contract::new(
+        web3.eth(),
+        CONTRACT.parse().expect("invalid contract address"),
+        abi.clone(),
+    );
+    let filters = EventFilterConfig {
+        contracts: vec![ContractFilterConfig {
+            address: eo_listener::to_checksummed(&second()),
+            events: vec!["Bridge".to_string()],
+            from_block: Some(90),
+        }],
+    };
+    EoServerBuilder::default()
+        .web3(web3)
+        .provider(provider)
+        .eo_address(eo_listener::EoAddress::new(CONTRACT))
+        .block_time(Duration::from_millis(200))
+        .bridge_processed_blocks(Default::default())
+        .settled_processed_blocks(Default::default())
+        .contract(contract)
+        .bridge_topic(eo_listener::get_bridge_event_topic())
+        .blob_settled_topic(eo_listener::get_blob_index_settled_topic())
+        .current_bridge_filter_block(0.into())
+        .current_blob_settlement_filter_block(0.into())
+        .blob_settled_event(abi.event("BlobIndexSettled").unwrap().clone())
+        .bridge_event(abi.event("Bridge").unwrap().clone())
+        .path(path.to_path_buf())
+        .log_range(log_range)
+        .event_filters(EventFilters::new(
+            filters.validate().expect("invalid filters"),
+        ))
+        .build()
+        .expect("failed to build eo server")
+}
+
+/// Calls `next` until `done`, saving a checkpoint after each call as the
+/// listener does, and returns the bridge event ids delivered.
+async fn run_until(server: &mut EoServer, mut done: impl FnMut(&EoServer) -> bool) -> Vec<u64> {
+    let mut delivered = Vec::new();
+    for _ in 0..100 {
+        if done(server) {
+            return delivered;
+        }
+        let logs = server.next().await.log_result.expect("scan failed");
+        delivered.extend(logs.iter().map(|log| {
+            log.params
+                .iter()
+                .find(|param| param.name == "bridgeEventId")
+                .and_then(|param| param.value.clone().into_uint())
+                .expect("deposit has no bridge event id")
+                .as_u64()
+        }));
+        server.save_checkpoint().await.expect("checkpoint failed");
+    }
+    panic!("eo server did not get there in time");
+}
+
+fn backfilled(server: &EoServer) -> bool {
+    !server.settlement_backfill().is_running()
+}
+
+#[tokio::test]
+async fn backfill_hands_on_only_events_not_delivered_before() {
+    let (addr, node) = chain().serve().await;
+    let dir = temp_dir("applied");
+    let mut server = eo_server(addr, &dir.join("blocks_processed.dat"), 1000);
+
+    let mut calls = 0;
+    let mut delivered = run_until(&mut server, |_| {
+        calls += 1;
+        calls > 8
+    })
+    .await;
+    delivered.sort();
+    assert_eq!(delivered, vec![1, 2, 3, 3]);
+
+    let backfill = server.settlement_backfill();
+    backfill.start(0, 100).expect("backfill was not started");
+    assert!(backfill.start(0, 100).is_err());
+
+    // Only the second contract's deposits from before it was watched.
+    assert_eq!(run_until(&mut server, backfilled).await, vec![1, 2]);
+    let progress = backfill.progress().expect("no backfill progress");
+    assert_eq!(progress.status, BackfillStatus::Completed);
+    assert_eq!((progress.applied, progress.skipped), (2, 4));
+    assert_eq!(progress.scanned_through(), Some(100));
+
+    node.stop().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn an_interrupted_backfill_resumes_where_it_got_to() {
+    let (addr, node) = chain().serve().await;
+    let dir = temp_dir("resumed");
+    let path = dir.join("blocks_processed.dat");
+    let mut server = eo_server(addr, &path, 30);
+    server.settlement_backfill().start(0, 100).unwrap();
+
+    // Stopped once every event has had its first range backfilled.
+    run_until(&mut server, |server| {
+        server
+            .settlement_backfill()
+            .progress()
+            .is_some_and(|progress| progress.scanned_through() >= Some(29))
+    })
+    .await;
+    let interrupted = server.settlement_backfill().progress().unwrap();
+    assert_eq!(interrupted.status, BackfillStatus::Running);
+    drop(server);
+
+    let mut server = eo_server(addr, &path, 30);
+    server.resume().await.expect("unable to resume");
+    assert_eq!(
+        server.settlement_backfill().progress(),
+        Some(interrupted.clone())
+    );
+
+    run_until(&mut server, backfilled).await;
+    let progress = server.settlement_backfill().progress().unwrap();
+    assert_eq!(progress.status, BackfillStatus::Completed);
+    assert!(progress
+        .cursors
+        .values()
+        .all(|next_block| *next_block == 101));
+    assert!(progress.applied + progress.skipped > interrupted.applied + interrupted.skipped);
+
+    node.stop().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
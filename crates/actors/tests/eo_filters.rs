@@ -0,0 +1,229 @@
+#![cfg(test)]
+//! Test coverage for reloading the contracts and events the eo_server
+//! watches, and the backfill of those added.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eo_listener::{
+    ContractFilterConfig, EoServer, EoServerBuilder, EventFilterConfig, FailoverProvider,
+};
+use jsonrpsee::{
+    server::{ServerBuilder, ServerHandle},
+    types::ErrorObjectOwned,
+    RpcModule,
+};
+use serde_json::{json, Value};
+use web3::ethabi::Token;
+use web3::types::{H160, H256, U256, U64};
+
+const CONTRACT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+/// A chain read over http, with bridge deposits from several contracts.
+#[derive(Default)]
+struct Chain {
+    head: Mutex<u64>,
+    logs: Mutex<Vec<(u64, Value)>>,
+}
+
+impl Chain {
+    fn log(&self, contract: H160, number: u64, topics: Vec<H256>, data: Vec<u8>) {
+        let mut logs = self.logs.lock().unwrap();
+        let log_index = logs.len();
+        logs.push((
+            number,
+            json!({
+                "address": contract,
+                "topics": topics,
+                "data": format!("0x{}", hex::encode(data)),
+                "blockHash": H256::from_low_u64_be(number + 1),
+                "blockNumber": U64::from(number),
+                "transactionHash": H256::from_low_u64_be(number),
+                "transactionIndex": U64::zero(),
+                "logIndex": U256::from(log_index),
+                "removed": false,
+            }),
+        ));
+    }
+
+    fn deposit(&self, contract: H160, number: u64, bridge_event_id: u64) {
+        let data = web3::ethabi::encode(&[
+            Token::Uint(100.into()),
+            Token::Uint(0.into()),
+            Token::String("fungible".to_string()),
+            Token::Uint(bridge_event_id.into()),
+        ]);
+        self.log(
+            contract,
+            number,
+            vec![
+                bridge_topic(),
+                H256::from(H160::repeat_byte(1)),
+                H256::zero(),
+            ],
+            data,
+        );
+    }
+
+    async fn serve(self: &Arc<Self>) -> (SocketAddr, ServerHandle) {
+        let mut module = RpcModule::new(Arc::clone(self));
+        module
+            .register_method("eth_blockNumber", |_, chain| {
+                Ok::<_, ErrorObjectOwned>(U64::from(*chain.head.lock().unwrap()))
+            })
+            .expect("failed to register eth_blockNumber");
+        module
+            .register_method("eth_getLogs", |params, chain| {
+                let filter: Value = params.sequence().next()?;
+                let block = |field: &str| {
+                    serde_json::from_value::<U64>(filter[field].clone())
+                        .expect("not a block number")
+                        .as_u64()
+                };
+                let (from, to) = (block("fromBlock"), block("toBlock"));
+                let filter = filter.to_string();
+                Ok::<_, ErrorObjectOwned>(
+                    chain
+                        .logs
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .filter(|(number, log)| {
+                            (from..=to).contains(number)
+                                && filter.contains(log["address"].as_str().unwrap())
+                                && filter.contains(log["topics"][0].as_str().unwrap())
+                        })
+                        .map(|(_, log)| log.clone())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .expect("failed to register eth_getLogs");
+        let server = ServerBuilder::default()
+            .build("127.0.0.1:0")
+            .await
+            .expect("failed to build mock node");
+        let addr = server.local_addr().expect("mock node has no address");
+        (addr, server.start(module))
+    }
+}
+
+fn bridge_topic() -> H256 {
+    eo_listener::get_bridge_event_topic().expect("no bridge topic")[0]
+}
+
+fn eo_server(addr: SocketAddr) -> EoServer {
+    let provider =
+        FailoverProvider::from_urls(&[format!("http://{addr}")]).expect("invalid provider url");
+    let web3 = provider.primary().clone();
+    let abi = eo_listener::get_abi().expect("invalid abi");
+    let contract = web3::contract::Contract::new(
+        web3.eth(),
+        CONTRACT.parse().expect("invalid contract address"),
+        abi.clone(),
+    );
+    EoServerBuilder::default()
+        .web3(web3)
+        .provider(provider)
+        .eo_address(eo_listener::EoAddress::new(CONTRACT))
+        .block_time(Duration::from_millis(200))
+        .bridge_processed_blocks(Default::default())
+        .settled_processed_blocks(Default::default())
+        .contract(contract)
+        .bridge_topic(eo_listener::get_bridge_event_topic())
+        .blob_settled_topic(eo_listener::get_blob_index_settled_topic())
+        .current_bridge_filter_block(0.into())
+        .current_blob_settlement_filter_block(0.into())
+        .blob_settled_event(abi.event("BlobIndexSettled").unwrap().clone())
+        .bridge_event(abi.event("Bridge").unwrap().clone())
+        .path(std::env::temp_dir().join("eo_filters_blocks_processed.dat"))
+        .backfill_blocks(50)
+        .build()
+        .expect("failed to build eo server")
+}
+
+/// Calls `next` enough times for every cursor to reach the head, returning
+/// the bridge event ids delivered.
+async fn drain(server: &mut EoServer) -> Vec<u64> {
+    let mut delivered = Vec::new();
+    for _ in 0..8 {
+        let logs = server.next().await.log_result.expect("scan failed");
+        delivered.extend(logs.iter().map(|log| {
+            log.params
+                .iter()
+                .find(|param| param.name == "bridgeEventId")
+                .and_then(|param| param.value.clone().into_uint())
+                .expect("deposit has no bridge event id")
+                .as_u64()
+        }));
+    }
+    delivered
+}
+
+fn watching(address: &str, from_block: Option<u64>) -> ContractFilterConfig {
+    ContractFilterConfig {
+        address: address.to_string(),
+        events: vec!["Bridge".to_string()],
+        from_block,
+    }
+}
+
+#[tokio::test]
+async fn reloaded_contracts_are_backfilled_and_removed_ones_dropped() {
+    let primary: H160 = CONTRACT.parse().unwrap();
+    let second = H160::repeat_byte(0xb2);
+    let third = H160::repeat_byte(0xc3);
+    let chain = Arc::new(Chain::default());
+    *chain.head.lock().unwrap() = 100;
+    chain.deposit(primary, 10, 1);
+    // Further back than the backfill limit.
+    chain.deposit(second, 20, 2);
+    chain.deposit(second, 95, 3);
+    // Matches the bridge filter, but is missing the indexed parameters.
+    chain.log(second, 96, vec![bridge_topic()], Vec::new());
+    chain.deposit(second, 98, 4);
+    chain.deposit(third, 80, 5);
+    chain.deposit(third, 92, 6);
+    let (addr, node) = chain.serve().await;
+    let mut server = eo_server(addr);
+    let filters = server.event_filters();
+
+    assert_eq!(drain(&mut server).await, vec![1]);
+
+    let reload = filters
+        .reload(&EventFilterConfig {
+            contracts: vec![watching(&eo_listener::to_checksummed(&second), Some(0))],
+        })
+        .expect("valid filters were rejected");
+    assert_eq!(reload.added.len(), 1);
+    assert!(reload.removed.is_empty());
+    assert_eq!(drain(&mut server).await, vec![3, 4]);
+    assert_eq!(filters.unknown_events(), 1);
+
+    // Swapped for the third contract, backfilled from its configured block.
+    let reload = filters
+        .reload(&EventFilterConfig {
+            contracts: vec![watching(&eo_listener::to_checksummed(&third), Some(90))],
+        })
+        .expect("valid filters were rejected");
+    assert_eq!((reload.added.len(), reload.removed.len()), (1, 1));
+    assert_eq!(drain(&mut server).await, vec![6]);
+
+    *chain.head.lock().unwrap() = 101;
+    // Each contract numbers its own deposits.
+    chain.deposit(second, 101, 5);
+    chain.deposit(third, 101, 7);
+    chain.deposit(primary, 101, 2);
+    let mut delivered = drain(&mut server).await;
+    delivered.sort();
+    assert_eq!(delivered, vec![2, 7]);
+
+    // Filters that fail validation leave the set as it was.
+    assert!(filters
+        .reload(&EventFilterConfig {
+            contracts: vec![watching(&format!("{second:?}"), None)],
+        })
+        .is_err());
+    assert_eq!(filters.current().1.watched[0].address, third);
+    node.stop().ok();
+}
@@ -0,0 +1,293 @@
+#![cfg(test)]
+//! Test coverage for the eo_server's subscription mode against a mock
+//! settlement layer node that drops the websocket connection mid-stream.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eo_listener::{EoServer, EoServerBuilder, FailoverProvider, Subscription, WsSubscriber};
+use jsonrpsee::{
+    core::SubscriptionResult,
+    server::{RandomStringIdProvider, ServerBuilder, ServerHandle},
+    types::ErrorObjectOwned,
+    RpcModule, SubscriptionMessage,
+};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use web3::ethabi::Token;
+use web3::types::{H160, H256, U256, U64};
+
+const CONTRACT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+#[derive(Clone, Debug)]
+enum Pushed {
+    Head(Value),
+    Log(Value),
+}
+
+/// A chain that only has blocks mined when the test says so, with a bridge
+/// deposit in some of them.
+struct Chain {
+    head: Mutex<u64>,
+    logs: Mutex<Vec<(u64, Value)>>,
+    pushed: broadcast::Sender<Pushed>,
+}
+
+fn hash(number: u64) -> H256 {
+    H256::from_low_u64_be(number + 1)
+}
+
+fn header(number: u64) -> Value {
+    json!({
+        "hash": hash(number),
+        "parentHash": hash(number.saturating_sub(1)),
+        "sha3Uncles": H256::zero(),
+        "miner": H160::zero(),
+        "stateRoot": H256::zero(),
+        "transactionsRoot": H256::zero(),
+        "receiptsRoot": H256::zero(),
+        "number": U64::from(number),
+        "gasUsed": U256::zero(),
+        "gasLimit": U256::zero(),
+        "extraData": "0x",
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "timestamp": U256::from(number),
+        "difficulty": U256::zero(),
+        "mixHash": H256::zero(),
+        "nonce": "0x0000000000000000",
+    })
+}
+
+fn deposit(number: u64, bridge_event_id: u64) -> Value {
+    let topic = eo_listener::get_bridge_event_topic().expect("no bridge topic")[0];
+    let data = web3::ethabi::encode(&[
+        Token::Uint(100.into()),
+        Token::Uint(0.into()),
+        Token::String("fungible".to_string()),
+        Token::Uint(bridge_event_id.into()),
+    ]);
+    json!({
+        "address": CONTRACT.parse::<H160>().expect("invalid contract address"),
+        "topics": [topic, H256::from(H160::repeat_byte(1)), H256::zero()],
+        "data": format!("0x{}", hex::encode(data)),
+        "blockHash": hash(number),
+        "blockNumber": U64::from(number),
+        "transactionHash": H256::from_low_u64_be(bridge_event_id),
+        "transactionIndex": U64::zero(),
+        "logIndex": U256::zero(),
+        "removed": false,
+    })
+}
+
+fn quantity(value: &Value) -> u64 {
+    serde_json::from_value::<U64>(value.clone())
+        .expect("not a quantity")
+        .as_u64()
+}
+
+impl Chain {
+    fn new() -> Arc<Self> {
+        let (pushed, _) = broadcast::channel(64);
+        Arc::new(Self {
+            head: Mutex::new(0),
+            logs: Mutex::new(Vec::new()),
+            pushed,
+        })
+    }
+
+    /// Mines the next block, with a deposit of `bridge_event_id` in it if
+    /// given, and pushes it to any subscribers.
+    fn mine(&self, bridge_event_id: Option<u64>) -> u64 {
+        let number = {
+            let mut head = self.head.lock().unwrap();
+            *head += 1;
+            *head
+        };
+        if let Some(bridge_event_id) = bridge_event_id {
+            let log = deposit(number, bridge_event_id);
+            self.logs.lock().unwrap().push((number, log.clone()));
+            self.pushed.send(Pushed::Log(log)).ok();
+        }
+        self.pushed.send(Pushed::Head(header(number))).ok();
+        number
+    }
+
+    fn module(self: &Arc<Self>) -> RpcModule<Arc<Chain>> {
+        let mut module = RpcModule::new(Arc::clone(self));
+        module
+            .register_method("eth_blockNumber", |_, chain| {
+                Ok::<_, ErrorObjectOwned>(U64::from(*chain.head.lock().unwrap()))
+            })
+            .expect("failed to register eth_blockNumber");
+        module
+            .register_method("eth_getLogs", |params, chain| {
+                let filter: Value = params.sequence().next()?;
+                let (from, to) = (quantity(&filter["fromBlock"]), quantity(&filter["toBlock"]));
+                Ok::<_, ErrorObjectOwned>(
+                    chain
+                        .logs
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .filter(|(number, _)| (from..=to).contains(number))
+                        .filter(|(_, log)| {
+                            filter
+                                .to_string()
+                                .contains(log["topics"][0].as_str().unwrap())
+                        })
+                        .map(|(_, log)| log.clone())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .expect("failed to register eth_getLogs");
+        module
+            .register_subscription(
+                "eth_subscribe",
+                "eth_subscription",
+                "eth_unsubscribe",
+                |params, pending, chain| async move {
+                    let mut params = params.sequence();
+                    let kind: String = params.next()?;
+                    let topics = if kind == "logs" {
+                        Some(params.next::<Value>()?["topics"].to_string())
+                    } else {
+                        None
+                    };
+                    let mut pushed = chain.pushed.subscribe();
+                    let sink = pending.accept().await?;
+                    while let Ok(event) = pushed.recv().await {
+                        let value = match (&event, &topics) {
+                            (Pushed::Head(header), None) => header,
+                            (Pushed::Log(log), Some(topics))
+                                if topics.contains(log["topics"][0].as_str().unwrap()) =>
+                            {
+                                log
+                            }
+                            _ => continue,
+                        };
+                        sink.send(SubscriptionMessage::from_json(value)?).await?;
+                    }
+                    SubscriptionResult::Ok(())
+                },
+            )
+            .expect("failed to register eth_subscribe");
+        module
+    }
+
+    async fn serve(self: &Arc<Self>, addr: SocketAddr) -> (SocketAddr, ServerHandle) {
+        let server = ServerBuilder::default()
+            .set_id_provider(RandomStringIdProvider::new(16))
+            .build(addr)
+            .await
+            .expect("failed to build mock node");
+        let addr = server.local_addr().expect("mock node has no address");
+        (addr, server.start(self.module()))
+    }
+}
+
+fn eo_server(addr: SocketAddr, path: &std::path::Path) -> EoServer {
+    let url = format!("http://{addr}");
+    let provider = FailoverProvider::from_urls(&[url]).expect("invalid provider url");
+    let web3 = provider.primary().clone();
+    let abi = eo_listener::get_abi().expect("invalid abi");
+    let contract = web3::contract::Contract::new(
+        web3.eth(),
+        CONTRACT.parse().expect("invalid contract address"),
+        abi.clone(),
+    );
+    EoServerBuilder::default()
+        .web3(web3)
+        .provider(provider)
+        .eo_address(eo_listener::EoAddress::new(CONTRACT))
+        .block_time(Duration::from_millis(200))
+        .bridge_processed_blocks(Default::default())
+        .settled_processed_blocks(Default::default())
+        .contract(contract)
+        .bridge_topic(eo_listener::get_bridge_event_topic())
+        .blob_settled_topic(eo_listener::get_blob_index_settled_topic())
+        .current_bridge_filter_block(0.into())
+        .current_blob_settlement_filter_block(0.into())
+        .blob_settled_event(abi.event("BlobIndexSettled").unwrap().clone())
+        .bridge_event(abi.event("Bridge").unwrap().clone())
+        .path(path.to_path_buf())
+        .subscription(Some(Subscription::new(WsSubscriber::new(format!(
+            "ws://{addr}"
+        )))))
+        .build()
+        .expect("failed to build eo server")
+}
+
+/// Calls `next` until `done`, collecting the bridge event ids delivered.
+async fn run_until(
+    server: &mut EoServer,
+    delivered: &mut Vec<u64>,
+    done: impl Fn(&EoServer, &[u64]) -> bool,
+) {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while !done(server, delivered) {
+            match server.next().await.log_result {
+                Ok(logs) => delivered.extend(logs.iter().map(|log| {
+                    log.params
+                        .iter()
+                        .find(|param| param.name == "bridgeEventId")
+                        .and_then(|param| param.value.clone().into_uint())
+                        .expect("deposit has no bridge event id")
+                        .as_u64()
+                })),
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        }
+    })
+    .await
+    .expect("eo server did not get there in time");
+}
+
+#[tokio::test]
+async fn resubscribes_and_queries_for_logs_missed_while_disconnected() {
+    let chain = Chain::new();
+    chain.mine(None);
+    chain.mine(Some(1));
+    chain.mine(None);
+    let (addr, node) = chain.serve("127.0.0.1:0".parse().unwrap()).await;
+    let dir = std::env::temp_dir().join(format!("eo_subscription_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut server = eo_server(addr, &dir.join("blocks_processed.dat"));
+    let mut delivered = Vec::new();
+
+    // Blocks before the subscription are queried.
+    run_until(&mut server, &mut delivered, |server, _| server.is_live()).await;
+    assert_eq!(delivered, vec![1]);
+
+    // Then deposits are pushed as they are mined.
+    chain.mine(Some(2));
+    chain.mine(None);
+    run_until(&mut server, &mut delivered, |_, delivered| {
+        delivered.len() == 2
+    })
+    .await;
+    assert_eq!(delivered, vec![1, 2]);
+
+    // The connection drops, and a deposit is mined while it is down.
+    node.stop().unwrap();
+    node.stopped().await;
+    chain.mine(Some(3));
+    chain.mine(None);
+    run_until(&mut server, &mut delivered, |server, _| !server.is_live()).await;
+    let (_, node) = chain.serve(addr).await;
+
+    // The missed deposit is queried once resubscribed, and pushing resumes.
+    run_until(&mut server, &mut delivered, |server, delivered| {
+        server.is_live() && delivered.len() == 3
+    })
+    .await;
+    chain.mine(Some(4));
+    run_until(&mut server, &mut delivered, |_, delivered| {
+        delivered.len() == 4
+    })
+    .await;
+
+    assert_eq!(delivered, vec![1, 2, 3, 4]);
+    node.stop().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
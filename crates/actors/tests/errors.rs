@@ -0,0 +1,138 @@
+#![cfg(test)]
+//! Test coverage for how the errors of each part of the node are classified
+//! once converted to a `LasrError`, as retriable or not and by the RPC error
+//! code they are answered with.
+
+use lasr_actors::{
+    AccountCacheError, AdmissionError, BatcherError, DaClientError, EngineError,
+    ExecutionPoolError, InputError, OutputValidationError, PendingTransactionError,
+    ValidatorCoreError, ValidatorError,
+};
+use lasr_compute::{ExecutionError, ResourceKind};
+use lasr_messages::{CacheError, LasrError, MempoolFull, RetryReason, RpcErrorCode, WalletError};
+use lasr_types::{Address, AddressOrNamespace, Transaction, U256};
+
+fn classify(e: impl Into<LasrError>) -> (bool, RpcErrorCode) {
+    let e = e.into();
+    (e.is_retriable(), e.error_code())
+}
+
+#[test]
+fn cache_failures_are_retriable_only_while_the_cache_is_unreachable() {
+    assert_eq!(
+        classify(AccountCacheError::RactorRegistryError),
+        (true, RpcErrorCode::Internal)
+    );
+
+    let missing = AccountCacheError::FailedAccountAcquisition {
+        addr: Address::new([1; 20]),
+    };
+    assert_eq!(
+        LasrError::from(missing.clone()),
+        LasrError::Cache(CacheError::AccountNotFound(Address::new([1; 20])))
+    );
+    assert_eq!(classify(missing), (false, RpcErrorCode::NotFound));
+}
+
+#[test]
+fn a_transaction_the_batcher_cannot_apply_is_not_retried() {
+    let failed = BatcherError::FailedTransaction {
+        msg: "insufficient balance".to_string(),
+        txn: Box::new(Transaction::default()),
+    };
+    assert_eq!(classify(failed), (false, RpcErrorCode::ProgramReverted));
+    assert!(LasrError::from(BatcherError::RactorRegistryError).is_retriable());
+}
+
+#[test]
+fn an_unreachable_da_client_is_retried() {
+    assert_eq!(
+        classify(DaClientError::RactorRegistryError),
+        (true, RpcErrorCode::Internal)
+    );
+    assert!(!LasrError::from(DaClientError::Custom("bad blob".to_string())).is_retriable());
+}
+
+#[test]
+fn a_reverted_program_is_not_retried() {
+    let reverted = EngineError::ProgramReverted {
+        program_id: Address::new([2; 20]).to_full_string(),
+        reason: OutputValidationError::Malformed("not JSON".to_string()),
+    };
+    assert_eq!(classify(reverted), (false, RpcErrorCode::ProgramReverted));
+
+    let update = ValidatorError::ValidatorCoreError(ValidatorCoreError::FailedProgramUpdate {
+        program_account: AddressOrNamespace::This,
+        reason: "not the owner".to_string(),
+    });
+    assert_eq!(classify(update), (false, RpcErrorCode::ProgramReverted));
+    assert!(LasrError::from(ValidatorError::RactorRegistryError).is_retriable());
+}
+
+#[test]
+fn a_saturated_executor_is_retried_and_a_breached_limit_is_not() {
+    let saturated = LasrError::from(ExecutionPoolError::Saturated { queued: 8 });
+    assert!(saturated.is_retriable());
+    let LasrError::Executor(e) = &saturated else {
+        panic!("{saturated:?} is not an executor error");
+    };
+    assert_eq!(e.retry_reason(), Some(RetryReason::Saturated));
+
+    let breached = ExecutionError::ResourceExceeded {
+        kind: ResourceKind::Memory,
+        limit: 1024,
+    };
+    assert_eq!(classify(breached), (false, RpcErrorCode::ProgramReverted));
+}
+
+#[test]
+fn a_full_mempool_is_retried_and_a_used_nonce_is_not() {
+    let full = PendingTransactionError::MempoolFull(MempoolFull {
+        transactions: 10,
+        max_transactions: 10,
+        bytes: 0,
+        max_bytes: 1024,
+        over_quota: false,
+    });
+    assert_eq!(classify(full), (true, RpcErrorCode::MempoolFull));
+
+    let used = PendingTransactionError::NonceTooLow {
+        nonce: U256::from(1),
+        next: U256::from(2),
+    };
+    assert_eq!(classify(used), (false, RpcErrorCode::InvalidNonce));
+    assert!(LasrError::from(PendingTransactionError::RactorRegistryError).is_retriable());
+
+    let rejected = AdmissionError::NonceTooLow {
+        nonce: U256::from(1),
+        next: U256::from(2),
+    };
+    assert_eq!(classify(rejected), (false, RpcErrorCode::InvalidNonce));
+    let oversized = InputError::TooLarge { size: 2, limit: 1 };
+    assert_eq!(classify(oversized), (false, RpcErrorCode::InvalidParams));
+}
+
+#[test]
+fn a_wallet_that_cannot_reach_the_node_retries() {
+    assert_eq!(
+        classify(WalletError::Unreachable("connection refused".to_string())),
+        (true, RpcErrorCode::Internal)
+    );
+    let refused = WalletError::Refused {
+        code: RpcErrorCode::RateLimited,
+        reason: "slow down".to_string(),
+    };
+    assert_eq!(classify(refused), (true, RpcErrorCode::RateLimited));
+    assert!(!LasrError::from(WalletError::Signing("no key".to_string())).is_retriable());
+}
+
+#[test]
+fn boxed_errors_are_kept_as_unclassified() {
+    let boxed: Box<dyn std::error::Error + Send> = Box::new(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "disk on fire",
+    ));
+    let e = LasrError::from(boxed);
+    assert_eq!(e, LasrError::Custom("disk on fire".to_string()));
+    assert_eq!(classify(e), (false, RpcErrorCode::Internal));
+}
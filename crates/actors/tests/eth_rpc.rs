@@ -0,0 +1,350 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for the `eth` methods, driven by an ethers provider.
+
+mod common;
+
+use std::collections::{BTreeMap, HashSet};
+
+use async_trait::async_trait;
+use common::{rpc_proxy, serve, signed, spawn_as, spawn_named, stop};
+use ethers::{
+    providers::{Http, Middleware, Provider, ProviderError, RpcError as _},
+    signers::{LocalWallet, Signer},
+    types::{transaction::eip2718::TypedTransaction, Bytes, TransactionRequest, H256, U256, U64},
+};
+use jsonrpsee::{
+    server::ServerHandle,
+    types::error::{INVALID_PARAMS_CODE, METHOD_NOT_FOUND_CODE},
+};
+use lasr_actors::{
+    AccountCacheActor, BatchReceipts, LasrRpcServerImpl, PendingTransactionActor,
+    TransactionInclusions, ETH_ADDR,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{
+    AccountCacheMessage, ActorType, BatchHeader, BatchStatus, RpcMessage, SchedulerMessage,
+    TransactionResponse,
+};
+use lasr_rpc::{EthRpcServer, LasrRpcServer};
+use lasr_types::{
+    Account, Address, ArbitraryData, Metadata, MockPersistenceStore, PayloadBuilder,
+    PersistenceStore, Status, Token, TokenBuilder, Transaction, TransactionType,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serial_test::serial;
+
+const CHAIN_ID: u64 = 31337;
+
+/// Stands in for the scheduler, applying every send it is given at once.
+struct MockScheduler;
+
+#[async_trait]
+impl Actor for MockScheduler {
+    type Msg = SchedulerMessage;
+    type State = HashSet<String>;
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(HashSet::new())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        applied: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            SchedulerMessage::Send {
+                transaction,
+                rpc_reply,
+                ..
+            } => {
+                applied.insert(transaction.hash_string());
+                rpc_reply
+                    .send(RpcMessage::Response {
+                        response: Ok(TransactionResponse::SendResponse(token(1))),
+                        reply: None,
+                    })
+                    .ok();
+            }
+            SchedulerMessage::GetReceipt {
+                transaction_hash,
+                reply,
+            } => {
+                let receipt = applied
+                    .contains(&transaction_hash)
+                    .then(|| TransactionResponse::SendResponse(token(1)));
+                reply.send(receipt).ok();
+            }
+            SchedulerMessage::GetAttestation { reply, .. } => {
+                reply.send(None).ok();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn token(balance: u64) -> Token {
+    TokenBuilder::default()
+        .program_id(ETH_ADDR)
+        .owner_id(address(&sender()))
+        .balance(lasr_types::U256::from(balance))
+        .metadata(Metadata::new())
+        .token_ids(Vec::new())
+        .allowance(BTreeMap::new())
+        .approvals(BTreeMap::new())
+        .data(ArbitraryData::new())
+        .status(Status::Free)
+        .build()
+        .unwrap()
+}
+
+fn sender() -> SecretKey {
+    SecretKey::from_slice(&[1; 32]).unwrap()
+}
+
+fn address(secret_key: &SecretKey) -> Address {
+    PublicKey::from_secret_key(&Secp256k1::new(), secret_key).into()
+}
+
+/// A send of 1 wei from the holder of `sender()`, at nonce 4.
+fn send() -> Transaction {
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(lasr_types::U256::from(0)))
+        .from(address(&sender()).into())
+        .to([2; 20])
+        .program_id(ETH_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(lasr_types::U256::from(1))
+        .nonce(lasr_types::U256::from(4))
+        .build()
+        .expect("failed to build payload");
+    signed(payload, &sender())
+}
+
+/// `transaction` in an Ethereum transaction signed by `wallet`.
+fn envelope(transaction: &Transaction, wallet: &LocalWallet) -> Bytes {
+    let envelope: TypedTransaction = TransactionRequest::new()
+        .data(serde_json::to_vec(transaction).unwrap())
+        .nonce(0)
+        .gas(0)
+        .gas_price(0)
+        .chain_id(wallet.chain_id())
+        .into();
+    let signature = wallet.sign_transaction_sync(&envelope).unwrap();
+    envelope.rlp_signed(&signature)
+}
+
+fn wallet(secret_key: &[u8; 32], chain_id: u64) -> LocalWallet {
+    LocalWallet::from_bytes(secret_key)
+        .unwrap()
+        .with_chain_id(chain_id)
+}
+
+struct Node {
+    actors: Vec<ractor::ActorCell>,
+    handle: ServerHandle,
+    provider: Provider<Http>,
+    inclusions: TransactionInclusions,
+    receipts: BatchReceipts,
+}
+
+impl Node {
+    /// Starts a node whose account cache holds the sender's account, with
+    /// 1000 wei at nonce 3.
+    async fn start() -> Self {
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .unwrap();
+        let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+        let mut account = Account::new(address(&sender()));
+        account.programs_mut().insert(ETH_ADDR, token(1000));
+        for _ in 0..3 {
+            account.increment_nonce();
+        }
+        account_cache
+            .cast(AccountCacheMessage::Write {
+                account,
+                who: ActorType::Engine,
+                location: "test".to_string(),
+            })
+            .unwrap();
+        let scheduler = spawn_as(ActorType::Scheduler, MockScheduler, ()).await;
+        let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+        let proxy = rpc_proxy().await;
+
+        let inclusions = TransactionInclusions::default();
+        let receipts = BatchReceipts::default();
+        let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_eth_chain_id(CHAIN_ID)
+            .with_transaction_inclusions(inclusions.clone())
+            .with_batch_receipts(receipts.clone());
+        let mut module = LasrRpcServer::into_rpc(rpc.clone());
+        module.merge(EthRpcServer::into_rpc(rpc)).unwrap();
+        let (handle, addr) = serve(module).await;
+        Self {
+            actors: vec![
+                proxy.get_cell(),
+                pending.get_cell(),
+                scheduler.get_cell(),
+                account_cache.get_cell(),
+            ],
+            handle,
+            provider: Provider::<Http>::try_from(format!("http://{addr}")).unwrap(),
+            inclusions,
+            receipts,
+        }
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+fn error_code(error: ProviderError) -> i64 {
+    error
+        .as_error_response()
+        .unwrap_or_else(|| panic!("not an error response: {error:?}"))
+        .code
+}
+
+#[tokio::test]
+#[serial]
+async fn accounts_are_answered_in_ethereum_quantities() {
+    let node = Node::start().await;
+    let holder = address(&sender()).into();
+
+    assert_eq!(node.provider.get_chainid().await.unwrap(), CHAIN_ID.into());
+    assert_eq!(
+        node.provider.get_balance(holder, None).await.unwrap(),
+        U256::from(1000)
+    );
+    assert_eq!(
+        node.provider
+            .get_transaction_count(holder, None)
+            .await
+            .unwrap(),
+        U256::from(3)
+    );
+    // Quantities are as short as they can be, not zero-padded.
+    let balance: String = node
+        .provider
+        .request("eth_getBalance", (holder, "latest"))
+        .await
+        .unwrap();
+    assert_eq!(balance, "0x3e8");
+    let chain_id: String = node.provider.request("eth_chainId", ()).await.unwrap();
+    assert_eq!(chain_id, "0x7a69");
+    // An address with no account holds nothing.
+    assert_eq!(
+        node.provider
+            .get_balance(ethers::types::Address::repeat_byte(9), None)
+            .await
+            .unwrap(),
+        U256::zero()
+    );
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn methods_with_no_lasr_meaning_are_not_found() {
+    let node = Node::start().await;
+
+    let error = node.provider.get_block_number().await.unwrap_err();
+    assert_eq!(error_code(error), i64::from(METHOD_NOT_FOUND_CODE));
+    let error = node.provider.get_gas_price().await.unwrap_err();
+    assert_eq!(error_code(error), i64::from(METHOD_NOT_FOUND_CODE));
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_raw_transaction_is_sent_and_its_receipt_follows_its_batch() {
+    let node = Node::start().await;
+    let transaction = send();
+    let transaction_hash = H256::from_slice(&transaction.hash());
+    assert_eq!(
+        node.provider
+            .get_transaction_receipt(transaction_hash)
+            .await
+            .unwrap(),
+        None
+    );
+
+    let pending = node
+        .provider
+        .send_raw_transaction(envelope(&transaction, &wallet(&[1; 32], CHAIN_ID)))
+        .await
+        .expect("raw transaction was turned away");
+    assert_eq!(pending.tx_hash(), transaction_hash);
+
+    // As the batcher does once it has applied the transaction.
+    node.inclusions.applied(&transaction, Vec::new());
+    let receipt = node
+        .provider
+        .get_transaction_receipt(transaction_hash)
+        .await
+        .unwrap()
+        .expect("no receipt for an applied transaction");
+    assert_eq!(receipt.transaction_hash, transaction_hash);
+    assert_eq!(receipt.status, Some(U64::from(1)));
+    assert_eq!(receipt.from, address(&sender()).into());
+    assert_eq!(receipt.to, Some(Address::new([2; 20]).into()));
+    assert_eq!(receipt.block_number, None);
+
+    let header = BatchHeader {
+        batch_id: 7,
+        state_root: H256::repeat_byte(0x01),
+        transactions_root: H256::zero(),
+        events_root: H256::zero(),
+        blob_commitment: H256::repeat_byte(0x02),
+        blob_index: 3,
+        withdrawals: Vec::new(),
+    };
+    node.inclusions
+        .batched([&transaction.hash_string()], header.batch_id);
+    node.receipts.set(&header, BatchStatus::Finalized);
+    let receipt = node
+        .provider
+        .get_transaction_receipt(transaction_hash)
+        .await
+        .unwrap()
+        .expect("no receipt for a batched transaction");
+    assert_eq!(receipt.block_number, Some(U64::from(7)));
+    assert_eq!(receipt.block_hash, Some(H256::repeat_byte(0x02)));
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn raw_transactions_not_from_the_sender_for_this_chain_are_turned_away() {
+    let node = Node::start().await;
+    let transaction = send();
+
+    for raw in [
+        // Signed by someone other than the sender.
+        envelope(&transaction, &wallet(&[2; 32], CHAIN_ID)),
+        // Signed for another chain.
+        envelope(&transaction, &wallet(&[1; 32], 1)),
+        Bytes::from(vec![1, 2, 3]),
+    ] {
+        let error = node.provider.send_raw_transaction(raw).await.unwrap_err();
+        assert_eq!(error_code(error), i64::from(INVALID_PARAMS_CODE));
+    }
+
+    node.stop().await;
+}
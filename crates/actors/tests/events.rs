@@ -0,0 +1,153 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the events programs emit, looked up by program and
+//! topic, streamed to subscribers, and carried in their call's receipt.
+
+use std::time::Duration;
+
+use ethereum_types::H256;
+use jsonrpsee::ws_client::WsClientBuilder;
+use lasr_actors::{Batcher, EmittedEvent, Page, TestHarness};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{
+    Address, EventFilter, OutputsBuilder, ProgramEvent, TransactionBuilder, TransactionType, U256,
+};
+use serde_json::Value;
+use serial_test::serial;
+
+fn program_id() -> Address {
+    Address::new([0xee; 20])
+}
+
+async fn events(
+    harness: &TestHarness,
+    filter: EventFilter,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Page<EmittedEvent> {
+    let page = harness
+        .rpc()
+        .unwrap()
+        .get_events(Some(filter), cursor, limit)
+        .await
+        .expect("failed to look up events");
+    serde_json::from_str(&page).unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn events_are_looked_up_streamed_and_put_in_the_receipt_of_their_call() {
+    let harness = TestHarness::start_with(|config| config.instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let alice = harness
+        .genesis_wallet()
+        .await
+        .expect("the genesis account is not there");
+    let transfers = EventFilter {
+        program_id: Some(program_id()),
+        topic_prefix: Some("transfer".to_string()),
+    };
+    let client = WsClientBuilder::default()
+        .build(harness.rpc_url().replacen("http", "ws", 1))
+        .await
+        .expect("failed to connect");
+    let mut subscription = client
+        .subscribe_events(transfers.clone())
+        .await
+        .expect("failed to subscribe");
+
+    let call = TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from(alice.address().into())
+        .to(alice.address().into())
+        .program_id(program_id().into())
+        .op("transferMany".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(0))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap();
+    let emitted = vec![
+        ProgramEvent::new("transfer").with_attribute("amount", "5"),
+        ProgramEvent::new("approval").with_attribute("spender", "bob"),
+        ProgramEvent::new("transfer.fee").with_attribute("amount", "1"),
+    ];
+    let outputs = emitted
+        .iter()
+        .cloned()
+        .fold(OutputsBuilder::new(), OutputsBuilder::add_event)
+        .build()
+        .unwrap();
+    Batcher::apply_instructions_to_accounts(harness.batcher(), call.clone(), outputs)
+        .await
+        .expect("failed to apply the call");
+    let records = harness.seal_batch().await.expect("failed to seal batch");
+    assert!(records
+        .iter()
+        .any(|record| record.events_root != H256::zero()));
+
+    let by_program = events(
+        &harness,
+        EventFilter {
+            program_id: Some(program_id()),
+            topic_prefix: None,
+        },
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(
+        by_program
+            .items
+            .iter()
+            .map(|event| event.topic.as_str())
+            .collect::<Vec<_>>(),
+        vec!["transfer", "approval", "transfer.fee"]
+    );
+    assert!(by_program
+        .items
+        .iter()
+        .all(|event| event.transaction_hash == call.hash_string()));
+
+    let first = events(&harness, transfers.clone(), None, Some(1)).await;
+    assert_eq!(first.items.len(), 1);
+    assert_eq!(first.items[0].index, 0);
+    let second = events(&harness, transfers.clone(), first.next_cursor, Some(1)).await;
+    assert_eq!(second.items[0].topic, "transfer.fee");
+    assert_eq!(second.items[0].index, 2);
+    assert_eq!(second.next_cursor, None);
+    let elsewhere = EventFilter {
+        program_id: Some(Address::new([0xef; 20])),
+        topic_prefix: None,
+    };
+    assert!(events(&harness, elsewhere, None, None)
+        .await
+        .items
+        .is_empty());
+
+    for topic in ["transfer", "transfer.fee"] {
+        let notification = tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("no event was streamed")
+            .unwrap()
+            .unwrap();
+        assert_eq!(notification["topic"], topic);
+        assert_eq!(notification["programId"], program_id().to_string());
+        assert_eq!(notification["dropped"], 0);
+    }
+
+    let receipt: Value = serde_json::from_str(
+        &harness
+            .rpc()
+            .unwrap()
+            .get_transaction_receipt(call.hash_string())
+            .await
+            .expect("failed to get receipt"),
+    )
+    .unwrap();
+    assert_eq!(receipt["events"], serde_json::to_value(&emitted).unwrap());
+}
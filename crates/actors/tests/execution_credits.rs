@@ -0,0 +1,219 @@
+#![cfg(test)]
+//! Test coverage for calls being held back while the executor has no room
+//! for them, and handed on as it finishes others.
+
+mod common;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{spawn_as, spawn_named};
+use lasr_actors::PendingTransactionActor;
+use lasr_messages::{ActorType, ExecutionCreditStatus, ExecutorMessage, PendingTransactionMessage};
+use lasr_types::{Transaction, TransactionBuilder, TransactionType, U256};
+use ractor::{rpc::CallResult, Actor, ActorProcessingErr, ActorRef};
+use serial_test::serial;
+use tokio::sync::mpsc;
+
+/// Stands in for a slow executor with room for two calls, running nothing
+/// and handing on the calls it is given, which the test finishes for it.
+struct SlowExecutor;
+
+const CREDITS: usize = 2;
+
+fn grant() {
+    let pending: ActorRef<PendingTransactionMessage> =
+        ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+            .expect("pending transactions actor is not running")
+            .into();
+    pending
+        .cast(PendingTransactionMessage::ExecutionCredits { credits: CREDITS })
+        .expect("failed to cast to pending transactions");
+}
+
+#[async_trait]
+impl Actor for SlowExecutor {
+    type Msg = ExecutorMessage;
+    type State = mpsc::UnboundedSender<Transaction>;
+    type Arguments = mpsc::UnboundedSender<Transaction>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        executed: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(executed)
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _executed: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        grant();
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        executed: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            ExecutorMessage::Exec { transaction } => {
+                executed.send(transaction).ok();
+            }
+            ExecutorMessage::GrantCredits => grant(),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn call(from: u8) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from([from; 20])
+        .to([3; 20])
+        .program_id([3; 20])
+        .op("run".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(0))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+async fn credits(pending: &ActorRef<PendingTransactionMessage>) -> ExecutionCreditStatus {
+    let result = pending
+        .call(
+            |reply| PendingTransactionMessage::GetExecutionCredits { reply },
+            Some(Duration::from_secs(5)),
+        )
+        .await
+        .expect("failed to call pending transactions actor");
+    match result {
+        CallResult::Success(status) => status,
+        _ => panic!("pending transactions actor did not answer"),
+    }
+}
+
+async fn next_executed(executed: &mut mpsc::UnboundedReceiver<Transaction>) -> Transaction {
+    tokio::time::timeout(Duration::from_secs(5), executed.recv())
+        .await
+        .expect("no call was handed to the executor")
+        .expect("executor went away")
+}
+
+async fn start(
+    calls: u8,
+) -> (
+    ActorRef<PendingTransactionMessage>,
+    ActorRef<ExecutorMessage>,
+    mpsc::UnboundedReceiver<Transaction>,
+) {
+    let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+    let (executed_tx, executed) = mpsc::unbounded_channel();
+    let executor = spawn_as(ActorType::Executor, SlowExecutor, executed_tx).await;
+    // Calls sent before the executor's grant arrives are not held back.
+    while credits(&pending).await.granted.is_none() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    for from in 1..=calls {
+        pending
+            .cast(PendingTransactionMessage::NewCall {
+                transaction: call(from),
+            })
+            .unwrap();
+    }
+    (pending, executor, executed)
+}
+
+#[tokio::test]
+#[serial]
+async fn calls_wait_for_room_with_the_executor_and_none_are_lost() {
+    let (pending, executor, mut executed) = start(5).await;
+    assert_eq!(
+        credits(&pending).await,
+        ExecutionCreditStatus {
+            granted: Some(CREDITS),
+            in_flight: 2,
+            waiting: 3,
+        }
+    );
+
+    let mut running = vec![
+        next_executed(&mut executed).await,
+        next_executed(&mut executed).await,
+    ];
+    let mut finished = Vec::new();
+    while !running.is_empty() {
+        let transaction = running.remove(0);
+        pending
+            .cast(PendingTransactionMessage::ExecSuccess {
+                transaction: transaction.clone(),
+            })
+            .unwrap();
+        finished.push(transaction.hash_string());
+        let status = credits(&pending).await;
+        assert!(status.in_flight <= CREDITS, "{status:?}");
+        if status.in_flight > running.len() {
+            running.push(next_executed(&mut executed).await);
+        }
+    }
+    // Every call ran once, and nothing was handed on past the credits.
+    assert!(executed.try_recv().is_err());
+    finished.sort();
+    let mut calls: Vec<String> = (1..=5).map(|from| call(from).hash_string()).collect();
+    calls.sort();
+    assert_eq!(finished, calls);
+    assert_eq!(
+        credits(&pending).await,
+        ExecutionCreditStatus {
+            granted: Some(CREDITS),
+            in_flight: 0,
+            waiting: 0,
+        }
+    );
+
+    executor.stop_and_wait(None, None).await.unwrap();
+    pending.stop_and_wait(None, None).await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_restarted_executor_is_not_kept_waiting_on_credits_it_lost() {
+    let (pending, executor, mut executed) = start(4).await;
+    next_executed(&mut executed).await;
+    next_executed(&mut executed).await;
+    assert_eq!(credits(&pending).await.waiting, 2);
+
+    // The calls the executor had are lost with it, and never finish.
+    executor.stop_and_wait(None, None).await.unwrap();
+    let (executed_tx, mut executed) = mpsc::unbounded_channel();
+    let executor = spawn_as(ActorType::Executor, SlowExecutor, executed_tx).await;
+
+    let mut handed_on = vec![
+        next_executed(&mut executed).await.hash_string(),
+        next_executed(&mut executed).await.hash_string(),
+    ];
+    handed_on.sort();
+    let mut waiting = vec![call(3).hash_string(), call(4).hash_string()];
+    waiting.sort();
+    assert_eq!(handed_on, waiting);
+    assert_eq!(
+        credits(&pending).await,
+        ExecutionCreditStatus {
+            granted: Some(CREDITS),
+            in_flight: 2,
+            waiting: 0,
+        }
+    );
+
+    executor.stop_and_wait(None, None).await.unwrap();
+    pending.stop_and_wait(None, None).await.unwrap();
+}
@@ -0,0 +1,89 @@
+#![cfg(test)]
+//! Test coverage for streaming execution logs to RPC subscribers.
+
+mod common;
+
+use common::{rpc_proxy, serve, ws_client};
+use lasr_actors::LasrRpcServerImpl;
+use lasr_compute::{ExecutionLimits, ExecutionLogs, OciBundlerBuilder, WasmRuntime};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{ExecutionLogEvent, ExecutionLogLine};
+
+use std::time::Duration;
+
+const PROGRAM_ID: &str = "0x0303030303030303030303030303030303030303";
+const TRANSACTION_HASH: &str = "0xabcdef";
+
+fn wasm_runtime() -> WasmRuntime {
+    WasmRuntime::new(
+        OciBundlerBuilder::default()
+            .runtime("/usr/local/bin/runsc".to_string())
+            .base_images("./base_image".to_string())
+            .containers("./containers".to_string())
+            .payload_path("./payload".to_string())
+            .build()
+            .expect("failed to build bundler"),
+    )
+}
+
+#[tokio::test]
+async fn chatty_program_logs_stream_to_subscribers() {
+    let logs = ExecutionLogs::new(100, 64);
+    let proxy = rpc_proxy().await;
+    let (server_handle, addr) =
+        serve(LasrRpcServerImpl::new(proxy.clone(), logs.clone()).into_rpc()).await;
+
+    let client = ws_client(addr).await;
+    let mut subscription = client
+        .subscribe_execution_logs(PROGRAM_ID.to_string())
+        .await
+        .expect("failed to subscribe to execution logs");
+    let mut other_program = client
+        .subscribe_execution_logs("0x0404040404040404040404040404040404040404".to_string())
+        .await
+        .expect("failed to subscribe to execution logs");
+
+    let recorder = logs.recorder(PROGRAM_ID, Some(TRANSACTION_HASH.to_string()));
+    let output = wasm_runtime()
+        .run_module(
+            include_bytes!("programs/chatty.wat").to_vec(),
+            b"{}",
+            &ExecutionLimits::default(),
+            Some(&recorder),
+        )
+        .await
+        .expect("chatty program failed");
+    assert_eq!(output, b"{}");
+    recorder.finish();
+
+    for sequence in 0..20 {
+        let event = tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("timed out waiting for a log line")
+            .expect("subscription closed")
+            .expect("invalid log event");
+        assert_eq!(
+            event,
+            ExecutionLogEvent::Line(ExecutionLogLine {
+                program_id: PROGRAM_ID.to_string(),
+                transaction_hash: Some(TRANSACTION_HASH.to_string()),
+                sequence,
+                line: "still working".to_string(),
+            })
+        );
+    }
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), other_program.next())
+            .await
+            .is_err(),
+        "another program's subscriber received logs"
+    );
+    assert_eq!(
+        logs.for_transaction(TRANSACTION_HASH)
+            .map(|lines| lines.len()),
+        Some(20)
+    );
+
+    server_handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
@@ -0,0 +1,250 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for funding accounts through the devnet faucet.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{http_client, rpc_proxy, serve, spawn_as, spawn_named, stop};
+use eigenda_client::proof::BlobVerificationProof;
+use jsonrpsee::{core::ClientError, http_client::HttpClient, server::ServerHandle};
+use lasr_actors::{
+    get_account, verse_total_supply, AccountCacheActor, Batcher, BatcherActor, EngineActor, Faucet,
+    FaucetConfig, LasrRpcServerImpl, ETH_ADDR, VERSE_ADDR,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{ActorType, PendingTransactionMessage, RpcErrorCode};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{Address, MockPersistenceStore, PersistenceStore, Transaction, U256};
+use ractor::{concurrency::OneshotReceiver, Actor, ActorCell, ActorProcessingErr, ActorRef};
+use serde_json::Value;
+use serial_test::serial;
+use tokio::sync::{mpsc, Mutex};
+
+/// Stands in for the pending pool, handing on the transactions the engine
+/// sets.
+struct MockPendingTransactions;
+
+#[async_trait]
+impl Actor for MockPendingTransactions {
+    type Msg = PendingTransactionMessage;
+    type State = mpsc::UnboundedSender<Transaction>;
+    type Arguments = mpsc::UnboundedSender<Transaction>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        pending: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(pending)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        pending: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let PendingTransactionMessage::New { transaction, .. } = message {
+            pending.send(transaction).ok();
+        }
+        Ok(())
+    }
+}
+
+fn recipient() -> Address {
+    Address::new([7; 20])
+}
+
+struct Node {
+    _batcher_rx: mpsc::Receiver<OneshotReceiver<(String, BlobVerificationProof)>>,
+    batcher: Arc<Mutex<Batcher>>,
+    pending: mpsc::UnboundedReceiver<Transaction>,
+    actors: Vec<ActorCell>,
+    handle: ServerHandle,
+    client: HttpClient,
+}
+
+impl Node {
+    async fn start(faucet: Faucet) -> Self {
+        let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+        let batcher = Arc::new(Mutex::new(Batcher::new(receivers_thread_tx)));
+        let batcher_actor = spawn_named(BatcherActor::new(), batcher.clone()).await;
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .expect("failed to create mock storage");
+        let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+        let engine = spawn_as(ActorType::Engine, EngineActor::new(), ()).await;
+        let (pending_tx, pending) = mpsc::unbounded_channel();
+        let pending_transactions = spawn_as(
+            ActorType::PendingTransactions,
+            MockPendingTransactions,
+            pending_tx,
+        )
+        .await;
+        let proxy = rpc_proxy().await;
+
+        let rpc =
+            LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default()).with_faucet(faucet);
+        let (handle, addr) = serve(rpc.into_rpc()).await;
+        Self {
+            _batcher_rx,
+            batcher,
+            pending,
+            actors: vec![
+                proxy.get_cell(),
+                pending_transactions.get_cell(),
+                engine.get_cell(),
+                batcher_actor.get_cell(),
+                account_cache.get_cell(),
+            ],
+            handle,
+            client: http_client(addr),
+        }
+    }
+
+    /// Applies the next transaction the engine set, as the batcher would
+    /// once it was validated.
+    async fn apply_pending(&mut self) -> Transaction {
+        let transaction = tokio::time::timeout(Duration::from_secs(5), self.pending.recv())
+            .await
+            .expect("engine set no transaction")
+            .expect("pending pool closed");
+        Batcher::add_transaction_to_account(self.batcher.clone(), transaction.clone())
+            .await
+            .expect("failed to apply the mint");
+        transaction
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+fn devnet_faucet() -> Faucet {
+    Faucet::new(FaucetConfig {
+        enabled: true,
+        max_amount: U256::from(1000),
+        cooldown: Duration::from_secs(60),
+    })
+}
+
+fn error_code(result: Result<String, ClientError>) -> i32 {
+    match result {
+        Err(ClientError::Call(error)) => error.code(),
+        other => panic!("expected an error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn faucet_mints_to_the_address_asked_for() {
+    let mut node = Node::start(devnet_faucet()).await;
+
+    let funded: Value = serde_json::from_str(
+        &node
+            .client
+            .faucet(recipient().to_full_string(), "400".to_string(), None)
+            .await
+            .expect("faucet failed"),
+    )
+    .expect("faucet answer is not JSON");
+    let transaction = node.apply_pending().await;
+    assert_eq!(funded["transactionHash"], transaction.hash_string());
+    assert_eq!(funded["programId"], VERSE_ADDR.to_string());
+    assert_eq!(transaction.to(), recipient());
+
+    let account = get_account(recipient(), ActorType::RpcServer)
+        .await
+        .expect("recipient was not created");
+    assert_eq!(account.balance(&VERSE_ADDR), U256::from(400));
+    let verse = get_account(VERSE_ADDR, ActorType::RpcServer)
+        .await
+        .expect("no VERSE program account");
+    assert_eq!(verse_total_supply(&verse).unwrap(), U256::from(400));
+
+    // ETH goes to an address of its own, as the recipient is cooling down.
+    let other = Address::new([8; 20]);
+    node.client
+        .faucet(
+            other.to_full_string(),
+            "0x10".to_string(),
+            Some(ETH_ADDR.to_full_string()),
+        )
+        .await
+        .expect("faucet failed");
+    node.apply_pending().await;
+    let account = get_account(other, ActorType::RpcServer)
+        .await
+        .expect("recipient was not created");
+    assert_eq!(account.balance(&ETH_ADDR), U256::from(16));
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn faucet_requests_are_cooled_down_and_capped() {
+    let mut node = Node::start(devnet_faucet()).await;
+
+    node.client
+        .faucet(recipient().to_full_string(), "1".to_string(), None)
+        .await
+        .expect("faucet failed");
+    node.apply_pending().await;
+    assert_eq!(
+        error_code(
+            node.client
+                .faucet(recipient().to_full_string(), "1".to_string(), None)
+                .await
+        ),
+        RpcErrorCode::RateLimited.code()
+    );
+
+    let other = Address::new([8; 20]).to_full_string();
+    assert_eq!(
+        error_code(
+            node.client
+                .faucet(other.clone(), "1001".to_string(), None)
+                .await
+        ),
+        RpcErrorCode::InvalidParams.code()
+    );
+    assert_eq!(
+        error_code(
+            node.client
+                .faucet(
+                    other.clone(),
+                    "1".to_string(),
+                    Some(Address::new([9; 20]).to_full_string())
+                )
+                .await
+        ),
+        RpcErrorCode::InvalidParams.code()
+    );
+    assert!(node.pending.try_recv().is_err());
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn faucet_is_disabled_off_devnet() {
+    let mut node = Node::start(Faucet::default()).await;
+
+    assert_eq!(
+        error_code(
+            node.client
+                .faucet(recipient().to_full_string(), "1".to_string(), None)
+                .await
+        ),
+        RpcErrorCode::Disabled.code()
+    );
+    assert!(node.pending.try_recv().is_err());
+
+    node.stop().await;
+}
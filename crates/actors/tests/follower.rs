@@ -0,0 +1,246 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for a follower syncing a primary's batches into a read
+//! replica, filling in those it missed from the DA layer, carrying on where
+//! it left off after a restart, and serving reads only.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::http_client;
+use jsonrpsee::{core::ClientError, http_client::HttpClient, server::ServerHandle};
+use lasr_actors::{
+    metrics, read_only_rpc, serve_rpc, AdminAccess, DaClient, Follower, FollowerConfig,
+    LocalFileDa, RateLimiter, Replica, RequestLimits, TestHarness, VERSE_ADDR,
+};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Address, TransactionBuilder, TransactionType, U256};
+use lasr_wallet::Wallet;
+use serde_json::Value;
+use serial_test::serial;
+use tokio::task::JoinHandle;
+
+fn receiver(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+fn sample(name: &str) -> f64 {
+    metrics::encode()
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{name} ")))
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Follows `primary` into `replica`, reading missed batches from its DA
+/// directory and replaying the inclusion of every transaction.
+fn follow(primary: &TestHarness, replica: &Replica) -> JoinHandle<()> {
+    let url = primary.rpc_url().replacen("http://", "ws://", 1);
+    let da = DaClient::local(LocalFileDa::open(&primary.config().da.local_path).unwrap());
+    let config = FollowerConfig::new(url)
+        .with_witness_sample(1)
+        .with_reconnect_after(Duration::from_millis(50));
+    tokio::spawn(Follower::new(config, replica.clone(), da).run())
+}
+
+async fn serve(replica: &Replica) -> (HttpClient, ServerHandle) {
+    let (addr, handle) = serve_rpc(
+        SocketAddr::from(([127, 0, 0, 1], 0)),
+        read_only_rpc(replica.clone()).unwrap(),
+        RateLimiter::default(),
+        AdminAccess::Disabled,
+        None,
+        RequestLimits::default(),
+    )
+    .await
+    .unwrap();
+    let client = http_client(addr);
+    (client, handle)
+}
+
+/// Sends `amount` VERSE from `wallet` to `to` and seals the batch it lands
+/// in, once `to` holds `balance`.
+async fn send_and_seal(
+    primary: &TestHarness,
+    wallet: &mut Wallet<HttpClient>,
+    to: Address,
+    amount: u64,
+    balance: u64,
+) {
+    wallet
+        .send(&to, &VERSE_ADDR, U256::from(amount))
+        .await
+        .expect("failed to send");
+    primary
+        .wait_for_account(to, |account| {
+            account.balance(&VERSE_ADDR) == U256::from(balance)
+        })
+        .await
+        .expect("the send was not applied");
+    assert!(!primary.seal_batch().await.unwrap().is_empty());
+}
+
+async fn caught_up(primary: &TestHarness, replica: &Replica) {
+    let next = primary.batches().next_batch_id();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while replica.next_batch_id() < next {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the follower did not catch up with the primary");
+}
+
+/// Asserts the follower answers for every address in `addresses`, and for
+/// the latest batch, as the primary does.
+async fn assert_converged(primary: &HttpClient, follower: &HttpClient, addresses: &[Address]) {
+    for address in addresses {
+        assert_eq!(
+            follower
+                .get_account(address.to_full_string())
+                .await
+                .unwrap(),
+            primary.get_account(address.to_full_string()).await.unwrap(),
+            "{}",
+            address.to_full_string()
+        );
+    }
+    let latest = |batch: String| {
+        let batch: Value = serde_json::from_str(&batch).unwrap();
+        (batch["batchId"].clone(), batch["stateRoot"].clone())
+    };
+    assert_eq!(
+        latest(follower.get_latest_batch().await.unwrap()),
+        latest(primary.get_latest_batch().await.unwrap())
+    );
+}
+
+fn read_only(result: Result<String, ClientError>) {
+    match result {
+        Err(ClientError::Call(error)) => assert_eq!(error.code(), RpcErrorCode::ReadOnly.code()),
+        other => panic!("expected the follower to turn the write away, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn a_follower_converges_on_the_primary_across_a_reconnect() {
+    let primary = TestHarness::start().await.expect("failed to start node");
+    let primary_rpc = primary.rpc().unwrap();
+    let mut wallet = primary.wallet().await.expect("failed to fund wallet");
+    send_and_seal(&primary, &mut wallet, receiver(9), 5, 5).await;
+    let genesis = primary.genesis().accounts[0].address;
+    let addresses = [genesis, wallet.address(), receiver(9), receiver(10)];
+
+    // Batches finalized before the follower started are read from the DA
+    // layer, those after streamed.
+    let replica = Replica::from_genesis(primary.genesis()).unwrap();
+    let (filled, applied) = (
+        sample("lasr_follower_filled_total"),
+        sample("lasr_follower_applied_total"),
+    );
+    let following = follow(&primary, &replica);
+    let (follower_rpc, server) = serve(&replica).await;
+    caught_up(&primary, &replica).await;
+    let missed = primary.batches().next_batch_id() as f64;
+    assert_eq!(sample("lasr_follower_filled_total"), filled + missed);
+    send_and_seal(&primary, &mut wallet, receiver(9), 3, 8).await;
+    send_and_seal(&primary, &mut wallet, receiver(10), 7, 7).await;
+    caught_up(&primary, &replica).await;
+    assert_eq!(sample("lasr_follower_filled_total"), filled + missed);
+    assert_eq!(
+        sample("lasr_follower_applied_total"),
+        applied + primary.batches().next_batch_id() as f64
+    );
+    assert_converged(&primary_rpc, &follower_rpc, &addresses).await;
+
+    // Writes are turned away.
+    read_only(
+        follower_rpc
+            .faucet(receiver(11).to_full_string(), "10".to_string(), None)
+            .await,
+    );
+    let transaction = TransactionBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(0)))
+        .from(wallet.address().into())
+        .to(receiver(9).into())
+        .program_id(VERSE_ADDR.into())
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(1))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap();
+    read_only(follower_rpc.send(transaction).await);
+
+    // Batches finalized while the follower is away are filled in once it is
+    // back.
+    following.abort();
+    let before = primary.batches().next_batch_id();
+    send_and_seal(&primary, &mut wallet, receiver(10), 1, 8).await;
+    send_and_seal(&primary, &mut wallet, receiver(10), 2, 10).await;
+    assert_eq!(replica.next_batch_id(), before);
+    let following = follow(&primary, &replica);
+    caught_up(&primary, &replica).await;
+    let gap = (primary.batches().next_batch_id() - before) as f64;
+    assert!(gap >= 2.0);
+    assert_eq!(sample("lasr_follower_filled_total"), filled + missed + gap);
+    assert_converged(&primary_rpc, &follower_rpc, &addresses).await;
+    assert_eq!(
+        replica.account(receiver(10)).unwrap().balance(&VERSE_ADDR),
+        U256::from(10)
+    );
+
+    following.abort();
+    server.stop().ok();
+    primary.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_follower_converges_on_the_primary_after_a_restart() {
+    let primary = TestHarness::start().await.expect("failed to start node");
+    let primary_rpc = primary.rpc().unwrap();
+    let mut wallet = primary.wallet().await.expect("failed to fund wallet");
+    send_and_seal(&primary, &mut wallet, receiver(12), 4, 4).await;
+    let addresses = [wallet.address(), receiver(12), receiver(13)];
+    let dir = std::env::temp_dir().join(format!("follower_replica_{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+
+    let replica = Replica::open(&dir, Some(primary.genesis())).unwrap();
+    let following = follow(&primary, &replica);
+    caught_up(&primary, &replica).await;
+    following.abort();
+    let applied = replica.next_batch_id();
+    drop(replica);
+
+    // Reopened, the replica holds what it applied before stopping, and
+    // carries on from there rather than from the first batch.
+    send_and_seal(&primary, &mut wallet, receiver(13), 6, 6).await;
+    let replica = Replica::open(&dir, Some(primary.genesis())).unwrap();
+    assert_eq!(replica.next_batch_id(), applied);
+    assert_eq!(
+        replica.account(receiver(12)).unwrap().balance(&VERSE_ADDR),
+        U256::from(4)
+    );
+    let filled = sample("lasr_follower_filled_total");
+    let following = follow(&primary, &replica);
+    let (follower_rpc, server) = serve(&replica).await;
+    caught_up(&primary, &replica).await;
+    assert_eq!(
+        sample("lasr_follower_filled_total"),
+        filled + (primary.batches().next_batch_id() - applied) as f64
+    );
+    assert_converged(&primary_rpc, &follower_rpc, &addresses).await;
+
+    following.abort();
+    server.stop().ok();
+    primary.stop().await;
+    std::fs::remove_dir_all(&dir).ok();
+}
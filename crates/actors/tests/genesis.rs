@@ -0,0 +1,216 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for applying a genesis to storage as a node first starts,
+//! and refusing storage started from another.
+
+use std::collections::BTreeMap;
+
+use lasr_actors::{
+    check_verse_supply_invariant,
+    encoding::{self, Encoding},
+    recorded_genesis_hash, verse_total_supply, AccountValue, Genesis, GenesisAccount, GenesisError,
+    GenesisOutcome, GenesisProgram, StorageRef, VERSE_ADDR,
+};
+use lasr_types::{Account, Address, MockPersistenceStore, PersistenceStore, U256};
+
+fn program_id() -> Address {
+    Address::new([0xaa; 20])
+}
+
+fn alice() -> Address {
+    Address::new([1; 20])
+}
+
+fn bob() -> Address {
+    Address::new([2; 20])
+}
+
+fn genesis() -> Genesis {
+    Genesis {
+        chain_id: 7,
+        programs: vec![GenesisProgram {
+            program_id: program_id(),
+            owner: bob(),
+            content_id: "bafy".to_string(),
+            metadata: BTreeMap::from([("symbol".to_string(), "TKN".to_string())]),
+            auth_program: None,
+        }],
+        accounts: vec![
+            GenesisAccount {
+                address: alice(),
+                balances: BTreeMap::from([
+                    (VERSE_ADDR, U256::from(1_000)),
+                    (program_id(), U256::from(50)),
+                ]),
+            },
+            GenesisAccount {
+                address: bob(),
+                balances: BTreeMap::from([(VERSE_ADDR, U256::from(250))]),
+            },
+        ],
+    }
+}
+
+async fn storage() -> StorageRef {
+    <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+        .await
+        .unwrap()
+}
+
+async fn stored(storage: &StorageRef, address: Address) -> Option<Account> {
+    let bytes = PersistenceStore::get(storage, address.to_full_string())
+        .await
+        .ok()
+        .flatten()?;
+    encoding::decode::<AccountValue>(&bytes)
+        .ok()
+        .map(|value| value.account)
+}
+
+async fn put(storage: &StorageRef, account: Account) {
+    PersistenceStore::put(
+        storage,
+        account.owner_address().to_full_string(),
+        bincode::serialize(&AccountValue { account }).unwrap(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn a_genesis_puts_its_accounts_programs_and_supply_in_storage() {
+    let storage = storage().await;
+    let genesis = genesis();
+    assert_eq!(
+        genesis.apply(&storage).await.unwrap(),
+        GenesisOutcome::Applied
+    );
+
+    let alice = stored(&storage, alice()).await.unwrap();
+    assert_eq!(alice.balance(&VERSE_ADDR), U256::from(1_000));
+    assert_eq!(alice.balance(&program_id()), U256::from(50));
+    let bob = stored(&storage, bob()).await.unwrap();
+    assert_eq!(bob.balance(&VERSE_ADDR), U256::from(250));
+
+    let program = stored(&storage, program_id()).await.unwrap();
+    assert_eq!(program.owner_address(), self::bob());
+    let metadata = program.program_account_metadata().inner();
+    assert_eq!(metadata.get("content_id").map(String::as_str), Some("bafy"));
+    assert_eq!(metadata.get("symbol").map(String::as_str), Some("TKN"));
+
+    let verse = stored(&storage, VERSE_ADDR).await.unwrap();
+    assert_eq!(verse_total_supply(&verse).unwrap(), U256::from(1_250));
+    assert_eq!(
+        check_verse_supply_invariant([&alice, &bob, &program, &verse]).unwrap(),
+        U256::from(1_250)
+    );
+    assert_eq!(
+        recorded_genesis_hash(&storage).await.unwrap(),
+        Some(genesis.hash().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn a_restart_from_the_same_genesis_leaves_storage_as_it_is() {
+    let storage = storage().await;
+    let genesis = genesis();
+    genesis.apply(&storage).await.unwrap();
+
+    // Alice spends after the first start.
+    let mut alice = stored(&storage, alice()).await.unwrap();
+    alice.increment_nonce();
+    put(&storage, alice.clone()).await;
+
+    assert_eq!(
+        genesis.apply(&storage).await.unwrap(),
+        GenesisOutcome::AlreadyApplied
+    );
+    assert_eq!(stored(&storage, self::alice()).await, Some(alice));
+}
+
+#[tokio::test]
+async fn storage_started_from_another_genesis_is_refused() {
+    let storage = storage().await;
+    genesis().apply(&storage).await.unwrap();
+
+    let mut other = genesis();
+    other.accounts[1]
+        .balances
+        .insert(VERSE_ADDR, U256::from(251));
+    match other.apply(&storage).await {
+        Err(GenesisError::Mismatch { recorded, genesis }) => {
+            assert_eq!(recorded, self::genesis().hash().unwrap());
+            assert_eq!(genesis, other.hash().unwrap());
+        }
+        outcome => panic!("expected a mismatch, got {outcome:?}"),
+    }
+    // Nothing of the other genesis was written.
+    assert_eq!(
+        stored(&storage, bob()).await.unwrap().balance(&VERSE_ADDR),
+        U256::from(250)
+    );
+}
+
+#[tokio::test]
+async fn an_interrupted_genesis_is_applied_again_but_never_over_other_accounts() {
+    // Stopped after writing Alice, before recording the hash.
+    let storage = storage().await;
+    let genesis = genesis();
+    let accounts = genesis.accounts().unwrap();
+    let alice = accounts
+        .iter()
+        .find(|account| account.owner_address() == alice())
+        .unwrap()
+        .clone();
+    put(&storage, alice.clone()).await;
+    assert_eq!(
+        genesis.apply(&storage).await.unwrap(),
+        GenesisOutcome::Applied
+    );
+
+    // Storage a node ran on without a genesis is not overwritten.
+    let storage = self::storage().await;
+    let mut spent = alice;
+    spent.increment_nonce();
+    put(&storage, spent.clone()).await;
+    assert_eq!(
+        genesis.apply(&storage).await,
+        Err(GenesisError::Occupied {
+            key: self::alice().to_full_string()
+        })
+    );
+    assert_eq!(stored(&storage, self::alice()).await, Some(spent));
+    assert_eq!(recorded_genesis_hash(&storage).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn a_genesis_interrupted_in_one_encoding_is_finished_in_another() {
+    // Stopped after writing Alice in bare bincode, as older nodes stored her.
+    let storage = storage().await;
+    let genesis = genesis();
+    let alice = genesis
+        .accounts()
+        .unwrap()
+        .into_iter()
+        .find(|account| account.owner_address() == alice())
+        .unwrap();
+    put(&storage, alice.clone()).await;
+    assert_eq!(
+        genesis
+            .apply_encoded(&storage, Encoding::Borsh)
+            .await
+            .unwrap(),
+        GenesisOutcome::Applied
+    );
+
+    let bytes = PersistenceStore::get(&storage, bob().to_full_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Encoding::of(&bytes), Some(Encoding::Borsh));
+    assert_eq!(stored(&storage, self::alice()).await, Some(alice));
+    assert_eq!(
+        stored(&storage, bob()).await.unwrap().balance(&VERSE_ADDR),
+        U256::from(250)
+    );
+}
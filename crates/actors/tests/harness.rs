@@ -0,0 +1,227 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the flows a user goes through, run against the whole
+//! node booted in-process.
+
+use lasr_actors::{TestHarness, TokenDelta, VERSE_ADDR};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{
+    Address, ContractLogType, Instruction, LogInstruction, Outputs, Payload, PayloadBuilder,
+    TransactionType, U256,
+};
+use serde_json::{json, Value};
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn payload(
+    transaction_type: TransactionType,
+    from: Address,
+    to: Address,
+    value: u64,
+    nonce: U256,
+) -> Payload {
+    PayloadBuilder::default()
+        .transaction_type(transaction_type)
+        .from(from.into())
+        .to(to.into())
+        .program_id(VERSE_ADDR.into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(value))
+        .nonce(nonce)
+        .build()
+        .expect("failed to build payload")
+}
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).expect("answer is not JSON")
+}
+
+#[tokio::test]
+#[serial]
+async fn a_send_is_batched_dispersed_and_settled() {
+    // Sealed and settled a step at a time, as the test asks.
+    let harness = TestHarness::start_with(|config| config.instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    // The faucet's mint is sealed on its own, ahead of the send.
+    assert!(!harness.seal_batch().await.unwrap().is_empty());
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(25))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(25)
+        })
+        .await
+        .expect("the send was not applied");
+
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    assert_eq!(sealed.len(), 1);
+    let batch = sealed[0].clone();
+    assert_eq!(batch.transaction_hashes.len(), 1);
+    let transaction_hash = batch.transaction_hashes[0].clone();
+    let status = parse(
+        &rpc.get_transaction_status(transaction_hash.clone())
+            .await
+            .unwrap(),
+    );
+    assert_eq!(status["status"], "included");
+
+    // The batch is read back from the local DA layer.
+    let info = parse(&rpc.get_batch(batch.batch_id, Some(true)).await.unwrap());
+    assert_eq!(info["status"], "finalized");
+    assert_eq!(info["transactionHashes"], json!([transaction_hash]));
+    assert_eq!(
+        info["accounts"].as_array().map(Vec::len),
+        Some(batch.account_count)
+    );
+
+    let settled = harness
+        .settle(batch.batch_id)
+        .await
+        .expect("failed to settle");
+    let status = parse(
+        &rpc.get_transaction_status(transaction_hash.clone())
+            .await
+            .unwrap(),
+    );
+    assert_eq!(status["status"], "settled");
+    let info = parse(&rpc.get_batch(batch.batch_id, None).await.unwrap());
+    assert_eq!(info["status"], "settled");
+    assert_eq!(info["blockNumber"], settled.block_number);
+
+    let report = harness.stop().await;
+    assert_eq!(report.abandoned, None);
+    assert_eq!(report.persisted, 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn simulations_predict_sends_and_scripted_calls_without_applying_them() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    let funds = harness.config().devnet.faucet_max_amount;
+    let nonce = harness.account(sender).await.unwrap().nonce();
+
+    let send = payload(
+        TransactionType::Send(nonce),
+        sender,
+        receiver(),
+        5,
+        nonce + U256::from(1),
+    );
+    let transaction = wallet
+        .sign_payload(&serde_json::to_string(&send).unwrap())
+        .await
+        .expect("failed to sign send");
+    let simulation = parse(&rpc.simulate_transaction(transaction).await.unwrap());
+    assert_eq!(simulation["success"], true, "{simulation}");
+    let mut deltas = vec![
+        TokenDelta {
+            address: sender,
+            program_id: VERSE_ADDR,
+            before: funds,
+            after: funds - U256::from(5),
+        },
+        TokenDelta {
+            address: receiver(),
+            program_id: VERSE_ADDR,
+            before: U256::from(0),
+            after: U256::from(5),
+        },
+    ];
+    deltas.sort_by_key(|delta| (delta.address, delta.program_id));
+    assert_eq!(simulation["tokenDeltas"], json!(deltas));
+
+    harness.script_executor(|_, inputs| {
+        Ok(Outputs::new(
+            inputs,
+            vec![Instruction::Log(LogInstruction(ContractLogType::Info(
+                "scripted".to_string(),
+            )))],
+        ))
+    });
+    let call = payload(
+        TransactionType::Call(nonce),
+        sender,
+        VERSE_ADDR,
+        0,
+        nonce + U256::from(1),
+    );
+    let simulation = parse(
+        &rpc.simulate_payload(call.clone(), sender.to_full_string())
+            .await
+            .unwrap(),
+    );
+    assert_eq!(simulation["success"], true, "{simulation}");
+    assert_eq!(
+        simulation["events"],
+        json!([{ "level": "info", "message": "scripted" }])
+    );
+
+    harness.script_executor(|_, _| Err("division by zero".to_string()));
+    let reverted = parse(
+        &rpc.simulate_payload(call, sender.to_full_string())
+            .await
+            .unwrap(),
+    );
+    assert_eq!(reverted["success"], false);
+    assert_eq!(reverted["revertReason"], "division by zero");
+
+    // Nothing simulated was applied.
+    assert!(harness.account(receiver()).await.is_none());
+    assert_eq!(
+        harness.account(sender).await.unwrap().balance(&VERSE_ADDR),
+        funds
+    );
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn an_account_funded_at_genesis_sends_without_the_faucet() {
+    let harness = TestHarness::start_with(|config| config.instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let mut wallet = harness
+        .genesis_wallet()
+        .await
+        .expect("the genesis account is not there");
+    let funds = harness.config().devnet.faucet_max_amount;
+    assert_eq!(
+        harness
+            .account(wallet.address())
+            .await
+            .unwrap()
+            .balance(&VERSE_ADDR),
+        funds
+    );
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(25))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(25)
+        })
+        .await
+        .expect("the send was not applied");
+    // Nothing was minted, so the send is all there is to seal.
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    assert_eq!(sealed.len(), 1);
+    assert_eq!(sealed[0].transaction_hashes.len(), 1);
+
+    harness.stop().await;
+}
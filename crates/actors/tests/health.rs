@@ -0,0 +1,246 @@
+#![cfg(test)]
+//! Test coverage for the health, readiness and node status methods.
+
+mod common;
+
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use common::{http_client, rpc_proxy, serve, spawn_as, stop};
+use ethereum_types::H256;
+use jsonrpsee::{http_client::HttpClient, server::ServerHandle};
+use lasr_actors::{BatchReceipts, DaHealth, LasrRpcServerImpl, MempoolMetrics, Shutdown};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{
+    AccountCacheMessage, ActorType, BatchHeader, BatchStatus, DaClientMessage, ExecutorHealth,
+    ExecutorMessage, ExecutorStatus,
+};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use ractor::{Actor, ActorProcessingErr, ActorRef, Message};
+use serde_json::{json, Value};
+use serial_test::serial;
+
+const CHAIN_ID: u64 = 31337;
+
+/// Stands in for an actor the node needs running, answering nothing.
+struct Stub<M>(PhantomData<fn() -> M>);
+
+#[async_trait]
+impl<M: Message> Actor for Stub<M> {
+    type Msg = M;
+    type State = ();
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(())
+    }
+}
+
+/// Stands in for the executor, reporting whatever status it is set to.
+struct MockExecutor;
+
+#[async_trait]
+impl Actor for MockExecutor {
+    type Msg = ExecutorMessage;
+    type State = Arc<Mutex<ExecutorStatus>>;
+    type Arguments = Self::State;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        status: Self::State,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(status)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        status: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let ExecutorMessage::GetHealth { reply } = message {
+            let status = *status.lock().unwrap();
+            reply
+                .send(ExecutorHealth {
+                    status,
+                    last_error: (status == ExecutorStatus::Unhealthy)
+                        .then(|| "runtime exited".to_string()),
+                    ..Default::default()
+                })
+                .ok();
+        }
+        Ok(())
+    }
+}
+
+struct Node {
+    actors: Vec<ractor::ActorCell>,
+    account_cache: ractor::ActorCell,
+    executor: Arc<Mutex<ExecutorStatus>>,
+    da: DaHealth,
+    shutdown: Shutdown,
+    receipts: BatchReceipts,
+    handle: ServerHandle,
+    client: HttpClient,
+}
+
+impl Node {
+    /// Starts a node whose account cache, executor and DA client are ready.
+    async fn start() -> Self {
+        let account_cache = spawn_as(
+            ActorType::AccountCache,
+            Stub::<AccountCacheMessage>(PhantomData),
+            (),
+        )
+        .await;
+        let da_client = spawn_as(
+            ActorType::DaClient,
+            Stub::<DaClientMessage>(PhantomData),
+            (),
+        )
+        .await;
+        let executor_status = Arc::new(Mutex::new(ExecutorStatus::Healthy));
+        let executor = spawn_as(ActorType::Executor, MockExecutor, executor_status.clone()).await;
+        let proxy = rpc_proxy().await;
+
+        let da = DaHealth::new(2);
+        let shutdown = Shutdown::default();
+        let receipts = BatchReceipts::default();
+        let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_eth_chain_id(CHAIN_ID)
+            .with_mempool_metrics(Arc::new(MempoolMetrics::default()))
+            .with_batch_receipts(receipts.clone())
+            .with_da_health(da.clone())
+            .with_shutdown(shutdown.clone());
+        let (handle, addr) = serve(rpc.into_rpc()).await;
+        Self {
+            actors: vec![proxy.get_cell(), executor.get_cell(), da_client.get_cell()],
+            account_cache: account_cache.get_cell(),
+            executor: executor_status,
+            da,
+            shutdown,
+            receipts,
+            handle,
+            client: http_client(addr),
+        }
+    }
+
+    async fn ready(&self) -> Value {
+        serde_json::from_str(&self.client.ready().await.expect("ready failed"))
+            .expect("readiness is not JSON")
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+        self.account_cache.stop_and_wait(None, None).await.ok();
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn readiness_follows_the_health_of_each_component() {
+    let node = Node::start().await;
+
+    assert_eq!(
+        node.ready().await,
+        json!({
+            "ready": true,
+            "shuttingDown": false,
+            "components": {
+                "accountCache": { "ready": true },
+                "da": { "ready": true },
+                "executor": { "ready": true },
+            },
+        })
+    );
+
+    // Failing health checks are only the executor's concern until it is
+    // restarted.
+    *node.executor.lock().unwrap() = ExecutorStatus::Degraded;
+    assert_eq!(node.ready().await["ready"], true);
+    *node.executor.lock().unwrap() = ExecutorStatus::Unhealthy;
+    let readiness = node.ready().await;
+    assert_eq!(readiness["ready"], false);
+    assert_eq!(
+        readiness["components"]["executor"],
+        json!({ "ready": false, "detail": "runtime exited" })
+    );
+    *node.executor.lock().unwrap() = ExecutorStatus::Healthy;
+
+    node.da.failed("connection refused");
+    assert_eq!(node.ready().await["ready"], true);
+    node.da.failed("connection refused");
+    let readiness = node.ready().await;
+    assert_eq!(readiness["ready"], false);
+    let detail = readiness["components"]["da"]["detail"].as_str().unwrap();
+    assert!(detail.contains("connection refused"), "{detail}");
+    node.da.succeeded();
+    assert_eq!(node.ready().await["ready"], true);
+
+    node.account_cache.stop_and_wait(None, None).await.unwrap();
+    let readiness = node.ready().await;
+    assert_eq!(readiness["ready"], false);
+    assert_eq!(readiness["components"]["accountCache"]["ready"], false);
+    assert_eq!(readiness["components"]["executor"]["ready"], true);
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_node_shutting_down_is_not_ready_but_still_answers() {
+    let node = Node::start().await;
+    node.shutdown.start();
+
+    let readiness = node.ready().await;
+    assert_eq!(readiness["ready"], false);
+    assert_eq!(readiness["shuttingDown"], true);
+    assert_eq!(readiness["components"]["executor"]["ready"], true);
+    let health: Value = serde_json::from_str(&node.client.health().await.unwrap()).unwrap();
+    assert_eq!(health, json!({ "status": "ok" }));
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn node_status_reports_the_latest_batch_and_the_da_breaker() {
+    let node = Node::start().await;
+
+    let status: Value = serde_json::from_str(&node.client.node_status().await.unwrap()).unwrap();
+    assert_eq!(status["versions"]["node"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(status["chainId"], CHAIN_ID);
+    assert_eq!(status["pendingTransactions"], 0);
+    assert_eq!(status["latestBatchId"], Value::Null);
+    assert_eq!(status["da"]["breaker"], "closed");
+
+    for batch_id in [3, 7, 5] {
+        let header = BatchHeader {
+            batch_id,
+            state_root: H256::repeat_byte(0x01),
+            transactions_root: H256::zero(),
+            events_root: H256::zero(),
+            blob_commitment: H256::repeat_byte(0x02),
+            blob_index: 0,
+            withdrawals: Vec::new(),
+        };
+        node.receipts.set(&header, BatchStatus::Finalized);
+    }
+    node.da.failed("timed out");
+    node.da.failed("timed out");
+    let status: Value = serde_json::from_str(&node.client.node_status().await.unwrap()).unwrap();
+    assert_eq!(status["latestBatchId"], 7);
+    assert_eq!(
+        status["da"],
+        json!({ "breaker": "open", "consecutiveFailures": 2, "lastError": "timed out" })
+    );
+
+    node.stop().await;
+}
@@ -0,0 +1,261 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for `HttpLasrClient`, reading and sending through the node's
+//! RPC server as a client outside the node would.
+
+use std::time::Duration;
+
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_rpc::{
+    AdminRpcClient, BearerAuthLayer, ClientError, HttpLasrClient, LasrRpcClient, RetryLayer,
+    WithLayer,
+};
+use lasr_types::{
+    Account, Address, Payload, PayloadBuilder, RecoverableSignature, RpcErrorCode, Transaction,
+    TransactionType, U256,
+};
+use lasr_wallet::{Wallet, WalletBuilder};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn key() -> (SecretKey, Address) {
+    let secret_key = SecretKey::from_slice(&[23; 32]).unwrap();
+    let address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    (secret_key, address)
+}
+
+fn payload(from: Address, value: u64, nonce: U256) -> Payload {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(nonce))
+        .from(from.inner())
+        .to(receiver().inner())
+        .program_id(VERSE_ADDR.inner())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(value))
+        .nonce(nonce)
+        .build()
+        .unwrap()
+}
+
+fn send(secret_key: &SecretKey, from: Address, value: u64, nonce: U256) -> Transaction {
+    let payload = payload(from, value, nonce);
+    let signature = RecoverableSignature::sign(&payload.digest(), secret_key);
+    (payload, signature).into()
+}
+
+/// A wallet for `secret_key` over `client`, funded by the node's faucet.
+async fn funded_wallet(
+    harness: &TestHarness,
+    client: &HttpLasrClient,
+    secret_key: SecretKey,
+    address: Address,
+) -> Wallet<HttpLasrClient> {
+    let funds = harness.config().devnet.faucet_max_amount;
+    LasrRpcClient::faucet(client, address.to_full_string(), funds.to_string(), None)
+        .await
+        .expect("the faucet turned the wallet away");
+    harness
+        .wait_for_account(address, |account| account.balance(&VERSE_ADDR) >= funds)
+        .await
+        .expect("the wallet was not funded");
+    let mut wallet = WalletBuilder::default()
+        .client(client.clone())
+        .sk(secret_key)
+        .builder(PayloadBuilder::default())
+        .address(address)
+        .account(Account::new(address))
+        .build()
+        .unwrap();
+    wallet
+        .get_account(&address)
+        .await
+        .expect("the wallet could not read its account");
+    wallet
+}
+
+#[tokio::test]
+#[serial]
+async fn a_wallet_sends_over_the_client_and_reads_back_what_it_sent() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let client = HttpLasrClient::builder()
+        .with_timeout(Duration::from_secs(10))
+        .with_header("x-client", "http-client-test")
+        .build(harness.rpc_url())
+        .unwrap();
+    assert_eq!(client.url(), harness.rpc_url());
+    let (secret_key, address) = key();
+    let mut wallet = funded_wallet(&harness, &client, secret_key, address).await;
+
+    let before = client.get_account(address).await.unwrap();
+    assert!(before.found);
+    assert_eq!(
+        client.get_nonce(address).await.unwrap(),
+        before.nonce + U256::from(1)
+    );
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("the wallet failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(5)
+        })
+        .await
+        .expect("the send was not applied");
+    let after = client.get_account(address).await.unwrap();
+    assert_eq!(after.nonce, before.nonce + U256::from(1));
+    assert_eq!(
+        client.get_nonce(address).await.unwrap(),
+        after.nonce + U256::from(1)
+    );
+
+    // An address with no account is answered as an empty one.
+    let nobody = client.get_account(Address::new([77; 20])).await.unwrap();
+    assert!(!nobody.found);
+    assert_eq!(
+        client.get_nonce(Address::new([77; 20])).await.unwrap(),
+        U256::from(1)
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn signed_transactions_are_simulated_estimated_and_sent_raw() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let client = HttpLasrClient::new(harness.rpc_url()).unwrap();
+    let (secret_key, address) = key();
+    funded_wallet(&harness, &client, secret_key, address).await;
+
+    let nonce = client.get_nonce(address).await.unwrap();
+    let transaction = send(&secret_key, address, 7, nonce);
+    let simulation = client.simulate(transaction.clone()).await.unwrap();
+    assert!(simulation.is_object(), "{simulation}");
+    let estimate = client
+        .estimate_fees(payload(address, 7, nonce))
+        .await
+        .unwrap();
+    assert!(estimate.is_object(), "{estimate}");
+    // Nothing was applied by either.
+    assert_eq!(client.get_nonce(address).await.unwrap(), nonce);
+
+    let raw = borsh::to_vec(&transaction).unwrap();
+    let transaction_hash = client.send_raw(&raw).await.unwrap();
+    assert_eq!(transaction_hash, transaction.hash_string());
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(7)
+        })
+        .await
+        .expect("the raw send was not applied");
+    let status = client
+        .get_transaction_status(&transaction_hash)
+        .await
+        .unwrap();
+    assert_ne!(status["status"], "unknown", "{status}");
+    let receipt = client.get_receipt(&transaction_hash).await.unwrap();
+    assert!(receipt.is_object(), "{receipt}");
+    assert_eq!(
+        client.get_nonce(address).await.unwrap(),
+        nonce + U256::from(1)
+    );
+
+    // Sent again, the nonce it took has been used.
+    let error = client.send_raw(&raw).await.unwrap_err();
+    assert!(error.code().is_some(), "{error}");
+}
+
+#[tokio::test]
+#[serial]
+async fn failures_are_told_apart_by_the_code_they_were_answered_with() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let client = HttpLasrClient::new(harness.rpc_url()).unwrap();
+    let (secret_key, address) = key();
+    funded_wallet(&harness, &client, secret_key, address).await;
+
+    let nonce = client.get_nonce(address).await.unwrap();
+    let far_ahead = send(&secret_key, address, 1, nonce + U256::from(1_000_000));
+    match client.send_raw(&borsh::to_vec(&far_ahead).unwrap()).await {
+        Err(ClientError::Rejected {
+            code: RpcErrorCode::InvalidNonce,
+            ..
+        }) => {}
+        other => panic!("a nonce far ahead was answered with {other:?}"),
+    }
+
+    match client.send_raw(b"not a transaction").await {
+        Err(ClientError::Rejected {
+            code: RpcErrorCode::InvalidParams,
+            message,
+            ..
+        }) => assert!(message.contains("borsh"), "{message}"),
+        other => panic!("bytes that are no transaction were answered with {other:?}"),
+    }
+
+    // The server's own codes for requests no method was called for are
+    // not LASR's.
+    let unknown = jsonrpsee::core::client::ClientT::request::<String, _>(
+        &client,
+        "lasr_noSuchMethod",
+        jsonrpsee::rpc_params![],
+    )
+    .await
+    .map_err(ClientError::from)
+    .unwrap_err();
+    assert!(
+        matches!(unknown, ClientError::Other { code: -32601, .. }),
+        "{unknown:?}"
+    );
+    assert_eq!(unknown.code(), None);
+
+    let unreachable = HttpLasrClient::builder()
+        .with_timeout(Duration::from_millis(200))
+        .build("http://127.0.0.1:9")
+        .unwrap();
+    let error = unreachable.get_nonce(address).await.unwrap_err();
+    assert!(
+        matches!(error, ClientError::Transport(_) | ClientError::Timeout),
+        "{error:?}"
+    );
+    assert!(matches!(
+        HttpLasrClient::builder()
+            .with_header("bad header", "x")
+            .build(harness.rpc_url()),
+        Err(ClientError::Config(_))
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn a_layered_client_is_let_through_to_the_admin_methods_with_its_token() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let client = HttpLasrClient::new(harness.rpc_url()).unwrap();
+    let error = ClientError::from(
+        AdminRpcClient::get_program_stats(&client, None, None, None)
+            .await
+            .unwrap_err(),
+    );
+    assert_eq!(error.code(), Some(RpcErrorCode::Unauthorized), "{error}");
+
+    let layered = client
+        .clone()
+        .with_layer(BearerAuthLayer::new(harness.admin_token()))
+        .with_layer(RetryLayer::default());
+    let stats = AdminRpcClient::get_program_stats(&layered, None, None, None)
+        .await
+        .expect("the token did not let the client through");
+    assert!(
+        serde_json::from_str::<serde_json::Value>(&stats).is_ok(),
+        "{stats}"
+    );
+    // The others are answered as they are without it.
+    let (_, address) = key();
+    LasrRpcClient::get_nonce(&layered, address.to_full_string())
+        .await
+        .unwrap();
+}
@@ -0,0 +1,80 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the proofs receipts carry that their transaction went
+//! into its batch, checked against the batch's header alone.
+
+use ethereum_types::H256;
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_messages::{verify_receipt, BatchHeader, InclusionProof};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Address, MAX_PROOF_DEPTH, U256};
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+#[tokio::test]
+#[serial]
+async fn a_genuine_receipt_is_verified_and_a_forged_one_is_not() {
+    let harness = TestHarness::start_with(|config| config.instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    harness.seal_batch().await.unwrap();
+    for byte in 9..12 {
+        wallet
+            .send(&receiver(byte), &VERSE_ADDR, U256::from(5))
+            .await
+            .expect("failed to send");
+    }
+    harness
+        .wait_for_account(receiver(11), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(5)
+        })
+        .await
+        .expect("the sends were not applied");
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    let batch = sealed[0].clone();
+    assert_eq!(batch.transaction_hashes.len(), 3);
+
+    // The header is fetched on its own, as a wallet would.
+    let header: BatchHeader =
+        serde_json::from_str(&rpc.get_batch(batch.batch_id, None).await.unwrap())
+            .expect("the batch is no header");
+    for transaction_hash in &batch.transaction_hashes {
+        let receipt: Value = serde_json::from_str(
+            &rpc.get_transaction_receipt(transaction_hash.clone())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let proof: InclusionProof = serde_json::from_value(receipt["inclusionProof"].clone())
+            .expect("the receipt carries no proof");
+        assert_eq!(proof.batch_id, batch.batch_id);
+        assert!(proof.proof.siblings.len() <= MAX_PROOF_DEPTH);
+        assert!(proof
+            .accounts
+            .iter()
+            .any(|account| account.address == sender));
+        assert!(verify_receipt(&proof, &header), "{transaction_hash}");
+
+        let mut forged = proof.clone();
+        forged.transaction_hash = H256::repeat_byte(0xee);
+        assert!(!verify_receipt(&forged, &header));
+        let mut forged = proof.clone();
+        forged.accounts[0].account_hash = H256::repeat_byte(0xee);
+        assert!(!verify_receipt(&forged, &header));
+        let mut forged = proof.clone();
+        forged.batch_id += 1;
+        assert!(!verify_receipt(&forged, &header));
+        let mut other = header.clone();
+        other.transactions_root = H256::repeat_byte(0xee);
+        assert!(!verify_receipt(&proof, &other));
+    }
+
+    harness.stop().await;
+}
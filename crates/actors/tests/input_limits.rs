@@ -0,0 +1,129 @@
+#![cfg(test)]
+//! Test coverage for limits on transaction inputs at the RPC server and the
+//! executor.
+
+mod common;
+
+use common::{rpc_proxy, serve, spawn_named, ws_client};
+use jsonrpsee::core::ClientError;
+use lasr_actors::{InputLimits, LasrRpcServerImpl};
+use lasr_compute::ExecutionLogs;
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{PayloadBuilder, Transaction, TransactionType, U256};
+
+const PROGRAM_ID: [u8; 20] = [3; 20];
+
+fn call(inputs: String) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from([1; 20])
+        .to(PROGRAM_ID)
+        .program_id(PROGRAM_ID)
+        .inputs(inputs)
+        .op("op".to_string())
+        .value(U256::from(0))
+        .nonce(U256::from(0))
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
+
+#[tokio::test]
+async fn rpc_server_rejects_oversized_inputs_with_the_limit() {
+    let proxy = rpc_proxy().await;
+    let (server_handle, addr) = serve(
+        LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_input_limits(InputLimits::new(1024, 4096))
+            .into_rpc(),
+    )
+    .await;
+    let client = ws_client(addr).await;
+
+    for transaction in [call("a".repeat(1025)), call("a".repeat(100_000))] {
+        let size = transaction.inputs().len();
+        match client.call(transaction).await {
+            Err(ClientError::Call(error)) => {
+                let data: serde_json::Value =
+                    serde_json::from_str(error.data().expect("error has no data").get())
+                        .expect("error data is not JSON");
+                assert_eq!(
+                    data,
+                    serde_json::json!({ "reason": "tooLarge", "size": size, "limit": 1024 })
+                );
+            }
+            other => panic!("oversized call was admitted: {other:?}"),
+        }
+    }
+
+    server_handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[cfg(feature = "mock_storage")]
+#[tokio::test]
+async fn executor_holds_inputs_to_the_program_limit() {
+    use lasr_actors::{AccountCacheActor, ExecutionEngine, MAX_INPUT_BYTES_KEY};
+    use lasr_compute::{OciBundler, OciBundlerBuilder, OciManager};
+    use lasr_messages::{AccountCacheMessage, ActorName, ActorType};
+    use lasr_types::{
+        AccountBuilder, AccountType, Address, ArbitraryData, Metadata, MockPersistenceStore,
+        PersistenceStore,
+    };
+    use std::collections::{BTreeMap, BTreeSet};
+
+    let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+        .await
+        .expect("failed to create mock storage");
+    let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+
+    let mut metadata = Metadata::new();
+    metadata
+        .inner_mut()
+        .insert(MAX_INPUT_BYTES_KEY.to_string(), "2048".to_string());
+    let program = AccountBuilder::default()
+        .account_type(AccountType::Program(Address::new(PROGRAM_ID)))
+        .owner_address(Address::new([1; 20]))
+        .nonce(U256::from(0))
+        .programs(BTreeMap::new())
+        .program_namespace(None)
+        .program_account_linked_programs(BTreeSet::new())
+        .program_account_data(ArbitraryData::new())
+        .program_account_metadata(metadata)
+        .try_build()
+        .expect("failed to build program account");
+    account_cache
+        .cast(AccountCacheMessage::Write {
+            account: program,
+            who: ActorType::Executor,
+            location: "executor_holds_inputs_to_the_program_limit".into(),
+        })
+        .expect("failed to cache program account");
+
+    let bundler: OciBundler<String, String> = OciBundlerBuilder::default()
+        .runtime("/usr/local/bin/runsc".to_string())
+        .base_images("./base_image".to_string())
+        .containers("./containers".to_string())
+        .payload_path("./payload".to_string())
+        .build()
+        .expect("failed to build bundler");
+    let engine =
+        ExecutionEngine::<jsonrpsee::ws_client::WsClient>::new(OciManager::new(bundler, None))
+            .with_input_limits(InputLimits::new(1024, 4096));
+
+    let at_limit = call("a".repeat(2048));
+    let inputs = engine
+        .parse_inputs(&at_limit, at_limit.op(), at_limit.inputs())
+        .await
+        .expect("inputs at the program's limit were rejected");
+    assert_eq!(inputs.inputs.len(), 2048);
+
+    let over_limit = call("a".repeat(2049));
+    let error = engine
+        .parse_inputs(&over_limit, over_limit.op(), over_limit.inputs())
+        .await
+        .expect_err("inputs over the program's limit were accepted");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(error.to_string().contains("limit of 2048 bytes"));
+
+    account_cache.stop_and_wait(None, None).await.ok();
+}
@@ -0,0 +1,65 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for instant-seal mode, in which every transaction is
+//! sealed into a batch of its own and settled before it is answered.
+
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Address, U256};
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).expect("answer is not JSON")
+}
+
+#[tokio::test]
+#[serial]
+async fn what_a_send_writes_is_read_back_as_soon_as_it_returns() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+
+    for sent in 1..=3u64 {
+        let nonce = harness.account(wallet.address()).await.unwrap().nonce();
+        wallet
+            .send(&receiver(), &VERSE_ADDR, U256::from(5))
+            .await
+            .expect("failed to send");
+
+        // Read straight after the send, without waiting on anything.
+        let received = harness
+            .account(receiver())
+            .await
+            .expect("the send was not applied");
+        assert_eq!(received.balance(&VERSE_ADDR), U256::from(5 * sent));
+        assert_eq!(
+            harness.account(wallet.address()).await.unwrap().nonce(),
+            nonce + U256::from(1)
+        );
+
+        let batch = harness
+            .batches()
+            .list()
+            .pop()
+            .expect("the send was not sealed");
+        assert_eq!(batch.transaction_hashes.len(), 1);
+        let status = parse(
+            &rpc.get_transaction_status(batch.transaction_hashes[0].clone())
+                .await
+                .unwrap(),
+        );
+        assert_eq!(status["status"], "settled");
+        let info = parse(&rpc.get_batch(batch.batch_id, None).await.unwrap());
+        assert_eq!(info["status"], "settled");
+    }
+
+    // Nothing is left for the batch requestor, or for a drain.
+    assert!(harness.seal_batch().await.unwrap().is_empty());
+    let report = harness.stop().await;
+    assert_eq!(report.persisted, 0);
+}
@@ -0,0 +1,205 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the spans a transaction is followed by from its
+//! admission to its batch, captured from a node booted in-process.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lasr_actors::{TestHarness, LIFECYCLE_TARGET, VERSE_ADDR};
+use lasr_types::{Address, U256};
+use serial_test::serial;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// A lifecycle span as it was opened, with the span it was opened under.
+#[derive(Clone, Debug, Default)]
+struct CapturedSpan {
+    name: String,
+    tx_hash: Option<String>,
+    parent: Option<(String, Option<String>)>,
+    /// The hashes of the transactions whose spans this one follows from.
+    follows_from: Vec<Option<String>>,
+    closed: bool,
+}
+
+/// The index of a span in the spans captured.
+struct Captured(usize);
+
+#[derive(Default)]
+struct TxHash(Option<String>);
+
+impl Visit for TxHash {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "tx_hash" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+}
+
+/// Keeps every lifecycle span opened.
+#[derive(Clone, Default)]
+struct Capture {
+    spans: Arc<Mutex<Vec<CapturedSpan>>>,
+}
+
+impl Capture {
+    fn spans(&self) -> Vec<CapturedSpan> {
+        self.spans.lock().unwrap().clone()
+    }
+
+    fn captured<S>(&self, id: &Id, ctx: &Context<'_, S>) -> Option<CapturedSpan>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let span = ctx.span(id)?;
+        let extensions = span.extensions();
+        let Captured(index) = extensions.get::<Captured>()?;
+        self.spans.lock().unwrap().get(*index).cloned()
+    }
+}
+
+impl<S> Layer<S> for Capture
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut tx_hash = TxHash::default();
+        attrs.record(&mut tx_hash);
+        let parent = span
+            .parent()
+            .and_then(|parent| self.captured(&parent.id(), &ctx))
+            .map(|parent| (parent.name, parent.tx_hash));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(CapturedSpan {
+            name: span.name().to_string(),
+            tx_hash: tx_hash.0,
+            parent,
+            ..Default::default()
+        });
+        span.extensions_mut().insert(Captured(spans.len() - 1));
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<'_, S>) {
+        let follows = self.captured(follows, &ctx).and_then(|span| span.tx_hash);
+        if let Some(span) = ctx.span(id) {
+            if let Some(Captured(index)) = span.extensions().get::<Captured>() {
+                self.spans.lock().unwrap()[*index]
+                    .follows_from
+                    .push(follows);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(Captured(index)) = span.extensions().get::<Captured>() {
+                self.spans.lock().unwrap()[*index].closed = true;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn a_send_is_followed_by_one_span_per_stage_under_its_transaction_span() {
+    let capture = Capture::default();
+    tracing_subscriber::registry()
+        .with(
+            capture
+                .clone()
+                .with_filter(filter_fn(|metadata| metadata.target() == LIFECYCLE_TARGET)),
+        )
+        .try_init()
+        .expect("failed to set the capturing subscriber");
+
+    let harness = TestHarness::start_with(|config| config.instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    harness.seal_batch().await.unwrap();
+    let receiver = Address::new([9; 20]);
+    wallet
+        .send(&receiver, &VERSE_ADDR, U256::from(25))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver, |account| {
+            account.balance(&VERSE_ADDR) == U256::from(25)
+        })
+        .await
+        .expect("the send was not applied");
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    let transaction_hash = sealed[0].transaction_hashes[0].clone();
+    let of_transaction = |span: &CapturedSpan| span.tx_hash.as_ref() == Some(&transaction_hash);
+
+    // Once batched, the transaction's span is closed.
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !capture
+            .spans()
+            .iter()
+            .any(|span| span.name == "transaction" && of_transaction(span) && span.closed)
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the transaction's span was not closed");
+
+    let spans = capture.spans();
+    let transactions: Vec<_> = spans
+        .iter()
+        .filter(|span| span.name == "transaction" && of_transaction(span))
+        .collect();
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].parent, None);
+    for stage in [
+        "rpc",
+        "scheduler",
+        "engine",
+        "pending",
+        "validator",
+        "batcher",
+    ] {
+        let stage_spans: Vec<_> = spans
+            .iter()
+            .filter(|span| span.name == stage && of_transaction(span))
+            .collect();
+        assert!(!stage_spans.is_empty(), "no {stage} span was opened");
+        for span in stage_spans {
+            assert_eq!(
+                span.parent,
+                Some(("transaction".to_string(), Some(transaction_hash.clone()))),
+                "the {stage} span is not under the transaction's span"
+            );
+            assert!(span.closed, "the {stage} span was left open");
+        }
+    }
+    // A send is never run by the executor.
+    assert!(!spans
+        .iter()
+        .any(|span| span.name == "executor" && of_transaction(span)));
+    // The batch it was sealed in was dispersed in a span of its own, linked
+    // to the transaction's.
+    let dispersals: Vec<_> = spans
+        .iter()
+        .filter(|span| {
+            span.name == "da_client" && span.follows_from.contains(&Some(transaction_hash.clone()))
+        })
+        .collect();
+    assert_eq!(dispersals.len(), 1);
+    assert_eq!(dispersals[0].parent, None);
+
+    harness.stop().await;
+}
@@ -0,0 +1,136 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for a node turning writes away while it is overloaded, and
+//! serving reads all the while.
+
+use std::time::Duration;
+
+use jsonrpsee::core::ClientError;
+use lasr_actors::{metrics, LoadSignals, TestHarness, VERSE_ADDR};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Address, Transaction, TransactionBuilder, TransactionType, U256};
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn transaction(from: Address, transaction_type: TransactionType) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(transaction_type)
+        .from(from.into())
+        .to(receiver().into())
+        .program_id(VERSE_ADDR.into())
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(1))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+/// The retry-after hint of a request turned away as overloaded.
+fn overloaded(result: Result<String, ClientError>) -> u64 {
+    match result {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::Overloaded.code());
+            let data: Value = serde_json::from_str(error.data().expect("error has no data").get())
+                .expect("error data is not JSON");
+            data["retryAfterMs"].as_u64().expect("no retry-after hint")
+        }
+        other => panic!("expected the node to be overloaded, got {other:?}"),
+    }
+}
+
+fn sample(name: &str) -> Option<f64> {
+    metrics::encode()
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{name} ")))
+        .and_then(|value| value.parse().ok())
+}
+
+#[tokio::test]
+#[serial]
+async fn writes_are_turned_away_while_overloaded_and_reads_are_served() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    let shedder = harness.load_shedder();
+    let config = shedder.config();
+    let shed_before = sample("lasr_rpc_shed_total").unwrap_or_default();
+
+    // The executor falls behind.
+    assert!(shedder.observe(LoadSignals {
+        execution_queue: config.shed.execution_queue,
+        ..Default::default()
+    }));
+    assert_eq!(sample("lasr_rpc_overloaded"), Some(1.0));
+    let retry_after = config.retry_after.as_millis() as u64;
+    assert_eq!(
+        overloaded(
+            rpc.send(transaction(sender, TransactionType::Send(U256::from(0))))
+                .await
+        ),
+        retry_after
+    );
+    assert_eq!(
+        overloaded(
+            rpc.call(transaction(sender, TransactionType::Call(U256::from(0))))
+                .await
+        ),
+        retry_after
+    );
+    assert!(wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .is_err());
+    assert_eq!(
+        sample("lasr_rpc_shed_total"),
+        Some(shed_before + 3.0),
+        "every write turned away is counted"
+    );
+
+    // Reads are served as ever.
+    rpc.get_account(sender.to_full_string())
+        .await
+        .expect("the sender's account was not served");
+    rpc.node_status()
+        .await
+        .expect("the node's status was not served");
+
+    // Load easing off a little is not enough to take writes again.
+    assert!(shedder.observe(LoadSignals {
+        execution_queue: config.recover.execution_queue + 1,
+        ..Default::default()
+    }));
+    overloaded(
+        rpc.send(transaction(sender, TransactionType::Send(U256::from(0))))
+            .await,
+    );
+
+    // Once it has fallen far enough they are.
+    assert!(!shedder.observe(LoadSignals {
+        execution_queue: config.recover.execution_queue,
+        event_loop_lag: Duration::from_millis(1),
+        ..Default::default()
+    }));
+    assert_eq!(sample("lasr_rpc_overloaded"), Some(0.0));
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("the send was turned away after the node recovered");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(5)
+        })
+        .await
+        .expect("the send was not applied");
+
+    harness.stop().await;
+}
@@ -0,0 +1,155 @@
+#![cfg(test)]
+//! Test coverage for the mailbox monitor raising the alarm on an actor that
+//! falls behind on its messages, and clearing it once the actor catches up.
+
+mod common;
+
+use std::future::Future;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::spawn_as;
+use lasr_actors::{
+    mailbox_handling, mailbox_probed, metrics, monitor_mailboxes, MailboxConfig, MailboxLoad,
+    MailboxMonitor, Overloaded,
+};
+use lasr_messages::{ActorType, MailboxProbe};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use ractor_cluster::RactorMessage;
+
+#[derive(RactorMessage)]
+enum SlowMessage {
+    /// Takes as long as given to handle.
+    Work(Duration),
+    ProbeMailbox(MailboxProbe),
+}
+
+impl From<MailboxProbe> for SlowMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        SlowMessage::ProbeMailbox(probe)
+    }
+}
+
+/// Stands in for the engine, handling each message as slowly as it is told
+/// to.
+struct SlowActor;
+
+#[async_trait]
+impl Actor for SlowActor {
+    type Msg = SlowMessage;
+    type State = ();
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let _handling = mailbox_handling(ActorType::Engine);
+        match message {
+            SlowMessage::Work(duration) => tokio::time::sleep(duration).await,
+            SlowMessage::ProbeMailbox(probe) => mailbox_probed(ActorType::Engine, probe),
+        }
+        Ok(())
+    }
+}
+
+fn sample(name: &str) -> f64 {
+    metrics::encode()
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{name} ")))
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+async fn eventually<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition().await {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting for {what}"));
+}
+
+#[tokio::test]
+async fn a_slowed_actor_raises_the_alarm_until_it_catches_up() {
+    let actor = spawn_as(ActorType::Engine, SlowActor, ()).await;
+    let monitor = MailboxMonitor::new(MailboxConfig::new(
+        MailboxLoad {
+            depth: 8,
+            latency: Duration::from_millis(200),
+        },
+        50,
+        Duration::from_millis(500),
+    ))
+    .watch::<SlowMessage>(ActorType::Engine);
+    let probing = tokio::spawn(monitor_mailboxes(
+        monitor.clone(),
+        Duration::from_millis(20),
+    ));
+    let overloaded = r#"lasr_mailbox_overloaded{actor="engine"}"#;
+    let overloads = r#"lasr_mailbox_overloads_total{actor="engine"}"#;
+    let overloads_before = sample(overloads);
+
+    // Keeping up with its messages, it is not overloaded.
+    for _ in 0..20 {
+        actor
+            .cast(SlowMessage::Work(Duration::from_millis(1)))
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(monitor.overloaded().is_empty());
+    assert!(monitor.check().is_ok());
+
+    // Slowed down, it falls behind and raises the alarm.
+    for _ in 0..10 {
+        actor
+            .cast(SlowMessage::Work(Duration::from_millis(100)))
+            .unwrap();
+    }
+    eventually("the alarm to be raised", || async {
+        !monitor.overloaded().is_empty()
+    })
+    .await;
+    assert_eq!(monitor.overloaded(), vec![ActorType::Engine]);
+    assert!(monitor.status()["engine"].overloaded);
+    assert_eq!(
+        monitor.check(),
+        Err(Overloaded {
+            retry_after: Duration::from_millis(500)
+        })
+    );
+    assert_eq!(sample(overloaded), 1.0);
+
+    // Once it has worked through them, the alarm clears.
+    eventually("the alarm to clear", || async {
+        monitor.overloaded().is_empty()
+    })
+    .await;
+    assert!(monitor.check().is_ok());
+    assert!(!monitor.status()["engine"].overloaded);
+    assert_eq!(sample(overloaded), 0.0);
+    assert_eq!(
+        sample(overloads),
+        overloads_before + 1.0,
+        "the alarm was raised once, not flapping"
+    );
+
+    probing.abort();
+    actor.stop(None);
+}
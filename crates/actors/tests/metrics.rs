@@ -0,0 +1,139 @@
+#![cfg(test)]
+//! Test coverage for the metrics served alongside the RPC server.
+
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+
+use common::{http_client, rpc_proxy};
+use jsonrpsee::{
+    core::{client::ClientT, ClientError},
+    rpc_params,
+};
+use lasr_actors::{
+    serve_rpc, AdminAccess, DaHealth, LasrRpcServerImpl, RateLimit, RateLimitConfig, RateLimiter,
+    RequestLimits, METRICS_PATH,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The samples served at `addr`, by name with their labels.
+async fn scrape(addr: SocketAddr) -> HashMap<String, f64> {
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("failed to connect to the node");
+    stream
+        .write_all(
+            format!("GET {METRICS_PATH} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await
+        .expect("failed to ask for metrics");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("failed to read metrics");
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("response has no body");
+    assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+    assert!(head.contains("text/plain; version=0.0.4"), "{head}");
+
+    body.lines()
+        .filter(|line| !line.starts_with('#') && !line.is_empty())
+        .map(|line| {
+            let (sample, value) = line.rsplit_once(' ').expect("sample has no value");
+            (
+                sample.to_string(),
+                value.parse().expect("value is not a number"),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn metrics_count_the_activity_of_the_node() {
+    let rate_limiter = RateLimiter::new(RateLimitConfig {
+        default: RateLimit {
+            per_second: 1000.0,
+            burst: 1000,
+        },
+        methods: BTreeMap::from([(
+            "lasr_health".to_string(),
+            RateLimit {
+                per_second: 0.001,
+                burst: 2,
+            },
+        )]),
+        allowlist: Vec::new(),
+    })
+    .unwrap();
+    let proxy = rpc_proxy().await;
+    let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default());
+    let (addr, handle) = serve_rpc(
+        "127.0.0.1:0".parse().unwrap(),
+        rpc.into_rpc(),
+        rate_limiter,
+        AdminAccess::Disabled,
+        None,
+        RequestLimits::default(),
+    )
+    .await
+    .expect("failed to serve rpc");
+    let client = http_client(addr);
+
+    client.health().await.expect("health failed");
+    client.health().await.expect("health failed");
+    assert!(
+        client.health().await.is_err(),
+        "health was not rate limited"
+    );
+    let unknown: Result<String, ClientError> =
+        client.request("lasr_noSuchMethod", rpc_params![]).await;
+    assert!(unknown.is_err());
+
+    let da = DaHealth::new(2);
+    da.failed("connection refused");
+    da.failed("connection refused");
+
+    let metrics = scrape(addr).await;
+    let sample = |name: &str| match metrics.get(name) {
+        Some(value) => *value,
+        None => panic!("{name} was not served"),
+    };
+    assert_eq!(
+        sample(r#"lasr_rpc_requests_total{method="lasr_health",outcome="ok"}"#),
+        2.0
+    );
+    assert_eq!(
+        sample(r#"lasr_rpc_requests_total{method="lasr_health",outcome="error"}"#),
+        1.0
+    );
+    assert_eq!(
+        sample(r#"lasr_rpc_request_duration_seconds_count{method="lasr_health"}"#),
+        3.0
+    );
+    assert_eq!(sample("lasr_rpc_rate_limited_total"), 1.0);
+    assert_eq!(
+        sample(r#"lasr_rpc_requests_total{method="unknown",outcome="error"}"#),
+        1.0
+    );
+    assert_eq!(
+        sample(r#"lasr_da_client_requests_total{outcome="error"}"#),
+        2.0
+    );
+    assert_eq!(sample("lasr_da_client_breaker_open"), 1.0);
+
+    // Methods a client makes up are not series of their own.
+    assert!(metrics
+        .keys()
+        .all(|sample| !sample.contains("noSuchMethod")));
+    assert!(metrics.keys().all(|sample| sample.starts_with("lasr_")));
+
+    handle.stop().ok();
+    proxy.stop(None);
+}
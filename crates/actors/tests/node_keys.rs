@@ -0,0 +1,214 @@
+#![cfg(test)]
+//! Test coverage for rotating the operator keys over the admin methods, and
+//! the fingerprints the node status reports for them.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use common::rpc_proxy;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use jsonrpsee::{
+    core::ClientError,
+    http_client::{HttpClient, HttpClientBuilder},
+    server::ServerHandle,
+};
+use lasr_actors::{
+    serve_rpc, AdminAccess, KeyRole, LasrRpcServerImpl, NodeKeys, RateLimiter, RequestLimits,
+    KEYSTORE_MANIFEST,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::{AdminRpcClient, AdminRpcServer, LasrRpcClient, LasrRpcServer};
+use lasr_types::Address;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::Value;
+
+const PASSWORD: &str = "correct horse battery staple";
+
+fn key(byte: u8) -> SecretKey {
+    SecretKey::from_slice(&[byte; 32]).unwrap()
+}
+
+fn signer(secret_key: &SecretKey) -> String {
+    Address::from(PublicKey::from_secret_key(&Secp256k1::new(), secret_key)).to_full_string()
+}
+
+/// A keystore settling with `operator.json` and attesting with
+/// `attestor.json`, holding `next.json` to rotate to.
+fn keystore(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lasr_keystore_{}_{name}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, secret_key) in [
+        ("operator.json", key(1)),
+        ("attestor.json", key(2)),
+        ("next.json", key(3)),
+    ] {
+        key_file(&dir, file, &secret_key);
+    }
+    std::fs::write(
+        dir.join(KEYSTORE_MANIFEST),
+        "[settlement]\nactive = \"operator.json\"\n\n[attestation]\nactive = \"attestor.json\"\n",
+    )
+    .unwrap();
+    dir
+}
+
+fn key_file(dir: &Path, file: &str, secret_key: &SecretKey) {
+    eth_keystore::encrypt_key(
+        dir,
+        &mut secp256k1::rand::rngs::OsRng,
+        secret_key.secret_bytes(),
+        PASSWORD,
+        Some(file),
+    )
+    .expect("failed to write key file");
+}
+
+async fn start(keys: NodeKeys) -> (ServerHandle, SocketAddr) {
+    let proxy = rpc_proxy().await;
+    let rate_limiter = RateLimiter::default();
+    let rpc = LasrRpcServerImpl::new(proxy, ExecutionLogs::default())
+        .with_rate_limiter(rate_limiter.clone())
+        .with_node_keys(keys);
+    let mut methods = LasrRpcServer::into_rpc(rpc.clone());
+    methods
+        .merge(AdminRpcServer::into_rpc(rpc))
+        .expect("failed to merge admin methods");
+    let admin = AdminAccess::Token("operator".to_string());
+    serve_rpc(
+        "127.0.0.1:0".parse().unwrap(),
+        methods,
+        rate_limiter,
+        admin,
+        None,
+        RequestLimits::default(),
+    )
+    .await
+    .expect("failed to serve rpc")
+}
+
+fn client(addr: SocketAddr) -> HttpClient {
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer operator"));
+    HttpClientBuilder::default()
+        .set_headers(headers)
+        .build(format!("http://{addr}"))
+        .expect("failed to build rpc client")
+}
+
+fn parse(json: &str) -> Value {
+    serde_json::from_str(json).expect("answer is not JSON")
+}
+
+/// The signers of the keys the node status reports, those retired included,
+/// under `role`.
+fn signers(status: &Value, role: &str) -> Vec<(String, bool)> {
+    status["keys"]
+        .as_array()
+        .expect("the node status has no keys")
+        .iter()
+        .filter(|key| key["role"] == role)
+        .map(|key| {
+            (
+                key["signer"].as_str().unwrap().to_string(),
+                key["active"].as_bool().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn error_code<T: std::fmt::Debug>(result: Result<T, ClientError>) -> i32 {
+    match result {
+        Err(ClientError::Call(error)) => error.code(),
+        other => panic!("rotation was not refused: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn a_rotation_swaps_the_active_key_and_keeps_the_old_one_trusted() {
+    let dir = keystore("rotation");
+    let keys = NodeKeys::open(&dir, PASSWORD).expect("failed to open keystore");
+    let (handle, addr) = start(keys.clone()).await;
+    let rpc = client(addr);
+
+    let status = parse(&rpc.node_status().await.unwrap());
+    assert_eq!(
+        signers(&status, "settlement"),
+        vec![(signer(&key(1)), true)]
+    );
+    assert_eq!(
+        signers(&status, "attestation"),
+        vec![(signer(&key(2)), true)]
+    );
+
+    let rotated = parse(
+        &rpc.rotate_key("attestation".to_string(), "next.json".to_string())
+            .await
+            .expect("failed to rotate"),
+    );
+    assert_eq!(rotated["signer"], signer(&key(3)));
+    assert_eq!(rotated["active"], true);
+    assert!(rotated["fingerprint"].as_str().unwrap().starts_with("0x"));
+    assert_eq!(
+        keys.key(KeyRole::Attestation).signer().to_full_string(),
+        signer(&key(3))
+    );
+
+    let status = parse(&rpc.node_status().await.unwrap());
+    assert_eq!(
+        signers(&status, "attestation"),
+        vec![(signer(&key(3)), true), (signer(&key(2)), false)]
+    );
+    // Only the attestation key was rotated.
+    assert_eq!(
+        signers(&status, "settlement"),
+        vec![(signer(&key(1)), true)]
+    );
+
+    // Reopened, the keystore holds what was rotated to.
+    let reopened = NodeKeys::open(&dir, PASSWORD).unwrap();
+    assert_eq!(reopened.fingerprints(), keys.fingerprints());
+
+    handle.stop().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn rotations_that_cannot_be_made_are_refused() {
+    let dir = keystore("refused");
+    let keys = NodeKeys::open(&dir, PASSWORD).expect("failed to open keystore");
+    let (handle, addr) = start(keys.clone()).await;
+    let rpc = client(addr);
+    let before = keys.fingerprints();
+
+    for (role, file) in [
+        ("operator", "next.json"),
+        ("settlement", "missing.json"),
+        ("settlement", "../next.json"),
+        ("settlement", "operator.json"),
+    ] {
+        assert_eq!(
+            error_code(rpc.rotate_key(role.to_string(), file.to_string()).await),
+            RpcErrorCode::InvalidParams.code(),
+            "rotating {role} to {file}"
+        );
+    }
+    assert_eq!(keys.fingerprints(), before);
+    handle.stop().ok();
+
+    // Keys given in the environment cannot be rotated.
+    let (handle, addr) = start(NodeKeys::new(key(1))).await;
+    assert_eq!(
+        error_code(
+            client(addr)
+                .rotate_key("settlement".to_string(), "next.json".to_string())
+                .await
+        ),
+        RpcErrorCode::Internal.code()
+    );
+    handle.stop().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
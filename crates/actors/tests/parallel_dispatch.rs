@@ -0,0 +1,217 @@
+#![cfg(test)]
+//! Test coverage for calls that do not conflict being handed to the
+//! executor at once, and those that do waiting only for the calls they
+//! conflict with.
+
+mod common;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{spawn_as, spawn_named};
+use lasr_actors::PendingTransactionActor;
+use lasr_messages::{ActorType, ExecutionCreditStatus, ExecutorMessage, PendingTransactionMessage};
+use lasr_types::{Address, Transaction, TransactionBuilder, TransactionType, U256};
+use ractor::{rpc::CallResult, Actor, ActorProcessingErr, ActorRef};
+use serial_test::serial;
+use tokio::sync::mpsc;
+
+const CREDITS: usize = 4;
+
+fn grant() {
+    let pending: ActorRef<PendingTransactionMessage> =
+        ractor::registry::where_is(ActorType::PendingTransactions.to_string())
+            .expect("pending transactions actor is not running")
+            .into();
+    pending
+        .cast(PendingTransactionMessage::ExecutionCredits { credits: CREDITS })
+        .expect("failed to cast to pending transactions");
+}
+
+/// Stands in for an executor with room for four calls, running nothing and
+/// handing on the calls it is given, which the test finishes for it.
+struct ParkingExecutor;
+
+#[async_trait]
+impl Actor for ParkingExecutor {
+    type Msg = ExecutorMessage;
+    type State = mpsc::UnboundedSender<Transaction>;
+    type Arguments = mpsc::UnboundedSender<Transaction>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        executed: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(executed)
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _executed: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        grant();
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        executed: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            ExecutorMessage::Exec { transaction } => {
+                executed.send(transaction).ok();
+            }
+            ExecutorMessage::GrantCredits => grant(),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// A call from `[from; 20]` to the program at `[program; 20]`.
+fn call(from: u8, program: u8, nonce: u64) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(0)))
+        .from([from; 20])
+        .to([program; 20])
+        .program_id([program; 20])
+        .op("run".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(nonce))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+async fn credits(pending: &ActorRef<PendingTransactionMessage>) -> ExecutionCreditStatus {
+    let result = pending
+        .call(
+            |reply| PendingTransactionMessage::GetExecutionCredits { reply },
+            Some(Duration::from_secs(5)),
+        )
+        .await
+        .expect("failed to call pending transactions actor");
+    match result {
+        CallResult::Success(status) => status,
+        _ => panic!("pending transactions actor did not answer"),
+    }
+}
+
+/// What running a workload achieved.
+struct Run {
+    /// The most calls with the executor at once.
+    parallelism: usize,
+    /// The nonces of each sender's calls, in the order they were run.
+    order: HashMap<Address, Vec<U256>>,
+}
+
+/// Runs `workload`, finishing the calls with the executor one at a time,
+/// latest first.
+async fn run(workload: Vec<Transaction>) -> Run {
+    let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+    let (executed_tx, mut executed) = mpsc::unbounded_channel();
+    let executor = spawn_as(ActorType::Executor, ParkingExecutor, executed_tx).await;
+    while credits(&pending).await.granted.is_none() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let calls = workload.len();
+    for transaction in workload {
+        pending
+            .cast(PendingTransactionMessage::NewCall { transaction })
+            .unwrap();
+    }
+    let mut running: Vec<Transaction> = Vec::new();
+    let mut finished = 0;
+    let mut parallelism = 0;
+    let mut order: HashMap<Address, Vec<U256>> = HashMap::new();
+    while finished < calls {
+        // Everything handed on so far has reached the executor once it has
+        // as many calls as the pending transactions say are with it.
+        let in_flight = credits(&pending).await.in_flight;
+        while running.len() < in_flight {
+            let transaction = tokio::time::timeout(Duration::from_secs(5), executed.recv())
+                .await
+                .expect("no call was handed to the executor")
+                .expect("executor went away");
+            order
+                .entry(transaction.from())
+                .or_default()
+                .push(transaction.nonce());
+            running.push(transaction);
+        }
+        parallelism = parallelism.max(running.len());
+        let transaction = running.pop().expect("no call is with the executor");
+        pending
+            .cast(PendingTransactionMessage::ExecSuccess { transaction })
+            .unwrap();
+        finished += 1;
+    }
+    // Every call ran once.
+    assert!(executed.try_recv().is_err());
+    assert_eq!(credits(&pending).await.in_flight, 0);
+
+    executor.stop_and_wait(None, None).await.unwrap();
+    pending.stop_and_wait(None, None).await.unwrap();
+    Run { parallelism, order }
+}
+
+#[tokio::test]
+#[serial]
+async fn calls_that_do_not_conflict_run_at_once_up_to_the_credits() {
+    let run = run((1..=8).map(|from| call(from, from + 100, 0)).collect()).await;
+    assert_eq!(run.parallelism, CREDITS);
+}
+
+#[tokio::test]
+#[serial]
+async fn calls_that_conflict_run_one_after_another_in_the_order_they_were_made() {
+    let run = run((0..4).map(|nonce| call(1, 101, nonce)).collect()).await;
+    assert_eq!(run.parallelism, 1);
+    assert_eq!(
+        run.order[&Address::new([1; 20])],
+        (0..4).map(U256::from).collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn a_call_waits_only_for_the_calls_it_conflicts_with() {
+    let workload = (0..3)
+        .flat_map(|nonce| [call(1, 101, nonce), call(2, 102, nonce)])
+        .collect();
+    let run = run(workload).await;
+    assert_eq!(run.parallelism, 2);
+    for from in [1, 2] {
+        assert_eq!(
+            run.order[&Address::new([from; 20])],
+            (0..3).map(U256::from).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn calls_of_two_senders_to_one_program_run_one_after_another() {
+    // Both bump one counter the program keeps, so neither may run on what
+    // the program held before the other's call.
+    let workload = (0..3)
+        .flat_map(|nonce| [call(1, 100, nonce), call(2, 100, nonce)])
+        .collect();
+    let run = run(workload).await;
+    assert_eq!(run.parallelism, 1);
+    for from in [1, 2] {
+        assert_eq!(
+            run.order[&Address::new([from; 20])],
+            (0..3).map(U256::from).collect::<Vec<_>>()
+        );
+    }
+}
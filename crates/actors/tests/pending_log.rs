@@ -0,0 +1,129 @@
+#![cfg(test)]
+//! Test coverage for the pending pool being replayed from its log when the
+//! pending transactions actor is started again.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{send, spawn_as, spawn_named};
+use lasr_actors::PendingTransactionActor;
+use lasr_messages::{
+    ActorType, LasrError, PendingTransactionMessage, PendingTransactionSummary, ValidatorMessage,
+};
+use lasr_types::{Address, Transaction, U256};
+use ractor::{rpc::CallResult, Actor, ActorProcessingErr, ActorRef};
+use tokio::sync::mpsc;
+
+/// Stands in for the validator, handing on the transactions it is given.
+struct MockValidator;
+
+#[async_trait]
+impl Actor for MockValidator {
+    type Msg = ValidatorMessage;
+    type State = mpsc::UnboundedSender<Transaction>;
+    type Arguments = mpsc::UnboundedSender<Transaction>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        validated: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(validated)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        validated: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let ValidatorMessage::PendingTransaction { transaction } = message {
+            validated.send(transaction).ok();
+        }
+        Ok(())
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("pending_log_{}_{}.log", name, std::process::id()));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+async fn start(path: &Path) -> ActorRef<PendingTransactionMessage> {
+    let actor = spawn_named(
+        PendingTransactionActor::new().with_log_path(path.to_path_buf()),
+        (),
+    )
+    .await;
+    actor
+}
+
+async fn pending(actor: &ActorRef<PendingTransactionMessage>) -> Vec<PendingTransactionSummary> {
+    let result = actor
+        .call(
+            |reply| PendingTransactionMessage::GetPendingByAddress {
+                address: Address::new([1; 20]),
+                reply,
+            },
+            Some(Duration::from_secs(5)),
+        )
+        .await
+        .expect("failed to call pending transactions actor");
+    match result {
+        CallResult::Success(page) => page,
+        _ => panic!("pending transactions actor did not answer"),
+    }
+}
+
+#[tokio::test]
+async fn the_pending_pool_is_replayed_after_a_restart() {
+    let (validated_tx, mut validated) = mpsc::unbounded_channel();
+    let validator = spawn_as(ActorType::Validator, MockValidator, validated_tx).await;
+    let path = temp_path("restart");
+
+    let actor = start(&path).await;
+    for nonce in 0..3 {
+        actor
+            .cast(PendingTransactionMessage::New {
+                transaction: send(nonce),
+                outputs: None,
+                replace: false,
+            })
+            .unwrap();
+    }
+    // One found invalid leaves the pool, and is not replayed.
+    actor
+        .cast(PendingTransactionMessage::Invalid {
+            transaction: send(2),
+            e: LasrError::Custom("invalid".to_string()),
+        })
+        .unwrap();
+    assert_eq!(pending(&actor).await.len(), 2);
+    actor.stop_and_wait(None, None).await.unwrap();
+    while validated.try_recv().is_ok() {}
+
+    let actor = start(&path).await;
+    assert_eq!(pending(&actor).await.len(), 0);
+    actor.cast(PendingTransactionMessage::Replay).unwrap();
+    assert_eq!(
+        pending(&actor)
+            .await
+            .iter()
+            .map(|summary| summary.nonce)
+            .collect::<Vec<_>>(),
+        vec![U256::from(0), U256::from(1)]
+    );
+    let revalidated = tokio::time::timeout(Duration::from_secs(5), validated.recv())
+        .await
+        .expect("replayed transaction was not validated");
+    assert_eq!(revalidated, Some(send(0)));
+
+    actor.stop_and_wait(None, None).await.unwrap();
+    validator.stop_and_wait(None, None).await.unwrap();
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,220 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for counting what each program costs the node, ranked by
+//! `admin_getProgramStats` and exported to Prometheus for the programs
+//! invoked most.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use lasr_actors::{
+    metrics, snapshot_program_stats, Batcher, Genesis, GenesisAccount, GenesisProgram, Page,
+    ProgramStat, TestHarness, DEFAULT_ETH_CHAIN_ID, VERSE_ADDR,
+};
+use lasr_rpc::AdminRpcClient;
+use lasr_types::{
+    Address, AddressOrNamespace, DataValue, Instruction, Outputs, OutputsBuilder, TokenField,
+    TokenFieldValue, TokenOrProgramUpdate, TokenUpdateBuilder, TokenUpdateFieldBuilder,
+    Transaction, TransactionBuilder, TransactionType, UpdateInstructionBuilder, U256,
+};
+use serial_test::serial;
+
+fn alice() -> Address {
+    Address::new([1; 20])
+}
+
+fn program(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+async fn harness() -> TestHarness {
+    let path = std::env::temp_dir().join(format!("program_stats_{}.toml", std::process::id()));
+    let genesis = Genesis {
+        chain_id: DEFAULT_ETH_CHAIN_ID,
+        programs: [0xaa, 0xbb]
+            .into_iter()
+            .map(|byte| GenesisProgram {
+                program_id: program(byte),
+                owner: alice(),
+                content_id: "bafy".to_string(),
+                metadata: BTreeMap::new(),
+                auth_program: None,
+            })
+            .collect(),
+        accounts: vec![GenesisAccount {
+            address: alice(),
+            balances: BTreeMap::from([
+                (VERSE_ADDR, U256::from(1_000)),
+                (program(0xaa), U256::from(10)),
+                (program(0xbb), U256::from(10)),
+            ]),
+        }],
+    };
+    std::fs::write(&path, toml::to_string(&genesis).unwrap()).unwrap();
+    TestHarness::start_with(|config| config.genesis(path))
+        .await
+        .expect("failed to start node")
+}
+
+fn call(program_id: Address, nonce: u64) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(nonce)))
+        .from(alice().into())
+        .to(alice().into())
+        .program_id(program_id.into())
+        .op("store".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(nonce))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+/// Outputs storing `value` under `key` in the data of Alice's `token`.
+fn store(token: Address, key: &str, value: String) -> Outputs {
+    let update = TokenUpdateBuilder::new()
+        .account(AddressOrNamespace::Address(alice()))
+        .token(AddressOrNamespace::Address(token))
+        .add_update(
+            TokenUpdateFieldBuilder::new()
+                .field(TokenField::Data)
+                .value(TokenFieldValue::Data(DataValue::Insert(
+                    key.to_string(),
+                    value,
+                )))
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    OutputsBuilder::new()
+        .add_instruction(Instruction::Update(
+            UpdateInstructionBuilder::new()
+                .add_update(TokenOrProgramUpdate::TokenUpdate(update))
+                .build(),
+        ))
+        .build()
+        .unwrap()
+}
+
+/// The value of the sample of `name` for `program_id`, if it is exported.
+fn sample(name: &str, program_id: Address) -> Option<f64> {
+    let prefix = format!("{name}{{program=\"{}\"}} ", program_id.to_full_string());
+    metrics::encode()
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .and_then(|value| value.parse().ok())
+}
+
+async fn ranked(harness: &TestHarness, metric: &str) -> Vec<ProgramStat> {
+    let page: Page<ProgramStat> = serde_json::from_str(
+        &harness
+            .admin()
+            .unwrap()
+            .get_program_stats(None, None, Some(metric.to_string()))
+            .await
+            .expect("failed to get program stats"),
+    )
+    .unwrap();
+    page.items
+}
+
+fn ids(stats: &[ProgramStat]) -> Vec<String> {
+    stats.iter().map(|stat| stat.program_id.clone()).collect()
+}
+
+#[tokio::test]
+#[serial]
+async fn a_mixed_workload_is_counted_and_ranked_by_each_metric() {
+    let harness = harness().await;
+    let stats = harness.program_stats();
+    let (a, b, c) = (program(0xaa), program(0xbb), program(0xcc));
+
+    // Counted as the executor counts the calls it runs: many quick calls of
+    // one program, some failing, a few slow ones of another, and a single
+    // failure of a third.
+    for run in 0..6 {
+        stats.executed(&a.to_full_string(), run % 3 != 0, Duration::from_millis(2));
+    }
+    for _ in 0..3 {
+        stats.executed(&b.to_full_string(), true, Duration::from_millis(40));
+    }
+    stats.executed(&c.to_full_string(), false, Duration::from_millis(1));
+    // And the outputs of those that succeeded applied, most of them by the
+    // slow program.
+    for (nonce, (program_id, value)) in [(b, "x".repeat(48)), (b, "y".repeat(48)), (a, "z".into())]
+        .into_iter()
+        .enumerate()
+    {
+        Batcher::apply_instructions_to_accounts(
+            harness.batcher(),
+            call(program_id, nonce as u64),
+            store(program_id, &format!("k{nonce}"), value),
+        )
+        .await
+        .expect("failed to apply outputs");
+    }
+
+    let by_invocations = ranked(&harness, "invocations").await;
+    let order = |programs: [Address; 3]| programs.map(|p| p.to_full_string()).to_vec();
+    assert_eq!(ids(&by_invocations), order([a, b, c]));
+    assert_eq!(
+        by_invocations
+            .iter()
+            .map(|stat| (stat.invocations, stat.failures))
+            .collect::<Vec<_>>(),
+        vec![(6, 2), (3, 0), (1, 1)]
+    );
+    assert_eq!(by_invocations[1].total_execution_micros, 120_000);
+    assert_eq!(by_invocations[1].p99_execution_micros, 40_000);
+    assert_eq!(by_invocations[2].bytes_written, 0);
+    assert_eq!(ids(&ranked(&harness, "failures").await), order([a, c, b]));
+    assert_eq!(
+        ids(&ranked(&harness, "executionTime").await),
+        order([b, a, c])
+    );
+    let by_bytes = ranked(&harness, "bytesWritten").await;
+    assert_eq!(ids(&by_bytes), order([b, a, c]));
+    assert!(by_bytes[1].bytes_written > 0);
+
+    // Paged in the same order.
+    let admin = harness.admin().unwrap();
+    let first: Page<ProgramStat> = serde_json::from_str(
+        &admin
+            .get_program_stats(None, Some(2), Some("bytesWritten".to_string()))
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(ids(&first.items), order([b, a, c])[..2].to_vec());
+    let rest: Page<ProgramStat> = serde_json::from_str(
+        &admin
+            .get_program_stats(first.next_cursor, Some(2), Some("bytesWritten".to_string()))
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(ids(&rest.items), vec![c.to_full_string()]);
+    assert!(admin
+        .get_program_stats(None, None, Some("latency".to_string()))
+        .await
+        .is_err());
+
+    // Only the programs invoked most are exported.
+    let snapshots = tokio::spawn(snapshot_program_stats(
+        stats
+            .with_top(2)
+            .with_flush_interval(Duration::from_millis(20)),
+    ));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    snapshots.abort();
+    assert_eq!(sample("lasr_programs_top_invocations", a), Some(6.0));
+    assert_eq!(sample("lasr_programs_top_failures", a), Some(2.0));
+    assert_eq!(sample("lasr_programs_top_invocations", b), Some(3.0));
+    assert_eq!(sample("lasr_programs_top_invocations", c), None);
+
+    harness.stop().await;
+}
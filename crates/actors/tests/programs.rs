@@ -0,0 +1,239 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for querying the programs registered through the RPC server.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{http_client, rpc_proxy, serve, spawn_named, stop};
+use eigenda_client::proof::BlobVerificationProof;
+use jsonrpsee::{core::ClientError, http_client::HttpClient, server::ServerHandle};
+use lasr_actors::{
+    get_account, AccountCacheActor, Batcher, BatcherActor, LasrRpcServerImpl, TaskScheduler,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{AccountCacheMessage, ActorType, RpcErrorCode};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{
+    Account, Address, MockPersistenceStore, PayloadBuilder, PersistenceStore, Transaction,
+    TransactionType, U256,
+};
+use ractor::{concurrency::OneshotReceiver, ActorCell};
+use serde_json::{json, Value};
+use serial_test::serial;
+use tokio::sync::{mpsc, Mutex};
+
+fn deployer() -> Address {
+    Address::new([1; 20])
+}
+
+fn registration(nonce: u64, inputs: Value) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::RegisterProgram(U256::from(nonce)))
+        .from(deployer().into())
+        .to(deployer().into())
+        .program_id(Address::new([0; 20]).into())
+        .inputs(inputs.to_string())
+        .op(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(nonce))
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
+
+struct Node {
+    _batcher_rx: mpsc::Receiver<OneshotReceiver<(String, BlobVerificationProof)>>,
+    batcher: Arc<Mutex<Batcher>>,
+    actors: Vec<ActorCell>,
+    handle: ServerHandle,
+    client: HttpClient,
+}
+
+impl Node {
+    /// Starts a node with a deployer account and nothing registered.
+    async fn start() -> Self {
+        let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+        let batcher = Batcher::new(receivers_thread_tx);
+        let programs = batcher.programs();
+        let batcher = Arc::new(Mutex::new(batcher));
+        let batcher_actor = spawn_named(BatcherActor::new(), batcher.clone()).await;
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .expect("failed to create mock storage");
+        let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+        let scheduler = spawn_named(TaskScheduler::new(), ()).await;
+        let proxy = rpc_proxy().await;
+
+        account_cache
+            .cast(AccountCacheMessage::Write {
+                account: Account::new(deployer()),
+                who: ActorType::AccountCache,
+                location: "programs test".into(),
+            })
+            .expect("failed to seed the deployer");
+
+        let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_program_registry(programs);
+        let (handle, addr) = serve(rpc.into_rpc()).await;
+        Self {
+            _batcher_rx,
+            batcher,
+            actors: vec![
+                proxy.get_cell(),
+                scheduler.get_cell(),
+                batcher_actor.get_cell(),
+                account_cache.get_cell(),
+            ],
+            handle,
+            client: http_client(addr),
+        }
+    }
+
+    /// Registers the program in `inputs`, answering its program id.
+    async fn register(&self, nonce: u64, inputs: Value, runtime: Option<&str>) -> Address {
+        let transaction = registration(nonce, inputs.clone());
+        let program_id = lasr_contract::create_program_id(
+            inputs["contentId"].as_str().unwrap().to_string(),
+            &transaction,
+        )
+        .expect("failed to create program id");
+        Batcher::apply_program_registration_in_runtime(
+            self.batcher.clone(),
+            transaction,
+            runtime.map(str::to_string),
+        )
+        .await
+        .expect("failed to register program");
+        program_id
+    }
+
+    async fn program(&self, program_id: Address) -> Value {
+        serde_json::from_str(
+            &self
+                .client
+                .get_program(program_id.to_full_string())
+                .await
+                .expect("getProgram failed"),
+        )
+        .expect("program is not JSON")
+    }
+
+    async fn list(&self, cursor: Option<String>, limit: usize) -> Value {
+        serde_json::from_str(
+            &self
+                .client
+                .list_programs(cursor, Some(limit))
+                .await
+                .expect("listPrograms failed"),
+        )
+        .expect("page is not JSON")
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn registered_programs_are_answered_by_id_and_listed() {
+    let node = Node::start().await;
+    let token = node
+        .register(0, json!({ "contentId": "token-cid" }), None)
+        .await;
+    let service = node
+        .register(
+            1,
+            json!({ "contentId": "service-cid", "inputFormat": "json", "maxInputBytes": 512 }),
+            Some("wasm"),
+        )
+        .await;
+
+    // The token program describes its token after it is registered.
+    let mut account = get_account(token, ActorType::RpcServer)
+        .await
+        .expect("token program was not written");
+    for (key, value) in [("name", "Token"), ("symbol", "TKN"), ("decimals", "18")] {
+        account
+            .program_account_metadat_mut()
+            .insert(key.to_string(), value.to_string());
+    }
+    ractor::registry::where_is(ActorType::AccountCache.to_string())
+        .expect("account cache is not running")
+        .send_message(AccountCacheMessage::Write {
+            account,
+            who: ActorType::AccountCache,
+            location: "programs test".into(),
+        })
+        .expect("failed to write token metadata");
+
+    let program = node.program(token).await;
+    assert_eq!(program["programId"], token.to_string());
+    assert_eq!(program["contentId"], "token-cid");
+    assert_eq!(program["runtime"], Value::Null);
+    assert_eq!(program["owner"], deployer().to_string());
+    assert_eq!(program["batchId"], Value::Null);
+    assert_eq!(
+        program["tokenMetadata"],
+        json!({
+            "name": "Token",
+            "symbol": "TKN",
+            "decimals": 18,
+            "totalSupply": null,
+            "initializedSupply": null,
+        })
+    );
+
+    let program = node.program(service).await;
+    assert_eq!(program["contentId"], "service-cid");
+    assert_eq!(program["runtime"], "wasm");
+    assert_eq!(program["metadata"]["input_format"], "json");
+    assert_eq!(program["metadata"]["max_input_bytes"], "512");
+    assert_eq!(program["tokenMetadata"], Value::Null);
+
+    let mut ids = vec![token, service];
+    ids.sort();
+    let ids: Vec<_> = ids.iter().map(Address::to_string).collect();
+    let first = node.list(None, 1).await;
+    assert_eq!(first["total"], 2);
+    assert_eq!(first["items"][0]["programId"], ids[0]);
+    let next = node
+        .list(first["nextCursor"].as_str().map(str::to_string), 1)
+        .await;
+    assert_eq!(next["items"][0]["programId"], ids[1]);
+    assert_eq!(next["nextCursor"], Value::Null);
+    let whole = node.list(None, 10).await;
+    assert_eq!(whole["items"].as_array().unwrap().len(), 2);
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn unknown_programs_are_not_found() {
+    let node = Node::start().await;
+    node.register(0, json!({ "contentId": "token-cid" }), None)
+        .await;
+
+    match node
+        .client
+        .get_program(Address::new([9; 20]).to_full_string())
+        .await
+    {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::NotFound.code())
+        }
+        other => panic!("expected not found, got {other:?}"),
+    }
+    match node.client.get_program("not an address".to_string()).await {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::InvalidParams.code())
+        }
+        other => panic!("expected invalid params, got {other:?}"),
+    }
+
+    node.stop().await;
+}
@@ -0,0 +1,20 @@
+;; Example WASM program that logs while it runs.
+;;
+;; Logs "still working" twenty times through the `log` host function, then
+;; writes empty outputs.
+(module
+  (import "lasr" "log" (func $log (param i32 i32)))
+  (import "lasr" "write_output" (func $write_output (param i32 i32)))
+
+  (memory (export "memory") 1)
+
+  (data (i32.const 0) "still working")
+  (data (i32.const 64) "{}")
+
+  (func (export "run")
+    (local $i i32)
+    (loop $chatter
+      (call $log (i32.const 0) (i32.const 13))
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $chatter (i32.lt_u (local.get $i) (i32.const 20))))
+    (call $write_output (i32.const 64) (i32.const 2))))
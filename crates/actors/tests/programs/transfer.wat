@@ -0,0 +1,27 @@
+;; Example WASM program for the `wasm` runtime.
+;;
+;; Echoes the inputs it was called with and transfers one of the program's
+;; tokens, 0x0303..03, from the caller, 0x0202..02, to 0x0101..01.
+(module
+  (import "lasr" "input_len" (func $input_len (result i32)))
+  (import "lasr" "read_input" (func $read_input (param i32)))
+  (import "lasr" "write_output" (func $write_output (param i32 i32)))
+
+  (memory (export "memory") 1)
+
+  ;; Written before the inputs.
+  (data (i32.const 0) "{\"computeInputs\":")
+  ;; Written after the inputs.
+  (data (i32.const 1024) ",\"instructions\":[{\"transfer\":{\"token\":\"0x0303030303030303030303030303030303030303\",\"from\":{\"address\":\"0x0202020202020202020202020202020202020202\"},\"to\":{\"address\":\"0x0101010101010101010101010101010101010101\"},\"amount\":\"0x0000000000000000000000000000000000000000000000000000000000000001\",\"ids\":[]}}]}")
+
+  (func (export "run")
+    (local $len i32)
+    (local.set $len (call $input_len))
+    ;; The inputs are read into the pages after the first.
+    (drop
+      (memory.grow
+        (i32.add (i32.div_u (local.get $len) (i32.const 65536)) (i32.const 1))))
+    (call $read_input (i32.const 65536))
+    (call $write_output (i32.const 0) (i32.const 17))
+    (call $write_output (i32.const 65536) (local.get $len))
+    (call $write_output (i32.const 1024) (i32.const 299))))
@@ -0,0 +1,143 @@
+#![cfg(test)]
+//! Test coverage for rate limiting clients of the RPC server.
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use common::rpc_proxy;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use jsonrpsee::{
+    core::ClientError,
+    http_client::{HttpClient, HttpClientBuilder},
+    server::ServerHandle,
+    types::ErrorObjectOwned,
+};
+use lasr_actors::{
+    serve_rpc, AdminAccess, LasrRpcServerImpl, RateLimit, RateLimitConfig, RateLimiter,
+    RequestLimits,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{RpcErrorCode, RpcMessage};
+use lasr_rpc::{AdminRpcClient, AdminRpcServer, LasrRpcClient, LasrRpcServer};
+use lasr_types::Address;
+use ractor::ActorRef;
+use serde_json::{json, Value};
+
+async fn start(rate_limiter: RateLimiter) -> (ActorRef<RpcMessage>, ServerHandle, HttpClient) {
+    let proxy = rpc_proxy().await;
+    let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+        .with_rate_limiter(rate_limiter.clone());
+    let mut methods = LasrRpcServer::into_rpc(rpc.clone());
+    methods
+        .merge(AdminRpcServer::into_rpc(rpc))
+        .expect("failed to merge admin methods");
+    let (addr, handle) = serve_rpc(
+        "127.0.0.1:0".parse().unwrap(),
+        methods,
+        rate_limiter,
+        AdminAccess::Token("operator".to_string()),
+        None,
+        RequestLimits::default(),
+    )
+    .await
+    .expect("failed to serve rpc");
+    let client = HttpClientBuilder::default()
+        .set_headers(HeaderMap::from_iter([(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer operator"),
+        )]))
+        .build(format!("http://{addr}"))
+        .expect("failed to build rpc client");
+    (proxy, handle, client)
+}
+
+fn limit(per_second: f64, burst: u32) -> RateLimit {
+    RateLimit { per_second, burst }
+}
+
+fn throttled<T: std::fmt::Debug>(result: Result<T, ClientError>) -> ErrorObjectOwned {
+    match result {
+        Err(ClientError::Call(error)) if error.code() == RpcErrorCode::RateLimited.code() => error,
+        other => panic!("request was not rate limited: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn a_client_past_its_burst_is_limited_until_its_bucket_refills() {
+    let rate_limiter = RateLimiter::new(RateLimitConfig {
+        default: limit(5.0, 3),
+        methods: BTreeMap::from([("admin_getRateLimits".to_string(), limit(100.0, 100))]),
+        allowlist: Vec::new(),
+    })
+    .unwrap();
+    let (proxy, handle, client) = start(rate_limiter).await;
+
+    for _ in 0..3 {
+        client
+            .get_verse_supply()
+            .await
+            .expect("request within the burst was turned away");
+    }
+    let error = throttled(client.get_verse_supply().await);
+    let data: Value = serde_json::from_str(error.data().expect("error has no data").get())
+        .expect("error data is not JSON");
+    let retry_after_ms = data["retryAfterMs"].as_u64().expect("no retry after hint");
+    assert!((1..=200).contains(&retry_after_ms), "{retry_after_ms}");
+
+    tokio::time::sleep(Duration::from_millis(retry_after_ms + 50)).await;
+    client
+        .get_verse_supply()
+        .await
+        .expect("request after the wait was turned away");
+
+    let status: Value = serde_json::from_str(&client.get_rate_limits().await.unwrap()).unwrap();
+    assert_eq!(status["throttled"], json!({ "lasr_getVerseSupply": 1 }));
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+async fn limits_are_adjusted_at_runtime() {
+    let (proxy, handle, client) = start(RateLimiter::default()).await;
+    let address = Address::new([1; 20]).to_full_string();
+
+    // An expensive method is limited on its own, more tightly than the rest.
+    let limits = json!({
+        "default": { "perSecond": 100.0, "burst": 100 },
+        "methods": { "lasr_getAccount": { "perSecond": 0.1, "burst": 1 } },
+    });
+    let set: Value =
+        serde_json::from_str(&client.set_rate_limits(limits.to_string()).await.unwrap()).unwrap();
+    assert_eq!(set["methods"]["lasr_getAccount"]["burst"], 1);
+    client.get_account(address.clone()).await.unwrap();
+    throttled(client.get_account(address.clone()).await);
+    client
+        .get_verse_supply()
+        .await
+        .expect("a cheap method was limited with an expensive one");
+
+    // Once allowlisted, the client is not limited at all.
+    let limits = json!({
+        "default": { "perSecond": 0.1, "burst": 1 },
+        "methods": { "lasr_getAccount": { "perSecond": 0.1, "burst": 1 } },
+        "allowlist": ["127.0.0.1"],
+    });
+    client.set_rate_limits(limits.to_string()).await.unwrap();
+    for _ in 0..5 {
+        client.get_account(address.clone()).await.unwrap();
+    }
+
+    let never = json!({ "default": { "perSecond": 0.0, "burst": 1 } });
+    match client.set_rate_limits(never.to_string()).await {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::InvalidParams.code())
+        }
+        other => panic!("a limit admitting nothing was set: {other:?}"),
+    }
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
@@ -0,0 +1,204 @@
+#![cfg(test)]
+//! Test coverage for how the RPC server checks the requests it is sent.
+
+mod common;
+
+use common::rpc_proxy;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use jsonrpsee::{
+    core::{client::ClientT, ClientError},
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+    server::ServerHandle,
+    types::ErrorObjectOwned,
+};
+use lasr_actors::{
+    serve_rpc, AdminAccess, LasrRpcServerImpl, RateLimitConfig, RateLimiter, RequestLimits,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{RpcErrorCode, RpcMessage};
+use lasr_rpc::{AdminRpcServer, LasrRpcClient, LasrRpcServer};
+use lasr_types::{Payload, PayloadBuilder, Transaction, TransactionType, U256};
+use proptest::prelude::*;
+use ractor::ActorRef;
+use serde_json::{json, Value};
+use serial_test::serial;
+
+async fn start(limits: RequestLimits) -> (ActorRef<RpcMessage>, ServerHandle, HttpClient) {
+    let proxy = rpc_proxy().await;
+    let rpc = LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default());
+    let mut methods = LasrRpcServer::into_rpc(rpc.clone());
+    methods
+        .merge(AdminRpcServer::into_rpc(rpc))
+        .expect("failed to merge admin methods");
+    let (addr, handle) = serve_rpc(
+        "127.0.0.1:0".parse().unwrap(),
+        methods,
+        RateLimiter::default(),
+        AdminAccess::Token("operator".to_string()),
+        None,
+        limits,
+    )
+    .await
+    .expect("failed to serve rpc");
+    let client = HttpClientBuilder::default()
+        .set_headers(HeaderMap::from_iter([(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer operator"),
+        )]))
+        .build(format!("http://{addr}"))
+        .expect("failed to build rpc client");
+    (proxy, handle, client)
+}
+
+fn transaction(inputs: String) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(0)))
+        .from([1; 20])
+        .to([2; 20])
+        .program_id([3; 20])
+        .inputs(inputs)
+        .op(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(0))
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
+
+fn invalid_params<T: std::fmt::Debug>(result: Result<T, ClientError>) -> ErrorObjectOwned {
+    match result {
+        Err(ClientError::Call(error)) if error.code() == RpcErrorCode::InvalidParams.code() => {
+            error
+        }
+        other => panic!("request was not refused as invalid params: {other:?}"),
+    }
+}
+
+/// The error message, and its data if it has any.
+fn described(error: &ErrorObjectOwned) -> String {
+    format!(
+        "{} {}",
+        error.message(),
+        error.data().map(|data| data.get()).unwrap_or_default()
+    )
+}
+
+#[tokio::test]
+#[serial]
+async fn request_bodies_over_the_limit_are_refused() {
+    let (proxy, handle, client) = start(RequestLimits {
+        max_request_bytes: 4096,
+        ..RequestLimits::default()
+    })
+    .await;
+
+    client
+        .health()
+        .await
+        .expect("a request under the limit was refused");
+    match client.send(transaction("a".repeat(8192))).await {
+        Err(ClientError::Transport(error)) => {
+            assert!(error.to_string().contains("413"), "{error}")
+        }
+        other => panic!("an oversized request was read: {other:?}"),
+    }
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn unknown_fields_are_refused_by_name() {
+    let (proxy, handle, client) = start(RequestLimits::default()).await;
+
+    let mut sent = serde_json::to_value(transaction(String::new())).unwrap();
+    sent["gasPrice"] = json!("0x1");
+    let error = invalid_params(
+        client
+            .request::<String, _>("lasr_send", rpc_params![sent])
+            .await,
+    );
+    assert!(described(&error).contains("gasPrice"), "{error:?}");
+
+    let error = invalid_params(
+        client
+            .request::<String, _>(
+                "admin_setRateLimits",
+                rpc_params![json!({
+                    "default": { "perSecond": 1.0, "burst": 1, "refill": 2 }
+                })
+                .to_string()],
+            )
+            .await,
+    );
+    assert!(described(&error).contains("refill"), "{error:?}");
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_19_byte_address_is_refused_naming_the_parameter() {
+    let (proxy, handle, client) = start(RequestLimits::default()).await;
+    let short = format!("0x{}", "01".repeat(19));
+
+    let error = invalid_params(client.get_account(short.clone()).await);
+    assert_eq!(
+        error.message(),
+        "`address` must be 20 bytes of hex, not 38 hex digits"
+    );
+
+    let mut sent = serde_json::to_value(transaction(String::new())).unwrap();
+    sent["from"] = json!(short);
+    let error = invalid_params(
+        client
+            .request::<String, _>("lasr_send", rpc_params![sent])
+            .await,
+    );
+    assert!(
+        described(&error).contains("`from` must be 20 bytes of hex, not 38 hex digits"),
+        "{error:?}"
+    );
+
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+}
+
+fn arbitrary_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        any::<String>().prop_map(Value::from),
+        "(0x)?[0-9a-f]{0,70}".prop_map(Value::from),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        let field = prop_oneof![
+            Just("from".to_string()),
+            Just("to".to_string()),
+            Just("programId".to_string()),
+            Just("transactionType".to_string()),
+            Just("r".to_string()),
+            Just("default".to_string()),
+            any::<String>(),
+        ];
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..8).prop_map(Value::from),
+            prop::collection::btree_map(field, inner, 0..8)
+                .prop_map(|fields| Value::Object(fields.into_iter().collect())),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn request_parameters_are_parsed_or_refused(value in arbitrary_json()) {
+        let _ = serde_json::from_value::<Transaction>(value.clone());
+        let _ = serde_json::from_value::<Payload>(value.clone());
+        let _ = serde_json::from_value::<RateLimitConfig>(value);
+    }
+}
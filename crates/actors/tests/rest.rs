@@ -0,0 +1,305 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the read-only REST resources served alongside the RPC
+//! methods, run against the whole node booted in-process.
+
+use lasr_actors::{Batcher, TestHarness, VERSE_ADDR};
+use lasr_messages::RpcErrorCode;
+use lasr_types::{Address, PayloadBuilder, Transaction, TransactionType, U256};
+use serde_json::{json, Value};
+use serial_test::serial;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+struct Answer {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+impl Answer {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The JSON-RPC error code answered, asserting it was answered with
+    /// `status`.
+    fn error_code(&self, status: u16) -> i64 {
+        assert_eq!(self.status, status, "{}", self.body);
+        self.body["code"].as_i64().expect("no error code")
+    }
+}
+
+/// Makes a `method` request for `path` of the node serving at `url`.
+async fn request(url: &str, method: &str, path: &str, headers: &[(&str, &str)]) -> Answer {
+    let addr = url.trim_start_matches("http://");
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("failed to connect to the node");
+    let mut head = format!("{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n");
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if method == "POST" {
+        head.push_str("Content-Type: application/json\r\nContent-Length: 2\r\n\r\n{}");
+    } else {
+        head.push_str("\r\n");
+    }
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .expect("failed to send request");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("failed to read response");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .expect("response has no status");
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    let body = match body.is_empty() {
+        true => Value::Null,
+        false => serde_json::from_str(body).unwrap_or_else(|_| Value::String(body.to_string())),
+    };
+    Answer {
+        status,
+        headers,
+        body,
+    }
+}
+
+async fn get(url: &str, path: &str) -> Answer {
+    request(url, "GET", path, &[]).await
+}
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn registration(from: Address, nonce: U256) -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::RegisterProgram(nonce))
+        .from(from.into())
+        .to(from.into())
+        .program_id(Address::new([0; 20]).into())
+        .inputs(json!({ "contentId": "rest-cid" }).to_string())
+        .op(String::new())
+        .value(U256::from(0))
+        .nonce(nonce)
+        .build()
+        .expect("failed to build payload")
+        .into()
+}
+
+#[tokio::test]
+#[serial]
+async fn every_resource_is_answered_as_its_rpc_method_answers() {
+    let harness = TestHarness::start_with(|config| config.rest(true).instant_seal(false))
+        .await
+        .expect("failed to start node");
+    let url = harness.rpc_url().to_string();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    harness.seal_batch().await.unwrap();
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(25))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(25)
+        })
+        .await
+        .expect("the send was not applied");
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    let batch = sealed[0].clone();
+    let transaction_hash = batch.transaction_hashes[0].clone();
+
+    let account = get(&url, &format!("/accounts/{}", receiver().to_full_string())).await;
+    assert_eq!(account.status, 200);
+    assert_eq!(account.header("content-type"), Some("application/json"));
+    assert_eq!(account.body["found"], true);
+    assert_eq!(account.body["address"], receiver().to_string());
+
+    let token = get(
+        &url,
+        &format!(
+            "/accounts/{}/tokens/{}",
+            receiver().to_full_string(),
+            VERSE_ADDR.to_full_string()
+        ),
+    )
+    .await;
+    assert_eq!(token.status, 200);
+    assert_eq!(token.body["programId"], VERSE_ADDR.to_string());
+    assert_eq!(token.body, account.body["programs"][VERSE_ADDR.to_string()]);
+
+    let receipt = get(&url, &format!("/transactions/{transaction_hash}")).await;
+    assert_eq!(receipt.status, 200);
+    assert_eq!(receipt.body["transactionHash"], transaction_hash);
+    assert_eq!(receipt.body["status"]["status"], "included");
+    assert_eq!(receipt.body["batchId"], batch.batch_id);
+
+    let info = get(&url, &format!("/batches/{}?full=true", batch.batch_id)).await;
+    assert_eq!(info.status, 200);
+    assert_eq!(info.body["transactionHashes"], json!([transaction_hash]));
+    assert_eq!(
+        info.body["accounts"].as_array().map(Vec::len),
+        Some(batch.account_count)
+    );
+    assert_eq!(
+        get(&url, &format!("/batches/{}", batch.batch_id))
+            .await
+            .body["accounts"],
+        Value::Null
+    );
+
+    // Lists are paged as the RPC methods page them.
+    let first = get(&url, "/batches?limit=1").await;
+    assert_eq!(first.status, 200);
+    assert_eq!(first.body["total"], 2);
+    assert_eq!(first.body["items"].as_array().map(Vec::len), Some(1));
+    let cursor = first.body["nextCursor"].as_str().expect("no next page");
+    let next = get(&url, &format!("/batches?limit=1&cursor={cursor}")).await;
+    assert_eq!(next.body["items"][0]["batchId"], batch.batch_id);
+    assert_eq!(next.body["nextCursor"], Value::Null);
+
+    let nonce = harness.account(sender).await.unwrap().nonce() + U256::from(1);
+    let transaction = registration(sender, nonce);
+    let program_id = lasr_contract::create_program_id("rest-cid".to_string(), &transaction)
+        .expect("failed to create program id");
+    Batcher::apply_program_registration_in_runtime(harness.batcher(), transaction, None)
+        .await
+        .expect("failed to register program");
+    let program = get(&url, &format!("/programs/{}", program_id.to_full_string())).await;
+    assert_eq!(program.status, 200);
+    assert_eq!(program.body["contentId"], "rest-cid");
+    assert_eq!(program.body["owner"], sender.to_string());
+    let programs = get(&url, "/programs?limit=10").await;
+    assert!(programs.body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|item| item["programId"] == program_id.to_string()));
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn what_does_not_exist_is_not_found_and_what_is_malformed_is_refused() {
+    let harness = TestHarness::start_with(|config| config.rest(true))
+        .await
+        .expect("failed to start node");
+    let url = harness.rpc_url().to_string();
+    let wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address().to_full_string();
+    let not_found = RpcErrorCode::NotFound.code() as i64;
+    let invalid = RpcErrorCode::InvalidParams.code() as i64;
+    let nobody = Address::new([7; 20]).to_full_string();
+    let no_hash = format!("0x{}", "00".repeat(32));
+
+    for path in [
+        format!("/accounts/{nobody}"),
+        format!("/accounts/{nobody}/tokens/{}", VERSE_ADDR.to_full_string()),
+        format!("/accounts/{sender}/tokens/{nobody}"),
+        format!("/transactions/{no_hash}"),
+        "/batches/999".to_string(),
+        format!("/programs/{nobody}"),
+        format!("/accounts/{sender}/nothing"),
+    ] {
+        assert_eq!(get(&url, &path).await.error_code(404), not_found, "{path}");
+    }
+    for path in [
+        "/accounts/not-an-address".to_string(),
+        format!("/accounts/{sender}/tokens/not-an-address"),
+        "/transactions/0x1234".to_string(),
+        "/batches/first".to_string(),
+        "/batches?limit=many".to_string(),
+        "/programs/not-an-address".to_string(),
+    ] {
+        assert_eq!(get(&url, &path).await.error_code(400), invalid, "{path}");
+    }
+
+    // Writes are JSON-RPC only.
+    let post = request(&url, "POST", &format!("/accounts/{sender}"), &[]).await;
+    assert_eq!(post.status, 405);
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn account_reads_are_answered_not_modified_while_the_etag_matches() {
+    let harness = TestHarness::start_with(|config| config.rest(true))
+        .await
+        .expect("failed to start node");
+    let url = harness.rpc_url().to_string();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let path = format!("/accounts/{}", wallet.address().to_full_string());
+    let token_path = format!("{path}/tokens/{}", VERSE_ADDR.to_full_string());
+
+    let first = get(&url, &path).await;
+    assert_eq!(first.status, 200);
+    let etag = first.header("etag").expect("no ETag").to_string();
+    assert_eq!(get(&url, &path).await.header("etag"), Some(etag.as_str()));
+
+    let unchanged = request(&url, "GET", &path, &[("If-None-Match", &etag)]).await;
+    assert_eq!(unchanged.status, 304);
+    assert_eq!(unchanged.body, Value::Null);
+    assert_eq!(unchanged.header("etag"), Some(etag.as_str()));
+    for held in [format!("\"0xstale\", W/{etag}"), "*".to_string()] {
+        let answer = request(&url, "GET", &path, &[("If-None-Match", &held)]).await;
+        assert_eq!(answer.status, 304, "{held}");
+    }
+    let stale = request(&url, "GET", &path, &[("If-None-Match", "\"stale\"")]).await;
+    assert_eq!(stale.status, 200);
+    assert_eq!(stale.body, first.body);
+
+    let token = get(&url, &token_path).await;
+    let token_etag = token.header("etag").expect("no ETag").to_string();
+    assert_ne!(token_etag, etag);
+    let unchanged = request(&url, "GET", &token_path, &[("If-None-Match", &token_etag)]).await;
+    assert_eq!(unchanged.status, 304);
+
+    // Once the account changes, so does its ETag.
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(1))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(1)
+        })
+        .await
+        .expect("the send was not applied");
+    let changed = request(&url, "GET", &path, &[("If-None-Match", &etag)]).await;
+    assert_eq!(changed.status, 200);
+    assert_ne!(changed.header("etag"), Some(etag.as_str()));
+    let changed = request(&url, "GET", &token_path, &[("If-None-Match", &token_etag)]).await;
+    assert_eq!(changed.status, 200);
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn the_resources_are_not_served_unless_enabled() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let url = harness.rpc_url().to_string();
+    let answer = get(&url, &format!("/accounts/{}", receiver().to_full_string())).await;
+    assert_ne!(answer.status, 200);
+    assert_eq!(answer.header("etag"), None);
+    harness.stop().await;
+}
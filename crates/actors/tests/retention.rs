@@ -0,0 +1,137 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for pruning blobs and receipts past the node's retention,
+//! only once their batches are submitted for settlement, and for what is
+//! pruned answering where on the DA layer to retrieve it.
+
+use jsonrpsee::core::ClientError;
+use lasr_actors::{
+    IndexedTransaction, Page, PruneTier, RetentionPolicy, RetentionWindow, TestHarness, VERSE_ADDR,
+};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::{AdminRpcClient, LasrRpcClient};
+use lasr_types::{Address, U256};
+use serde_json::Value;
+use serial_test::serial;
+
+fn latest(batches: u64) -> RetentionWindow {
+    RetentionWindow {
+        days: None,
+        batches: Some(batches),
+    }
+}
+
+/// The data of the `Pruned` error `result` should be.
+fn pruned(result: Result<String, ClientError>) -> Value {
+    match result {
+        Err(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::Pruned.code());
+            serde_json::from_str(error.data().expect("no data").get()).unwrap()
+        }
+        other => panic!("expected it to have been pruned, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn batches_past_retention_are_pruned_once_submitted_and_say_where_to_find_them() {
+    let harness = TestHarness::start_with(|config| {
+        config
+            .instant_seal(false)
+            .index_transactions(true)
+            .retention(RetentionPolicy {
+                blobs: latest(1),
+                receipts: latest(3),
+                ..RetentionPolicy::default()
+            })
+    })
+    .await
+    .expect("failed to start node");
+    let rpc = harness.rpc().unwrap();
+    let admin = harness.admin().unwrap();
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let mut sealed = harness.seal_batch().await.expect("failed to seal batch");
+    let to = Address::new([9; 20]);
+    for balance in 1..=3 {
+        wallet
+            .send(&to, &VERSE_ADDR, U256::from(1))
+            .await
+            .expect("failed to send");
+        harness
+            .wait_for_account(to, |account| {
+                account.balance(&VERSE_ADDR) == U256::from(balance)
+            })
+            .await
+            .expect("the send was not applied");
+        sealed.extend(harness.seal_batch().await.expect("failed to seal batch"));
+    }
+    assert_eq!(sealed.len(), 4);
+    // Only the first two are settled, the others still to be submitted.
+    harness.settle(sealed[0].batch_id).await.unwrap();
+    harness.settle(sealed[1].batch_id).await.unwrap();
+
+    let space: Value = serde_json::from_str(&admin.get_reclaimable_space().await.unwrap()).unwrap();
+    assert_eq!(space["blobs"]["window"]["batches"], 1);
+    assert_eq!(space["blobs"]["prunedThrough"], Value::Null);
+    assert_eq!(space["blobs"]["reclaimable"]["through"], sealed[1].batch_id);
+    assert!(space["blobs"]["reclaimable"]["bytes"].as_u64().unwrap() > 0);
+    assert_eq!(
+        space["receipts"]["reclaimable"]["through"],
+        sealed[0].batch_id
+    );
+
+    let report: Value = serde_json::from_str(&admin.prune().await.unwrap()).unwrap();
+    assert_eq!(report["blobs"], space["blobs"]["reclaimable"]);
+    assert_eq!(report["receipts"]["through"], sealed[0].batch_id);
+    assert_eq!(report["receipts"]["batches"], 1);
+    let pruner = harness.pruner();
+    assert_eq!(
+        pruner.pruned_through(PruneTier::Blobs),
+        Some(sealed[1].batch_id)
+    );
+
+    // A pruned blob answers where on the DA layer its batch is.
+    let data = pruned(rpc.get_batch(sealed[1].batch_id, Some(true)).await);
+    assert_eq!(data["tier"], "blobs");
+    assert_eq!(data["batchId"], sealed[1].batch_id);
+    assert_eq!(
+        data["blobCommitment"],
+        serde_json::to_value(sealed[1].blob_commitment).unwrap()
+    );
+    assert_eq!(data["blobIndex"], sealed[1].blob_index as u64);
+    assert!(rpc.get_batch(sealed[1].batch_id, None).await.is_ok());
+    // The batch not yet submitted is kept, though past the window.
+    assert!(rpc.get_batch(sealed[2].batch_id, Some(true)).await.is_ok());
+
+    // So does a receipt, and an attestation.
+    let minted = sealed[0].transaction_hashes[0].clone();
+    let data = pruned(rpc.get_transaction_receipt(minted.clone()).await);
+    assert_eq!(data["tier"], "receipts");
+    assert_eq!(data["batchId"], sealed[0].batch_id);
+    assert_eq!(data["transactionHash"], minted);
+    pruned(rpc.get_attestation(minted.clone()).await);
+    assert!(rpc
+        .get_transaction_receipt(sealed[1].transaction_hashes[0].clone())
+        .await
+        .is_ok());
+
+    // The batch is no longer in the transaction index.
+    let page: Page<IndexedTransaction> = serde_json::from_str(
+        &rpc.get_transactions_by_address(wallet.address().to_full_string(), None, None)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(page.total, 3);
+    assert!(page
+        .items
+        .iter()
+        .all(|transaction| transaction.batch_id > sealed[0].batch_id));
+
+    // Nothing more goes until the next batch is submitted.
+    let report: Value = serde_json::from_str(&admin.prune().await.unwrap()).unwrap();
+    assert_eq!(report["blobs"]["batches"], 0);
+    assert_eq!(report["receipts"]["batches"], 0);
+
+    harness.stop().await;
+}
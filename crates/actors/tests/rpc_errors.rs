@@ -0,0 +1,332 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for the error codes failing requests are answered with.
+
+mod common;
+
+use std::collections::{BTreeMap, HashSet};
+
+use async_trait::async_trait;
+use common::{rpc_proxy, serve, spawn_as, spawn_named, stop, ws_client};
+use jsonrpsee::{
+    core::ClientError, server::ServerHandle, types::ErrorObjectOwned, ws_client::WsClient,
+};
+use lasr_actors::{
+    AccountCacheActor, EngineActor, LasrRpcServerImpl, MempoolLimits, PendingTransactionActor,
+    TaskScheduler,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{
+    AccountCacheMessage, ActorType, ExecutorMessage, PendingTransactionMessage, RpcErrorCode,
+    SchedulerMessage,
+};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{
+    Account, Address, ArbitraryData, Metadata, MockPersistenceStore, PayloadBuilder,
+    PersistenceStore, Status, Token, TokenBuilder, Transaction, TransactionBuilder,
+    TransactionType, U256,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::Value;
+use serial_test::serial;
+
+const REVERT: &str = "program exited with status 1";
+
+/// Stands in for the executor, failing every call it is given as a program
+/// exiting with an error would.
+struct MockExecutor;
+
+#[async_trait]
+impl Actor for MockExecutor {
+    type Msg = ExecutorMessage;
+    type State = ();
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        _: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let ExecutorMessage::Set { transaction } = message {
+            let scheduler: ActorRef<SchedulerMessage> =
+                ractor::registry::where_is(ActorType::Scheduler.to_string())
+                    .expect("no scheduler")
+                    .into();
+            scheduler.cast(SchedulerMessage::CallTransactionFailure {
+                transaction_hash: transaction.hash_string(),
+                outputs: String::new(),
+                error: REVERT.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn sender() -> SecretKey {
+    SecretKey::from_slice(&[1; 32]).unwrap()
+}
+
+fn address(secret_key: &SecretKey) -> Address {
+    PublicKey::from_secret_key(&Secp256k1::new(), secret_key).into()
+}
+
+fn token(balance: u64) -> Token {
+    TokenBuilder::default()
+        .program_id(Address::new([0; 20]))
+        .owner_id(address(&sender()))
+        .balance(U256::from(balance))
+        .metadata(Metadata::new())
+        .token_ids(Vec::new())
+        .allowance(BTreeMap::new())
+        .approvals(BTreeMap::new())
+        .data(ArbitraryData::new())
+        .status(Status::Free)
+        .build()
+        .unwrap()
+}
+
+/// A transaction from the holder of `sender()`, signed by `signer`.
+fn signed(
+    signer: &SecretKey,
+    transaction_type: TransactionType,
+    program_id: [u8; 20],
+    value: u64,
+    nonce: u64,
+) -> Transaction {
+    let payload = PayloadBuilder::default()
+        .transaction_type(transaction_type)
+        .from(address(&sender()).into())
+        .to([2; 20])
+        .program_id(program_id)
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(value))
+        .nonce(U256::from(nonce))
+        .build()
+        .expect("failed to build payload");
+    common::signed(payload, signer)
+}
+
+fn send(value: u64, nonce: u64) -> Transaction {
+    signed(
+        &sender(),
+        TransactionType::Send(U256::from(0)),
+        [0; 20],
+        value,
+        nonce,
+    )
+}
+
+fn call(program_id: [u8; 20], nonce: u64) -> Transaction {
+    signed(
+        &sender(),
+        TransactionType::Call(U256::from(0)),
+        program_id,
+        0,
+        nonce,
+    )
+}
+
+struct Node {
+    actors: Vec<ractor::ActorCell>,
+    pending: ActorRef<PendingTransactionMessage>,
+    handle: ServerHandle,
+    client: WsClient,
+}
+
+impl Node {
+    /// Starts a node whose account cache holds the sender's account, with
+    /// 1000 of the token at nonce 3.
+    async fn start() -> Self {
+        Self::start_with(MempoolLimits::default()).await
+    }
+
+    /// A node whose pending pool is bounded by `limits`.
+    async fn start_with(limits: MempoolLimits) -> Self {
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .unwrap();
+        let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+        let mut account = Account::new(address(&sender()));
+        account
+            .programs_mut()
+            .insert(Address::new([0; 20]), token(1000));
+        for _ in 0..3 {
+            account.increment_nonce();
+        }
+        account_cache
+            .cast(AccountCacheMessage::Write {
+                account,
+                who: ActorType::Engine,
+                location: "test".to_string(),
+            })
+            .unwrap();
+        let scheduler = spawn_named(TaskScheduler::new(), ()).await;
+        let engine = spawn_named(EngineActor::new(), ()).await;
+        let executor = spawn_as(ActorType::Executor, MockExecutor, ()).await;
+        let pending = spawn_named(
+            PendingTransactionActor::new().with_mempool_limits(limits),
+            (),
+        )
+        .await;
+        let proxy = rpc_proxy().await;
+
+        let (handle, addr) =
+            serve(LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default()).into_rpc()).await;
+        let client = ws_client(addr).await;
+        Self {
+            actors: vec![
+                proxy.get_cell(),
+                pending.get_cell(),
+                executor.get_cell(),
+                engine.get_cell(),
+                scheduler.get_cell(),
+                account_cache.get_cell(),
+            ],
+            pending,
+            handle,
+            client,
+        }
+    }
+
+    async fn stop(self) {
+        drop(self.client);
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+fn failure<T: std::fmt::Debug>(result: Result<T, ClientError>) -> ErrorObjectOwned {
+    match result {
+        Err(ClientError::Call(error)) => error,
+        other => panic!("request did not fail with an error response: {other:?}"),
+    }
+}
+
+#[test]
+fn codes_are_unique_and_clear_of_the_servers() {
+    let codes: HashSet<i32> = RpcErrorCode::ALL.iter().map(|code| code.code()).collect();
+    assert_eq!(codes.len(), RpcErrorCode::ALL.len());
+    // Those the JSON-RPC server turns oversized requests and batches away
+    // with.
+    assert!(codes.iter().all(|code| !(-32011..=-32007).contains(code)));
+}
+
+#[tokio::test]
+#[serial]
+async fn transactions_turned_away_on_admission_are_answered_with_their_codes() {
+    let node = Node::start().await;
+
+    for (transaction, expected) in [
+        (send(1001, 4), RpcErrorCode::InsufficientBalance),
+        (send(1, 2), RpcErrorCode::InvalidNonce),
+        (send(1, 100), RpcErrorCode::InvalidNonce),
+        (call([0; 20], 4), RpcErrorCode::MissingProgramId),
+    ] {
+        let error = match transaction.transaction_type() {
+            TransactionType::Call(_) => failure(node.client.call(transaction).await),
+            _ => failure(node.client.send(transaction).await),
+        };
+        assert_eq!(error.code(), expected.code(), "{}", error.message());
+    }
+
+    let unsigned = TransactionBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(0)))
+        .from(address(&sender()).into())
+        .to([2; 20])
+        .program_id([0; 20])
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(4))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap();
+    let error = failure(node.client.send(unsigned).await);
+    assert_eq!(error.code(), RpcErrorCode::InvalidSignature.code());
+
+    let forged = signed(
+        &SecretKey::from_slice(&[2; 32]).unwrap(),
+        TransactionType::Send(U256::from(0)),
+        [0; 20],
+        1,
+        4,
+    );
+    let error = failure(node.client.send(forged).await);
+    assert_eq!(error.code(), RpcErrorCode::SenderMismatch.code());
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_reverted_call_is_answered_with_its_reason() {
+    let node = Node::start().await;
+
+    let error = failure(node.client.call(call([3; 20], 4)).await);
+    assert_eq!(error.code(), RpcErrorCode::ProgramReverted.code());
+    assert!(error.message().starts_with("program reverted: "));
+    let data: Value = serde_json::from_str(error.data().expect("error has no data").get())
+        .expect("error data is not JSON");
+    let reason = data["reason"].as_str().expect("no revert reason");
+    assert!(reason.contains(REVERT), "{reason}");
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn sends_turned_away_by_the_pending_pool_are_answered_with_their_codes() {
+    let node = Node::start_with(MempoolLimits {
+        max_transactions: 1,
+        ..MempoolLimits::default()
+    })
+    .await;
+
+    // Nothing is pending at nonce 4 to be replaced.
+    let error = failure(node.client.replace(send(1, 4)).await);
+    assert_eq!(error.code(), RpcErrorCode::NotFound.code());
+
+    node.pending
+        .cast(PendingTransactionMessage::New {
+            transaction: send(1, 4),
+            outputs: None,
+            replace: false,
+        })
+        .unwrap();
+    // Another send with the nonce of the one pending.
+    let error = failure(node.client.send(send(2, 4)).await);
+    assert_eq!(error.code(), RpcErrorCode::InvalidNonce.code());
+    // The pool holds no more than the one.
+    let error = failure(node.client.send(send(1, 5)).await);
+    assert_eq!(error.code(), RpcErrorCode::MempoolFull.code());
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_missing_attestation_is_not_found() {
+    let node = Node::start().await;
+
+    let error = failure(
+        node.client
+            .get_attestation(format!("0x{}", "ab".repeat(32)))
+            .await,
+    );
+    assert_eq!(error.code(), RpcErrorCode::NotFound.code());
+
+    node.stop().await;
+}
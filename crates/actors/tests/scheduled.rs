@@ -0,0 +1,148 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for transactions valid only within a window of time, held
+//! by the node until it opens, run against the whole node booted in-process.
+
+mod common;
+
+use std::time::Duration;
+
+use common::unix_now;
+use jsonrpsee::core::ClientError;
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Address, U256};
+use lasr_wallet::Sent;
+use serde_json::Value;
+use serial_test::serial;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+/// Waits for the status of `transaction_hash` to be `status`, answering it.
+async fn wait_for_status(harness: &TestHarness, transaction_hash: &str, status: &str) -> Value {
+    let rpc = harness.rpc().unwrap();
+    for _ in 0..200 {
+        let answer: Value = serde_json::from_str(
+            &rpc.get_transaction_status(transaction_hash.to_string())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        if answer["status"] == status {
+            return answer;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("{transaction_hash} never became {status}");
+}
+
+#[tokio::test]
+#[serial]
+async fn a_transaction_not_yet_valid_is_held_and_applied_once_it_is() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let valid_after = unix_now() + 2;
+
+    let sent = wallet
+        .send_within(
+            &receiver(),
+            &VERSE_ADDR,
+            U256::from(25),
+            Some(valid_after),
+            None,
+        )
+        .await
+        .expect("failed to send");
+    let Sent::Scheduled(transaction_hash) = sent else {
+        panic!("the send was applied before it was valid: {sent:?}");
+    };
+    let status = wait_for_status(&harness, &transaction_hash, "scheduled").await;
+    assert_eq!(status["validAfter"], valid_after);
+    assert!(harness.account(receiver()).await.is_none());
+
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(25)
+        })
+        .await
+        .expect("the send was not applied once valid");
+    assert!(unix_now() >= valid_after);
+    wait_for_status(&harness, &transaction_hash, "included").await;
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_held_transaction_holds_no_nonce() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let sender = wallet.address();
+    let before = harness.account(sender).await.expect("sender is not funded");
+
+    let sent = wallet
+        .send_within(
+            &receiver(),
+            &VERSE_ADDR,
+            U256::from(25),
+            Some(unix_now() + 2),
+            None,
+        )
+        .await
+        .expect("failed to send");
+    let Sent::Scheduled(held) = sent else {
+        panic!("the send was applied before it was valid: {sent:?}");
+    };
+
+    // A send with the same nonce goes ahead of it.
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    let after = harness.account(sender).await.unwrap();
+    assert_eq!(after.nonce(), before.nonce() + U256::from(1));
+
+    // And once valid, the one held is turned away for the nonce it reused.
+    let status = wait_for_status(&harness, &held, "failed").await;
+    assert_eq!(status["reason"], "invalidNonce");
+    assert_eq!(
+        harness
+            .account(receiver())
+            .await
+            .unwrap()
+            .balance(&VERSE_ADDR),
+        U256::from(5)
+    );
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_transaction_whose_window_has_closed_is_turned_away() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+
+    let e = wallet
+        .send_within(
+            &receiver(),
+            &VERSE_ADDR,
+            U256::from(25),
+            None,
+            Some(unix_now() - 1),
+        )
+        .await
+        .expect_err("an expired send was admitted");
+    match e.downcast_ref::<ClientError>() {
+        Some(ClientError::Call(error)) => {
+            assert_eq!(error.code(), RpcErrorCode::Expired.code())
+        }
+        other => panic!("the expired send was not turned away: {other:?}"),
+    }
+    assert!(harness.account(receiver()).await.is_none());
+
+    harness.stop().await;
+}
@@ -0,0 +1,368 @@
+#![cfg(test)]
+//! Test coverage for submitting batch headers to the settlement contract,
+//! replacing submissions that are not mined.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use jsonrpsee::{
+    server::{ServerBuilder, ServerHandle},
+    types::ErrorObjectOwned,
+    RpcModule,
+};
+use lasr_actors::{KeyRole, NodeKeys, Settler, SettlerConfig, KEYSTORE_MANIFEST};
+use lasr_messages::{BatchHeader, BatchStatus, Withdrawal};
+use lasr_types::Address;
+use secp256k1::SecretKey;
+use serde_json::{json, Value};
+use web3::ethabi::Token;
+use web3::signing::keccak256;
+use web3::types::{Bytes, H160, H256, U256, U64};
+
+const CHAIN_ID: u64 = 31337;
+
+/// A node that only mines transactions paying at least `mined_at`.
+struct Node {
+    gas_price: Mutex<u64>,
+    mined_at: u64,
+    nonce: u64,
+    sent: Mutex<Vec<Vec<u8>>>,
+    /// The addresses nonces were asked for, in order.
+    counted: Mutex<Vec<H160>>,
+}
+
+impl Node {
+    fn new(gas_price: u64, mined_at: u64) -> Arc<Self> {
+        Arc::new(Self {
+            gas_price: Mutex::new(gas_price),
+            mined_at,
+            nonce: 7,
+            sent: Mutex::new(Vec::new()),
+            counted: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn sent(&self) -> Vec<LegacyTransaction> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|raw| LegacyTransaction::decode(raw))
+            .collect()
+    }
+
+    async fn serve(self: &Arc<Self>) -> (SocketAddr, ServerHandle) {
+        let mut module = RpcModule::new(Arc::clone(self));
+        module
+            .register_method("eth_chainId", |_, _| {
+                Ok::<_, ErrorObjectOwned>(U64::from(CHAIN_ID))
+            })
+            .expect("failed to register eth_chainId");
+        module
+            .register_method("eth_getTransactionCount", |params, node| {
+                let address: H160 = params.sequence().next()?;
+                node.counted.lock().unwrap().push(address);
+                Ok::<_, ErrorObjectOwned>(U256::from(node.nonce))
+            })
+            .expect("failed to register eth_getTransactionCount");
+        module
+            .register_method("eth_gasPrice", |_, node| {
+                Ok::<_, ErrorObjectOwned>(U256::from(*node.gas_price.lock().unwrap()))
+            })
+            .expect("failed to register eth_gasPrice");
+        module
+            .register_method("eth_sendRawTransaction", |params, node| {
+                let raw: Bytes = params.sequence().next()?;
+                node.sent.lock().unwrap().push(raw.0.clone());
+                Ok::<_, ErrorObjectOwned>(H256(keccak256(&raw.0)))
+            })
+            .expect("failed to register eth_sendRawTransaction");
+        module
+            .register_method("eth_getTransactionReceipt", |params, node| {
+                let hash: H256 = params.sequence().next()?;
+                let mined = node.sent.lock().unwrap().iter().position(|raw| {
+                    H256(keccak256(raw)) == hash
+                        && LegacyTransaction::decode(raw).gas_price >= node.mined_at
+                });
+                Ok::<_, ErrorObjectOwned>(mined.map(|index| receipt(hash, index)))
+            })
+            .expect("failed to register eth_getTransactionReceipt");
+        let server = ServerBuilder::default()
+            .build("127.0.0.1:0")
+            .await
+            .expect("failed to build mock node");
+        let addr = server.local_addr().expect("mock node has no address");
+        (addr, server.start(module))
+    }
+}
+
+fn receipt(hash: H256, index: usize) -> Value {
+    json!({
+        "transactionHash": hash,
+        "transactionIndex": U64::zero(),
+        "blockHash": H256::repeat_byte(0xbb),
+        "blockNumber": U64::from(100 + index),
+        "from": H160::zero(),
+        "to": contract(),
+        "cumulativeGasUsed": U256::from(21_000),
+        "gasUsed": U256::from(21_000),
+        "contractAddress": null,
+        "logs": [],
+        "status": U64::one(),
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+    })
+}
+
+/// The fields of a signed legacy transaction checked here.
+#[derive(Debug)]
+struct LegacyTransaction {
+    nonce: u64,
+    gas_price: u64,
+    to: H160,
+    data: Vec<u8>,
+}
+
+impl LegacyTransaction {
+    fn decode(raw: &[u8]) -> Self {
+        let (list, _) = rlp_item(raw);
+        let mut fields = Vec::new();
+        let mut rest = list;
+        while !rest.is_empty() {
+            let (field, next) = rlp_item(rest);
+            fields.push(field);
+            rest = next;
+        }
+        let number = |bytes: &[u8]| bytes.iter().fold(0u64, |n, b| n << 8 | *b as u64);
+        Self {
+            nonce: number(fields[0]),
+            gas_price: number(fields[1]),
+            to: H160::from_slice(fields[3]),
+            data: fields[5].to_vec(),
+        }
+    }
+}
+
+/// Splits the first RLP item off `buf`, returning its payload and the rest.
+fn rlp_item(buf: &[u8]) -> (&[u8], &[u8]) {
+    let length = |bytes: &[u8]| bytes.iter().fold(0usize, |n, b| n << 8 | *b as usize);
+    let (offset, len) = match buf[0] {
+        0x00..=0x7f => return (&buf[..1], &buf[1..]),
+        b @ 0x80..=0xb7 => (1, (b - 0x80) as usize),
+        b @ 0xb8..=0xbf => {
+            let n = (b - 0xb7) as usize;
+            (1 + n, length(&buf[1..1 + n]))
+        }
+        b @ 0xc0..=0xf7 => (1, (b - 0xc0) as usize),
+        b => {
+            let n = (b - 0xf7) as usize;
+            (1 + n, length(&buf[1..1 + n]))
+        }
+    };
+    (&buf[offset..offset + len], &buf[offset + len..])
+}
+
+fn contract() -> H160 {
+    H160::repeat_byte(0x5e)
+}
+
+fn header(batch_id: u64) -> BatchHeader {
+    BatchHeader {
+        batch_id,
+        state_root: H256::repeat_byte(0x01),
+        transactions_root: H256::zero(),
+        events_root: H256::zero(),
+        blob_commitment: H256::repeat_byte(0x02),
+        blob_index: 3,
+        withdrawals: vec![Withdrawal {
+            token: Address::new([0x04; 20]),
+            recipient: Address::new([0x05; 20]),
+            amount: lasr_types::U256::from(batch_id + 1),
+        }],
+    }
+}
+
+/// The calldata expected for `header`, encoded apart from the settler.
+fn calldata(header: &BatchHeader) -> Vec<u8> {
+    let selector = &keccak256(
+        b"submitBatchHeader(uint256,bytes32,bytes32,uint128,(address,address,uint256)[])",
+    )[..4];
+    let withdrawals = header
+        .withdrawals
+        .iter()
+        .map(|withdrawal| {
+            Token::Tuple(vec![
+                Token::Address(H160::from(withdrawal.token.inner())),
+                Token::Address(H160::from(withdrawal.recipient.inner())),
+                Token::Uint(withdrawal.amount.into()),
+            ])
+        })
+        .collect();
+    let args = web3::ethabi::encode(&[
+        Token::Uint(header.batch_id.into()),
+        Token::FixedBytes(header.state_root.0.to_vec()),
+        Token::FixedBytes(header.blob_commitment.0.to_vec()),
+        Token::Uint(header.blob_index.into()),
+        Token::Array(withdrawals),
+    ]);
+    [selector, &args].concat()
+}
+
+fn settler(addr: SocketAddr, max_attempts: u32) -> Settler {
+    let key = SecretKey::from_slice(&[0x11; 32]).expect("invalid key");
+    settler_with_keys(addr, max_attempts, &NodeKeys::new(key))
+}
+
+fn settler_with_keys(addr: SocketAddr, max_attempts: u32, keys: &NodeKeys) -> Settler {
+    let http = web3::transports::Http::new(&format!("http://{addr}")).expect("invalid node url");
+    let config = SettlerConfig {
+        replace_after: Duration::from_millis(300),
+        max_attempts,
+        poll_interval: Duration::from_millis(50),
+        ..SettlerConfig::new(contract())
+    };
+    Settler::new(web3::Web3::new(http), keys.key(KeyRole::Settlement), config)
+}
+
+const PASSWORD: &str = "correct horse battery staple";
+
+/// A keystore holding `keys`, the first of them active.
+fn keystore(keys: &[(&str, SecretKey)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lasr_settler_keys_{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, secret_key) in keys {
+        key_file(&dir, file, secret_key);
+    }
+    std::fs::write(
+        dir.join(KEYSTORE_MANIFEST),
+        format!("[settlement]\nactive = \"{}\"\n", keys[0].0),
+    )
+    .unwrap();
+    dir
+}
+
+fn key_file(dir: &Path, file: &str, secret_key: &SecretKey) {
+    eth_keystore::encrypt_key(
+        dir,
+        &mut secp256k1::rand::rngs::OsRng,
+        secret_key.secret_bytes(),
+        PASSWORD,
+        Some(file),
+    )
+    .expect("failed to write key file");
+}
+
+fn eth_address(keys: &NodeKeys) -> H160 {
+    H160(keys.key(KeyRole::Settlement).signer().inner())
+}
+
+#[tokio::test]
+async fn unmined_headers_are_replaced_at_a_higher_gas_price() {
+    let node = Node::new(100, 110);
+    let (addr, handle) = node.serve().await;
+    let mut settler = settler(addr, 5);
+    let receipts = settler.receipts();
+
+    let status = settler.submit(&header(0)).await;
+    let sent = node.sent();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(
+        sent.iter()
+            .map(|tx| (tx.nonce, tx.gas_price))
+            .collect::<Vec<_>>(),
+        vec![(7, 100), (7, 120)]
+    );
+    for tx in &sent {
+        assert_eq!(tx.to, contract());
+        assert_eq!(tx.data, calldata(&header(0)));
+    }
+    let replacement = H256(keccak256(&node.sent.lock().unwrap()[1]));
+    assert_eq!(
+        status,
+        BatchStatus::Settled {
+            transaction_hash: replacement,
+            block_number: 101,
+        }
+    );
+    assert_eq!(receipts.get(0).map(|receipt| receipt.status), Some(status));
+
+    // The next batch takes the nonce after, at the network's price.
+    *node.gas_price.lock().unwrap() = 110;
+    let status = settler.submit(&header(1)).await;
+    assert!(matches!(status, BatchStatus::Settled { .. }));
+    let next = node.sent().pop().unwrap();
+    assert_eq!((next.nonce, next.gas_price), (8, 110));
+    assert_eq!(next.data, calldata(&header(1)));
+    assert!(receipts.get(2).is_none());
+
+    handle.stop().ok();
+}
+
+#[tokio::test]
+async fn headers_never_mined_fail_once_the_attempts_run_out() {
+    let node = Node::new(100, u64::MAX);
+    let (addr, handle) = node.serve().await;
+    let mut settler = settler(addr, 2);
+
+    let status = settler.submit(&header(4)).await;
+    assert!(matches!(status, BatchStatus::Failed { .. }));
+    assert_eq!(node.sent().len(), 2);
+    assert_eq!(
+        settler.receipts().get(4).map(|receipt| receipt.status),
+        Some(status)
+    );
+
+    // Whether either will be mined is unknown, so the nonce is fetched again.
+    settler.submit(&header(5)).await;
+    assert_eq!(node.sent()[2].nonce, 7);
+
+    handle.stop().ok();
+}
+
+#[tokio::test]
+async fn headers_after_a_rotation_are_signed_with_the_new_key_and_its_nonce() {
+    let node = Node::new(100, 100);
+    let (addr, handle) = node.serve().await;
+    let old = SecretKey::from_slice(&[0x11; 32]).unwrap();
+    let new = SecretKey::from_slice(&[0x22; 32]).unwrap();
+    let dir = keystore(&[("old.json", old), ("new.json", new)]);
+    let keys = NodeKeys::open(&dir, PASSWORD).expect("failed to open keystore");
+    let old_signer = eth_address(&keys);
+    let mut settler = settler_with_keys(addr, 5, &keys);
+
+    assert!(matches!(
+        settler.submit(&header(0)).await,
+        BatchStatus::Settled { .. }
+    ));
+    assert!(matches!(
+        settler.submit(&header(1)).await,
+        BatchStatus::Settled { .. }
+    ));
+    assert_eq!(
+        node.sent().iter().map(|tx| tx.nonce).collect::<Vec<_>>(),
+        vec![7, 8]
+    );
+
+    // Rotated between two headers, the settler's handle follows.
+    keys.rotate(KeyRole::Settlement, "new.json")
+        .expect("failed to rotate");
+    let new_signer = eth_address(&keys);
+    assert_ne!(new_signer, old_signer);
+    assert!(matches!(
+        settler.submit(&header(2)).await,
+        BatchStatus::Settled { .. }
+    ));
+    // The nonce is the new signer's, not the one after the old signer's.
+    let next = node.sent().pop().unwrap();
+    assert_eq!(next.nonce, 7);
+    assert_eq!(next.data, calldata(&header(2)));
+    assert_eq!(
+        node.counted.lock().unwrap().clone(),
+        vec![old_signer, new_signer]
+    );
+
+    handle.stop().ok();
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,230 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for a node told to stop draining what it took on stage by
+//! stage, and keeping the batches it could not disperse for the next start.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{send, spawn_as, spawn_named, stop};
+use eigenda_client::proof::BlobVerificationProof;
+use lasr_actors::{
+    Batch, Batcher, DrainReport, DrainStage, Shutdown, ShutdownDrain, StorageRef, TaskScheduler,
+    UnsealedBatches,
+};
+use lasr_messages::{
+    ActorType, EngineMessage, RpcErrorCode, RpcMessage, SchedulerMessage, TransactionResponse,
+};
+use lasr_types::{MockPersistenceStore, PersistenceStore, Transaction};
+use ractor::{
+    concurrency::{oneshot, OneshotReceiver},
+    Actor, ActorCell, ActorProcessingErr, ActorRef,
+};
+use serial_test::serial;
+use tokio::sync::{mpsc, Mutex};
+
+/// Stands in for the engine, answering that it is working on as many
+/// transactions as the test says it is.
+struct MockEngine;
+
+#[async_trait]
+impl Actor for MockEngine {
+    type Msg = EngineMessage;
+    type State = Arc<AtomicUsize>;
+    type Arguments = Arc<AtomicUsize>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        in_flight: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(in_flight)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        in_flight: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let EngineMessage::Flush { reply } = message {
+            reply.send(in_flight.load(Ordering::SeqCst)).ok();
+        }
+        Ok(())
+    }
+}
+
+/// A batch holding a send for each of `nonces`, as applied before the node
+/// was told to stop.
+fn batch(nonces: impl IntoIterator<Item = u64>) -> Batch {
+    let mut batch = Batch::new();
+    for nonce in nonces {
+        batch.insert_transaction(send(nonce)).unwrap();
+    }
+    batch
+}
+
+fn hashes(batches: &[Batch]) -> Vec<String> {
+    let mut hashes: Vec<String> = batches
+        .iter()
+        .flat_map(|batch| batch.transactions().into_keys())
+        .collect();
+    hashes.sort();
+    hashes
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("shutdown_{}_{}.log", name, std::process::id()));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+struct Node {
+    _batcher_rx: mpsc::Receiver<OneshotReceiver<(String, BlobVerificationProof)>>,
+    shutdown: Shutdown,
+    batcher: Arc<Mutex<Batcher>>,
+    storage: StorageRef,
+    scheduler: ActorRef<SchedulerMessage>,
+    engine_in_flight: Arc<AtomicUsize>,
+    actors: Vec<ActorCell>,
+}
+
+impl Node {
+    /// Starts a node with no DA client, so the batches it holds can not be
+    /// dispersed.
+    async fn start(batches: Vec<Batch>) -> Self {
+        let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+        let batcher = Arc::new(Mutex::new(
+            Batcher::new(receivers_thread_tx).with_unsealed(batches),
+        ));
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .expect("failed to create mock storage");
+        let scheduler = spawn_named(TaskScheduler::new().with_persisted_receipts(false), ()).await;
+        let engine_in_flight = Arc::new(AtomicUsize::new(0));
+        let engine = spawn_as(ActorType::Engine, MockEngine, engine_in_flight.clone()).await;
+        Self {
+            _batcher_rx,
+            shutdown: Shutdown::default(),
+            batcher,
+            storage,
+            scheduler: scheduler.clone(),
+            engine_in_flight,
+            actors: vec![scheduler.get_cell(), engine.get_cell()],
+        }
+    }
+
+    fn drain(&self, unsealed: &UnsealedBatches, deadline: Duration) -> ShutdownDrain {
+        ShutdownDrain::new(
+            self.shutdown.clone(),
+            self.batcher.clone(),
+            self.storage.clone(),
+        )
+        .with_deadline(deadline)
+        .with_unsealed_batches(unsealed.clone())
+    }
+
+    /// Submits a send to the scheduler, answering with the error it was
+    /// turned away with, if any.
+    async fn submit(&self, transaction: Transaction) -> Option<RpcErrorCode> {
+        let (tx, rx) = oneshot();
+        self.scheduler
+            .cast(SchedulerMessage::Send {
+                transaction,
+                replace: false,
+                rpc_reply: tx.into(),
+            })
+            .unwrap();
+        match tokio::time::timeout(Duration::from_millis(200), rx).await {
+            Ok(Ok(RpcMessage::Response {
+                response: Ok(TransactionResponse::TransactionError(error)),
+                ..
+            })) => Some(error.code),
+            _ => None,
+        }
+    }
+
+    async fn stop(self) {
+        stop(self.actors).await;
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn a_stopping_node_drains_each_stage_and_keeps_the_batches_it_could_not_disperse() {
+    let applied = vec![batch(1..=3), batch(4..=5)];
+    let node = Node::start(applied.clone()).await;
+    let unsealed = UnsealedBatches::new(temp_path("drained"));
+
+    // A write admitted before the shutdown, and the engine still working,
+    // are waited for.
+    let admitted = node.shutdown.admit().expect("the node turned a write away");
+    node.engine_in_flight.store(2, Ordering::SeqCst);
+    let in_flight = node.engine_in_flight.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(admitted);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        in_flight.store(0, Ordering::SeqCst);
+    });
+
+    let report = node.drain(&unsealed, Duration::from_secs(5)).run().await;
+    assert_eq!(
+        report,
+        DrainReport {
+            drained: vec![
+                DrainStage::Admission,
+                DrainStage::Scheduler,
+                DrainStage::Engine
+            ],
+            abandoned: None,
+            sealed: 0,
+            persisted: 2,
+        }
+    );
+    assert_eq!(node.shutdown.writes_in_flight(), 0);
+
+    // Nothing new is taken on once the node has started stopping.
+    assert!(node.shutdown.admit().is_none());
+    assert_eq!(node.submit(send(6)).await, Some(RpcErrorCode::ShuttingDown));
+
+    // Every transaction applied is taken back on the next start, once.
+    let taken = unsealed
+        .take()
+        .expect("failed to take the unsealed batches");
+    assert_eq!(hashes(&taken), hashes(&applied));
+    assert!(unsealed.take().unwrap().is_empty());
+    let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+    let restarted = Batcher::new(receivers_thread_tx).with_unsealed(taken);
+    assert_eq!(hashes(&restarted.unsealed()), hashes(&applied));
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_stage_still_draining_at_the_deadline_is_abandoned_and_the_batches_kept() {
+    let applied = vec![batch(1..=2)];
+    let node = Node::start(applied.clone()).await;
+    let unsealed = UnsealedBatches::new(temp_path("abandoned"));
+    // The engine never finishes what it is working on.
+    node.engine_in_flight.store(3, Ordering::SeqCst);
+
+    let report = node
+        .drain(&unsealed, Duration::from_millis(300))
+        .run()
+        .await;
+    assert_eq!(
+        report.drained,
+        vec![DrainStage::Admission, DrainStage::Scheduler]
+    );
+    assert_eq!(report.abandoned, Some((DrainStage::Engine, 3)));
+    assert_eq!(report.persisted, 1);
+    assert_eq!(hashes(&unsealed.take().unwrap()), hashes(&applied));
+
+    node.stop().await;
+}
@@ -0,0 +1,466 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for simulating transactions through the RPC server.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::{rpc_proxy, serve, signed, spawn_as, spawn_named, stop, ws_client};
+use eigenda_client::proof::BlobVerificationProof;
+use futures::future::join_all;
+use jsonrpsee::{core::ClientError, server::ServerHandle, ws_client::WsClient};
+use lasr_actors::{
+    get_account, AccountCacheActor, Batcher, BatcherActor, LasrRpcServerImpl,
+    PendingTransactionActor, TaskScheduler, TokenDelta,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{AccountCacheMessage, ActorType, ExecutorMessage, LasrError, RpcErrorCode};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{
+    Account, AccountBuilder, AccountType, Address, AddressOrNamespace, ArbitraryData,
+    ContractLogType, Inputs, Instruction, LogInstruction, Metadata, MockPersistenceStore, Outputs,
+    Payload, PayloadBuilder, PersistenceStore, Status, TokenBuilder, Transaction, TransactionType,
+    TransferInstructionBuilder, U256,
+};
+use ractor::{concurrency::OneshotReceiver, Actor, ActorCell, ActorProcessingErr, ActorRef};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::{json, Value};
+use serial_test::serial;
+use tokio::sync::{mpsc, Mutex};
+
+const STARTING_BALANCE: u64 = 1000;
+
+fn token() -> Address {
+    Address::new([3; 20])
+}
+
+fn receiver() -> Address {
+    Address::new([2; 20])
+}
+
+fn signer() -> SecretKey {
+    SecretKey::from_slice(&[1; 32]).unwrap()
+}
+
+fn forger() -> SecretKey {
+    SecretKey::from_slice(&[2; 32]).unwrap()
+}
+
+fn sender() -> Address {
+    PublicKey::from_secret_key(&Secp256k1::new(), &signer()).into()
+}
+
+fn payload(transaction_type: TransactionType, value: u64, nonce: u64) -> Payload {
+    let to = match transaction_type {
+        TransactionType::Call(_) => token(),
+        _ => receiver(),
+    };
+    PayloadBuilder::default()
+        .transaction_type(transaction_type)
+        .from(sender().into())
+        .to(to.into())
+        .program_id(token().into())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(value))
+        .nonce(U256::from(nonce))
+        .build()
+        .expect("failed to build payload")
+}
+
+fn send(value: u64, nonce: u64) -> Transaction {
+    signed(
+        payload(TransactionType::Send(U256::from(nonce)), value, nonce),
+        &signer(),
+    )
+}
+
+fn call(nonce: u64) -> Transaction {
+    signed(
+        payload(TransactionType::Call(U256::from(nonce)), 0, nonce),
+        &signer(),
+    )
+}
+
+fn user(owner: Address, balance: u64) -> Account {
+    let holding = TokenBuilder::default()
+        .program_id(token())
+        .owner_id(owner)
+        .balance(U256::from(balance))
+        .metadata(Metadata::new())
+        .token_ids(Vec::new())
+        .allowance(BTreeMap::new())
+        .approvals(BTreeMap::new())
+        .data(ArbitraryData::new())
+        .status(Status::Free)
+        .build()
+        .expect("failed to build token");
+    AccountBuilder::default()
+        .account_type(AccountType::User)
+        .program_namespace(None)
+        .owner_address(owner)
+        .programs(BTreeMap::from([(token(), holding)]))
+        .nonce(U256::from(0))
+        .program_account_data(ArbitraryData::new())
+        .program_account_metadata(Metadata::new())
+        .program_account_linked_programs(BTreeSet::new())
+        .try_build()
+        .expect("failed to build user account")
+}
+
+fn program() -> Account {
+    AccountBuilder::default()
+        .account_type(AccountType::Program(token()))
+        .program_namespace(None)
+        .owner_address(sender())
+        .programs(BTreeMap::new())
+        .nonce(U256::from(0))
+        .program_account_data(ArbitraryData::new())
+        .program_account_metadata(Metadata::new())
+        .program_account_linked_programs(BTreeSet::new())
+        .try_build()
+        .expect("failed to build program account")
+}
+
+/// The outputs of the program every call runs: a transfer of 2 from the
+/// caller to the receiver, with a log line.
+fn outputs(inputs: Inputs) -> String {
+    let transfer = TransferInstructionBuilder::new()
+        .token(token())
+        .from(AddressOrNamespace::Address(sender()))
+        .to(AddressOrNamespace::Address(receiver()))
+        .amount(U256::from(2))
+        .build()
+        .expect("failed to build transfer instruction");
+    let outputs = Outputs::new(
+        inputs,
+        vec![
+            Instruction::Transfer(transfer),
+            Instruction::Log(LogInstruction(ContractLogType::Info("moved 2".to_string()))),
+        ],
+    );
+    serde_json::to_string(&outputs).expect("failed to serialize outputs")
+}
+
+/// Stands in for the executor, running every simulated call as the program
+/// above, or reverting it with the reason it is set to.
+struct MockExecutor;
+
+#[async_trait]
+impl Actor for MockExecutor {
+    type Msg = ExecutorMessage;
+    type State = Arc<std::sync::Mutex<Option<String>>>;
+    type Arguments = Self::State;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        revert: Self::State,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(revert)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        revert: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let ExecutorMessage::Simulate { inputs, reply, .. } = message {
+            let result = match revert.lock().unwrap().clone() {
+                Some(reason) => Err(LasrError::Custom(reason)),
+                None => Ok(outputs(inputs)),
+            };
+            reply.send(result).ok();
+        }
+        Ok(())
+    }
+}
+
+struct Node {
+    actors: Vec<ActorCell>,
+    batcher: Arc<Mutex<Batcher>>,
+    revert: Arc<std::sync::Mutex<Option<String>>>,
+    handle: ServerHandle,
+    client: WsClient,
+    /// Must outlive the node for the batcher to be constructed.
+    _batcher_rx: mpsc::Receiver<OneshotReceiver<(String, BlobVerificationProof)>>,
+}
+
+impl Node {
+    /// Starts a node holding the sender's and receiver's accounts and the
+    /// token's program account.
+    async fn start() -> Self {
+        let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+            .await
+            .unwrap();
+        let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+        account_cache
+            .cast(AccountCacheMessage::WriteBatch {
+                accounts: vec![
+                    user(sender(), STARTING_BALANCE),
+                    user(receiver(), 0),
+                    program(),
+                ],
+                who: ActorType::Engine,
+                location: "test".to_string(),
+            })
+            .unwrap();
+
+        let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+        let batcher = Arc::new(Mutex::new(Batcher::new(receivers_thread_tx)));
+        let batcher_ref = spawn_named(BatcherActor::new(), batcher.clone()).await;
+        let scheduler = spawn_named(TaskScheduler::new(), ()).await;
+        let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+        let revert = Arc::default();
+        let executor = spawn_as(ActorType::Executor, MockExecutor, Arc::clone(&revert)).await;
+        let proxy = rpc_proxy().await;
+
+        let (handle, addr) = serve(
+            LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+                .with_unsigned_simulation(true)
+                .into_rpc(),
+        )
+        .await;
+        let client = ws_client(addr).await;
+        Self {
+            actors: vec![
+                proxy.get_cell(),
+                executor.get_cell(),
+                pending.get_cell(),
+                scheduler.get_cell(),
+                batcher_ref.get_cell(),
+                account_cache.get_cell(),
+            ],
+            batcher,
+            revert,
+            handle,
+            client,
+            _batcher_rx,
+        }
+    }
+
+    async fn simulate(&self, transaction: Transaction) -> Value {
+        let simulation = self
+            .client
+            .simulate_transaction(transaction)
+            .await
+            .expect("failed to simulate transaction");
+        serde_json::from_str(&simulation).expect("simulation is not JSON")
+    }
+
+    async fn accounts(&self) -> Vec<Option<Account>> {
+        let mut accounts = Vec::new();
+        for address in [sender(), receiver(), token()] {
+            accounts.push(get_account(address, ActorType::RpcServer).await);
+        }
+        accounts
+    }
+
+    async fn stop(self) {
+        self.handle.stop().ok();
+        stop(self.actors).await;
+    }
+}
+
+fn delta(address: Address, before: u64, after: u64) -> TokenDelta {
+    TokenDelta {
+        address,
+        program_id: token(),
+        before: U256::from(before),
+        after: U256::from(after),
+    }
+}
+
+/// `deltas` in the order a simulation reports them.
+fn sorted(mut deltas: Vec<TokenDelta>) -> Value {
+    deltas.sort_by_key(|delta| (delta.address, delta.program_id));
+    json!(deltas)
+}
+
+#[tokio::test]
+#[serial]
+async fn simulating_a_send_predicts_what_sending_it_does() {
+    let node = Node::start().await;
+    let transaction = send(5, 1);
+
+    let simulation = node.simulate(transaction.clone()).await;
+    assert_eq!(simulation["success"], true, "{simulation}");
+    assert_eq!(
+        simulation["tokenDeltas"],
+        sorted(vec![
+            delta(sender(), STARTING_BALANCE, STARTING_BALANCE - 5),
+            delta(receiver(), 0, 5),
+        ])
+    );
+    assert_eq!(simulation["estimatedFee"], json!(U256::from(0)));
+    assert_eq!(simulation["trace"]["accountsTouched"], 2);
+    assert_eq!(simulation["trace"]["snapshots"], 1);
+    assert!(simulation.get("revertReason").is_none());
+
+    Batcher::add_transaction_to_account(node.batcher.clone(), transaction.clone())
+        .await
+        .expect("failed to apply send");
+    let applied = node
+        .batcher
+        .lock()
+        .await
+        .inclusions()
+        .get(&transaction.hash_string())
+        .expect("send was not applied")
+        .token_deltas;
+    assert_eq!(simulation["tokenDeltas"], sorted(applied));
+
+    // The next simulation starts from the balances the send left.
+    let overdrawn = node.simulate(send(STARTING_BALANCE, 2)).await;
+    assert_eq!(overdrawn["success"], false);
+    assert_eq!(overdrawn["tokenDeltas"], json!([]));
+    assert!(overdrawn["revertReason"].is_string(), "{overdrawn}");
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn simulating_a_call_reports_what_its_program_did() {
+    let node = Node::start().await;
+
+    let simulation = node.simulate(call(1)).await;
+    assert_eq!(simulation["success"], true, "{simulation}");
+    assert_eq!(
+        simulation["tokenDeltas"],
+        sorted(vec![
+            delta(sender(), STARTING_BALANCE, STARTING_BALANCE - 2),
+            delta(receiver(), 0, 2),
+        ])
+    );
+    assert_eq!(
+        simulation["events"],
+        json!([{ "level": "info", "message": "moved 2" }])
+    );
+    assert_eq!(
+        simulation["trace"]["instructions"],
+        json!({ "log": 1, "transfer": 1 })
+    );
+
+    *node.revert.lock().unwrap() = Some("division by zero".to_string());
+    let reverted = node.simulate(call(1)).await;
+    assert_eq!(reverted["success"], false);
+    assert_eq!(reverted["tokenDeltas"], json!([]));
+    assert_eq!(reverted["revertReason"], "division by zero");
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn simulations_change_nothing() {
+    let node = Node::start().await;
+    let before = node.accounts().await;
+
+    let transactions: Vec<Transaction> = (1..=20)
+        .map(|nonce| {
+            if nonce % 2 == 0 {
+                send(nonce, nonce)
+            } else {
+                call(nonce)
+            }
+        })
+        .collect();
+    let simulations = join_all(
+        transactions
+            .iter()
+            .map(|transaction| node.simulate(transaction.clone())),
+    )
+    .await;
+    for simulation in &simulations {
+        assert_eq!(simulation["success"], true, "{simulation}");
+    }
+
+    assert_eq!(node.accounts().await, before);
+    let inclusions = node.batcher.lock().await.inclusions();
+    for transaction in &transactions {
+        assert!(inclusions.get(&transaction.hash_string()).is_none());
+    }
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn simulations_alongside_sends_never_see_half_a_send() {
+    let node = Node::start().await;
+
+    let sends = {
+        let batcher = node.batcher.clone();
+        tokio::spawn(async move {
+            for nonce in 1..=10 {
+                Batcher::add_transaction_to_account(batcher.clone(), send(1, nonce))
+                    .await
+                    .expect("failed to apply send");
+            }
+        })
+    };
+    let simulations = join_all((0..20).map(|_| node.simulate(send(1, 100)))).await;
+    sends.await.unwrap();
+
+    // What the sender is debited the receiver is credited, so the two hold
+    // the starting balance between them unless a send was seen half applied.
+    for simulation in simulations {
+        let held = simulation["tokenDeltas"]
+            .as_array()
+            .expect("simulation has no deltas")
+            .iter()
+            .map(|delta| serde_json::from_value::<U256>(delta["before"].clone()).unwrap())
+            .fold(U256::from(0), |held, before| held + before);
+        assert_eq!(held, U256::from(STARTING_BALANCE), "{simulation}");
+    }
+
+    node.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn only_the_sender_of_a_transaction_is_simulated_as_sending_it() {
+    let node = Node::start().await;
+
+    let unsigned = payload(TransactionType::Send(U256::from(1)), 5, 1);
+    let simulation: Value = serde_json::from_str(
+        &node
+            .client
+            .simulate_payload(unsigned.clone(), sender().to_full_string())
+            .await
+            .expect("failed to simulate payload"),
+    )
+    .unwrap();
+    assert_eq!(
+        simulation["tokenDeltas"],
+        node.simulate(send(5, 1)).await["tokenDeltas"]
+    );
+
+    let code = |result: Result<String, ClientError>| match result {
+        Err(ClientError::Call(error)) => error.code(),
+        other => panic!("transaction was simulated: {other:?}"),
+    };
+    assert_eq!(
+        code(
+            node.client
+                .simulate_payload(unsigned.clone(), receiver().to_full_string())
+                .await
+        ),
+        RpcErrorCode::InvalidParams.code()
+    );
+    assert_eq!(
+        code(
+            node.client
+                .simulate_transaction(signed(unsigned, &forger()))
+                .await
+        ),
+        RpcErrorCode::SenderMismatch.code()
+    );
+
+    node.stop().await;
+}
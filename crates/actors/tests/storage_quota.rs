@@ -0,0 +1,194 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for holding the metadata and data programs keep in
+//! accounts to the node's storage quota.
+
+use std::collections::BTreeMap;
+
+use lasr_actors::{
+    Batcher, Genesis, GenesisAccount, GenesisProgram, StorageConfig, TestHarness,
+    DEFAULT_ETH_CHAIN_ID, VERSE_ADDR,
+};
+use lasr_types::{
+    Address, AddressOrNamespace, DataValue, Instruction, Outputs, OutputsBuilder, TokenField,
+    TokenFieldValue, TokenOrProgramUpdate, TokenUpdateBuilder, TokenUpdateFieldBuilder,
+    Transaction, TransactionBuilder, TransactionType, UpdateInstructionBuilder, U256,
+};
+use serial_test::serial;
+
+const MAX_TOKEN_BYTES: usize = 64;
+const MAX_ACCOUNT_BYTES: usize = 256;
+
+fn alice() -> Address {
+    Address::new([1; 20])
+}
+
+fn program_id() -> Address {
+    Address::new([0xaa; 20])
+}
+
+async fn harness() -> TestHarness {
+    let path = std::env::temp_dir().join(format!("storage_quota_{}.toml", std::process::id()));
+    let genesis = Genesis {
+        chain_id: DEFAULT_ETH_CHAIN_ID,
+        programs: vec![GenesisProgram {
+            program_id: program_id(),
+            owner: alice(),
+            content_id: "bafy".to_string(),
+            metadata: BTreeMap::new(),
+            auth_program: None,
+        }],
+        accounts: vec![GenesisAccount {
+            address: alice(),
+            balances: BTreeMap::from([
+                (VERSE_ADDR, U256::from(1_000)),
+                (program_id(), U256::from(10)),
+            ]),
+        }],
+    };
+    std::fs::write(&path, toml::to_string(&genesis).unwrap()).unwrap();
+    TestHarness::start_with(|config| {
+        config.genesis(path).storage(StorageConfig {
+            max_token_bytes: MAX_TOKEN_BYTES,
+            max_account_bytes: MAX_ACCOUNT_BYTES,
+            exempt_system_programs: true,
+            ..StorageConfig::default()
+        })
+    })
+    .await
+    .expect("failed to start node")
+}
+
+fn call(nonce: u64) -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Call(U256::from(nonce)))
+        .from(alice().into())
+        .to(alice().into())
+        .program_id(program_id().into())
+        .op("store".to_string())
+        .inputs(String::new())
+        .value(U256::from(0))
+        .nonce(U256::from(nonce))
+        .v(0)
+        .r([0; 32])
+        .s([0; 32])
+        .build()
+        .unwrap()
+}
+
+/// Outputs storing `value` under `key` in the data of Alice's `token`.
+fn store(token: Address, key: &str, value: String) -> Outputs {
+    let update = TokenUpdateBuilder::new()
+        .account(AddressOrNamespace::Address(alice()))
+        .token(AddressOrNamespace::Address(token))
+        .add_update(
+            TokenUpdateFieldBuilder::new()
+                .field(TokenField::Data)
+                .value(TokenFieldValue::Data(DataValue::Insert(
+                    key.to_string(),
+                    value,
+                )))
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    OutputsBuilder::new()
+        .add_instruction(Instruction::Update(
+            UpdateInstructionBuilder::new()
+                .add_update(TokenOrProgramUpdate::TokenUpdate(update))
+                .build(),
+        ))
+        .build()
+        .unwrap()
+}
+
+async fn token_size(harness: &TestHarness, token: Address) -> usize {
+    harness
+        .account(alice())
+        .await
+        .expect("alice is not there")
+        .programs()
+        .get(&token)
+        .map(|token| token.storage_size())
+        .unwrap_or(0)
+}
+
+#[tokio::test]
+#[serial]
+async fn a_token_may_be_filled_to_its_quota_and_not_a_byte_past_it() {
+    let harness = harness().await;
+    let held = token_size(&harness, program_id()).await;
+
+    // A key of one byte, and a value filling the rest of the budget.
+    let fill = "x".repeat(MAX_TOKEN_BYTES - held - 1);
+    Batcher::apply_instructions_to_accounts(
+        harness.batcher(),
+        call(0),
+        store(program_id(), "k", fill),
+    )
+    .await
+    .expect("an update filling the token to its quota was refused");
+    let filled = harness
+        .wait_for_account(alice(), |account| {
+            account
+                .programs()
+                .get(&program_id())
+                .is_some_and(|token| token.storage_size() == MAX_TOKEN_BYTES)
+        })
+        .await;
+    assert!(filled.is_ok(), "the update was not applied");
+
+    let error = Batcher::apply_instructions_to_accounts(
+        harness.batcher(),
+        call(1),
+        store(program_id(), "l", String::new()),
+    )
+    .await
+    .expect_err("an update one byte past the quota was applied");
+    assert!(
+        error
+            .to_string()
+            .contains(&format!("over the limit of {MAX_TOKEN_BYTES}")),
+        "{error}"
+    );
+    assert_eq!(token_size(&harness, program_id()).await, MAX_TOKEN_BYTES);
+
+    let sizes = harness.batcher().lock().await.account_sizes();
+    assert!(sizes.contains(&(alice(), MAX_TOKEN_BYTES)), "{sizes:?}");
+}
+
+#[tokio::test]
+#[serial]
+async fn the_tokens_of_the_system_programs_are_not_held_to_the_quota() {
+    let harness = harness().await;
+
+    let large = "x".repeat(MAX_ACCOUNT_BYTES * 2);
+    Batcher::apply_instructions_to_accounts(
+        harness.batcher(),
+        call(0),
+        store(VERSE_ADDR, "k", large),
+    )
+    .await
+    .expect("an update to an exempt token was refused");
+    let stored = harness
+        .wait_for_account(alice(), |account| {
+            account
+                .programs()
+                .get(&VERSE_ADDR)
+                .is_some_and(|token| token.storage_size() > MAX_ACCOUNT_BYTES)
+        })
+        .await;
+    assert!(stored.is_ok(), "the update was not applied");
+
+    // Nor does it count against the quota of the account holding it.
+    let held = token_size(&harness, program_id()).await;
+    let fill = "x".repeat(MAX_TOKEN_BYTES - held - 1);
+    Batcher::apply_instructions_to_accounts(
+        harness.batcher(),
+        call(1),
+        store(program_id(), "k", fill),
+    )
+    .await
+    .expect("an update within the quota was refused");
+}
@@ -0,0 +1,235 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for notifications streamed to websocket subscribers.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{rpc_proxy, serve, spawn_named, ws_client};
+use jsonrpsee::{
+    core::{client::Subscription, ClientError, JsonValue},
+    server::ServerHandle,
+    types::error::INVALID_PARAMS_CODE,
+    ws_client::WsClient,
+};
+use lasr_actors::{
+    AccountCacheActor, BatchReceipts, LasrRpcServerImpl, Notifications, PendingTransactionActor,
+    TaskScheduler,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{
+    AccountCacheMessage, ActorType, BatchHeader, BatchStatus, RpcMessage, SchedulerMessage,
+};
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Account, Address, MockPersistenceStore, PersistenceStore};
+use ractor::ActorRef;
+use serde_json::json;
+use serial_test::serial;
+use web3::types::H256;
+
+struct Server {
+    proxy: ActorRef<RpcMessage>,
+    handle: ServerHandle,
+    client: WsClient,
+}
+
+impl Server {
+    async fn start(rpc: impl FnOnce(LasrRpcServerImpl) -> LasrRpcServerImpl) -> Self {
+        let proxy = rpc_proxy().await;
+        let (handle, addr) = serve(
+            rpc(LasrRpcServerImpl::new(
+                proxy.clone(),
+                ExecutionLogs::default(),
+            ))
+            .into_rpc(),
+        )
+        .await;
+        let client = ws_client(addr).await;
+        Self {
+            proxy,
+            handle,
+            client,
+        }
+    }
+
+    async fn stop(self) {
+        drop(self.client);
+        self.handle.stop().ok();
+        self.proxy.stop_and_wait(None, None).await.ok();
+    }
+}
+
+async fn next(subscription: &mut Subscription<JsonValue>) -> JsonValue {
+    tokio::time::timeout(Duration::from_secs(5), subscription.next())
+        .await
+        .expect("no notification arrived")
+        .expect("subscription closed")
+        .expect("notification is not JSON")
+}
+
+fn write(account_cache: &ActorRef<AccountCacheMessage>, account: &Account) {
+    account_cache
+        .cast(AccountCacheMessage::Write {
+            account: account.clone(),
+            who: ActorType::Engine,
+            location: "test".to_string(),
+        })
+        .expect("failed to write account");
+}
+
+#[tokio::test]
+#[serial]
+async fn account_changes_are_delivered_until_unsubscribed() {
+    let notifications = Notifications::default();
+    let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+        .await
+        .unwrap();
+    let account_cache = spawn_named(
+        AccountCacheActor::new().with_notifications(notifications.clone()),
+        storage,
+    )
+    .await;
+    let server = Server::start(|rpc| rpc.with_notifications(notifications)).await;
+
+    let address = Address::new([1; 20]);
+    let mut first = server
+        .client
+        .subscribe_notifications("accountChanged".to_string(), Some(address.to_full_string()))
+        .await
+        .expect("failed to subscribe");
+    let mut second = server
+        .client
+        .subscribe_notifications("accountChanged".to_string(), Some(address.to_full_string()))
+        .await
+        .expect("failed to subscribe");
+
+    let mut account = Account::new(address);
+    // Someone else's account is not delivered.
+    write(&account_cache, &Account::new(Address::new([2; 20])));
+    write(&account_cache, &account);
+    for subscription in [&mut first, &mut second] {
+        let notification = next(subscription).await;
+        assert_eq!(notification["kind"], "accountChanged");
+        assert_eq!(notification["dropped"], 0);
+        assert_eq!(notification["account"]["found"], true);
+        assert_eq!(notification["account"]["address"], json!(address));
+        assert_eq!(notification["account"]["nonce"], json!(account.nonce()));
+    }
+
+    first.unsubscribe().await.expect("failed to unsubscribe");
+    account.increment_nonce();
+    write(&account_cache, &account);
+    let notification = next(&mut second).await;
+    assert_eq!(notification["account"]["nonce"], json!(account.nonce()));
+
+    server.stop().await;
+    account_cache.stop_and_wait(None, None).await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_transaction_status_subscription_starts_where_the_transaction_has_got_to() {
+    let notifications = Notifications::default();
+    let scheduler = spawn_named(
+        TaskScheduler::new().with_notifications(notifications.clone()),
+        (),
+    )
+    .await;
+    let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+    let server = Server::start(|rpc| rpc.with_notifications(notifications)).await;
+
+    let transaction_hash = format!("0x{}", "ab".repeat(32));
+    let replaced_by = format!("0x{}", "cd".repeat(32));
+    let mut subscription = server
+        .client
+        .subscribe_notifications(
+            "transactionStatus".to_string(),
+            Some(transaction_hash.clone()),
+        )
+        .await
+        .expect("failed to subscribe");
+    assert_eq!(
+        next(&mut subscription).await,
+        json!({
+            "kind": "transactionStatus",
+            "transactionHash": transaction_hash,
+            "status": { "status": "unknown" },
+            "dropped": 0,
+        })
+    );
+
+    scheduler
+        .cast(SchedulerMessage::TransactionReplaced {
+            transaction_hash: transaction_hash.clone(),
+            replaced_by: replaced_by.clone(),
+        })
+        .unwrap();
+    assert_eq!(
+        next(&mut subscription).await,
+        json!({
+            "kind": "transactionStatus",
+            "transactionHash": transaction_hash,
+            "status": { "status": "replaced", "replacedBy": replaced_by },
+            "dropped": 0,
+        })
+    );
+
+    server.stop().await;
+    pending.stop_and_wait(None, None).await.unwrap();
+    scheduler.stop_and_wait(None, None).await.unwrap();
+}
+
+#[tokio::test]
+async fn batch_receipts_are_delivered_as_they_are_set() {
+    let receipts = BatchReceipts::default();
+    let server = Server::start(|rpc| rpc.with_batch_receipts(receipts.clone())).await;
+
+    let mut subscription = server
+        .client
+        .subscribe_notifications("newBatch".to_string(), None)
+        .await
+        .expect("failed to subscribe");
+    let header = BatchHeader {
+        batch_id: 7,
+        state_root: H256::repeat_byte(0x01),
+        transactions_root: H256::zero(),
+        events_root: H256::zero(),
+        blob_commitment: H256::repeat_byte(0x02),
+        blob_index: 3,
+        withdrawals: Vec::new(),
+    };
+    receipts.set(&header, BatchStatus::Finalized);
+
+    let notification = next(&mut subscription).await;
+    assert_eq!(notification["kind"], "newBatch");
+    assert_eq!(notification["dropped"], 0);
+    assert_eq!(notification["batch"]["header"]["batchId"], 7);
+    assert_eq!(notification["batch"]["status"], "finalized");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn subscriptions_with_the_wrong_parameters_are_turned_away() {
+    let server = Server::start(|rpc| rpc).await;
+
+    for (kind, param) in [
+        ("accountChanged", None),
+        ("accountChanged", Some("not an address".to_string())),
+        ("transactionStatus", None),
+        ("newBatch", Some("7".to_string())),
+        ("blockProduced", None),
+    ] {
+        match server
+            .client
+            .subscribe_notifications(kind.to_string(), param.clone())
+            .await
+        {
+            Err(ClientError::Call(error)) => assert_eq!(error.code(), INVALID_PARAMS_CODE),
+            other => panic!("{kind} {param:?} was subscribed to: {other:?}"),
+        }
+    }
+
+    server.stop().await;
+}
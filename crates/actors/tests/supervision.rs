@@ -0,0 +1,267 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+#![cfg(feature = "crash_test")]
+//! Test coverage for actors told to panic being restarted by their
+//! supervisors with the state they keep elsewhere, and one panicking too
+//! often being given up on.
+
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{send, spawn_as, spawn_named};
+use lasr_actors::{
+    get_account, replay_pending_log, AccountCacheActor, AccountCacheSupervisor, AccountValue,
+    Batch, Batcher, BatcherActor, BatcherSupervisor, CrashLoop, PendingTransactionActor,
+    PendingTransactionSupervisor, Restarts, SupervisionPolicy,
+};
+use lasr_messages::{
+    AccountCacheMessage, ActorName, ActorType, BatcherMessage, PendingTransactionMessage,
+    PendingTransactionSummary, ToActorType, ValidatorMessage,
+};
+use lasr_types::{Account, Address, MockPersistenceStore, PersistenceStore, U256};
+use ractor::{rpc::CallResult, Actor, ActorCell, ActorProcessingErr, ActorRef};
+use serial_test::serial;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::Instant;
+
+/// Stands in for the validator, taking whatever it is given.
+struct MockValidator;
+
+#[async_trait]
+impl Actor for MockValidator {
+    type Msg = ValidatorMessage;
+    type State = ();
+    type Arguments = ();
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        _: (),
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(())
+    }
+}
+
+fn account(address: [u8; 20]) -> Account {
+    Account::new(Address::new(address))
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("supervision_{}_{}.log", name, std::process::id()));
+    std::fs::remove_file(&path).ok();
+    path
+}
+
+/// Waits for a supervisor to hand back the actor that panicked under it, as
+/// the node does, recording the panic.
+async fn panicked(
+    panics: &mut mpsc::Receiver<ActorCell>,
+    restarts: &mut Restarts,
+) -> Result<usize, CrashLoop> {
+    let actor = tokio::time::timeout(Duration::from_secs(5), panics.recv())
+        .await
+        .expect("no actor panicked")
+        .expect("supervisor went away");
+    let actor_name = actor.get_name().expect("the actor has no name");
+    // Respawned under the same name once the one that panicked is gone.
+    while ractor::registry::where_is(actor_name.clone()).is_some() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    restarts.record(actor_name.to_actor_type(), Instant::now())
+}
+
+fn restarts(max_restarts: u32) -> Restarts {
+    Restarts::new(SupervisionPolicy {
+        max_restarts,
+        window: Duration::from_secs(60),
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn the_account_cache_is_restarted_with_the_accounts_it_held() {
+    let (panic_tx, mut panics) = mpsc::channel(8);
+    let supervisor = spawn_named(AccountCacheSupervisor::new(panic_tx), ()).await;
+    let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+        .await
+        .expect("failed to create mock storage");
+    // One account was persisted with a sealed batch, the other is only on
+    // the batch being built.
+    let sealed = account([5; 20]);
+    PersistenceStore::put(
+        &storage,
+        sealed.owner_address().to_full_string().into(),
+        bincode::serialize(&AccountValue {
+            account: sealed.clone(),
+        })
+        .unwrap(),
+    )
+    .await
+    .expect("failed to persist the sealed account");
+    let unsealed = account([6; 20]);
+    let mut batch = Batch::new();
+    batch.insert_account(unsealed.clone()).unwrap();
+    let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+    let batcher = Arc::new(Mutex::new(
+        Batcher::new(receivers_thread_tx).with_unsealed(vec![batch]),
+    ));
+
+    let spawn = || async {
+        let actor = AccountCacheActor::new();
+        Actor::spawn_linked(
+            Some(actor.name()),
+            actor,
+            storage.clone(),
+            supervisor.get_cell(),
+        )
+        .await
+        .expect("failed to spawn account cache")
+        .0
+    };
+    let account_cache = spawn().await;
+    Batcher::cache_unsealed(batcher.clone()).await;
+    assert!(get_account(unsealed.owner_address(), ActorType::Batcher)
+        .await
+        .is_some());
+
+    account_cache.cast(AccountCacheMessage::Panic).unwrap();
+    let mut restarts = restarts(5);
+    assert_eq!(panicked(&mut panics, &mut restarts).await, Ok(1));
+    let account_cache = spawn().await;
+    Batcher::cache_unsealed(batcher.clone()).await;
+
+    for account in [sealed, unsealed] {
+        let found = get_account(account.owner_address(), ActorType::Batcher)
+            .await
+            .expect("the account was lost with the restart");
+        assert_eq!(found.owner_address(), account.owner_address());
+    }
+
+    account_cache.stop_and_wait(None, None).await.ok();
+    supervisor.stop_and_wait(None, None).await.ok();
+}
+
+async fn pending(actor: &ActorRef<PendingTransactionMessage>) -> Vec<PendingTransactionSummary> {
+    let result = actor
+        .call(
+            |reply| PendingTransactionMessage::GetPendingByAddress {
+                address: Address::new([1; 20]),
+                reply,
+            },
+            Some(Duration::from_secs(5)),
+        )
+        .await
+        .expect("failed to call pending transactions actor");
+    match result {
+        CallResult::Success(page) => page,
+        _ => panic!("pending transactions actor did not answer"),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn the_pending_pool_is_restarted_from_its_log() {
+    let validator = spawn_as(ActorType::Validator, MockValidator, ()).await;
+    let (panic_tx, mut panics) = mpsc::channel(8);
+    let supervisor = spawn_named(PendingTransactionSupervisor::new(panic_tx), ()).await;
+    let path = temp_path("pending");
+
+    let spawn = || async {
+        let actor = PendingTransactionActor::new().with_log_path(path.clone());
+        Actor::spawn_linked(Some(actor.name()), actor, (), supervisor.get_cell())
+            .await
+            .expect("failed to spawn pending transactions actor")
+            .0
+    };
+    let pending_actor = spawn().await;
+    for nonce in 0..2 {
+        pending_actor
+            .cast(PendingTransactionMessage::New {
+                transaction: send(nonce),
+                outputs: None,
+                replace: false,
+            })
+            .unwrap();
+    }
+    assert_eq!(pending(&pending_actor).await.len(), 2);
+
+    pending_actor
+        .cast(PendingTransactionMessage::Panic)
+        .unwrap();
+    let mut restarts = restarts(5);
+    assert_eq!(panicked(&mut panics, &mut restarts).await, Ok(1));
+    let pending_actor = spawn().await;
+    replay_pending_log();
+
+    assert_eq!(
+        pending(&pending_actor)
+            .await
+            .iter()
+            .map(|summary| summary.nonce)
+            .collect::<Vec<_>>(),
+        vec![U256::from(0), U256::from(1)]
+    );
+
+    pending_actor.stop_and_wait(None, None).await.ok();
+    supervisor.stop_and_wait(None, None).await.ok();
+    validator.stop_and_wait(None, None).await.ok();
+}
+
+#[tokio::test]
+#[serial]
+async fn a_batcher_keeps_its_batches_over_restarts_until_it_crash_loops() {
+    let (panic_tx, mut panics) = mpsc::channel(8);
+    let supervisor = spawn_named(BatcherSupervisor::new(panic_tx), ()).await;
+    let mut batch = Batch::new();
+    for nonce in 1..=3 {
+        batch.insert_transaction(send(nonce)).unwrap();
+    }
+    let (receivers_thread_tx, _batcher_rx) = mpsc::channel(1);
+    let batcher = Arc::new(Mutex::new(
+        Batcher::new(receivers_thread_tx).with_unsealed(vec![batch.clone()]),
+    ));
+
+    let spawn = || async {
+        let actor = BatcherActor::new();
+        Actor::spawn_linked(
+            Some(actor.name()),
+            actor,
+            batcher.clone(),
+            supervisor.get_cell(),
+        )
+        .await
+        .expect("failed to spawn batcher")
+        .0
+    };
+    let mut restarts = restarts(1);
+    let batcher_actor = spawn().await;
+    batcher_actor.cast(BatcherMessage::Panic).unwrap();
+    assert_eq!(panicked(&mut panics, &mut restarts).await, Ok(1));
+
+    let batcher_actor = spawn().await;
+    let held = batcher.lock().await.unsealed();
+    assert_eq!(held.len(), 1);
+    let mut hashes: Vec<String> = held[0].transactions().into_keys().collect();
+    let mut applied: Vec<String> = batch.transactions().into_keys().collect();
+    hashes.sort();
+    applied.sort();
+    assert_eq!(hashes, applied);
+
+    // Panicking again within the window, it is not restarted.
+    batcher_actor.cast(BatcherMessage::Panic).unwrap();
+    assert_eq!(
+        panicked(&mut panics, &mut restarts).await,
+        Err(CrashLoop {
+            actor_type: ActorType::Batcher,
+            panics: 2,
+            window: Duration::from_secs(60),
+        })
+    );
+
+    supervisor.stop_and_wait(None, None).await.ok();
+}
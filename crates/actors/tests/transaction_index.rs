@@ -0,0 +1,161 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for looking up the transactions a node has finalized by the
+//! accounts and programs they involve.
+
+use jsonrpsee::core::ClientError;
+use lasr_actors::{
+    DaClient, IndexedTransaction, LocalFileDa, Page, TestHarness, TransactionIndex, VERSE_ADDR,
+};
+use lasr_messages::RpcErrorCode;
+use lasr_rpc::LasrRpcClient;
+use lasr_types::{Address, U256};
+use serial_test::serial;
+
+async fn by_address(
+    harness: &TestHarness,
+    address: Address,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Page<IndexedTransaction> {
+    let page = harness
+        .rpc()
+        .unwrap()
+        .get_transactions_by_address(address.to_full_string(), cursor, limit)
+        .await
+        .expect("failed to look up the address");
+    serde_json::from_str(&page).unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn transactions_are_looked_up_by_address_and_program_a_page_at_a_time() {
+    let harness =
+        TestHarness::start_with(|config| config.index_transactions(true).instant_seal(false))
+            .await
+            .expect("failed to start node");
+    let mut alice = harness
+        .genesis_wallet()
+        .await
+        .expect("the genesis account is not there");
+    let mut bob = harness.wallet().await.expect("failed to fund wallet");
+    let minted = harness.seal_batch().await.expect("failed to seal batch");
+    let carol = Address::new([7; 20]);
+
+    alice
+        .send(&bob.address(), &VERSE_ADDR, U256::from(10))
+        .await
+        .expect("failed to send");
+    alice
+        .send(&carol, &VERSE_ADDR, U256::from(1))
+        .await
+        .expect("failed to send");
+    bob.send(&alice.address(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(carol, |account| {
+            account.balance(&VERSE_ADDR) == U256::from(1)
+        })
+        .await
+        .expect("the send to carol was not applied");
+    harness
+        .wait_for_account(bob.address(), |account| account.nonce() >= U256::from(1))
+        .await
+        .expect("bob's send was not applied");
+    let sealed = harness.seal_batch().await.expect("failed to seal batch");
+    let sends: Vec<_> = sealed
+        .iter()
+        .flat_map(|record| record.transaction_hashes.clone())
+        .collect();
+    assert_eq!(sends.len(), 3);
+
+    // Alice sent two and received one, a page of two at a time.
+    let first = by_address(&harness, alice.address(), None, Some(2)).await;
+    assert_eq!(first.total, 3);
+    assert_eq!(first.items.len(), 2);
+    let second = by_address(
+        &harness,
+        alice.address(),
+        first.next_cursor.clone(),
+        Some(2),
+    )
+    .await;
+    assert_eq!(second.items.len(), 1);
+    assert_eq!(second.next_cursor, None);
+    let alices: Vec<_> = first.items.iter().chain(&second.items).collect();
+    assert!(alices.windows(2).all(|pair| pair[0].key() < pair[1].key()));
+    assert!(alices.iter().all(|tx| sends.contains(&tx.transaction_hash)));
+    assert_eq!(
+        alices
+            .iter()
+            .filter(|tx| tx.from == alice.address())
+            .count(),
+        2
+    );
+
+    // Bob was minted to before both his sends.
+    let bobs = by_address(&harness, bob.address(), None, None).await;
+    assert_eq!(bobs.total, 3);
+    assert!(minted[0]
+        .transaction_hashes
+        .contains(&bobs.items[0].transaction_hash));
+    let carols = by_address(&harness, carol, None, None).await;
+    assert_eq!(carols.items.len(), 1);
+    assert_eq!(carols.items[0].from, alice.address());
+    assert_eq!(carols.items[0].to, carol);
+    assert_eq!(carols.items[0].batch_id, sealed[0].batch_id);
+    assert_eq!(carols.items[0].timestamp, sealed[0].finalized_at);
+
+    let verse: Page<IndexedTransaction> = serde_json::from_str(
+        &harness
+            .rpc()
+            .unwrap()
+            .get_transactions_by_program(VERSE_ADDR.to_full_string(), None, None)
+            .await
+            .expect("failed to look up the program"),
+    )
+    .unwrap();
+    for send in &sends {
+        assert!(verse
+            .items
+            .iter()
+            .any(|tx| &tx.transaction_hash == send && tx.program_id == VERSE_ADDR));
+    }
+
+    // The same transactions are indexed again from the batches' blobs.
+    let rebuilt = TransactionIndex::default();
+    let da_client = DaClient::local(
+        LocalFileDa::open(&harness.config().da.local_path).expect("failed to open the blobs"),
+    );
+    assert_eq!(
+        rebuilt.rebuild(&harness.batches(), &da_client).unwrap(),
+        harness.batches().list().len()
+    );
+    assert_eq!(
+        rebuilt.by_address(&alice.address()),
+        alices.into_iter().cloned().collect::<Vec<_>>()
+    );
+    assert_eq!(rebuilt.by_address(&bob.address()), bobs.items);
+    assert_eq!(rebuilt.by_program(&VERSE_ADDR), verse.items);
+
+    harness.stop().await;
+}
+
+#[tokio::test]
+#[serial]
+async fn a_node_not_indexing_transactions_says_so() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let code = match harness
+        .rpc()
+        .unwrap()
+        .get_transactions_by_address(Address::new([7; 20]).to_full_string(), None, None)
+        .await
+    {
+        Err(ClientError::Call(error)) => error.code(),
+        other => panic!("expected an error, got {other:?}"),
+    };
+    assert_eq!(code, RpcErrorCode::Disabled.code());
+
+    harness.stop().await;
+}
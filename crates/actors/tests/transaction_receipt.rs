@@ -0,0 +1,272 @@
+#![cfg(test)]
+#![cfg(feature = "mock_storage")]
+//! Test coverage for following a transaction through its statuses and receipt.
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{rpc_proxy, send, serve, spawn_as, spawn_named, ws_client};
+use jsonrpsee::{server::ServerHandle, ws_client::WsClient};
+use lasr_actors::{
+    token_deltas, AccountCacheActor, BatchReceipts, LasrRpcServerImpl, PendingTransactionActor,
+    TaskScheduler, TransactionInclusions,
+};
+use lasr_compute::ExecutionLogs;
+use lasr_messages::{
+    AccountCacheMessage, ActorType, BatchHeader, BatchStatus, PendingTransactionMessage,
+    RpcMessage, SchedulerMessage, ValidatorMessage,
+};
+use lasr_rpc::{LasrRpcClient, LasrRpcServer};
+use lasr_types::{
+    Account, Address, ArbitraryData, Metadata, MockPersistenceStore, PersistenceStore, Status,
+    Token, TokenBuilder, Transaction, U256,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use serde_json::{json, Value};
+use serial_test::serial;
+use tokio::sync::mpsc;
+use web3::types::H256;
+
+/// Stands in for the validator, handing on the transactions it is given.
+struct MockValidator;
+
+#[async_trait]
+impl Actor for MockValidator {
+    type Msg = ValidatorMessage;
+    type State = mpsc::UnboundedSender<Transaction>;
+    type Arguments = mpsc::UnboundedSender<Transaction>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        validating: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(validating)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        validating: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let ValidatorMessage::PendingTransaction { transaction } = message {
+            validating.send(transaction).ok();
+        }
+        Ok(())
+    }
+}
+
+fn token(balance: u64) -> Token {
+    TokenBuilder::default()
+        .program_id(Address::new([0; 20]))
+        .owner_id(Address::new([1; 20]))
+        .balance(U256::from(balance))
+        .metadata(Metadata::new())
+        .token_ids(Vec::new())
+        .allowance(BTreeMap::new())
+        .approvals(BTreeMap::new())
+        .data(ArbitraryData::new())
+        .status(Status::Free)
+        .build()
+        .unwrap()
+}
+
+fn holding(balance: u64) -> Account {
+    let mut account = Account::new(Address::new([1; 20]));
+    account
+        .programs_mut()
+        .insert(Address::new([0; 20]), token(balance));
+    account
+}
+
+async fn status(client: &WsClient, transaction_hash: &str) -> Value {
+    let status = client
+        .get_transaction_status(transaction_hash.to_string())
+        .await
+        .expect("failed to get transaction status");
+    serde_json::from_str(&status).unwrap()
+}
+
+/// Waits for `transaction_hash` to get to `expected`, as the actors are told
+/// of each step by casts.
+async fn reaches(client: &WsClient, transaction_hash: &str, expected: Value) {
+    for _ in 0..50 {
+        if status(client, transaction_hash).await == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(status(client, transaction_hash).await, expected);
+}
+
+async fn receipt(client: &WsClient, transaction_hash: &str) -> Value {
+    let receipt = client
+        .get_transaction_receipt(transaction_hash.to_string())
+        .await
+        .expect("failed to get transaction receipt");
+    serde_json::from_str(&receipt).unwrap()
+}
+
+async fn start(
+    inclusions: TransactionInclusions,
+    receipts: BatchReceipts,
+) -> (ActorRef<RpcMessage>, ServerHandle, WsClient) {
+    let proxy = rpc_proxy().await;
+    let (handle, addr) = serve(
+        LasrRpcServerImpl::new(proxy.clone(), ExecutionLogs::default())
+            .with_transaction_inclusions(inclusions)
+            .with_batch_receipts(receipts)
+            .into_rpc(),
+    )
+    .await;
+    let client = ws_client(addr).await;
+    (proxy, handle, client)
+}
+
+#[tokio::test]
+#[serial]
+async fn a_transaction_is_followed_from_the_pool_to_settlement() {
+    let storage = <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new()
+        .await
+        .unwrap();
+    let account_cache = spawn_named(AccountCacheActor::new(), storage).await;
+    account_cache
+        .cast(AccountCacheMessage::Write {
+            account: holding(10),
+            who: ActorType::Engine,
+            location: "test".to_string(),
+        })
+        .unwrap();
+    let scheduler = spawn_named(TaskScheduler::new(), ()).await;
+    let (validating_tx, mut validating) = mpsc::unbounded_channel();
+    let validator = spawn_as(ActorType::Validator, MockValidator, validating_tx).await;
+    let pending = spawn_named(PendingTransactionActor::new(), ()).await;
+    let inclusions = TransactionInclusions::default();
+    let receipts = BatchReceipts::default();
+    let (proxy, handle, client) = start(inclusions.clone(), receipts.clone()).await;
+
+    let first = send(1);
+    let transaction = send(2);
+    let transaction_hash = transaction.hash_string();
+    assert_eq!(
+        receipt(&client, &transaction_hash).await,
+        json!({
+            "transactionHash": transaction_hash,
+            "status": { "status": "unknown" },
+            "batchId": null,
+            "blobCommitment": null,
+            "tokenDeltas": [],
+            "events": [],
+            "revertReason": null,
+            "attestation": null,
+        })
+    );
+
+    let submit = |transaction: &Transaction| {
+        pending
+            .cast(PendingTransactionMessage::New {
+                transaction: transaction.clone(),
+                outputs: None,
+                replace: false,
+            })
+            .unwrap()
+    };
+    submit(&transaction);
+    reaches(
+        &client,
+        &transaction_hash,
+        json!({ "status": "queued", "waitingOn": U256::from(1) }),
+    )
+    .await;
+
+    submit(&first);
+    let validated = tokio::time::timeout(Duration::from_secs(5), validating.recv())
+        .await
+        .expect("nothing was validated")
+        .unwrap();
+    assert_eq!(validated.hash_string(), first.hash_string());
+    reaches(&client, &transaction_hash, json!({ "status": "pending" })).await;
+
+    pending
+        .cast(PendingTransactionMessage::Valid {
+            transaction: first,
+            cert: None,
+        })
+        .unwrap();
+    let validated = tokio::time::timeout(Duration::from_secs(5), validating.recv())
+        .await
+        .expect("nothing was validated")
+        .unwrap();
+    assert_eq!(validated.hash_string(), transaction_hash);
+    reaches(&client, &transaction_hash, json!({ "status": "executing" })).await;
+
+    // As the batcher does once it has applied the transaction.
+    inclusions.applied(&transaction, token_deltas(Some(&holding(10)), &holding(9)));
+    scheduler
+        .cast(SchedulerMessage::TransactionApplied {
+            transaction_hash: transaction_hash.clone(),
+            token: token(9),
+        })
+        .unwrap();
+    pending
+        .cast(PendingTransactionMessage::Valid {
+            transaction,
+            cert: None,
+        })
+        .unwrap();
+    reaches(&client, &transaction_hash, json!({ "status": "included" })).await;
+
+    let header = BatchHeader {
+        batch_id: 7,
+        state_root: H256::repeat_byte(0x01),
+        transactions_root: H256::zero(),
+        events_root: H256::zero(),
+        blob_commitment: H256::repeat_byte(0x02),
+        blob_index: 3,
+        withdrawals: Vec::new(),
+    };
+    inclusions.batched([&transaction_hash], header.batch_id);
+    receipts.set(&header, BatchStatus::Finalized);
+    let included = receipt(&client, &transaction_hash).await;
+    assert_eq!(included["status"], json!({ "status": "included" }));
+    assert_eq!(included["batchId"], 7);
+    assert_eq!(included["blobCommitment"], json!(header.blob_commitment));
+
+    receipts.set(
+        &header,
+        BatchStatus::Settled {
+            transaction_hash: H256::repeat_byte(0x03),
+            block_number: 12,
+        },
+    );
+    assert_eq!(
+        receipt(&client, &transaction_hash).await,
+        json!({
+            "transactionHash": transaction_hash,
+            "status": { "status": "settled" },
+            "batchId": 7,
+            "blobCommitment": header.blob_commitment,
+            "tokenDeltas": [{
+                "address": Address::new([1; 20]),
+                "programId": Address::new([0; 20]),
+                "before": U256::from(10),
+                "after": U256::from(9),
+            }],
+            "events": [],
+            "revertReason": null,
+            "attestation": null,
+        })
+    );
+
+    drop(client);
+    handle.stop().ok();
+    proxy.stop_and_wait(None, None).await.ok();
+    pending.stop_and_wait(None, None).await.unwrap();
+    validator.stop_and_wait(None, None).await.unwrap();
+    scheduler.stop_and_wait(None, None).await.unwrap();
+    account_cache.stop_and_wait(None, None).await.unwrap();
+}
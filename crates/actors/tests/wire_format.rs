@@ -0,0 +1,212 @@
+#![cfg(test)]
+//! Test coverage for the JSON the RPC API answers in, pinning each response
+//! type to the golden file checked in under `fixtures/json`: `U256`s as hex
+//! quantities, hashes and bytes as `0x` hex, and bincode kept to the bytes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+use ethereum_types::H256;
+use lasr_messages::{
+    AccountProof, BatchHeader, BatchReceipt, BatchStatus, InclusionProof, Withdrawal,
+};
+use lasr_types::{
+    Account, AccountBuilder, AccountDigest, AccountMetadata, AccountState, AccountType, Address,
+    ArbitraryData, Delegation, ExecutionAttestation, MerkleProof, Metadata,
+    RecoverableSignatureBuilder, Status, Token, TokenBuilder, TokenDelta, Transaction,
+    TransactionBuilder, TransactionType, U256,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+fn alice() -> Address {
+    Address::new([1; 20])
+}
+
+fn bob() -> Address {
+    Address::new([2; 20])
+}
+
+fn program_id() -> Address {
+    Address::new([3; 20])
+}
+
+fn token() -> Token {
+    let mut metadata = Metadata::new();
+    metadata.insert("symbol".to_string(), "TKN".to_string());
+    TokenBuilder::default()
+        .program_id(program_id())
+        .owner_id(alice())
+        .balance(U256::from(1_000))
+        .metadata(metadata)
+        .token_ids(vec![U256::from(7)])
+        .allowance(BTreeMap::from([(bob(), U256::from(5))]))
+        .approvals(BTreeMap::from([(bob(), vec![U256::from(7)])]))
+        .data(ArbitraryData::new())
+        .status(Status::Free)
+        .build()
+        .unwrap()
+}
+
+fn account() -> Account {
+    let delegation = Delegation {
+        delegate: bob(),
+        program_ids: BTreeSet::from([program_id()]),
+        max_value: U256::from(10),
+        expires_at_nonce: Some(U256::from(9)),
+        expires_at: None,
+    };
+    AccountBuilder::default()
+        .account_type(AccountType::User)
+        .program_namespace(None)
+        .owner_address(alice())
+        .programs(BTreeMap::from([(program_id(), token())]))
+        .nonce(U256::from(3))
+        .program_account_data(ArbitraryData::new())
+        .program_account_metadata(Metadata::new())
+        .program_account_linked_programs(BTreeSet::new())
+        .delegations(BTreeMap::from([(bob(), delegation)]))
+        .account_metadata(AccountMetadata {
+            display_name: Some("alice".to_string()),
+            avatar_uri: None,
+            contact_program_ids: BTreeSet::from([program_id()]),
+        })
+        .try_build()
+        .unwrap()
+}
+
+fn transaction() -> Transaction {
+    TransactionBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(3)))
+        .from(alice().inner())
+        .to(bob().inner())
+        .program_id(program_id().inner())
+        .op(String::new())
+        .inputs(String::new())
+        .value(U256::from(5))
+        .nonce(U256::from(4))
+        .max_fee(Some(U256::from(2)))
+        .v(1)
+        .r([0x11; 32])
+        .s([0x22; 32])
+        .build()
+        .unwrap()
+}
+
+fn attestation() -> ExecutionAttestation {
+    ExecutionAttestation {
+        transaction_hash: format!("0x{}", "ab".repeat(32)),
+        input_accounts: vec![AccountDigest::new(alice(), None)],
+        token_deltas: vec![TokenDelta {
+            address: bob(),
+            program_id: program_id(),
+            before: U256::from(0),
+            after: U256::from(5),
+        }],
+        trace_hash: "0x00".to_string(),
+        signer: alice(),
+        signature: RecoverableSignatureBuilder::default()
+            .r([0x33; 32])
+            .s([0x44; 32])
+            .v(0)
+            .build()
+            .unwrap(),
+    }
+}
+
+fn golden(fixture: &str) -> Value {
+    serde_json::from_str(fixture).expect("fixture is not JSON")
+}
+
+/// That `value` is written as `fixture` holds, and read back from it.
+fn pinned<T>(value: &T, fixture: &str)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    assert_eq!(serde_json::to_value(value).unwrap(), golden(fixture));
+    assert_eq!(
+        &serde_json::from_value::<T>(golden(fixture)).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn every_response_type_is_written_as_its_golden_file() {
+    pinned(
+        &AccountState::new(alice(), Some(&account())),
+        include_str!("fixtures/json/account.json"),
+    );
+    pinned(&token(), include_str!("fixtures/json/token.json"));
+    pinned(
+        &transaction(),
+        include_str!("fixtures/json/transaction.json"),
+    );
+    pinned(
+        &attestation(),
+        include_str!("fixtures/json/attestation.json"),
+    );
+    pinned(
+        &InclusionProof {
+            batch_id: 7,
+            transaction_hash: H256::repeat_byte(0x66),
+            proof: MerkleProof {
+                index: 1,
+                leaves: 2,
+                siblings: vec![H256::repeat_byte(0x77)],
+            },
+            accounts: vec![AccountProof {
+                address: alice(),
+                account_hash: H256::repeat_byte(0x88),
+                proof: MerkleProof {
+                    index: 0,
+                    leaves: 1,
+                    siblings: Vec::new(),
+                },
+            }],
+        },
+        include_str!("fixtures/json/inclusion_proof.json"),
+    );
+
+    let receipt = BatchReceipt {
+        header: BatchHeader {
+            batch_id: 7,
+            state_root: H256::repeat_byte(0x11),
+            transactions_root: H256::repeat_byte(0x22),
+            events_root: H256::repeat_byte(0x33),
+            blob_commitment: H256::repeat_byte(0x44),
+            blob_index: 2,
+            withdrawals: vec![Withdrawal {
+                token: Address::new([3; 20]),
+                recipient: alice(),
+                amount: U256::from(5),
+            }],
+        },
+        status: BatchStatus::Submitted {
+            transaction_hash: H256::repeat_byte(0x55),
+            attempts: 1,
+        },
+    };
+    assert_eq!(
+        serde_json::to_value(&receipt).unwrap(),
+        golden(include_str!("fixtures/json/batch_receipt.json"))
+    );
+}
+
+#[test]
+fn bincode_keeps_the_bytes_rather_than_their_hex() {
+    for bytes in [
+        bincode::serialize(&token()).unwrap(),
+        bincode::serialize(&transaction()).unwrap(),
+        bincode::serialize(&attestation().token_deltas).unwrap(),
+    ] {
+        assert!(!bytes.windows(2).any(|pair| pair == b"0x"));
+    }
+    assert_eq!(
+        bincode::serialize(&U256::from(1_000)).unwrap().len(),
+        8 + 32
+    );
+    assert_eq!(
+        bincode::deserialize::<Token>(&bincode::serialize(&token()).unwrap()).unwrap(),
+        token()
+    );
+}
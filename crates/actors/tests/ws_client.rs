@@ -0,0 +1,304 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for `WsLasrClient`, streaming what the node notifies
+//! through a proxy that cuts the connection as a dropped network would.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_rpc::{LasrRpcClient, StreamEvent, StreamResult, WsLasrClient};
+use lasr_types::{
+    AccountState, Address, PayloadBuilder, RecoverableSignature, Transaction, TransactionType, U256,
+};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::Value;
+use serial_test::serial;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+/// Forwards connections to the node's RPC server, until cut.
+struct Proxy {
+    url: String,
+    cut: Arc<AtomicBool>,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    accept: JoinHandle<()>,
+}
+
+impl Proxy {
+    async fn start(harness: &TestHarness) -> Self {
+        let upstream = harness.rpc_url().replacen("http://", "", 1);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let cut = Arc::new(AtomicBool::new(false));
+        let connections: Arc<Mutex<Vec<JoinHandle<()>>>> = Arc::default();
+        let accept = tokio::spawn({
+            let (cut, connections) = (cut.clone(), connections.clone());
+            async move {
+                while let Ok((mut inbound, _)) = listener.accept().await {
+                    if cut.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    let upstream = upstream.clone();
+                    connections.lock().unwrap().push(tokio::spawn(async move {
+                        if let Ok(mut outbound) = TcpStream::connect(upstream).await {
+                            tokio::io::copy_bidirectional(&mut inbound, &mut outbound)
+                                .await
+                                .ok();
+                        }
+                    }));
+                }
+            }
+        });
+        Self {
+            url,
+            cut,
+            connections,
+            accept,
+        }
+    }
+
+    /// Drops every connection through the proxy, turning new ones away
+    /// until it is restored.
+    fn cut(&self) {
+        self.cut.store(true, Ordering::SeqCst);
+        for connection in self.connections.lock().unwrap().drain(..) {
+            connection.abort();
+        }
+    }
+
+    fn restore(&self) {
+        self.cut.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.cut();
+        self.accept.abort();
+    }
+}
+
+async fn client(proxy: &Proxy, buffer: usize) -> WsLasrClient {
+    WsLasrClient::builder()
+        .with_timeout(Duration::from_secs(5))
+        .with_reconnect_after(Duration::from_millis(50))
+        .with_buffer(buffer)
+        .build(proxy.url.clone())
+        .await
+        .expect("failed to connect through the proxy")
+}
+
+async fn next<T>(stream: &mut (impl Stream<Item = StreamResult<T>> + Unpin)) -> StreamEvent<T> {
+    tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .expect("nothing was streamed")
+        .expect("the stream ended")
+        .expect("the stream failed")
+}
+
+fn verse(account: &AccountState) -> U256 {
+    account
+        .programs
+        .get(&VERSE_ADDR)
+        .map(|token| token.balance)
+        .unwrap_or_default()
+}
+
+/// The next item, skipping the notifications of accounts written on the
+/// way to it.
+async fn account_with(
+    stream: &mut (impl Stream<Item = StreamResult<AccountState>> + Unpin),
+    balance: u64,
+) -> AccountState {
+    loop {
+        if let StreamEvent::Item(account) = next(stream).await {
+            if verse(&account) == U256::from(balance) {
+                return account;
+            }
+        }
+    }
+}
+
+fn key() -> (SecretKey, Address) {
+    let secret_key = SecretKey::from_slice(&[29; 32]).unwrap();
+    let address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    (secret_key, address)
+}
+
+fn send(secret_key: &SecretKey, from: Address, value: u64, nonce: U256) -> Transaction {
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(nonce))
+        .from(from.inner())
+        .to(receiver().inner())
+        .program_id(VERSE_ADDR.inner())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(value))
+        .nonce(nonce)
+        .build()
+        .unwrap();
+    let signature = RecoverableSignature::sign(&payload.digest(), secret_key);
+    (payload, signature).into()
+}
+
+/// Funds `address` from the node's faucet over `client`, answering the
+/// nonce of its first send.
+async fn fund(harness: &TestHarness, client: &impl LasrRpcClient, address: Address) -> U256 {
+    let funds = harness.config().devnet.faucet_max_amount;
+    client
+        .faucet(address.to_full_string(), funds.to_string(), None)
+        .await
+        .expect("the faucet turned the address away");
+    harness
+        .wait_for_account(address, |account| account.balance(&VERSE_ADDR) >= funds)
+        .await
+        .expect("the address was not funded");
+    serde_json::from_str(&client.get_nonce(address.to_full_string()).await.unwrap()).unwrap()
+}
+
+fn receipt_of(event: StreamEvent<Value>) -> Option<u64> {
+    match event {
+        StreamEvent::Item(receipt) => receipt["header"]["batchId"].as_u64(),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn account_changes_missed_while_disconnected_are_read_back() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let proxy = Proxy::start(&harness).await;
+    let client = client(&proxy, 16).await;
+    let mut changes = Box::pin(client.account_changes(receiver()));
+
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    let account = account_with(&mut changes, 5).await;
+    assert!(account.found);
+
+    // Sent while the connection is down, so only read back.
+    proxy.cut();
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(10)
+        })
+        .await
+        .unwrap();
+    proxy.restore();
+    while !matches!(next(&mut changes).await, StreamEvent::Resumed) {}
+    match next(&mut changes).await {
+        StreamEvent::Item(account) => assert_eq!(verse(&account), U256::from(10)),
+        other => panic!("expected the account read back, got {other:?}"),
+    }
+
+    // Then streamed again as it changes.
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    account_with(&mut changes, 15).await;
+
+    // Requests are made over the connection made again.
+    let state: AccountState = serde_json::from_str(
+        &LasrRpcClient::get_account(&client, receiver().to_full_string())
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(verse(&state), U256::from(15));
+}
+
+#[tokio::test]
+#[serial]
+async fn batches_and_transaction_status_are_streamed_across_a_disconnect() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let proxy = Proxy::start(&harness).await;
+    let client = client(&proxy, 64).await;
+    let (secret_key, address) = key();
+    let nonce = fund(&harness, &client, address).await;
+    let mut batches = Box::pin(client.new_batches());
+
+    let transaction = send(&secret_key, address, 5, nonce);
+    client.send(transaction.clone()).await.unwrap();
+    let mut status = Box::pin(client.transaction_status(transaction.hash_string()));
+    match next(&mut status).await {
+        StreamEvent::Item(status) => assert_ne!(status["status"], "unknown", "{status}"),
+        other => panic!("expected where the transaction has got to, got {other:?}"),
+    }
+    let sealed = harness.seal_batch().await.unwrap().pop().unwrap().batch_id;
+    while receipt_of(next(&mut batches).await) != Some(sealed) {}
+    loop {
+        if let StreamEvent::Item(status) = next(&mut status).await {
+            if status["status"] == "included" || status["status"] == "settled" {
+                break;
+            }
+        }
+    }
+
+    // A batch finalized while the connection is down is read back.
+    proxy.cut();
+    let rpc = harness.rpc().unwrap();
+    rpc.send(send(&secret_key, address, 5, nonce + U256::from(1)))
+        .await
+        .unwrap();
+    let missed = harness.seal_batch().await.unwrap().pop().unwrap().batch_id;
+    assert!(missed > sealed);
+    proxy.restore();
+    while !matches!(next(&mut batches).await, StreamEvent::Resumed) {}
+    let mut read_back = Vec::new();
+    while !read_back.contains(&missed) {
+        read_back.extend(receipt_of(next(&mut batches).await));
+    }
+    assert!(read_back.iter().all(|batch_id| *batch_id >= sealed));
+}
+
+#[tokio::test]
+#[serial]
+async fn a_consumer_that_falls_behind_is_told_how_much_it_missed() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let mut wallet = harness.wallet().await.expect("failed to fund wallet");
+    let proxy = Proxy::start(&harness).await;
+    let client = client(&proxy, 1).await;
+    let mut changes = Box::pin(client.account_changes(receiver()));
+
+    // Nothing is taken from the stream while three sends are applied.
+    for sent in 1..=3u64 {
+        wallet
+            .send(&receiver(), &VERSE_ADDR, U256::from(5))
+            .await
+            .expect("failed to send");
+        harness
+            .wait_for_account(receiver(), |account| {
+                account.balance(&VERSE_ADDR) == U256::from(5 * sent)
+            })
+            .await
+            .unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(matches!(next(&mut changes).await, StreamEvent::Item(_)));
+
+    // What did not fit is counted, and told of before the next change.
+    wallet
+        .send(&receiver(), &VERSE_ADDR, U256::from(5))
+        .await
+        .expect("failed to send");
+    match next(&mut changes).await {
+        StreamEvent::Lagged { missed } => assert!(missed >= 1, "{missed}"),
+        other => panic!("expected the changes missed to be counted, got {other:?}"),
+    }
+    account_with(&mut changes, 20).await;
+}
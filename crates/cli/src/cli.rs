@@ -8,26 +8,23 @@ use clap::{
     value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command, Parser, Subcommand, ValueEnum,
 };
 use ethereum_types::Address as EthereumAddress;
-use hex::ToHex;
-use jsonrpsee::{
-    core::client::ClientT,
-    http_client::{HttpClient, HttpClientBuilder},
-};
+use jsonrpsee::core::{client::ClientT, params::ArrayParams, JsonValue};
 use lasr_compute::{
     LasrContentType, LasrObject, LasrObjectBuilder, LasrObjectCid, LasrObjectPayloadBuilder,
     LasrPackage, LasrPackageBuilder, LasrPackagePayloadBuilder, LasrPackageType, SignableObject,
 };
-use lasr_rpc::LasrRpcClient;
+use lasr_rpc::{BearerAuthLayer, ClientError, HttpLasrClient, LasrRpcClient, WithLayer};
 use lasr_types::{
-    Account, AccountType, Address, BurnInstruction, CreateInstruction, HexOr20Bytes, HexOr32Bytes,
-    Instruction, Namespace, Outputs, PayloadBuilder, Transaction, TransferInstruction,
-    UpdateInstruction, U256,
+    Account, AccountState, Address, BurnInstruction, CreateInstruction, Delegation, HexOr20Bytes,
+    HexOr32Bytes, Instruction, Namespace, Outputs, PayloadBuilder, Transaction,
+    TransferInstruction, UpdateInstruction, U256,
 };
 use lasr_wallet::{Wallet, WalletBuilder, WalletInfo};
 use secp256k1::PublicKey;
 use secp256k1::{rand::rngs::OsRng, Keypair, Secp256k1, SecretKey};
+use serde::Deserialize;
 use serde_json::json;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::OpenOptions;
 use std::io::Read;
 use std::io::Write;
@@ -37,6 +34,19 @@ use std::{path::Path, str::FromStr};
 use walkdir::WalkDir;
 use web3_pkg::web3_store::Web3Store;
 
+mod outcome;
+use outcome::*;
+
+/// The node the CLI talks to when neither `--rpc-url`, `LASR_RPC_URL` nor
+/// the config file names one.
+const DEFAULT_RPC_URL: &str = "http://127.0.0.1:9292";
+
+/// What the config file at `--config` may set.
+#[derive(Debug, Default, Deserialize)]
+struct CliConfig {
+    rpc_url: Option<String>,
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 enum Unit {
     Echo,
@@ -143,7 +153,7 @@ fn handle_new_wallet_command(children: &ArgMatches) -> Result<(), Box<dyn std::e
     let seed = children.get_one::<u128>("seed");
     let passphrase = children.get_one::<String>("passphrase");
     let size = children.get_one::<usize>("mnemonic-size");
-    let wallet_info = Wallet::<HttpClient>::get_info(seed, passphrase, size)
+    let wallet_info = Wallet::<HttpLasrClient>::get_info(seed, passphrase, size)
         .expect("Unable to acquire WalletInfo");
 
     if let Some(flag) = children.get_one::<bool>("keypair-json") {
@@ -154,52 +164,49 @@ fn handle_new_wallet_command(children: &ArgMatches) -> Result<(), Box<dyn std::e
 
     let save = children.get_one::<bool>("save");
     if let Some(true) = save {
-        let path = std::path::Path::new(
-            children
-                .get_one::<String>("path")
-                .expect("required or default"),
-        );
-        let dir_path = path.parent().expect("dir has no parent");
-        if !dir_path.exists() {
-            std::fs::create_dir_all(dir_path).expect("unable to create directory for keyfile");
-        }
-        let overwrite = children
-            .get_one::<bool>("overwrite")
-            .expect("required or default");
-        let mut file_buffer = Vec::new();
-        let mut existing = if path.exists() {
-            std::fs::File::open(path)
-                .expect("file cant be opened")
-                .read_to_end(&mut file_buffer)
-                .expect("file should exist");
-            if !file_buffer.is_empty() {
-                serde_json::from_slice::<Vec<WalletInfo>>(&file_buffer)
-                    .expect("keypair file has been corrupted")
-            } else {
-                Vec::new()
-            }
+        save_wallet_info(children, wallet_info)?;
+    }
+
+    Ok(())
+}
+
+/// Adds `wallet_info` to the keypair file at `--path`, after the wallets it
+/// holds, answering its index there.
+fn save_wallet_info(
+    children: &ArgMatches,
+    wallet_info: WalletInfo,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let path = std::path::Path::new(
+        children
+            .get_one::<String>("path")
+            .expect("required or default"),
+    );
+    if let Some(dir_path) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir_path)?;
+    }
+    let mut file_buffer = Vec::new();
+    let mut existing = if path.exists() {
+        std::fs::File::open(path)?.read_to_end(&mut file_buffer)?;
+        if !file_buffer.is_empty() {
+            serde_json::from_slice::<Vec<WalletInfo>>(&file_buffer)
+                .map_err(|e| format!("keypair file has been corrupted: {e}"))?
         } else {
             Vec::new()
-        };
+        }
+    } else {
+        Vec::new()
+    };
 
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .read(true)
-            .create(true)
-            .truncate(true)
-            .open(path)
-            .expect("unable to open file to write WalletInfo");
-
-        existing.push(wallet_info);
-        file.write_all(
-            serde_json::to_string_pretty(&existing)
-                .expect("unable to serialize wallet info")
-                .as_bytes(),
-        )
-        .expect("unable to write wallet info to file");
-    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .read(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
 
-    Ok(())
+    existing.push(wallet_info);
+    file.write_all(serde_json::to_string_pretty(&existing)?.as_bytes())?;
+    Ok(existing.len() - 1)
 }
 
 fn from_mnemonic_command() -> Command {
@@ -544,7 +551,6 @@ fn content_type_arg() -> Arg {
 
 fn verbose_arg() -> Arg {
     Arg::new("verbose")
-        .short('v')
         .long("verbose")
         .aliases(["vbs", "verb", "verbosity", "print"])
         .value_parser(value_parser!(bool))
@@ -978,10 +984,11 @@ fn register_program_command() -> Command {
         .arg(from_secret_key_arg())
         .arg(secret_key_arg())
         .arg(cid_arg())
+        .arg(output_json_arg())
         .arg(verbose_arg())
 }
 
-async fn handle_register_program(children: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+async fn handle_register_program(children: &ArgMatches) -> Result<(), CliError> {
     let verbose = *children
         .get_one::<bool>("verbose")
         .expect("required or default");
@@ -993,9 +1000,6 @@ async fn handle_register_program(children: &ArgMatches) -> Result<(), Box<dyn st
     if verbose {
         println!("Wallet address: {:?}", address);
     }
-    if verbose {
-        dbg!("Getting account");
-    }
     let cid = children.get_one::<String>("content-id").expect("required");
     let inputs = json!({"contentId": cid});
     if verbose {
@@ -1007,29 +1011,16 @@ async fn handle_register_program(children: &ArgMatches) -> Result<(), Box<dyn st
     let program_address = wallet
         .register_program(&inputs.to_string())
         .await
-        .map_err(|e| {
-            Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                e.to_string(),
-            )) as Box<dyn std::error::Error>
-        })?;
+        .map_err(CliError::from_wallet)?;
     if verbose {
         dbg!("Program registered");
     }
-    if verbose {
-        dbg!("Getting account again");
-    }
 
-    println!(
-        "{}",
-        serde_json::to_string_pretty(&serde_json::json!({"program_address": program_address}))
-            .unwrap()
+    print(
+        json_output(children),
+        &json!({"program_address": program_address}),
+        |registered| serde_json::to_string_pretty(registered).unwrap_or_default(),
     );
-
-    wallet.get_account(&address).await;
-    if verbose {
-        dbg!("Account obtained again");
-    }
     Ok(())
 }
 
@@ -1049,20 +1040,15 @@ fn call_command() -> Command {
         .arg(inputs_arg())
         .arg(value_arg().required(false))
         .arg(unit_arg().required(false))
+        .arg(output_json_arg())
         .arg(verbose_arg())
 }
 
-async fn handle_call_command(children: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
-    let values = children.ids();
+async fn handle_call_command(children: &ArgMatches) -> Result<(), CliError> {
     let mut wallet = get_wallet(children).await?;
-    let address = wallet.address();
     let verbose = children
         .get_one::<bool>("verbose")
         .expect("required or default");
-    if *verbose {
-        dbg!("attempting to get account");
-    }
-    wallet.get_account(&address).await;
 
     if *verbose {
         dbg!("parsing cli args");
@@ -1084,8 +1070,12 @@ async fn handle_call_command(children: &ArgMatches) -> Result<(), Box<dyn std::e
     let tx_hash_string = wallet
         .call(pid, to, amount, op, inputs)
         .await
-        .map_err(|e| e as Box<dyn std::error::Error>)?;
-    println!("{}", tx_hash_string);
+        .map_err(CliError::from_wallet)?;
+    print(
+        json_output(children),
+        &json!({ "transactionHash": tx_hash_string }),
+        |_| tx_hash_string.clone(),
+    );
     Ok(())
 }
 
@@ -1103,18 +1093,13 @@ fn send_command() -> Command {
         .arg(program_id_arg())
         .arg(value_arg())
         .arg(unit_arg())
+        .arg(output_json_arg())
         .arg(verbose_arg())
 }
 
-async fn handle_send_command(children: &ArgMatches) -> Result<(), Box<dyn ::std::error::Error>> {
-    println!("received `wallet send` command");
-    let values = children.ids();
+async fn handle_send_command(children: &ArgMatches) -> Result<(), CliError> {
     let mut wallet = get_wallet(children).await?;
 
-    let address = wallet.address();
-
-    wallet.get_account(&address).await;
-
     let to = children.get_one::<Address>("to").expect("required");
     let pid = children
         .get_one::<Address>("content-namespace")
@@ -1124,14 +1109,16 @@ async fn handle_send_command(children: &ArgMatches) -> Result<(), Box<dyn ::std:
 
     let amount = value.0 * unit;
 
-    let token = wallet.send(to, pid, amount).await.map_err(|e| {
-        Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            e.to_string(),
-        )) as Box<dyn std::error::Error>
-    })?;
+    let token = wallet
+        .send(to, pid, amount)
+        .await
+        .map_err(CliError::from_wallet)?;
 
-    println!("{}", serde_json::to_string_pretty(&token)?);
+    print(
+        json_output(children),
+        &serde_json::to_value(&token).map_err(|e| CliError::User(e.to_string()))?,
+        |token| serde_json::to_string_pretty(token).unwrap_or_default(),
+    );
 
     Ok(())
 }
@@ -1150,19 +1137,397 @@ fn get_account_command() -> Command {
         .arg(verbose_arg())
 }
 
-async fn handle_get_account_command(
-    children: &ArgMatches,
-) -> Result<(), Box<dyn std::error::Error>> {
-    use lasr_rpc::LasrRpcClient;
+async fn handle_get_account_command(children: &ArgMatches) -> Result<(), CliError> {
     let address = children
         .get_one::<String>("address")
-        .expect("address is required");
-    let client: HttpClient = get_client().await?;
-    let account = client.get_account(address.clone()).await?;
+        .ok_or_else(|| CliError::User("--address is required".to_string()))?;
+    let client = get_client(children)?;
+    let account = LasrRpcClient::get_account(&client, address.clone()).await?;
     println!("{}", account);
     Ok(())
 }
 
+fn import_wallet_command() -> Command {
+    Command::new("import")
+        .about("derives a wallet from a mnemonic phrase, as `wallet new` does, and saves it to the keypair file")
+        .arg(
+            Arg::new("mnemonic")
+                .short('m')
+                .long("mnemonic")
+                .help("The mnemonic phrase of 12 or 24 words that `wallet new` gave")
+                .required(true),
+        )
+        .arg(passphrase_arg().required(false))
+        .arg(keyfile_path_arg())
+        .arg(output_json_arg())
+}
+
+fn handle_import_wallet_command(children: &ArgMatches) -> Result<(), CliError> {
+    let phrase = children.get_one::<String>("mnemonic").expect("required");
+    let passphrase = children
+        .get_one::<String>("passphrase")
+        .map(String::as_str)
+        .unwrap_or_default();
+    let mnemonic = Mnemonic::parse_in_normalized(Language::English, phrase)
+        .map_err(|e| CliError::User(format!("the mnemonic could not be read: {e}")))?;
+    let wallet_info = WalletInfo::from_mnemonic(mnemonic, passphrase);
+    let address = *wallet_info.address();
+    let index = save_wallet_info(children, wallet_info)?;
+    print(
+        json_output(children),
+        &json!({ "address": address.to_full_string(), "walletIndex": index }),
+        |_| format!("imported {address} as wallet {index}"),
+    );
+    Ok(())
+}
+
+fn balance_command() -> Command {
+    Command::new("balance")
+        .about("prints the balances held by the wallet's account, or by --address")
+        .arg(
+            address_arg()
+                .value_parser(value_parser!(Address))
+                .help("The address to print the balances of, instead of the wallet's"),
+        )
+        .arg(from_file_arg())
+        .arg(keyfile_path_arg())
+        .arg(wallet_index_arg())
+        .arg(from_mnemonic_arg())
+        .arg(mnemonic_arg())
+        .arg(from_secret_key_arg())
+        .arg(secret_key_arg())
+        .arg(output_json_arg())
+        .arg(verbose_arg())
+}
+
+async fn handle_balance_command(children: &ArgMatches) -> Result<(), CliError> {
+    let address = match children.get_one::<Address>("address") {
+        Some(address) => *address,
+        None => get_keypair(children)?.1.into(),
+    };
+    let account = get_client(children)?.get_account(address).await?;
+    let balances: BTreeMap<String, String> = account
+        .programs
+        .iter()
+        .map(|(program_id, token)| (program_id.to_full_string(), token.balance.to_string()))
+        .collect();
+    print(
+        json_output(children),
+        &json!({ "address": address.to_full_string(), "balances": balances }),
+        |_| {
+            let mut lines = vec![format!("{address}")];
+            if balances.is_empty() {
+                lines.push("holds nothing".to_string());
+            }
+            lines.extend(
+                balances
+                    .iter()
+                    .map(|(program_id, balance)| format!("{program_id}  {balance}")),
+            );
+            lines.join("\n")
+        },
+    );
+    Ok(())
+}
+
+fn spender_arg() -> Arg {
+    Arg::new("spender")
+        .long("spender")
+        .aliases(["delegate"])
+        .help("The address let send the wallet's tokens, 40 hexadecimal characters")
+        .value_name("Address")
+        .value_parser(value_parser!(Address))
+        .required(true)
+}
+
+fn expires_at_arg() -> Arg {
+    Arg::new("expires-at")
+        .long("expires-at")
+        .help("The unix time, in seconds, from which the spender may send nothing")
+        .value_parser(value_parser!(u64))
+        .required(false)
+}
+
+fn approve_command() -> Command {
+    Command::new("approve")
+        .about("lets a spender send up to a value of a token from the wallet's account in each transaction it signs, until it expires or is revoked")
+        .arg(from_file_arg())
+        .arg(keyfile_path_arg())
+        .arg(wallet_index_arg())
+        .arg(from_mnemonic_arg())
+        .arg(mnemonic_arg())
+        .arg(from_secret_key_arg())
+        .arg(secret_key_arg())
+        .arg(spender_arg())
+        .arg(program_id_arg())
+        .arg(value_arg())
+        .arg(unit_arg())
+        .arg(expires_at_arg())
+        .arg(output_json_arg())
+        .arg(verbose_arg())
+}
+
+async fn handle_approve_command(children: &ArgMatches) -> Result<(), CliError> {
+    let mut wallet = get_wallet(children).await?;
+    let spender = *children.get_one::<Address>("spender").expect("required");
+    let pid = *children
+        .get_one::<Address>("content-namespace")
+        .expect("required");
+    let value = children.get_one::<U256Wrapper>("value").expect("required");
+    let unit = children.get_one::<Unit>("unit").expect("required");
+    let delegation = Delegation {
+        delegate: spender,
+        program_ids: BTreeSet::from([pid]),
+        max_value: value.0 * unit,
+        expires_at_nonce: None,
+        expires_at: children.get_one::<u64>("expires-at").copied(),
+    };
+    let max_value = delegation.max_value;
+    wallet
+        .delegate(delegation)
+        .await
+        .map_err(CliError::from_wallet)?;
+    print(
+        json_output(children),
+        &json!({
+            "spender": spender.to_full_string(),
+            "programId": pid.to_full_string(),
+            "maxValue": max_value,
+        }),
+        |_| format!("{spender} may send up to {max_value} of {pid} in each transaction"),
+    );
+    Ok(())
+}
+
+fn output_json_arg() -> Arg {
+    Arg::new("output-json")
+        .long("json")
+        .help("Print what the command answers, and why it failed, as JSON")
+        .action(ArgAction::SetTrue)
+}
+
+fn json_output(children: &ArgMatches) -> bool {
+    children
+        .try_get_one::<bool>("output-json")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+fn rpc_url_arg() -> Arg {
+    Arg::new("rpc-url")
+        .long("rpc-url")
+        .env("LASR_RPC_URL")
+        .global(true)
+        .help("The URL of the node's RPC server, read from the config file if not given, defaults to http://127.0.0.1:9292")
+}
+
+fn config_arg() -> Arg {
+    Arg::new("config")
+        .long("config")
+        .env("LASR_CLI_CONFIG")
+        .global(true)
+        .default_value(".lasr/config.toml")
+        .help("A TOML file setting rpc_url, read if it exists")
+}
+
+fn transaction_hash_arg() -> Arg {
+    Arg::new("transaction-hash")
+        .help("The hash of the transaction, as its sender was answered")
+        .required(true)
+}
+
+fn tx_command() -> Command {
+    Command::new("tx")
+        .about("reads where a transaction has got to")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("status")
+                .about("prints the status of a transaction, exiting with 4 if it failed")
+                .arg(transaction_hash_arg())
+                .arg(output_json_arg()),
+        )
+        .subcommand(
+            Command::new("receipt")
+                .about("prints the receipt of a transaction, exiting with 4 if it failed")
+                .arg(transaction_hash_arg())
+                .arg(output_json_arg()),
+        )
+}
+
+/// Why `status`, as `getTransactionStatus` answers it, says the transaction
+/// failed, if it does.
+fn failure(status: &JsonValue) -> Option<String> {
+    match status["status"].as_str()? {
+        "failed" => Some(
+            status["description"]
+                .as_str()
+                .unwrap_or("the transaction failed")
+                .to_string(),
+        ),
+        "expired" => Some("the transaction expired before it was applied".to_string()),
+        "replaced" => Some(format!(
+            "the transaction was replaced by {}",
+            status["replacedBy"].as_str().unwrap_or("another")
+        )),
+        _ => None,
+    }
+}
+
+async fn handle_tx_command(sub: &ArgMatches) -> Result<(), CliError> {
+    let (name, children) = sub.subcommand().expect("subcommand required");
+    let client = get_client(children)?;
+    let transaction_hash = children
+        .get_one::<String>("transaction-hash")
+        .expect("required");
+    let (answer, status) = match name {
+        "status" => {
+            let status = client.get_transaction_status(transaction_hash).await?;
+            (status.clone(), status)
+        }
+        _ => {
+            let receipt = client.get_receipt(transaction_hash).await?;
+            (receipt.clone(), receipt["status"].clone())
+        }
+    };
+    print(json_output(children), &answer, fields);
+    match failure(&status) {
+        Some(reason) => Err(CliError::Transaction(reason)),
+        None => Ok(()),
+    }
+}
+
+fn account_command() -> Command {
+    Command::new("account")
+        .about("reads accounts")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("get")
+                .about("prints the account at an address")
+                .arg(
+                    Arg::new("address")
+                        .help("The address of the account, 40 hexadecimal characters")
+                        .value_parser(value_parser!(Address))
+                        .required(true),
+                )
+                .arg(output_json_arg()),
+        )
+}
+
+async fn handle_account_command(sub: &ArgMatches) -> Result<(), CliError> {
+    let (_, children) = sub.subcommand().expect("subcommand required");
+    let address = *children.get_one::<Address>("address").expect("required");
+    let account = get_client(children)?.get_account(address).await?;
+    let found = account.found;
+    print(
+        json_output(children),
+        &serde_json::to_value(&account).map_err(|e| CliError::User(e.to_string()))?,
+        |_| {
+            if !found {
+                return format!("{address} has no account");
+            }
+            let mut lines = vec![
+                format!("address: {address}"),
+                format!("nonce: {}", account.nonce),
+            ];
+            lines.extend(account.programs.iter().map(|(program_id, token)| {
+                format!("{}  {}", program_id.to_full_string(), token.balance)
+            }));
+            lines.join("\n")
+        },
+    );
+    Ok(())
+}
+
+fn node_command() -> Command {
+    Command::new("node")
+        .about("reads how the node is doing")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("status")
+                .about("prints the node's status")
+                .arg(output_json_arg()),
+        )
+}
+
+async fn handle_node_command(sub: &ArgMatches) -> Result<(), CliError> {
+    let (_, children) = sub.subcommand().expect("subcommand required");
+    let client = get_client(children)?;
+    let status = read_answer(LasrRpcClient::node_status(&client).await?);
+    print(json_output(children), &status, fields);
+    Ok(())
+}
+
+fn admin_command() -> Command {
+    Command::new("admin")
+        .about("calls an admin method of the node, as `admin getProgramStats null 10`")
+        .arg(
+            Arg::new("method")
+                .help("The admin method, with or without the admin_ prefix")
+                .required(true),
+        )
+        .arg(
+            Arg::new("params")
+                .help("The method's parameters in order, each as JSON")
+                .num_args(0..)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("token")
+                .long("token")
+                .env("ADMIN_RPC_TOKEN")
+                .hide_env_values(true)
+                .help("The bearer token the node lets callers through to the admin methods with"),
+        )
+        .arg(output_json_arg())
+}
+
+async fn handle_admin_command(children: &ArgMatches) -> Result<(), CliError> {
+    let method = children.get_one::<String>("method").expect("required");
+    let method = if method.starts_with("admin_") {
+        method.clone()
+    } else {
+        format!("admin_{method}")
+    };
+    let mut params = ArrayParams::new();
+    for param in children.get_many::<String>("params").into_iter().flatten() {
+        // What is not JSON is taken for a string.
+        let param = serde_json::from_str::<JsonValue>(param)
+            .unwrap_or_else(|_| JsonValue::String(param.clone()));
+        params
+            .insert(param)
+            .map_err(|e| CliError::User(e.to_string()))?;
+    }
+    let client = get_client(children)?;
+    let answer: JsonValue = match children.get_one::<String>("token") {
+        Some(token) => {
+            client
+                .with_layer(BearerAuthLayer::new(token))
+                .request(&method, params)
+                .await?
+        }
+        None => client.request(&method, params).await?,
+    };
+    // The admin methods answer JSON written into a string.
+    let answer = match answer {
+        JsonValue::String(answer) => read_answer(answer),
+        answer => answer,
+    };
+    print(json_output(children), &answer, |answer| {
+        serde_json::to_string_pretty(answer).unwrap_or_default()
+    });
+    Ok(())
+}
+
+/// What a method answered, as JSON if it is, or else as the string it is.
+fn read_answer(answer: String) -> JsonValue {
+    serde_json::from_str(&answer).unwrap_or(JsonValue::String(answer))
+}
+
 fn wallet_command() -> Command {
     Command::new("wallet")
         .about("a wallet cli for interacting with the LASR network as a user")
@@ -1170,6 +1535,9 @@ fn wallet_command() -> Command {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .subcommand(new_wallet_command())
+        .subcommand(import_wallet_command())
+        .subcommand(balance_command())
+        .subcommand(approve_command())
         .subcommand(from_mnemonic_command())
         .subcommand(from_secret_key_command())
         .subcommand(send_command())
@@ -1299,27 +1667,59 @@ fn handle_instruction_command(children: &ArgMatches) -> Result<(), Box<dyn std::
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let matches = command!()
+fn cli() -> Command {
+    command!()
         .propagate_version(true)
         .subcommand_required(true)
         .arg_required_else_help(true)
         .about("A cli interacting with the LASR network")
+        .after_help("Exits with 2 when the arguments are wrong, 3 when the node cannot be reached or fails to answer, and 4 when a transaction is turned away or fails.")
+        .arg(rpc_url_arg())
+        .arg(config_arg())
         .subcommand(wallet_command())
+        .subcommand(tx_command())
+        .subcommand(account_command())
+        .subcommand(node_command())
+        .subcommand(admin_command())
         .subcommand(parse_outputs())
         .subcommand(parse_transaction())
         .subcommand(get_default_transaction())
         .subcommand(hex_or_bytes())
         .subcommand(instruction())
         .subcommand(publish_command())
-        .get_matches();
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = cli().get_matches();
+    if let Err(e) = run(&matches).await {
+        // Whether the command that failed was asked for JSON.
+        let mut json = false;
+        let mut sub = &matches;
+        while let Some((_, children)) = sub.subcommand() {
+            json |= json_output(children);
+            sub = children;
+        }
+        e.report(json);
+        std::process::exit(e.exit_code());
+    }
+}
 
+async fn run(matches: &ArgMatches) -> Result<(), CliError> {
     match matches.subcommand() {
         Some(("wallet", sub)) => match sub.subcommand() {
             Some(("new", children)) => {
                 handle_new_wallet_command(children)?;
             }
+            Some(("import", children)) => {
+                handle_import_wallet_command(children)?;
+            }
+            Some(("balance", children)) => {
+                handle_balance_command(children).await?;
+            }
+            Some(("approve", children)) => {
+                handle_approve_command(children).await?;
+            }
             Some(("mnemonic", children)) => {
                 println!("received `wallet mnemonic` command");
             }
@@ -1343,6 +1743,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             _ => {}
         },
+        Some(("tx", sub)) => {
+            handle_tx_command(sub).await?;
+        }
+        Some(("account", sub)) => {
+            handle_account_command(sub).await?;
+        }
+        Some(("node", sub)) => {
+            handle_node_command(sub).await?;
+        }
+        Some(("admin", children)) => {
+            handle_admin_command(children).await?;
+        }
         Some(("parse-outputs", children)) => {
             handle_parse_outputs_command(children)?;
         }
@@ -1371,52 +1783,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn get_keypair(
-    children: &ArgMatches,
-) -> Result<(SecretKey, PublicKey), Box<dyn std::error::Error>> {
-    let values = children.ids();
-
-    let from_file = children.get_flag("from-file");
+/// The keypair `--from-mnemonic` or `--from-secret-key` give, or else the
+/// one at `--wallet-index` in the keypair file at `--path`.
+fn get_keypair(children: &ArgMatches) -> Result<(SecretKey, PublicKey), CliError> {
     let from_mnemonic = children.get_flag("from-mnemonic");
-    let from_secret_key = children.get_flag("from-secret-key");
+    let from_secret_key =
+        children.get_flag("from-secret-key") || children.contains_id("secret-key");
 
-    if from_file {
-        let keypair_file = children
-            .get_one::<String>("path")
-            .expect("required if from-file flag");
-        let wallet_index = children
-            .get_one::<usize>("wallet-index")
-            .expect("required or default");
-        let mut file = std::fs::File::open(keypair_file)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents);
-        let keypair_file: Vec<WalletInfo> = serde_json::from_str(&contents)?;
-        let wallet_info = &keypair_file[*wallet_index];
-        let master = wallet_info.secret_key();
-        let pubkey = wallet_info.public_key();
-        return Ok((master, pubkey));
-    } else if from_mnemonic {
+    if from_mnemonic {
         let phrase = children
             .get_one::<String>("mnemonic")
             .expect("required if from-mnemonic flag");
-        let mnemonic = Mnemonic::parse_in_normalized(Language::English, phrase)?;
-        let seed = mnemonic.to_seed("");
-        let secp = Secp256k1::new();
-        let master = SecretKey::from_slice(&seed[0..32])?;
-        let pubkey = master.public_key(&secp);
-        return Ok((master, pubkey));
-    } else {
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, phrase)
+            .map_err(|e| CliError::User(format!("the mnemonic could not be read: {e}")))?;
+        // Derived as `wallet new` and `wallet import` derive it.
+        let wallet_info = WalletInfo::from_mnemonic(mnemonic, "");
+        Ok((wallet_info.secret_key(), wallet_info.public_key()))
+    } else if from_secret_key {
         let sk = children
             .get_one::<String>("secret-key")
-            .expect("required if from-secret-key flag");
+            .ok_or_else(|| CliError::User("--secret-key is required".to_string()))?;
         let secp = Secp256k1::new();
-        let master = SecretKey::from_str(sk)?;
+        let master = SecretKey::from_str(sk)
+            .map_err(|e| CliError::User(format!("the secret key could not be read: {e}")))?;
         let pubkey = master.public_key(&secp);
         let address: Address = pubkey.into();
-        let eaddr: EthereumAddress = address.into();
-        let verbose = *children
-            .get_one::<bool>("verbose")
-            .expect("required or default");
+        let verbose = children
+            .try_get_one::<bool>("verbose")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
         if verbose {
             println!("************************************************************");
             println!("************************************************************\n");
@@ -1426,50 +1823,83 @@ fn get_keypair(
             println!("************************************************************\n");
             println!("************************************************************");
             println!("******************       ADDRESS         *******************");
-            println!("*******  0x{}  *******", &address.encode_hex::<String>());
+            println!("*******  {}  *******", &address);
             println!("************************************************************\n");
         }
-        return Ok((master, pubkey));
+        Ok((master, pubkey))
+    } else {
+        let keypair_file = children
+            .get_one::<String>("path")
+            .expect("required or default");
+        let wallet_index = *children
+            .get_one::<usize>("wallet-index")
+            .expect("required or default");
+        let contents = std::fs::read_to_string(keypair_file).map_err(|e| {
+            CliError::User(format!(
+                "the keypair file {keypair_file} could not be read: {e}"
+            ))
+        })?;
+        let keypair_file: Vec<WalletInfo> = serde_json::from_str(&contents)
+            .map_err(|e| CliError::User(format!("keypair file has been corrupted: {e}")))?;
+        let wallet_info = keypair_file.get(wallet_index).ok_or_else(|| {
+            CliError::User(format!(
+                "the keypair file holds {} wallets, so has none at index {wallet_index}",
+                keypair_file.len()
+            ))
+        })?;
+        Ok((wallet_info.secret_key(), wallet_info.public_key()))
     }
-
-    Err(Box::new(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "unable to derive secret and public key",
-    )))
 }
 
-async fn get_wallet(
-    children: &ArgMatches,
-) -> Result<Wallet<HttpClient>, Box<dyn std::error::Error>> {
+async fn get_wallet(children: &ArgMatches) -> Result<Wallet<HttpLasrClient>, CliError> {
     let (secret_key, public_key) = get_keypair(children)?;
     let address: Address = public_key.into();
-    let lasr_rpc_url =
-        std::env::var("LASR_RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:9292".to_string());
-    let client = HttpClientBuilder::default().build(lasr_rpc_url)?;
-    let res = &client.get_account(format!("{:x}", address)).await;
-    let account = if let Ok(account_str) = res {
-        println!("{}", account_str);
-        let account: Account = serde_json::from_str(account_str)?;
-        account
+    let client = get_client(children)?;
+    let state = client.get_account(address).await?;
+    let account = if state.found {
+        Account::try_from(state)
+            .map_err(|e| CliError::Rpc(ClientError::InvalidResponse(e.to_string())))?
     } else {
-        Account::new(AccountType::User, None, address, None)
+        Account::new(address)
     };
 
     WalletBuilder::default()
         .sk(secret_key)
-        .client(client.clone())
+        .client(client)
         .address(address)
         .builder(PayloadBuilder::default())
         .account(account)
         .build()
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+        .map_err(|e| CliError::User(e.to_string()))
 }
 
-async fn get_client() -> Result<HttpClient, Box<dyn std::error::Error>> {
-    let lasr_rpc_url =
-        std::env::var("LASR_RPC_URL").unwrap_or_else(|_| "http://127.0.0.1:9292".to_string());
-    let client = HttpClientBuilder::default().build(lasr_rpc_url)?;
-    Ok(client)
+/// The URL `--rpc-url` or `LASR_RPC_URL` give, or else the config file's
+/// `rpc_url`, or else the default.
+fn rpc_url(children: &ArgMatches) -> Result<String, CliError> {
+    if let Some(url) = children.get_one::<String>("rpc-url") {
+        return Ok(url.clone());
+    }
+    let config = match children.get_one::<String>("config") {
+        Some(path) if Path::new(path).exists() => {
+            let contents = std::fs::read_to_string(path).map_err(|e| {
+                CliError::User(format!("the config file {path} could not be read: {e}"))
+            })?;
+            toml::from_str::<CliConfig>(&contents).map_err(|e| {
+                CliError::User(format!("the config file {path} could not be read: {e}"))
+            })?
+        }
+        _ => CliConfig::default(),
+    };
+    Ok(config
+        .rpc_url
+        .unwrap_or_else(|| DEFAULT_RPC_URL.to_string()))
+}
+
+fn get_client(children: &ArgMatches) -> Result<HttpLasrClient, CliError> {
+    HttpLasrClient::new(rpc_url(children)?).map_err(|e| match e {
+        ClientError::Config(e) => CliError::User(format!("the RPC URL could not be used: {e}")),
+        e => CliError::Rpc(e),
+    })
 }
 
 fn pretty_print_keypair_info(wallet_info: &WalletInfo) {
@@ -1494,9 +1924,6 @@ fn pretty_print_wallet_info(wallet_info: &WalletInfo) {
     println!("{}\n", &wallet_info.public_key().to_string());
     println!("************************************************************");
     println!("******************       ADDRESS         *******************");
-    println!(
-        "*******  0x{}  *******",
-        &wallet_info.address().encode_hex::<String>()
-    );
+    println!("*******  {}  *******", &wallet_info.address());
     println!("************************************************************\n");
 }
@@ -0,0 +1,147 @@
+//! How a command ends: what it prints, and the code it exits with, so a
+//! script can tell a mistake of its own from the node failing it and from a
+//! transaction the node turned away.
+use jsonrpsee::core::{ClientError as RequestError, JsonValue};
+use lasr_rpc::ClientError;
+use lasr_types::{AccountError, AccountStateError, IntentError};
+use lasr_wallet::WalletError;
+use thiserror::Error;
+
+/// The arguments were wrong, or a key or file they name could not be read.
+/// Clap exits with it too when it cannot parse them.
+pub const EXIT_USER_ERROR: i32 = 2;
+/// The node could not be reached, or failed to answer.
+pub const EXIT_RPC_ERROR: i32 = 3;
+/// The transaction could not be made, was turned away, or failed.
+pub const EXIT_TRANSACTION_FAILED: i32 = 4;
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    User(String),
+
+    #[error("{0}")]
+    Rpc(#[from] ClientError),
+
+    #[error("{0}")]
+    Transaction(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::User(_) => EXIT_USER_ERROR,
+            CliError::Rpc(_) => EXIT_RPC_ERROR,
+            CliError::Transaction(_) => EXIT_TRANSACTION_FAILED,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            CliError::User(_) => "user",
+            CliError::Rpc(_) => "rpc",
+            CliError::Transaction(_) => "transaction",
+        }
+    }
+
+    /// Why a wallet failed to make a transaction. The node turning it away
+    /// with one of its codes is the transaction failing. The account not
+    /// holding enough for it, or not being found, or the transaction
+    /// missing what it needs, is the caller's mistake. An answer the node
+    /// gave that could not be read is the node failing.
+    pub fn from_wallet(e: WalletError) -> Self {
+        let e = match e.downcast::<RequestError>() {
+            Ok(e) => {
+                return match ClientError::from(*e) {
+                    ClientError::Rejected { message, .. } => CliError::Transaction(message),
+                    e => CliError::Rpc(e),
+                }
+            }
+            Err(e) => e,
+        };
+        let e = match e.downcast::<IntentError>() {
+            Ok(e) => {
+                return match *e {
+                    IntentError::Payload(message) => CliError::Transaction(message),
+                    e @ (IntentError::Incomplete { .. }
+                    | IntentError::Invalid { .. }
+                    | IntentError::WrongChain { .. }) => CliError::User(e.to_string()),
+                }
+            }
+            Err(e) => e,
+        };
+        let e = match e.downcast::<AccountError>() {
+            // What the account holds, or whether there is one, is checked
+            // before the transaction is signed.
+            Ok(e) => return CliError::User(e.to_string()),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<serde_json::Error>() {
+            Ok(e) => return CliError::Rpc(ClientError::InvalidResponse(e.to_string())),
+            Err(e) => e,
+        };
+        match e.downcast::<AccountStateError>() {
+            Ok(e) => CliError::Rpc(ClientError::InvalidResponse(e.to_string())),
+            Err(e) => CliError::Transaction(e.to_string()),
+        }
+    }
+
+    /// Writes the error to stderr, as JSON with `json`.
+    pub fn report(&self, json: bool) {
+        if json {
+            let code = match self {
+                CliError::Rpc(e) => e.code().map(|code| code.code()),
+                _ => None,
+            };
+            let error = serde_json::json!({
+                "error": { "kind": self.kind(), "message": self.to_string(), "code": code }
+            });
+            eprintln!("{error}");
+        } else {
+            eprintln!("error: {self}");
+        }
+    }
+}
+
+impl From<RequestError> for CliError {
+    fn from(e: RequestError) -> Self {
+        CliError::Rpc(e.into())
+    }
+}
+
+/// What the commands written before exit codes were told apart fail with,
+/// taken as the caller's mistake unless it came from the node.
+impl From<Box<dyn std::error::Error>> for CliError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        match e.downcast::<RequestError>() {
+            Ok(e) => (*e).into(),
+            Err(e) => CliError::User(e.to_string()),
+        }
+    }
+}
+
+/// Prints `value` as JSON with `json`, or as `human` writes it.
+pub fn print(json: bool, value: &JsonValue, human: impl FnOnce(&JsonValue) -> String) {
+    if json {
+        println!("{value}");
+    } else {
+        println!("{}", human(value));
+    }
+}
+
+/// The fields of `value` one to a line, for what has no better way of being
+/// read.
+pub fn fields(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Object(fields) => fields
+            .iter()
+            .map(|(name, value)| match value {
+                JsonValue::String(value) => format!("{name}: {value}"),
+                value => format!("{name}: {value}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        JsonValue::String(value) => value.clone(),
+        value => serde_json::to_string_pretty(value).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,316 @@
+#![cfg(test)]
+#![cfg(feature = "test_harness")]
+//! Test coverage for the `lasr_cli` binary, run against an in-process node
+//! as a user at a terminal, or a script, would run it.
+
+use std::path::Path;
+use std::process::Output;
+
+use assert_cmd::Command;
+use lasr_actors::{TestHarness, VERSE_ADDR};
+use lasr_rpc::{HttpLasrClient, LasrRpcClient};
+use lasr_types::{
+    Address, PayloadBuilder, RecoverableSignature, Transaction, TransactionType, U256,
+};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::Value;
+use serial_test::serial;
+
+const MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+/// Runs the CLI with `args`, against the node at `rpc_url` unless `args`
+/// name another.
+async fn cli(rpc_url: &str, args: &[&str]) -> Output {
+    let mut command = lasr_cli();
+    command
+        .env_remove("ADMIN_RPC_TOKEN")
+        .env("LASR_CLI_CONFIG", "no-such-config.toml")
+        .arg("--rpc-url")
+        .arg(rpc_url)
+        .args(args);
+    run(command).await
+}
+
+/// The CLI binary, with no RPC URL from the environment.
+fn lasr_cli() -> Command {
+    let mut command = Command::cargo_bin("lasr_cli").expect("the cli was not built");
+    command.env_remove("LASR_RPC_URL");
+    command
+}
+
+/// Runs `command` off the runtime, which the node it is run against is
+/// served from.
+async fn run(mut command: Command) -> Output {
+    tokio::task::spawn_blocking(move || command.output())
+        .await
+        .unwrap()
+        .expect("failed to run the cli")
+}
+
+fn json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+        panic!(
+            "the cli printed no JSON ({e}): {}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )
+    })
+}
+
+fn exit_code(output: &Output) -> i32 {
+    output.status.code().expect("the cli was killed")
+}
+
+/// Imports the wallet of [`MNEMONIC`] into the keypair file at `path`,
+/// funding it from the node's faucet.
+async fn imported_wallet(harness: &TestHarness, path: &Path) -> Address {
+    let path = path.to_str().unwrap();
+    let output = cli(
+        harness.rpc_url(),
+        &[
+            "wallet",
+            "import",
+            "--mnemonic",
+            MNEMONIC,
+            "--path",
+            path,
+            "--json",
+        ],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    let address: Address = json(&output)["address"].as_str().unwrap().parse().unwrap();
+
+    let client = HttpLasrClient::new(harness.rpc_url()).unwrap();
+    let funds = harness.config().devnet.faucet_max_amount;
+    LasrRpcClient::faucet(&client, address.to_full_string(), funds.to_string(), None)
+        .await
+        .expect("the faucet turned the address away");
+    harness
+        .wait_for_account(address, |account| account.balance(&VERSE_ADDR) >= funds)
+        .await
+        .expect("the address was not funded");
+    address
+}
+
+#[tokio::test]
+#[serial]
+async fn an_imported_wallet_sends_and_its_balances_are_read_back() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let dir = tempfile::tempdir().unwrap();
+    let keypair_file = dir.path().join("keypair.json");
+    let address = imported_wallet(&harness, &keypair_file).await;
+    let receiver = receiver().to_full_string();
+
+    let output = cli(
+        harness.rpc_url(),
+        &[
+            "wallet",
+            "send",
+            "--path",
+            keypair_file.to_str().unwrap(),
+            "--to",
+            &receiver,
+            "--content-namespace",
+            "1",
+            "--value",
+            "5",
+            "--json",
+        ],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    json(&output);
+    harness
+        .wait_for_account(receiver(), |account| {
+            account.balance(&VERSE_ADDR) == U256::from(5)
+        })
+        .await
+        .unwrap();
+
+    let output = cli(
+        harness.rpc_url(),
+        &["wallet", "balance", "--address", &receiver, "--json"],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    assert_eq!(
+        json(&output)["balances"][VERSE_ADDR.to_full_string()],
+        U256::from(5).to_string()
+    );
+
+    let output = cli(harness.rpc_url(), &["account", "get", &receiver, "--json"]).await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    assert_eq!(json(&output)["found"], true);
+
+    // Read as a person would read it.
+    let output = cli(
+        harness.rpc_url(),
+        &[
+            "wallet",
+            "balance",
+            "--path",
+            keypair_file.to_str().unwrap(),
+        ],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    let printed = String::from_utf8(output.stdout).unwrap();
+    assert!(printed.contains(&address.to_string()), "{printed}");
+    assert!(printed.contains(&VERSE_ADDR.to_full_string()), "{printed}");
+}
+
+#[tokio::test]
+#[serial]
+async fn where_a_transaction_has_got_to_is_printed() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let secret_key = SecretKey::from_slice(&[37; 32]).unwrap();
+    let address: Address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    let client = HttpLasrClient::new(harness.rpc_url()).unwrap();
+    let funds = harness.config().devnet.faucet_max_amount;
+    LasrRpcClient::faucet(&client, address.to_full_string(), funds.to_string(), None)
+        .await
+        .unwrap();
+    harness
+        .wait_for_account(address, |account| account.balance(&VERSE_ADDR) >= funds)
+        .await
+        .unwrap();
+    let nonce = client.get_nonce(address).await.unwrap();
+    let payload = PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(nonce))
+        .from(address.inner())
+        .to(receiver().inner())
+        .program_id(VERSE_ADDR.inner())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(5))
+        .nonce(nonce)
+        .build()
+        .unwrap();
+    let signature = RecoverableSignature::sign(&payload.digest(), &secret_key);
+    let transaction: Transaction = (payload, signature).into();
+    LasrRpcClient::send(&client, transaction.clone())
+        .await
+        .unwrap();
+    harness.seal_batch().await.unwrap();
+
+    let hash = transaction.hash_string();
+    let output = cli(harness.rpc_url(), &["tx", "status", &hash, "--json"]).await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    let status = json(&output);
+    assert!(
+        status["status"] == "included" || status["status"] == "settled",
+        "{status}"
+    );
+
+    let output = cli(harness.rpc_url(), &["tx", "receipt", &hash, "--json"]).await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    assert_ne!(json(&output)["status"]["status"], "unknown");
+
+    let output = cli(harness.rpc_url(), &["tx", "status", &hash]).await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("status: "));
+}
+
+#[tokio::test]
+#[serial]
+async fn exit_codes_tell_the_callers_mistakes_from_the_node_failing() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let dir = tempfile::tempdir().unwrap();
+    let keypair_file = dir.path().join("keypair.json");
+
+    // The caller's mistakes.
+    let output = cli(harness.rpc_url(), &["account", "get", "not-an-address"]).await;
+    assert_eq!(exit_code(&output), 2, "{output:?}");
+    let missing = dir.path().join("missing.json");
+    let output = cli(
+        harness.rpc_url(),
+        &[
+            "wallet",
+            "balance",
+            "--path",
+            missing.to_str().unwrap(),
+            "--json",
+        ],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 2, "{output:?}");
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["error"]["kind"], "user");
+
+    // A node that cannot be reached.
+    let output = cli("http://127.0.0.1:1", &["node", "status", "--json"]).await;
+    assert_eq!(exit_code(&output), 3, "{output:?}");
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["error"]["kind"], "rpc");
+
+    // A send for more than the account holds is turned away before it is
+    // signed.
+    imported_wallet(&harness, &keypair_file).await;
+    let funds = harness.config().devnet.faucet_max_amount + U256::from(1);
+    let output = cli(
+        harness.rpc_url(),
+        &[
+            "wallet",
+            "send",
+            "--path",
+            keypair_file.to_str().unwrap(),
+            "--to",
+            &receiver().to_full_string(),
+            "--content-namespace",
+            "1",
+            "--value",
+            &funds.to_string(),
+        ],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 2, "{output:?}");
+    assert!(harness.account(receiver()).await.is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn the_node_and_its_admin_methods_are_read_with_the_url_a_config_file_names() {
+    let harness = TestHarness::start().await.expect("failed to start node");
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, format!("rpc_url = \"{}\"\n", harness.rpc_url())).unwrap();
+    let config = config.to_str().unwrap();
+
+    // The flag is left to the config file.
+    let mut command = lasr_cli();
+    command.args(["--config", config, "node", "status", "--json"]);
+    let output = run(command).await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    assert!(json(&output).is_object());
+
+    // The admin methods are for those holding the token.
+    let output = cli(
+        harness.rpc_url(),
+        &["admin", "getProgramStats", "null", "10"],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 3, "{output:?}");
+    let output = cli(
+        harness.rpc_url(),
+        &[
+            "admin",
+            "getProgramStats",
+            "null",
+            "10",
+            "--token",
+            harness.admin_token(),
+            "--json",
+        ],
+    )
+    .await;
+    assert_eq!(exit_code(&output), 0, "{output:?}");
+    json(&output);
+}
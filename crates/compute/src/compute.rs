@@ -1,4 +1,9 @@
-use crate::{LasrContentType, LasrObjectRuntime, LasrPackage, LasrPackageType, ProgramFormat};
+use crate::{
+    ContainerRuntime, ContentAddress, ExecutionError, ExecutionLimits, ExecutionLimitsConfig,
+    ExecutionLogRecorder, ExecutionLogs, ExecutionRuntime, FetchError, LasrContentType,
+    LasrObjectRuntime, LasrPackage, LasrPackageType, ProgramFetcher, ProgramFetchers,
+    ProgramFormat, ProgramRuntime, WasmRuntime,
+};
 use derive_builder::Builder;
 use lasr_messages::{ActorType, ExecutorMessage};
 use lasr_types::{Inputs, ProgramSchema, Transaction};
@@ -7,10 +12,9 @@ use ractor::ActorRef;
 use std::io::Read;
 use std::io::Write;
 use std::os::unix::prelude::PermissionsExt;
-use std::path::Path;
-use std::process::Stdio;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{ffi::OsStr, fmt::Display};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 use web3_pkg::web3_store::Web3Store;
@@ -20,6 +24,15 @@ use ipfs_api::{IpfsApi, IpfsClient};
 
 const IPFS_TIMEOUT: Duration = Duration::from_secs(3);
 
+/// The program [`OciManager::check_runtime`] runs. It writes empty outputs.
+const HEALTH_CHECK_PROGRAM: &str = r#"
+    (module
+      (import "lasr" "write_output" (func $write_output (param i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "{}")
+      (func (export "run") (call $write_output (i32.const 0) (i32.const 2))))
+"#;
+
 #[derive(Debug)]
 pub enum BaseImage {
     Wasm,
@@ -108,11 +121,56 @@ impl PackageContainerMetadata {
 pub struct OciManager {
     bundler: OciBundler<String, String>,
     store: Option<String>,
+    #[builder(default = "ExecutionLimitsConfig::default()")]
+    limits: ExecutionLimitsConfig,
+    #[builder(default = "ExecutionLogs::default()")]
+    logs: ExecutionLogs,
+    #[builder(default = "ProgramFetchers::local(None)")]
+    fetchers: ProgramFetchers,
 }
 
 impl OciManager {
     pub fn new(bundler: OciBundler<String, String>, store: Option<String>) -> Self {
-        Self { bundler, store }
+        Self {
+            bundler,
+            fetchers: ProgramFetchers::local(store.clone()),
+            store,
+            limits: ExecutionLimitsConfig::default(),
+            logs: ExecutionLogs::default(),
+        }
+    }
+
+    /// Also fetches program content from `fetcher`, after the sources
+    /// already configured.
+    pub fn with_program_fetcher(mut self, fetcher: Arc<dyn ProgramFetcher>) -> Self {
+        self.fetchers = self.fetchers.with(fetcher);
+        self
+    }
+
+    /// Uses `fetchers` in place of the node's IPFS store alone.
+    pub fn with_program_fetchers(mut self, fetchers: ProgramFetchers) -> Self {
+        self.fetchers = fetchers;
+        self
+    }
+
+    /// Runs programs under `limits` instead of the default limits.
+    pub fn with_limits(mut self, limits: ExecutionLimitsConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Records what programs log in `logs`.
+    pub fn with_execution_logs(mut self, logs: ExecutionLogs) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    pub fn limits(&self) -> &ExecutionLimitsConfig {
+        &self.limits
+    }
+
+    pub fn execution_logs(&self) -> &ExecutionLogs {
+        &self.logs
     }
 
     pub fn try_get_store(&self) -> Result<Web3Store, std::io::Error> {
@@ -166,39 +224,66 @@ impl OciManager {
         Ok(())
     }
 
+    /// Fetches the package for `content_id` from the first source that has
+    /// it and checks it against its hash. Packages stored as JSON are staged
+    /// where [`OciManager::bundle`] looks for them, so bundling does not
+    /// have to fetch them again; anything else is only checked, and is read
+    /// back through the content store when bundled.
+    pub async fn prefetch_program(&self, content_id: &str) -> Result<(), FetchError> {
+        let address = content_id.parse::<ContentAddress>()?;
+        let package_data = self.fetchers.fetch(content_id).await?;
+        if !address.is_json() {
+            return Ok(());
+        }
+
+        serde_json::from_slice::<LasrPackage>(&package_data).map_err(|e| {
+            FetchError::NotAPackage {
+                content_id: content_id.to_string(),
+                reason: e.to_string(),
+            }
+        })?;
+        let package_dir = self.bundler.get_package_path(content_id);
+        std::fs::create_dir_all(&package_dir)?;
+        std::fs::write(package_dir.join("metadata.json"), &package_data)?;
+        Ok(())
+    }
+
     pub async fn create_payload_package(
         &self,
         content_id: impl AsRef<Path>,
     ) -> Result<Option<PackageContainerMetadata>, std::io::Error> {
         let cid = content_id.as_ref().to_string_lossy().to_string();
-        let payload_path_string = self
+        let package_dir = self
             .bundler
-            .get_payload_path(content_id.as_ref())
-            .as_ref()
+            .get_package_path(content_id.as_ref())
             .to_string_lossy()
             .to_string();
 
-        tracing::info!("Attempting to read DAG for {} from Web3Store...", &cid);
         let store = self.try_get_store()?;
-        let package_data = match timeout(IPFS_TIMEOUT, store.read_dag(&cid)).await {
-            Ok(Ok(data)) => data,
-            Ok(Err(e)) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    e.to_string(),
-                ))
-            }
-            Err(_) => {
-                tracing::error!("Timed out reading from IPFS");
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::TimedOut,
-                    "Timed out reading from IPFS",
-                ));
+        let staged = Path::new(&package_dir).join("metadata.json");
+        let package_data = if staged.exists() {
+            tracing::info!("using the package already fetched for {}", &cid);
+            std::fs::read(&staged)?
+        } else {
+            tracing::info!("Attempting to read DAG for {} from Web3Store...", &cid);
+            match timeout(IPFS_TIMEOUT, store.read_dag(&cid)).await {
+                Ok(Ok(data)) => data,
+                Ok(Err(e)) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        e.to_string(),
+                    ))
+                }
+                Err(_) => {
+                    tracing::error!("Timed out reading from IPFS");
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Timed out reading from IPFS",
+                    ));
+                }
             }
         };
 
-        let package_dir = format!("{}/{}", &payload_path_string, &cid);
-
         tracing::info!("creating all directories in path: {}", &package_dir);
         std::fs::create_dir_all(&package_dir)?;
 
@@ -307,6 +392,10 @@ impl OciManager {
         let container_metadata = self.create_payload_package(content_id).await?;
         if let Some(metadata) = container_metadata {
             tracing::info!("received container metadata: {:?}", &metadata);
+            if let BaseImage::Wasm = metadata.base_image() {
+                tracing::info!("{} is a wasm program, no container needed", cid);
+                return Ok(());
+            }
             tracing::info!("building container bundle");
             self.bundler.bundle(&cid, &metadata).await?;
             self.add_payload(&cid).await?;
@@ -332,6 +421,95 @@ impl OciManager {
         self.bundler.add_payload(content_id).await
     }
 
+    /// The runtime the program in the fetched package for `content_id` runs
+    /// in.
+    pub fn program_runtime(
+        &self,
+        content_id: impl AsRef<Path>,
+    ) -> Result<ProgramRuntime, std::io::Error> {
+        let package = self.bundler.get_package(content_id)?;
+        match package.package_payload.package_type {
+            LasrPackageType::Program(LasrObjectRuntime::Wasm) => Ok(ProgramRuntime::Wasm),
+            _ => Ok(ProgramRuntime::Container),
+        }
+    }
+
+    /// The number of bytes the prepared bundle for `content_id` takes up.
+    pub fn bundle_size(&self, content_id: impl AsRef<Path>) -> Result<u64, std::io::Error> {
+        fs_extra::dir::get_size(self.bundler.get_payload_path(&content_id))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    }
+
+    /// Removes everything [`OciManager::bundle`] created for `content_id`.
+    pub fn remove_bundle(&self, content_id: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        self.bundler.remove_bundle(content_id)
+    }
+
+    /// Checks programs can still be executed, by running a trivial built in
+    /// program in wasmtime and making sure runsc still answers.
+    pub async fn check_runtime(&self) -> Result<(), ExecutionError> {
+        let output = WasmRuntime::new(self.bundler.clone())
+            .run_module(
+                HEALTH_CHECK_PROGRAM.into(),
+                b"{}",
+                &ExecutionLimits::default(),
+                None,
+            )
+            .await?;
+        if output != b"{}" {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "health check program produced unexpected output",
+            )
+            .into());
+        }
+
+        let list = Command::new("runsc")
+            .arg("--rootless")
+            .arg("list")
+            .output()
+            .await?;
+        if !list.status.success() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!(
+                    "runsc list exited with {}: {}",
+                    list.status,
+                    String::from_utf8_lossy(&list.stderr).trim()
+                ),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Force deletes every sandbox runsc still has, so nothing an aborted
+    /// execution started is left running.
+    pub async fn reset_runtime(&self) -> Result<(), std::io::Error> {
+        let list = Command::new("runsc")
+            .arg("--rootless")
+            .arg("list")
+            .arg("-quiet")
+            .output()
+            .await?;
+        for container_id in String::from_utf8_lossy(&list.stdout)
+            .lines()
+            .filter(|id| !id.is_empty())
+        {
+            tracing::warn!("deleting container {container_id} while resetting the runtime");
+            Command::new("runsc")
+                .arg("--rootless")
+                .arg("delete")
+                .arg("--force")
+                .arg(container_id)
+                .output()
+                .await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn base_spec(&self, content_id: impl AsRef<Path>) -> Result<(), std::io::Error> {
         self.bundler.base_spec(content_id).await
     }
@@ -354,93 +532,85 @@ impl OciManager {
         self.bundler.get_program_schema(content_id)
     }
 
+    /// Runs the program for `content_id` with `inputs` and returns its
+    /// outputs, without telling the executor. Used directly to run a program
+    /// again when verifying its execution is deterministic.
+    pub async fn run_program(
+        &self,
+        content_id: &str,
+        program_id: &str,
+        runtime: ProgramRuntime,
+        inputs: &Inputs,
+        logs: &ExecutionLogRecorder,
+    ) -> Result<String, ExecutionError> {
+        self.run_program_within(content_id, program_id, runtime, inputs, logs, None)
+            .await
+    }
+
+    /// Runs the program as [`OciManager::run_program`] does, held to
+    /// `budget` as well as its own limits, whichever is tighter, if given
+    /// one.
+    pub async fn run_program_within(
+        &self,
+        content_id: &str,
+        program_id: &str,
+        runtime: ProgramRuntime,
+        inputs: &Inputs,
+        logs: &ExecutionLogRecorder,
+        budget: Option<&ExecutionLimits>,
+    ) -> Result<String, ExecutionError> {
+        let limits = self.limits.for_program(program_id);
+        let limits = match budget {
+            Some(budget) => limits.within(budget),
+            None => *limits,
+        };
+        let runtime: Box<dyn ExecutionRuntime> = match runtime {
+            ProgramRuntime::Container => Box::new(ContainerRuntime::new(self.bundler.clone())),
+            ProgramRuntime::Wasm => Box::new(WasmRuntime::new(self.bundler.clone())),
+        };
+
+        let stdio_inputs = serde_json::to_string(inputs).map_err(std::io::Error::from)?;
+        tracing::info!("passing inputs to program: {:#?}", &stdio_inputs);
+        let output = runtime
+            .run(content_id, stdio_inputs.as_bytes(), &limits, logs)
+            .await?;
+
+        let outputs = String::from_utf8(output)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if outputs.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "program produced no output",
+            )
+            .into());
+        }
+
+        tracing::warn!("result from program: {content_id} = {:#?}", outputs);
+        Ok(outputs)
+    }
+
+    /// Runs the program in `runtime` under the limits configured for
+    /// `program_id`. A breach stops the program and the returned handle
+    /// resolves to [`ExecutionError::ResourceExceeded`]. The executor is
+    /// told the results are ready whether or not the execution succeeded.
     pub async fn run_container(
         &self,
         content_id: impl AsRef<Path> + Send + 'static,
         program_id: String,
+        runtime: ProgramRuntime,
         transaction: Option<Transaction>,
         inputs: Inputs,
         transaction_hash: Option<String>,
-    ) -> Result<tokio::task::JoinHandle<Result<String, std::io::Error>>, std::io::Error> {
-        let container_path = self
-            .bundler
-            .get_container_path(&content_id)
-            .as_ref()
-            .to_string_lossy()
-            .into_owned();
-
+    ) -> Result<tokio::task::JoinHandle<Result<String, ExecutionError>>, std::io::Error> {
         let container_id = content_id.as_ref().to_string_lossy().into_owned();
+        let logs = self.logs.recorder(&program_id, transaction_hash.clone());
+        let manager = self.clone();
 
-        let inner_inputs = inputs.clone();
-        tracing::warn!(
-            "Calling: runsc --rootless --network=none run -bundle {} {}",
-            &container_path,
-            &container_id
-        );
         Ok(tokio::spawn(async move {
-            // Create temp file for this container to output to
-            let temp_file_path = std::env::temp_dir().join(format!("lasr/{}.out", container_id));
-
-            // Create the temporary file for container output
-            if let Some(parent) = temp_file_path.parent() {
-                tokio::fs::create_dir_all(parent).await?;
-            }
-
-            // Check if the file exists
-            if tokio::fs::metadata(&temp_file_path).await.is_ok() {
-                // If it exists, make sure it is empty
-                tokio::fs::write(&temp_file_path, b"").await?;
-            }
-
-            // Create a standard file for stdout redirection
-            let container_stdout_file = std::fs::File::create(&temp_file_path)?;
-
-            let mut child = Command::new("runsc")
-                .arg("--rootless")
-                .arg("--network=none")
-                .arg("run")
-                .arg("-bundle")
-                .arg(&container_path)
-                .arg(&container_id)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::from(container_stdout_file))
-                .spawn()?;
-
-            let mut stdin = child.stdin.take().ok_or({
-                std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "unable to acquire child stdin, compute.rs: 120".to_string(),
-                )
-            })?;
-            let stdio_inputs = serde_json::to_string(&inner_inputs.clone())?;
-            tracing::info!("passing inputs to stdio: {:#?}", &stdio_inputs);
-            let _ = tokio::task::spawn(async move {
-                stdin.write_all(stdio_inputs.clone().as_bytes()).await?;
-
-                drop(stdin);
-                Ok::<_, std::io::Error>(())
-            })
-            .await?;
-            let status = child.wait().await?;
-            if !status.success() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "runsc command failed",
-                ));
-            }
-
-            let mut file = tokio::fs::File::open(&temp_file_path).await?;
-            let mut outputs = String::new();
-            file.read_to_string(&mut outputs).await?;
-
-            if outputs.is_empty() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "temporary file is empty after container execution",
-                ));
-            }
-
-            tracing::warn!("result from container: {container_id} = {:#?}", outputs);
+            let result = manager
+                .run_program(&container_id, &program_id, runtime, &inputs, &logs)
+                .await;
+            logs.finish();
 
             let actor: ActorRef<ExecutorMessage> =
                 ractor::registry::where_is(ActorType::Executor.to_string())
@@ -452,7 +622,7 @@ impl OciManager {
 
             tracing::warn!("results received, informing executor");
             let message = ExecutorMessage::Results {
-                content_id: content_id.as_ref().to_string_lossy().into_owned(),
+                content_id: container_id,
                 program_id,
                 transaction_hash,
                 transaction,
@@ -464,10 +634,7 @@ impl OciManager {
 
             tracing::warn!("casted message to inform executor");
 
-            // Clean up the temporary file
-            tokio::fs::remove_file(temp_file_path).await?;
-
-            Ok::<_, std::io::Error>(outputs)
+            result
         }))
     }
 }
@@ -483,6 +650,9 @@ pub struct OciBundler<R: AsRef<OsStr>, P: AsRef<Path>> {
 impl<R: AsRef<OsStr>, P: AsRef<Path>> OciBundler<R, P> {
     pub const CONTAINER_ROOT: &'static str = "rootfs";
     pub const CONTAINER_BIN: &'static str = "bin";
+    /// Where in a container's directory the bundles its executions run
+    /// from are written.
+    pub const EXECUTIONS: &'static str = "executions";
 
     pub fn new(runtime: R, containers: P, base_images: P, payload_path: P) -> Self {
         Self {
@@ -636,6 +806,91 @@ impl<R: AsRef<OsStr>, P: AsRef<Path>> OciBundler<R, P> {
         Ok(())
     }
 
+    /// Writes the bundle one execution of the container for `content_id`
+    /// runs from, under `execution_id`: a copy of the container's spec,
+    /// with the memory limit the runtime enforces set to `limit_bytes` and
+    /// its root the container's own. Executions of one program at once so
+    /// never share a spec or see each other's limits. Answers the bundle's
+    /// path, for [`OciBundler::remove_execution_bundle`] to remove.
+    pub fn execution_bundle(
+        &self,
+        content_id: impl AsRef<Path>,
+        execution_id: &str,
+        limit_bytes: u64,
+    ) -> Result<PathBuf, std::io::Error> {
+        let container_path = self.get_container_path(&content_id);
+        let mut spec: Spec = Spec::load(container_path.as_ref().join("config.json"))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        let mut linux = spec.linux().clone().unwrap_or_default();
+        let mut resources = linux.resources().clone().unwrap_or_default();
+        let mut memory = resources.memory().clone().unwrap_or_default();
+        memory.set_limit(Some(i64::try_from(limit_bytes).unwrap_or(i64::MAX)));
+        resources.set_memory(Some(memory));
+        linux.set_resources(Some(resources));
+        spec.set_linux(Some(linux));
+
+        // The bundle is elsewhere, so the root is named in full.
+        let mut root = if let Some(root) = spec.root() {
+            root.to_owned()
+        } else {
+            RootBuilder::default()
+                .build()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        };
+        root.set_path(std::fs::canonicalize(
+            container_path.as_ref().join(root.path()),
+        )?);
+        spec.set_root(Some(root));
+
+        let bundle = self.execution_bundle_path(&content_id, execution_id);
+        std::fs::create_dir_all(&bundle)?;
+        std::fs::write(
+            bundle.join("config.json"),
+            serde_json::to_string_pretty(&spec)?,
+        )?;
+        Ok(bundle)
+    }
+
+    /// Removes the bundle written for `execution_id`, once it has run.
+    pub fn remove_execution_bundle(
+        &self,
+        content_id: impl AsRef<Path>,
+        execution_id: &str,
+    ) -> Result<(), std::io::Error> {
+        let bundle = self.execution_bundle_path(&content_id, execution_id);
+        if bundle.exists() {
+            std::fs::remove_dir_all(bundle)?;
+        }
+        Ok(())
+    }
+
+    fn execution_bundle_path(&self, content_id: impl AsRef<Path>, execution_id: &str) -> PathBuf {
+        self.get_container_path(content_id)
+            .as_ref()
+            .join(Self::EXECUTIONS)
+            .join(execution_id)
+    }
+
+    /// Removes the container, the payload copied into its root and the
+    /// fetched payload for `content_id`.
+    pub fn remove_bundle(&self, content_id: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        let container_path = self.get_container_path(&content_id);
+        let container_root = self.container_root_path(&container_path);
+        let copied_payload = container_root.as_ref().join(&content_id);
+        for path in [
+            copied_payload.as_path(),
+            container_path.as_ref(),
+            self.get_payload_path(&content_id).as_ref(),
+        ] {
+            if path.exists() {
+                std::fs::remove_dir_all(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn get_container_path(&self, content_id: impl AsRef<Path>) -> impl AsRef<Path> {
         let container_path = self.containers.as_ref().join(content_id);
         container_path
@@ -655,6 +910,22 @@ impl<R: AsRef<OsStr>, P: AsRef<Path>> OciBundler<R, P> {
         payload_path
     }
 
+    /// The directory a fetched package's objects and `metadata.json` are
+    /// written to.
+    pub fn get_package_path(&self, content_id: impl AsRef<Path>) -> PathBuf {
+        self.get_payload_path(&content_id)
+            .as_ref()
+            .join(content_id.as_ref())
+    }
+
+    /// Reads the package fetched for `content_id` back from its
+    /// `metadata.json`.
+    pub fn get_package(&self, content_id: impl AsRef<Path>) -> std::io::Result<LasrPackage> {
+        let metadata_path = self.get_package_path(content_id).join("metadata.json");
+        let package_data = std::fs::read(metadata_path)?;
+        Ok(serde_json::from_slice(&package_data)?)
+    }
+
     pub fn container_bin_path(&self, container_path: impl AsRef<Path>) -> impl AsRef<Path> {
         let container_root_path = container_path.as_ref().join(Self::CONTAINER_ROOT);
         container_root_path.join(Self::CONTAINER_BIN)
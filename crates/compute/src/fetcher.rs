@@ -0,0 +1,492 @@
+//! Fetching program content by its content address.
+//!
+//! A program's package may live on the node's own IPFS store, behind an
+//! HTTP gateway or in a blob on the DA layer. [`ProgramFetchers`] tries each
+//! configured [`ProgramFetcher`] in turn and only accepts content whose hash
+//! matches the address it was asked for, so a slow or lying source is
+//! skipped in favour of the next one.
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use ipfs_api::{IpfsApi, IpfsClient, TryFromUri};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::timeout;
+
+/// Prefix of content ids that point at a blob on the DA layer, as in
+/// `da:<batch header hash>:<blob index>:<sha256 of the blob>`.
+pub const DA_CONTENT_PREFIX: &str = "da:";
+
+const CODEC_RAW: u64 = 0x55;
+const CODEC_DAG_PB: u64 = 0x70;
+const CODEC_DAG_JSON: u64 = 0x0129;
+const CODEC_JSON: u64 = 0x0200;
+
+const HASH_IDENTITY: u64 = 0x00;
+const HASH_SHA2_256: u64 = 0x12;
+const HASH_KECCAK_256: u64 = 0x1b;
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("{content_id} is not a valid content id: {reason}")]
+    InvalidContentId { content_id: String, reason: String },
+
+    #[error("unable to fetch {content_id}: {}", .reasons.join("; "))]
+    Unavailable {
+        content_id: String,
+        reasons: Vec<String>,
+    },
+
+    #[error("{content_id} is not a lasr package: {reason}")]
+    NotAPackage { content_id: String, reason: String },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Where a program's content lives and the hash it must have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentAddress {
+    /// An IPFS CID. `hash_code` is the multihash function `digest` was
+    /// produced with.
+    Ipfs {
+        cid: String,
+        codec: u64,
+        hash_code: u64,
+        digest: Vec<u8>,
+    },
+    DataAvailability {
+        batch_header_hash: String,
+        blob_index: u128,
+        sha256: Vec<u8>,
+    },
+}
+
+impl FromStr for ContentAddress {
+    type Err = FetchError;
+
+    fn from_str(content_id: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| FetchError::InvalidContentId {
+            content_id: content_id.to_string(),
+            reason: reason.to_string(),
+        };
+
+        if let Some(reference) = content_id.strip_prefix(DA_CONTENT_PREFIX) {
+            let mut parts = reference.split(':');
+            let (Some(batch_header_hash), Some(blob_index), Some(sha256), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid(
+                    "expected da:<batch header hash>:<blob index>:<sha256>",
+                ));
+            };
+            let batch_header_hash = batch_header_hash.trim_start_matches("0x");
+            if !matches!(hex::decode(batch_header_hash), Ok(hash) if hash.len() == 32) {
+                return Err(invalid("batch header hash must be 32 hex encoded bytes"));
+            }
+            let blob_index = blob_index
+                .parse::<u128>()
+                .map_err(|_| invalid("blob index is not a number"))?;
+            let sha256 = hex::decode(sha256.trim_start_matches("0x"))
+                .ok()
+                .filter(|digest| digest.len() == 32)
+                .ok_or_else(|| invalid("sha256 must be 32 hex encoded bytes"))?;
+            return Ok(ContentAddress::DataAvailability {
+                batch_header_hash: batch_header_hash.to_lowercase(),
+                blob_index,
+                sha256,
+            });
+        }
+
+        // CIDv0 is a bare base58btc sha2-256 multihash of a dag-pb node.
+        let (version, codec, multihash) = if content_id.len() == 46 && content_id.starts_with("Qm")
+        {
+            let (_, multihash) =
+                multibase::decode(format!("z{content_id}")).map_err(|e| invalid(&e.to_string()))?;
+            (0, CODEC_DAG_PB, multihash)
+        } else {
+            let (_, bytes) = multibase::decode(content_id).map_err(|e| invalid(&e.to_string()))?;
+            let mut bytes = bytes.as_slice();
+            let version = read_varint(&mut bytes).ok_or_else(|| invalid("truncated version"))?;
+            let codec = read_varint(&mut bytes).ok_or_else(|| invalid("truncated codec"))?;
+            (version, codec, bytes.to_vec())
+        };
+        if version > 1 {
+            return Err(invalid("unsupported CID version"));
+        }
+
+        let mut bytes = multihash.as_slice();
+        let hash_code = read_varint(&mut bytes).ok_or_else(|| invalid("truncated multihash"))?;
+        let length = read_varint(&mut bytes).ok_or_else(|| invalid("truncated multihash"))?;
+        if bytes.len() as u64 != length {
+            return Err(invalid("multihash length does not match its digest"));
+        }
+        if !matches!(hash_code, HASH_IDENTITY | HASH_SHA2_256 | HASH_KECCAK_256) {
+            return Err(invalid(&format!(
+                "hash function 0x{hash_code:x} cannot be verified"
+            )));
+        }
+        Ok(ContentAddress::Ipfs {
+            cid: content_id.to_string(),
+            codec,
+            hash_code,
+            digest: bytes.to_vec(),
+        })
+    }
+}
+
+impl ContentAddress {
+    pub fn is_ipfs(&self) -> bool {
+        matches!(self, ContentAddress::Ipfs { .. })
+    }
+
+    /// Whether the content is the package JSON itself, rather than an
+    /// encoding only the content store knows how to read back.
+    pub fn is_json(&self) -> bool {
+        match self {
+            ContentAddress::Ipfs { codec, .. } => {
+                matches!(*codec, CODEC_RAW | CODEC_JSON | CODEC_DAG_JSON)
+            }
+            ContentAddress::DataAvailability { .. } => true,
+        }
+    }
+
+    /// Checks `data` hashes to this address.
+    pub fn verify(&self, data: &[u8]) -> bool {
+        match self {
+            ContentAddress::Ipfs {
+                hash_code, digest, ..
+            } => match *hash_code {
+                HASH_IDENTITY => data == digest.as_slice(),
+                HASH_SHA2_256 => Sha256::digest(data).as_slice() == digest.as_slice(),
+                HASH_KECCAK_256 => Keccak256::digest(data).as_slice() == digest.as_slice(),
+                _ => false,
+            },
+            ContentAddress::DataAvailability { sha256, .. } => {
+                Sha256::digest(data).as_slice() == sha256.as_slice()
+            }
+        }
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let slice = *bytes;
+    let mut value = 0u64;
+    for (i, byte) in slice.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &slice[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// A source program content can be fetched from.
+#[async_trait]
+pub trait ProgramFetcher: std::fmt::Debug + Send + Sync {
+    /// Names the source in logs and errors.
+    fn name(&self) -> &str;
+
+    /// Whether `address` can be fetched from this source at all.
+    fn supports(&self, address: &ContentAddress) -> bool;
+
+    /// Fetches the raw content at `address`. It is checked against the
+    /// address by the caller.
+    async fn fetch(&self, address: &ContentAddress) -> std::io::Result<Vec<u8>>;
+}
+
+/// Reads raw blocks from the node's own IPFS store.
+#[derive(Debug, Clone)]
+pub struct LocalStoreFetcher {
+    store: Option<String>,
+}
+
+impl LocalStoreFetcher {
+    /// Fetches from the IPFS API at the multiaddr `store`, or the default
+    /// local API if there is none.
+    pub fn new(store: Option<String>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl ProgramFetcher for LocalStoreFetcher {
+    fn name(&self) -> &str {
+        "local store"
+    }
+
+    fn supports(&self, address: &ContentAddress) -> bool {
+        address.is_ipfs()
+    }
+
+    async fn fetch(&self, address: &ContentAddress) -> std::io::Result<Vec<u8>> {
+        let ContentAddress::Ipfs { cid, .. } = address else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "not an IPFS content id",
+            ));
+        };
+        let client = match &self.store {
+            Some(addr) => IpfsClient::from_multiaddr_str(addr)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?,
+            None => IpfsClient::default(),
+        };
+        client
+            .block_get(cid)
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+/// Reads raw blocks from an HTTP IPFS gateway.
+#[derive(Debug, Clone)]
+pub struct GatewayFetcher {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl GatewayFetcher {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProgramFetcher for GatewayFetcher {
+    fn name(&self) -> &str {
+        "gateway"
+    }
+
+    fn supports(&self, address: &ContentAddress) -> bool {
+        address.is_ipfs()
+    }
+
+    async fn fetch(&self, address: &ContentAddress) -> std::io::Result<Vec<u8>> {
+        let ContentAddress::Ipfs { cid, .. } = address else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "not an IPFS content id",
+            ));
+        };
+        let to_io = |e: reqwest::Error| std::io::Error::new(std::io::ErrorKind::Other, e);
+        let response = self
+            .client
+            .get(format!("{}/ipfs/{cid}?format=raw", self.base_url))
+            .header("Accept", "application/vnd.ipld.raw")
+            .send()
+            .await
+            .map_err(to_io)?
+            .error_for_status()
+            .map_err(to_io)?;
+        Ok(response.bytes().await.map_err(to_io)?.to_vec())
+    }
+}
+
+/// How long each source gets to return program content by default.
+pub const DEFAULT_PROGRAM_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The sources program content is fetched from, tried in order.
+#[derive(Debug, Clone)]
+pub struct ProgramFetchers {
+    fetchers: Vec<Arc<dyn ProgramFetcher>>,
+    timeout: Duration,
+}
+
+impl ProgramFetchers {
+    /// No sources yet, giving each fetch `timeout` to finish.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            fetchers: Vec::new(),
+            timeout,
+        }
+    }
+
+    /// The node's IPFS store at `store`, each fetch given the default
+    /// timeout.
+    pub fn local(store: Option<String>) -> Self {
+        Self::new(DEFAULT_PROGRAM_FETCH_TIMEOUT).with(Arc::new(LocalStoreFetcher::new(store)))
+    }
+
+    /// Tries `fetcher` after the sources already configured.
+    pub fn with(mut self, fetcher: Arc<dyn ProgramFetcher>) -> Self {
+        self.fetchers.push(fetcher);
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Fetches `content_id` from the first source that returns content
+    /// matching its hash.
+    pub async fn fetch(&self, content_id: &str) -> Result<Vec<u8>, FetchError> {
+        let address = content_id.parse::<ContentAddress>()?;
+        let mut reasons = Vec::new();
+        for fetcher in self.fetchers.iter().filter(|f| f.supports(&address)) {
+            let reason = match timeout(self.timeout, fetcher.fetch(&address)).await {
+                Ok(Ok(data)) if address.verify(&data) => {
+                    tracing::info!("fetched {content_id} from {}", fetcher.name());
+                    return Ok(data);
+                }
+                Ok(Ok(_)) => format!(
+                    "{} returned content that does not match its hash",
+                    fetcher.name()
+                ),
+                Ok(Err(e)) => format!("{} failed: {e}", fetcher.name()),
+                Err(_) => format!("{} timed out after {:?}", fetcher.name(), self.timeout),
+            };
+            tracing::warn!("fetching {content_id}: {reason}");
+            reasons.push(reason);
+        }
+        if reasons.is_empty() {
+            reasons.push("no configured source serves this kind of content id".to_string());
+        }
+
+        Err(FetchError::Unavailable {
+            content_id: content_id.to_string(),
+            reasons,
+        })
+    }
+}
+
+#[cfg(test)]
+mod fetcher_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Debug)]
+    struct MockFetcher {
+        name: &'static str,
+        delay: Duration,
+        response: Vec<u8>,
+        calls: AtomicU32,
+    }
+
+    impl MockFetcher {
+        fn new(name: &'static str, response: &[u8]) -> Arc<Self> {
+            Self::delayed(name, response, Duration::ZERO)
+        }
+
+        fn delayed(name: &'static str, response: &[u8], delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                delay,
+                response: response.to_vec(),
+                calls: AtomicU32::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ProgramFetcher for MockFetcher {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn supports(&self, _address: &ContentAddress) -> bool {
+            true
+        }
+
+        async fn fetch(&self, _address: &ContentAddress) -> std::io::Result<Vec<u8>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(self.response.clone())
+        }
+    }
+
+    /// A CIDv1 for `data` stored as plain JSON.
+    fn cid_for(data: &[u8]) -> String {
+        let mut bytes = vec![0x01, 0x80, 0x04, 0x12, 0x20];
+        bytes.extend_from_slice(&Sha256::digest(data));
+        multibase::encode(multibase::Base::Base32Lower, bytes)
+    }
+
+    const PACKAGE: &[u8] = br#"{"packagePayload":{}}"#;
+
+    #[tokio::test]
+    async fn corrupted_content_falls_through_to_the_next_source() {
+        let corrupted = MockFetcher::new("corrupted", b"{\"tampered\":true}");
+        let honest = MockFetcher::new("honest", PACKAGE);
+        let fetchers = ProgramFetchers::new(Duration::from_secs(1))
+            .with(corrupted.clone())
+            .with(honest.clone());
+
+        let data = fetchers.fetch(&cid_for(PACKAGE)).await.unwrap();
+        assert_eq!(data, PACKAGE);
+        assert_eq!(corrupted.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(honest.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_source_times_out() {
+        let slow = MockFetcher::delayed("slow", PACKAGE, Duration::from_secs(5));
+        let honest = MockFetcher::new("honest", PACKAGE);
+        let fetchers = ProgramFetchers::new(Duration::from_millis(50))
+            .with(slow)
+            .with(honest);
+
+        let started = std::time::Instant::now();
+        assert_eq!(fetchers.fetch(&cid_for(PACKAGE)).await.unwrap(), PACKAGE);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn every_source_failing_explains_why() {
+        let fetchers = ProgramFetchers::new(Duration::from_millis(50))
+            .with(MockFetcher::new("corrupted", b"not the package"))
+            .with(MockFetcher::delayed(
+                "slow",
+                PACKAGE,
+                Duration::from_secs(5),
+            ));
+
+        let err = fetchers.fetch(&cid_for(PACKAGE)).await.unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("corrupted returned content that does not match its hash"),
+            "{message}"
+        );
+        assert!(message.contains("slow timed out"), "{message}");
+    }
+
+    #[test]
+    fn content_ids_are_parsed_and_verified() {
+        let v1 = cid_for(PACKAGE).parse::<ContentAddress>().unwrap();
+        assert!(v1.is_ipfs());
+        assert!(v1.is_json());
+        assert!(v1.verify(PACKAGE));
+        assert!(!v1.verify(b"{}"));
+
+        let mut multihash = vec![0x12, 0x20];
+        multihash.extend_from_slice(&Sha256::digest(b"node"));
+        let v0 = multibase::encode(multibase::Base::Base58Btc, multihash);
+        let v0 = v0
+            .trim_start_matches('z')
+            .parse::<ContentAddress>()
+            .unwrap();
+        assert!(!v0.is_json());
+        assert!(v0.verify(b"node"));
+
+        let da = format!(
+            "da:0x{}:7:{}",
+            "ab".repeat(32),
+            hex::encode(Sha256::digest(PACKAGE))
+        )
+        .parse::<ContentAddress>()
+        .unwrap();
+        assert!(!da.is_ipfs());
+        assert!(da.verify(PACKAGE));
+
+        assert!("da:0x01:7:00".parse::<ContentAddress>().is_err());
+        assert!("not a cid".parse::<ContentAddress>().is_err());
+    }
+}
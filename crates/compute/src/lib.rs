@@ -1,5 +1,13 @@
 pub mod compute;
+pub mod fetcher;
+pub mod limits;
+pub mod logs;
 pub mod package;
+pub mod runtime;
 
 pub use compute::*;
+pub use fetcher::*;
+pub use limits::*;
+pub use logs::*;
 pub use package::*;
+pub use runtime::*;
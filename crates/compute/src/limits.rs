@@ -0,0 +1,385 @@
+//! Resource limits enforced on every program execution.
+//!
+//! Each execution gets a wall-clock timeout, a memory limit and a cap on the
+//! size of its output. The defaults come from the node config and can be
+//! overridden per program id, so trusted system programs can be given more
+//! room than arbitrary user programs.
+use crate::ExecutionLogRecorder;
+use lasr_messages::{ExecutorError, LasrError};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::os::unix::process::ExitStatusExt;
+use std::process::Stdio;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+const SIGKILL: i32 = 9;
+
+/// How long to wait for the last of a program's logs once it has exited.
+const LOG_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    WallClock,
+    Memory,
+    OutputSize,
+}
+
+impl ResourceKind {
+    fn unit(&self) -> &'static str {
+        match self {
+            ResourceKind::WallClock => "ms",
+            ResourceKind::Memory | ResourceKind::OutputSize => " bytes",
+        }
+    }
+}
+
+impl Display for ResourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceKind::WallClock => write!(f, "wall clock"),
+            ResourceKind::Memory => write!(f, "memory"),
+            ResourceKind::OutputSize => write!(f, "output size"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExecutionError {
+    /// The program was killed for breaching one of its limits. `limit` is in
+    /// milliseconds for [`ResourceKind::WallClock`] and bytes otherwise.
+    #[error("execution exceeded its {kind} limit of {limit}{}", kind.unit())]
+    ResourceExceeded { kind: ResourceKind, limit: u64 },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<ExecutionError> for LasrError {
+    fn from(e: ExecutionError) -> Self {
+        let e = match e {
+            ExecutionError::ResourceExceeded { .. } => {
+                ExecutorError::ResourceExceeded(e.to_string())
+            }
+            ExecutionError::Io(e) => ExecutorError::Failed(e.to_string()),
+        };
+        LasrError::Executor(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    pub timeout: Duration,
+    pub memory_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            memory_bytes: 512 * 1024 * 1024,
+            output_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl ExecutionLimits {
+    /// The tighter of these limits and `budget`, limit by limit.
+    pub fn within(&self, budget: &ExecutionLimits) -> Self {
+        Self {
+            timeout: self.timeout.min(budget.timeout),
+            memory_bytes: self.memory_bytes.min(budget.memory_bytes),
+            output_bytes: self.output_bytes.min(budget.output_bytes),
+        }
+    }
+
+    fn with_override(&self, limits_override: &ExecutionLimitsOverride) -> Self {
+        Self {
+            timeout: limits_override
+                .timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(self.timeout),
+            memory_bytes: limits_override
+                .memory_mb
+                .map(|mb| mb.saturating_mul(1024 * 1024))
+                .unwrap_or(self.memory_bytes),
+            output_bytes: limits_override
+                .output_kb
+                .map(|kb| kb.saturating_mul(1024))
+                .unwrap_or(self.output_bytes),
+        }
+    }
+}
+
+/// A per program override of the default limits, as given in
+/// `EXECUTION_LIMIT_OVERRIDES`. Any limit left out keeps its default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ExecutionLimitsOverride {
+    pub timeout_secs: Option<u64>,
+    pub memory_mb: Option<u64>,
+    pub output_kb: Option<u64>,
+}
+
+/// The limits every program runs under, and the programs that run under
+/// their own.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionLimitsConfig {
+    default: ExecutionLimits,
+    overrides: HashMap<String, ExecutionLimits>,
+}
+
+impl ExecutionLimitsConfig {
+    pub fn new(default: ExecutionLimits) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Runs the programs without limits of their own under `default`.
+    pub fn with_default(mut self, default: ExecutionLimits) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Runs each program keyed in `overrides`, e.g.
+    /// `{"0x...": {"timeoutSecs": 300, "memoryMb": 2048, "outputKb": 4096}}`,
+    /// under the default with its override applied.
+    pub fn with_overrides(mut self, overrides: &HashMap<String, ExecutionLimitsOverride>) -> Self {
+        for (program_id, limits_override) in overrides {
+            let limits = self.default.with_override(limits_override);
+            self = self.with_program_limits(program_id, limits);
+        }
+        self
+    }
+
+    pub fn with_program_limits(mut self, program_id: &str, limits: ExecutionLimits) -> Self {
+        self.overrides.insert(program_id.to_lowercase(), limits);
+        self
+    }
+
+    pub fn for_program(&self, program_id: &str) -> &ExecutionLimits {
+        self.overrides
+            .get(&program_id.to_lowercase())
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Runs `command` with `input` on its stdin and returns everything it wrote
+/// to stdout. The process is killed as soon as it runs past the timeout or
+/// writes more than the output limit. A process that is killed by anything
+/// else is assumed to have been killed by the runtime for exceeding its
+/// memory limit, which is the only other reason a sandbox kills a program.
+///
+/// With `logs`, every line the program writes to stderr is recorded there.
+pub async fn run_with_limits(
+    mut command: Command,
+    input: &[u8],
+    limits: &ExecutionLimits,
+    logs: Option<&ExecutionLogRecorder>,
+) -> Result<Vec<u8>, ExecutionError> {
+    if logs.is_some() {
+        command.stderr(Stdio::piped());
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().ok_or(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "unable to acquire child stdin",
+    ))?;
+    let mut stdout = child.stdout.take().ok_or(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "unable to acquire child stdout",
+    ))?;
+
+    // Written separately so a program that never reads its inputs cannot
+    // stall us before the timeout starts.
+    let input = input.to_vec();
+    tokio::task::spawn(async move {
+        if let Err(e) = stdin.write_all(&input).await {
+            tracing::warn!("failed to pass inputs to program: {e}");
+        }
+    });
+
+    // Read separately too, so a program that fills the stderr pipe never
+    // blocks on it.
+    let log_reader = match (logs, child.stderr.take()) {
+        (Some(logs), Some(stderr)) => {
+            let logs = logs.clone();
+            Some(tokio::task::spawn(async move {
+                if let Err(e) = logs.record_lines(stderr).await {
+                    tracing::warn!("failed to read program logs: {e}");
+                }
+            }))
+        }
+        _ => None,
+    };
+
+    let output_limit = limits.output_bytes;
+    let run = async {
+        let mut output = Vec::new();
+        (&mut stdout)
+            .take(output_limit.saturating_add(1))
+            .read_to_end(&mut output)
+            .await?;
+        if output.len() as u64 > output_limit {
+            return Err(ExecutionError::ResourceExceeded {
+                kind: ResourceKind::OutputSize,
+                limit: output_limit,
+            });
+        }
+        let status = child.wait().await?;
+        Ok((status, output))
+    };
+
+    let result = match timeout(limits.timeout, run).await {
+        Ok(result) => result,
+        Err(_) => Err(ExecutionError::ResourceExceeded {
+            kind: ResourceKind::WallClock,
+            limit: limits.timeout.as_millis() as u64,
+        }),
+    };
+    if let Err(e) = &result {
+        if let Err(kill_err) = child.kill().await {
+            tracing::error!("failed to kill program after {e}: {kill_err}");
+        }
+    }
+    if let Some(mut log_reader) = log_reader {
+        // Anything the program spawned may still hold stderr open.
+        if timeout(LOG_DRAIN_TIMEOUT, &mut log_reader).await.is_err() {
+            log_reader.abort();
+        }
+    }
+
+    let (status, output) = result?;
+    if status.signal() == Some(SIGKILL) || status.code() == Some(128 + SIGKILL) {
+        return Err(ExecutionError::ResourceExceeded {
+            kind: ResourceKind::Memory,
+            limit: limits.memory_bytes,
+        });
+    }
+    if !status.success() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("program exited with {status}"),
+        )
+        .into());
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use super::*;
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    fn limits(timeout: Duration, output_bytes: u64) -> ExecutionLimits {
+        ExecutionLimits {
+            timeout,
+            output_bytes,
+            ..ExecutionLimits::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn program_within_limits_returns_output() {
+        let output = run_with_limits(
+            Command::new("cat"),
+            b"{\"inputs\":\"test\"}",
+            &limits(Duration::from_secs(5), 1024),
+            None,
+        )
+        .await
+        .expect("program within limits failed");
+
+        assert_eq!(output, b"{\"inputs\":\"test\"}");
+    }
+
+    #[tokio::test]
+    async fn spinning_program_is_killed_at_timeout() {
+        let started = std::time::Instant::now();
+        let res = run_with_limits(
+            shell("while :; do :; done"),
+            b"",
+            &limits(Duration::from_millis(200), 1024),
+            None,
+        )
+        .await;
+
+        assert!(matches!(
+            res,
+            Err(ExecutionError::ResourceExceeded {
+                kind: ResourceKind::WallClock,
+                limit: 200
+            })
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn huge_output_is_cut_off_at_limit() {
+        let res = run_with_limits(
+            shell("yes"),
+            b"",
+            &limits(Duration::from_secs(5), 4096),
+            None,
+        )
+        .await;
+
+        assert!(matches!(
+            res,
+            Err(ExecutionError::ResourceExceeded {
+                kind: ResourceKind::OutputSize,
+                limit: 4096
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn chatty_program_logs_are_recorded_up_to_the_limit() {
+        let logs = crate::ExecutionLogs::new(50, 16);
+        let recorder = logs.recorder("0xaaaa", None);
+        let output = run_with_limits(
+            shell("i=0; while [ $i -lt 200 ]; do echo \"log $i\" >&2; i=$((i+1)); done; cat"),
+            b"{}",
+            &limits(Duration::from_secs(5), 1024),
+            Some(&recorder),
+        )
+        .await
+        .expect("chatty program failed");
+
+        assert_eq!(output, b"{}");
+        let lines = recorder.lines();
+        assert_eq!(lines.len(), 51);
+        assert_eq!(lines[0].line, "log 0");
+        assert_eq!(lines[49].line, "log 49");
+        assert!(lines[50].line.starts_with("log limit of 50 lines reached"));
+    }
+
+    #[test]
+    fn program_overrides_replace_defaults() {
+        let trusted = ExecutionLimits {
+            timeout: Duration::from_secs(600),
+            ..ExecutionLimits::default()
+        };
+        let config = ExecutionLimitsConfig::new(ExecutionLimits::default())
+            .with_program_limits("0xABCDEF", trusted);
+
+        assert_eq!(config.for_program("0xabcdef"), &trusted);
+        assert_eq!(config.for_program("0x123456"), &ExecutionLimits::default());
+    }
+}
@@ -0,0 +1,334 @@
+//! Logs programs write while they execute.
+//!
+//! Every execution gets an [`ExecutionLogRecorder`] from the node's
+//! [`ExecutionLogs`]. Lines are capped per execution, published live to
+//! anyone subscribed to the program, and the lines of recent executions are
+//! kept by transaction hash so they can be attached to whatever reports on
+//! that transaction afterwards.
+//!
+//! Each program id gets its own broadcast channel with a bounded buffer. A
+//! subscriber that falls behind loses the oldest lines it had not read yet
+//! and is told how many it lost, rather than slowing the execution down.
+use lasr_types::{ExecutionLogEvent, ExecutionLogLine};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::sync::broadcast;
+
+/// Longest line that is recorded. Anything past it is cut off.
+pub const MAX_LOG_LINE_BYTES: usize = 4096;
+
+/// Lines kept of each execution by default.
+pub const DEFAULT_LOG_MAX_LINES: usize = 1000;
+
+/// Lines buffered for a subscriber that falls behind by default.
+pub const DEFAULT_LOG_SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Number of executions whose lines are kept after they finish.
+const RETAINED_EXECUTIONS: usize = 1024;
+
+#[derive(Debug, Default)]
+struct RetainedLogs {
+    /// Oldest first.
+    order: VecDeque<String>,
+    lines: HashMap<String, Vec<ExecutionLogLine>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutionLogs {
+    max_lines: usize,
+    subscriber_buffer: usize,
+    channels: Arc<Mutex<HashMap<String, broadcast::Sender<ExecutionLogLine>>>>,
+    retained: Arc<Mutex<RetainedLogs>>,
+}
+
+impl Default for ExecutionLogs {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_MAX_LINES, DEFAULT_LOG_SUBSCRIBER_BUFFER)
+    }
+}
+
+impl ExecutionLogs {
+    pub fn new(max_lines: usize, subscriber_buffer: usize) -> Self {
+        Self {
+            max_lines,
+            subscriber_buffer: subscriber_buffer.max(1),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            retained: Arc::new(Mutex::new(RetainedLogs::default())),
+        }
+    }
+
+    pub fn max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// Starts recording the logs of one execution of `program_id`.
+    pub fn recorder(
+        &self,
+        program_id: &str,
+        transaction_hash: Option<String>,
+    ) -> ExecutionLogRecorder {
+        ExecutionLogRecorder {
+            logs: self.clone(),
+            program_id: program_id.to_lowercase(),
+            transaction_hash,
+            state: Arc::new(Mutex::new(RecorderState::default())),
+        }
+    }
+
+    /// Subscribes to the lines every later execution of `program_id` logs.
+    pub fn subscribe(&self, program_id: &str) -> ExecutionLogSubscription {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        let receiver = channels
+            .entry(program_id.to_lowercase())
+            .or_insert_with(|| broadcast::channel(self.subscriber_buffer).0)
+            .subscribe();
+        ExecutionLogSubscription { receiver }
+    }
+
+    /// The lines logged by the execution of `transaction_hash`, if it ran
+    /// recently enough for them to still be kept.
+    pub fn for_transaction(&self, transaction_hash: &str) -> Option<Vec<ExecutionLogLine>> {
+        self.retained
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .lines
+            .get(transaction_hash)
+            .cloned()
+    }
+
+    fn publish(&self, line: &ExecutionLogLine) {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = channels.get(&line.program_id) {
+            // Only fails once every subscriber is gone.
+            if sender.send(line.clone()).is_err() {
+                channels.remove(&line.program_id);
+            }
+        }
+    }
+
+    fn retain(&self, transaction_hash: String, lines: Vec<ExecutionLogLine>) {
+        let mut retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+        if retained
+            .lines
+            .insert(transaction_hash.clone(), lines)
+            .is_none()
+        {
+            retained.order.push_back(transaction_hash);
+        }
+        while retained.order.len() > RETAINED_EXECUTIONS {
+            if let Some(oldest) = retained.order.pop_front() {
+                retained.lines.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RecorderState {
+    lines: Vec<ExecutionLogLine>,
+    limit_reached: bool,
+}
+
+/// Records the lines of a single execution. Clones record into the same
+/// execution.
+#[derive(Debug, Clone)]
+pub struct ExecutionLogRecorder {
+    logs: ExecutionLogs,
+    program_id: String,
+    transaction_hash: Option<String>,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl ExecutionLogRecorder {
+    /// Records `line`, cut off at [`MAX_LOG_LINE_BYTES`]. Once the execution
+    /// has logged its limit of lines, one last line saying so is recorded
+    /// and everything after it is dropped.
+    pub fn record(&self, line: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.limit_reached {
+            return;
+        }
+        let line = if state.lines.len() >= self.logs.max_lines {
+            state.limit_reached = true;
+            format!(
+                "log limit of {} lines reached, dropping the rest",
+                self.logs.max_lines
+            )
+        } else {
+            truncate(line).to_string()
+        };
+
+        let line = ExecutionLogLine {
+            program_id: self.program_id.clone(),
+            transaction_hash: self.transaction_hash.clone(),
+            sequence: state.lines.len() as u64,
+            line,
+        };
+        self.logs.publish(&line);
+        state.lines.push(line);
+    }
+
+    /// Records every line `reader` produces until it is closed.
+    pub async fn record_lines<R: AsyncRead + Unpin>(&self, reader: R) -> std::io::Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            // Bounded so a program that never writes a newline cannot make
+            // us buffer everything it writes.
+            let read = (&mut reader)
+                .take(MAX_LOG_LINE_BYTES as u64)
+                .read_until(b'\n', &mut line)
+                .await?;
+            if read == 0 {
+                return Ok(());
+            }
+            if line.ends_with(b"\n") || read < MAX_LOG_LINE_BYTES {
+                self.record(String::from_utf8_lossy(&line).trim_end_matches(['\n', '\r']));
+            } else {
+                // The rest of an over long line is skipped.
+                self.record(&String::from_utf8_lossy(&line));
+                loop {
+                    let buf = reader.fill_buf().await?;
+                    if buf.is_empty() {
+                        break;
+                    }
+                    match buf.iter().position(|b| *b == b'\n') {
+                        Some(pos) => {
+                            reader.consume(pos + 1);
+                            break;
+                        }
+                        None => {
+                            let len = buf.len();
+                            reader.consume(len);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn lines(&self) -> Vec<ExecutionLogLine> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .lines
+            .clone()
+    }
+
+    /// Ends the execution, keeping its lines under its transaction hash.
+    pub fn finish(self) -> Vec<ExecutionLogLine> {
+        let lines = self.lines();
+        if let Some(transaction_hash) = self.transaction_hash.clone() {
+            self.logs.retain(transaction_hash, lines.clone());
+        }
+        lines
+    }
+}
+
+fn truncate(line: &str) -> &str {
+    if line.len() <= MAX_LOG_LINE_BYTES {
+        return line;
+    }
+    let mut end = MAX_LOG_LINE_BYTES;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+pub struct ExecutionLogSubscription {
+    receiver: broadcast::Receiver<ExecutionLogLine>,
+}
+
+impl ExecutionLogSubscription {
+    /// Waits for the next line, or for notice of the lines dropped because
+    /// the subscriber fell behind.
+    pub async fn next(&mut self) -> Option<ExecutionLogEvent> {
+        match self.receiver.recv().await {
+            Ok(line) => Some(ExecutionLogEvent::Line(line)),
+            Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                Some(ExecutionLogEvent::Dropped { dropped })
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod logs_tests {
+    use super::*;
+
+    fn line(event: Option<ExecutionLogEvent>) -> ExecutionLogLine {
+        match event {
+            Some(ExecutionLogEvent::Line(line)) => line,
+            other => panic!("expected a log line, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_only_see_their_program() {
+        let logs = ExecutionLogs::new(100, 16);
+        let mut subscription = logs.subscribe("0xAAAA");
+
+        logs.recorder("0xbbbb", None).record("not for us");
+        let recorder = logs.recorder("0xaaaa", Some("0x01".to_string()));
+        recorder.record("hello");
+        recorder.record("world");
+
+        let first = line(subscription.next().await);
+        let second = line(subscription.next().await);
+        assert_eq!(first.line, "hello");
+        assert_eq!(first.sequence, 0);
+        assert_eq!(first.transaction_hash.as_deref(), Some("0x01"));
+        assert_eq!(second.line, "world");
+        assert_eq!(second.sequence, 1);
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_is_told_how_many_lines_it_lost() {
+        let logs = ExecutionLogs::new(100, 4);
+        let mut subscription = logs.subscribe("0xaaaa");
+
+        let recorder = logs.recorder("0xaaaa", None);
+        for i in 0..10 {
+            recorder.record(&format!("line {i}"));
+        }
+
+        assert_eq!(
+            subscription.next().await,
+            Some(ExecutionLogEvent::Dropped { dropped: 6 })
+        );
+        // Only the newest lines are left.
+        assert_eq!(line(subscription.next().await).line, "line 6");
+    }
+
+    #[test]
+    fn lines_past_the_limit_are_dropped() {
+        let logs = ExecutionLogs::new(3, 16);
+        let recorder = logs.recorder("0xaaaa", Some("0x01".to_string()));
+        for i in 0..10 {
+            recorder.record(&format!("line {i}"));
+        }
+        recorder.record(&"x".repeat(MAX_LOG_LINE_BYTES * 2));
+
+        let lines = recorder.finish();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].line.starts_with("log limit of 3 lines reached"));
+        assert_eq!(logs.for_transaction("0x01"), Some(lines));
+    }
+
+    #[tokio::test]
+    async fn long_lines_are_cut_off() {
+        let logs = ExecutionLogs::new(10, 16);
+        let recorder = logs.recorder("0xaaaa", None);
+        let input = format!("{}\nshort\n", "x".repeat(MAX_LOG_LINE_BYTES * 3));
+        recorder.record_lines(input.as_bytes()).await.unwrap();
+
+        let lines = recorder.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].line.len(), MAX_LOG_LINE_BYTES);
+        assert_eq!(lines[1].line, "short");
+    }
+}
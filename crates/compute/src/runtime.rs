@@ -0,0 +1,620 @@
+//! The backends programs are executed in.
+//!
+//! Most programs run in a gVisor container built from their package. Programs
+//! packaged as WASM skip the container and are instantiated directly in
+//! wasmtime. Which backend a program uses is decided by its package when it
+//! is registered, and recorded in its program account metadata under
+//! [`PROGRAM_RUNTIME_KEY`].
+//!
+//! A WASM program gets no WASI and no ambient filesystem or network access.
+//! It imports its host functions from the `lasr` module and exports its
+//! `memory` and a `run` function taking no arguments:
+//!
+//! - `input_len() -> i32` returns the length of the JSON encoded inputs.
+//! - `read_input(ptr: i32)` copies the inputs into memory at `ptr`.
+//! - `write_output(ptr: i32, len: i32)` appends `len` bytes at `ptr` to the
+//!   program's output, which must be the same JSON outputs a container
+//!   program writes to stdout.
+//! - `log(ptr: i32, len: i32)` logs the UTF-8 text at `ptr`, the way a
+//!   container program logs by writing to stderr.
+use crate::{
+    run_with_limits, ExecutionError, ExecutionLimits, ExecutionLogRecorder, OciBundler,
+    ResourceKind,
+};
+use async_trait::async_trait;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::process::Command;
+use wasmtime::{Caller, Config, Engine, Extern, Linker, Module, ResourceLimiter, Store, Trap};
+
+/// Metadata key on a program account naming the runtime it executes in.
+pub const PROGRAM_RUNTIME_KEY: &str = "runtime";
+
+/// How often the WASM engine's epoch advances. Timeouts are rounded up to a
+/// whole number of ticks.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgramRuntime {
+    #[default]
+    Container,
+    Wasm,
+}
+
+impl Display for ProgramRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramRuntime::Container => write!(f, "container"),
+            ProgramRuntime::Wasm => write!(f, "wasm"),
+        }
+    }
+}
+
+impl FromStr for ProgramRuntime {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "container" => Ok(ProgramRuntime::Container),
+            "wasm" => Ok(ProgramRuntime::Wasm),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown program runtime: {s}"),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+pub trait ExecutionRuntime: Send + Sync {
+    /// Runs the prepared program for `content_id` with `inputs`, returning
+    /// the raw output it produced. Anything it logs is recorded in `logs`.
+    async fn run(
+        &self,
+        content_id: &str,
+        inputs: &[u8],
+        limits: &ExecutionLimits,
+        logs: &ExecutionLogRecorder,
+    ) -> Result<Vec<u8>, ExecutionError>;
+}
+
+/// Runs programs in their gVisor container.
+#[derive(Debug, Clone)]
+pub struct ContainerRuntime {
+    bundler: OciBundler<String, String>,
+}
+
+impl ContainerRuntime {
+    pub fn new(bundler: OciBundler<String, String>) -> Self {
+        Self { bundler }
+    }
+
+    /// Runs the container `execution_id` from `bundle`.
+    async fn run_bundle(
+        &self,
+        bundle: &Path,
+        execution_id: &str,
+        inputs: &[u8],
+        limits: &ExecutionLimits,
+        logs: &ExecutionLogRecorder,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        tracing::warn!(
+            "Calling: runsc --rootless --network=none run -bundle {} {}",
+            bundle.display(),
+            execution_id
+        );
+        let mut command = Command::new("runsc");
+        command
+            .arg("--rootless")
+            .arg("--network=none")
+            .arg("run")
+            .arg("-bundle")
+            .arg(bundle)
+            .arg(execution_id);
+
+        match run_with_limits(command, inputs, limits, Some(logs)).await {
+            Ok(output) => Ok(output),
+            Err(e @ ExecutionError::ResourceExceeded { .. }) => {
+                tracing::error!("killed container {execution_id}: {e}");
+                // Killing `runsc run` can leave the sandbox behind.
+                if let Err(delete_err) = Command::new("runsc")
+                    .arg("--rootless")
+                    .arg("delete")
+                    .arg("--force")
+                    .arg(execution_id)
+                    .output()
+                    .await
+                {
+                    tracing::error!("failed to delete container {execution_id}: {delete_err}");
+                }
+                Err(e)
+            }
+            Err(e) => {
+                tracing::error!("runsc command failed: {e}");
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ExecutionRuntime for ContainerRuntime {
+    async fn run(
+        &self,
+        content_id: &str,
+        inputs: &[u8],
+        limits: &ExecutionLimits,
+        logs: &ExecutionLogRecorder,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let execution_id = execution_id(content_id);
+        let bundle =
+            self.bundler
+                .execution_bundle(content_id, &execution_id, limits.memory_bytes)?;
+        let ran = self
+            .run_bundle(&bundle, &execution_id, inputs, limits, logs)
+            .await;
+        if let Err(e) = self
+            .bundler
+            .remove_execution_bundle(content_id, &execution_id)
+        {
+            tracing::error!("failed to remove the bundle of {execution_id}: {e}");
+        }
+        ran
+    }
+}
+
+/// The container id of one execution of the program `content_id`, of it
+/// alone, so executions of one program at once neither share a sandbox nor
+/// delete each other's.
+fn execution_id(content_id: &str) -> String {
+    static EXECUTIONS: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{content_id}-{}-{}",
+        std::process::id(),
+        EXECUTIONS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// The engine every WASM program is compiled and run with. Its epoch is
+/// advanced by a single background thread, which is what interrupts
+/// programs that run past their timeout.
+fn wasm_engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).expect("default wasmtime config is valid");
+        let ticker = engine.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        });
+        engine
+    })
+}
+
+struct WasmState {
+    input: Vec<u8>,
+    output: Vec<u8>,
+    limits: ExecutionLimits,
+    breach: Option<ResourceKind>,
+    logs: Option<ExecutionLogRecorder>,
+}
+
+impl ResourceLimiter for WasmState {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if desired as u64 > self.limits.memory_bytes {
+            self.breach = Some(ResourceKind::Memory);
+            return Err(wasmtime::Error::msg("memory limit exceeded"));
+        }
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> wasmtime::Result<bool> {
+        Ok(true)
+    }
+}
+
+fn guest_memory(caller: &mut Caller<'_, WasmState>) -> wasmtime::Result<wasmtime::Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => Err(wasmtime::Error::msg("program does not export its memory")),
+    }
+}
+
+fn guest_range(
+    ptr: i32,
+    len: usize,
+    memory_len: usize,
+) -> wasmtime::Result<std::ops::Range<usize>> {
+    let start = usize::try_from(ptr).map_err(|_| wasmtime::Error::msg("negative pointer"))?;
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= memory_len)
+        .ok_or(wasmtime::Error::msg("pointer out of bounds"))?;
+    Ok(start..end)
+}
+
+fn wasm_linker(engine: &Engine) -> wasmtime::Result<Linker<WasmState>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap("lasr", "input_len", |caller: Caller<'_, WasmState>| {
+        caller.data().input.len() as i32
+    })?;
+    linker.func_wrap(
+        "lasr",
+        "read_input",
+        |mut caller: Caller<'_, WasmState>, ptr: i32| -> wasmtime::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            let range = guest_range(ptr, state.input.len(), data.len())?;
+            data[range].copy_from_slice(&state.input);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "lasr",
+        "write_output",
+        |mut caller: Caller<'_, WasmState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            let len = usize::try_from(len).map_err(|_| wasmtime::Error::msg("negative length"))?;
+            let range = guest_range(ptr, len, data.len())?;
+            if (state.output.len() + len) as u64 > state.limits.output_bytes {
+                state.breach = Some(ResourceKind::OutputSize);
+                return Err(wasmtime::Error::msg("output limit exceeded"));
+            }
+            state.output.extend_from_slice(&data[range]);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "lasr",
+        "log",
+        |mut caller: Caller<'_, WasmState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            let len = usize::try_from(len).map_err(|_| wasmtime::Error::msg("negative length"))?;
+            let range = guest_range(ptr, len, data.len())?;
+            if let Some(logs) = &state.logs {
+                for line in String::from_utf8_lossy(&data[range]).lines() {
+                    logs.record(line);
+                }
+            }
+            Ok(())
+        },
+    )?;
+    Ok(linker)
+}
+
+fn breach_error(kind: ResourceKind, limits: &ExecutionLimits) -> ExecutionError {
+    let limit = match kind {
+        ResourceKind::WallClock => limits.timeout.as_millis() as u64,
+        ResourceKind::Memory => limits.memory_bytes,
+        ResourceKind::OutputSize => limits.output_bytes,
+    };
+    ExecutionError::ResourceExceeded { kind, limit }
+}
+
+fn run_wasm(
+    module: &[u8],
+    inputs: Vec<u8>,
+    limits: ExecutionLimits,
+    logs: Option<ExecutionLogRecorder>,
+) -> Result<Vec<u8>, ExecutionError> {
+    let engine = wasm_engine();
+    let wasm_error = |e: wasmtime::Error| {
+        ExecutionError::Io(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("wasm program failed: {e:#}"),
+        ))
+    };
+
+    let module = Module::new(engine, module).map_err(wasm_error)?;
+    let linker = wasm_linker(engine).map_err(wasm_error)?;
+    let mut store = Store::new(
+        engine,
+        WasmState {
+            input: inputs,
+            output: Vec::new(),
+            limits,
+            breach: None,
+            logs,
+        },
+    );
+    store.limiter(|state| state);
+    let ticks = limits
+        .timeout
+        .as_millis()
+        .div_ceil(EPOCH_TICK.as_millis())
+        .max(1);
+    store.set_epoch_deadline(u64::try_from(ticks).unwrap_or(u64::MAX));
+
+    let result = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.get_typed_func::<(), ()>(&mut store, "run"))
+        .and_then(|run| run.call(&mut store, ()));
+
+    match result {
+        Ok(()) => Ok(store.into_data().output),
+        Err(e) => {
+            if let Some(kind) = store.data().breach {
+                return Err(breach_error(kind, &limits));
+            }
+            if e.downcast_ref::<Trap>() == Some(&Trap::Interrupt) {
+                return Err(breach_error(ResourceKind::WallClock, &limits));
+            }
+            Err(wasm_error(e))
+        }
+    }
+}
+
+/// Runs programs packaged as WASM modules in wasmtime.
+#[derive(Debug, Clone)]
+pub struct WasmRuntime {
+    bundler: OciBundler<String, String>,
+}
+
+impl WasmRuntime {
+    pub fn new(bundler: OciBundler<String, String>) -> Self {
+        Self { bundler }
+    }
+
+    /// Runs a module, given as either WASM or WAT, with `inputs`, recording
+    /// anything it logs in `logs`.
+    pub async fn run_module(
+        &self,
+        module: Vec<u8>,
+        inputs: &[u8],
+        limits: &ExecutionLimits,
+        logs: Option<&ExecutionLogRecorder>,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let inputs = inputs.to_vec();
+        let limits = *limits;
+        let logs = logs.cloned();
+        tokio::task::spawn_blocking(move || run_wasm(&module, inputs, limits, logs))
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+    }
+
+    fn module_path(&self, content_id: &str) -> std::io::Result<PathBuf> {
+        let package = self.bundler.get_package(content_id)?;
+        Ok(self.bundler.get_package_path(content_id).join(
+            package
+                .package_payload
+                .package_entrypoint
+                .trim_start_matches("./"),
+        ))
+    }
+}
+
+#[async_trait]
+impl ExecutionRuntime for WasmRuntime {
+    async fn run(
+        &self,
+        content_id: &str,
+        inputs: &[u8],
+        limits: &ExecutionLimits,
+        logs: &ExecutionLogRecorder,
+    ) -> Result<Vec<u8>, ExecutionError> {
+        let module = tokio::fs::read(self.module_path(content_id)?).await?;
+        self.run_module(module, inputs, limits, Some(logs)).await
+    }
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use super::*;
+    use crate::OciBundlerBuilder;
+    use oci_spec::runtime::Spec;
+
+    fn runtime() -> WasmRuntime {
+        let bundler: OciBundler<String, String> = OciBundlerBuilder::default()
+            .runtime("/usr/local/bin/runsc".to_string())
+            .base_images("./base_image".to_string())
+            .containers("./containers".to_string())
+            .payload_path("./payload".to_string())
+            .build()
+            .expect("failed to build bundler");
+        WasmRuntime::new(bundler)
+    }
+
+    fn limits() -> ExecutionLimits {
+        ExecutionLimits {
+            timeout: Duration::from_millis(200),
+            memory_bytes: 4 * 65536,
+            output_bytes: 1024,
+        }
+    }
+
+    const ECHO: &str = r#"
+        (module
+          (import "lasr" "input_len" (func $input_len (result i32)))
+          (import "lasr" "read_input" (func $read_input (param i32)))
+          (import "lasr" "write_output" (func $write_output (param i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "run")
+            (call $read_input (i32.const 0))
+            (call $write_output (i32.const 0) (call $input_len))))
+    "#;
+
+    #[test]
+    fn executions_of_one_program_run_from_bundles_of_their_own() {
+        let dir = std::env::temp_dir().join(format!("lasr-bundles-{}", std::process::id()));
+        let bundler: OciBundler<String, String> = OciBundlerBuilder::default()
+            .runtime("/usr/local/bin/runsc".to_string())
+            .base_images("./base_image".to_string())
+            .containers(dir.to_string_lossy().into_owned())
+            .payload_path("./payload".to_string())
+            .build()
+            .expect("failed to build bundler");
+        let container = dir.join("program");
+        std::fs::create_dir_all(container.join(OciBundler::<String, String>::CONTAINER_ROOT))
+            .unwrap();
+        let shared = serde_json::to_string(&Spec::default()).unwrap();
+        std::fs::write(container.join("config.json"), &shared).unwrap();
+
+        let (first, second) = (execution_id("program"), execution_id("program"));
+        assert_ne!(first, second);
+        let first_bundle = bundler.execution_bundle("program", &first, 1024).unwrap();
+        let second_bundle = bundler.execution_bundle("program", &second, 2048).unwrap();
+        assert_ne!(first_bundle, second_bundle);
+        let spec = |bundle: &Path| Spec::load(bundle.join("config.json")).unwrap();
+        let limit = |bundle: &Path| {
+            spec(bundle)
+                .linux()
+                .clone()
+                .and_then(|linux| linux.resources().clone())
+                .and_then(|resources| resources.memory().clone())
+                .and_then(|memory| memory.limit())
+        };
+        assert_eq!(limit(&first_bundle), Some(1024));
+        assert_eq!(limit(&second_bundle), Some(2048));
+        assert_eq!(
+            spec(&first_bundle).root().clone().unwrap().path(),
+            &container.join("rootfs").canonicalize().unwrap()
+        );
+        // The container's own spec is left as it was.
+        assert_eq!(
+            std::fs::read_to_string(container.join("config.json")).unwrap(),
+            shared
+        );
+
+        bundler.remove_execution_bundle("program", &first).unwrap();
+        assert!(!first_bundle.exists());
+        assert!(second_bundle.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn program_reads_inputs_and_writes_output() {
+        let output = runtime()
+            .run_module(ECHO.into(), b"{\"inputs\":\"test\"}", &limits(), None)
+            .await
+            .expect("echo program failed");
+
+        assert_eq!(output, b"{\"inputs\":\"test\"}");
+    }
+
+    #[tokio::test]
+    async fn spinning_program_is_interrupted_at_timeout() {
+        let spin = r#"(module (memory (export "memory") 1) (func (export "run") (loop (br 0))))"#;
+        let res = runtime()
+            .run_module(spin.into(), b"", &limits(), None)
+            .await;
+
+        assert!(matches!(
+            res,
+            Err(ExecutionError::ResourceExceeded {
+                kind: ResourceKind::WallClock,
+                limit: 200
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn growing_past_memory_limit_is_stopped() {
+        let grow = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "run") (drop (memory.grow (i32.const 16)))))
+        "#;
+        let res = runtime()
+            .run_module(grow.into(), b"", &limits(), None)
+            .await;
+
+        assert!(matches!(
+            res,
+            Err(ExecutionError::ResourceExceeded {
+                kind: ResourceKind::Memory,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn output_past_limit_is_stopped() {
+        let flood = r#"
+            (module
+              (import "lasr" "write_output" (func $write_output (param i32 i32)))
+              (memory (export "memory") 1)
+              (func (export "run") (call $write_output (i32.const 0) (i32.const 4096))))
+        "#;
+        let res = runtime()
+            .run_module(flood.into(), b"", &limits(), None)
+            .await;
+
+        assert!(matches!(
+            res,
+            Err(ExecutionError::ResourceExceeded {
+                kind: ResourceKind::OutputSize,
+                limit: 1024
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn ambient_host_access_is_not_linked() {
+        let wasi = r#"
+            (module
+              (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (func (export "run")))
+        "#;
+        let res = runtime()
+            .run_module(wasi.into(), b"", &limits(), None)
+            .await;
+
+        assert!(matches!(res, Err(ExecutionError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn program_logs_through_the_host() {
+        let chatty = r#"
+            (module
+              (import "lasr" "log" (func $log (param i32 i32)))
+              (import "lasr" "write_output" (func $write_output (param i32 i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "starting\nstill going")
+              (data (i32.const 32) "{}")
+              (func (export "run")
+                (call $log (i32.const 0) (i32.const 20))
+                (call $write_output (i32.const 32) (i32.const 2))))
+        "#;
+        let logs = crate::ExecutionLogs::default();
+        let recorder = logs.recorder("0xaaaa", Some("0x01".to_string()));
+        let output = runtime()
+            .run_module(chatty.into(), b"", &limits(), Some(&recorder))
+            .await
+            .expect("chatty program failed");
+
+        assert_eq!(output, b"{}");
+        let lines: Vec<String> = recorder.lines().into_iter().map(|l| l.line).collect();
+        assert_eq!(lines, vec!["starting", "still going"]);
+    }
+
+    #[test]
+    fn runtime_names_round_trip() {
+        for runtime in [ProgramRuntime::Container, ProgramRuntime::Wasm] {
+            assert_eq!(
+                runtime.to_string().parse::<ProgramRuntime>().unwrap(),
+                runtime
+            );
+        }
+        assert!("python".parse::<ProgramRuntime>().is_err());
+    }
+}
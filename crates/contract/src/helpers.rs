@@ -5,25 +5,23 @@ pub fn create_program_id(
     content_id: String,
     transaction: &Transaction,
 ) -> Result<Address, Box<dyn std::error::Error + Send>> {
-    let pubkey = transaction
-        .sig()
-        .map_err(|e| {
-            Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                e.to_string(),
-            )) as Box<dyn std::error::Error + Send>
-        })?
-        .recover(&transaction.hash())
-        .map_err(|e| {
-            Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                e.to_string(),
-            )) as Box<dyn std::error::Error + Send>
-        })?;
+    let pubkey = transaction.recover().map_err(|e| {
+        Box::new(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            e.to_string(),
+        )) as Box<dyn std::error::Error + Send>
+    })?;
 
     let mut hasher = Keccak256::new();
     hasher.update(content_id.clone());
-    hasher.update(pubkey.to_string());
+    // Hashed as addresses were once displayed, the first and last four hex
+    // digits, so programs keep the ids they were registered under.
+    let owner = pubkey.to_full_string();
+    hasher.update(format!(
+        "0x{}...{}",
+        &owner[2..6],
+        &owner[owner.len() - 4..]
+    ));
     let hash = hasher.finalize();
     let mut addr_bytes = [0u8; 20];
     addr_bytes.copy_from_slice(&hash[..20]);
@@ -0,0 +1,149 @@
+//! Backfills of settlement events over a range of blocks, started by an
+//! operator.
+//!
+//! Deposits in blocks the listener was misconfigured for are never credited.
+//! A backfill scans the range again for every event watched, and hands the
+//! logs on through the same delivery as any other, so events delivered
+//! already are skipped, and bridge ins credited already are not applied
+//! twice by the batcher. Its progress is saved next to the checkpoint once
+//! each range is handed on, so one interrupted by a restart carries on from
+//! where it got to.
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::EoServerError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackfillStatus {
+    #[default]
+    Running,
+    Completed,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillProgress {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub status: BackfillStatus,
+    /// The next block to scan for each event watched, by cursor key. Empty
+    /// until the backfill starts scanning.
+    pub cursors: BTreeMap<String, u64>,
+    /// Events handed on that had not been delivered before.
+    pub applied: u64,
+    /// Events dropped as delivered already.
+    pub skipped: u64,
+}
+
+impl BackfillProgress {
+    pub fn new(from_block: u64, to_block: u64) -> Self {
+        Self {
+            from_block,
+            to_block,
+            ..Default::default()
+        }
+    }
+
+    /// The highest block every event has been scanned through.
+    pub fn scanned_through(&self) -> Option<u64> {
+        self.cursors.values().min()?.checked_sub(1)
+    }
+
+    /// The progress saved at `path`, or `None` if none was saved there.
+    pub fn load(path: &Path) -> Result<Option<Self>, EoServerError> {
+        let mut file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(EoServerError::Other(e.to_string())),
+        };
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)
+            .map_err(|e| EoServerError::Other(e.to_string()))?;
+        bincode::deserialize(&buf)
+            .map(Some)
+            .map_err(|e| EoServerError::Other(e.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), EoServerError> {
+        let bytes = bincode::serialize(self).map_err(|e| EoServerError::Other(e.to_string()))?;
+        let partial = path.with_extension("partial");
+        let mut file =
+            std::fs::File::create(&partial).map_err(|e| EoServerError::Other(e.to_string()))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| EoServerError::Other(e.to_string()))?;
+        std::fs::rename(&partial, path).map_err(|e| EoServerError::Other(e.to_string()))
+    }
+}
+
+/// The backfill, shared between the listener running it and whatever
+/// starts it.
+#[derive(Clone, Debug, Default)]
+pub struct SettlementBackfill {
+    progress: Arc<Mutex<Option<BackfillProgress>>>,
+}
+
+impl SettlementBackfill {
+    /// Starts a backfill of the blocks `from_block` through `to_block`,
+    /// unless one is running already.
+    pub fn start(&self, from_block: u64, to_block: u64) -> Result<BackfillProgress, EoServerError> {
+        if from_block > to_block {
+            return Err(EoServerError::Other(format!(
+                "backfill from block {from_block} ends before it starts, at block {to_block}"
+            )));
+        }
+        let mut progress = self.progress.lock().unwrap();
+        if let Some(running) = progress
+            .as_ref()
+            .filter(|progress| progress.status == BackfillStatus::Running)
+        {
+            return Err(EoServerError::Other(format!(
+                "a backfill of blocks {} to {} is running already",
+                running.from_block, running.to_block
+            )));
+        }
+        let started = BackfillProgress::new(from_block, to_block);
+        *progress = Some(started.clone());
+        Ok(started)
+    }
+
+    /// The last backfill started, whether it is still running or not.
+    pub fn progress(&self) -> Option<BackfillProgress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.progress
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|progress| progress.status == BackfillStatus::Running)
+    }
+
+    pub(crate) fn update(&self, progress: BackfillProgress) {
+        *self.progress.lock().unwrap() = Some(progress);
+    }
+}
+
+#[cfg(test)]
+mod backfill_tests {
+    use super::*;
+
+    #[test]
+    fn one_backfill_runs_at_a_time() {
+        let backfill = SettlementBackfill::default();
+        assert!(backfill.start(10, 5).is_err());
+        assert!(backfill.start(5, 10).is_ok());
+        assert!(backfill.start(20, 30).is_err());
+
+        let mut progress = backfill.progress().unwrap();
+        progress.status = BackfillStatus::Completed;
+        backfill.update(progress);
+        assert_eq!(backfill.start(20, 30).unwrap().from_block, 20);
+    }
+}
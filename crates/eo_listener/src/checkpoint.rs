@@ -0,0 +1,369 @@
+//! Where the listener resumes after a restart.
+//!
+//! Logs are fetched for each contract event from a cursor, in ranges of at
+//! most `max_range` blocks so a listener that fell behind catches up without
+//! asking the provider for more than it serves at once. The cursors are saved,
+//! along with the last block every event has been processed up to, after each
+//! range is handed on. A restart resumes from there, so at most the range in
+//! flight when the listener stopped is delivered twice, and duplicates are
+//! dropped by the transaction hashes bridge ins are applied under.
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use web3::{
+    transports::Http,
+    types::{BlockNumber, Filter, FilterBuilder, Log, H160, H256},
+    Web3,
+};
+
+use crate::EoServerError;
+
+/// Blocks per log query, unless configured.
+pub const DEFAULT_LOG_RANGE: u64 = 1000;
+
+/// Source of the logs a cursor scans.
+pub trait LogProvider {
+    fn logs(&self, filter: Filter) -> BoxFuture<'_, Result<Vec<Log>, EoServerError>>;
+}
+
+impl LogProvider for Web3<Http> {
+    fn logs(&self, filter: Filter) -> BoxFuture<'_, Result<Vec<Log>, EoServerError>> {
+        async move {
+            self.eth()
+                .logs(filter)
+                .await
+                .map_err(|e| EoServerError::Other(format!("Error attempting to get logs: {}", e)))
+        }
+        .boxed()
+    }
+}
+
+/// The logs of one contract event, scanned from `next_block` on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogCursor {
+    pub address: H160,
+    pub topics: Option<Vec<H256>>,
+    pub next_block: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogRange {
+    pub from: u64,
+    pub to: u64,
+    pub logs: Vec<Log>,
+}
+
+impl LogCursor {
+    pub fn new(address: H160, topics: Option<Vec<H256>>, next_block: u64) -> Self {
+        Self {
+            address,
+            topics,
+            next_block,
+        }
+    }
+
+    /// Identifies the cursor in a checkpoint.
+    pub fn key(&self) -> String {
+        let topic = self
+            .topics
+            .as_ref()
+            .and_then(|topics| topics.first())
+            .copied()
+            .unwrap_or_default();
+        format!("{:?}/{:?}", self.address, topic)
+    }
+
+    /// Fetches the logs of the next range up to `head`, moving the cursor
+    /// past it. A range the provider refuses is halved, and `max_range` is
+    /// kept at the size it last served.
+    pub async fn scan<P: LogProvider>(
+        &mut self,
+        provider: &P,
+        head: u64,
+        max_range: &mut u64,
+    ) -> Result<Option<LogRange>, EoServerError> {
+        if self.next_block > head {
+            return Ok(None);
+        }
+
+        let from = self.next_block;
+        let mut to = head.min(from + (*max_range).max(1) - 1);
+        loop {
+            let filter = FilterBuilder::default()
+                .from_block(BlockNumber::Number(from.into()))
+                .to_block(BlockNumber::Number(to.into()))
+                .address(vec![self.address])
+                .topics(self.topics.clone(), None, None, None)
+                .build();
+            match provider.logs(filter).await {
+                Ok(logs) => {
+                    self.next_block = to + 1;
+                    return Ok(Some(LogRange { from, to, logs }));
+                }
+                Err(e) if to > from => {
+                    *max_range = (to - from + 1) / 2;
+                    to = from + *max_range - 1;
+                    log::warn!(
+                        "log query refused, retrying with {} blocks: {}",
+                        max_range,
+                        e
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Moves the cursor back so `block` is scanned again.
+    pub fn rewind(&mut self, block: u64) {
+        self.next_block = self.next_block.min(block);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointBlock {
+    pub number: u64,
+    pub hash: H256,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The last block whose logs have been handed on for every cursor.
+    pub block: Option<CheckpointBlock>,
+    /// The next block of each cursor, by key.
+    pub cursors: BTreeMap<String, u64>,
+}
+
+impl Checkpoint {
+    pub fn new<'a>(cursors: impl IntoIterator<Item = &'a LogCursor>) -> Self {
+        Self {
+            block: None,
+            cursors: cursors
+                .into_iter()
+                .map(|cursor| (cursor.key(), cursor.next_block))
+                .collect(),
+        }
+    }
+
+    /// The highest block every cursor has scanned past.
+    pub fn processed_through(&self) -> Option<u64> {
+        self.cursors.values().min()?.checked_sub(1)
+    }
+
+    /// Moves `cursor` to where this checkpoint left it, if it is recorded.
+    pub fn restore(&self, cursor: &mut LogCursor) {
+        if let Some(next_block) = self.cursors.get(&cursor.key()) {
+            cursor.next_block = *next_block;
+        }
+    }
+
+    /// The checkpoint at `path`, or `None` if none was saved there.
+    pub fn load(path: &Path) -> Result<Option<Self>, EoServerError> {
+        let mut file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(EoServerError::Other(e.to_string())),
+        };
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)
+            .map_err(|e| EoServerError::Other(e.to_string()))?;
+        bincode::deserialize(&buf)
+            .map(Some)
+            .map_err(|e| EoServerError::Other(e.to_string()))
+    }
+
+    /// Writes the checkpoint next to `path` first and moves it into place,
+    /// so a listener stopped mid write still finds the previous one.
+    pub fn save(&self, path: &Path) -> Result<(), EoServerError> {
+        let bytes = bincode::serialize(self).map_err(|e| EoServerError::Other(e.to_string()))?;
+        let partial = path.with_extension("partial");
+        let mut file =
+            std::fs::File::create(&partial).map_err(|e| EoServerError::Other(e.to_string()))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| EoServerError::Other(e.to_string()))?;
+        std::fs::rename(&partial, path).map_err(|e| EoServerError::Other(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Mutex;
+    use web3::types::{Bytes, U64};
+
+    const CONTRACT: H160 = H160::repeat_byte(0xe0);
+
+    /// A chain with one deposit log every few blocks, which refuses
+    /// queries over more than `limit` blocks.
+    struct ScriptedProvider {
+        deposits: Vec<(u64, u64)>,
+        limit: u64,
+        queries: Mutex<Vec<(u64, u64)>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(head: u64, limit: u64) -> Self {
+            Self {
+                deposits: (1..=head)
+                    .filter(|number| number % 3 == 0)
+                    .map(|number| (number, number * 10))
+                    .collect(),
+                limit,
+                queries: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    fn block_of(filter: &Filter, field: &str) -> u64 {
+        let value = serde_json::to_value(filter).unwrap();
+        serde_json::from_value::<U64>(value[field].clone())
+            .unwrap()
+            .as_u64()
+    }
+
+    impl LogProvider for ScriptedProvider {
+        fn logs(&self, filter: Filter) -> BoxFuture<'_, Result<Vec<Log>, EoServerError>> {
+            let (from, to) = (block_of(&filter, "fromBlock"), block_of(&filter, "toBlock"));
+            self.queries.lock().unwrap().push((from, to));
+            let result = if to - from + 1 > self.limit {
+                Err(EoServerError::Other(
+                    "query exceeds block range limit".into(),
+                ))
+            } else {
+                Ok(self
+                    .deposits
+                    .iter()
+                    .filter(|(number, _)| (from..=to).contains(number))
+                    .map(|(number, bridge_event_id)| Log {
+                        address: CONTRACT,
+                        topics: Vec::new(),
+                        data: Bytes(web3::ethabi::encode(&[web3::ethabi::Token::Uint(
+                            (*bridge_event_id).into(),
+                        )])),
+                        block_hash: None,
+                        block_number: Some((*number).into()),
+                        transaction_hash: None,
+                        transaction_index: None,
+                        log_index: None,
+                        transaction_log_index: None,
+                        log_type: None,
+                        removed: None,
+                    })
+                    .collect())
+            };
+            async move { result }.boxed()
+        }
+    }
+
+    /// Credits each deposit under its bridge event id, the way the batcher
+    /// applies each bridge in once under its transaction hash.
+    #[derive(Default)]
+    struct Ledger {
+        applied: HashSet<u64>,
+        credits: HashMap<u64, u64>,
+        duplicates: usize,
+    }
+
+    impl Ledger {
+        fn deliver(&mut self, logs: &[Log]) {
+            for log in logs {
+                let id = web3::ethabi::decode(&[web3::ethabi::ParamType::Uint(256)], &log.data.0)
+                    .unwrap()[0]
+                    .clone()
+                    .into_uint()
+                    .unwrap()
+                    .as_u64();
+                if self.applied.insert(id) {
+                    *self.credits.entry(id).or_default() += 1;
+                } else {
+                    self.duplicates += 1;
+                }
+            }
+        }
+    }
+
+    /// Scans `ranges` ranges, handing each on and then checkpointing it,
+    /// except the last which is handed on when the listener is killed.
+    async fn run_until_killed(
+        provider: &ScriptedProvider,
+        path: &Path,
+        head: u64,
+        ranges: usize,
+        ledger: &mut Ledger,
+    ) {
+        let mut cursor = LogCursor::new(CONTRACT, None, 0);
+        if let Some(checkpoint) = Checkpoint::load(path).unwrap() {
+            checkpoint.restore(&mut cursor);
+        }
+        let mut max_range = provider.limit;
+        for scanned in 0..ranges {
+            let Some(range) = cursor.scan(provider, head, &mut max_range).await.unwrap() else {
+                break;
+            };
+            ledger.deliver(&range.logs);
+            if scanned + 1 < ranges {
+                Checkpoint::new([&cursor]).save(path).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_mid_range_credits_each_deposit_once() {
+        let dir = std::env::temp_dir().join(format!("eo_checkpoint_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.dat");
+        let _ = std::fs::remove_file(&path);
+
+        let head = 95;
+        let provider = ScriptedProvider::new(head, 10);
+        let mut ledger = Ledger::default();
+        run_until_killed(&provider, &path, head, 4, &mut ledger).await;
+        assert_eq!(
+            Checkpoint::load(&path)
+                .unwrap()
+                .unwrap()
+                .processed_through(),
+            Some(29)
+        );
+        run_until_killed(&provider, &path, head, usize::MAX, &mut ledger).await;
+
+        assert_eq!(ledger.credits.len(), provider.deposits.len());
+        assert!(ledger.credits.values().all(|credits| *credits == 1));
+        // The range in flight when the listener was killed came round again.
+        assert_eq!(ledger.duplicates, 4);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn refused_ranges_are_halved() {
+        let provider = ScriptedProvider::new(100, 25);
+        let mut cursor = LogCursor::new(CONTRACT, None, 1);
+        let mut max_range = 100;
+        let range = cursor
+            .scan(&provider, 100, &mut max_range)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((range.from, range.to), (1, 25));
+        assert_eq!(max_range, 25);
+        assert_eq!(cursor.next_block, 26);
+        assert_eq!(
+            *provider.queries.lock().unwrap(),
+            vec![(1, 100), (1, 50), (1, 25)]
+        );
+
+        // Nothing is fetched past the head.
+        cursor.next_block = 101;
+        assert!(cursor
+            .scan(&provider, 100, &mut max_range)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,592 @@
+//! Exactly once, in order delivery of settlement layer events.
+//!
+//! The same event can reach the listener more than once, when a queried range
+//! overlaps with logs already pushed or is scanned again after a reconnect or
+//! a restart, and events of one contract can arrive out of order. Each event
+//! is keyed by its block number, transaction hash and log index, and dropped
+//! if its key was seen already. Events carrying the sequence number their
+//! contract gave them, `bridgeEventId` or `blobEventId`, are delivered in
+//! that order: one arriving ahead of the next expected is held until the gap
+//! fills. Once a gap has been open for the timeout the blocks it may be in
+//! are backfilled, and if it is still open a timeout later it is given up on,
+//! releasing what was held behind it.
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use web3::types::{Log, H160, H256, U256};
+
+use crate::EventType;
+
+pub const DEFAULT_GAP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where an event is on the settlement layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventKey {
+    pub block_number: u64,
+    pub transaction_hash: H256,
+    pub log_index: U256,
+}
+
+impl EventKey {
+    /// The key of `log`, unless it is missing where it is.
+    pub fn of(log: &Log) -> Option<Self> {
+        Some(Self {
+            block_number: log.block_number?.as_u64(),
+            transaction_hash: log.transaction_hash?,
+            log_index: log.log_index?,
+        })
+    }
+
+    fn first_in(block_number: u64) -> Self {
+        Self {
+            block_number,
+            transaction_hash: H256::zero(),
+            log_index: U256::zero(),
+        }
+    }
+}
+
+/// A decoded event, with where it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyedEvent {
+    pub key: Option<EventKey>,
+    pub contract: H160,
+    pub topic: H256,
+    pub event_type: EventType,
+    pub log: web3::ethabi::Log,
+}
+
+impl KeyedEvent {
+    pub fn new(raw: &Log, event_type: EventType, log: web3::ethabi::Log) -> Self {
+        Self {
+            key: EventKey::of(raw),
+            contract: raw.address,
+            topic: raw.topics.first().copied().unwrap_or_default(),
+            event_type,
+            log,
+        }
+    }
+
+    /// The sequence number the contract gave the event, if it has one.
+    pub fn sequence(&self) -> Option<U256> {
+        self.log
+            .params
+            .iter()
+            .find(|param| param.name == "bridgeEventId" || param.name == "blobEventId")
+            .and_then(|param| param.value.clone().into_uint())
+    }
+
+    fn stream(&self) -> StreamId {
+        (self.contract, self.topic)
+    }
+}
+
+/// Blocks to scan again for the events missing from a contract's sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backfill {
+    pub contract: H160,
+    pub topic: H256,
+    pub from_block: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct DeliveryMetrics {
+    duplicates_dropped: AtomicU64,
+    gaps_healed: AtomicU64,
+    gaps_skipped: AtomicU64,
+    backfills: AtomicU64,
+}
+
+impl DeliveryMetrics {
+    /// Events dropped as delivered already.
+    pub fn duplicates_dropped(&self) -> u64 {
+        self.duplicates_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Gaps in a sequence that filled while events were held behind them.
+    pub fn gaps_healed(&self) -> u64 {
+        self.gaps_healed.load(Ordering::Relaxed)
+    }
+
+    /// Gaps given up on after their backfill did not fill them.
+    pub fn gaps_skipped(&self) -> u64 {
+        self.gaps_skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn backfills(&self) -> u64 {
+        self.backfills.load(Ordering::Relaxed)
+    }
+
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A contract and the topic of one of its events.
+type StreamId = (H160, H256);
+
+/// An event's stream and sequence number.
+type Sequenced = (StreamId, U256);
+
+/// The events of one contract with one topic.
+#[derive(Clone, Debug, Default)]
+struct Stream {
+    /// The first sequence number delivered.
+    first: Option<U256>,
+    /// The sequence number delivered next.
+    next: Option<U256>,
+    /// The block of the last event delivered.
+    last_block: u64,
+    /// Events ahead of `next`, by sequence number.
+    held: BTreeMap<U256, KeyedEvent>,
+    /// Sequence numbers of gaps given up on, delivered if they turn up.
+    skipped: BTreeSet<U256>,
+    /// When the gap in front of `held` opened, or was last backfilled.
+    gap_since: Option<Instant>,
+    backfilled: bool,
+}
+
+impl Stream {
+    fn deliver(
+        &mut self,
+        event: KeyedEvent,
+        keys: &mut BTreeMap<EventKey, Option<Sequenced>>,
+        delivered: &mut Vec<KeyedEvent>,
+    ) {
+        let sequence = event.sequence();
+        if let Some(key) = event.key {
+            keys.insert(key, sequence.map(|sequence| (event.stream(), sequence)));
+            self.last_block = self.last_block.max(key.block_number);
+        }
+        if let Some(sequence) = sequence {
+            if self.next.map_or(true, |next| sequence >= next) {
+                self.next = Some(sequence + 1);
+            }
+        }
+        delivered.push(event);
+    }
+
+    /// Delivers the held events `next` has caught up with.
+    fn drain(
+        &mut self,
+        keys: &mut BTreeMap<EventKey, Option<Sequenced>>,
+        delivered: &mut Vec<KeyedEvent>,
+    ) {
+        while let Some(event) = self.next.and_then(|next| self.held.remove(&next)) {
+            self.deliver(event, keys, delivered);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EventDelivery {
+    gap_timeout: Duration,
+    /// Keys of the events delivered or held, with their sequence numbers.
+    keys: BTreeMap<EventKey, Option<Sequenced>>,
+    streams: BTreeMap<StreamId, Stream>,
+    metrics: Arc<DeliveryMetrics>,
+}
+
+impl Default for EventDelivery {
+    fn default() -> Self {
+        Self::new(DEFAULT_GAP_TIMEOUT)
+    }
+}
+
+impl EventDelivery {
+    pub fn new(gap_timeout: Duration) -> Self {
+        Self {
+            gap_timeout,
+            keys: BTreeMap::new(),
+            streams: BTreeMap::new(),
+            metrics: Arc::new(DeliveryMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<DeliveryMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Whether `log` might still need delivering: it is from `floor` or
+    /// later, and its key has not been seen. Checked before decoding, so
+    /// duplicates are not decoded again.
+    pub fn admit(&self, log: &Log, floor: u64) -> bool {
+        let Some(key) = EventKey::of(log) else {
+            return true;
+        };
+        if key.block_number < floor || self.keys.contains_key(&key) {
+            DeliveryMetrics::count(&self.metrics.duplicates_dropped);
+            return false;
+        }
+        true
+    }
+
+    /// The block of the last event delivered before the gap in the events
+    /// of `contract` with `topic`, if one is open.
+    pub fn gap_from(&self, contract: H160, topic: H256) -> Option<u64> {
+        self.streams
+            .get(&(contract, topic))
+            .filter(|stream| !stream.held.is_empty())
+            .map(|stream| stream.last_block)
+    }
+
+    /// Forgets the keys of events before `floor`, which are no longer
+    /// admitted anyway.
+    pub fn prune(&mut self, floor: u64) {
+        self.keys = self.keys.split_off(&EventKey::first_in(floor));
+    }
+
+    /// Takes events in whatever order they arrived, and returns those that
+    /// can be delivered, in order.
+    pub fn accept(&mut self, mut events: Vec<KeyedEvent>, now: Instant) -> Vec<KeyedEvent> {
+        events.sort_by_key(|event| (event.sequence(), event.key));
+        let mut delivered = Vec::new();
+        for event in events {
+            if let Some(key) = event.key {
+                if self.keys.contains_key(&key) {
+                    DeliveryMetrics::count(&self.metrics.duplicates_dropped);
+                    continue;
+                }
+            }
+            let stream = self.streams.entry(event.stream()).or_default();
+            let Some(sequence) = event.sequence() else {
+                stream.deliver(event, &mut self.keys, &mut delivered);
+                continue;
+            };
+            stream.first.get_or_insert(sequence);
+            let next = *stream.next.get_or_insert(sequence);
+            if sequence < next {
+                if stream.skipped.remove(&sequence) {
+                    log::warn!(
+                        "event {} of {:?} arrived after its gap was skipped",
+                        sequence,
+                        event.contract
+                    );
+                    stream.deliver(event, &mut self.keys, &mut delivered);
+                } else {
+                    DeliveryMetrics::count(&self.metrics.duplicates_dropped);
+                }
+            } else if sequence == next {
+                stream.deliver(event, &mut self.keys, &mut delivered);
+                stream.drain(&mut self.keys, &mut delivered);
+                if stream.held.is_empty() && stream.gap_since.take().is_some() {
+                    stream.backfilled = false;
+                    DeliveryMetrics::count(&self.metrics.gaps_healed);
+                }
+            } else if stream.held.contains_key(&sequence) {
+                DeliveryMetrics::count(&self.metrics.duplicates_dropped);
+            } else {
+                if let Some(key) = event.key {
+                    self.keys.insert(key, Some((event.stream(), sequence)));
+                }
+                stream.held.insert(sequence, event);
+                stream.gap_since.get_or_insert(now);
+            }
+        }
+        delivered
+    }
+
+    /// Takes events scanned again from blocks already scanned. Those from
+    /// before where their contract's sequence got to are delivered straight
+    /// away unless they were delivered already, while the rest are taken as
+    /// by `accept`.
+    pub fn accept_backfilled(
+        &mut self,
+        mut events: Vec<KeyedEvent>,
+        now: Instant,
+    ) -> Vec<KeyedEvent> {
+        events.sort_by_key(|event| (event.sequence(), event.key));
+        let mut delivered = Vec::new();
+        let mut current = Vec::new();
+        for event in events {
+            if let Some(key) = event.key {
+                if self.keys.contains_key(&key) {
+                    DeliveryMetrics::count(&self.metrics.duplicates_dropped);
+                    continue;
+                }
+            }
+            let stream = self.streams.entry(event.stream()).or_default();
+            match (event.sequence(), stream.first, stream.next) {
+                (Some(sequence), first, Some(next)) if sequence < next => {
+                    let delivered_before = first.is_some_and(|first| sequence >= first)
+                        && !stream.skipped.contains(&sequence);
+                    if delivered_before {
+                        DeliveryMetrics::count(&self.metrics.duplicates_dropped);
+                        continue;
+                    }
+                    stream.skipped.remove(&sequence);
+                    stream.deliver(event, &mut self.keys, &mut delivered);
+                }
+                _ => current.push(event),
+            }
+        }
+        delivered.extend(self.accept(current, now));
+        delivered
+    }
+
+    /// Acts on the gaps open for longer than the timeout. Those not
+    /// backfilled yet are returned for backfilling, and the events held
+    /// behind the first one backfilled already are released.
+    pub fn expired(
+        &mut self,
+        now: Instant,
+    ) -> (Vec<Backfill>, Option<(EventType, Vec<KeyedEvent>)>) {
+        let mut backfills = Vec::new();
+        let mut released = None;
+        for ((contract, topic), stream) in self.streams.iter_mut() {
+            let Some(since) = stream.gap_since else {
+                continue;
+            };
+            if now.saturating_duration_since(since) < self.gap_timeout {
+                continue;
+            }
+            if !stream.backfilled {
+                stream.backfilled = true;
+                stream.gap_since = Some(now);
+                backfills.push(Backfill {
+                    contract: *contract,
+                    topic: *topic,
+                    from_block: stream.last_block,
+                });
+                DeliveryMetrics::count(&self.metrics.backfills);
+            } else if released.is_none() {
+                let mut events = Vec::new();
+                while let Some(sequence) = stream.held.keys().next().copied() {
+                    let mut missing = stream.next.unwrap_or(sequence);
+                    if missing < sequence {
+                        log::warn!(
+                            "skipping events {} to {} of {:?}, still missing after a backfill",
+                            missing,
+                            sequence - 1,
+                            contract
+                        );
+                    }
+                    while missing < sequence {
+                        stream.skipped.insert(missing);
+                        missing += U256::one();
+                    }
+                    stream.next = Some(sequence);
+                    stream.drain(&mut self.keys, &mut events);
+                }
+                stream.gap_since = None;
+                stream.backfilled = false;
+                DeliveryMetrics::count(&self.metrics.gaps_skipped);
+                let event_type = events.first().map(|event| event.event_type.clone());
+                released = event_type.map(|event_type| (event_type, events));
+            }
+        }
+        (backfills, released)
+    }
+
+    /// Forgets the events from `block` on, which were retracted by a reorg,
+    /// so they are delivered again once the canonical chain is scanned.
+    pub fn rewind(&mut self, block: u64) {
+        let mut rewound: BTreeMap<StreamId, U256> = BTreeMap::new();
+        for (stream, sequence) in self
+            .keys
+            .split_off(&EventKey::first_in(block))
+            .into_values()
+            .flatten()
+        {
+            let first = rewound.entry(stream).or_insert(sequence);
+            *first = (*first).min(sequence);
+        }
+        for (id, stream) in self.streams.iter_mut() {
+            stream
+                .held
+                .retain(|_, event| event.key.map_or(true, |key| key.block_number < block));
+            if let Some(first) = rewound.get(id) {
+                stream.next = stream.next.map(|next| next.min(*first));
+            }
+            stream.last_block = stream.last_block.min(block.saturating_sub(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod delivery_tests {
+    use super::*;
+    use web3::ethabi::{LogParam, Token};
+
+    fn bridge_type() -> EventType {
+        EventType::Bridge(crate::get_abi().unwrap().event("Bridge").unwrap().clone())
+    }
+
+    fn event(contract: u8, sequence: u64) -> KeyedEvent {
+        KeyedEvent {
+            key: Some(EventKey {
+                block_number: sequence * 2,
+                transaction_hash: H256::from_low_u64_be(sequence),
+                log_index: U256::from(contract),
+            }),
+            contract: H160::repeat_byte(contract),
+            topic: H256::repeat_byte(1),
+            event_type: bridge_type(),
+            log: web3::ethabi::Log {
+                params: vec![LogParam {
+                    name: "bridgeEventId".to_string(),
+                    value: Token::Uint(sequence.into()),
+                }],
+            },
+        }
+    }
+
+    fn ids(events: &[KeyedEvent]) -> Vec<(H160, u64)> {
+        events
+            .iter()
+            .map(|event| (event.contract, event.sequence().unwrap().as_u64()))
+            .collect()
+    }
+
+    /// Shuffles the same way every run.
+    fn shuffle<T>(items: &mut [T], mut seed: u64) {
+        for i in (1..items.len()).rev() {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            items.swap(i, (seed >> 33) as usize % (i + 1));
+        }
+    }
+
+    #[test]
+    fn shuffled_and_duplicated_events_are_delivered_once_in_order() {
+        let mut stream: Vec<KeyedEvent> = (1..=40)
+            .flat_map(|sequence| [event(0xa, sequence), event(0xb, sequence)])
+            .collect();
+        let duplicates: Vec<KeyedEvent> = stream.iter().step_by(3).cloned().collect();
+        stream.extend(duplicates);
+        // The first event of each contract starts its sequence.
+        shuffle(&mut stream[2..], 7);
+
+        let mut delivery = EventDelivery::default();
+        let now = Instant::now();
+        let mut delivered = Vec::new();
+        for chunk in stream.chunks(5) {
+            delivered.extend(delivery.accept(chunk.to_vec(), now));
+        }
+
+        for contract in [0xa, 0xb] {
+            let contract = H160::repeat_byte(contract);
+            let sequences: Vec<u64> = ids(&delivered)
+                .into_iter()
+                .filter(|(from, _)| *from == contract)
+                .map(|(_, sequence)| sequence)
+                .collect();
+            assert_eq!(sequences, (1..=40).collect::<Vec<_>>());
+        }
+        assert_eq!(delivery.metrics().duplicates_dropped(), 27);
+        assert!(delivery.metrics().gaps_healed() > 0);
+
+        // Seen again after the fact, e.g. when a range is queried again.
+        assert!(delivery.accept(vec![event(0xa, 7)], now).is_empty());
+        let raw = Log {
+            block_number: Some(14.into()),
+            transaction_hash: Some(H256::from_low_u64_be(7)),
+            log_index: Some(U256::from(0xa)),
+            ..Default::default()
+        };
+        assert!(!delivery.admit(&raw, 0));
+    }
+
+    #[test]
+    fn a_gap_is_backfilled_then_skipped() {
+        let mut delivery = EventDelivery::new(Duration::from_secs(10));
+        let start = Instant::now();
+        let delivered = delivery.accept(vec![event(0xa, 1), event(0xa, 3)], start);
+        assert_eq!(ids(&delivered), vec![(H160::repeat_byte(0xa), 1)]);
+
+        let (backfills, released) = delivery.expired(start + Duration::from_secs(5));
+        assert!(backfills.is_empty() && released.is_none());
+
+        let (backfills, released) = delivery.expired(start + Duration::from_secs(10));
+        assert_eq!(
+            backfills,
+            vec![Backfill {
+                contract: H160::repeat_byte(0xa),
+                topic: H256::repeat_byte(1),
+                from_block: 2,
+            }]
+        );
+        assert!(released.is_none());
+
+        // The backfill did not find it, so what was held is released.
+        let (backfills, released) = delivery.expired(start + Duration::from_secs(20));
+        assert!(backfills.is_empty());
+        let (_, released) = released.expect("held events were not released");
+        assert_eq!(ids(&released), vec![(H160::repeat_byte(0xa), 3)]);
+        assert_eq!(delivery.metrics().gaps_skipped(), 1);
+
+        // If it does turn up, it is still delivered, and only once.
+        let late = delivery.accept(vec![event(0xa, 2), event(0xa, 2)], start);
+        assert_eq!(ids(&late), vec![(H160::repeat_byte(0xa), 2)]);
+        assert_eq!(delivery.metrics().gaps_healed(), 0);
+    }
+
+    #[test]
+    fn a_gap_filled_by_the_backfill_is_healed() {
+        let mut delivery = EventDelivery::new(Duration::from_secs(10));
+        let start = Instant::now();
+        delivery.accept(vec![event(0xa, 1), event(0xa, 4), event(0xa, 3)], start);
+        let (backfills, _) = delivery.expired(start + Duration::from_secs(10));
+        assert_eq!(backfills.len(), 1);
+
+        // The backfilled range has the missing event, and ones delivered.
+        let delivered = delivery.accept(
+            vec![event(0xa, 1), event(0xa, 2), event(0xa, 3)],
+            start + Duration::from_secs(11),
+        );
+        assert_eq!(ids(&delivered).len(), 3);
+        assert_eq!(delivery.metrics().gaps_healed(), 1);
+        assert_eq!(delivery.metrics().duplicates_dropped(), 2);
+    }
+
+    #[test]
+    fn backfilled_events_are_delivered_unless_delivered_before() {
+        let mut delivery = EventDelivery::default();
+        let now = Instant::now();
+        delivery.accept((4..=6).map(|s| event(0xa, s)).collect(), now);
+
+        // Events from before the sequence started, again with a different
+        // key, and after where it got to.
+        let mut again = event(0xa, 5);
+        again.key.as_mut().unwrap().log_index = U256::from(0xff);
+        let delivered = delivery.accept_backfilled(
+            vec![
+                event(0xa, 6),
+                event(0xa, 2),
+                again,
+                event(0xa, 7),
+                event(0xa, 1),
+            ],
+            now,
+        );
+        assert_eq!(
+            ids(&delivered),
+            [1, 2, 7]
+                .into_iter()
+                .map(|s| (H160::repeat_byte(0xa), s))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(delivery.metrics().duplicates_dropped(), 2);
+    }
+
+    #[test]
+    fn events_retracted_by_a_reorg_are_delivered_again() {
+        let mut delivery = EventDelivery::default();
+        let now = Instant::now();
+        let delivered = delivery.accept((1..=5).map(|s| event(0xa, s)).collect(), now);
+        assert_eq!(delivered.len(), 5);
+
+        // Blocks from 6 on were abandoned, taking events 3 to 5 with them.
+        delivery.rewind(6);
+        let delivered = delivery.accept((2..=5).map(|s| event(0xa, s)).collect(), now);
+        assert_eq!(
+            ids(&delivered),
+            (3..=5)
+                .map(|s| (H160::repeat_byte(0xa), s))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Which contracts and events the listener watches besides the Executable
+//! Oracle's own.
+//!
+//! Further settlement contracts are configured by address, with the events
+//! of the Executable Oracle ABI to watch on each. The set is validated when it
+//! is loaded, and can be swapped while the listener runs: each event added is
+//! backfilled from its configured block, but never from further back than
+//! the backfill limit, and each event removed stops being scanned.
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use web3::types::{H160, H256};
+
+use crate::{get_abi, EoServerError, EventType, LogCursor};
+
+/// Blocks before the latest a newly watched event is backfilled from, at
+/// most, unless configured.
+pub const DEFAULT_FILTER_BACKFILL_BLOCKS: u64 = 10_000;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ContractFilterConfig {
+    /// The EIP-55 checksummed address of the contract.
+    pub address: String,
+    /// Names of the events of the Executable Oracle ABI to watch, or all of
+    /// them if empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// The block to backfill the contract's events from.
+    #[serde(default)]
+    pub from_block: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EventFilterConfig {
+    pub contracts: Vec<ContractFilterConfig>,
+}
+
+/// One event of one contract.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchedEvent {
+    pub address: H160,
+    pub event_type: EventType,
+    pub from_block: Option<u64>,
+}
+
+impl WatchedEvent {
+    pub fn topic(&self) -> H256 {
+        match &self.event_type {
+            EventType::Bridge(event) | EventType::Settlement(event) => event.signature(),
+        }
+    }
+
+    pub fn cursor(&self, next_block: u64) -> LogCursor {
+        LogCursor::new(self.address, Some(vec![self.topic()]), next_block)
+    }
+
+    /// The key of the event's cursor.
+    pub fn key(&self) -> String {
+        self.cursor(0).key()
+    }
+}
+
+/// A validated [`EventFilterConfig`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventFilterSet {
+    pub watched: Vec<WatchedEvent>,
+}
+
+impl EventFilterConfig {
+    /// Reads the JSON `EO_EVENT_FILTERS`, or no further contracts if it is
+    /// unset.
+    pub fn from_env() -> Result<Self, EoServerError> {
+        match std::env::var("EO_EVENT_FILTERS") {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| EoServerError::Other(format!("invalid EO_EVENT_FILTERS: {e}"))),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Checks every address is checksummed and every event is one the
+    /// listener knows how to decode.
+    pub fn validate(&self) -> Result<EventFilterSet, EoServerError> {
+        let abi = get_abi()?;
+        let mut addresses = BTreeSet::new();
+        let mut watched = Vec::new();
+        for contract in &self.contracts {
+            let address = parse_checksummed(&contract.address)?;
+            if !addresses.insert(address) {
+                return Err(EoServerError::Other(format!(
+                    "contract {} is configured more than once",
+                    contract.address
+                )));
+            }
+            let names = if contract.events.is_empty() {
+                vec!["Bridge".to_string(), "BlobIndexSettled".to_string()]
+            } else {
+                contract.events.clone()
+            };
+            for name in names {
+                let event = abi.event(&name).map_err(|_| {
+                    EoServerError::Other(format!("{name} is not an event of the contract ABI"))
+                })?;
+                let event_type = match name.as_str() {
+                    "Bridge" => EventType::Bridge(event.clone()),
+                    "BlobIndexSettled" => EventType::Settlement(event.clone()),
+                    _ => {
+                        return Err(EoServerError::Other(format!(
+                            "{name} events are not handled by the listener"
+                        )))
+                    }
+                };
+                watched.push(WatchedEvent {
+                    address,
+                    event_type,
+                    from_block: contract.from_block,
+                });
+            }
+        }
+        Ok(EventFilterSet { watched })
+    }
+}
+
+/// The address `address` stands for, if it carries a valid EIP-55 checksum.
+pub fn parse_checksummed(address: &str) -> Result<H160, EoServerError> {
+    let invalid = || EoServerError::Other(format!("{address} is not a checksummed address"));
+    let hex = address.strip_prefix("0x").ok_or_else(invalid)?;
+    let parsed: H160 = hex.parse().map_err(|_| invalid())?;
+    if hex.len() != 40 || to_checksummed(&parsed) != address {
+        return Err(invalid());
+    }
+    Ok(parsed)
+}
+
+/// `address` with the EIP-55 checksum in the case of its letters.
+pub fn to_checksummed(address: &H160) -> String {
+    let lower = hex::encode(address.as_bytes());
+    let hash = Keccak256::digest(lower.as_bytes());
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            let nibble = (hash[index / 2] >> (if index % 2 == 0 { 4 } else { 0 })) & 0xf;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{checksummed}")
+}
+
+/// The cursor keys an [`EventFilters::reload`] started and stopped watching.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterReload {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// The filter set, shared between the listener and whatever reloads it.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilters {
+    current: Arc<Mutex<(u64, Arc<EventFilterSet>)>>,
+    unknown_events: Arc<AtomicU64>,
+}
+
+impl EventFilters {
+    pub fn new(set: EventFilterSet) -> Self {
+        Self {
+            current: Arc::new(Mutex::new((0, Arc::new(set)))),
+            unknown_events: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The set, and how many times it has been swapped.
+    pub fn current(&self) -> (u64, Arc<EventFilterSet>) {
+        let current = self.current.lock().unwrap();
+        (current.0, Arc::clone(&current.1))
+    }
+
+    /// Validates `config` and swaps it in for the current set.
+    pub fn reload(&self, config: &EventFilterConfig) -> Result<FilterReload, EoServerError> {
+        let set = config.validate()?;
+        let mut current = self.current.lock().unwrap();
+        let keys = |set: &EventFilterSet| -> BTreeSet<String> {
+            set.watched.iter().map(WatchedEvent::key).collect()
+        };
+        let (before, after) = (keys(&current.1), keys(&set));
+        *current = (current.0 + 1, Arc::new(set));
+        Ok(FilterReload {
+            added: after.difference(&before).cloned().collect(),
+            removed: before.difference(&after).cloned().collect(),
+        })
+    }
+
+    /// Logs matched by a filter that could not be decoded as the event
+    /// watched for.
+    pub fn unknown_events(&self) -> u64 {
+        self.unknown_events.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn count_unknown_event(&self) {
+        self.unknown_events.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod filters_tests {
+    use super::*;
+
+    const CONTRACT: &str = "0x5FbDB2315678afecb367f032d93F642f64180aa3";
+
+    fn config(address: &str, events: &[&str]) -> EventFilterConfig {
+        EventFilterConfig {
+            contracts: vec![ContractFilterConfig {
+                address: address.to_string(),
+                events: events.iter().map(|event| event.to_string()).collect(),
+                from_block: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn addresses_must_be_checksummed() {
+        assert!(parse_checksummed(CONTRACT).is_ok());
+        assert!(parse_checksummed(&CONTRACT.to_lowercase()).is_err());
+        assert!(parse_checksummed(&CONTRACT.replace('F', "f")).is_err());
+        assert!(parse_checksummed(&CONTRACT[2..]).is_err());
+        assert_eq!(
+            to_checksummed(&CONTRACT.to_lowercase()[2..].parse().unwrap()),
+            CONTRACT
+        );
+    }
+
+    #[test]
+    fn events_must_be_in_the_abi() {
+        let set = config(CONTRACT, &[]).validate().unwrap();
+        assert_eq!(set.watched.len(), 2);
+        assert!(matches!(set.watched[0].event_type, EventType::Bridge(_)));
+
+        let set = config(CONTRACT, &["BlobIndexSettled"]).validate().unwrap();
+        assert_eq!(set.watched.len(), 1);
+        assert!(matches!(
+            set.watched[0].event_type,
+            EventType::Settlement(_)
+        ));
+
+        assert!(config(CONTRACT, &["Withdraw"]).validate().is_err());
+    }
+
+    #[test]
+    fn reload_swaps_the_set_and_reports_the_difference() {
+        let filters = EventFilters::new(config(CONTRACT, &["Bridge"]).validate().unwrap());
+        let reload = filters
+            .reload(&config(CONTRACT, &["BlobIndexSettled"]))
+            .unwrap();
+        assert_eq!(reload.added.len(), 1);
+        assert_eq!(reload.removed.len(), 1);
+        let (version, set) = filters.current();
+        assert_eq!(version, 1);
+        assert!(matches!(
+            set.watched[0].event_type,
+            EventType::Settlement(_)
+        ));
+
+        // An invalid set is not swapped in.
+        assert!(filters.reload(&config("0x1234", &[])).is_err());
+        assert_eq!(filters.current().0, 1);
+    }
+}
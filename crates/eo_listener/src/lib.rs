@@ -1,14 +1,28 @@
 #![allow(unused)]
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{Read, Write};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+pub mod backfill;
+pub mod checkpoint;
+pub mod delivery;
 mod eo_contract_abi;
+pub mod filters;
+pub mod providers;
+pub mod reorg;
+pub mod subscription;
+pub use crate::backfill::*;
+pub use crate::checkpoint::*;
+pub use crate::delivery::*;
 use crate::eo_contract_abi::EO_CONTRACT_ABI_JSON;
+pub use crate::filters::*;
+pub use crate::providers::*;
+pub use crate::reorg::*;
+pub use crate::subscription::*;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
-use web3::types::U64;
+use web3::types::{U256, U64};
 use web3::{
     contract::{
         tokens::{Detokenize, Tokenize},
@@ -80,7 +94,7 @@ pub enum Event {
     },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
     Bridge(web3::ethabi::Event),
     Settlement(web3::ethabi::Event),
@@ -140,6 +154,8 @@ impl From<String> for ContractAddress {
 #[derive(Builder, Debug, Clone)]
 pub struct EoServer {
     web3: Web3<Http>,
+    /// Where blocks and logs are read from, failing over between providers.
+    provider: FailoverProvider,
     eo_address: EoAddress,
     block_time: Duration,
     bridge_processed_blocks: BTreeSet<U64>,
@@ -147,13 +163,58 @@ pub struct EoServer {
     contract: web3::contract::Contract<Http>,
     bridge_topic: Option<Vec<H256>>,
     blob_settled_topic: Option<Vec<H256>>,
-    blob_settled_filter: Filter,
-    bridge_filter: Filter,
+    /// The next block to scan for bridge logs.
     current_bridge_filter_block: U64,
+    /// The next block to scan for settlement logs.
     current_blob_settlement_filter_block: U64,
     blob_settled_event: web3::ethabi::Event,
     bridge_event: web3::ethabi::Event,
     path: std::path::PathBuf,
+    #[builder(default)]
+    reorg_tracker: ReorgTracker,
+    /// Most blocks asked for in one log query.
+    #[builder(default = "DEFAULT_LOG_RANGE")]
+    log_range: u64,
+    /// The checkpoint last saved.
+    #[builder(default)]
+    checkpoint: Checkpoint,
+    /// The latest block as of the last scan.
+    #[builder(default)]
+    latest_block: u64,
+    /// Where logs are pushed from, in subscription mode.
+    #[builder(default)]
+    subscription: Option<Subscription>,
+    /// One past the head when the subscription was made. Blocks before it
+    /// are queried, and the cursors follow new heads once they reach it.
+    #[builder(setter(skip))]
+    synced_from: Option<u64>,
+    /// Drops the logs handled already, whether pushed or queried, and puts
+    /// each contract's events in order.
+    #[builder(default)]
+    delivery: EventDelivery,
+    /// The backfill an operator started, if any.
+    #[builder(default)]
+    backfill: SettlementBackfill,
+    /// The backfill progress last saved.
+    #[builder(setter(skip))]
+    backfill_saved: Option<BackfillProgress>,
+    /// Whether the backfill scans on the next call, as it takes turns with
+    /// the events being watched.
+    #[builder(setter(skip))]
+    backfill_turn: bool,
+    /// Further contracts and events to watch, from configuration.
+    #[builder(default)]
+    event_filters: EventFilters,
+    /// Most blocks back from the latest a newly watched event is
+    /// backfilled from.
+    #[builder(default = "DEFAULT_FILTER_BACKFILL_BLOCKS")]
+    backfill_blocks: u64,
+    /// The version of the filter set `watched` follows.
+    #[builder(setter(skip))]
+    filters_version: Option<u64>,
+    /// The cursors of the events watched from the filter set, by key.
+    #[builder(setter(skip))]
+    watched: BTreeMap<String, (EventType, LogCursor)>,
 }
 
 impl EoServer {
@@ -182,131 +243,664 @@ impl EoServer {
         self.run_loop().await
     }
 
-    pub async fn next(&mut self) -> EventLogResult {
-        let log_handler = log_handler!();
-        tokio::select!(
-            blob_settled_logs = self.web3.eth().logs(
-                self.blob_settled_filter.clone()
-            ) => {
-                let log_result = self.process_logs(
-                    EventType::Settlement(
-                        self.blob_settled_event.clone()
-                    ), blob_settled_logs,
-                    log_handler,
-                ).await.map_err(|e| Web3Error::from(e.to_string()));
+    /// Requests, errors and switches of the settlement layer providers.
+    pub fn provider_metrics(&self) -> std::sync::Arc<ProviderMetrics> {
+        self.provider.metrics()
+    }
 
-                return EventLogResult {
-                    event_type: EventType::Settlement(self.blob_settled_event.clone()),
-                    log_result
-                };
-            },
-            bridge_logs = self.web3.eth().logs(
-                self.bridge_filter.clone()
-            ) => {
-                let log_result = self.process_logs(
-                    EventType::Bridge(
-                        self.bridge_event.clone()
-                    ), bridge_logs,
-                    log_handler,
-                ).await.map_err(|e| Web3Error::from(e.to_string()));
+    pub async fn check_provider_health(&self) {
+        self.provider.check_health().await
+    }
+
+    /// Duplicates dropped and gaps healed by the delivery of events.
+    pub fn delivery_metrics(&self) -> std::sync::Arc<DeliveryMetrics> {
+        self.delivery.metrics()
+    }
+
+    /// The next log pushed by the subscription, if it is live, or otherwise
+    /// the next range of logs for whichever event is furthest behind, so all
+    /// of them catch up together.
+    pub async fn next(&mut self) -> EventLogResult {
+        if let Err(e) = self.apply_event_filters().await {
+            log::warn!("unable to apply the event filters: {}", e);
+        }
+        if let Some(released) = self.next_released() {
+            return released;
+        }
+        if let Some(backfilled) = self.next_backfilled().await {
+            return backfilled;
+        }
+        if let Some(pushed) = self.next_pushed().await {
+            return pushed;
+        }
+        let (event_type, cursor) = match self.cursors() {
+            Ok(cursors) => cursors
+                .into_iter()
+                .min_by_key(|(_, cursor)| cursor.next_block)
+                .expect("the Executable Oracle's events are always watched"),
+            Err(e) => {
                 return EventLogResult {
                     event_type: EventType::Bridge(self.bridge_event.clone()),
-                    log_result
-                };
-            },
-        )
+                    log_result: Err(Web3Error::from(e.to_string())),
+                }
+            }
+        };
+        let log_result = self
+            .scan(event_type.clone(), cursor)
+            .await
+            .map_err(|e| Web3Error::from(e.to_string()));
+        EventLogResult {
+            event_type,
+            log_result,
+        }
     }
 
-    async fn run_loop(&mut self) -> Result<(), web3::Error> {
-        let blob_settled_event = self
-            .contract
-            .abi()
-            .event("BlobIndexSettled")
-            .map_err(|e| Web3Error::from(e.to_string()))?
-            .clone();
-
-        let bridge_event = self
-            .contract
-            .abi()
-            .event("Bridge")
-            .map_err(|e| Web3Error::from(e.to_string()))?
-            .clone();
+    /// The filter set further contracts and events are watched from, which
+    /// can be reloaded while the listener runs.
+    pub fn event_filters(&self) -> EventFilters {
+        self.event_filters.clone()
+    }
 
-        loop {
-            let log_handler = log_handler!();
-            tokio::select!(
-                blob_settled_logs = self.web3.eth().logs(self.blob_settled_filter.clone()) => {
-                    self.process_logs(EventType::Settlement(blob_settled_event.clone()), blob_settled_logs, log_handler).await;
-                },
-                bridge_logs = self.web3.eth().logs(self.bridge_filter.clone()) => {
-                    self.process_logs(
-                        EventType::Bridge(
-                            bridge_event.clone()
-                        ), bridge_logs, log_handler
-                    ).await;
-                },
+    /// The backfill of settlement events an operator can start while the
+    /// listener runs.
+    pub fn settlement_backfill(&self) -> SettlementBackfill {
+        self.backfill.clone()
+    }
+
+    /// The next range of the running backfill, for whichever event is
+    /// furthest behind in it. Runs every other call, so the events being
+    /// watched keep up meanwhile.
+    async fn next_backfilled(&mut self) -> Option<EventLogResult> {
+        let mut progress = self
+            .backfill
+            .progress()
+            .filter(|progress| progress.status == BackfillStatus::Running)?;
+        self.backfill_turn = !self.backfill_turn;
+        if !self.backfill_turn {
+            return None;
+        }
+        let cursors = self.cursors().ok()?;
+        if progress.cursors.is_empty() {
+            log::info!(
+                "backfilling settlement events from block {} to block {}",
+                progress.from_block,
+                progress.to_block
+            );
+            progress.cursors = cursors
+                .iter()
+                .map(|(_, cursor)| (cursor.key(), progress.from_block))
+                .collect();
+        }
+        // Events no longer watched are not backfilled any further.
+        progress
+            .cursors
+            .retain(|key, _| cursors.iter().any(|(_, cursor)| cursor.key() == *key));
+
+        let next = cursors
+            .into_iter()
+            .filter_map(|(event_type, mut cursor)| {
+                cursor.next_block = *progress.cursors.get(&cursor.key())?;
+                Some((event_type, cursor))
+            })
+            .filter(|(_, cursor)| cursor.next_block <= progress.to_block)
+            .min_by_key(|(_, cursor)| cursor.next_block);
+        let Some((event_type, mut cursor)) = next else {
+            log::info!(
+                "backfill of blocks {} to {} completed: {} events applied, {} skipped",
+                progress.from_block,
+                progress.to_block,
+                progress.applied,
+                progress.skipped
             );
-            tokio::time::sleep(self.block_time).await;
+            progress.status = BackfillStatus::Completed;
+            self.backfill.update(progress);
+            return None;
+        };
+
+        let log_result = self
+            .backfill_range(&event_type, &mut cursor, &mut progress)
+            .await;
+        if log_result.is_ok() {
+            progress.cursors.insert(cursor.key(), cursor.next_block);
+        }
+        self.backfill.update(progress);
+        Some(EventLogResult {
+            event_type,
+            log_result: log_result.map_err(|e| Web3Error::from(e.to_string())),
+        })
+    }
+
+    /// Scans the next range of `cursor` for the backfill, counting the
+    /// events that had not been delivered before and those that had.
+    async fn backfill_range(
+        &mut self,
+        event_type: &EventType,
+        cursor: &mut LogCursor,
+        progress: &mut BackfillProgress,
+    ) -> Result<Vec<web3::ethabi::Log>, EoServerError> {
+        let latest = self.provider.latest_block_number().await?;
+        let Some(range) = cursor
+            .scan(
+                &self.provider,
+                progress.to_block.min(latest),
+                &mut self.log_range,
+            )
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        let metrics = self.delivery.metrics();
+        let dropped = metrics.duplicates_dropped();
+        // However far back they are, so no floor.
+        let unseen = range
+            .logs
+            .into_iter()
+            .filter(|log| self.delivery.admit(log, 0))
+            .collect();
+        let events = self.decode(event_type, unseen)?;
+        let decoded = events.len() as u64;
+        let dropped_undecoded = metrics.duplicates_dropped();
+        let events = self.delivery.accept_backfilled(events, Instant::now());
+
+        let skipped = metrics.duplicates_dropped() - dropped;
+        progress.skipped += skipped;
+        progress.applied += decoded - (skipped - (dropped_undecoded - dropped));
+        Ok(events.into_iter().map(|event| event.log).collect())
+    }
+
+    /// Follows the filter set if it was swapped since the last call. Events
+    /// added are backfilled from their configured block, or the backfill
+    /// limit if that is later, and events removed are no longer scanned.
+    async fn apply_event_filters(&mut self) -> Result<(), EoServerError> {
+        let (version, set) = self.event_filters.current();
+        if self.filters_version == Some(version) {
+            return Ok(());
+        }
+        let latest = self.provider.latest_block_number().await?;
+        let primary = [
+            self.cursor(&EventType::Bridge(self.bridge_event.clone()))?
+                .key(),
+            self.cursor(&EventType::Settlement(self.blob_settled_event.clone()))?
+                .key(),
+        ];
+
+        let mut watched = BTreeMap::new();
+        for event in set.watched.iter() {
+            let key = event.key();
+            if primary.contains(&key) || watched.contains_key(&key) {
+                continue;
+            }
+            let entry = match self.watched.remove(&key) {
+                Some(entry) => entry,
+                None => {
+                    let from = latest
+                        .saturating_sub(self.backfill_blocks)
+                        .max(event.from_block.unwrap_or_default());
+                    let mut cursor = event.cursor(from);
+                    // Resumes where it left off if watched before a restart.
+                    self.checkpoint.restore(&mut cursor);
+                    log::info!("watching {} from block {}", key, cursor.next_block);
+                    (event.event_type.clone(), cursor)
+                }
+            };
+            watched.insert(key, entry);
+        }
+        for key in self.watched.keys() {
+            log::info!("no longer watching {}", key);
+        }
+        self.watched = watched;
+        self.filters_version = Some(version);
+
+        // Subscribed again for the new set, once the events added catch up.
+        if let Some(subscription) = self.subscription.as_mut() {
+            subscription.reset();
         }
+        self.synced_from = None;
         Ok(())
     }
 
-    async fn process_logs<F>(
+    /// Moves the cursors of the events with a gap open too long back to
+    /// before it, so the missing events are queried again, and returns the
+    /// events released from behind a gap given up on, if any.
+    fn next_released(&mut self) -> Option<EventLogResult> {
+        let (backfills, released) = self.delivery.expired(Instant::now());
+        for backfill in backfills {
+            let Ok(cursors) = self.cursors() else {
+                break;
+            };
+            for (event_type, mut cursor) in cursors {
+                if cursor.address == backfill.contract
+                    && cursor.topics.as_ref().and_then(|topics| topics.first())
+                        == Some(&backfill.topic)
+                {
+                    log::warn!(
+                        "events of {} missing, backfilling from block {}",
+                        cursor.key(),
+                        backfill.from_block
+                    );
+                    cursor.rewind(backfill.from_block);
+                    self.set_cursor(&event_type, &cursor);
+                }
+            }
+        }
+        let (event_type, events) = released?;
+        Some(EventLogResult {
+            event_type,
+            log_result: Ok(events.into_iter().map(|event| event.log).collect()),
+        })
+    }
+
+    /// Whether either event is more than one log query behind the latest
+    /// block, and should be scanned again without waiting for a new one.
+    pub fn is_catching_up(&self) -> bool {
+        self.behind() + self.log_range <= self.latest_block || self.backfill.is_running()
+    }
+
+    /// Whether logs are pushed by the subscription, which waits for them
+    /// itself, rather than queried.
+    pub fn is_live(&self) -> bool {
+        self.subscription
+            .as_ref()
+            .is_some_and(|subscription| subscription.is_connected())
+            && self
+                .synced_from
+                .is_some_and(|synced_from| self.behind() >= synced_from)
+    }
+
+    /// The next block of whichever cursor is furthest behind.
+    fn behind(&self) -> u64 {
+        self.watched
+            .values()
+            .map(|(_, cursor)| cursor.next_block)
+            .chain([
+                self.current_bridge_filter_block.as_u64(),
+                self.current_blob_settlement_filter_block.as_u64(),
+            ])
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Waits up to the block time for the subscription to push a log, once
+    /// the listener is subscribed and the cursors have caught up with the
+    /// head it was subscribed at. Returns `None` when logs should be queried
+    /// instead: while not subscribed, still catching up, or once the
+    /// subscription ends, so the outage is queried for straight away.
+    async fn next_pushed(&mut self) -> Option<EventLogResult> {
+        self.subscription.as_ref()?;
+        let filters = self.log_filters().ok()?;
+        if self.subscription.as_mut()?.connect(filters).await {
+            self.synced_from = None;
+        }
+        if !self.subscription.as_ref()?.is_connected() {
+            self.synced_from = None;
+            return None;
+        }
+        if self.synced_from.is_none() {
+            let head = self.provider.latest_block_number().await.ok()?;
+            self.latest_block = self.latest_block.max(head);
+            self.synced_from = Some(head + 1);
+            log::info!("subscribed to settlement layer events at block {}", head);
+        }
+        if !self.is_live() {
+            return None;
+        }
+
+        let bridge = EventType::Bridge(self.bridge_event.clone());
+        let event = self.subscription.as_mut()?.next(self.block_time).await;
+        let (event_type, logs) = match event {
+            None if !self.subscription.as_ref()?.is_connected() => {
+                self.synced_from = None;
+                return None;
+            }
+            None => (bridge, Vec::new()),
+            Some(SubscriptionEvent::Head(number)) => {
+                // Every log before the new head has been pushed, so the
+                // cursors move up to it and a restart resumes from there.
+                self.latest_block = self.latest_block.max(number);
+                self.current_bridge_filter_block =
+                    self.current_bridge_filter_block.max(number.into());
+                self.current_blob_settlement_filter_block =
+                    self.current_blob_settlement_filter_block.max(number.into());
+                for (_, cursor) in self.watched.values_mut() {
+                    cursor.next_block = cursor.next_block.max(number);
+                }
+                (bridge, Vec::new())
+            }
+            // Logs from abandoned blocks are retracted by the reorg check.
+            Some(SubscriptionEvent::Log(log)) if log.removed == Some(true) => (bridge, Vec::new()),
+            Some(SubscriptionEvent::Log(log)) => {
+                let watched = self.cursors().ok()?.into_iter().find(|(_, cursor)| {
+                    cursor.address == log.address
+                        && cursor.topics.as_ref().and_then(|topics| topics.first())
+                            == log.topics.first()
+                });
+                match watched {
+                    Some((event_type, _)) => (event_type, vec![log]),
+                    None => {
+                        self.unknown_event(&log, "it matches no watched event");
+                        (bridge, Vec::new())
+                    }
+                }
+            }
+        };
+        let log_result = self
+            .handle_logs(&event_type, logs)
+            .map_err(|e| Web3Error::from(e.to_string()));
+        Some(EventLogResult {
+            event_type,
+            log_result,
+        })
+    }
+
+    fn log_filters(&self) -> Result<Vec<Filter>, EoServerError> {
+        Ok(self
+            .cursors()?
+            .into_iter()
+            .map(|(_, cursor)| {
+                FilterBuilder::default()
+                    .address(vec![cursor.address])
+                    .topics(cursor.topics, None, None, None)
+                    .build()
+            })
+            .collect())
+    }
+
+    /// Checks the settlement layer for a reorg since the last call. If the
+    /// chain switched branches, the cursors are moved back to the fork point
+    /// so the canonical branch is processed again, and the events emitted on
+    /// the abandoned branch are returned for retraction.
+    pub async fn check_for_reorg(&mut self) -> Result<Option<Reorg>, EoServerError> {
+        let Some(reorg) = self.reorg_tracker.advance(&self.provider).await? else {
+            return Ok(None);
+        };
+        log::warn!(
+            "settlement layer reorg: fork point {:?}, {} blocks abandoned, {} events retracted",
+            reorg.fork_point,
+            reorg.abandoned.len(),
+            reorg.retracted.len()
+        );
+
+        let above = U64::from(reorg.fork_point.map_or(0, |fork_point| fork_point + 1));
+        self.bridge_processed_blocks.retain(|block| *block < above);
+        self.settled_processed_blocks.retain(|block| *block < above);
+        self.current_bridge_filter_block = self.current_bridge_filter_block.min(above);
+        self.current_blob_settlement_filter_block =
+            self.current_blob_settlement_filter_block.min(above);
+        for (_, cursor) in self.watched.values_mut() {
+            cursor.rewind(above.as_u64());
+        }
+        self.delivery.rewind(above.as_u64());
+
+        Ok(Some(reorg))
+    }
+
+    async fn run_loop(&mut self) -> Result<(), web3::Error> {
+        loop {
+            self.next().await;
+            self.save_checkpoint()
+                .await
+                .map_err(|e| Web3Error::from(e.to_string()))?;
+            if !self.is_catching_up() && !self.is_live() {
+                tokio::time::sleep(self.block_time).await;
+            }
+        }
+        Ok(())
+    }
+
+    fn cursor(&self, event_type: &EventType) -> Result<LogCursor, EoServerError> {
+        let contract_address = self
+            .eo_address
+            .parse()
+            .map_err(|err| EoServerError::Other(err.to_string()))?;
+        Ok(match event_type {
+            EventType::Bridge(_) => LogCursor::new(
+                contract_address,
+                self.bridge_topic.clone(),
+                self.current_bridge_filter_block.as_u64(),
+            ),
+            EventType::Settlement(_) => LogCursor::new(
+                contract_address,
+                self.blob_settled_topic.clone(),
+                self.current_blob_settlement_filter_block.as_u64(),
+            ),
+        })
+    }
+
+    /// The cursors of every event watched, the Executable Oracle's first.
+    fn cursors(&self) -> Result<Vec<(EventType, LogCursor)>, EoServerError> {
+        let bridge = EventType::Bridge(self.bridge_event.clone());
+        let settlement = EventType::Settlement(self.blob_settled_event.clone());
+        let mut cursors = vec![
+            (bridge.clone(), self.cursor(&bridge)?),
+            (settlement.clone(), self.cursor(&settlement)?),
+        ];
+        cursors.extend(self.watched.values().cloned());
+        Ok(cursors)
+    }
+
+    fn set_cursor(&mut self, event_type: &EventType, cursor: &LogCursor) {
+        if let Some((_, watched)) = self.watched.get_mut(&cursor.key()) {
+            *watched = cursor.clone();
+            return;
+        }
+        match event_type {
+            EventType::Bridge(_) => self.current_bridge_filter_block = cursor.next_block.into(),
+            EventType::Settlement(_) => {
+                self.current_blob_settlement_filter_block = cursor.next_block.into()
+            }
+        }
+    }
+
+    async fn scan(
         &mut self,
         event_type: EventType,
-        logs: Result<Vec<Log>, Web3Error>,
-        handler: F,
-    ) -> Result<Vec<web3::ethabi::Log>, EoServerError>
-    where
-        F: FnOnce(Result<Vec<Log>, Web3Error>) -> Vec<Log>,
-    {
-        let block_number = self.web3.eth().block_number().await.map_err(|e| {
-            EoServerError::Other(format!("Error attempting to get block number: {}", e))
-        })?;
+        mut cursor: LogCursor,
+    ) -> Result<Vec<web3::ethabi::Log>, EoServerError> {
+        self.latest_block = self.provider.latest_block_number().await?;
+        let Some(range) = cursor
+            .scan(&self.provider, self.latest_block, &mut self.log_range)
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+        if !range.logs.is_empty() {
+            log::info!(
+                "discovered logs in blocks {} to {}: logs.len() = {}",
+                range.from,
+                range.to,
+                range.logs.len()
+            );
+        }
+
+        let logs = self.handle_logs(&event_type, range.logs)?;
+        self.set_cursor(&event_type, &cursor);
+        Ok(logs)
+    }
+
+    /// Handles the logs not handled already, whether they were pushed or
+    /// queried, and returns those that can be delivered in order. Logs
+    /// further behind the cursors than the confirmation depth were queried
+    /// by the time they are pushed, so are dropped too.
+    fn handle_logs(
+        &mut self,
+        event_type: &EventType,
+        logs: Vec<Log>,
+    ) -> Result<Vec<web3::ethabi::Log>, EoServerError> {
+        let floor = self
+            .behind()
+            .saturating_sub(self.reorg_tracker.confirmations());
+        self.delivery.prune(floor);
+        let unseen = logs
+            .into_iter()
+            .filter(|log| self.delivery.admit(log, floor))
+            .collect();
+
+        let events = self.decode(event_type, unseen)?;
+        Ok(self
+            .delivery
+            .accept(events, Instant::now())
+            .into_iter()
+            .map(|event| event.log)
+            .collect())
+    }
 
-        let events = handler(logs);
+    fn decode(
+        &mut self,
+        event_type: &EventType,
+        logs: Vec<Log>,
+    ) -> Result<Vec<KeyedEvent>, EoServerError> {
         match event_type {
-            EventType::Bridge(event_abi) => {
-                let bridge_log = self.handle_bridge_event(events, &event_abi);
-                if let Ok(logs) = &bridge_log {
-                    if logs.len() > 0 {
-                        log::info!("discovered logs: logs.len() = {}", logs.len());
-                        self.increment_bridge_filter(block_number, true);
-                    } else {
-                        self.increment_bridge_filter(block_number, false);
-                    }
+            EventType::Bridge(event_abi) => self.handle_bridge_event(logs, event_abi),
+            EventType::Settlement(event_abi) => self.handle_settlement_event(logs, event_abi),
+        }
+    }
+
+    fn checkpoint_path(&self) -> std::path::PathBuf {
+        self.path.with_extension("checkpoint")
+    }
+
+    fn backfill_path(&self) -> std::path::PathBuf {
+        self.path.with_extension("backfill")
+    }
+
+    /// Moves both cursors to the saved checkpoint, if there is one. If the
+    /// last block it processed has since been reorged out, the cursors are
+    /// moved back by the confirmation depth so the events that replaced it
+    /// are picked up. A backfill that was running carries on from where it
+    /// got to.
+    pub async fn resume(&mut self) -> Result<(), EoServerError> {
+        if let Some(progress) = BackfillProgress::load(&self.backfill_path())? {
+            if progress.status == BackfillStatus::Running {
+                log::info!(
+                    "resuming the backfill of blocks {} to {}, scanned through block {:?}",
+                    progress.from_block,
+                    progress.to_block,
+                    progress.scanned_through()
+                );
+            }
+            self.backfill.update(progress.clone());
+            self.backfill_saved = Some(progress);
+        }
+
+        let Some(checkpoint) = Checkpoint::load(&self.checkpoint_path())? else {
+            return Ok(());
+        };
+        let bridge = EventType::Bridge(self.bridge_event.clone());
+        let settlement = EventType::Settlement(self.blob_settled_event.clone());
+        let mut cursors = [self.cursor(&bridge)?, self.cursor(&settlement)?];
+        for cursor in cursors.iter_mut() {
+            checkpoint.restore(cursor);
+        }
+
+        if let Some(block) = checkpoint.block {
+            let canonical = self.provider.block(block.number).await?;
+            if canonical.map(|canonical| canonical.hash) != Some(block.hash) {
+                let from = block
+                    .number
+                    .saturating_sub(self.reorg_tracker.confirmations());
+                log::warn!(
+                    "checkpointed block {} was reorged out, resuming from block {}",
+                    block.number,
+                    from
+                );
+                for cursor in cursors.iter_mut() {
+                    cursor.rewind(from);
                 }
-                return bridge_log;
             }
-            EventType::Settlement(event_abi) => {
-                let blob_log = self.handle_settlement_event(events, &event_abi);
-                if let Ok(logs) = &blob_log {
-                    if logs.len() > 0 {
-                        self.increment_blob_filter(block_number, true);
-                    } else {
-                        self.increment_blob_filter(block_number, false);
-                    }
+        }
+
+        let [bridge_cursor, settlement_cursor] = cursors;
+        self.set_cursor(&bridge, &bridge_cursor);
+        self.set_cursor(&settlement, &settlement_cursor);
+        log::info!(
+            "resuming from checkpoint: bridge logs from block {}, settlement logs from block {}",
+            bridge_cursor.next_block,
+            settlement_cursor.next_block
+        );
+        self.checkpoint = checkpoint;
+        Ok(())
+    }
+
+    /// Saves where both cursors are, if either moved since the last save,
+    /// and the backfill's progress. Called once the logs scanned have been
+    /// handed on.
+    pub async fn save_checkpoint(&mut self) -> Result<(), EoServerError> {
+        // Events held behind a gap are not handed on yet, so a restart scans
+        // them again.
+        let cursors: Vec<LogCursor> = self
+            .cursors()?
+            .into_iter()
+            .map(|(_, mut cursor)| {
+                let topic = cursor.topics.as_ref().and_then(|topics| topics.first());
+                if let Some(from) =
+                    topic.and_then(|topic| self.delivery.gap_from(cursor.address, *topic))
+                {
+                    cursor.rewind(from);
                 }
-                return blob_log;
+                cursor
+            })
+            .collect();
+        let mut checkpoint = Checkpoint::new(cursors.iter());
+
+        let backfill = self.backfill.progress();
+        if backfill != self.backfill_saved {
+            if let Some(progress) = backfill.as_ref() {
+                progress.save(&self.backfill_path())?;
             }
+            self.backfill_saved = backfill;
+        }
+        if checkpoint.cursors == self.checkpoint.cursors {
+            return Ok(());
         }
 
-        Ok(vec![])
+        if let Some(number) = checkpoint.processed_through() {
+            checkpoint.block = self
+                .provider
+                .block(number)
+                .await?
+                .map(|block| CheckpointBlock {
+                    number,
+                    hash: block.hash,
+                });
+        }
+        checkpoint.save(&self.checkpoint_path())?;
+        self.checkpoint = checkpoint;
+        Ok(())
     }
 
     fn handle_bridge_event(
         &mut self,
         events: Vec<Log>,
         event_abi: &web3::ethabi::Event,
-    ) -> Result<Vec<web3::ethabi::Log>, EoServerError> {
+    ) -> Result<Vec<KeyedEvent>, EoServerError> {
         let mut parsed_logs = Vec::new();
         let mut blocks_processed = Vec::new();
         for event in events {
             let block_number = event
                 .block_number
                 .ok_or(EoServerError::Other("Log missing block number".to_string()))?;
-            let log = self.parse_bridge_event(event, event_abi)?;
-            parsed_logs.push(log);
+            let block_hash = event
+                .block_hash
+                .ok_or(EoServerError::Other("Log missing block hash".to_string()))?;
+            let log = match self.parse_bridge_event(event.clone(), event_abi) {
+                Ok(log) => log,
+                Err(e) => {
+                    self.unknown_event(&event, &e.to_string());
+                    continue;
+                }
+            };
+            self.reorg_tracker.record(
+                block_number.as_u64(),
+                block_hash,
+                EventType::Bridge(event_abi.clone()),
+                log.clone(),
+            );
+            parsed_logs.push(KeyedEvent::new(
+                &event,
+                EventType::Bridge(event_abi.clone()),
+                log,
+            ));
             blocks_processed.push(block_number);
         }
         self.bridge_processed_blocks.extend(blocks_processed);
@@ -317,21 +911,52 @@ impl EoServer {
         &mut self,
         events: Vec<Log>,
         event_abi: &web3::ethabi::Event,
-    ) -> Result<Vec<web3::ethabi::Log>, EoServerError> {
+    ) -> Result<Vec<KeyedEvent>, EoServerError> {
         let mut parsed_logs = Vec::new();
         let mut blocks_processed = Vec::new();
         for event in events {
             let block_number = event
                 .block_number
                 .ok_or(EoServerError::Other("Log missing block number".to_string()))?;
-            let log = self.parse_settlement_event(event, event_abi)?;
-            parsed_logs.push(log);
+            let block_hash = event
+                .block_hash
+                .ok_or(EoServerError::Other("Log missing block hash".to_string()))?;
+            let log = match self.parse_settlement_event(event.clone(), event_abi) {
+                Ok(log) => log,
+                Err(e) => {
+                    self.unknown_event(&event, &e.to_string());
+                    continue;
+                }
+            };
+            self.reorg_tracker.record(
+                block_number.as_u64(),
+                block_hash,
+                EventType::Settlement(event_abi.clone()),
+                log.clone(),
+            );
+            parsed_logs.push(KeyedEvent::new(
+                &event,
+                EventType::Settlement(event_abi.clone()),
+                log,
+            ));
             blocks_processed.push(block_number);
         }
         self.settled_processed_blocks.extend(blocks_processed);
         Ok(parsed_logs)
     }
 
+    /// Skips a log that matched a filter but is not an event the listener
+    /// can decode, rather than failing the range it is in.
+    fn unknown_event(&self, log: &Log, reason: &str) {
+        log::warn!(
+            "skipping unknown event from {:?} in block {:?}, as {}",
+            log.address,
+            log.block_number,
+            reason
+        );
+        self.event_filters.count_unknown_event();
+    }
+
     fn parse_bridge_event(
         &self,
         event: Log,
@@ -361,75 +986,6 @@ impl EoServer {
         Ok(parsed_log)
     }
 
-    fn increment_bridge_filter(
-        &mut self,
-        block_number: U64,
-        log_discovered: bool,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let contract_address = self
-            .eo_address
-            .clone()
-            .parse()
-            .map_err(|err| EoServerError::Other(err.to_string()))?;
-
-        let default: U64 = U64::from(0);
-        let from_block = self
-            .bridge_processed_blocks
-            .last()
-            .unwrap_or_else(|| &default)
-            + U64::from(1);
-        let to_block = self.current_bridge_filter_block + U64::from(1);
-        log::info!(
-            "filtering from block {} to block {}",
-            &from_block,
-            &to_block
-        );
-
-        let new_filter = FilterBuilder::default()
-            .from_block(BlockNumber::Number(from_block)) // Last processed block
-            .to_block(BlockNumber::Number(to_block))
-            .address(vec![contract_address])
-            .topics(self.bridge_topic.clone(), None, None, None)
-            .build();
-
-        self.current_bridge_filter_block = block_number;
-        self.bridge_filter = new_filter;
-
-        Ok(())
-    }
-
-    fn increment_blob_filter(
-        &mut self,
-        block_number: U64,
-        log_discovered: bool,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let contract_address = self
-            .eo_address
-            .clone()
-            .parse()
-            .map_err(|err| EoServerError::Other(err.to_string()))?;
-
-        let default: U64 = U64::from(0);
-        let from_block = self
-            .settled_processed_blocks
-            .last()
-            .unwrap_or_else(|| &default)
-            + U64::from(1);
-        let to_block = self.current_blob_settlement_filter_block + U64::from(1);
-
-        let new_filter = FilterBuilder::default()
-            .from_block(BlockNumber::Number(from_block))
-            .to_block(BlockNumber::Number(to_block))
-            .address(vec![contract_address])
-            .topics(self.blob_settled_topic.clone(), None, None, None)
-            .build();
-
-        self.current_blob_settlement_filter_block = block_number;
-        self.blob_settled_filter = new_filter;
-
-        Ok(())
-    }
-
     fn inner_highest_bridge_block_processed(&self) -> Option<&U64> {
         self.bridge_processed_blocks.last()
     }
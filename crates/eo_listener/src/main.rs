@@ -1,7 +1,12 @@
-use eo_listener::{EoServer, EoServerError};
+use eo_listener::{
+    EoServer, EoServerError, EventDelivery, FailoverProvider, IngestionMode, DEFAULT_GAP_TIMEOUT,
+    DEFAULT_PROVIDER_RETRY_AFTER, DEFAULT_PROVIDER_TIMEOUT,
+};
 use std::collections::BTreeSet;
+use std::fmt::Display;
 use std::str::FromStr;
-use web3::{transports::Http, types::BlockNumber, Web3};
+use std::time::Duration;
+use web3::{transports::Http, Web3};
 
 #[tokio::main]
 async fn main() -> Result<(), EoServerError> {
@@ -11,12 +16,23 @@ async fn main() -> Result<(), EoServerError> {
 
     let eth_rpc_url = std::env::var("ETH_RPC_URL").expect("ETH_RPC_URL environment variable is not set. Please set the ETH_RPC_URL environment variable with the JSON/RPC HTTP endpoint.");
 
-    let http: Http =
-        Http::new(&eth_rpc_url).map_err(|err| EoServerError::Other(err.to_string()))?;
-    let web3: Web3<Http> = Web3::new(http);
+    let eth_rpc_urls = eo_listener::provider_urls(&eth_rpc_url);
+    let provider = FailoverProvider::from_urls(&eth_rpc_urls)?.with_timeouts(
+        Duration::from_secs(env_or(
+            "ETH_RPC_PROVIDER_TIMEOUT_SECS",
+            DEFAULT_PROVIDER_TIMEOUT.as_secs(),
+        )?),
+        Duration::from_secs(env_or(
+            "ETH_RPC_PROVIDER_RETRY_AFTER_SECS",
+            DEFAULT_PROVIDER_RETRY_AFTER.as_secs(),
+        )?),
+    );
+    let web3: Web3<Http> = provider.primary().clone();
 
     let path = "./blocks_processed.dat";
-    let eo_server = setup_eo_server(web3, path).map_err(|e| EoServerError::Other(e.to_string()))?;
+    let mut eo_server =
+        setup_eo_server(web3, provider, path).map_err(|e| EoServerError::Other(e.to_string()))?;
+    eo_server.resume().await?;
 
     let res = eo_server.run().await;
     println!("{:?}", &res);
@@ -24,8 +40,43 @@ async fn main() -> Result<(), EoServerError> {
     Ok(())
 }
 
+/// The value of `var`, or `default` if it is not set. A value that does not
+/// parse is an error rather than the default.
+fn env_or<T>(var: &str, default: T) -> Result<T, EoServerError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(var) {
+        Ok(raw) => raw
+            .trim()
+            .parse()
+            .map_err(|e| EoServerError::Other(format!("{var} {raw:?} is not valid: {e}"))),
+        Err(_) => Ok(default),
+    }
+}
+
+/// `EO_INGESTION_MODE`, `polling` or `subscription`, subscribing over the
+/// websocket at `ETH_WS_URL`.
+fn ingestion_mode() -> Result<IngestionMode, EoServerError> {
+    match env_or("EO_INGESTION_MODE", "polling".to_string())?.as_str() {
+        "polling" => Ok(IngestionMode::Polling),
+        "subscription" => std::env::var("ETH_WS_URL")
+            .map(IngestionMode::Subscription)
+            .map_err(|_| {
+                EoServerError::Other(
+                    "ETH_WS_URL must be set when EO_INGESTION_MODE is subscription".to_string(),
+                )
+            }),
+        other => Err(EoServerError::Other(format!(
+            "unknown EO_INGESTION_MODE {other}, expected polling or subscription"
+        ))),
+    }
+}
+
 fn setup_eo_server(
     web3_instance: web3::Web3<web3::transports::Http>,
+    provider: FailoverProvider,
     path: &str,
 ) -> Result<EoServer, EoServerError> {
     // Initialize the ExecutableOracle Address
@@ -42,20 +93,6 @@ fn setup_eo_server(
     let blob_settled_topic = eo_listener::get_blob_index_settled_topic();
     let bridge_topic = eo_listener::get_bridge_event_topic();
 
-    let blob_settled_filter = web3::types::FilterBuilder::default()
-        .from_block(BlockNumber::Number(0.into()))
-        .to_block(BlockNumber::Latest)
-        .address(vec![contract_address])
-        .topics(blob_settled_topic.clone(), None, None, None)
-        .build();
-
-    let bridge_filter = web3::types::FilterBuilder::default()
-        .from_block(BlockNumber::Number(0.into()))
-        .to_block(BlockNumber::Latest)
-        .address(vec![contract_address])
-        .topics(bridge_topic.clone(), None, None, None)
-        .build();
-
     let blob_settled_event = contract
         .abi()
         .event("BlobIndexSettled")
@@ -70,6 +107,7 @@ fn setup_eo_server(
 
     let eo_server = eo_listener::EoServerBuilder::default()
         .web3(web3_instance)
+        .provider(provider)
         .eo_address(eo_address)
         .block_time(std::time::Duration::from_millis(2500))
         .bridge_processed_blocks(BTreeSet::new())
@@ -77,13 +115,19 @@ fn setup_eo_server(
         .contract(contract)
         .bridge_topic(bridge_topic)
         .blob_settled_topic(blob_settled_topic)
-        .bridge_filter(bridge_filter)
         .current_bridge_filter_block(0.into())
         .current_blob_settlement_filter_block(0.into())
-        .blob_settled_filter(blob_settled_filter)
         .blob_settled_event(blob_settled_event)
         .bridge_event(bridge_event)
         .path(std::path::PathBuf::from_str(path).map_err(|e| EoServerError::Other(e.to_string()))?)
+        .subscription(ingestion_mode()?.subscription())
+        .event_filters(eo_listener::EventFilters::new(
+            eo_listener::EventFilterConfig::from_env()?.validate()?,
+        ))
+        .delivery(EventDelivery::new(Duration::from_secs(env_or(
+            "EO_GAP_TIMEOUT_SECS",
+            DEFAULT_GAP_TIMEOUT.as_secs(),
+        )?)))
         .build()?;
 
     Ok(eo_server)
@@ -0,0 +1,466 @@
+//! Settlement layer RPC providers with failover.
+//!
+//! Requests go to the highest priority provider that is not marked down. One
+//! that errors or times out is marked down for `retry_after` and the request
+//! moves on to the next, so the listener keeps going on a fallback and returns
+//! to the primary once it answers again. Which blocks have been processed is
+//! kept by the listener's cursors, so switching providers never replays or
+//! skips a range.
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::Serialize;
+use web3::{
+    transports::Http,
+    types::{Filter, Log},
+    Web3,
+};
+
+use crate::{BlockProvider, BlockRef, EoServerError, LogProvider};
+
+pub const DEFAULT_PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_PROVIDER_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// The urls of a comma separated, highest priority first, provider list.
+pub fn provider_urls(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug)]
+struct ProviderCounters {
+    url: String,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl ProviderCounters {
+    fn is_down(&self, now: Instant) -> bool {
+        self.down_until
+            .lock()
+            .unwrap()
+            .is_some_and(|down_until| down_until > now)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProviderMetrics {
+    providers: Vec<ProviderCounters>,
+    active: AtomicUsize,
+    switches: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderStatus {
+    pub url: String,
+    pub active: bool,
+    pub down: bool,
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementProvidersStatus {
+    pub switches: u64,
+    pub providers: Vec<ProviderStatus>,
+}
+
+impl ProviderMetrics {
+    fn new(urls: &[String]) -> Self {
+        Self {
+            providers: urls
+                .iter()
+                .map(|url| ProviderCounters {
+                    url: redact(url),
+                    requests: AtomicU64::new(0),
+                    errors: AtomicU64::new(0),
+                    last_error: Mutex::new(None),
+                    down_until: Mutex::new(None),
+                })
+                .collect(),
+            active: AtomicUsize::new(0),
+            switches: AtomicU64::new(0),
+        }
+    }
+
+    /// The index, in priority order, of the provider last answered by.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Times requests moved from one provider to another.
+    pub fn switches(&self) -> u64 {
+        self.switches.load(Ordering::Relaxed)
+    }
+
+    pub fn requests(&self, provider: usize) -> u64 {
+        self.providers[provider].requests.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self, provider: usize) -> u64 {
+        self.providers[provider].errors.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> SettlementProvidersStatus {
+        let now = Instant::now();
+        let providers = self
+            .providers
+            .iter()
+            .enumerate()
+            .map(|(index, counters)| {
+                let requests = counters.requests.load(Ordering::Relaxed);
+                let errors = counters.errors.load(Ordering::Relaxed);
+                ProviderStatus {
+                    url: counters.url.clone(),
+                    active: index == self.active(),
+                    down: counters.is_down(now),
+                    requests,
+                    errors,
+                    error_rate: if requests == 0 {
+                        0.0
+                    } else {
+                        errors as f64 / requests as f64
+                    },
+                    last_error: counters.last_error.lock().unwrap().clone(),
+                }
+            })
+            .collect();
+        SettlementProvidersStatus {
+            switches: self.switches(),
+            providers,
+        }
+    }
+
+    fn answered(&self, provider: usize) {
+        *self.providers[provider].down_until.lock().unwrap() = None;
+        let previous = self.active.swap(provider, Ordering::Relaxed);
+        if previous != provider {
+            self.switches.fetch_add(1, Ordering::Relaxed);
+            log::warn!(
+                "settlement layer provider switched from {} to {}",
+                self.providers[previous].url,
+                self.providers[provider].url
+            );
+        }
+    }
+
+    fn failed(&self, provider: usize, error: &EoServerError, retry_after: Duration) {
+        let counters = &self.providers[provider];
+        counters.errors.fetch_add(1, Ordering::Relaxed);
+        *counters.last_error.lock().unwrap() = Some(error.to_string());
+        *counters.down_until.lock().unwrap() = Some(Instant::now() + retry_after);
+        log::warn!(
+            "settlement layer provider {} failed: {}",
+            counters.url,
+            error
+        );
+    }
+}
+
+/// Keeps only the scheme and host of `url`, which is enough to tell
+/// providers apart without publishing the API keys their paths often carry.
+fn redact(url: &str) -> String {
+    let host_start = url.find("://").map_or(0, |scheme| scheme + 3);
+    match url[host_start..].find(['/', '?']) {
+        Some(path) => url[..host_start + path].to_string(),
+        None => url.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FailoverProvider<P = Web3<Http>> {
+    providers: Vec<P>,
+    metrics: Arc<ProviderMetrics>,
+    timeout: Duration,
+    retry_after: Duration,
+}
+
+impl FailoverProvider<Web3<Http>> {
+    /// Connects to each of `urls`, highest priority first.
+    pub fn from_urls(urls: &[String]) -> Result<Self, EoServerError> {
+        let providers = urls
+            .iter()
+            .map(|url| {
+                Http::new(url)
+                    .map(Web3::new)
+                    .map_err(|e| EoServerError::Other(format!("invalid provider {url}: {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(urls, providers)
+    }
+}
+
+impl<P> FailoverProvider<P> {
+    /// `providers` in priority order, each named by the url at the same
+    /// position in `urls`.
+    pub fn new(urls: &[String], providers: Vec<P>) -> Result<Self, EoServerError> {
+        if providers.is_empty() || providers.len() != urls.len() {
+            return Err(EoServerError::Other(
+                "at least one settlement layer provider must be configured".to_string(),
+            ));
+        }
+        Ok(Self {
+            providers,
+            metrics: Arc::new(ProviderMetrics::new(urls)),
+            timeout: DEFAULT_PROVIDER_TIMEOUT,
+            retry_after: DEFAULT_PROVIDER_RETRY_AFTER,
+        })
+    }
+
+    pub fn with_timeouts(mut self, timeout: Duration, retry_after: Duration) -> Self {
+        self.timeout = timeout;
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub fn metrics(&self) -> Arc<ProviderMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// The highest priority provider, used for anything outside the listener.
+    pub fn primary(&self) -> &P {
+        &self.providers[0]
+    }
+
+    /// Providers that are up in priority order, then those marked down, so a
+    /// request is still attempted when every provider has failed recently.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (up, down): (Vec<_>, Vec<_>) = (0..self.providers.len())
+            .partition(|index| !self.metrics.providers[*index].is_down(now));
+        up.into_iter().chain(down).collect()
+    }
+
+    async fn request<'a, T, F>(&'a self, request: F) -> Result<T, EoServerError>
+    where
+        F: Fn(&'a P) -> BoxFuture<'a, Result<T, EoServerError>>,
+    {
+        let mut last_error = None;
+        for index in self.order() {
+            self.metrics.providers[index]
+                .requests
+                .fetch_add(1, Ordering::Relaxed);
+            let error =
+                match tokio::time::timeout(self.timeout, request(&self.providers[index])).await {
+                    Ok(Ok(value)) => {
+                        self.metrics.answered(index);
+                        return Ok(value);
+                    }
+                    Ok(Err(e)) => e,
+                    Err(_) => {
+                        EoServerError::Other(format!("request timed out after {:?}", self.timeout))
+                    }
+                };
+            self.metrics.failed(index, &error, self.retry_after);
+            last_error = Some(error);
+        }
+        Err(last_error.unwrap_or_else(|| {
+            EoServerError::Other("no settlement layer providers configured".to_string())
+        }))
+    }
+
+    /// Asks every provider for the latest block, marking the ones that fail
+    /// down and the ones that answer up again.
+    pub async fn check_health(&self)
+    where
+        P: BlockProvider,
+    {
+        for (index, provider) in self.providers.iter().enumerate() {
+            let counters = &self.metrics.providers[index];
+            counters.requests.fetch_add(1, Ordering::Relaxed);
+            match tokio::time::timeout(self.timeout, provider.latest_block_number()).await {
+                Ok(Ok(_)) => *counters.down_until.lock().unwrap() = None,
+                Ok(Err(e)) => self.metrics.failed(index, &e, self.retry_after),
+                Err(_) => self.metrics.failed(
+                    index,
+                    &EoServerError::Other(format!(
+                        "health check timed out after {:?}",
+                        self.timeout
+                    )),
+                    self.retry_after,
+                ),
+            }
+        }
+    }
+}
+
+impl<P: BlockProvider + Sync> BlockProvider for FailoverProvider<P> {
+    fn latest_block_number(&self) -> BoxFuture<'_, Result<u64, EoServerError>> {
+        self.request(|provider| provider.latest_block_number())
+            .boxed()
+    }
+
+    fn block(&self, number: u64) -> BoxFuture<'_, Result<Option<BlockRef>, EoServerError>> {
+        self.request(move |provider| provider.block(number)).boxed()
+    }
+}
+
+impl<P: LogProvider + Sync> LogProvider for FailoverProvider<P> {
+    fn logs(&self, filter: Filter) -> BoxFuture<'_, Result<Vec<Log>, EoServerError>> {
+        self.request(move |provider| provider.logs(filter.clone()))
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod providers_tests {
+    use super::*;
+    use crate::LogCursor;
+    use std::sync::atomic::AtomicBool;
+    use web3::types::{Bytes, H160, U64};
+
+    /// A provider serving one log per block, which can be made to fail.
+    #[derive(Default)]
+    struct MockProvider {
+        failing: AtomicBool,
+        head: u64,
+    }
+
+    impl MockProvider {
+        fn result<T>(&self, value: T) -> BoxFuture<'_, Result<T, EoServerError>>
+        where
+            T: Send + 'static,
+        {
+            let result = if self.failing.load(Ordering::SeqCst) {
+                Err(EoServerError::Other("connection refused".to_string()))
+            } else {
+                Ok(value)
+            };
+            async move { result }.boxed()
+        }
+    }
+
+    impl BlockProvider for MockProvider {
+        fn latest_block_number(&self) -> BoxFuture<'_, Result<u64, EoServerError>> {
+            self.result(self.head)
+        }
+
+        fn block(&self, _number: u64) -> BoxFuture<'_, Result<Option<BlockRef>, EoServerError>> {
+            self.result(None)
+        }
+    }
+
+    fn block_of(filter: &Filter, field: &str) -> u64 {
+        let value = serde_json::to_value(filter).unwrap();
+        serde_json::from_value::<U64>(value[field].clone())
+            .unwrap()
+            .as_u64()
+    }
+
+    impl LogProvider for MockProvider {
+        fn logs(&self, filter: Filter) -> BoxFuture<'_, Result<Vec<Log>, EoServerError>> {
+            let blocks = block_of(&filter, "fromBlock")..=block_of(&filter, "toBlock");
+            self.result(
+                blocks
+                    .map(|number| Log {
+                        address: H160::zero(),
+                        topics: Vec::new(),
+                        data: Bytes(Vec::new()),
+                        block_hash: None,
+                        block_number: Some(number.into()),
+                        transaction_hash: None,
+                        transaction_index: None,
+                        log_index: None,
+                        transaction_log_index: None,
+                        log_type: None,
+                        removed: None,
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn primary_failing_mid_stream_fails_over_without_replaying() {
+        let urls = vec![
+            "https://primary.example/v3/secret-key".to_string(),
+            "https://fallback.example?key=secret".to_string(),
+        ];
+        let providers = vec![
+            MockProvider {
+                head: 40,
+                ..Default::default()
+            },
+            MockProvider {
+                head: 40,
+                ..Default::default()
+            },
+        ];
+        let failover = FailoverProvider::new(&urls, providers)
+            .unwrap()
+            .with_timeouts(Duration::from_secs(1), Duration::from_secs(60));
+        let mut cursor = LogCursor::new(H160::zero(), None, 1);
+        let mut max_range = 10;
+        let mut delivered = Vec::new();
+
+        for range in 0..4 {
+            if range == 2 {
+                failover.providers[0].failing.store(true, Ordering::SeqCst);
+            }
+            let head = failover.latest_block_number().await.unwrap();
+            let range = cursor
+                .scan(&failover, head, &mut max_range)
+                .await
+                .unwrap()
+                .unwrap();
+            delivered.extend(
+                range
+                    .logs
+                    .iter()
+                    .map(|log| log.block_number.unwrap().as_u64()),
+            );
+        }
+
+        assert_eq!(delivered, (1..=40).collect::<Vec<_>>());
+        let metrics = failover.metrics();
+        assert_eq!(metrics.active(), 1);
+        assert_eq!(metrics.switches(), 1);
+        // The primary failed once and has been skipped since.
+        assert_eq!(metrics.errors(0), 1);
+        assert_eq!(metrics.requests(0), 5);
+
+        let status = metrics.status();
+        assert_eq!(status.providers[0].url, "https://primary.example");
+        assert_eq!(status.providers[1].url, "https://fallback.example");
+        assert!(status.providers[0].down);
+        assert!(status.providers[1].active);
+        assert_eq!(status.providers[0].error_rate, 0.2);
+        assert_eq!(
+            status.providers[0].last_error.as_deref(),
+            Some("Other(\"connection refused\")")
+        );
+    }
+
+    #[tokio::test]
+    async fn recovered_primary_is_used_again() {
+        let urls = vec!["http://primary".to_string(), "http://fallback".to_string()];
+        let failover = FailoverProvider::new(
+            &urls,
+            vec![MockProvider::default(), MockProvider::default()],
+        )
+        .unwrap()
+        .with_timeouts(Duration::from_secs(1), Duration::from_secs(60));
+
+        failover.providers[0].failing.store(true, Ordering::SeqCst);
+        failover.latest_block_number().await.unwrap();
+        assert_eq!(failover.metrics().active(), 1);
+
+        failover.providers[0].failing.store(false, Ordering::SeqCst);
+        failover.check_health().await;
+        failover.latest_block_number().await.unwrap();
+        assert_eq!(failover.metrics().active(), 0);
+        assert_eq!(failover.metrics().switches(), 2);
+    }
+}
@@ -0,0 +1,437 @@
+//! Reorg detection for the settlement layer.
+//!
+//! Every event the listener hands on is recorded against the block it was
+//! emitted in. Each poll the tracker follows the chain on from the last block
+//! it saw, and when a new block's parent is not that block it walks back
+//! until its record agrees with the chain again. Events recorded above that
+//! fork point were emitted on an abandoned branch and are handed back so they
+//! can be retracted. Once a block is buried under `confirmations` blocks its
+//! events are final and are no longer tracked.
+use std::collections::BTreeMap;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use web3::{
+    transports::Http,
+    types::{BlockId, BlockNumber, H256, U64},
+    Web3,
+};
+
+use crate::{EoServerError, EventType};
+
+/// Confirmations before an event is considered final, unless configured.
+pub const DEFAULT_CONFIRMATIONS: u64 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockRef {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+}
+
+/// Source of the blocks the tracker compares its record against.
+pub trait BlockProvider {
+    fn latest_block_number(&self) -> BoxFuture<'_, Result<u64, EoServerError>>;
+
+    /// The canonical block at `number`, if the chain has reached it.
+    fn block(&self, number: u64) -> BoxFuture<'_, Result<Option<BlockRef>, EoServerError>>;
+}
+
+impl BlockProvider for Web3<Http> {
+    fn latest_block_number(&self) -> BoxFuture<'_, Result<u64, EoServerError>> {
+        async move {
+            self.eth()
+                .block_number()
+                .await
+                .map(|number| number.as_u64())
+                .map_err(|e| {
+                    EoServerError::Other(format!("Error attempting to get block number: {}", e))
+                })
+        }
+        .boxed()
+    }
+
+    fn block(&self, number: u64) -> BoxFuture<'_, Result<Option<BlockRef>, EoServerError>> {
+        async move {
+            let block = self
+                .eth()
+                .block(BlockId::Number(BlockNumber::Number(U64::from(number))))
+                .await
+                .map_err(|e| {
+                    EoServerError::Other(format!("Error attempting to get block {}: {}", number, e))
+                })?;
+            Ok(block.and_then(|block| {
+                Some(BlockRef {
+                    number: block.number?.as_u64(),
+                    hash: block.hash?,
+                    parent_hash: block.parent_hash,
+                })
+            }))
+        }
+        .boxed()
+    }
+}
+
+/// An event recorded against the block it was emitted in.
+#[derive(Clone, Debug)]
+pub struct TrackedEvent {
+    pub block_hash: H256,
+    pub event_type: EventType,
+    pub log: web3::ethabi::Log,
+}
+
+/// A switch to another branch of the settlement layer.
+#[derive(Clone, Debug, Default)]
+pub struct Reorg {
+    /// The highest block both branches share, or `None` if the branches
+    /// share none of the blocks still tracked.
+    pub fork_point: Option<u64>,
+    /// Blocks on the abandoned branch, lowest first.
+    pub abandoned: Vec<BlockRef>,
+    /// Events emitted in blocks above the fork point, to be retracted.
+    pub retracted: Vec<TrackedEvent>,
+}
+
+impl Reorg {
+    fn merge(reorg: Option<Reorg>, other: Reorg) -> Option<Reorg> {
+        let Some(mut reorg) = reorg else {
+            return Some(other);
+        };
+        // `None` sorts first, and is also the deepest fork point.
+        reorg.fork_point = reorg.fork_point.min(other.fork_point);
+        reorg.abandoned.extend(other.abandoned);
+        reorg.abandoned.sort_by_key(|block| block.number);
+        reorg.retracted.extend(other.retracted);
+        Some(reorg)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReorgTracker {
+    confirmations: u64,
+    /// The recent chain as last observed, and at least its head.
+    blocks: BTreeMap<u64, BlockRef>,
+    /// Events that are not final yet, by block number.
+    events: BTreeMap<u64, Vec<TrackedEvent>>,
+}
+
+impl Default for ReorgTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONFIRMATIONS)
+    }
+}
+
+impl ReorgTracker {
+    pub fn new(confirmations: u64) -> Self {
+        Self {
+            confirmations,
+            blocks: BTreeMap::new(),
+            events: BTreeMap::new(),
+        }
+    }
+
+    pub fn confirmations(&self) -> u64 {
+        self.confirmations
+    }
+
+    /// The last block observed.
+    pub fn head(&self) -> Option<&BlockRef> {
+        self.blocks.values().next_back()
+    }
+
+    /// Events that could still be retracted.
+    pub fn unfinalized(&self) -> impl Iterator<Item = &TrackedEvent> {
+        self.events.values().flatten()
+    }
+
+    pub fn record(
+        &mut self,
+        block_number: u64,
+        block_hash: H256,
+        event_type: EventType,
+        log: web3::ethabi::Log,
+    ) {
+        self.events
+            .entry(block_number)
+            .or_default()
+            .push(TrackedEvent {
+                block_hash,
+                event_type,
+                log,
+            });
+    }
+
+    /// Follows the chain up to the latest block, returning the reorg if the
+    /// chain switched branches since the last call, then finalizes events
+    /// that are `confirmations` deep.
+    pub async fn advance<P: BlockProvider>(
+        &mut self,
+        provider: &P,
+    ) -> Result<Option<Reorg>, EoServerError> {
+        let latest = provider.latest_block_number().await?;
+        let mut reorg: Option<Reorg> = None;
+
+        let mut next = match self.head() {
+            Some(head) => head.number + 1,
+            None => self
+                .events
+                .keys()
+                .next()
+                .copied()
+                .unwrap_or(latest)
+                .max(latest.saturating_sub(self.confirmations))
+                .min(latest),
+        };
+        while next <= latest {
+            let Some(block) = provider.block(next).await? else {
+                break;
+            };
+            if let Some(head) = self.head() {
+                if block.parent_hash != head.hash {
+                    let fork_point = self.find_fork_point(provider).await?;
+                    let abandoned = self.rewind(fork_point);
+                    next = match fork_point {
+                        Some(fork_point) => fork_point + 1,
+                        None => abandoned
+                            .abandoned
+                            .first()
+                            .map_or(next, |block| block.number),
+                    };
+                    reorg = Reorg::merge(reorg, abandoned);
+                    continue;
+                }
+            }
+            self.blocks.insert(block.number, block);
+            next = block.number + 1;
+        }
+
+        // Logs can come from a different branch than the blocks followed
+        // above, if the chain switched between the two requests. Everything
+        // recorded from that block on is retracted, and the canonical events
+        // come back when the listener processes those blocks again.
+        let stale = self.events.iter().find_map(|(number, events)| {
+            let block = self.blocks.get(number)?;
+            events
+                .iter()
+                .any(|event| event.block_hash != block.hash)
+                .then_some(*number)
+        });
+        if let Some(number) = stale {
+            let retracted = self
+                .events
+                .split_off(&number)
+                .into_values()
+                .flatten()
+                .collect();
+            let stale = Reorg {
+                fork_point: number.checked_sub(1),
+                abandoned: Vec::new(),
+                retracted,
+            };
+            reorg = Reorg::merge(reorg, stale);
+        }
+
+        self.finalize(latest);
+        Ok(reorg)
+    }
+
+    async fn find_fork_point<P: BlockProvider>(
+        &self,
+        provider: &P,
+    ) -> Result<Option<u64>, EoServerError> {
+        for (number, recorded) in self.blocks.iter().rev() {
+            if let Some(canonical) = provider.block(*number).await? {
+                if canonical.hash == recorded.hash {
+                    return Ok(Some(*number));
+                }
+            }
+        }
+
+        let lowest = self.blocks.keys().next().copied();
+        log::error!(
+            "settlement layer reorg is deeper than the {} tracked blocks, from block {:?}",
+            self.blocks.len(),
+            lowest
+        );
+        Ok(lowest.and_then(|lowest| lowest.checked_sub(1)))
+    }
+
+    /// Forgets every block and event above `fork_point`.
+    fn rewind(&mut self, fork_point: Option<u64>) -> Reorg {
+        let above = fork_point.map_or(0, |fork_point| fork_point + 1);
+        let abandoned = self.blocks.split_off(&above).into_values().collect();
+        let retracted = self
+            .events
+            .split_off(&above)
+            .into_values()
+            .flatten()
+            .collect();
+        if fork_point.is_none() {
+            self.blocks.clear();
+            self.events.clear();
+        }
+        Reorg {
+            fork_point,
+            abandoned,
+            retracted,
+        }
+    }
+
+    fn finalize(&mut self, latest: u64) {
+        let Some(safe) = latest.checked_sub(self.confirmations) else {
+            return;
+        };
+        self.events = self.events.split_off(&(safe + 1));
+
+        // The head is kept so the next block's parent can be checked.
+        let keep_from = self.head().map_or(0, |head| head.number).min(safe + 1);
+        self.blocks = self.blocks.split_off(&keep_from);
+    }
+}
+
+#[cfg(test)]
+mod reorg_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn hash(branch: u64, number: u64) -> H256 {
+        H256::from_low_u64_be((branch << 32) | number)
+    }
+
+    /// A chain whose blocks can be replaced from any height onwards.
+    #[derive(Default)]
+    struct ScriptedProvider {
+        chain: Mutex<Vec<BlockRef>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(length: u64) -> Self {
+            let provider = Self::default();
+            provider.reorg(0, 0, length);
+            provider
+        }
+
+        /// Replaces every block from `from` with `to - from + 1` blocks of
+        /// `branch`.
+        fn reorg(&self, branch: u64, from: u64, to: u64) {
+            let mut chain = self.chain.lock().unwrap();
+            chain.truncate(from as usize);
+            for number in from..=to {
+                let parent_hash = chain.last().map_or(H256::zero(), |parent| parent.hash);
+                chain.push(BlockRef {
+                    number,
+                    hash: hash(branch, number),
+                    parent_hash,
+                });
+            }
+        }
+    }
+
+    impl BlockProvider for ScriptedProvider {
+        fn latest_block_number(&self) -> BoxFuture<'_, Result<u64, EoServerError>> {
+            let latest = self.chain.lock().unwrap().len() as u64 - 1;
+            async move { Ok(latest) }.boxed()
+        }
+
+        fn block(&self, number: u64) -> BoxFuture<'_, Result<Option<BlockRef>, EoServerError>> {
+            let block = self.chain.lock().unwrap().get(number as usize).copied();
+            async move { Ok(block) }.boxed()
+        }
+    }
+
+    fn bridge_event() -> EventType {
+        EventType::Bridge(crate::get_abi().unwrap().event("Bridge").unwrap().clone())
+    }
+
+    fn log(id: u64) -> web3::ethabi::Log {
+        web3::ethabi::Log {
+            params: vec![web3::ethabi::LogParam {
+                name: "bridgeEventId".to_string(),
+                value: web3::ethabi::Token::Uint(id.into()),
+            }],
+        }
+    }
+
+    fn ids(events: &[TrackedEvent]) -> Vec<u64> {
+        events
+            .iter()
+            .map(|event| {
+                event.log.params[0]
+                    .value
+                    .clone()
+                    .into_uint()
+                    .unwrap()
+                    .as_u64()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn three_block_reorg_retracts_abandoned_events() {
+        let provider = ScriptedProvider::new(10);
+        let mut tracker = ReorgTracker::new(6);
+        for number in [6, 8, 9, 10] {
+            tracker.record(number, hash(0, number), bridge_event(), log(number));
+        }
+        assert!(tracker.advance(&provider).await.unwrap().is_none());
+        assert_eq!(tracker.head().unwrap().hash, hash(0, 10));
+
+        provider.reorg(1, 8, 11);
+        let reorg = tracker
+            .advance(&provider)
+            .await
+            .unwrap()
+            .expect("reorg was not detected");
+        assert_eq!(reorg.fork_point, Some(7));
+        assert_eq!(
+            reorg
+                .abandoned
+                .iter()
+                .map(|block| block.hash)
+                .collect::<Vec<_>>(),
+            vec![hash(0, 8), hash(0, 9), hash(0, 10)]
+        );
+        assert_eq!(ids(&reorg.retracted), vec![8, 9, 10]);
+
+        // The canonical branch is followed after the fork point.
+        assert_eq!(tracker.head().unwrap().hash, hash(1, 11));
+        assert_eq!(
+            ids(&tracker.unfinalized().cloned().collect::<Vec<_>>()),
+            vec![6]
+        );
+    }
+
+    #[tokio::test]
+    async fn confirmed_events_are_not_retracted() {
+        let provider = ScriptedProvider::new(10);
+        let mut tracker = ReorgTracker::new(2);
+        for number in [7, 9] {
+            tracker.record(number, hash(0, number), bridge_event(), log(number));
+        }
+        tracker.advance(&provider).await.unwrap();
+        assert_eq!(
+            ids(&tracker.unfinalized().cloned().collect::<Vec<_>>()),
+            vec![9]
+        );
+
+        provider.reorg(1, 6, 12);
+        let reorg = tracker.advance(&provider).await.unwrap().unwrap();
+        assert_eq!(ids(&reorg.retracted), vec![9]);
+        assert_eq!(tracker.head().unwrap().hash, hash(1, 12));
+    }
+
+    #[tokio::test]
+    async fn logs_from_a_stale_branch_are_retracted() {
+        let provider = ScriptedProvider::new(10);
+        let mut tracker = ReorgTracker::new(6);
+        tracker.advance(&provider).await.unwrap();
+
+        // The filter returned a log from a block the chain has moved past.
+        tracker.record(10, hash(7, 10), bridge_event(), log(10));
+        let reorg = tracker.advance(&provider).await.unwrap().unwrap();
+        assert_eq!(reorg.fork_point, Some(9));
+        assert!(reorg.abandoned.is_empty());
+        assert_eq!(ids(&reorg.retracted), vec![10]);
+        assert_eq!(tracker.head().unwrap().hash, hash(0, 10));
+    }
+}
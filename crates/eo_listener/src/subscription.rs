@@ -0,0 +1,237 @@
+//! Settlement layer events pushed over a websocket.
+//!
+//! In subscription mode the listener subscribes to `newHeads` and to the
+//! contract's logs, and handles each log as it arrives instead of waiting for
+//! the next poll. The cursors stay the source of truth: once they have caught
+//! up with the head the subscription started at, each new head moves them
+//! along, and when the connection drops they stay where the last head left
+//! them, so the range query run while reconnecting picks up anything missed
+//! during the outage.
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, StreamExt};
+use futures::FutureExt;
+use web3::{
+    transports::WebSocket,
+    types::{Filter, Log},
+    Web3,
+};
+
+use crate::EoServerError;
+
+pub const DEFAULT_RESUBSCRIBE_AFTER: Duration = Duration::from_secs(1);
+pub const MAX_RESUBSCRIBE_AFTER: Duration = Duration::from_secs(60);
+
+/// How the listener learns about new settlement layer events.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum IngestionMode {
+    /// Logs are queried every block time.
+    #[default]
+    Polling,
+    /// Logs are pushed over the websocket at this url, and queried only to
+    /// fill gaps.
+    Subscription(String),
+}
+
+impl IngestionMode {
+    /// The subscription to push events over, in subscription mode.
+    pub fn subscription(&self) -> Option<Subscription> {
+        match self {
+            Self::Polling => None,
+            Self::Subscription(url) => Some(Subscription::new(WsSubscriber::new(url.clone()))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SubscriptionEvent {
+    Head(u64),
+    Log(Log),
+}
+
+pub type EventStream = BoxStream<'static, Result<SubscriptionEvent, EoServerError>>;
+
+/// Source of pushed heads and logs.
+pub trait EventSubscriber {
+    /// Subscribes to new heads and to the logs matching each of `filters`.
+    fn subscribe(&self, filters: Vec<Filter>) -> BoxFuture<'_, Result<EventStream, EoServerError>>;
+}
+
+#[derive(Clone, Debug)]
+pub struct WsSubscriber {
+    url: String,
+}
+
+impl WsSubscriber {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl EventSubscriber for WsSubscriber {
+    fn subscribe(&self, filters: Vec<Filter>) -> BoxFuture<'_, Result<EventStream, EoServerError>> {
+        async move {
+            let ws = WebSocket::new(&self.url)
+                .await
+                .map_err(|e| EoServerError::Other(format!("unable to connect: {e}")))?;
+            let web3 = Web3::new(ws);
+            let subscribe_error =
+                |e: web3::Error| EoServerError::Other(format!("unable to subscribe: {e}"));
+
+            let heads = web3
+                .eth_subscribe()
+                .subscribe_new_heads()
+                .await
+                .map_err(subscribe_error)?
+                .filter_map(|header| async move {
+                    match header {
+                        Ok(header) => header
+                            .number
+                            .map(|number| Ok(SubscriptionEvent::Head(number.as_u64()))),
+                        Err(e) => Some(Err(EoServerError::Other(e.to_string()))),
+                    }
+                })
+                .boxed();
+            let mut streams = vec![heads];
+            for filter in filters {
+                let logs = web3
+                    .eth_subscribe()
+                    .subscribe_logs(filter)
+                    .await
+                    .map_err(subscribe_error)?
+                    .map(|log| {
+                        log.map(SubscriptionEvent::Log)
+                            .map_err(|e| EoServerError::Other(e.to_string()))
+                    })
+                    .boxed();
+                streams.push(logs);
+            }
+            Ok(futures::stream::select_all(streams).boxed())
+        }
+        .boxed()
+    }
+}
+
+/// A subscription that is made again, backing off, whenever it ends.
+pub struct Subscription<S = WsSubscriber> {
+    subscriber: S,
+    stream: Option<EventStream>,
+    retry_after: Duration,
+    next_attempt: Option<Instant>,
+    subscribed: bool,
+    reconnects: u64,
+}
+
+impl<S> std::fmt::Debug for Subscription<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("connected", &self.stream.is_some())
+            .field("retry_after", &self.retry_after)
+            .field("reconnects", &self.reconnects)
+            .finish()
+    }
+}
+
+/// A clone makes its own subscription.
+impl<S: Clone> Clone for Subscription<S> {
+    fn clone(&self) -> Self {
+        Self {
+            subscriber: self.subscriber.clone(),
+            stream: None,
+            retry_after: DEFAULT_RESUBSCRIBE_AFTER,
+            next_attempt: None,
+            subscribed: false,
+            reconnects: 0,
+        }
+    }
+}
+
+impl<S: EventSubscriber> Subscription<S> {
+    pub fn new(subscriber: S) -> Self {
+        Self {
+            subscriber,
+            stream: None,
+            retry_after: DEFAULT_RESUBSCRIBE_AFTER,
+            next_attempt: None,
+            subscribed: false,
+            reconnects: 0,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Times the subscription was made again after it ended.
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+
+    /// Subscribes if not subscribed and the backoff since the last failed
+    /// attempt has passed. Returns whether a new subscription was made.
+    pub async fn connect(&mut self, filters: Vec<Filter>) -> bool {
+        if self.is_connected()
+            || self
+                .next_attempt
+                .is_some_and(|next_attempt| Instant::now() < next_attempt)
+        {
+            return false;
+        }
+        match self.subscriber.subscribe(filters).await {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.retry_after = DEFAULT_RESUBSCRIBE_AFTER;
+                self.next_attempt = None;
+                if self.subscribed {
+                    self.reconnects += 1;
+                }
+                self.subscribed = true;
+                true
+            }
+            Err(e) => {
+                log::warn!(
+                    "subscription failed, retrying in {:?}: {}",
+                    self.retry_after,
+                    e
+                );
+                self.disconnected();
+                false
+            }
+        }
+    }
+
+    /// Ends the subscription, so the next `connect` subscribes again without
+    /// waiting, e.g. to filters that changed.
+    pub fn reset(&mut self) {
+        self.stream = None;
+        self.next_attempt = None;
+    }
+
+    /// The next event, or `None` if nothing arrived within `wait` or the
+    /// subscription ended, in which case it is made again on the next
+    /// `connect`.
+    pub async fn next(&mut self, wait: Duration) -> Option<SubscriptionEvent> {
+        let stream = self.stream.as_mut()?;
+        match tokio::time::timeout(wait, stream.next()).await {
+            Err(_) => None,
+            Ok(Some(Ok(event))) => Some(event),
+            Ok(Some(Err(e))) => {
+                log::warn!("subscription ended: {}", e);
+                self.disconnected();
+                None
+            }
+            Ok(None) => {
+                log::warn!("subscription ended: connection closed");
+                self.disconnected();
+                None
+            }
+        }
+    }
+
+    fn disconnected(&mut self) {
+        self.stream = None;
+        self.next_attempt = Some(Instant::now() + self.retry_after);
+        self.retry_after = (self.retry_after * 2).min(MAX_RESUBSCRIBE_AFTER);
+    }
+}
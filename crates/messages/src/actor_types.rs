@@ -111,8 +111,17 @@ impl ToActorType for ractor::ActorName {
 
 #[derive(Debug, Clone)]
 pub enum RpcRequestMethod {
-    Call { transaction: Transaction },
-    Send { transaction: Transaction },
-    RegisterProgram { transaction: Transaction },
-    GetAccount { address: Address },
+    Call {
+        transaction: Transaction,
+    },
+    Send {
+        transaction: Transaction,
+        replace: bool,
+    },
+    RegisterProgram {
+        transaction: Transaction,
+    },
+    GetAccount {
+        address: Address,
+    },
 }
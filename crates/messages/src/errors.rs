@@ -0,0 +1,362 @@
+//! The errors actors answer one another with, grouped by the part of the
+//! node that failed, so a failure can be told apart as retriable or not and
+//! answered with the right RPC error code wherever it ends up.
+use crate::{RetryReason, RpcErrorCode};
+use lasr_types::Address;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum LasrError {
+    #[error(transparent)]
+    Cache(#[from] CacheError),
+
+    #[error(transparent)]
+    Batch(#[from] BatchError),
+
+    #[error(transparent)]
+    Da(#[from] DaError),
+
+    #[error(transparent)]
+    Engine(#[from] EngineError),
+
+    #[error(transparent)]
+    Executor(#[from] ExecutorError),
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+
+    /// A failure not yet classified, as errors still boxed are converted.
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl LasrError {
+    /// Whether the same request may succeed if it is made again later.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            LasrError::Cache(e) => e.is_retriable(),
+            LasrError::Batch(e) => e.is_retriable(),
+            LasrError::Da(e) => e.is_retriable(),
+            LasrError::Engine(e) => e.is_retriable(),
+            LasrError::Executor(e) => e.is_retriable(),
+            LasrError::Rpc(e) => e.is_retriable(),
+            LasrError::Wallet(e) => e.is_retriable(),
+            LasrError::Custom(_) => false,
+        }
+    }
+
+    /// The RPC error code the failure is answered with.
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            LasrError::Cache(e) => e.error_code(),
+            LasrError::Batch(e) => e.error_code(),
+            LasrError::Da(e) => e.error_code(),
+            LasrError::Engine(e) => e.error_code(),
+            LasrError::Executor(e) => e.error_code(),
+            LasrError::Rpc(e) => e.error_code(),
+            LasrError::Wallet(e) => e.error_code(),
+            LasrError::Custom(_) => RpcErrorCode::Internal,
+        }
+    }
+}
+
+impl From<String> for LasrError {
+    fn from(value: String) -> Self {
+        LasrError::Custom(value)
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send>> for LasrError {
+    fn from(value: Box<dyn std::error::Error + Send>) -> Self {
+        LasrError::Custom(value.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for LasrError {
+    fn from(value: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        LasrError::Custom(value.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for LasrError {
+    fn from(value: Box<dyn std::error::Error>) -> Self {
+        LasrError::Custom(value.to_string())
+    }
+}
+
+/// Failures reading or writing accounts, receipts and attestations.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum CacheError {
+    #[error("account {} does not exist", .0.to_full_string())]
+    AccountNotFound(Address),
+
+    /// Storage could not be reached or refused the request.
+    #[error("storage failed: {0}")]
+    Storage(String),
+
+    #[error("stored value cannot be decoded: {0}")]
+    Decode(String),
+
+    /// The account cache actor is not running, as while it restarts.
+    #[error("account cache is unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl CacheError {
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, CacheError::Storage(_) | CacheError::Unavailable(_))
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            CacheError::AccountNotFound(_) => RpcErrorCode::NotFound,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// Failures adding transactions to a batch or sealing it.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum BatchError {
+    /// The batch being filled has no room left, so the transaction waits
+    /// for the next.
+    #[error("batch is at capacity")]
+    AtCapacity,
+
+    /// The transaction cannot be applied to the accounts it touches.
+    #[error("transaction cannot be applied: {0}")]
+    InvalidTransaction(String),
+
+    /// The commit log or storage could not be written.
+    #[error("batch could not be persisted: {0}")]
+    Persistence(String),
+
+    /// The batcher, or an actor it answers to, is not running.
+    #[error("batcher is unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl BatchError {
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            BatchError::AtCapacity | BatchError::Persistence(_) | BatchError::Unavailable(_)
+        )
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            BatchError::InvalidTransaction(_) => RpcErrorCode::ProgramReverted,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// Failures dispersing batches to the DA layer or reading them back.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum DaError {
+    #[error("batch could not be dispersed: {0}")]
+    Dispersal(String),
+
+    /// The blob is not stored yet, or no longer.
+    #[error("blob {0} was not found")]
+    BlobNotFound(String),
+
+    #[error("blob cannot be decoded: {0}")]
+    Decode(String),
+
+    /// The DA client actor is not running, as while it restarts.
+    #[error("DA client is unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl DaError {
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            DaError::Dispersal(_) | DaError::BlobNotFound(_) | DaError::Unavailable(_)
+        )
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            DaError::BlobNotFound(_) => RpcErrorCode::NotFound,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// Failures applying what programs return.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum EngineError {
+    #[error("program {program_id} reverted: {reason}")]
+    ProgramReverted { program_id: String, reason: String },
+
+    /// The outputs ask for what the caller may not do.
+    #[error("{0}")]
+    InvalidOutputs(String),
+
+    /// The engine, or an actor it answers to, is not running.
+    #[error("engine is unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl EngineError {
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, EngineError::Unavailable(_))
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            EngineError::ProgramReverted { .. } | EngineError::InvalidOutputs(_) => {
+                RpcErrorCode::ProgramReverted
+            }
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// Failures running programs.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ExecutorError {
+    #[error("executor is at capacity with {queued} executions queued")]
+    Saturated { queued: usize },
+
+    #[error("executor restarted while the program ran")]
+    Restarting,
+
+    #[error("program artifact is unavailable: {0}")]
+    ArtifactUnavailable(String),
+
+    #[error("program ran past its deadline")]
+    TimedOut,
+
+    /// The program breached one of its limits, and would again.
+    #[error("{0}")]
+    ResourceExceeded(String),
+
+    #[error("program failed: {0}")]
+    Failed(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl ExecutorError {
+    pub fn is_retriable(&self) -> bool {
+        self.retry_reason().is_some()
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            ExecutorError::TimedOut => RpcErrorCode::DeadlineExceeded,
+            ExecutorError::ResourceExceeded(_) | ExecutorError::Failed(_) => {
+                RpcErrorCode::ProgramReverted
+            }
+            _ => RpcErrorCode::Internal,
+        }
+    }
+
+    /// Why a call that failed this way is run again, if it is.
+    pub fn retry_reason(&self) -> Option<RetryReason> {
+        match self {
+            ExecutorError::Saturated { .. } => Some(RetryReason::Saturated),
+            ExecutorError::Restarting => Some(RetryReason::Restarting),
+            ExecutorError::ArtifactUnavailable(_) => Some(RetryReason::ArtifactUnavailable),
+            ExecutorError::TimedOut => Some(RetryReason::TimedOut),
+            _ => None,
+        }
+    }
+}
+
+/// Requests turned away before anything ran, and actors the RPC server
+/// could not reach.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum RpcError {
+    #[error("{reason}")]
+    Rejected { code: RpcErrorCode, reason: String },
+
+    #[error("{0} is unavailable")]
+    Unavailable(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl RpcError {
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            RpcError::Rejected { code, .. } => retriable(*code),
+            RpcError::Unavailable(_) => true,
+            RpcError::Custom(_) => false,
+        }
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            RpcError::Rejected { code, .. } => *code,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
+
+/// Whether a request turned away with `code` may be taken if it is sent
+/// again later, unchanged.
+fn retriable(code: RpcErrorCode) -> bool {
+    matches!(
+        code,
+        RpcErrorCode::MempoolFull
+            | RpcErrorCode::RateLimited
+            | RpcErrorCode::ShuttingDown
+            | RpcErrorCode::Overloaded
+    )
+}
+
+/// Failures signing transactions, or sending them to a node.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum WalletError {
+    #[error("transaction could not be signed: {0}")]
+    Signing(String),
+
+    /// The node answered the transaction with an error.
+    #[error("node refused the transaction: {reason}")]
+    Refused { code: RpcErrorCode, reason: String },
+
+    #[error("node could not be reached: {0}")]
+    Unreachable(String),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl WalletError {
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            WalletError::Refused { code, .. } => retriable(*code),
+            WalletError::Unreachable(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn error_code(&self) -> RpcErrorCode {
+        match self {
+            WalletError::Refused { code, .. } => *code,
+            _ => RpcErrorCode::Internal,
+        }
+    }
+}
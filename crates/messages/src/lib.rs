@@ -1,5 +1,9 @@
 pub mod actor_types;
+pub mod errors;
 pub mod messages;
+pub mod proofs;
 
 pub use actor_types::*;
+pub use errors::*;
 pub use messages::*;
+pub use proofs::*;
@@ -1,17 +1,21 @@
-use crate::{ActorType, RpcRequestMethod};
+use crate::{ActorType, LasrError, RpcRequestMethod};
+use borsh::{BorshDeserialize, BorshSerialize};
 use derive_builder::Builder;
 use eigenda_client::batch::BatchHeaderHash;
 use eigenda_client::proof::BlobVerificationProof;
 use eigenda_client::response::BlobResponse;
 use eo_listener::EventType;
 use ethereum_types::H256;
-use lasr_types::{Account, Certificate, Outputs, Transaction};
-use lasr_types::{Address, Token, U256};
+pub use lasr_types::RpcErrorCode;
+use lasr_types::{Account, Certificate, ExecutionAttestation, Inputs, Outputs, Transaction};
+use lasr_types::{Address, Token, TransactionType, U256};
 use ractor::concurrency::OneshotSender;
 use ractor::RpcReplyPort;
 use ractor_cluster::RactorMessage;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
+use std::time::Duration;
 #[cfg(not(feature = "mock_storage"))]
 use tikv_client::RawClient as TikvClient;
 use web3::ethabi::{Address as EthereumAddress, FixedBytes};
@@ -20,9 +24,14 @@ use web3::types::TransactionReceipt;
 pub type ContractBlob = String;
 
 /// An error type for RPC Responses
-#[derive(thiserror::Error, Debug, Clone)]
+#[derive(
+    thiserror::Error, Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
 pub struct RpcResponseError {
     pub description: String,
+    /// What kind of failure it was, answered as the RPC error's code.
+    #[serde(default)]
+    pub code: RpcErrorCode,
 }
 
 /// Required trait to be considered an `Error` type
@@ -32,7 +41,60 @@ impl Display for RpcResponseError {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A submission turned away because the pending pool is full, and the
+/// sender is over their quota or no one else is.
+#[derive(
+    thiserror::Error,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+#[error(
+    "mempool full: {transactions} of {max_transactions} transactions and {bytes} of {max_bytes} bytes pending"
+)]
+pub struct MempoolFull {
+    pub transactions: u64,
+    pub max_transactions: u64,
+    pub bytes: u64,
+    pub max_bytes: u64,
+    /// Whether the sender already has more pending than their quota.
+    pub over_quota: bool,
+}
+
+/// Why a call failed through no fault of its own, so that running it again
+/// may succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RetryReason {
+    /// The executor's queue was full.
+    Saturated,
+    /// The executor restarted its runtime while the call ran.
+    Restarting,
+    /// The program's artifact could not be fetched or prepared.
+    ArtifactUnavailable,
+    /// The call ran past its deadline and the executor cancelled it before
+    /// it produced any outputs.
+    TimedOut,
+}
+
+impl Display for RetryReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetryReason::Saturated => write!(f, "executor saturated"),
+            RetryReason::Restarting => write!(f, "executor restarting"),
+            RetryReason::ArtifactUnavailable => write!(f, "artifact unavailable"),
+            RetryReason::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
 pub enum TransactionResponse {
     SendResponse(Token),
     CallResponse(Account),
@@ -40,6 +102,26 @@ pub enum TransactionResponse {
     GetAccountResponse(Account),
     RegisterProgramResponse(Option<String>),
     TransactionError(RpcResponseError),
+    /// The transaction was replaced while pending by the one with this hash.
+    Replaced(String),
+    /// The transaction waited too long to be validated and was evicted.
+    Expired,
+    /// The pending pool had no room for the transaction.
+    MempoolFull(MempoolFull),
+    /// The transaction was evicted from a full pending pool to make room for
+    /// one from a sender under their quota.
+    Evicted,
+    /// The same transaction was submitted before and has yet to be applied.
+    AlreadyPending,
+    /// The same transaction was submitted before and applied, with this
+    /// receipt.
+    AlreadyIncluded(Box<TransactionResponse>),
+    /// The transaction is not valid until `valid_after`, and is held until
+    /// then. Not its outcome, which follows once it is applied.
+    #[serde(rename_all = "camelCase")]
+    Scheduled {
+        valid_after: u64,
+    },
 }
 
 /// A message type that the RpcServer Actor can `handle`
@@ -55,6 +137,58 @@ pub enum RpcMessage {
     },
 }
 
+/// Cast by the mailbox monitor to a core actor, and handed back to it once
+/// the actor reaches it: how long it waited is how far behind the actor is,
+/// and the messages the actor handled meanwhile those queued ahead of it.
+#[derive(Debug, Clone, Copy)]
+pub struct MailboxProbe {
+    pub sent_at: std::time::Instant,
+    /// Messages the actor had handled when the probe was cast.
+    pub handled: u64,
+}
+
+impl From<MailboxProbe> for SchedulerMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        SchedulerMessage::ProbeMailbox(probe)
+    }
+}
+
+impl From<MailboxProbe> for ValidatorMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        ValidatorMessage::ProbeMailbox(probe)
+    }
+}
+
+impl From<MailboxProbe> for EngineMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        EngineMessage::ProbeMailbox(probe)
+    }
+}
+
+impl From<MailboxProbe> for AccountCacheMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        AccountCacheMessage::ProbeMailbox(probe)
+    }
+}
+
+impl From<MailboxProbe> for PendingTransactionMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        PendingTransactionMessage::ProbeMailbox(probe)
+    }
+}
+
+impl From<MailboxProbe> for BatcherMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        BatcherMessage::ProbeMailbox(probe)
+    }
+}
+
+impl From<MailboxProbe> for ExecutorMessage {
+    fn from(probe: MailboxProbe) -> Self {
+        ExecutorMessage::ProbeMailbox(probe)
+    }
+}
+
 /// Message types that the `Scheduler` actor can `handle`
 #[derive(Debug, RactorMessage)]
 pub enum SchedulerMessage {
@@ -64,6 +198,9 @@ pub enum SchedulerMessage {
     },
     Send {
         transaction: Transaction,
+        /// Whether the transaction replaces the one pending from the same
+        /// sender with the same nonce.
+        replace: bool,
         rpc_reply: RpcReplyPort<RpcMessage>,
     },
     RegisterProgram {
@@ -93,17 +230,59 @@ pub enum SchedulerMessage {
         address: Address,
         rpc_reply: RpcReplyPort<RpcMessage>,
     },
+    GetAttestation {
+        transaction_hash: String,
+        reply: RpcReplyPort<Option<ExecutionAttestation>>,
+    },
+    /// The receipt of a transaction already applied, replaced or failed.
+    GetReceipt {
+        transaction_hash: String,
+        reply: RpcReplyPort<Option<TransactionResponse>>,
+    },
     TransactionApplied {
         transaction_hash: String,
         token: Token,
     },
     SendTransactionFailure {
         transaction_hash: String,
-        error: Box<dyn std::error::Error + Send>,
+        error: LasrError,
+    },
+    /// The transaction was dropped from the pending pool before it was
+    /// validated, in favour of `replaced_by`.
+    TransactionReplaced {
+        transaction_hash: String,
+        replaced_by: String,
+    },
+    /// The transaction was evicted from the pending pool, having waited
+    /// longer than its TTL to be validated.
+    TransactionExpired {
+        transaction_hash: String,
+    },
+    /// The transaction is held in the pending pool until `valid_after`, the
+    /// earliest it may be applied.
+    TransactionScheduled {
+        transaction_hash: String,
+        valid_after: u64,
+    },
+    /// The transaction was turned away from a full pending pool.
+    MempoolFull {
+        transaction_hash: String,
+        full: MempoolFull,
+    },
+    /// The transaction was evicted from a full pending pool before it was
+    /// validated.
+    TransactionEvicted {
+        transaction_hash: String,
+    },
+    /// The transaction was in the pending pool already.
+    TransactionAlreadyPending {
+        transaction_hash: String,
     },
     CallTransactionApplied {
         transaction_hash: String,
         account: Account,
+        /// Set only in nodes built with the `attestations` feature.
+        attestation: Option<ExecutionAttestation>,
     },
     CallTransactionFailure {
         transaction_hash: String,
@@ -113,6 +292,27 @@ pub enum SchedulerMessage {
     CallTransactionAsyncPending {
         transaction_hash: String,
     },
+    /// The executor turned the call away because its execution queue is full.
+    ExecutorSaturated {
+        transaction_hash: String,
+        queued: usize,
+    },
+    /// The call was still running when the executor restarted its runtime.
+    ExecutorRestarting {
+        transaction_hash: String,
+    },
+    /// The program's artifact could not be prepared for the call.
+    ArtifactUnavailable {
+        transaction_hash: String,
+        error: String,
+    },
+    /// The call ran past its deadline and was cancelled. Unless the executor
+    /// confirmed it was stopped before producing outputs, they may have been
+    /// `committed`.
+    CallTimedOut {
+        transaction_hash: String,
+        committed: bool,
+    },
     RegistrationSuccess {
         transaction: Transaction,
         program_id: Address,
@@ -121,6 +321,13 @@ pub enum SchedulerMessage {
         transaction_hash: String,
         error_string: String,
     },
+    /// Stops taking new transactions for the node to shut down, replying
+    /// with how many are still in flight.
+    Drain {
+        reply: RpcReplyPort<usize>,
+    },
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
 }
 
 /// A message type that the `Validator` actor can handle
@@ -136,6 +343,8 @@ pub enum ValidatorMessage {
     PendingRegistration {
         transaction: Transaction,
     },
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
 }
 
 /// A message type that the Engine can `handle`
@@ -146,6 +355,7 @@ pub enum EngineMessage {
     },
     Send {
         transaction: Transaction,
+        replace: bool,
     },
     RegisterProgram {
         transaction: Transaction,
@@ -153,6 +363,11 @@ pub enum EngineMessage {
     EoEvent {
         event: EoEvent,
     },
+    /// An event that was emitted in a block the settlement layer has since
+    /// abandoned, and must be undone.
+    EoRetraction {
+        event: EoEvent,
+    },
     BlobIndexAcquired {
         address: Address,
         batch_header_hash: String,
@@ -174,7 +389,24 @@ pub enum EngineMessage {
     RegistrationSuccess {
         transaction_hash: String,
     },
+    /// Mints `amount` of `program_id` to `address` outside of the settlement
+    /// layer, as the devnet faucet does, replying with the hash of the
+    /// minting transaction. `id` tells mints of the same amount apart.
+    Mint {
+        address: Address,
+        program_id: Address,
+        amount: U256,
+        id: U256,
+        reply: RpcReplyPort<Result<String, String>>,
+    },
+    /// Replies with how many transactions the engine is still working on,
+    /// and so has yet to write to the account cache.
+    Flush {
+        reply: RpcReplyPort<usize>,
+    },
     CommTest,
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
 }
 
 /// An event type that the Executable Oracle contract listener
@@ -259,6 +491,88 @@ pub enum HashOrError {
     Error(web3::Error),
 }
 
+/// What a batch commits to, submitted to the settlement contract once the
+/// batch is finalized on the DA layer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchHeader {
+    pub batch_id: u64,
+    /// Commits to the accounts as the batch left them.
+    pub state_root: H256,
+    /// Commits to the hashes of the batch's transactions. Not yet part of
+    /// what the settlement contract is submitted.
+    #[serde(default)]
+    pub transactions_root: H256,
+    /// Commits to the events the batch's calls emitted. Not yet part of
+    /// what the settlement contract is submitted.
+    #[serde(default)]
+    pub events_root: H256,
+    /// The DA layer's header hash of the blob the batch is in.
+    pub blob_commitment: H256,
+    pub blob_index: u128,
+    /// The value the batch's bridge outs took off the chain, in the order of
+    /// their hashes, for the settlement contract to release.
+    #[serde(default)]
+    pub withdrawals: Vec<Withdrawal>,
+}
+
+/// The value a bridge out took off the chain, released to `recipient` on the
+/// settlement layer once the batch it is in is settled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Withdrawal {
+    /// The program whose token was taken off the chain.
+    pub token: Address,
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+impl Withdrawal {
+    /// What `transaction` withdraws, if it is a bridge out.
+    pub fn of(transaction: &Transaction) -> Option<Self> {
+        match transaction.transaction_type() {
+            TransactionType::BridgeOutTo { recipient, .. } => Some(Withdrawal {
+                token: transaction.program_id(),
+                recipient,
+                amount: transaction.value(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "status",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum BatchStatus {
+    /// Finalized on the DA layer, and not yet submitted.
+    Finalized,
+    /// Sent to the settlement layer, `attempts` times counting
+    /// replacements, as `transaction_hash` last.
+    Submitted {
+        transaction_hash: H256,
+        attempts: u32,
+    },
+    Settled {
+        transaction_hash: H256,
+        block_number: u64,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchReceipt {
+    pub header: BatchHeader,
+    #[serde(flatten)]
+    pub status: BatchStatus,
+}
+
 /// A message type that the `EoServer` can `handle
 #[derive(Debug, RactorMessage)]
 pub enum EoMessage {
@@ -266,6 +580,11 @@ pub enum EoMessage {
         log: Vec<web3::ethabi::Log>,
         log_type: EventType,
     },
+    /// Logs emitted in blocks abandoned by a settlement layer reorg.
+    Retract {
+        log: Vec<web3::ethabi::Log>,
+        log_type: EventType,
+    },
     Bridge {
         program_id: Address,
         address: Address,
@@ -277,6 +596,11 @@ pub enum EoMessage {
         batch_header_hash: H256,
         blob_index: u128,
     },
+    /// Submits the header of a batch finalized on the DA layer to the
+    /// settlement contract.
+    SettleBatchHeader {
+        header: BatchHeader,
+    },
     GetContractBlobIndex {
         program_id: Address,
         sender: OneshotSender<EoMessage>,
@@ -340,6 +664,9 @@ pub enum DaClientMessage {
     StoreBatch {
         batch: String,
         tx: OneshotSender<Result<BlobResponse, std::io::Error>>,
+        /// The span the batch is dispersed in, linked to the spans of the
+        /// transactions in it.
+        span: tracing::Span,
     },
     StoreTransactionBlob,
     ValidateBlob {
@@ -364,6 +691,12 @@ pub enum DaClientMessage {
         blob_index: u128,
         tx: OneshotSender<Option<ContractBlob>>,
     },
+    /// Retrieves the data of any blob, such as a program's package.
+    RetrieveBlob {
+        batch_header_hash: H256,
+        blob_index: u128,
+        tx: OneshotSender<Result<Vec<u8>, std::io::Error>>,
+    },
     EoEvent {
         event: EoEvent,
     },
@@ -377,11 +710,22 @@ pub enum AccountCacheMessage {
         who: ActorType,
         location: String,
     },
+    WriteBatch {
+        accounts: Vec<Account>,
+        who: ActorType,
+        location: String,
+    },
     Read {
         address: Address,
         tx: OneshotSender<Option<Account>>,
         who: ActorType,
     },
+    /// Reads every one of `addresses` as of the same moment, answering the
+    /// ones with an account.
+    ReadSnapshot {
+        addresses: Vec<Address>,
+        tx: OneshotSender<HashMap<Address, Account>>,
+    },
     Remove {
         address: Address,
     },
@@ -392,6 +736,44 @@ pub enum AccountCacheMessage {
         address: Address,
         reply: RpcReplyPort<RpcMessage>,
     },
+    WriteReceipt {
+        transaction_hash: String,
+        receipt: TransactionResponse,
+    },
+    ReadReceipt {
+        transaction_hash: String,
+        tx: OneshotSender<Option<TransactionResponse>>,
+    },
+    WriteAttestation {
+        transaction_hash: String,
+        attestation: ExecutionAttestation,
+    },
+    ReadAttestation {
+        transaction_hash: String,
+        tx: OneshotSender<Option<ExecutionAttestation>>,
+    },
+    /// Deletes the receipts and attestations of `transaction_hashes`, whose
+    /// batches are past the node's retention.
+    PruneReceipts {
+        transaction_hashes: Vec<String>,
+    },
+    /// Holds what `transaction` takes from its sender until it is applied or
+    /// turned away, answering why not if the sender cannot spend it on top
+    /// of what it already holds.
+    ReserveDebit {
+        transaction: Transaction,
+        tx: OneshotSender<Result<(), String>>,
+    },
+    /// Releases what the transaction `transaction_hash` held, once it is
+    /// applied or turned away.
+    ReleaseDebit {
+        transaction_hash: String,
+    },
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
+    /// Panics the actor, for tests that it is restarted with its state.
+    #[cfg(feature = "crash_test")]
+    Panic,
 }
 
 #[derive(Debug, RactorMessage)]
@@ -403,6 +785,9 @@ pub enum BlobCacheMessage {
     },
     Get,
     Remove,
+    /// Panics the actor, for tests that it is restarted with its state.
+    #[cfg(feature = "crash_test")]
+    Panic,
 }
 
 #[derive(Debug, RactorMessage)]
@@ -410,6 +795,9 @@ pub enum PendingTransactionMessage {
     New {
         transaction: Transaction,
         outputs: Option<Outputs>,
+        /// Whether the transaction replaces the one pending from the same
+        /// sender with the same nonce.
+        replace: bool,
     },
     NewCall {
         transaction: Transaction,
@@ -417,13 +805,44 @@ pub enum PendingTransactionMessage {
     ExecSuccess {
         transaction: Transaction,
     },
+    /// Runs the call again after `after`, once it failed for `reason`.
+    /// Later calls from its sender are held until it completes or fails.
+    RetryCall {
+        transaction_hash: String,
+        reason: RetryReason,
+        after: std::time::Duration,
+    },
+    /// The call failed for good, so calls waiting on it may go ahead.
+    CallFailed {
+        transaction_hash: String,
+    },
+    /// Dispatch `dispatch` of the call reached its deadline.
+    CallDeadline {
+        transaction_hash: String,
+        dispatch: u64,
+    },
+    /// The executor answered the cancellation of a call past its deadline,
+    /// having `confirmed` it stopped the call before its outputs were taken.
+    CallCancelled {
+        transaction_hash: String,
+        confirmed: bool,
+    },
+    /// The executor can take `credits` calls at once. Voids the credits it
+    /// granted before, so those held by calls lost with a restarted
+    /// executor are not waited on.
+    ExecutionCredits {
+        credits: usize,
+    },
+    GetExecutionCredits {
+        reply: RpcReplyPort<ExecutionCreditStatus>,
+    },
     Valid {
         transaction: Transaction,
         cert: Option<Certificate>,
     },
     Invalid {
         transaction: Transaction,
-        e: Box<dyn std::error::Error + Send>,
+        e: LasrError,
     },
     Confirmed {
         map: HashMap<Address, Transaction>,
@@ -434,12 +853,71 @@ pub enum PendingTransactionMessage {
         transaction_hash: String,
         sender: OneshotSender<Option<Transaction>>,
     },
+    /// Every transaction pending from `address`, in nonce order.
+    GetPendingByAddress {
+        address: Address,
+        reply: RpcReplyPort<Vec<PendingTransactionSummary>>,
+    },
+    GetPendingByHash {
+        transaction_hash: [u8; 32],
+        reply: RpcReplyPort<Option<PendingTransactionSummary>>,
+    },
     ValidCall {
         outputs: Outputs,
         transaction: Transaction,
         cert: Option<Certificate>,
     },
     CleanGraph,
+    /// Submits the transactions held until a time that has now come.
+    ReleaseScheduled,
+    /// Resubmits the transactions read back from the pending log, once the
+    /// actors they are validated by are up.
+    Replay,
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
+    /// Panics the actor, for tests that it is restarted with its state.
+    #[cfg(feature = "crash_test")]
+    Panic,
+}
+
+/// Where a transaction in the pending pool has got to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum PendingStatus {
+    /// Waiting on transactions before it that touch the same accounts.
+    Pending,
+    /// Handed to the validator.
+    Validating,
+    /// Held for a gap in its sender's nonces, until `waiting_on` arrives.
+    #[serde(rename_all = "camelCase")]
+    Queued { waiting_on: U256 },
+    /// Not valid until `valid_after`, in seconds since the epoch, and held
+    /// apart from the sender's nonces until then.
+    #[serde(rename_all = "camelCase")]
+    Scheduled { valid_after: u64 },
+}
+
+/// How many calls the executor has room for, and how many are with it or
+/// waiting for room.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionCreditStatus {
+    /// The credits the executor granted, or none if it has not, in which
+    /// case calls are not held back.
+    pub granted: Option<usize>,
+    pub in_flight: usize,
+    pub waiting: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTransactionSummary {
+    pub hash: String,
+    pub nonce: U256,
+    pub transaction_type: String,
+    /// Milliseconds since the transaction reached the pool.
+    pub age_ms: u64,
+    pub status: PendingStatus,
 }
 
 #[derive(RactorMessage)]
@@ -448,6 +926,12 @@ pub enum BatcherMessage {
         transaction: Transaction,
         outputs: Option<Outputs>,
     },
+    /// A registered program whose artifact has been prepared, along with
+    /// the runtime it executes in.
+    AppendProgramRegistration {
+        transaction: Transaction,
+        runtime: String,
+    },
     GetNextBatch {
         #[cfg(not(feature = "mock_storage"))]
         storage_ref: TikvClient,
@@ -458,6 +942,54 @@ pub enum BatcherMessage {
         request_id: String,
         proof: BlobVerificationProof,
     },
+    /// Takes back the credit of a bridge in whose event was retracted.
+    RevertBridgeIn { transaction: Transaction },
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
+    /// Panics the actor, for tests that it is restarted with its state.
+    #[cfg(feature = "crash_test")]
+    Panic,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecutorStatus {
+    /// No health check has finished yet.
+    #[default]
+    Starting,
+    Healthy,
+    /// Recent health checks failed, but not enough of them to restart.
+    Degraded,
+    Restarting,
+    /// The runtime could not be brought back by a restart.
+    Unhealthy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutorHealth {
+    pub status: ExecutorStatus,
+    pub consecutive_failures: u32,
+    pub restarts: u64,
+    pub last_error: Option<String>,
+    /// Unix timestamp of the last health check, in seconds.
+    pub last_checked: Option<u64>,
+}
+
+/// Two executions of the same transaction that produced different outputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionMismatch {
+    pub transaction_hash: String,
+    pub program_id: String,
+    pub content_id: String,
+    /// Everything the program was given, so the mismatch can be reproduced.
+    pub inputs: Inputs,
+    /// The outputs that were used.
+    pub first_output: String,
+    pub second_output: String,
+    /// Unix timestamp the mismatch was found at, in seconds.
+    pub detected_at: u64,
 }
 
 #[derive(Debug, RactorMessage)]
@@ -489,4 +1021,42 @@ pub enum ExecutorMessage {
     PollJobStatus {
         job_id: uuid::Uuid,
     },
+    /// Asks the executor to grant the pending transactions its execution
+    /// credits again.
+    GrantCredits,
+    /// Stops the execution of a call that ran past its deadline, answering
+    /// whether it was stopped before its outputs were taken.
+    Cancel {
+        transaction_hash: String,
+        reply: RpcReplyPort<bool>,
+    },
+    /// Checks the runtime is still able to execute programs.
+    HealthCheck,
+    /// Sent to the executor supervisor whenever the executor's health changes.
+    HealthReport(ExecutorHealth),
+    GetHealth {
+        reply: RpcReplyPort<ExecutorHealth>,
+    },
+    GetExecutionMismatches {
+        reply: RpcReplyPort<Vec<ExecutionMismatch>>,
+    },
+    /// Runs `program` with `inputs` and answers its outputs, or why it
+    /// failed, without applying them or publishing its logs.
+    Simulate {
+        program: Account,
+        inputs: Inputs,
+        reply: RpcReplyPort<Result<String, LasrError>>,
+    },
+    /// Runs the auth `program` with `inputs` as a simulation is run, held
+    /// to `timeout` and `memory_bytes` as well as its own limits, and
+    /// answers its outputs, or why it failed.
+    Authorize {
+        program: Account,
+        inputs: Inputs,
+        timeout: Duration,
+        memory_bytes: u64,
+        reply: RpcReplyPort<Result<String, LasrError>>,
+    },
+    /// Handed back to the mailbox monitor once reached.
+    ProbeMailbox(MailboxProbe),
 }
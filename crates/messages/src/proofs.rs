@@ -0,0 +1,54 @@
+//! Proofs that a transaction, and the accounts it left behind, went into a
+//! batch, carried in its receipt.
+//!
+//! A batch commits to the hashes of its transactions in its transactions
+//! root, and to its accounts as it left them in its state root, both Merkle
+//! roots. The batcher proves each transaction against them as the batch is
+//! finalized. `verify_receipt` checks a proof against the header of the
+//! batch, which `getBatch` answers, so a wallet can check a receipt without
+//! trusting the node that gave it.
+use ethereum_types::H256;
+use lasr_types::{account_leaf, transaction_leaf, Address, MerkleProof};
+use serde::{Deserialize, Serialize};
+
+use crate::BatchHeader;
+
+/// That an account was left as hashed by the batch it is proven in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: Address,
+    /// Keccak256 of the account as it is persisted.
+    pub account_hash: H256,
+    /// Of the account against the batch's state root.
+    pub proof: MerkleProof,
+}
+
+/// That a transaction went into the batch `batch_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InclusionProof {
+    pub batch_id: u64,
+    pub transaction_hash: H256,
+    /// Of the transaction's hash against the batch's transactions root.
+    pub proof: MerkleProof,
+    /// The accounts the transaction was sent from and to, as the batch left
+    /// them.
+    pub accounts: Vec<AccountProof>,
+}
+
+/// Whether `receipt` proves its transaction, and every account it names,
+/// went into the batch `header` is of.
+pub fn verify_receipt(receipt: &InclusionProof, header: &BatchHeader) -> bool {
+    receipt.batch_id == header.batch_id
+        && receipt.proof.verify(
+            transaction_leaf(&receipt.transaction_hash),
+            header.transactions_root,
+        )
+        && receipt.accounts.iter().all(|account| {
+            account.proof.verify(
+                account_leaf(&account.address, &account.account_hash),
+                header.state_root,
+            )
+        })
+}
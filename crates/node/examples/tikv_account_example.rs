@@ -21,7 +21,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .program_account_data(ArbitraryData::new())
         .program_account_metadata(Metadata::new())
         .program_account_linked_programs(BTreeSet::new())
-        .build()?;
+        .try_build()?;
     println!(
         "Mock user account built: {:?}",
         mock_user_account.owner_address().to_full_string()
@@ -36,7 +36,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .program_account_data(ArbitraryData::new())
         .program_account_metadata(Metadata::new())
         .program_account_linked_programs(BTreeSet::new())
-        .build()?;
+        .try_build()?;
 
     if let AccountType::Program(program_addr) = mock_program_account.account_type() {
         println!(
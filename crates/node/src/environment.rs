@@ -1,14 +1,23 @@
 use lazy_static::lazy_static;
+/// The secrets and remote endpoints the node reads from the environment
+/// alone, everything else being in its `NodeConfig`.
 pub struct Environment {
     pub secret_key: String,
-    pub blocks_processed_path: String,
-    pub batch_interval: String,
-    pub eo_contract_address: String,
-    pub eth_rpc_url: String,
     pub compute_rpc_url: String,
     pub storage_rpc_url: String,
-    pub port: String,
     pub vipfs_address: Option<String>,
+    /// Key execution attestations are signed with, the operator key unless
+    /// set separately.
+    pub attestation_secret_key: String,
+    /// Comma separated signers of attestations made with keys since rotated
+    /// out, still trusted when verifying.
+    pub attestation_retired_signers: String,
+    /// Password of the keystore the operator keys are loaded from instead,
+    /// when the node is configured with one.
+    pub keystore_password: Option<String>,
+    /// Bearer token the admin methods are answered to on the public
+    /// listener, if they are.
+    pub admin_token: Option<String>,
 }
 
 fn get_var_or_err(var: &str, err_buf: &mut String) -> String {
@@ -25,30 +34,33 @@ fn get_var_or_err(var: &str, err_buf: &mut String) -> String {
 impl Environment {
     pub fn new() -> Self {
         let mut err_buf = String::new();
-        let secret_key = get_var_or_err("SECRET_KEY", &mut err_buf);
-        let blocks_processed_path = get_var_or_err("BLOCKS_PROCESSED_PATH", &mut err_buf);
-        let batch_interval = get_var_or_err("BATCH_INTERVAL", &mut err_buf);
-        let eo_contract_address = get_var_or_err("EO_CONTRACT_ADDRESS", &mut err_buf);
-        let eth_rpc_url = get_var_or_err("ETH_RPC_URL", &mut err_buf);
-
+        let keystore_password = std::env::var("KEYSTORE_PASSWORD").ok();
+        // A keystore holds the keys the environment would otherwise give.
+        let secret_key = match keystore_password {
+            Some(_) => std::env::var("SECRET_KEY").unwrap_or_default(),
+            None => get_var_or_err("SECRET_KEY", &mut err_buf),
+        };
         let compute_rpc_url = get_var_or_err("COMPUTE_RPC_URL", &mut err_buf);
         let storage_rpc_url = get_var_or_err("STORAGE_RPC_URL", &mut err_buf);
-        let port = std::env::var("PORT").unwrap_or_else(|_| "9292".to_string());
         let vipfs_address = std::env::var("VIPFS_ADDRESS").ok();
+        let attestation_secret_key =
+            std::env::var("ATTESTATION_SECRET_KEY").unwrap_or_else(|_| secret_key.clone());
+        let attestation_retired_signers =
+            std::env::var("ATTESTATION_RETIRED_SIGNERS").unwrap_or_default();
+        let admin_token = std::env::var("ADMIN_RPC_TOKEN").ok();
 
         if !err_buf.is_empty() {
             panic!("Environment variables missing: {}", err_buf);
         }
         Self {
             secret_key,
-            blocks_processed_path,
-            batch_interval,
-            eo_contract_address,
-            eth_rpc_url,
             compute_rpc_url,
             storage_rpc_url,
-            port,
             vipfs_address,
+            attestation_secret_key,
+            attestation_retired_signers,
+            keystore_password,
+            admin_token,
         }
     }
 }
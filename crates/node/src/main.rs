@@ -1,22 +1,26 @@
 #![allow(unreachable_code)]
-use std::{io::Read, path::PathBuf, str::FromStr, sync::Arc};
+use std::{io::Read, path::Path, sync::Arc};
 
 use eo_listener::{BlocksProcessed, EoServer as EoListener, EoServerError};
 use futures::StreamExt;
-use jsonrpsee::server::ServerBuilder as RpcServerBuilder;
 use lasr_actors::{
-    graph_cleaner, helpers::Coerce, AccountCacheActor, AccountCacheSupervisor, ActorExt,
-    ActorManager, ActorManagerBuilder, Batcher, BatcherActor, BatcherError, BatcherSupervisor,
-    BlobCacheActor, BlobCacheSupervisor, DaClient, DaClientActor, DaClientSupervisor, EngineActor,
-    EngineSupervisor, EoClient, EoClientActor, EoClientSupervisor, EoServerActor,
-    EoServerSupervisor, EoServerWrapper, ExecutionEngine, ExecutorActor, ExecutorSupervisor,
-    LasrRpcServerActor, LasrRpcServerImpl, LasrRpcServerSupervisor, PendingTransactionActor,
-    PendingTransactionSupervisor, StorageRef, TaskScheduler, TaskSchedulerSupervisor,
-    ValidatorActor, ValidatorCore, ValidatorSupervisor, STORAGE_PROCESSED_BLOCKS_KEY,
+    graph_cleaner, helpers::Coerce, read_only_rpc, replay_pending_log, rest_router, serve_rpc,
+    AccountCacheActor, AccountCacheSupervisor, ActorExt, ActorManager, ActorManagerBuilder,
+    AdminAccess, Attestor, BatchFeed, BatchIndex, Batcher, BatcherActor, BatcherError,
+    BatcherSupervisor, BlobCacheActor, BlobCacheSupervisor, CommitLog, DaBackend, DaClient,
+    DaClientActor, DaClientSupervisor, EngineActor, EngineSupervisor, EoClient, EoClientActor,
+    EoClientSupervisor, EoServerActor, EoServerConfig, EoServerSupervisor, EoServerWrapper,
+    EventLog, ExecutionEngine, ExecutorActor, ExecutorSupervisor, Faucet, Follower, FollowerConfig,
+    Genesis, GenesisOutcome, KeyHandle, KeyRole, LasrRpcServerActor, LasrRpcServerImpl,
+    LasrRpcServerSupervisor, LocalFileDa, NodeConfig, NodeKeys, NodeKeysError,
+    PendingTransactionActor, PendingTransactionSupervisor, PruneTier, Pruner, RateLimiter, Replica,
+    Restarts, Settler, SettlerConfig, Shutdown, ShutdownDrain, SpanTimings, StorageRef,
+    TaskScheduler, TaskSchedulerSupervisor, TransactionIndex, ValidatorActor, ValidatorCore,
+    ValidatorSupervisor, STORAGE_PROCESSED_BLOCKS_KEY,
 };
 use lasr_compute::{OciBundler, OciBundlerBuilder, OciManager};
 use lasr_messages::{ActorName, ActorType, ToActorType};
-use lasr_rpc::LasrRpcServer;
+use lasr_rpc::{AdminRpcServer, EthRpcServer, LasrRpcServer};
 #[cfg(feature = "mock_storage")]
 use lasr_types::MockPersistenceStore;
 use lasr_types::{Address, PersistenceStore};
@@ -31,31 +35,47 @@ use tokio::sync::{
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::Layer;
-use web3::types::BlockNumber;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter, Layer};
 
 pub(crate) mod environment;
 pub(crate) use environment::ENVIRONMENT;
 
+/// What is logged to file when `LOG_FILTER` is not set, or is invalid.
+const DEFAULT_LOG_FILTER: &str = "info";
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv::dotenv().ok();
     let file_appender = tracing_appender::rolling::daily("./logs", "lasr.log");
 
-    // Create a file layer with a lower log level (e.g., below ERROR).
+    // The file layer logs what `LOG_FILTER` lets through, and spans as they
+    // close with how long they were open: `info,lasr::lifecycle=debug` adds
+    // every stage of every transaction, each carrying the transaction's hash.
+    let log_filter = std::env::var("LOG_FILTER").unwrap_or_else(|_| DEFAULT_LOG_FILTER.to_string());
+    let (file_filter, invalid_filter) = match EnvFilter::try_new(&log_filter) {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new(DEFAULT_LOG_FILTER), Some(e)),
+    };
     let file_layer = tracing_subscriber::fmt::Layer::new()
         .with_writer(file_appender)
-        .with_filter(LevelFilter::INFO);
+        .with_span_events(FmtSpan::CLOSE)
+        .with_filter(file_filter);
 
     // Create a stdout layer with ERROR log level.
     let stdout_layer = tracing_subscriber::fmt::Layer::new()
         .with_writer(std::io::stdout)
         .with_filter(LevelFilter::ERROR);
 
-    // Set up the subscriber with both layers.
+    // Set up the subscriber with both layers, timing lifecycle spans
+    // whatever is logged.
     tracing_subscriber::registry()
         .with(file_layer)
         .with(stdout_layer)
+        .with(SpanTimings::layer())
         .init();
+    if let Some(e) = invalid_filter {
+        tracing::warn!("LOG_FILTER {log_filter:?} is invalid, logging {DEFAULT_LOG_FILTER}: {e}");
+    }
 
     tracing::info!("Current Working Directory: {:?}", std::env::current_dir());
 
@@ -65,24 +85,69 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         option_env!("GIT_REV").unwrap_or("N/A")
     );
 
-    dotenv::dotenv().ok();
+    // Every invalid setting is reported before anything is started.
+    let config = NodeConfig::load().map_err(|e| {
+        tracing::error!("{e}");
+        Box::new(e)
+    })?;
+    let da_client = match config.da.backend {
+        DaBackend::EigenDa => DaClient::new(
+            eigenda_client::EigenDaGrpcClientBuilder::default()
+                .proto_path(config.da.proto_path.to_string_lossy().to_string())
+                .server_address(config.da.server_address.clone())
+                .build()?,
+        ),
+        DaBackend::LocalFile => {
+            tracing::warn!(
+                "storing batches locally in {}, they are not dispersed",
+                config.da.local_path.display()
+            );
+            DaClient::local(LocalFileDa::open(&config.da.local_path)?)
+        }
+    };
+    // A follower needs neither operator keys nor a settlement chain.
+    if let Some(follower) = config.follow.follower() {
+        return follow(follower, &config, da_client).await;
+    }
+
     let env = &*ENVIRONMENT;
-    //TODO(asmith): Move this to be read in when and where needed and dropped
-    //afterwards to minimize security vulnerabilities
-    let sk = web3::signing::SecretKey::from_str(&env.secret_key).map_err(Box::new)?;
-    let eigen_da_client = eigenda_client::EigenDaGrpcClientBuilder::default()
-        .proto_path("./eigenda/api/proto/disperser/disperser.proto".to_string())
-        //TODO(asmith): Move the network endpoint for EigenDA to an
-        //environment variable.
-        .server_address("disperser-holesky.eigenda.xyz:443".to_string())
-        .build()?;
+    let node_keys = match (&config.keys.keystore_path, &env.keystore_password) {
+        (Some(dir), Some(password)) => NodeKeys::open(dir, password),
+        (Some(_), None) => Err(NodeKeysError::Custom(
+            "KEYSTORE_PASSWORD must be set to open the keystore".to_string(),
+        )),
+        (None, _) => NodeKeys::from_config(
+            &env.secret_key,
+            &env.attestation_secret_key,
+            &env.attestation_retired_signers,
+        ),
+    }
+    .map_err(|e| {
+        tracing::error!("failed to load the operator keys: {e}");
+        Box::new(e)
+    })?;
+    for key in node_keys.fingerprints() {
+        tracing::warn!(
+            "{} key {} ({})",
+            key.role,
+            key.signer.to_full_string(),
+            key.fingerprint
+        );
+    }
 
-    tracing::warn!("Ethereum RPC URL: {}", env.eth_rpc_url);
-    let http = web3::transports::Http::new(&env.eth_rpc_url).expect("Invalid ETH_RPC_URL");
+    let eth_rpc_urls = &config.eo_server.eth_rpc_urls;
+    tracing::warn!("Ethereum RPC URLs: {eth_rpc_urls:?}");
+    let http = web3::transports::Http::new(&eth_rpc_urls[0]).expect("Invalid ETH_RPC_URL");
     let web3_instance: web3::Web3<web3::transports::Http> = web3::Web3::new(http);
-    let eo_client = Arc::new(Mutex::new(
-        setup_eo_client(web3_instance.clone(), sk).await?,
-    ));
+    let eo_client = setup_eo_client(
+        web3_instance.clone(),
+        node_keys.key(KeyRole::Settlement),
+        &config.eo_server.contract_address,
+        config.settlement.settler(),
+    )
+    .await?;
+    let batch_receipts = eo_client.batch_receipts();
+    let eo_client = Arc::new(Mutex::new(eo_client));
 
     #[cfg(not(feature = "mock_storage"))]
     let persistence_storage = <TikvClient as PersistenceStore>::new().await?;
@@ -90,13 +155,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let persistence_storage =
         <MockPersistenceStore<String, Vec<u8>> as PersistenceStore>::new().await?;
 
+    // The genesis is applied before any actor reads storage, and a node
+    // whose storage was started from another refuses to start.
+    let genesis = config
+        .genesis
+        .path
+        .as_deref()
+        .map(Genesis::load)
+        .transpose()
+        .map_err(Box::new)?;
+    if let Some(genesis) = &genesis {
+        // Transactions are signed for the genesis' chain, and so hashed.
+        if !lasr_types::set_chain_id(genesis.chain_id) {
+            return Err(format!(
+                "transactions were already hashed for chain {}, not {}",
+                lasr_types::chain_id(),
+                genesis.chain_id
+            )
+            .into());
+        }
+        let outcome = genesis
+            .apply_encoded(&persistence_storage, config.storage.encoding)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e}");
+                Box::new(e)
+            })?;
+        match outcome {
+            GenesisOutcome::Applied => tracing::info!("applied genesis {}", genesis.hash()?),
+            GenesisOutcome::AlreadyApplied => {
+                tracing::info!("storage was started from genesis {}", genesis.hash()?)
+            }
+        }
+    }
+
     let inner_eo_server = setup_eo_server(
         web3_instance.clone(),
-        &env.blocks_processed_path,
+        &config.eo_server,
         persistence_storage.clone(),
     )
     .await
     .map_err(Box::new)?;
+    let settlement_providers = inner_eo_server.provider_metrics();
+    let event_filters = inner_eo_server.event_filters();
+    let settlement_backfill = inner_eo_server.settlement_backfill();
 
     #[cfg(not(feature = "remote"))]
     let bundler: OciBundler<String, String> = OciBundlerBuilder::default()
@@ -107,10 +209,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build()?;
 
     #[cfg(not(feature = "remote"))]
-    let oci_manager = OciManager::new(bundler, env.vipfs_address.clone());
+    let oci_manager = OciManager::new(bundler, env.vipfs_address.clone())
+        .with_limits(config.executor.limits_config())
+        .with_execution_logs(config.executor.execution_logs())
+        .with_program_fetchers(config.executor.program_fetchers(env.vipfs_address.clone()))
+        .with_program_fetcher(Arc::new(lasr_actors::DaProgramFetcher));
+
+    // Executions that run remotely log there, so the hub stays empty.
+    #[cfg(feature = "remote")]
+    let execution_logs = config.executor.execution_logs();
+    #[cfg(not(feature = "remote"))]
+    let execution_logs = oci_manager.execution_logs().clone();
 
+    let program_stats = config.program_stats.open().map_err(Box::new)?;
+    let admission_policy = config.admission.policy_gate().map_err(Box::new)?;
+    #[cfg(not(feature = "remote"))]
+    let execution_pool = config.executor.pool();
     #[cfg(not(feature = "remote"))]
-    let execution_engine = Arc::new(Mutex::new(ExecutionEngine::new(oci_manager)));
+    let execution_engine = Arc::new(Mutex::new(
+        ExecutionEngine::new(oci_manager)
+            .with_execution_pool(execution_pool.clone())
+            .with_restart_policy(config.executor.health)
+            .with_input_limits(config.admission.input_limits())
+            .with_artifact_cache(config.caches.artifact_cache())
+            .with_verifier(config.executor.verifier())
+            .with_program_stats(program_stats.clone())
+            .with_admission_policy(admission_policy.clone()),
+    ));
 
     #[cfg(feature = "remote")]
     tracing::info!("Attempting to connect compute agent");
@@ -131,7 +256,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 
     #[cfg(feature = "remote")]
-    let execution_engine = ExecutionEngine::new(compute_rpc_client, storage_rpc_client);
+    let execution_engine = ExecutionEngine::new(compute_rpc_client, storage_rpc_client)
+        .with_artifact_cache(config.caches.artifact_cache())
+        .with_admission_policy(admission_policy.clone());
 
     let (panic_tx, mut panic_rx): (Sender<ActorCell>, Receiver<ActorCell>) =
         tokio::sync::mpsc::channel(120);
@@ -210,25 +337,103 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .await
             .map_err(Box::new)?;
 
+    let notifications = config.rpc.notifications();
     let blob_cache_actor = BlobCacheActor::new();
-    let account_cache_actor = AccountCacheActor::new();
-    let pending_transaction_actor = PendingTransactionActor::new();
+    let account_cache_actor = AccountCacheActor::new()
+        .with_notifications(notifications.clone())
+        .with_encoding(config.storage.encoding)
+        .with_verse_audit(config.caches.audit_verse_supply);
+    let pending_transaction_actor = PendingTransactionActor::new()
+        .with_config(config.pending.clone())
+        .with_mempool_limits(config.caches.mempool)
+        .with_scheduling(config.scheduling.clone())
+        .with_fee_schedule(config.fees.schedule());
     let lasr_rpc_actor = LasrRpcServerActor::new();
-    let scheduler_actor = TaskScheduler::new();
+    let dead_letters = config.dead_letters.open().map_err(Box::new)?;
+    let mailboxes = config.mailbox_monitor();
+    let scheduler_actor = TaskScheduler::new()
+        .with_notifications(notifications.clone())
+        .with_dead_letters(dead_letters.clone())
+        .with_mailboxes(mailboxes.clone())
+        .with_retry_policy(config.retry)
+        .with_persisted_receipts(config.receipts.persist)
+        .with_recent_window(config.receipts.recent_window);
     let eo_server_actor = EoServerActor::new();
-    let engine_actor = EngineActor::new();
+    let engine_actor = EngineActor::new().with_output_limits(config.outputs);
     let validator_actor = ValidatorActor::new();
     let eo_client_actor = EoClientActor::new();
-    let da_client_actor = DaClientActor::new();
+    let da_client_actor = DaClientActor::new().with_health(config.da.health());
+    let da_health = da_client_actor.health();
     let batcher_actor = BatcherActor::new();
     let executor_actor = ExecutorActor::new();
+    let mempool_metrics = pending_transaction_actor.mempool_metrics();
+    let retry_metrics = scheduler_actor.retry_metrics();
 
     let (receivers_thread_tx, receivers_thread_rx) = tokio::sync::mpsc::channel(128);
-    let batcher = Arc::new(Mutex::new(Batcher::new(receivers_thread_tx)));
+    let attestor = Attestor::from_keys(&node_keys);
+    let batch_index = BatchIndex::open(&config.batcher.batch_index_path).map_err(Box::new)?;
+    let unsealed = config
+        .batcher
+        .unsealed_batches()
+        .take()
+        .unwrap_or_else(|e| {
+            tracing::error!("failed to take back the batches not dispersed before stopping: {e}");
+            Vec::new()
+        });
+    let event_log = EventLog::open(&config.events.log_path)?;
+    let batch_feed = BatchFeed::default();
+    let (commit_log, committed) = CommitLog::open(&config.batcher.commit_log_path)?;
+    let commit_log = commit_log.with_encoding(config.storage.encoding);
+    let mut batcher = Batcher::new(receivers_thread_tx)
+        .with_encoding(config.storage.encoding)
+        .with_batch_index(batch_index.clone())
+        .with_commit_log(commit_log)
+        .with_event_log(event_log.clone())
+        .with_batch_feed(batch_feed.clone())
+        .with_fee_schedule(config.fees.schedule())
+        .with_recent_window(config.receipts.recent_window)
+        .with_storage_quota(config.storage.quota())
+        .with_program_stats(program_stats.clone())
+        .with_unsealed(unsealed);
+    if config.devnet.instant_seal {
+        tracing::warn!("sealing every transaction into a batch of its own as it is applied");
+        batcher = batcher.with_instant_seal(persistence_storage.clone(), batch_receipts.clone());
+    }
+    let mut pruner = Pruner::open(config.retention.clone(), batch_index.clone())?
+        .with_batch_receipts(batch_receipts.clone());
+    if let Some(local_da) = da_client.local_files() {
+        pruner = pruner.with_local_da(local_da);
+    }
+    let transaction_index = if config.transaction_index.enabled {
+        let index = TransactionIndex::open(&config.transaction_index.path)?;
+        // Pruned before rebuilding, so what was pruned is not indexed again.
+        if let Some(through) = pruner.pruned_through(PruneTier::Receipts) {
+            index.prune(through)?;
+        }
+        let replayed = index.rebuild(&batch_index, &da_client)?;
+        tracing::info!("indexed the transactions of {replayed} batches replayed from DA");
+        batcher = batcher.with_transaction_index(index.clone());
+        pruner = pruner.with_transaction_index(index.clone());
+        Some(index)
+    } else {
+        None
+    };
+    #[cfg(feature = "attestations")]
+    let batcher = batcher.with_attestor(attestor.clone());
+    let transaction_inclusions = batcher.inclusions();
+    let program_registry = batcher.programs();
+    if let Some(genesis) = &genesis {
+        genesis.register_programs(&program_registry)?;
+    }
+    let batcher = Arc::new(Mutex::new(batcher));
     tokio::spawn(Batcher::run_receivers(receivers_thread_rx));
 
-    let da_client = Arc::new(Mutex::new(DaClient::new(eigen_da_client)));
-    let validator_core = Arc::new(Mutex::new(ValidatorCore::default()));
+    let da_client = Arc::new(Mutex::new(da_client));
+    let validator_core = Arc::new(Mutex::new(
+        ValidatorCore::default()
+            .with_admission(config.admission())
+            .with_admission_policy(admission_policy.clone()),
+    ));
 
     let actor_manager_inner: ActorManager = ActorManagerBuilder::default()
         .blob_cache(blob_cache_actor.clone(), blob_cache_supervisor)
@@ -292,13 +497,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let executor_actor_clone = executor_actor.clone();
     let execution_engine_clone = execution_engine.clone();
     let persistence_storage_clone = persistence_storage.clone();
+    let (crash_loop_tx, mut crash_loop_rx) = tokio::sync::mpsc::channel(1);
+    let mut restarts = Restarts::new(config.supervision);
 
     tokio::spawn(async move {
         while let Some(actor) = panic_rx.recv().await {
             if let ActorStatus::Stopped = actor.get_status() {
                 if let Some(actor_name) = actor.get_name() {
+                    let actor_type = actor_name.to_actor_type();
+                    if let Err(crash_loop) =
+                        restarts.record(actor_type.clone(), tokio::time::Instant::now())
+                    {
+                        tracing::error!("{crash_loop}, not restarting it again");
+                        crash_loop_tx.send(crash_loop).await.ok();
+                        break;
+                    }
                     let manager_ptr = Arc::clone(&actor_manager);
-                    match actor_name.to_actor_type() {
+                    match actor_type {
                         ActorType::BlobCache => {
                             ActorManager::respawn_blob_cache(
                                 manager_ptr,
@@ -319,6 +534,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .await
                             .typecast()
                             .log_err(|e| e);
+                            Batcher::cache_unsealed(batcher_clone.clone()).await;
                         }
                         ActorType::PendingTransactions => {
                             ActorManager::respawn_pending_tx(
@@ -329,6 +545,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .await
                             .typecast()
                             .log_err(|e| e);
+                            replay_pending_log();
                         }
                         ActorType::RpcServer => {
                             ActorManager::respawn_lasr_rpc_server(
@@ -432,26 +649,129 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let lasr_rpc = LasrRpcServerImpl::new(lasr_rpc_actor_ref);
-    let server = RpcServerBuilder::default()
-        .max_connections(1000)
-        .build(format!("0.0.0.0:{}", env.port))
-        .await
+    let rate_limiter = RateLimiter::new(config.rpc.rate_limits()).map_err(Box::new)?;
+    let admin = config
+        .rpc
+        .admin(env.admin_token.clone())
+        .map_err(Box::new)?;
+    let shutdown = Shutdown::default();
+    let load_shedder = config.load_shedding.shedder();
+    // Executions that run remotely are queued there, out of the node's sight.
+    #[cfg(not(feature = "remote"))]
+    let execution_metrics = Some(execution_pool.metrics());
+    #[cfg(feature = "remote")]
+    let execution_metrics = None;
+    tokio::spawn(lasr_actors::monitor_load(
+        load_shedder.clone(),
+        mempool_metrics.clone(),
+        execution_metrics,
+        lasr_actors::LOAD_SAMPLE_INTERVAL,
+    ));
+    tokio::spawn(lasr_actors::monitor_mailboxes(
+        mailboxes.clone(),
+        lasr_actors::MAILBOX_PROBE_INTERVAL,
+    ));
+    if config.retention.prunes() {
+        tokio::spawn(lasr_actors::prune_periodically(pruner.clone()));
+    }
+    let mut lasr_rpc = LasrRpcServerImpl::new(lasr_rpc_actor_ref, execution_logs)
+        .with_admission(config.admission())
+        .with_admission_policy(admission_policy)
+        .with_faucet(Faucet::new(config.devnet.faucet()))
+        .with_paginator(config.rpc.paginator())
+        .with_output_limits(config.outputs)
+        .with_node_keys(node_keys.clone())
+        .with_settlement_providers(settlement_providers)
+        .with_mempool_metrics(mempool_metrics)
+        .with_retry_metrics(retry_metrics)
+        .with_event_filters(event_filters)
+        .with_settlement_backfill(settlement_backfill)
+        .with_batch_receipts(batch_receipts)
+        .with_transaction_inclusions(transaction_inclusions)
+        .with_program_registry(program_registry)
+        .with_batch_index(batch_index)
+        .with_event_log(event_log)
+        .with_batch_feed(batch_feed)
+        .with_storage_quota(config.storage.quota())
+        .with_dead_letters(dead_letters)
+        .with_program_stats(program_stats.clone())
+        .with_notifications(notifications)
+        .with_rate_limiter(rate_limiter.clone())
+        .with_da_health(da_health)
+        .with_load_shedder(load_shedder)
+        .with_mailboxes(mailboxes)
+        .with_pruner(pruner)
+        .with_shutdown(shutdown.clone())
+        .with_eth_chain_id(config.rpc.eth_chain_id)
+        .with_unsigned_simulation(config.rpc.simulate_unsigned_payloads);
+    if let Some(genesis) = &genesis {
+        lasr_rpc = lasr_rpc.with_eth_chain_id(genesis.chain_id);
+    }
+    if let Some(index) = transaction_index {
+        lasr_rpc = lasr_rpc.with_transaction_index(index);
+    }
+    let rest = config.rpc.rest.then(|| rest_router(lasr_rpc.clone()));
+    let mut rpc_module = LasrRpcServer::into_rpc(lasr_rpc.clone());
+    rpc_module
+        .merge(EthRpcServer::into_rpc(lasr_rpc.clone()))
+        .map_err(Box::new)?;
+    rpc_module
+        .merge(AdminRpcServer::into_rpc(lasr_rpc))
         .map_err(Box::new)?;
-    let server_handle = server.start(lasr_rpc.into_rpc());
+    let (_, server_handle) = serve_rpc(
+        config.rpc.bind_addr(),
+        rpc_module.clone(),
+        rate_limiter.clone(),
+        admin.public_access(),
+        rest,
+        config.rpc.requests,
+    )
+    .await
+    .map_err(Box::new)?;
+    let admin_server_handle = match admin.listen_addr {
+        Some(addr) => {
+            let (addr, handle) = serve_rpc(
+                addr,
+                rpc_module,
+                rate_limiter,
+                AdminAccess::Localhost,
+                None,
+                config.rpc.requests,
+            )
+            .await
+            .map_err(Box::new)?;
+            tracing::warn!("serving the admin methods to the local host at {addr}");
+            Some(handle)
+        }
+        None => None,
+    };
     let eo_server_wrapper = EoServerWrapper::new(inner_eo_server);
 
-    let (_stop_tx, stop_rx) = tokio::sync::mpsc::channel(1);
+    let (stop_tx, stop_rx) = tokio::sync::mpsc::channel(1);
 
+    Batcher::cache_unsealed(batcher.clone()).await;
+    Batcher::replay_commits(batcher.clone(), committed).await?;
+    replay_pending_log();
     tokio::spawn(graph_cleaner());
-    tokio::spawn(eo_server_wrapper.run(
-        env.blocks_processed_path.to_string(),
-        persistence_storage.clone(),
-    ));
-    tokio::spawn(server_handle.stopped());
+    tokio::spawn(
+        eo_server_wrapper.run(
+            config
+                .eo_server
+                .blocks_processed_path
+                .to_string_lossy()
+                .to_string(),
+            persistence_storage.clone(),
+        ),
+    );
     tokio::spawn(lasr_actors::batch_requestor(
         stop_rx,
         persistence_storage.clone(),
+        config.batcher.interval,
+    ));
+    tokio::spawn(lasr_actors::snapshot_program_stats(program_stats.clone()));
+    #[cfg(not(feature = "remote"))]
+    tokio::spawn(lasr_actors::schedule_health_checks(
+        config.executor.health.interval,
     ));
 
     let future_thread_pool = tokio_rayon::rayon::ThreadPoolBuilder::new()
@@ -519,25 +839,86 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
-    loop {
-        interval.tick().await;
+    // A crash looping actor shuts the node down as a signal would, and the
+    // node then exits with it as the error.
+    let crash_loop = tokio::select! {
+        signalled = Shutdown::signalled() => {
+            signalled?;
+            None
+        }
+        Some(crash_loop) = crash_loop_rx.recv() => Some(crash_loop),
+    };
+    // Reported not ready for a while before connections are drained, for
+    // load balancers to stop routing requests here. Writes are turned away
+    // from the start, and those already taken are seen through to a batch.
+    let drain_period = config.shutdown.drain_period;
+    tracing::warn!("shutting down, draining connections in {drain_period:?}");
+    shutdown.start();
+    let drain = ShutdownDrain::new(shutdown.clone(), batcher, persistence_storage.clone())
+        .with_deadline(config.shutdown.deadline)
+        .with_unsealed_batches(config.batcher.unsealed_batches());
+    let (_, report) = tokio::join!(tokio::time::sleep(drain_period), drain.run());
+    tracing::warn!("drained {report:?}");
+    server_handle.stop().ok();
+    server_handle.stopped().await;
+    if let Some(handle) = admin_server_handle {
+        handle.stop().ok();
+        handle.stopped().await;
+    }
+
+    stop_tx.send(1).await?;
+    ShutdownDrain::stop_actors().await;
+    if let Err(e) = program_stats.flush() {
+        tracing::error!("failed to flush program stats: {e}");
     }
 
-    _stop_tx.send(1).await?;
+    match crash_loop {
+        Some(crash_loop) => Err(Box::new(crash_loop)),
+        None => Ok(()),
+    }
+}
 
+/// Follows the primary `follower` names as a read replica rather than
+/// sequencing, serving what it has applied read-only until signalled.
+async fn follow(
+    follower: FollowerConfig,
+    config: &NodeConfig,
+    da_client: DaClient,
+) -> Result<(), Box<dyn std::error::Error>> {
+    tracing::warn!("following {}, serving reads only", follower.primary_url);
+    let genesis = match config.genesis.path.as_deref() {
+        Some(path) => Some(Genesis::load(path).map_err(Box::new)?),
+        None => None,
+    };
+    let replica = Replica::open(&follower.replica_dir, genesis.as_ref())?;
+    let (_, server_handle) = serve_rpc(
+        config.rpc.bind_addr(),
+        read_only_rpc(replica.clone())?,
+        RateLimiter::new(config.rpc.rate_limits()).map_err(Box::new)?,
+        AdminAccess::Disabled,
+        None,
+        config.rpc.requests,
+    )
+    .await
+    .map_err(Box::new)?;
+    let following = tokio::spawn(Follower::new(follower, replica, da_client).run());
+
+    Shutdown::signalled().await?;
+    following.abort();
+    server_handle.stop().ok();
+    server_handle.stopped().await;
     Ok(())
 }
 
 async fn setup_eo_server(
     web3_instance: web3::Web3<web3::transports::Http>,
-    path: &str,
+    config: &EoServerConfig,
     storage: StorageRef,
 ) -> Result<EoListener, EoServerError> {
     // Initialize the ExecutableOracle Address
     //0x5FbDB2315678afecb367f032d93F642f64180aa3
-    let eo_address =
-        eo_listener::EoAddress::new(&crate::environment::ENVIRONMENT.eo_contract_address);
+    let eo_address = eo_listener::EoAddress::new(&config.contract_address);
+    let path = config.blocks_processed_path.as_path();
     let contract_address = eo_address
         .parse()
         .map_err(|err| EoServerError::Other(err.to_string()))?;
@@ -556,19 +937,12 @@ async fn setup_eo_server(
     let bridge_processed = blocks_processed.bridge_processed;
     let settled_processed = blocks_processed.settled_processed;
 
-    let blob_settled_filter = web3::types::FilterBuilder::default()
-        .from_block(BlockNumber::Number(settle_from_block.unwrap_or_default()))
-        .to_block(BlockNumber::Latest)
-        .address(vec![contract_address])
-        .topics(blob_settled_topic.clone(), None, None, None)
-        .build();
-
-    let bridge_filter = web3::types::FilterBuilder::default()
-        .from_block(BlockNumber::Number(bridge_from_block.unwrap_or_default()))
-        .to_block(BlockNumber::Latest)
-        .address(vec![contract_address])
-        .topics(bridge_topic.clone(), None, None, None)
-        .build();
+    let log_range = config.log_range;
+    let provider = eo_listener::FailoverProvider::from_urls(&config.eth_rpc_urls)?
+        .with_timeouts(config.provider_timeout, config.provider_retry_after);
+    let ingestion_mode = config.ingestion_mode();
+    let event_filters = config.event_filters.validate()?;
+    let backfill_blocks = config.filter_backfill_blocks;
 
     let blob_settled_event = contract
         .abi()
@@ -590,14 +964,18 @@ async fn setup_eo_server(
     tracing::info!("contract: {:?}", contract);
     tracing::info!("blob_settled_topic: {:?}", blob_settled_topic);
     tracing::info!("bridge_topic: {:?}", bridge_topic);
-    tracing::info!("blob_settled_filter: {:?}", blob_settled_filter);
-    tracing::info!("bridge_filter: {:?}", bridge_filter);
+    tracing::info!("log_range: {:?}", log_range);
+    tracing::info!("provider: {:?}", provider.metrics().status());
+    tracing::info!("ingestion_mode: {:?}", ingestion_mode);
+    tracing::info!("event_filters: {:?}", event_filters);
+    tracing::info!("backfill_blocks: {:?}", backfill_blocks);
     tracing::info!("blob_settled_event: {:?}", blob_settled_event);
     tracing::info!("bridge_event: {:?}", bridge_event);
     tracing::info!("path: {:?}", path);
 
-    let eo_server = eo_listener::EoServerBuilder::default()
+    let mut eo_server = eo_listener::EoServerBuilder::default()
         .web3(web3_instance)
+        .provider(provider)
         .eo_address(eo_address)
         .block_time(std::time::Duration::from_millis(2500))
         .bridge_processed_blocks(bridge_processed)
@@ -605,27 +983,34 @@ async fn setup_eo_server(
         .contract(contract)
         .bridge_topic(bridge_topic)
         .blob_settled_topic(blob_settled_topic)
-        .bridge_filter(bridge_filter)
         .current_bridge_filter_block(bridge_from_block.unwrap_or_default())
         .current_blob_settlement_filter_block(settle_from_block.unwrap_or_default())
-        .blob_settled_filter(blob_settled_filter)
         .blob_settled_event(blob_settled_event)
         .bridge_event(bridge_event)
-        .path(PathBuf::from_str(path).map_err(|e| EoServerError::Other(e.to_string()))?)
+        .path(path.to_path_buf())
+        .reorg_tracker(eo_listener::ReorgTracker::new(config.confirmations))
+        .log_range(log_range)
+        .subscription(ingestion_mode.subscription())
+        .event_filters(eo_listener::EventFilters::new(event_filters))
+        .backfill_blocks(backfill_blocks)
+        .delivery(eo_listener::EventDelivery::new(config.gap_timeout))
         .build()?;
+    eo_server.resume().await?;
 
     Ok(eo_server)
 }
 
 async fn setup_eo_client(
     web3_instance: web3::Web3<web3::transports::Http>,
-    sk: web3::signing::SecretKey,
+    key: KeyHandle,
+    eo_contract_address: &str,
+    settlement: Option<SettlerConfig>,
 ) -> Result<EoClient, Box<dyn std::error::Error>> {
     // Initialize the ExecutableOracle Address
     //0x5FbDB2315678afecb367f032d93F642f64180aa3
     //0x5FbDB2315678afecb367f032d93F642f64180aa3
 
-    let eo_address = eo_listener::EoAddress::new(&crate::ENVIRONMENT.eo_contract_address);
+    let eo_address = eo_listener::EoAddress::new(eo_contract_address);
     // Initialize the web3 instance
     let contract_address = eo_address
         .parse()
@@ -645,14 +1030,21 @@ async fn setup_eo_client(
     tracing::info!("web3_instance: {:?}", web3_instance);
     tracing::info!("contract: {:?}", contract);
     tracing::info!("user_address: {:?}", user_address);
-    tracing::info!("sk: {:?}", sk);
+    tracing::info!("settlement signer: {}", key.signer().to_full_string());
 
-    EoClient::new(web3_instance, contract, user_address, sk)
+    let eo_client = EoClient::new(web3_instance.clone(), contract, user_address, key.clone())
         .await
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    match settlement {
+        Some(config) => {
+            tracing::info!("settling batch headers to {:?}", config.contract);
+            Ok(eo_client.with_settler(Settler::new(web3_instance, key, config)))
+        }
+        None => Ok(eo_client),
+    }
 }
 
-async fn load_processed_blocks(path: &str, storage: StorageRef) -> Option<BlocksProcessed> {
+async fn load_processed_blocks(path: &Path, storage: StorageRef) -> Option<BlocksProcessed> {
     tracing::info!("attempting to load processed blocks in eo server setup");
     let blocks_processed_bytes = if let Ok(mut file) = std::fs::OpenOptions::new()
         .read(true)
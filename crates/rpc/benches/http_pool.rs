@@ -0,0 +1,77 @@
+//! Latency of `getAccount` made of a node a thousand times over, by a
+//! client keeping its connections open and making the next over them,
+//! against one opening a connection for each.
+//!
+//! Run with `cargo bench -p lasr_rpc --bench http_pool`.
+
+use std::time::{Duration, Instant};
+
+use jsonrpsee::{
+    server::{RpcModule, Server},
+    types::ErrorObjectOwned,
+};
+use lasr_rpc::HttpLasrClient;
+use lasr_types::{AccountState, Address};
+
+const CALLS: usize = 1_000;
+
+/// How long each of [`CALLS`] calls to `getAccount` made one after another
+/// took, shortest first.
+async fn latencies(client: &HttpLasrClient) -> Vec<Duration> {
+    let address = Address::new([1; 20]);
+    let mut latencies = Vec::with_capacity(CALLS);
+    for _ in 0..CALLS {
+        let started = Instant::now();
+        client.get_account(address).await.unwrap();
+        latencies.push(started.elapsed());
+    }
+    latencies.sort();
+    latencies
+}
+
+fn report(name: &str, latencies: &[Duration]) {
+    let total: Duration = latencies.iter().sum();
+    let percentile = |p: usize| latencies[(latencies.len() * p / 100).min(latencies.len() - 1)];
+    println!(
+        "{name:<9} total {:>9.2?}  mean {:>9.2?}  p50 {:>9.2?}  p99 {:>9.2?}",
+        total,
+        total / latencies.len() as u32,
+        percentile(50),
+        percentile(99),
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let mut module = RpcModule::new(());
+    module
+        .register_method("lasr_getAccount", |params, _| {
+            let address: String = params.one()?;
+            let address: Address = address.parse().unwrap();
+            Ok::<_, ErrorObjectOwned>(
+                serde_json::to_string(&AccountState::new(address, None)).unwrap(),
+            )
+        })
+        .unwrap();
+    let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    let _handle = server.start(module);
+
+    let pooled = HttpLasrClient::new(&url).unwrap();
+    let unpooled = HttpLasrClient::builder()
+        .with_keep_alive(false)
+        .build(&url)
+        .unwrap();
+    // Warm up both the node and the pooled client's connection.
+    latencies(&pooled).await;
+
+    let pooled_latencies = latencies(&pooled).await;
+    let unpooled_latencies = latencies(&unpooled).await;
+    report("pooled:", &pooled_latencies);
+    report("unpooled:", &unpooled_latencies);
+    let total = |latencies: &[Duration]| latencies.iter().sum::<Duration>().as_secs_f64();
+    println!(
+        "speedup:  {:>10.2}x",
+        total(&unpooled_latencies) / total(&pooled_latencies)
+    );
+}
@@ -0,0 +1,113 @@
+use jsonrpsee::{proc_macros::rpc, types::ErrorObjectOwned as RpcError};
+
+/// The methods operators run the node with. The node answers them only to
+/// callers it authenticates as its operators, and logs every call.
+#[rpc(client, server, namespace = "admin")]
+#[async_trait::async_trait]
+pub trait AdminRpc {
+    /// JSON page of the executions that produced different outputs
+    /// when they were run again, with the inputs to reproduce them, oldest
+    /// first. Paged as `lasr_getPendingTransactions`.
+    #[method(name = "getExecutionMismatches")]
+    async fn get_execution_mismatches(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON page of what each program has cost the node: its invocations
+    /// and failures, the time it ran for in total and at the 50th, 95th and
+    /// 99th percentiles, and the bytes of state its calls wrote. Highest
+    /// first by `metric`, `invocations` unless it is `failures`,
+    /// `executionTime` or `bytesWritten`. Paged as
+    /// `lasr_getPendingTransactions`.
+    #[method(name = "getProgramStats")]
+    async fn get_program_stats(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+        metric: Option<String>,
+    ) -> Result<String, RpcError>;
+
+    /// Swaps the further contracts and events the eo_server watches
+    /// for those in the JSON `filters`, backfilling the events added. Returns
+    /// JSON of the events added and removed.
+    #[method(name = "reloadEventFilters")]
+    async fn reload_event_filters(&self, filters: String) -> Result<String, RpcError>;
+
+    /// Starts scanning the blocks `from_block` through `to_block`
+    /// again for the events the eo_server watches, handing on those not
+    /// delivered before. Returns JSON of the backfill's progress.
+    #[method(name = "backfillSettlementEvents")]
+    async fn backfill_settlement_events(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of the progress of the last backfill started, with the
+    /// events it applied and skipped so far, or null if none was.
+    #[method(name = "getSettlementBackfill")]
+    async fn get_settlement_backfill(&self) -> Result<String, RpcError>;
+
+    /// JSON of the rate limits clients are held to, and the requests
+    /// turned away for each method since the node started.
+    #[method(name = "getRateLimits")]
+    async fn get_rate_limits(&self) -> Result<String, RpcError>;
+
+    /// Holds clients to the rate limits in the JSON `limits` from now
+    /// on, starting every bucket full. Returns JSON of the limits as set.
+    #[method(name = "setRateLimits")]
+    async fn set_rate_limits(&self, limits: String) -> Result<String, RpcError>;
+
+    /// JSON of the admission policy transactions are held to, and the
+    /// transactions it has denied by rule since the node started.
+    #[method(name = "getAdmissionPolicy")]
+    async fn get_admission_policy(&self) -> Result<String, RpcError>;
+
+    /// Holds transactions, pending ones included, to the admission policy
+    /// in the JSON `policy` from now on: sender and program allow and deny
+    /// lists, and the most value a transaction may carry. Returns JSON of
+    /// the policy as set.
+    #[method(name = "setAdmissionPolicy")]
+    async fn set_admission_policy(&self, policy: String) -> Result<String, RpcError>;
+
+    /// JSON page of the calls given up on after exhausting their retries,
+    /// each with its transaction and every failure it had, oldest first.
+    /// Paged as `lasr_getPendingTransactions`.
+    #[method(name = "getDeadLetters")]
+    async fn get_dead_letters(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// Admits the dead lettered call `tx_hash` again against the accounts as
+    /// they are now and, if it passes, takes it out of the dead letters and
+    /// runs it again. Returns JSON of the letter re-driven.
+    #[method(name = "redriveDeadLetter")]
+    async fn redrive_dead_letter(&self, tx_hash: String) -> Result<String, RpcError>;
+
+    /// Drops the dead lettered call `tx_hash`, or every one without it.
+    /// Returns JSON of how many were `purged`.
+    #[method(name = "purgeDeadLetters")]
+    async fn purge_dead_letters(&self, tx_hash: Option<String>) -> Result<String, RpcError>;
+
+    /// Makes the key in keystore file `file` the one the node signs for
+    /// `role`, `settlement` or `attestation`, with from now on, still
+    /// trusting what the key it replaces signed. Returns JSON of the key's
+    /// fingerprint.
+    #[method(name = "rotateKey")]
+    async fn rotate_key(&self, role: String, file: String) -> Result<String, RpcError>;
+
+    /// Prunes the blobs and receipts past the node's retention now, rather
+    /// than at the next interval. Returns JSON of the batches, transactions
+    /// and bytes pruned from each tier, `blobs` and `receipts`.
+    #[method(name = "prune")]
+    async fn prune(&self) -> Result<String, RpcError>;
+
+    /// JSON of the retention of each tier, `blobs` and `receipts`, how far
+    /// it has been pruned, and what pruning it now would reclaim.
+    #[method(name = "getReclaimableSpace")]
+    async fn get_reclaimable_space(&self) -> Result<String, RpcError>;
+}
@@ -0,0 +1,913 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use hyper::{
+    body::Bytes,
+    client::HttpConnector,
+    header::{HeaderMap, CONTENT_TYPE},
+    Body, Request, Uri,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use jsonrpsee::{
+    core::{
+        async_trait,
+        client::{BatchResponse, ClientT, Subscription, SubscriptionClientT},
+        params::BatchRequestBuilder,
+        traits::ToRpcParams,
+        ClientError as RequestError, JsonValue,
+    },
+    types::ErrorObjectOwned,
+};
+use lasr_types::{AccountState, Address, Payload, RpcErrorCode, Transaction, U256};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore};
+
+use crate::{ClientRequest, LasrRpcClient, Transport};
+
+/// How long a request waits on the node by default.
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How many requests a client has with the node at once by default.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+/// How many connections to a node a client keeps open once idle by
+/// default, one for each request it may have of the node at once.
+pub const DEFAULT_POOL_SIZE: usize = DEFAULT_MAX_IN_FLIGHT;
+/// How long a connection is kept open idle by default before it is closed.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// How many calls a client sends in one batch by default, as many as a node
+/// accepts in one unless its `RPC_MAX_BATCH_SIZE` says otherwise.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+/// Why a request made through an [`HttpLasrClient`] failed.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("the client could not be built: {0}")]
+    Config(String),
+
+    /// The node turned the request away with one of the codes it answers
+    /// failures with, and what it said of it.
+    #[error("{message}")]
+    Rejected {
+        code: RpcErrorCode,
+        message: String,
+        data: Option<JsonValue>,
+    },
+
+    /// The request was turned away with a code that is not one of the
+    /// node's own, as the JSON-RPC server answers unknown methods or
+    /// oversized requests.
+    #[error("{message} (code {code})")]
+    Other {
+        code: i32,
+        message: String,
+        data: Option<JsonValue>,
+    },
+
+    #[error("the node did not answer in time")]
+    Timeout,
+
+    #[error("the node could not be reached: {0}")]
+    Transport(String),
+
+    #[error("the node's answer could not be read: {0}")]
+    InvalidResponse(String),
+}
+
+impl ClientError {
+    /// The code the node turned the request away with, if it did.
+    pub fn code(&self) -> Option<RpcErrorCode> {
+        match self {
+            ClientError::Rejected { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl From<RequestError> for ClientError {
+    fn from(e: RequestError) -> Self {
+        match e {
+            RequestError::Call(error) => {
+                let message = error.message().to_string();
+                let data = error
+                    .data()
+                    .and_then(|data| serde_json::from_str(data.get()).ok());
+                match RpcErrorCode::from_code(error.code()) {
+                    Some(code) => ClientError::Rejected {
+                        code,
+                        message,
+                        data,
+                    },
+                    None => ClientError::Other {
+                        code: error.code(),
+                        message,
+                        data,
+                    },
+                }
+            }
+            RequestError::RequestTimeout => ClientError::Timeout,
+            RequestError::ParseError(e) => ClientError::InvalidResponse(e.to_string()),
+            e => ClientError::Transport(e.to_string()),
+        }
+    }
+}
+
+/// Builds an [`HttpLasrClient`] for a node's URL, waiting on it for
+/// `timeout` and sending `headers` with every request.
+///
+/// Connections are kept open once a request is answered and made the next
+/// over, up to `pool_size` of them for `idle_timeout` each, and at most
+/// `max_in_flight` requests are had of the node at once, those over it
+/// waiting their turn rather than failing. A batch of more than
+/// `max_batch_size` calls is sent as several.
+#[derive(Clone, Debug)]
+pub struct HttpLasrClientBuilder {
+    timeout: Duration,
+    headers: HashMap<String, String>,
+    max_in_flight: usize,
+    keep_alive: bool,
+    pool_size: usize,
+    idle_timeout: Duration,
+    max_batch_size: usize,
+    batching: Option<Batching>,
+}
+
+/// How long the calls made near together are gathered for, and how many,
+/// before they are sent as one batch.
+#[derive(Clone, Copy, Debug)]
+struct Batching {
+    window: Duration,
+    max_batch: usize,
+}
+
+impl Default for HttpLasrClientBuilder {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_CLIENT_TIMEOUT,
+            headers: HashMap::new(),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            keep_alive: true,
+            pool_size: DEFAULT_POOL_SIZE,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            batching: None,
+        }
+    }
+}
+
+impl HttpLasrClientBuilder {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends the header `name` with `value` with every request, as an
+    /// `authorization` header lets a client through to the admin methods.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Has at most `max_in_flight` requests of the node at once, at least
+    /// one. A request over it waits for another to be answered, and the
+    /// time it waits is not taken from its timeout.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    /// Whether a connection is kept open once a request over it has been
+    /// answered, for the next to be made over. Without it every request
+    /// opens a connection of its own.
+    pub fn with_keep_alive(mut self, keep_alive: bool) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Keeps at most `pool_size` connections to the node open once idle,
+    /// closing those over it as their requests are answered.
+    pub fn with_pool_size(mut self, pool_size: usize) -> Self {
+        self.pool_size = pool_size;
+        self
+    }
+
+    /// Closes a connection left idle for `idle_timeout`.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sends at most `max_batch_size` calls in one batch, at least one, as
+    /// the node's `RPC_MAX_BATCH_SIZE` turns away a batch of more whole.
+    /// A longer batch is sent as several, answered as one.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
+    /// Sends the calls made within `window` of the first, up to `max_batch`
+    /// of them and the `max_batch_size`, to the node as one batch, each
+    /// answered as if it had been made alone. Calls with headers of their
+    /// own are still made alone.
+    pub fn with_batching(mut self, window: Duration, max_batch: usize) -> Self {
+        self.batching = Some(Batching {
+            window,
+            max_batch: max_batch.max(1),
+        });
+        self
+    }
+
+    pub fn build(self, url: impl AsRef<str>) -> Result<HttpLasrClient, ClientError> {
+        let url = url.as_ref();
+        let client = self.client(url, &self.headers)?;
+        let pool = Arc::new(Pool::new(host(url), self.max_in_flight));
+        let coalescer = self.batching.map(|batching| {
+            Arc::new(Coalescer {
+                batching: Batching {
+                    max_batch: batching.max_batch.min(self.max_batch_size),
+                    ..batching
+                },
+                calls: OnceLock::new(),
+            })
+        });
+        Ok(HttpLasrClient {
+            client,
+            url: url.to_string(),
+            config: self,
+            with_headers: Arc::default(),
+            pool,
+            coalescer,
+        })
+    }
+
+    fn client(
+        &self,
+        url: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<Connection, ClientError> {
+        let target: Uri = url
+            .parse()
+            .map_err(|e| ClientError::Config(e.to_string()))?;
+        let headers =
+            HeaderMap::try_from(headers).map_err(|e| ClientError::Config(e.to_string()))?;
+        let mut http = HttpConnector::new();
+        http.set_nodelay(true);
+        let mut client = hyper::Client::builder();
+        client
+            .pool_max_idle_per_host(if self.keep_alive { self.pool_size } else { 0 })
+            .pool_idle_timeout(self.idle_timeout);
+        // Only a node served over TLS needs the platform's certificates read.
+        let backend = match target.scheme_str() {
+            Some("http") => Backend::Http(client.build(http)),
+            Some("https") => {
+                http.enforce_http(false);
+                let connector = HttpsConnectorBuilder::new()
+                    .with_native_roots()
+                    .https_only()
+                    .enable_http1()
+                    .wrap_connector(http);
+                Backend::Https(client.build(connector))
+            }
+            _ => {
+                return Err(ClientError::Config(format!(
+                    "{url} is not an http or https URL"
+                )))
+            }
+        };
+        Ok(Connection {
+            backend,
+            target,
+            headers,
+            timeout: self.timeout,
+            ids: Arc::default(),
+        })
+    }
+}
+
+/// The connections of a client to its node, which its requests are posted
+/// over.
+#[derive(Clone, Debug)]
+struct Connection {
+    backend: Backend,
+    target: Uri,
+    headers: HeaderMap,
+    timeout: Duration,
+    ids: Arc<AtomicU64>,
+}
+
+#[derive(Clone, Debug)]
+enum Backend {
+    Http(hyper::Client<HttpConnector>),
+    Https(hyper::Client<HttpsConnector<HttpConnector>>),
+}
+
+/// A call as it is posted to the node.
+#[derive(Serialize)]
+struct Call<'a> {
+    jsonrpc: &'static str,
+    /// Unset for a notification, which is not answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    method: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<&'a RawValue>,
+}
+
+impl<'a> Call<'a> {
+    fn new(id: Option<u64>, method: &'a str, params: Option<&'a RawValue>) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        }
+    }
+}
+
+/// What the node answered a call with.
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(default)]
+    id: JsonValue,
+    #[serde(default)]
+    result: JsonValue,
+    error: Option<Failure>,
+}
+
+/// The error a call was answered with, read owning what it says.
+#[derive(Deserialize)]
+struct Failure {
+    code: i32,
+    message: String,
+    #[serde(default)]
+    data: Option<Box<RawValue>>,
+}
+
+impl Answer {
+    fn into_result(self) -> Result<JsonValue, ErrorObjectOwned> {
+        match self.error {
+            Some(error) => Err(ErrorObjectOwned::owned(
+                error.code,
+                error.message,
+                error.data,
+            )),
+            None => Ok(self.result),
+        }
+    }
+}
+
+impl Connection {
+    async fn request(
+        &self,
+        method: &str,
+        params: Option<&RawValue>,
+    ) -> Result<JsonValue, RequestError> {
+        let id = self.ids.fetch_add(1, Ordering::Relaxed);
+        let body = self.post(&Call::new(Some(id), method, params)).await?;
+        serde_json::from_slice::<Answer>(&body)?
+            .into_result()
+            .map_err(RequestError::Call)
+    }
+
+    async fn notify(&self, method: &str, params: Option<&RawValue>) -> Result<(), RequestError> {
+        self.post(&Call::new(None, method, params)).await?;
+        Ok(())
+    }
+
+    /// Sends `calls` as one batch, answering each in the order it was made.
+    async fn batch<M: AsRef<str>>(
+        &self,
+        calls: &[(M, Option<Box<RawValue>>)],
+    ) -> Result<Vec<Result<JsonValue, ErrorObjectOwned>>, RequestError> {
+        let first = self.ids.fetch_add(calls.len() as u64, Ordering::Relaxed);
+        let batch: Vec<Call> = calls
+            .iter()
+            .zip(first..)
+            .map(|((method, params), id)| Call::new(Some(id), method.as_ref(), params.as_deref()))
+            .collect();
+        let body = self.post(&batch).await?;
+        let answers: Vec<Answer> = match serde_json::from_slice(&body) {
+            Ok(answers) => answers,
+            // A batch turned away whole is answered with one error.
+            Err(_) => {
+                return Err(
+                    match serde_json::from_slice::<Answer>(&body)?.into_result() {
+                        Err(error) => RequestError::Call(error),
+                        Ok(_) => RequestError::Custom(
+                            "the node answered a batch with one answer".to_string(),
+                        ),
+                    },
+                )
+            }
+        };
+        let mut answered: HashMap<u64, Result<JsonValue, ErrorObjectOwned>> = answers
+            .into_iter()
+            .filter_map(|answer| Some((answer.id.as_u64()?, answer.into_result())))
+            .collect();
+        (first..first + calls.len() as u64)
+            .map(|id| {
+                answered.remove(&id).ok_or_else(|| {
+                    RequestError::Custom(format!("the node did not answer call {id} of a batch"))
+                })
+            })
+            .collect()
+    }
+
+    /// Posts `body` to the node, answering what it answered within the
+    /// timeout.
+    async fn post(&self, body: &impl Serialize) -> Result<Bytes, RequestError> {
+        let mut request = Request::post(self.target.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(body)?))
+            .map_err(|e| RequestError::Transport(e.into()))?;
+        request.headers_mut().extend(self.headers.clone());
+        let answered = async {
+            let response = match &self.backend {
+                Backend::Http(client) => client.request(request).await?,
+                Backend::Https(client) => client.request(request).await?,
+            };
+            let status = response.status();
+            Ok::<_, hyper::Error>((status, hyper::body::to_bytes(response.into_body()).await?))
+        };
+        let (status, body) = tokio::time::timeout(self.timeout, answered)
+            .await
+            .map_err(|_| RequestError::RequestTimeout)?
+            .map_err(|e| RequestError::Transport(e.into()))?;
+        if !status.is_success() {
+            return Err(RequestError::Transport(anyhow::anyhow!(
+                "the node answered with status {status}"
+            )));
+        }
+        Ok(body)
+    }
+}
+
+/// The host and port of `url`, which the metrics of its pool are read
+/// under.
+fn host(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    host.to_string()
+}
+
+/// How the requests a client, and every clone of it, has had of its node
+/// have gone, as [`HttpLasrClient::pool_metrics`] reads them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PoolMetrics {
+    /// The host and port of the node.
+    pub host: String,
+    pub max_in_flight: usize,
+    /// Requests had of the node now.
+    pub in_flight: usize,
+    /// The most requests ever had of the node at once.
+    pub peak_in_flight: usize,
+    /// Requests waiting now for another to be answered.
+    pub queued: usize,
+    /// Calls answered, or failed, since the client was built.
+    pub requests: u64,
+    /// Calls that had to wait for another to be answered.
+    pub waited: u64,
+    pub failed: u64,
+    /// Batches the calls made near together were sent in, and how many
+    /// calls were sent in them.
+    pub batches: u64,
+    pub batched: u64,
+}
+
+/// What a client, and every clone of it, has of its node at once, and how
+/// its requests have gone.
+#[derive(Debug)]
+struct Pool {
+    host: String,
+    max_in_flight: usize,
+    permits: Arc<Semaphore>,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    queued: AtomicUsize,
+    requests: AtomicU64,
+    waited: AtomicU64,
+    failed: AtomicU64,
+    batches: AtomicU64,
+    batched: AtomicU64,
+}
+
+impl Pool {
+    fn new(host: String, max_in_flight: usize) -> Self {
+        Self {
+            host,
+            max_in_flight,
+            permits: Arc::new(Semaphore::new(max_in_flight)),
+            in_flight: AtomicUsize::new(0),
+            peak_in_flight: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            requests: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            batches: AtomicU64::new(0),
+            batched: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for a request to be one of those had of the node at once, for
+    /// as long as the [`InFlight`] answered is held.
+    async fn acquire(self: &Arc<Self>) -> InFlight {
+        let permit = match Arc::clone(&self.permits).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.waited.fetch_add(1, Ordering::Relaxed);
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                let _queued = Queued(self);
+                Arc::clone(&self.permits)
+                    .acquire_owned()
+                    .await
+                    .expect("the pool's semaphore is never closed")
+            }
+        };
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+        InFlight {
+            pool: Arc::clone(self),
+            _permit: permit,
+        }
+    }
+
+    /// Counts a call answered with `answer`.
+    fn answered<T>(&self, answer: &Result<T, RequestError>) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if answer.is_err() {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            host: self.host.clone(),
+            max_in_flight: self.max_in_flight,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            peak_in_flight: self.peak_in_flight.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            waited: self.waited.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            batched: self.batched.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A request waiting its turn, counted as queued until it is dropped.
+struct Queued<'a>(&'a Pool);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A request had of the node, counted as in flight until it is dropped.
+struct InFlight {
+    pool: Arc<Pool>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.pool.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A call waiting to be sent in the next batch.
+struct Pending {
+    method: String,
+    params: Option<Box<RawValue>>,
+    answer: oneshot::Sender<Result<JsonValue, RequestError>>,
+}
+
+/// Where the calls of a client sending them in batches are gathered. The
+/// task gathering them is started with the first, and stops once every
+/// clone of the client is dropped.
+#[derive(Debug)]
+struct Coalescer {
+    batching: Batching,
+    calls: OnceLock<mpsc::UnboundedSender<Pending>>,
+}
+
+impl Coalescer {
+    async fn call(
+        &self,
+        client: &Connection,
+        pool: &Arc<Pool>,
+        request: ClientRequest,
+    ) -> Result<JsonValue, RequestError> {
+        let calls = self.calls.get_or_init(|| {
+            let (calls, pending) = mpsc::unbounded_channel();
+            tokio::spawn(gather(
+                client.clone(),
+                Arc::clone(pool),
+                self.batching,
+                pending,
+            ));
+            calls
+        });
+        let (answer, answered) = oneshot::channel();
+        calls
+            .send(Pending {
+                method: request.method,
+                params: request.params,
+                answer,
+            })
+            .map_err(|_| unanswered())?;
+        answered.await.map_err(|_| unanswered())?
+    }
+}
+
+fn unanswered() -> RequestError {
+    RequestError::Custom("the call was sent in a batch the node did not answer".to_string())
+}
+
+/// Gathers the calls made within `batching.window` of the first into a
+/// batch, sending each as one of the requests had of the node at once.
+async fn gather(
+    client: Connection,
+    pool: Arc<Pool>,
+    batching: Batching,
+    mut pending: mpsc::UnboundedReceiver<Pending>,
+) {
+    while let Some(first) = pending.recv().await {
+        let mut batch = vec![first];
+        let window = tokio::time::sleep(batching.window);
+        tokio::pin!(window);
+        while batch.len() < batching.max_batch {
+            tokio::select! {
+                _ = &mut window => break,
+                next = pending.recv() => match next {
+                    Some(next) => batch.push(next),
+                    None => break,
+                },
+            }
+        }
+        let in_flight = pool.acquire().await;
+        tokio::spawn(send_batch(
+            client.clone(),
+            Arc::clone(&pool),
+            in_flight,
+            batch,
+        ));
+    }
+}
+
+/// Sends `batch` as one request, answering each call in it with what the
+/// node answered it, or all of them with what the batch failed with. A
+/// batch of one call is sent as the call.
+async fn send_batch(
+    client: Connection,
+    pool: Arc<Pool>,
+    _in_flight: InFlight,
+    batch: Vec<Pending>,
+) {
+    let (calls, answers): (Vec<_>, Vec<_>) = batch
+        .into_iter()
+        .map(|call| ((call.method, call.params), call.answer))
+        .unzip();
+    if let [(method, params)] = calls.as_slice() {
+        let answer = client.request(method, params.as_deref()).await;
+        pool.answered(&answer);
+        if let Some(waiter) = answers.into_iter().next() {
+            let _ = waiter.send(answer);
+        }
+        return;
+    }
+
+    pool.batches.fetch_add(1, Ordering::Relaxed);
+    pool.batched
+        .fetch_add(calls.len() as u64, Ordering::Relaxed);
+    match client.batch(&calls).await {
+        Ok(response) => {
+            for (answer, waiter) in response.into_iter().zip(answers) {
+                let answer = answer.map_err(RequestError::Call);
+                pool.answered(&answer);
+                let _ = waiter.send(answer);
+            }
+        }
+        Err(e) => {
+            for waiter in answers {
+                let answer = Err(duplicate(&e));
+                pool.answered(&answer);
+                let _ = waiter.send(answer);
+            }
+        }
+    }
+}
+
+/// `error` again, for each call in a batch that failed as a whole, so a
+/// layer reading it tells it apart as it would had the call been made
+/// alone.
+fn duplicate(error: &RequestError) -> RequestError {
+    match error {
+        RequestError::Call(e) => RequestError::Call(e.clone()),
+        RequestError::Transport(e) => RequestError::Transport(anyhow::anyhow!("{e}")),
+        RequestError::RequestTimeout => RequestError::RequestTimeout,
+        e => RequestError::Custom(e.to_string()),
+    }
+}
+
+/// A client of a node's JSON-RPC over HTTP. It is a [`LasrRpcClient`], so a
+/// `lasr_wallet::Wallet` can be built on it, and reads what the node answers
+/// into the types it answers in, telling failures apart by their code.
+///
+/// Headers a layer adds to a request are sent with it over a client of their
+/// own, made the first time they are sent and kept for the next.
+///
+/// Clones of a client share its connections, its limit on the requests had
+/// of the node at once and the metrics of them, so should be made rather
+/// than building another client of the same node.
+#[derive(Clone, Debug)]
+pub struct HttpLasrClient {
+    client: Connection,
+    url: String,
+    config: HttpLasrClientBuilder,
+    with_headers: Arc<Mutex<HashMap<BTreeMap<String, String>, Connection>>>,
+    pool: Arc<Pool>,
+    coalescer: Option<Arc<Coalescer>>,
+}
+
+impl HttpLasrClient {
+    /// A client of the node at `url`, as [`HttpLasrClientBuilder`] builds
+    /// one by default.
+    pub fn new(url: impl AsRef<str>) -> Result<Self, ClientError> {
+        HttpLasrClientBuilder::default().build(url)
+    }
+
+    pub fn builder() -> HttpLasrClientBuilder {
+        HttpLasrClientBuilder::default()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// How the requests this client, and every clone of it, has had of its
+    /// node have gone.
+    pub fn pool_metrics(&self) -> PoolMetrics {
+        self.pool.metrics()
+    }
+
+    /// The account at `address`, empty with `found` unset if it has none.
+    pub async fn get_account(&self, address: Address) -> Result<AccountState, ClientError> {
+        read(LasrRpcClient::get_account(self, address.to_full_string()).await?)
+    }
+
+    /// The nonce the next transaction from `address` is to take.
+    pub async fn get_nonce(&self, address: Address) -> Result<U256, ClientError> {
+        read(LasrRpcClient::get_nonce(self, address.to_full_string()).await?)
+    }
+
+    /// Where the transaction `transaction_hash` has got to.
+    pub async fn get_transaction_status(
+        &self,
+        transaction_hash: &str,
+    ) -> Result<JsonValue, ClientError> {
+        read(LasrRpcClient::get_transaction_status(self, transaction_hash.to_string()).await?)
+    }
+
+    /// The receipt of the transaction `transaction_hash`.
+    pub async fn get_receipt(&self, transaction_hash: &str) -> Result<JsonValue, ClientError> {
+        read(LasrRpcClient::get_transaction_receipt(self, transaction_hash.to_string()).await?)
+    }
+
+    /// What applying the signed `transaction` would do, without applying
+    /// it.
+    pub async fn simulate(&self, transaction: Transaction) -> Result<JsonValue, ClientError> {
+        read(LasrRpcClient::simulate_transaction(self, transaction).await?)
+    }
+
+    /// The fees `payload` would be charged.
+    pub async fn estimate_fees(&self, payload: Payload) -> Result<JsonValue, ClientError> {
+        read(LasrRpcClient::estimate_fees(self, payload).await?)
+    }
+
+    /// Sends `raw`, the borsh encoding of a signed transaction, answering
+    /// its hash.
+    pub async fn send_raw(&self, raw: &[u8]) -> Result<String, ClientError> {
+        let raw = format!("0x{}", hex::encode(raw));
+        Ok(LasrRpcClient::send_raw(self, raw).await?)
+    }
+
+    /// The client sending `headers` on top of those it was built with.
+    fn client_with(&self, headers: HashMap<String, String>) -> Result<Connection, RequestError> {
+        let key: BTreeMap<String, String> = headers.into_iter().collect();
+        let mut clients = self.with_headers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        let mut headers = self.config.headers.clone();
+        headers.extend(key.clone());
+        let client = self
+            .config
+            .client(&self.url, &headers)
+            .map_err(|e| RequestError::Custom(e.to_string()))?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl Transport for HttpLasrClient {
+    async fn call(&self, request: ClientRequest) -> Result<JsonValue, RequestError> {
+        let client = if request.headers.is_empty() {
+            if let Some(coalescer) = &self.coalescer {
+                return coalescer.call(&self.client, &self.pool, request).await;
+            }
+            self.client.clone()
+        } else {
+            self.client_with(request.headers)?
+        };
+        let _in_flight = self.pool.acquire().await;
+        let answer = client
+            .request(&request.method, request.params.as_deref())
+            .await;
+        self.pool.answered(&answer);
+        answer
+    }
+}
+
+/// Reads the JSON a method answered with.
+fn read<T: DeserializeOwned>(json: String) -> Result<T, ClientError> {
+    serde_json::from_str(&json).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
+
+#[async_trait]
+impl ClientT for HttpLasrClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), RequestError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        let params = params.to_rpc_params()?;
+        let _in_flight = self.pool.acquire().await;
+        self.client.notify(method, params.as_deref()).await
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, RequestError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let request = ClientRequest::new(method, params.to_rpc_params()?);
+        Ok(serde_json::from_value(self.call(request).await?)?)
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, RequestError>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        let calls = batch.build()?;
+        let mut answers = Vec::with_capacity(calls.len());
+        for calls in calls.chunks(self.config.max_batch_size) {
+            let _in_flight = self.pool.acquire().await;
+            for answer in self.client.batch(calls).await? {
+                answers.push(match answer {
+                    Ok(answer) => Ok(serde_json::from_value(answer)?),
+                    Err(error) => Err(error),
+                });
+            }
+        }
+        let failed = answers.iter().filter(|answer| answer.is_err()).count();
+        Ok(BatchResponse::new(answers.len() - failed, answers, failed))
+    }
+}
+
+#[async_trait]
+impl SubscriptionClientT for HttpLasrClient {
+    async fn subscribe<'a, Notif, Params>(
+        &self,
+        _subscribe_method: &'a str,
+        _params: Params,
+        _unsubscribe_method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Params: ToRpcParams + Send,
+        Notif: DeserializeOwned,
+    {
+        Err(RequestError::HttpNotImplemented)
+    }
+
+    async fn subscribe_to_method<'a, Notif>(
+        &self,
+        _method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Notif: DeserializeOwned,
+    {
+        Err(RequestError::HttpNotImplemented)
+    }
+}
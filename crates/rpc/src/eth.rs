@@ -0,0 +1,44 @@
+use ethers_core::types::{Address, Bytes, TransactionReceipt, H256, U256, U64};
+use jsonrpsee::{core::JsonValue, proc_macros::rpc, types::ErrorObjectOwned as RpcError};
+
+/// The part of Ethereum's JSON-RPC that maps onto LASR, for wallets and
+/// scripts that only speak `eth_*`. Quantities are hex, as Ethereum has them.
+/// LASR keeps only its latest state, so the block a method is asked about is
+/// not looked at.
+#[rpc(client, server, namespace = "eth")]
+#[async_trait::async_trait]
+pub trait EthRpc {
+    #[method(name = "chainId")]
+    async fn chain_id(&self) -> Result<U64, RpcError>;
+
+    /// The ETH held by the account at `address`, in wei.
+    #[method(name = "getBalance")]
+    async fn get_balance(
+        &self,
+        address: Address,
+        block: Option<JsonValue>,
+    ) -> Result<U256, RpcError>;
+
+    /// The nonce of the account at `address`.
+    #[method(name = "getTransactionCount")]
+    async fn get_transaction_count(
+        &self,
+        address: Address,
+        block: Option<JsonValue>,
+    ) -> Result<U256, RpcError>;
+
+    /// Sends the LASR transaction carried, as JSON, in the data of the signed
+    /// Ethereum transaction `raw`, which its sender must have signed for this
+    /// chain. Answers the LASR transaction's hash.
+    #[method(name = "sendRawTransaction")]
+    async fn send_raw_transaction(&self, raw: Bytes) -> Result<H256, RpcError>;
+
+    /// The receipt of `transaction_hash` once it has been applied or has
+    /// failed, or null until then. The batch it went into stands in for its
+    /// block.
+    #[method(name = "getTransactionReceipt")]
+    async fn get_transaction_receipt(
+        &self,
+        transaction_hash: H256,
+    ) -> Result<Option<TransactionReceipt>, RpcError>;
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use jsonrpsee::core::{
+    async_trait,
+    client::{BatchResponse, ClientT, Subscription, SubscriptionClientT},
+    params::BatchRequestBuilder,
+    traits::ToRpcParams,
+    ClientError as RequestError, JsonValue,
+};
+use lasr_types::RpcErrorCode;
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+
+/// A request on its way through the layers of a [`Layered`] client to the
+/// node.
+#[derive(Clone, Debug)]
+pub struct ClientRequest {
+    /// The method, with its namespace, as `lasr_getAccount`.
+    pub method: String,
+    pub params: Option<Box<RawValue>>,
+    /// Headers to send with this request only, on top of those the client
+    /// was built with.
+    pub headers: HashMap<String, String>,
+}
+
+impl ClientRequest {
+    pub fn new(method: impl Into<String>, params: Option<Box<RawValue>>) -> Self {
+        Self {
+            method: method.into(),
+            params,
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// Parameters already written, passed on as they are.
+pub(crate) struct RawParams(pub(crate) Option<Box<RawValue>>);
+
+impl ToRpcParams for RawParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, serde_json::Error> {
+        Ok(self.0)
+    }
+}
+
+/// What a [`ClientLayer`] passes requests on to: the client a stack of
+/// layers is built on, or the layers below it.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Makes `request` of the node, answering what the method answered.
+    async fn call(&self, request: ClientRequest) -> Result<JsonValue, RequestError>;
+}
+
+/// Something done with every request a [`Layered`] client makes, around
+/// passing it on to `next`. A layer can change the request, answer it
+/// itself, pass it on more than once, or give up waiting on it.
+#[async_trait]
+pub trait ClientLayer: Send + Sync {
+    async fn call(
+        &self,
+        request: ClientRequest,
+        next: &dyn Transport,
+    ) -> Result<JsonValue, RequestError>;
+}
+
+/// `inner` with every request it makes passed through `layer` first. It is
+/// a `LasrRpcClient` whenever `inner` is, so a `lasr_wallet::Wallet` is
+/// built on it as on any other.
+///
+/// Layers are stacked with [`WithLayer::with_layer`], the last added
+/// outermost:
+/// `client.with_layer(TimeoutLayer::new(d)).with_layer(RetryLayer::default())`
+/// times out each attempt, while the other order gives all of them `d`
+/// together. Subscriptions, notifications and batches are passed straight
+/// to `inner`.
+#[derive(Clone, Debug)]
+pub struct Layered<C, L> {
+    inner: C,
+    layer: L,
+}
+
+impl<C, L> Layered<C, L> {
+    pub fn new(inner: C, layer: L) -> Self {
+        Self { inner, layer }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn layer(&self) -> &L {
+        &self.layer
+    }
+}
+
+/// Stacks layers on a client, the last added outermost.
+pub trait WithLayer: Transport + Sized {
+    fn with_layer<L: ClientLayer>(self, layer: L) -> Layered<Self, L> {
+        Layered::new(self, layer)
+    }
+}
+
+impl<T: Transport> WithLayer for T {}
+
+#[async_trait]
+impl<C: Transport, L: ClientLayer> Transport for Layered<C, L> {
+    async fn call(&self, request: ClientRequest) -> Result<JsonValue, RequestError> {
+        self.layer.call(request, &self.inner).await
+    }
+}
+
+#[async_trait]
+impl<C, L> ClientT for Layered<C, L>
+where
+    C: Transport + ClientT,
+    L: ClientLayer,
+{
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), RequestError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        self.inner.notification(method, params).await
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, RequestError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let request = ClientRequest::new(method, params.to_rpc_params()?);
+        Ok(serde_json::from_value(self.call(request).await?)?)
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, RequestError>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        self.inner.batch_request(batch).await
+    }
+}
+
+#[async_trait]
+impl<C, L> SubscriptionClientT for Layered<C, L>
+where
+    C: Transport + SubscriptionClientT,
+    L: ClientLayer,
+{
+    async fn subscribe<'a, Notif, Params>(
+        &self,
+        subscribe_method: &'a str,
+        params: Params,
+        unsubscribe_method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Params: ToRpcParams + Send,
+        Notif: DeserializeOwned,
+    {
+        self.inner
+            .subscribe(subscribe_method, params, unsubscribe_method)
+            .await
+    }
+
+    async fn subscribe_to_method<'a, Notif>(
+        &self,
+        method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Notif: DeserializeOwned,
+    {
+        self.inner.subscribe_to_method(method).await
+    }
+}
+
+/// Logs each request with how long it took and how it went.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingLayer;
+
+#[async_trait]
+impl ClientLayer for TracingLayer {
+    async fn call(
+        &self,
+        request: ClientRequest,
+        next: &dyn Transport,
+    ) -> Result<JsonValue, RequestError> {
+        let method = request.method.clone();
+        let started = Instant::now();
+        let answer = next.call(request).await;
+        let elapsed = started.elapsed();
+        match &answer {
+            Ok(_) => tracing::debug!("{method} answered in {elapsed:?}"),
+            Err(e) => tracing::warn!("{method} failed after {elapsed:?}: {e}"),
+        }
+        answer
+    }
+}
+
+/// Gives up on a request the node has not answered within `timeout`.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+#[async_trait]
+impl ClientLayer for TimeoutLayer {
+    async fn call(
+        &self,
+        request: ClientRequest,
+        next: &dyn Transport,
+    ) -> Result<JsonValue, RequestError> {
+        tokio::time::timeout(self.timeout, next.call(request))
+            .await
+            .map_err(|_| RequestError::RequestTimeout)?
+    }
+}
+
+/// Read methods named other than `get*` or `list*`, which answer the same
+/// however often they are asked.
+const IDEMPOTENT_METHODS: [&str; 8] = [
+    "lasr_simulateTransaction",
+    "lasr_simulatePayload",
+    "lasr_estimateFees",
+    "lasr_health",
+    "lasr_ready",
+    "lasr_nodeStatus",
+    "eth_chainId",
+    "eth_blockNumber",
+];
+
+/// Whether `method` can be made again without changing anything the first
+/// did. Nothing that takes a transaction is, so a send that may have
+/// reached the node is never sent twice.
+pub fn is_idempotent(method: &str) -> bool {
+    let name = method.split_once('_').map_or(method, |(_, name)| name);
+    name.starts_with("get") || name.starts_with("list") || IDEMPOTENT_METHODS.contains(&method)
+}
+
+/// Makes an idempotent request again when it fails in a way that may pass,
+/// waiting `backoff`, doubled each attempt up to `max_backoff`, in between.
+/// Other requests go through once.
+#[derive(Clone, Debug)]
+pub struct RetryLayer {
+    attempts: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryLayer {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryLayer {
+    /// Makes a request at most `attempts` times, at least once.
+    pub fn with_attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff.max(backoff);
+        self
+    }
+
+    /// Whether a request that failed with `error` may pass made again: it
+    /// did not reach the node or was not answered, or the node was too
+    /// busy for it.
+    fn retryable(error: &RequestError) -> bool {
+        match error {
+            RequestError::Call(error) => matches!(
+                RpcErrorCode::from_code(error.code()),
+                Some(RpcErrorCode::RateLimited | RpcErrorCode::Overloaded)
+            ),
+            RequestError::Transport(_)
+            | RequestError::RestartNeeded(_)
+            | RequestError::RequestTimeout => true,
+            _ => false,
+        }
+    }
+}
+
+#[async_trait]
+impl ClientLayer for RetryLayer {
+    async fn call(
+        &self,
+        request: ClientRequest,
+        next: &dyn Transport,
+    ) -> Result<JsonValue, RequestError> {
+        if !is_idempotent(&request.method) {
+            return next.call(request).await;
+        }
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match next.call(request.clone()).await {
+                Err(e) if attempt < self.attempts && Self::retryable(&e) => {
+                    tracing::debug!("{} failed, attempt {attempt}: {e}", request.method);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                answer => return answer,
+            }
+        }
+    }
+}
+
+/// Sends `Authorization: Bearer <token>` with the admin methods, so the
+/// node lets the client through to them. The token is not sent with the
+/// others.
+#[derive(Clone)]
+pub struct BearerAuthLayer {
+    token: String,
+}
+
+impl fmt::Debug for BearerAuthLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BearerAuthLayer").finish_non_exhaustive()
+    }
+}
+
+impl BearerAuthLayer {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl ClientLayer for BearerAuthLayer {
+    async fn call(
+        &self,
+        mut request: ClientRequest,
+        next: &dyn Transport,
+    ) -> Result<JsonValue, RequestError> {
+        if request.method.starts_with("admin_") {
+            request.headers.insert(
+                "authorization".to_string(),
+                format!("Bearer {}", self.token),
+            );
+        }
+        next.call(request).await
+    }
+}
@@ -1,2 +1,16 @@
+pub mod admin;
+pub mod client;
+pub mod eth;
+pub mod layer;
+#[cfg(feature = "test_utils")]
+pub mod mock;
 pub mod rpc;
+pub mod ws_client;
+pub use admin::*;
+pub use client::*;
+pub use eth::*;
+pub use layer::*;
+#[cfg(feature = "test_utils")]
+pub use mock::*;
 pub use rpc::*;
+pub use ws_client::*;
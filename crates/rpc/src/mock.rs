@@ -0,0 +1,526 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use jsonrpsee::{
+    core::{
+        async_trait,
+        client::{BatchResponse, ClientT, Subscription, SubscriptionClientT},
+        params::BatchRequestBuilder,
+        traits::ToRpcParams,
+        ClientError as RequestError, JsonValue,
+    },
+    types::ErrorObjectOwned,
+};
+use lasr_types::{
+    Account, AccountState, Address, PayloadBuilder, RpcErrorCode, Token, Transaction,
+    TransactionType, U256,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::layer::RawParams;
+use crate::{ClientRequest, Transport};
+
+/// The JSON-RPC code the server answers a method it does not have with,
+/// as the mock answers one it has not been told how to.
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// Methods whose first parameter is a signed transaction the node takes.
+const SENDING_METHODS: [&str; 4] = [
+    "lasr_send",
+    "lasr_call",
+    "lasr_replace",
+    "lasr_registerProgram",
+];
+
+/// A request made of a [`MockLasrClient`], as it was made.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockRequest {
+    /// The method, with its namespace, as `lasr_send`.
+    pub method: String,
+    /// The parameters, in order.
+    pub params: Vec<JsonValue>,
+    /// The headers a layer added to the request.
+    pub headers: HashMap<String, String>,
+}
+
+impl MockRequest {
+    fn new(method: &str, params: Vec<JsonValue>) -> Self {
+        Self {
+            method: method.to_string(),
+            params,
+            headers: HashMap::new(),
+        }
+    }
+
+    /// The signed transaction the request gave the node, if it gave one.
+    pub fn transaction(&self) -> Option<Transaction> {
+        let first = self.params.first()?;
+        if self.method == "lasr_sendRaw" {
+            return decode_raw(first);
+        }
+        if !SENDING_METHODS.contains(&self.method.as_str()) {
+            return None;
+        }
+        serde_json::from_value(first.clone()).ok()
+    }
+}
+
+/// The transaction `raw` is the 0x hex of the borsh encoding of.
+fn decode_raw(raw: &JsonValue) -> Option<Transaction> {
+    let raw = raw.as_str()?;
+    let bytes = hex::decode(raw.strip_prefix("0x").unwrap_or(raw)).ok()?;
+    borsh::from_slice(&bytes).ok()
+}
+
+/// What a method answers next.
+#[derive(Clone, Debug)]
+enum Scripted {
+    Answer(String),
+    Fail(ErrorObjectOwned),
+}
+
+/// Accounts as a node would hold them, for a stateful mock.
+#[derive(Debug, Default)]
+struct Ledger {
+    accounts: HashMap<Address, Account>,
+    minted: u64,
+}
+
+impl Ledger {
+    fn account(&self, address: Address) -> Account {
+        self.accounts
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| Account::new(address))
+    }
+
+    fn mint(&mut self, address: Address, program_id: Address, amount: U256) -> String {
+        self.minted += 1;
+        let transaction: Transaction = PayloadBuilder::default()
+            .transaction_type(TransactionType::BridgeIn(U256::from(self.minted)))
+            .from(address.inner())
+            .to(address.inner())
+            .program_id(program_id.inner())
+            .inputs(String::new())
+            .op(String::new())
+            .value(amount)
+            .nonce(U256::from(self.minted))
+            .build()
+            .expect("a mint is always built")
+            .into();
+        let mut account = self.account(address);
+        account
+            .apply_send_transaction(transaction.clone(), None)
+            .expect("a mint is always applied");
+        self.accounts.insert(address, account);
+        transaction.hash_string()
+    }
+
+    /// Applies `transaction` to the accounts it sends between, as the node
+    /// would once it is validated.
+    fn apply(&mut self, transaction: Transaction) -> Result<Token, ErrorObjectOwned> {
+        if !transaction.transaction_type().is_send() {
+            return Err(rejected(
+                RpcErrorCode::InvalidParams,
+                "the mock applies sends only, script an answer for anything else",
+            ));
+        }
+        transaction
+            .verify_signature()
+            .map_err(|e| rejected(RpcErrorCode::InvalidSignature, e))?;
+        let (from, to) = (transaction.from(), transaction.to());
+        let mut sender = self.account(from);
+        sender
+            .validate_spendable(&transaction.program_id(), transaction.value())
+            .map_err(|e| rejected(RpcErrorCode::InsufficientBalance, e))?;
+        let token = sender
+            .apply_send_transaction(transaction.clone(), None)
+            .map_err(|e| rejected(RpcErrorCode::InvalidNonce, e))?;
+        if to != from {
+            let mut receiver = self.account(to);
+            receiver
+                .apply_send_transaction(transaction, None)
+                .map_err(|e| rejected(RpcErrorCode::Internal, e))?;
+            self.accounts.insert(to, receiver);
+        }
+        self.accounts.insert(from, sender);
+        Ok(token)
+    }
+
+    /// What the ledger answers `method` with, if it answers it.
+    fn answer(
+        &mut self,
+        method: &str,
+        params: &[JsonValue],
+    ) -> Option<Result<String, ErrorObjectOwned>> {
+        let answer = match method {
+            "lasr_getAccount" => address(params)
+                .and_then(|address| json(&AccountState::new(address, self.accounts.get(&address)))),
+            "lasr_getNonce" => {
+                address(params).and_then(|address| json(&self.account(address).expected_nonce()))
+            }
+            "lasr_send" => params
+                .first()
+                .and_then(|transaction| serde_json::from_value(transaction.clone()).ok())
+                .ok_or_else(unreadable)
+                .and_then(|transaction| json(&self.apply(transaction)?)),
+            "lasr_sendRaw" => params
+                .first()
+                .and_then(decode_raw)
+                .ok_or_else(unreadable)
+                .and_then(|transaction| {
+                    let transaction_hash = transaction.hash_string();
+                    self.apply(transaction).map(|_| transaction_hash)
+                }),
+            "lasr_faucet" => address(params).and_then(|address| {
+                let amount = params
+                    .get(1)
+                    .and_then(JsonValue::as_str)
+                    .and_then(|amount| U256::from_dec_str(amount).ok())
+                    .ok_or_else(|| {
+                        rejected(RpcErrorCode::InvalidParams, "amount is not a number")
+                    })?;
+                let program_id = match params.get(2).and_then(JsonValue::as_str) {
+                    Some(program_id) => parse(program_id)?,
+                    None => Address::verse_addr(),
+                };
+                Ok(self.mint(address, program_id, amount))
+            }),
+            _ => return None,
+        };
+        Some(answer)
+    }
+}
+
+fn rejected(code: RpcErrorCode, message: impl ToString) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(code.code(), message.to_string(), None::<()>)
+}
+
+fn json(value: &impl Serialize) -> Result<String, ErrorObjectOwned> {
+    serde_json::to_string(value).map_err(|e| rejected(RpcErrorCode::Internal, e))
+}
+
+fn parse(address: &str) -> Result<Address, ErrorObjectOwned> {
+    Address::from_hex(address).map_err(|e| rejected(RpcErrorCode::InvalidParams, e))
+}
+
+fn address(params: &[JsonValue]) -> Result<Address, ErrorObjectOwned> {
+    params
+        .first()
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| rejected(RpcErrorCode::InvalidParams, "an address is missing"))
+        .and_then(parse)
+}
+
+fn unreadable() -> ErrorObjectOwned {
+    rejected(
+        RpcErrorCode::InvalidParams,
+        "the transaction could not be read",
+    )
+}
+
+/// `method` with the `lasr` namespace, if it was named without one.
+fn namespaced(method: &str) -> String {
+    if method.contains('_') {
+        method.to_string()
+    } else {
+        format!("lasr_{method}")
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    scripted: HashMap<String, VecDeque<Scripted>>,
+    latencies: HashMap<String, Duration>,
+    requests: Vec<MockRequest>,
+    ledger: Option<Ledger>,
+}
+
+/// A [`LasrRpcClient`](crate::LasrRpcClient) that answers as it is told to,
+/// for testing a `lasr_wallet::Wallet` or an application without a node.
+///
+/// Answers are queued per method, named as the node names them (`send`, or
+/// `lasr_send`), and each is given once, in the order queued. A method can
+/// be told to fail with one of the node's codes, or to take a while to
+/// answer. Every request is recorded, in order, to be asserted on.
+///
+/// A stateful mock, made with [`MockLasrClient::stateful`], also holds
+/// accounts: it answers `getAccount`, `getNonce` and `faucet`, and applies
+/// sends, whether by `send` or `sendRaw`, checking their signature, balance
+/// and nonce as the node would. What is queued is answered first. Anything
+/// else it has not been told how to answer fails with the code the server
+/// answers unknown methods with. Subscriptions and batches are not mocked.
+///
+/// It is a [`Transport`], so layers are stacked on it as on a client of a
+/// node, and records the headers they add to a request.
+///
+/// Clones share what they were told and what was asked of them, so a clone
+/// can be given to a wallet and the original kept to script and assert
+/// with.
+#[derive(Clone, Default)]
+pub struct MockLasrClient {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl fmt::Debug for MockLasrClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockLasrClient")
+            .field("requests", &self.state().requests.len())
+            .finish()
+    }
+}
+
+impl MockLasrClient {
+    /// A mock that answers only what it is told to.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A mock that holds accounts, applying the sends made of it.
+    pub fn stateful() -> Self {
+        let mock = Self::default();
+        mock.state().ledger = Some(Ledger::default());
+        mock
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn script(&self, method: &str, scripted: Scripted) -> &Self {
+        self.state()
+            .scripted
+            .entry(namespaced(method))
+            .or_default()
+            .push_back(scripted);
+        self
+    }
+
+    /// Queues `answer` as what `method` answers next, exactly as the
+    /// method would, as the JSON of an account for `getAccount`.
+    pub fn respond(&self, method: &str, answer: impl Into<String>) -> &Self {
+        self.script(method, Scripted::Answer(answer.into()))
+    }
+
+    /// Queues the JSON of `answer` as what `method` answers next.
+    pub fn respond_with(&self, method: &str, answer: &impl Serialize) -> &Self {
+        let answer = serde_json::to_string(answer).expect("the answer could not be written");
+        self.respond(method, answer)
+    }
+
+    /// Queues a failure with `code` as what `method` answers next.
+    pub fn fail(&self, method: &str, code: RpcErrorCode, message: impl Into<String>) -> &Self {
+        self.script(method, Scripted::Fail(rejected(code, message.into())))
+    }
+
+    /// Queues a failure with `code` and `data`, as the node gives a
+    /// receipt with `AlreadyIncluded`.
+    pub fn fail_with_data(
+        &self,
+        method: &str,
+        code: RpcErrorCode,
+        message: impl Into<String>,
+        data: &impl Serialize,
+    ) -> &Self {
+        let error = ErrorObjectOwned::owned(code.code(), message.into(), Some(data));
+        self.script(method, Scripted::Fail(error))
+    }
+
+    /// Has every request of `method` wait `latency` before it is answered.
+    pub fn delay(&self, method: &str, latency: Duration) -> &Self {
+        self.state().latencies.insert(namespaced(method), latency);
+        self
+    }
+
+    /// Credits `amount` of `program_id` to `address`, as a bridge in
+    /// would. Only a stateful mock holds accounts.
+    pub fn fund(&self, address: Address, program_id: Address, amount: U256) -> &Self {
+        self.state()
+            .ledger
+            .as_mut()
+            .expect("only a stateful mock holds accounts")
+            .mint(address, program_id, amount);
+        self
+    }
+
+    /// The account a stateful mock holds at `address`.
+    pub fn account(&self, address: Address) -> Option<Account> {
+        self.state()
+            .ledger
+            .as_ref()?
+            .accounts
+            .get(&address)
+            .cloned()
+    }
+
+    /// Every request made, in the order made.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The requests made of `method`, in the order made.
+    pub fn requests_of(&self, method: &str) -> Vec<MockRequest> {
+        let method = namespaced(method);
+        self.state()
+            .requests
+            .iter()
+            .filter(|request| request.method == method)
+            .cloned()
+            .collect()
+    }
+
+    /// The signed transactions given to the node, in the order given.
+    pub fn sent(&self) -> Vec<Transaction> {
+        self.state()
+            .requests
+            .iter()
+            .filter_map(MockRequest::transaction)
+            .collect()
+    }
+
+    /// Panics unless a transaction was given to the node that `matches`.
+    #[track_caller]
+    pub fn assert_sent(&self, matches: impl Fn(&Transaction) -> bool) {
+        let sent = self.sent();
+        assert!(
+            sent.iter().any(matches),
+            "no transaction sent matches, of the {} sent: {sent:#?}",
+            sent.len()
+        );
+    }
+
+    /// Panics unless `method` was requested.
+    #[track_caller]
+    pub fn assert_requested(&self, method: &str) {
+        assert!(
+            !self.requests_of(method).is_empty(),
+            "{} was not requested, of: {:?}",
+            namespaced(method),
+            self.requests()
+                .iter()
+                .map(|request| request.method.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Records the request and answers it, after any latency it is given.
+    async fn answer(&self, request: MockRequest) -> Result<String, RequestError> {
+        let (method, params) = (request.method.clone(), request.params.clone());
+        let method = method.as_str();
+        let latency = {
+            let mut state = self.state();
+            state.requests.push(request);
+            state.latencies.get(method).copied()
+        };
+        if let Some(latency) = latency {
+            tokio::time::sleep(latency).await;
+        }
+        let mut state = self.state();
+        let scripted = state.scripted.get_mut(method).and_then(VecDeque::pop_front);
+        let answer = match scripted {
+            Some(Scripted::Answer(answer)) => Ok(answer),
+            Some(Scripted::Fail(error)) => Err(error),
+            None => state
+                .ledger
+                .as_mut()
+                .and_then(|ledger| ledger.answer(method, &params))
+                .unwrap_or_else(|| {
+                    Err(ErrorObjectOwned::owned(
+                        METHOD_NOT_FOUND,
+                        format!("no answer is scripted for {method}"),
+                        None::<()>,
+                    ))
+                }),
+        };
+        answer.map_err(RequestError::Call)
+    }
+}
+
+/// The parameters of a request, read as the server would read them.
+fn read_params(params: impl ToRpcParams) -> Result<Vec<JsonValue>, RequestError> {
+    match params.to_rpc_params()? {
+        Some(raw) => match serde_json::from_str(raw.get())? {
+            JsonValue::Array(params) => Ok(params),
+            JsonValue::Null => Ok(Vec::new()),
+            params => Ok(vec![params]),
+        },
+        None => Ok(Vec::new()),
+    }
+}
+
+#[async_trait]
+impl Transport for MockLasrClient {
+    async fn call(&self, request: ClientRequest) -> Result<JsonValue, RequestError> {
+        let mut recorded =
+            MockRequest::new(&request.method, read_params(RawParams(request.params))?);
+        recorded.headers = request.headers;
+        Ok(JsonValue::String(self.answer(recorded).await?))
+    }
+}
+
+#[async_trait]
+impl ClientT for MockLasrClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), RequestError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        let request = MockRequest::new(method, read_params(params)?);
+        self.state().requests.push(request);
+        Ok(())
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, RequestError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let answer = self
+            .answer(MockRequest::new(method, read_params(params)?))
+            .await?;
+        Ok(serde_json::from_value(JsonValue::String(answer))?)
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        _batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, RequestError>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        Err(RequestError::Custom("batches are not mocked".to_string()))
+    }
+}
+
+#[async_trait]
+impl SubscriptionClientT for MockLasrClient {
+    async fn subscribe<'a, Notif, Params>(
+        &self,
+        subscribe_method: &'a str,
+        params: Params,
+        _unsubscribe_method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Params: ToRpcParams + Send,
+        Notif: DeserializeOwned,
+    {
+        let request = MockRequest::new(subscribe_method, read_params(params)?);
+        self.state().requests.push(request);
+        Err(RequestError::Custom(
+            "subscriptions are not mocked".to_string(),
+        ))
+    }
+
+    async fn subscribe_to_method<'a, Notif>(
+        &self,
+        _method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Notif: DeserializeOwned,
+    {
+        Err(RequestError::Custom(
+            "subscriptions are not mocked".to_string(),
+        ))
+    }
+}
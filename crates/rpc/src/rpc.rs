@@ -1,5 +1,9 @@
-use jsonrpsee::{proc_macros::rpc, types::ErrorObjectOwned as RpcError};
-use lasr_types::Transaction;
+use jsonrpsee::{
+    core::{JsonValue, SubscriptionResult},
+    proc_macros::rpc,
+    types::ErrorObjectOwned as RpcError,
+};
+use lasr_types::{EventFilter, ExecutionLogEvent, Payload, Transaction};
 
 #[rpc(client, server, namespace = "lasr")]
 #[async_trait::async_trait]
@@ -7,12 +11,248 @@ pub trait LasrRpc {
     #[method(name = "call")]
     async fn call(&self, transaction: Transaction) -> Result<String, RpcError>;
 
+    /// Sends a transaction that runs no program: a send, a burn, a bridge
+    /// out or a delegation. Any other is turned away, to be sent with the
+    /// method for its type.
     #[method(name = "send")]
     async fn send(&self, transaction: Transaction) -> Result<String, RpcError>;
 
+    /// Sends `transaction` in place of the one pending from the same sender
+    /// with the same nonce, which is answered as replaced. Fails if that one
+    /// is already being validated, or none is pending.
+    #[method(name = "replace")]
+    async fn replace(&self, transaction: Transaction) -> Result<String, RpcError>;
+
+    /// Sends `raw`, the 0x hex of the borsh encoding of a signed
+    /// transaction, as the method for its type would. Answers its hash.
+    #[method(name = "sendRaw")]
+    async fn send_raw(&self, raw: String) -> Result<String, RpcError>;
+
+    /// Registers or deploys a program, answering its id.
     #[method(name = "registerProgram")]
     async fn register_program(&self, transaction: Transaction) -> Result<String, RpcError>;
 
+    /// JSON of what applying the signed `transaction`, a send or a call,
+    /// would do: the balances it would change, the logs its program would
+    /// emit, a summary of its execution, its fee, and why it would fail if
+    /// it would. Nothing is applied.
+    #[method(name = "simulateTransaction")]
+    async fn simulate_transaction(&self, transaction: Transaction) -> Result<String, RpcError>;
+
+    /// Simulates `payload` unsigned, as sent by `from`, on nodes that allow
+    /// it. Answered as `simulateTransaction`.
+    #[method(name = "simulatePayload")]
+    async fn simulate_payload(&self, payload: Payload, from: String) -> Result<String, RpcError>;
+
+    /// JSON of the fee floor of `payload`, signed or not, broken down into
+    /// its base, input and execution fees, with what it would be charged
+    /// and why it would be turned away, if it would. A payload without a
+    /// `maxFee` is estimated as if it offered the floor and its priority fee.
+    #[method(name = "estimateFees")]
+    async fn estimate_fees(&self, payload: Payload) -> Result<String, RpcError>;
+
+    /// JSON of the account at `address`, in the shape of
+    /// `lasr_types::AccountState`. An address with no account is answered
+    /// as an empty one with `found` unset.
     #[method(name = "getAccount")]
     async fn get_account(&self, address: String) -> Result<String, RpcError>;
+
+    /// JSON of the nonce the next transaction from `address` is to take:
+    /// the one after the account's, or after the last of the transactions
+    /// pending from it in turn.
+    #[method(name = "getNonce")]
+    async fn get_nonce(&self, address: String) -> Result<String, RpcError>;
+
+    #[method(name = "getVerseSupply")]
+    async fn get_verse_supply(&self) -> Result<String, RpcError>;
+
+    /// Mints `amount`, in decimal or 0x hex, of VERSE to `address`, or of
+    /// ETH if `program_id` is the ETH address, on devnet nodes. An address
+    /// is funded at most once per cooldown. Answers JSON of the minting
+    /// transaction's hash with what it minted to whom.
+    #[method(name = "faucet")]
+    async fn faucet(
+        &self,
+        address: String,
+        amount: String,
+        program_id: Option<String>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON describing the health of the node's components.
+    #[method(name = "getNodeStatus")]
+    async fn get_node_status(&self) -> Result<String, RpcError>;
+
+    /// Answers whenever the node is up, without checking anything else.
+    #[method(name = "health")]
+    async fn health(&self) -> Result<String, RpcError>;
+
+    /// JSON of whether the node is ready for requests, with the readiness of
+    /// each of the components it needs. A node shutting down is not ready.
+    #[method(name = "ready")]
+    async fn ready(&self) -> Result<String, RpcError>;
+
+    /// JSON of the node's version, chain id, latest batch, pending pool size
+    /// and DA breaker state.
+    #[method(name = "nodeStatus")]
+    async fn node_status(&self) -> Result<String, RpcError>;
+
+    /// JSON of the header of batch `batch_id` and where its settlement to
+    /// the settlement contract has got to, or null if it is not known.
+    #[method(name = "getBatchReceipt")]
+    async fn get_batch_receipt(&self, batch_id: u64) -> Result<String, RpcError>;
+
+    /// JSON of batch `batch_id`: its header, how many accounts it holds, the
+    /// hashes of its transactions, when it was sealed and finalized, and
+    /// where its settlement has got to. With `full`, the accounts it holds
+    /// too, read back from the DA layer.
+    #[method(name = "getBatch")]
+    async fn get_batch(&self, batch_id: u64, full: Option<bool>) -> Result<String, RpcError>;
+
+    /// JSON of the batch finalized last, as `getBatch` answers it, or null
+    /// if none has been.
+    #[method(name = "getLatestBatch")]
+    async fn get_latest_batch(&self) -> Result<String, RpcError>;
+
+    /// JSON of a page of the batches finalized, oldest first, each as
+    /// `getBatch` answers it. Paged as `getPendingTransactions`.
+    #[method(name = "getBatches")]
+    async fn get_batches(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of a page of the transactions pending from `address`, in nonce
+    /// order, with where each has got to in the pool. Pages start after the
+    /// `cursor` the last one handed back, and hold `limit` transactions, 20
+    /// by default and 100 at most, or fewer with `truncated` set if they
+    /// would not fit in a response.
+    #[method(name = "getPendingTransactions")]
+    async fn get_pending_transactions(
+        &self,
+        address: String,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of how `program_id` was registered: its content hash, runtime,
+    /// owner, the batch its registration went into and its metadata, with
+    /// the token it describes if it is a token program.
+    #[method(name = "getProgram")]
+    async fn get_program(&self, program_id: String) -> Result<String, RpcError>;
+
+    /// JSON of a page of the programs registered, by program id, each as
+    /// `getProgram` answers it. Paged as `getPendingTransactions`.
+    #[method(name = "listPrograms")]
+    async fn list_programs(
+        &self,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of a page of the transactions finalized that `address` sent or
+    /// received, oldest batch first, each with its batch, hash, sender,
+    /// recipient, program and the time its batch was finalized at. Paged as
+    /// `getPendingTransactions`. Only answered by nodes indexing
+    /// transactions.
+    #[method(name = "getTransactionsByAddress")]
+    async fn get_transactions_by_address(
+        &self,
+        address: String,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of a page of the transactions finalized under `program_id`, as
+    /// `getTransactionsByAddress` answers them.
+    #[method(name = "getTransactionsByProgram")]
+    async fn get_transactions_by_program(
+        &self,
+        program_id: String,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of a page of the events programs emitted in the batches
+    /// finalized that `filter` matches, by program id and topic prefix,
+    /// oldest batch first, each with its batch, transaction, program, topic
+    /// and attributes. Paged as `getPendingTransactions`.
+    #[method(name = "getEvents")]
+    async fn get_events(
+        &self,
+        filter: Option<EventFilter>,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<String, RpcError>;
+
+    /// JSON of where `tx_hash` has got to: pending, queued for the nonce it
+    /// waits on, or executing while in the pool, and included, settled,
+    /// failed, expired or replaced once it has left it. A hash the node
+    /// knows nothing of is unknown.
+    #[method(name = "getTransactionStatus")]
+    async fn get_transaction_status(&self, tx_hash: String) -> Result<String, RpcError>;
+
+    /// JSON of the receipt of `tx_hash`: its status, the batch it went into
+    /// and that batch's blob commitment, the balances it changed, the events
+    /// its program emitted, why it failed, and its attestation, each null
+    /// until there is one.
+    #[method(name = "getTransactionReceipt")]
+    async fn get_transaction_receipt(&self, tx_hash: String) -> Result<String, RpcError>;
+
+    /// JSON of the signed attestation of the call `tx_hash`, and whether it
+    /// verifies against one of this node's attestation signers.
+    #[method(name = "getAttestation")]
+    async fn get_attestation(&self, tx_hash: String) -> Result<String, RpcError>;
+
+    /// Streams the lines logged by every execution of `program_id` from now
+    /// on. A subscriber that falls behind gets a `dropped` event in place of
+    /// the lines it missed.
+    #[subscription(
+        name = "subscribeExecutionLogs" => "executionLog",
+        unsubscribe = "unsubscribeExecutionLogs",
+        item = ExecutionLogEvent
+    )]
+    async fn subscribe_execution_logs(&self, program_id: String) -> SubscriptionResult;
+
+    /// Streams the events `filter` matches as the batches they were emitted
+    /// in are finalized, from now on. Each is a JSON object with the event,
+    /// as `getEvents` answers it, and the number of events `dropped` before
+    /// it because the subscriber fell behind.
+    #[subscription(
+        name = "subscribeEvents" => "eventEmitted",
+        unsubscribe = "unsubscribeEvents",
+        item = JsonValue
+    )]
+    async fn subscribe_events(&self, filter: EventFilter) -> SubscriptionResult;
+
+    /// Streams every batch finalized from now on, whole: the record it was
+    /// finalized as, as `getBatch` answers it, and the batch itself, with
+    /// its transactions, the accounts it left and the events its calls
+    /// emitted. Each is a JSON object with the `batch` and the number of
+    /// batches `dropped` before it because the subscriber fell behind.
+    /// Followers sync from it.
+    #[subscription(
+        name = "subscribeBatches" => "batchFinalized",
+        unsubscribe = "unsubscribeBatches",
+        item = JsonValue
+    )]
+    async fn subscribe_batches(&self) -> SubscriptionResult;
+
+    /// Streams notifications of `kind`: `accountChanged`, with the address
+    /// in `param`, each time the account is written; `newBatch` each time a
+    /// batch is finalized or its settlement gets on; or `transactionStatus`,
+    /// with the transaction hash in `param`, starting with where it has got
+    /// to and then with its outcome. Each is a JSON object with the `kind`,
+    /// the account, batch receipt or status, and the number of notifications
+    /// `dropped` before it because the subscriber fell behind.
+    #[subscription(
+        name = "subscribe" => "subscription",
+        unsubscribe = "unsubscribe",
+        item = JsonValue
+    )]
+    async fn subscribe_notifications(
+        &self,
+        kind: String,
+        param: Option<String>,
+    ) -> SubscriptionResult;
 }
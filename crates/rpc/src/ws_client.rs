@@ -0,0 +1,643 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{stream, Stream, StreamExt};
+use jsonrpsee::{
+    core::{
+        async_trait,
+        client::{BatchResponse, ClientT, Subscription, SubscriptionClientT},
+        params::BatchRequestBuilder,
+        traits::ToRpcParams,
+        ClientError as RequestError, JsonValue,
+    },
+    http_client::HeaderMap,
+    ws_client::{WsClient, WsClientBuilder},
+};
+use lasr_types::{AccountState, Address, EventFilter};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    RwLock,
+};
+
+use crate::layer::RawParams;
+use crate::{ClientError, ClientRequest, LasrRpcClient, Transport, DEFAULT_CLIENT_TIMEOUT};
+
+/// How long to wait before connecting again once the node is lost.
+pub const DEFAULT_WS_RECONNECT_AFTER: Duration = Duration::from_secs(1);
+/// Items a subscription stream holds for a consumer that has not taken
+/// them before it counts the rest as missed.
+pub const DEFAULT_STREAM_BUFFER: usize = 256;
+
+/// What a subscription stream of a [`WsLasrClient`] yields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamEvent<T> {
+    /// An item the node notified, or read back from it after a reconnect.
+    Item(T),
+    /// `missed` items were dropped before the next, because the consumer
+    /// or the node fell behind.
+    Lagged { missed: u64 },
+    /// The connection was lost and made again, and the stream subscribed
+    /// again. What changed in between follows, read back from the node.
+    Resumed,
+}
+
+/// What a subscription stream yields: an event, or why the stream ended.
+pub type StreamResult<T> = Result<StreamEvent<T>, ClientError>;
+
+/// Builds a [`WsLasrClient`] for a node's websocket URL.
+#[derive(Clone, Debug)]
+pub struct WsLasrClientBuilder {
+    timeout: Duration,
+    reconnect_after: Duration,
+    buffer: usize,
+    headers: HashMap<String, String>,
+}
+
+impl Default for WsLasrClientBuilder {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_CLIENT_TIMEOUT,
+            reconnect_after: DEFAULT_WS_RECONNECT_AFTER,
+            buffer: DEFAULT_STREAM_BUFFER,
+            headers: HashMap::new(),
+        }
+    }
+}
+
+impl WsLasrClientBuilder {
+    /// How long to wait on the node to connect, and on each request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_reconnect_after(mut self, reconnect_after: Duration) -> Self {
+        self.reconnect_after = reconnect_after;
+        self
+    }
+
+    /// Holds up to `buffer` items of each stream for its consumer, at least
+    /// one.
+    pub fn with_buffer(mut self, buffer: usize) -> Self {
+        self.buffer = buffer.max(1);
+        self
+    }
+
+    /// Sends the header `name` with `value` each time it connects.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub async fn build(self, url: impl Into<String>) -> Result<WsLasrClient, ClientError> {
+        HeaderMap::try_from(&self.headers).map_err(|e| ClientError::Config(e.to_string()))?;
+        let url = url.into();
+        let connection = self.connect(&url).await?;
+        Ok(WsLasrClient {
+            url,
+            config: Arc::new(self),
+            connection: Arc::new(RwLock::new(Arc::new(connection))),
+        })
+    }
+
+    async fn connect(&self, url: &str) -> Result<WsClient, RequestError> {
+        let headers =
+            HeaderMap::try_from(&self.headers).map_err(|e| RequestError::Custom(e.to_string()))?;
+        WsClientBuilder::default()
+            .connection_timeout(self.timeout)
+            .request_timeout(self.timeout)
+            .set_headers(headers)
+            .build(url)
+            .await
+    }
+}
+
+/// A client of a node's JSON-RPC over a websocket, connecting again
+/// whenever it is lost. It is a [`LasrRpcClient`], and streams what the
+/// node notifies, subscribing again after a reconnect and reading back what
+/// changed meanwhile, so nothing is missed without a [`StreamEvent::Lagged`]
+/// saying so.
+#[derive(Clone, Debug)]
+pub struct WsLasrClient {
+    url: String,
+    config: Arc<WsLasrClientBuilder>,
+    connection: Arc<RwLock<Arc<WsClient>>>,
+}
+
+impl WsLasrClient {
+    /// A client of the node at `url`, as [`WsLasrClientBuilder`] builds one
+    /// by default.
+    pub async fn new(url: impl Into<String>) -> Result<Self, ClientError> {
+        WsLasrClientBuilder::default().build(url).await
+    }
+
+    pub fn builder() -> WsLasrClientBuilder {
+        WsLasrClientBuilder::default()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The connection to the node, made again first if it was lost.
+    async fn connection(&self) -> Result<Arc<WsClient>, RequestError> {
+        let connection = self.connection.read().await.clone();
+        if connection.is_connected() {
+            return Ok(connection);
+        }
+        let mut connection = self.connection.write().await;
+        if !connection.is_connected() {
+            *connection = Arc::new(self.config.connect(&self.url).await?);
+        }
+        Ok(connection.clone())
+    }
+
+    /// The account at `address` each time it is written. After a reconnect
+    /// it is read back as it is.
+    pub fn account_changes(
+        &self,
+        address: Address,
+    ) -> impl Stream<Item = StreamResult<AccountState>> {
+        self.stream(AccountFeed { address })
+    }
+
+    /// JSON of the receipt of each batch as it is finalized and as its
+    /// settlement gets on. After a reconnect those of the batches since the
+    /// last one streamed are read back.
+    pub fn new_batches(&self) -> impl Stream<Item = StreamResult<JsonValue>> {
+        self.stream(BatchFeed { last: None })
+    }
+
+    /// JSON of where `transaction_hash` has got to, starting with where it
+    /// is now and again each time it gets on. After a reconnect it starts
+    /// again with where it is then.
+    pub fn transaction_status(
+        &self,
+        transaction_hash: impl Into<String>,
+    ) -> impl Stream<Item = StreamResult<JsonValue>> {
+        self.stream(TransactionFeed {
+            transaction_hash: transaction_hash.into(),
+        })
+    }
+
+    /// JSON of each event `filter` matches as its batch is finalized, from
+    /// now on. After a reconnect those finalized meanwhile are read back.
+    pub fn events(&self, filter: EventFilter) -> impl Stream<Item = StreamResult<JsonValue>> {
+        self.stream(EventFeed {
+            filter,
+            started: false,
+            seen_through: None,
+            seen: BTreeSet::new(),
+        })
+    }
+
+    fn stream<F: Feed>(&self, feed: F) -> impl Stream<Item = StreamResult<F::Item>> {
+        let (sender, receiver) = mpsc::channel(self.config.buffer);
+        tokio::spawn(pump(self.clone(), feed, sender));
+        stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|item| (item, receiver))
+        })
+    }
+}
+
+/// Streams what `feed` subscribes to into `sender` until its consumer is
+/// gone or the node turns the subscription away, subscribing again each
+/// time the connection is lost.
+async fn pump<F: Feed>(
+    client: WsLasrClient,
+    mut feed: F,
+    sender: mpsc::Sender<StreamResult<F::Item>>,
+) {
+    let mut outlet = Outlet { sender, missed: 0 };
+    let mut resumed = false;
+    loop {
+        let subscribed = match client.connection().await {
+            Ok(connection) => feed
+                .subscribe(&connection)
+                .await
+                .map(|subscription| (connection, subscription)),
+            Err(e) => Err(e),
+        };
+        let (connection, mut subscription) = match subscribed {
+            Ok(subscribed) => subscribed,
+            // The node would turn it away again.
+            Err(RequestError::Call(e)) => {
+                let _ = outlet.sender.send(Err(RequestError::Call(e).into())).await;
+                return;
+            }
+            Err(_) if outlet.sender.is_closed() => return,
+            Err(_) => {
+                tokio::time::sleep(client.config.reconnect_after).await;
+                continue;
+            }
+        };
+
+        if resumed {
+            if outlet.sender.send(Ok(StreamEvent::Resumed)).await.is_err() {
+                return;
+            }
+            match feed.resume(&connection).await {
+                Ok(items) => {
+                    for item in items {
+                        if !outlet.send(StreamEvent::Item(item)) {
+                            return;
+                        }
+                    }
+                    resumed = false;
+                }
+                // Lost again before it was read back, so read back next time.
+                Err(_) => {
+                    tokio::time::sleep(client.config.reconnect_after).await;
+                    continue;
+                }
+            }
+        }
+
+        loop {
+            let next = tokio::select! {
+                _ = outlet.sender.closed() => return,
+                next = subscription.next() => next,
+            };
+            let Some(Ok(notification)) = next else {
+                break;
+            };
+            match feed.read(notification) {
+                Ok(Some((item, dropped))) => {
+                    outlet.missed += dropped;
+                    if !outlet.send(StreamEvent::Item(item)) {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let _ = outlet.sender.send(Err(e)).await;
+                    return;
+                }
+            }
+        }
+        resumed = true;
+        tokio::time::sleep(client.config.reconnect_after).await;
+    }
+}
+
+/// The consumer's end of a stream, counting what it has no room for as
+/// missed.
+struct Outlet<T> {
+    sender: mpsc::Sender<StreamResult<T>>,
+    missed: u64,
+}
+
+impl<T> Outlet<T> {
+    /// Hands `event` on, after how many were missed before it if any were,
+    /// or counts it missed if the consumer has no room for it. False once
+    /// the consumer is gone.
+    fn send(&mut self, event: StreamEvent<T>) -> bool {
+        if self.missed > 0 {
+            match self.sender.try_send(Ok(StreamEvent::Lagged {
+                missed: self.missed,
+            })) {
+                Ok(()) => self.missed = 0,
+                Err(TrySendError::Full(_)) => {
+                    self.missed += 1;
+                    return true;
+                }
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+        match self.sender.try_send(Ok(event)) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.missed += 1;
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// A subscription a stream is kept on across reconnects.
+#[async_trait]
+trait Feed: Send + 'static {
+    type Item: Send + 'static;
+
+    async fn subscribe(
+        &mut self,
+        client: &WsClient,
+    ) -> Result<Subscription<JsonValue>, RequestError>;
+
+    /// What changed since the last item streamed, read back once subscribed
+    /// again after a reconnect.
+    async fn resume(&mut self, client: &WsClient) -> Result<Vec<Self::Item>, RequestError>;
+
+    /// The item `notification` carries, with how many the node dropped
+    /// before it, or none if it was streamed already.
+    fn read(&mut self, notification: JsonValue) -> Result<Option<(Self::Item, u64)>, ClientError>;
+}
+
+/// A notification of `subscribe`, of what it carries under `T`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Notified<T> {
+    #[serde(alias = "account", alias = "batch", alias = "status")]
+    item: T,
+    #[serde(default)]
+    dropped: u64,
+}
+
+fn notified<T: DeserializeOwned>(notification: JsonValue) -> Result<(T, u64), ClientError> {
+    let Notified { item, dropped } = serde_json::from_value(notification)
+        .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+    Ok((item, dropped))
+}
+
+fn answer<T: DeserializeOwned>(json: String) -> Result<T, RequestError> {
+    serde_json::from_str(&json).map_err(RequestError::ParseError)
+}
+
+struct AccountFeed {
+    address: Address,
+}
+
+#[async_trait]
+impl Feed for AccountFeed {
+    type Item = AccountState;
+
+    async fn subscribe(
+        &mut self,
+        client: &WsClient,
+    ) -> Result<Subscription<JsonValue>, RequestError> {
+        client
+            .subscribe_notifications(
+                "accountChanged".to_string(),
+                Some(self.address.to_full_string()),
+            )
+            .await
+    }
+
+    async fn resume(&mut self, client: &WsClient) -> Result<Vec<AccountState>, RequestError> {
+        let account =
+            answer(LasrRpcClient::get_account(client, self.address.to_full_string()).await?)?;
+        Ok(vec![account])
+    }
+
+    fn read(
+        &mut self,
+        notification: JsonValue,
+    ) -> Result<Option<(AccountState, u64)>, ClientError> {
+        notified(notification).map(Some)
+    }
+}
+
+struct BatchFeed {
+    /// The latest batch a receipt was streamed of.
+    last: Option<u64>,
+}
+
+fn batch_id(receipt: &JsonValue) -> Option<u64> {
+    receipt["header"]["batchId"].as_u64()
+}
+
+#[async_trait]
+impl Feed for BatchFeed {
+    type Item = JsonValue;
+
+    async fn subscribe(
+        &mut self,
+        client: &WsClient,
+    ) -> Result<Subscription<JsonValue>, RequestError> {
+        client
+            .subscribe_notifications("newBatch".to_string(), None)
+            .await
+    }
+
+    async fn resume(&mut self, client: &WsClient) -> Result<Vec<JsonValue>, RequestError> {
+        let latest: JsonValue = answer(client.get_latest_batch().await?)?;
+        let Some(latest) = latest["batchId"].as_u64() else {
+            return Ok(Vec::new());
+        };
+        // The last batch streamed is read back too, as it may have been
+        // settled since.
+        let mut receipts = Vec::new();
+        for batch_id in self.last.unwrap_or(latest).min(latest)..=latest {
+            let receipt: JsonValue = answer(client.get_batch_receipt(batch_id).await?)?;
+            if !receipt.is_null() {
+                receipts.push(receipt);
+            }
+        }
+        self.last = Some(latest);
+        Ok(receipts)
+    }
+
+    fn read(&mut self, notification: JsonValue) -> Result<Option<(JsonValue, u64)>, ClientError> {
+        let (receipt, dropped) = notified::<JsonValue>(notification)?;
+        if let Some(batch_id) = batch_id(&receipt) {
+            self.last = self.last.max(Some(batch_id));
+        }
+        Ok(Some((receipt, dropped)))
+    }
+}
+
+struct TransactionFeed {
+    transaction_hash: String,
+}
+
+#[async_trait]
+impl Feed for TransactionFeed {
+    type Item = JsonValue;
+
+    async fn subscribe(
+        &mut self,
+        client: &WsClient,
+    ) -> Result<Subscription<JsonValue>, RequestError> {
+        client
+            .subscribe_notifications(
+                "transactionStatus".to_string(),
+                Some(self.transaction_hash.clone()),
+            )
+            .await
+    }
+
+    /// Nothing is read back, the node starting each subscription with where
+    /// the transaction has got to.
+    async fn resume(&mut self, _client: &WsClient) -> Result<Vec<JsonValue>, RequestError> {
+        Ok(Vec::new())
+    }
+
+    fn read(&mut self, notification: JsonValue) -> Result<Option<(JsonValue, u64)>, ClientError> {
+        notified(notification).map(Some)
+    }
+}
+
+/// Where an event is in the node's log: its batch, transaction and index.
+type EventKey = (u64, String, u64);
+
+fn event_key(event: &JsonValue) -> Option<EventKey> {
+    Some((
+        event["batchId"].as_u64()?,
+        event["transactionHash"].as_str()?.to_string(),
+        event["index"].as_u64()?,
+    ))
+}
+
+struct EventFeed {
+    filter: EventFilter,
+    started: bool,
+    /// The latest batch every event of which has been streamed or was
+    /// finalized before the stream started.
+    seen_through: Option<u64>,
+    /// The events streamed of batches after it.
+    seen: BTreeSet<EventKey>,
+}
+
+impl EventFeed {
+    /// Whether the event at `key` is yet to be streamed, taking it as
+    /// streamed if so. Events are streamed in order of their batches, so
+    /// once one of a later batch is, so have all of those before it.
+    fn take(&mut self, key: EventKey) -> bool {
+        if self.seen_through.is_some_and(|through| key.0 <= through) || self.seen.contains(&key) {
+            return false;
+        }
+        if let Some(newest) = self.seen.last().map(|seen| seen.0) {
+            if key.0 > newest {
+                self.seen.clear();
+                self.seen_through = Some(newest);
+            }
+        }
+        self.seen.insert(key);
+        true
+    }
+}
+
+#[async_trait]
+impl Feed for EventFeed {
+    type Item = JsonValue;
+
+    async fn subscribe(
+        &mut self,
+        client: &WsClient,
+    ) -> Result<Subscription<JsonValue>, RequestError> {
+        let subscription = client.subscribe_events(self.filter.clone()).await?;
+        // Looked up once subscribed, so an event finalized in between is
+        // streamed rather than left out.
+        if !self.started {
+            let latest: JsonValue = answer(client.get_latest_batch().await?)?;
+            self.seen_through = latest["batchId"].as_u64();
+            self.started = true;
+        }
+        Ok(subscription)
+    }
+
+    async fn resume(&mut self, client: &WsClient) -> Result<Vec<JsonValue>, RequestError> {
+        let mut events = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page: JsonValue = answer(
+                client
+                    .get_events(Some(self.filter.clone()), cursor, None)
+                    .await?,
+            )?;
+            for event in page["items"].as_array().into_iter().flatten() {
+                if event_key(event).is_some_and(|key| self.take(key)) {
+                    events.push(event.clone());
+                }
+            }
+            match page["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
+            }
+        }
+        Ok(events)
+    }
+
+    fn read(
+        &mut self,
+        mut notification: JsonValue,
+    ) -> Result<Option<(JsonValue, u64)>, ClientError> {
+        let dropped = notification
+            .as_object_mut()
+            .and_then(|event| event.remove("dropped"))
+            .and_then(|dropped| dropped.as_u64())
+            .unwrap_or(0);
+        let key = event_key(&notification).ok_or_else(|| {
+            ClientError::InvalidResponse(format!("{notification} is not an event"))
+        })?;
+        Ok(self.take(key).then_some((notification, dropped)))
+    }
+}
+
+/// Headers are sent only in the handshake, so a request with headers of its
+/// own is turned away.
+#[async_trait]
+impl Transport for WsLasrClient {
+    async fn call(&self, request: ClientRequest) -> Result<JsonValue, RequestError> {
+        if !request.headers.is_empty() {
+            return Err(RequestError::Custom(
+                "headers are sent only when connecting, give them to the builder".to_string(),
+            ));
+        }
+        self.connection()
+            .await?
+            .request(&request.method, RawParams(request.params))
+            .await
+    }
+}
+
+#[async_trait]
+impl ClientT for WsLasrClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), RequestError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        self.connection().await?.notification(method, params).await
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, RequestError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        self.connection().await?.request(method, params).await
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, RequestError>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        self.connection().await?.batch_request(batch).await
+    }
+}
+
+#[async_trait]
+impl SubscriptionClientT for WsLasrClient {
+    async fn subscribe<'a, Notif, Params>(
+        &self,
+        subscribe_method: &'a str,
+        params: Params,
+        unsubscribe_method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Params: ToRpcParams + Send,
+        Notif: DeserializeOwned,
+    {
+        self.connection()
+            .await?
+            .subscribe(subscribe_method, params, unsubscribe_method)
+            .await
+    }
+
+    async fn subscribe_to_method<'a, Notif>(
+        &self,
+        method: &'a str,
+    ) -> Result<Subscription<Notif>, RequestError>
+    where
+        Notif: DeserializeOwned,
+    {
+        self.connection().await?.subscribe_to_method(method).await
+    }
+}
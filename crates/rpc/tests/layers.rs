@@ -0,0 +1,130 @@
+#![cfg(test)]
+#![cfg(feature = "test_utils")]
+//! Test coverage for client layers, stacked on a `MockLasrClient` scripted
+//! to fail or answer late.
+
+use std::time::{Duration, Instant};
+
+use jsonrpsee::core::ClientError as RequestError;
+use lasr_rpc::{
+    is_idempotent, AdminRpcClient, BearerAuthLayer, LasrRpcClient, MockLasrClient, RetryLayer,
+    TimeoutLayer, TracingLayer, WithLayer,
+};
+use lasr_types::{Address, PayloadBuilder, RpcErrorCode, Transaction, TransactionType, U256};
+
+fn address() -> String {
+    Address::new([4; 20]).to_full_string()
+}
+
+fn send() -> Transaction {
+    PayloadBuilder::default()
+        .transaction_type(TransactionType::Send(U256::from(1)))
+        .from([4; 20])
+        .to([9; 20])
+        .program_id(Address::verse_addr().inner())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(1))
+        .nonce(U256::from(1))
+        .build()
+        .unwrap()
+        .into()
+}
+
+fn retry() -> RetryLayer {
+    RetryLayer::default()
+        .with_attempts(3)
+        .with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+}
+
+#[tokio::test]
+async fn a_timeout_inside_a_retry_is_given_to_each_attempt() {
+    let mock = MockLasrClient::stateful();
+    mock.delay("getNonce", Duration::from_millis(200));
+    let client = mock
+        .clone()
+        .with_layer(TimeoutLayer::new(Duration::from_millis(20)))
+        .with_layer(retry());
+
+    let error = client.get_nonce(address()).await.unwrap_err();
+    assert!(matches!(error, RequestError::RequestTimeout), "{error:?}");
+    assert_eq!(mock.requests_of("getNonce").len(), 3);
+}
+
+#[tokio::test]
+async fn a_timeout_outside_a_retry_is_given_to_all_attempts_together() {
+    let mock = MockLasrClient::stateful();
+    mock.delay("getNonce", Duration::from_millis(200));
+    let client = mock
+        .clone()
+        .with_layer(retry())
+        .with_layer(TimeoutLayer::new(Duration::from_millis(20)));
+
+    let started = Instant::now();
+    let error = client.get_nonce(address()).await.unwrap_err();
+    assert!(matches!(error, RequestError::RequestTimeout), "{error:?}");
+    assert!(started.elapsed() < Duration::from_millis(200));
+    assert_eq!(mock.requests_of("getNonce").len(), 1);
+}
+
+#[tokio::test]
+async fn reads_the_node_is_too_busy_for_are_made_again() {
+    let mock = MockLasrClient::stateful();
+    mock.fail("getNonce", RpcErrorCode::RateLimited, "slow down")
+        .fail("getNonce", RpcErrorCode::Overloaded, "too busy");
+    let client = mock.clone().with_layer(TracingLayer).with_layer(retry());
+
+    let nonce: U256 = serde_json::from_str(&client.get_nonce(address()).await.unwrap()).unwrap();
+    assert_eq!(nonce, U256::from(1));
+    assert_eq!(mock.requests_of("getNonce").len(), 3);
+
+    // What would fail however often it is made is made once.
+    mock.fail("getNonce", RpcErrorCode::InvalidParams, "not an address");
+    assert!(client.get_nonce(address()).await.is_err());
+    assert_eq!(mock.requests_of("getNonce").len(), 4);
+}
+
+#[tokio::test]
+async fn a_send_is_never_made_again() {
+    let mock = MockLasrClient::stateful();
+    mock.fail("sendRaw", RpcErrorCode::Overloaded, "too busy")
+        .delay("send", Duration::from_millis(200));
+    let client = mock
+        .clone()
+        .with_layer(TimeoutLayer::new(Duration::from_millis(20)))
+        .with_layer(retry());
+
+    assert!(client.send_raw("0x00".to_string()).await.is_err());
+    assert_eq!(mock.requests_of("sendRaw").len(), 1);
+
+    // Timed out, it may still have reached the node.
+    let error = client.send(send()).await.unwrap_err();
+    assert!(matches!(error, RequestError::RequestTimeout), "{error:?}");
+    assert_eq!(mock.requests_of("send").len(), 1);
+    assert!(!is_idempotent("lasr_send"));
+    assert!(!is_idempotent("lasr_call"));
+    assert!(!is_idempotent("eth_sendRawTransaction"));
+    assert!(is_idempotent("lasr_getAccount"));
+    assert!(is_idempotent("lasr_estimateFees"));
+}
+
+#[tokio::test]
+async fn the_bearer_token_is_sent_with_the_admin_methods_only() {
+    let mock = MockLasrClient::stateful();
+    mock.respond("admin_getProgramStats", "{}");
+    let client = mock
+        .clone()
+        .with_layer(BearerAuthLayer::new("secret"))
+        .with_layer(retry());
+
+    client.get_program_stats(None, None, None).await.unwrap();
+    client.get_nonce(address()).await.unwrap();
+
+    let requests = mock.requests();
+    assert_eq!(
+        requests[0].headers.get("authorization").map(String::as_str),
+        Some("Bearer secret")
+    );
+    assert!(requests[1].headers.is_empty());
+    assert!(!format!("{:?}", client.inner().layer()).contains("secret"));
+}
@@ -0,0 +1,172 @@
+#![cfg(test)]
+//! Test coverage for the requests an `HttpLasrClient` has of a node at once,
+//! against a node answering `getAccount` late.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::join_all;
+use jsonrpsee::{
+    core::{client::ClientT, params::BatchRequestBuilder, ClientError as RequestError},
+    rpc_params,
+    server::{BatchRequestConfig, RpcModule, Server, ServerHandle},
+    types::{error::INVALID_PARAMS_CODE, ErrorObjectOwned},
+};
+use lasr_rpc::{HttpLasrClient, LasrRpcClient};
+use lasr_types::{AccountState, Address};
+
+/// A node answering `getAccount` `delay` after it is asked, counting how
+/// often it is asked and the most it is asked at once.
+#[derive(Default)]
+struct Node {
+    delay: Duration,
+    asked: AtomicUsize,
+    answering: AtomicUsize,
+    most_answering: AtomicUsize,
+}
+
+async fn start(delay: Duration) -> (Arc<Node>, String, ServerHandle) {
+    start_accepting(delay, BatchRequestConfig::Unlimited).await
+}
+
+/// As [`start`], the node accepting the batches `batches` lets through.
+async fn start_accepting(
+    delay: Duration,
+    batches: BatchRequestConfig,
+) -> (Arc<Node>, String, ServerHandle) {
+    let node = Arc::new(Node {
+        delay,
+        ..Node::default()
+    });
+    let mut module = RpcModule::new(Arc::clone(&node));
+    module
+        .register_async_method("lasr_getAccount", |params, node| async move {
+            let address: String = params.one()?;
+            let address: Address = address.parse().map_err(|_| {
+                ErrorObjectOwned::owned(INVALID_PARAMS_CODE, "not an address", None::<()>)
+            })?;
+            node.asked.fetch_add(1, Ordering::SeqCst);
+            let answering = node.answering.fetch_add(1, Ordering::SeqCst) + 1;
+            node.most_answering.fetch_max(answering, Ordering::SeqCst);
+            tokio::time::sleep(node.delay).await;
+            node.answering.fetch_sub(1, Ordering::SeqCst);
+            Ok::<_, ErrorObjectOwned>(
+                serde_json::to_string(&AccountState::new(address, None)).unwrap(),
+            )
+        })
+        .unwrap();
+    let server = Server::builder()
+        .set_batch_request_config(batches)
+        .build("127.0.0.1:0")
+        .await
+        .expect("failed to build rpc server");
+    let url = format!("http://{}", server.local_addr().unwrap());
+    (node, url, server.start(module))
+}
+
+#[tokio::test]
+async fn requests_over_the_limit_wait_their_turn_rather_than_failing() {
+    let (node, url, _handle) = start(Duration::from_millis(50)).await;
+    // Ten turns of 50ms each is longer than any one request may take.
+    let client = HttpLasrClient::builder()
+        .with_max_in_flight(2)
+        .with_timeout(Duration::from_millis(200))
+        .build(&url)
+        .unwrap();
+
+    let answers = join_all((0..20).map(|_| client.get_account(Address::new([1; 20])))).await;
+    for answer in answers {
+        answer.expect("a request over the limit failed");
+    }
+    assert_eq!(node.asked.load(Ordering::SeqCst), 20);
+    assert_eq!(node.most_answering.load(Ordering::SeqCst), 2);
+
+    let metrics = client.pool_metrics();
+    assert_eq!(metrics.host, url.trim_start_matches("http://"));
+    assert_eq!(metrics.requests, 20);
+    assert_eq!(metrics.waited, 18);
+    assert_eq!(metrics.failed, 0);
+    assert_eq!(metrics.peak_in_flight, 2);
+    assert_eq!((metrics.in_flight, metrics.queued), (0, 0));
+}
+
+#[tokio::test]
+async fn calls_made_near_together_are_sent_as_one_batch() {
+    let (node, url, _handle) = start(Duration::from_millis(10)).await;
+    let client = HttpLasrClient::builder()
+        .with_batching(Duration::from_millis(20), 8)
+        .build(&url)
+        .unwrap();
+
+    let addresses: Vec<Address> = (0..20).map(|i| Address::new([i; 20])).collect();
+    let answers = join_all(addresses.iter().map(|address| client.get_account(*address))).await;
+    for (answer, address) in answers.into_iter().zip(&addresses) {
+        assert_eq!(answer.unwrap().address, *address);
+    }
+    assert_eq!(node.asked.load(Ordering::SeqCst), 20);
+
+    let metrics = client.pool_metrics();
+    assert_eq!(metrics.batches, 3);
+    assert_eq!(metrics.batched, 20);
+    assert_eq!(metrics.requests, 20);
+}
+
+#[tokio::test]
+async fn a_call_the_node_fails_fails_alone_in_its_batch() {
+    let (_node, url, _handle) = start(Duration::ZERO).await;
+    let client = HttpLasrClient::builder()
+        .with_batching(Duration::from_millis(20), 8)
+        .build(&url)
+        .unwrap();
+
+    let (found, failed) = tokio::join!(
+        client.get_account(Address::new([1; 20])),
+        LasrRpcClient::get_account(&client, "not an address".to_string()),
+    );
+    assert!(!found.unwrap().found);
+    match failed.unwrap_err() {
+        RequestError::Call(e) => assert_eq!(e.code(), INVALID_PARAMS_CODE),
+        e => panic!("the call failed with {e:?}"),
+    }
+
+    let metrics = client.pool_metrics();
+    assert_eq!((metrics.batches, metrics.batched), (1, 2));
+    assert_eq!(metrics.failed, 1);
+}
+
+#[tokio::test]
+async fn batches_are_split_at_the_most_the_node_accepts_in_one() {
+    let (node, url, _handle) =
+        start_accepting(Duration::from_millis(10), BatchRequestConfig::Limit(8)).await;
+    let client = HttpLasrClient::builder()
+        .with_batching(Duration::from_millis(20), 50)
+        .with_max_batch_size(8)
+        .build(&url)
+        .unwrap();
+
+    let addresses: Vec<Address> = (0..20).map(|i| Address::new([i; 20])).collect();
+    let answers = join_all(addresses.iter().map(|address| client.get_account(*address))).await;
+    for (answer, address) in answers.into_iter().zip(&addresses) {
+        assert_eq!(answer.unwrap().address, *address);
+    }
+    let metrics = client.pool_metrics();
+    assert_eq!((metrics.batches, metrics.batched), (3, 20));
+
+    // A batch made whole is split too, and answered as one.
+    let mut batch = BatchRequestBuilder::new();
+    for address in &addresses {
+        batch
+            .insert("lasr_getAccount", rpc_params![address.to_full_string()])
+            .unwrap();
+    }
+    let answers = ClientT::batch_request::<String>(&client, batch)
+        .await
+        .unwrap();
+    assert_eq!(answers.num_successful_calls(), 20);
+    for (answer, address) in answers.into_iter().zip(&addresses) {
+        let account: AccountState = serde_json::from_str(&answer.unwrap()).unwrap();
+        assert_eq!(account.address, *address);
+    }
+    assert_eq!(node.asked.load(Ordering::SeqCst), 40);
+}
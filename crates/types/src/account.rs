@@ -1,32 +1,43 @@
 use crate::{
-    AddressOrNamespace, ArbitraryData, DataValue, Metadata, MetadataValue, ProgramUpdate, Status,
-    ToTokenError, Token, TokenBuilder, TokenUpdateField, Transaction,
+    AccountMetadata, AddressOrNamespace, ArbitraryData, DataValue, Delegation, DelegationError,
+    DelegationOp, Metadata, MetadataValue, ProgramUpdate, StorageQuota, ToTokenError, Token,
+    TokenUpdateField, Transaction,
 };
+use borsh::{BorshDeserialize, BorshSerialize};
 use derive_builder::Builder;
-use hex::{FromHexError, ToHex};
 use schemars::JsonSchema;
 use secp256k1::PublicKey;
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha3::{Digest, Keccak256};
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::{Debug, Display, LowerHex},
     hash::Hash,
     str::FromStr,
 };
+use thiserror::Error;
 
 pub type AccountError = std::io::Error;
 
 pub type AccountResult<T> = Result<T, Box<dyn std::error::Error + Send>>;
 
+/// The sends an account remembers having applied, so applying one again
+/// answers as it did the first time rather than applying it twice.
+pub const RECENTLY_APPLIED: usize = 64;
+
+/// Addresses are written as their checksummed hex to JSON and the other
+/// human-readable formats, and as their 20 bytes to bincode.
 impl Serialize for Address {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let hex_string = hex::encode(self.inner());
-        serializer.serialize_str(&format!("0x{}", hex_string))
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_checksum_string())
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
     }
 }
 
@@ -43,16 +54,7 @@ impl<'de> Visitor<'de> for AddressVisitor {
     where
         E: serde::de::Error,
     {
-        if let Some(v) = value.strip_prefix("0x") {
-            let bytes = hex::decode(v).map_err(E::custom)?;
-            if bytes.len() == 20 {
-                let mut arr = [0u8; 20];
-                arr.copy_from_slice(&bytes);
-                Ok(Address(arr))
-            } else {
-                Err(E::custom("Hex string does not represent a valid Address"))
-            }
-        } else if value.starts_with('[') && value.ends_with(']') {
+        if value.starts_with('[') && value.ends_with(']') {
             let bytes_str = &value[1..value.len() - 1];
             let bytes: Vec<u8> = bytes_str
                 .split(',')
@@ -68,7 +70,21 @@ impl<'de> Visitor<'de> for AddressVisitor {
                 Err(E::custom("invalid length for address"))
             }
         } else {
-            Err(E::custom("Invalid address format"))
+            Address::from_hex(value).map_err(E::custom)
+        }
+    }
+
+    /// Takes the 20 bytes bincode holds an address as, or the hex string it
+    /// was written as before.
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        match <[u8; 20]>::try_from(value) {
+            Ok(bytes) => Ok(Address(bytes)),
+            Err(_) => std::str::from_utf8(value)
+                .map_err(|_| E::custom(AddressError::Length(value.len() * 2)))
+                .and_then(|value| self.visit_str(value)),
         }
     }
 }
@@ -78,7 +94,102 @@ impl<'de> Deserialize<'de> for Address {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_str(AddressVisitor)
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(AddressVisitor)
+        } else {
+            deserializer.deserialize_bytes(AddressVisitor)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum AddressError {
+    #[error("an address must start with 0x")]
+    Prefix,
+
+    #[error("an address is 40 hex digits, not {0}")]
+    Length(usize),
+
+    #[error("an address is hex")]
+    NotHex,
+
+    #[error("address does not match its checksum {0}")]
+    Checksum(String),
+}
+
+/// Why a transaction was not applied to an account. Boxed in an
+/// [`AccountResult`], it is found again with `downcast_ref`.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum ApplyError {
+    /// The transaction is not next from the account, which is behind or
+    /// ahead of whoever sent it and should be read again.
+    #[error("nonce {nonce} is out of order, the account is next at {expected}")]
+    OutOfOrder {
+        expected: crate::U256,
+        nonce: crate::U256,
+    },
+}
+
+/// Why an account does not take a transaction at a nonce.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum NonceError {
+    #[error("nonce {nonce} has been used already, the next is {next}")]
+    TooLow {
+        nonce: crate::U256,
+        next: crate::U256,
+    },
+
+    #[error("nonce {nonce} is too far ahead, the highest accepted is {max}")]
+    TooHigh {
+        nonce: crate::U256,
+        max: crate::U256,
+    },
+
+    /// The nonce is one a transaction recently applied to the account was
+    /// sent at.
+    #[error("nonce {nonce} was sent at by a transaction already applied, the next is {next}")]
+    Replayed {
+        nonce: crate::U256,
+        next: crate::U256,
+    },
+
+    /// The account has used the highest nonce there is.
+    #[error("the account has used every nonce")]
+    Exhausted,
+}
+
+/// Why an account was not built.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum AccountInvariantError {
+    #[error("{0}")]
+    Incomplete(String),
+
+    #[error("the token of {program_id} is held under {key}")]
+    ProgramMismatch { key: Address, program_id: Address },
+
+    #[error("the token of {program_id} is owned by {owner_id}, not the account {account}")]
+    OwnerMismatch {
+        program_id: Address,
+        owner_id: Address,
+        account: Address,
+    },
+
+    #[error("program account {program_address} sends nothing, so cannot be at nonce {nonce}")]
+    ProgramNonce {
+        program_address: Address,
+        nonce: crate::U256,
+    },
+}
+
+impl AccountBuilder {
+    /// The account, if every field is set and it holds to
+    /// [`Account::check_invariants`].
+    pub fn try_build(&self) -> Result<Account, AccountInvariantError> {
+        let account = self
+            .build_unchecked()
+            .map_err(|e| AccountInvariantError::Incomplete(e.to_string()))?;
+        account.check_invariants()?;
+        Ok(account)
     }
 }
 
@@ -87,13 +198,25 @@ impl<'de> Deserialize<'de> for Address {
 /// This structure is used to store Ethereum Compatible addresses, which are
 /// derived from the public key. It implements traits like Clone, Copy, Debug,
 /// Serialize, Deserialize, etc., for ease of use across various contexts.
-#[derive(Clone, Copy, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[derive(
+    Clone,
+    Copy,
+    JsonSchema,
+    BorshSerialize,
+    BorshDeserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct Address([u8; 20]);
 
 impl std::fmt::Debug for Address {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_full_string())
+        write!(f, "{}", self.to_checksum_string())
     }
 }
 
@@ -113,27 +236,57 @@ impl Address {
         Address(bytes)
     }
 
-    /// Converts the inner Address to a full hexadecimal string
-    /// this exists because in the Disply implementation we abbreviate the
-    /// address
+    /// Converts the inner Address to a full lowercase hexadecimal string,
+    /// the form accounts are keyed by and transactions hashed with. Shown
+    /// to anyone, an address is its `to_checksum_string`.
     pub fn to_full_string(&self) -> String {
         format!("0x{:x}", self)
     }
 
-    pub fn from_hex(hex_str: &str) -> Result<Self, FromHexError> {
-        let hex_str = if let Some(v) = hex_str.strip_prefix("0x") {
-            v
-        } else {
-            hex_str
-        };
-        let bytes = hex::decode(hex_str)?;
-        let mut addr_inner = [0u8; 20];
-        if bytes.len() != 20 {
-            return Err(FromHexError::OddLength);
-        }
+    /// The address as EIP-55 checksummed hex: a letter is uppercase where
+    /// the same nibble of the keccak hash of the lowercase hex is 8 or over.
+    pub fn to_checksum_string(&self) -> String {
+        let lower = format!("{:x}", self);
+        let hash = Keccak256::digest(lower.as_bytes());
+        let checksummed: String = lower
+            .char_indices()
+            .map(|(i, c)| {
+                let nibble = if i % 2 == 0 {
+                    hash[i / 2] >> 4
+                } else {
+                    hash[i / 2] & 0x0f
+                };
+                if nibble >= 8 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            })
+            .collect();
+        format!("0x{checksummed}")
+    }
 
-        addr_inner.copy_from_slice(&bytes[..]);
-        Ok(Address(addr_inner))
+    /// Parses `0x` and exactly 40 hex digits. Digits all in one case are
+    /// taken as they are; mixed case must match the address's checksum.
+    pub fn from_hex(hex_str: &str) -> Result<Self, AddressError> {
+        let digits = hex_str.strip_prefix("0x").ok_or(AddressError::Prefix)?;
+        Self::from_digits(digits)
+    }
+
+    fn from_digits(digits: &str) -> Result<Self, AddressError> {
+        if digits.len() != 40 {
+            return Err(AddressError::Length(digits.len()));
+        }
+        let mut bytes = [0u8; 20];
+        hex::decode_to_slice(digits, &mut bytes).map_err(|_| AddressError::NotHex)?;
+        let address = Address(bytes);
+        let mixed_case = digits.bytes().any(|b| b.is_ascii_lowercase())
+            && digits.bytes().any(|b| b.is_ascii_uppercase());
+        let checksummed = address.to_checksum_string();
+        if mixed_case && checksummed[2..] != *digits {
+            return Err(AddressError::Checksum(checksummed));
+        }
+        Ok(address)
     }
 
     pub fn inner(&self) -> [u8; 20] {
@@ -150,7 +303,13 @@ impl Address {
     Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
 #[serde(rename_all = "camelCase")]
-pub struct AccountHash([u8; 32]);
+pub struct AccountHash(
+    #[serde(
+        serialize_with = "crate::hex_bytes::serialize",
+        deserialize_with = "crate::hex_bytes::deserialize"
+    )]
+    [u8; 32],
+);
 
 impl AccountHash {
     /// Creates a new `AccountHash` instance from a 32-byte array.
@@ -178,7 +337,18 @@ pub struct AccountNonce {
 pub struct ProgramNamespace(Namespace, Address);
 
 #[derive(
-    Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub struct Namespace(pub String);
@@ -290,7 +460,19 @@ impl ProgramAccount {
 }
 
 #[derive(
-    Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
 )]
 #[serde(rename_all = "camelCase")]
 pub enum AccountType {
@@ -310,6 +492,8 @@ pub enum AccountType {
     Debug,
     Serialize,
     Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
     JsonSchema,
     PartialEq,
     Eq,
@@ -318,6 +502,7 @@ pub enum AccountType {
     Hash,
     Default,
 )]
+#[builder(build_fn(private, name = "build_unchecked"))]
 #[serde(rename_all = "camelCase")]
 pub struct Account {
     account_type: AccountType,
@@ -328,31 +513,304 @@ pub struct Account {
     program_account_data: ArbitraryData,
     program_account_metadata: Metadata,
     program_account_linked_programs: BTreeSet<AddressOrNamespace>,
+    /// The program that authorizes the account's transactions in place of
+    /// a signature by its owner's key, if it names one. Written even when
+    /// unset, as bincode cannot read back a struct missing a field; accounts
+    /// written before it are read through [`Account::from_legacy_bincode`].
+    #[builder(default)]
+    #[serde(default)]
+    auth_program: Option<Address>,
+    /// What the owner delegated to other keys, by delegate.
+    #[builder(default)]
+    #[serde(default)]
+    delegations: BTreeMap<Address, Delegation>,
+    /// What the owner says of the account, as opposed to its tokens. Read
+    /// as empty from an account written without it, in JSON or through
+    /// [`Account::from_legacy_bincode`].
+    #[builder(default)]
+    #[serde(default)]
+    account_metadata: AccountMetadata,
+    /// What sends admitted from the account, and not yet applied or turned
+    /// away, will take from it. Only held in memory.
+    #[serde(skip)]
+    #[borsh(skip)]
+    #[builder(default)]
+    pending_debits: PendingDebits,
+    /// The last [`RECENTLY_APPLIED`] sends applied to the account, with the
+    /// token each answered. Only held in memory.
+    #[serde(skip)]
+    #[borsh(skip)]
+    #[builder(default)]
+    applied: AppliedTransactions,
+}
+
+/// An account as it was written before auth programs, delegations and
+/// account metadata were appended to it. bincode and borsh write fields one
+/// after another with nothing to say where a struct ends, so an account
+/// written without a field cannot be read as one with it, and is read back
+/// in the layout it was written in instead. Each layout after the first is
+/// the one before with a field appended, which nesting writes the same way.
+#[derive(Deserialize, BorshDeserialize)]
+struct AccountV0 {
+    account_type: AccountType,
+    program_namespace: Option<AddressOrNamespace>,
+    owner_address: Address,
+    programs: BTreeMap<Address, Token>,
+    nonce: crate::U256,
+    program_account_data: ArbitraryData,
+    program_account_metadata: Metadata,
+    program_account_linked_programs: BTreeSet<AddressOrNamespace>,
+}
+
+/// An account with an auth program, before delegations.
+#[derive(Deserialize, BorshDeserialize)]
+struct AccountV1 {
+    account: AccountV0,
+    auth_program: Option<Address>,
+}
+
+/// An account with delegations, before account metadata.
+#[derive(Deserialize, BorshDeserialize)]
+struct AccountV2 {
+    account: AccountV1,
+    delegations: BTreeMap<Address, Delegation>,
+}
+
+impl From<AccountV0> for Account {
+    fn from(account: AccountV0) -> Self {
+        Account {
+            account_type: account.account_type,
+            program_namespace: account.program_namespace,
+            owner_address: account.owner_address,
+            programs: account.programs,
+            nonce: account.nonce,
+            program_account_data: account.program_account_data,
+            program_account_metadata: account.program_account_metadata,
+            program_account_linked_programs: account.program_account_linked_programs,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<AccountV1> for Account {
+    fn from(account: AccountV1) -> Self {
+        Account {
+            auth_program: account.auth_program,
+            ..account.account.into()
+        }
+    }
+}
+
+impl From<AccountV2> for Account {
+    fn from(account: AccountV2) -> Self {
+        Account {
+            delegations: account.delegations,
+            ..account.account.into()
+        }
+    }
 }
 
 impl Account {
-    /// Constructs a new `Account` with the given address and optional program data.
-    ///
-    /// This function initializes an account with the provided address and an optional
-    /// map of programs. It updates the account hash before returning.
-    pub fn new(
-        account_type: AccountType,
-        program_namespace: Option<AddressOrNamespace>,
-        owner_address: Address,
-        _programs: Option<BTreeMap<Address, Token>>,
-    ) -> Self {
+    /// The account bincode `bytes` hold in an older layout, newest first.
+    /// A layout must take up every byte, so an account in a newer layout is
+    /// not read as the start of an older one.
+    pub fn from_legacy_bincode(bytes: &[u8]) -> Option<Self> {
+        fn read<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+            use bincode::Options;
+            bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .reject_trailing_bytes()
+                .deserialize(bytes)
+                .ok()
+        }
+        read::<AccountV2>(bytes)
+            .map(Account::from)
+            .or_else(|| read::<AccountV1>(bytes).map(Account::from))
+            .or_else(|| read::<AccountV0>(bytes).map(Account::from))
+    }
+
+    /// The account borsh `bytes` hold in an older layout, as
+    /// [`Account::from_legacy_bincode`] reads bincode.
+    pub fn from_legacy_borsh(bytes: &[u8]) -> Option<Self> {
+        borsh::from_slice::<AccountV2>(bytes)
+            .map(Account::from)
+            .or_else(|_| borsh::from_slice::<AccountV1>(bytes).map(Account::from))
+            .or_else(|_| borsh::from_slice::<AccountV0>(bytes).map(Account::from))
+            .ok()
+    }
+}
+
+/// The value a transaction admitted from an account will take from it, of
+/// the token of `program_id`, once applied at `nonce`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingDebit {
+    pub program_id: Address,
+    pub value: crate::U256,
+    pub nonce: crate::U256,
+}
+
+/// The pending debits of an account, by transaction hash. They play no part
+/// in comparing, ordering or hashing accounts, nor in how they are written.
+#[derive(Clone, Debug, Default)]
+pub struct PendingDebits(BTreeMap<String, PendingDebit>);
+
+impl PendingDebits {
+    pub fn inner(&self) -> &BTreeMap<String, PendingDebit> {
+        &self.0
+    }
+}
+
+impl From<BTreeMap<String, PendingDebit>> for PendingDebits {
+    fn from(debits: BTreeMap<String, PendingDebit>) -> Self {
+        Self(debits)
+    }
+}
+
+impl PartialEq for PendingDebits {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PendingDebits {}
+
+impl PartialOrd for PendingDebits {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PendingDebits {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl Hash for PendingDebits {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+}
+
+/// The sends applied to an account, oldest first, by transaction hash. Like
+/// [`PendingDebits`], they play no part in comparing accounts.
+#[derive(Clone, Debug, Default)]
+pub struct AppliedTransactions(VecDeque<AppliedTransaction>);
+
+#[derive(Clone, Debug)]
+struct AppliedTransaction {
+    hash: String,
+    /// The nonce it was sent at, if it was sent from the account.
+    nonce: Option<crate::U256>,
+    token: Token,
+}
+
+impl AppliedTransactions {
+    /// The token `transaction_hash` answered when it was applied.
+    pub fn get(&self, transaction_hash: &str) -> Option<&Token> {
+        self.0
+            .iter()
+            .find(|applied| applied.hash == transaction_hash)
+            .map(|applied| &applied.token)
+    }
+
+    /// Whether a send from the account applied to it was sent at `nonce`.
+    pub fn used(&self, nonce: crate::U256) -> bool {
+        self.0.iter().any(|applied| applied.nonce == Some(nonce))
+    }
+
+    fn record(&mut self, hash: String, nonce: Option<crate::U256>, token: Token) {
+        if self.0.len() == RECENTLY_APPLIED {
+            self.0.pop_front();
+        }
+        self.0.push_back(AppliedTransaction { hash, nonce, token });
+    }
+}
+
+impl PartialEq for AppliedTransactions {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for AppliedTransactions {}
+
+impl PartialOrd for AppliedTransactions {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AppliedTransactions {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl Hash for AppliedTransactions {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+}
+
+impl Account {
+    /// An empty user account of `owner_address`.
+    pub fn new(owner_address: Address) -> Self {
+        Self::empty(AccountType::User, owner_address)
+    }
+
+    /// An empty account of the program at `program_address`, registered by
+    /// `owner_address`.
+    pub fn new_program(program_address: Address, owner_address: Address) -> Self {
+        Self::empty(AccountType::Program(program_address), owner_address)
+    }
+
+    fn empty(account_type: AccountType, owner_address: Address) -> Self {
         Self {
             account_type,
-            program_namespace,
+            program_namespace: None,
             owner_address,
             programs: BTreeMap::new(),
             nonce: crate::U256::default(),
             program_account_data: ArbitraryData::new(),
             program_account_metadata: Metadata::new(),
             program_account_linked_programs: BTreeSet::new(),
+            auth_program: None,
+            delegations: BTreeMap::new(),
+            account_metadata: AccountMetadata::default(),
+            pending_debits: PendingDebits::default(),
+            applied: AppliedTransactions::default(),
         }
     }
 
+    /// Checks the account holds to what every account does: each token is
+    /// keyed by its program and owned by the account, and a program account,
+    /// which sends nothing, is at the first nonce.
+    pub fn check_invariants(&self) -> Result<(), AccountInvariantError> {
+        let account = self.token_owner();
+        for (key, token) in &self.programs {
+            if token.program_id() != *key {
+                return Err(AccountInvariantError::ProgramMismatch {
+                    key: *key,
+                    program_id: token.program_id(),
+                });
+            }
+            if token.owner_id() != account {
+                return Err(AccountInvariantError::OwnerMismatch {
+                    program_id: *key,
+                    owner_id: token.owner_id(),
+                    account,
+                });
+            }
+        }
+        if let AccountType::Program(program_address) = self.account_type {
+            if !self.nonce.is_zero() {
+                return Err(AccountInvariantError::ProgramNonce {
+                    program_address,
+                    nonce: self.nonce,
+                });
+            }
+        }
+        Ok(())
+    }
+
     pub fn account_type(&self) -> AccountType {
         self.account_type.clone()
     }
@@ -401,23 +859,218 @@ impl Account {
         &mut self.program_account_linked_programs
     }
 
+    pub fn auth_program(&self) -> Option<Address> {
+        self.auth_program
+    }
+
+    pub fn set_auth_program(&mut self, auth_program: Option<Address>) {
+        self.auth_program = auth_program;
+    }
+
+    pub fn delegations(&self) -> &BTreeMap<Address, Delegation> {
+        &self.delegations
+    }
+
+    pub fn delegation(&self, delegate: &Address) -> Option<&Delegation> {
+        self.delegations.get(delegate)
+    }
+
+    pub fn account_metadata(&self) -> &AccountMetadata {
+        &self.account_metadata
+    }
+
+    pub fn set_account_metadata(&mut self, account_metadata: AccountMetadata) {
+        self.account_metadata = account_metadata;
+    }
+
+    pub fn pending_debits(&self) -> &PendingDebits {
+        &self.pending_debits
+    }
+
+    /// What the pending debits of the account will take of the token of
+    /// `program_id`.
+    pub fn pending_debit(&self, program_id: &Address) -> crate::U256 {
+        self.pending_debits
+            .0
+            .values()
+            .filter(|debit| debit.program_id == *program_id)
+            .fold(crate::U256::zero(), |total, debit| {
+                total.saturating_add(debit.value)
+            })
+    }
+
+    /// The balance of the token of `program_id` left once the pending
+    /// debits of the account are applied.
+    pub fn spendable(&self, program_id: &Address) -> crate::U256 {
+        self.balance(program_id)
+            .saturating_sub(self.pending_debit(program_id))
+    }
+
+    /// Holds what `transaction` takes from the account until it is applied
+    /// or turned away, if it is a send, burn or bridge out from it.
+    pub fn reserve_debit(&mut self, transaction: &Transaction) {
+        if transaction.from() != self.owner_address
+            || !transaction.transaction_type().debits_sender()
+            || transaction.value().is_zero()
+        {
+            return;
+        }
+        self.pending_debits.0.insert(
+            transaction.hash_string(),
+            PendingDebit {
+                program_id: transaction.program_id(),
+                value: transaction.value(),
+                nonce: transaction.nonce(),
+            },
+        );
+    }
+
+    /// Releases what the transaction `transaction_hash` held, answering
+    /// whether it held anything.
+    pub fn release_debit(&mut self, transaction_hash: &str) -> bool {
+        self.pending_debits.0.remove(transaction_hash).is_some()
+    }
+
+    /// Takes on the pending debits of `previous`, the account as it was
+    /// before, but those of transactions at nonces the account has reached,
+    /// as they were applied or outnumbered.
+    pub fn keep_pending_debits(&mut self, previous: &Account) {
+        let nonce = self.nonce;
+        self.pending_debits = PendingDebits(
+            previous
+                .pending_debits
+                .0
+                .iter()
+                .filter(|(_, debit)| debit.nonce > nonce)
+                .map(|(hash, debit)| (hash.clone(), debit.clone()))
+                .collect(),
+        );
+    }
+
+    /// Grants or revokes a delegation as `op` says.
+    pub fn apply_delegation_op(&mut self, op: DelegationOp) -> Result<(), DelegationError> {
+        match op {
+            DelegationOp::Grant(delegation) => {
+                if delegation.delegate == self.owner_address {
+                    return Err(DelegationError::SelfDelegation);
+                }
+                self.delegations.insert(delegation.delegate, delegation);
+            }
+            DelegationOp::Revoke(delegate) => {
+                self.delegations
+                    .remove(&delegate)
+                    .ok_or(DelegationError::NotDelegated(delegate))?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn balance(&self, program_id: &Address) -> crate::U256 {
-        if let Some(entry) = self.programs().get(program_id) {
+        if let Some(entry) = self.token(program_id) {
             return entry.balance();
         }
 
         crate::U256::from(0)
     }
+
+    /// The address the account holds its tokens as, its program's for a
+    /// program account.
+    fn token_owner(&self) -> Address {
+        match self.account_type {
+            AccountType::Program(program_address) => program_address,
+            AccountType::User => self.owner_address,
+        }
+    }
+
+    pub fn token(&self, program_id: &Address) -> Option<&Token> {
+        self.programs.get(program_id)
+    }
+
+    /// The token of `program_id`, created empty for the account if it holds
+    /// none. Every token an account comes to hold starts here.
+    pub fn token_mut_or_default(&mut self, program_id: &Address) -> &mut Token {
+        let owner_id = self.token_owner();
+        self.programs
+            .entry(*program_id)
+            .or_insert_with(|| Token::empty(*program_id, owner_id))
+    }
+
+    /// The token of `program_id` as [`Account::token_mut_or_default`] has
+    /// it, but created with the metadata and data of `program_account`.
+    fn token_or_inherited(
+        &mut self,
+        program_id: &Address,
+        program_account: Option<&Account>,
+    ) -> &mut Token {
+        let created = self.token(program_id).is_none();
+        let token = self.token_mut_or_default(program_id);
+        if let (true, Some(program_account)) = (created, program_account) {
+            token.set_metadata(program_account.program_account_metadata().clone());
+            token.set_data(program_account.program_account_data().clone());
+        }
+        token
+    }
+
+    /// Drops the token of `program_id` if it holds nothing, answering
+    /// whether it did.
+    pub fn remove_token_if_empty(&mut self, program_id: &Address) -> bool {
+        if !self.token(program_id).is_some_and(Token::is_empty) {
+            return false;
+        }
+        self.programs.remove(program_id).is_some()
+    }
+
+    /// The account's tokens, by program id.
+    pub fn tokens_iter(&self) -> impl Iterator<Item = (&Address, &Token)> {
+        self.programs.iter()
+    }
+    pub fn applied(&self) -> &AppliedTransactions {
+        &self.applied
+    }
+
+    /// Applies a send, or a bridge in, to the account once. Applied again,
+    /// it answers the token it did the first time and changes nothing. A
+    /// send from the account must be the next it sends, and takes it to the
+    /// send's nonce.
     pub fn apply_send_transaction(
         &mut self,
         transaction: Transaction,
         program_account: Option<&Account>,
     ) -> AccountResult<Token> {
+        let transaction_hash = transaction.hash_string();
+        if let Some(token) = self.applied.get(&transaction_hash) {
+            return Ok(token.clone());
+        }
+        let nonce = transaction.nonce();
+        let sent = transaction.from() == self.owner_address
+            && transaction.transaction_type().debits_sender();
+        if sent && self.accept_nonce(nonce).is_err() {
+            return Err(Box::new(ApplyError::OutOfOrder {
+                expected: self.expected_nonce(),
+                nonce,
+            }));
+        }
+        let token = self.apply_send(transaction, program_account)?;
+        if sent {
+            self.increment_nonce();
+        }
+        self.applied
+            .record(transaction_hash, sent.then_some(nonce), token.clone());
+        Ok(token)
+    }
+
+    fn apply_send(
+        &mut self,
+        transaction: Transaction,
+        program_account: Option<&Account>,
+    ) -> AccountResult<Token> {
+        // Applied, what the send held is taken from the balance instead.
+        self.release_debit(&transaction.hash_string());
         if transaction.transaction_type().is_bridge_in() {
-            if !self.programs.contains_key(&transaction.program_id()) {
-                let token: Token = transaction.into();
-                self.insert_program(&token.program_id(), token.clone());
-                return Ok(token);
+            if self.token(&transaction.program_id()).is_none() {
+                let token = self.token_mut_or_default(&transaction.program_id());
+                token.credit(&transaction.value())?;
+                return Ok(token.clone());
             }
             let mut programs = self.programs.clone();
             if let Some(token) = programs.get_mut(&transaction.program_id()) {
@@ -505,18 +1158,9 @@ impl Account {
             }
 
             if transaction.to() == self.owner_address() {
-                let mut token: Token = transaction.into();
-                if let Some(account) = program_account {
-                    let program_account_metadata = account.program_account_metadata();
-                    let program_account_data = account.program_account_data();
-                    token.set_metadata(program_account_metadata.clone());
-                    token.set_data(program_account_data.clone());
-                    self.insert_program(&token.program_id(), token.clone());
-                    return Ok(token);
-                } else {
-                    self.insert_program(&token.program_id(), token.clone());
-                    return Ok(token);
-                }
+                let token = self.token_or_inherited(&transaction.program_id(), program_account);
+                token.credit(&transaction.value())?;
+                return Ok(token.clone());
             }
 
             if let AccountType::Program(program_address) = self.account_type() {
@@ -562,18 +1206,10 @@ impl Account {
                             return Ok(token.clone());
                         }
                     } else {
-                        let mut token: Token = transaction.into();
-                        if let Some(account) = program_account {
-                            let program_account_metadata = account.program_account_metadata();
-                            let program_account_data = account.program_account_data();
-                            token.set_metadata(program_account_metadata.clone());
-                            token.set_data(program_account_data.clone());
-                            self.insert_program(&token.program_id(), token.clone());
-                            return Ok(token);
-                        } else {
-                            self.insert_program(&token.program_id(), token.clone());
-                            return Ok(token);
-                        }
+                        let token =
+                            self.token_or_inherited(&transaction.program_id(), program_account);
+                        token.credit(&transaction.value())?;
+                        return Ok(token.clone());
                     }
                 }
             }
@@ -591,52 +1227,15 @@ impl Account {
         token_ids: &Vec<crate::U256>,
         program_account: Option<&Account>,
     ) -> AccountResult<Token> {
-        if let Some(entry) = self.programs.get_mut(token_address) {
-            if let Some(amt) = amount {
-                entry.credit(amt)?;
-            }
-
-            if !token_ids.is_empty() {
-                entry.add_token_ids(token_ids)?;
-            }
-            Ok(entry.clone())
-        } else {
-            let token_metadata = if let Some(program_account) = program_account {
-                program_account.program_account_metadata().clone()
-            } else {
-                Metadata::new()
-            };
-
-            let token_data = if let Some(program_account) = program_account {
-                program_account.program_account_data().clone()
-            } else {
-                ArbitraryData::new()
-            };
-
-            let mut token = TokenBuilder::default()
-                .program_id(*token_address)
-                .owner_id(self.owner_address)
-                .balance(crate::U256::from(0))
-                .token_ids(vec![])
-                .metadata(token_metadata.clone())
-                .data(token_data.clone())
-                .approvals(BTreeMap::new())
-                .allowance(BTreeMap::new())
-                .status(crate::Status::Free)
-                .build()
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-            if let Some(amt) = amount {
-                token.credit(amt)?;
-            }
-
-            if !token_ids.is_empty() {
-                token.add_token_ids(token_ids)?;
-            }
-            self.programs.insert(token.program_id(), token.clone());
+        let token = self.token_or_inherited(token_address, program_account);
+        if let Some(amt) = amount {
+            token.credit(amt)?;
+        }
 
-            Ok(token)
+        if !token_ids.is_empty() {
+            token.add_token_ids(token_ids)?;
         }
+        Ok(token.clone())
     }
 
     pub fn apply_transfer_from_instruction(
@@ -670,7 +1269,9 @@ impl Account {
             if !token_ids.is_empty() {
                 entry.remove_token_ids(token_ids)?;
             }
-            return Ok(entry.clone());
+            let token = entry.clone();
+            self.remove_token_if_empty(token_address);
+            return Ok(token);
         }
 
         Err(Box::new(std::io::Error::new(
@@ -695,7 +1296,9 @@ impl Account {
                 entry.remove_token_ids(token_ids)?;
             }
 
-            return Ok(entry.clone());
+            let token = entry.clone();
+            self.remove_token_if_empty(token_address);
+            return Ok(token);
         }
 
         Err(Box::new(std::io::Error::new(
@@ -711,90 +1314,32 @@ impl Account {
         token_ids: &Vec<crate::U256>,
         token_updates: &Vec<TokenUpdateField>,
         program_account: &Account,
+        quota: &StorageQuota,
     ) -> AccountResult<Token> {
-        let token_owner = {
-            if let AccountType::Program(program_account_address) = self.account_type() {
-                tracing::warn!(
-                    "applying distribution to program acocunt: {}",
-                    &program_account_address.to_full_string()
-                );
-                program_account_address
-            } else {
-                self.owner_address()
-            }
-        };
-
-        if let Some(token) = self.programs.get_mut(program_id) {
-            if let Some(amt) = amount {
-                tracing::warn!("applying {} to {}", &amt, &token_owner);
-                token.credit(amt)?;
-            }
-
-            if !token_ids.is_empty() {
-                token.add_token_ids(token_ids)?;
-            }
-
-            for update in token_updates {
-                tracing::info!("Applying token update: {:?}", &update);
-                token.apply_token_update_field_values(update.value())?;
-            }
-
-            Ok(token.clone())
-        } else {
-            tracing::info!("creating token for token distribution");
-            let token_owner = {
-                if let AccountType::Program(program_account_address) = self.account_type() {
-                    program_account_address
-                } else {
-                    self.owner_address()
-                }
-            };
-
-            let token_metadata = program_account.program_account_metadata();
-            let token_data = program_account.program_account_data();
-            let mut token = TokenBuilder::default()
-                .program_id(*program_id)
-                .owner_id(token_owner)
-                .balance(crate::U256::from(0))
-                .token_ids(vec![])
-                .metadata(token_metadata.clone())
-                .data(token_data.clone())
-                .approvals(BTreeMap::new())
-                .allowance(BTreeMap::new())
-                .status(Status::Free)
-                .build()
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-            if let Some(amt) = amount {
-                tracing::warn!("applying {} to {}", &amt, &token_owner);
-                tracing::warn!("applying credits to token: {:?}", &token);
-                token.credit(amt)?;
-                tracing::warn!("applied credits from token distribution");
-            }
-
-            if !token_ids.is_empty() {
-                token.add_token_ids(token_ids)?;
-                tracing::warn!("applied token ids from token distribution");
-            }
-
+        let token_owner = self.token_owner();
+        if let AccountType::Program(program_account_address) = self.account_type() {
             tracing::warn!(
-                "token distribution includes token updates: {:?}",
-                &token_updates
+                "applying distribution to program acocunt: {}",
+                &program_account_address.to_full_string()
             );
-            for update in token_updates {
-                tracing::warn!("Applying token update: {:?}", &update);
-                token.apply_token_update_field_values(update.value())?;
-            }
+        }
 
-            tracing::warn!(
-                "inserting token: {} into account {}",
-                token.program_id(),
-                token_owner
-            );
-            self.programs.insert(token.program_id(), token.clone());
+        let token = self.token_or_inherited(program_id, Some(program_account));
+        if let Some(amt) = amount {
+            tracing::warn!("applying {} to {}", &amt, &token_owner);
+            token.credit(amt)?;
+        }
+
+        if !token_ids.is_empty() {
+            token.add_token_ids(token_ids)?;
+        }
 
-            Ok(token.clone())
+        for update in token_updates {
+            tracing::info!("Applying token update: {:?}", &update);
+            token.apply_token_update_field_values(update.value(), quota)?;
         }
+
+        Ok(token.clone())
     }
 
     pub fn apply_token_update(
@@ -802,51 +1347,20 @@ impl Account {
         program_id: &Address,
         updates: &Vec<TokenUpdateField>,
         program_account: &Account,
+        quota: &StorageQuota,
     ) -> AccountResult<Token> {
-        let owner_address = {
-            if let AccountType::Program(program_account_address) = self.account_type() {
-                program_account_address
-            } else {
-                self.owner_address()
-            }
-        };
-
-        if let Some(token) = self.programs.get_mut(program_id) {
-            for update in updates {
-                tracing::warn!("token data before update {:?}", token.data());
-                token.apply_token_update_field_values(update.value())?;
-                tracing::warn!("token data after update {:?}", token.data());
-            }
-            Ok(token.clone())
-        } else {
-            let token_metadata = program_account.program_account_metadata();
-            let token_data = program_account.program_account_data();
-            let mut token = TokenBuilder::default()
-                .program_id(*program_id)
-                .owner_id(owner_address)
-                .balance(crate::U256::from(0))
-                .token_ids(vec![])
-                .approvals(BTreeMap::new())
-                .allowance(BTreeMap::new())
-                .data(token_data.clone())
-                .metadata(token_metadata.clone())
-                .status(crate::Status::Free)
-                .build()
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-            for update in updates {
-                tracing::warn!(
-                    "applying {:?} to account: {}",
-                    &update,
-                    &owner_address.to_full_string()
-                );
-                token.apply_token_update_field_values(update.value())?;
-                tracing::warn!("token data after applying update: {:?}", token.data());
-            }
-
-            self.programs.insert(*program_id, token.clone());
-            Ok(token)
+        let token_owner = self.token_owner();
+        let token = self.token_or_inherited(program_id, Some(program_account));
+        for update in updates {
+            tracing::warn!(
+                "applying {:?} to account: {}",
+                &update,
+                &token_owner.to_full_string()
+            );
+            token.apply_token_update_field_values(update.value(), quota)?;
+            tracing::warn!("token data after applying update: {:?}", token.data());
         }
+        Ok(token.clone())
     }
 
     fn apply_program_update_field_values(
@@ -928,7 +1442,7 @@ impl Account {
 
     pub fn validate_program_id(&self, program_id: &Address) -> AccountResult<()> {
         tracing::warn!("attempting to validate program_id");
-        if let Some(_token) = self.programs.get(program_id) {
+        if self.token(program_id).is_some() {
             return Ok(());
         }
 
@@ -943,7 +1457,7 @@ impl Account {
 
     pub fn validate_balance(&self, program_id: &Address, amount: crate::U256) -> AccountResult<()> {
         tracing::warn!("attempting to validate balance");
-        if let Some(token) = self.programs.get(program_id) {
+        if let Some(token) = self.token(program_id) {
             tracing::warn!("token.balance() {} >= {} amount", &token.balance(), &amount);
             if token.balance() >= amount {
                 return Ok(());
@@ -964,12 +1478,33 @@ impl Account {
         )))
     }
 
+    /// Like [`Account::validate_balance`], less what the pending debits of
+    /// the account will take, so sends admitted but not yet applied cannot
+    /// together spend more than it holds.
+    pub fn validate_spendable(
+        &self,
+        program_id: &Address,
+        amount: crate::U256,
+    ) -> AccountResult<()> {
+        self.validate_balance(program_id, amount)?;
+        let pending = self.pending_debit(program_id);
+        if self.spendable(program_id) < amount {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!(
+                    "account balance insufficient, {pending} of it is held by pending transactions"
+                ),
+            )));
+        }
+        Ok(())
+    }
+
     pub fn validate_token_ownership(
         &self,
         program_id: &Address,
         token_ids: &Vec<crate::U256>,
     ) -> AccountResult<()> {
-        if let Some(token) = self.programs.get(program_id) {
+        if let Some(token) = self.token(program_id) {
             for nft in token_ids {
                 if !token.token_ids().contains(nft) {
                     return Err(Box::new(std::io::Error::new(
@@ -997,7 +1532,7 @@ impl Account {
         amount: &crate::U256,
     ) -> AccountResult<()> {
         tracing::warn!("attempting to validate an approved spend");
-        if let Some(token) = self.programs.get(program_id) {
+        if let Some(token) = self.token(program_id) {
             tracing::warn!("found token: {}", &program_id);
             if let Some(entry) = token.allowance().get(spender) {
                 if entry > amount {
@@ -1048,7 +1583,7 @@ impl Account {
         spender: &Address,
         token_ids: &[crate::U256],
     ) -> AccountResult<()> {
-        if let Some(token) = self.programs.get(program_id) {
+        if let Some(token) = self.token(program_id) {
             if let Some(entry) = token.approvals().get(spender) {
                 if entry.is_empty() {
                     return Err(Box::new(std::io::Error::new(
@@ -1079,35 +1614,59 @@ impl Account {
         )))
     }
 
+    /// Checks `nonce` is one the account has not used, however far ahead.
     pub fn validate_nonce(&self, nonce: crate::U256) -> AccountResult<()> {
         tracing::info!("checking nonce: {nonce} > {}", self.nonce);
-        if self.nonce == crate::U256::from(0) && nonce == crate::U256::from(0) {
-            return Ok(());
+        self.accept_nonce_within(nonce, u64::MAX)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)
+    }
+
+    /// The nonce the account's next transaction is sent at, the highest
+    /// there is once the account has used it.
+    pub fn expected_nonce(&self) -> crate::U256 {
+        self.nonce.saturating_add(crate::U256::from(1))
+    }
+
+    /// Checks a transaction at `nonce` is the next from the account.
+    pub fn accept_nonce(&self, nonce: crate::U256) -> Result<(), NonceError> {
+        self.accept_nonce_within(nonce, 0)
+    }
+
+    /// Checks a transaction at `nonce` is the next from the account, or no
+    /// more than `window` past it. Nonces are used one after the other, so
+    /// one below the next has been used, and once the account has used the
+    /// highest there is it takes none.
+    pub fn accept_nonce_within(&self, nonce: crate::U256, window: u64) -> Result<(), NonceError> {
+        if self.nonce == crate::U256::MAX {
+            return Err(NonceError::Exhausted);
         }
-        if nonce > self.nonce {
-            return Ok(());
+        let next = self.expected_nonce();
+        if self.applied.used(nonce) {
+            return Err(NonceError::Replayed { nonce, next });
+        }
+        if nonce < next {
+            return Err(NonceError::TooLow { nonce, next });
+        }
+        let max = next.saturating_add(crate::U256::from(window));
+        if nonce > max {
+            return Err(NonceError::TooHigh { nonce, max });
         }
+        Ok(())
+    }
 
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "unable to validate nonce",
-        )))
+    pub fn set_nonce(&mut self, nonce: crate::U256) {
+        self.nonce = nonce;
     }
 
+    /// Takes the account to its next nonce, and no further than the highest.
     pub fn increment_nonce(&mut self) {
-        self.nonce += crate::U256::from(1);
+        self.nonce = self.expected_nonce();
     }
 }
 
 impl Display for Address {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let hex_str: String = self.encode_hex();
-        write!(
-            f,
-            "0x{}...{}",
-            &hex_str[0..4],
-            &hex_str[hex_str.len() - 4..]
-        )
+        f.write_str(&self.to_checksum_string())
     }
 }
 
@@ -1123,33 +1682,17 @@ impl From<&[u8; 20]> for Address {
     }
 }
 
+/// Parses an address as `from_hex` does, though the `0x` may be left off,
+/// and `0` and `1` stand for the ETH and VERSE addresses.
 impl FromStr for Address {
-    type Err = FromHexError;
+    type Err = AddressError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let hex_str = if let Some(v) = s.strip_prefix("0x") {
-            v
-        } else {
-            s
-        };
-
-        if hex_str == "0" {
-            return Ok(Address::new([0u8; 20]));
+        let hex_str = s.strip_prefix("0x").unwrap_or(s);
+        match hex_str {
+            "0" => Ok(Address::eth_addr()),
+            "1" => Ok(Address::verse_addr()),
+            digits => Address::from_digits(digits),
         }
-
-        if hex_str == "1" {
-            let mut inner: [u8; 20] = [0; 20];
-            inner[19] = 1;
-            return Ok(Address::new(inner));
-        }
-
-        let decoded = hex::decode(hex_str)?;
-        if decoded.len() != 20 {
-            return Err(FromHexError::InvalidStringLength);
-        }
-
-        let mut inner: [u8; 20] = [0; 20];
-        inner.copy_from_slice(&decoded);
-        Ok(Address::new(inner))
     }
 }
 
@@ -1232,3 +1775,400 @@ impl From<[u8; 32]> for Address {
         Address(address)
     }
 }
+
+#[cfg(test)]
+mod account_tests {
+    use super::*;
+    use crate::Status;
+
+    /// The mixed-case vectors of EIP-55.
+    const CHECKSUMMED: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    /// An account holding `balance` of the token of `[3; 20]`.
+    fn holder(balance: u64) -> Account {
+        let mut account = Account::new(Address::new([1; 20]));
+        *account
+            .token_mut_or_default(&Address::new([3; 20]))
+            .balance_mut() = crate::U256::from(balance);
+        account
+    }
+
+    fn send(value: u64, nonce: u64) -> Transaction {
+        crate::TransactionBuilder::default()
+            .transaction_type(crate::TransactionType::Send(crate::U256::from(nonce)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([3; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(crate::U256::from(value))
+            .nonce(crate::U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn pending_debits_hold_what_sends_will_take() {
+        let program_id = Address::new([3; 20]);
+        let value = |value: u64| crate::U256::from(value);
+        let mut account = holder(10);
+        let first = send(6, 1);
+        account.validate_spendable(&program_id, value(6)).unwrap();
+        account.reserve_debit(&first);
+        assert_eq!(account.spendable(&program_id), value(4));
+
+        // The balance covers a second send of as much, but not on top of
+        // the first.
+        account.validate_balance(&program_id, value(6)).unwrap();
+        assert!(account.validate_spendable(&program_id, value(6)).is_err());
+        account.validate_spendable(&program_id, value(4)).unwrap();
+
+        // What is held is no part of the account's state.
+        assert_eq!(account, holder(10));
+        assert_eq!(
+            bincode::serialize(&account).unwrap(),
+            bincode::serialize(&holder(10)).unwrap()
+        );
+
+        // Turned away, the first releases what it held.
+        assert!(account.release_debit(&first.hash_string()));
+        assert!(!account.release_debit(&first.hash_string()));
+        account.validate_spendable(&program_id, value(6)).unwrap();
+
+        // A hold outlasts the account being written again, until the
+        // account reaches the nonce of its send.
+        account.reserve_debit(&first);
+        let mut written = holder(10);
+        written.keep_pending_debits(&account);
+        assert_eq!(written.pending_debit(&program_id), value(6));
+        let mut applied = holder(4);
+        applied.increment_nonce();
+        applied.keep_pending_debits(&written);
+        assert!(applied.pending_debits().inner().is_empty());
+        applied.validate_spendable(&program_id, value(4)).unwrap();
+    }
+
+    #[test]
+    fn sends_are_applied_once_and_in_order() {
+        let program_id = Address::new([3; 20]);
+        let value = |value: u64| crate::U256::from(value);
+        let mut account = holder(100);
+        let first = send(4, 1);
+        let token = account.apply_send_transaction(first.clone(), None).unwrap();
+        assert_eq!(token.balance(), value(96));
+        assert_eq!(account.nonce(), value(1));
+
+        // Applied again, it answers as it did and takes nothing more.
+        assert_eq!(account.apply_send_transaction(first, None).unwrap(), token);
+        assert_eq!(account.balance(&program_id), value(96));
+        assert_eq!(account.nonce(), value(1));
+
+        // Sends behind or ahead of the account are turned away untouched,
+        // saying where it is, and are applied once sent again from there.
+        for nonce in [1, 3] {
+            let e = account
+                .apply_send_transaction(send(1, nonce), None)
+                .unwrap_err();
+            assert_eq!(
+                e.downcast_ref::<ApplyError>(),
+                Some(&ApplyError::OutOfOrder {
+                    expected: value(2),
+                    nonce: value(nonce),
+                })
+            );
+        }
+        assert_eq!(account.balance(&program_id), value(96));
+        account.apply_send_transaction(send(1, 2), None).unwrap();
+        assert_eq!(account.balance(&program_id), value(95));
+        assert_eq!(account.nonce(), value(2));
+
+        // Only the most recent are remembered, and what they applied is no
+        // part of the account's state.
+        for nonce in 3..3 + RECENTLY_APPLIED as u64 {
+            account
+                .apply_send_transaction(send(1, nonce), None)
+                .unwrap();
+        }
+        assert!(account.applied().get(&send(4, 1).hash_string()).is_none());
+        assert!(account.apply_send_transaction(send(4, 1), None).is_err());
+        let mut written = holder(95 - RECENTLY_APPLIED as u64);
+        written.set_nonce(account.nonce());
+        assert_eq!(account, written);
+    }
+
+    #[test]
+    fn nonces_are_taken_in_turn_within_a_window_until_none_are_left() {
+        let value = |value: u64| crate::U256::from(value);
+        let mut account = holder(100);
+        assert_eq!(account.expected_nonce(), value(1));
+        assert_eq!(
+            account.accept_nonce(value(0)),
+            Err(NonceError::TooLow {
+                nonce: value(0),
+                next: value(1),
+            })
+        );
+        assert!(account.accept_nonce(value(1)).is_ok());
+        assert_eq!(
+            account.accept_nonce(value(2)),
+            Err(NonceError::TooHigh {
+                nonce: value(2),
+                max: value(1),
+            })
+        );
+        assert!(account.accept_nonce_within(value(4), 3).is_ok());
+        assert!(account.accept_nonce_within(value(5), 3).is_err());
+
+        // A nonce a send was applied at is known to have been, and any below
+        // it to have been used.
+        account.apply_send_transaction(send(1, 1), None).unwrap();
+        assert_eq!(
+            account.accept_nonce(value(1)),
+            Err(NonceError::Replayed {
+                nonce: value(1),
+                next: value(2),
+            })
+        );
+        assert_eq!(
+            account.accept_nonce(value(0)),
+            Err(NonceError::TooLow {
+                nonce: value(0),
+                next: value(2),
+            })
+        );
+
+        // The last nonce there is is taken once, and the account stays at it.
+        account.set_nonce(crate::U256::MAX - 1);
+        assert!(account.accept_nonce(crate::U256::MAX).is_ok());
+        account.increment_nonce();
+        account.increment_nonce();
+        assert_eq!(account.nonce(), crate::U256::MAX);
+        assert_eq!(account.expected_nonce(), crate::U256::MAX);
+        assert_eq!(
+            account.accept_nonce(crate::U256::MAX),
+            Err(NonceError::Exhausted)
+        );
+        assert!(account.validate_nonce(crate::U256::MAX).is_err());
+    }
+
+    #[test]
+    fn accounts_are_only_built_holding_to_their_invariants() {
+        let (owner, program_id) = (Address::new([1; 20]), Address::new([3; 20]));
+        let token = |program_id: Address, owner_id: Address| Token::empty(program_id, owner_id);
+        let builder = |account_type: AccountType, tokens: Vec<(Address, Token)>, nonce: u64| {
+            let mut builder = AccountBuilder::default();
+            builder
+                .account_type(account_type)
+                .program_namespace(None)
+                .owner_address(owner)
+                .programs(tokens.into_iter().collect())
+                .nonce(crate::U256::from(nonce))
+                .program_account_data(ArbitraryData::new())
+                .program_account_metadata(Metadata::new())
+                .program_account_linked_programs(BTreeSet::new());
+            builder
+        };
+
+        let held = vec![(program_id, token(program_id, owner))];
+        let account = builder(AccountType::User, held, 4).try_build().unwrap();
+        assert_eq!(account.nonce(), crate::U256::from(4));
+        assert_eq!(Account::new(owner).check_invariants(), Ok(()));
+        assert_eq!(
+            Account::new_program(program_id, owner).check_invariants(),
+            Ok(())
+        );
+
+        let misfiled = vec![(Address::new([4; 20]), token(program_id, owner))];
+        assert_eq!(
+            builder(AccountType::User, misfiled, 0).try_build(),
+            Err(AccountInvariantError::ProgramMismatch {
+                key: Address::new([4; 20]),
+                program_id,
+            })
+        );
+
+        let someone_elses = vec![(program_id, token(program_id, Address::new([2; 20])))];
+        assert_eq!(
+            builder(AccountType::User, someone_elses, 0).try_build(),
+            Err(AccountInvariantError::OwnerMismatch {
+                program_id,
+                owner_id: Address::new([2; 20]),
+                account: owner,
+            })
+        );
+
+        // A program account holds its tokens as the program, not its owner.
+        let program = AccountType::Program(Address::new([5; 20]));
+        let held = vec![(program_id, token(program_id, Address::new([5; 20])))];
+        assert!(builder(program.clone(), held, 0).try_build().is_ok());
+        let as_owner = vec![(program_id, token(program_id, owner))];
+        assert!(matches!(
+            builder(program.clone(), as_owner, 0).try_build(),
+            Err(AccountInvariantError::OwnerMismatch { .. })
+        ));
+        assert_eq!(
+            builder(program, Vec::new(), 1).try_build(),
+            Err(AccountInvariantError::ProgramNonce {
+                program_address: Address::new([5; 20]),
+                nonce: crate::U256::from(1),
+            })
+        );
+
+        let mut incomplete = AccountBuilder::default();
+        incomplete.owner_address(owner);
+        assert!(matches!(
+            incomplete.try_build(),
+            Err(AccountInvariantError::Incomplete(_))
+        ));
+    }
+
+    #[test]
+    fn tokens_are_created_on_first_credit_and_dropped_once_emptied() {
+        let program_id = Address::new([4; 20]);
+        let value = |value: u64| crate::U256::from(value);
+        let mut account = holder(10);
+        assert!(account.token(&program_id).is_none());
+
+        // Created by the first credit, with what the program account has.
+        let mut program_account = Account::new_program(program_id, Address::new([9; 20]));
+        program_account
+            .program_account_metadat_mut()
+            .insert("symbol".to_string(), "TKN".to_string());
+        let token = account
+            .apply_transfer_to_instruction(
+                &program_id,
+                &Some(value(5)),
+                &vec![],
+                Some(&program_account),
+            )
+            .unwrap();
+        assert_eq!(token.program_id(), program_id);
+        assert_eq!(token.owner_id(), account.owner_address());
+        assert_eq!(token.balance(), value(5));
+        assert_eq!(token.status(), Status::Free);
+        assert_eq!(token.metadata().get("symbol"), Some(&"TKN".to_string()));
+        let token = account
+            .apply_transfer_to_instruction(&program_id, &Some(value(3)), &vec![], None)
+            .unwrap();
+        assert_eq!(token.balance(), value(8));
+        assert_eq!(token.metadata().get("symbol"), Some(&"TKN".to_string()));
+        assert_eq!(
+            account.tokens_iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![Address::new([3; 20]), program_id]
+        );
+
+        // A program account holds its tokens as its program.
+        assert_eq!(
+            program_account.token_mut_or_default(&program_id).owner_id(),
+            program_id
+        );
+
+        // Kept while it holds anything, and dropped once it holds nothing.
+        account
+            .apply_transfer_from_instruction(&program_id, &Some(value(3)), &[])
+            .unwrap();
+        assert_eq!(account.balance(&program_id), value(5));
+        let token = account
+            .apply_burn_instruction(&program_id, &Some(value(5)), &[])
+            .unwrap();
+        assert!(token.is_empty());
+        assert!(account.token(&program_id).is_none());
+        assert_eq!(account.balance(&program_id), value(0));
+
+        account
+            .token_mut_or_default(&program_id)
+            .data_mut()
+            .insert("k".to_string(), "v".to_string());
+        assert!(!account.remove_token_if_empty(&program_id));
+        assert!(account.token(&program_id).is_some());
+        account
+            .token_mut_or_default(&program_id)
+            .data_mut()
+            .remove("k");
+        assert!(account.remove_token_if_empty(&program_id));
+        assert!(!account.remove_token_if_empty(&program_id));
+    }
+
+    #[test]
+    fn addresses_are_shown_checksummed() {
+        for checksummed in CHECKSUMMED {
+            let address = Address::from_hex(&checksummed.to_lowercase()).unwrap();
+            assert_eq!(address.to_string(), checksummed);
+            assert_eq!(format!("{address:?}"), checksummed);
+            assert_eq!(address.to_full_string(), checksummed.to_lowercase());
+            assert_eq!(Address::from_hex(checksummed), Ok(address));
+            assert_eq!(checksummed.parse::<Address>(), Ok(address));
+        }
+        let upper = format!("0x{}", CHECKSUMMED[0][2..].to_uppercase());
+        assert!(Address::from_hex(&upper).is_ok());
+
+        let h160 = ethereum_types::H160([7; 20]);
+        assert_eq!(ethereum_types::H160::from(Address::from(h160)), h160);
+    }
+
+    #[test]
+    fn malformed_addresses_are_refused() {
+        let digits = &CHECKSUMMED[0][2..];
+        assert_eq!(Address::from_hex(digits), Err(AddressError::Prefix));
+        assert_eq!(
+            Address::from_hex(&CHECKSUMMED[0][..40]),
+            Err(AddressError::Length(38))
+        );
+        assert_eq!(
+            Address::from_hex(&format!("{}00", CHECKSUMMED[0])),
+            Err(AddressError::Length(42))
+        );
+        assert_eq!(
+            Address::from_hex(&format!("0x{}", "zz".repeat(20))),
+            Err(AddressError::NotHex)
+        );
+        let miscased = CHECKSUMMED[0].replace('a', "A");
+        assert_eq!(
+            Address::from_hex(&miscased),
+            Err(AddressError::Checksum(CHECKSUMMED[0].to_string()))
+        );
+        assert_eq!(
+            digits.parse::<Address>().unwrap().to_string(),
+            CHECKSUMMED[0]
+        );
+        assert_eq!("0".parse::<Address>(), Ok(Address::eth_addr()));
+        assert_eq!("0x1".parse::<Address>(), Ok(Address::verse_addr()));
+        assert!("0x12".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn addresses_serialize_as_hex_or_as_bytes() {
+        let address = Address::from_hex(CHECKSUMMED[1]).unwrap();
+
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, format!("\"{}\"", CHECKSUMMED[1]));
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+        let lowercase = format!("\"{}\"", address.to_full_string());
+        assert_eq!(
+            serde_json::from_str::<Address>(&lowercase).unwrap(),
+            address
+        );
+        let programs = BTreeMap::from([(address, 1u8)]);
+        let json = serde_json::to_string(&programs).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BTreeMap<Address, u8>>(&json).unwrap(),
+            programs
+        );
+
+        let bytes = bincode::serialize(&address).unwrap();
+        assert_eq!(bytes.len(), 8 + 20);
+        assert_eq!(&bytes[8..], &address.inner());
+        assert_eq!(bincode::deserialize::<Address>(&bytes).unwrap(), address);
+        // As bincode held them before, as hex strings.
+        let legacy = bincode::serialize(&address.to_full_string()).unwrap();
+        assert_eq!(bincode::deserialize::<Address>(&legacy).unwrap(), address);
+    }
+}
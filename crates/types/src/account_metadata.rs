@@ -0,0 +1,210 @@
+use std::collections::BTreeSet;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Address, Transaction};
+
+/// The most bytes of a display name.
+pub const MAX_DISPLAY_NAME_BYTES: usize = 64;
+/// The most bytes of an avatar URI.
+pub const MAX_AVATAR_URI_BYTES: usize = 512;
+/// The most contact programs an account names.
+pub const MAX_CONTACT_PROGRAMS: usize = 16;
+
+/// What the owner of an account says of it, as opposed to any token it
+/// holds: a profile others see it by. It is set whole by a
+/// [`TransactionType::SetAccountMetadata`] the owner signs, carrying it as
+/// its inputs.
+///
+/// [`TransactionType::SetAccountMetadata`]: crate::TransactionType::SetAccountMetadata
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMetadata {
+    /// The name the account goes by, if it gives one.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Where the image the account is shown with is, if it has one.
+    #[serde(default)]
+    pub avatar_uri: Option<String>,
+    /// The programs the account may be reached through.
+    #[serde(default)]
+    pub contact_program_ids: BTreeSet<Address>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum AccountMetadataError {
+    #[error("{field} is {bytes} bytes long, over the {limit} allowed")]
+    TooLong {
+        field: &'static str,
+        bytes: usize,
+        limit: usize,
+    },
+
+    #[error("{count} contact programs are over the {limit} allowed")]
+    TooManyContactPrograms { count: usize, limit: usize },
+
+    #[error("the inputs of an account metadata transaction are not account metadata: {0}")]
+    InvalidInputs(String),
+}
+
+impl AccountMetadata {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Checks the metadata is within the limits an account is held to.
+    pub fn validate(&self) -> Result<(), AccountMetadataError> {
+        let fields = [
+            ("displayName", &self.display_name, MAX_DISPLAY_NAME_BYTES),
+            ("avatarUri", &self.avatar_uri, MAX_AVATAR_URI_BYTES),
+        ];
+        for (field, value, limit) in fields {
+            let bytes = value.as_ref().map_or(0, String::len);
+            if bytes > limit {
+                return Err(AccountMetadataError::TooLong {
+                    field,
+                    bytes,
+                    limit,
+                });
+            }
+        }
+        let count = self.contact_program_ids.len();
+        if count > MAX_CONTACT_PROGRAMS {
+            return Err(AccountMetadataError::TooManyContactPrograms {
+                count,
+                limit: MAX_CONTACT_PROGRAMS,
+            });
+        }
+        Ok(())
+    }
+
+    /// The metadata an account metadata transaction carries in its inputs,
+    /// checked to be within the limits.
+    pub fn of(transaction: &Transaction) -> Result<Self, AccountMetadataError> {
+        let metadata: Self = serde_json::from_str(&transaction.inputs())
+            .map_err(|e| AccountMetadataError::InvalidInputs(e.to_string()))?;
+        metadata.validate()?;
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod account_metadata_tests {
+    use super::*;
+    use crate::{Account, TransactionBuilder, TransactionType, U256};
+
+    fn transaction(inputs: &str) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::SetAccountMetadata(U256::from(1)))
+            .from([1; 20])
+            .to([0; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(inputs.to_string())
+            .value(U256::from(0))
+            .nonce(U256::from(1))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn metadata_is_read_from_the_inputs_within_the_limits() {
+        let inputs = serde_json::json!({
+            "displayName": "alice",
+            "contactProgramIds": [Address::new([9; 20])],
+        });
+        let metadata = AccountMetadata::of(&transaction(&inputs.to_string())).unwrap();
+        assert_eq!(metadata.display_name.as_deref(), Some("alice"));
+        assert_eq!(metadata.avatar_uri, None);
+        assert_eq!(
+            metadata.contact_program_ids,
+            BTreeSet::from([Address::new([9; 20])])
+        );
+        assert!(AccountMetadata::of(&transaction("{}")).unwrap().is_empty());
+
+        let name = "n".repeat(MAX_DISPLAY_NAME_BYTES + 1);
+        assert_eq!(
+            AccountMetadata::of(&transaction(&format!(r#"{{"displayName":"{name}"}}"#))),
+            Err(AccountMetadataError::TooLong {
+                field: "displayName",
+                bytes: MAX_DISPLAY_NAME_BYTES + 1,
+                limit: MAX_DISPLAY_NAME_BYTES,
+            })
+        );
+        let contacts = AccountMetadata {
+            contact_program_ids: (0..=MAX_CONTACT_PROGRAMS as u8)
+                .map(|byte| Address::new([byte; 20]))
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            contacts.validate(),
+            Err(AccountMetadataError::TooManyContactPrograms {
+                count: MAX_CONTACT_PROGRAMS + 1,
+                limit: MAX_CONTACT_PROGRAMS,
+            })
+        );
+        assert!(matches!(
+            AccountMetadata::of(&transaction("alice")),
+            Err(AccountMetadataError::InvalidInputs(_))
+        ));
+    }
+
+    #[test]
+    fn metadata_is_part_of_the_account_and_empty_when_missing() {
+        let mut account = Account::new(Address::new([1; 20]));
+        let before = account.clone();
+        let metadata = AccountMetadata {
+            display_name: Some("alice".to_string()),
+            ..Default::default()
+        };
+        account.set_account_metadata(metadata.clone());
+        assert_eq!(account.account_metadata(), &metadata);
+        assert_ne!(account, before);
+        assert_ne!(
+            bincode::serialize(&account).unwrap(),
+            bincode::serialize(&before).unwrap()
+        );
+
+        // Accounts written before there was metadata read back with none.
+        let mut json = serde_json::to_value(&before).unwrap();
+        json.as_object_mut()
+            .unwrap()
+            .remove("accountMetadata")
+            .unwrap();
+        let read: Account = serde_json::from_value(json).unwrap();
+        assert!(read.account_metadata().is_empty());
+        assert_eq!(read, before);
+
+        // bincode cannot tell the field is missing, so they are read in the
+        // layout they were written in.
+        let bytes = bincode::serialize(&before).unwrap();
+        let missing = bincode::serialize(&AccountMetadata::default())
+            .unwrap()
+            .len();
+        let written = &bytes[..bytes.len() - missing];
+        assert!(bincode::deserialize::<Account>(written).is_err());
+        assert_eq!(Account::from_legacy_bincode(written), Some(before.clone()));
+        assert_eq!(Account::from_legacy_bincode(&bytes), None);
+    }
+}
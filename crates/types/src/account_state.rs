@@ -0,0 +1,301 @@
+//! The shape an account takes over RPC.
+//!
+//! Numbers and addresses are 0x-prefixed hex. The values kept in account and
+//! token data and metadata are free-form strings, so they are hex-encoded from
+//! their UTF-8 bytes and arrive unchanged whatever they hold. An address with
+//! no account is answered as an empty one with `found` unset, so it can be
+//! told apart from an account that exists but holds nothing.
+use std::collections::{BTreeMap, BTreeSet};
+use std::string::FromUtf8Error;
+
+use hex::FromHexError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    Account, AccountBuilder, AccountMetadata, AccountType, Address, AddressOrNamespace,
+    ArbitraryData, Delegation, Metadata, PendingDebit, PendingDebits, Status, Token, TokenBuilder,
+    U256,
+};
+
+#[derive(Debug, Error)]
+pub enum AccountStateError {
+    #[error("{0}")]
+    Hex(#[from] FromHexError),
+
+    #[error("{0}")]
+    Utf8(#[from] FromUtf8Error),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenState {
+    pub program_id: Address,
+    pub owner_id: Address,
+    pub balance: U256,
+    pub token_ids: Vec<U256>,
+    pub allowance: BTreeMap<Address, U256>,
+    pub approvals: BTreeMap<Address, Vec<U256>>,
+    pub metadata: BTreeMap<String, String>,
+    pub data: BTreeMap<String, String>,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountState {
+    /// Whether there is an account at `address`.
+    pub found: bool,
+    pub address: Address,
+    pub account_type: AccountType,
+    pub program_namespace: Option<AddressOrNamespace>,
+    pub nonce: U256,
+    /// Tokens held, by the program that issued them.
+    pub programs: BTreeMap<Address, TokenState>,
+    pub data: BTreeMap<String, String>,
+    pub metadata: BTreeMap<String, String>,
+    pub linked_programs: BTreeSet<AddressOrNamespace>,
+    /// The program authorizing the account's transactions, if it names one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_program: Option<Address>,
+    /// What the owner delegated to other keys, by delegate.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub delegations: BTreeMap<Address, Delegation>,
+    /// What the owner says of the account: its display name, avatar and
+    /// the programs it may be reached through.
+    #[serde(default, skip_serializing_if = "AccountMetadata::is_empty")]
+    pub account_metadata: AccountMetadata,
+    /// What sends admitted from the account and not yet applied or turned
+    /// away will take from it, by transaction hash.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pending_debits: BTreeMap<String, PendingDebit>,
+}
+
+impl AccountState {
+    /// The state of `account`, found at `address`, or of an empty account if
+    /// there is none.
+    pub fn new(address: Address, account: Option<&Account>) -> Self {
+        let Some(account) = account else {
+            return Self {
+                found: false,
+                address,
+                account_type: AccountType::User,
+                program_namespace: None,
+                nonce: U256::from(0),
+                programs: BTreeMap::new(),
+                data: BTreeMap::new(),
+                metadata: BTreeMap::new(),
+                linked_programs: BTreeSet::new(),
+                auth_program: None,
+                delegations: BTreeMap::new(),
+                account_metadata: AccountMetadata::default(),
+                pending_debits: BTreeMap::new(),
+            };
+        };
+        Self {
+            found: true,
+            address,
+            account_type: account.account_type(),
+            program_namespace: account.program_namespace(),
+            nonce: account.nonce(),
+            programs: account
+                .tokens_iter()
+                .map(|(program_id, token)| (*program_id, TokenState::from(token)))
+                .collect(),
+            data: encode_values(account.program_account_data().inner()),
+            metadata: encode_values(account.program_account_metadata().inner()),
+            linked_programs: account.program_account_linked_programs().clone(),
+            auth_program: account.auth_program(),
+            delegations: account.delegations().clone(),
+            account_metadata: account.account_metadata().clone(),
+            pending_debits: account.pending_debits().inner().clone(),
+        }
+    }
+}
+
+impl From<&Token> for TokenState {
+    fn from(token: &Token) -> Self {
+        Self {
+            program_id: token.program_id(),
+            owner_id: token.owner_id(),
+            balance: token.balance(),
+            token_ids: token.token_ids(),
+            allowance: token.allowance(),
+            approvals: token.approvals(),
+            metadata: encode_values(token.metadata().inner()),
+            data: encode_values(token.data().inner()),
+            status: token.status(),
+        }
+    }
+}
+
+impl TryFrom<TokenState> for Token {
+    type Error = AccountStateError;
+
+    fn try_from(state: TokenState) -> Result<Self, Self::Error> {
+        let mut metadata = Metadata::new();
+        metadata.extend(decode_values(state.metadata)?);
+        let mut data = ArbitraryData::new();
+        data.extend(decode_values(state.data)?);
+        TokenBuilder::default()
+            .program_id(state.program_id)
+            .owner_id(state.owner_id)
+            .balance(state.balance)
+            .metadata(metadata)
+            .token_ids(state.token_ids)
+            .allowance(state.allowance)
+            .approvals(state.approvals)
+            .data(data)
+            .status(state.status)
+            .build()
+            .map_err(|e| AccountStateError::Custom(e.to_string()))
+    }
+}
+
+impl TryFrom<AccountState> for Account {
+    type Error = AccountStateError;
+
+    fn try_from(state: AccountState) -> Result<Self, Self::Error> {
+        let programs = state
+            .programs
+            .into_iter()
+            .map(|(program_id, token)| Ok((program_id, Token::try_from(token)?)))
+            .collect::<Result<BTreeMap<Address, Token>, AccountStateError>>()?;
+        let mut data = ArbitraryData::new();
+        data.extend(decode_values(state.data)?);
+        let mut metadata = Metadata::new();
+        metadata.extend(decode_values(state.metadata)?);
+        AccountBuilder::default()
+            .account_type(state.account_type)
+            .program_namespace(state.program_namespace)
+            .owner_address(state.address)
+            .programs(programs)
+            .nonce(state.nonce)
+            .program_account_data(data)
+            .program_account_metadata(metadata)
+            .program_account_linked_programs(state.linked_programs)
+            .auth_program(state.auth_program)
+            .delegations(state.delegations)
+            .account_metadata(state.account_metadata)
+            .pending_debits(PendingDebits::from(state.pending_debits))
+            .try_build()
+            .map_err(|e| AccountStateError::Custom(e.to_string()))
+    }
+}
+
+fn encode_values(map: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    map.iter()
+        .map(|(key, value)| (key.clone(), format!("0x{}", hex::encode(value))))
+        .collect()
+}
+
+fn decode_values(
+    map: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, AccountStateError> {
+    map.into_iter()
+        .map(|(key, value)| {
+            let bytes = hex::decode(value.strip_prefix("0x").unwrap_or(&value))?;
+            Ok((key, String::from_utf8(bytes)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod account_state_tests {
+    use super::*;
+    use serde_json::json;
+
+    fn account() -> Account {
+        let mut token_metadata = Metadata::new();
+        token_metadata.insert("symbol".to_string(), "ETH".to_string());
+        let token = TokenBuilder::default()
+            .program_id(Address::new([3; 20]))
+            .owner_id(Address::new([1; 20]))
+            .balance(U256::from(1000))
+            .metadata(token_metadata)
+            .token_ids(vec![U256::from(7)])
+            .allowance(BTreeMap::from([(Address::new([2; 20]), U256::from(5))]))
+            .approvals(BTreeMap::new())
+            .data(ArbitraryData::new())
+            .status(Status::Free)
+            .build()
+            .unwrap();
+        let mut data = ArbitraryData::new();
+        data.insert("greeting".to_string(), "{\"hi\":1}".to_string());
+        AccountBuilder::default()
+            .account_type(AccountType::User)
+            .program_namespace(None)
+            .owner_address(Address::new([1; 20]))
+            .programs(BTreeMap::from([(Address::new([3; 20]), token)]))
+            .nonce(U256::from(2))
+            .program_account_data(data)
+            .program_account_metadata(Metadata::new())
+            .program_account_linked_programs(BTreeSet::new())
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn an_account_serializes_to_its_wire_shape() {
+        let state = AccountState::new(Address::new([1; 20]), Some(&account()));
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            json!({
+                "found": true,
+                "address": "0x0101010101010101010101010101010101010101",
+                "accountType": "user",
+                "programNamespace": null,
+                "nonce": "0x2",
+                "programs": {
+                    "0x0303030303030303030303030303030303030303": {
+                        "programId": "0x0303030303030303030303030303030303030303",
+                        "ownerId": "0x0101010101010101010101010101010101010101",
+                        "balance": "0x3e8",
+                        "tokenIds": ["0x7"],
+                        "allowance": {
+                            "0x0202020202020202020202020202020202020202": "0x5"
+                        },
+                        "approvals": {},
+                        "metadata": { "symbol": "0x455448" },
+                        "data": {},
+                        "status": "free"
+                    }
+                },
+                "data": { "greeting": "0x7b226869223a317d" },
+                "metadata": {},
+                "linkedPrograms": []
+            })
+        );
+    }
+
+    #[test]
+    fn a_missing_account_is_not_found() {
+        let state = AccountState::new(Address::new([9; 20]), None);
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            json!({
+                "found": false,
+                "address": "0x0909090909090909090909090909090909090909",
+                "accountType": "user",
+                "programNamespace": null,
+                "nonce": "0x0",
+                "programs": {},
+                "data": {},
+                "metadata": {},
+                "linkedPrograms": []
+            })
+        );
+    }
+
+    #[test]
+    fn an_account_comes_back_from_its_wire_shape() {
+        let state = AccountState::new(Address::new([1; 20]), Some(&account()));
+        let json = serde_json::to_string(&state).unwrap();
+        let decoded: AccountState = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, state);
+        assert_eq!(Account::try_from(decoded).unwrap(), account());
+    }
+}
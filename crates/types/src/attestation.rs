@@ -0,0 +1,264 @@
+//! Signed evidence that a node produced a specific execution result.
+//!
+//! After a call transaction's outputs are applied, the node can sign a digest
+//! of the transaction hash, the state of every account the transaction
+//! touched before it was applied, the token balances it changed and the hash
+//! of what the program returned. Anyone holding the attestation can check it
+//! was signed by the key it names, and that none of those parts were altered
+//! afterwards.
+use crate::{Account, Address, Outputs, RecoverableSignature, SigError, U256};
+use borsh::{BorshDeserialize, BorshSerialize};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// The state of an account before a transaction was applied. `hash` is
+/// `None` if the account did not exist yet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDigest {
+    pub address: Address,
+    pub hash: Option<String>,
+}
+
+impl AccountDigest {
+    pub fn new(address: Address, account: Option<&Account>) -> Self {
+        Self {
+            address,
+            hash: account.map(account_hash),
+        }
+    }
+}
+
+/// A token balance a transaction changed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenDelta {
+    pub address: Address,
+    pub program_id: Address,
+    pub before: U256,
+    pub after: U256,
+}
+
+#[derive(Debug, Error)]
+pub enum AttestationError {
+    #[error("attestation signature is invalid: {0}")]
+    InvalidSignature(#[from] SigError),
+
+    #[error("attestation claims to be signed by {claimed} but was signed by {recovered}")]
+    WrongSigner {
+        claimed: Address,
+        recovered: Address,
+    },
+
+    #[error("attestation was signed by {0}, which is not a trusted signer")]
+    UntrustedSigner(Address),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionAttestation {
+    pub transaction_hash: String,
+    pub input_accounts: Vec<AccountDigest>,
+    pub token_deltas: Vec<TokenDelta>,
+    /// Hash of the outputs the program returned.
+    pub trace_hash: String,
+    pub signer: Address,
+    pub signature: RecoverableSignature,
+}
+
+/// The signed part of an attestation.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AttestedExecution<'a> {
+    transaction_hash: &'a str,
+    input_accounts: &'a [AccountDigest],
+    token_deltas: &'a [TokenDelta],
+    trace_hash: &'a str,
+    signer: &'a Address,
+}
+
+impl AttestedExecution<'_> {
+    fn digest(&self) -> [u8; 32] {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&Keccak256::digest(bytes));
+        digest
+    }
+}
+
+impl ExecutionAttestation {
+    /// Signs the execution of `transaction_hash` with `secret_key`.
+    pub fn sign(
+        transaction_hash: String,
+        input_accounts: Vec<AccountDigest>,
+        token_deltas: Vec<TokenDelta>,
+        trace_hash: String,
+        secret_key: &SecretKey,
+    ) -> Self {
+        let secp = Secp256k1::signing_only();
+        let signer = Address::from(PublicKey::from_secret_key(&secp, secret_key));
+        let digest = AttestedExecution {
+            transaction_hash: &transaction_hash,
+            input_accounts: &input_accounts,
+            token_deltas: &token_deltas,
+            trace_hash: &trace_hash,
+            signer: &signer,
+        }
+        .digest();
+        let signature = RecoverableSignature::sign(&digest, secret_key);
+
+        Self {
+            transaction_hash,
+            input_accounts,
+            token_deltas,
+            trace_hash,
+            signer,
+            signature,
+        }
+    }
+
+    /// The digest the signature covers.
+    pub fn digest(&self) -> [u8; 32] {
+        AttestedExecution {
+            transaction_hash: &self.transaction_hash,
+            input_accounts: &self.input_accounts,
+            token_deltas: &self.token_deltas,
+            trace_hash: &self.trace_hash,
+            signer: &self.signer,
+        }
+        .digest()
+    }
+
+    /// Checks the attestation was signed by `signer` and has not been
+    /// altered since.
+    pub fn verify(&self) -> Result<(), AttestationError> {
+        self.signature
+            .verify(&self.digest(), &self.signer)
+            .map_err(|e| match e {
+                SigError::WrongSigner {
+                    expected,
+                    recovered,
+                } => AttestationError::WrongSigner {
+                    claimed: expected,
+                    recovered,
+                },
+                e => AttestationError::InvalidSignature(e),
+            })
+    }
+
+    /// Like [`ExecutionAttestation::verify`], and also checks the signer is
+    /// one of `trusted`.
+    pub fn verify_signed_by(&self, trusted: &[Address]) -> Result<(), AttestationError> {
+        self.verify()?;
+        if !trusted.contains(&self.signer) {
+            return Err(AttestationError::UntrustedSigner(self.signer));
+        }
+        Ok(())
+    }
+}
+
+/// Keccak256 of the account as it is persisted.
+pub fn account_hash(account: &Account) -> String {
+    let bytes = bincode::serialize(account).unwrap_or_default();
+    format!("0x{:x}", Keccak256::digest(bytes))
+}
+
+/// Keccak256 of the outputs a program returned.
+pub fn outputs_hash(outputs: &Outputs) -> String {
+    let bytes = serde_json::to_vec(outputs).unwrap_or_default();
+    format!("0x{:x}", Keccak256::digest(bytes))
+}
+
+/// The token balances of the account at `address` that differ between
+/// `before` and `after`, in program id order.
+pub fn token_deltas(
+    address: Address,
+    before: Option<&Account>,
+    after: &Account,
+) -> Vec<TokenDelta> {
+    let mut program_ids: BTreeSet<Address> = after
+        .tokens_iter()
+        .map(|(program_id, _)| *program_id)
+        .collect();
+    if let Some(before) = before {
+        program_ids.extend(before.tokens_iter().map(|(program_id, _)| *program_id));
+    }
+
+    program_ids
+        .into_iter()
+        .filter_map(|program_id| {
+            let previous = before
+                .map(|account| account.balance(&program_id))
+                .unwrap_or_default();
+            let current = after.balance(&program_id);
+            (previous != current).then_some(TokenDelta {
+                address,
+                program_id,
+                before: previous,
+                after: current,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod attestation_tests {
+    use super::*;
+
+    fn attestation(secret_key: &SecretKey) -> ExecutionAttestation {
+        let address = Address::new([7; 20]);
+        ExecutionAttestation::sign(
+            "0xabcdef".to_string(),
+            vec![AccountDigest {
+                address,
+                hash: Some("0x01".to_string()),
+            }],
+            vec![TokenDelta {
+                address,
+                program_id: Address::verse_addr(),
+                before: U256::from(100),
+                after: U256::from(40),
+            }],
+            "0x02".to_string(),
+            secret_key,
+        )
+    }
+
+    #[test]
+    fn attestation_verifies_against_its_signer() {
+        let (secret_key, public_key) =
+            Secp256k1::new().generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let attestation = attestation(&secret_key);
+
+        assert_eq!(attestation.signer, Address::from(public_key));
+        attestation.verify().unwrap();
+        attestation.verify_signed_by(&[attestation.signer]).unwrap();
+        assert!(matches!(
+            attestation.verify_signed_by(&[Address::new([1; 20])]),
+            Err(AttestationError::UntrustedSigner(_))
+        ));
+    }
+
+    #[test]
+    fn tampered_delta_fails_verification() {
+        let (secret_key, _) = Secp256k1::new().generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let mut attestation = attestation(&secret_key);
+        attestation.token_deltas[0].after = U256::from(90);
+
+        assert!(attestation.verify().is_err());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let (secret_key, _) = Secp256k1::new().generate_keypair(&mut secp256k1::rand::rngs::OsRng);
+        let attestation = attestation(&secret_key);
+        let json = serde_json::to_string(&attestation).unwrap();
+        let decoded: ExecutionAttestation = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, attestation);
+        decoded.verify().unwrap();
+    }
+}
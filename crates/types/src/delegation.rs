@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Address, Transaction, TransactionType};
+
+/// Authority the owner of an account hands to another key, a session key,
+/// to sign some of the account's transactions in its place. A transaction
+/// the delegate signs is the account's own, sent with the account's nonces,
+/// and is only taken while it stays within the delegation: a send or a call
+/// of one of `program_ids`, carrying no more than `max_value`, before the
+/// delegation expires.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Delegation {
+    pub delegate: Address,
+    /// The programs whose tokens the delegate may send, or which it may call.
+    pub program_ids: BTreeSet<Address>,
+    /// The most value one transaction the delegate signs may carry.
+    pub max_value: crate::U256,
+    /// The last nonce the delegate may sign, if it is held to one. Written
+    /// even when unset, delegations being stored with their account.
+    #[serde(default)]
+    pub expires_at_nonce: Option<crate::U256>,
+    /// The unix time, in seconds, from which the delegate may sign nothing,
+    /// if it is held to one.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+/// What a delegate transaction does to its sender's delegations, carried as
+/// its inputs.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DelegationOp {
+    /// Delegates to `delegation.delegate`, replacing what was delegated to
+    /// it before, if anything was.
+    Grant(Delegation),
+    /// Takes back what was delegated to the address.
+    Revoke(Address),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum DelegationError {
+    #[error("only sends and calls may be signed by a delegate")]
+    NotDelegable,
+
+    #[error("program {0} is not one of those delegated")]
+    ProgramNotDelegated(Address),
+
+    #[error("value {value} is over the {max_value} delegated")]
+    ValueOverLimit {
+        value: crate::U256,
+        max_value: crate::U256,
+    },
+
+    #[error("nonce {nonce} is past {expires_at_nonce}, the last delegated")]
+    NonceExpired {
+        nonce: crate::U256,
+        expires_at_nonce: crate::U256,
+    },
+
+    #[error("the delegation expired at {expires_at}")]
+    Expired { expires_at: u64 },
+
+    #[error("an account cannot delegate to its own key")]
+    SelfDelegation,
+
+    #[error("nothing is delegated to {0}")]
+    NotDelegated(Address),
+
+    #[error("the inputs of a delegate transaction are not a delegation: {0}")]
+    InvalidInputs(String),
+}
+
+impl Delegation {
+    /// Checks `transaction`, signed by the delegate, stays within the
+    /// delegation at unix time `now`, in seconds.
+    pub fn permits(&self, transaction: &Transaction, now: u64) -> Result<(), DelegationError> {
+        if !matches!(
+            transaction.transaction_type(),
+            TransactionType::Send(_) | TransactionType::Call(_)
+        ) {
+            return Err(DelegationError::NotDelegable);
+        }
+        if !self.program_ids.contains(&transaction.program_id()) {
+            return Err(DelegationError::ProgramNotDelegated(
+                transaction.program_id(),
+            ));
+        }
+        if transaction.value() > self.max_value {
+            return Err(DelegationError::ValueOverLimit {
+                value: transaction.value(),
+                max_value: self.max_value,
+            });
+        }
+        if let Some(expires_at_nonce) = self.expires_at_nonce {
+            if transaction.nonce() > expires_at_nonce {
+                return Err(DelegationError::NonceExpired {
+                    nonce: transaction.nonce(),
+                    expires_at_nonce,
+                });
+            }
+        }
+        if let Some(expires_at) = self.expires_at {
+            if now >= expires_at {
+                return Err(DelegationError::Expired { expires_at });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DelegationOp {
+    /// The op a delegate transaction carries in its inputs.
+    pub fn of(transaction: &Transaction) -> Result<Self, DelegationError> {
+        serde_json::from_str(&transaction.inputs())
+            .map_err(|e| DelegationError::InvalidInputs(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod delegation_tests {
+    use super::*;
+    use crate::{Account, TransactionBuilder, U256};
+
+    fn delegation() -> Delegation {
+        Delegation {
+            delegate: Address::new([2; 20]),
+            program_ids: BTreeSet::from([Address::new([9; 20])]),
+            max_value: U256::from(10),
+            expires_at_nonce: Some(U256::from(5)),
+            expires_at: Some(1_000),
+        }
+    }
+
+    fn transaction(transaction_type: TransactionType, program_id: u8, value: u64) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(transaction_type)
+            .from([1; 20])
+            .to([3; 20])
+            .program_id([program_id; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(value))
+            .nonce(U256::from(5))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn a_delegate_is_held_to_the_scope_it_was_given() {
+        let delegation = delegation();
+        let send = TransactionType::Send(U256::from(5));
+        assert_eq!(
+            delegation.permits(&transaction(send.clone(), 9, 10), 999),
+            Ok(())
+        );
+        assert_eq!(
+            delegation.permits(
+                &transaction(TransactionType::Call(U256::from(5)), 9, 0),
+                999
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            delegation.permits(&transaction(send.clone(), 8, 1), 999),
+            Err(DelegationError::ProgramNotDelegated(Address::new([8; 20])))
+        );
+        assert_eq!(
+            delegation.permits(&transaction(send.clone(), 9, 11), 999),
+            Err(DelegationError::ValueOverLimit {
+                value: U256::from(11),
+                max_value: U256::from(10),
+            })
+        );
+        assert_eq!(
+            delegation.permits(&transaction(send.clone(), 9, 1), 1_000),
+            Err(DelegationError::Expired { expires_at: 1_000 })
+        );
+        assert_eq!(
+            delegation.permits(
+                &transaction(TransactionType::Delegate(U256::from(5)), 9, 0),
+                999
+            ),
+            Err(DelegationError::NotDelegable)
+        );
+
+        let spent = Delegation {
+            expires_at_nonce: Some(U256::from(4)),
+            ..delegation
+        };
+        assert!(matches!(
+            spent.permits(&transaction(send, 9, 1), 999),
+            Err(DelegationError::NonceExpired { .. })
+        ));
+    }
+
+    #[test]
+    fn delegations_are_granted_replaced_and_revoked() {
+        let owner = Address::new([1; 20]);
+        let mut account = Account::new(owner);
+        account
+            .apply_delegation_op(DelegationOp::Grant(delegation()))
+            .unwrap();
+        let wider = Delegation {
+            max_value: U256::from(20),
+            ..delegation()
+        };
+        account
+            .apply_delegation_op(DelegationOp::Grant(wider.clone()))
+            .unwrap();
+        assert_eq!(account.delegation(&Address::new([2; 20])), Some(&wider));
+        assert_eq!(account.delegations().len(), 1);
+
+        let to_self = Delegation {
+            delegate: owner,
+            ..delegation()
+        };
+        assert_eq!(
+            account.apply_delegation_op(DelegationOp::Grant(to_self)),
+            Err(DelegationError::SelfDelegation)
+        );
+
+        account
+            .apply_delegation_op(DelegationOp::Revoke(Address::new([2; 20])))
+            .unwrap();
+        assert!(account.delegations().is_empty());
+        assert_eq!(
+            account.apply_delegation_op(DelegationOp::Revoke(Address::new([2; 20]))),
+            Err(DelegationError::NotDelegated(Address::new([2; 20])))
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::Address;
+
+/// An event a program emits alongside its instructions, e.g. a transfer log
+/// or one of its own application events. Events change nothing, they are
+/// recorded with the transaction's receipt and in its batch for clients to
+/// look up by program and topic.
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProgramEvent {
+    pub topic: String,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl ProgramEvent {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Bytes in the topic and every attribute's key and value.
+    pub fn size(&self) -> usize {
+        self.topic.len()
+            + self
+                .attributes
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum::<usize>()
+    }
+}
+
+/// Which events `lasr_getEvents` and `lasr_subscribeEvents` answer with.
+/// Every event matches a filter with neither set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EventFilter {
+    #[serde(default)]
+    pub program_id: Option<Address>,
+    #[serde(default)]
+    pub topic_prefix: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, program_id: &Address, topic: &str) -> bool {
+        self.program_id.iter().all(|id| id == program_id)
+            && self
+                .topic_prefix
+                .iter()
+                .all(|prefix| topic.starts_with(prefix.as_str()))
+    }
+}
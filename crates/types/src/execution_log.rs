@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// A line a program logged while it was executing. Container programs log
+/// whatever they write to stderr, WASM programs whatever they pass to the
+/// `log` host function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionLogLine {
+    pub program_id: String,
+    pub transaction_hash: Option<String>,
+    /// Position of the line within its execution, starting at 0.
+    pub sequence: u64,
+    pub line: String,
+}
+
+/// What a `lasr_subscribeExecutionLogs` subscriber receives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ExecutionLogEvent {
+    Line(ExecutionLogLine),
+    /// The subscriber fell behind and this many of the oldest lines it had
+    /// not received yet were dropped.
+    Dropped {
+        dropped: u64,
+    },
+}
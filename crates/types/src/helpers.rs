@@ -0,0 +1,214 @@
+//! Serde adapters for the hex the JSON-RPC API writes numbers and bytes in.
+//!
+//! Human-readable formats such as JSON get `0x` hex: `U256`s as quantities,
+//! without leading zeros, and bytes as two digits each. Compact formats such
+//! as bincode keep the bytes, so nothing persisted or dispersed grows hex.
+//! Use them with `#[serde(with = "hex_bytes")]`, or with `serialize_with` and
+//! `deserialize_with` on types deriving `JsonSchema`.
+
+/// `U256`s as `0x` hex quantities, `0x0` for zero, or 32 big endian bytes.
+pub mod hex_quantity {
+    use serde::{de::Visitor, Deserializer, Serializer};
+
+    use crate::U256;
+
+    /// The quantity `value` is written as.
+    pub fn encode(value: &U256) -> String {
+        format!("0x{value:x}")
+    }
+
+    pub fn serialize<S>(value: &U256, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&encode(value))
+        } else {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            serializer.serialize_bytes(&bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<U256, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(QuantityVisitor)
+        } else {
+            deserializer.deserialize_bytes(QuantityVisitor)
+        }
+    }
+
+    struct QuantityVisitor;
+
+    impl<'de> Visitor<'de> for QuantityVisitor {
+        type Value = U256;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a 0x hex quantity, 64 hex digits or [u64; 4] array as a string")
+        }
+
+        /// Takes a quantity, and the 64 digits, with or without `0x`, and
+        /// the list of limbs it was written as before.
+        fn visit_str<E>(self, v: &str) -> Result<U256, E>
+        where
+            E: serde::de::Error,
+        {
+            if v.starts_with('[') && v.ends_with(']') {
+                let limbs = v[1..v.len() - 1]
+                    .split(',')
+                    .map(str::trim)
+                    .map(|s| s.parse::<u64>().map_err(E::custom))
+                    .collect::<Result<Vec<u64>, E>>()?;
+                let limbs: [u64; 4] = limbs
+                    .try_into()
+                    .map_err(|_| E::custom("Array does not have 4 elements"))?;
+                return Ok(U256(limbs));
+            }
+            let digits = match v.strip_prefix("0x") {
+                Some(digits) => digits,
+                None if v.len() == 64 => v,
+                None => return Err(E::custom("Invalid format for U256")),
+            };
+            if digits.is_empty() || digits.len() > 64 {
+                return Err(E::custom(format!(
+                    "a U256 is 1 to 64 hex digits, not {}",
+                    digits.len()
+                )));
+            }
+            if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(E::custom("U256 is not hex"));
+            }
+            U256::from_str_radix(digits, 16).map_err(|e| E::custom(format!("{e:?}")))
+        }
+
+        /// Takes the 32 bytes bincode holds a `U256` as, or the hex string
+        /// it was written as before.
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<U256, E>
+        where
+            E: serde::de::Error,
+        {
+            if v.len() == 32 {
+                return Ok(U256::from_big_endian(v));
+            }
+            std::str::from_utf8(v)
+                .map_err(|_| E::custom(format!("a U256 is 32 bytes, not {}", v.len())))
+                .and_then(|v| self.visit_str(v))
+        }
+    }
+}
+
+/// Bytes as `0x` hex, or as the bytes themselves, length first.
+pub mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S, T>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: AsRef<[u8]>,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+        } else {
+            serializer.serialize_bytes(bytes.as_ref())
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: TryFrom<Vec<u8>>,
+    {
+        let bytes = if deserializer.is_human_readable() {
+            let hex = String::deserialize(deserializer)?;
+            let digits = hex
+                .strip_prefix("0x")
+                .ok_or_else(|| serde::de::Error::custom("hex must start with 0x"))?;
+            hex::decode(digits).map_err(serde::de::Error::custom)?
+        } else {
+            Vec::<u8>::deserialize(deserializer)?
+        };
+        let len = bytes.len();
+        T::try_from(bytes)
+            .map_err(|_| serde::de::Error::custom(format!("{len} bytes is the wrong length")))
+    }
+}
+
+#[cfg(test)]
+mod helpers_tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::U256;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wire {
+        #[serde(with = "hex_quantity")]
+        amount: U256,
+        #[serde(with = "hex_bytes")]
+        hash: [u8; 32],
+        #[serde(with = "hex_bytes")]
+        data: Vec<u8>,
+    }
+
+    fn wire() -> Wire {
+        Wire {
+            amount: U256::from(1000),
+            hash: [0xab; 32],
+            data: vec![1, 2],
+        }
+    }
+
+    #[test]
+    fn json_gets_hex_and_bincode_the_bytes() {
+        let json = serde_json::to_value(wire()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "amount": "0x3e8",
+                "hash": format!("0x{}", "ab".repeat(32)),
+                "data": "0x0102",
+            })
+        );
+        assert_eq!(serde_json::from_value::<Wire>(json).unwrap(), wire());
+
+        let bytes = bincode::serialize(&wire()).unwrap();
+        assert_eq!(bytes.len(), (8 + 32) * 2 + 8 + 2);
+        assert!(!bytes.windows(2).any(|pair| pair == b"0x"));
+        assert_eq!(bincode::deserialize::<Wire>(&bytes).unwrap(), wire());
+    }
+
+    #[test]
+    fn quantities_are_read_in_every_form_they_were_written_in() {
+        let quantity = |value: &str| serde_json::from_value::<U256>(value.into());
+        assert_eq!(hex_quantity::encode(&U256::from(0)), "0x0");
+        assert_eq!(quantity("0x0").unwrap(), U256::from(0));
+        assert_eq!(quantity("0x3e8").unwrap(), U256::from(1000));
+        assert_eq!(
+            quantity(&format!("0x{:064x}", 1000)).unwrap(),
+            U256::from(1000)
+        );
+        assert_eq!(
+            quantity(&format!("{:064x}", 1000)).unwrap(),
+            U256::from(1000)
+        );
+        assert_eq!(quantity("[1000, 0, 0, 0]").unwrap(), U256::from(1000));
+        assert!(quantity("0x").is_err());
+        assert!(quantity("0xzz").is_err());
+        assert!(quantity("3e8").is_err());
+        assert!(quantity(&format!("0x1{:064x}", 0)).is_err());
+
+        // Bincode held them as their padded hex before.
+        let legacy = bincode::serialize(&format!("0x{:064x}", 1000)).unwrap();
+        assert_eq!(
+            bincode::deserialize::<U256>(&legacy).unwrap(),
+            U256::from(1000)
+        );
+        let max = U256::MAX;
+        let bytes = bincode::serialize(&max).unwrap();
+        assert_eq!(bytes.len(), 8 + 32);
+        assert_eq!(bincode::deserialize::<U256>(&bytes).unwrap(), max);
+    }
+}
@@ -0,0 +1,716 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    chain_id, Account, AccountMetadata, Address, DelegationOp, DeployRuntime, Payload,
+    PayloadBuilder, Signer, Transaction, TransactionType, U256,
+};
+
+/// Why an [`IntentBuilder`] could not build its payload.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum IntentError {
+    #[error("a {intent} transaction needs {missing}")]
+    Incomplete {
+        intent: &'static str,
+        missing: &'static str,
+    },
+
+    #[error("a {intent} transaction cannot be sent: {reason}")]
+    Invalid {
+        intent: &'static str,
+        reason: String,
+    },
+
+    #[error(
+        "signed for chain {expected}, but transactions here are signed for chain {configured}"
+    )]
+    WrongChain { expected: u64, configured: u64 },
+
+    #[error("the transaction could not be written: {0}")]
+    Payload(String),
+}
+
+/// What a transaction is meant to do, as an [`IntentBuilder`] starts from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Intent {
+    Transfer {
+        to: Address,
+        program_id: Address,
+        amount: U256,
+    },
+    Invoke {
+        program_id: Address,
+        op: String,
+        inputs: String,
+    },
+    Deploy {
+        content_id: String,
+        runtime: DeployRuntime,
+    },
+    RegisterProgram {
+        inputs: String,
+    },
+    BridgeOut {
+        program_id: Address,
+        value: U256,
+        recipient: Address,
+    },
+    Burn {
+        program_id: Address,
+        amount: U256,
+    },
+    Delegate(DelegationOp),
+    SetAccountMetadata(AccountMetadata),
+}
+
+impl Intent {
+    fn name(&self) -> &'static str {
+        match self {
+            Intent::Transfer { .. } => "transfer",
+            Intent::Invoke { .. } => "call",
+            Intent::Deploy { .. } => "deploy",
+            Intent::RegisterProgram { .. } => "register program",
+            Intent::BridgeOut { .. } => "bridge out",
+            Intent::Burn { .. } => "burn",
+            Intent::Delegate(_) => "delegate",
+            Intent::SetAccountMetadata(_) => "account metadata",
+        }
+    }
+}
+
+/// The fields of a payload an intent sets.
+struct Shape {
+    transaction_type: TransactionType,
+    to: Address,
+    program_id: Address,
+    op: String,
+    inputs: String,
+    value: U256,
+}
+
+/// Builds a transaction from what it is meant to do, rather than field by
+/// field as a [`PayloadBuilder`] does. Each intent sets the transaction
+/// type, and the fields that go with it, together, so the two cannot
+/// disagree; the sender and nonce come from the account the transaction is
+/// sent from, and what is left unset, as fees, from the payload builder
+/// given as defaults.
+///
+/// ```rust,ignore
+/// let transaction = IntentBuilder::transfer(to, Address::verse_addr(), amount)
+///     .from_account(&account)
+///     .with_max_fee(fee)
+///     .sign(&secret_key)?;
+/// ```
+///
+/// [`IntentBuilder::build`] answers the payload unsigned, for it to be
+/// signed elsewhere, as offline.
+#[derive(Clone, Debug)]
+pub struct IntentBuilder {
+    intent: Intent,
+    from: Option<Address>,
+    nonce: Option<U256>,
+    to: Option<Address>,
+    value: Option<U256>,
+    max_fee: Option<U256>,
+    priority_fee: Option<U256>,
+    valid_after: Option<u64>,
+    valid_before: Option<u64>,
+    chain_id: Option<u64>,
+    defaults: PayloadBuilder,
+}
+
+impl IntentBuilder {
+    fn new(intent: Intent) -> Self {
+        Self {
+            intent,
+            from: None,
+            nonce: None,
+            to: None,
+            value: None,
+            max_fee: None,
+            priority_fee: None,
+            valid_after: None,
+            valid_before: None,
+            chain_id: None,
+            defaults: PayloadBuilder::default(),
+        }
+    }
+
+    /// Sends `amount` of the program's token to `to`.
+    pub fn transfer(to: Address, program_id: Address, amount: U256) -> Self {
+        Self::new(Intent::Transfer {
+            to,
+            program_id,
+            amount,
+        })
+    }
+
+    /// Calls `op` of the program with `inputs`, carrying no value unless
+    /// [`IntentBuilder::with_value`] says, to the program unless
+    /// [`IntentBuilder::with_to`] says.
+    pub fn invoke(program_id: Address, op: impl Into<String>, inputs: impl Into<String>) -> Self {
+        Self::new(Intent::Invoke {
+            program_id,
+            op: op.into(),
+            inputs: inputs.into(),
+        })
+    }
+
+    /// Deploys the program whose package is at `content_id`, to run in
+    /// `runtime`.
+    pub fn deploy(content_id: impl Into<String>, runtime: DeployRuntime) -> Self {
+        Self::new(Intent::Deploy {
+            content_id: content_id.into(),
+            runtime,
+        })
+    }
+
+    /// Registers a program as its `inputs` describe it.
+    pub fn register_program(inputs: impl Into<String>) -> Self {
+        Self::new(Intent::RegisterProgram {
+            inputs: inputs.into(),
+        })
+    }
+
+    /// Takes `value` of the program's token off the chain, for `recipient`
+    /// on the settlement layer.
+    pub fn bridge_out(program_id: Address, value: U256, recipient: Address) -> Self {
+        Self::new(Intent::BridgeOut {
+            program_id,
+            value,
+            recipient,
+        })
+    }
+
+    /// Burns `amount` of the sender's balance of the program's token.
+    pub fn burn(program_id: Address, amount: U256) -> Self {
+        Self::new(Intent::Burn { program_id, amount })
+    }
+
+    /// Grants or revokes a delegation of the sender's.
+    pub fn delegate(op: DelegationOp) -> Self {
+        Self::new(Intent::Delegate(op))
+    }
+
+    /// Sets the sender's account metadata to `metadata`.
+    pub fn set_account_metadata(metadata: AccountMetadata) -> Self {
+        Self::new(Intent::SetAccountMetadata(metadata))
+    }
+
+    /// Sends from `account`, with the nonce it takes next.
+    pub fn from_account(mut self, account: &Account) -> Self {
+        self.from = Some(account.owner_address());
+        self.nonce = Some(account.expected_nonce());
+        self
+    }
+
+    /// Sends from `from`, which signing would otherwise take from the
+    /// signer.
+    pub fn with_sender(mut self, from: Address) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// The nonce the transaction takes, one past the sender's account.
+    pub fn with_nonce(mut self, nonce: U256) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Who a call is made to, if not the program it calls.
+    pub fn with_to(mut self, to: Address) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    /// The value a call carries.
+    pub fn with_value(mut self, value: U256) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn with_max_fee(mut self, max_fee: U256) -> Self {
+        self.max_fee = Some(max_fee);
+        self
+    }
+
+    pub fn with_priority_fee(mut self, priority_fee: U256) -> Self {
+        self.priority_fee = Some(priority_fee);
+        self
+    }
+
+    /// Valid only from `valid_after` and before `valid_before`, in seconds
+    /// since the epoch, either end left open with `None`.
+    pub fn with_window(mut self, valid_after: Option<u64>, valid_before: Option<u64>) -> Self {
+        self.valid_after = valid_after;
+        self.valid_before = valid_before;
+        self
+    }
+
+    /// Fails to build unless transactions here are signed for `chain_id`,
+    /// as [`crate::set_chain_id`] sets.
+    pub fn for_chain(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Takes what the intent leaves unset, as fees, from `defaults`.
+    pub fn with_defaults(mut self, defaults: &PayloadBuilder) -> Self {
+        self.defaults = defaults.clone();
+        self
+    }
+
+    /// The fields the intent is sent with, checked as the node checks them
+    /// on admission.
+    fn shape(&self, from: Address) -> Result<Shape, IntentError> {
+        let intent = self.intent.name();
+        let incomplete = |missing| Err(IntentError::Incomplete { intent, missing });
+        let invalid = |reason: &str| {
+            Err(IntentError::Invalid {
+                intent,
+                reason: reason.to_string(),
+            })
+        };
+        let Some(nonce) = self.nonce else {
+            return incomplete("a nonce");
+        };
+        // The type carries the nonce the sender's account is at.
+        let at = nonce.saturating_sub(U256::from(1));
+        let is_call = matches!(self.intent, Intent::Invoke { .. });
+        if self.value.is_some() && !is_call {
+            return invalid("only a call is given a value, the rest carry their own");
+        }
+        if self.to.is_some() && !is_call {
+            return invalid("only a call is given a receiver, the rest name their own");
+        }
+        // What a transaction to no one else is sent with.
+        let to_self = |transaction_type, inputs| Shape {
+            transaction_type,
+            to: from,
+            program_id: Address::verse_addr(),
+            op: String::new(),
+            inputs,
+            value: U256::from(0),
+        };
+        Ok(match &self.intent {
+            Intent::Transfer {
+                to,
+                program_id,
+                amount,
+            } => Shape {
+                transaction_type: TransactionType::Send(at),
+                to: *to,
+                program_id: *program_id,
+                op: String::new(),
+                inputs: String::new(),
+                value: *amount,
+            },
+            Intent::Invoke {
+                program_id,
+                op,
+                inputs,
+            } => {
+                if *program_id == Address::default() {
+                    return incomplete("the program it calls");
+                }
+                if op.is_empty() {
+                    return incomplete("the op it calls");
+                }
+                Shape {
+                    transaction_type: TransactionType::Call(at),
+                    to: self.to.unwrap_or(*program_id),
+                    program_id: *program_id,
+                    op: op.clone(),
+                    inputs: inputs.clone(),
+                    value: self.value.unwrap_or_default(),
+                }
+            }
+            Intent::Deploy {
+                content_id,
+                runtime,
+            } => {
+                if content_id.is_empty() {
+                    return incomplete("the content id of its package");
+                }
+                Shape {
+                    transaction_type: TransactionType::Deploy {
+                        nonce: at,
+                        content_id: content_id.clone(),
+                        runtime: *runtime,
+                    },
+                    to: Address::default(),
+                    program_id: Address::default(),
+                    op: String::new(),
+                    inputs: String::new(),
+                    value: U256::from(0),
+                }
+            }
+            Intent::RegisterProgram { inputs } => {
+                if inputs.is_empty() {
+                    return incomplete("inputs describing the program");
+                }
+                Shape {
+                    transaction_type: TransactionType::RegisterProgram(at),
+                    to: Address::default(),
+                    program_id: Address::default(),
+                    op: String::new(),
+                    inputs: inputs.clone(),
+                    value: U256::from(0),
+                }
+            }
+            Intent::BridgeOut {
+                program_id,
+                value,
+                recipient,
+            } => {
+                if *recipient == Address::default() {
+                    return incomplete("a recipient");
+                }
+                if value.is_zero() {
+                    return incomplete("a value to take off the chain");
+                }
+                Shape {
+                    transaction_type: TransactionType::BridgeOutTo {
+                        nonce: at,
+                        recipient: *recipient,
+                    },
+                    to: from,
+                    program_id: *program_id,
+                    op: String::new(),
+                    inputs: String::new(),
+                    value: *value,
+                }
+            }
+            Intent::Burn { program_id, amount } => {
+                if *program_id != Address::verse_addr() {
+                    return invalid("only VERSE is burned");
+                }
+                if amount.is_zero() {
+                    return incomplete("an amount to burn");
+                }
+                Shape {
+                    transaction_type: TransactionType::BurnAmount { nonce: at },
+                    to: from,
+                    program_id: *program_id,
+                    op: String::new(),
+                    inputs: String::new(),
+                    value: *amount,
+                }
+            }
+            Intent::Delegate(op) => to_self(TransactionType::Delegate(at), inputs_json(op)?),
+            Intent::SetAccountMetadata(metadata) => {
+                if let Err(e) = metadata.validate() {
+                    return invalid(&e.to_string());
+                }
+                to_self(
+                    TransactionType::SetAccountMetadata(at),
+                    inputs_json(metadata)?,
+                )
+            }
+        })
+    }
+
+    fn payload(&self, from: Address) -> Result<Payload, IntentError> {
+        if let Some(expected) = self.chain_id {
+            let configured = chain_id();
+            if expected != configured {
+                return Err(IntentError::WrongChain {
+                    expected,
+                    configured,
+                });
+            }
+        }
+        if let (Some(valid_after), Some(valid_before)) = (self.valid_after, self.valid_before) {
+            if valid_after >= valid_before {
+                return Err(IntentError::Invalid {
+                    intent: self.intent.name(),
+                    reason: format!("it is valid from {valid_after}, not before {valid_before}"),
+                });
+            }
+        }
+        let shape = self.shape(from)?;
+        let mut builder = self.defaults.clone();
+        builder
+            .transaction_type(shape.transaction_type)
+            .from(from.inner())
+            .to(shape.to.inner())
+            .program_id(shape.program_id.inner())
+            .op(shape.op)
+            .inputs(shape.inputs)
+            .value(shape.value)
+            .nonce(self.nonce.unwrap_or_default());
+        if let Some(max_fee) = self.max_fee {
+            builder.max_fee(Some(max_fee));
+        }
+        if let Some(priority_fee) = self.priority_fee {
+            builder.priority_fee(Some(priority_fee));
+        }
+        if self.valid_after.is_some() || self.valid_before.is_some() {
+            builder
+                .valid_after(self.valid_after)
+                .valid_before(self.valid_before);
+        }
+        builder
+            .build()
+            .map_err(|e| IntentError::Payload(e.to_string()))
+    }
+
+    /// The payload unsigned, for it to be signed elsewhere.
+    pub fn build(&self) -> Result<Payload, IntentError> {
+        match self.from {
+            Some(from) => self.payload(from),
+            None => Err(IntentError::Incomplete {
+                intent: self.intent.name(),
+                missing: "a sender",
+            }),
+        }
+    }
+
+    /// The transaction signed by `signer`, sent from its address unless a
+    /// sender was given, as an account's delegate signs for it.
+    pub fn sign(&self, signer: &impl Signer) -> Result<Transaction, IntentError> {
+        let payload = self.payload(self.from.unwrap_or_else(|| signer.address()))?;
+        let signature = signer.sign_digest(&payload.digest());
+        Ok((payload, signature).into())
+    }
+}
+
+/// `inputs` written as the JSON a transaction carries them as.
+fn inputs_json(inputs: &impl Serialize) -> Result<String, IntentError> {
+    serde_json::to_string(inputs).map_err(|e| IntentError::Payload(e.to_string()))
+}
+
+#[cfg(test)]
+mod intent_tests {
+    use super::*;
+    use crate::Delegation;
+    use secp256k1::SecretKey;
+    use std::collections::BTreeSet;
+
+    fn key() -> SecretKey {
+        SecretKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    /// The account of [`key`], having sent two transactions.
+    fn account() -> Account {
+        let mut account = Account::new(key().address());
+        account.increment_nonce();
+        account.increment_nonce();
+        account
+    }
+
+    fn sign(intent: IntentBuilder) -> Transaction {
+        intent.from_account(&account()).sign(&key()).unwrap()
+    }
+
+    fn assert_signed_by_the_sender(transaction: &Transaction) {
+        assert_eq!(transaction.from(), key().address());
+        assert_eq!(transaction.nonce(), U256::from(3));
+        transaction.verify_signature().unwrap();
+    }
+
+    #[test]
+    fn each_intent_is_sent_as_its_transaction_type_with_the_fields_it_needs() {
+        let (to, program) = (Address::new([2; 20]), Address::new([3; 20]));
+        let at = U256::from(2);
+
+        let transfer = sign(IntentBuilder::transfer(to, program, U256::from(5)));
+        assert_signed_by_the_sender(&transfer);
+        assert_eq!(transfer.transaction_type(), TransactionType::Send(at));
+        assert_eq!((transfer.to(), transfer.program_id()), (to, program));
+        assert_eq!(transfer.value(), U256::from(5));
+
+        let call = sign(
+            IntentBuilder::invoke(program, "mint", r#"{"amount":1}"#).with_value(U256::from(4)),
+        );
+        assert_signed_by_the_sender(&call);
+        assert_eq!(call.transaction_type(), TransactionType::Call(at));
+        assert_eq!((call.to(), call.program_id()), (program, program));
+        assert_eq!(
+            (call.op(), call.inputs()),
+            ("mint".into(), r#"{"amount":1}"#.into())
+        );
+        assert_eq!(call.value(), U256::from(4));
+        let call = sign(IntentBuilder::invoke(program, "mint", "").with_to(to));
+        assert_eq!((call.to(), call.value()), (to, U256::from(0)));
+
+        let deploy = sign(IntentBuilder::deploy("bafy", DeployRuntime::Wasm));
+        assert_signed_by_the_sender(&deploy);
+        assert_eq!(
+            deploy.transaction_type(),
+            TransactionType::Deploy {
+                nonce: at,
+                content_id: "bafy".to_string(),
+                runtime: DeployRuntime::Wasm,
+            }
+        );
+        assert_eq!(deploy.value(), U256::from(0));
+
+        let register = sign(IntentBuilder::register_program(r#"{"contentId":"bafy"}"#));
+        assert_eq!(
+            register.transaction_type(),
+            TransactionType::RegisterProgram(at)
+        );
+        assert_eq!(register.inputs(), r#"{"contentId":"bafy"}"#);
+
+        let bridge_out = sign(IntentBuilder::bridge_out(program, U256::from(6), to));
+        assert_eq!(
+            bridge_out.transaction_type(),
+            TransactionType::BridgeOutTo {
+                nonce: at,
+                recipient: to,
+            }
+        );
+        assert_eq!(
+            (bridge_out.to(), bridge_out.value()),
+            (key().address(), U256::from(6))
+        );
+
+        let verse = Address::verse_addr();
+        let burn = sign(IntentBuilder::burn(verse, U256::from(8)));
+        assert_eq!(
+            burn.transaction_type(),
+            TransactionType::BurnAmount { nonce: at }
+        );
+        assert_eq!(burn.value(), U256::from(8));
+
+        let grant = DelegationOp::Grant(Delegation {
+            delegate: to,
+            program_ids: BTreeSet::from([program]),
+            max_value: U256::from(10),
+            expires_at_nonce: None,
+            expires_at: None,
+        });
+        let delegate = sign(IntentBuilder::delegate(grant.clone()));
+        assert_eq!(delegate.transaction_type(), TransactionType::Delegate(at));
+        assert_eq!(
+            (delegate.to(), delegate.program_id()),
+            (key().address(), verse)
+        );
+        assert_eq!(
+            serde_json::from_str::<DelegationOp>(&delegate.inputs()).unwrap(),
+            grant
+        );
+
+        let metadata = sign(IntentBuilder::set_account_metadata(
+            AccountMetadata::default(),
+        ));
+        assert_eq!(
+            metadata.transaction_type(),
+            TransactionType::SetAccountMetadata(at)
+        );
+        assert_eq!(metadata.to(), key().address());
+    }
+
+    #[test]
+    fn an_intent_missing_what_it_needs_is_not_built() {
+        let program = Address::new([3; 20]);
+        let incomplete = |intent: IntentBuilder| match intent.from_account(&account()).build() {
+            Err(IntentError::Incomplete { missing, .. }) => missing,
+            other => panic!("expected the intent to be incomplete, got {other:?}"),
+        };
+        assert_eq!(
+            incomplete(IntentBuilder::invoke(Address::default(), "mint", "")),
+            "the program it calls"
+        );
+        assert_eq!(
+            incomplete(IntentBuilder::invoke(program, "", "")),
+            "the op it calls"
+        );
+        assert_eq!(
+            incomplete(IntentBuilder::deploy("", DeployRuntime::Container)),
+            "the content id of its package"
+        );
+        assert_eq!(
+            incomplete(IntentBuilder::bridge_out(
+                program,
+                U256::from(1),
+                Address::default()
+            )),
+            "a recipient"
+        );
+        assert_eq!(
+            incomplete(IntentBuilder::burn(Address::verse_addr(), U256::from(0))),
+            "an amount to burn"
+        );
+
+        // Nothing says who sends it, or with what nonce.
+        let transfer = IntentBuilder::transfer(program, program, U256::from(1));
+        assert!(matches!(
+            transfer.clone().with_nonce(U256::from(1)).build(),
+            Err(IntentError::Incomplete {
+                missing: "a sender",
+                ..
+            })
+        ));
+        assert!(matches!(
+            transfer.sign(&key()),
+            Err(IntentError::Incomplete {
+                missing: "a nonce",
+                ..
+            })
+        ));
+
+        // What does not go with the intent.
+        let burn = IntentBuilder::burn(program, U256::from(1)).from_account(&account());
+        assert!(matches!(burn.build(), Err(IntentError::Invalid { .. })));
+        let transfer = IntentBuilder::transfer(program, program, U256::from(1))
+            .from_account(&account())
+            .with_value(U256::from(2));
+        assert!(matches!(transfer.build(), Err(IntentError::Invalid { .. })));
+        let window = IntentBuilder::transfer(program, program, U256::from(1))
+            .from_account(&account())
+            .with_window(Some(10), Some(10));
+        assert!(matches!(window.build(), Err(IntentError::Invalid { .. })));
+        let elsewhere = IntentBuilder::transfer(program, program, U256::from(1))
+            .from_account(&account())
+            .for_chain(chain_id() + 1);
+        assert!(matches!(
+            elsewhere.build(),
+            Err(IntentError::WrongChain { .. })
+        ));
+    }
+
+    #[test]
+    fn an_unsigned_payload_takes_fees_and_its_window_from_the_builder_and_its_defaults() {
+        let mut defaults = PayloadBuilder::default();
+        defaults
+            .max_fee(Some(U256::from(50)))
+            .priority_fee(Some(U256::from(1)));
+        let to = Address::new([2; 20]);
+        let intent = IntentBuilder::transfer(to, Address::verse_addr(), U256::from(5))
+            .with_defaults(&defaults)
+            .with_sender(Address::new([1; 20]))
+            .with_nonce(U256::from(9));
+
+        let payload = intent.build().unwrap();
+        assert_eq!(
+            payload.transaction_type(),
+            TransactionType::Send(U256::from(8))
+        );
+        assert_eq!(payload.from(), [1; 20]);
+        assert_eq!(payload.nonce(), U256::from(9));
+        assert_eq!(payload.max_fee(), Some(U256::from(50)));
+        assert_eq!(payload.priority_fee(), Some(U256::from(1)));
+        assert_eq!(payload.valid_before(), None);
+
+        let payload = intent
+            .with_priority_fee(U256::from(3))
+            .with_window(None, Some(100))
+            .for_chain(chain_id())
+            .build()
+            .unwrap();
+        assert_eq!(payload.max_fee(), Some(U256::from(50)));
+        assert_eq!(payload.priority_fee(), Some(U256::from(3)));
+        assert_eq!(payload.valid_before(), Some(100));
+
+        // Signed by a delegate, the transaction is still the sender's.
+        let delegate = SecretKey::from_slice(&[8; 32]).unwrap();
+        let transaction = IntentBuilder::transfer(to, Address::verse_addr(), U256::from(5))
+            .from_account(&account())
+            .sign(&delegate)
+            .unwrap();
+        assert_eq!(transaction.from(), key().address());
+        assert_eq!(transaction.recover().unwrap(), delegate.address());
+    }
+}
@@ -1,13 +1,39 @@
 pub mod account;
+pub mod account_metadata;
+pub mod account_state;
+pub mod attestation;
+pub mod delegation;
+pub mod events;
+pub mod execution_log;
+pub mod helpers;
+pub mod intent;
+pub mod merkle;
 pub mod persistence;
 pub mod programming_model;
+pub mod reconcile;
+pub mod rpc_error_code;
 pub mod signing;
+pub mod storage_quota;
 pub mod token;
 pub mod transaction;
+pub mod typed_data;
 
 pub use account::*;
+pub use account_metadata::*;
+pub use account_state::*;
+pub use attestation::*;
+pub use delegation::*;
+pub use events::*;
+pub use execution_log::*;
+pub use helpers::*;
+pub use intent::*;
+pub use merkle::*;
 pub use persistence::*;
 pub use programming_model::*;
+pub use reconcile::*;
+pub use rpc_error_code::*;
 pub use signing::*;
+pub use storage_quota::*;
 pub use token::*;
 pub use transaction::*;
+pub use typed_data::*;
@@ -0,0 +1,200 @@
+//! Merkle trees over what a batch holds, and proofs that something is in
+//! one.
+//!
+//! Leaves and the nodes above them are hashed with Keccak256 under a prefix
+//! byte of their own, so a leaf can never be passed off as a node. A node
+//! left without a sibling at the end of a level is carried up as it is. A
+//! proof holds one sibling per level it is combined at, so it is no longer
+//! than the tree is deep, at most `MAX_PROOF_DEPTH`.
+use ethereum_types::H256;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::Address;
+
+/// The deepest a tree is, and so the most siblings a proof holds.
+pub const MAX_PROOF_DEPTH: usize = 64;
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// The leaf of `data`.
+pub fn merkle_leaf(data: &[u8]) -> H256 {
+    let mut hasher = Keccak256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    H256::from_slice(&hasher.finalize())
+}
+
+fn merkle_node(left: &H256, right: &H256) -> H256 {
+    let mut hasher = Keccak256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    H256::from_slice(&hasher.finalize())
+}
+
+/// The leaf a transaction is committed to by its hash.
+pub fn transaction_leaf(transaction_hash: &H256) -> H256 {
+    merkle_leaf(transaction_hash.as_bytes())
+}
+
+/// The leaf an account is committed to by, its address and the hash of the
+/// account as it is persisted.
+pub fn account_leaf(address: &Address, account_hash: &H256) -> H256 {
+    let mut data = address.inner().to_vec();
+    data.extend_from_slice(account_hash.as_bytes());
+    merkle_leaf(&data)
+}
+
+/// The level above `level`.
+fn parent_level(level: &[H256]) -> Vec<H256> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => merkle_node(left, right),
+            [carried] => *carried,
+            _ => unreachable!("chunks of two are never empty"),
+        })
+        .collect()
+}
+
+/// The root of the tree over `leaves`, zero if there are none.
+pub fn merkle_root(leaves: &[H256]) -> H256 {
+    MerkleTree::new(leaves.to_vec()).root()
+}
+
+/// A tree kept whole, to prove each of its leaves without hashing it again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// Each level, from the leaves up to the root.
+    levels: Vec<Vec<H256>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: Vec<H256>) -> Self {
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let parent = parent_level(&levels[levels.len() - 1]);
+            levels.push(parent);
+        }
+        Self { levels }
+    }
+
+    /// The root, zero if there are no leaves.
+    pub fn root(&self) -> H256 {
+        self.levels[self.levels.len() - 1]
+            .first()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The proof of the leaf at `index`, if there is one.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        let leaves = &self.levels[0];
+        if index >= leaves.len() {
+            return None;
+        }
+        let mut position = index;
+        let mut siblings = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            position /= 2;
+        }
+        Some(MerkleProof {
+            index: index as u64,
+            leaves: leaves.len() as u64,
+            siblings,
+        })
+    }
+}
+
+/// That the leaf at `index` of a tree of `leaves` leaves is under its root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleProof {
+    pub index: u64,
+    pub leaves: u64,
+    /// The sibling the leaf, and each node above it, is combined with, from
+    /// the leaf's level up.
+    pub siblings: Vec<H256>,
+}
+
+impl MerkleProof {
+    /// The root `leaf` is under by this proof, if the proof is well formed.
+    pub fn root(&self, leaf: H256) -> Option<H256> {
+        if self.index >= self.leaves || self.siblings.len() > MAX_PROOF_DEPTH {
+            return None;
+        }
+        let mut siblings = self.siblings.iter();
+        let mut hash = leaf;
+        let mut position = self.index;
+        let mut width = self.leaves;
+        while width > 1 {
+            if position % 2 == 1 {
+                hash = merkle_node(siblings.next()?, &hash);
+            } else if position + 1 < width {
+                hash = merkle_node(&hash, siblings.next()?);
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+        // Every sibling is used, or the proof is not of this tree's shape.
+        siblings.next().is_none().then_some(hash)
+    }
+
+    /// Whether `leaf` is under `root` by this proof.
+    pub fn verify(&self, leaf: H256, root: H256) -> bool {
+        self.root(leaf) == Some(root)
+    }
+}
+
+#[cfg(test)]
+mod merkle_tests {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<H256> {
+        (0..count).map(|byte| merkle_leaf(&[byte])).collect()
+    }
+
+    #[test]
+    fn every_leaf_of_every_shape_is_proven_under_the_root() {
+        for count in 1..=17 {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(leaves.clone());
+            let root = merkle_root(&leaves);
+            assert_eq!(tree.root(), root);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(proof.siblings.len() <= 5);
+                assert!(proof.verify(*leaf, root), "{index} of {count}");
+            }
+        }
+        assert_eq!(merkle_root(&[]), H256::zero());
+        assert_eq!(MerkleTree::new(leaves(3)).proof(3), None);
+    }
+
+    #[test]
+    fn a_proof_altered_in_any_part_is_refused() {
+        let leaves = leaves(6);
+        let root = merkle_root(&leaves);
+        let proof = MerkleTree::new(leaves.clone()).proof(4).unwrap();
+        assert!(!proof.verify(leaves[3], root));
+        assert!(!proof.verify(merkle_node(&leaves[4], &leaves[5]), root));
+
+        let mut moved = proof.clone();
+        moved.index = 5;
+        assert!(!moved.verify(leaves[4], root));
+        let mut forged = proof.clone();
+        forged.siblings[0] = H256::repeat_byte(7);
+        assert!(!forged.verify(leaves[4], root));
+        let mut padded = proof.clone();
+        padded.siblings.push(H256::zero());
+        assert!(!padded.verify(leaves[4], root));
+        let mut outside = proof;
+        outside.index = 6;
+        assert_eq!(outside.root(leaves[4]), None);
+    }
+}
@@ -27,6 +27,9 @@ pub trait PersistenceStore: Clone {
     async fn get(&self, key: Self::Key) -> Result<Option<Self::Value>, Self::Error>;
 
     async fn put(&self, key: Self::Key, val: Self::Value) -> Result<(), Self::Error>;
+
+    /// Removes whatever is stored under `key`, if anything is.
+    async fn delete(&self, key: Self::Key) -> Result<(), Self::Error>;
 }
 
 impl PersistenceStore for tikv_client::RawClient {
@@ -47,6 +50,10 @@ impl PersistenceStore for tikv_client::RawClient {
     async fn put(&self, key: Self::Key, val: Self::Value) -> Result<(), Self::Error> {
         tikv_client::RawClient::put(self, key, val).await
     }
+
+    async fn delete(&self, key: Self::Key) -> Result<(), Self::Error> {
+        tikv_client::RawClient::delete(self, key).await
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -93,4 +100,9 @@ where
         map.insert(key, val);
         Ok(())
     }
+
+    async fn delete(&self, key: Self::Key) -> Result<(), Self::Error> {
+        self.0.lock().await.remove(&key);
+        Ok(())
+    }
 }
@@ -1,9 +1,10 @@
 //! This file contains types the protocol uses to prepare data, structure it
 //! and call out to a particular compute payload.
 use crate::{
-    Account, Address, Certificate, Namespace, ProgramField, ProgramFieldValue, TokenField,
-    TokenFieldValue, TokenWitness, Transaction, TransactionFields, U256,
+    Account, Address, Certificate, Namespace, ProgramEvent, ProgramField, ProgramFieldValue,
+    TokenField, TokenFieldValue, TokenWitness, Transaction, TransactionFields, U256,
 };
+use borsh::{BorshDeserialize, BorshSerialize};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
@@ -73,8 +74,11 @@ pub struct ParamPreRequisite {
 }
 
 /// The structure returned by a program or [`CallTransactionResult`].
+///
+/// Outputs and the instructions in them reject unknown fields, anything the
+/// protocol does not understand is treated as malformed.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct Outputs {
     #[serde(
         rename(serialize = "computeInputs", deserialize = "computeInputs"),
@@ -82,6 +86,9 @@ pub struct Outputs {
     )]
     inputs: Inputs,
     instructions: Vec<Instruction>,
+    /// The events the program emits, in order. Left out when there are none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<ProgramEvent>,
 }
 
 /// This struct is a builder for the [`Outputs`] struct.
@@ -90,6 +97,7 @@ pub struct Outputs {
 pub struct OutputsBuilder {
     pub inputs: Option<Inputs>,
     pub instructions: Vec<Instruction>,
+    pub events: Vec<ProgramEvent>,
 }
 
 impl OutputsBuilder {
@@ -112,6 +120,11 @@ impl OutputsBuilder {
         self
     }
 
+    pub fn add_event(mut self, event: ProgramEvent) -> Self {
+        self.events.push(event);
+        self
+    }
+
     pub fn build(&self) -> std::io::Result<Outputs> {
         Ok(Outputs {
             inputs: self
@@ -119,6 +132,7 @@ impl OutputsBuilder {
                 .clone()
                 .ok_or(std::io::Error::new(ErrorKind::Other, "inputs is required"))?,
             instructions: self.instructions.clone(),
+            events: self.events.clone(),
         })
     }
 }
@@ -128,12 +142,22 @@ impl Outputs {
         Self {
             inputs,
             instructions,
+            events: Vec::new(),
         }
     }
 
+    pub fn with_events(mut self, events: Vec<ProgramEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
     pub fn instructions(&self) -> &Vec<Instruction> {
         &self.instructions
     }
+
+    pub fn events(&self) -> &Vec<ProgramEvent> {
+        &self.events
+    }
 }
 
 /// This type is constructed from the combination of the original transaction,
@@ -192,7 +216,18 @@ pub struct ReadParams {
 /// Used in specifying location information that could either
 /// point to an account, token or a program's name, or ID.
 #[derive(
-    Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub enum AddressOrNamespace {
@@ -220,7 +255,7 @@ pub enum AddressOrNamespace {
 #[derive(
     Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct CreateInstruction {
     program_namespace: AddressOrNamespace,
     program_id: AddressOrNamespace,
@@ -371,7 +406,7 @@ impl CreateInstruction {
 #[derive(
     Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct TokenDistribution {
     program_id: AddressOrNamespace,
     to: AddressOrNamespace,
@@ -509,7 +544,7 @@ impl Default for TokenOrProgramUpdate {
 #[derive(
     Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct TokenUpdateField {
     field: TokenField,
     value: TokenFieldValue,
@@ -576,7 +611,7 @@ impl TokenUpdateField {
 #[derive(
     Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct ProgramUpdateField {
     field: ProgramField,
     value: ProgramFieldValue,
@@ -634,7 +669,7 @@ impl ProgramUpdateField {
 
 /// A vector of updates to either a token, or program.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct UpdateInstruction {
     updates: Vec<TokenOrProgramUpdate>,
 }
@@ -702,7 +737,7 @@ impl UpdateInstruction {
 
 /// Represents an update to a token, including the account, token address, and update fields.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct TokenUpdate {
     account: AddressOrNamespace,
     token: AddressOrNamespace,
@@ -783,7 +818,7 @@ impl TokenUpdate {
 
 /// Represents an update to a program, including the account and update fields.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct ProgramUpdate {
     account: AddressOrNamespace,
     updates: Vec<ProgramUpdateField>,
@@ -849,7 +884,7 @@ impl ProgramUpdate {
 /// Information needed to make a transfer of assets from one account
 /// to another.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct TransferInstruction {
     token: Address,
     from: AddressOrNamespace,
@@ -1002,7 +1037,7 @@ impl TransferInstruction {
 
 /// Information used in token destruction.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct BurnInstruction {
     caller: Address,
     program_id: AddressOrNamespace,
@@ -0,0 +1,315 @@
+//! Reconciling an account read from a node with what a client knows of its
+//! own transactions that the node had not confirmed when it last looked.
+//!
+//! The node's account is taken as it is. A transaction reaches the nonce it
+//! takes its sender to, so the node's account has reached every transaction
+//! at or below its nonce, applied or not. One it has reached is confirmed if
+//! the account says it applied it, or if the client's transactions account
+//! for every nonce the account moved through since the client last read it,
+//! which they do unless something else was sent from the account meanwhile.
+//! Otherwise the transaction conflicts with whatever took its nonce, and is
+//! for the caller to resolve.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Account, Transaction, U256};
+
+/// What reconciling an account with a client's unconfirmed transactions
+/// found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconcileOutcome {
+    /// The account as the node has it, holding what the transactions still
+    /// pending will take from it.
+    pub account: Account,
+    /// Transactions the account has not reached, by nonce.
+    pub pending: Vec<Transaction>,
+    /// Transactions the account already reflects, to be dropped, by nonce.
+    pub confirmed: Vec<Transaction>,
+    /// Transactions the account moved past without being shown to apply,
+    /// by nonce.
+    pub conflicts: Vec<Transaction>,
+}
+
+/// The nonce applying a transaction at `nonce` takes its sender to, an
+/// account that has not sent anything taking its first at 0 or 1.
+fn reaches(nonce: U256) -> U256 {
+    nonce.max(U256::from(1))
+}
+
+impl Account {
+    /// Reconciles `remote`, the account as a node has it, with `pending`,
+    /// the transactions from it sent since this account was read and not
+    /// confirmed. Transactions are taken once each, and ones from any other
+    /// account are left pending as they are.
+    pub fn reconcile(&self, remote: Account, pending: &[Transaction]) -> ReconcileOutcome {
+        let mut seen = BTreeSet::new();
+        let mut transactions: Vec<&Transaction> = pending
+            .iter()
+            .filter(|transaction| seen.insert(transaction.hash_string()))
+            .collect();
+        transactions.sort_by_key(|transaction| (transaction.nonce(), transaction.hash_string()));
+
+        let owner = remote.owner_address();
+        let (local, remote_nonce) = (self.nonce(), remote.nonce());
+        let mut outcome = ReconcileOutcome {
+            account: remote,
+            pending: Vec::new(),
+            confirmed: Vec::new(),
+            conflicts: Vec::new(),
+        };
+
+        let mut reached = Vec::new();
+        for transaction in transactions {
+            if transaction.from() != owner || reaches(transaction.nonce()) > remote_nonce {
+                outcome.pending.push(transaction.clone());
+            } else if outcome
+                .account
+                .applied()
+                .get(&transaction.hash_string())
+                .is_some()
+            {
+                outcome.confirmed.push(transaction.clone());
+            } else {
+                reached.push(transaction);
+            }
+        }
+
+        // The nonces the account moved through, and what took each.
+        let mut taken: BTreeMap<U256, usize> = BTreeMap::new();
+        for transaction in outcome.confirmed.iter().chain(reached.iter().copied()) {
+            *taken.entry(reaches(transaction.nonce())).or_default() += 1;
+        }
+        let accounted = taken
+            .iter()
+            .all(|(nonce, count)| *nonce > local && *nonce <= remote_nonce && *count == 1)
+            && U256::from(taken.len()) == remote_nonce.saturating_sub(local);
+        let reached = reached.into_iter().cloned();
+        if accounted {
+            outcome.confirmed.extend(reached);
+            outcome
+                .confirmed
+                .sort_by_key(|transaction| (transaction.nonce(), transaction.hash_string()));
+        } else {
+            outcome.conflicts.extend(reached);
+        }
+
+        for transaction in outcome.confirmed.iter().chain(&outcome.conflicts) {
+            outcome.account.release_debit(&transaction.hash_string());
+        }
+        for transaction in &outcome.pending {
+            outcome.account.reserve_debit(transaction);
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod reconcile_tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        Address, ArbitraryData, Metadata, Status, TokenBuilder, TransactionBuilder, TransactionType,
+    };
+
+    fn program_id() -> Address {
+        Address::new([3; 20])
+    }
+
+    /// An account at `nonce` holding `balance` of the token of
+    /// [`program_id`].
+    fn account(balance: u64, nonce: u64) -> Account {
+        let owner = Address::new([1; 20]);
+        let token = TokenBuilder::default()
+            .program_id(program_id())
+            .owner_id(owner)
+            .balance(U256::from(balance))
+            .metadata(Metadata::new())
+            .token_ids(vec![])
+            .allowance(BTreeMap::new())
+            .approvals(BTreeMap::new())
+            .data(ArbitraryData::new())
+            .status(Status::Free)
+            .build()
+            .unwrap();
+        let mut account = Account::new(owner);
+        account.insert_program(&token.program_id(), token);
+        account.set_nonce(U256::from(nonce));
+        account
+    }
+
+    fn send_from(from: u8, value: u64, nonce: u64) -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(nonce)))
+            .from([from; 20])
+            .to([2; 20])
+            .program_id(program_id().inner())
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(value))
+            .nonce(U256::from(nonce))
+            .v(0)
+            .r([0; 32])
+            .s([0; 32])
+            .build()
+            .unwrap()
+    }
+
+    fn send(value: u64, nonce: u64) -> Transaction {
+        send_from(1, value, nonce)
+    }
+
+    /// `remote` with `transactions` applied to it, as a node would.
+    fn applied(mut remote: Account, transactions: &[Transaction]) -> Account {
+        for transaction in transactions {
+            remote
+                .apply_send_transaction(transaction.clone(), None)
+                .unwrap();
+        }
+        remote
+    }
+
+    #[test]
+    fn an_account_caught_up_with_is_taken_as_it_is() {
+        let remote = account(7, 4);
+        let outcome = account(10, 2).reconcile(remote.clone(), &[]);
+        assert_eq!(outcome.account, remote);
+        assert_eq!(outcome.account.balance(&program_id()), U256::from(7));
+        assert!(outcome.pending.is_empty());
+        assert!(outcome.confirmed.is_empty());
+        assert!(outcome.conflicts.is_empty());
+    }
+
+    #[test]
+    fn transactions_not_reached_stay_pending_and_hold_what_they_take() {
+        let (first, second) = (send(3, 1), send(4, 2));
+        let outcome = account(10, 0).reconcile(account(10, 0), &[first.clone(), second.clone()]);
+        assert_eq!(outcome.pending, vec![first, second]);
+        assert!(outcome.confirmed.is_empty() && outcome.conflicts.is_empty());
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(3));
+        assert_eq!(outcome.account.balance(&program_id()), U256::from(10));
+    }
+
+    #[test]
+    fn transactions_the_account_reflects_are_dropped() {
+        let (first, second) = (send(3, 1), send(4, 2));
+        let local = account(10, 0);
+        let pending = [first.clone(), second.clone()];
+
+        // The node says it applied the first.
+        let remote = applied(account(10, 0), &[first.clone()]);
+        let outcome = local.reconcile(remote, &pending);
+        assert_eq!(outcome.confirmed, vec![first.clone()]);
+        assert_eq!(outcome.pending, vec![second.clone()]);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(outcome.account.balance(&program_id()), U256::from(7));
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(3));
+
+        // Or only that the account moved through both of their nonces, as
+        // a node read through the API does.
+        let outcome = local.reconcile(account(3, 2), &pending);
+        assert_eq!(outcome.confirmed, vec![first, second]);
+        assert!(outcome.pending.is_empty() && outcome.conflicts.is_empty());
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(3));
+    }
+
+    #[test]
+    fn the_first_send_of_an_account_may_be_at_either_nonce() {
+        for nonce in [0, 1] {
+            let send = send(3, nonce);
+            let outcome = account(10, 0).reconcile(account(7, 1), &[send.clone()]);
+            assert_eq!(outcome.confirmed, vec![send]);
+            assert!(outcome.conflicts.is_empty());
+        }
+
+        // Both cannot have been applied, so neither is taken to be.
+        let (zero, one) = (send(3, 0), send(4, 1));
+        let outcome = account(10, 0).reconcile(account(7, 1), &[zero.clone(), one.clone()]);
+        assert_eq!(outcome.conflicts, vec![zero, one]);
+        assert!(outcome.confirmed.is_empty());
+    }
+
+    #[test]
+    fn an_account_moved_past_a_transaction_by_something_else_conflicts() {
+        // Something else was sent at 1, so which the account applied at 2
+        // is not known, but the send at 3 is still next.
+        let (second, third) = (send(3, 2), send(4, 3));
+        let outcome = account(10, 0).reconcile(account(5, 2), &[second.clone(), third.clone()]);
+        assert_eq!(outcome.conflicts, vec![second]);
+        assert_eq!(outcome.pending, vec![third]);
+        assert!(outcome.confirmed.is_empty());
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(1));
+
+        // The node applied another send at the nonce of one of ours.
+        let ours = send(3, 1);
+        let replaced = send(5, 1);
+        let remote = applied(account(10, 0), &[replaced.clone()]);
+        let outcome = account(10, 0).reconcile(remote, &[ours.clone(), replaced.clone()]);
+        assert_eq!(outcome.confirmed, vec![replaced]);
+        assert_eq!(outcome.conflicts, vec![ours]);
+    }
+
+    #[test]
+    fn an_account_behind_what_was_read_before_is_still_taken() {
+        // The node is behind; what the client sent past it stays pending,
+        // and what it sent at or below it is for the caller to resolve.
+        let (stale, next) = (send(1, 2), send(2, 4));
+        let remote = account(6, 2);
+        let outcome = account(10, 3).reconcile(remote.clone(), &[stale.clone(), next.clone()]);
+        assert_eq!(outcome.account, remote);
+        assert_eq!(outcome.conflicts, vec![stale]);
+        assert_eq!(outcome.pending, vec![next]);
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(4));
+    }
+
+    #[test]
+    fn holds_of_transactions_no_longer_pending_are_released() {
+        let (first, second) = (send(3, 1), send(4, 2));
+        let mut remote = account(7, 1);
+        remote.reserve_debit(&first);
+        remote.reserve_debit(&second);
+        let outcome = account(10, 0).reconcile(remote, &[first, second.clone()]);
+        assert_eq!(outcome.pending, vec![second.clone()]);
+        assert_eq!(
+            outcome
+                .account
+                .pending_debits()
+                .inner()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec![&second.hash_string()]
+        );
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(3));
+    }
+
+    #[test]
+    fn reconciling_is_the_same_however_the_transactions_are_given() {
+        let transactions = [send(3, 1), send(4, 2), send(1, 3), send_from(9, 5, 1)];
+        let local = account(10, 0);
+        let remote = account(7, 1);
+        let outcome = local.reconcile(remote.clone(), &transactions);
+        assert_eq!(outcome.confirmed, vec![transactions[0].clone()]);
+        // Another account's transaction is left pending as it is, holding
+        // nothing of this one.
+        assert_eq!(
+            outcome.pending,
+            vec![
+                transactions[3].clone(),
+                transactions[1].clone(),
+                transactions[2].clone()
+            ]
+        );
+        assert_eq!(outcome.account.spendable(&program_id()), U256::from(2));
+
+        let mut given = transactions.to_vec();
+        given.reverse();
+        given.extend(transactions.iter().cloned());
+        let again = local.reconcile(remote.clone(), &given);
+        assert_eq!(again, outcome);
+        assert_eq!(
+            again.account.pending_debits().inner(),
+            outcome.account.pending_debits().inner()
+        );
+        assert_eq!(local, account(10, 0));
+    }
+}
@@ -0,0 +1,193 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// The error codes the RPC server answers with, so clients can tell failures
+/// apart without reading messages. Clients branch on the numbers, so a code
+/// is never changed or given to another failure. -32007 to -32011 are left to
+/// the JSON-RPC server, which turns oversized requests and batches away with
+/// them before a method is called.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum RpcErrorCode {
+    /// The sender holds less of a token than the transaction takes.
+    InsufficientBalance,
+    /// The nonce has been used already, is too far ahead, or is held by
+    /// another pending transaction.
+    InvalidNonce,
+    /// The pending pool is full, or the transaction was evicted from it.
+    MempoolFull,
+    /// The program called failed, with the reason in the error data.
+    ProgramReverted,
+    /// What was asked for does not exist.
+    NotFound,
+    /// The signature could not be recovered to any signer.
+    InvalidSignature,
+    /// The signature was made by someone other than the sender.
+    SenderMismatch,
+    /// A call names no program.
+    MissingProgramId,
+    /// The transaction was submitted before and is still pending.
+    AlreadyPending,
+    /// The transaction was submitted before and has been applied, with its
+    /// receipt in the error data.
+    AlreadyIncluded,
+    /// Another transaction took the transaction's place before it was
+    /// validated.
+    Replaced,
+    /// The transaction waited too long in the pending pool.
+    Expired,
+    /// The client has made more requests than it is allowed for now, with
+    /// how long to wait in the error data.
+    RateLimited,
+    /// The method is not enabled on this node.
+    Disabled,
+    /// The caller is not authenticated for the method.
+    Unauthorized,
+    /// The most the transaction would pay in fees is under the node's fee
+    /// floor, with both in the error data.
+    FeeTooLow,
+    /// The call failed through no fault of its own as often as it is
+    /// retried, and was parked in the dead letters.
+    ExhaustedRetries,
+    /// The call ran past its deadline and the executor could not confirm it
+    /// was cancelled in time, so its outputs may still be applied.
+    DeadlineExceeded,
+    /// The node is shutting down and no longer takes transactions.
+    ShuttingDown,
+    /// The node is overloaded and turns writes away for now, with how long
+    /// to wait in the error data.
+    Overloaded,
+    /// The admission policy in force denies the transaction, with the rule
+    /// it broke in the error data.
+    PolicyDenied,
+    /// The node follows a primary and serves reads only.
+    ReadOnly,
+    /// What was asked for is past the node's retention and was pruned, with
+    /// where to retrieve it from the DA layer in the error data.
+    Pruned,
+    InvalidParams,
+    #[default]
+    Internal,
+}
+
+impl RpcErrorCode {
+    pub const ALL: [RpcErrorCode; 25] = [
+        RpcErrorCode::InsufficientBalance,
+        RpcErrorCode::InvalidNonce,
+        RpcErrorCode::MempoolFull,
+        RpcErrorCode::ProgramReverted,
+        RpcErrorCode::NotFound,
+        RpcErrorCode::InvalidSignature,
+        RpcErrorCode::SenderMismatch,
+        RpcErrorCode::MissingProgramId,
+        RpcErrorCode::AlreadyPending,
+        RpcErrorCode::AlreadyIncluded,
+        RpcErrorCode::Replaced,
+        RpcErrorCode::Expired,
+        RpcErrorCode::RateLimited,
+        RpcErrorCode::Disabled,
+        RpcErrorCode::Unauthorized,
+        RpcErrorCode::FeeTooLow,
+        RpcErrorCode::ExhaustedRetries,
+        RpcErrorCode::DeadlineExceeded,
+        RpcErrorCode::ShuttingDown,
+        RpcErrorCode::Overloaded,
+        RpcErrorCode::PolicyDenied,
+        RpcErrorCode::ReadOnly,
+        RpcErrorCode::Pruned,
+        RpcErrorCode::InvalidParams,
+        RpcErrorCode::Internal,
+    ];
+
+    pub const fn code(self) -> i32 {
+        match self {
+            RpcErrorCode::InsufficientBalance => -32001,
+            RpcErrorCode::InvalidNonce => -32002,
+            RpcErrorCode::MempoolFull => -32003,
+            RpcErrorCode::ProgramReverted => -32004,
+            RpcErrorCode::NotFound => -32005,
+            RpcErrorCode::InvalidSignature => -32006,
+            RpcErrorCode::SenderMismatch => -32012,
+            RpcErrorCode::MissingProgramId => -32013,
+            RpcErrorCode::AlreadyPending => -32014,
+            RpcErrorCode::AlreadyIncluded => -32015,
+            RpcErrorCode::Replaced => -32016,
+            RpcErrorCode::Expired => -32017,
+            RpcErrorCode::RateLimited => -32018,
+            RpcErrorCode::Disabled => -32019,
+            RpcErrorCode::Unauthorized => -32020,
+            RpcErrorCode::FeeTooLow => -32021,
+            RpcErrorCode::ExhaustedRetries => -32022,
+            RpcErrorCode::DeadlineExceeded => -32023,
+            RpcErrorCode::ShuttingDown => -32024,
+            RpcErrorCode::Overloaded => -32025,
+            RpcErrorCode::PolicyDenied => -32026,
+            RpcErrorCode::ReadOnly => -32027,
+            RpcErrorCode::Pruned => -32028,
+            RpcErrorCode::InvalidParams => -32602,
+            RpcErrorCode::Internal => -32603,
+        }
+    }
+
+    /// The code answered as `code`, if it is one of these.
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.code() == code)
+    }
+
+    /// What the code means, in a few words.
+    pub const fn message(self) -> &'static str {
+        match self {
+            RpcErrorCode::InsufficientBalance => "insufficient balance",
+            RpcErrorCode::InvalidNonce => "invalid nonce",
+            RpcErrorCode::MempoolFull => "mempool full",
+            RpcErrorCode::ProgramReverted => "program reverted",
+            RpcErrorCode::NotFound => "not found",
+            RpcErrorCode::InvalidSignature => "invalid signature",
+            RpcErrorCode::SenderMismatch => "signed by someone other than the sender",
+            RpcErrorCode::MissingProgramId => "missing program id",
+            RpcErrorCode::AlreadyPending => "already pending",
+            RpcErrorCode::AlreadyIncluded => "already included",
+            RpcErrorCode::Replaced => "replaced",
+            RpcErrorCode::Expired => "expired",
+            RpcErrorCode::RateLimited => "rate limited",
+            RpcErrorCode::Disabled => "disabled",
+            RpcErrorCode::Unauthorized => "unauthorized",
+            RpcErrorCode::FeeTooLow => "fee too low",
+            RpcErrorCode::ExhaustedRetries => "exhausted retries",
+            RpcErrorCode::DeadlineExceeded => "deadline exceeded",
+            RpcErrorCode::ShuttingDown => "shutting down",
+            RpcErrorCode::Overloaded => "node overloaded",
+            RpcErrorCode::PolicyDenied => "denied by policy",
+            RpcErrorCode::ReadOnly => "read-only node",
+            RpcErrorCode::Pruned => "pruned",
+            RpcErrorCode::InvalidParams => "invalid params",
+            RpcErrorCode::Internal => "internal error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod rpc_error_code_tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_read_back_as_what_they_were_answered_for() {
+        for code in RpcErrorCode::ALL {
+            assert_eq!(RpcErrorCode::from_code(code.code()), Some(code));
+        }
+        assert_eq!(RpcErrorCode::from_code(-32009), None);
+        assert_eq!(RpcErrorCode::from_code(0), None);
+    }
+}
@@ -1,21 +1,47 @@
-use crate::{deserialize_sig_bytes_or_string, Address};
+use crate::{deserialize_sig_bytes_or_string, hex_bytes, Address, U256};
+use borsh::{BorshDeserialize, BorshSerialize};
 use derive_builder::Builder;
-use ethers_core::types::Signature as ElectrumSignature;
 use schemars::JsonSchema;
 use secp256k1::{
     ecdsa::{RecoverableSignature as Signature, RecoveryId},
-    Message, PublicKey,
+    Message, PublicKey, Secp256k1, SecretKey,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::MapAccess, Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::BTreeSet;
+use thiserror::Error;
 
-// Custom serializer for byte arrays to hex strings
-fn serialize_as_hex<S>(bytes: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    let hex_string = hex::encode(bytes);
-    serializer.serialize_str(&format!("0x{}", hex_string))
+/// The order of the secp256k1 group, big endian.
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// Half the order of the group, the greatest `s` that is not malleable.
+const HALF_CURVE_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Why a signature does not recover, or recovers to the wrong key.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SigError {
+    #[error("recovery id {0} is not 0, 1, 27 or 28")]
+    InvalidRecoveryId(i32),
+
+    #[error("`{0}` is not in the range 1 to the curve order")]
+    OutOfRange(&'static str),
+
+    #[error("`s` is in the upper half of the curve order, so the signature is malleable")]
+    HighS,
+
+    #[error("signature recovers no key: {0}")]
+    Unrecoverable(#[from] secp256k1::Error),
+
+    #[error("signed by {recovered}, not by {expected}")]
+    WrongSigner {
+        expected: Address,
+        recovered: Address,
+    },
 }
 
 /// Represents a recoverable ECDSA signature.
@@ -24,68 +50,235 @@ where
 /// two 32-byte arrays `r` and `s`, and a recovery id `v`. The signature can be
 /// used in cryptographic operations where the public key needs to be recovered
 /// from the signature and the original message.
+///
+/// Human-readable formats such as JSON write it as the `0x` hex of its 65
+/// bytes, `r`, `s` and then `v`, and read the object of the three it was
+/// written as before. Compact formats such as bincode keep the three.
 #[derive(
-    Builder, Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Builder,
+    Clone,
+    Debug,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 pub struct RecoverableSignature {
+    r: [u8; 32],
+    s: [u8; 32],
+    v: i32,
+}
+
+/// The signature as compact formats hold it, and as JSON held it before.
+#[derive(Serialize, Deserialize)]
+struct SignatureParts {
     #[serde(
-        serialize_with = "serialize_as_hex",
+        serialize_with = "hex_bytes::serialize",
         deserialize_with = "deserialize_sig_bytes_or_string"
     )]
     r: [u8; 32],
     #[serde(
-        serialize_with = "serialize_as_hex",
+        serialize_with = "hex_bytes::serialize",
         deserialize_with = "deserialize_sig_bytes_or_string"
     )]
     s: [u8; 32],
     v: i32,
 }
 
-impl RecoverableSignature {
-    /// Recovers the public key from the signature and the original message.
-    ///
-    /// This method takes a message slice as input, computes its SHA3-256 hash,
-    /// and then attempts to recover the public key that was used to sign the message.
-    /// It returns the recovered public key if successful.
-    pub fn recover(&self, message_bytes: &[u8]) -> Result<Address, secp256k1::Error> {
-        let r_hex = hex::encode(self.get_r());
-        let s_hex = hex::encode(self.get_s());
-        tracing::warn!("attempting to recover from r: {}, s: {}", r_hex, s_hex);
-        tracing::warn!("MessageBytes: {}", hex::encode(message_bytes));
-        if self.v >= 27 && self.v <= 28 {
-            tracing::warn!("v is >= 27, <= 28, using electrum signature");
-            let esig = ElectrumSignature {
-                r: ethers_core::abi::ethereum_types::U256::from(self.get_r()),
-                s: ethers_core::abi::ethereum_types::U256::from(self.get_s()),
-                v: self.get_v() as u64,
-            };
-
-            let eaddr = esig
-                .recover(message_bytes)
-                .map_err(|_| secp256k1::Error::InvalidSignature)?;
-            tracing::warn!("{:?}", eaddr);
-
-            Ok(Address::from(eaddr))
+impl From<SignatureParts> for RecoverableSignature {
+    fn from(SignatureParts { r, s, v }: SignatureParts) -> Self {
+        Self { r, s, v }
+    }
+}
+
+impl Serialize for RecoverableSignature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if !serializer.is_human_readable() {
+            let Self { r, s, v } = *self;
+            return SignatureParts { r, s, v }.serialize(serializer);
+        }
+        let v = u8::try_from(self.v).map_err(|_| {
+            serde::ser::Error::custom(format!("recovery id {} is not one byte", self.v))
+        })?;
+        serializer.serialize_str(&format!(
+            "0x{}{}{:02x}",
+            hex::encode(self.r),
+            hex::encode(self.s),
+            v
+        ))
+    }
+}
+
+impl<'de> Deserialize<'de> for RecoverableSignature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(SignatureVisitor)
         } else {
-            let message = Message::from_digest_slice(message_bytes)?;
-            let secp = secp256k1::Secp256k1::new();
-            let recoverable_sig = Signature::try_from(self)?;
-            let pk = secp.recover_ecdsa(&message, &recoverable_sig)?;
-            Ok(Address::from(pk))
+            SignatureParts::deserialize(deserializer).map(Self::from)
         }
     }
+}
 
-    pub fn verify(&self, message: &[u8], pk: PublicKey) -> Result<(), secp256k1::Error> {
-        tracing::info!("attemting to recover signature");
-        let sig = Signature::try_from(self)?.to_standard();
-        tracing::info!("reconstructing message: {}", hex::encode(message));
-        let msg = Message::from_digest_slice(message)?;
-        tracing::info!(
-            "verifying message: {} with pubkey: {}",
-            hex::encode(message),
-            pk.to_string()
-        );
-        sig.verify(&msg, &pk)
+struct SignatureVisitor;
+
+impl<'de> serde::de::Visitor<'de> for SignatureVisitor {
+    type Value = RecoverableSignature;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("the 0x hex of a 65 byte signature, or an object of r, s and v")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<RecoverableSignature, E>
+    where
+        E: serde::de::Error,
+    {
+        let digits = v
+            .strip_prefix("0x")
+            .ok_or_else(|| E::custom("a signature must start with 0x"))?;
+        let mut bytes = [0u8; 65];
+        if digits.len() != 2 * bytes.len() {
+            return Err(E::custom(format!(
+                "a signature is 65 bytes of hex, not {} hex digits",
+                digits.len()
+            )));
+        }
+        hex::decode_to_slice(digits, &mut bytes)
+            .map_err(|e| E::custom(format!("signature is not hex: {e}")))?;
+        Ok(RecoverableSignature::from_bytes(&bytes))
+    }
+
+    /// Takes the object of `r`, `s` and `v` a signature was written as
+    /// before.
+    fn visit_map<A>(self, map: A) -> Result<RecoverableSignature, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        SignatureParts::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+            .map(RecoverableSignature::from)
+    }
+}
+
+/// Something that signs transactions for an address.
+pub trait Signer {
+    /// The address the signatures recover to.
+    fn address(&self) -> Address;
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> RecoverableSignature;
+}
+
+impl Signer for SecretKey {
+    fn address(&self) -> Address {
+        PublicKey::from_secret_key(&Secp256k1::signing_only(), self).into()
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> RecoverableSignature {
+        RecoverableSignature::sign(digest, self)
+    }
+}
+
+impl RecoverableSignature {
+    pub fn new(r: [u8; 32], s: [u8; 32], v: i32) -> Self {
+        Self { r, s, v }
+    }
+
+    /// Signs `digest` with `secret_key`. The signature is low-s, as
+    /// [`RecoverableSignature::recover`] needs.
+    pub fn sign(digest: &[u8; 32], secret_key: &SecretKey) -> Self {
+        Secp256k1::signing_only()
+            .sign_ecdsa_recoverable(&Message::from_digest(*digest), secret_key)
+            .into()
+    }
+
+    /// The signature of the 65 bytes `r`, `s` and `v`.
+    pub fn from_bytes(bytes: &[u8; 65]) -> Self {
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        s.copy_from_slice(&bytes[32..64]);
+        Self {
+            r,
+            s,
+            v: i32::from(bytes[64]),
+        }
+    }
+
+    /// The recovery id `v` names, 0 or 1, or 27 or 28 as Ethereum writes
+    /// them. Ids 2 and 3, for an `r` past the curve order, and chain ids
+    /// folded into `v` are not taken.
+    pub fn recovery_id(&self) -> Result<RecoveryId, SigError> {
+        match self.v {
+            0 | 1 => Ok(RecoveryId::from_i32(self.v)?),
+            27 | 28 => Ok(RecoveryId::from_i32(self.v - 27)?),
+            v => Err(SigError::InvalidRecoveryId(v)),
+        }
+    }
+
+    /// Whether `s` is in the upper half of the curve order, where anyone
+    /// holding the signature could flip it to another valid for the same
+    /// digest.
+    pub fn is_high_s(&self) -> bool {
+        self.s > HALF_CURVE_ORDER
+    }
+
+    /// The same signature with `s` in the lower half of the curve order,
+    /// for signers that do not normalize it. Its recovery id flips with it.
+    pub fn normalize_s(self) -> Self {
+        if !self.is_high_s() {
+            return self;
+        }
+        let mut s = [0u8; 32];
+        (U256::from_big_endian(&CURVE_ORDER) - U256::from_big_endian(&self.s))
+            .to_big_endian(&mut s);
+        let v = match self.v {
+            0 | 1 | 27 | 28 => self.v ^ 1,
+            v => v,
+        };
+        Self { s, v, ..self }
+    }
+
+    /// Recovers the address that signed `digest`. The recovery id must be
+    /// one [`RecoverableSignature::recovery_id`] takes, `r` and `s` must be
+    /// in range, and `s` must be low, so no signature has a malleable twin.
+    pub fn recover(&self, digest: &[u8; 32]) -> Result<Address, SigError> {
+        let recovery_id = self.recovery_id()?;
+        for (name, scalar) in [("r", &self.r), ("s", &self.s)] {
+            if *scalar == [0u8; 32] || *scalar >= CURVE_ORDER {
+                return Err(SigError::OutOfRange(name));
+            }
+        }
+        if self.is_high_s() {
+            return Err(SigError::HighS);
+        }
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&self.r);
+        compact[32..].copy_from_slice(&self.s);
+        let signature = Signature::from_compact(&compact, recovery_id)?;
+        let public_key = Secp256k1::verification_only()
+            .recover_ecdsa(&Message::from_digest(*digest), &signature)?;
+        Ok(Address::from(public_key))
+    }
+
+    /// Checks `expected` signed `digest`, as [`RecoverableSignature::recover`]
+    /// recovers it.
+    pub fn verify(&self, digest: &[u8; 32], expected: &Address) -> Result<(), SigError> {
+        let recovered = self.recover(digest)?;
+        if recovered != *expected {
+            return Err(SigError::WrongSigner {
+                expected: *expected,
+                recovered,
+            });
+        }
+        Ok(())
     }
 
     /// Converts the signature into a vector of bytes.
@@ -125,6 +318,31 @@ impl RecoverableSignature {
     }
 }
 
+/// What a transaction's signature is over: its digest, or its EIP-712 typed
+/// data, as browser wallets sign.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureScheme {
+    #[default]
+    Digest,
+    TypedData,
+}
+
 impl From<Signature> for RecoverableSignature {
     fn from(value: Signature) -> Self {
         let (v, rs) = value.serialize_compact();
@@ -241,3 +459,141 @@ impl Certificate {
         serde_json::from_str(&String::from_utf8_lossy(bytes))
     }
 }
+
+#[cfg(test)]
+mod signing_tests {
+    use super::*;
+
+    /// The key ethers' documentation signs with.
+    const KEY: [u8; 32] = [
+        0x4c, 0x08, 0x83, 0xa6, 0x91, 0x02, 0x93, 0x7d, 0x62, 0x31, 0x47, 0x1b, 0x5d, 0xbb, 0x62,
+        0x04, 0xfe, 0x51, 0x29, 0x61, 0x70, 0x82, 0x79, 0x2a, 0xe4, 0x68, 0xd0, 0x1a, 0x3f, 0x36,
+        0x23, 0x18,
+    ];
+
+    fn bytes(hex: &str) -> [u8; 32] {
+        hex::decode(hex).unwrap().try_into().unwrap()
+    }
+
+    fn signer() -> Address {
+        "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+            .parse()
+            .unwrap()
+    }
+
+    /// Keccak256 of `lasr`.
+    fn digest() -> [u8; 32] {
+        bytes("9fd8ffa43f6ed1d83e22e6595f930536c38ac641dd9c3287eb6040b75c779504")
+    }
+
+    /// [`digest`] as [`KEY`] signs it.
+    fn signed() -> RecoverableSignature {
+        RecoverableSignature::new(
+            bytes("ae8e8eff392ee9d274cac9ab10b92467059d0c2230a235b4713c518fa61af396"),
+            bytes("633789df7ccc92d313ce3b6b7fd0d099d299573b80d4bd7dc4a354eae49d35fa"),
+            0,
+        )
+    }
+
+    #[test]
+    fn a_signature_recovers_to_the_key_that_made_it() {
+        let signature =
+            RecoverableSignature::sign(&digest(), &SecretKey::from_slice(&KEY).unwrap());
+        assert_eq!(signature, signed());
+        assert_eq!(signed().recover(&digest()), Ok(signer()));
+        assert_eq!(signed().verify(&digest(), &signer()), Ok(()));
+        assert_eq!(
+            signed().verify(&digest(), &Address::new([1; 20])),
+            Err(SigError::WrongSigner {
+                expected: Address::new([1; 20]),
+                recovered: signer(),
+            })
+        );
+        let mut other = digest();
+        other[0] ^= 1;
+        assert_ne!(signed().recover(&other).ok(), Some(signer()));
+
+        // Ethereum writes the recovery id plus 27.
+        let ethereum = RecoverableSignature::new(signed().r, signed().s, 27);
+        assert_eq!(ethereum.recover(&digest()), Ok(signer()));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let (r, s) = (signed().r, signed().s);
+        for v in [2, 3, 26, 29, 35, 37, -1, 256] {
+            assert_eq!(
+                RecoverableSignature::new(r, s, v).recover(&digest()),
+                Err(SigError::InvalidRecoveryId(v))
+            );
+        }
+        for (malformed, name) in [
+            (RecoverableSignature::new([0; 32], s, 0), "r"),
+            (RecoverableSignature::new(CURVE_ORDER, s, 0), "r"),
+            (RecoverableSignature::new(r, [0; 32], 0), "s"),
+            (RecoverableSignature::new(r, CURVE_ORDER, 0), "s"),
+        ] {
+            assert_eq!(
+                malformed.recover(&digest()),
+                Err(SigError::OutOfRange(name))
+            );
+        }
+
+        // The high-s twin of a signature is as valid to secp256k1, and is
+        // rejected rather than recovered.
+        let twin = RecoverableSignature::new(
+            r,
+            bytes("9cc8762083336d2cec31c494802f2f64e81585ab2e73e2bdfb2f09a1eb990b47"),
+            1,
+        );
+        assert!(twin.is_high_s());
+        assert_eq!(twin.recover(&digest()), Err(SigError::HighS));
+        assert_eq!(twin.verify(&digest(), &signer()), Err(SigError::HighS));
+        assert_eq!(twin.clone().normalize_s(), signed());
+        assert_eq!(signed().normalize_s(), signed());
+        let ethereum_twin = RecoverableSignature::new(twin.r, twin.s, 28);
+        assert_eq!(ethereum_twin.normalize_s().recover(&digest()), Ok(signer()));
+    }
+
+    #[test]
+    fn json_gets_the_hex_of_the_65_bytes() {
+        let hex = "0xae8e8eff392ee9d274cac9ab10b92467059d0c2230a235b4713c518fa61af396\
+             633789df7ccc92d313ce3b6b7fd0d099d299573b80d4bd7dc4a354eae49d35fa00"
+            .to_string();
+        assert_eq!(
+            serde_json::to_value(signed()).unwrap(),
+            serde_json::Value::from(hex.clone())
+        );
+        let read = |json: serde_json::Value| serde_json::from_value::<RecoverableSignature>(json);
+        assert_eq!(read(hex.clone().into()).unwrap(), signed());
+
+        // Signatures were written as an object of the three before.
+        let legacy = serde_json::json!({
+            "r": format!("0x{}", hex::encode(signed().r)),
+            "s": format!("0x{}", hex::encode(signed().s)),
+            "v": 0,
+        });
+        assert_eq!(read(legacy).unwrap(), signed());
+
+        assert!(read(hex[2..].into()).is_err());
+        assert!(read(hex[..hex.len() - 2].into()).is_err());
+        assert!(read(format!("{}zz", &hex[..hex.len() - 2]).into()).is_err());
+        assert!(serde_json::to_value(RecoverableSignature::new([1; 32], [1; 32], 256)).is_err());
+    }
+
+    #[test]
+    fn bincode_keeps_the_three_parts() {
+        let bytes = bincode::serialize(&signed()).unwrap();
+        assert_eq!(bytes.len(), (8 + 32) * 2 + 4);
+        assert!(!bytes.windows(2).any(|pair| pair == b"0x"));
+        let RecoverableSignature { r, s, v } = signed();
+        assert_eq!(
+            bincode::serialize(&SignatureParts { r, s, v }).unwrap(),
+            bytes
+        );
+        assert_eq!(
+            bincode::deserialize::<RecoverableSignature>(&bytes).unwrap(),
+            signed()
+        );
+    }
+}
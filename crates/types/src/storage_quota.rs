@@ -0,0 +1,240 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+use crate::{Account, AccountType, Address, Token};
+
+pub const DEFAULT_MAX_TOKEN_BYTES: usize = 16 * 1024;
+pub const DEFAULT_MAX_ACCOUNT_BYTES: usize = 64 * 1024;
+
+/// How many bytes of metadata and data programs may keep in accounts, so no
+/// program can grow an account without bound and bloat every batch it goes
+/// into after. A token is held to `max_token_bytes`, and an account to
+/// `max_account_bytes` across its tokens and its own program metadata and
+/// data. The tokens of the `exempt` programs, and their program accounts,
+/// are not counted.
+///
+/// An update that leaves a token or an account no larger than it was is
+/// never refused, so one over a budget lowered since can still shrink.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageQuota {
+    pub max_token_bytes: usize,
+    pub max_account_bytes: usize,
+    pub exempt: BTreeSet<Address>,
+}
+
+impl Default for StorageQuota {
+    fn default() -> Self {
+        Self {
+            max_token_bytes: DEFAULT_MAX_TOKEN_BYTES,
+            max_account_bytes: DEFAULT_MAX_ACCOUNT_BYTES,
+            exempt: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum StorageQuotaError {
+    #[error("the token of program {program_id} would hold {size} bytes of metadata and data, over the limit of {limit}")]
+    TokenOverQuota {
+        program_id: String,
+        size: usize,
+        limit: usize,
+    },
+
+    #[error(
+        "account {address} would hold {size} bytes of metadata and data, over the limit of {limit}"
+    )]
+    AccountOverQuota {
+        address: String,
+        size: usize,
+        limit: usize,
+    },
+}
+
+impl StorageQuota {
+    pub fn is_exempt(&self, program_id: &Address) -> bool {
+        self.exempt.contains(program_id)
+    }
+
+    /// Checks `after`, the token as an update leaves it, which held
+    /// `before_size` bytes before it.
+    pub fn check_token(&self, before_size: usize, after: &Token) -> Result<(), StorageQuotaError> {
+        let size = after.storage_size();
+        if self.is_exempt(&after.program_id())
+            || size <= self.max_token_bytes
+            || size <= before_size
+        {
+            return Ok(());
+        }
+        Err(StorageQuotaError::TokenOverQuota {
+            program_id: after.program_id().to_full_string(),
+            size,
+            limit: self.max_token_bytes,
+        })
+    }
+
+    /// The bytes of `account` counted against its budget.
+    pub fn account_size(&self, account: &Account) -> usize {
+        let own = match account.account_type() {
+            AccountType::Program(_) => {
+                map_size(account.program_account_metadata().inner())
+                    + map_size(account.program_account_data().inner())
+            }
+            AccountType::User => 0,
+        };
+        own + account
+            .tokens_iter()
+            .map(|(_, token)| token)
+            .filter(|token| !self.is_exempt(&token.program_id()))
+            .map(Token::storage_size)
+            .sum::<usize>()
+    }
+
+    /// Checks `after`, the account as a transaction leaves it, against how
+    /// it was `before`, if it existed.
+    pub fn check_account(
+        &self,
+        before: Option<&Account>,
+        after: &Account,
+    ) -> Result<(), StorageQuotaError> {
+        let address = match after.account_type() {
+            AccountType::Program(program_address) => program_address,
+            AccountType::User => after.owner_address(),
+        };
+        let size = self.account_size(after);
+        let before_size = before.map(|account| self.account_size(account));
+        if self.is_exempt(&address)
+            || size <= self.max_account_bytes
+            || before_size.is_some_and(|before_size| size <= before_size)
+        {
+            return Ok(());
+        }
+        Err(StorageQuotaError::AccountOverQuota {
+            address: address.to_full_string(),
+            size,
+            limit: self.max_account_bytes,
+        })
+    }
+}
+
+/// Bytes in every key and value of `map`.
+pub(crate) fn map_size(map: &BTreeMap<String, String>) -> usize {
+    map.iter().map(|(key, value)| key.len() + value.len()).sum()
+}
+
+#[cfg(test)]
+mod storage_quota_tests {
+    use super::*;
+    use crate::{
+        ArbitraryData, DataValue, Metadata, MetadataValue, Status, TokenBuilder, TokenFieldValue,
+        U256,
+    };
+
+    fn token(program_id: Address, data: &[(&str, &str)]) -> Token {
+        let mut arbitrary = ArbitraryData::new();
+        for (key, value) in data {
+            arbitrary.insert(key.to_string(), value.to_string());
+        }
+        TokenBuilder::default()
+            .program_id(program_id)
+            .owner_id(Address::new([1; 20]))
+            .balance(U256::from(0))
+            .metadata(Metadata::new())
+            .token_ids(vec![])
+            .allowance(BTreeMap::new())
+            .approvals(BTreeMap::new())
+            .data(arbitrary)
+            .status(Status::Free)
+            .build()
+            .unwrap()
+    }
+
+    fn quota(max_token_bytes: usize, max_account_bytes: usize) -> StorageQuota {
+        StorageQuota {
+            max_token_bytes,
+            max_account_bytes,
+            exempt: BTreeSet::from([Address::verse_addr()]),
+        }
+    }
+
+    fn insert(key: &str, value: &str) -> TokenFieldValue {
+        TokenFieldValue::Data(DataValue::Insert(key.to_string(), value.to_string()))
+    }
+
+    #[test]
+    fn a_token_may_fill_its_budget_to_the_byte() {
+        let quota = quota(10, 100);
+        let program_id = Address::new([2; 20]);
+        let mut token = token(program_id, &[]);
+        token
+            .apply_token_update_field_values(&insert("ab", "cdefghij"), &quota)
+            .unwrap();
+        assert_eq!(token.storage_size(), 10);
+
+        let error = token
+            .apply_token_update_field_values(
+                &TokenFieldValue::Metadata(MetadataValue::Insert("k".into(), String::new())),
+                &quota,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "the token of program {} would hold 11 bytes of metadata and data, over the limit of 10",
+                program_id.to_full_string()
+            )
+        );
+        // A refused update leaves the token as it was.
+        assert_eq!(token.storage_size(), 10);
+        assert!(token.metadata().inner().is_empty());
+    }
+
+    #[test]
+    fn a_token_over_its_budget_may_still_shrink() {
+        let quota = quota(4, 100);
+        let mut token = token(
+            Address::new([2; 20]),
+            &[("key", "value"), ("other", "value")],
+        );
+        token
+            .apply_token_update_field_values(
+                &TokenFieldValue::Data(DataValue::Remove("other".to_string())),
+                &quota,
+            )
+            .unwrap();
+        assert_eq!(token.storage_size(), 8);
+        assert!(token
+            .apply_token_update_field_values(&insert("key", "values"), &quota)
+            .is_err());
+    }
+
+    #[test]
+    fn the_tokens_of_an_exempt_program_are_not_counted() {
+        let quota = quota(8, 10);
+        let mut verse = token(Address::verse_addr(), &[]);
+        verse
+            .apply_token_update_field_values(&insert("supply", "1000000000"), &quota)
+            .unwrap();
+
+        let (first, second) = (Address::new([2; 20]), Address::new([3; 20]));
+        let mut account = Account::new(Address::new([1; 20]));
+        account.insert_program(&Address::verse_addr(), verse);
+        account.insert_program(&first, token(first, &[("ab", "cd")]));
+        account.insert_program(&second, token(second, &[("abc", "def")]));
+        assert_eq!(quota.account_size(&account), 10);
+        assert!(quota.check_account(None, &account).is_ok());
+
+        let before = account.clone();
+        account.insert_program(&second, token(second, &[("abc", "defg")]));
+        assert_eq!(
+            quota.check_account(Some(&before), &account),
+            Err(StorageQuotaError::AccountOverQuota {
+                address: Address::new([1; 20]).to_full_string(),
+                size: 11,
+                limit: 10,
+            })
+        );
+        assert!(quota.check_account(Some(&account), &before).is_ok());
+    }
+}
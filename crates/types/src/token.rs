@@ -1,15 +1,16 @@
 #![allow(clippy::assign_op_pattern)]
+use borsh::{BorshDeserialize, BorshSerialize};
 use derive_builder::Builder;
 use ethereum_types::U256 as EthU256;
 use hex::FromHexError;
 use schemars::JsonSchema;
-use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::BTreeMap;
 use std::fmt::{Debug, Display};
 use std::ops::{AddAssign, SubAssign};
 use uint::construct_uint;
 
-use crate::{Address, RecoverableSignature, Transaction};
+use crate::{hex_quantity, map_size, Address, RecoverableSignature, StorageQuota, Transaction};
 
 pub const TOKEN_WITNESS_VERSION: &str = "0.1.0";
 
@@ -20,13 +21,14 @@ construct_uint! {
     pub struct U256(4);
 }
 
+/// `U256`s are written as `0x` hex quantities to JSON, and as their bytes to
+/// bincode.
 impl Serialize for U256 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let hex_str = format!("0x{:064x}", self);
-        serializer.serialize_str(&hex_str)
+        hex_quantity::serialize(self, serializer)
     }
 }
 
@@ -35,56 +37,24 @@ impl<'de> Deserialize<'de> for U256 {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_str(U256Visitor)
+        hex_quantity::deserialize(deserializer)
     }
 }
 
-struct U256Visitor;
-
-impl<'de> Visitor<'de> for U256Visitor {
-    type Value = U256;
-
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a 64-character hex string or [u64; 4] array as a string")
+/// Borsh writes a `U256` as a 32 byte little endian integer, as it does its
+/// own integers.
+impl BorshSerialize for U256 {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut bytes = [0u8; 32];
+        self.to_little_endian(&mut bytes);
+        writer.write_all(&bytes)
     }
+}
 
-    fn visit_str<E>(self, v: &str) -> Result<U256, E>
-    where
-        E: serde::de::Error,
-    {
-        let value = if let Some(value) = v.strip_prefix("0x") {
-            value
-        } else {
-            v
-        };
-        if value.starts_with('[') && v.ends_with(']') {
-            // Parse as a byte array
-            let nums_str = &v[1..v.len() - 1];
-            let nums: Vec<u64> = nums_str
-                .split(',')
-                .map(str::trim)
-                .map(|s| s.parse::<u64>().map_err(E::custom))
-                .collect::<Result<Vec<u64>, E>>()?;
-
-            if nums.len() == 4 {
-                let arr = [nums[0], nums[1], nums[2], nums[3]];
-                Ok(U256(arr))
-            } else {
-                Err(E::custom("Array does not have 4 elements"))
-            }
-        } else if value.len() == 64 {
-            // Parse as a hex string
-            let decoded = hex::decode(value).map_err(E::custom)?;
-            if decoded.len() == 32 {
-                let mut bytes = [0u8; 32];
-                bytes.copy_from_slice(&decoded[..]);
-                Ok(U256::from(&bytes))
-            } else {
-                Err(E::custom("decoded result is improper length"))
-            }
-        } else {
-            Err(E::custom("Invalid format for U256"))
-        }
+impl BorshDeserialize for U256 {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let bytes = <[u8; 32]>::deserialize_reader(reader)?;
+        Ok(U256::from_little_endian(&bytes))
     }
 }
 
@@ -130,7 +100,19 @@ impl From<&EthU256> for U256 {
 /// It provides a default, cloneable, serializable, and debuggable interface. It is
 /// typically used for storing data that doesn't have a fixed format or structure.
 #[derive(
-    Clone, Default, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Clone,
+    Default,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub struct ArbitraryData(BTreeMap<String, String>);
@@ -187,7 +169,19 @@ impl ArbitraryData {
 /// It supports cloning, serialization, and debugging. The metadata can be of any
 /// form that fits into a byte array, making it a flexible container.
 #[derive(
-    Clone, Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Clone,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub struct Metadata(BTreeMap<String, String>);
@@ -246,7 +240,19 @@ pub enum TokenType {
 }
 
 #[derive(
-    Builder, Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Builder,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub struct Token {
@@ -262,6 +268,33 @@ pub struct Token {
 }
 
 impl Token {
+    /// The token of `program_id` as `owner_id` first holds it: free, with no
+    /// balance, ids, allowances or approvals, and nothing in its metadata or
+    /// data.
+    pub fn empty(program_id: Address, owner_id: Address) -> Self {
+        Self {
+            program_id,
+            owner_id,
+            balance: U256::from(0),
+            metadata: Metadata::new(),
+            token_ids: Vec::new(),
+            allowance: BTreeMap::new(),
+            approvals: BTreeMap::new(),
+            data: ArbitraryData::new(),
+            status: Status::Free,
+        }
+    }
+
+    /// Whether the token holds nothing: no balance, ids, allowances,
+    /// approvals or data. The metadata is the program's, not the holder's.
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_zero()
+            && self.token_ids.is_empty()
+            && self.allowance.is_empty()
+            && self.approvals.is_empty()
+            && self.data.inner().is_empty()
+    }
+
     pub(crate) fn set_metadata(&mut self, metadata: Metadata) {
         self.metadata = metadata;
     }
@@ -321,9 +354,25 @@ impl Token {
         Ok(())
     }
 
+    /// Applies `token_update_value`, unless it would grow the token past
+    /// `quota`.
     pub(crate) fn apply_token_update_field_values(
         &mut self,
         token_update_value: &TokenFieldValue,
+        quota: &StorageQuota,
+    ) -> Result<(), Box<dyn std::error::Error + Send>> {
+        let mut updated = self.clone();
+        updated.apply_field_value(token_update_value)?;
+        quota
+            .check_token(self.storage_size(), &updated)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        *self = updated;
+        Ok(())
+    }
+
+    fn apply_field_value(
+        &mut self,
+        token_update_value: &TokenFieldValue,
     ) -> Result<(), Box<dyn std::error::Error + Send>> {
         tracing::warn!("applying TokenFieldValue: {:?}", token_update_value);
         match token_update_value {
@@ -620,6 +669,12 @@ impl Token {
         &mut self.status
     }
 
+    /// Bytes in the keys and values of the token's metadata and data, which
+    /// a [`StorageQuota`] holds it to.
+    pub fn storage_size(&self) -> usize {
+        map_size(self.metadata.inner()) + map_size(self.data.inner())
+    }
+
     pub fn update_balance(&mut self, receive: U256, send: U256) {
         self.balance += receive;
         self.balance -= send;
@@ -627,7 +682,18 @@ impl Token {
 }
 
 #[derive(
-    Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub enum Status {
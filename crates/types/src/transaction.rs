@@ -1,13 +1,37 @@
-use crate::{Address, ArbitraryData, Metadata, Status, Token, TokenBuilder};
-use crate::{RecoverableSignature, RecoverableSignatureBuilder};
+use crate::{hex_bytes, Address, ArbitraryData, Metadata, Status, Token, TokenBuilder};
+use crate::{RecoverableSignature, SigError, SignatureScheme};
+use borsh::{BorshDeserialize, BorshSerialize};
 use derive_builder::Builder;
 use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize};
 use sha3::{Digest, Keccak256};
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::fmt::{Display, LowerHex};
+use std::sync::OnceLock;
 use thiserror::Error;
 
+/// The version of what transactions are hashed and signed over. Any change
+/// to [`Transaction::as_bytes`] bumps it.
+pub const DIGEST_VERSION: u8 = 2;
+
+/// The chain transactions are signed for unless [`set_chain_id`] says
+/// otherwise, `LASR` in ASCII.
+pub const DEFAULT_CHAIN_ID: u64 = 0x4c41_5352;
+
+static CHAIN_ID: OnceLock<u64> = OnceLock::new();
+
+/// Sets the chain transaction digests commit to, for the rest of the process.
+/// False if a digest was already taken, or the chain set, for another.
+pub fn set_chain_id(chain_id: u64) -> bool {
+    *CHAIN_ID.get_or_init(|| chain_id) == chain_id
+}
+
+/// The chain transaction digests commit to.
+pub fn chain_id() -> u64 {
+    *CHAIN_ID.get_or_init(|| DEFAULT_CHAIN_ID)
+}
+
 #[derive(Clone, Debug, Error)]
 pub enum ToTokenError {
     Custom(String),
@@ -20,7 +44,18 @@ impl Display for ToTokenError {
 }
 
 #[derive(
-    Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "camelCase")]
 pub enum TransactionType {
@@ -29,6 +64,66 @@ pub enum TransactionType {
     Call(crate::U256),
     BridgeOut(crate::U256),
     RegisterProgram(crate::U256),
+    Burn(crate::U256),
+    /// Grants or revokes a delegation of the sender's, as its inputs say.
+    Delegate(crate::U256),
+    /// Registers the program whose package is at `content_id`, to run in
+    /// `runtime`.
+    #[serde(rename_all = "camelCase")]
+    Deploy {
+        nonce: crate::U256,
+        content_id: String,
+        runtime: DeployRuntime,
+    },
+    /// Takes `value` of the program's token off the chain, to `recipient` on
+    /// the settlement layer.
+    #[serde(rename_all = "camelCase")]
+    BridgeOutTo {
+        nonce: crate::U256,
+        recipient: Address,
+    },
+    /// Burns the transaction's value of the sender's balance of the
+    /// program's token.
+    #[serde(rename_all = "camelCase")]
+    BurnAmount {
+        nonce: crate::U256,
+    },
+    /// Sets the sender's account metadata to what its inputs hold.
+    SetAccountMetadata(crate::U256),
+}
+
+/// The runtime a [`TransactionType::Deploy`] says its program runs in, as
+/// it is recorded on the program's account.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployRuntime {
+    #[default]
+    Container,
+    Wasm,
+}
+
+impl Display for DeployRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeployRuntime::Container => write!(f, "container"),
+            DeployRuntime::Wasm => write!(f, "wasm"),
+        }
+    }
 }
 
 impl TransactionType {
@@ -52,6 +147,89 @@ impl TransactionType {
         matches!(self, TransactionType::RegisterProgram(_))
     }
 
+    pub fn is_burn(&self) -> bool {
+        matches!(self, TransactionType::Burn(_))
+    }
+
+    pub fn is_delegate(&self) -> bool {
+        matches!(self, TransactionType::Delegate(_))
+    }
+
+    pub fn is_deploy(&self) -> bool {
+        matches!(self, TransactionType::Deploy { .. })
+    }
+
+    pub fn is_bridge_out_to(&self) -> bool {
+        matches!(self, TransactionType::BridgeOutTo { .. })
+    }
+
+    pub fn is_burn_amount(&self) -> bool {
+        matches!(self, TransactionType::BurnAmount { .. })
+    }
+
+    pub fn is_set_account_metadata(&self) -> bool {
+        matches!(self, TransactionType::SetAccountMetadata(_))
+    }
+
+    /// Whether the type takes its value from the sender, as sends, burns
+    /// and bridge outs do.
+    pub fn debits_sender(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Send(_)
+                | TransactionType::Burn(_)
+                | TransactionType::BurnAmount { .. }
+                | TransactionType::BridgeOutTo { .. }
+        )
+    }
+
+    /// The byte the type is hashed as, 0 to 10 in the order of the variants,
+    /// and the nonce it carries.
+    pub(crate) fn tag(&self) -> (u8, crate::U256) {
+        match self {
+            Self::BridgeIn(n) => (0, *n),
+            Self::Send(n) => (1, *n),
+            Self::Call(n) => (2, *n),
+            Self::BridgeOut(n) => (3, *n),
+            Self::RegisterProgram(n) => (4, *n),
+            Self::Burn(n) => (5, *n),
+            Self::Delegate(n) => (6, *n),
+            Self::Deploy { nonce, .. } => (7, *nonce),
+            Self::BridgeOutTo { nonce, .. } => (8, *nonce),
+            Self::BurnAmount { nonce, .. } => (9, *nonce),
+            Self::SetAccountMetadata(n) => (10, *n),
+        }
+    }
+
+    /// What else the type carries, as it is hashed: the length of a deploy's
+    /// content id, big endian, the id and its runtime as a byte, or a bridge
+    /// out's recipient. None for the types that carry only a nonce.
+    pub(crate) fn data(&self) -> Vec<u8> {
+        match self {
+            Self::BridgeIn(_)
+            | Self::Send(_)
+            | Self::Call(_)
+            | Self::BridgeOut(_)
+            | Self::RegisterProgram(_)
+            | Self::Burn(_)
+            | Self::Delegate(_)
+            | Self::BurnAmount { .. }
+            | Self::SetAccountMetadata(_) => Vec::new(),
+            Self::Deploy {
+                content_id,
+                runtime,
+                ..
+            } => {
+                let mut data = Vec::with_capacity(8 + content_id.len() + 1);
+                data.extend_from_slice(&(content_id.len() as u64).to_be_bytes());
+                data.extend_from_slice(content_id.as_bytes());
+                data.push(*runtime as u8);
+                data
+            }
+            Self::BridgeOutTo { recipient, .. } => recipient.inner().to_vec(),
+        }
+    }
+
     pub fn to_json(&self) -> serde_json::Value {
         match self {
             Self::BridgeIn(n) => serde_json::json!({"bridgeIn": format!("0x{:064x}", n)}),
@@ -61,6 +239,27 @@ impl TransactionType {
                 serde_json::json!({"registerProgram": format!("0x{:064x}", n)})
             }
             Self::BridgeOut(n) => serde_json::json!({"bridgeOut": format!("0x{:064x}", n)}),
+            Self::Burn(n) => serde_json::json!({"burn": format!("0x{:064x}", n)}),
+            Self::Delegate(n) => serde_json::json!({"delegate": format!("0x{:064x}", n)}),
+            Self::Deploy {
+                nonce,
+                content_id,
+                runtime,
+            } => serde_json::json!({"deploy": {
+                "nonce": format!("0x{:064x}", nonce),
+                "contentId": content_id,
+                "runtime": runtime.to_string(),
+            }}),
+            Self::BridgeOutTo { nonce, recipient } => serde_json::json!({"bridgeOutTo": {
+                "nonce": format!("0x{:064x}", nonce),
+                "recipient": recipient.to_string(),
+            }}),
+            Self::BurnAmount { nonce } => serde_json::json!({"burnAmount": {
+                "nonce": format!("0x{:064x}", nonce),
+            }}),
+            Self::SetAccountMetadata(n) => {
+                serde_json::json!({"setAccountMetadata": format!("0x{:064x}", n)})
+            }
         }
     }
 }
@@ -73,6 +272,12 @@ impl Display for TransactionType {
             TransactionType::Call(n) => write!(f, "call{n}"),
             TransactionType::BridgeOut(n) => write!(f, "bridgeOut{n}"),
             TransactionType::RegisterProgram(n) => write!(f, "deploy{n}"),
+            TransactionType::Burn(n) => write!(f, "burn{n}"),
+            TransactionType::Delegate(n) => write!(f, "delegate{n}"),
+            TransactionType::Deploy { nonce, .. } => write!(f, "deploy{nonce}"),
+            TransactionType::BridgeOutTo { nonce, .. } => write!(f, "bridgeOut{nonce}"),
+            TransactionType::BurnAmount { nonce, .. } => write!(f, "burn{nonce}"),
+            TransactionType::SetAccountMetadata(n) => write!(f, "setAccountMetadata{n}"),
         }
     }
 }
@@ -80,20 +285,47 @@ impl Display for TransactionType {
 #[derive(
     Builder, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema, PartialOrd, Ord, Hash,
 )]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct Payload {
     transaction_type: TransactionType,
-    #[serde(deserialize_with = "deserialize_address_bytes_or_string")]
+    #[serde(
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_from"
+    )]
     from: [u8; 20],
-    #[serde(deserialize_with = "deserialize_address_bytes_or_string")]
+    #[serde(
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_to"
+    )]
     to: [u8; 20],
-    #[serde(deserialize_with = "deserialize_address_bytes_or_string")]
+    #[serde(
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_program_id"
+    )]
     program_id: [u8; 20],
     op: String,
     #[serde(rename(serialize = "transactionInputs", deserialize = "transactionInputs"))]
     inputs: String,
     value: crate::U256,
     nonce: crate::U256,
+    /// The most the sender will pay in fees, in VERSE. Unset is none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    max_fee: Option<crate::U256>,
+    /// What the sender offers over the fee floor, in VERSE.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    priority_fee: Option<crate::U256>,
+    /// The earliest the transaction may be applied, in seconds since the
+    /// epoch. Unset is at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    valid_after: Option<u64>,
+    /// When the transaction may no longer be applied, in seconds since the
+    /// epoch. Unset is never.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    valid_before: Option<u64>,
 }
 
 impl Payload {
@@ -129,36 +361,37 @@ impl Payload {
         self.nonce
     }
 
+    pub fn max_fee(&self) -> Option<crate::U256> {
+        self.max_fee
+    }
+
+    pub fn priority_fee(&self) -> Option<crate::U256> {
+        self.priority_fee
+    }
+
+    pub fn valid_after(&self) -> Option<u64> {
+        self.valid_after
+    }
+
+    pub fn valid_before(&self) -> Option<u64> {
+        self.valid_before
+    }
+
+    /// The digest of the transaction the payload is signed as.
+    pub fn digest(&self) -> [u8; 32] {
+        Transaction::from(self.clone()).digest()
+    }
+
     pub fn hash_string(&self) -> String {
-        let mut hasher = Keccak256::new();
-        hasher.update(&self.as_bytes());
-        let res = hasher.finalize();
-        format!("0x{:x}", res)
+        format!("0x{}", hex::encode(self.digest()))
     }
 
     pub fn hash(&self) -> Vec<u8> {
-        let mut hasher = Keccak256::new();
-        hasher.update(&self.as_bytes());
-        let res = hasher.finalize();
-        tracing::info!("transaction hash: 0x{:x}", res);
-        res.to_vec()
+        self.digest().to_vec()
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        let transaction_json = serde_json::json!({
-            "transactionType": self.transaction_type().to_json(),
-            "from": Address::from(self.from()).to_full_string(),
-            "to": Address::from(self.to()).to_full_string(),
-            "programId": Address::from(self.program_id()).to_full_string(),
-            "op": self.op.clone(),
-            "transactionInputs": self.inputs().clone(),
-            "value": format!("0x{:064x}", self.value()),
-            "nonce": format!("0x{:064x}", self.nonce())
-        })
-        .to_string();
-
-        tracing::info!("converted payload to json: {}", &transaction_json);
-        transaction_json.as_bytes().to_vec()
+        Transaction::from(self.clone()).as_bytes()
     }
 }
 
@@ -169,50 +402,83 @@ pub enum HexOr20Bytes {
     Bytes([u8; 20]),
 }
 
-// Custom serializer for byte arrays to hex strings
-fn serialize_as_hex<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    let hex_string = hex::encode(bytes);
-    serializer.serialize_str(&format!("0x{}", hex_string))
+/// An `N` byte field as it is sent: hex, a string of a bracketed list of
+/// bytes, or the list itself.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HexOrByteList {
+    Hex(String),
+    Bytes(Vec<u8>),
 }
 
-pub fn deserialize_address_bytes_or_string<'de, D>(deserializer: D) -> Result<[u8; 20], D::Error>
+/// Decodes the `N` bytes of `field`, checking the length of its hex before
+/// decoding it. Hex without a `0x` prefix is only taken if `bare_hex`.
+/// Compact formats, which cannot tell hex from a list, carry the bytes.
+fn fixed_bytes<'de, D, const N: usize>(
+    field: &'static str,
+    bare_hex: bool,
+    deserializer: D,
+) -> Result<[u8; N], D::Error>
 where
     D: Deserializer<'de>,
 {
-    let input = HexOr20Bytes::deserialize(deserializer)?;
-    match input {
-        HexOr20Bytes::Hex(value) => {
+    let sized = |bytes: Vec<u8>| -> Result<[u8; N], D::Error> {
+        let len = bytes.len();
+        bytes.try_into().map_err(|_| {
+            serde::de::Error::custom(format!("`{field}` must be {N} bytes, not {len}"))
+        })
+    };
+    if !deserializer.is_human_readable() {
+        return sized(Vec::<u8>::deserialize(deserializer)?);
+    }
+    let decode_hex = |digits: &str| -> Result<[u8; N], D::Error> {
+        if digits.len() != 2 * N {
+            return Err(serde::de::Error::custom(format!(
+                "`{field}` must be {N} bytes of hex, not {} hex digits",
+                digits.len()
+            )));
+        }
+        let mut bytes = [0; N];
+        hex::decode_to_slice(digits, &mut bytes)
+            .map_err(|e| serde::de::Error::custom(format!("`{field}` is not hex: {e}")))?;
+        Ok(bytes)
+    };
+    let bytes = match HexOrByteList::deserialize(deserializer).map_err(|_| {
+        serde::de::Error::custom(format!("`{field}` is neither hex nor a list of bytes"))
+    })? {
+        HexOrByteList::Hex(value) => {
             if let Some(value) = value.strip_prefix("0x") {
-                let bytes =
-                    hex::decode(value).map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                bytes.try_into().map_err(|_| {
-                    serde::de::Error::custom("Hex string does not represent valid 20-byte array")
-                })
+                return decode_hex(value);
             } else if value.starts_with('[') && value.ends_with(']') {
-                let bytes_str = &value[1..value.len() - 1];
-                let bytes: Vec<u8> = bytes_str
+                value[1..value.len() - 1]
                     .split(',')
                     .map(str::trim)
-                    .map(|s| s.parse::<u8>().map_err(serde::de::Error::custom))
-                    .collect::<Result<Vec<u8>, D::Error>>()?;
-                bytes.try_into().map_err(|_| {
-                    serde::de::Error::custom(
-                        "Bytes string does not represent a value 20-byte array",
-                    )
-                })
+                    .map(|s| {
+                        s.parse::<u8>().map_err(|e| {
+                            serde::de::Error::custom(format!(
+                                "`{field}` is not a list of bytes: {e}"
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<u8>, D::Error>>()?
+            } else if bare_hex {
+                return decode_hex(&value);
             } else {
-                let bytes =
-                    hex::decode(&value).map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                bytes.try_into().map_err(|_| {
-                    serde::de::Error::custom("Hex string does not represent valid 20 byte array")
-                })
+                return Err(serde::de::Error::custom(format!(
+                    "`{field}` is neither hex nor a list of bytes"
+                )));
             }
         }
-        HexOr20Bytes::Bytes(v) => Ok(v),
-    }
+        HexOrByteList::Bytes(bytes) => bytes,
+    };
+    sized(bytes)
+}
+
+pub fn deserialize_address_bytes_or_string<'de, D>(deserializer: D) -> Result<[u8; 20], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    fixed_bytes("address", true, deserializer)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -226,56 +492,62 @@ pub fn deserialize_sig_bytes_or_string<'de, D>(deserializer: D) -> Result<[u8; 3
 where
     D: Deserializer<'de>,
 {
-    let input = HexOr32Bytes::deserialize(deserializer)?;
-    match input {
-        HexOr32Bytes::Hex(value) => {
-            if let Some(value) = value.strip_prefix("0x") {
-                let bytes =
-                    hex::decode(value).map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                bytes.try_into().map_err(|_| {
-                    serde::de::Error::custom("Hex string does not represent valid 20-byte array")
-                })
-            } else if value.starts_with('[') && value.ends_with(']') {
-                let bytes_str = &value[1..value.len() - 1];
-                let bytes: Vec<u8> = bytes_str
-                    .split(',')
-                    .map(str::trim)
-                    .map(|s| s.parse::<u8>().map_err(serde::de::Error::custom))
-                    .collect::<Result<Vec<u8>, D::Error>>()?;
-                bytes.try_into().map_err(|_| {
-                    serde::de::Error::custom(
-                        "Bytes string does not represent a value 20-byte array",
-                    )
-                })
-            } else {
-                Err(serde::de::Error::custom(
-                    "unable to deserialize as a string",
-                ))
-            }
-        }
-        HexOr32Bytes::Bytes(v) => Ok(v),
-    }
+    fixed_bytes("signature", false, deserializer)
+}
+
+fn deserialize_from<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 20], D::Error> {
+    fixed_bytes("from", true, deserializer)
+}
+
+fn deserialize_to<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 20], D::Error> {
+    fixed_bytes("to", true, deserializer)
+}
+
+fn deserialize_program_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<[u8; 20], D::Error> {
+    fixed_bytes("programId", true, deserializer)
+}
+
+fn deserialize_r<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+    fixed_bytes("r", false, deserializer)
+}
+
+fn deserialize_s<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+    fixed_bytes("s", false, deserializer)
 }
 
 #[derive(
-    Builder, Clone, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash,
+    Builder,
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    JsonSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct Transaction {
     transaction_type: TransactionType,
     #[serde(
-        serialize_with = "serialize_as_hex",
-        deserialize_with = "deserialize_address_bytes_or_string"
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_from"
     )]
     from: [u8; 20],
     #[serde(
-        serialize_with = "serialize_as_hex",
-        deserialize_with = "deserialize_address_bytes_or_string"
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_to"
     )]
     to: [u8; 20],
     #[serde(
-        serialize_with = "serialize_as_hex",
-        deserialize_with = "deserialize_address_bytes_or_string",
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_program_id",
         alias = "token",
         alias = "token_address",
         alias = "program_address"
@@ -286,17 +558,81 @@ pub struct Transaction {
     inputs: String,
     value: crate::U256,
     nonce: crate::U256,
+    /// The most the sender will pay in fees, in VERSE. Unset is none.
+    /// Written even when unset, as bincode cannot read back a struct missing
+    /// a field.
+    #[serde(default)]
+    #[builder(default)]
+    max_fee: Option<crate::U256>,
+    /// What the sender offers over the fee floor, in VERSE.
+    #[serde(default)]
+    #[builder(default)]
+    priority_fee: Option<crate::U256>,
+    /// The earliest the transaction may be applied, in seconds since the
+    /// epoch. Unset is at once.
+    #[serde(default)]
+    #[builder(default)]
+    valid_after: Option<u64>,
+    /// When the transaction may no longer be applied, in seconds since the
+    /// epoch. Unset is never.
+    #[serde(default)]
+    #[builder(default)]
+    valid_before: Option<u64>,
     v: i32,
     #[serde(
-        serialize_with = "serialize_as_hex",
-        deserialize_with = "deserialize_sig_bytes_or_string"
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_r"
     )]
     r: [u8; 32],
     #[serde(
-        serialize_with = "serialize_as_hex",
-        deserialize_with = "deserialize_sig_bytes_or_string"
+        serialize_with = "hex_bytes::serialize",
+        deserialize_with = "deserialize_s"
     )]
     s: [u8; 32],
+    /// What the signature is over. Unset is the digest.
+    #[serde(default)]
+    #[builder(default)]
+    signature_scheme: SignatureScheme,
+    #[serde(skip)]
+    #[borsh(skip)]
+    #[builder(setter(skip))]
+    cached_digest: CachedDigest,
+}
+
+/// A transaction's digest once it is taken, kept through clones. It plays no
+/// part in comparing, ordering or hashing transactions, nor in how they are
+/// written.
+#[derive(Clone, Default)]
+struct CachedDigest(OnceLock<[u8; 32]>);
+
+impl std::fmt::Debug for CachedDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CachedDigest")
+    }
+}
+
+impl PartialEq for CachedDigest {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CachedDigest {}
+
+impl PartialOrd for CachedDigest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CachedDigest {
+    fn cmp(&self, _: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl std::hash::Hash for CachedDigest {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
 }
 
 impl Default for Transaction {
@@ -310,9 +646,15 @@ impl Default for Transaction {
             inputs: String::from(""),
             value: crate::U256::from(0),
             nonce: crate::U256::from(0),
+            max_fee: None,
+            priority_fee: None,
+            valid_after: None,
+            valid_before: None,
             v: 0,
             r: [0u8; 32],
             s: [0u8; 32],
+            signature_scheme: SignatureScheme::Digest,
+            cached_digest: CachedDigest::default(),
         }
     }
 }
@@ -368,92 +710,172 @@ impl Transaction {
         self.nonce
     }
 
+    pub fn max_fee(&self) -> Option<crate::U256> {
+        self.max_fee
+    }
+
+    pub fn priority_fee(&self) -> Option<crate::U256> {
+        self.priority_fee
+    }
+
+    pub fn valid_after(&self) -> Option<u64> {
+        self.valid_after
+    }
+
+    pub fn valid_before(&self) -> Option<u64> {
+        self.valid_before
+    }
+
     pub fn sig(&self) -> Result<RecoverableSignature, Box<dyn std::error::Error>> {
-        let sig = RecoverableSignatureBuilder::default()
-            .r(self.r)
-            .s(self.s)
-            .v(self.v)
-            .build()
-            .map_err(Box::new)?;
+        Ok(RecoverableSignature::new(self.r, self.s, self.v))
+    }
 
-        Ok(sig)
+    pub fn signature_scheme(&self) -> SignatureScheme {
+        self.signature_scheme
     }
 
-    pub fn recover(&self) -> Result<Address, Box<dyn std::error::Error>> {
-        let r = self.r;
-        let s = self.s;
-        let v = self.v;
-
-        if (27..=28).contains(&v) {
-            let sig = ethers_core::types::Signature {
-                r: ethers_core::abi::ethereum_types::U256::from(r),
-                s: ethers_core::abi::ethereum_types::U256::from(s),
-                v: v as u64,
-            };
-            tracing::warn!("attempting to recover from {}", sig.to_string());
-            let addr = sig.recover(self.hash())?;
-            return Ok(addr.into());
+    /// The transaction as signed under `signature_scheme`. Its hash is the
+    /// same under either.
+    pub fn with_signature_scheme(mut self, signature_scheme: SignatureScheme) -> Self {
+        self.signature_scheme = signature_scheme;
+        self
+    }
+
+    /// What the signature is over: the EIP-712 digest under
+    /// [`SignatureScheme::TypedData`], or else the digest, as personal
+    /// messages are signed if `v` is 27 or 28.
+    fn signed_digest(&self) -> [u8; 32] {
+        match self.signature_scheme {
+            SignatureScheme::TypedData => self.typed_data_digest(),
+            SignatureScheme::Digest if (27..=28).contains(&self.v) => {
+                let mut hasher = Keccak256::new();
+                hasher.update(b"\x19Ethereum Signed Message:\n32");
+                hasher.update(self.digest());
+                let mut digest = [0; 32];
+                digest.copy_from_slice(&hasher.finalize());
+                digest
+            }
+            SignatureScheme::Digest => self.digest(),
         }
-        let addr = self.sig()?.recover(&self.hash())?;
-        Ok(addr)
+    }
+
+    pub fn recover(&self) -> Result<Address, Box<dyn std::error::Error>> {
+        Ok(self.sig()?.recover(&self.signed_digest())?)
     }
 
     pub fn message(&self) -> String {
         format!("{:02x}", self)
     }
 
+    /// The keccak256 hash of [`Transaction::as_bytes`], which the
+    /// transaction is signed over and known by. It is taken once and kept
+    /// with the transaction.
+    pub fn digest(&self) -> [u8; 32] {
+        *self.cached_digest.0.get_or_init(|| {
+            let mut digest = [0; 32];
+            digest.copy_from_slice(&Keccak256::digest(self.as_bytes()));
+            digest
+        })
+    }
+
     pub fn hash_string(&self) -> String {
-        let mut hasher = Keccak256::new();
-        hasher.update(&self.as_bytes());
-        let res = hasher.finalize();
-        format!("0x{:x}", res)
+        format!("0x{}", hex::encode(self.digest()))
     }
 
     pub fn hash(&self) -> Vec<u8> {
-        let mut hasher = Keccak256::new();
-        hasher.update(&self.as_bytes());
-        let res = hasher.finalize();
-        tracing::info!("transaction hash: 0x{:x}", res);
-        res.to_vec()
+        self.digest().to_vec()
     }
 
+    /// What the digest is taken over, under [`DIGEST_VERSION`] 2, in order:
+    ///
+    /// 1. the version, one byte;
+    /// 2. the chain id, 8 bytes big endian;
+    /// 3. the type, one byte, 0 to 10 for bridge in, send, call, bridge out,
+    ///    register program, burn, delegate, deploy, bridge out to a recipient,
+    ///    burn of an amount and set account metadata, then its nonce and what
+    ///    else it carries;
+    /// 4. `from`, `to` and `program_id`, 20 bytes each;
+    /// 5. `op` and `inputs`, each its length in 8 bytes big endian, then its
+    ///    UTF-8;
+    /// 6. `value` and `nonce`;
+    /// 7. `max_fee` and `priority_fee`, each a 0 byte if unset, else a 1
+    ///    byte and the fee;
+    /// 8. `valid_after` and `valid_before`, likewise, in 8 bytes big endian.
+    ///
+    /// `U256`s are 32 bytes big endian. Any change here bumps the version.
     pub fn as_bytes(&self) -> Vec<u8> {
-        let transaction_json = serde_json::json!({
-            "transactionType": self.transaction_type().to_json(),
-            "from": self.from().to_full_string(),
-            "to": self.to().to_full_string(),
-            "programId": self.program_id().to_full_string(),
-            "op": self.op.clone(),
-            "transactionInputs": self.inputs().clone(),
-            "value": format!("0x{:064x}", self.value()),
-            "nonce": format!("0x{:064x}", self.nonce())
-        })
-        .to_string();
-
-        tracing::info!("converted payload to json: {}", &transaction_json);
-        transaction_json.as_bytes().to_vec()
-    }
-
-    pub fn verify_signature(&self) -> Result<(), secp256k1::Error> {
-        let addr = self
-            .sig()
-            .map_err(|_| secp256k1::Error::InvalidMessage)?
-            .recover(&self.hash())?;
-        if self.from() != addr {
-            tracing::error!(
-                "self.from() {} != addr {}",
-                self.from().to_full_string(),
-                addr.to_full_string()
-            );
-            return Err(secp256k1::Error::InvalidSignature);
+        fn u256(bytes: &mut Vec<u8>, value: crate::U256) {
+            let mut be = [0; 32];
+            value.to_big_endian(&mut be);
+            bytes.extend_from_slice(&be);
+        }
+        fn string(bytes: &mut Vec<u8>, value: &str) {
+            bytes.extend_from_slice(&(value.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(value.as_bytes());
         }
 
-        Ok(())
+        let mut bytes = Vec::with_capacity(256 + self.op.len() + self.inputs.len());
+        bytes.push(DIGEST_VERSION);
+        bytes.extend_from_slice(&chain_id().to_be_bytes());
+        let (tag, type_nonce) = self.transaction_type.tag();
+        bytes.push(tag);
+        u256(&mut bytes, type_nonce);
+        bytes.extend_from_slice(&self.transaction_type.data());
+        bytes.extend_from_slice(&self.from);
+        bytes.extend_from_slice(&self.to);
+        bytes.extend_from_slice(&self.program_id);
+        string(&mut bytes, &self.op);
+        string(&mut bytes, &self.inputs);
+        u256(&mut bytes, self.value);
+        u256(&mut bytes, self.nonce);
+        for fee in [self.max_fee, self.priority_fee] {
+            match fee {
+                Some(fee) => {
+                    bytes.push(1);
+                    u256(&mut bytes, fee);
+                }
+                None => bytes.push(0),
+            }
+        }
+        for time in [self.valid_after, self.valid_before] {
+            match time {
+                Some(time) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&time.to_be_bytes());
+                }
+                None => bytes.push(0),
+            }
+        }
+        bytes
+    }
+
+    pub fn verify_signature(&self) -> Result<(), SigError> {
+        RecoverableSignature::new(self.r, self.s, self.v)
+            .verify(&self.signed_digest(), &self.from())
     }
 
     pub fn get_accounts_involved(&self) -> Vec<Address> {
         vec![self.from(), self.to()]
     }
+
+    /// Where `now`, in seconds since the epoch, falls against the window
+    /// the transaction is valid in: from `valid_after` and before
+    /// `valid_before`.
+    pub fn validity_at(&self, now: u64) -> Validity {
+        match (self.valid_after, self.valid_before) {
+            (Some(valid_after), _) if now < valid_after => Validity::NotYet { valid_after },
+            (_, Some(valid_before)) if now >= valid_before => Validity::Expired { valid_before },
+            _ => Validity::Valid,
+        }
+    }
+}
+
+/// Where a time falls against the window a transaction is valid in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Validity {
+    Valid,
+    NotYet { valid_after: u64 },
+    Expired { valid_before: u64 },
 }
 
 impl LowerHex for Transaction {
@@ -476,9 +898,15 @@ impl From<(Payload, RecoverableSignature)> for Transaction {
             inputs: value.0.inputs(),
             value: value.0.value(),
             nonce: value.0.nonce(),
+            max_fee: value.0.max_fee(),
+            priority_fee: value.0.priority_fee(),
+            valid_after: value.0.valid_after(),
+            valid_before: value.0.valid_before(),
             v: value.1.get_v(),
             r: value.1.get_r(),
             s: value.1.get_s(),
+            signature_scheme: SignatureScheme::Digest,
+            cached_digest: CachedDigest::default(),
         }
     }
 }
@@ -494,6 +922,10 @@ impl From<Payload> for Transaction {
             inputs: value.inputs(),
             value: value.value(),
             nonce: value.nonce(),
+            max_fee: value.max_fee(),
+            priority_fee: value.priority_fee(),
+            valid_after: value.valid_after(),
+            valid_before: value.valid_before(),
             ..Default::default()
         }
     }
@@ -569,3 +1001,149 @@ impl TryFrom<(Token, Transaction)> for Token {
         )))
     }
 }
+
+#[cfg(test)]
+mod transaction_tests {
+    use super::*;
+    use crate::U256;
+
+    fn send() -> Transaction {
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(3)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([3; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(5))
+            .nonce(U256::from(4))
+            .max_fee(Some(U256::from(2)))
+            .v(1)
+            .r([0x11; 32])
+            .s([0x22; 32])
+            .build()
+            .unwrap()
+    }
+
+    fn call() -> Payload {
+        PayloadBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(9)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([3; 20])
+            .op("getName".to_string())
+            .inputs(r#"{"a":1}"#.to_string())
+            .value(U256::from(0))
+            .nonce(U256::from(9))
+            .valid_after(Some(100))
+            .valid_before(Some(200))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn digests_match_their_golden_vectors() {
+        assert_eq!(chain_id(), DEFAULT_CHAIN_ID);
+        let bytes = send().as_bytes();
+        assert_eq!(bytes.len(), 218);
+        assert_eq!(bytes[0], DIGEST_VERSION);
+        assert_eq!(bytes[1..9], DEFAULT_CHAIN_ID.to_be_bytes());
+        assert_eq!(
+            send().hash_string(),
+            "0xb7aa04968f3fd5c5cf53ba8514f34b38993c5af5305a13618b9a5a4361234d5a"
+        );
+        assert_eq!(call().as_bytes().len(), 216);
+        assert_eq!(
+            call().hash_string(),
+            "0xcd0668bb85c8f0d2c59f5c2c0a4d9d92de78b040709e8636db49081cdbd5a5ae"
+        );
+        // A payload is signed over the digest of the transaction it becomes.
+        assert_eq!(call().digest(), Transaction::from(call()).digest());
+
+        // What a type carries besides its nonce is hashed after it.
+        let deploy = TransactionBuilder::default()
+            .transaction_type(TransactionType::Deploy {
+                nonce: U256::from(2),
+                content_id: "bafyprogram".to_string(),
+                runtime: DeployRuntime::Wasm,
+            })
+            .from([1; 20])
+            .to([0; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(0))
+            .nonce(U256::from(2))
+            .build()
+            .unwrap();
+        let bytes = deploy.as_bytes();
+        assert_eq!(bytes.len(), 206);
+        assert_eq!(bytes[9], 7);
+        assert_eq!(&bytes[42..62], b"\0\0\0\0\0\0\0\x0bbafyprogram\x01");
+        assert_eq!(
+            deploy.hash_string(),
+            "0x2683c099e44f170a06d2a5f0843d0995ba7ab5cd3bd802f7f94995f16d49f3d2"
+        );
+    }
+
+    #[test]
+    fn transaction_types_serialized_before_the_new_variants_still_decode() {
+        let mut legacy = vec![1, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0];
+        legacy.extend_from_slice(&[0; 31]);
+        legacy.push(3);
+        assert_eq!(
+            bincode::deserialize::<TransactionType>(&legacy).unwrap(),
+            TransactionType::Send(U256::from(3))
+        );
+        assert_eq!(
+            serde_json::from_str::<TransactionType>(r#"{"delegate":"0x5"}"#).unwrap(),
+            TransactionType::Delegate(U256::from(5))
+        );
+        assert_eq!(
+            serde_json::from_str::<TransactionType>(r#"{"setAccountMetadata":"0x5"}"#).unwrap(),
+            TransactionType::SetAccountMetadata(U256::from(5))
+        );
+
+        let burn = TransactionType::BurnAmount {
+            nonce: U256::from(1),
+        };
+        let json = serde_json::to_value(&burn).unwrap();
+        assert_eq!(json, serde_json::json!({"burnAmount": {"nonce": "0x1"}}));
+        assert_eq!(
+            serde_json::from_value::<TransactionType>(json).unwrap(),
+            burn
+        );
+        let deploy = TransactionType::Deploy {
+            nonce: U256::from(1),
+            content_id: "bafyprogram".to_string(),
+            runtime: DeployRuntime::Container,
+        };
+        assert_eq!(
+            bincode::deserialize::<TransactionType>(&bincode::serialize(&deploy).unwrap()).unwrap(),
+            deploy
+        );
+        assert_eq!(
+            borsh::from_slice::<TransactionType>(&borsh::to_vec(&deploy).unwrap()).unwrap(),
+            deploy
+        );
+    }
+
+    #[test]
+    fn the_digest_is_kept_through_clones_and_plays_no_part_in_equality() {
+        let transaction = send();
+        let digest = transaction.digest();
+        let cloned = transaction.clone();
+        assert_eq!(cloned.cached_digest.0.get(), Some(&digest));
+        assert_eq!(cloned, send());
+        assert_eq!(cloned.cmp(&send()), Ordering::Equal);
+
+        let json = serde_json::to_value(&transaction).unwrap();
+        assert!(json.get("cachedDigest").is_none());
+        let read: Transaction = serde_json::from_value(json).unwrap();
+        assert!(read.cached_digest.0.get().is_none());
+        assert_eq!(read.digest(), digest);
+        let read: Transaction =
+            bincode::deserialize(&bincode::serialize(&cloned).unwrap()).unwrap();
+        assert_eq!(read.digest(), digest);
+    }
+}
@@ -0,0 +1,281 @@
+//! EIP-712 typed data for transactions signed in browser wallets, which sign
+//! with `eth_signTypedData_v4` rather than over a raw digest.
+//!
+//! The typed data commits to all [`Transaction::as_bytes`] does, the chain
+//! and the nonce among it, and a transaction is known by the same hash
+//! whichever scheme it is signed under, so a signature under one cannot be
+//! replayed under the other. Optional fields are arrays of none or one, and
+//! what a transaction type carries besides its nonce is its `bytes`.
+
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+
+use crate::{chain_id, hex_quantity, Payload, Transaction, U256};
+
+/// The name of the domain LASR transactions are signed in.
+pub const EIP712_NAME: &str = "LASR";
+
+/// The version of the domain, bumped with any change to [`PAYLOAD_TYPE`].
+pub const EIP712_VERSION: &str = "2";
+
+/// The type of the domain, committing to the chain.
+pub const DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,uint256 chainId)";
+
+/// The type a transaction is signed as.
+pub const PAYLOAD_TYPE: &str = "Payload(uint8 transactionType,uint256 transactionTypeNonce,\
+bytes transactionTypeData,address from,address to,address programId,string op,\
+string transactionInputs,uint256 value,uint256 nonce,uint256[] maxFee,uint256[] priorityFee,\
+uint64[] validAfter,uint64[] validBefore)";
+
+fn keccak(bytes: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&Keccak256::digest(bytes));
+    hash
+}
+
+fn uint256(value: U256) -> [u8; 32] {
+    let mut word = [0; 32];
+    value.to_big_endian(&mut word);
+    word
+}
+
+fn address(bytes: [u8; 20]) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[12..].copy_from_slice(&bytes);
+    word
+}
+
+/// An array of none or one word, encoded as the hash of its words.
+fn array(values: Option<[u8; 32]>) -> [u8; 32] {
+    keccak(values.as_ref().map_or(&[][..], |word| &word[..]))
+}
+
+/// The hash of the domain on the chain [`chain_id`] says.
+pub fn domain_separator() -> [u8; 32] {
+    let mut encoded = Vec::with_capacity(4 * 32);
+    encoded.extend_from_slice(&keccak(DOMAIN_TYPE.as_bytes()));
+    encoded.extend_from_slice(&keccak(EIP712_NAME.as_bytes()));
+    encoded.extend_from_slice(&keccak(EIP712_VERSION.as_bytes()));
+    encoded.extend_from_slice(&uint256(U256::from(chain_id())));
+    keccak(&encoded)
+}
+
+impl Transaction {
+    /// The EIP-712 hash of the transaction as a [`PAYLOAD_TYPE`].
+    pub fn typed_data_hash(&self) -> [u8; 32] {
+        let (tag, type_nonce) = self.transaction_type().tag();
+        let words = [
+            keccak(PAYLOAD_TYPE.as_bytes()),
+            uint256(U256::from(tag)),
+            uint256(type_nonce),
+            keccak(&self.transaction_type().data()),
+            address(self.from().inner()),
+            address(self.to().inner()),
+            address(self.program_id().inner()),
+            keccak(self.op().as_bytes()),
+            keccak(self.inputs().as_bytes()),
+            uint256(self.value()),
+            uint256(self.nonce()),
+            array(self.max_fee().map(uint256)),
+            array(self.priority_fee().map(uint256)),
+            array(self.valid_after().map(|time| uint256(U256::from(time)))),
+            array(self.valid_before().map(|time| uint256(U256::from(time)))),
+        ];
+        keccak(&words.concat())
+    }
+
+    /// What a browser wallet signs for the transaction's typed data.
+    pub fn typed_data_digest(&self) -> [u8; 32] {
+        let mut encoded = Vec::with_capacity(2 + 2 * 32);
+        encoded.extend_from_slice(b"\x19\x01");
+        encoded.extend_from_slice(&domain_separator());
+        encoded.extend_from_slice(&self.typed_data_hash());
+        keccak(&encoded)
+    }
+
+    /// The typed data `eth_signTypedData_v4` takes to sign the transaction,
+    /// its `uint256`s as hex quantities and its `uint64`s in decimal strings.
+    pub fn typed_data(&self) -> Value {
+        let (tag, type_nonce) = self.transaction_type().tag();
+        let field = |name: &str, kind: &str| json!({ "name": name, "type": kind });
+        json!({
+            "types": {
+                "EIP712Domain": [
+                    field("name", "string"),
+                    field("version", "string"),
+                    field("chainId", "uint256"),
+                ],
+                "Payload": [
+                    field("transactionType", "uint8"),
+                    field("transactionTypeNonce", "uint256"),
+                    field("transactionTypeData", "bytes"),
+                    field("from", "address"),
+                    field("to", "address"),
+                    field("programId", "address"),
+                    field("op", "string"),
+                    field("transactionInputs", "string"),
+                    field("value", "uint256"),
+                    field("nonce", "uint256"),
+                    field("maxFee", "uint256[]"),
+                    field("priorityFee", "uint256[]"),
+                    field("validAfter", "uint64[]"),
+                    field("validBefore", "uint64[]"),
+                ],
+            },
+            "primaryType": "Payload",
+            "domain": {
+                "name": EIP712_NAME,
+                "version": EIP712_VERSION,
+                "chainId": chain_id(),
+            },
+            "message": {
+                "transactionType": tag,
+                "transactionTypeNonce": hex_quantity::encode(&type_nonce),
+                "transactionTypeData": format!("0x{}", hex::encode(self.transaction_type().data())),
+                "from": self.from().to_string(),
+                "to": self.to().to_string(),
+                "programId": self.program_id().to_string(),
+                "op": self.op(),
+                "transactionInputs": self.inputs(),
+                "value": hex_quantity::encode(&self.value()),
+                "nonce": hex_quantity::encode(&self.nonce()),
+                "maxFee": self.max_fee().iter().map(hex_quantity::encode).collect::<Vec<_>>(),
+                "priorityFee": self
+                    .priority_fee()
+                    .iter()
+                    .map(hex_quantity::encode)
+                    .collect::<Vec<_>>(),
+                "validAfter": self.valid_after().iter().map(u64::to_string).collect::<Vec<_>>(),
+                "validBefore": self.valid_before().iter().map(u64::to_string).collect::<Vec<_>>(),
+            },
+        })
+    }
+}
+
+impl Payload {
+    /// What a browser wallet signs for the payload's typed data.
+    pub fn typed_data_digest(&self) -> [u8; 32] {
+        Transaction::from(self.clone()).typed_data_digest()
+    }
+
+    /// The typed data `eth_signTypedData_v4` takes to sign the payload.
+    pub fn typed_data(&self) -> Value {
+        Transaction::from(self.clone()).typed_data()
+    }
+}
+
+#[cfg(test)]
+mod typed_data_tests {
+    use secp256k1::{Message, Secp256k1, SecretKey};
+
+    use super::*;
+    use crate::{
+        Address, PayloadBuilder, RecoverableSignature, SignatureScheme, TransactionBuilder,
+        TransactionType,
+    };
+
+    /// The key ethers' documentation signs with.
+    const KEY: [u8; 32] = [
+        0x4c, 0x08, 0x83, 0xa6, 0x91, 0x02, 0x93, 0x7d, 0x62, 0x31, 0x47, 0x1b, 0x5d, 0xbb, 0x62,
+        0x04, 0xfe, 0x51, 0x29, 0x61, 0x70, 0x82, 0x79, 0x2a, 0xe4, 0x68, 0xd0, 0x1a, 0x3f, 0x36,
+        0x23, 0x18,
+    ];
+
+    fn signer() -> Address {
+        "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+            .parse()
+            .unwrap()
+    }
+
+    fn send(v: i32, r: &str, s: &str) -> Transaction {
+        let bytes = |hex: &str| -> [u8; 32] { hex::decode(hex).unwrap().try_into().unwrap() };
+        TransactionBuilder::default()
+            .transaction_type(TransactionType::Send(U256::from(3)))
+            .from(signer().inner())
+            .to([2; 20])
+            .program_id([0; 20])
+            .op(String::new())
+            .inputs(String::new())
+            .value(U256::from(5))
+            .nonce(U256::from(4))
+            .max_fee(Some(U256::from(2)))
+            .v(v)
+            .r(bytes(r))
+            .s(bytes(s))
+            .signature_scheme(SignatureScheme::TypedData)
+            .build()
+            .unwrap()
+    }
+
+    /// The send as `eth_signTypedData_v4` signs it with [`KEY`].
+    fn signed() -> Transaction {
+        send(
+            28,
+            "e8a6a14ca750733d466f52aef7d3fc6a4562ab29b12e1088f9a91bf82cbf3862",
+            "6ca46b8ebf824af66c60106da1958c9ebcaeb3e20e74a9f116319c56f4241ce5",
+        )
+    }
+
+    #[test]
+    fn typed_data_matches_its_golden_vectors() {
+        assert_eq!(
+            hex::encode(domain_separator()),
+            "fb8fa6e2f23a8625d2774e9faef942406b40d230465d3fa6226f1abefd140a2c"
+        );
+        assert_eq!(
+            hex::encode(signed().typed_data_digest()),
+            "e2c6e4a1b6366f3fd2091aff265261045b10fb8cf8e0b69857f088e29c29b6df"
+        );
+        let call = PayloadBuilder::default()
+            .transaction_type(TransactionType::Call(U256::from(9)))
+            .from([1; 20])
+            .to([2; 20])
+            .program_id([3; 20])
+            .op("getName".to_string())
+            .inputs(r#"{"a":1}"#.to_string())
+            .value(U256::from(0))
+            .nonce(U256::from(9))
+            .valid_after(Some(100))
+            .valid_before(Some(200))
+            .build()
+            .unwrap();
+        assert_eq!(
+            hex::encode(call.typed_data_digest()),
+            "bbe66190a1fd7643e87c4660a60f4985fe36507012861c6c7165f8317726831d"
+        );
+        let typed_data = call.typed_data();
+        assert_eq!(typed_data["primaryType"], "Payload");
+        assert_eq!(typed_data["domain"]["chainId"], chain_id());
+        assert_eq!(typed_data["message"]["transactionType"], 2);
+        assert_eq!(typed_data["message"]["nonce"], "0x9");
+        assert_eq!(typed_data["message"]["transactionTypeData"], "0x");
+        assert_eq!(typed_data["message"]["maxFee"], json!([]));
+        assert_eq!(typed_data["message"]["validBefore"], json!(["200"]));
+    }
+
+    #[test]
+    fn a_browser_wallet_signature_recovers_only_under_its_scheme() {
+        assert_eq!(signed().recover().unwrap(), signer());
+        assert!(signed().verify_signature().is_ok());
+
+        // It is the same transaction whichever way it is signed.
+        let as_digest = signed().with_signature_scheme(SignatureScheme::Digest);
+        assert_eq!(as_digest.hash_string(), signed().hash_string());
+        assert_ne!(as_digest.recover().ok(), Some(signer()));
+        assert!(as_digest.verify_signature().is_err());
+
+        // And a signature over the digest does not recover as typed data.
+        let secret_key = SecretKey::from_slice(&KEY).unwrap();
+        let sig: RecoverableSignature = Secp256k1::new()
+            .sign_ecdsa_recoverable(&Message::from_digest(signed().digest()), &secret_key)
+            .into();
+        let digest_signed = send(
+            sig.get_v(),
+            &hex::encode(sig.get_r()),
+            &hex::encode(sig.get_s()),
+        );
+        assert_ne!(digest_signed.recover().ok(), Some(signer()));
+        let digest_signed = digest_signed.with_signature_scheme(SignatureScheme::Digest);
+        assert_eq!(digest_signed.recover().unwrap(), signer());
+    }
+}
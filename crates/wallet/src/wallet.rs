@@ -4,13 +4,14 @@ use derive_builder::Builder;
 use ethereum_types::U256 as EthU256;
 use lasr_rpc::LasrRpcClient;
 use lasr_types::{
-    Account, Address, Payload, PayloadBuilder, RecoverableSignature, Token, Transaction,
-    TransactionType, U256,
+    Account, AccountMetadata, AccountState, Address, Delegation, DelegationOp, DeployRuntime,
+    IntentBuilder, Payload, PayloadBuilder, RecoverableSignature, SignatureScheme, Token,
+    Transaction, U256,
 };
 use rand::{rngs::StdRng, RngCore, SeedableRng};
 use secp256k1::{
     hashes::{sha256, Hash},
-    Keypair, Message, PublicKey, Secp256k1, SecretKey,
+    Keypair, PublicKey, Secp256k1, SecretKey,
 };
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha3::{Digest, Sha3_256};
@@ -28,6 +29,29 @@ pub struct WalletInfo {
 }
 
 impl WalletInfo {
+    /// The keys `mnemonic` derives with `passphrase`, as `Wallet::get_info`
+    /// derives those of the mnemonic it makes.
+    pub fn from_mnemonic(mnemonic: Mnemonic, passphrase: &str) -> Self {
+        let keypair_seed = mnemonic.to_seed(passphrase);
+
+        let secp = Secp256k1::new();
+        let mut hasher = Sha3_256::new();
+        hasher.update(keypair_seed);
+        let seed_hash = hasher.finalize().to_vec();
+        let secret_key = SecretKey::from_hashed_data::<sha256::Hash>(&seed_hash[..]);
+        let keypair = Keypair::from_secret_key(&secp, &secret_key);
+        let public_key = keypair.public_key();
+        let address = Address::from(keypair.public_key());
+
+        WalletInfo {
+            mnemonic,
+            keypair,
+            secret_key,
+            public_key,
+            address,
+        }
+    }
+
     pub fn mnemonic(&self) -> &Mnemonic {
         &self.mnemonic
     }
@@ -45,6 +69,14 @@ impl WalletInfo {
     }
 }
 
+/// What came of a send with a window it is valid in.
+#[derive(Clone, Debug)]
+pub enum Sent {
+    Applied(Token),
+    /// Held until it is valid, with the transaction's hash.
+    Scheduled(String),
+}
+
 #[derive(Builder, Clone)]
 pub struct Wallet<L>
 where
@@ -92,24 +124,7 @@ impl<L: LasrRpcClient + Send + Sync> Wallet<L> {
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?
         };
 
-        let keypair_seed = mnemonic.to_seed(unwrapped_passphrase);
-
-        let secp = Secp256k1::new();
-        let mut hasher = Sha3_256::new();
-        hasher.update(keypair_seed);
-        let seed_hash = hasher.finalize().to_vec();
-        let secret_key = SecretKey::from_hashed_data::<sha256::Hash>(&seed_hash[..]);
-        let keypair = Keypair::from_secret_key(&secp, &secret_key);
-        let public_key = keypair.public_key();
-        let address = Address::from(keypair.public_key());
-
-        Ok(WalletInfo {
-            mnemonic,
-            keypair,
-            secret_key,
-            public_key,
-            address,
-        })
+        Ok(WalletInfo::from_mnemonic(mnemonic, &unwrapped_passphrase))
     }
 
     pub async fn send(
@@ -118,38 +133,85 @@ impl<L: LasrRpcClient + Send + Sync> Wallet<L> {
         program_id: &Address,
         value: U256,
     ) -> WalletResult<Token> {
-        let account = self.account();
-        let address = self.address();
-
-        account.validate_balance(program_id, value)?;
-
-        let tx_nonce = account.nonce() + U256::from(1);
-        let payload = self
-            .builder
-            .transaction_type(TransactionType::Send(account.nonce()))
-            .from(address.into())
-            .to(to.into())
-            .program_id(program_id.into())
-            .inputs(String::new())
-            .op(String::new())
-            .value(value)
-            .nonce(tx_nonce)
-            .build()
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        self.account().validate_spendable(program_id, value)?;
+        self.send_intent(IntentBuilder::transfer(*to, *program_id, value))
+            .await
+    }
 
-        let msg = Message::from_digest_slice(&payload.hash())
+    /// Sends as `send` does, valid only from `valid_after` and before
+    /// `valid_before`, in seconds since the epoch, either end left open with
+    /// `None`. Answers the token sent if it was valid and applied at once,
+    /// or the transaction's hash if it is held until it is valid. A held
+    /// transaction holds no nonce, so once valid it is turned away if the
+    /// wallet sent another with its nonce meanwhile.
+    pub async fn send_within(
+        &mut self,
+        to: &Address,
+        program_id: &Address,
+        value: U256,
+        valid_after: Option<u64>,
+        valid_before: Option<u64>,
+    ) -> WalletResult<Sent> {
+        self.account().validate_spendable(program_id, value)?;
+
+        let transaction = self.sign_intent(
+            IntentBuilder::transfer(*to, *program_id, value).with_window(valid_after, valid_before),
+        )?;
+
+        let response = self
+            .client
+            .send(transaction)
+            .await
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        let sent = match serde_json::from_str::<Token>(&response) {
+            Ok(token) => Sent::Applied(token),
+            Err(_) => Sent::Scheduled(response),
+        };
+
+        self.get_account(&self.address()).await?;
+
+        Ok(sent)
+    }
+
+    /// Takes `value` of the program's token off the chain, for `recipient`
+    /// to be released it on the settlement layer.
+    pub async fn bridge_out(
+        &mut self,
+        program_id: &Address,
+        value: U256,
+        recipient: &Address,
+    ) -> WalletResult<Token> {
+        self.account().validate_spendable(program_id, value)?;
+        self.send_intent(IntentBuilder::bridge_out(*program_id, value, *recipient))
+            .await
+    }
 
-        let context = Secp256k1::new();
+    /// Burns `amount` of the account's balance of the program's token.
+    pub async fn burn(&mut self, program_id: &Address, amount: U256) -> WalletResult<Token> {
+        self.account().validate_spendable(program_id, amount)?;
+        self.send_intent(IntentBuilder::burn(*program_id, amount))
+            .await
+    }
 
-        let sig: RecoverableSignature = context.sign_ecdsa_recoverable(&msg, &self.sk).into();
+    /// The transaction `intent` makes from the account, signed with the
+    /// wallet's key and with what its payload builder sets, as fees.
+    fn sign_intent(&self, intent: IntentBuilder) -> WalletResult<Transaction> {
+        intent
+            .with_defaults(&self.builder)
+            .from_account(&self.account)
+            .sign(&self.sk)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)
+    }
 
-        let transaction: Transaction = (payload, sig.clone()).into();
+    /// Sends the transaction `intent` makes, answering the token it left
+    /// the account with, and reads the account back.
+    async fn send_intent(&mut self, intent: IntentBuilder) -> WalletResult<Token> {
+        let transaction = self.sign_intent(intent)?;
 
         let token: Token = serde_json::from_str(
             &self
                 .client
-                .send(transaction.clone())
+                .send(transaction)
                 .await
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?,
         )
@@ -160,22 +222,58 @@ impl<L: LasrRpcClient + Send + Sync> Wallet<L> {
         Ok(token)
     }
 
+    /// Lets `delegation.delegate` sign the account's transactions within
+    /// the delegation, until it expires or is revoked.
+    pub async fn delegate(&mut self, delegation: Delegation) -> WalletResult<Token> {
+        self.send_delegation_op(DelegationOp::Grant(delegation))
+            .await
+    }
+
+    /// Takes back what was delegated to `delegate`.
+    pub async fn revoke(&mut self, delegate: &Address) -> WalletResult<Token> {
+        self.send_delegation_op(DelegationOp::Revoke(*delegate))
+            .await
+    }
+
+    /// Sets what the account says of itself, in place of what it said
+    /// before.
+    pub async fn set_account_metadata(&mut self, metadata: AccountMetadata) -> WalletResult<Token> {
+        self.send_intent(IntentBuilder::set_account_metadata(metadata))
+            .await
+    }
+
+    async fn send_delegation_op(&mut self, op: DelegationOp) -> WalletResult<Token> {
+        self.send_intent(IntentBuilder::delegate(op)).await
+    }
+
+    /// The wallet signing with `session_key`, a key the account delegated
+    /// to, rather than the account's own. Its transactions are still the
+    /// account's, sent with the account's nonces.
+    pub fn with_session_key(mut self, session_key: SecretKey) -> Self {
+        self.sk = session_key;
+        self
+    }
+
     pub async fn sign_payload(
         &mut self,
         payload: &str,
     ) -> Result<Transaction, Box<dyn std::error::Error + Send>> {
         let payload: Payload = serde_json::from_str(payload)
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-        let message = Message::from_digest_slice(&payload.hash())
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-        let context = Secp256k1::new();
-        let sig: RecoverableSignature = context.sign_ecdsa_recoverable(&message, &self.sk).into();
+        let sig = RecoverableSignature::sign(&payload.digest(), &self.sk);
 
         let transaction = (payload, sig.clone()).into();
         Ok(transaction)
     }
 
+    /// Signs `payload` over its EIP-712 typed data, as a browser wallet does
+    /// given [`Payload::typed_data`] to `eth_signTypedData_v4`.
+    pub fn sign_typed(&self, payload: Payload) -> Transaction {
+        let sig = RecoverableSignature::sign(&payload.typed_data_digest(), &self.sk);
+
+        Transaction::from((payload, sig)).with_signature_scheme(SignatureScheme::TypedData)
+    }
+
     pub async fn call(
         &mut self,
         program_id: &Address,
@@ -184,39 +282,17 @@ impl<L: LasrRpcClient + Send + Sync> Wallet<L> {
         op: &String,
         inputs: &String,
     ) -> WalletResult<String> {
-        let account = self.account();
-        let address = self.address();
-
         dbg!("validating balance");
 
         if value > U256::from(0) {
-            account.validate_balance(program_id, value)?;
+            self.account().validate_spendable(program_id, value)?;
         }
 
-        dbg!("building transaciton payload");
-        let payload = self
-            .builder
-            .transaction_type(TransactionType::Call(account.nonce()))
-            .from(address.into())
-            .to(to.into())
-            .program_id(program_id.into())
-            .inputs(inputs.to_string())
-            .op(op.to_string())
-            .value(value)
-            .nonce(account.nonce() + lasr_types::U256::from(1))
-            .build()
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-        let msg = Message::from_digest_slice(&payload.hash())
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-        let context = Secp256k1::new();
-
-        dbg!("signing transaaction");
-        let sig: RecoverableSignature = context.sign_ecdsa_recoverable(&msg, &self.sk).into();
-
-        dbg!("packaging transaaction");
-        let transaction: Transaction = (payload, sig.clone()).into();
+        let transaction = self.sign_intent(
+            IntentBuilder::invoke(*program_id, op.as_str(), inputs.as_str())
+                .with_to(*to)
+                .with_value(value),
+        )?;
 
         dbg!("submitting transaction to RPC");
         let tx_hash_string = self
@@ -231,30 +307,7 @@ impl<L: LasrRpcClient + Send + Sync> Wallet<L> {
     }
 
     pub async fn register_program(&mut self, inputs: &String) -> WalletResult<String> {
-        let account = self.account();
-        let address = self.address();
-
-        let payload = self
-            .builder
-            .transaction_type(TransactionType::RegisterProgram(account.nonce()))
-            .from(address.into())
-            .to([0; 20])
-            .program_id([0; 20])
-            .inputs(inputs.to_string())
-            .op(String::from(""))
-            .value(U256::from(0))
-            .nonce(account.nonce() + lasr_types::U256::from(1))
-            .build()
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-        let msg = Message::from_digest_slice(&payload.hash())
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
-
-        let context = Secp256k1::new();
-
-        let sig: RecoverableSignature = context.sign_ecdsa_recoverable(&msg, &self.sk).into();
-
-        let transaction: Transaction = (payload, sig.clone()).into();
+        let transaction = self.sign_intent(IntentBuilder::register_program(inputs.as_str()))?;
 
         //TODO: return `payment token` with approval set to Address(0), i.e. network
         //should be able to pull fees from the contract deployer/owner account
@@ -264,37 +317,58 @@ impl<L: LasrRpcClient + Send + Sync> Wallet<L> {
             .await
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
-        println!("{}", &program_id);
-
         if let Err(e) = self.get_account(&self.address()).await {
-            println!("Error getting account: {e}");
+            tracing::warn!("unable to read back the account after registering: {e}");
         }
 
         Ok(program_id)
     }
 
+    /// Deploys the program whose package is at `content_id`, to run in
+    /// `runtime`, answering its program id.
+    pub async fn deploy(
+        &mut self,
+        content_id: &str,
+        runtime: DeployRuntime,
+    ) -> WalletResult<String> {
+        let transaction = self.sign_intent(IntentBuilder::deploy(content_id, runtime))?;
+
+        let program_id = self
+            .client
+            .register_program(transaction)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+        self.get_account(&self.address()).await?;
+
+        Ok(program_id)
+    }
+
     pub async fn get_account(&mut self, address: &Address) -> WalletResult<()> {
-        tracing::info!("calling get_account for {:x}", address);
-        let account: Account = serde_json::from_str(
+        tracing::info!("calling get_account for {}", address);
+        let state: AccountState = serde_json::from_str(
             &self
                 .client
-                .get_account(format!("{:x}", address))
+                .get_account(address.to_string())
                 .await
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?,
         )
         .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        if !state.found {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no account found for {}", address),
+            )));
+        }
+        let account = Account::try_from(state)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
         self.account = account;
 
-        println!("\n");
-        println!("****************** Wallet Balances ********************");
-        println!("*       Token           |           Balance           *");
-        println!("* ----------------------|---------------------------- *");
-        for (id, token) in self.account().programs() {
-            println!("*    {:<23}      | {:>23}     *", id, token.balance());
+        // Logged rather than printed, so what a caller prints is its own.
+        for (id, token) in self.account().tokens_iter() {
+            tracing::debug!("{address} holds {} of {id}", token.balance());
         }
-        println!("*******************************************************");
-        println!("\n");
 
         Ok(())
     }
@@ -0,0 +1,227 @@
+#![cfg(test)]
+//! Test coverage for `Wallet` over a `MockLasrClient`, sending as it would
+//! to a node without one running.
+
+use std::time::{Duration, Instant};
+
+use lasr_rpc::{LasrRpcClient, MockLasrClient, RetryLayer, TimeoutLayer, WithLayer};
+use lasr_types::{
+    Account, AccountState, Address, PayloadBuilder, RpcErrorCode, Transaction, TransactionType,
+    U256,
+};
+use lasr_wallet::{Sent, Wallet, WalletBuilder};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+fn verse() -> Address {
+    Address::verse_addr()
+}
+
+fn receiver() -> Address {
+    Address::new([9; 20])
+}
+
+fn wallet<L: LasrRpcClient + Clone + Send + Sync>(client: &L) -> (Wallet<L>, Address) {
+    let secret_key = SecretKey::from_slice(&[31; 32]).unwrap();
+    let address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    let wallet = WalletBuilder::default()
+        .client(client.clone())
+        .sk(secret_key)
+        .builder(PayloadBuilder::default())
+        .address(address)
+        .account(Account::new(address))
+        .build()
+        .unwrap();
+    (wallet, address)
+}
+
+#[tokio::test]
+async fn sends_are_applied_to_the_accounts_the_mock_holds() {
+    let mock = MockLasrClient::stateful();
+    let (mut wallet, address) = wallet(&mock);
+    mock.fund(address, verse(), U256::from(100));
+    wallet.get_account(&address).await.unwrap();
+
+    wallet
+        .send(&receiver(), &verse(), U256::from(5))
+        .await
+        .unwrap();
+    wallet
+        .send(&receiver(), &verse(), U256::from(7))
+        .await
+        .unwrap();
+
+    let sender = mock.account(address).unwrap();
+    assert_eq!(sender.balance(&verse()), U256::from(88));
+    assert_eq!(sender.nonce(), U256::from(2));
+    assert_eq!(
+        mock.account(receiver()).unwrap().balance(&verse()),
+        U256::from(12)
+    );
+    // Each took the nonce after the last.
+    let nonces: Vec<U256> = mock.sent().iter().map(|sent| sent.nonce()).collect();
+    assert_eq!(nonces, vec![U256::from(1), U256::from(2)]);
+    mock.assert_sent(|sent| sent.to() == receiver() && sent.value() == U256::from(7));
+    let methods: Vec<String> = mock
+        .requests()
+        .into_iter()
+        .map(|request| request.method)
+        .collect();
+    assert_eq!(
+        methods,
+        [
+            "lasr_getAccount",
+            "lasr_send",
+            "lasr_getAccount",
+            "lasr_send",
+            "lasr_getAccount"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_send_the_node_turns_away_is_told_to_the_caller() {
+    let mock = MockLasrClient::stateful();
+    let (mut wallet, address) = wallet(&mock);
+    mock.fund(address, verse(), U256::from(100));
+    wallet.get_account(&address).await.unwrap();
+
+    mock.fail(
+        "send",
+        RpcErrorCode::MempoolFull,
+        "the pending pool is full",
+    );
+    let error = wallet
+        .send(&receiver(), &verse(), U256::from(5))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("the pending pool is full"),
+        "{error}"
+    );
+    assert!(mock.account(receiver()).is_none());
+
+    // What is scripted is answered once, so the send made again is applied.
+    wallet
+        .send(&receiver(), &verse(), U256::from(5))
+        .await
+        .unwrap();
+    assert_eq!(mock.requests_of("send").len(), 2);
+    assert_eq!(
+        mock.account(receiver()).unwrap().balance(&verse()),
+        U256::from(5)
+    );
+}
+
+#[tokio::test]
+async fn a_mock_answers_only_what_it_is_told_to() {
+    let mock = MockLasrClient::new();
+    let (mut wallet, address) = wallet(&mock);
+    let mut account = Account::new(address);
+    let bridge_in: Transaction = PayloadBuilder::default()
+        .transaction_type(TransactionType::BridgeIn(U256::from(1)))
+        .from(address.inner())
+        .to(address.inner())
+        .program_id(verse().inner())
+        .inputs(String::new())
+        .op(String::new())
+        .value(U256::from(10))
+        .nonce(U256::from(1))
+        .build()
+        .unwrap()
+        .into();
+    account.apply_send_transaction(bridge_in, None).unwrap();
+    account.increment_nonce();
+    let state = AccountState::new(address, Some(&account));
+    mock.respond_with("getAccount", &state)
+        .respond_with("getAccount", &state)
+        .respond("send", "0xabcd")
+        .delay("getAccount", Duration::from_millis(50));
+
+    let started = Instant::now();
+    wallet.get_account(&address).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    // An answer that is no token is taken for the hash of a held send.
+    match wallet
+        .send_within(&receiver(), &verse(), U256::from(5), None, None)
+        .await
+        .unwrap()
+    {
+        Sent::Scheduled(transaction_hash) => assert_eq!(transaction_hash, "0xabcd"),
+        other => panic!("expected the send to be held, got {other:?}"),
+    }
+    // The nonce after the one the account was read back with.
+    mock.assert_sent(|sent| sent.nonce() == U256::from(2));
+    mock.assert_requested("getAccount");
+
+    // Nothing else was queued.
+    let error = wallet.get_account(&address).await.unwrap_err();
+    assert!(
+        error.to_string().contains("no answer is scripted"),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn a_wallet_sends_over_a_layered_client_as_over_any_other() {
+    let mock = MockLasrClient::stateful();
+    let client = mock
+        .clone()
+        .with_layer(TimeoutLayer::new(Duration::from_secs(5)))
+        .with_layer(RetryLayer::default());
+    let (mut wallet, address) = wallet(&client);
+    mock.fund(address, verse(), U256::from(100));
+    mock.fail("getAccount", RpcErrorCode::Overloaded, "too busy");
+
+    // The read the node was too busy for is made again.
+    wallet.get_account(&address).await.unwrap();
+    assert_eq!(mock.requests_of("getAccount").len(), 2);
+    wallet
+        .send(&receiver(), &verse(), U256::from(5))
+        .await
+        .unwrap();
+    mock.assert_sent(|sent| sent.value() == U256::from(5));
+}
+
+#[tokio::test]
+async fn a_wallet_sends_each_intent_with_the_fees_its_payload_builder_sets() {
+    let mock = MockLasrClient::stateful();
+    let secret_key = SecretKey::from_slice(&[31; 32]).unwrap();
+    let address: Address = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).into();
+    let mut defaults = PayloadBuilder::default();
+    defaults.max_fee(Some(U256::from(3)));
+    let mut wallet = WalletBuilder::default()
+        .client(mock.clone())
+        .sk(secret_key)
+        .builder(defaults)
+        .address(address)
+        .account(Account::new(address))
+        .build()
+        .unwrap();
+    mock.fund(address, verse(), U256::from(100));
+    wallet.get_account(&address).await.unwrap();
+
+    wallet
+        .send(&receiver(), &verse(), U256::from(5))
+        .await
+        .unwrap();
+    mock.assert_sent(|sent| {
+        sent.transaction_type() == TransactionType::Send(U256::from(0))
+            && sent.nonce() == U256::from(1)
+            && sent.max_fee() == Some(U256::from(3))
+    });
+    // A window is the send's own, and is not kept for the next.
+    mock.respond("send", "0xabcd");
+    wallet
+        .send_within(&receiver(), &verse(), U256::from(5), None, Some(u64::MAX))
+        .await
+        .unwrap();
+    mock.assert_sent(|sent| sent.valid_before() == Some(u64::MAX));
+    wallet
+        .send(&receiver(), &verse(), U256::from(5))
+        .await
+        .unwrap();
+    let last = mock.sent().pop().unwrap();
+    assert_eq!(last.valid_before(), None);
+    assert_eq!(last.max_fee(), Some(U256::from(3)));
+}